@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Arguments supplied from a configuration file (`--args-file`).
+//!
+//! The file is a TOML table whose keys are the names of the command-line flags, without the
+//! leading dashes (either `grpc-address` or `grpc_address`). Flags which take no value are set with
+//! a boolean, and flags which can be repeated take an array:
+//!
+//! ```toml
+//! driver = "kernel"
+//! num-workers = 4
+//! interface = ["eth0", "eth1=0000:02:01.0"]
+//! grpc-unix-socket = true
+//! ```
+//!
+//! Values from the file have the lowest precedence: a flag given on the command line or through
+//! its environment variable overrides the file.
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command};
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Id of the argument holding the path to the file
+pub(crate) const ARGS_FILE_ID: &str = "args_file";
+
+/// Translate the content of the file at `path` into command-line arguments, skipping the flags
+/// which `matches` already got from the command line or the environment.
+pub(crate) fn file_args(
+    cmd: &mut Command,
+    matches: &ArgMatches,
+    path: &Path,
+) -> Result<Vec<OsString>, clap::Error> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        cmd.error(
+            ErrorKind::Io,
+            format!("Failed to read args file {}: {e}", path.display()),
+        )
+    })?;
+    let table: toml::Table = content.parse().map_err(|e| {
+        cmd.error(
+            ErrorKind::InvalidValue,
+            format!("Failed to parse args file {}: {e}", path.display()),
+        )
+    })?;
+
+    let mut out = Vec::new();
+    for (key, value) in &table {
+        let id = key.replace('-', "_");
+        if id == ARGS_FILE_ID {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                format!("Args file {} can't include another file", path.display()),
+            ));
+        }
+        let Some((flag, takes_values)) = cmd
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str())
+            .and_then(|arg| Some((arg.get_long()?, arg.get_action().takes_values())))
+            .map(|(long, takes_values)| (format!("--{long}"), takes_values))
+        else {
+            return Err(cmd.error(
+                ErrorKind::UnknownArgument,
+                format!("Unknown argument '{key}' in args file {}", path.display()),
+            ));
+        };
+        if matches!(
+            matches.value_source(&id),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+        let values = match value {
+            toml::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            match (value, takes_values) {
+                (toml::Value::Boolean(set), false) => {
+                    if *set {
+                        out.push(OsString::from(&flag));
+                    }
+                }
+                (toml::Value::String(s), true) => {
+                    out.push(OsString::from(&flag));
+                    out.push(OsString::from(s));
+                }
+                (toml::Value::Integer(i), true) => {
+                    out.push(OsString::from(&flag));
+                    out.push(OsString::from(i.to_string()));
+                }
+                (toml::Value::Float(f), true) => {
+                    out.push(OsString::from(&flag));
+                    out.push(OsString::from(f.to_string()));
+                }
+                (toml::Value::Boolean(b), true) => {
+                    out.push(OsString::from(&flag));
+                    out.push(OsString::from(b.to_string()));
+                }
+                _ => {
+                    return Err(cmd.error(
+                        ErrorKind::InvalidValue,
+                        format!(
+                            "Invalid value for '{key}' in args file {}: unexpected {}",
+                            path.display(),
+                            value.type_str()
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(out)
+}
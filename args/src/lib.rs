@@ -1,16 +1,27 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright Open Network Fabric Authors
 
+mod file;
+pub mod preflight;
+
 pub use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
+use config::internal::device::pipeline::PipelineConfig;
 use hardware::pci::address::PciAddress;
 use mgmt::processor::launch::GrpcAddress;
 use net::interface::InterfaceName;
+pub use preflight::{CheckResult, CheckStatus, PreflightReport};
+use routing::DEFAULT_FLOW_TABLE_CAPACITY;
+use routing::rio::DEFAULT_CPI_GRACE_PERIOD;
+use routing::rio::DEFAULT_CPI_HOLD_TIME;
 use routing::rio::DEFAULT_DP_UX_PATH;
 use routing::rio::DEFAULT_DP_UX_PATH_CLI;
 use routing::rio::DEFAULT_FRR_AGENT_PATH;
+use std::ffi::OsString;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 use tracing::debug;
 
 #[derive(Debug, Clone)]
@@ -46,6 +57,16 @@ impl FromStr for InterfaceArg {
         }
     }
 }
+
+/// Parse and validate the layout of the pipelines
+fn parse_pipeline(input: &str) -> Result<PipelineConfig, String> {
+    let pipeline = PipelineConfig::from_str(input).map_err(|e| format!("Bad pipeline: {e}"))?;
+    pipeline
+        .validate()
+        .map_err(|e| format!("Bad pipeline: {e}"))?;
+    Ok(pipeline)
+}
+
 #[cfg(test)]
 mod tests {
     use hardware::pci::address::PciAddress;
@@ -54,8 +75,12 @@ mod tests {
     use hardware::pci::domain::Domain;
     use hardware::pci::function::Function;
 
-    use crate::InterfaceArg;
+    use crate::{CmdArgs, InterfaceArg};
+    use mgmt::processor::launch::GrpcAddress;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
     use std::str::FromStr;
+    use std::time::Duration;
 
     #[test]
     fn test_parse_interface() {
@@ -89,6 +114,107 @@ mod tests {
         // bad pci address
         assert!(InterfaceArg::from_str("GbEth1.9000=0000:02:01").is_err());
     }
+
+    #[test]
+    fn test_args_file() {
+        let path = std::env::temp_dir().join(format!("args-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+driver = "kernel"
+num-workers = 4
+interface = ["eth0", "eth1=0000:02:01.0"]
+grpc_unix_socket = true
+grpc-address = "/run/dataplane.sock"
+cpi-grace-period = 90
+cpi-hold-time = 15
+"#,
+        )
+        .unwrap();
+        let file = path.to_str().unwrap();
+
+        // the file fills in what the command line doesn't set
+        let args =
+            CmdArgs::load_from(["dataplane", "--args-file", file, "--num-workers", "2"]).unwrap();
+        assert_eq!(args.get_driver_name(), "kernel");
+        assert_eq!(args.kernel_num_workers(), 2);
+        assert_eq!(args.kernel_interfaces(), vec!["eth0", "eth1"]);
+        assert_eq!(args.cpi_grace_period(), Duration::from_secs(90));
+        assert_eq!(args.cpi_hold_time(), Duration::from_secs(15));
+        assert!(matches!(
+            args.get_grpc_address(),
+            Ok(GrpcAddress::UnixSocket(path)) if path == PathBuf::from("/run/dataplane.sock")
+        ));
+
+        // repeated flags on the command line replace those of the file
+        let args =
+            CmdArgs::load_from(["dataplane", "--args-file", file, "--interface", "eth2"]).unwrap();
+        assert_eq!(args.kernel_interfaces(), vec!["eth2"]);
+        assert_eq!(
+            args.flow_table_capacity(),
+            Some(DEFAULT_FLOW_TABLE_CAPACITY)
+        );
+
+        // a capacity of 0 leaves the flow table unbounded
+        let args = CmdArgs::load_from(["dataplane", "--flow-table-capacity", "0"]).unwrap();
+        assert_eq!(args.flow_table_capacity(), None);
+
+        // unknown keys are rejected
+        std::fs::write(&path, "no-such-flag = 1\n").unwrap();
+        assert!(CmdArgs::load_from(["dataplane", "--args-file", file]).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(CmdArgs::load_from(["dataplane", "--args-file", file]).is_err());
+    }
+
+    // Name of the variable telling `env_precedence_child` the path of its arguments file
+    const ENV_TEST_FILE: &str = "ARGS_ENV_TEST_FILE";
+
+    #[test]
+    fn test_env_precedence() {
+        let path = std::env::temp_dir().join(format!("args-env-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "cli-sock-path = \"/file/cli.sock\"\nfrr-agent-path = \"/file/frr.sock\"\n",
+        )
+        .unwrap();
+        // other tests parse arguments concurrently, and clap reads the environment of the
+        // process: set the variables for a child process running the actual test instead
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "tests::env_precedence_child"])
+            .env(ENV_TEST_FILE, &path)
+            .env("DATAPLANE_CLI_SOCK_PATH", "/env/cli.sock")
+            .env("DATAPLANE_FRR_AGENT_PATH", "/env/frr.sock")
+            .env("DATAPLANE_ALLOW", "0000:02:01.0,0000:02:01.1")
+            .env("DATAPLANE_LOG_LEVEL", "pmd.net.*,debug")
+            .status()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn env_precedence_child() {
+        // only meaningful when run by test_env_precedence
+        let Some(file) = std::env::var_os(ENV_TEST_FILE) else {
+            return;
+        };
+        let args = CmdArgs::load_from([
+            OsString::from("dataplane"),
+            OsString::from("--args-file"),
+            file,
+            OsString::from("--frr-agent-path"),
+            OsString::from("/cli/frr.sock"),
+        ])
+        .unwrap();
+        // CLI > env > file
+        assert_eq!(args.frr_agent_path(), "/cli/frr.sock");
+        assert_eq!(args.cli_sock_path(), "/env/cli.sock");
+        // lists are comma-separated in the environment
+        assert_eq!(args.allow, vec!["0000:02:01.0", "0000:02:01.1"]);
+        // but log levels are not split, since dpdk takes them as <regex>,<level>
+        assert_eq!(args.log_level, vec!["pmd.net.*,debug"]);
+    }
 }
 
 #[derive(Parser)]
@@ -97,25 +223,62 @@ mod tests {
 #[command(about = "A next-gen dataplane for next-gen fabric gateway", long_about = None)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct CmdArgs {
-    #[arg(long, value_name = "core-id used as main", default_value_t = 2)]
+    #[arg(
+        long,
+        env = "DATAPLANE_ARGS_FILE",
+        value_name = "PATH",
+        help = "TOML file with default values for any of the other arguments, keyed by argument name (e.g. num-workers = 4).
+Arguments given on the command line or in the environment take precedence over the file"
+    )]
+    args_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "DATAPLANE_MAIN_LCORE",
+        value_name = "core-id used as main",
+        default_value_t = 2
+    )]
     main_lcore: u8,
-    #[arg(long, value_name = "map lcore set to cpu set")]
+    #[arg(
+        long,
+        env = "DATAPLANE_LCORES",
+        value_name = "map lcore set to cpu set"
+    )]
     lcores: Option<String>,
-    #[arg(long, value_name = "PCI devices to probe")]
+    #[arg(
+        long,
+        env = "DATAPLANE_ALLOW",
+        value_name = "PCI devices to probe",
+        value_delimiter = ','
+    )]
     allow: Vec<String>,
-    #[arg(long, value_name = "huge pages", default_value_t = 8192)]
+    #[arg(
+        long,
+        env = "DATAPLANE_HUGE_WORKER_STACK",
+        value_name = "huge pages",
+        default_value_t = 8192
+    )]
     huge_worker_stack: u32,
-    #[arg(long, value_name = "socket memory")]
+    #[arg(long, env = "DATAPLANE_SOCKET_MEM", value_name = "socket memory")]
     socket_mem: Option<String>,
-    #[arg(long, value_name = "iova mode(va|pa)")]
+    #[arg(long, env = "DATAPLANE_IOVA_MODE", value_name = "iova mode(va|pa)")]
     iova_mode: Option<String>,
-    #[arg(long, value_name = "loglevel for a specific component")]
+    #[arg(
+        long,
+        env = "DATAPLANE_LOG_LEVEL",
+        value_name = "loglevel for a specific component"
+    )]
     log_level: Vec<String>,
     // Non-eal params
-    #[arg(long, value_name = "packet driver to use: kernel or dpdk")]
+    #[arg(
+        long,
+        env = "DATAPLANE_DRIVER",
+        value_name = "packet driver to use: kernel or dpdk"
+    )]
     driver: Option<String>,
     #[arg(
         long,
+        env = "DATAPLANE_INTERFACE",
         value_name = "interface name",
         value_parser=InterfaceArg::from_str,
         value_delimiter=',',
@@ -128,6 +291,7 @@ E.g. --interface eth1,eth0=0000:02:01.0"
     /// Number of worker threads for the kernel driver.
     #[arg(
         long,
+        env = "DATAPLANE_NUM_WORKERS",
         value_name = "N",
         default_value_t = 1,
         value_parser = clap::value_parser!(u16).range(1..=64),
@@ -135,9 +299,27 @@ E.g. --interface eth1,eth0=0000:02:01.0"
     )]
     num_workers: u16,
 
+    /// Cores to pin the worker threads of the kernel driver to.
+    #[arg(
+        long,
+        env = "DATAPLANE_WORKER_CORES",
+        value_name = "CORE,..",
+        value_delimiter = ',',
+        help = "Cores to pin the worker threads of the kernel driver to, one per worker, in order. Workers are not pinned if not set"
+    )]
+    worker_cores: Vec<usize>,
+
+    #[arg(
+        long,
+        env = "DATAPLANE_WORK_STEALING",
+        help = "Let idle workers process packets queued for the busiest workers, for skewed traffic. Stolen packets may be processed out of order within their flow"
+    )]
+    work_stealing: bool,
+
     /// gRPC server address (IP:PORT for TCP or path for UNIX socket)
     #[arg(
         long,
+        env = "DATAPLANE_GRPC_ADDRESS",
         value_name = "ADDRESS",
         default_value = "[::1]:50051",
         help = "IP Address and port or UNIX socket path to listen for management connections"
@@ -145,11 +327,16 @@ E.g. --interface eth1,eth0=0000:02:01.0"
     grpc_address: String,
 
     /// Treat grpc-address as a UNIX socket path
-    #[arg(long, help = "Use a unix socket to listen for management connections")]
+    #[arg(
+        long,
+        env = "DATAPLANE_GRPC_UNIX_SOCKET",
+        help = "Use a unix socket to listen for management connections"
+    )]
     grpc_unix_socket: bool,
 
     #[arg(
         long,
+        env = "DATAPLANE_CPI_SOCK_PATH",
         value_name = "CPI Unix socket path",
         help = "Unix socket for FRR to send route update messages to the dataplane",
         default_value = DEFAULT_DP_UX_PATH
@@ -158,6 +345,26 @@ E.g. --interface eth1,eth0=0000:02:01.0"
 
     #[arg(
         long,
+        env = "DATAPLANE_CPI_GRACE_PERIOD",
+        value_name = "seconds",
+        help = "Time to keep the routes learnt from FRR when it goes away or restarts",
+        default_value_t = DEFAULT_CPI_GRACE_PERIOD.as_secs()
+    )]
+    cpi_grace_period: u64,
+
+    #[arg(
+        long,
+        env = "DATAPLANE_CPI_HOLD_TIME",
+        value_name = "seconds",
+        help = "Time without hearing from FRR after which it is considered gone",
+        default_value_t = DEFAULT_CPI_HOLD_TIME.as_secs(),
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    cpi_hold_time: u64,
+
+    #[arg(
+        long,
+        env = "DATAPLANE_CLI_SOCK_PATH",
         value_name = "CLI Unix socket path",
         help = "Unix socket to listen for dataplane cli connections",
         default_value = DEFAULT_DP_UX_PATH_CLI
@@ -166,6 +373,7 @@ E.g. --interface eth1,eth0=0000:02:01.0"
 
     #[arg(
         long,
+        env = "DATAPLANE_FRR_AGENT_PATH",
         value_name = "FRR Agent Unix socket path",
         help = "Unix socket to connect to FRR agent that controls FRR configuration reload",
         default_value = DEFAULT_FRR_AGENT_PATH
@@ -175,6 +383,7 @@ E.g. --interface eth1,eth0=0000:02:01.0"
     /// Prometheus metrics server bind address
     #[arg(
         long,
+        env = "DATAPLANE_METRICS_ADDRESS",
         value_name = "Metrics Address and Port",
         default_value_t = SocketAddr::from(([127, 0, 0, 1], 9090)),
         help = "Bind address and port for Prometheus metrics HTTP endpoint"
@@ -183,6 +392,33 @@ E.g. --interface eth1,eth0=0000:02:01.0"
 
     #[arg(
         long,
+        env = "DATAPLANE_STAGE_LATENCIES",
+        help = "Measure the latency of each stage of the packet-processing pipelines, exported as histograms with the metrics. This costs a few clock reads per packet and per stage"
+    )]
+    stage_latencies: bool,
+
+    #[arg(
+        long,
+        env = "DATAPLANE_FLOW_TABLE_CAPACITY",
+        value_name = "flows",
+        help = "Maximum number of flows in the flow table, including the stateful NAT sessions. When close to full, half-open TCP sessions get evicted first. 0 for no limit",
+        default_value_t = DEFAULT_FLOW_TABLE_CAPACITY
+    )]
+    flow_table_capacity: usize,
+
+    #[arg(
+        long,
+        env = "DATAPLANE_PIPELINE",
+        value_name = "TYPE[=NAME],..",
+        value_parser = parse_pipeline,
+        help = "Layout of the packet-processing pipelines, as the comma-separated list of their stages, with optional names.
+E.g. --pipeline dump=rx,ingress,ip-forward,egress,stats. The default layout is used if not set"
+    )]
+    pipeline: Option<PipelineConfig>,
+
+    #[arg(
+        long,
+        env = "DATAPLANE_SHOW_TRACING_TAGS",
         default_value_t = false,
         help = "Show the available tracing tags and exit"
     )]
@@ -190,16 +426,22 @@ E.g. --interface eth1,eth0=0000:02:01.0"
 
     #[arg(
         long,
+        env = "DATAPLANE_SHOW_TRACING_TARGETS",
         default_value_t = false,
         help = "Show configurable tracing targets and exit"
     )]
     show_tracing_targets: bool,
 
-    #[arg(long, help = "generate tracing configuration as a string and exit")]
+    #[arg(
+        long,
+        env = "DATAPLANE_TRACING_CONFIG_GENERATE",
+        help = "generate tracing configuration as a string and exit"
+    )]
     tracing_config_generate: bool,
 
     #[arg(
         long,
+        env = "DATAPLANE_TRACING",
         value_name = "tracing configuration",
         help = "Tracing config string as comma-separated sequence of tag=level, with level one in [off,error,warn,info,debug,trace].
 Passing default=level sets the default log-level.
@@ -207,9 +449,60 @@ Passing all=level allows setting the log-level of all targets to level.
 E.g. default=error,all=info,nat=debug will set the default target to error, and all the registered targets to info, but enable debug for nat"
     )]
     tracing: Option<String>,
+
+    #[arg(
+        long,
+        env = "DATAPLANE_CHECK",
+        help = "Check that the environment suits the other arguments (hugepages, devices, interfaces, CPUs, socket paths), print a report and exit"
+    )]
+    check: bool,
 }
 
 impl CmdArgs {
+    /// Parse the arguments of the process, completing them with the args file if any.
+    /// Prints the error and exits on failure, like [`Parser::parse`].
+    #[must_use]
+    pub fn load() -> Self {
+        Self::load_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse `args`, completing them with the args file if any.
+    ///
+    /// Values are taken, by order of precedence, from the command line, from the environment,
+    /// from the args file, and finally from the defaults.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the arguments are invalid, or if the args file can't be read or is invalid.
+    pub fn load_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let mut cmd = Self::command();
+        let matches = cmd.try_get_matches_from_mut(&args)?;
+        let Some(path) = matches.get_one::<PathBuf>(file::ARGS_FILE_ID) else {
+            return Self::from_arg_matches(&matches);
+        };
+        let from_file = file::file_args(&mut cmd, &matches, path)?;
+        // Values from the file go first, so that the command line overrides them
+        let mut args = args.into_iter();
+        let merged: Vec<OsString> = args
+            .next()
+            .into_iter()
+            .chain(from_file)
+            .chain(args)
+            .collect();
+        let matches = cmd.try_get_matches_from_mut(merged)?;
+        Self::from_arg_matches(&matches)
+    }
+
+    /// The args file the arguments were completed with, if any
+    pub fn args_file(&self) -> Option<&PathBuf> {
+        self.args_file.as_ref()
+    }
+
     pub fn get_driver_name(&self) -> &str {
         match &self.driver {
             None => "dpdk",
@@ -230,10 +523,28 @@ impl CmdArgs {
         self.tracing.as_ref()
     }
 
+    pub fn check(&self) -> bool {
+        self.check
+    }
+
+    /// Check that the environment suits the arguments
+    #[must_use]
+    pub fn preflight(&self) -> PreflightReport {
+        preflight::Preflight::new(self, "/").run()
+    }
+
     pub fn kernel_num_workers(&self) -> usize {
         self.num_workers.into()
     }
     // backwards-compatible, to deprecate
+    pub fn worker_cores(&self) -> Vec<usize> {
+        self.worker_cores.clone()
+    }
+
+    pub fn work_stealing(&self) -> bool {
+        self.work_stealing
+    }
+
     pub fn kernel_interfaces(&self) -> Vec<String> {
         self.interface
             .iter()
@@ -326,6 +637,14 @@ impl CmdArgs {
         self.cpi_sock_path.clone()
     }
 
+    pub fn cpi_grace_period(&self) -> Duration {
+        Duration::from_secs(self.cpi_grace_period)
+    }
+
+    pub fn cpi_hold_time(&self) -> Duration {
+        Duration::from_secs(self.cpi_hold_time)
+    }
+
     pub fn cli_sock_path(&self) -> String {
         self.cli_sock_path.clone()
     }
@@ -338,4 +657,18 @@ impl CmdArgs {
     pub fn metrics_address(&self) -> SocketAddr {
         self.metrics_address
     }
+
+    pub fn stage_latencies(&self) -> bool {
+        self.stage_latencies
+    }
+
+    /// Get the capacity of the flow table, if bounded
+    pub fn flow_table_capacity(&self) -> Option<usize> {
+        (self.flow_table_capacity != 0).then_some(self.flow_table_capacity)
+    }
+
+    /// Get the layout of the pipelines, the default one if none was set
+    pub fn pipeline(&self) -> PipelineConfig {
+        self.pipeline.clone().unwrap_or_default()
+    }
 }
@@ -0,0 +1,515 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Preflight checks (`--check`).
+//!
+//! Validate that the environment is suitable for the dataplane to start with the given arguments,
+//! rather than failing midway through initialization: hugepages, driver binding of the PCI devices
+//! to probe, writability of the socket paths, existence of the interfaces and availability of the
+//! CPUs for the lcores.
+
+use crate::CmdArgs;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Kernel drivers that DPDK can drive a device through
+const DPDK_DRIVERS: [&str; 4] = ["vfio-pci", "uio_pci_generic", "igb_uio", "mlx5_core"];
+
+/// Highest number of CPUs the Linux kernel supports (`CONFIG_NR_CPUS`): CPU lists naming CPUs
+/// beyond it are rejected, rather than expanded
+const MAX_CPUS: u32 = 8192;
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    /// The check passed
+    Pass,
+    /// The check found something suspicious, which may not prevent the dataplane from starting
+    Warn,
+    /// The dataplane will fail to start
+    Fail,
+    /// The check does not apply to this configuration
+    Skip,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+            CheckStatus::Skip => write!(f, "SKIP"),
+        }
+    }
+}
+
+/// Result of a single check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    /// What was checked, e.g. "hugepages" or "interface eth0"
+    pub name: String,
+    pub status: CheckStatus,
+    /// Human-readable details
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Results of all the preflight checks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreflightReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl PreflightReport {
+    /// Tell if no check failed
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.status != CheckStatus::Fail)
+    }
+
+    /// The checks which failed
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|c| c.status == CheckStatus::Fail)
+    }
+
+    fn add(&mut self, name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(CheckResult::new(name, status, detail));
+    }
+}
+
+impl Display for PreflightReport {
+    /// One line per check, `STATUS name: detail`, followed by a summary line
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self.checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for check in &self.checks {
+            writeln!(
+                f,
+                "{} {:width$} : {}",
+                check.status, check.name, check.detail
+            )?;
+        }
+        let failed = self.failures().count();
+        if failed == 0 {
+            write!(f, "preflight: all {} checks passed", self.checks.len())
+        } else {
+            write!(
+                f,
+                "preflight: {failed} of {} checks failed",
+                self.checks.len()
+            )
+        }
+    }
+}
+
+/// Parse a CPU list such as `0-3,8,10-11`, as found in `/proc/self/status` or `cpuset`
+pub(crate) fn parse_cpu_list(list: &str) -> Result<BTreeSet<u32>, String> {
+    let mut cpus = BTreeSet::new();
+    for item in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let parse = |s: &str| {
+            let cpu = s
+                .trim()
+                .parse::<u32>()
+                .map_err(|e| format!("Invalid CPU '{s}' in '{list}': {e}"))?;
+            if cpu >= MAX_CPUS {
+                return Err(format!("CPU {cpu} out of range in '{list}'"));
+            }
+            Ok(cpu)
+        };
+        match item.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last {
+                    return Err(format!("Invalid CPU range '{item}' in '{list}'"));
+                }
+                cpus.extend(first..=last);
+            }
+            None => {
+                cpus.insert(parse(item)?);
+            }
+        }
+    }
+    Ok(cpus)
+}
+
+/// CPUs used by an EAL `--lcores` mapping, such as `2-4` or `0@1,(1-3)@(4-6)`.
+/// Lcores without an explicit CPU set run on the CPU with the same number.
+pub(crate) fn parse_lcores_cpus(lcores: &str) -> Result<BTreeSet<u32>, String> {
+    let mut cpus = BTreeSet::new();
+    // split on the commas which are not within parentheses
+    let mut depth = 0;
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for (i, c) in lcores.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                groups.push(&lcores[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    groups.push(&lcores[start..]);
+    for group in groups {
+        let cpuset = match group.split_once('@') {
+            Some((_, cpuset)) => cpuset,
+            None => group,
+        };
+        let cpuset = cpuset.trim_start_matches('(').trim_end_matches(')');
+        cpus.extend(parse_cpu_list(cpuset)?);
+    }
+    Ok(cpus)
+}
+
+/// Check that a directory accepts new files, by creating (and removing) a probe file in it
+fn dir_writable(dir: &Path) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!("directory {} does not exist", dir.display()));
+    }
+    let probe = dir.join(format!(".dataplane-preflight-{}", std::process::id()));
+    std::fs::File::create(&probe)
+        .map_err(|e| format!("directory {} is not writable: {e}", dir.display()))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// Runs the checks against the system, with `/proc` and `/sys` found under `root`
+pub(crate) struct Preflight<'a> {
+    args: &'a CmdArgs,
+    root: PathBuf,
+}
+
+impl<'a> Preflight<'a> {
+    pub(crate) fn new(args: &'a CmdArgs, root: impl Into<PathBuf>) -> Self {
+        Self {
+            args,
+            root: root.into(),
+        }
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+
+    fn is_dpdk(&self) -> bool {
+        self.args.get_driver_name() == "dpdk"
+    }
+
+    pub(crate) fn run(&self) -> PreflightReport {
+        let mut report = PreflightReport::default();
+        self.check_driver(&mut report);
+        self.check_hugepages(&mut report);
+        self.check_devices(&mut report);
+        self.check_interfaces(&mut report);
+        self.check_cpus(&mut report);
+        self.check_sockets(&mut report);
+        report
+    }
+
+    fn check_driver(&self, report: &mut PreflightReport) {
+        match self.args.get_driver_name() {
+            name @ ("dpdk" | "kernel") => {
+                report.add("driver", CheckStatus::Pass, format!("using {name} driver"));
+            }
+            name => report.add(
+                "driver",
+                CheckStatus::Fail,
+                format!("unknown driver '{name}', must be one of dpdk or kernel"),
+            ),
+        }
+    }
+
+    fn check_hugepages(&self, report: &mut PreflightReport) {
+        const NAME: &str = "hugepages";
+        if !self.is_dpdk() {
+            report.add(NAME, CheckStatus::Skip, "not needed by the kernel driver");
+            return;
+        }
+        let meminfo = match std::fs::read_to_string(self.path("/proc/meminfo")) {
+            Ok(meminfo) => meminfo,
+            Err(e) => {
+                report.add(NAME, CheckStatus::Fail, format!("can't read meminfo: {e}"));
+                return;
+            }
+        };
+        let field = |name: &str| {
+            meminfo
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .and_then(|value| value.trim_start_matches(':').split_whitespace().next())
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+        let (Some(free), Some(total)) = (field("HugePages_Free"), field("HugePages_Total")) else {
+            report.add(
+                NAME,
+                CheckStatus::Fail,
+                "hugepages not supported by the kernel",
+            );
+            return;
+        };
+        let size = field("Hugepagesize").unwrap_or(0);
+        if free == 0 {
+            report.add(
+                NAME,
+                CheckStatus::Fail,
+                format!("no free hugepages ({total} of {size} kB reserved)"),
+            );
+        } else {
+            report.add(
+                NAME,
+                CheckStatus::Pass,
+                format!("{free} of {total} hugepages of {size} kB free"),
+            );
+        }
+    }
+
+    fn check_devices(&self, report: &mut PreflightReport) {
+        if !self.is_dpdk() {
+            return;
+        }
+        let devices = self
+            .args
+            .allow
+            .iter()
+            .filter_map(|allow| allow.split(',').next())
+            .map(str::to_string)
+            .chain(
+                self.args
+                    .interfaces()
+                    .filter_map(|i| i.pciaddr.as_ref())
+                    .map(ToString::to_string),
+            )
+            .collect::<BTreeSet<_>>();
+        for device in devices {
+            let name = format!("device {device}");
+            let sysfs = self.path(&format!("/sys/bus/pci/devices/{device}"));
+            if !sysfs.exists() {
+                report.add(name, CheckStatus::Fail, "no such PCI device");
+                continue;
+            }
+            let driver = std::fs::read_link(sysfs.join("driver"))
+                .ok()
+                .and_then(|link| Some(link.file_name()?.to_string_lossy().into_owned()));
+            match driver {
+                Some(driver) if DPDK_DRIVERS.contains(&driver.as_str()) => {
+                    report.add(name, CheckStatus::Pass, format!("bound to {driver}"));
+                }
+                Some(driver) => report.add(
+                    name,
+                    CheckStatus::Fail,
+                    format!(
+                        "bound to {driver}, expected one of {}",
+                        DPDK_DRIVERS.join(", ")
+                    ),
+                ),
+                None => report.add(name, CheckStatus::Fail, "not bound to any driver"),
+            }
+        }
+    }
+
+    fn check_interfaces(&self, report: &mut PreflightReport) {
+        for interface in self.args.interfaces() {
+            let name = format!("interface {}", interface.interface);
+            if self.is_dpdk() && interface.pciaddr.is_some() {
+                // DPDK ports are checked with the devices, they need not exist in the kernel
+                continue;
+            }
+            let sysfs = self.path(&format!("/sys/class/net/{}", interface.interface));
+            if sysfs.exists() {
+                report.add(name, CheckStatus::Pass, "exists");
+            } else {
+                report.add(name, CheckStatus::Fail, "no such interface");
+            }
+        }
+    }
+
+    fn check_cpus(&self, report: &mut PreflightReport) {
+        const NAME: &str = "cpus";
+        let status = match std::fs::read_to_string(self.path("/proc/self/status")) {
+            Ok(status) => status,
+            Err(e) => {
+                report.add(NAME, CheckStatus::Warn, format!("can't read status: {e}"));
+                return;
+            }
+        };
+        let allowed = status
+            .lines()
+            .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+            .map(parse_cpu_list);
+        let allowed = match allowed {
+            Some(Ok(allowed)) => allowed,
+            Some(Err(e)) => {
+                report.add(NAME, CheckStatus::Warn, e);
+                return;
+            }
+            None => {
+                report.add(NAME, CheckStatus::Warn, "can't find the allowed CPUs");
+                return;
+            }
+        };
+        if !self.is_dpdk() {
+            let workers = self.args.kernel_num_workers();
+            if workers > allowed.len() {
+                report.add(
+                    NAME,
+                    CheckStatus::Warn,
+                    format!("{workers} workers for {} allowed CPUs", allowed.len()),
+                );
+            } else {
+                report.add(
+                    NAME,
+                    CheckStatus::Pass,
+                    format!("{} CPUs allowed", allowed.len()),
+                );
+            }
+            return;
+        }
+        let lcores = self.args.lcores.as_deref().unwrap_or("2-4");
+        let mut wanted = match parse_lcores_cpus(lcores) {
+            Ok(wanted) => wanted,
+            Err(e) => {
+                report.add(NAME, CheckStatus::Fail, e);
+                return;
+            }
+        };
+        wanted.insert(u32::from(self.args.main_lcore));
+        let missing: Vec<_> = wanted.difference(&allowed).map(u32::to_string).collect();
+        if missing.is_empty() {
+            report.add(
+                NAME,
+                CheckStatus::Pass,
+                format!(
+                    "lcores {lcores} and main lcore {} allowed",
+                    self.args.main_lcore
+                ),
+            );
+        } else {
+            report.add(
+                NAME,
+                CheckStatus::Fail,
+                format!("CPUs {} not available to the process", missing.join(",")),
+            );
+        }
+    }
+
+    fn check_sockets(&self, report: &mut PreflightReport) {
+        let mut sockets = vec![
+            ("cpi socket", PathBuf::from(&self.args.cpi_sock_path)),
+            ("cli socket", PathBuf::from(&self.args.cli_sock_path)),
+        ];
+        if self.args.grpc_unix_socket {
+            sockets.push(("grpc socket", PathBuf::from(&self.args.grpc_address)));
+        }
+        for (name, socket) in sockets {
+            let dir = socket.parent().unwrap_or(Path::new("/"));
+            match dir_writable(&self.path(&dir.to_string_lossy())) {
+                Ok(()) => report.add(
+                    name,
+                    CheckStatus::Pass,
+                    format!("{} writable", dir.display()),
+                ),
+                Err(e) => report.add(name, CheckStatus::Fail, e),
+            }
+        }
+        let agent = self.path(&self.args.frr_agent_path);
+        if agent.exists() {
+            report.add("frr agent", CheckStatus::Pass, "socket exists");
+        } else {
+            report.add(
+                "frr agent",
+                CheckStatus::Warn,
+                format!("{} does not exist (yet)", self.args.frr_agent_path),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_lists() {
+        assert_eq!(
+            parse_cpu_list("0-2,5, 7-8\n").unwrap(),
+            BTreeSet::from([0, 1, 2, 5, 7, 8])
+        );
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a").is_err());
+        // huge ranges are rejected before being expanded
+        assert!(parse_cpu_list("0-4294967295").is_err());
+        assert!(parse_cpu_list("8192").is_err());
+
+        assert_eq!(parse_lcores_cpus("2-4").unwrap(), BTreeSet::from([2, 3, 4]));
+        assert_eq!(
+            parse_lcores_cpus("0@1,(1-3)@(4-6),8").unwrap(),
+            BTreeSet::from([1, 4, 5, 6, 8])
+        );
+    }
+
+    #[test]
+    fn test_preflight() {
+        let root = std::env::temp_dir().join(format!("preflight-{}", std::process::id()));
+        let mkdir = |path: &str| std::fs::create_dir_all(root.join(path)).unwrap();
+        mkdir("proc/self");
+        mkdir("sys/bus/pci/devices/0000:01:00.0");
+        mkdir("sys/bus/pci/drivers/vfio-pci");
+        mkdir("run/frr/hh");
+        std::os::unix::fs::symlink(
+            root.join("sys/bus/pci/drivers/vfio-pci"),
+            root.join("sys/bus/pci/devices/0000:01:00.0/driver"),
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("proc/meminfo"),
+            "HugePages_Total:    1024\nHugePages_Free:     1000\nHugepagesize:       2048 kB\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("proc/self/status"), "Cpus_allowed_list:\t0-3\n").unwrap();
+
+        let args = CmdArgs::load_from([
+            "dataplane",
+            "--allow",
+            "0000:01:00.0,dv_flow_en=1",
+            "--lcores",
+            "2-3",
+            "--cpi-sock-path",
+            "/run/frr/hh/dataplane.sock",
+            "--cli-sock-path",
+            "/run/frr/hh/cli.sock",
+        ])
+        .unwrap();
+        let report = Preflight::new(&args, &root).run();
+        assert!(report.passed(), "{report}");
+
+        let args = CmdArgs::load_from([
+            "dataplane",
+            "--allow",
+            "0000:02:00.0",
+            "--lcores",
+            "2-5",
+            "--cpi-sock-path",
+            "/nonexistent/dataplane.sock",
+        ])
+        .unwrap();
+        let report = Preflight::new(&args, &root).run();
+        let failed: Vec<_> = report.failures().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            failed,
+            vec!["device 0000:02:00.0", "cpus", "cpi socket", "cli socket"]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
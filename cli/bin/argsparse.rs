@@ -3,7 +3,7 @@
 
 //! Adds main parser for command arguments
 
-use dataplane_cli::cliproto::{RequestArgs, RouteProtocol};
+use dataplane_cli::cliproto::{RequestArgs, RouteProtocol, TransportProtocol};
 use log::Level;
 use std::collections::HashMap;
 use std::net::IpAddr;
@@ -125,6 +125,41 @@ impl CliArgs {
                     .map_err(|_| ArgsError::UnknownProtocol(protocol))?,
             );
         }
+        if let Some(transport) = args_map.remove("transport") {
+            if transport.is_empty() {
+                return Err(ArgsError::MissingValue("transport"));
+            }
+            args.remote.transport = Some(
+                TransportProtocol::from_str(&transport)
+                    .map_err(|_| ArgsError::UnknownProtocol(transport))?,
+            );
+        }
+        if let Some(offset) = args_map.remove("offset") {
+            if offset.is_empty() {
+                return Err(ArgsError::MissingValue("offset"));
+            }
+            args.remote.offset = Some(
+                offset
+                    .parse::<u32>()
+                    .map_err(|_| ArgsError::BadValue(offset))?,
+            );
+        }
+        if let Some(count) = args_map.remove("count") {
+            if count.is_empty() {
+                return Err(ArgsError::MissingValue("count"));
+            }
+            args.remote.count = Some(
+                count
+                    .parse::<u32>()
+                    .map_err(|_| ArgsError::BadValue(count))?,
+            );
+        }
+        if let Some(stage) = args_map.remove("stage") {
+            if stage.is_empty() {
+                return Err(ArgsError::MissingValue("stage"));
+            }
+            args.remote.stage = Some(stage);
+        }
         if !args_map.is_empty() {
             Err(ArgsError::UnrecognizedArgs(args_map))
         } else {
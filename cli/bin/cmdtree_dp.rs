@@ -4,7 +4,7 @@
 //! Builds our command tree for dataplane
 
 use crate::cmdtree::{Node, NodeArg};
-use dataplane_cli::cliproto::{CliAction, RouteProtocol};
+use dataplane_cli::cliproto::{CliAction, RouteProtocol, TransportProtocol};
 use log::Level;
 use std::convert::AsRef;
 use strum::IntoEnumIterator;
@@ -32,11 +32,30 @@ fn cmd_show_router_eventlog() -> Node {
         .desc("Show relevant router events")
         .action(CliAction::RouterEventLog as u16)
 }
+fn cmd_show_router_snapshot() -> Node {
+    Node::new("snapshot")
+        .desc("Dump the RIB and FIB as JSON")
+        .action(CliAction::ShowRouterSnapshot as u16)
+        .arg("vrfid")
+}
+fn cmd_show_router_mroutes() -> Node {
+    Node::new("mroutes")
+        .desc("Show multicast routes and the group members learnt with IGMP/MLD snooping")
+        .action(CliAction::ShowRouterMroutes as u16)
+}
+fn cmd_show_router_urpf() -> Node {
+    Node::new("urpf")
+        .desc("Show the uRPF mode of interfaces and the packets dropped by uRPF checks")
+        .action(CliAction::ShowRouterUrpf as u16)
+}
 fn cmd_show_router() -> Node {
     let mut root = Node::new("router");
     root += cmd_show_router_frrmi();
     root += cmd_show_router_cpi();
     root += cmd_show_router_eventlog();
+    root += cmd_show_router_snapshot();
+    root += cmd_show_router_mroutes();
+    root += cmd_show_router_urpf();
     root
 }
 
@@ -51,7 +70,12 @@ fn cmd_show_pipelines() -> Node {
 
     root += Node::new("stats")
         .desc("Show packet-processing pipeline statistics")
-        .action(CliAction::ShowPipelineStats as u16);
+        .action(CliAction::ShowPipelineStats as u16)
+        .arg("stage");
+
+    root += Node::new("traces")
+        .desc("Show the traces of the packets traced through the pipelines")
+        .action(CliAction::ShowPipelineTraces as u16);
 
     root
 }
@@ -203,6 +227,26 @@ fn cmd_show_nat() -> Node {
     let mut root = Node::new("nat").desc("Show NAT (network address translation)");
     root += Node::new("rules").desc("Dump the current NAT mappings");
     root += Node::new("port-usage").desc("Usage of transport ports");
+
+    let mut sessions = Node::new("sessions")
+        .desc("Show active stateful NAT sessions")
+        .action(CliAction::ShowNatSessions as u16)
+        .arg("vni")
+        .arg("prefix")
+        .arg("offset")
+        .arg("count");
+
+    let mut arg = NodeArg::new("transport");
+    TransportProtocol::iter().for_each(|proto| arg.add_choice(proto.as_ref()));
+    sessions = sessions.arg_add(arg);
+    root += sessions;
+
+    root += Node::new("port-blocks")
+        .desc("Show the port blocks assigned by deterministic NAT")
+        .action(CliAction::ShowNatPortBlocks as u16)
+        .arg("vni")
+        .arg("prefix");
+
     root
 }
 fn cmd_show_dpdk() -> Node {
@@ -217,6 +261,14 @@ fn cmd_show_kernel() -> Node {
     root += Node::new("interfaces").desc("Kernel interface status");
     root
 }
+fn cmd_show_lldp() -> Node {
+    let mut root = Node::new("lldp");
+    root += Node::new("neighbors")
+        .desc("Show neighbors discovered with LLDP")
+        .action(CliAction::ShowLldpNeighbors as u16)
+        .arg("ifname");
+    root
+}
 fn cmd_show_tracing() -> Node {
     let mut root = Node::new("tracing");
     root += Node::new("targets")
@@ -236,6 +288,7 @@ fn cmd_show() -> Node {
     root += cmd_show_routing();
     root += cmd_show_dpdk();
     root += cmd_show_kernel();
+    root += cmd_show_lldp();
     root += cmd_show_tracing();
     root
 }
@@ -252,9 +305,20 @@ fn cmd_loglevel() -> Node {
     root = root.arg_add(arg);
     root
 }
+fn cmd_pipeline_trace() -> Node {
+    let mut root = Node::new("pipeline");
+    root += Node::new("trace")
+        .desc("Trace the next packets through the pipelines (count 0 to stop)")
+        .action(CliAction::SetPipelineTrace as u16)
+        .arg("count")
+        .arg("address")
+        .arg("transport");
+    root
+}
 fn cmd_set() -> Node {
     let mut root = Node::new("set");
     root += cmd_loglevel();
+    root += cmd_pipeline_trace();
 
     root
 }
@@ -21,17 +21,29 @@ pub enum RouteProtocol {
     Bgp,
 }
 
+#[derive(AsRefStr, EnumString, Debug, Clone, Copy, Serialize, Deserialize, EnumIter)]
+#[strum(ascii_case_insensitive)]
+pub enum TransportProtocol {
+    Tcp,
+    Udp,
+    Icmp,
+}
+
 /// Arguments to a cli request
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[allow(unused)]
 pub struct RequestArgs {
-    pub address: Option<IpAddr>,         /* an IP address */
-    pub prefix: Option<(IpAddr, u8)>,    /* an IP prefix */
-    pub vrfid: Option<u32>,              /* Id of a VRF */
-    pub vni: Option<u32>,                /* Vxlan vni */
-    pub ifname: Option<String>,          /* name of interface */
-    pub loglevel: Option<Level>,         /* loglevel, from crate log */
-    pub protocol: Option<RouteProtocol>, /* a type of route or routing protocol */
+    pub address: Option<IpAddr>,              /* an IP address */
+    pub prefix: Option<(IpAddr, u8)>,         /* an IP prefix */
+    pub vrfid: Option<u32>,                   /* Id of a VRF */
+    pub vni: Option<u32>,                     /* Vxlan vni */
+    pub ifname: Option<String>,               /* name of interface */
+    pub loglevel: Option<Level>,              /* loglevel, from crate log */
+    pub protocol: Option<RouteProtocol>,      /* a type of route or routing protocol */
+    pub transport: Option<TransportProtocol>, /* a transport protocol */
+    pub offset: Option<u32>,                  /* index of the first entry to show */
+    pub count: Option<u32>,                   /* max number of entries to show */
+    pub stage: Option<String>,                /* name of a pipeline stage */
 }
 
 /// A Cli request
@@ -154,6 +166,8 @@ pub enum CliAction {
     ShowPipeline,
     ShowPipelineStages,
     ShowPipelineStats,
+    ShowPipelineTraces,
+    SetPipelineTrace,
 
     // router
     ShowRouterInterfaces,
@@ -171,6 +185,9 @@ pub enum CliAction {
     ShowRouterIpv6FibEntries,
     ShowRouterIpv4FibGroups,
     ShowRouterIpv6FibGroups,
+    ShowRouterSnapshot,
+    ShowRouterMroutes,
+    ShowRouterUrpf,
 
     // DPDK
     ShowDpdkPort,
@@ -182,6 +199,11 @@ pub enum CliAction {
     // nat
     ShowNatRules,
     ShowNatPortUsage,
+    ShowNatSessions,
+    ShowNatPortBlocks,
+
+    // lldp
+    ShowLldpNeighbors,
 }
 
 impl CliAction {
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Async synchronization primitives: [`Mutex`], [`Notify`] and [`mpsc`] channels.
+//!
+//! These are backed by tokio by default. With the `shuttle` feature, they are implemented on top
+//! of shuttle's synchronization primitives, so that async code using them can be model-checked
+//! the same way as sync code using [`crate::sync`]. Both implementations expose the same API, a
+//! subset of tokio's.
+//!
+//! They are not available with the `loom` feature.
+
+#[cfg(any(not(feature = "shuttle"), feature = "silence_clippy"))]
+mod tokio_impl;
+#[cfg(any(not(feature = "shuttle"), feature = "silence_clippy"))]
+pub use tokio_impl::*;
+
+#[cfg(all(
+    feature = "shuttle",
+    not(feature = "loom"),
+    not(feature = "silence_clippy")
+))]
+mod shuttle_impl;
+#[cfg(all(
+    feature = "shuttle",
+    not(feature = "loom"),
+    not(feature = "silence_clippy")
+))]
+pub use shuttle_impl::*;
+
+/// Errors of the channels, common to both implementations
+pub mod error {
+    /// The value could not be sent because the receiver is gone. The value is given back.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SendError<T>(pub T);
+
+    impl<T> std::fmt::Display for SendError<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl<T: std::fmt::Debug> std::error::Error for SendError<T> {}
+
+    /// Reasons why [`try_recv`](super::mpsc::Receiver::try_recv) got no value
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TryRecvError {
+        /// The channel is empty, but senders remain
+        Empty,
+        /// The channel is empty and all the senders are gone
+        Disconnected,
+    }
+
+    impl std::fmt::Display for TryRecvError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                TryRecvError::Empty => write!(f, "channel empty"),
+                TryRecvError::Disconnected => write!(f, "channel closed"),
+            }
+        }
+    }
+
+    impl std::error::Error for TryRecvError {}
+}
+
+#[cfg(test)]
+mod test;
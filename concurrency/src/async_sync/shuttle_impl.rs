@@ -0,0 +1,521 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Implementation of the async primitives on top of shuttle's synchronization primitives.
+//!
+//! Tasks waiting on a primitive register their waker under its (shuttle) lock and get woken when
+//! the state changes. Every lock acquisition is a scheduling point for shuttle, which is what lets
+//! it explore the interleavings of the tasks.
+
+use crate::sync::{Arc, Mutex as SyncMutex, MutexGuard as SyncMutexGuard};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::future::{Future, poll_fn};
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::PoisonError;
+use std::task::{Context, Poll, Waker};
+
+/// Lock a shuttle mutex. Poisoning only happens when a test already failed, so ignore it.
+fn lock<T>(mutex: &SyncMutex<T>) -> SyncMutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn wake_all(wakers: &mut Vec<Waker>) {
+    for waker in wakers.drain(..) {
+        waker.wake();
+    }
+}
+
+#[derive(Debug, Default)]
+struct MutexState {
+    locked: bool,
+    waiters: Vec<Waker>,
+}
+
+/// An async mutex
+pub struct Mutex<T> {
+    state: SyncMutex<MutexState>,
+    // Only ever locked by the holder of the logical lock in `state`, so never contended
+    value: SyncMutex<T>,
+}
+
+/// Guard of a locked [`Mutex`]
+pub struct MutexGuard<'a, T> {
+    guard: SyncMutexGuard<'a, T>,
+    state: &'a SyncMutex<MutexState>,
+}
+
+impl<T> Mutex<T> {
+    /// Create a mutex protecting `value`
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self {
+            state: SyncMutex::new(MutexState::default()),
+            value: SyncMutex::new(value),
+        }
+    }
+
+    /// Consume the mutex, returning the protected value
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.value
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Mutex<T> {
+    /// Lock the mutex, waiting until it is available
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        poll_fn(|cx| {
+            let mut state = lock(&self.state);
+            if state.locked {
+                state.waiters.push(cx.waker().clone());
+                Poll::Pending
+            } else {
+                state.locked = true;
+                Poll::Ready(())
+            }
+        })
+        .await;
+        self.guard()
+    }
+
+    /// Lock the mutex if it is available
+    #[must_use]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        {
+            let mut state = lock(&self.state);
+            if state.locked {
+                return None;
+            }
+            state.locked = true;
+        }
+        Some(self.guard())
+    }
+
+    fn guard(&self) -> MutexGuard<'_, T> {
+        MutexGuard {
+            guard: lock(&self.value),
+            state: &self.state,
+        }
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> Debug for Mutex<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mutex")
+            .field("locked", &lock(&self.state).locked)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Debug> Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut state = lock(self.state);
+        state.locked = false;
+        // wake everybody and let them race for the lock
+        wake_all(&mut state.waiters);
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+#[derive(Debug, Default)]
+struct NotifyState {
+    permit: bool,
+    // Incremented by notify_waiters()
+    generation: u64,
+    next_id: u64,
+    waiters: VecDeque<(u64, Waker)>,
+}
+
+/// Notifies a task to wake up
+pub struct Notify {
+    state: SyncMutex<NotifyState>,
+}
+
+/// Future returned by [`Notify::notified`]
+#[derive(Debug)]
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    generation: u64,
+    id: Option<u64>,
+}
+
+impl Notify {
+    /// Create a `Notify` with no stored permit
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            state: SyncMutex::new(NotifyState::default()),
+        }
+    }
+
+    /// Wait for a notification
+    #[must_use]
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            generation: lock(&self.state).generation,
+            id: None,
+        }
+    }
+
+    /// Wake up one waiting task, or store a permit for the next one to wait if there is none
+    pub fn notify_one(&self) {
+        let mut state = lock(&self.state);
+        state.permit = true;
+        if let Some((_, waker)) = state.waiters.pop_front() {
+            waker.wake();
+        }
+    }
+
+    /// Wake up all the tasks currently waiting
+    pub fn notify_waiters(&self) {
+        let mut state = lock(&self.state);
+        state.generation += 1;
+        for (_, waker) in state.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for Notify {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Notify")
+            .field("permit", &lock(&self.state).permit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Notified<'_> {
+    fn unregister(&mut self, state: &mut NotifyState) {
+        if let Some(id) = self.id.take() {
+            state.waiters.retain(|(waiter, _)| *waiter != id);
+        }
+    }
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let notify = self.notify;
+        let mut state = lock(&notify.state);
+        if state.generation != self.generation || state.permit {
+            if state.generation == self.generation {
+                state.permit = false;
+            }
+            self.unregister(&mut state);
+            return Poll::Ready(());
+        }
+        self.unregister(&mut state);
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiters.push_back((id, cx.waker().clone()));
+        self.id = Some(id);
+        Poll::Pending
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        if self.id.is_some() {
+            let notify = self.notify;
+            let mut state = lock(&notify.state);
+            self.unregister(&mut state);
+        }
+    }
+}
+
+/// Multi-producer, single-consumer channels
+pub mod mpsc {
+    use super::super::error::{SendError, TryRecvError};
+    use super::{Arc, SyncMutex, lock, wake_all};
+    use std::collections::VecDeque;
+    use std::future::poll_fn;
+    use std::task::{Poll, Waker};
+
+    struct ChanState<T> {
+        queue: VecDeque<T>,
+        capacity: Option<usize>,
+        senders: usize,
+        receiver_alive: bool,
+        recv_waker: Option<Waker>,
+        send_wakers: Vec<Waker>,
+    }
+
+    type Chan<T> = Arc<SyncMutex<ChanState<T>>>;
+
+    fn new_chan<T>(capacity: Option<usize>) -> Chan<T> {
+        Arc::new(SyncMutex::new(ChanState {
+            queue: VecDeque::new(),
+            capacity,
+            senders: 1,
+            receiver_alive: true,
+            recv_waker: None,
+            send_wakers: Vec::new(),
+        }))
+    }
+
+    fn clone_sender<T>(chan: &Chan<T>) -> Chan<T> {
+        lock(chan).senders += 1;
+        chan.clone()
+    }
+
+    fn drop_sender<T>(chan: &Chan<T>) {
+        let mut state = lock(chan);
+        state.senders -= 1;
+        if state.senders == 0
+            && let Some(waker) = state.recv_waker.take()
+        {
+            waker.wake();
+        }
+    }
+
+    fn try_send<T>(state: &mut ChanState<T>, value: T) -> Result<(), SendError<T>> {
+        if !state.receiver_alive {
+            return Err(SendError(value));
+        }
+        state.queue.push_back(value);
+        if let Some(waker) = state.recv_waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn try_recv<T>(chan: &Chan<T>) -> Result<T, TryRecvError> {
+        let mut state = lock(chan);
+        match state.queue.pop_front() {
+            Some(value) => {
+                wake_all(&mut state.send_wakers);
+                Ok(value)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    async fn recv<T>(chan: &Chan<T>) -> Option<T> {
+        poll_fn(|cx| {
+            let mut state = lock(chan);
+            if let Some(value) = state.queue.pop_front() {
+                wake_all(&mut state.send_wakers);
+                Poll::Ready(Some(value))
+            } else if state.senders == 0 {
+                Poll::Ready(None)
+            } else {
+                state.recv_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    fn close_receiver<T>(chan: &Chan<T>) {
+        let mut state = lock(chan);
+        state.receiver_alive = false;
+        state.queue.clear();
+        wake_all(&mut state.send_wakers);
+    }
+
+    /// Sending half of a bounded channel
+    pub struct Sender<T>(Chan<T>);
+
+    /// Receiving half of a bounded channel
+    pub struct Receiver<T>(Chan<T>);
+
+    /// Sending half of an unbounded channel
+    pub struct UnboundedSender<T>(Chan<T>);
+
+    /// Receiving half of an unbounded channel
+    pub struct UnboundedReceiver<T>(Chan<T>);
+
+    /// Create a channel holding at most `capacity` values
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    #[must_use]
+    pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        assert!(capacity > 0, "mpsc bounded channel requires capacity > 0");
+        let chan = new_chan(Some(capacity));
+        (Sender(chan.clone()), Receiver(chan))
+    }
+
+    /// Create a channel with no bound on the number of values it holds
+    #[must_use]
+    pub fn unbounded_channel<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+        let chan = new_chan(None);
+        (UnboundedSender(chan.clone()), UnboundedReceiver(chan))
+    }
+
+    macro_rules! impl_debug {
+        ($($name:ident),*) => {
+            $(
+                impl<T> std::fmt::Debug for $name<T> {
+                    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                        let state = lock(&self.0);
+                        f.debug_struct(stringify!($name))
+                            .field("len", &state.queue.len())
+                            .field("senders", &state.senders)
+                            .finish_non_exhaustive()
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_debug!(Sender, Receiver, UnboundedSender, UnboundedReceiver);
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            Self(clone_sender(&self.0))
+        }
+    }
+
+    impl<T> Clone for UnboundedSender<T> {
+        fn clone(&self) -> Self {
+            Self(clone_sender(&self.0))
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            drop_sender(&self.0);
+        }
+    }
+
+    impl<T> Drop for UnboundedSender<T> {
+        fn drop(&mut self) {
+            drop_sender(&self.0);
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            close_receiver(&self.0);
+        }
+    }
+
+    impl<T> Drop for UnboundedReceiver<T> {
+        fn drop(&mut self) {
+            close_receiver(&self.0);
+        }
+    }
+
+    impl<T> Sender<T> {
+        /// Send a value, waiting for room in the channel if it is full
+        ///
+        /// # Errors
+        ///
+        /// Fails, giving the value back, if the receiver is gone.
+        pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+            let mut value = Some(value);
+            poll_fn(|cx| {
+                let mut state = lock(&self.0);
+                let Some(v) = value.take() else {
+                    return Poll::Ready(Ok(()));
+                };
+                let full = state
+                    .capacity
+                    .is_some_and(|capacity| state.queue.len() >= capacity);
+                if full && state.receiver_alive {
+                    value = Some(v);
+                    state.send_wakers.push(cx.waker().clone());
+                    return Poll::Pending;
+                }
+                Poll::Ready(try_send(&mut state, v))
+            })
+            .await
+        }
+
+        /// Tell if the receiver is gone
+        #[must_use]
+        pub fn is_closed(&self) -> bool {
+            !lock(&self.0).receiver_alive
+        }
+    }
+
+    impl<T> UnboundedSender<T> {
+        /// Send a value
+        ///
+        /// # Errors
+        ///
+        /// Fails, giving the value back, if the receiver is gone.
+        pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+            try_send(&mut lock(&self.0), value)
+        }
+
+        /// Tell if the receiver is gone
+        #[must_use]
+        pub fn is_closed(&self) -> bool {
+            !lock(&self.0).receiver_alive
+        }
+    }
+
+    impl<T> Receiver<T> {
+        /// Receive a value, waiting for one if the channel is empty.
+        /// Returns `None` once the channel is empty and all the senders are gone.
+        pub async fn recv(&mut self) -> Option<T> {
+            recv(&self.0).await
+        }
+
+        /// Receive a value if one is available
+        ///
+        /// # Errors
+        ///
+        /// Fails if the channel is empty.
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            try_recv(&self.0)
+        }
+    }
+
+    impl<T> UnboundedReceiver<T> {
+        /// Receive a value, waiting for one if the channel is empty.
+        /// Returns `None` once the channel is empty and all the senders are gone.
+        pub async fn recv(&mut self) -> Option<T> {
+            recv(&self.0).await
+        }
+
+        /// Receive a value if one is available
+        ///
+        /// # Errors
+        ///
+        /// Fails if the channel is empty.
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            try_recv(&self.0)
+        }
+    }
+}
@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! The same scenarios run on tokio, or under shuttle with the `shuttle` feature
+
+use super::error::{SendError, TryRecvError};
+use super::{Mutex, Notify, mpsc};
+use crate::sync::Arc;
+
+const TASKS: usize = 3;
+
+#[cfg(any(not(feature = "shuttle"), feature = "silence_clippy"))]
+fn run<F: Future<Output = ()> + Send + 'static>(scenario: impl Fn() -> F) {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .build()
+        .unwrap()
+        .block_on(scenario());
+}
+
+#[cfg(any(not(feature = "shuttle"), feature = "silence_clippy"))]
+use tokio::spawn;
+
+#[cfg(all(
+    feature = "shuttle",
+    not(feature = "loom"),
+    not(feature = "silence_clippy")
+))]
+fn run<F: Future<Output = ()> + Send + 'static>(scenario: impl Fn() -> F + Send + Sync + 'static) {
+    shuttle::check_random(move || shuttle::future::block_on(scenario()), 1000);
+}
+
+#[cfg(all(
+    feature = "shuttle",
+    not(feature = "loom"),
+    not(feature = "silence_clippy")
+))]
+use shuttle::future::spawn;
+
+async fn mutex_counter() {
+    let counter = Arc::new(Mutex::new(0));
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let counter = counter.clone();
+            spawn(async move {
+                let mut guard = counter.lock().await;
+                let value = *guard;
+                *guard = value + 1;
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(*counter.lock().await, TASKS);
+    let guard = counter.try_lock().unwrap();
+    assert!(counter.try_lock().is_none());
+    drop(guard);
+}
+
+async fn notify_one() {
+    let notify = Arc::new(Notify::new());
+    let ready = Arc::new(Mutex::new(false));
+    let waiter = {
+        let notify = notify.clone();
+        let ready = ready.clone();
+        spawn(async move {
+            loop {
+                if *ready.lock().await {
+                    break;
+                }
+                notify.notified().await;
+            }
+        })
+    };
+    *ready.lock().await = true;
+    // the permit is stored if the waiter is not waiting yet, so no wakeup is lost
+    notify.notify_one();
+    waiter.await.unwrap();
+}
+
+async fn channel_bounded() {
+    let (tx, mut rx) = mpsc::channel(1);
+    let senders: Vec<_> = (0..TASKS)
+        .map(|i| {
+            let tx = tx.clone();
+            spawn(async move { tx.send(i).await.unwrap() })
+        })
+        .collect();
+    drop(tx);
+    let mut received = Vec::new();
+    while let Some(value) = rx.recv().await {
+        received.push(value);
+    }
+    received.sort_unstable();
+    assert_eq!(received, (0..TASKS).collect::<Vec<_>>());
+    assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    for sender in senders {
+        sender.await.unwrap();
+    }
+}
+
+async fn channel_unbounded_closed() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    tx.send(1).unwrap();
+    let receiver = spawn(async move { rx.recv().await });
+    assert_eq!(receiver.await.unwrap(), Some(1));
+    assert!(tx.is_closed());
+    assert_eq!(tx.send(2), Err(SendError(2)));
+}
+
+#[test]
+fn test_mutex() {
+    run(mutex_counter);
+}
+
+#[test]
+fn test_notify() {
+    run(notify_one);
+}
+
+#[test]
+fn test_channels() {
+    run(channel_bounded);
+    run(channel_unbounded_closed);
+}
@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Tokio-backed implementation of the async primitives
+
+use std::ops::{Deref, DerefMut};
+
+/// An async mutex, see [`tokio::sync::Mutex`]
+#[derive(Debug, Default)]
+pub struct Mutex<T>(tokio::sync::Mutex<T>);
+
+/// Guard of a locked [`Mutex`]
+#[derive(Debug)]
+pub struct MutexGuard<'a, T>(tokio::sync::MutexGuard<'a, T>);
+
+impl<T> Mutex<T> {
+    /// Create a mutex protecting `value`
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self(tokio::sync::Mutex::new(value))
+    }
+
+    /// Consume the mutex, returning the protected value
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+}
+
+impl<T> Mutex<T> {
+    /// Lock the mutex, waiting until it is available
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        MutexGuard(self.0.lock().await)
+    }
+
+    /// Lock the mutex if it is available
+    #[must_use]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.0.try_lock().ok().map(MutexGuard)
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+/// Notifies a task to wake up, see [`tokio::sync::Notify`]
+#[derive(Debug, Default)]
+pub struct Notify(tokio::sync::Notify);
+
+/// Future returned by [`Notify::notified`]
+pub type Notified<'a> = tokio::sync::futures::Notified<'a>;
+
+impl Notify {
+    /// Create a `Notify` with no stored permit
+    #[must_use]
+    pub fn new() -> Self {
+        Self(tokio::sync::Notify::new())
+    }
+
+    /// Wait for a notification
+    #[must_use]
+    pub fn notified(&self) -> Notified<'_> {
+        self.0.notified()
+    }
+
+    /// Wake up one waiting task, or store a permit for the next one to wait if there is none
+    pub fn notify_one(&self) {
+        self.0.notify_one();
+    }
+
+    /// Wake up all the tasks currently waiting
+    pub fn notify_waiters(&self) {
+        self.0.notify_waiters();
+    }
+}
+
+/// Multi-producer, single-consumer channels, see [`tokio::sync::mpsc`]
+pub mod mpsc {
+    use super::super::error::{SendError, TryRecvError};
+
+    /// Sending half of a bounded channel
+    #[derive(Debug)]
+    pub struct Sender<T>(tokio::sync::mpsc::Sender<T>);
+
+    /// Receiving half of a bounded channel
+    #[derive(Debug)]
+    pub struct Receiver<T>(tokio::sync::mpsc::Receiver<T>);
+
+    /// Sending half of an unbounded channel
+    #[derive(Debug)]
+    pub struct UnboundedSender<T>(tokio::sync::mpsc::UnboundedSender<T>);
+
+    /// Receiving half of an unbounded channel
+    #[derive(Debug)]
+    pub struct UnboundedReceiver<T>(tokio::sync::mpsc::UnboundedReceiver<T>);
+
+    /// Create a channel holding at most `capacity` values
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    #[must_use]
+    pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        let (tx, rx) = tokio::sync::mpsc::channel(capacity);
+        (Sender(tx), Receiver(rx))
+    }
+
+    /// Create a channel with no bound on the number of values it holds
+    #[must_use]
+    pub fn unbounded_channel<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (UnboundedSender(tx), UnboundedReceiver(rx))
+    }
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+
+    impl<T> Clone for UnboundedSender<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+
+    impl<T> Sender<T> {
+        /// Send a value, waiting for room in the channel if it is full
+        ///
+        /// # Errors
+        ///
+        /// Fails, giving the value back, if the receiver is gone.
+        pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+            self.0.send(value).await.map_err(|e| SendError(e.0))
+        }
+
+        /// Tell if the receiver is gone
+        #[must_use]
+        pub fn is_closed(&self) -> bool {
+            self.0.is_closed()
+        }
+    }
+
+    impl<T> UnboundedSender<T> {
+        /// Send a value
+        ///
+        /// # Errors
+        ///
+        /// Fails, giving the value back, if the receiver is gone.
+        pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+            self.0.send(value).map_err(|e| SendError(e.0))
+        }
+
+        /// Tell if the receiver is gone
+        #[must_use]
+        pub fn is_closed(&self) -> bool {
+            self.0.is_closed()
+        }
+    }
+
+    fn try_recv_error(e: tokio::sync::mpsc::error::TryRecvError) -> TryRecvError {
+        match e {
+            tokio::sync::mpsc::error::TryRecvError::Empty => TryRecvError::Empty,
+            tokio::sync::mpsc::error::TryRecvError::Disconnected => TryRecvError::Disconnected,
+        }
+    }
+
+    impl<T> Receiver<T> {
+        /// Receive a value, waiting for one if the channel is empty.
+        /// Returns `None` once the channel is empty and all the senders are gone.
+        pub async fn recv(&mut self) -> Option<T> {
+            self.0.recv().await
+        }
+
+        /// Receive a value if one is available
+        ///
+        /// # Errors
+        ///
+        /// Fails if the channel is empty.
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            self.0.try_recv().map_err(try_recv_error)
+        }
+    }
+
+    impl<T> UnboundedReceiver<T> {
+        /// Receive a value, waiting for one if the channel is empty.
+        /// Returns `None` once the channel is empty and all the senders are gone.
+        pub async fn recv(&mut self) -> Option<T> {
+            self.0.recv().await
+        }
+
+        /// Receive a value if one is available
+        ///
+        /// # Errors
+        ///
+        /// Fails if the channel is empty.
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            self.0.try_recv().map_err(try_recv_error)
+        }
+    }
+}
@@ -13,6 +13,10 @@
 #![allow(missing_docs)]
 
 pub mod macros;
+pub mod rcu;
+
+#[cfg(any(not(feature = "loom"), feature = "silence_clippy"))]
+pub mod async_sync;
 
 #[cfg(not(any(feature = "loom", feature = "shuttle")))]
 pub use std::sync;
@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Epoch-based read-copy-update cell.
+//!
+//! [`Rcu`] holds a value which readers access through cheap guards, and which writers replace
+//! wholesale. Replaced values are reclaimed by the writer once every reader which may still see
+//! them is gone, so readers never block and never run destructors.
+//!
+//! Compared to left-right, there is a single copy of the data and no operation log, but each
+//! update waits for a grace period: this is meant for data which is read all the time and updated
+//! extremely rarely (e.g., configuration snapshots).
+//!
+//! Readers announce themselves in one of two counters, selected by the parity of the current
+//! epoch. An update publishes the new value, flips the epoch, and waits for the counter of the
+//! previous epoch to drain before dropping the old value.
+
+use crate::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use crate::sync::{Mutex, MutexGuard};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// A read-mostly cell with deferred reclamation of replaced values
+pub struct Rcu<T> {
+    ptr: AtomicPtr<T>,
+    epoch: AtomicUsize,
+    readers: [AtomicUsize; 2],
+    // serializes updates
+    writer: Mutex<()>,
+    // Rcu<T> owns a T, which is shared between threads and dropped by whichever thread updates
+    _owns: PhantomData<std::sync::Arc<T>>,
+}
+
+/// Read access to the value of an [`Rcu`]. The value is not reclaimed while the guard lives.
+pub struct RcuGuard<'a, T> {
+    rcu: &'a Rcu<T>,
+    slot: usize,
+    value: &'a T,
+}
+
+impl<T> Rcu<T> {
+    /// Create a cell holding `value`
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self {
+            ptr: AtomicPtr::new(Box::into_raw(Box::new(value))),
+            epoch: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writer: Mutex::new(()),
+            _owns: PhantomData,
+        }
+    }
+
+    /// Get read access to the current value. This never blocks.
+    pub fn read(&self) -> RcuGuard<'_, T> {
+        let slot = loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            let slot = epoch & 1;
+            self.readers[slot].fetch_add(1, Ordering::SeqCst);
+            // If the epoch flipped in between, an update may already be waiting on the other
+            // slot and would not wait for us: retry in the new epoch.
+            // This check and the one of `replace` are read-modify-write operations, so that one of
+            // the two sides is guaranteed to see the other's write.
+            if self.epoch.fetch_add(0, Ordering::SeqCst) == epoch {
+                break slot;
+            }
+            self.readers[slot].fetch_sub(1, Ordering::SeqCst);
+        };
+        let ptr = self.ptr.load(Ordering::SeqCst);
+        // SAFETY: ptr comes from Box::into_raw and is only freed by `update` after the readers of
+        // the epoch in which it could be loaded are gone. We are registered in the current epoch,
+        // so it lives at least as long as the guard.
+        #[allow(unsafe_code)]
+        let value = unsafe { &*ptr };
+        RcuGuard {
+            rcu: self,
+            slot,
+            value,
+        }
+    }
+
+    /// Replace the value, and drop the previous one once no reader can access it anymore.
+    ///
+    /// This waits for the readers which started before the update to drop their guards, so it
+    /// must not be called while holding a guard of the same cell.
+    pub fn update(&self, value: T) {
+        drop(self.replace(value));
+    }
+
+    /// Replace the value, and return the previous one once no reader can access it anymore.
+    ///
+    /// This waits for the readers which started before the update to drop their guards, so it
+    /// must not be called while holding a guard of the same cell.
+    pub fn replace(&self, value: T) -> T {
+        let writer = self.lock_writer();
+        self.replace_locked(&writer, value)
+    }
+
+    /// Apply `f` to a copy of the current value and publish the result.
+    /// Updates are serialized for the whole operation, so concurrent modifications are not lost,
+    /// and `f` must not update the same cell.
+    /// See [`update`](Self::update) regarding the wait for readers.
+    pub fn modify(&self, f: impl FnOnce(&mut T))
+    where
+        T: Clone,
+    {
+        let writer = self.lock_writer();
+        let mut value = (*self.read()).clone();
+        f(&mut value);
+        drop(self.replace_locked(&writer, value));
+    }
+
+    fn lock_writer(&self) -> MutexGuard<'_, ()> {
+        self.writer
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Replace the value, with updates serialized by the caller
+    fn replace_locked(&self, _writer: &MutexGuard<'_, ()>, value: T) -> T {
+        let old = self
+            .ptr
+            .swap(Box::into_raw(Box::new(value)), Ordering::SeqCst);
+        let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+        // Readers registered in the previous epoch may hold the old value, new ones can't see it
+        while self.readers[epoch & 1].fetch_add(0, Ordering::SeqCst) != 0 {
+            crate::thread::yield_now();
+        }
+        // SAFETY: old comes from Box::into_raw, has been unpublished, and the grace period has
+        // elapsed: no reader holds a reference to it anymore.
+        #[allow(unsafe_code)]
+        let old = unsafe { Box::from_raw(old) };
+        *old
+    }
+}
+
+impl<T> Drop for Rcu<T> {
+    fn drop(&mut self) {
+        let ptr = self.ptr.load(Ordering::SeqCst);
+        // SAFETY: we have exclusive access, so there are no readers left
+        #[allow(unsafe_code)]
+        drop(unsafe { Box::from_raw(ptr) });
+    }
+}
+
+impl<T: Default> Default for Rcu<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Debug> Debug for Rcu<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Rcu").field(&*self.read()).finish()
+    }
+}
+
+impl<T> Deref for RcuGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: Debug> Debug for RcuGuard<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.value, f)
+    }
+}
+
+impl<T> Drop for RcuGuard<'_, T> {
+    fn drop(&mut self) {
+        self.rcu.readers[self.slot].fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(all(test, any(not(feature = "shuttle"), feature = "silence_clippy")))]
+mod test {
+    use super::Rcu;
+    use crate::sync::Arc;
+    use crate::sync::atomic::{AtomicBool, Ordering};
+
+    /// A value which records when it gets dropped
+    struct Tracked {
+        value: u32,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl Tracked {
+        fn new(value: u32) -> (Self, Arc<AtomicBool>) {
+            let dropped = Arc::new(AtomicBool::new(false));
+            (
+                Self {
+                    value,
+                    dropped: dropped.clone(),
+                },
+                dropped,
+            )
+        }
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[cfg(any(not(feature = "loom"), feature = "silence_clippy"))]
+    #[test]
+    fn test_rcu() {
+        let (first, first_dropped) = Tracked::new(1);
+        let rcu = Rcu::new(first);
+        let guard = rcu.read();
+        assert_eq!(guard.value, 1);
+        drop(guard);
+
+        let (second, second_dropped) = Tracked::new(2);
+        let old = rcu.replace(second);
+        assert_eq!(old.value, 1);
+        assert!(!first_dropped.load(Ordering::SeqCst));
+        drop(old);
+        assert!(first_dropped.load(Ordering::SeqCst));
+        assert_eq!(rcu.read().value, 2);
+
+        drop(rcu);
+        assert!(second_dropped.load(Ordering::SeqCst));
+
+        let numbers = Rcu::new(vec![1, 2]);
+        numbers.modify(|v| v.push(3));
+        assert_eq!(*numbers.read(), vec![1, 2, 3]);
+    }
+
+    #[cfg(all(feature = "loom", not(feature = "silence_clippy")))]
+    #[test]
+    fn loom_rcu_reclamation() {
+        loom::model(|| {
+            let (first, first_dropped) = Tracked::new(1);
+            let rcu = Arc::new(Rcu::new(first));
+
+            let reader = {
+                let rcu = rcu.clone();
+                loom::thread::spawn(move || {
+                    let guard = rcu.read();
+                    // whichever value we got, it must stay alive while we hold the guard
+                    let value = guard.value;
+                    assert!(!guard.dropped.load(Ordering::SeqCst));
+                    assert!(value == 1 || value == 2);
+                    value
+                })
+            };
+
+            let (second, second_dropped) = Tracked::new(2);
+            rcu.update(second);
+            // the update returns after the grace period, with the old value reclaimed
+            assert!(first_dropped.load(Ordering::SeqCst));
+            assert_eq!(rcu.read().value, 2);
+
+            reader.join().unwrap();
+            drop(rcu);
+            assert!(second_dropped.load(Ordering::SeqCst));
+        });
+    }
+
+    #[cfg(all(feature = "loom", not(feature = "silence_clippy")))]
+    #[test]
+    fn loom_rcu_concurrent_updates() {
+        loom::model(|| {
+            let rcu = Arc::new(Rcu::new(0_u32));
+            let writers: Vec<_> = (1..=2)
+                .map(|value| {
+                    let rcu = rcu.clone();
+                    loom::thread::spawn(move || rcu.update(value))
+                })
+                .collect();
+            let seen = *rcu.read();
+            assert!(seen <= 2);
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            let last = *rcu.read();
+            assert!(last == 1 || last == 2);
+        });
+    }
+
+    #[cfg(all(feature = "loom", not(feature = "silence_clippy")))]
+    #[test]
+    fn loom_rcu_concurrent_modifications() {
+        loom::model(|| {
+            let rcu = Arc::new(Rcu::new(0_u32));
+            let writers: Vec<_> = (0..2)
+                .map(|_| {
+                    let rcu = rcu.clone();
+                    loom::thread::spawn(move || rcu.modify(|value| *value += 1))
+                })
+                .collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            // no modification is lost
+            assert_eq!(*rcu.read(), 2);
+        });
+    }
+}
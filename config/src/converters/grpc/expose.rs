@@ -5,12 +5,109 @@ use ::gateway_config::google;
 use gateway_config::config as gateway_config;
 
 use std::convert::TryFrom;
+use std::net::IpAddr;
+use std::ops::RangeInclusive;
 
 use crate::external::overlay::vpcpeering::{
-    VpcExpose, VpcExposeNatConfig, VpcExposeStatefulNat, VpcExposeStatelessNat,
+    NatSessionTimeouts, VpcExpose, VpcExposeNatConfig, VpcExposeStatefulNat, VpcExposeStatelessNat,
+    VpcExposeStaticNat,
 };
 use lpm::prefix::{Prefix, PrefixString};
 
+fn port_range_from_grpc(range: &gateway_config::PortRange) -> Result<RangeInclusive<u16>, String> {
+    let start = u16::try_from(range.start)
+        .map_err(|_| format!("Invalid port range start: {}", range.start))?;
+    let end =
+        u16::try_from(range.end).map_err(|_| format!("Invalid port range end: {}", range.end))?;
+    Ok(start..=end)
+}
+
+fn duration_from_grpc(
+    duration: Option<google::protobuf::Duration>,
+) -> Result<Option<std::time::Duration>, String> {
+    duration
+        .map(|d| std::time::Duration::try_from(d).map_err(|e| format!("Invalid duration: {e}")))
+        .transpose()
+}
+
+fn duration_to_grpc(
+    duration: Option<std::time::Duration>,
+) -> Result<Option<google::protobuf::Duration>, String> {
+    duration
+        .map(|d| {
+            google::protobuf::Duration::try_from(d)
+                .map_err(|e| format!("Unable to convert duration: {e}"))
+        })
+        .transpose()
+}
+
+fn session_timeouts_from_grpc(
+    timeouts: &gateway_config::NatSessionTimeouts,
+) -> Result<NatSessionTimeouts, String> {
+    Ok(NatSessionTimeouts {
+        tcp_established: duration_from_grpc(timeouts.tcp_established)?,
+        tcp_transitory: duration_from_grpc(timeouts.tcp_transitory)?,
+        udp: duration_from_grpc(timeouts.udp)?,
+        icmp: duration_from_grpc(timeouts.icmp)?,
+    })
+}
+
+fn session_timeouts_to_grpc(
+    timeouts: &NatSessionTimeouts,
+) -> Result<Option<gateway_config::NatSessionTimeouts>, String> {
+    // No per-protocol timeout: leave the message out
+    if *timeouts == NatSessionTimeouts::default() {
+        return Ok(None);
+    }
+    Ok(Some(gateway_config::NatSessionTimeouts {
+        tcp_established: duration_to_grpc(timeouts.tcp_established)?,
+        tcp_transitory: duration_to_grpc(timeouts.tcp_transitory)?,
+        udp: duration_to_grpc(timeouts.udp)?,
+        icmp: duration_to_grpc(timeouts.icmp)?,
+    }))
+}
+
+fn static_binding_from_grpc(
+    binding: &gateway_config::StaticNatBinding,
+) -> Result<VpcExposeStaticNat, String> {
+    let port = |port: u32| u16::try_from(port).map_err(|_| format!("Invalid port: {port}"));
+    Ok(VpcExposeStaticNat {
+        internal: binding
+            .internal
+            .parse::<IpAddr>()
+            .map_err(|e| format!("Invalid address: {}: {e}", binding.internal))?,
+        public: binding
+            .public
+            .parse::<IpAddr>()
+            .map_err(|e| format!("Invalid address: {}: {e}", binding.public))?,
+        ports: binding
+            .ports
+            .as_ref()
+            .map(|ports| Ok::<_, String>((port(ports.internal)?, port(ports.public)?)))
+            .transpose()?,
+    })
+}
+
+fn static_binding_to_grpc(binding: &VpcExposeStaticNat) -> gateway_config::StaticNatBinding {
+    gateway_config::StaticNatBinding {
+        internal: binding.internal.to_string(),
+        public: binding.public.to_string(),
+        ports: binding
+            .ports
+            .map(|(internal, public)| gateway_config::StaticNatPorts {
+                internal: u32::from(internal),
+                public: u32::from(public),
+            }),
+    }
+}
+
+fn port_range_to_grpc(range: &RangeInclusive<u16>) -> gateway_config::PortRange {
+    gateway_config::PortRange {
+        start: u32::from(*range.start()),
+        end: u32::from(*range.end()),
+    }
+}
+
 impl TryFrom<&gateway_config::Expose> for VpcExpose {
     type Error = String;
 
@@ -58,6 +155,10 @@ impl TryFrom<&gateway_config::Expose> for VpcExpose {
             }
         }
 
+        if expose.r#as.is_empty() && !expose.exclusions.is_empty() {
+            return Err("NAT exclusions require 'as' prefixes".to_string());
+        }
+
         if !expose.r#as.is_empty() {
             vpc_expose = vpc_expose.make_nat();
             if let (Some(grpc_nat), Some(nat)) = (expose.nat.as_ref(), vpc_expose.nat.as_mut()) {
@@ -76,10 +177,46 @@ impl TryFrom<&gateway_config::Expose> for VpcExpose {
                                     std::time::Duration::try_from(t)
                                         .map_err(|e| format!("Invalid duration: {e}"))
                                 })?,
+                            port_range: grpc_s
+                                .port_range
+                                .as_ref()
+                                .map(port_range_from_grpc)
+                                .transpose()?,
+                            session_timeouts: grpc_s
+                                .session_timeouts
+                                .as_ref()
+                                .map(session_timeouts_from_grpc)
+                                .transpose()?
+                                .unwrap_or_default(),
+                            port_block_size: grpc_s
+                                .port_block_size
+                                .map(|size| {
+                                    u16::try_from(size)
+                                        .map_err(|_| format!("Invalid port block size: {size}"))
+                                })
+                                .transpose()?,
+                            endpoint_independent: grpc_s.endpoint_independent,
+                            static_bindings: grpc_s
+                                .static_bindings
+                                .iter()
+                                .map(static_binding_from_grpc)
+                                .collect::<Result<_, _>>()?,
                         });
                     }
                 }
             }
+
+            // Process NAT exclusions
+            for exclusion in &expose.exclusions {
+                let prefix = Prefix::try_from(PrefixString(&exclusion.cidr))
+                    .map_err(|e| format!("Invalid CIDR format: {}: {e}", exclusion.cidr))?;
+                let ports = exclusion
+                    .ports
+                    .as_ref()
+                    .map(port_range_from_grpc)
+                    .transpose()?;
+                vpc_expose = vpc_expose.no_nat(prefix, ports);
+            }
         }
 
         Ok(vpc_expose)
@@ -92,6 +229,7 @@ impl TryFrom<&VpcExpose> for gateway_config::Expose {
     fn try_from(expose: &VpcExpose) -> Result<Self, Self::Error> {
         let mut ips = Vec::new();
         let mut as_rules = Vec::new();
+        let mut exclusions = Vec::new();
 
         // Convert IP inclusion rules
         for prefix in &expose.ips {
@@ -118,6 +256,14 @@ impl TryFrom<&VpcExpose> for gateway_config::Expose {
                 as_rules.push(gateway_config::PeeringAs { rule: Some(rule) });
             }
 
+            // Convert NAT exclusions
+            for exclusion in &nat.exclusions {
+                exclusions.push(gateway_config::NatExclusion {
+                    cidr: exclusion.prefix.to_string(),
+                    ports: exclusion.ports.as_ref().map(port_range_to_grpc),
+                });
+            }
+
             match &nat.config {
                 VpcExposeNatConfig::Stateful(config) => {
                     let idle_timeout = google::protobuf::Duration::try_from(config.idle_timeout)
@@ -125,6 +271,15 @@ impl TryFrom<&VpcExpose> for gateway_config::Expose {
                     Some(gateway_config::expose::Nat::Stateful(
                         gateway_config::PeeringStatefulNat {
                             idle_timeout: Some(idle_timeout),
+                            port_range: config.port_range.as_ref().map(port_range_to_grpc),
+                            session_timeouts: session_timeouts_to_grpc(&config.session_timeouts)?,
+                            port_block_size: config.port_block_size.map(u32::from),
+                            endpoint_independent: config.endpoint_independent,
+                            static_bindings: config
+                                .static_bindings
+                                .iter()
+                                .map(static_binding_to_grpc)
+                                .collect(),
                         },
                     ))
                 }
@@ -139,6 +294,7 @@ impl TryFrom<&VpcExpose> for gateway_config::Expose {
             ips,
             r#as: as_rules,
             nat,
+            exclusions,
         })
     }
 }
@@ -45,14 +45,22 @@ pub use vrf::*;
 mod test {
     use gateway_config::GatewayConfig;
     use gateway_config::config::TracingConfig as ApiTracingConfig;
+    use lpm::prefix::Prefix;
     use pretty_assertions::assert_eq;
 
+    use std::time::Duration;
+
     use crate::converters::grpc::convert_gateway_config_from_grpc_with_defaults;
     use crate::converters::grpc::{
         convert_dataplane_status_from_grpc, convert_dataplane_status_to_grpc,
     };
+    use crate::external::overlay::vpcpeering::{
+        NatSessionTimeouts, VpcExpose, VpcExposeNatConfig, VpcExposeNatExclusion,
+        VpcExposeStaticNat,
+    };
     use crate::internal::device::DeviceConfig;
     use crate::internal::interfaces::interface::InterfaceConfig;
+    use crate::internal::routing::statics::{StaticRoute, StaticRouteNhop};
 
     fn normalize_order(config: &GatewayConfig) -> GatewayConfig {
         let mut config = config.clone();
@@ -90,6 +98,9 @@ mod test {
                     });
                 }
             });
+            underlay
+                .static_routes
+                .sort_by_key(|route| format!("{route:?}"));
         }
 
         config
@@ -202,7 +213,18 @@ mod test {
         };
 
         // Create Underlay
-        let underlay = gateway_config::Underlay { vrfs: vec![vrf] };
+        let underlay = gateway_config::Underlay {
+            vrfs: vec![vrf],
+            static_routes: vec![gateway_config::config::StaticRoute {
+                prefix: "10.10.0.0/16".to_string(),
+                action: gateway_config::config::StaticRouteAction::Forward.into(),
+                nhop_address: Some("10.0.0.2".to_string()),
+                nhop_interface: None,
+                nhop_vrf: None,
+                tag: None,
+                distance: Some(10),
+            }],
+        };
 
         // Create interfaces for VPCs
         let vpc1_if1 = gateway_config::Interface {
@@ -279,6 +301,7 @@ mod test {
             nat: Some(gateway_config::config::expose::Nat::Stateless(
                 gateway_config::config::PeeringStatelessNat {},
             )),
+            exclusions: Vec::new(),
         };
 
         let vpc2_expose = gateway_config::Expose {
@@ -287,6 +310,7 @@ mod test {
             nat: Some(gateway_config::config::expose::Nat::Stateless(
                 gateway_config::config::PeeringStatelessNat {},
             )),
+            exclusions: Vec::new(),
         };
 
         // Create PeeringEntryFor
@@ -413,6 +437,139 @@ mod test {
         assert!(!interface_back.ipaddrs.is_empty());
     }
 
+    #[test]
+    fn test_stateful_nat_expose_conversions() {
+        let expose = gateway_config::Expose {
+            ips: vec![gateway_config::PeeringIPs {
+                rule: Some(gateway_config::config::peering_i_ps::Rule::Cidr(
+                    "10.1.0.0/16".to_string(),
+                )),
+            }],
+            r#as: vec![gateway_config::PeeringAs {
+                rule: Some(gateway_config::config::peering_as::Rule::Cidr(
+                    "192.168.1.0/24".to_string(),
+                )),
+            }],
+            nat: Some(gateway_config::config::expose::Nat::Stateful(
+                gateway_config::config::PeeringStatefulNat {
+                    idle_timeout: Some(Duration::from_secs(60).try_into().unwrap()),
+                    port_range: Some(gateway_config::config::PortRange {
+                        start: 1024,
+                        end: 2047,
+                    }),
+                    session_timeouts: Some(gateway_config::config::NatSessionTimeouts {
+                        tcp_established: Some(Duration::from_secs(3600).try_into().unwrap()),
+                        tcp_transitory: None,
+                        udp: Some(Duration::from_secs(30).try_into().unwrap()),
+                        icmp: None,
+                    }),
+                    port_block_size: Some(64),
+                    endpoint_independent: true,
+                    static_bindings: vec![
+                        gateway_config::config::StaticNatBinding {
+                            internal: "10.1.0.1".to_string(),
+                            public: "192.168.1.1".to_string(),
+                            ports: None,
+                        },
+                        gateway_config::config::StaticNatBinding {
+                            internal: "10.1.0.2".to_string(),
+                            public: "192.168.1.2".to_string(),
+                            ports: Some(gateway_config::config::StaticNatPorts {
+                                internal: 8080,
+                                public: 80,
+                            }),
+                        },
+                    ],
+                },
+            )),
+            exclusions: vec![
+                gateway_config::config::NatExclusion {
+                    cidr: "10.1.1.0/24".to_string(),
+                    ports: None,
+                },
+                gateway_config::config::NatExclusion {
+                    cidr: "10.1.2.0/24".to_string(),
+                    ports: Some(gateway_config::config::PortRange { start: 22, end: 22 }),
+                },
+            ],
+        };
+
+        let vpc_expose = VpcExpose::try_from(&expose).unwrap();
+        let Some(VpcExposeNatConfig::Stateful(nat)) =
+            vpc_expose.nat.as_ref().map(|nat| &nat.config)
+        else {
+            panic!("expected stateful NAT, got {vpc_expose:?}");
+        };
+        assert_eq!(nat.idle_timeout, Duration::from_secs(60));
+        assert_eq!(nat.port_range, Some(1024..=2047));
+        assert_eq!(
+            nat.session_timeouts,
+            NatSessionTimeouts {
+                tcp_established: Some(Duration::from_secs(3600)),
+                tcp_transitory: None,
+                udp: Some(Duration::from_secs(30)),
+                icmp: None,
+            }
+        );
+        assert_eq!(nat.port_block_size, Some(64));
+        assert!(nat.endpoint_independent);
+        assert_eq!(
+            nat.static_bindings,
+            vec![
+                VpcExposeStaticNat {
+                    internal: "10.1.0.1".parse().unwrap(),
+                    public: "192.168.1.1".parse().unwrap(),
+                    ports: None,
+                },
+                VpcExposeStaticNat {
+                    internal: "10.1.0.2".parse().unwrap(),
+                    public: "192.168.1.2".parse().unwrap(),
+                    ports: Some((8080, 80)),
+                },
+            ]
+        );
+        assert_eq!(
+            vpc_expose.nat_exclusions(),
+            &[
+                VpcExposeNatExclusion {
+                    prefix: Prefix::expect_from(("10.1.1.0", 24)),
+                    ports: None,
+                },
+                VpcExposeNatExclusion {
+                    prefix: Prefix::expect_from(("10.1.2.0", 24)),
+                    ports: Some(22..=22),
+                },
+            ]
+        );
+
+        // Back to gRPC
+        let expose_back = gateway_config::Expose::try_from(&vpc_expose).unwrap();
+        assert_eq!(expose_back, expose);
+
+        // Ports and port block sizes must fit in 16 bits
+        let mut invalid = expose.clone();
+        if let Some(gateway_config::config::expose::Nat::Stateful(nat)) = &mut invalid.nat {
+            nat.port_range = Some(gateway_config::config::PortRange {
+                start: 1024,
+                end: 65536,
+            });
+        }
+        assert!(VpcExpose::try_from(&invalid).is_err());
+        let mut invalid = expose.clone();
+        if let Some(gateway_config::config::expose::Nat::Stateful(nat)) = &mut invalid.nat {
+            nat.port_block_size = Some(65536);
+        }
+        assert!(VpcExpose::try_from(&invalid).is_err());
+
+        // Exclusions only apply to exposes with NAT
+        let invalid = gateway_config::Expose {
+            r#as: Vec::new(),
+            nat: None,
+            ..expose
+        };
+        assert!(VpcExpose::try_from(&invalid).is_err());
+    }
+
     #[allow(clippy::too_many_lines)]
     fn create_test_status() -> gateway_config::GetDataplaneStatusResponse {
         // interface_statuses
@@ -439,7 +596,10 @@ mod test {
             applied_config_gen: 42,
             restarts: 1,
             applied_configs: 10,
-            failed_configs: 0,
+            failed_configs: 1,
+            rollbacks: 1,
+            rollback_failures: 0,
+            last_failed_config_gen: Some(41),
         });
 
         // Dataplane overall
@@ -565,6 +725,64 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_static_route_conversions() {
+        use gateway_config::config::StaticRouteAction::{Blackhole, Forward};
+
+        let route = |prefix: &str, action: gateway_config::config::StaticRouteAction| {
+            gateway_config::config::StaticRoute {
+                prefix: prefix.to_string(),
+                action: action.into(),
+                nhop_address: None,
+                nhop_interface: None,
+                nhop_vrf: None,
+                tag: None,
+                distance: None,
+            }
+        };
+
+        let forward = gateway_config::config::StaticRoute {
+            nhop_address: Some("10.0.0.2".to_string()),
+            nhop_interface: Some("eth0".to_string()),
+            tag: Some(7),
+            distance: Some(20),
+            ..route("10.10.0.0/16", Forward)
+        };
+        let static_route = StaticRoute::try_from(&forward).unwrap();
+        assert_eq!(
+            static_route,
+            StaticRoute::new(Prefix::expect_from(("10.10.0.0", 16)))
+                .nhop_addr_iface("10.0.0.2".parse().unwrap(), "eth0".to_string())
+                .tag(7)
+                .distance(20)
+        );
+        assert_eq!(
+            gateway_config::config::StaticRoute::try_from(&static_route).unwrap(),
+            forward
+        );
+
+        let blackhole = route("10.20.0.0/16", Blackhole);
+        let static_route = StaticRoute::try_from(&blackhole).unwrap();
+        assert_eq!(static_route.next_hop, StaticRouteNhop::Blackhole);
+        assert_eq!(
+            gateway_config::config::StaticRoute::try_from(&static_route).unwrap(),
+            blackhole
+        );
+
+        // forwarding needs a next-hop, and dropping excludes it
+        assert!(StaticRoute::try_from(&route("10.30.0.0/16", Forward)).is_err());
+        let blackhole_nhop = gateway_config::config::StaticRoute {
+            nhop_address: Some("10.0.0.2".to_string()),
+            ..route("10.30.0.0/16", Blackhole)
+        };
+        assert!(StaticRoute::try_from(&blackhole_nhop).is_err());
+        let bad_distance = gateway_config::config::StaticRoute {
+            distance: Some(256),
+            ..forward.clone()
+        };
+        assert!(StaticRoute::try_from(&bad_distance).is_err());
+    }
+
     #[test]
     fn test_convert_to_from_grpc_status() {
         let grpc_status = create_test_status();
@@ -280,6 +280,9 @@ impl TryFrom<&gateway_config::FrrStatus> for FrrStatus {
             restarts: p.restarts,
             applied_configs: p.applied_configs,
             failed_configs: p.failed_configs,
+            rollbacks: p.rollbacks,
+            rollback_failures: p.rollback_failures,
+            last_failed_config_gen: p.last_failed_config_gen,
         })
     }
 }
@@ -310,6 +313,9 @@ impl TryFrom<&FrrStatus> for gateway_config::FrrStatus {
             restarts: s.restarts,
             applied_configs: s.applied_configs,
             failed_configs: s.failed_configs,
+            rollbacks: s.rollbacks,
+            rollback_failures: s.rollback_failures,
+            last_failed_config_gen: s.last_failed_config_gen,
         })
     }
 }
@@ -2,11 +2,110 @@
 // Copyright Open Network Fabric Authors
 
 use gateway_config::config as gateway_config;
+use std::collections::BTreeSet;
+use std::net::IpAddr;
 use std::string::ToString;
 
 use crate::external::underlay::Underlay;
+use crate::internal::routing::statics::{StaticRoute, StaticRouteNhop};
 use crate::internal::routing::vrf::VrfConfig;
 
+use lpm::prefix::{Prefix, PrefixString};
+
+use gateway_config::StaticRouteAction;
+
+impl TryFrom<&gateway_config::StaticRoute> for StaticRoute {
+    type Error = String;
+
+    fn try_from(route: &gateway_config::StaticRoute) -> Result<Self, Self::Error> {
+        let prefix = Prefix::try_from(PrefixString(&route.prefix))
+            .map_err(|e| format!("Invalid static route prefix {}: {e}", route.prefix))?;
+        let address = route
+            .nhop_address
+            .as_ref()
+            .map(|address| {
+                address
+                    .parse::<IpAddr>()
+                    .map_err(|e| format!("Invalid next-hop address {address}: {e}"))
+            })
+            .transpose()?;
+        let interface = route.nhop_interface.clone();
+        let action = StaticRouteAction::try_from(route.action)
+            .map_err(|_| format!("Unknown static route action: {}", route.action))?;
+        let next_hop = match (action, address, interface) {
+            (StaticRouteAction::Forward, Some(address), Some(interface)) => {
+                StaticRouteNhop::AddressInterface(address, interface)
+            }
+            (StaticRouteAction::Forward, Some(address), None) => StaticRouteNhop::Address(address),
+            (StaticRouteAction::Forward, None, Some(interface)) => {
+                StaticRouteNhop::Interface(interface)
+            }
+            (StaticRouteAction::Forward, None, None) => {
+                return Err(format!("Static route to {prefix} has no next-hop"));
+            }
+            (_, Some(_), _) | (_, _, Some(_)) => {
+                return Err(format!(
+                    "Static route to {prefix} has a next-hop but does not forward traffic"
+                ));
+            }
+            (StaticRouteAction::Blackhole, None, None) => StaticRouteNhop::Blackhole,
+            (StaticRouteAction::Reject, None, None) => StaticRouteNhop::Reject,
+            (StaticRouteAction::Null0, None, None) => StaticRouteNhop::Null0,
+        };
+        let distance = route
+            .distance
+            .map(|distance| {
+                u8::try_from(distance)
+                    .map_err(|_| format!("Invalid static route distance: {distance}"))
+            })
+            .transpose()?;
+
+        Ok(StaticRoute {
+            prefix,
+            next_hop,
+            next_hop_vrf: route.nhop_vrf.clone(),
+            tag: route.tag,
+            distance,
+        })
+    }
+}
+
+impl TryFrom<&StaticRoute> for gateway_config::StaticRoute {
+    type Error = String;
+
+    fn try_from(route: &StaticRoute) -> Result<Self, Self::Error> {
+        let (action, nhop_address, nhop_interface) = match &route.next_hop {
+            StaticRouteNhop::Unset => {
+                return Err(format!("Static route to {} has no next-hop", route.prefix));
+            }
+            StaticRouteNhop::Interface(interface) => {
+                (StaticRouteAction::Forward, None, Some(interface.clone()))
+            }
+            StaticRouteNhop::Address(address) => {
+                (StaticRouteAction::Forward, Some(address.to_string()), None)
+            }
+            StaticRouteNhop::AddressInterface(address, interface) => (
+                StaticRouteAction::Forward,
+                Some(address.to_string()),
+                Some(interface.clone()),
+            ),
+            StaticRouteNhop::Null0 => (StaticRouteAction::Null0, None, None),
+            StaticRouteNhop::Blackhole => (StaticRouteAction::Blackhole, None, None),
+            StaticRouteNhop::Reject => (StaticRouteAction::Reject, None, None),
+        };
+
+        Ok(gateway_config::StaticRoute {
+            prefix: route.prefix.to_string(),
+            action: action.into(),
+            nhop_address,
+            nhop_interface,
+            nhop_vrf: route.next_hop_vrf.clone(),
+            tag: route.tag,
+            distance: route.distance.map(u32::from),
+        })
+    }
+}
+
 impl TryFrom<&gateway_config::Underlay> for Underlay {
     type Error = String;
 
@@ -26,10 +125,18 @@ impl TryFrom<&gateway_config::Underlay> for Underlay {
         // Convert VRF to VrfConfig
         let vrf_config = VrfConfig::try_from(default_vrf)?;
 
+        // Convert the static routes, programmed in the default VRF
+        let static_routes = underlay
+            .static_routes
+            .iter()
+            .map(StaticRoute::try_from)
+            .collect::<Result<BTreeSet<_>, _>>()?;
+
         // Create Underlay with the VRF config
         Ok(Underlay {
             vrf: vrf_config,
             vtep: None,
+            static_routes,
         })
     }
 }
@@ -41,8 +148,16 @@ impl TryFrom<&Underlay> for gateway_config::Underlay {
         // Convert the VRF
         let vrf_grpc = gateway_config::Vrf::try_from(&underlay.vrf)?;
 
+        // Convert the static routes
+        let static_routes = underlay
+            .static_routes
+            .iter()
+            .map(gateway_config::StaticRoute::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
         Ok(gateway_config::Underlay {
             vrfs: vec![vrf_grpc],
+            static_routes,
         })
     }
 }
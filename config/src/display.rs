@@ -10,7 +10,9 @@ use std::fmt::Display;
 
 use crate::external::overlay::vpc::{Peering, VpcId, VpcTable};
 use crate::external::overlay::vpcpeering::VpcManifest;
-use crate::external::overlay::vpcpeering::{VpcExpose, VpcPeering, VpcPeeringTable};
+use crate::external::overlay::vpcpeering::{
+    VpcExpose, VpcExposeNatExclusion, VpcExposeStaticNat, VpcPeering, VpcPeeringTable,
+};
 
 struct Heading(String);
 const LINE_WIDTH: usize = 81;
@@ -25,6 +27,29 @@ impl Display for Heading {
 
 const SEP: &str = "       ";
 
+impl Display for VpcExposeNatExclusion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.prefix)?;
+        if let Some(ports) = &self.ports {
+            write!(f, " ports {}-{}", ports.start(), ports.end())?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for VpcExposeStaticNat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.ports {
+            Some((internal_port, public_port)) => write!(
+                f,
+                "{}:{internal_port}->{}:{public_port}",
+                self.internal, self.public
+            ),
+            None => write!(f, "{}->{}", self.internal, self.public),
+        }
+    }
+}
+
 impl Display for VpcExpose {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut carriage = false;
@@ -59,6 +84,29 @@ impl Display for VpcExpose {
                 });
                 carriage = true;
             }
+
+            if !nat.exclusions.is_empty() {
+                if carriage {
+                    writeln!(f)?;
+                }
+                write!(f, "{SEP}   no NAT:")?;
+                nat.exclusions.iter().for_each(|x| {
+                    let _ = write!(f, " {x}");
+                });
+                carriage = true;
+            }
+
+            let static_bindings = self.nat_static_bindings();
+            if !static_bindings.is_empty() {
+                if carriage {
+                    writeln!(f)?;
+                }
+                write!(f, "{SEP}   static:")?;
+                static_bindings.iter().for_each(|x| {
+                    let _ = write!(f, " {x}");
+                });
+                carriage = true;
+            }
         }
         if carriage { writeln!(f) } else { Ok(()) }
     }
@@ -18,6 +18,7 @@ pub mod test {
     use crate::external::overlay::vpcpeering::{VpcPeering, VpcPeeringTable};
 
     use lpm::prefix::Prefix;
+    use std::net::IpAddr;
 
     /* Build sample manifests for a peering */
     fn build_manifest_vpc1() -> VpcManifest {
@@ -206,6 +207,140 @@ pub mod test {
         );
     }
 
+    #[test]
+    fn test_expose_validate_nat_exclusions() {
+        let expose = VpcExpose::empty()
+            .ip("10.0.0.0/16".into())
+            .not("10.0.1.0/24".into())
+            .as_range("2.0.0.0/16".into())
+            .not_as("2.0.0.0/24".into())
+            .no_nat("10.0.2.0/24".into(), None)
+            .no_nat("10.0.3.0/24".into(), Some(22..=22));
+        assert_eq!(expose.validate(), Ok(()));
+        assert_eq!(expose.nat_exclusions().len(), 2);
+
+        // Incorrect: no NAT to make exceptions for
+        let expose = VpcExpose::empty()
+            .ip("10.0.0.0/16".into())
+            .no_nat("10.0.2.0/24".into(), None);
+        assert!(matches!(expose.validate(), Err(ConfigError::Invalid(_))));
+
+        // Incorrect: exception outside of exposed prefixes
+        let expose = VpcExpose::empty()
+            .ip("10.0.0.0/16".into())
+            .as_range("2.0.0.0/16".into())
+            .no_nat("10.1.0.0/24".into(), None);
+        assert_eq!(
+            expose.validate(),
+            Err(ConfigError::OutOfRangeExclusionPrefix("10.1.0.0/24".into()))
+        );
+
+        // Incorrect: exception for excluded prefix
+        let expose = VpcExpose::empty()
+            .ip("10.0.0.0/16".into())
+            .not("10.0.1.0/24".into())
+            .as_range("2.0.0.0/16".into())
+            .not_as("2.0.0.0/24".into())
+            .no_nat("10.0.1.128/25".into(), None);
+        assert_eq!(
+            expose.validate(),
+            Err(ConfigError::OutOfRangeExclusionPrefix(
+                "10.0.1.128/25".into()
+            ))
+        );
+
+        // Incorrect: empty port range
+        #[allow(clippy::reversed_empty_ranges)]
+        let expose = VpcExpose::empty()
+            .ip("10.0.0.0/16".into())
+            .as_range("2.0.0.0/16".into())
+            .no_nat("10.0.2.0/24".into(), Some(80..=79));
+        assert!(matches!(expose.validate(), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_expose_validate_nat_static_bindings() {
+        let addr = |ip: &str| ip.parse::<IpAddr>().unwrap();
+        let stateful_expose = || {
+            VpcExpose::empty()
+                .make_stateful_nat(None)
+                .unwrap()
+                .ip("10.0.0.0/16".into())
+                .not("10.0.1.0/24".into())
+                .as_range("2.0.0.0/16".into())
+        };
+
+        let expose = stateful_expose()
+            .make_nat_static_binding(addr("10.0.0.1"), addr("2.0.0.1"), None)
+            .unwrap()
+            .make_nat_static_binding(addr("10.0.0.2"), addr("2.0.0.2"), Some((80, 8080)))
+            .unwrap()
+            .make_nat_static_binding(addr("10.0.0.3"), addr("2.0.0.2"), Some((80, 8081)))
+            .unwrap();
+        assert_eq!(expose.validate(), Ok(()));
+        assert_eq!(expose.nat_static_bindings().len(), 3);
+
+        // Incorrect: not stateful NAT
+        let expose = VpcExpose::empty()
+            .make_stateless_nat()
+            .unwrap()
+            .ip("10.0.0.0/16".into())
+            .as_range("2.0.0.0/16".into());
+        assert!(
+            expose
+                .make_nat_static_binding(addr("10.0.0.1"), addr("2.0.0.1"), None)
+                .is_err()
+        );
+
+        // Incorrect: out of the exposed prefixes
+        for (internal, public) in [
+            ("10.1.0.1", "2.0.0.1"),
+            ("10.0.1.1", "2.0.0.1"),
+            ("10.0.0.1", "3.0.0.1"),
+            ("10.0.0.1", "2::1"),
+        ] {
+            let expose = stateful_expose()
+                .make_nat_static_binding(addr(internal), addr(public), None)
+                .unwrap();
+            assert!(matches!(expose.validate(), Err(ConfigError::Invalid(_))));
+        }
+
+        // Incorrect: port 0
+        let expose = stateful_expose()
+            .make_nat_static_binding(addr("10.0.0.1"), addr("2.0.0.1"), Some((0, 8080)))
+            .unwrap();
+        assert!(matches!(expose.validate(), Err(ConfigError::Invalid(_))));
+
+        // Incorrect: conflicting bindings
+        for (first, second) in [
+            (("10.0.0.1", "2.0.0.1", None), ("10.0.0.2", "2.0.0.1", None)),
+            (("10.0.0.1", "2.0.0.1", None), ("10.0.0.1", "2.0.0.2", None)),
+            (
+                ("10.0.0.1", "2.0.0.1", None),
+                ("10.0.0.2", "2.0.0.1", Some((80, 80))),
+            ),
+            (
+                ("10.0.0.1", "2.0.0.1", Some((80, 8080))),
+                ("10.0.0.2", "2.0.0.1", Some((80, 8080))),
+            ),
+        ] {
+            let expose = stateful_expose()
+                .make_nat_static_binding(addr(first.0), addr(first.1), first.2)
+                .unwrap()
+                .make_nat_static_binding(addr(second.0), addr(second.1), second.2)
+                .unwrap();
+            assert!(matches!(expose.validate(), Err(ConfigError::Invalid(_))));
+        }
+
+        // Incorrect: static bindings with port blocks
+        let expose = stateful_expose()
+            .make_nat_port_blocks(256)
+            .unwrap()
+            .make_nat_static_binding(addr("10.0.0.1"), addr("2.0.0.1"), None)
+            .unwrap();
+        assert!(matches!(expose.validate(), Err(ConfigError::Invalid(_))));
+    }
+
     #[test]
     fn test_manifest_expose_overlap() {
         let expose1 = VpcExpose::empty()
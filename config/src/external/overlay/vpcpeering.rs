@@ -6,26 +6,73 @@
 use lpm::prefix::{Prefix, PrefixSize};
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::net::IpAddr;
 use std::ops::Bound::{Excluded, Unbounded};
+use std::ops::RangeInclusive;
 use std::time::Duration;
 use tracing::debug;
 
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct VpcExposeStatelessNat;
 
+/// Per-protocol idle timeouts for stateful NAT sessions. Protocols with no timeout set use the
+/// `idle_timeout` of the [`VpcExposeStatefulNat`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NatSessionTimeouts {
+    /// TCP sessions, once the connection is established
+    pub tcp_established: Option<Duration>,
+    /// TCP sessions, while the connection is being opened or closed
+    pub tcp_transitory: Option<Duration>,
+    pub udp: Option<Duration>,
+    pub icmp: Option<Duration>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct VpcExposeStatefulNat {
     pub idle_timeout: Duration,
+    /// The ports to translate into (port address translation). If `None`, all ports are used.
+    pub port_range: Option<RangeInclusive<u16>>,
+    pub session_timeouts: NatSessionTimeouts,
+    /// Deterministic NAT: the number of ports from the port range to assign to each address to
+    /// translate, always on the same public address. If `None`, ports are allocated dynamically.
+    pub port_block_size: Option<u16>,
+    /// Endpoint-independent mapping and filtering for UDP (RFC 4787): each internal address and
+    /// port keeps the same public address and port for all remote endpoints, and packets from any
+    /// remote endpoint are accepted for this public address and port while the mapping is in use.
+    pub endpoint_independent: bool,
+    /// Static 1:1 bindings and port forwarding rules, applied before dynamic allocation
+    pub static_bindings: Vec<VpcExposeStaticNat>,
 }
 
 impl Default for VpcExposeStatefulNat {
     fn default() -> Self {
         VpcExposeStatefulNat {
             idle_timeout: Duration::from_secs(120),
+            port_range: None,
+            session_timeouts: NatSessionTimeouts::default(),
+            port_block_size: None,
+            endpoint_independent: false,
+            static_bindings: Vec::new(),
         }
     }
 }
 
+/// A static NAT binding, for stateful NAT: the internal address (from `ips`) always translates
+/// into the public address (from `as_range`), and packets from any remote endpoint to the public
+/// address are translated back to the internal address. The public address is no longer used for
+/// dynamic allocation, and ports are preserved.
+///
+/// With ports, the binding is a port forwarding rule for TCP and UDP instead: only the internal
+/// port is bound to the public port, and the public address remains available for dynamic
+/// allocation on other ports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VpcExposeStaticNat {
+    pub internal: IpAddr,
+    pub public: IpAddr,
+    /// Internal port and public port, for port forwarding rules
+    pub ports: Option<(u16, u16)>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum VpcExposeNatConfig {
     Stateful(VpcExposeStatefulNat),
@@ -39,11 +86,21 @@ impl Default for VpcExposeNatConfig {
     }
 }
 
+/// A "no-NAT" exception for a [`VpcExpose`] with NAT: traffic from or to the addresses of the
+/// prefix, on the given ports (all ports if `None`), bypasses address translation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VpcExposeNatExclusion {
+    pub prefix: Prefix,
+    pub ports: Option<RangeInclusive<u16>>,
+}
+
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct VpcExposeNat {
     pub as_range: BTreeSet<Prefix>,
     pub not_as: BTreeSet<Prefix>,
     pub config: VpcExposeNatConfig,
+    /// Prefixes (within `ips`) and ports for which NAT does not apply
+    pub exclusions: Vec<VpcExposeNatExclusion>,
 }
 
 impl VpcExposeNat {
@@ -113,9 +170,9 @@ impl VpcExpose {
     ) -> Result<Self, ConfigError> {
         match self.nat.as_mut() {
             Some(nat) if nat.is_stateful() => {
-                nat.config = VpcExposeNatConfig::Stateful(VpcExposeStatefulNat {
-                    idle_timeout: idle_timeout.unwrap_or_default(),
-                });
+                if let VpcExposeNatConfig::Stateful(config) = &mut nat.config {
+                    config.idle_timeout = idle_timeout.unwrap_or_default();
+                }
                 Ok(self)
             }
             Some(_) => Err(ConfigError::Invalid(format!(
@@ -125,6 +182,11 @@ impl VpcExpose {
                 self.nat = Some(VpcExposeNat {
                     config: VpcExposeNatConfig::Stateful(VpcExposeStatefulNat {
                         idle_timeout: idle_timeout.unwrap_or_default(),
+                        port_range: None,
+                        session_timeouts: NatSessionTimeouts::default(),
+                        port_block_size: None,
+                        endpoint_independent: false,
+                        static_bindings: Vec::new(),
                     }),
                     ..VpcExposeNat::default()
                 });
@@ -133,6 +195,184 @@ impl VpcExpose {
         }
     }
 
+    // Restrict the ports that stateful NAT for the [`VpcExpose`] translates into to `port_range`.
+    //
+    // # Errors
+    //
+    // Returns an error if the [`VpcExpose`] is not in stateful mode, or if the range is invalid.
+    pub fn make_nat_port_range(
+        mut self,
+        port_range: RangeInclusive<u16>,
+    ) -> Result<Self, ConfigError> {
+        validate_nat_port_range(&port_range)?;
+        match self.nat.as_mut().map(|nat| &mut nat.config) {
+            Some(VpcExposeNatConfig::Stateful(config)) => {
+                config.port_range = Some(port_range);
+                Ok(self)
+            }
+            _ => Err(ConfigError::Invalid(format!(
+                "port ranges are only supported with stateful NAT, for VpcExpose {self}"
+            ))),
+        }
+    }
+
+    #[must_use]
+    pub fn nat_port_range(&self) -> Option<&RangeInclusive<u16>> {
+        self.nat.as_ref().and_then(|nat| {
+            if let VpcExposeNatConfig::Stateful(config) = &nat.config {
+                config.port_range.as_ref()
+            } else {
+                None
+            }
+        })
+    }
+
+    // Set per-protocol idle timeouts for the sessions of stateful NAT for the [`VpcExpose`].
+    //
+    // # Errors
+    //
+    // Returns an error if the [`VpcExpose`] is not in stateful mode, or if a timeout is zero.
+    pub fn make_nat_session_timeouts(
+        mut self,
+        session_timeouts: NatSessionTimeouts,
+    ) -> Result<Self, ConfigError> {
+        validate_nat_session_timeouts(&session_timeouts)?;
+        match self.nat.as_mut().map(|nat| &mut nat.config) {
+            Some(VpcExposeNatConfig::Stateful(config)) => {
+                config.session_timeouts = session_timeouts;
+                Ok(self)
+            }
+            _ => Err(ConfigError::Invalid(format!(
+                "session timeouts are only supported with stateful NAT, for VpcExpose {self}"
+            ))),
+        }
+    }
+
+    #[must_use]
+    pub fn nat_session_timeouts(&self) -> Option<&NatSessionTimeouts> {
+        self.nat.as_ref().and_then(|nat| {
+            if let VpcExposeNatConfig::Stateful(config) = &nat.config {
+                Some(&config.session_timeouts)
+            } else {
+                None
+            }
+        })
+    }
+
+    // Make stateful NAT for the [`VpcExpose`] deterministic: each address to translate gets a fixed
+    // block of `block_size` ports on a fixed public address.
+    //
+    // # Errors
+    //
+    // Returns an error if the [`VpcExpose`] is not in stateful mode, or if the block size is zero.
+    pub fn make_nat_port_blocks(mut self, block_size: u16) -> Result<Self, ConfigError> {
+        match self.nat.as_mut().map(|nat| &mut nat.config) {
+            Some(VpcExposeNatConfig::Stateful(config)) => {
+                validate_nat_port_block_size(block_size, config.port_range.as_ref())?;
+                config.port_block_size = Some(block_size);
+                Ok(self)
+            }
+            _ => Err(ConfigError::Invalid(format!(
+                "port blocks are only supported with stateful NAT, for VpcExpose {self}"
+            ))),
+        }
+    }
+
+    // Make stateful NAT for the [`VpcExpose`] use endpoint-independent mapping and filtering for
+    // UDP, for UDP hole punching to work (STUN, WebRTC).
+    //
+    // # Errors
+    //
+    // Returns an error if the [`VpcExpose`] is not in stateful mode.
+    pub fn make_nat_endpoint_independent(mut self) -> Result<Self, ConfigError> {
+        match self.nat.as_mut().map(|nat| &mut nat.config) {
+            Some(VpcExposeNatConfig::Stateful(config)) => {
+                config.endpoint_independent = true;
+                Ok(self)
+            }
+            _ => Err(ConfigError::Invalid(format!(
+                "endpoint-independent NAT is only supported with stateful NAT, for VpcExpose {self}"
+            ))),
+        }
+    }
+
+    // Add a static NAT binding between `internal` and `public` for stateful NAT, or a port
+    // forwarding rule if `ports` (internal port and public port) is set. See
+    // [`VpcExposeStaticNat`].
+    //
+    // # Errors
+    //
+    // Returns an error if the [`VpcExpose`] is not in stateful mode.
+    pub fn make_nat_static_binding(
+        mut self,
+        internal: IpAddr,
+        public: IpAddr,
+        ports: Option<(u16, u16)>,
+    ) -> Result<Self, ConfigError> {
+        match self.nat.as_mut().map(|nat| &mut nat.config) {
+            Some(VpcExposeNatConfig::Stateful(config)) => {
+                config.static_bindings.push(VpcExposeStaticNat {
+                    internal,
+                    public,
+                    ports,
+                });
+                Ok(self)
+            }
+            _ => Err(ConfigError::Invalid(format!(
+                "static NAT bindings are only supported with stateful NAT, for VpcExpose {self}"
+            ))),
+        }
+    }
+
+    #[must_use]
+    pub fn nat_static_bindings(&self) -> &[VpcExposeStaticNat] {
+        match self.nat.as_ref().map(|nat| &nat.config) {
+            Some(VpcExposeNatConfig::Stateful(config)) => config.static_bindings.as_slice(),
+            _ => &[],
+        }
+    }
+
+    #[must_use]
+    pub fn nat_endpoint_independent(&self) -> bool {
+        self.nat.as_ref().is_some_and(|nat| {
+            if let VpcExposeNatConfig::Stateful(config) = &nat.config {
+                config.endpoint_independent
+            } else {
+                false
+            }
+        })
+    }
+
+    #[must_use]
+    pub fn nat_port_block_size(&self) -> Option<u16> {
+        self.nat.as_ref().and_then(|nat| {
+            if let VpcExposeNatConfig::Stateful(config) = &nat.config {
+                config.port_block_size
+            } else {
+                None
+            }
+        })
+    }
+
+    // Exempt the addresses in `prefix` from NAT, for the given ports (all ports if `None`).
+    // Exclusion prefixes must be within the exposed prefixes, see [`VpcExpose::validate`].
+    #[must_use]
+    pub fn no_nat(self, prefix: Prefix, ports: Option<RangeInclusive<u16>>) -> Self {
+        let mut ret = self.make_nat();
+        let Some(nat) = ret.nat.as_mut() else {
+            unreachable!()
+        };
+        nat.exclusions.push(VpcExposeNatExclusion { prefix, ports });
+        ret
+    }
+
+    #[must_use]
+    pub fn nat_exclusions(&self) -> &[VpcExposeNatExclusion] {
+        self.nat
+            .as_ref()
+            .map_or(&[], |nat| nat.exclusions.as_slice())
+    }
+
     #[must_use]
     pub fn idle_timeout(&self) -> Option<Duration> {
         self.nat.as_ref().and_then(|nat| {
@@ -266,6 +506,12 @@ impl VpcExpose {
     ///    associated prefixes list.
     /// 5. Make sure we have the same number of addresses available on each side (public/private),
     ///    taking exclusion prefixes into account.
+    /// 6. Make sure the port range for stateful NAT, if any, is valid.
+    /// 7. Make sure the session timeouts for stateful NAT, if any, are valid.
+    /// 8. For deterministic NAT, make sure all addresses to translate can get a port block.
+    /// 9. Make sure "no-NAT" exceptions, if any, are for exposed addresses of an expose with NAT.
+    /// 10. Make sure static NAT bindings, if any, are between exposed addresses and public
+    ///     addresses, and don't conflict with each other.
     pub fn validate(&self) -> ConfigResult {
         // 1. Static NAT: Check that all prefixes in a list are of the same IP version, as we don't
         // support NAT46 or NAT64 at the moment.
@@ -354,8 +600,166 @@ impl VpcExpose {
                 "Empty 'as_range' with non-empty 'not_as' is currently not supported",
             ));
         }
+
+        // 6. Check the port range for stateful NAT
+        if let Some(port_range) = self.nat_port_range() {
+            validate_nat_port_range(port_range)?;
+        }
+
+        // 7. Check the session timeouts for stateful NAT
+        if let Some(session_timeouts) = self.nat_session_timeouts() {
+            validate_nat_session_timeouts(session_timeouts)?;
+        }
+
+        // 8. Deterministic NAT: check that there are enough public addresses and ports to assign a
+        //    port block to each address to translate
+        if let Some(block_size) = self.nat_port_block_size() {
+            let blocks_per_ip = validate_nat_port_block_size(block_size, self.nat_port_range())?;
+            if ips_sizes - nots_sizes > (as_range_sizes - not_as_sizes) * blocks_per_ip {
+                return Err(ConfigError::Invalid(format!(
+                    "not enough public addresses for NAT port blocks of size {block_size}: {self}"
+                )));
+            }
+        }
+
+        // 9. Check "no-NAT" exceptions
+        for exclusion in self.nat_exclusions() {
+            self.validate_nat_exclusion(exclusion)?;
+        }
+
+        // 10. Check static NAT bindings
+        self.validate_nat_static_bindings()?;
+        Ok(())
+    }
+
+    fn validate_nat_static_bindings(&self) -> ConfigResult {
+        let bindings = self.nat_static_bindings();
+        if !bindings.is_empty() && self.nat_port_block_size().is_some() {
+            return Err(ConfigError::Invalid(format!(
+                "static NAT bindings are not supported with NAT port blocks: {self}"
+            )));
+        }
+        let contains = |prefixes: &BTreeSet<Prefix>, excluded: &BTreeSet<Prefix>, addr: &IpAddr| {
+            prefixes.iter().any(|p| p.covers_addr(addr))
+                && !excluded.iter().any(|p| p.covers_addr(addr))
+        };
+        for (index, binding) in bindings.iter().enumerate() {
+            let VpcExposeStaticNat {
+                internal,
+                public,
+                ports,
+            } = binding;
+            if internal.is_ipv4() != public.is_ipv4()
+                || !contains(&self.ips, &self.nots, internal)
+                || !contains(self.as_range_or_empty(), self.not_as_or_empty(), public)
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "static NAT binding {internal} -> {public} out of the exposed prefixes: {self}"
+                )));
+            }
+            if let Some((internal_port, public_port)) = ports
+                && (*internal_port == 0 || *public_port == 0)
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "invalid ports for static NAT binding {internal} -> {public}"
+                )));
+            }
+            // A 1:1 binding owns its addresses, port forwarding rules own their ports
+            let conflicts = bindings[..index]
+                .iter()
+                .any(|other| match (ports, other.ports) {
+                    (None, None) => other.internal == *internal || other.public == *public,
+                    (Some(_), None) | (None, Some(_)) => other.public == *public,
+                    (Some((internal_port, public_port)), Some((other_internal, other_public))) => {
+                        (other.internal == *internal && other_internal == *internal_port)
+                            || (other.public == *public && other_public == *public_port)
+                    }
+                });
+            if conflicts {
+                return Err(ConfigError::Invalid(format!(
+                    "conflicting static NAT binding {internal} -> {public}: {self}"
+                )));
+            }
+        }
         Ok(())
     }
+
+    fn validate_nat_exclusion(&self, exclusion: &VpcExposeNatExclusion) -> ConfigResult {
+        let VpcExposeNatExclusion { prefix, ports } = exclusion;
+        if !self.has_nat() {
+            return Err(ConfigError::Invalid(format!(
+                "NAT exception {prefix} for VpcExpose without NAT: {self}"
+            )));
+        }
+        if !self.ips.iter().any(|p| p.covers(prefix))
+            || self
+                .nots
+                .iter()
+                .any(|p| p.covers(prefix) || prefix.covers(p))
+        {
+            return Err(ConfigError::OutOfRangeExclusionPrefix(*prefix));
+        }
+        if let Some(ports) = ports
+            && ports.is_empty()
+        {
+            return Err(ConfigError::Invalid(format!(
+                "invalid port range {}-{} for NAT exception {prefix}",
+                ports.start(),
+                ports.end()
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Port 0 is reserved, and can't be used for port address translation.
+fn validate_nat_port_range(port_range: &RangeInclusive<u16>) -> ConfigResult {
+    if port_range.is_empty() || *port_range.start() == 0 {
+        return Err(ConfigError::Invalid(format!(
+            "invalid NAT port range {}-{}",
+            port_range.start(),
+            port_range.end()
+        )));
+    }
+    Ok(())
+}
+
+// Blocks must fit in the port range, or in all ports but port 0 if there is no range. Returns the
+// number of blocks for each public address.
+fn validate_nat_port_block_size(
+    block_size: u16,
+    port_range: Option<&RangeInclusive<u16>>,
+) -> Result<u128, ConfigError> {
+    let available_ports = port_range.map_or(usize::from(u16::MAX), ExactSizeIterator::len);
+    if block_size == 0 || usize::from(block_size) > available_ports {
+        return Err(ConfigError::Invalid(format!(
+            "invalid NAT port block size {block_size} for {available_ports} available ports"
+        )));
+    }
+    Ok((available_ports / usize::from(block_size)) as u128)
+}
+
+// A zero timeout would expire sessions as soon as they are created.
+fn validate_nat_session_timeouts(session_timeouts: &NatSessionTimeouts) -> ConfigResult {
+    let NatSessionTimeouts {
+        tcp_established,
+        tcp_transitory,
+        udp,
+        icmp,
+    } = session_timeouts;
+    for (name, timeout) in [
+        ("TCP established", tcp_established),
+        ("TCP transitory", tcp_transitory),
+        ("UDP", udp),
+        ("ICMP", icmp),
+    ] {
+        if timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(ConfigError::Invalid(format!(
+                "invalid NAT session timeout for {name}: zero"
+            )));
+        }
+    }
+    Ok(())
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -5,11 +5,13 @@
 
 use crate::internal::interfaces::interface::{InterfaceConfig, InterfaceType};
 use crate::internal::routing::evpn::VtepConfig;
+use crate::internal::routing::statics::StaticRoute;
 use crate::internal::routing::vrf::VrfConfig;
 use crate::{ConfigError, ConfigResult};
 
 use net::eth::mac::SourceMac;
 use net::ipv4::UnicastIpv4Addr;
+use std::collections::BTreeSet;
 use std::net::IpAddr;
 
 use tracing::debug;
@@ -18,6 +20,7 @@ use tracing::debug;
 pub struct Underlay {
     pub vrf: VrfConfig, /* default vrf */
     pub vtep: Option<VtepConfig>,
+    pub static_routes: BTreeSet<StaticRoute>, /* programmed in the default vrf, without FRR */
 }
 
 impl TryFrom<&InterfaceConfig> for VtepConfig {
@@ -51,6 +54,11 @@ impl Underlay {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Add a static route to the underlay. Unlike the static routes of the VRF configuration,
+    /// which are configured in FRR, these are programmed directly in the routing table.
+    pub fn add_static_route(&mut self, static_route: StaticRoute) {
+        self.static_routes.insert(static_route);
+    }
     /// Look for a vtep interface in the list of interfaces of the underlay VRF
     /// and, if found, build a `VtepConfig` out of it. We accept at most one VTEP
     /// interface and it has to have valid ip and mac. No Vtep interface is valid
@@ -81,6 +89,17 @@ impl Underlay {
             .values()
             .try_for_each(|iface| iface.validate())?;
 
+        // validate static routes
+        self.static_routes
+            .iter()
+            .try_for_each(StaticRoute::validate)?;
+        if let Some(route) = self.static_routes.iter().find(|r| r.next_hop_vrf.is_some()) {
+            return Err(ConfigError::Invalid(format!(
+                "Underlay static route to {} can't have a next-hop vrf",
+                route.prefix
+            )));
+        }
+
         // set vtep information if a vtep interface has been specified in the config
         self.vtep = self.get_vtep_info()?;
 
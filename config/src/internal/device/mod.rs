@@ -3,6 +3,7 @@
 
 //! Dataplane configuration model: device
 
+pub mod pipeline;
 pub mod ports;
 pub mod settings;
 pub mod tracecfg;
@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Dataplane configuration model: layout of the packet-processing pipeline
+
+use crate::{ConfigError, ConfigResult};
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::str::FromStr;
+use tracing::debug;
+
+/// The types of the stages of a packet-processing pipeline: each maps to a network function
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StageType {
+    Dump,
+    Lldp,
+    Bfd,
+    ArpNd,
+    Ingress,
+    Urpf,
+    Mpls,
+    Srv6,
+    Mcast,
+    IpForward,
+    DstVpcdLookup,
+    FlowLookup,
+    StatelessNat,
+    StatefulNat,
+    Egress,
+    Punt,
+    FlowExpirations,
+    Stats,
+}
+
+impl StageType {
+    pub const ALL: [StageType; 18] = [
+        StageType::Dump,
+        StageType::Lldp,
+        StageType::Bfd,
+        StageType::ArpNd,
+        StageType::Ingress,
+        StageType::Urpf,
+        StageType::Mpls,
+        StageType::Srv6,
+        StageType::Mcast,
+        StageType::IpForward,
+        StageType::DstVpcdLookup,
+        StageType::FlowLookup,
+        StageType::StatelessNat,
+        StageType::StatefulNat,
+        StageType::Egress,
+        StageType::Punt,
+        StageType::FlowExpirations,
+        StageType::Stats,
+    ];
+
+    /// The keyword for this stage type, in textual layouts
+    #[must_use]
+    pub fn keyword(self) -> &'static str {
+        match self {
+            StageType::Dump => "dump",
+            StageType::Lldp => "lldp",
+            StageType::Bfd => "bfd",
+            StageType::ArpNd => "arp-nd",
+            StageType::Ingress => "ingress",
+            StageType::Urpf => "urpf",
+            StageType::Mpls => "mpls",
+            StageType::Srv6 => "srv6",
+            StageType::Mcast => "mcast",
+            StageType::IpForward => "ip-forward",
+            StageType::DstVpcdLookup => "dst-vpcd-lookup",
+            StageType::FlowLookup => "flow-lookup",
+            StageType::StatelessNat => "stateless-nat",
+            StageType::StatefulNat => "stateful-nat",
+            StageType::Egress => "egress",
+            StageType::Punt => "punt",
+            StageType::FlowExpirations => "flow-expirations",
+            StageType::Stats => "stats",
+        }
+    }
+
+    /// Tell if stages of this type have to go at the end of a pipeline, after any other stage
+    fn is_trailing(self) -> bool {
+        matches!(self, StageType::FlowExpirations | StageType::Stats)
+    }
+}
+
+impl Display for StageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.keyword())
+    }
+}
+
+impl FromStr for StageType {
+    type Err = ConfigError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        StageType::ALL
+            .into_iter()
+            .find(|stage| stage.keyword() == input)
+            .ok_or_else(|| ConfigError::Invalid(format!("Unknown pipeline stage type '{input}'")))
+    }
+}
+
+/// A stage of a pipeline: the type of the stage and its name, unique in the pipeline
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageConfig {
+    pub stage: StageType,
+    pub name: String,
+}
+
+impl StageConfig {
+    #[must_use]
+    pub fn new(stage: StageType, name: &str) -> Self {
+        Self {
+            stage,
+            name: name.to_owned(),
+        }
+    }
+}
+
+impl Display for StageConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.stage, self.name)
+    }
+}
+
+/// Parse a stage in the format TYPE[=NAME]. Stages without a name are named after their type.
+impl FromStr for StageConfig {
+    type Err = ConfigError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (stage, name) = input.split_once('=').unwrap_or((input, input));
+        if name.is_empty() {
+            return Err(ConfigError::Invalid(format!(
+                "Missing name for pipeline stage '{input}'"
+            )));
+        }
+        Ok(StageConfig::new(stage.parse()?, name))
+    }
+}
+
+/// The layout of the packet-processing pipeline: its stages, in order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PipelineConfig {
+    pub stages: Vec<StageConfig>,
+}
+
+/// The default layout of the pipeline
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        let mut config = PipelineConfig::new();
+        config.add_stage(StageType::Dump, "pre-ingress");
+        config.add_stage(StageType::Lldp, "LLDP");
+        config.add_stage(StageType::Bfd, "BFD");
+        config.add_stage(StageType::ArpNd, "ARP-ND");
+        config.add_stage(StageType::Ingress, "Ingress");
+        config.add_stage(StageType::Urpf, "uRPF");
+        config.add_stage(StageType::Mpls, "MPLS-Forward");
+        config.add_stage(StageType::Srv6, "SRv6");
+        config.add_stage(StageType::Mcast, "Mcast-Forward");
+        config.add_stage(StageType::IpForward, "IP-Forward-1");
+        config.add_stage(StageType::DstVpcdLookup, "dst-vni-lookup");
+        config.add_stage(StageType::FlowLookup, "flow-lookup");
+        config.add_stage(StageType::StatelessNat, "stateless-NAT");
+        config.add_stage(StageType::StatefulNat, "stateful-NAT");
+        config.add_stage(StageType::IpForward, "IP-Forward-2");
+        config.add_stage(StageType::Egress, "Egress");
+        config.add_stage(StageType::Punt, "punt");
+        config.add_stage(StageType::Dump, "post-egress");
+        config.add_stage(StageType::FlowExpirations, "flow-expirations");
+        config.add_stage(StageType::Stats, "stats");
+        config
+    }
+}
+
+impl PipelineConfig {
+    /// Create an empty pipeline layout
+    #[must_use]
+    pub fn new() -> Self {
+        Self { stages: vec![] }
+    }
+    pub fn add_stage(&mut self, stage: StageType, name: &str) {
+        self.stages.push(StageConfig::new(stage, name));
+    }
+    pub fn validate(&self) -> ConfigResult {
+        debug!("Validating pipeline configuration..");
+        let position = |stage| self.stages.iter().position(|s| s.stage == stage);
+        let (Some(ingress), Some(egress)) =
+            (position(StageType::Ingress), position(StageType::Egress))
+        else {
+            return Err(ConfigError::Invalid(
+                "Pipeline needs an ingress and an egress stage".to_string(),
+            ));
+        };
+        if egress < ingress {
+            return Err(ConfigError::Invalid(
+                "Pipeline egress stage goes before its ingress stage".to_string(),
+            ));
+        }
+        let mut names = HashSet::new();
+        for stage in &self.stages {
+            if !names.insert(stage.name.as_str()) {
+                return Err(ConfigError::Invalid(format!(
+                    "Duplicate pipeline stage name '{}'",
+                    stage.name
+                )));
+            }
+        }
+        // the stats and flow expiration stages have to see all the packets, with their verdict
+        let mut trailing = self
+            .stages
+            .iter()
+            .skip_while(|stage| !stage.stage.is_trailing());
+        if let Some(stage) = trailing.find(|stage| !stage.stage.is_trailing()) {
+            return Err(ConfigError::Invalid(format!(
+                "Pipeline stage '{}' goes after the stats or flow-expirations stage",
+                stage.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Display for PipelineConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stages: Vec<String> = self.stages.iter().map(ToString::to_string).collect();
+        write!(f, "{}", stages.join(","))
+    }
+}
+
+/// Parse a pipeline layout, as a comma-separated list of stages (see [`StageConfig`])
+impl FromStr for PipelineConfig {
+    type Err = ConfigError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let stages = input
+            .split(',')
+            .map(str::trim)
+            .map(StageConfig::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(PipelineConfig { stages })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_config() {
+        let default = PipelineConfig::default();
+        assert_eq!(default.validate(), Ok(()));
+        assert_eq!(default.to_string().parse(), Ok(default));
+
+        let config: PipelineConfig = "ingress,ip-forward=fwd,egress,stats".parse().unwrap();
+        assert_eq!(
+            config.stages[0],
+            StageConfig::new(StageType::Ingress, "ingress")
+        );
+        assert_eq!(
+            config.stages[1],
+            StageConfig::new(StageType::IpForward, "fwd")
+        );
+        assert_eq!(config.validate(), Ok(()));
+
+        assert!("ingress,router,egress".parse::<PipelineConfig>().is_err());
+        assert!("ingress,dump=,egress".parse::<PipelineConfig>().is_err());
+        for bad in [
+            "ip-forward,egress",
+            "egress,ingress",
+            "ingress,dump,dump,egress",
+            "ingress,stats,egress",
+        ] {
+            let config: PipelineConfig = bad.parse().unwrap();
+            assert!(config.validate().is_err(), "{bad} is valid");
+        }
+    }
+}
@@ -3,6 +3,7 @@
 
 //! Dataplane configuration model: static routes
 
+use crate::{ConfigError, ConfigResult};
 use lpm::prefix::Prefix;
 use std::net::IpAddr;
 
@@ -11,6 +12,7 @@ pub enum StaticRouteNhop {
     Unset,
     Interface(String),
     Address(IpAddr),
+    AddressInterface(IpAddr, String),
     Null0,
     Blackhole,
     Reject,
@@ -22,6 +24,7 @@ pub struct StaticRoute {
     pub next_hop: StaticRouteNhop,
     pub next_hop_vrf: Option<String>,
     pub tag: Option<u32>,
+    pub distance: Option<u8>,
 }
 
 impl StaticRoute {
@@ -32,6 +35,7 @@ impl StaticRoute {
             next_hop: StaticRouteNhop::Unset,
             next_hop_vrf: None,
             tag: None,
+            distance: None,
         }
     }
     #[must_use]
@@ -45,6 +49,11 @@ impl StaticRoute {
         self
     }
     #[must_use]
+    pub fn nhop_addr_iface(mut self, addr: IpAddr, ifname: String) -> Self {
+        self.next_hop = StaticRouteNhop::AddressInterface(addr, ifname);
+        self
+    }
+    #[must_use]
     pub fn nhop_blackhole(mut self) -> Self {
         self.next_hop = StaticRouteNhop::Blackhole;
         self
@@ -69,4 +78,31 @@ impl StaticRoute {
         self.tag = Some(tag);
         self
     }
+    #[must_use]
+    pub fn distance(mut self, distance: u8) -> Self {
+        self.distance = Some(distance);
+        self
+    }
+    /// Check that a static route has a next-hop, of the same address family as its prefix
+    pub fn validate(&self) -> ConfigResult {
+        let address = match &self.next_hop {
+            StaticRouteNhop::Unset => {
+                return Err(ConfigError::Invalid(format!(
+                    "Static route to {} has no next-hop",
+                    self.prefix
+                )));
+            }
+            StaticRouteNhop::Address(address) | StaticRouteNhop::AddressInterface(address, _) => {
+                address
+            }
+            _ => return Ok(()),
+        };
+        if address.is_ipv4() != self.prefix.is_ipv4() {
+            return Err(ConfigError::Invalid(format!(
+                "Static route to {} has next-hop {address} of another address family",
+                self.prefix
+            )));
+        }
+        Ok(())
+    }
 }
@@ -181,6 +181,9 @@ pub struct FrrStatus {
     pub restarts: u32,
     pub applied_configs: u32,
     pub failed_configs: u32,
+    pub rollbacks: u32,
+    pub rollback_failures: u32,
+    pub last_failed_config_gen: Option<i64>,
 }
 
 impl FrrStatus {
@@ -218,6 +221,21 @@ impl FrrStatus {
         self.failed_configs = v;
         self
     }
+    #[must_use]
+    pub fn set_rollbacks(mut self, v: u32) -> Self {
+        self.rollbacks = v;
+        self
+    }
+    #[must_use]
+    pub fn set_rollback_failures(mut self, v: u32) -> Self {
+        self.rollback_failures = v;
+        self
+    }
+    #[must_use]
+    pub fn set_last_failed_config_gen(mut self, v: i64) -> Self {
+        self.last_failed_config_gen = Some(v);
+        self
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -5,20 +5,48 @@
 
 #![allow(unused)]
 
-use dpdk::dev::{Dev, TxOffloadConfig};
+use dpdk::dev::{Dev, DevIndex, DevInfo, HotplugError, TxOffloadConfig};
 use dpdk::eal::Eal;
 use dpdk::lcore::{LCoreId, WorkerThread};
-use dpdk::mem::{Mbuf, Pool, PoolConfig, PoolParams, RteAllocator};
-use dpdk::queue::rx::{RxQueueConfig, RxQueueIndex};
-use dpdk::queue::tx::{TxQueueConfig, TxQueueIndex};
+use dpdk::mem::{Mbuf, Pool, PoolConfig, PoolDemand, PoolParams, RteAllocator};
+use dpdk::queue::rx::{RxQueue, RxQueueConfig, RxQueueIndex};
+use dpdk::queue::tx::{TxQueue, TxQueueConfig, TxQueueIndex};
+use dpdk::socket::SocketId;
 use dpdk::{dev, eal, socket};
 use tracing::{debug, error, info, trace, warn};
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use concurrency::sync::atomic::{AtomicU64, Ordering};
+use concurrency::sync::{Arc, Mutex, PoisonError};
+use concurrency::thread;
+
 use crate::CmdArgs;
+use crate::drivers::executor::{Executor, ExecutorConfig, PipelineFactory, WorkerIo};
 use net::buffer::PacketBufferMut;
-use net::packet::Packet;
+use net::headers::{Net, TryIp};
+use net::packet::{ChecksumOffload, Packet};
 use pipeline::sample_nfs::Passthrough;
 use pipeline::{self, DynPipeline, NetworkFunction};
+use stats::{NicPortStats, NicStatsSource};
+
+/// MTU of the devices, for jumbo frames. Frames larger than the mbufs of the receive queues are
+/// received in chained mbufs: ports without scatter receive keep the standard MTU instead.
+const DEV_MTU: u32 = 9000;
+
+/// Number of descriptors of each receive queue
+const NUM_RX_DESCRIPTORS: u16 = 2048;
+
+/// Number of descriptors of each transmit queue
+const NUM_TX_DESCRIPTORS: u16 = 2048;
+
+/// Longest time to wait for the workers to stop using a port being detached
+const DETACH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval at which the driver checks for the stop signal while waiting for device updates
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 /*
 #[global_allocator]
@@ -37,107 +65,511 @@ fn init_eal(args: impl IntoIterator<Item = impl AsRef<str>>) -> Eal {
     rte
 }
 
-fn init_devices(eal: &Eal) -> Vec<Dev> {
-    eal.dev
-        .iter()
-        .map(|dev| {
-            let config = dev::DevConfig {
-                num_rx_queues: 2,
-                num_tx_queues: 2,
-                num_hairpin_queues: 0,
-                rx_offloads: None,
-                tx_offloads: Some(TxOffloadConfig::default()),
-            };
-            let mut dev = match config.apply(dev) {
-                Ok(stopped_dev) => {
-                    warn!("Device configured {stopped_dev:?}");
-                    stopped_dev
-                }
-                Err(err) => {
-                    Eal::fatal_error(format!("Failed to configure device: {err:?}"));
-                }
-            };
-            LCoreId::iter().enumerate().for_each(|(i, lcore_id)| {
-                let rx_queue_config = RxQueueConfig {
-                    dev: dev.info.index(),
-                    queue_index: RxQueueIndex(u16::try_from(i).unwrap()),
-                    num_descriptors: 2048,
-                    socket_preference: socket::Preference::LCore(lcore_id),
-                    offloads: dev.info.rx_offload_caps(),
-                    pool: Pool::new_pkt_pool(
-                        PoolConfig::new(
-                            format!("dev-{d}-lcore-{l}", d = dev.info.index(), l = lcore_id.0),
-                            PoolParams {
-                                socket_id: socket::Preference::LCore(lcore_id).try_into().unwrap(),
-                                ..Default::default()
-                            },
-                        )
-                        .unwrap(),
-                    )
-                    .unwrap(),
-                };
-                dev.new_rx_queue(rx_queue_config).unwrap();
-                let tx_queue_config = TxQueueConfig {
-                    queue_index: TxQueueIndex(u16::try_from(i).unwrap()),
-                    num_descriptors: 2048,
-                    socket_preference: socket::Preference::LCore(lcore_id),
-                    config: (),
-                };
-                dev.new_tx_queue(tx_queue_config).unwrap();
-            });
-            dev.start().unwrap();
-            dev
+/// The socket of each lcore, to place its queues and their memory pools on
+fn lcore_sockets() -> Result<Vec<(LCoreId, SocketId)>, String> {
+    LCoreId::iter()
+        .map(|lcore_id| {
+            socket::Preference::LCore(lcore_id)
+                .try_into()
+                .map(|socket_id| (lcore_id, socket_id))
+                .map_err(|err| format!("No socket for lcore {lcore_id:?}: {err:?}"))
         })
         .collect()
 }
 
-fn start_rte_workers(devices: &[Dev], setup_pipeline: &(impl Sync + Fn() -> DynPipeline<Mbuf>)) {
-    LCoreId::iter().enumerate().for_each(|(i, lcore_id)| {
-        info!("Starting RTE Worker on {lcore_id:?}");
-        WorkerThread::launch(lcore_id, move || {
-            let mut pipeline = setup_pipeline();
-            let rx_queue = devices[0]
-                .rx_queue(RxQueueIndex(u16::try_from(i).unwrap()))
-                .unwrap();
-            let tx_queue = devices[0]
-                .tx_queue(TxQueueIndex(u16::try_from(i).unwrap()))
-                .unwrap();
-            loop {
-                let mbufs = rx_queue.receive();
-                let pkts = mbufs.filter_map(|mbuf| match Packet::new(mbuf) {
-                    Ok(pkt) => {
-                        debug!("packet: {pkt:?}");
-                        Some(pkt)
-                    }
-                    Err(e) => {
-                        trace!("Failed to parse packet: {e:?}");
-                        None
-                    }
-                });
-
-                let pkts_out = pipeline.process(pkts);
-                let buffers = pkts_out.filter_map(|pkt| match pkt.serialize() {
-                    Ok(buf) => Some(buf),
-                    Err(e) => {
-                        error!("{e:?}");
-                        None
-                    }
-                });
-                tx_queue.transmit(buffers);
+/// Create a packet memory pool on each socket with lcores, sized for the queues that the lcores
+/// have on `num_ports` ports
+fn init_pools(prefix: &str, num_ports: usize) -> Result<BTreeMap<SocketId, Pool>, String> {
+    let mut demands: BTreeMap<SocketId, PoolDemand> = BTreeMap::new();
+    for (_, socket_id) in lcore_sockets()? {
+        let demand = demands.entry(socket_id).or_default();
+        demand.add_lcore();
+        for _ in 0..num_ports {
+            demand.add_rx_queue(NUM_RX_DESCRIPTORS);
+            demand.add_tx_queue(NUM_TX_DESCRIPTORS);
+        }
+    }
+    let configs = PoolConfig::per_socket(prefix, PoolParams::default(), &demands)
+        .map_err(|err| format!("Invalid pools {prefix}: {err:?}"))?;
+    Pool::new_pkt_pools(configs).map_err(|err| format!("Failed to create pools {prefix}: {err:?}"))
+}
+
+/// Configure and start an ethernet port, with a receive and a transmit queue for each lcore.
+/// The receive queues take their mbufs from the pool on the socket of their lcore.
+fn init_device(info: DevInfo, pools: &BTreeMap<SocketId, Pool>) -> Result<Dev, String> {
+    let port = info.index();
+    let config = dev::DevConfig {
+        num_rx_queues: 2,
+        num_tx_queues: 2,
+        num_hairpin_queues: 0,
+        mtu: Some(DEV_MTU),
+        rx_offloads: None,
+        tx_offloads: Some(TxOffloadConfig::default()),
+    };
+    let mut dev = config
+        .apply(info)
+        .map_err(|err| format!("Failed to configure port {port}: {err:?}"))?;
+    warn!("Device configured {dev:?}");
+    for (i, (lcore_id, socket_id)) in lcore_sockets()?.into_iter().enumerate() {
+        let queue = u16::try_from(i).map_err(|_| format!("Too many lcores for port {port}"))?;
+        let pool = pools
+            .get(&socket_id)
+            .ok_or_else(|| format!("No pool on the socket of lcore {lcore_id:?}"))?;
+        let rx_queue_config = RxQueueConfig {
+            dev: port,
+            queue_index: RxQueueIndex(queue),
+            num_descriptors: NUM_RX_DESCRIPTORS,
+            socket_preference: socket::Preference::LCore(lcore_id),
+            offloads: dev.info.rx_offload_caps(),
+            pool: pool.clone(),
+        };
+        dev.new_rx_queue(rx_queue_config)
+            .map_err(|err| format!("Failed to set up rx queue {queue} of port {port}: {err}"))?;
+        let tx_queue_config = TxQueueConfig {
+            queue_index: TxQueueIndex(queue),
+            num_descriptors: NUM_TX_DESCRIPTORS,
+            socket_preference: socket::Preference::LCore(lcore_id),
+            config: (),
+        };
+        dev.new_tx_queue(tx_queue_config)
+            .map_err(|err| format!("Failed to set up tx queue {queue} of port {port}: {err:?}"))?;
+    }
+    dev.start()
+        .map_err(|err| format!("Failed to start port {port}: {err}"))?;
+    Ok(dev)
+}
+
+fn init_devices(eal: &Eal) -> Vec<Dev> {
+    let infos: Vec<DevInfo> = eal.dev.iter().collect();
+    // the ports share a pool per socket, which goes once all their queues are gone
+    let pools = init_pools("pkt", infos.len()).unwrap_or_else(|err| Eal::fatal_error(err));
+    infos
+        .into_iter()
+        .map(|info| init_device(info, &pools).unwrap_or_else(|err| Eal::fatal_error(err)))
+        .collect()
+}
+
+/// The ethernet ports of the driver, shared with the workers. Ports come and go at runtime
+/// (see [`DriverDpdk::sync_devices`]): each change bumps the generation of the table, and the
+/// workers refresh their copy of the table when they see a new generation.
+struct Ports {
+    generation: AtomicU64,
+    ports: Mutex<Vec<Arc<Dev>>>,
+    // ports out of the table which the workers did not release in time, for removals to retry
+    releasing: Mutex<Vec<Arc<Dev>>>,
+}
+
+impl Ports {
+    fn new(devices: Vec<Dev>) -> Self {
+        Self {
+            generation: AtomicU64::new(0),
+            ports: Mutex::new(devices.into_iter().map(Arc::new).collect()),
+            releasing: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn snapshot(&self) -> Vec<Arc<Dev>> {
+        self.ports
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn insert(&self, dev: Dev) {
+        self.ports
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(dev));
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Tell if a port is in the table, or out of it but not released by the workers yet
+    fn contains(&self, port: DevIndex) -> bool {
+        let has = |devs: &Mutex<Vec<Arc<Dev>>>| {
+            devs.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .any(|dev| dev.info.index() == port)
+        };
+        has(&self.ports) || has(&self.releasing)
+    }
+
+    /// Remove a port from the table, and wait for the workers to release it. If they do not
+    /// in time, the port is kept aside for a later call to retry.
+    fn remove(&self, port: DevIndex) -> Result<Dev, String> {
+        let position = |devs: &Vec<Arc<Dev>>| devs.iter().position(|dev| dev.info.index() == port);
+        let releasing = {
+            let mut releasing = self
+                .releasing
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            position(&releasing).map(|index| releasing.swap_remove(index))
+        };
+        let mut dev = match releasing {
+            Some(dev) => dev,
+            None => {
+                let dev = {
+                    let mut ports = self.ports.lock().unwrap_or_else(PoisonError::into_inner);
+                    let index = position(&ports).ok_or_else(|| format!("Unknown port {port}"))?;
+                    ports.remove(index)
+                };
+                self.generation.fetch_add(1, Ordering::Release);
+                dev
+            }
+        };
+        let start = Instant::now();
+        loop {
+            match Arc::try_unwrap(dev) {
+                Ok(dev) => return Ok(dev),
+                Err(shared) if start.elapsed() > DETACH_TIMEOUT => {
+                    // keep a reference: dropping it would leave the last worker to release the
+                    // port to stop and close it, on a datapath lcore
+                    self.releasing
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push(shared);
+                    return Err(format!("Port {port} still in use by the workers"));
+                }
+                Err(shared) => dev = shared,
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// Get the checksums which `dev` computes on transmission. Ports are configured with all the
+/// transmit offloads they support (see [`TxOffloadConfig::default`]).
+fn checksum_offload(dev: &Dev) -> ChecksumOffload {
+    let caps = TxOffloadConfig::from(dev.info.tx_offload_caps());
+    let mut offload = ChecksumOffload::empty();
+    offload.set(ChecksumOffload::IPV4, caps.ipv4_cksum);
+    offload.set(ChecksumOffload::TCP, caps.tcp_cksum);
+    offload.set(ChecksumOffload::UDP, caps.udp_cksum);
+    offload
+}
+
+/// The packet I/O of a worker: the receive and transmit queues of its lcore, on each port. The
+/// NIC shards the packets over the receive queues of a port with RSS.
+struct DpdkWorkerIo {
+    queue: u16,
+    ports: Arc<Ports>,
+    generation: u64,
+    local: Vec<Arc<Dev>>,
+}
+
+impl DpdkWorkerIo {
+    fn new(queue: u16, ports: Arc<Ports>) -> Self {
+        let generation = ports.generation();
+        let local = ports.snapshot();
+        Self {
+            queue,
+            ports,
+            generation,
+            local,
+        }
+    }
+
+    /// Pick up the ports attached or detached since the last call
+    fn refresh(&mut self) {
+        let generation = self.ports.generation();
+        if generation != self.generation {
+            self.local = self.ports.snapshot();
+            self.generation = generation;
+        }
+    }
+}
+
+impl WorkerIo<Mbuf> for DpdkWorkerIo {
+    fn is_polled(&self) -> bool {
+        true
+    }
+
+    fn receive(&mut self, packets: &mut Vec<Packet<Mbuf>>) {
+        self.refresh();
+        for dev in &self.local {
+            let Some(rx_queue) = dev.rx_queue(RxQueueIndex(self.queue)) else {
+                continue;
+            };
+            packets.extend(
+                rx_queue
+                    .receive()
+                    .filter_map(|mbuf| match Packet::new(mbuf) {
+                        Ok(pkt) => {
+                            debug!("packet: {pkt:?}");
+                            Some(pkt)
+                        }
+                        Err(e) => {
+                            trace!("Failed to parse packet: {e:?}");
+                            None
+                        }
+                    }),
+            );
+        }
+    }
+
+    /// Transmit the packets on their egress port, or on the first port if they have none. The
+    /// checksums which the port can compute are left to it.
+    fn transmit(&mut self, packets: &mut Vec<Packet<Mbuf>>) {
+        let mut batches: Vec<Vec<Mbuf>> = self.local.iter().map(|_| Vec::new()).collect();
+        let offloads: Vec<_> = self.local.iter().map(|dev| checksum_offload(dev)).collect();
+        for mut pkt in packets.drain(..) {
+            let position = pkt
+                .get_meta()
+                .oport
+                .and_then(|oport| {
+                    self.local
+                        .iter()
+                        .position(|dev| dev.info.index().as_u16() == oport.as_u16())
+                })
+                .unwrap_or(0);
+            let Some(batch) = batches.get_mut(position) else {
+                trace!("No port to transmit packet");
+                continue;
+            };
+            let offload = pkt.headers().offloadable_checksums(offloads[position]);
+            let (l2_len, l3_len) = pkt.headers().offload_lengths();
+            let ipv4 = matches!(pkt.headers().try_ip(), Some(Net::Ipv4(_)));
+            pkt.get_meta_mut().set_checksum_offload(offload);
+            match pkt.serialize() {
+                Ok(mut buf) => {
+                    buf.request_checksums(offload, ipv4, l2_len, l3_len);
+                    batch.push(buf);
+                }
+                Err(e) => error!("{e:?}"),
+            }
+        }
+        for (dev, batch) in self.local.iter().zip(batches) {
+            if let Some(tx_queue) = dev.tx_queue(TxQueueIndex(self.queue)) {
+                tx_queue.transmit(batch);
             }
-        });
-    });
+        }
+    }
 }
 
-pub struct DriverDpdk;
+fn start_rte_workers(
+    ports: &Arc<Ports>,
+    config: &ExecutorConfig,
+    setup_pipeline: &PipelineFactory<Mbuf>,
+) -> Executor<Mbuf> {
+    let lcores: Vec<LCoreId> = LCoreId::iter().collect();
+    let io = (0..lcores.len())
+        .map(|i| DpdkWorkerIo::new(u16::try_from(i).unwrap(), ports.clone()))
+        .collect();
+    let spawn = |i: usize, task: Box<dyn FnOnce() + Send>| {
+        info!("Starting RTE Worker on {:?}", lcores[i]);
+        WorkerThread::launch(lcores[i], task);
+        Ok(())
+    };
+    match Executor::start_with(config, setup_pipeline, io, spawn) {
+        Ok(executor) => executor,
+        Err(err) => Eal::fatal_error(format!("Failed to start workers: {err}")),
+    }
+}
+
+pub struct DriverDpdk {
+    eal: Eal,
+    ports: Arc<Ports>,
+    executor: Executor<Mbuf>,
+    /// The ports of the devices attached at runtime, by device
+    hotplugged: BTreeMap<String, Vec<DevIndex>>,
+}
 
 impl DriverDpdk {
+    /// Start the DPDK driver: one worker per lcore, with its own pipeline and device queues.
+    /// The workers keep running once started.
     pub fn start(
         args: impl IntoIterator<Item = impl AsRef<str>>,
-        setup_pipeline: &(impl Sync + Fn() -> DynPipeline<Mbuf>),
-    ) {
+        config: &ExecutorConfig,
+        setup_pipeline: &PipelineFactory<Mbuf>,
+    ) -> DriverDpdk {
         let eal = init_eal(args);
-        let devices = init_devices(&eal);
-        start_rte_workers(&devices, setup_pipeline);
+        let ports = Arc::new(Ports::new(init_devices(&eal)));
+        let executor = start_rte_workers(&ports, config, setup_pipeline);
+        DriverDpdk {
+            eal,
+            ports,
+            executor,
+            hotplugged: BTreeMap::new(),
+        }
+    }
+
+    /// Get a source of the counters of the ports of the driver, including the ports attached
+    /// later on, for the stats collector
+    #[must_use]
+    pub fn nic_stats_source(&self) -> NicStatsSource {
+        let ports = self.ports.clone();
+        Box::new(move || {
+            ports
+                .snapshot()
+                .iter()
+                .filter_map(|dev| {
+                    let port = dev.info.index();
+                    let stats = port
+                        .stats()
+                        .map_err(|err| debug!("No stats for port {port}: {err}"))
+                        .ok()?;
+                    let xstats = port.xstats().unwrap_or_else(|err| {
+                        debug!("No xstats for port {port}: {err}");
+                        Vec::new()
+                    });
+                    let stats = NicPortStats {
+                        rx_packets: stats.rx_packets,
+                        tx_packets: stats.tx_packets,
+                        rx_bytes: stats.rx_bytes,
+                        tx_bytes: stats.tx_bytes,
+                        rx_missed: stats.rx_missed,
+                        rx_errors: stats.rx_errors,
+                        tx_errors: stats.tx_errors,
+                        rx_nombuf: stats.rx_nombuf,
+                        xstats: xstats
+                            .into_iter()
+                            .map(|xstat| (xstat.name, xstat.value))
+                            .collect(),
+                    };
+                    Some((port.to_string(), stats))
+                })
+                .collect()
+        })
+    }
+
+    /// Attach the device `devargs` and bring up its ports
+    fn attach(&mut self, devargs: &str) -> Result<(), String> {
+        let infos = match self.eal.dev.attach(devargs) {
+            Ok(infos) => infos,
+            // e.g. a device given to the EAL at startup, whose ports the driver already has
+            Err(HotplugError::AlreadyAttached(_)) => {
+                let ports = self
+                    .eal
+                    .dev
+                    .find_ports(devargs)
+                    .map_err(|err| err.to_string())?;
+                if ports.iter().all(|port| self.ports.contains(*port)) {
+                    debug!("Device {devargs} is already attached");
+                    return Ok(());
+                }
+                return Err(format!(
+                    "Device {devargs} is already attached, but its ports {ports:?} are unknown"
+                ));
+            }
+            Err(err) => return Err(err.to_string()),
+        };
+        let Some(first) = infos.first().map(DevInfo::index) else {
+            return Ok(());
+        };
+        // all the ports of the device are recorded, configured or not, as they all go with it
+        let ports: Vec<DevIndex> = infos.iter().map(DevInfo::index).collect();
+        // the ports of the device get their own pools, freed when the device is detached
+        let pools = match init_pools(&format!("hotplug-{first}"), infos.len()) {
+            Ok(pools) => pools,
+            Err(err) => {
+                self.rollback_attach(devargs, first);
+                return Err(err);
+            }
+        };
+        let mut configured = 0;
+        for info in infos {
+            match init_device(info, &pools) {
+                Ok(dev) => {
+                    self.ports.insert(dev);
+                    configured += 1;
+                }
+                Err(err) => error!("{err}"),
+            }
+        }
+        if configured == 0 {
+            self.rollback_attach(devargs, first);
+            return Err(format!(
+                "None of the ports {ports:?} of device {devargs} is usable"
+            ));
+        }
+        info!("Attached device {devargs}, ports {ports:?}");
+        self.hotplugged.insert(devargs.to_string(), ports);
+        Ok(())
+    }
+
+    /// Detach the device `devargs` right after attaching it, none of its ports being in use
+    fn rollback_attach(&self, devargs: &str, port: DevIndex) {
+        if let Err(err) = self.eal.dev.detach_port(port) {
+            error!("Failed to detach device {devargs} after a failed attach: {err}");
+        }
+    }
+
+    /// Take down the ports of the device `devargs`, and detach it. The device is forgotten
+    /// only once detached, so that a failed detach is retried on the next sync.
+    fn detach(&mut self, devargs: &str) -> Result<(), String> {
+        let ports = self
+            .hotplugged
+            .get(devargs)
+            .cloned()
+            .ok_or_else(|| format!("Device {devargs} was not attached at runtime"))?;
+        // the ports not in the table were not configured, or were taken out of it by a previous
+        // attempt, and are already released
+        let mut devs = Vec::with_capacity(ports.len());
+        for port in &ports {
+            if self.ports.contains(*port) {
+                devs.push(self.ports.remove(*port)?);
+            }
+        }
+        // all the ports of the device go with it: only one of them needs detaching, the others
+        // are stopped first
+        let dev = devs.pop();
+        drop(devs);
+        let detached = match (dev, ports.first()) {
+            (Some(dev), _) => self.eal.dev.detach(dev),
+            (None, Some(port)) => self.eal.dev.detach_port(*port),
+            (None, None) => Ok(()),
+        };
+        detached.map_err(|err| err.to_string())?;
+        self.hotplugged.remove(devargs);
+        info!("Detached device {devargs}");
+        Ok(())
+    }
+
+    /// Attach the devices in `devices` that the driver does not have yet, and detach the
+    /// devices attached at runtime which are not in `devices` anymore. The devices given to the
+    /// EAL at startup are left alone.
+    pub fn sync_devices(&mut self, devices: &BTreeSet<String>) {
+        let stale: Vec<String> = self
+            .hotplugged
+            .keys()
+            .filter(|devargs| !devices.contains(*devargs))
+            .cloned()
+            .collect();
+        for devargs in stale {
+            if let Err(err) = self.detach(&devargs) {
+                error!("Failed to detach device {devargs}: {err}");
+            }
+        }
+        for devargs in devices {
+            if self.hotplugged.contains_key(devargs) {
+                continue;
+            }
+            if let Err(err) = self.attach(devargs) {
+                error!("Failed to attach device {devargs}: {err}");
+            }
+        }
+    }
+
+    /// Attach and detach devices as requested over `devices`, until a stop signal is received
+    /// over `stop`
+    pub fn serve_devices(&mut self, devices: &Receiver<BTreeSet<String>>, stop: &Receiver<()>) {
+        loop {
+            match devices.recv_timeout(STOP_POLL_INTERVAL) {
+                Ok(devices) => self.sync_devices(&devices),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    warn!("No more device updates");
+                    let _ = stop.recv();
+                    return;
+                }
+            }
+            if stop.try_recv().is_ok() {
+                return;
+            }
+        }
     }
 }
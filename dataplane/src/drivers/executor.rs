@@ -0,0 +1,564 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Multi-core pipeline executor, shared by the dataplane drivers.
+//!
+//! The [`Executor`] runs one pipeline instance per worker, each worker on its own thread,
+//! optionally pinned to a core. Packets are sharded to the workers by flow, so that a worker
+//! sees all the packets of the flows it handles, in order:
+//!  * either by the NIC, which spreads the packets over one receive queue per worker with RSS,
+//!    each worker polling its own queue (see [`WorkerIo::receive`]). This is the DPDK case.
+//!  * or by the executor, for the packets that the driver hands over with
+//!    [`Executor::dispatch`]: the packets are sharded by their RSS hash if the driver got one
+//!    from the NIC (see [`RssHash`]), or by a symmetric hash of their flow key otherwise, so
+//!    that both directions of a flow go to the same worker. This is the kernel case.
+//!
+//! Traffic can be skewed, with a few heavy flows loading a few workers only. With work-stealing
+//! enabled, idle workers take packets from the queues of the busiest workers. The stolen packets
+//! are processed out of order with respect to the other packets of their flows.
+
+#![deny(
+    unsafe_code,
+    clippy::all,
+    clippy::pedantic,
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic
+)]
+
+use concurrency::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use concurrency::sync::{Arc, Condvar, Mutex};
+use concurrency::thread;
+
+use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::time::Duration;
+
+use net::buffer::PacketBufferMut;
+use net::packet::{Annotation, Packet};
+use nix::sched::{CpuSet, sched_setaffinity};
+use nix::unistd::Pid;
+use pipeline::{DynPipeline, NetworkFunction};
+use pkt_meta::flow_table::flow_key::{Bidi, FlowKey};
+
+#[allow(unused)]
+use tracing::{debug, error, info, trace, warn};
+
+/// A factory of pipeline instances, called once by each worker
+pub type PipelineFactory<Buf> = Arc<dyn Send + Sync + Fn() -> DynPipeline<Buf>>;
+
+/// Annotation of the packets with the RSS hash computed by the NIC, set by the drivers which
+/// get it, for [`Executor::dispatch`] to shard the packets the way the NIC does
+pub struct RssHash;
+
+impl Annotation for RssHash {
+    type Value = u32;
+}
+
+/// Compute the hash used to shard `packet` to a worker: its RSS hash if it has one, or a
+/// symmetric hash of its flow key. Packets without a flow key all go to the same worker.
+#[must_use]
+pub fn shard_hash<Buf: PacketBufferMut>(packet: &Packet<Buf>) -> u64 {
+    if let Some(hash) = packet.get_meta().annotations.get::<RssHash>() {
+        return u64::from(*hash);
+    }
+    // A<->B flow keys are equal, and so are their hashes
+    let Ok(flow_key) = FlowKey::try_from(Bidi(packet)) else {
+        return 0;
+    };
+    let mut hasher = DefaultHasher::new();
+    flow_key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The packet I/O of a worker of an [`Executor`]
+pub trait WorkerIo<Buf: PacketBufferMut>: Send + 'static {
+    /// Tell if the worker has its own receive queue to poll, in which case it never sleeps.
+    /// Workers without one sleep while there are no packets dispatched to them.
+    fn is_polled(&self) -> bool {
+        false
+    }
+
+    /// Receive the packets of the receive queue of the worker, if it has one, into `packets`.
+    fn receive(&mut self, packets: &mut Vec<Packet<Buf>>) {
+        let _ = packets;
+    }
+
+    /// Transmit the packets processed by the worker, taking them out of `packets`.
+    fn transmit(&mut self, packets: &mut Vec<Packet<Buf>>);
+}
+
+/// The configuration of an [`Executor`]
+#[derive(Debug, Clone)]
+pub struct ExecutorConfig {
+    /// Cores to pin the workers to, in order. Workers are not pinned if there are fewer cores
+    /// than workers.
+    pub cores: Vec<usize>,
+    /// Number of packets that can wait in the queue of each worker, over which packets are dropped
+    pub queue_size: usize,
+    /// Maximum number of packets processed at once by a worker
+    pub batch_size: usize,
+    /// Whether idle workers take packets from the queues of the busiest workers
+    pub work_stealing: bool,
+}
+
+impl Default for ExecutorConfig {
+    fn default() -> Self {
+        Self {
+            cores: vec![],
+            queue_size: 4096,
+            batch_size: 256,
+            work_stealing: false,
+        }
+    }
+}
+
+/// Time that an idle worker waits for packets, before looking for packets to steal
+const IDLE_WAIT: Duration = Duration::from_millis(1);
+
+/// Minimum number of packets in the queue of a worker for other workers to steal from it
+const STEAL_THRESHOLD: usize = 32;
+
+/// The queue of the packets waiting for a worker
+struct WorkQueue<Buf: PacketBufferMut> {
+    packets: Mutex<VecDeque<Packet<Buf>>>,
+    ready: Condvar,
+    len: AtomicUsize, /* number of packets queued, read without the lock to pick steal victims */
+    capacity: usize,
+}
+
+impl<Buf: PacketBufferMut> WorkQueue<Buf> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            packets: Mutex::new(VecDeque::with_capacity(capacity)),
+            ready: Condvar::new(),
+            len: AtomicUsize::new(0),
+            capacity,
+        }
+    }
+
+    /// Queue `packets`, dropping those over the capacity of the queue. Returns the number of
+    /// packets dropped.
+    fn push(&self, packets: impl IntoIterator<Item = Packet<Buf>>) -> usize {
+        let Ok(mut queue) = self.packets.lock() else {
+            return packets.into_iter().count();
+        };
+        let mut dropped = 0;
+        for packet in packets {
+            if queue.len() < self.capacity {
+                queue.push_back(packet);
+            } else {
+                dropped += 1;
+            }
+        }
+        self.len.store(queue.len(), Ordering::Relaxed);
+        drop(queue);
+        self.ready.notify_one();
+        dropped
+    }
+
+    /// Take up to `max` packets from the queue into `batch`, waiting up to `wait` for packets
+    /// if the queue is empty
+    fn pop(&self, max: usize, batch: &mut Vec<Packet<Buf>>, wait: Option<Duration>) {
+        let Ok(mut queue) = self.packets.lock() else {
+            return;
+        };
+        if queue.is_empty()
+            && let Some(wait) = wait
+        {
+            queue = match self.ready.wait_timeout(queue, wait) {
+                Ok((queue, _)) => queue,
+                Err(_) => return,
+            };
+        }
+        let count = max.min(queue.len());
+        batch.extend(queue.drain(..count));
+        self.len.store(queue.len(), Ordering::Relaxed);
+    }
+
+    /// Take up to half of the packets of the queue, and up to `max`, into `batch`
+    fn steal(&self, max: usize, batch: &mut Vec<Packet<Buf>>) -> usize {
+        let Ok(mut queue) = self.packets.lock() else {
+            return 0;
+        };
+        let count = max.min(queue.len() / 2);
+        batch.extend(queue.drain(..count));
+        self.len.store(queue.len(), Ordering::Relaxed);
+        count
+    }
+}
+
+/// The counters of a worker
+#[derive(Debug, Default)]
+struct WorkerCounters {
+    processed: AtomicU64,
+    dropped: AtomicU64,
+    stolen: AtomicU64,
+}
+
+/// The packet counts of a worker of an [`Executor`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkerStats {
+    /// Number of packets processed by the pipeline of the worker
+    pub processed: u64,
+    /// Number of packets dropped because the queue of the worker was full
+    pub dropped: u64,
+    /// Number of packets taken by the worker from the queues of other workers
+    pub stolen: u64,
+}
+
+/// The state shared by the workers and the [`Executor`]
+struct Shared<Buf: PacketBufferMut> {
+    queues: Vec<WorkQueue<Buf>>,
+    counters: Vec<WorkerCounters>,
+    running: AtomicBool,
+}
+
+/// A worker: a pipeline instance, with its packet I/O
+struct Worker<Buf: PacketBufferMut, Io: WorkerIo<Buf>> {
+    index: usize,
+    pipeline: DynPipeline<Buf>,
+    io: Io,
+    shared: Arc<Shared<Buf>>,
+    batch_size: usize,
+    work_stealing: bool,
+}
+
+impl<Buf: PacketBufferMut, Io: WorkerIo<Buf>> Worker<Buf, Io> {
+    /// Take packets from the queue of the busiest other worker, if it has enough of them
+    fn steal(&self, batch: &mut Vec<Packet<Buf>>) {
+        let victim = self
+            .shared
+            .queues
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != self.index)
+            .max_by_key(|(_, queue)| queue.len.load(Ordering::Relaxed));
+        if let Some((victim, queue)) = victim
+            && queue.len.load(Ordering::Relaxed) >= STEAL_THRESHOLD
+        {
+            let stolen = queue.steal(self.batch_size, batch);
+            self.shared.counters[self.index]
+                .stolen
+                .fetch_add(stolen as u64, Ordering::Relaxed);
+            trace!(
+                worker = self.index,
+                "stole {stolen} packets from worker {victim}"
+            );
+        }
+    }
+
+    fn run(mut self) {
+        debug!(worker = self.index, "worker started");
+        let polled = self.io.is_polled();
+        let wait = if polled { None } else { Some(IDLE_WAIT) };
+        let queue = &self.shared.queues[self.index];
+        let counters = &self.shared.counters[self.index];
+        let mut batch = Vec::with_capacity(self.batch_size);
+        let mut output = Vec::with_capacity(self.batch_size);
+        while self.shared.running.load(Ordering::Relaxed) {
+            if polled {
+                self.io.receive(&mut batch);
+                if self.work_stealing && !batch.is_empty() {
+                    // make the packets received available to idle workers
+                    let dropped = queue.push(batch.drain(..));
+                    counters
+                        .dropped
+                        .fetch_add(dropped as u64, Ordering::Relaxed);
+                }
+            }
+            if batch.is_empty() {
+                queue.pop(self.batch_size, &mut batch, wait);
+            }
+            if batch.is_empty() && self.work_stealing {
+                self.steal(&mut batch);
+            }
+            if batch.is_empty() {
+                continue;
+            }
+            counters
+                .processed
+                .fetch_add(batch.len() as u64, Ordering::Relaxed);
+            output.extend(self.pipeline.process(batch.drain(..)));
+            self.io.transmit(&mut output);
+            output.clear();
+        }
+        debug!(worker = self.index, "worker stopped");
+    }
+}
+
+/// Pin the calling thread to `core`
+fn pin_to_core(core: usize) -> io::Result<()> {
+    let mut cpus = CpuSet::new();
+    cpus.set(core).map_err(io::Error::from)?;
+    sched_setaffinity(Pid::from_raw(0), &cpus).map_err(io::Error::from)
+}
+
+/// Runs one pipeline instance per worker, and shards the packets to the workers. See the
+/// [module documentation](self).
+pub struct Executor<Buf: PacketBufferMut> {
+    shared: Arc<Shared<Buf>>,
+    handles: Vec<thread::JoinHandle<()>>,
+}
+
+impl<Buf: PacketBufferMut> Executor<Buf> {
+    /// Start one worker per entry of `io`, on its own thread, pinned to the cores of `config` if
+    /// there are enough of them. Each worker builds its pipeline with `setup_pipeline`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a thread cannot be spawned. The workers already started are stopped.
+    pub fn start<Io: WorkerIo<Buf>>(
+        config: &ExecutorConfig,
+        setup_pipeline: &PipelineFactory<Buf>,
+        io: Vec<Io>,
+    ) -> io::Result<Self> {
+        let pinned = config.cores.len() >= io.len();
+        if !config.cores.is_empty() && !pinned {
+            warn!(
+                "Fewer cores ({}) than workers ({}): workers are not pinned",
+                config.cores.len(),
+                io.len()
+            );
+        }
+        let mut handles = Vec::with_capacity(io.len());
+        let executor = Self::start_with(config, setup_pipeline, io, |index, task| {
+            let core = pinned.then(|| config.cores[index]);
+            let handle = thread::Builder::new()
+                .name(format!("dp-worker-{index}"))
+                .spawn(move || {
+                    if let Some(core) = core
+                        && let Err(e) = pin_to_core(core)
+                    {
+                        error!("Failed to pin worker {index} to core {core}: {e}");
+                    }
+                    task();
+                })?;
+            handles.push(handle);
+            Ok(())
+        })?;
+        Ok(Self {
+            handles,
+            ..executor
+        })
+    }
+
+    /// Same as [`Executor::start`], but with the threads of the workers started by `spawn`,
+    /// which gets the index of the worker and the task to run on its thread. This is for the
+    /// drivers with their own worker threads (e.g. DPDK lcores).
+    ///
+    /// # Errors
+    ///
+    /// Fails if `spawn` fails. The workers already started are stopped.
+    pub fn start_with<Io: WorkerIo<Buf>>(
+        config: &ExecutorConfig,
+        setup_pipeline: &PipelineFactory<Buf>,
+        io: Vec<Io>,
+        mut spawn: impl FnMut(usize, Box<dyn FnOnce() + Send>) -> io::Result<()>,
+    ) -> io::Result<Self> {
+        let workers = io.len();
+        let shared = Arc::new(Shared {
+            queues: (0..workers)
+                .map(|_| WorkQueue::new(config.queue_size))
+                .collect(),
+            counters: (0..workers).map(|_| WorkerCounters::default()).collect(),
+            running: AtomicBool::new(true),
+        });
+        info!("Starting {workers} workers");
+        for (index, io) in io.into_iter().enumerate() {
+            let setup = setup_pipeline.clone();
+            let worker_shared = shared.clone();
+            let batch_size = config.batch_size.max(1);
+            let work_stealing = config.work_stealing && workers > 1;
+            let task = Box::new(move || {
+                Worker {
+                    index,
+                    pipeline: setup(),
+                    io,
+                    shared: worker_shared,
+                    batch_size,
+                    work_stealing,
+                }
+                .run();
+            });
+            if let Err(e) = spawn(index, task) {
+                error!("Failed to spawn worker {index}: {e}");
+                shared.running.store(false, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+        Ok(Self {
+            shared,
+            handles: vec![],
+        })
+    }
+
+    /// Get the number of workers
+    #[must_use]
+    pub fn workers(&self) -> usize {
+        self.shared.queues.len()
+    }
+
+    /// Shard `packets` to the queues of the workers (see [`shard_hash`]). Packets are dropped if
+    /// the queue of their worker is full. Returns the number of packets dropped.
+    #[allow(clippy::cast_possible_truncation)] // the remainder is below the number of workers
+    pub fn dispatch(&self, packets: impl IntoIterator<Item = Packet<Buf>>) -> usize {
+        let workers = self.workers();
+        if workers == 0 {
+            return packets.into_iter().count();
+        }
+        let mut shards: Vec<Vec<Packet<Buf>>> = (0..workers).map(|_| Vec::new()).collect();
+        for packet in packets {
+            let index = (shard_hash(&packet) % workers as u64) as usize;
+            shards[index].push(packet);
+        }
+        let mut total = 0;
+        for (index, shard) in shards.into_iter().enumerate() {
+            if shard.is_empty() {
+                continue;
+            }
+            let dropped = self.shared.queues[index].push(shard);
+            if dropped > 0 {
+                warn!("Worker {index} queue full: dropped {dropped} packets");
+                self.shared.counters[index]
+                    .dropped
+                    .fetch_add(dropped as u64, Ordering::Relaxed);
+            }
+            total += dropped;
+        }
+        total
+    }
+
+    /// Get the packet counts of each worker
+    #[must_use]
+    pub fn stats(&self) -> Vec<WorkerStats> {
+        self.shared
+            .counters
+            .iter()
+            .map(|counters| WorkerStats {
+                processed: counters.processed.load(Ordering::Relaxed),
+                dropped: counters.dropped.load(Ordering::Relaxed),
+                stolen: counters.stolen.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Stop the workers, once done with the packets they are processing, and wait for the
+    /// threads started by [`Executor::start`] to exit. Workers keep running as long as the
+    /// executor is not stopped, even if it is dropped.
+    pub fn stop(self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        for handle in self.handles {
+            if handle.join().is_err() {
+                error!("A worker panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use concurrency::sync::mpsc::{Receiver, Sender, channel};
+    use net::buffer::TestBuffer;
+    use net::packet::test_utils::build_test_ipv4_packet;
+    use pipeline::sample_nfs::Passthrough;
+    use std::time::Instant;
+
+    struct ChannelIo(Sender<(usize, Packet<TestBuffer>)>, usize);
+
+    impl WorkerIo<TestBuffer> for ChannelIo {
+        fn transmit(&mut self, packets: &mut Vec<Packet<TestBuffer>>) {
+            for packet in packets.drain(..) {
+                let _ = self.0.send((self.1, packet));
+            }
+        }
+    }
+
+    fn start(
+        workers: usize,
+        config: &ExecutorConfig,
+    ) -> (Executor<TestBuffer>, Receiver<(usize, Packet<TestBuffer>)>) {
+        let (sender, receiver) = channel();
+        let setup: PipelineFactory<TestBuffer> =
+            Arc::new(|| DynPipeline::new().add_stage(Passthrough));
+        let io = (0..workers)
+            .map(|index| ChannelIo(sender.clone(), index))
+            .collect();
+        let executor = Executor::start(config, &setup, io).unwrap();
+        (executor, receiver)
+    }
+
+    fn collect(
+        receiver: &Receiver<(usize, Packet<TestBuffer>)>,
+        count: usize,
+    ) -> Vec<(usize, Packet<TestBuffer>)> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut received = vec![];
+        while received.len() < count && Instant::now() < deadline {
+            if let Ok(item) = receiver.recv_timeout(Duration::from_millis(10)) {
+                received.push(item);
+            }
+        }
+        received
+    }
+
+    #[test]
+    fn executor_sharding() {
+        let (executor, receiver) = start(4, &ExecutorConfig::default());
+        assert_eq!(executor.workers(), 4);
+
+        let mut packets: Vec<_> = (0..100)
+            .map(|_| build_test_ipv4_packet(64).unwrap())
+            .collect();
+        // same flow: all the packets go to the same worker
+        let dropped = executor.dispatch(packets.drain(..));
+        assert_eq!(dropped, 0);
+        let received = collect(&receiver, 100);
+        assert_eq!(received.len(), 100);
+        assert!(received.iter().all(|(worker, _)| *worker == received[0].0));
+
+        // the RSS hash, if any, selects the worker
+        let packets = (0..8u32).map(|hash| {
+            let mut packet = build_test_ipv4_packet(64).unwrap();
+            packet.get_meta_mut().annotations.insert::<RssHash>(hash);
+            packet
+        });
+        assert_eq!(executor.dispatch(packets), 0);
+        let mut workers: Vec<_> = collect(&receiver, 8)
+            .into_iter()
+            .map(|(worker, _)| worker)
+            .collect();
+        workers.sort_unstable();
+        assert_eq!(workers, [0, 0, 1, 1, 2, 2, 3, 3]);
+
+        let stats = executor.stats();
+        assert_eq!(stats.iter().map(|stats| stats.processed).sum::<u64>(), 108);
+        executor.stop();
+    }
+
+    #[test]
+    fn executor_work_stealing() {
+        let config = ExecutorConfig {
+            queue_size: 100_000,
+            work_stealing: true,
+            batch_size: 16,
+            ..ExecutorConfig::default()
+        };
+        let (executor, receiver) = start(2, &config);
+        // a single heavy flow, for a single worker
+        let packets = (0..20_000).map(|_| build_test_ipv4_packet(64).unwrap());
+        assert_eq!(executor.dispatch(packets), 0);
+        let received = collect(&receiver, 20_000);
+        assert_eq!(received.len(), 20_000);
+        let stats = executor.stats();
+        assert_eq!(
+            stats.iter().map(|stats| stats.processed).sum::<u64>(),
+            20_000
+        );
+        assert_eq!(stats.iter().map(|stats| stats.dropped).sum::<u64>(), 0);
+        executor.stop();
+    }
+}
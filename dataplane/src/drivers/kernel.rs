@@ -14,42 +14,27 @@
 
 use afpacket::sync::RawPacketStream;
 
-use concurrency::sync::Arc;
-use concurrency::thread;
-
-use tokio::sync::mpsc as chan;
-
 use mio::unix::SourceFd;
 use mio::{Events, Interest, Poll, Token};
 
 use std::collections::HashMap;
 use std::io::{self, Read, Write};
 use std::os::fd::{AsRawFd, RawFd};
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
 use std::time::Duration;
 
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-
 use net::buffer::test_buffer::TestBuffer;
 use net::interface::InterfaceIndex;
 use net::packet::{DoneReason, Packet};
 use netdev::Interface;
-use pipeline::{DynPipeline, NetworkFunction};
 #[allow(unused)]
 use tracing::{debug, error, info, trace, warn};
 
-// Flow-key based symmetric hashing
-use pkt_meta::flow_table::flow_key::{Bidi, FlowKey};
-
 use tracectl::trace_target;
 
-use crate::drivers::tokio_util::run_in_tokio_runtime;
+use crate::drivers::executor::{Executor, ExecutorConfig, PipelineFactory, WorkerIo};
 trace_target!("kernel-driver", LevelFilter::ERROR, &["driver"]);
 
-type WorkerTx = chan::Sender<Box<Packet<TestBuffer>>>;
-type WorkerRx = chan::Receiver<Box<Packet<TestBuffer>>>;
-type WorkerChans = (Vec<WorkerTx>, WorkerRx);
-
 /// Simple representation of a kernel interface.
 pub struct Kif {
     ifindex: InterfaceIndex, /* ifindex of interface */
@@ -193,116 +178,42 @@ fn build_kif_table(args: impl IntoIterator<Item = impl AsRef<str>>) -> io::Resul
 /// Main structure representing the kernel driver.
 /// This driver:
 ///  * receives raw frames via `AF_PACKET`, parses to `Packet<TestBuffer>`
-///  * selects a worker by symmetric flow hash
+///  * shards them to the workers of an [`Executor`] by symmetric flow hash
 ///  * workers run independent pipelines and send processed packets back
 ///  * dispatcher serializes & transmits on the chosen outgoing interface
 pub struct DriverKernel;
 
-fn single_worker(
-    id: usize,
-    thread_builder: thread::Builder,
-    tx_to_control: WorkerTx,
-    setup_pipeline: &Arc<dyn Send + Sync + Fn() -> DynPipeline<TestBuffer>>,
-) -> Result<WorkerTx, std::io::Error> {
-    let (tx_to_worker, mut rx_from_control) = chan::channel::<Box<Packet<TestBuffer>>>(4096);
-    let setup = setup_pipeline.clone();
-
-    let handle_res = thread_builder.spawn(move || {
-        let mut pipeline = setup();
-        run_in_tokio_runtime(async || {
-            loop {
-                tracing::debug!(
-                    worker = id,
-                    thread = %thread::current().name().unwrap_or("unnamed"),
-                    "awaiting packets"
-                );
-
-                let mut packets_vec = Vec::new();
-                let pkt_count = rx_from_control.recv_many(&mut packets_vec, 1024).await;
-                if (pkt_count == 0) {
-                    trace!(worker = id, thread = %thread::current().name().unwrap_or("unnamed"), "sender closed, exiting");
-                    return; // The sender closed so no more packets can ever be received
-                }
-
-                // Try to receive everything else that is in the buffer
-                let packets = packets_vec.into_iter();
-
-                let mut count = 0;
-                for out_pkt in pipeline.process(packets.map(|pkt| *pkt)) {
-                    // backpressure via bounded channel
-                    if tx_to_control.send(Box::new(out_pkt)).await.is_err() {
-                        // dispatcher gone; exit the thread
-                        return;
-                    }
-                    count += 1;
-                }
-
-                tracing::debug!(
-                    worker = id,
-                    thread = %thread::current().name().unwrap_or("unnamed"),
-                    "processed {count} packets"
-                );
-            }
-        });
-    })?;
-    Ok(tx_to_worker)
+/// The packet I/O of the workers: processed packets go back to the dispatcher, which owns the
+/// packet sockets
+struct KernelWorkerIo {
+    to_dispatcher: SyncSender<Packet<TestBuffer>>,
 }
 
-#[allow(clippy::cast_possible_truncation)]
-impl DriverKernel {
-    /// Compute a **symmetric** worker index for a parsed `Packet` using a bidirectional flow key.
-    #[must_use]
-    pub fn compute_worker_idx(pkt: &Packet<TestBuffer>, workers: usize) -> usize {
-        let n = workers.max(1);
-
-        // Prefer symmetric flow-key hash (A<->B go to the same bucket)
-        if let Ok(flow_key) = FlowKey::try_from(Bidi(pkt)) {
-            let mut h = DefaultHasher::new();
-            flow_key.hash(&mut h);
-            let hv = h.finish() as usize;
-            return hv % n;
-        }
-        //TODO: fallback to L2/VLAN to build
-        0
-    }
-
-    /// Spawn `workers` processing threads, each with its own pipeline instance.
-    ///
-    /// Returns:
-    ///   - `Vec<Sender<Packet<TestBuffer>>>` one sender per worker (dispatcher -> worker)
-    ///   - `Receiver<Packet<TestBuffer>>` a single queue for processed packets (worker -> dispatcher)
-    fn spawn_workers(
-        num_workers: usize,
-        setup_pipeline: &Arc<dyn Send + Sync + Fn() -> DynPipeline<TestBuffer>>,
-    ) -> io::Result<WorkerChans> {
-        let (tx_to_control, rx_from_workers) = chan::channel::<Box<Packet<TestBuffer>>>(4096);
-        let mut to_workers = Vec::with_capacity(num_workers);
-        info!("Spawning {num_workers} workers");
-        for wid in 0..num_workers {
-            let builder = thread::Builder::new().name(format!("dp-worker-{wid}"));
-            let tx_to_worker =
-                match single_worker(wid, builder, tx_to_control.clone(), setup_pipeline) {
-                    Ok(tx_to_worker) => tx_to_worker,
-                    Err(e) => {
-                        error!("Failed to spawn worker {wid}: {e}");
-                        return Err(io::Error::other("worker spawn failed"));
-                    }
-                };
-            to_workers.push(tx_to_worker);
+impl WorkerIo<TestBuffer> for KernelWorkerIo {
+    fn transmit(&mut self, packets: &mut Vec<Packet<TestBuffer>>) {
+        for packet in packets.drain(..) {
+            // backpressure via bounded channel. If the dispatcher is gone, packets are dropped
+            if self.to_dispatcher.send(packet).is_err() {
+                return;
+            }
         }
-
-        Ok((to_workers, rx_from_workers))
     }
+}
 
+impl DriverKernel {
     /// Starts the kernel driver, spawns worker threads, and runs the dispatcher loop.
     ///
     /// - `args`: kernel driver CLI parameters (e.g., `--interface` list)
-    /// - `workers`: number of worker threads / pipelines
+    /// - `num_workers`: number of worker threads / pipelines
+    /// - `config`: configuration of the executor running the workers
     /// - `setup_pipeline`: factory returning a **fresh** `DynPipeline<TestBuffer>` per worker
+    /// - `originated`: frames originated out of the pipeline, transmitted as they are
     pub fn start(
         args: impl IntoIterator<Item = impl AsRef<str> + Clone>,
         num_workers: usize,
-        setup_pipeline: &Arc<dyn Send + Sync + Fn() -> DynPipeline<TestBuffer>>,
+        config: &ExecutorConfig,
+        setup_pipeline: &PipelineFactory<TestBuffer>,
+        originated: &Receiver<Packet<TestBuffer>>,
     ) {
         // Prepare interfaces/poller
         let mut kiftable = match build_kif_table(args) {
@@ -314,67 +225,32 @@ impl DriverKernel {
         };
 
         // Spawn workers
-        let (to_workers, mut from_workers) = match Self::spawn_workers(num_workers, setup_pipeline)
-        {
-            Ok(chans) => chans,
+        let (to_dispatcher, from_workers) = sync_channel::<Packet<TestBuffer>>(4096);
+        let io = (0..num_workers.max(1))
+            .map(|_| KernelWorkerIo {
+                to_dispatcher: to_dispatcher.clone(),
+            })
+            .collect();
+        let executor = match Executor::start(config, setup_pipeline, io) {
+            Ok(executor) => executor,
             Err(e) => {
                 error!("Failed to start workers: {e}");
                 return;
             }
         };
 
-        let num_worker_chans = to_workers.len();
-        assert!(num_worker_chans != 0, "No worker channels available!");
-        if num_worker_chans != num_workers {
-            warn!(
-                "Number of to_worker channels ({num_worker_chans}) does not match number of workers ({num_workers})"
-            );
-        }
-
         let poll_timeout = Some(Duration::from_millis(2));
 
         // Dispatcher loop: drain processed packets, poll RX, parse+shard, TX results.
         let mut events = Events::with_capacity(256);
         loop {
             // 1) Drain processed packets coming back from workers, serialize + TX
-            while let Ok(mut pkt) = from_workers.try_recv() {
-                // choose outgoing interface from meta
-                let oif_id_opt = pkt.get_meta().oif;
-                if let Some(oif_id) = oif_id_opt {
-                    if let Some(outgoing) = kiftable.get_mut_by_index(oif_id) {
-                        match pkt.serialize() {
-                            Ok(out) => {
-                                let len = out.as_ref().len();
-                                if let Err(e) = outgoing.sock.write_all(out.as_ref()) {
-                                    error!(
-                                        "TX failed for pkt ({len} octets) on '{}': {e}",
-                                        &outgoing.name
-                                    );
-                                } else {
-                                    trace!("TX {len} bytes on interface {}", &outgoing.name);
-                                }
-                            }
-                            Err(e) => error!("Serialize failed: {e:?}"),
-                        }
-                    } else {
-                        warn!("TX drop: unknown oif {}", oif_id);
-                    }
-                } else {
-                    // No oif set -> inspect DoneReason via enforce()
-                    match pkt.enforce() {
-                        Some(_keep) => {
-                            // Packet is not marked for drop by the pipeline (Delivered/None/keep=true),
-                            // but we still can't TX without an oif; drop here.
-                            error!(
-                                "No oif in packet meta; enforce() => keep/Delivered; dropping here"
-                            );
-                        }
-                        None => {
-                            // Pipeline explicitly marked it to be dropped
-                            debug!("Packet marked for drop by pipeline (enforce() => None)");
-                        }
-                    }
-                }
+            while let Ok(pkt) = from_workers.try_recv() {
+                Self::packet_send(&mut kiftable, pkt);
+            }
+            // and the frames originated out of the pipeline
+            while let Ok(pkt) = originated.try_recv() {
+                Self::packet_send(&mut kiftable, pkt);
             }
 
             // 2) Poll for new RX events
@@ -384,24 +260,48 @@ impl DriverKernel {
             }
 
             // 3) For readable interfaces, pull frames, parse to Packet<TestBuffer>, shard to workers
-            Self::recv_packets(&mut kiftable, &events).for_each(|pkt| {
-                let target = Self::compute_worker_idx(&pkt, num_worker_chans);
-                if let Err(e) = to_workers[target].try_send(pkt) {
-                    match e {
-                        chan::error::TrySendError::Full(_) => {
-                            // queue full => soft drop
-                            // FIXME(mvachhar): this is bad, we need to increment drop stats here, but how?
-                            // FIXME(mvachhar): We need to backpressure the NIC without starving other workers, how do we do that?
-                            warn!("Worker {target} queue full: dropping packet");
-                        }
-                        chan::error::TrySendError::Closed(_) => {
-                            error!("Worker {target} channel closed: dropping packet");
+            // FIXME(mvachhar): We need to backpressure the NIC without starving other workers, how do we do that?
+            // Packets dropped because of full worker queues are accounted by the executor
+            let _ = executor.dispatch(Self::recv_packets(&mut kiftable, &events).map(|pkt| *pkt));
+        }
+    }
+
+    /// Serializes a [`Packet`] and transmits it over its outgoing interface, if it has one.
+    fn packet_send(kiftable: &mut KifTable, mut pkt: Packet<TestBuffer>) {
+        // choose outgoing interface from meta
+        let oif_id_opt = pkt.get_meta().oif;
+        if let Some(oif_id) = oif_id_opt {
+            if let Some(outgoing) = kiftable.get_mut_by_index(oif_id) {
+                match pkt.serialize() {
+                    Ok(out) => {
+                        let len = out.as_ref().len();
+                        if let Err(e) = outgoing.sock.write_all(out.as_ref()) {
+                            error!(
+                                "TX failed for pkt ({len} octets) on '{}': {e}",
+                                &outgoing.name
+                            );
+                        } else {
+                            trace!("TX {len} bytes on interface {}", &outgoing.name);
                         }
                     }
-                } else {
-                    trace!(worker = target, "dispatched packet to worker");
+                    Err(e) => error!("Serialize failed: {e:?}"),
+                }
+            } else {
+                warn!("TX drop: unknown oif {}", oif_id);
+            }
+        } else {
+            // No oif set -> inspect DoneReason via enforce()
+            match pkt.enforce() {
+                Some(_keep) => {
+                    // Packet is not marked for drop by the pipeline (Delivered/None/keep=true),
+                    // but we still can't TX without an oif; drop here.
+                    error!("No oif in packet meta; enforce() => keep/Delivered; dropping here");
+                }
+                None => {
+                    // Pipeline explicitly marked it to be dropped
+                    debug!("Packet marked for drop by pipeline (enforce() => None)");
                 }
-            });
+            }
         }
     }
 
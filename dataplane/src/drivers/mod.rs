@@ -4,5 +4,6 @@
 #![allow(unused)]
 
 pub mod dpdk;
+pub mod executor;
 pub mod kernel;
 mod tokio_util;
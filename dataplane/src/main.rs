@@ -9,16 +9,21 @@ mod drivers;
 mod packet_processor;
 mod statistics;
 
-use crate::packet_processor::start_router;
+use crate::packet_processor::{FrameFactory, start_router};
 use crate::statistics::MetricsServer;
-use args::{CmdArgs, Parser};
+use args::CmdArgs;
 
 use drivers::dpdk::DriverDpdk;
+use drivers::executor::{ExecutorConfig, PipelineFactory};
 use drivers::kernel::DriverKernel;
 
 use mgmt::processor::launch::start_mgmt;
 
+use concurrency::sync::Arc;
+
+use dpdk::mem::Mbuf;
 use net::buffer::PacketBufferMut;
+use net::buffer::test_buffer::TestBuffer;
 use net::packet::Packet;
 
 use pipeline::DynPipeline;
@@ -27,7 +32,7 @@ use pipeline::sample_nfs::PacketDumper;
 use routing::RouterParamsBuilder;
 use tracectl::{custom_target, get_trace_ctl, trace_target};
 
-use tracing::{error, info, level_filters::LevelFilter};
+use tracing::{debug, error, info, level_filters::LevelFilter};
 
 trace_target!("dataplane", LevelFilter::DEBUG, &[]);
 custom_target!("tonic", LevelFilter::ERROR, &[]);
@@ -83,10 +88,19 @@ fn process_tracing_cmds(args: &CmdArgs) {
     }
 }
 
+fn process_check_cmd(args: &CmdArgs) {
+    if args.check() {
+        let report = args.preflight();
+        println!("{report}");
+        std::process::exit(i32::from(!report.passed()));
+    }
+}
+
 fn main() {
     init_logging();
-    let args = CmdArgs::parse();
+    let args = CmdArgs::load();
     process_tracing_cmds(&args);
+    process_check_cmd(&args);
 
     info!("Starting gateway process...");
 
@@ -107,7 +121,11 @@ fn main() {
         .metrics_addr(args.metrics_address())
         .cli_sock_path(args.cli_sock_path())
         .cpi_sock_path(args.cpi_sock_path())
+        .cpi_grace_period(args.cpi_grace_period())
+        .cpi_hold_time(args.cpi_hold_time())
         .frr_agent_path(args.frr_agent_path())
+        .stage_latencies(args.stage_latencies())
+        .flow_table_capacity(args.flow_table_capacity())
         .build()
     else {
         error!("Bad router configuration");
@@ -115,14 +133,27 @@ fn main() {
     };
 
     // start the router; returns control-plane handles and a pipeline factory (Arc<... Fn() -> DynPipeline<_> >)
-    let setup = start_router(config).expect("failed to start router");
-
-    MetricsServer::new(args.metrics_address(), setup.stats);
+    let frame_factory: FrameFactory<TestBuffer> =
+        Arc::new(|frame: &[u8]| Packet::new(TestBuffer::from_raw_data(frame)).ok());
+    let setup =
+        start_router(config, args.pipeline(), frame_factory).expect("failed to start router");
 
     /* pipeline builder */
     let pipeline_factory = setup.pipeline;
 
-    /* start management */
+    /* punt path: no control-plane consumer is wired yet, so punted packets are logged and freed */
+    let punt_receiver = setup.punt;
+    std::thread::Builder::new()
+        .name("punt".to_string())
+        .spawn(move || {
+            while let Some(punted) = punt_receiver.recv() {
+                debug!("Punted packet ({}):\n{}", punted.reason, punted.packet);
+            }
+        })
+        .expect("Failed to start punt thread");
+
+    /* start management, which tells the driver about the devices of each config generation */
+    let (device_tx, device_rx) = std::sync::mpsc::channel();
     start_mgmt(
         grpc_addr,
         setup.router.get_ctl_tx(),
@@ -131,30 +162,53 @@ fn main() {
         setup.vpcdtablesw,
         setup.vpcmapw,
         setup.vpc_stats_store,
+        device_tx,
+        setup.flow_table,
     )
     .expect("Failed to start gRPC server");
 
     /* start driver with the provided pipeline builder */
-    match args.get_driver_name() {
+    let executor_config = ExecutorConfig {
+        cores: args.worker_cores(),
+        work_stealing: args.work_stealing(),
+        ..ExecutorConfig::default()
+    };
+    let mut stats = setup.stats;
+    let dpdk_driver = match args.get_driver_name() {
         "dpdk" => {
             info!("Using driver DPDK...");
-            DriverDpdk::start(args.eal_params(), &setup_pipeline);
+            let dpdk_pipeline: PipelineFactory<Mbuf> = Arc::new(setup_pipeline::<Mbuf>);
+            // the DPDK pipeline does not run the stages originating frames
+            drop(setup.originated);
+            let driver = DriverDpdk::start(args.eal_params(), &executor_config, &dpdk_pipeline);
+            stats.add_nic_metrics(driver.nic_stats_source());
+            Some(driver)
         }
         "kernel" => {
             info!("Using driver kernel...");
             DriverKernel::start(
                 args.kernel_interfaces(),
                 args.kernel_num_workers(),
+                &executor_config,
                 &pipeline_factory,
+                &setup.originated,
             );
+            None
         }
         other => {
             error!("Unknown driver '{other}'. Aborting...");
             panic!("Packet processing pipeline failed to start. Aborting...");
         }
+    };
+
+    /* the metrics server starts once the driver has registered its own metrics */
+    MetricsServer::new(args.metrics_address(), stats);
+
+    match dpdk_driver {
+        Some(mut driver) => driver.serve_devices(&device_rx, &stop_rx),
+        None => stop_rx.recv().expect("failed to receive stop signal"),
     }
 
-    stop_rx.recv().expect("failed to receive stop signal");
     info!("Shutting down dataplane");
     std::process::exit(0);
 }
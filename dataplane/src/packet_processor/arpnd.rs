@@ -0,0 +1,310 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+//
+//! Implements an ARP/ND stage: it answers the ARP requests for the prefixes configured for
+//! proxy ARP, and the ARP and ND requests for the addresses with an EVPN MAC/IP binding
+//! (ARP/ND suppression). It also announces the addresses of the interfaces that are up with
+//! gratuitous ARP requests (IPv4) and unsolicited neighbor advertisements (IPv6).
+
+use std::collections::BTreeSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Instant;
+use tracing::{debug, trace, warn};
+
+use net::arp::{ArpBuilder, ArpOperation};
+use net::buffer::PacketBufferMut;
+use net::eth::ethtype::EthType;
+use net::eth::mac::{DestinationMac, Mac, SourceMac};
+use net::headers::{Net, TryArp, TryArpMut, TryEth, TryEthMut, TryIcmp6, TryIp};
+use net::icmp6::Icmp6;
+use net::interface::InterfaceIndex;
+use net::ipv6::{Ipv6, UnicastIpv6Addr};
+use net::packet::{DoneReason, Packet, PacketBuilder};
+use net::parse::{DeParse, IntoNonZeroUSize};
+use net::vlan::Vid;
+use pipeline::NetworkFunction;
+
+use routing::atable::arpnd::ArpNdAgent;
+use routing::evpn::MacIpBindings;
+use routing::fib::fibtype::FibKey;
+use routing::interfaces::iftablerw::IfTableReader;
+use routing::interfaces::interface::{Attachment, IfState, IfType, Interface};
+use routing::rib::vrf::VrfId;
+
+use super::lldp::FrameFactory;
+
+use tracectl::trace_target;
+trace_target!("arp-nd", LevelFilter::WARN, &["pipeline"]);
+
+/// Hop limit of neighbor discovery messages (RFC 4861, 7.1.2)
+const ND_HOP_LIMIT: u8 = 255;
+
+/// Type of the target link-layer address option of neighbor discovery messages
+const ND_OPT_TARGET_LL_ADDR: u8 = 2;
+
+/// Length of the target address of neighbor discovery messages
+const ND_TARGET_LEN: usize = 16;
+
+pub struct ArpNd<Buf: PacketBufferMut> {
+    name: String,
+    iftr: IfTableReader,
+    agent: ArpNdAgent,
+    macip: MacIpBindings,
+    frame_factory: FrameFactory<Buf>,
+}
+
+/// Tell if the addresses of `interface` can be announced
+fn can_announce(interface: &Interface) -> bool {
+    matches!(interface.iftype, IfType::Ethernet(_) | IfType::Dot1q(_))
+        && interface.admin_state == IfState::Up
+        && interface.oper_state == IfState::Up
+}
+
+/// Get the VRF `interface` is attached to, if any
+fn attached_vrf(interface: &Interface) -> Option<VrfId> {
+    match interface.attachment {
+        Some(Attachment::VRF(FibKey::Id(vrfid))) => Some(vrfid),
+        _ => None,
+    }
+}
+
+/// Get the vlan of `interface`, if it is a vlan interface
+fn vlan(interface: &Interface) -> Option<Vid> {
+    match &interface.iftype {
+        IfType::Dot1q(dot1q) => Some(dot1q.vlanid),
+        _ => None,
+    }
+}
+
+/// Build a neighbor advertisement telling that `target` is at `mac`, sent to `destination`
+fn nd_advertisement(
+    mac: Mac,
+    eth_destination: Mac,
+    target: Ipv6Addr,
+    destination: Ipv6Addr,
+    solicited: bool,
+) -> Option<PacketBuilder> {
+    let mut ip = Ipv6::default();
+    ip.set_source(UnicastIpv6Addr::new(target).ok()?)
+        .set_destination(destination)
+        .set_hop_limit(ND_HOP_LIMIT);
+    /* target address, then its link-layer address option */
+    let mut payload = target.octets().to_vec();
+    payload.extend_from_slice(&[ND_OPT_TARGET_LL_ADDR, 1]);
+    payload.extend_from_slice(&mac.0);
+    /* solicited advertisements answer on behalf of other hosts: they don't override */
+    Some(
+        PacketBuilder::new()
+            .eth(mac, eth_destination)
+            .ipv6(ip)
+            .icmp6(Icmp6::neighbor_advertisement(false, solicited, !solicited))
+            .payload(payload),
+    )
+}
+
+impl<Buf: PacketBufferMut> ArpNd<Buf> {
+    /// Creates a new [`ArpNd`] stage
+    pub fn new(
+        name: &str,
+        iftr: IfTableReader,
+        agent: ArpNdAgent,
+        macip: MacIpBindings,
+        frame_factory: FrameFactory<Buf>,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            iftr,
+            agent,
+            macip,
+            frame_factory,
+        }
+    }
+
+    /// Get the mac address to answer a request for `target` received on interface `iif`
+    /// with, if the request must be answered by this stage, and the vlan of the interface
+    fn answer_mac(&self, iif: InterfaceIndex, target: IpAddr) -> Option<(Mac, Option<Vid>)> {
+        let iftable = self.iftr.enter()?;
+        let interface = iftable.get_interface(iif)?;
+        /* requests for our own addresses are answered by the kernel */
+        if interface.has_address(&target) {
+            return None;
+        }
+        if let IpAddr::V4(target) = target {
+            if self.agent.proxies(iif, target) {
+                return Some((interface.get_mac()?, vlan(interface)));
+            }
+        }
+        let mac = self.macip.lookup(attached_vrf(interface)?, target)?;
+        Some((mac, vlan(interface)))
+    }
+
+    /// Turn `packet` into a reply if it is an ARP request to be answered by this stage
+    fn arp_rx(&self, packet: &mut Packet<Buf>) {
+        let Some(arp) = packet.try_arp().copied() else {
+            return;
+        };
+        if arp.operation() != ArpOperation::REQUEST || arp.is_gratuitous() {
+            return;
+        }
+        let nfi = &self.name;
+        let Some(iif) = packet.get_meta().iif else {
+            warn!("{nfi}: Received ARP request without incoming interface");
+            return;
+        };
+        let target = arp.target_ip();
+        let Some((mac, _)) = self.answer_mac(iif, IpAddr::V4(target)) else {
+            return;
+        };
+        let (Ok(source), Ok(destination)) =
+            (SourceMac::new(mac), DestinationMac::new(arp.sender_mac()))
+        else {
+            debug!("{nfi}: Can't answer ARP request from {}", arp.sender_mac());
+            return;
+        };
+        let Some(eth) = packet.try_eth_mut() else {
+            unreachable!()
+        };
+        eth.set_source(source).set_destination(destination);
+        if let Some(request) = packet.try_arp_mut() {
+            *request = arp.reply(mac);
+        }
+        trace!(
+            "{nfi}: Answering ARP request for {target} from {}",
+            arp.sender_ip()
+        );
+        packet.get_meta_mut().oif = Some(iif);
+        packet.done(DoneReason::Delivered);
+    }
+
+    /// Build the neighbor advertisement answering `packet`, if it is a neighbor solicitation
+    /// to be answered by this stage
+    fn nd_rx(&self, packet: &Packet<Buf>) -> Option<Packet<Buf>> {
+        if !packet.try_icmp6()?.is_neighbor_solicitation() {
+            return None;
+        }
+        let Some(Net::Ipv6(ip)) = packet.try_ip() else {
+            return None;
+        };
+        /* solicitations for duplicate address detection are left to the kernel */
+        let source = ip.source().inner();
+        if ip.hop_limit() != ND_HOP_LIMIT || source.is_unspecified() {
+            return None;
+        }
+        let target = packet.payload().as_ref().get(..ND_TARGET_LEN)?;
+        let target = Ipv6Addr::from(<[u8; ND_TARGET_LEN]>::try_from(target).ok()?);
+        let iif = packet.get_meta().iif?;
+        let (mac, vid) = self.answer_mac(iif, IpAddr::V6(target))?;
+        let requester = packet.try_eth()?.source().inner();
+        let nfi = &self.name;
+        let mut builder = nd_advertisement(mac, requester, target, source, true)?;
+        if let Some(vid) = vid {
+            builder = builder.vlan(vid);
+        }
+        let frame = builder
+            .build_bytes()
+            .inspect_err(|e| warn!("{nfi}: Failed to build neighbor advertisement: {e}"))
+            .ok()?;
+        let mut reply = (self.frame_factory)(&frame)?;
+        trace!("{nfi}: Answering neighbor solicitation for {target} from {source}");
+        reply.get_meta_mut().oif = Some(iif);
+        reply.done(DoneReason::Delivered);
+        Some(reply)
+    }
+
+    /// Build a gratuitous ARP request announcing that `address` is at `mac`
+    fn garp_payload(&self, address: Ipv4Addr, mac: Mac) -> Option<Vec<u8>> {
+        let arp = ArpBuilder::default()
+            .operation(ArpOperation::REQUEST)
+            .sender_mac(mac)
+            .sender_ip(address)
+            .target_ip(address)
+            .build()
+            .inspect_err(|e| warn!("{}: Failed to build gratuitous ARP: {e}", self.name))
+            .ok()?;
+        let mut payload = vec![0u8; arp.size().into_non_zero_usize().get()];
+        arp.deparse(&mut payload).ok()?;
+        Some(payload)
+    }
+
+    /// Build the frame announcing `address` over `interface`
+    fn announce_frame(&self, interface: &Interface, address: IpAddr) -> Option<Packet<Buf>> {
+        let nfi = &self.name;
+        let mac = interface.get_mac()?;
+        let builder = match address {
+            IpAddr::V4(address) => PacketBuilder::new()
+                .eth(mac, Mac::BROADCAST)
+                .ether_type(EthType::ARP)
+                .payload(self.garp_payload(address, mac)?),
+            IpAddr::V6(address) => {
+                let all_nodes = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+                let all_nodes_mac = Mac::from_multicast_group(all_nodes.into())?;
+                nd_advertisement(mac, all_nodes_mac, address, all_nodes, false)?
+            }
+        };
+        let builder = match vlan(interface) {
+            Some(vid) => builder.vlan(vid),
+            None => builder,
+        };
+        let frame = builder
+            .build_bytes()
+            .inspect_err(|e| warn!("{nfi}: Failed to build announcement of {address}: {e}"))
+            .ok()?;
+        let mut packet = (self.frame_factory)(&frame)?;
+        packet.get_meta_mut().oif = Some(interface.ifindex);
+        packet.done(DoneReason::Delivered);
+        Some(packet)
+    }
+
+    /// Build the frames announcing the new addresses at `now`, if any
+    fn announce_tx(&self, now: Instant) -> Vec<Packet<Buf>> {
+        let Some(iftable) = self.iftr.enter() else {
+            warn!("{}: Interface table no longer readable!", &self.name);
+            return Vec::new();
+        };
+        let announcements = self.agent.announcements(now, || {
+            iftable
+                .values()
+                .filter(|interface| can_announce(interface))
+                .flat_map(|interface| {
+                    interface
+                        .addresses
+                        .iter()
+                        .map(|(address, _)| (interface.ifindex, *address))
+                })
+                .collect::<BTreeSet<(InterfaceIndex, IpAddr)>>()
+        });
+        let frames: Vec<_> = announcements
+            .iter()
+            .filter_map(|(ifindex, address)| {
+                let interface = iftable.get_interface(*ifindex)?;
+                self.announce_frame(interface, *address)
+            })
+            .collect();
+        if !frames.is_empty() {
+            debug!("{}: Announcing {} address(es)", &self.name, frames.len());
+        }
+        frames
+    }
+}
+
+impl<Buf: PacketBufferMut> NetworkFunction<Buf> for ArpNd<Buf> {
+    fn process<'a, Input: Iterator<Item = Packet<Buf>> + 'a>(
+        &'a mut self,
+        input: Input,
+    ) -> impl Iterator<Item = Packet<Buf>> + 'a {
+        trace!("Stage '{}'...", self.name);
+        let frames = self.announce_tx(Instant::now());
+        input
+            .filter_map(move |mut packet| {
+                if !packet.is_done() {
+                    self.arp_rx(&mut packet);
+                    /* answered solicitations are replaced by their answer */
+                    if let Some(reply) = self.nd_rx(&packet) {
+                        return Some(reply);
+                    }
+                }
+                packet.enforce()
+            })
+            .chain(frames)
+    }
+}
@@ -0,0 +1,210 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+//
+//! Implements a BFD stage, which feeds the received BFD control packets to the
+//! BFD sessions, and the BFD timer thread, which runs the detection timers of the
+//! sessions and emits their control packets when due.
+
+use std::net::IpAddr;
+use std::sync::mpsc::TrySendError;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, trace, warn};
+
+use net::bfd::BfdControl;
+use net::buffer::PacketBufferMut;
+use net::headers::{Net, TryIp, TryUdp};
+use net::ipv4::{Ipv4, UnicastIpv4Addr};
+use net::ipv6::{Ipv6, UnicastIpv6Addr};
+use net::packet::{DoneReason, Packet, PacketBuilder};
+use net::parse::{DeParse, IntoNonZeroUSize, Parse};
+use net::udp::{Udp, UdpPort};
+use pipeline::NetworkFunction;
+
+use routing::atable::atablerw::{AtableReader, AtableReaderFactory};
+use routing::bfd::{BfdSessions, BfdTx};
+use routing::interfaces::iftablerw::{IfTableReader, IfTableReaderFactory};
+
+use super::OriginatedSender;
+use super::lldp::FrameFactory;
+
+use tracectl::trace_target;
+trace_target!("bfd", LevelFilter::WARN, &["pipeline"]);
+
+/// Interval at which the BFD timer thread runs the timers of the sessions. This bounds the
+/// jitter of the emissions and of the detection of the sessions going down.
+pub const BFD_TIMER_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct Bfd {
+    name: String,
+    sessions: BfdSessions,
+}
+
+impl Bfd {
+    /// Creates a new [`Bfd`] stage
+    pub fn new(name: &str, sessions: BfdSessions) -> Self {
+        Self {
+            name: name.to_owned(),
+            sessions,
+        }
+    }
+
+    /// Process `packet` if it is a single-hop BFD control packet for one of our sessions
+    fn bfd_rx<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>, now: Instant) {
+        let Some(udp) = packet.try_udp() else {
+            return;
+        };
+        if u16::from(udp.destination()) != BfdControl::UDP_PORT {
+            return;
+        }
+        /* single-hop BFD packets must not have been forwarded (RFC 5881, 5) */
+        let (source, destination) = match packet.try_ip() {
+            Some(Net::Ipv4(ip)) if ip.ttl() == BfdControl::TTL => (
+                IpAddr::V4(ip.source().inner()),
+                IpAddr::V4(ip.destination()),
+            ),
+            Some(Net::Ipv6(ip)) if ip.hop_limit() == BfdControl::TTL => (
+                IpAddr::V6(ip.source().inner()),
+                IpAddr::V6(ip.destination()),
+            ),
+            _ => return,
+        };
+        let nfi = &self.name;
+        let Some(iif) = packet.get_meta().iif else {
+            warn!("{nfi}: Received BFD packet without incoming interface");
+            return;
+        };
+        let control = match BfdControl::parse(packet.payload().as_ref()) {
+            Ok((control, _)) => control,
+            Err(e) => {
+                debug!("{nfi}: Failed to parse BFD packet from {source} on ifindex {iif}: {e:?}");
+                return;
+            }
+        };
+        trace!("{nfi}: Received BFD packet from {source} on ifindex {iif}: {control:?}");
+        if self.sessions.rx(iif, source, destination, &control, now) {
+            packet.done(DoneReason::Local);
+        }
+    }
+}
+
+/// Builds the frames carrying the BFD control packets of the sessions
+struct BfdEmitter<Buf: PacketBufferMut> {
+    iftr: IfTableReader,
+    atabler: AtableReader,
+    sessions: BfdSessions,
+    frame_factory: FrameFactory<Buf>,
+}
+
+impl<Buf: PacketBufferMut> BfdEmitter<Buf> {
+    /// Build the frame carrying the control packet `tx`
+    fn bfd_frame(&self, tx: &BfdTx) -> Option<Packet<Buf>> {
+        let src_mac = {
+            let iftable = self.iftr.enter()?;
+            iftable.get_interface(tx.ifindex)?.get_mac()?
+        };
+        let dst_mac = {
+            let atable = self.atabler.enter()?;
+            let Some(adj) = atable.get_adjacency(tx.peer, tx.ifindex) else {
+                trace!("Missing L2 info for BFD peer {}", tx.peer);
+                return None;
+            };
+            adj.get_mac()
+        };
+        let mut payload = vec![0u8; BfdControl::LENGTH.into_non_zero_usize().get()];
+        if let Err(e) = tx.control.deparse(&mut payload) {
+            warn!("Failed to build BFD control packet: {e:?}");
+            return None;
+        }
+        let udp = Udp::new(
+            UdpPort::new_checked(tx.src_port).ok()?,
+            UdpPort::new_checked(BfdControl::UDP_PORT).ok()?,
+        );
+        let builder = PacketBuilder::new().eth(src_mac, dst_mac);
+        let builder = match (tx.local, tx.peer) {
+            (IpAddr::V4(local), IpAddr::V4(peer)) => {
+                let mut ip = Ipv4::default();
+                ip.set_source(UnicastIpv4Addr::new(local).ok()?)
+                    .set_destination(peer)
+                    .set_ttl(BfdControl::TTL);
+                builder.ipv4(ip)
+            }
+            (IpAddr::V6(local), IpAddr::V6(peer)) => {
+                let mut ip = Ipv6::default();
+                ip.set_source(UnicastIpv6Addr::new(local).ok()?)
+                    .set_destination(peer)
+                    .set_hop_limit(BfdControl::TTL);
+                builder.ipv6(ip)
+            }
+            _ => return None,
+        };
+        let frame = builder
+            .udp(udp)
+            .payload(payload)
+            .build_bytes()
+            .inspect_err(|e| warn!("Failed to build BFD frame: {e}"))
+            .ok()?;
+        let mut packet = (self.frame_factory)(&frame)?;
+        packet.get_meta_mut().oif = Some(tx.ifindex);
+        packet.done(DoneReason::Delivered);
+        Some(packet)
+    }
+
+    /// Run the timers of the sessions and build the BFD frames to emit at `now`, if any
+    fn frames(&self, now: Instant) -> Vec<Packet<Buf>> {
+        self.sessions
+            .poll(now)
+            .iter()
+            .filter_map(|tx| self.bfd_frame(tx))
+            .collect()
+    }
+}
+
+/// Start the thread running the timers of the BFD sessions, which sends the control packets
+/// to emit over `originated`
+pub(crate) fn start_bfd_tx<Buf: PacketBufferMut>(
+    iftr_factory: IfTableReaderFactory,
+    atabler_factory: AtableReaderFactory,
+    sessions: BfdSessions,
+    frame_factory: FrameFactory<Buf>,
+    originated: OriginatedSender<Buf>,
+) {
+    let spawned = std::thread::Builder::new()
+        .name("bfd-tx".to_string())
+        .spawn(move || {
+            let emitter = BfdEmitter {
+                iftr: iftr_factory.handle(),
+                atabler: atabler_factory.handle(),
+                sessions,
+                frame_factory,
+            };
+            loop {
+                for frame in emitter.frames(Instant::now()) {
+                    match originated.try_send(frame) {
+                        Ok(()) => {}
+                        Err(TrySendError::Full(_)) => warn!("Originated frames queue full"),
+                        Err(TrySendError::Disconnected(_)) => return,
+                    }
+                }
+                std::thread::sleep(BFD_TIMER_INTERVAL);
+            }
+        });
+    if let Err(e) = spawned {
+        error!("Failed to start the BFD transmitter: {e}");
+    }
+}
+
+impl<Buf: PacketBufferMut> NetworkFunction<Buf> for Bfd {
+    fn process<'a, Input: Iterator<Item = Packet<Buf>> + 'a>(
+        &'a mut self,
+        input: Input,
+    ) -> impl Iterator<Item = Packet<Buf>> + 'a {
+        trace!("Stage '{}'...", self.name);
+        let now = Instant::now();
+        input.filter_map(move |mut packet| {
+            if !packet.is_done() {
+                self.bfd_rx(&mut packet, now);
+            }
+            packet.enforce()
+        })
+    }
+}
@@ -8,7 +8,7 @@ use tracing::{debug, error, trace, warn};
 
 use net::eth::Eth;
 use net::eth::ethtype::EthType;
-use net::eth::mac::{DestinationMac, SourceMac};
+use net::eth::mac::{DestinationMac, Mac, SourceMac};
 use net::{
     buffer::PacketBufferMut,
     headers::{TryIpv4, TryIpv6},
@@ -162,9 +162,13 @@ impl Egress {
         // adjacency table. Otherwise, that means that the packet is directly connected
         // to us (on the same subnet). So, fetch the destination IP address and try to
         // resolve it with the adjacency table as well. If that fails, that's where the
-        // ARP/ND would need to be triggered.
+        // ARP/ND would need to be triggered. Packets to a multicast group are sent to the
+        // MAC address that the group maps to.
         if let Some(nh_addr) = packet.get_meta().nh_addr {
             self.get_adj_mac(packet, nh_addr, ifindex)
+        } else if let Some(group_mac) = packet.ip_destination().and_then(Mac::from_multicast_group)
+        {
+            DestinationMac::new(group_mac).ok()
         } else if let Some(destination) = packet.ip_destination() {
             self.get_adj_mac(packet, destination, ifindex)
         } else {
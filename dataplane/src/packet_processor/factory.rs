@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Instantiation of the packet-processing pipelines from their layout
+
+use super::arpnd::ArpNd;
+use super::bfd::Bfd;
+use super::egress::Egress;
+use super::ingress::Ingress;
+use super::ipforward::IpForwarder;
+use super::lldp::{FrameFactory, Lldp};
+use super::mcast::McastForwarder;
+use super::mpls::MplsForwarder;
+use super::srv6::Srv6;
+use super::urpf::Urpf;
+
+use concurrency::sync::Arc;
+use config::internal::device::pipeline::{PipelineConfig, StageConfig, StageType};
+
+use nat::stateful::NatAllocatorReaderFactory;
+use nat::stateless::natrw::NatTablesReaderFactory;
+use nat::{NatVpcCounters, StatefulNat, StatelessNat};
+
+use net::buffer::PacketBufferMut;
+use pipeline::sample_nfs::PacketDumper;
+use pipeline::{DynNetworkFunction, DynPipeline, Punt, PuntSender, nf_dyn};
+
+use pkt_meta::dst_vpcd_lookup::{DstVpcdLookup, VpcDiscTablesReaderFactory};
+use pkt_meta::flow_table::{ExpirationsNF, FlowTable, LookupNF};
+
+use routing::atable::arpnd::ArpNdAgent;
+use routing::atable::atablerw::AtableReaderFactory;
+use routing::bfd::BfdSessions;
+use routing::evpn::MacIpBindings;
+use routing::fib::fibtable::FibTableReaderFactory;
+use routing::fib::urpf::UrpfDrops;
+use routing::interfaces::iftablerw::IfTableReaderFactory;
+use routing::interfaces::lldp::LldpNeighbors;
+use routing::lfib::lfibrw::LfibReaderFactory;
+use routing::mcast::mroutetablerw::MrouteTableReaderFactory;
+use routing::mcast::snooping::McastMembers;
+use routing::srv6::sidtablerw::SidTableReaderFactory;
+
+use stats::{PacketStatsWriter, Stats};
+
+use tracing::debug;
+
+use vpcmap::usage::VpcUsage;
+
+/// The state shared by the network functions of the pipelines: readers of the tables that
+/// the control plane populates, and the state shared by the workers. Each pipeline instance
+/// gets its own network functions, built from these.
+pub(crate) struct StageFactory<Buf: PacketBufferMut> {
+    pub(crate) iftr_factory: IfTableReaderFactory,
+    pub(crate) fibtr_factory: FibTableReaderFactory,
+    pub(crate) lfibr_factory: LfibReaderFactory,
+    pub(crate) sidtabler_factory: SidTableReaderFactory,
+    pub(crate) mroutetabler_factory: MrouteTableReaderFactory,
+    pub(crate) atabler_factory: AtableReaderFactory,
+    pub(crate) mcast_members: McastMembers,
+    pub(crate) lldp_neighbors: LldpNeighbors,
+    pub(crate) bfd_sessions: BfdSessions,
+    pub(crate) arpnd_agent: ArpNdAgent,
+    pub(crate) macip_bindings: MacIpBindings,
+    pub(crate) urpf_drops: UrpfDrops,
+    pub(crate) vpcdtablesr_factory: VpcDiscTablesReaderFactory,
+    pub(crate) nattabler_factory: NatTablesReaderFactory,
+    pub(crate) natallocator_factory: NatAllocatorReaderFactory,
+    pub(crate) nat_counters: Arc<NatVpcCounters>,
+    pub(crate) vni_usage: Arc<VpcUsage>,
+    pub(crate) flow_table: Arc<FlowTable>,
+    pub(crate) punt_sender: PuntSender<Buf>,
+    pub(crate) stats_writer: PacketStatsWriter,
+    pub(crate) frame_factory: FrameFactory<Buf>,
+}
+
+impl<Buf: PacketBufferMut> StageFactory<Buf> {
+    /// Build the network function of a stage
+    fn build_stage(&self, config: &StageConfig) -> Box<dyn DynNetworkFunction<Buf>> {
+        let name = config.name.as_str();
+        match config.stage {
+            StageType::Dump => nf_dyn(PacketDumper::new(name, true, None)),
+            StageType::Lldp => nf_dyn(Lldp::new(name, self.lldp_neighbors.clone())),
+            StageType::Bfd => nf_dyn(Bfd::new(name, self.bfd_sessions.clone())),
+            StageType::ArpNd => nf_dyn(ArpNd::new(
+                name,
+                self.iftr_factory.handle(),
+                self.arpnd_agent.clone(),
+                self.macip_bindings.clone(),
+                self.frame_factory.clone(),
+            )),
+            StageType::Ingress => nf_dyn(Ingress::new(name, self.iftr_factory.handle())),
+            StageType::Urpf => nf_dyn(Urpf::new(
+                name,
+                self.iftr_factory.handle(),
+                self.fibtr_factory.handle(),
+                self.urpf_drops.clone(),
+            )),
+            StageType::Mpls => nf_dyn(MplsForwarder::new(name, self.lfibr_factory.handle())),
+            StageType::Srv6 => nf_dyn(Srv6::new(name, self.sidtabler_factory.handle())),
+            StageType::Mcast => nf_dyn(McastForwarder::new(
+                name,
+                self.mroutetabler_factory.handle(),
+                self.mcast_members.clone(),
+                self.frame_factory.clone(),
+            )),
+            StageType::IpForward => nf_dyn(
+                IpForwarder::new(name, self.fibtr_factory.handle())
+                    .with_usage(self.vni_usage.clone()),
+            ),
+            StageType::DstVpcdLookup => {
+                nf_dyn(DstVpcdLookup::new(name, self.vpcdtablesr_factory.handle()))
+            }
+            StageType::FlowLookup => nf_dyn(LookupNF::new(self.flow_table.clone())),
+            StageType::StatelessNat => nf_dyn(
+                StatelessNat::with_reader(name, self.nattabler_factory.handle())
+                    .with_counters(self.nat_counters.clone()),
+            ),
+            // The stateful NAT stage finds its sessions in the flow info that the flow lookup
+            // stage attaches to packets, and relies on the expiration stage to reap its expired
+            // sessions: all three must use the same table.
+            StageType::StatefulNat => nf_dyn(
+                StatefulNat::with_reader(name, self.natallocator_factory.handle())
+                    .with_sessions(self.flow_table.clone())
+                    .with_counters(self.nat_counters.clone()),
+            ),
+            StageType::Egress => nf_dyn(Egress::new(
+                name,
+                self.iftr_factory.handle(),
+                self.atabler_factory.handle(),
+            )),
+            StageType::Punt => nf_dyn(Punt::new(name, self.punt_sender.clone())),
+            StageType::FlowExpirations => nf_dyn(ExpirationsNF::new(self.flow_table.clone())),
+            StageType::Stats => nf_dyn(Stats::new(name, self.stats_writer.clone())),
+        }
+    }
+
+    /// Build a pipeline instance with the stages of `config`, in order
+    pub(crate) fn build_pipeline(&self, config: &PipelineConfig) -> DynPipeline<Buf> {
+        config
+            .stages
+            .iter()
+            .fold(DynPipeline::new(), |pipeline, stage| {
+                debug!("Adding pipeline stage {stage}");
+                pipeline.add_named_stage_dyn(&stage.name, self.build_stage(stage))
+            })
+    }
+}
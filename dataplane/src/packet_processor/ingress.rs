@@ -100,6 +100,25 @@ impl Ingress {
         );
     }
 
+    #[tracing::instrument(level = "trace")]
+    fn interface_ingress_eth_mcast<Buf: PacketBufferMut>(
+        &self,
+        interface: &Interface,
+        packet: &mut Packet<Buf>,
+    ) {
+        /* IP multicast is handled in the VRF of the interface, like unicast */
+        if packet.try_ip().is_some() {
+            self.interface_ingress_eth_ucast_local(interface, packet);
+        } else {
+            trace!(
+                "{nfi}: Processing of non-ip multicast frames is not supported ({ifname})",
+                nfi = self.name(),
+                ifname = interface.name
+            );
+            packet.done(DoneReason::Unhandled);
+        }
+    }
+
     #[tracing::instrument(level = "trace")]
     fn interface_ingress_eth<Buf: PacketBufferMut>(
         &self,
@@ -118,6 +137,8 @@ impl Ingress {
                     let dmac = eth.destination().inner();
                     if dmac.is_broadcast() {
                         self.interface_ingress_eth_bcast(interface, packet);
+                    } else if dmac.is_multicast() {
+                        self.interface_ingress_eth_mcast(interface, packet);
                     } else if dmac == if_mac {
                         self.interface_ingress_eth_ucast_local(interface, packet);
                     } else {
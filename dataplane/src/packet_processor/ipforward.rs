@@ -6,16 +6,17 @@
 #![allow(clippy::similar_names)]
 
 use arrayvec::ArrayVec;
-use net::headers::{TryHeadersMut, TryIpv4Mut, TryIpv6Mut};
+use concurrency::sync::Arc;
+use net::headers::{TryHeadersMut, TryIpv4, TryIpv4Mut, TryIpv6, TryIpv6Mut};
 use net::packet::{DoneReason, Packet};
 use net::{buffer::PacketBufferMut, checksum::Checksum};
-use pipeline::NetworkFunction;
+use pipeline::{NetworkFunction, PuntReason, punt};
 use std::net::IpAddr;
 use tracing::{debug, error, trace, warn};
 
 use routing::fib::fibobjects::{EgressObject, FibEntry, PktInstruction};
 use routing::fib::fibtable::FibTableReader;
-use routing::fib::fibtype::FibKey;
+use routing::fib::fibtype::{FIB_LOOKUP_BATCH, FibKey};
 
 use routing::evpn::Vtep;
 use routing::rib::encapsulation::{Encapsulation, VxlanEncapsulation};
@@ -29,17 +30,24 @@ use net::ipv4::Ipv4;
 use net::ipv4::UnicastIpv4Addr;
 use net::ipv6::Ipv6;
 use net::ipv6::UnicastIpv6Addr;
+use net::mpls::MplsLabel;
 use net::packet::VpcDiscriminant;
 use net::udp::UdpEncap;
 use net::vxlan::Vxlan;
 use net::vxlan::VxlanEncap;
 
+use vpcmap::usage::VpcUsage;
+
 use tracectl::trace_target;
 trace_target!("ip-forward", LevelFilter::WARN, &["pipeline"]);
 
+/// Maximum number of packets forwarded together, see [`FIB_LOOKUP_BATCH`]
+const IPFWD_BATCH: usize = FIB_LOOKUP_BATCH;
+
 pub struct IpForwarder {
     name: String,
     fibtr: FibTableReader,
+    usage: Option<Arc<VpcUsage>>,
 }
 
 impl IpForwarder {
@@ -49,44 +57,130 @@ impl IpForwarder {
         Self {
             name: name.to_owned(),
             fibtr,
+            usage: None,
         }
     }
 
-    /// Forward a [`Packet`]
-    fn forward_packet<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>, vrfid: VrfId) {
+    /// Account the resolutions of the VNIs of the VxLAN packets received in `usage`: a VNI
+    /// without a fib usually denotes traffic for a VPC that is not configured
+    #[must_use]
+    pub fn with_usage(mut self, usage: Arc<VpcUsage>) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Tell the key of the fib to forward a [`Packet`] with
+    fn fib_key<Buf: PacketBufferMut>(
+        &self,
+        packet: &mut Packet<Buf>,
+        vrfid: VrfId,
+    ) -> Option<FibKey> {
         let nfi = &self.name;
-        let fibkey = if let Some(dst_vpcd) = packet.get_meta().dst_vpcd {
-            let VpcDiscriminant::VNI(dst_vni) = dst_vpcd;
-            FibKey::from_vni(dst_vni)
+        if let Some(dst_vpcd) = packet.get_meta().dst_vpcd {
+            let VpcDiscriminant::VNI(dst_vni) = dst_vpcd else {
+                // FIBs are only keyed by VNI for now
+                debug!("{nfi}: no fib for discriminant {dst_vpcd}");
+                packet.done(DoneReason::Unhandled);
+                return None;
+            };
+            Some(FibKey::from_vni(dst_vni))
         } else {
-            FibKey::from_vrfid(vrfid)
-        };
+            Some(FibKey::from_vrfid(vrfid))
+        }
+    }
 
-        /* get destination ip address */
-        let Some(dst) = packet.ip_destination() else {
-            error!("{nfi}: logic error, failed to get destination ip address for packet");
-            packet.done(DoneReason::InternalFailure);
-            return;
-        };
-        debug!("{nfi}: processing packet to {dst} with vrf {vrfid}");
+    /// Forward a batch of [`Packet`]s. The packets to be forwarded with the same fib are looked
+    /// up together, entering the fib only once.
+    fn forward_batch<Buf: PacketBufferMut>(&self, packets: &mut [Packet<Buf>]) {
+        let nfi = &self.name;
+
+        /* the index, fib key and destination of each packet to forward */
+        let mut pending: Vec<(usize, FibKey, IpAddr)> = Vec::with_capacity(packets.len());
+        for (index, packet) in packets.iter_mut().enumerate() {
+            if packet.is_done() {
+                continue;
+            }
+            // strip off vrf id from metadata
+            let Some(vrfid) = packet.get_meta_mut().vrf.take() else {
+                warn!("{nfi}: missing information to handle packet");
+                continue;
+            };
+            let Some(fibkey) = self.fib_key(packet, vrfid) else {
+                continue;
+            };
+
+            /* get destination ip address */
+            let Some(dst) = packet.ip_destination() else {
+                error!("{nfi}: logic error, failed to get destination ip address for packet");
+                packet.done(DoneReason::InternalFailure);
+                continue;
+            };
+            debug!("{nfi}: processing packet to {dst} with vrf {vrfid}");
+            pending.push((index, fibkey, dst));
+        }
+
+        /* batches mostly use a single fib: look up the packets of each fib at once */
+        let mut group: Vec<(usize, IpAddr)> = Vec::with_capacity(pending.len());
+        while let Some(&(_, fibkey, _)) = pending.first() {
+            group.clear();
+            pending.retain(|&(index, key, dst)| {
+                if key == fibkey {
+                    group.push((index, dst));
+                }
+                key != fibkey
+            });
+            self.forward_with_fib(packets, fibkey, &group);
+        }
+    }
+
+    /// Forward the packets at the given indices of a batch, to the given destinations, with the
+    /// fib for the given [`FibKey`]
+    fn forward_with_fib<Buf: PacketBufferMut>(
+        &self,
+        packets: &mut [Packet<Buf>],
+        fibkey: FibKey,
+        group: &[(usize, IpAddr)],
+    ) {
+        let nfi = &self.name;
 
         /* access fib, by fetching FibReader from cache */
         let Ok(fibr) = &self.fibtr.get_fib_reader(fibkey) else {
             warn!("{nfi}: Unable to read fib. Key={fibkey}");
-            packet.done(DoneReason::InternalFailure);
+            for (index, _) in group {
+                packets[*index].done(DoneReason::InternalFailure);
+            }
             return;
         };
         let Some(fib) = fibr.enter() else {
             warn!("{nfi}: Unable to read from fib. Key={fibkey}");
-            packet.done(DoneReason::InternalFailure);
+            for (index, _) in group {
+                packets[*index].done(DoneReason::InternalFailure);
+            }
             return;
         };
 
-        /* Perform lookup in the fib. This always returns a FibEntry */
-        let (prefix, fibentry) = fib.lpm_entry_prefix(packet);
-        debug!("{nfi}: Packet hits prefix {prefix} in fib {fibkey}");
-        debug!("{nfi}: Entry is:\n{fibentry}");
+        /* Perform the lookups in the fib. These always return a FibRoute */
+        let targets: Vec<IpAddr> = group.iter().map(|(_, dst)| *dst).collect();
+        let mut routes = Vec::with_capacity(targets.len());
+        fib.lpm_batch(&targets, &mut routes);
+
+        for ((index, dst), (prefix, route)) in group.iter().zip(routes) {
+            let packet = &mut packets[*index];
+            let fibentry = fib.select_entry(route, packet);
+            debug!("{nfi}: Packet hits prefix {prefix} in fib {fibkey}");
+            debug!("{nfi}: Entry is:\n{fibentry}");
+            self.forward_packet(packet, *dst, fibentry, fib.get_vtep());
+        }
+    }
 
+    /// Forward a [`Packet`] according to the [`FibEntry`] it hit
+    fn forward_packet<Buf: PacketBufferMut>(
+        &self,
+        packet: &mut Packet<Buf>,
+        dst: IpAddr,
+        fibentry: &FibEntry,
+        vtep: &Vtep,
+    ) {
         /* decrement packet TTL, unless the packet is for us */
         if !fibentry.is_iplocal() {
             Self::decrement_ttl(packet, dst);
@@ -96,7 +190,7 @@ impl IpForwarder {
             }
         }
         /* execute instructions according to FIB */
-        self.packet_exec_instructions(packet, fibentry, fib.get_vtep());
+        self.packet_exec_instructions(packet, fibentry, vtep);
     }
 
     /// Execute a local packet instruction
@@ -118,7 +212,11 @@ impl IpForwarder {
 
                 // access fib for Vni vni
                 let fibkey = FibKey::from_vni(vni);
-                let Ok(fibr) = self.fibtr.get_fib_reader(fibkey) else {
+                let fibr = self.fibtr.get_fib_reader(fibkey);
+                if let Some(usage) = &self.usage {
+                    usage.record(VpcDiscriminant::VNI(vni), fibr.is_ok());
+                }
+                let Ok(fibr) = fibr else {
                     error!("{nfi}: Failed to find fib associated to vni {vni}. Fib key = {fibkey}");
                     packet.done(DoneReason::Unroutable);
                     return;
@@ -150,7 +248,7 @@ impl IpForwarder {
                 We can't re-inject packet on ingress, so let's disable this to avoid churn
                 packet.get_meta_mut().oif = Some(packet.get_meta().iif);
                  */
-                packet.done(DoneReason::Local);
+                punt(packet, PuntReason::Local);
             }
         }
     }
@@ -194,8 +292,13 @@ impl IpForwarder {
         let headers = Headers {
             eth: None, /* to be set at egress */
             vlan: ArrayVec::default(),
+            pppoe: None,
+            nsh: None,
+            mpls: ArrayVec::default(),
+            arp: None,
             net: Some(net),
             net_ext: ArrayVec::default(),
+            gre: None,
             transport: None, /* should be UDP, but it is automatically done */
             udp_encap: Some(udp_encap),
             embedded_ip: None,
@@ -275,6 +378,30 @@ impl IpForwarder {
         }
     }
 
+    /// Push an MPLS label on a packet, with the TTL of its (already decremented) IP header
+    fn mpls_push<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>, label: u32) {
+        let nfi = &self.name;
+        let Ok(label) = MplsLabel::new(label) else {
+            error!("{nfi}: MPLS encap FAILED: invalid label {label}");
+            packet.done(DoneReason::InternalFailure);
+            return;
+        };
+        let ttl = if let Some(ipv4) = packet.try_ipv4() {
+            ipv4.ttl()
+        } else if let Some(ipv6) = packet.try_ipv6() {
+            ipv6.hop_limit()
+        } else {
+            unreachable!()
+        };
+        match packet.push_mpls(label, ttl) {
+            Ok(()) => debug!("{nfi}: pushed MPLS label {label}"),
+            Err(e) => {
+                error!("{nfi}: Failed to push MPLS label {label}: {e}");
+                packet.done(DoneReason::InternalFailure);
+            }
+        }
+    }
+
     /// Execute an encapsulation instruction on a packet as indicated by [`Encapsulation`]
     fn packet_exec_instruction_encap<Buf: PacketBufferMut>(
         #[allow(clippy::unused_self)] // Reserve the right to use self in the future
@@ -284,7 +411,7 @@ impl IpForwarder {
         vtep: &Vtep,
     ) {
         match encap {
-            Encapsulation::Mpls(_label) => todo!(),
+            Encapsulation::Mpls(label) => self.mpls_push(packet, *label),
             Encapsulation::Vxlan(vxlan) => self.vxlan_encap(packet, vxlan, vtep),
         }
     }
@@ -377,17 +504,20 @@ impl<Buf: PacketBufferMut> NetworkFunction<Buf> for IpForwarder {
         input: Input,
     ) -> impl Iterator<Item = Packet<Buf>> + 'a {
         trace!("{}'", self.name);
-        input.filter_map(move |mut packet| {
-            if !packet.is_done() {
-                // strip off vrf id from metadata
-                let vrfid = packet.get_meta_mut().vrf.take();
-                if let Some(vrfid) = vrfid {
-                    self.forward_packet(&mut packet, vrfid);
-                } else {
-                    warn!("{}: missing information to handle packet", self.name);
+        let mut input = input;
+        let mut batch: Vec<Packet<Buf>> = Vec::with_capacity(IPFWD_BATCH);
+        std::iter::from_fn(move || {
+            if batch.is_empty() {
+                batch.extend(input.by_ref().take(IPFWD_BATCH));
+                if batch.is_empty() {
+                    return None;
                 }
+                self.forward_batch(&mut batch);
+                // packets are popped from the back: keep them in order
+                batch.reverse();
             }
-            packet.enforce()
+            batch.pop()
         })
+        .filter_map(Packet::enforce)
     }
 }
@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+//
+//! Implements an LLDP stage, which learns neighbors from received LLDP frames,
+//! and the LLDP transmitter, which periodically emits LLDP frames on every
+//! ethernet interface from its own thread.
+
+use std::sync::mpsc::TrySendError;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, trace, warn};
+
+use concurrency::sync::Arc;
+
+use net::buffer::PacketBufferMut;
+use net::eth::ethtype::EthType;
+use net::headers::TryEth;
+use net::lldp::{ChassisId, LldpTlv, Lldpdu, PortId};
+use net::packet::{DoneReason, Packet, PacketBuilder};
+use net::parse::{DeParse, IntoNonZeroUSize, Parse};
+use pipeline::NetworkFunction;
+
+use super::OriginatedSender;
+
+use routing::interfaces::iftablerw::{IfTableReader, IfTableReaderFactory};
+use routing::interfaces::interface::{IfState, IfType, Interface};
+use routing::interfaces::lldp::LldpNeighbors;
+
+use tracectl::trace_target;
+trace_target!("lldp", LevelFilter::WARN, &["pipeline"]);
+
+/// Interval between two emissions of LLDP frames
+pub const LLDP_TX_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Number of intervals during which the information we advertise remains valid
+const LLDP_TX_HOLD: u16 = 4;
+
+/// Builds a [`Packet`] out of the bytes of an ethernet frame
+pub type FrameFactory<Buf> = Arc<dyn Send + Sync + Fn(&[u8]) -> Option<Packet<Buf>>>;
+
+pub struct Lldp {
+    name: String,
+    neighbors: LldpNeighbors,
+}
+
+impl Lldp {
+    /// Creates a new [`Lldp`] stage
+    pub fn new(name: &str, neighbors: LldpNeighbors) -> Self {
+        Self {
+            name: name.to_owned(),
+            neighbors,
+        }
+    }
+
+    /// Learn the neighbor advertised in `packet`, if it is an LLDP frame
+    fn lldp_rx<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>, now: Instant) {
+        if packet.try_eth().map(|eth| eth.ether_type()) != Some(EthType::LLDP) {
+            return;
+        }
+        let nfi = &self.name;
+        let Some(iif) = packet.get_meta().iif else {
+            warn!("{nfi}: Received LLDP frame without incoming interface");
+            packet.done(DoneReason::InternalFailure);
+            return;
+        };
+        match Lldpdu::parse(packet.payload().as_ref()) {
+            Ok((lldpdu, _)) => {
+                trace!("{nfi}: Received LLDP data unit on ifindex {iif}: {lldpdu:?}");
+                self.neighbors.update(iif, &lldpdu, now);
+            }
+            Err(e) => debug!("{nfi}: Failed to parse LLDP data unit on ifindex {iif}: {e:?}"),
+        }
+        packet.done(DoneReason::Local);
+    }
+}
+
+/// Builds the LLDP frames to emit over the ethernet interfaces
+struct LldpTx<Buf: PacketBufferMut> {
+    iftr: IfTableReader,
+    system_name: String,
+    frame_factory: FrameFactory<Buf>,
+}
+
+impl<Buf: PacketBufferMut> LldpTx<Buf> {
+    fn new(iftr: IfTableReader, frame_factory: FrameFactory<Buf>) -> Self {
+        let system_name = std::fs::read_to_string("/etc/hostname")
+            .ok()
+            .map(|name| name.trim().to_owned())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "dataplane".to_owned());
+        Self {
+            iftr,
+            system_name,
+            frame_factory,
+        }
+    }
+
+    /// Build the LLDP frame to emit over `interface`, if LLDP can be run on it
+    fn lldp_frame(&self, interface: &Interface) -> Option<Packet<Buf>> {
+        if !matches!(interface.iftype, IfType::Ethernet(_)) || interface.admin_state != IfState::Up
+        {
+            return None;
+        }
+        let mac = interface.get_mac()?;
+        #[allow(clippy::cast_possible_truncation)] // the interval is a few seconds
+        let ttl = LLDP_TX_HOLD * LLDP_TX_INTERVAL.as_secs() as u16;
+        let mut lldpdu = Lldpdu::new(
+            ChassisId::Local(self.system_name.clone()),
+            PortId::InterfaceName(interface.name.clone()),
+            ttl,
+        )
+        .with_tlv(LldpTlv::SystemName(self.system_name.clone()));
+        if let Some(description) = &interface.description {
+            lldpdu = lldpdu.with_tlv(LldpTlv::PortDescription(description.clone()));
+        }
+        let mut payload = vec![0u8; lldpdu.size().into_non_zero_usize().get()];
+        if let Err(e) = lldpdu.deparse(&mut payload) {
+            warn!("Failed to build LLDP data unit: {e:?}");
+            return None;
+        }
+        let frame = PacketBuilder::new()
+            .eth(mac, Lldpdu::DESTINATION)
+            .ether_type(EthType::LLDP)
+            .payload(payload)
+            .build_bytes()
+            .inspect_err(|e| warn!("Failed to build LLDP frame: {e}"))
+            .ok()?;
+        let mut packet = (self.frame_factory)(&frame)?;
+        packet.get_meta_mut().oif = Some(interface.ifindex);
+        packet.done(DoneReason::Delivered);
+        Some(packet)
+    }
+
+    /// Build the LLDP frames to emit over every interface
+    fn frames(&self) -> Vec<Packet<Buf>> {
+        let Some(iftable) = self.iftr.enter() else {
+            warn!("Interface table no longer readable!");
+            return Vec::new();
+        };
+        iftable
+            .values()
+            .filter_map(|interface| self.lldp_frame(interface))
+            .collect()
+    }
+}
+
+/// Start the thread emitting LLDP frames every [`LLDP_TX_INTERVAL`]. The frames are handed over
+/// to the driver with `originated`, without going through the pipeline.
+pub(crate) fn start_lldp_tx<Buf: PacketBufferMut>(
+    iftr_factory: IfTableReaderFactory,
+    frame_factory: FrameFactory<Buf>,
+    originated: OriginatedSender<Buf>,
+) {
+    let spawned = std::thread::Builder::new()
+        .name("lldp-tx".to_string())
+        .spawn(move || {
+            let tx = LldpTx::new(iftr_factory.handle(), frame_factory);
+            loop {
+                let frames = tx.frames();
+                debug!("Emitting {} LLDP frames", frames.len());
+                for frame in frames {
+                    match originated.try_send(frame) {
+                        Ok(()) => {}
+                        Err(TrySendError::Full(_)) => warn!("Originated frames queue full"),
+                        Err(TrySendError::Disconnected(_)) => return,
+                    }
+                }
+                std::thread::sleep(LLDP_TX_INTERVAL);
+            }
+        });
+    if let Err(e) = spawned {
+        error!("Failed to start the LLDP transmitter: {e}");
+    }
+}
+
+impl<Buf: PacketBufferMut> NetworkFunction<Buf> for Lldp {
+    fn process<'a, Input: Iterator<Item = Packet<Buf>> + 'a>(
+        &'a mut self,
+        input: Input,
+    ) -> impl Iterator<Item = Packet<Buf>> + 'a {
+        trace!("Stage '{}'...", self.name);
+        let now = Instant::now();
+        input.filter_map(move |mut packet| {
+            if !packet.is_done() {
+                self.lldp_rx(&mut packet, now);
+            }
+            packet.enforce()
+        })
+    }
+}
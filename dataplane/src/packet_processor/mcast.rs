@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Implements a multicast forwarding stage. IGMP and MLD reports are snooped to learn the
+//! interfaces with members of each group. Packets sent to a routable multicast group are
+//! replicated to the interfaces of the (S,G) or (*,G) mroute of the group, if any, and to the
+//! interfaces with members of the group. Link-local groups are left to the next stages.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::net::IpAddr;
+use std::time::Instant;
+use tracing::{debug, trace, warn};
+
+use net::buffer::PacketBufferMut;
+use net::headers::{TryIpv4Mut, TryIpv6Mut};
+use net::igmp::{GroupRecord, GroupRecordType, Igmp};
+use net::interface::InterfaceIndex;
+use net::mld::Mld;
+use net::packet::{DoneReason, Packet};
+use pipeline::NetworkFunction;
+
+use routing::mcast::mroutetablerw::MrouteTableReader;
+use routing::mcast::snooping::McastMembers;
+use routing::rib::vrf::VrfId;
+
+use super::lldp::FrameFactory;
+
+use tracectl::trace_target;
+trace_target!("mcast-forward", LevelFilter::WARN, &["pipeline"]);
+
+pub struct McastForwarder<Buf: PacketBufferMut> {
+    name: String,
+    mroutetabler: MrouteTableReader,
+    members: McastMembers,
+    frame_factory: FrameFactory<Buf>,
+}
+
+/// Tell if packets to a multicast `group` may be forwarded beyond the local link
+fn is_routable_group(group: IpAddr) -> bool {
+    match group {
+        IpAddr::V4(group) => group.is_multicast() && group.octets()[..3] != [224, 0, 0],
+        IpAddr::V6(group) => group.is_multicast() && group.segments()[0] & 0x000f > 2,
+    }
+}
+
+/// Tell if a group record of an IGMPv3 or MLDv2 report is a join (`Some(true)`), a leave
+/// (`Some(false)`), or does not change the membership to the group (`None`). Sources are
+/// not tracked: listening to some sources of a group is a membership to the whole group.
+fn record_membership<A>(record: &GroupRecord<A>) -> Option<bool> {
+    if record.is_join() {
+        Some(true)
+    } else if record.record_type() == GroupRecordType::BlockOldSources {
+        None
+    } else {
+        Some(false)
+    }
+}
+
+impl<Buf: PacketBufferMut> McastForwarder<Buf> {
+    /// Build a new multicast forwarding stage to use the indicated [`MrouteTableReader`] and
+    /// table of group members. Packets replicated to multiple interfaces are rebuilt with
+    /// `frame_factory`.
+    pub fn new(
+        name: &str,
+        mroutetabler: MrouteTableReader,
+        members: McastMembers,
+        frame_factory: FrameFactory<Buf>,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            mroutetabler,
+            members,
+            frame_factory,
+        }
+    }
+
+    /// Learn the group memberships reported in an IGMP or MLD `packet`, received on interface
+    /// `iif` in VRF `vrfid`. Returns false if the packet is not a report.
+    fn snoop(&self, packet: &Packet<Buf>, iif: InterfaceIndex, vrfid: VrfId, now: Instant) -> bool {
+        let mut changes: Vec<(IpAddr, Option<bool>)> = Vec::new();
+        if let Some(igmp) = packet.igmp() {
+            match igmp {
+                Igmp::V1Report(group) | Igmp::V2Report(group) => {
+                    changes.push((group.into(), Some(true)));
+                }
+                Igmp::Leave(group) => changes.push((group.into(), Some(false))),
+                Igmp::V3Report(records) => changes.extend(
+                    records
+                        .iter()
+                        .map(|record| ((*record.group()).into(), record_membership(record))),
+                ),
+                Igmp::Query(_) => return false,
+            }
+        } else if let Some(mld) = packet.mld() {
+            match mld {
+                Mld::V1Report(group) => changes.push((group.into(), Some(true))),
+                Mld::Done(group) => changes.push((group.into(), Some(false))),
+                Mld::V2Report(records) => changes.extend(
+                    records
+                        .iter()
+                        .map(|record| ((*record.group()).into(), record_membership(record))),
+                ),
+                Mld::Query(_) => return false,
+            }
+        } else {
+            return false;
+        }
+        for (group, join) in changes {
+            if !is_routable_group(group) {
+                continue;
+            }
+            match join {
+                Some(true) => self.members.join(vrfid, group, iif, now),
+                Some(false) => self.members.leave(vrfid, group, iif),
+                None => {}
+            }
+        }
+        true
+    }
+
+    /// Decrement the TTL or the hop limit of a packet to forward
+    fn decrement_ttl(packet: &mut Packet<Buf>) {
+        let exceeded = if let Some(ipv4) = packet.try_ipv4_mut() {
+            ipv4.decrement_ttl().is_err() || ipv4.ttl() == 0
+        } else if let Some(ipv6) = packet.try_ipv6_mut() {
+            ipv6.decrement_hop_limit().is_err() || ipv6.hop_limit() == 0
+        } else {
+            unreachable!()
+        };
+        if exceeded {
+            packet.done(DoneReason::HopLimitExceeded);
+        }
+    }
+
+    /// Get the interfaces to replicate a packet from `source` to `group` to, received on
+    /// interface `iif` in VRF `vrfid`, or `None` if it fails the RPF check of its mroute
+    fn lookup_oifs(
+        &self,
+        vrfid: VrfId,
+        source: IpAddr,
+        group: IpAddr,
+        iif: Option<InterfaceIndex>,
+        now: Instant,
+    ) -> Option<BTreeSet<InterfaceIndex>> {
+        let nfi = &self.name;
+        let mut oifs = BTreeSet::new();
+        match self.mroutetabler.enter() {
+            Some(mroutes) => {
+                if let Some(mroute) = mroutes.lookup(vrfid, source, group) {
+                    if !mroute.rpf_check(iif) {
+                        debug!("{nfi}: RPF check failed for {mroute}");
+                        return None;
+                    }
+                    oifs.extend(mroute.oifs.iter().copied());
+                }
+            }
+            None => warn!("{nfi}: Unable to read from mroute table"),
+        }
+        oifs.extend(self.members.members(vrfid, group, now));
+        if let Some(iif) = iif {
+            oifs.remove(&iif);
+        }
+        Some(oifs)
+    }
+
+    /// Replicate a packet to the given (non-empty) set of interfaces. The copies are pushed to
+    /// `output`, to be sent over their interface by the egress stage.
+    fn replicate(
+        &self,
+        packet: Packet<Buf>,
+        oifs: &BTreeSet<InterfaceIndex>,
+        output: &mut VecDeque<Packet<Buf>>,
+    ) {
+        let nfi = &self.name;
+        let mut meta = packet.get_meta().clone();
+        meta.vrf = None;
+        meta.nh_addr = None;
+        let set_meta = |mut copy: Packet<Buf>, oif: InterfaceIndex| {
+            *copy.get_meta_mut() = meta.clone();
+            copy.get_meta_mut().oif = Some(oif);
+            copy
+        };
+
+        let Some(&last) = oifs.last() else {
+            unreachable!()
+        };
+        if oifs.len() == 1 {
+            output.push_back(set_meta(packet, last));
+            return;
+        }
+
+        /* copies are rebuilt from the bytes of the packet; the last one reuses its buffer */
+        let buffer = match packet.serialize() {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                warn!("{nfi}: Failed to serialize packet to replicate: {e:?}");
+                return;
+            }
+        };
+        for &oif in oifs.range(..last) {
+            match (self.frame_factory)(buffer.as_ref()) {
+                Some(copy) => output.push_back(set_meta(copy, oif)),
+                None => warn!("{nfi}: Failed to replicate packet to ifindex {oif}"),
+            }
+        }
+        match Packet::new(buffer) {
+            Ok(packet) => output.push_back(set_meta(packet, last)),
+            Err(e) => warn!("{nfi}: Failed to rebuild packet to replicate: {e:?}"),
+        }
+    }
+
+    /// Process a [`Packet`]: snoop it if it is a membership report and replicate it if it is
+    /// addressed to a routable multicast group. The packets to forward are pushed to `output`.
+    fn process_packet(
+        &self,
+        mut packet: Packet<Buf>,
+        now: Instant,
+        output: &mut VecDeque<Packet<Buf>>,
+    ) {
+        let nfi = &self.name;
+        let meta = packet.get_meta();
+        let (Some(vrfid), iif) = (meta.vrf, meta.iif) else {
+            output.push_back(packet);
+            return;
+        };
+        let Some(group) = packet.ip_destination().filter(IpAddr::is_multicast) else {
+            output.push_back(packet);
+            return;
+        };
+        if let Some(iif) = iif {
+            if self.snoop(&packet, iif, vrfid, now) {
+                trace!("{nfi}: Snooped membership report on ifindex {iif}");
+                packet.done(DoneReason::Local);
+                output.push_back(packet);
+                return;
+            }
+        }
+        if !is_routable_group(group) {
+            output.push_back(packet);
+            return;
+        }
+        let Some(source) = packet.ip_source() else {
+            unreachable!()
+        };
+        match self.lookup_oifs(vrfid, source, group, iif, now) {
+            None => packet.done(DoneReason::Filtered),
+            Some(oifs) if oifs.is_empty() => {
+                debug!("{nfi}: No receivers for ({source}, {group}) in vrf {vrfid}");
+                packet.done(DoneReason::Unroutable);
+            }
+            Some(oifs) => {
+                Self::decrement_ttl(&mut packet);
+                if !packet.is_done() {
+                    debug!("{nfi}: Replicating ({source}, {group}) in vrf {vrfid} to {oifs:?}");
+                    self.replicate(packet, &oifs, output);
+                    return;
+                }
+            }
+        }
+        output.push_back(packet);
+    }
+}
+
+impl<Buf: PacketBufferMut> NetworkFunction<Buf> for McastForwarder<Buf> {
+    #[tracing::instrument(level = "trace", skip(self, input))]
+    fn process<'a, Input: Iterator<Item = Packet<Buf>> + 'a>(
+        &'a mut self,
+        input: Input,
+    ) -> impl Iterator<Item = Packet<Buf>> + 'a {
+        trace!("{}'", self.name);
+        let now = Instant::now();
+        let mut input = input;
+        let mut output: VecDeque<Packet<Buf>> = VecDeque::new();
+        std::iter::from_fn(move || {
+            loop {
+                if let Some(packet) = output.pop_front() {
+                    return Some(packet);
+                }
+                let packet = input.next()?;
+                if packet.is_done() {
+                    return Some(packet);
+                }
+                self.process_packet(packet, now, &mut output);
+            }
+        })
+        .filter_map(Packet::enforce)
+    }
+}
@@ -1,33 +1,54 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright Open Network Fabric Authors
 
+mod arpnd;
+mod bfd;
 mod egress;
+mod factory;
 mod ingress;
 mod ipforward;
+mod lldp;
+mod mcast;
+mod mpls;
+mod natcli;
+mod pipelinecli;
+mod srv6;
+mod urpf;
 
-#[allow(unused)]
-use super::packet_processor::egress::Egress;
-use super::packet_processor::ingress::Ingress;
-use super::packet_processor::ipforward::IpForwarder;
+use super::packet_processor::bfd::start_bfd_tx;
+use super::packet_processor::factory::StageFactory;
+pub(crate) use super::packet_processor::lldp::FrameFactory;
+use super::packet_processor::lldp::start_lldp_tx;
+use super::packet_processor::natcli::register_nat_cli_handlers;
+use super::packet_processor::pipelinecli::{
+    SharedLayout, register_pipeline_cli_handlers, register_pipeline_trace_cli_handlers,
+};
 
 use concurrency::sync::Arc;
+use config::internal::device::pipeline::{PipelineConfig, StageType};
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
 
-use pkt_meta::dst_vpcd_lookup::{DstVpcdLookup, VpcDiscTablesWriter};
-use pkt_meta::flow_table::{ExpirationsNF, FlowTable, LookupNF};
+use pkt_meta::dst_vpcd_lookup::VpcDiscTablesWriter;
+use pkt_meta::flow_table::FlowTable;
 
+use nat::NatVpcCounters;
 use nat::stateful::NatAllocatorWriter;
 use nat::stateless::NatTablesWriter;
-use nat::{StatefulNat, StatelessNat};
 
 use net::buffer::PacketBufferMut;
-use pipeline::DynPipeline;
-use pipeline::sample_nfs::PacketDumper;
+use net::packet::Packet;
+use pipeline::{
+    DynPipeline, PacketTracer, PipelineCounters, PipelineLatencies, PuntReceiver, RateLimit,
+    punt_channel,
+};
 
+use routing::fib::fibtable::fibtable_cache_stats;
 use routing::{Router, RouterError, RouterParams};
 
 use vpcmap::map::VpcMapWriter;
+use vpcmap::usage::VpcUsage;
 
-use stats::{Stats, StatsCollector, VpcMapName, VpcStatsStore};
+use stats::{StatsCollector, VpcMapName, VpcNatStats, VpcStatsStore};
 
 pub(crate) struct InternalSetup<Buf>
 where
@@ -41,15 +62,38 @@ where
     pub vpcdtablesw: VpcDiscTablesWriter,
     pub stats: StatsCollector,
     pub vpc_stats_store: Arc<VpcStatsStore>,
+    pub punt: PuntReceiver<Buf>,
+    pub originated: Receiver<Packet<Buf>>,
+    pub flow_table: Arc<FlowTable>,
 }
 
-/// Start a router and provide the associated pipeline
+/// Sender of the frames originated outside of the pipeline (e.g. LLDP frames), for the driver to
+/// transmit them as they are
+pub(crate) type OriginatedSender<Buf> = SyncSender<Packet<Buf>>;
+
+/// Number of punted packets waiting for the control plane, over which packets are dropped
+const PUNT_QUEUE_SIZE: usize = 1024;
+/// Rate limit of the punt path, in packets per second and burst size
+const PUNT_RATE_LIMIT: RateLimit = RateLimit {
+    rate: 1000,
+    burst: 100,
+};
+/// Number of originated frames waiting for the driver, over which frames are dropped
+const ORIGINATED_QUEUE_SIZE: usize = 1024;
+
+/// Start a router and provide the associated pipeline, with the stages of `pipeline_config`.
+/// The `frame_factory` is used to build the packets originated by the pipeline (e.g. LLDP
+/// frames).
 pub(crate) fn start_router<Buf: PacketBufferMut>(
     params: RouterParams,
+    pipeline_config: PipelineConfig,
+    frame_factory: FrameFactory<Buf>,
 ) -> Result<InternalSetup<Buf>, RouterError> {
     let nattablew = NatTablesWriter::new();
     let natallocatorw = NatAllocatorWriter::new();
     let vpcdtablesw = VpcDiscTablesWriter::new();
+    let stage_latencies = params.stage_latencies;
+    let flow_table_capacity = params.flow_table_capacity;
     let router = Router::new(params)?;
     let vpcmapw = VpcMapWriter::<VpcMapName>::new();
 
@@ -58,48 +102,159 @@ pub(crate) fn start_router<Buf: PacketBufferMut>(
 
     // Build stats collector + writer, wiring the same store instance in
     // Also returns stats store handle for gRPC server access
-    let (stats, writer, vpc_stats_store) =
+    let (mut stats, writer, vpc_stats_store) =
         StatsCollector::new_with_store(vpcmapw.get_reader(), vpc_stats_store.clone());
+    stats.add_cache_metrics("fibtable", fibtable_cache_stats);
+    let natallocatorr = natallocatorw.get_reader();
+    stats.add_counters(
+        "stateful-nat",
+        Box::new(move || {
+            let nat_stats = natallocatorr.stats();
+            vec![
+                ("nat_ip_exhausted_count", nat_stats.ip_exhausted),
+                ("nat_port_exhausted_count", nat_stats.port_exhausted),
+                ("nat_session_eviction_count", nat_stats.sessions_evicted),
+            ]
+        }),
+    );
+
+    // Per-VPC counters, shared by the NAT stages of all workers
+    let nat_counters = Arc::new(NatVpcCounters::new());
+    let nat_counters_stats = nat_counters.clone();
+    let natallocatorr = natallocatorw.get_reader();
+    stats.add_nat_metrics(Box::new(move || {
+        let mut port_usage = natallocatorr.port_usage();
+        let mut vpcs: Vec<_> = nat_counters_stats
+            .snapshot()
+            .into_iter()
+            .map(|(vpcd, counters)| {
+                let usage = port_usage.remove(&vpcd).unwrap_or_default();
+                let stats = VpcNatStats {
+                    translated_packets: counters.translated_packets,
+                    active_sessions: counters.active_sessions,
+                    allocation_failures: counters.allocation_failures,
+                    ports_in_use: usage.in_use,
+                    ports_total: usage.total,
+                };
+                (vpcd, stats)
+            })
+            .collect();
+        // VPCs with source NAT pools that did not translate any packet yet
+        vpcs.extend(port_usage.into_iter().map(|(vpcd, usage)| {
+            let stats = VpcNatStats {
+                ports_in_use: usage.in_use,
+                ports_total: usage.total,
+                ..VpcNatStats::default()
+            };
+            (vpcd, stats)
+        }));
+        vpcs
+    }));
+
+    // Resolutions of the VNIs of the packets received, shared by the workers
+    let vni_usage = Arc::new(VpcUsage::new());
+    stats.add_usage_metrics(vni_usage.clone());
+
+    // Per-stage counters, shared by the pipelines of all workers
+    let pipeline_counters = Arc::new(PipelineCounters::new());
+    let pipeline_layout = SharedLayout::default();
+    stats.add_pipeline_metrics(pipeline_counters.clone());
+    register_pipeline_cli_handlers(&router, pipeline_counters.clone(), &pipeline_layout);
+
+    // Punt path, shared by the pipelines of all workers
+    let (punt_sender, punt_receiver) = punt_channel(PUNT_QUEUE_SIZE, PUNT_RATE_LIMIT);
+    let punt_stats = punt_sender.clone();
+    stats.add_counters(
+        "punt",
+        Box::new(move || {
+            let stats = punt_stats.stats();
+            vec![
+                ("punt_packet_count", stats.punted),
+                ("punt_rate_limited_count", stats.rate_limited),
+                ("punt_queue_full_count", stats.queue_full),
+            ]
+        }),
+    );
+
+    // Packet tracer, shared by the pipelines of all workers, started from the cli
+    let pipeline_tracer = Arc::new(PacketTracer::new());
+    register_pipeline_trace_cli_handlers(&router, &pipeline_tracer);
+
+    // Per-stage latencies, if enabled, shared by the pipelines of all workers
+    let pipeline_latencies = stage_latencies.then(|| Arc::new(PipelineLatencies::new()));
+    if let Some(latencies) = &pipeline_latencies {
+        stats.add_latency_metrics(latencies.clone());
+    }
+
+    // Frames originated by the control functions, out of the pipeline
+    let (originated_sender, originated_receiver) = sync_channel(ORIGINATED_QUEUE_SIZE);
+    if pipeline_config
+        .stages
+        .iter()
+        .any(|stage| stage.stage == StageType::Lldp)
+    {
+        start_lldp_tx(
+            router.get_iftabler_factory(),
+            frame_factory.clone(),
+            originated_sender.clone(),
+        );
+    }
+    if pipeline_config
+        .stages
+        .iter()
+        .any(|stage| stage.stage == StageType::Bfd)
+    {
+        start_bfd_tx(
+            router.get_iftabler_factory(),
+            router.get_atabler_factory(),
+            router.get_bfd_sessions(),
+            frame_factory.clone(),
+            originated_sender,
+        );
+    }
 
     let flow_table = Arc::new(FlowTable::default());
+    flow_table.set_capacity(flow_table_capacity);
+    register_nat_cli_handlers(&router, flow_table.clone(), natallocatorw.get_reader());
 
-    let iftr_factory = router.get_iftabler_factory();
-    let fibtr_factory = router.get_fibtr_factory();
-    let vpcdtablesr_factory = vpcdtablesw.get_reader_factory();
-    let atabler_factory = router.get_atabler_factory();
-    let nattabler_factory = nattablew.get_reader_factory();
-    let natallocator_factory = natallocatorw.get_reader_factory();
+    let stage_factory = StageFactory {
+        iftr_factory: router.get_iftabler_factory(),
+        fibtr_factory: router.get_fibtr_factory(),
+        lfibr_factory: router.get_lfibr_factory(),
+        sidtabler_factory: router.get_sidtabler_factory(),
+        mroutetabler_factory: router.get_mroutetabler_factory(),
+        atabler_factory: router.get_atabler_factory(),
+        mcast_members: router.get_mcast_members(),
+        lldp_neighbors: router.get_lldp_neighbors(),
+        bfd_sessions: router.get_bfd_sessions(),
+        arpnd_agent: router.get_arpnd_agent(),
+        macip_bindings: router.get_macip_bindings(),
+        urpf_drops: router.get_urpf_drops(),
+        vpcdtablesr_factory: vpcdtablesw.get_reader_factory(),
+        nattabler_factory: nattablew.get_reader_factory(),
+        natallocator_factory: natallocatorw.get_reader_factory(),
+        nat_counters,
+        vni_usage,
+        flow_table: flow_table.clone(),
+        punt_sender,
+        stats_writer: writer,
+        frame_factory,
+    };
 
     let pipeline_builder = move || {
-        // Build network functions
-        let stage_ingress = Ingress::new("Ingress", iftr_factory.handle());
-        let stage_egress = Egress::new("Egress", iftr_factory.handle(), atabler_factory.handle());
-        let dst_vpcd_lookup = DstVpcdLookup::new("dst-vni-lookup", vpcdtablesr_factory.handle());
-        let iprouter1 = IpForwarder::new("IP-Forward-1", fibtr_factory.handle());
-        let iprouter2 = IpForwarder::new("IP-Forward-2", fibtr_factory.handle());
-        let stateless_nat = StatelessNat::with_reader("stateless-NAT", nattabler_factory.handle());
-        let stateful_nat = StatefulNat::with_reader("stateful-NAT", natallocator_factory.handle());
-        let dumper1 = PacketDumper::new("pre-ingress", true, None);
-        let dumper2 = PacketDumper::new("post-egress", true, None);
-        let stats_stage = Stats::new("stats", writer.clone());
-        let flow_lookup_nf = LookupNF::new(flow_table.clone());
-        let flow_expirations_nf = ExpirationsNF::new(flow_table.clone());
-
-        // Build the pipeline for a router. The composition of the pipeline (in stages) is currently
-        // hard-coded. In any pipeline, the Stats and ExpirationsNF stages should go last
-        DynPipeline::new()
-            .add_stage(dumper1)
-            .add_stage(stage_ingress)
-            .add_stage(iprouter1)
-            .add_stage(dst_vpcd_lookup)
-            .add_stage(flow_lookup_nf)
-            .add_stage(stateless_nat)
-            .add_stage(stateful_nat)
-            .add_stage(iprouter2)
-            .add_stage(stage_egress)
-            .add_stage(dumper2)
-            .add_stage(flow_expirations_nf)
-            .add_stage(stats_stage)
+        // Build the pipeline for a router, with the stages of the configured layout
+        let pipeline = stage_factory
+            .build_pipeline(&pipeline_config)
+            .with_counters(pipeline_counters.clone())
+            .with_tracer(pipeline_tracer.clone());
+        let pipeline = match &pipeline_latencies {
+            Some(latencies) => pipeline.with_latencies(latencies.clone()),
+            None => pipeline,
+        };
+        if let Ok(mut layout) = pipeline_layout.lock() {
+            *layout = pipeline.layout();
+        }
+        pipeline
     };
 
     Ok(InternalSetup {
@@ -111,5 +266,8 @@ pub(crate) fn start_router<Buf: PacketBufferMut>(
         vpcdtablesw,
         stats,
         vpc_stats_store,
+        punt: punt_receiver,
+        originated: originated_receiver,
+        flow_table,
     })
 }
@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Implements an MPLS forwarding stage. Labeled packets are forwarded according to the LFIB
+//! entry of their outermost label. Packets leaving their LSP (pop and lookup) get their VRF set
+//! and are routed by the next IP forwarding stage. TTLs follow the pipe model: the TTL of the IP
+//! header is left untouched when popping the last label.
+
+use net::buffer::PacketBufferMut;
+use net::mpls::MplsLabel;
+use net::packet::{DoneReason, HashFields, Packet};
+use pipeline::NetworkFunction;
+use tracing::{debug, trace, warn};
+
+use routing::lfib::lfibrw::LfibReader;
+use routing::lfib::{LabelOp, LspNhop};
+
+use tracectl::trace_target;
+trace_target!("mpls-forward", LevelFilter::WARN, &["pipeline"]);
+
+pub struct MplsForwarder {
+    name: String,
+    lfibr: LfibReader,
+}
+
+impl MplsForwarder {
+    /// Build a new MPLS forwarding stage to use the indicated [`LfibReader`]
+    pub fn new(name: &str, lfibr: LfibReader) -> Self {
+        Self {
+            name: name.to_owned(),
+            lfibr,
+        }
+    }
+
+    /// Replace the outermost label of a packet with the given labels (outermost first)
+    fn swap_labels<Buf: PacketBufferMut>(
+        &self,
+        packet: &mut Packet<Buf>,
+        labels: &[MplsLabel],
+        ttl: u8,
+    ) {
+        let Some((innermost, others)) = labels.split_last() else {
+            unreachable!()
+        };
+        if let Some(outer) = packet.outer_mpls_mut() {
+            outer.set_label(*innermost).set_ttl(ttl);
+        }
+        for label in others.iter().rev() {
+            if let Err(e) = packet.push_mpls(*label, ttl) {
+                warn!("{}: Failed to push label {label}: {e}", self.name);
+                packet.done(DoneReason::InternalFailure);
+                return;
+            }
+        }
+    }
+
+    /// Pop the outermost label of a packet
+    fn pop_label<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>) {
+        if let Err(e) = packet.pop_mpls() {
+            debug!("{}: Failed to pop label: {e}", self.name);
+            packet.done(DoneReason::NotIp);
+        }
+    }
+
+    /// Set the metadata to send the packet to the next-hop (at an egress stage)
+    fn set_egress<Buf: PacketBufferMut>(packet: &mut Packet<Buf>, nhop: &LspNhop) {
+        let meta = packet.get_meta_mut();
+        meta.oif = nhop.ifindex;
+        meta.nh_addr = nhop.address;
+    }
+
+    /// Forward a labeled [`Packet`]
+    fn forward_packet<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>) {
+        let nfi = &self.name;
+
+        /* labels are not VRF-specific: only packets leaving their LSP are routed in a VRF */
+        packet.get_meta_mut().vrf.take();
+
+        let Some(lfib) = self.lfibr.enter() else {
+            warn!("{nfi}: Unable to read from lfib");
+            packet.done(DoneReason::InternalFailure);
+            return;
+        };
+
+        /* popping a label may expose another one to look up */
+        while let Some(outer) = packet.mpls_labels().first().copied() {
+            let label = outer.label();
+            let Some(lsp) = lfib.get_lsp(label) else {
+                debug!("{nfi}: No LSP for label {label}");
+                packet.done(DoneReason::Unroutable);
+                return;
+            };
+            if outer.ttl() <= 1 {
+                debug!("{nfi}: TTL limit exceeded for label {label}");
+                packet.done(DoneReason::HopLimitExceeded);
+                return;
+            }
+            let ttl = outer.ttl() - 1;
+            let hash = if lsp.nhops.len() > 1 {
+                packet.packet_hash_fields(HashFields::FIVE_TUPLE)
+            } else {
+                0
+            };
+            let Some(nhop) = lsp.select_nhop(hash) else {
+                debug!("{nfi}: LSP for label {label} has no next-hop");
+                packet.done(DoneReason::Unroutable);
+                return;
+            };
+            debug!("{nfi}: Label {label}: {:?}", nhop.op);
+            match &nhop.op {
+                LabelOp::Swap(labels) if !labels.is_empty() => {
+                    self.swap_labels(packet, labels, ttl);
+                    Self::set_egress(packet, nhop);
+                    return;
+                }
+                LabelOp::Swap(_) | LabelOp::Pop => {
+                    self.pop_label(packet);
+                    if let Some(exposed) = packet.outer_mpls_mut() {
+                        exposed.set_ttl(ttl);
+                    }
+                    Self::set_egress(packet, nhop);
+                    return;
+                }
+                LabelOp::PopLookup(vrfid) => {
+                    self.pop_label(packet);
+                    if packet.is_done() {
+                        return;
+                    }
+                    match packet.outer_mpls_mut() {
+                        Some(exposed) => {
+                            exposed.set_ttl(ttl.min(exposed.ttl()));
+                        }
+                        None => {
+                            packet.get_meta_mut().vrf = Some(*vrfid);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<Buf: PacketBufferMut> NetworkFunction<Buf> for MplsForwarder {
+    #[tracing::instrument(level = "trace", skip(self, input))]
+    fn process<'a, Input: Iterator<Item = Packet<Buf>> + 'a>(
+        &'a mut self,
+        input: Input,
+    ) -> impl Iterator<Item = Packet<Buf>> + 'a {
+        trace!("{}'", self.name);
+        input.filter_map(move |mut packet| {
+            if !packet.is_done() && !packet.mpls_labels().is_empty() {
+                self.forward_packet(&mut packet);
+            }
+            packet.enforce()
+        })
+    }
+}
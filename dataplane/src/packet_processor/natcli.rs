@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Cli handlers for the state of the NAT stages
+
+use cli::cliproto::{CliAction, CliError, RequestArgs, TransportProtocol};
+use concurrency::sync::Arc;
+use lpm::prefix::Prefix;
+use nat::stateful::{NatAllocatorReader, NatSessionQuery, query_sessions};
+use net::packet::VpcDiscriminant;
+use net::vxlan::Vni;
+use pkt_meta::flow_table::{FlowFilter, FlowProto, FlowTable};
+use routing::Router;
+use std::fmt::Write;
+
+fn vpcd_arg(args: &RequestArgs) -> Result<Option<VpcDiscriminant>, CliError> {
+    let Some(vni) = args.vni else {
+        return Ok(None);
+    };
+    let Ok(checked_vni) = Vni::try_from(vni) else {
+        return Err(CliError::NotFound(format!("Invalid vni value: {vni}")));
+    };
+    Ok(Some(VpcDiscriminant::VNI(checked_vni)))
+}
+
+fn prefix_arg(args: &RequestArgs) -> Result<Option<Prefix>, CliError> {
+    let Some((addr, len)) = args.prefix else {
+        return Ok(None);
+    };
+    let Ok(prefix) = Prefix::try_from((addr, len)) else {
+        return Err(CliError::NotFound(format!("Invalid prefix: {addr}/{len}")));
+    };
+    Ok(Some(prefix))
+}
+
+fn show_nat_sessions(sessions: &FlowTable, args: &RequestArgs) -> Result<String, CliError> {
+    let mut filter = FlowFilter::new();
+    if let Some(vpcd) = vpcd_arg(args)? {
+        filter = filter.with_vpcd(vpcd);
+    }
+    if let Some(prefix) = prefix_arg(args)? {
+        filter = filter.with_prefix(prefix);
+    }
+    if let Some(transport) = args.transport {
+        filter = filter.with_proto(match transport {
+            TransportProtocol::Tcp => FlowProto::Tcp,
+            TransportProtocol::Udp => FlowProto::Udp,
+            TransportProtocol::Icmp => FlowProto::Icmp,
+        });
+    }
+
+    let mut query = NatSessionQuery::new(filter);
+    if let Some(offset) = args.offset {
+        query = query.with_offset(offset as usize);
+    }
+    if let Some(count) = args.count {
+        query = query.with_limit(count as usize);
+    }
+    Ok(query_sessions(sessions, &query).to_string())
+}
+
+fn show_nat_port_blocks(
+    allocator: &NatAllocatorReader,
+    args: &RequestArgs,
+) -> Result<String, CliError> {
+    let vpcd = vpcd_arg(args)?;
+    let prefix = prefix_arg(args)?;
+    let Some(allocator) = allocator.get() else {
+        return Ok("No stateful NAT configuration".to_string());
+    };
+
+    let mut out = String::new();
+    allocator
+        .port_blocks()
+        .filter(|entry| {
+            vpcd.is_none_or(|vpcd| entry.src_vpcd == vpcd)
+                && prefix.is_none_or(|prefix| prefix.covers_addr(&entry.addr))
+        })
+        .for_each(|entry| {
+            let _ = writeln!(out, "{entry}");
+        });
+    if out.is_empty() {
+        out.push_str("No port blocks");
+    }
+    Ok(out)
+}
+
+/// Register the handlers for the cli requests about the NAT stages
+pub(crate) fn register_nat_cli_handlers(
+    router: &Router,
+    sessions: Arc<FlowTable>,
+    allocator: NatAllocatorReader,
+) {
+    router.register_cli_handler(
+        CliAction::ShowNatSessions,
+        Box::new(move |args| show_nat_sessions(&sessions, args)),
+    );
+    router.register_cli_handler(
+        CliAction::ShowNatPortBlocks,
+        Box::new(move |args| show_nat_port_blocks(&allocator, args)),
+    );
+}
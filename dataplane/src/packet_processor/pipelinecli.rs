@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Cli handlers for the state of the pipelines
+
+use cli::cliproto::{CliAction, CliError, RequestArgs, TransportProtocol};
+use concurrency::sync::{Arc, Mutex};
+use net::buffer::PacketBufferMut;
+use net::headers::{TryIcmp4, TryIcmp6, TryTcp, TryUdp};
+use net::packet::Packet;
+use pipeline::{PacketTracer, PipelineCounters, PipelineLayout, TraceFilter};
+use routing::Router;
+use std::fmt::Write;
+
+/// Number of packets traced by default
+const DEFAULT_TRACE_COUNT: u32 = 10;
+
+/// The layout of the pipelines of the workers, which are all built the same way
+pub(crate) type SharedLayout = Arc<Mutex<PipelineLayout>>;
+
+fn show_pipeline(layout: &SharedLayout, stages: bool) -> Result<String, CliError> {
+    let layout = layout.lock().map_err(|_| CliError::InternalError)?;
+    if layout.is_empty() {
+        return Ok("No pipeline".to_string());
+    }
+    if stages {
+        return Ok(layout.to_string());
+    }
+    let names: Vec<_> = layout.stages().map(|stage| stage.name.as_str()).collect();
+    Ok(format!("{} stages: {}", layout.len(), names.join(" -> ")))
+}
+
+fn show_pipeline_stats(
+    counters: &PipelineCounters,
+    args: &RequestArgs,
+) -> Result<String, CliError> {
+    let Some(stage) = &args.stage else {
+        return Ok(counters.to_string());
+    };
+    let Some(stats) = counters.get(stage) else {
+        return Err(CliError::NotFound(format!("No such stage: {stage}")));
+    };
+    Ok(format!(
+        "stage {stage}: {} packets in, {} packets out, {} drops",
+        stats.packets_in, stats.packets_out, stats.drops
+    ))
+}
+
+fn trace_filter<Buf: PacketBufferMut>(args: &RequestArgs) -> Option<TraceFilter<Buf>> {
+    if args.address.is_none() && args.transport.is_none() {
+        return None;
+    }
+    let address = args.address;
+    let transport = args.transport;
+    Some(Box::new(move |packet: &Packet<Buf>| {
+        let address_match = address.is_none_or(|address| {
+            packet.ip_source() == Some(address) || packet.ip_destination() == Some(address)
+        });
+        let transport_match = transport.is_none_or(|transport| match transport {
+            TransportProtocol::Tcp => packet.try_tcp().is_some(),
+            TransportProtocol::Udp => packet.try_udp().is_some(),
+            TransportProtocol::Icmp => packet.try_icmp4().is_some() || packet.try_icmp6().is_some(),
+        });
+        address_match && transport_match
+    }))
+}
+
+fn set_pipeline_trace<Buf: PacketBufferMut>(
+    tracer: &PacketTracer<Buf>,
+    args: &RequestArgs,
+) -> Result<String, CliError> {
+    let count = args.count.unwrap_or(DEFAULT_TRACE_COUNT);
+    if count == 0 {
+        tracer.stop();
+        return Ok("Packet tracing stopped".to_string());
+    }
+    tracer.clear();
+    tracer.start(u64::from(count), trace_filter(args));
+    Ok(format!("Tracing the next {count} packets"))
+}
+
+fn show_pipeline_traces<Buf: PacketBufferMut>(tracer: &PacketTracer<Buf>) -> String {
+    let traces = tracer.traces();
+    if traces.is_empty() {
+        return "No packet traces".to_string();
+    }
+    let mut out = String::new();
+    for trace in traces {
+        let _ = writeln!(out, "{trace}");
+    }
+    out
+}
+
+/// Register the handlers for the cli requests about the packet traces of the pipelines
+pub(crate) fn register_pipeline_trace_cli_handlers<Buf: PacketBufferMut>(
+    router: &Router,
+    tracer: &Arc<PacketTracer<Buf>>,
+) {
+    let pipeline_tracer = tracer.clone();
+    router.register_cli_handler(
+        CliAction::SetPipelineTrace,
+        Box::new(move |args| set_pipeline_trace(&pipeline_tracer, args)),
+    );
+    let pipeline_tracer = tracer.clone();
+    router.register_cli_handler(
+        CliAction::ShowPipelineTraces,
+        Box::new(move |_| Ok(show_pipeline_traces(&pipeline_tracer))),
+    );
+}
+
+/// Register the handlers for the cli requests about the pipelines
+pub(crate) fn register_pipeline_cli_handlers(
+    router: &Router,
+    counters: Arc<PipelineCounters>,
+    layout: &SharedLayout,
+) {
+    let pipeline_layout = layout.clone();
+    router.register_cli_handler(
+        CliAction::ShowPipeline,
+        Box::new(move |_| show_pipeline(&pipeline_layout, false)),
+    );
+    let pipeline_layout = layout.clone();
+    router.register_cli_handler(
+        CliAction::ShowPipelineStages,
+        Box::new(move |_| show_pipeline(&pipeline_layout, true)),
+    );
+    router.register_cli_handler(
+        CliAction::ShowPipelineStats,
+        Box::new(move |args| show_pipeline_stats(&counters, args)),
+    );
+}
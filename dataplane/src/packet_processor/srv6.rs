@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Implements an SRv6 stage. IPv6 packets addressed to a local SID are processed according to
+//! the behavior of the SID: with End, the packet moves to its next segment and is routed to it by
+//! the next IP forwarding stage; with End.DT4 and End.DT6, the inner packet is decapsulated and
+//! routed in the VRF of the SID.
+
+use net::buffer::PacketBufferMut;
+use net::headers::{TryIpv4, TryIpv6};
+use net::ipv6::SrhError;
+use net::packet::{DoneReason, Packet};
+use pipeline::NetworkFunction;
+use tracing::{debug, trace, warn};
+
+use routing::rib::vrf::VrfId;
+use routing::srv6::SidBehavior;
+use routing::srv6::sidtablerw::SidTableReader;
+
+use tracectl::trace_target;
+trace_target!("srv6", LevelFilter::WARN, &["pipeline"]);
+
+pub struct Srv6 {
+    name: String,
+    sidtabler: SidTableReader,
+}
+
+impl Srv6 {
+    /// Build a new SRv6 stage to use the indicated [`SidTableReader`]
+    pub fn new(name: &str, sidtabler: SidTableReader) -> Self {
+        Self {
+            name: name.to_owned(),
+            sidtabler,
+        }
+    }
+
+    /// Apply the End behavior: move to the next segment of the segment list
+    fn end<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>) {
+        let nfi = &self.name;
+        match packet.srv6_advance() {
+            Ok(segment) => debug!("{nfi}: End: next segment is {segment}"),
+            Err(SrhError::Missing | SrhError::NoSegmentsLeft) => {
+                debug!("{nfi}: End: no segment left to visit");
+                packet.done(DoneReason::Unhandled);
+            }
+            Err(e) => {
+                debug!("{nfi}: End: invalid segment routing header: {e}");
+                packet.done(DoneReason::Malformed);
+            }
+        }
+    }
+
+    /// Apply the End.DT4 (`ipv4`) or End.DT6 behavior: decapsulate the inner packet, which must
+    /// be at the end of its segment list, and set the VRF to route it in.
+    fn end_dt<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>, vrfid: VrfId, ipv4: bool) {
+        let nfi = &self.name;
+        match packet.srh() {
+            Ok(srh) if srh.segments_left() > 0 => {
+                debug!("{nfi}: End.DT: {} segment(s) left", srh.segments_left());
+                packet.done(DoneReason::Malformed);
+                return;
+            }
+            Ok(_) | Err(SrhError::Missing) => {}
+            Err(e) => {
+                debug!("{nfi}: End.DT: invalid segment routing header: {e}");
+                packet.done(DoneReason::Malformed);
+                return;
+            }
+        }
+        match packet.ipv6_decap() {
+            Some(Ok(_)) => {}
+            None => {
+                debug!("{nfi}: End.DT: payload is not an IP packet");
+                packet.done(DoneReason::Unhandled);
+                return;
+            }
+            Some(Err(e)) => {
+                debug!("{nfi}: End.DT: failed to parse inner packet: {e:?}");
+                packet.done(DoneReason::Malformed);
+                return;
+            }
+        }
+        let family_ok = if ipv4 {
+            packet.try_ipv4().is_some()
+        } else {
+            packet.try_ipv6().is_some()
+        };
+        if !family_ok {
+            debug!("{nfi}: End.DT: inner packet has the wrong address family");
+            packet.done(DoneReason::Unhandled);
+            return;
+        }
+        debug!("{nfi}: End.DT: decapsulated packet to route in vrf {vrfid}");
+        packet.get_meta_mut().vrf = Some(vrfid);
+    }
+
+    /// Process a [`Packet`] addressed to a local SID, if any
+    fn process_packet<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>) {
+        let nfi = &self.name;
+        let Some(destination) = packet.try_ipv6().map(|ipv6| ipv6.destination()) else {
+            return;
+        };
+        let Some(sidtable) = self.sidtabler.enter() else {
+            warn!("{nfi}: Unable to read from local SID table");
+            packet.done(DoneReason::InternalFailure);
+            return;
+        };
+        let Some(behavior) = sidtable.get_sid(&destination).map(|local| local.behavior) else {
+            return;
+        };
+        drop(sidtable);
+        debug!("{nfi}: Packet to local SID {destination} ({behavior:?})");
+        match behavior {
+            SidBehavior::End => self.end(packet),
+            SidBehavior::EndDt4(vrfid) => self.end_dt(packet, vrfid, true),
+            SidBehavior::EndDt6(vrfid) => self.end_dt(packet, vrfid, false),
+        }
+    }
+}
+
+impl<Buf: PacketBufferMut> NetworkFunction<Buf> for Srv6 {
+    #[tracing::instrument(level = "trace", skip(self, input))]
+    fn process<'a, Input: Iterator<Item = Packet<Buf>> + 'a>(
+        &'a mut self,
+        input: Input,
+    ) -> impl Iterator<Item = Packet<Buf>> + 'a {
+        trace!("{}'", self.name);
+        input.filter_map(move |mut packet| {
+            if !packet.is_done() {
+                self.process_packet(&mut packet);
+            }
+            packet.enforce()
+        })
+    }
+}
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+//
+//! Implements a unicast reverse path forwarding (uRPF) stage. The source of the IP packets
+//! received on interfaces with a uRPF mode is checked against the fib of their VRF, and the
+//! packets failing the check are dropped and counted per interface.
+
+use net::buffer::PacketBufferMut;
+use net::interface::InterfaceIndex;
+use net::packet::{DoneReason, Packet};
+use pipeline::NetworkFunction;
+use std::net::IpAddr;
+use tracing::{debug, trace, warn};
+
+use routing::fib::fibtable::FibTableReader;
+use routing::fib::fibtype::FibKey;
+use routing::fib::urpf::{UrpfDrops, UrpfMode};
+use routing::interfaces::iftablerw::IfTableReader;
+use routing::rib::vrf::VrfId;
+
+use tracectl::trace_target;
+trace_target!("urpf", LevelFilter::WARN, &["pipeline"]);
+
+pub struct Urpf {
+    name: String,
+    iftr: IfTableReader,
+    fibtr: FibTableReader,
+    drops: UrpfDrops,
+}
+
+impl Urpf {
+    /// Build a new uRPF stage to use the indicated [`IfTableReader`] and [`FibTableReader`].
+    /// Dropped packets are counted in `drops`.
+    pub fn new(name: &str, iftr: IfTableReader, fibtr: FibTableReader, drops: UrpfDrops) -> Self {
+        Self {
+            name: name.to_owned(),
+            iftr,
+            fibtr,
+            drops,
+        }
+    }
+
+    /// Get the uRPF mode of interface `iif`, if any
+    fn mode(&self, iif: InterfaceIndex) -> Option<UrpfMode> {
+        let iftable = self.iftr.enter()?;
+        iftable.get_interface(iif)?.urpf
+    }
+
+    /// Check a packet from `source`, received on interface `iif` in VRF `vrfid`
+    fn check(&self, vrfid: VrfId, source: IpAddr, iif: InterfaceIndex, mode: UrpfMode) -> bool {
+        let nfi = &self.name;
+        let fibkey = FibKey::from_vrfid(vrfid);
+        let Ok(fibr) = self.fibtr.get_fib_reader(fibkey) else {
+            warn!("{nfi}: Unable to read fib. Key={fibkey}");
+            return true;
+        };
+        let Some(fib) = fibr.enter() else {
+            warn!("{nfi}: Unable to read from fib. Key={fibkey}");
+            return true;
+        };
+        fib.urpf_check(source, iif, mode)
+    }
+
+    fn process_packet<Buf: PacketBufferMut>(&self, packet: &mut Packet<Buf>) {
+        let meta = packet.get_meta();
+        let (Some(iif), Some(vrfid)) = (meta.iif, meta.vrf) else {
+            return;
+        };
+        let Some(mode) = self.mode(iif) else {
+            return;
+        };
+        let Some(source) = packet.ip_source() else {
+            return;
+        };
+        if !self.check(vrfid, source, iif, mode) {
+            debug!(
+                "{}: {mode} uRPF check failed for {source} on ifindex {iif}",
+                &self.name
+            );
+            self.drops.add(iif, 1);
+            packet.done(DoneReason::Filtered);
+        }
+    }
+}
+
+impl<Buf: PacketBufferMut> NetworkFunction<Buf> for Urpf {
+    fn process<'a, Input: Iterator<Item = Packet<Buf>> + 'a>(
+        &'a mut self,
+        input: Input,
+    ) -> impl Iterator<Item = Packet<Buf>> + 'a {
+        trace!("Stage '{}'...", self.name);
+        input.filter_map(move |mut packet| {
+            if !packet.is_done() {
+                self.process_packet(&mut packet);
+            }
+            packet.enforce()
+        })
+    }
+}
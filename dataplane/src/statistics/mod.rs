@@ -3,7 +3,7 @@
 
 use axum::{Router, response::Response, routing::get};
 use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
-use stats::StatsCollector;
+use stats::{PIPELINE_STAGE_LATENCY, StatsCollector};
 use std::thread::JoinHandle;
 use std::time::Duration;
 use tracing::{error, info};
@@ -26,6 +26,13 @@ impl PrometheusHandler {
                 ],
             )
             .unwrap()
+            .set_buckets_for_metric(
+                Matcher::Full(PIPELINE_STAGE_LATENCY.to_string()),
+                &[
+                    100e-9, 250e-9, 500e-9, 1e-6, 2.5e-6, 5e-6, 10e-6, 25e-6, 50e-6, 100e-6, 1e-3,
+                ],
+            )
+            .unwrap()
             .install_recorder()
             .unwrap();
 
@@ -3,12 +3,14 @@
 
 //! Ethernet device management.
 
+use alloc::ffi::CString;
 use alloc::format;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::ffi::{CStr, c_uint};
 use core::fmt::{Debug, Display, Formatter};
 use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::eal::Eal;
 use crate::queue;
@@ -188,6 +190,90 @@ impl DevIndex {
 
         Ok(SocketId(socket_id as c_uint))
     }
+
+    /// Get the basic statistics of the port.
+    ///
+    /// Safe wrapper around [`rte_eth_stats_get`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`StatsError`] if the statistics could not be retrieved.
+    #[tracing::instrument(level = "trace", ret)]
+    pub fn stats(&self) -> Result<DevStats, StatsError> {
+        let mut stats = rte_eth_stats::default();
+        let ret = unsafe { rte_eth_stats_get(self.0, &mut stats) };
+        self.check_stats(ret)?;
+        Ok(DevStats::from(&stats))
+    }
+
+    /// Get the extended statistics (xstats) of the port, with their names.
+    ///
+    /// The set of extended statistics depends on the driver of the device.
+    ///
+    /// Safe wrapper around [`rte_eth_xstats_get_names`] and [`rte_eth_xstats_get`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`StatsError`] if the statistics could not be retrieved.
+    #[tracing::instrument(level = "trace")]
+    pub fn xstats(&self) -> Result<Vec<XStat>, StatsError> {
+        // with no array to fill, DPDK returns the number of xstats
+        let ret = unsafe { rte_eth_xstats_get_names(self.0, core::ptr::null_mut(), 0) };
+        let count = self.check_stats(ret)?;
+        let mut names = Vec::new();
+        names.resize_with(count as usize, rte_eth_xstat_name::default);
+        let ret = unsafe { rte_eth_xstats_get_names(self.0, names.as_mut_ptr(), count) };
+        let num_names = self.check_stats(ret)?;
+        let mut values = Vec::new();
+        values.resize_with(count as usize, rte_eth_xstat::default);
+        let ret = unsafe { rte_eth_xstats_get(self.0, values.as_mut_ptr(), count) };
+        let num_values = self.check_stats(ret)?;
+        if num_names > count || num_values > count {
+            // the port was reconfigured in between calls: the arrays are too small
+            return Err(StatsError::Changed(*self));
+        }
+        names.truncate(num_names as usize);
+        values.truncate(num_values as usize);
+        Ok(values
+            .iter()
+            .filter_map(|xstat| {
+                let name = names.get(usize::try_from(xstat.id).ok()?)?;
+                Some(XStat {
+                    name: xstat_name(name),
+                    value: xstat.value,
+                })
+            })
+            .collect())
+    }
+
+    /// Reset the basic and extended statistics of the port.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`StatsError`] if the statistics could not be reset.
+    #[tracing::instrument(level = "info")]
+    pub fn reset_stats(&self) -> Result<(), StatsError> {
+        let ret = unsafe { rte_eth_stats_reset(self.0) };
+        self.check_stats(ret)?;
+        let ret = unsafe { rte_eth_xstats_reset(self.0) };
+        self.check_stats(ret)?;
+        Ok(())
+    }
+
+    /// Map the return value of the DPDK statistics functions to a count, or to a [`StatsError`].
+    fn check_stats(self, ret: i32) -> Result<u32, StatsError> {
+        if let Ok(count) = u32::try_from(ret) {
+            return Ok(count);
+        }
+        match ret {
+            errno::NEG_ENOTSUP => Err(StatsError::NotSupported(self)),
+            errno::NEG_ENODEV => Err(StatsError::NoDevice(self)),
+            ret => Err(StatsError::Unexpected {
+                port: self,
+                code: ErrorCode::parse_i32(ret),
+            }),
+        }
+    }
 }
 
 impl From<DevIndex> for u16 {
@@ -196,6 +282,75 @@ impl From<DevIndex> for u16 {
     }
 }
 
+/// The basic statistics of an ethernet port, as counted by the device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DevStats {
+    /// Number of packets received
+    pub rx_packets: u64,
+    /// Number of packets transmitted
+    pub tx_packets: u64,
+    /// Number of bytes received
+    pub rx_bytes: u64,
+    /// Number of bytes transmitted
+    pub tx_bytes: u64,
+    /// Number of packets dropped by the device, because the receive queues were full
+    pub rx_missed: u64,
+    /// Number of erroneous packets received
+    pub rx_errors: u64,
+    /// Number of packets which failed to be transmitted
+    pub tx_errors: u64,
+    /// Number of packets dropped for lack of mbufs to receive them
+    pub rx_nombuf: u64,
+}
+
+impl From<&rte_eth_stats> for DevStats {
+    fn from(stats: &rte_eth_stats) -> Self {
+        DevStats {
+            rx_packets: stats.ipackets,
+            tx_packets: stats.opackets,
+            rx_bytes: stats.ibytes,
+            tx_bytes: stats.obytes,
+            rx_missed: stats.imissed,
+            rx_errors: stats.ierrors,
+            tx_errors: stats.oerrors,
+            rx_nombuf: stats.rx_nombuf,
+        }
+    }
+}
+
+/// An extended statistic of an ethernet port (see [`DevIndex::xstats`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XStat {
+    /// The name of the statistic, as reported by the driver (e.g. `rx_good_packets`)
+    pub name: String,
+    /// The value of the statistic
+    pub value: u64,
+}
+
+/// Get the name of an extended statistic, which DPDK stores as a nul-terminated C string.
+fn xstat_name(name: &rte_eth_xstat_name) -> String {
+    let bytes: Vec<u8> = name
+        .name
+        .iter()
+        .map(|&c| c as u8)
+        .take_while(|&c| c != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Errors that can occur when retrieving the statistics of an ethernet port.
+#[derive(Debug, thiserror::Error)]
+pub enum StatsError {
+    #[error("Statistics not supported for port {0}")]
+    NotSupported(DevIndex),
+    #[error("No such port {0}")]
+    NoDevice(DevIndex),
+    #[error("The statistics of port {0} changed while being retrieved")]
+    Changed(DevIndex),
+    #[error("Unexpected error getting the statistics of port {port}: {code}")]
+    Unexpected { port: DevIndex, code: ErrorCode },
+}
+
 #[derive(Debug, PartialEq, Copy, Clone, Eq, PartialOrd, Ord, Hash)]
 /// TODO: add `rx_offloads` support
 pub struct DevConfig {
@@ -207,6 +362,13 @@ pub struct DevConfig {
     pub num_tx_queues: u16,
     /// The number of hairpin queues to be made available after device initialization.
     pub num_hairpin_queues: u16,
+    /// The MTU of the device. This is capped to the maximum MTU of the device.
+    ///
+    /// If `None`, the MTU is that of standard ethernet frames.
+    /// Frames larger than the mbufs of the receive queues are received in chained mbufs, which
+    /// requires the scatter receive offload (see [`RxOffload::SCATTER`]): without it, the MTU
+    /// is not raised above that of standard ethernet frames.
+    pub mtu: Option<u32>,
     /// The transmit offloads to be requested on the device.
     ///
     /// If `None`, the device will use all supported Offloads.
@@ -232,6 +394,25 @@ impl DevConfig {
     /// Apply the configuration to the device.
     pub fn apply(&self, dev: DevInfo) -> Result<Dev, DevConfigError> {
         const ANY_SUPPORTED: u64 = u64::MAX;
+        let rx_offloads = {
+            let requested = self.rx_offloads.unwrap_or(RxOffload(ANY_SUPPORTED));
+            let supported = dev.rx_offload_caps();
+            requested.0 & supported.0
+        };
+        let mtu = match self.mtu {
+            None => rx_queue_defaults::RX_MTU,
+            Some(mtu) if rx_offloads & RxOffload::SCATTER.0 != 0 => mtu.min(dev.max_mtu().into()),
+            Some(mtu) => {
+                let capped = mtu.min(rx_queue_defaults::RX_MTU);
+                if capped < mtu {
+                    warn!(
+                        "Port {port} does not support scatter receive, MTU {mtu} lowered to {capped}",
+                        port = dev.index(),
+                    );
+                }
+                capped.min(dev.max_mtu().into())
+            }
+        };
         let eth_conf = rte_eth_conf {
             txmode: rte_eth_txmode {
                 mq_mode: RTE_ETH_MQ_TX_NONE,
@@ -245,14 +426,10 @@ impl DevConfig {
                 ..Default::default()
             },
             rxmode: rte_eth_rxmode {
-                mtu: rx_queue_defaults::RX_MTU,
+                mtu,
                 mq_mode: RTE_ETH_MQ_RX_RSS,
                 max_lro_pkt_size: rx_queue_defaults::MAX_LRO,
-                offloads: {
-                    let requested = self.rx_offloads.unwrap_or(RxOffload(ANY_SUPPORTED));
-                    let supported = dev.rx_offload_caps();
-                    requested.0 & supported.0
-                },
+                offloads: rx_offloads,
                 ..Default::default()
             },
             ..Default::default()
@@ -324,6 +501,21 @@ impl From<u64> for RxOffload {
     }
 }
 
+impl RxOffload {
+    /// Reception of packets larger than the mbufs of the queue, in chained mbufs.
+    pub const SCATTER: RxOffload = RxOffload(rte_eth_rx_offload::RX_OFFLOAD_SCATTER);
+    /// TCP large receive offload, aggregating TCP segments in chained mbufs.
+    pub const TCP_LRO: RxOffload = RxOffload(rte_eth_rx_offload::RX_OFFLOAD_TCP_LRO);
+}
+
+impl BitOr for RxOffload {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> RxOffload {
+        RxOffload(self.0 | rhs.0)
+    }
+}
+
 #[non_exhaustive]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// Verbose configuration for transmit offloads.
@@ -360,6 +552,8 @@ pub struct TxOffloadConfig {
     pub vlan_insert: bool,
     /// VXLAN tunnel segmentation offload.
     pub vxlan_tnl_tso: bool,
+    /// Transmission of packets in chained mbufs.
+    pub multi_segs: bool,
     /// Any flags that are not known to map to a valid offload.
     pub unknown: u64,
 }
@@ -382,6 +576,7 @@ impl Default for TxOffloadConfig {
             udp_tso: true,
             vlan_insert: true,
             vxlan_tnl_tso: true,
+            multi_segs: true,
             unknown: 0,
         }
     }
@@ -450,6 +645,11 @@ impl From<TxOffloadConfig> for TxOffload {
                 } else {
                     0
                 }
+                | if value.multi_segs {
+                    TX_OFFLOAD_MULTI_SEGS
+                } else {
+                    0
+                }
                 | value.unknown,
         )
     }
@@ -473,6 +673,7 @@ impl From<TxOffload> for TxOffloadConfig {
             udp_tso: value.0 & TX_OFFLOAD_UDP_TSO != 0,
             vlan_insert: value.0 & TX_OFFLOAD_VLAN_INSERT != 0,
             vxlan_tnl_tso: value.0 & TX_OFFLOAD_VXLAN_TNL_TSO != 0,
+            multi_segs: value.0 & TX_OFFLOAD_MULTI_SEGS != 0,
             unknown: value.0 & !TxOffload::ALL_KNOWN.0,
         }
     }
@@ -508,6 +709,8 @@ impl TxOffload {
     pub const VXLAN_TNL_TSO: TxOffload = TxOffload(rte_eth_tx_offload::TX_OFFLOAD_VXLAN_TNL_TSO);
     /// VLAN tag insertion.
     pub const VLAN_INSERT: TxOffload = TxOffload(rte_eth_tx_offload::TX_OFFLOAD_VLAN_INSERT);
+    /// Transmission of packets in chained mbufs.
+    pub const MULTI_SEGS: TxOffload = TxOffload(rte_eth_tx_offload::TX_OFFLOAD_MULTI_SEGS);
 
     /// Union of all [`TxOffload`]s documented at the time of writing.
     pub const ALL_KNOWN: TxOffload = {
@@ -526,7 +729,8 @@ impl TxOffload {
                 | TX_OFFLOAD_UDP_CKSUM
                 | TX_OFFLOAD_UDP_TSO
                 | TX_OFFLOAD_VLAN_INSERT
-                | TX_OFFLOAD_VXLAN_TNL_TSO,
+                | TX_OFFLOAD_VXLAN_TNL_TSO
+                | TX_OFFLOAD_MULTI_SEGS,
         )
     };
 }
@@ -685,6 +889,175 @@ impl Manager {
     pub fn num_devices(&self) -> u16 {
         unsafe { rte_eth_dev_count_avail() }
     }
+
+    /// Attach a device at runtime (hotplug), and return information about its ethernet ports.
+    ///
+    /// # Arguments
+    ///
+    /// * `devargs`: the device arguments, as for the EAL allow list (e.g. a PCI address).
+    ///
+    /// The ports are left unconfigured (see [`DevConfig::apply`]).
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`HotplugError`] if the device could not be probed, or if it
+    /// has no ethernet port.
+    #[cold]
+    #[tracing::instrument(level = "info")]
+    pub fn attach(&self, devargs: &str) -> Result<Vec<DevInfo>, HotplugError> {
+        let c_devargs =
+            CString::new(devargs).map_err(|_| HotplugError::InvalidArgs(devargs.to_string()))?;
+        match unsafe { rte_dev_probe(c_devargs.as_ptr()) } {
+            0 => {
+                info!("Attached device {devargs}");
+            }
+            errno::NEG_EEXIST => return Err(HotplugError::AlreadyAttached(devargs.to_string())),
+            errno::NEG_EINVAL => return Err(HotplugError::InvalidArgs(devargs.to_string())),
+            errno::NEG_ENOTSUP => return Err(HotplugError::NotSupported(devargs.to_string())),
+            ret => {
+                return Err(HotplugError::Unexpected {
+                    devargs: devargs.to_string(),
+                    code: ErrorCode::parse_i32(ret),
+                });
+            }
+        }
+        self.find_ports(devargs)?
+            .into_iter()
+            .map(|port| {
+                port.info().map_err(|err| HotplugError::Info {
+                    devargs: devargs.to_string(),
+                    err,
+                })
+            })
+            .collect()
+    }
+
+    /// Find the ethernet ports of an attached device.
+    ///
+    /// # Arguments
+    ///
+    /// * `devargs`: the device arguments, as for [`Manager::attach`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`HotplugError`] if the ports could not be looked up, or if
+    /// the device has no ethernet port.
+    #[cold]
+    #[tracing::instrument(level = "debug")]
+    pub fn find_ports(&self, devargs: &str) -> Result<Vec<DevIndex>, HotplugError> {
+        let c_devargs =
+            CString::new(devargs).map_err(|_| HotplugError::InvalidArgs(devargs.to_string()))?;
+        let mut iter = rte_dev_iterator::default();
+        let ret = unsafe { rte_eth_iterator_init(&mut iter, c_devargs.as_ptr()) };
+        if ret != 0 {
+            return Err(HotplugError::Unexpected {
+                devargs: devargs.to_string(),
+                code: ErrorCode::parse_i32(ret),
+            });
+        }
+        // the iterator cleans up after itself once exhausted
+        let ports: Vec<DevIndex> = core::iter::from_fn(|| {
+            let port = unsafe { rte_eth_iterator_next(&mut iter) };
+            (port < DevIndex::MAX).then_some(DevIndex(port))
+        })
+        .collect();
+        if ports.is_empty() {
+            return Err(HotplugError::NoPort(devargs.to_string()));
+        }
+        Ok(ports)
+    }
+
+    /// Detach the device of an ethernet port at runtime (hot-unplug): stop and close the port,
+    /// release its queues, and remove the device.
+    ///
+    /// All the ports of the underlying device are removed with it.
+    /// The queues of the port must not be in use anymore.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`HotplugError`] if the port could not be closed or the
+    /// device removed.
+    #[cold]
+    #[tracing::instrument(level = "info")]
+    pub fn detach(&self, dev: Dev) -> Result<(), HotplugError> {
+        let port = dev.info.index();
+        let device = dev.info.inner.device;
+        // the device is stopped and closed here, not when dropped
+        let mut dev = core::mem::ManuallyDrop::new(dev);
+        if let Err(code) = dev.stop() {
+            warn!("Failed to stop port {port} before detaching it: {code}");
+        }
+        let queues = (
+            core::mem::take(&mut dev.rx_queues),
+            core::mem::take(&mut dev.tx_queues),
+            core::mem::take(&mut dev.hairpin_queues),
+        );
+        Self::close_and_remove(port, device)?;
+        // the pools of the queues can go once the port is closed
+        drop(queues);
+        Ok(())
+    }
+
+    /// Detach the device of an ethernet port which has no [`Dev`] (anymore), e.g. a port that
+    /// could not be configured: close the port and remove the device.
+    ///
+    /// All the ports of the underlying device are removed with it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`HotplugError`] if the port is unknown, or if it could not
+    /// be closed or the device removed.
+    #[cold]
+    #[tracing::instrument(level = "info")]
+    pub fn detach_port(&self, port: DevIndex) -> Result<(), HotplugError> {
+        let info = port.info().map_err(|err| HotplugError::Info {
+            devargs: format!("port {port}"),
+            err,
+        })?;
+        Self::close_and_remove(port, info.inner.device)
+    }
+
+    fn close_and_remove(port: DevIndex, device: *mut rte_device) -> Result<(), HotplugError> {
+        let ret = unsafe { rte_eth_dev_close(port.as_u16()) };
+        if ret != 0 {
+            return Err(HotplugError::Close {
+                port,
+                code: ErrorCode::parse_i32(ret),
+            });
+        }
+        match unsafe { rte_dev_remove(device) } {
+            0 => {
+                info!("Detached port {port}");
+                Ok(())
+            }
+            errno::NEG_ENOTSUP => Err(HotplugError::NotSupported(format!("port {port}"))),
+            ret => Err(HotplugError::Remove {
+                port,
+                code: ErrorCode::parse_i32(ret),
+            }),
+        }
+    }
+}
+
+/// Errors that can occur when attaching or detaching devices at runtime.
+#[derive(Debug, thiserror::Error)]
+pub enum HotplugError {
+    #[error("Invalid device arguments '{0}'")]
+    InvalidArgs(String),
+    #[error("Device '{0}' is already attached")]
+    AlreadyAttached(String),
+    #[error("Hotplug not supported for device '{0}'")]
+    NotSupported(String),
+    #[error("No ethernet port for device '{0}'")]
+    NoPort(String),
+    #[error("Failed to get information about the ports of device '{devargs}': {err}")]
+    Info { devargs: String, err: DevInfoError },
+    #[error("Failed to close port {port}: {code}")]
+    Close { port: DevIndex, code: ErrorCode },
+    #[error("Failed to remove the device of port {port}: {code}")]
+    Remove { port: DevIndex, code: ErrorCode },
+    #[error("Unexpected error for device '{devargs}': {code}")]
+    Unexpected { devargs: String, code: ErrorCode },
 }
 
 impl DevInfo {
@@ -726,6 +1099,12 @@ impl DevInfo {
     pub fn rx_offload_caps(&self) -> RxOffload {
         self.inner.rx_offload_capa.into()
     }
+
+    /// Get the maximum MTU supported by the device.
+    #[must_use]
+    pub fn max_mtu(&self) -> u16 {
+        self.inner.max_mtu
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,524 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Typed builder for flow rules.
+//!
+//! A [`FlowBuilder`] collects the attributes, the pattern and the actions of a flow rule, and
+//! keeps the DPDK representation of their specifications alive while the rule is validated or
+//! created on a port. A created rule is a [`FlowRule`], destroyed when dropped.
+
+use super::{
+    CounterId, EthHeader, FlowActionType, FlowGroup, FlowRule, FlowSpec, Ipv4Header, Ipv6Header,
+    MAX_ACTION_NUM, MAX_PATTERN_NUM, MatchType, SetFlowField, TcpHeader, UdpHeader, VxlanHeader,
+};
+use crate::dev::DevIndex;
+use crate::queue::rx::RxQueueIndex;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ffi::{CStr, c_void};
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+use errno::{ErrorCode, StandardErrno};
+use tracing::{debug, error};
+
+/// The attributes of a flow rule
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FlowAttr {
+    /// Group of the rule. Rules of group 0 are always looked up, other groups are reached by
+    /// jumping to them.
+    pub group: u32,
+    /// Priority of the rule in its group, 0 being the highest
+    pub priority: u32,
+    /// Apply the rule to the traffic received by the port
+    pub ingress: bool,
+    /// Apply the rule to the traffic sent by the port
+    pub egress: bool,
+    /// Apply the rule at the level of the embedded switch of the device
+    pub transfer: bool,
+}
+
+impl FlowAttr {
+    /// The attributes of a rule of group 0 applying to the traffic received by the port
+    #[must_use]
+    pub fn ingress() -> Self {
+        Self {
+            ingress: true,
+            ..Self::default()
+        }
+    }
+
+    /// The attributes of a rule of group 0 applying to the traffic sent by the port
+    #[must_use]
+    pub fn egress() -> Self {
+        Self {
+            egress: true,
+            ..Self::default()
+        }
+    }
+
+    /// Set the group of the rule
+    #[must_use]
+    pub fn with_group(mut self, group: FlowGroup) -> Self {
+        self.group = group.0;
+        self
+    }
+
+    /// Set the priority of the rule
+    #[must_use]
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Apply the rule at the level of the embedded switch of the device
+    #[must_use]
+    pub fn with_transfer(mut self) -> Self {
+        self.transfer = true;
+        self
+    }
+
+    fn to_raw(self) -> dpdk_sys::rte_flow_attr {
+        let mut attr = dpdk_sys::rte_flow_attr {
+            group: self.group,
+            priority: self.priority,
+            ..Default::default()
+        };
+        attr.set_ingress(u32::from(self.ingress));
+        attr.set_egress(u32::from(self.egress));
+        attr.set_transfer(u32::from(self.transfer));
+        attr
+    }
+}
+
+/// The part of a flow rule that DPDK blames for an error (see `enum rte_flow_error_type`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowErrorCause {
+    /// No particular part of the rule
+    Unspecified,
+    /// The flow rule handle
+    Handle,
+    /// The attributes of the rule
+    Attribute,
+    /// The pattern of the rule
+    Pattern,
+    /// The actions of the rule
+    Action,
+    /// The current state of the device
+    State,
+}
+
+impl FlowErrorCause {
+    fn from_raw(error_type: u32) -> Self {
+        use dpdk_sys::rte_flow_error_type::*;
+        match error_type {
+            RTE_FLOW_ERROR_TYPE_HANDLE => FlowErrorCause::Handle,
+            RTE_FLOW_ERROR_TYPE_ATTR_GROUP
+            | RTE_FLOW_ERROR_TYPE_ATTR_PRIORITY
+            | RTE_FLOW_ERROR_TYPE_ATTR_INGRESS
+            | RTE_FLOW_ERROR_TYPE_ATTR_EGRESS
+            | RTE_FLOW_ERROR_TYPE_ATTR_TRANSFER
+            | RTE_FLOW_ERROR_TYPE_ATTR => FlowErrorCause::Attribute,
+            RTE_FLOW_ERROR_TYPE_ITEM_NUM
+            | RTE_FLOW_ERROR_TYPE_ITEM_SPEC
+            | RTE_FLOW_ERROR_TYPE_ITEM_LAST
+            | RTE_FLOW_ERROR_TYPE_ITEM_MASK
+            | RTE_FLOW_ERROR_TYPE_ITEM => FlowErrorCause::Pattern,
+            RTE_FLOW_ERROR_TYPE_ACTION_NUM
+            | RTE_FLOW_ERROR_TYPE_ACTION_CONF
+            | RTE_FLOW_ERROR_TYPE_ACTION => FlowErrorCause::Action,
+            RTE_FLOW_ERROR_TYPE_STATE => FlowErrorCause::State,
+            _ => FlowErrorCause::Unspecified,
+        }
+    }
+}
+
+/// Errors with flow rules
+#[derive(Debug, thiserror::Error)]
+pub enum FlowError {
+    #[error("Flow rule with {0} pattern items, the maximum is {}", MAX_PATTERN_NUM - 1)]
+    TooManyItems(usize),
+    #[error("Flow rule with {0} actions, the maximum is {}", MAX_ACTION_NUM - 1)]
+    TooManyActions(usize),
+    #[error("Flow rule without any action")]
+    NoAction,
+    #[error("Port {0} does not support flow rules")]
+    NotImplemented(DevIndex),
+    #[error("Invalid flow rule for port {port} ({cause:?}): {message}")]
+    Invalid {
+        port: DevIndex,
+        cause: FlowErrorCause,
+        message: String,
+    },
+    #[error("Flow rule not supported by port {port} ({cause:?}): {message}")]
+    NotSupported {
+        port: DevIndex,
+        cause: FlowErrorCause,
+        message: String,
+    },
+    #[error("Flow rule collides with an existing rule of port {port}: {message}")]
+    Collision { port: DevIndex, message: String },
+    #[error("Not enough memory for flow rule on port {0}")]
+    NoMemory(DevIndex),
+    #[error("Port {0} is busy")]
+    Busy(DevIndex),
+    #[error("Port {0} was removed")]
+    Removed(DevIndex),
+    #[error("Unexpected error {code} for flow rule on port {port} ({cause:?}): {message}")]
+    Unexpected {
+        port: DevIndex,
+        code: ErrorCode,
+        cause: FlowErrorCause,
+        message: String,
+    },
+}
+
+impl FlowError {
+    /// Map the (positive) errno and the error details set by a `rte_flow_*` function
+    fn from_raw(port: DevIndex, errno: i32, error: &dpdk_sys::rte_flow_error) -> Self {
+        let cause = FlowErrorCause::from_raw(error.type_);
+        let message = if error.message.is_null() {
+            String::from("no details")
+        } else {
+            // SAFETY: DPDK sets the message to a null-terminated static string, if any
+            unsafe { CStr::from_ptr(error.message) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        error!("Flow rule error on port {port}, errno {errno} ({cause:?}): {message}");
+        match ErrorCode::parse_i32(errno) {
+            ErrorCode::Standard(StandardErrno::FunctionNotImplemented) => {
+                FlowError::NotImplemented(port)
+            }
+            ErrorCode::Standard(StandardErrno::InvalidArgument) => FlowError::Invalid {
+                port,
+                cause,
+                message,
+            },
+            ErrorCode::Standard(StandardErrno::NotSupported) => FlowError::NotSupported {
+                port,
+                cause,
+                message,
+            },
+            ErrorCode::Standard(StandardErrno::FileExists) => {
+                FlowError::Collision { port, message }
+            }
+            ErrorCode::Standard(StandardErrno::NoMemory) => FlowError::NoMemory(port),
+            ErrorCode::Standard(StandardErrno::Busy) => FlowError::Busy(port),
+            ErrorCode::Standard(StandardErrno::Io) => FlowError::Removed(port),
+            code => FlowError::Unexpected {
+                port,
+                code,
+                cause,
+                message,
+            },
+        }
+    }
+}
+
+/// The specification of a pattern item, and its mask, in DPDK representation
+struct ItemSpec<T> {
+    spec: T,
+    mask: Option<T>,
+}
+
+impl<T> ItemSpec<T> {
+    fn new<H: Into<T>>(spec: FlowSpec<H>) -> Self {
+        let FlowSpec { spec, mask } = spec;
+        Self {
+            spec: spec.into(),
+            mask: mask.map(Into::into),
+        }
+    }
+
+    /// The item pointing to the spec and mask. Valid as long as `self` is not moved or dropped.
+    fn to_raw(&self, item_type: MatchType) -> dpdk_sys::rte_flow_item {
+        dpdk_sys::rte_flow_item {
+            type_: item_type as u32,
+            spec: ptr::from_ref(&self.spec).cast::<c_void>(),
+            last: ptr::null(),
+            mask: self
+                .mask
+                .as_ref()
+                .map_or(ptr::null(), |mask| ptr::from_ref(mask).cast::<c_void>()),
+        }
+    }
+}
+
+/// A pattern item of a [`FlowBuilder`]
+enum PatternItem {
+    Eth(ItemSpec<dpdk_sys::rte_flow_item_eth>),
+    Ipv4(ItemSpec<dpdk_sys::rte_flow_item_ipv4>),
+    Ipv6(ItemSpec<dpdk_sys::rte_flow_item_ipv6>),
+    Tcp(ItemSpec<dpdk_sys::rte_flow_item_tcp>),
+    Udp(ItemSpec<dpdk_sys::rte_flow_item_udp>),
+    Vxlan(ItemSpec<dpdk_sys::rte_flow_item_vxlan>),
+}
+
+impl PatternItem {
+    fn to_raw(&self) -> dpdk_sys::rte_flow_item {
+        match self {
+            PatternItem::Eth(item) => item.to_raw(MatchType::Eth),
+            PatternItem::Ipv4(item) => item.to_raw(MatchType::Ipv4),
+            PatternItem::Ipv6(item) => item.to_raw(MatchType::Ipv6),
+            PatternItem::Tcp(item) => item.to_raw(MatchType::Tcp),
+            PatternItem::Udp(item) => item.to_raw(MatchType::Udp),
+            PatternItem::Vxlan(item) => item.to_raw(MatchType::Vxlan),
+        }
+    }
+}
+
+/// An action of a [`FlowBuilder`], with its configuration in DPDK representation
+enum Action {
+    Queue(dpdk_sys::rte_flow_action_queue),
+    Drop,
+    Count(dpdk_sys::rte_flow_action_count),
+    ModifyField(dpdk_sys::rte_flow_action_modify_field),
+}
+
+impl Action {
+    /// The action pointing to its configuration. Valid as long as `self` is not moved or dropped.
+    fn to_raw(&self) -> dpdk_sys::rte_flow_action {
+        fn conf<T>(conf: &T) -> *const c_void {
+            ptr::from_ref(conf).cast::<c_void>()
+        }
+        let (action_type, conf) = match self {
+            Action::Queue(queue) => (FlowActionType::Queue, conf(queue)),
+            Action::Drop => (FlowActionType::Drop, ptr::null()),
+            Action::Count(count) => (FlowActionType::Count, conf(count)),
+            Action::ModifyField(modify) => (FlowActionType::ModifyField, conf(modify)),
+        };
+        dpdk_sys::rte_flow_action {
+            type_: action_type as u32,
+            conf,
+        }
+    }
+}
+
+/// Builder of flow rules.
+///
+/// The pattern items are matched in the order they are added, from the outermost header.
+/// The rule is only checked by DPDK (and the driver of the port) when validated or created.
+pub struct FlowBuilder {
+    attr: FlowAttr,
+    pattern: Vec<PatternItem>,
+    actions: Vec<Action>,
+}
+
+impl FlowBuilder {
+    /// Start building a flow rule with attributes `attr`, an empty pattern and no action
+    #[must_use]
+    pub fn new(attr: FlowAttr) -> Self {
+        Self {
+            attr,
+            pattern: Vec::new(),
+            actions: Vec::new(),
+        }
+    }
+
+    /// Match an Ethernet header
+    #[must_use]
+    pub fn eth(mut self, spec: FlowSpec<EthHeader>) -> Self {
+        self.pattern.push(PatternItem::Eth(ItemSpec::new(spec)));
+        self
+    }
+
+    /// Match an IPv4 header
+    #[must_use]
+    pub fn ipv4(mut self, spec: FlowSpec<Ipv4Header>) -> Self {
+        self.pattern.push(PatternItem::Ipv4(ItemSpec::new(spec)));
+        self
+    }
+
+    /// Match an IPv6 header
+    #[must_use]
+    pub fn ipv6(mut self, spec: FlowSpec<Ipv6Header>) -> Self {
+        self.pattern.push(PatternItem::Ipv6(ItemSpec::new(spec)));
+        self
+    }
+
+    /// Match a TCP header
+    #[must_use]
+    pub fn tcp(mut self, spec: FlowSpec<TcpHeader>) -> Self {
+        self.pattern.push(PatternItem::Tcp(ItemSpec::new(spec)));
+        self
+    }
+
+    /// Match a UDP header
+    #[must_use]
+    pub fn udp(mut self, spec: FlowSpec<UdpHeader>) -> Self {
+        self.pattern.push(PatternItem::Udp(ItemSpec::new(spec)));
+        self
+    }
+
+    /// Match a VXLAN header
+    #[must_use]
+    pub fn vxlan(mut self, spec: FlowSpec<VxlanHeader>) -> Self {
+        self.pattern.push(PatternItem::Vxlan(ItemSpec::new(spec)));
+        self
+    }
+
+    /// Send the matching packets to the receive queue `queue`
+    #[must_use]
+    pub fn queue(mut self, queue: RxQueueIndex) -> Self {
+        self.actions
+            .push(Action::Queue(dpdk_sys::rte_flow_action_queue {
+                index: queue.0,
+            }));
+        self
+    }
+
+    /// Drop the matching packets
+    #[must_use]
+    pub fn drop_packets(mut self) -> Self {
+        self.actions.push(Action::Drop);
+        self
+    }
+
+    /// Count the matching packets with the counter `counter`
+    #[must_use]
+    pub fn count(mut self, counter: CounterId) -> Self {
+        self.actions
+            .push(Action::Count(dpdk_sys::rte_flow_action_count {
+                id: counter.0,
+            }));
+        self
+    }
+
+    /// Set a field of the matching packets
+    #[must_use]
+    pub fn modify_field(mut self, field: SetFlowField) -> Self {
+        self.actions
+            .push(Action::ModifyField(field.to_flow_rule().conf));
+        self
+    }
+
+    /// The raw pattern and actions, each terminated with an end marker. They point into `self`.
+    fn to_raw(
+        &self,
+    ) -> Result<(Vec<dpdk_sys::rte_flow_item>, Vec<dpdk_sys::rte_flow_action>), FlowError> {
+        if self.pattern.len() >= MAX_PATTERN_NUM {
+            return Err(FlowError::TooManyItems(self.pattern.len()));
+        }
+        if self.actions.is_empty() {
+            return Err(FlowError::NoAction);
+        }
+        if self.actions.len() >= MAX_ACTION_NUM {
+            return Err(FlowError::TooManyActions(self.actions.len()));
+        }
+        let mut pattern: Vec<_> = self.pattern.iter().map(PatternItem::to_raw).collect();
+        pattern.push(dpdk_sys::rte_flow_item {
+            type_: MatchType::End as u32,
+            spec: ptr::null(),
+            last: ptr::null(),
+            mask: ptr::null(),
+        });
+        let mut actions: Vec<_> = self.actions.iter().map(Action::to_raw).collect();
+        actions.push(dpdk_sys::rte_flow_action {
+            type_: FlowActionType::End as u32,
+            conf: ptr::null(),
+        });
+        Ok((pattern, actions))
+    }
+
+    /// Check if the rule could be created on port `port`, without creating it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FlowError`] if the rule is malformed, or if the port can't implement it.
+    pub fn validate(&self, port: DevIndex) -> Result<(), FlowError> {
+        let (pattern, actions) = self.to_raw()?;
+        let attr = self.attr.to_raw();
+        let mut error = dpdk_sys::rte_flow_error::default();
+        // SAFETY: the pattern and actions are end-terminated and point into `self`, borrowed
+        // for the duration of the call
+        let ret = unsafe {
+            dpdk_sys::rte_flow_validate(
+                port.as_u16(),
+                &attr,
+                pattern.as_ptr(),
+                actions.as_ptr(),
+                &mut error,
+            )
+        };
+        if ret != 0 {
+            return Err(FlowError::from_raw(port, -ret, &error));
+        }
+        Ok(())
+    }
+
+    /// Create the rule on port `port`. The rule is destroyed when the returned [`FlowRule`]
+    /// is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FlowError`] if the rule is malformed, or if the port can't implement it.
+    pub fn create(&self, port: DevIndex) -> Result<FlowRule, FlowError> {
+        let (pattern, actions) = self.to_raw()?;
+        let attr = self.attr.to_raw();
+        let mut error = dpdk_sys::rte_flow_error::default();
+        // SAFETY: the pattern and actions are end-terminated and point into `self`, borrowed
+        // for the duration of the call. DPDK copies what it needs to keep.
+        let flow = unsafe {
+            dpdk_sys::rte_flow_create(
+                port.as_u16(),
+                &attr,
+                pattern.as_ptr(),
+                actions.as_ptr(),
+                &mut error,
+            )
+        };
+        match NonNull::new(flow) {
+            Some(flow) => {
+                debug!("Created flow rule on port {port}");
+                Ok(FlowRule {
+                    port,
+                    flow,
+                    _phantom: PhantomData,
+                })
+            }
+            None => {
+                let errno = unsafe { dpdk_sys::rte_errno_get() };
+                Err(FlowError::from_raw(port, errno, &error))
+            }
+        }
+    }
+}
+
+impl FlowRule {
+    /// The port the rule is installed on
+    #[must_use]
+    pub fn port(&self) -> DevIndex {
+        self.port
+    }
+
+    /// Destroy the rule, reporting errors (which dropping the rule only logs).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FlowError`] if DPDK fails to destroy the rule.
+    pub fn destroy(self) -> Result<(), FlowError> {
+        let rule = core::mem::ManuallyDrop::new(self);
+        rule.destroy_raw()
+    }
+
+    fn destroy_raw(&self) -> Result<(), FlowError> {
+        let mut error = dpdk_sys::rte_flow_error::default();
+        // SAFETY: the flow was created on this port, and is only destroyed once
+        let ret = unsafe {
+            dpdk_sys::rte_flow_destroy(self.port.as_u16(), self.flow.as_ptr(), &mut error)
+        };
+        if ret != 0 {
+            return Err(FlowError::from_raw(self.port, -ret, &error));
+        }
+        debug!("Destroyed flow rule on port {port}", port = self.port);
+        Ok(())
+    }
+}
+
+impl Drop for FlowRule {
+    fn drop(&mut self) {
+        if let Err(err) = self.destroy_raw() {
+            error!("Failed to destroy flow rule: {err}");
+        }
+    }
+}
@@ -15,6 +15,10 @@ use core::marker::PhantomData;
 use core::ptr::NonNull;
 use net;
 
+mod builder;
+
+pub use builder::{FlowAttr, FlowBuilder, FlowError, FlowErrorCause};
+
 /// Flow manager
 ///
 /// This is a zero-sized type that is used for lifetime management and to ensure that the Eal is
@@ -539,6 +543,18 @@ pub struct EthHeader {
     ether_type: EtherType,
 }
 
+impl EthHeader {
+    /// Create an Ethernet header to match on
+    #[must_use]
+    pub fn new(src: MacAddr, dst: MacAddr, ether_type: EtherType) -> Self {
+        Self {
+            src,
+            dst,
+            ether_type,
+        }
+    }
+}
+
 /// TODO: forbid multicast mac src
 impl From<EthHeader> for dpdk_sys::rte_flow_item_eth {
     fn from(header: EthHeader) -> Self {
@@ -562,6 +578,59 @@ impl From<EthHeader> for dpdk_sys::rte_flow_item_eth {
     }
 }
 
+impl From<Ipv4Header> for dpdk_sys::rte_flow_item_ipv4 {
+    fn from(header: Ipv4Header) -> Self {
+        let mut ipv4 = dpdk_sys::rte_flow_item_ipv4::default();
+        ipv4.hdr.src_addr = u32::from(header.src).to_be();
+        ipv4.hdr.dst_addr = u32::from(header.dst).to_be();
+        ipv4
+    }
+}
+
+impl From<Ipv6Header> for dpdk_sys::rte_flow_item_ipv6 {
+    fn from(header: Ipv6Header) -> Self {
+        let mut ipv6 = dpdk_sys::rte_flow_item_ipv6::default();
+        ipv6.hdr.src_addr = dpdk_sys::rte_ipv6_addr {
+            a: header.src.octets(),
+        };
+        ipv6.hdr.dst_addr = dpdk_sys::rte_ipv6_addr {
+            a: header.dst.octets(),
+        };
+        ipv6
+    }
+}
+
+impl From<TcpHeader> for dpdk_sys::rte_flow_item_tcp {
+    fn from(header: TcpHeader) -> Self {
+        let mut tcp = dpdk_sys::rte_flow_item_tcp::default();
+        tcp.hdr.src_port = hton_16(header.src_port.0);
+        tcp.hdr.dst_port = hton_16(header.dst_port.0);
+        tcp
+    }
+}
+
+impl From<UdpHeader> for dpdk_sys::rte_flow_item_udp {
+    fn from(header: UdpHeader) -> Self {
+        let mut udp = dpdk_sys::rte_flow_item_udp::default();
+        udp.hdr.src_port = hton_16(header.src_port.0);
+        udp.hdr.dst_port = hton_16(header.dst_port.0);
+        udp
+    }
+}
+
+impl From<VxlanHeader> for dpdk_sys::rte_flow_item_vxlan {
+    fn from(header: VxlanHeader) -> Self {
+        /// The I flag, telling that the VNI is valid
+        const VXLAN_FLAG_VNI: u8 = 0x08;
+        let mut vxlan = dpdk_sys::rte_flow_item_vxlan::default();
+        let mut vni = [0u8; 3];
+        vni.copy_from_slice(&header.vni.0.to_be_bytes()[1..]);
+        vxlan.annon1.annon1.flags = VXLAN_FLAG_VNI;
+        vxlan.annon1.annon1.vni = vni;
+        vxlan
+    }
+}
+
 pub struct VlanTci(pub u16);
 
 pub struct VlanHeader {
@@ -999,52 +1068,86 @@ pub struct SetFieldAction {
 }
 
 impl SetFlowField {
+    /// The field to modify, its new value, in network byte order, and its width in bits
+    fn target(&self) -> (FlowFieldId, Vec<u8>, u32) {
+        match *self {
+            SetFlowField::MacDst(mac) => (FlowFieldId::MacDst, mac.0.to_vec(), 48),
+            SetFlowField::MacSrc(mac) => (FlowFieldId::MacSrc, mac.0.to_vec(), 48),
+            SetFlowField::VlanType(ether_type) => (
+                FlowFieldId::VlanType,
+                ether_type.0.to_be_bytes().to_vec(),
+                16,
+            ),
+            SetFlowField::VlanVid(vid) => (
+                FlowFieldId::VlanVid,
+                vid.as_u16().to_be_bytes().to_vec(),
+                12,
+            ),
+            SetFlowField::EtherType(ether_type) => (
+                FlowFieldId::EtherType,
+                ether_type.0.to_be_bytes().to_vec(),
+                16,
+            ),
+            SetFlowField::Ipv4Dscp(dscp) => (FlowFieldId::Ipv4Dscp, vec![dscp], 6),
+            SetFlowField::Ipv4Ttl(ttl) => (FlowFieldId::Ipv4Ttl, vec![ttl], 8),
+            SetFlowField::Ipv4Src(addr) => (FlowFieldId::Ipv4Src, addr.octets().to_vec(), 32),
+            SetFlowField::Ipv4Dst(addr) => (FlowFieldId::Ipv4Dst, addr.octets().to_vec(), 32),
+            SetFlowField::Ipv6Dscp(dscp) => (FlowFieldId::Ipv6Dscp, vec![dscp], 6),
+            SetFlowField::Ipv6HopLimit(hops) => (FlowFieldId::Ipv6HopLimit, vec![hops], 8),
+            SetFlowField::Ipv6Src(addr) => (FlowFieldId::Ipv6Src, addr.octets().to_vec(), 128),
+            SetFlowField::Ipv6Dst(addr) => (FlowFieldId::Ipv6Dst, addr.octets().to_vec(), 128),
+            SetFlowField::TcpPortSrc(port) => {
+                (FlowFieldId::TcpPortSrc, port.to_be_bytes().to_vec(), 16)
+            }
+            SetFlowField::TcpPortDst(port) => {
+                (FlowFieldId::TcpPortDst, port.to_be_bytes().to_vec(), 16)
+            }
+            SetFlowField::TcpSeqNum(seq) => {
+                (FlowFieldId::TcpSeqNum, seq.to_be_bytes().to_vec(), 32)
+            }
+            SetFlowField::TcpAckNum(ack) => {
+                (FlowFieldId::TcpAckNum, ack.to_be_bytes().to_vec(), 32)
+            }
+            SetFlowField::TcpFlags(flags) => {
+                (FlowFieldId::TcpFlags, flags.to_be_bytes().to_vec(), 9)
+            }
+            SetFlowField::UdpPortSrc(port) => {
+                (FlowFieldId::UdpPortSrc, port.to_be_bytes().to_vec(), 16)
+            }
+            SetFlowField::UdpPortDst(port) => {
+                (FlowFieldId::UdpPortDst, port.to_be_bytes().to_vec(), 16)
+            }
+            SetFlowField::VxlanVni(vni) => (
+                FlowFieldId::VxlanVni,
+                vni.as_u32().to_be_bytes()[1..].to_vec(),
+                24,
+            ),
+            SetFlowField::Tag(tag) => {
+                (FlowFieldId::Tag, tag.data.0.to_be_bytes()[1..].to_vec(), 24)
+            }
+            SetFlowField::Meta(meta) => (FlowFieldId::Meta, meta.data.to_be_bytes().to_vec(), 32),
+            SetFlowField::IpV4Ecn(ecn) => (FlowFieldId::Ipv4Ecn, vec![ecn], 2),
+            SetFlowField::IpV6Ecn(ecn) => (FlowFieldId::Ipv6Ecn, vec![ecn], 2),
+        }
+    }
+
     /// Converts the `SetFlowField` into a `SetFieldAction`.
     #[must_use]
     pub fn to_flow_rule(&self) -> SetFieldAction {
-        let conf = match self {
-            SetFlowField::MacDst(mac_addr) => {
-                let mut value = [0u8; 16];
-                value[0..size_of::<MacAddr>()].copy_from_slice(&mac_addr.0);
-                let value = value;
-                dpdk_sys::rte_flow_action_modify_field {
-                    operation: FieldModificationOperation::Set as u32,
-                    src: dpdk_sys::rte_flow_field_data {
-                        field: FlowFieldId::Value as u32,
-                        annon1: dpdk_sys::rte_flow_field_data__bindgen_ty_1 { value },
-                    },
-                    dst: dpdk_sys::rte_flow_field_data {
-                        field: FlowFieldId::MacDst as u32,
-                        annon1: dpdk_sys::rte_flow_field_data__bindgen_ty_1::default(),
-                    },
-                    width: size_of::<MacAddr>() as u32,
-                }
-            }
-            _ => todo!(),
-            // SetFlowField::MacSrc(_) => {}
-            // SetFlowField::VlanType(_) => {}
-            // SetFlowField::VlanVid(_) => {}
-            // SetFlowField::EtherType(_) => {}
-            // SetFlowField::Ipv4Dscp(_) => {}
-            // SetFlowField::Ipv4Ttl(_) => {}
-            // SetFlowField::Ipv4Src(_) => {}
-            // SetFlowField::Ipv4Dst(_) => {}
-            // SetFlowField::Ipv6Dscp(_) => {}
-            // SetFlowField::Ipv6HopLimit(_) => {}
-            // SetFlowField::Ipv6Src(_) => {}
-            // SetFlowField::Ipv6Dst(_) => {}
-            // SetFlowField::TcpPortSrc(_) => {}
-            // SetFlowField::TcpPortDst(_) => {}
-            // SetFlowField::TcpSeqNum(_) => {}
-            // SetFlowField::TcpAckNum(_) => {}
-            // SetFlowField::TcpFlags(_) => {}
-            // SetFlowField::UdpPortSrc(_) => {}
-            // SetFlowField::UdpPortDst(_) => {}
-            // SetFlowField::VxlanVni(_) => {}
-            // SetFlowField::Tag(_) => {}
-            // SetFlowField::Meta(_) => {}
-            // SetFlowField::IpV4Ecn(_) => {}
-            // SetFlowField::IpV6Ecn(_) => {}
+        let (field, bytes, width) = self.target();
+        let mut value = [0u8; 16];
+        value[..bytes.len()].copy_from_slice(&bytes);
+        let conf = dpdk_sys::rte_flow_action_modify_field {
+            operation: FieldModificationOperation::Set as u32,
+            src: dpdk_sys::rte_flow_field_data {
+                field: FlowFieldId::Value as u32,
+                annon1: dpdk_sys::rte_flow_field_data__bindgen_ty_1 { value },
+            },
+            dst: dpdk_sys::rte_flow_field_data {
+                field: field as u32,
+                annon1: dpdk_sys::rte_flow_field_data__bindgen_ty_1::default(),
+            },
+            width,
         };
         SetFieldAction { rule: *self, conf }
     }
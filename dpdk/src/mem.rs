@@ -4,9 +4,12 @@
 //! DPDK memory management wrappers.
 
 use crate::eal::{Eal, EalErrno};
+use crate::queue::rx::RxQueue;
 use crate::socket::SocketId;
+use alloc::collections::BTreeMap;
 use alloc::format;
 use alloc::string::String;
+use alloc::sync::Arc;
 use core::alloc::{GlobalAlloc, Layout};
 use core::cell::Cell;
 use core::ffi::c_uint;
@@ -22,11 +25,13 @@ use errno::Errno;
 use tracing::{error, info, warn};
 
 use dpdk_sys::{
-    rte_pktmbuf_adj, rte_pktmbuf_append, rte_pktmbuf_headroom, rte_pktmbuf_prepend,
-    rte_pktmbuf_tailroom, rte_pktmbuf_trim,
+    RTE_MBUF_F_TX_IP_CKSUM, RTE_MBUF_F_TX_IPV4, RTE_MBUF_F_TX_IPV6, RTE_MBUF_F_TX_TCP_CKSUM,
+    RTE_MBUF_F_TX_UDP_CKSUM, rte_pktmbuf_adj, rte_pktmbuf_append, rte_pktmbuf_headroom,
+    rte_pktmbuf_prepend, rte_pktmbuf_tailroom, rte_pktmbuf_trim,
 };
 // unfortunately, we need the standard library to swap allocators
-use net::buffer::{Append, Headroom, Prepend, Tailroom, TrimFromEnd, TrimFromStart};
+use net::buffer::{Append, Headroom, Prepend, Segments, Tailroom, TrimFromEnd, TrimFromStart};
+use net::packet::ChecksumOffload;
 use std::alloc::System;
 use std::ffi::CString;
 
@@ -50,6 +55,9 @@ impl Drop for Manager {
 
 /// Safe wrapper around a DPDK memory pool
 ///
+/// The pool is reference counted: clones share the same DPDK memory pool (e.g. the receive
+/// queues on a socket), which is freed with the last of them.
+///
 /// <div class="warning">
 ///
 /// # Note:
@@ -59,8 +67,8 @@ impl Drop for Manager {
 ///
 /// </div>
 #[repr(transparent)]
-#[derive(Debug)]
-pub struct Pool(PoolInner);
+#[derive(Debug, Clone)]
+pub struct Pool(Arc<PoolInner>);
 
 impl PartialEq for Pool {
     fn eq(&self, other: &Self) -> bool {
@@ -128,7 +136,24 @@ impl Pool {
             Some(pool) => pool,
         };
 
-        Ok(Pool(PoolInner { config, pool }))
+        Ok(Pool(Arc::new(PoolInner { config, pool })))
+    }
+
+    /// Create a packet memory pool on each socket, from the configs of
+    /// [`PoolConfig::per_socket`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidMemPoolConfig`] if any of the pools could not be created.
+    #[cold]
+    #[tracing::instrument(level = "debug")]
+    pub fn new_pkt_pools(
+        configs: BTreeMap<SocketId, PoolConfig>,
+    ) -> Result<BTreeMap<SocketId, Pool>, InvalidMemPoolConfig> {
+        configs
+            .into_iter()
+            .map(|(socket_id, config)| Ok((socket_id, Pool::new_pkt_pool(config)?)))
+            .collect()
     }
 
     /// Get the name of the memory pool.
@@ -161,8 +186,8 @@ impl Pool {
     }
 }
 
-/// This value is RAII-managed and must never implement `Copy` and can likely never implement
-/// `Clone` unless the internal representation is changed to use a reference-counted pointer.
+/// This value is RAII-managed and must never implement `Copy` or `Clone`: [`Pool`] shares it
+/// with a reference-counted pointer instead.
 #[non_exhaustive]
 #[derive(Debug)]
 pub(crate) struct PoolInner {
@@ -245,6 +270,75 @@ impl Default for PoolParams {
     }
 }
 
+impl PoolParams {
+    /// Number of mbufs that each lcore may hold on to, on top of its cache: the packets it
+    /// is processing, from a few bursts
+    pub const MBUFS_IN_FLIGHT_PER_LCORE: u32 = 4 * RxQueue::PKT_BURST_SIZE as u32;
+
+    /// Size the pool for `demand`, with enough mbufs to fill all the descriptors of the queues
+    /// and the caches of the lcores, and for the packets in flight.
+    ///
+    /// The size is rounded up to a power of two minus one, and the cache size is capped to what
+    /// DPDK accepts for a pool of that size.
+    #[must_use]
+    pub fn sized_for(self, demand: &PoolDemand) -> PoolParams {
+        let per_lcore = self
+            .cache_size
+            .saturating_add(PoolParams::MBUFS_IN_FLIGHT_PER_LCORE);
+        let needed = demand
+            .rx_descriptors
+            .saturating_add(demand.tx_descriptors)
+            .saturating_add(demand.lcores.saturating_mul(per_lcore));
+        let size = needed
+            .saturating_add(1)
+            .checked_next_power_of_two()
+            .map_or(u32::MAX, |size| size - 1);
+        // DPDK rejects caches larger than the pool size divided by 1.5
+        let cache_size = self
+            .cache_size
+            .min(size / 3 * 2)
+            .min(dpdk_sys::RTE_MEMPOOL_CACHE_MAX_SIZE);
+        PoolParams {
+            size,
+            cache_size,
+            ..self
+        }
+    }
+}
+
+/// The mbufs that the queues on a socket need from a pool, to size the pool (see
+/// [`PoolParams::sized_for`]).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PoolDemand {
+    /// Number of receive descriptors, over all the receive queues using the pool
+    pub rx_descriptors: u32,
+    /// Number of transmit descriptors, over all the transmit queues sending mbufs from the pool
+    pub tx_descriptors: u32,
+    /// Number of lcores using the pool
+    pub lcores: u32,
+}
+
+impl PoolDemand {
+    /// Account for a receive queue with `num_descriptors` descriptors
+    pub fn add_rx_queue(&mut self, num_descriptors: u16) {
+        self.rx_descriptors = self
+            .rx_descriptors
+            .saturating_add(u32::from(num_descriptors));
+    }
+
+    /// Account for a transmit queue with `num_descriptors` descriptors
+    pub fn add_tx_queue(&mut self, num_descriptors: u16) {
+        self.tx_descriptors = self
+            .tx_descriptors
+            .saturating_add(u32::from(num_descriptors));
+    }
+
+    /// Account for an lcore using the pool
+    pub fn add_lcore(&mut self) {
+        self.lcores = self.lcores.saturating_add(1);
+    }
+}
+
 /// Memory pool config
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PoolConfig {
@@ -355,6 +449,35 @@ impl PoolConfig {
         Ok(PoolConfig { name, params })
     }
 
+    /// Create the configs of one memory pool per socket, each sized for the demand of the
+    /// queues on that socket (see [`PoolParams::sized_for`]).
+    ///
+    /// The pools are named after `prefix` and the socket, and get the other parameters from
+    /// `params`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidMemPoolConfig`] if the name of any of the pools is invalid.
+    #[cold]
+    #[tracing::instrument(level = "debug", ret)]
+    pub fn per_socket(
+        prefix: &str,
+        params: PoolParams,
+        demands: &BTreeMap<SocketId, PoolDemand>,
+    ) -> Result<BTreeMap<SocketId, PoolConfig>, InvalidMemPoolConfig> {
+        demands
+            .iter()
+            .map(|(&socket_id, demand)| {
+                let name = format!("{prefix}-{socket}", socket = socket_id.as_c_uint());
+                let params = PoolParams {
+                    socket_id,
+                    ..params.sized_for(demand)
+                };
+                Ok((socket_id, PoolConfig::new_internal(&name, params)?))
+            })
+            .collect()
+    }
+
     /// Get the name of the memory pool.
     ///
     /// # Panics
@@ -468,6 +591,11 @@ impl TrimFromEnd for Mbuf {
     fn trim_from_end(&mut self, len: u16) -> Result<&mut [u8], Self::Error> {
         match unsafe { rte_pktmbuf_trim(self.raw.as_ptr(), len) } {
             0 => Ok(self.raw_data_mut()),
+            // rte_pktmbuf_trim only trims the last segment
+            -1 if self.num_segments() > 1 => {
+                self.trim_segments_from_end(len)?;
+                Ok(self.raw_data_mut())
+            }
             -1 => Err(MbufManipulationError::NotLongEnough),
             // TODO: this only happens when DPDK has a programmer error (deviation from docs)
             ret => {
@@ -479,6 +607,38 @@ impl TrimFromEnd for Mbuf {
     }
 }
 
+impl Segments for Mbuf {
+    fn num_segments(&self) -> u16 {
+        unsafe { self.raw.as_ref().annon1.annon1.nb_segs }
+    }
+
+    fn segments(&self) -> impl Iterator<Item = &[u8]> {
+        let mut segment = self.raw.as_ptr();
+        core::iter::from_fn(move || {
+            let current = NonNull::new(segment)?;
+            // the segments of the chain live as long as its first segment
+            unsafe {
+                segment = current.as_ref().next;
+                let (data, len) = Mbuf::segment_data(current);
+                Some(core::slice::from_raw_parts(data.cast_const(), len))
+            }
+        })
+    }
+
+    fn segment_mut(&mut self, index: u16) -> Option<&mut [u8]> {
+        let mut segment = self.raw;
+        for _ in 0..index {
+            segment = NonNull::new(unsafe { segment.as_ref().next })?;
+        }
+        let (data, len) = unsafe { Mbuf::segment_data(segment) };
+        Some(unsafe { from_raw_parts_mut(data, len) })
+    }
+
+    fn data_len(&self) -> usize {
+        unsafe { self.raw.as_ref().annon2.annon1.pkt_len as usize }
+    }
+}
+
 impl Mbuf {
     /// Create a new mbuf from an existing rte_mbuf pointer.
     ///
@@ -501,14 +661,10 @@ impl Mbuf {
 
     /// Get an immutable ref to the raw data of an Mbuf
     ///
-    /// TODO: deal with multi segment packets
+    /// For multi-segment mbufs, this is the data of the first segment only (see [`Segments`]).
     #[must_use]
     #[tracing::instrument(level = "trace")]
     pub fn raw_data(&self) -> &[u8] {
-        debug_assert!(
-            unsafe { self.raw.as_ref().annon1.annon1.nb_segs } == 1,
-            "multi seg packets not properly supported yet"
-        );
         let pkt_data_start = unsafe {
             (self.raw.as_ref().buf_addr as *const u8)
                 .offset(self.raw.as_ref().annon1.annon1.data_off as isize)
@@ -521,15 +677,51 @@ impl Mbuf {
         }
     }
 
-    // TODO: deal with multi seg packets
+    /// Request the NIC to compute the `checksums` of the packet on transmission.
+    ///
+    /// `ipv4` tells if the network header is IPv4 (or IPv6), and `l2_len` and `l3_len` are the
+    /// lengths of the link and network headers. The packet must have been prepared for the NIC,
+    /// with its IPv4 checksum zeroed and its transport checksum seeded with the sum of the
+    /// pseudo-header (see `Packet::update_checksums`). The port must have the matching transmit
+    /// offloads enabled.
+    pub fn request_checksums(
+        &mut self,
+        checksums: ChecksumOffload,
+        ipv4: bool,
+        l2_len: u16,
+        l3_len: u16,
+    ) {
+        if checksums.is_empty() {
+            return;
+        }
+        let mut flags = if ipv4 {
+            RTE_MBUF_F_TX_IPV4
+        } else {
+            RTE_MBUF_F_TX_IPV6
+        };
+        if checksums.contains(ChecksumOffload::IPV4) {
+            flags |= RTE_MBUF_F_TX_IP_CKSUM;
+        }
+        if checksums.contains(ChecksumOffload::TCP) {
+            flags |= RTE_MBUF_F_TX_TCP_CKSUM;
+        } else if checksums.contains(ChecksumOffload::UDP) {
+            flags |= RTE_MBUF_F_TX_UDP_CKSUM;
+        }
+        unsafe {
+            let raw = self.raw.as_mut();
+            raw.ol_flags |= flags;
+            // l2_len is the lowest 7 bits of the tx_offload field, l3_len the next 9 bits
+            raw.annon3.tx_offload = u64::from(l2_len & 0x7f) | (u64::from(l3_len & 0x1ff) << 7);
+        }
+    }
+
     /// Get a mutable ref to the raw data of an Mbuf (usually the binary contents of a packet).
+    ///
+    /// For multi-segment mbufs, this is the data of the first segment only (see [`Segments`]).
     #[must_use]
     #[tracing::instrument(level = "trace")]
     pub fn raw_data_mut(&mut self) -> &mut [u8] {
         unsafe {
-            if self.raw.as_ref().annon1.annon1.nb_segs > 1 {
-                error!("multi seg packets not supported yet");
-            }
             let data_start = self
                 .raw
                 .as_mut()
@@ -543,6 +735,79 @@ impl Mbuf {
         }
     }
 
+    /// Get the start and the length of the data of the mbuf segment `segment`.
+    ///
+    /// # Safety
+    ///
+    /// `segment` must point to a valid mbuf.
+    unsafe fn segment_data(segment: NonNull<dpdk_sys::rte_mbuf>) -> (*mut u8, usize) {
+        unsafe {
+            let segment = segment.as_ref();
+            let data = segment
+                .buf_addr
+                .cast::<u8>()
+                .offset(segment.annon1.annon1.data_off as isize);
+            (data, segment.annon2.annon1.data_len as usize)
+        }
+    }
+
+    /// Trim `len` bytes from the end of a multi-segment mbuf, across segments, freeing the
+    /// segments left empty.
+    #[tracing::instrument(level = "trace")]
+    fn trim_segments_from_end(&mut self, len: u16) -> Result<(), MbufManipulationError> {
+        let Some(new_len) = self.data_len().checked_sub(usize::from(len)) else {
+            return Err(MbufManipulationError::NotLongEnough);
+        };
+        unsafe {
+            let head = self.raw.as_ptr();
+            // find the segment holding the new end of the data
+            let mut segment = head;
+            let mut offset = 0;
+            let mut nb_segs = 1;
+            loop {
+                let data_len = (*segment).annon2.annon1.data_len as usize;
+                if offset + data_len >= new_len || (*segment).next.is_null() {
+                    break;
+                }
+                offset += data_len;
+                segment = (*segment).next;
+                nb_segs += 1;
+            }
+            (*segment).annon2.annon1.data_len = (new_len - offset) as u16;
+            if !(*segment).next.is_null() {
+                dpdk_sys::rte_pktmbuf_free((*segment).next);
+                (*segment).next = null_mut();
+            }
+            (*head).annon2.annon1.pkt_len = new_len as u32;
+            (*head).annon1.annon1.nb_segs = nb_segs;
+        }
+        Ok(())
+    }
+
+    /// Chain the segments of `tail` after those of this mbuf, e.g. to build a jumbo frame to
+    /// transmit. The data of `tail` then follows the data of this mbuf.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MbufManipulationError::TooManySegments`] if the chain would exceed the maximum
+    /// number of segments of an mbuf. `tail` is freed in that case.
+    #[tracing::instrument(level = "trace")]
+    pub fn chain(&mut self, tail: Mbuf) -> Result<(), MbufManipulationError> {
+        let tail = core::mem::ManuallyDrop::new(tail);
+        match unsafe { dpdk_sys::rte_pktmbuf_chain(self.raw.as_ptr(), tail.raw.as_ptr()) } {
+            // the segments of tail are now owned by this mbuf
+            0 => Ok(()),
+            ret => {
+                drop(core::mem::ManuallyDrop::into_inner(tail));
+                if ret == errno::NEG_EOVERFLOW {
+                    Err(MbufManipulationError::TooManySegments)
+                } else {
+                    Err(MbufManipulationError::Unknown(ret))
+                }
+            }
+        }
+    }
+
     #[tracing::instrument(level = "trace")]
     fn prepend_to_headroom(&mut self, len: u16) -> Result<&mut [u8], NotEnoughHeadRoom> {
         let val = unsafe { rte_pktmbuf_prepend(self.raw.as_mut(), len) };
@@ -584,6 +849,8 @@ pub struct MemoryBufferNotLongEnough;
 pub enum MbufManipulationError {
     #[error("buffer not long enough")]
     NotLongEnough,
+    #[error("too many segments in buffer")]
+    TooManySegments,
     #[error("Undocumented DPDK error: {0}")]
     Unknown(c_int),
 }
@@ -694,3 +961,102 @@ unsafe impl GlobalAlloc for RteAllocator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(cache_size: u32) -> PoolParams {
+        PoolParams {
+            size: 0,
+            cache_size,
+            private_size: 256,
+            data_size: 2048,
+            socket_id: SocketId::ANY,
+        }
+    }
+
+    fn demand(rx_descriptors: u32, tx_descriptors: u32, lcores: u32) -> PoolDemand {
+        PoolDemand {
+            rx_descriptors,
+            tx_descriptors,
+            lcores,
+        }
+    }
+
+    #[test]
+    fn sized_for_rounds_up_to_power_of_two_minus_one() {
+        // 1024 + 1024 descriptors, and 2 lcores with 256 cached and 256 in flight mbufs each
+        let sized = params(256).sized_for(&demand(1024, 1024, 2));
+        assert_eq!(sized.size, 4095);
+        assert_eq!(sized.cache_size, 256);
+        assert_eq!((sized.private_size, sized.data_size), (256, 2048));
+
+        // a demand of a power of two minus one is not rounded up further
+        assert_eq!(params(256).sized_for(&demand(1023, 0, 0)).size, 1023);
+        assert_eq!(params(256).sized_for(&demand(1024, 0, 0)).size, 2047);
+    }
+
+    #[test]
+    fn sized_for_caps_cache_size() {
+        // no larger than the pool size divided by 1.5
+        let sized = params(256).sized_for(&demand(100, 0, 0));
+        assert_eq!(sized.size, 127);
+        assert_eq!(sized.cache_size, 84);
+        assert_eq!(params(256).sized_for(&demand(0, 0, 0)).cache_size, 0);
+
+        // no larger than what DPDK supports, whatever the pool size
+        let sized =
+            params(4 * dpdk_sys::RTE_MEMPOOL_CACHE_MAX_SIZE).sized_for(&demand(1 << 16, 0, 1));
+        assert_eq!(sized.size, (1 << 17) - 1);
+        assert_eq!(sized.cache_size, dpdk_sys::RTE_MEMPOOL_CACHE_MAX_SIZE);
+    }
+
+    #[test]
+    fn sized_for_saturates() {
+        let sized = params(256).sized_for(&demand(u32::MAX, 1, 0));
+        assert_eq!(sized.size, u32::MAX);
+        assert_eq!(sized.cache_size, 256);
+        assert_eq!(
+            params(256).sized_for(&demand(0, 0, u32::MAX)).size,
+            u32::MAX
+        );
+        assert_eq!(params(u32::MAX).sized_for(&demand(0, 0, 1)).size, u32::MAX);
+
+        // past 2^31, there is no power of two to round up to
+        assert_eq!(params(256).sized_for(&demand(1 << 31, 0, 0)).size, u32::MAX);
+
+        let mut saturated = demand(u32::MAX, u32::MAX, u32::MAX);
+        saturated.add_rx_queue(1);
+        saturated.add_tx_queue(1);
+        saturated.add_lcore();
+        assert_eq!(saturated, demand(u32::MAX, u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn per_socket_configs() {
+        let demands = BTreeMap::from([
+            (SocketId(0), demand(1024, 1024, 2)),
+            (SocketId(1), demand(100, 0, 0)),
+        ]);
+        let configs = PoolConfig::per_socket("pkt", params(256), &demands).unwrap();
+        assert_eq!(configs.len(), 2);
+
+        let config = &configs[&SocketId(0)];
+        assert_eq!(config.name(), "pkt-0");
+        assert_eq!(config.params.socket_id, SocketId(0));
+        assert_eq!((config.params.size, config.params.cache_size), (4095, 256));
+
+        let config = &configs[&SocketId(1)];
+        assert_eq!(config.name(), "pkt-1");
+        assert_eq!(config.params.socket_id, SocketId(1));
+        assert_eq!((config.params.size, config.params.cache_size), (127, 84));
+
+        assert!(PoolConfig::per_socket("1pkt", params(256), &demands).is_err());
+        assert!(
+            PoolConfig::per_socket("pkt", params(256), &BTreeMap::new())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,221 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Conversions between errno values and [`std::io::Error`] / [`std::io::ErrorKind`].
+//!
+//! The numeric values of this crate are not necessarily those of the host platform, so the
+//! conversions go through the meaning of the errors ([`ErrorKind`]) rather than through raw OS
+//! error codes.
+
+use crate::{ErrorCode, StandardErrno};
+use std::io::{Error, ErrorKind};
+
+impl StandardErrno {
+    /// Get the [`ErrorKind`] matching this errno.
+    ///
+    /// Negative values map to the kind of their positive counterpart.
+    /// Errno values without a matching kind map to [`ErrorKind::Other`].
+    #[must_use]
+    pub const fn io_error_kind(self) -> ErrorKind {
+        #[allow(clippy::enum_glob_use)]
+        use StandardErrno::*;
+        match self.abs() {
+            PermissionDenied | AccessDenied => ErrorKind::PermissionDenied,
+            NoSuchFileOrDirectory => ErrorKind::NotFound,
+            Interrupted => ErrorKind::Interrupted,
+            TooBig => ErrorKind::ArgumentListTooLong,
+            TryAgain => ErrorKind::WouldBlock,
+            NoMemory => ErrorKind::OutOfMemory,
+            Busy => ErrorKind::ResourceBusy,
+            FileExists => ErrorKind::AlreadyExists,
+            CrossDeviceLink => ErrorKind::CrossesDevices,
+            NotADirectory => ErrorKind::NotADirectory,
+            IsADirectory => ErrorKind::IsADirectory,
+            InvalidArgument => ErrorKind::InvalidInput,
+            TextFileBusy => ErrorKind::ExecutableFileBusy,
+            FileTooLarge => ErrorKind::FileTooLarge,
+            NoSpaceLeftOnDevice => ErrorKind::StorageFull,
+            IllegalSeek => ErrorKind::NotSeekable,
+            ReadOnlyFileSystem => ErrorKind::ReadOnlyFilesystem,
+            TooManyLinks => ErrorKind::TooManyLinks,
+            BrokenPipe => ErrorKind::BrokenPipe,
+            Deadlock | FileLockingDeadlock => ErrorKind::Deadlock,
+            FunctionNotImplemented | OperationNotSupportedOnTransportEndpoint | NotSupported => {
+                ErrorKind::Unsupported
+            }
+            DirectoryNotEmpty => ErrorKind::DirectoryNotEmpty,
+            FileOrPathNameTooLong => ErrorKind::InvalidFilename,
+            ConnectionResetByPeer => ErrorKind::ConnectionReset,
+            ConnectionRefused => ErrorKind::ConnectionRefused,
+            AddressAlreadyInUse => ErrorKind::AddrInUse,
+            ConnectionAborted => ErrorKind::ConnectionAborted,
+            NetworkIsUnreachable => ErrorKind::NetworkUnreachable,
+            NetworkInterfaceNotConfigured => ErrorKind::NetworkDown,
+            ConnectionTimedOut => ErrorKind::TimedOut,
+            HostIsUnreachable => ErrorKind::HostUnreachable,
+            AddressNotAvailable => ErrorKind::AddrNotAvailable,
+            SocketIsNotConnected => ErrorKind::NotConnected,
+            DiskQuotaExceeded => ErrorKind::QuotaExceeded,
+            StaleNfsFileHandle => ErrorKind::StaleNetworkFileHandle,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// Get the (positive) errno which best represents an [`ErrorKind`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the original [`ErrorKind`] if no errno value represents it.
+    pub const fn from_io_error_kind(kind: ErrorKind) -> Result<StandardErrno, ErrorKind> {
+        #[allow(clippy::enum_glob_use)]
+        use StandardErrno::*;
+        Ok(match kind {
+            ErrorKind::PermissionDenied => PermissionDenied,
+            ErrorKind::NotFound => NoSuchFileOrDirectory,
+            ErrorKind::Interrupted => Interrupted,
+            ErrorKind::ArgumentListTooLong => TooBig,
+            ErrorKind::WouldBlock => TryAgain,
+            ErrorKind::OutOfMemory => NoMemory,
+            ErrorKind::ResourceBusy => Busy,
+            ErrorKind::AlreadyExists => FileExists,
+            ErrorKind::CrossesDevices => CrossDeviceLink,
+            ErrorKind::NotADirectory => NotADirectory,
+            ErrorKind::IsADirectory => IsADirectory,
+            ErrorKind::InvalidInput => InvalidArgument,
+            ErrorKind::ExecutableFileBusy => TextFileBusy,
+            ErrorKind::FileTooLarge => FileTooLarge,
+            ErrorKind::StorageFull => NoSpaceLeftOnDevice,
+            ErrorKind::NotSeekable => IllegalSeek,
+            ErrorKind::ReadOnlyFilesystem => ReadOnlyFileSystem,
+            ErrorKind::TooManyLinks => TooManyLinks,
+            ErrorKind::BrokenPipe => BrokenPipe,
+            ErrorKind::Deadlock => Deadlock,
+            ErrorKind::Unsupported => NotSupported,
+            ErrorKind::DirectoryNotEmpty => DirectoryNotEmpty,
+            ErrorKind::InvalidFilename => FileOrPathNameTooLong,
+            ErrorKind::ConnectionReset => ConnectionResetByPeer,
+            ErrorKind::ConnectionRefused => ConnectionRefused,
+            ErrorKind::AddrInUse => AddressAlreadyInUse,
+            ErrorKind::ConnectionAborted => ConnectionAborted,
+            ErrorKind::NetworkUnreachable => NetworkIsUnreachable,
+            ErrorKind::NetworkDown => NetworkInterfaceNotConfigured,
+            ErrorKind::TimedOut => ConnectionTimedOut,
+            ErrorKind::HostUnreachable => HostIsUnreachable,
+            ErrorKind::AddrNotAvailable => AddressNotAvailable,
+            ErrorKind::NotConnected => SocketIsNotConnected,
+            ErrorKind::QuotaExceeded => DiskQuotaExceeded,
+            ErrorKind::StaleNetworkFileHandle => StaleNfsFileHandle,
+            _ => return Err(kind),
+        })
+    }
+}
+
+impl From<StandardErrno> for ErrorKind {
+    fn from(value: StandardErrno) -> Self {
+        value.io_error_kind()
+    }
+}
+
+impl TryFrom<ErrorKind> for StandardErrno {
+    type Error = ErrorKind;
+
+    fn try_from(value: ErrorKind) -> Result<Self, Self::Error> {
+        StandardErrno::from_io_error_kind(value)
+    }
+}
+
+impl From<StandardErrno> for Error {
+    fn from(value: StandardErrno) -> Self {
+        Error::new(value.io_error_kind(), value)
+    }
+}
+
+impl From<ErrorCode> for Error {
+    fn from(value: ErrorCode) -> Self {
+        match value {
+            ErrorCode::Standard(standard) => standard.into(),
+            ErrorCode::Other(_) => Error::other(value),
+        }
+    }
+}
+
+impl From<&Error> for ErrorCode {
+    /// Map an [`Error`] into an [`ErrorCode`].
+    ///
+    /// Errors built from a [`StandardErrno`] or an [`ErrorCode`] are mapped back to it.
+    /// Other errors are mapped from their [`ErrorKind`] if possible.
+    /// Failing that, OS errors map to [`ErrorCode::Other`] with the raw (platform) error code,
+    /// and any other error maps to [`StandardErrno::Io`].
+    fn from(value: &Error) -> Self {
+        if let Some(inner) = value.get_ref() {
+            if let Some(standard) = inner.downcast_ref::<StandardErrno>() {
+                return ErrorCode::Standard(*standard);
+            }
+            if let Some(code) = inner.downcast_ref::<ErrorCode>() {
+                return *code;
+            }
+        }
+        match (
+            StandardErrno::from_io_error_kind(value.kind()),
+            value.raw_os_error(),
+        ) {
+            (Ok(standard), _) => ErrorCode::Standard(standard),
+            (Err(_), Some(raw)) => ErrorCode::Other(crate::Errno(raw)),
+            (Err(_), None) => ErrorCode::Standard(StandardErrno::Io),
+        }
+    }
+}
+
+impl From<Error> for ErrorCode {
+    fn from(value: Error) -> Self {
+        ErrorCode::from(&value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Errno, ErrorCode, StandardErrno};
+    use std::io::{Error, ErrorKind};
+
+    #[test]
+    fn io_error_round_trip() {
+        for errno in [
+            StandardErrno::InvalidArgument,
+            StandardErrno::NoMemoryNeg,
+            StandardErrno::Level2Halted,
+        ] {
+            let error = Error::from(errno);
+            assert_eq!(ErrorCode::from(&error), ErrorCode::Standard(errno));
+        }
+        let other = ErrorCode::Other(Errno(12345));
+        assert_eq!(ErrorCode::from(Error::from(other)), other);
+    }
+
+    #[test]
+    fn io_error_kinds() {
+        assert_eq!(
+            StandardErrno::NoSuchDeviceNeg.io_error_kind(),
+            ErrorKind::Other
+        );
+        assert_eq!(
+            StandardErrno::BusyNeg.io_error_kind(),
+            ErrorKind::ResourceBusy
+        );
+        assert_eq!(
+            StandardErrno::try_from(ErrorKind::InvalidInput),
+            Ok(StandardErrno::InvalidArgument)
+        );
+        assert_eq!(
+            StandardErrno::try_from(ErrorKind::UnexpectedEof),
+            Err(ErrorKind::UnexpectedEof)
+        );
+        assert_eq!(
+            ErrorCode::from(Error::new(ErrorKind::TimedOut, "timeout")),
+            ErrorCode::Standard(StandardErrno::ConnectionTimedOut)
+        );
+        assert_eq!(
+            ErrorCode::from(Error::new(ErrorKind::InvalidData, "garbage")),
+            ErrorCode::Standard(StandardErrno::Io)
+        );
+    }
+}
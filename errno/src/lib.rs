@@ -7,6 +7,9 @@
 //!
 //! It is also perfectly happy to work in `no_std` environments, which other `errno` oriented crates
 //! do not seem to.
+//!
+//! Conversions to and from [`std::io::Error`] are available with the `std` feature, and
+//! conversions to and from `nix::errno::Errno` with the `nix` feature.
 
 #![cfg_attr(not(test), no_std)]
 #![deny(clippy::all, clippy::pedantic)]
@@ -19,6 +22,14 @@
     unsafe_code
 )]
 
+#[cfg(all(feature = "std", not(test)))]
+extern crate std;
+
+#[cfg(feature = "std")]
+mod io;
+#[cfg(all(feature = "nix", target_os = "linux"))]
+mod nix;
+
 /// No error, operation succeeded
 pub const SUCCESS: i32 = 0;
 ///  Not super-user
@@ -1293,6 +1304,15 @@ impl StandardErrno {
         self as i32
     }
 
+    /// Get the positive counterpart of a negative errno, or the errno itself if not negative.
+    #[must_use]
+    pub const fn abs(self) -> StandardErrno {
+        match Self::parse_i32(self.as_i32().wrapping_abs()) {
+            Ok(positive) => positive,
+            Err(_) => self,
+        }
+    }
+
     /// Parse an `i32` value into a `StandardErrno`.
     ///
     /// # Errors
@@ -1552,6 +1572,548 @@ impl StandardErrno {
             _ => Err(value),
         }
     }
+
+    /// All standard errno values, positive ones first.
+    #[allow(clippy::enum_glob_use)]
+    pub const ALL: [StandardErrno; 245] = {
+        use StandardErrno::*;
+        [
+            Success,
+            PermissionDenied,
+            NoSuchFileOrDirectory,
+            NoSuchProcess,
+            Interrupted,
+            Io,
+            NoSuchDeviceOrAddress,
+            TooBig,
+            ExecFormat,
+            BadFileNumber,
+            NoChildProcesses,
+            TryAgain,
+            NoMemory,
+            AccessDenied,
+            BadAddress,
+            BlockDeviceRequired,
+            Busy,
+            FileExists,
+            CrossDeviceLink,
+            NoSuchDevice,
+            NotADirectory,
+            IsADirectory,
+            InvalidArgument,
+            FileTableOverflow,
+            TooManyOpenFiles,
+            NotATty,
+            TextFileBusy,
+            FileTooLarge,
+            NoSpaceLeftOnDevice,
+            IllegalSeek,
+            ReadOnlyFileSystem,
+            TooManyLinks,
+            BrokenPipe,
+            NumberOutOfDomain,
+            ResultTooLarge,
+            NoMessage,
+            IdentifierRemoved,
+            ChannelNumberOutOfRange,
+            Level2NotSynchronized,
+            Level3Halted,
+            Level3Reset,
+            LinkNumberOutOfRange,
+            ProtocolDriverNotAttached,
+            NoCsiStructureAvailable,
+            Level2Halted,
+            Deadlock,
+            NoRecordLocksAvailable,
+            InvalidExchange,
+            InvalidRequestDescriptor,
+            ExchangeFull,
+            NoAnode,
+            InvalidRequestCode,
+            InvalidSlot,
+            FileLockingDeadlock,
+            BadFontFileFormat,
+            DeviceNotAStream,
+            NoDataAvailable,
+            TimerExpired,
+            OutOfStreamsResources,
+            MachineNotOnTheNetwork,
+            PackageNotInstalled,
+            ObjectIsRemote,
+            LinkSevered,
+            AdvertiseError,
+            SrmountError,
+            CommunicationErrorOnSend,
+            ProtocolError,
+            MultihopAttempted,
+            InodeIsRemote,
+            CrossMountPoint,
+            TryingToReadUnreadableMessage,
+            InappropriateFileTypeOrFormat,
+            GivenLogNameNotUnique,
+            FdInvalidForThisOperation,
+            RemoteAddressChanged,
+            CantAccessNeededSharedLibrary,
+            AccessingCorruptedSharedLibrary,
+            LibSectionInAOutCorrupted,
+            AttemptingToLinkInTooManyLibs,
+            AttemptingToExecASharedLibrary,
+            FunctionNotImplemented,
+            NoMoreFiles,
+            DirectoryNotEmpty,
+            FileOrPathNameTooLong,
+            TooManySymbolicLinks,
+            OperationNotSupportedOnTransportEndpoint,
+            ProtocolFamilyNotSupported,
+            ConnectionResetByPeer,
+            NoBufferSpaceAvailable,
+            AddressFamilyNotSupportedByProtocolFamily,
+            ProtocolWrongTypeForSocket,
+            SocketOperationOnNonSocket,
+            ProtocolNotAvailable,
+            CantSendAfterSocketShutdown,
+            ConnectionRefused,
+            AddressAlreadyInUse,
+            ConnectionAborted,
+            NetworkIsUnreachable,
+            NetworkInterfaceNotConfigured,
+            ConnectionTimedOut,
+            HostIsDown,
+            HostIsUnreachable,
+            ConnectionAlreadyInProgress,
+            SocketAlreadyConnected,
+            DestinationAddressRequired,
+            MessageTooLong,
+            UnknownProtocol,
+            SocketTypeNotSupported,
+            AddressNotAvailable,
+            NetworkDroppedConnectionOnReset,
+            SocketIsAlreadyConnected,
+            SocketIsNotConnected,
+            TooManyReferences,
+            ProcessLimitExceeded,
+            TooManyUsers,
+            DiskQuotaExceeded,
+            StaleNfsFileHandle,
+            NotSupported,
+            NoMedium,
+            NoShare,
+            CaseClash,
+            IllegalSequence,
+            Overflow,
+            PermissionDeniedNeg,
+            NoSuchFileOrDirectoryNeg,
+            NoSuchProcessNeg,
+            InterruptedNeg,
+            IoNeg,
+            NoSuchDeviceOrAddressNeg,
+            TooBigNeg,
+            ExecFormatNeg,
+            BadFileNumberNeg,
+            NoChildProcessesNeg,
+            TryAgainNeg,
+            NoMemoryNeg,
+            AccessDeniedNeg,
+            BadAddressNeg,
+            BlockDeviceRequiredNeg,
+            BusyNeg,
+            FileExistsNeg,
+            CrossDeviceLinkNeg,
+            NoSuchDeviceNeg,
+            NotADirectoryNeg,
+            IsADirectoryNeg,
+            InvalidArgumentNeg,
+            FileTableOverflowNeg,
+            TooManyOpenFilesNeg,
+            NotATtyNeg,
+            TextFileBusyNeg,
+            FileTooLargeNeg,
+            NoSpaceLeftOnDeviceNeg,
+            IllegalSeekNeg,
+            ReadOnlyFileSystemNeg,
+            TooManyLinksNeg,
+            BrokenPipeNeg,
+            NumberOutOfDomainNeg,
+            ResultTooLargeNeg,
+            NoMessageNeg,
+            IdentifierRemovedNeg,
+            ChannelNumberOutOfRangeNeg,
+            Level2NotSynchronizedNeg,
+            Level3HaltedNeg,
+            Level3ResetNeg,
+            LinkNumberOutOfRangeNeg,
+            ProtocolDriverNotAttachedNeg,
+            NoCsiStructureAvailableNeg,
+            Level2HaltedNeg,
+            DeadlockNeg,
+            NoRecordLocksAvailableNeg,
+            InvalidExchangeNeg,
+            InvalidRequestDescriptorNeg,
+            ExchangeFullNeg,
+            NoAnodeNeg,
+            InvalidRequestCodeNeg,
+            InvalidSlotNeg,
+            FileLockingDeadlockNeg,
+            BadFontFileFormatNeg,
+            DeviceNotAStreamNeg,
+            NoDataAvailableNeg,
+            TimerExpiredNeg,
+            OutOfStreamsResourcesNeg,
+            MachineNotOnTheNetworkNeg,
+            PackageNotInstalledNeg,
+            ObjectIsRemoteNeg,
+            LinkSeveredNeg,
+            AdvertiseErrorNeg,
+            SrmountErrorNeg,
+            CommunicationErrorOnSendNeg,
+            ProtocolErrorNeg,
+            MultihopAttemptedNeg,
+            InodeIsRemoteNeg,
+            CrossMountPointNeg,
+            TryingToReadUnreadableMessageNeg,
+            InappropriateFileTypeOrFormatNeg,
+            GivenLogNameNotUniqueNeg,
+            FdInvalidForThisOperationNeg,
+            RemoteAddressChangedNeg,
+            CantAccessNeededSharedLibraryNeg,
+            AccessingCorruptedSharedLibraryNeg,
+            LibSectionInAOutCorruptedNeg,
+            AttemptingToLinkInTooManyLibsNeg,
+            AttemptingToExecASharedLibraryNeg,
+            FunctionNotImplementedNeg,
+            NoMoreFilesNeg,
+            DirectoryNotEmptyNeg,
+            FileOrPathNameTooLongNeg,
+            TooManySymbolicLinksNeg,
+            OperationNotSupportedOnTransportEndpointNeg,
+            ProtocolFamilyNotSupportedNeg,
+            ConnectionResetByPeerNeg,
+            NoBufferSpaceAvailableNeg,
+            AddressFamilyNotSupportedByProtocolFamilyNeg,
+            ProtocolWrongTypeForSocketNeg,
+            SocketOperationOnNonSocketNeg,
+            ProtocolNotAvailableNeg,
+            CantSendAfterSocketShutdownNeg,
+            ConnectionRefusedNeg,
+            AddressAlreadyInUseNeg,
+            ConnectionAbortedNeg,
+            NetworkIsUnreachableNeg,
+            NetworkInterfaceNotConfiguredNeg,
+            ConnectionTimedOutNeg,
+            HostIsDownNeg,
+            HostIsUnreachableNeg,
+            ConnectionAlreadyInProgressNeg,
+            SocketAlreadyConnectedNeg,
+            DestinationAddressRequiredNeg,
+            MessageTooLongNeg,
+            UnknownProtocolNeg,
+            SocketTypeNotSupportedNeg,
+            AddressNotAvailableNeg,
+            NetworkDroppedConnectionOnResetNeg,
+            SocketIsAlreadyConnectedNeg,
+            SocketIsNotConnectedNeg,
+            TooManyReferencesNeg,
+            ProcessLimitExceededNeg,
+            TooManyUsersNeg,
+            DiskQuotaExceededNeg,
+            StaleNfsFileHandleNeg,
+            NotSupportedNeg,
+            NoMediumNeg,
+            NoShareNeg,
+            CaseClashNeg,
+            IllegalSequenceNeg,
+            OverflowNeg,
+        ]
+    };
+
+    /// Iterate over all standard errno values.
+    pub fn iter() -> impl ExactSizeIterator<Item = StandardErrno> + Clone {
+        Self::ALL.into_iter()
+    }
+
+    /// Get the canonical symbolic name of a standard errno (e.g. `"EINVAL"`).
+    ///
+    /// The names of negative values are prefixed with a minus sign (e.g. `"-EINVAL"`).
+    #[must_use]
+    #[allow(clippy::too_many_lines)]
+    pub const fn name(self) -> &'static str {
+        #[allow(clippy::enum_glob_use)]
+        use StandardErrno::*;
+        match self {
+            Success => "SUCCESS",
+            PermissionDenied => "EPERM",
+            NoSuchFileOrDirectory => "ENOENT",
+            NoSuchProcess => "ESRCH",
+            Interrupted => "EINTR",
+            Io => "EIO",
+            NoSuchDeviceOrAddress => "ENXIO",
+            TooBig => "E2BIG",
+            ExecFormat => "ENOEXEC",
+            BadFileNumber => "EBADF",
+            NoChildProcesses => "ECHILD",
+            TryAgain => "EAGAIN",
+            NoMemory => "ENOMEM",
+            AccessDenied => "EACCES",
+            BadAddress => "EFAULT",
+            BlockDeviceRequired => "ENOTBLK",
+            Busy => "EBUSY",
+            FileExists => "EEXIST",
+            CrossDeviceLink => "EXDEV",
+            NoSuchDevice => "ENODEV",
+            NotADirectory => "ENOTDIR",
+            IsADirectory => "EISDIR",
+            InvalidArgument => "EINVAL",
+            FileTableOverflow => "ENFILE",
+            TooManyOpenFiles => "EMFILE",
+            NotATty => "ENOTTY",
+            TextFileBusy => "ETXTBSY",
+            FileTooLarge => "EFBIG",
+            NoSpaceLeftOnDevice => "ENOSPC",
+            IllegalSeek => "ESPIPE",
+            ReadOnlyFileSystem => "EROFS",
+            TooManyLinks => "EMLINK",
+            BrokenPipe => "EPIPE",
+            NumberOutOfDomain => "EDOM",
+            ResultTooLarge => "ERANGE",
+            NoMessage => "ENOMSG",
+            IdentifierRemoved => "EIDRM",
+            ChannelNumberOutOfRange => "ECHRNG",
+            Level2NotSynchronized => "EL2NSYNC",
+            Level3Halted => "EL3HLT",
+            Level3Reset => "EL3RST",
+            LinkNumberOutOfRange => "ELNRNG",
+            ProtocolDriverNotAttached => "EUNATCH",
+            NoCsiStructureAvailable => "ENOCSI",
+            Level2Halted => "EL2HLT",
+            Deadlock => "EDEADLK",
+            NoRecordLocksAvailable => "ENOLCK",
+            InvalidExchange => "EBADE",
+            InvalidRequestDescriptor => "EBADR",
+            ExchangeFull => "EXFULL",
+            NoAnode => "ENOANO",
+            InvalidRequestCode => "EBADRQC",
+            InvalidSlot => "EBADSLT",
+            FileLockingDeadlock => "EDEADLOCK",
+            BadFontFileFormat => "EBFONT",
+            DeviceNotAStream => "ENOSTR",
+            NoDataAvailable => "ENODATA",
+            TimerExpired => "ETIME",
+            OutOfStreamsResources => "ENOSR",
+            MachineNotOnTheNetwork => "ENONET",
+            PackageNotInstalled => "ENOPKG",
+            ObjectIsRemote => "EREMOTE",
+            LinkSevered => "ENOLINK",
+            AdvertiseError => "EADV",
+            SrmountError => "ESRMNT",
+            CommunicationErrorOnSend => "ECOMM",
+            ProtocolError => "EPROTO",
+            MultihopAttempted => "EMULTIHOP",
+            InodeIsRemote => "ELBIN",
+            CrossMountPoint => "EDOTDOT",
+            TryingToReadUnreadableMessage => "EBADMSG",
+            InappropriateFileTypeOrFormat => "EFTYPE",
+            GivenLogNameNotUnique => "ENOTUNIQ",
+            FdInvalidForThisOperation => "EBADFD",
+            RemoteAddressChanged => "EREMCHG",
+            CantAccessNeededSharedLibrary => "ELIBACC",
+            AccessingCorruptedSharedLibrary => "ELIBBAD",
+            LibSectionInAOutCorrupted => "ELIBSCN",
+            AttemptingToLinkInTooManyLibs => "ELIBMAX",
+            AttemptingToExecASharedLibrary => "ELIBEXEC",
+            FunctionNotImplemented => "ENOSYS",
+            NoMoreFiles => "ENMFILE",
+            DirectoryNotEmpty => "ENOTEMPTY",
+            FileOrPathNameTooLong => "ENAMETOOLONG",
+            TooManySymbolicLinks => "ELOOP",
+            OperationNotSupportedOnTransportEndpoint => "EOPNOTSUPP",
+            ProtocolFamilyNotSupported => "EPFNOSUPPORT",
+            ConnectionResetByPeer => "ECONNRESET",
+            NoBufferSpaceAvailable => "ENOBUFS",
+            AddressFamilyNotSupportedByProtocolFamily => "EAFNOSUPPORT",
+            ProtocolWrongTypeForSocket => "EPROTOTYPE",
+            SocketOperationOnNonSocket => "ENOTSOCK",
+            ProtocolNotAvailable => "ENOPROTOOPT",
+            CantSendAfterSocketShutdown => "ESHUTDOWN",
+            ConnectionRefused => "ECONNREFUSED",
+            AddressAlreadyInUse => "EADDRINUSE",
+            ConnectionAborted => "ECONNABORTED",
+            NetworkIsUnreachable => "ENETUNREACH",
+            NetworkInterfaceNotConfigured => "ENETDOWN",
+            ConnectionTimedOut => "ETIMEDOUT",
+            HostIsDown => "EHOSTDOWN",
+            HostIsUnreachable => "EHOSTUNREACH",
+            ConnectionAlreadyInProgress => "EINPROGRESS",
+            SocketAlreadyConnected => "EALREADY",
+            DestinationAddressRequired => "EDESTADDRREQ",
+            MessageTooLong => "EMSGSIZE",
+            UnknownProtocol => "EPROTONOSUPPORT",
+            SocketTypeNotSupported => "ESOCKTNOSUPPORT",
+            AddressNotAvailable => "EADDRNOTAVAIL",
+            NetworkDroppedConnectionOnReset => "ENETRESET",
+            SocketIsAlreadyConnected => "EISCONN",
+            SocketIsNotConnected => "ENOTCONN",
+            TooManyReferences => "ETOOMANYREFS",
+            ProcessLimitExceeded => "EPROCLIM",
+            TooManyUsers => "EUSERS",
+            DiskQuotaExceeded => "EDQUOT",
+            StaleNfsFileHandle => "ESTALE",
+            NotSupported => "ENOTSUP",
+            NoMedium => "ENOMEDIUM",
+            NoShare => "ENOSHARE",
+            CaseClash => "ECASECLASH",
+            IllegalSequence => "EILSEQ",
+            Overflow => "EOVERFLOW",
+            PermissionDeniedNeg => "-EPERM",
+            NoSuchFileOrDirectoryNeg => "-ENOENT",
+            NoSuchProcessNeg => "-ESRCH",
+            InterruptedNeg => "-EINTR",
+            IoNeg => "-EIO",
+            NoSuchDeviceOrAddressNeg => "-ENXIO",
+            TooBigNeg => "-E2BIG",
+            ExecFormatNeg => "-ENOEXEC",
+            BadFileNumberNeg => "-EBADF",
+            NoChildProcessesNeg => "-ECHILD",
+            TryAgainNeg => "-EAGAIN",
+            NoMemoryNeg => "-ENOMEM",
+            AccessDeniedNeg => "-EACCES",
+            BadAddressNeg => "-EFAULT",
+            BlockDeviceRequiredNeg => "-ENOTBLK",
+            BusyNeg => "-EBUSY",
+            FileExistsNeg => "-EEXIST",
+            CrossDeviceLinkNeg => "-EXDEV",
+            NoSuchDeviceNeg => "-ENODEV",
+            NotADirectoryNeg => "-ENOTDIR",
+            IsADirectoryNeg => "-EISDIR",
+            InvalidArgumentNeg => "-EINVAL",
+            FileTableOverflowNeg => "-ENFILE",
+            TooManyOpenFilesNeg => "-EMFILE",
+            NotATtyNeg => "-ENOTTY",
+            TextFileBusyNeg => "-ETXTBSY",
+            FileTooLargeNeg => "-EFBIG",
+            NoSpaceLeftOnDeviceNeg => "-ENOSPC",
+            IllegalSeekNeg => "-ESPIPE",
+            ReadOnlyFileSystemNeg => "-EROFS",
+            TooManyLinksNeg => "-EMLINK",
+            BrokenPipeNeg => "-EPIPE",
+            NumberOutOfDomainNeg => "-EDOM",
+            ResultTooLargeNeg => "-ERANGE",
+            NoMessageNeg => "-ENOMSG",
+            IdentifierRemovedNeg => "-EIDRM",
+            ChannelNumberOutOfRangeNeg => "-ECHRNG",
+            Level2NotSynchronizedNeg => "-EL2NSYNC",
+            Level3HaltedNeg => "-EL3HLT",
+            Level3ResetNeg => "-EL3RST",
+            LinkNumberOutOfRangeNeg => "-ELNRNG",
+            ProtocolDriverNotAttachedNeg => "-EUNATCH",
+            NoCsiStructureAvailableNeg => "-ENOCSI",
+            Level2HaltedNeg => "-EL2HLT",
+            DeadlockNeg => "-EDEADLK",
+            NoRecordLocksAvailableNeg => "-ENOLCK",
+            InvalidExchangeNeg => "-EBADE",
+            InvalidRequestDescriptorNeg => "-EBADR",
+            ExchangeFullNeg => "-EXFULL",
+            NoAnodeNeg => "-ENOANO",
+            InvalidRequestCodeNeg => "-EBADRQC",
+            InvalidSlotNeg => "-EBADSLT",
+            FileLockingDeadlockNeg => "-EDEADLOCK",
+            BadFontFileFormatNeg => "-EBFONT",
+            DeviceNotAStreamNeg => "-ENOSTR",
+            NoDataAvailableNeg => "-ENODATA",
+            TimerExpiredNeg => "-ETIME",
+            OutOfStreamsResourcesNeg => "-ENOSR",
+            MachineNotOnTheNetworkNeg => "-ENONET",
+            PackageNotInstalledNeg => "-ENOPKG",
+            ObjectIsRemoteNeg => "-EREMOTE",
+            LinkSeveredNeg => "-ENOLINK",
+            AdvertiseErrorNeg => "-EADV",
+            SrmountErrorNeg => "-ESRMNT",
+            CommunicationErrorOnSendNeg => "-ECOMM",
+            ProtocolErrorNeg => "-EPROTO",
+            MultihopAttemptedNeg => "-EMULTIHOP",
+            InodeIsRemoteNeg => "-ELBIN",
+            CrossMountPointNeg => "-EDOTDOT",
+            TryingToReadUnreadableMessageNeg => "-EBADMSG",
+            InappropriateFileTypeOrFormatNeg => "-EFTYPE",
+            GivenLogNameNotUniqueNeg => "-ENOTUNIQ",
+            FdInvalidForThisOperationNeg => "-EBADFD",
+            RemoteAddressChangedNeg => "-EREMCHG",
+            CantAccessNeededSharedLibraryNeg => "-ELIBACC",
+            AccessingCorruptedSharedLibraryNeg => "-ELIBBAD",
+            LibSectionInAOutCorruptedNeg => "-ELIBSCN",
+            AttemptingToLinkInTooManyLibsNeg => "-ELIBMAX",
+            AttemptingToExecASharedLibraryNeg => "-ELIBEXEC",
+            FunctionNotImplementedNeg => "-ENOSYS",
+            NoMoreFilesNeg => "-ENMFILE",
+            DirectoryNotEmptyNeg => "-ENOTEMPTY",
+            FileOrPathNameTooLongNeg => "-ENAMETOOLONG",
+            TooManySymbolicLinksNeg => "-ELOOP",
+            OperationNotSupportedOnTransportEndpointNeg => "-EOPNOTSUPP",
+            ProtocolFamilyNotSupportedNeg => "-EPFNOSUPPORT",
+            ConnectionResetByPeerNeg => "-ECONNRESET",
+            NoBufferSpaceAvailableNeg => "-ENOBUFS",
+            AddressFamilyNotSupportedByProtocolFamilyNeg => "-EAFNOSUPPORT",
+            ProtocolWrongTypeForSocketNeg => "-EPROTOTYPE",
+            SocketOperationOnNonSocketNeg => "-ENOTSOCK",
+            ProtocolNotAvailableNeg => "-ENOPROTOOPT",
+            CantSendAfterSocketShutdownNeg => "-ESHUTDOWN",
+            ConnectionRefusedNeg => "-ECONNREFUSED",
+            AddressAlreadyInUseNeg => "-EADDRINUSE",
+            ConnectionAbortedNeg => "-ECONNABORTED",
+            NetworkIsUnreachableNeg => "-ENETUNREACH",
+            NetworkInterfaceNotConfiguredNeg => "-ENETDOWN",
+            ConnectionTimedOutNeg => "-ETIMEDOUT",
+            HostIsDownNeg => "-EHOSTDOWN",
+            HostIsUnreachableNeg => "-EHOSTUNREACH",
+            ConnectionAlreadyInProgressNeg => "-EINPROGRESS",
+            SocketAlreadyConnectedNeg => "-EALREADY",
+            DestinationAddressRequiredNeg => "-EDESTADDRREQ",
+            MessageTooLongNeg => "-EMSGSIZE",
+            UnknownProtocolNeg => "-EPROTONOSUPPORT",
+            SocketTypeNotSupportedNeg => "-ESOCKTNOSUPPORT",
+            AddressNotAvailableNeg => "-EADDRNOTAVAIL",
+            NetworkDroppedConnectionOnResetNeg => "-ENETRESET",
+            SocketIsAlreadyConnectedNeg => "-EISCONN",
+            SocketIsNotConnectedNeg => "-ENOTCONN",
+            TooManyReferencesNeg => "-ETOOMANYREFS",
+            ProcessLimitExceededNeg => "-EPROCLIM",
+            TooManyUsersNeg => "-EUSERS",
+            DiskQuotaExceededNeg => "-EDQUOT",
+            StaleNfsFileHandleNeg => "-ESTALE",
+            NotSupportedNeg => "-ENOTSUP",
+            NoMediumNeg => "-ENOMEDIUM",
+            NoShareNeg => "-ENOSHARE",
+            CaseClashNeg => "-ECASECLASH",
+            IllegalSequenceNeg => "-EILSEQ",
+            OverflowNeg => "-EOVERFLOW",
+        }
+    }
+
+    /// Look up a standard errno by its canonical symbolic name (e.g. `"EINVAL"` or `"-EINVAL"`).
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<StandardErrno> {
+        Self::iter().find(|errno| errno.name() == name)
+    }
+}
+
+/// Error returned when parsing an unknown errno name
+#[derive(Debug, Copy, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("Unknown errno name")]
+pub struct UnknownErrnoName;
+
+impl core::str::FromStr for StandardErrno {
+    type Err = UnknownErrnoName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StandardErrno::from_name(s).ok_or(UnknownErrnoName)
+    }
+}
+
+impl TryFrom<&str> for StandardErrno {
+    type Error = UnknownErrnoName;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
 }
 
 /// Newtype wrapper around an errno value.
@@ -1635,3 +2197,21 @@ impl ErrorCode {
         Self::parse_i32(val.into())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{StandardErrno, UnknownErrnoName};
+
+    #[test]
+    fn names_round_trip() {
+        assert_eq!(StandardErrno::iter().len(), StandardErrno::ALL.len());
+        for errno in StandardErrno::iter() {
+            assert_eq!(errno.name().parse(), Ok(errno));
+            assert_eq!(StandardErrno::parse_i32(errno.as_i32()), Ok(errno));
+        }
+        assert_eq!(StandardErrno::InvalidArgument.name(), "EINVAL");
+        assert_eq!(StandardErrno::InvalidArgumentNeg.name(), "-EINVAL");
+        assert_eq!("EINVAL".parse(), Ok(StandardErrno::InvalidArgument));
+        assert_eq!("einval".parse::<StandardErrno>(), Err(UnknownErrnoName));
+    }
+}
@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Conversions between errno values and [`nix::errno::Errno`].
+//!
+//! Values are mapped by symbolic name, since the numeric values of this crate are not
+//! necessarily those of the host platform.
+
+use crate::{Errno, ErrorCode, StandardErrno};
+use ::nix::errno::Errno as NixErrno;
+
+/// Generate the conversions between `StandardErrno` and `nix::errno::Errno`.
+/// Pairs in `one_way` are only used to convert into `nix::errno::Errno`, for values which are
+/// aliases of another value on Linux.
+macro_rules! nix_errno_map {
+    (
+        both: { $($std:ident <=> $nix:ident),* $(,)? }
+        one_way: { $($std_only:ident => $nix_only:ident),* $(,)? }
+    ) => {
+        impl StandardErrno {
+            /// Get the [`nix::errno::Errno`] with the same meaning as this errno.
+            ///
+            /// Negative values map to the value of their positive counterpart.
+            #[must_use]
+            pub const fn to_nix(self) -> Option<NixErrno> {
+                match self.abs() {
+                    $(StandardErrno::$std => Some(NixErrno::$nix),)*
+                    $(StandardErrno::$std_only => Some(NixErrno::$nix_only),)*
+                    _ => None,
+                }
+            }
+
+            /// Get the (positive) errno with the same meaning as a [`nix::errno::Errno`], if any.
+            #[must_use]
+            pub const fn from_nix(value: NixErrno) -> Option<StandardErrno> {
+                match value {
+                    $(NixErrno::$nix => Some(StandardErrno::$std),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+nix_errno_map! {
+    both: {
+        PermissionDenied <=> EPERM,
+        NoSuchFileOrDirectory <=> ENOENT,
+        NoSuchProcess <=> ESRCH,
+        Interrupted <=> EINTR,
+        Io <=> EIO,
+        NoSuchDeviceOrAddress <=> ENXIO,
+        TooBig <=> E2BIG,
+        ExecFormat <=> ENOEXEC,
+        BadFileNumber <=> EBADF,
+        NoChildProcesses <=> ECHILD,
+        TryAgain <=> EAGAIN,
+        NoMemory <=> ENOMEM,
+        AccessDenied <=> EACCES,
+        BadAddress <=> EFAULT,
+        BlockDeviceRequired <=> ENOTBLK,
+        Busy <=> EBUSY,
+        FileExists <=> EEXIST,
+        CrossDeviceLink <=> EXDEV,
+        NoSuchDevice <=> ENODEV,
+        NotADirectory <=> ENOTDIR,
+        IsADirectory <=> EISDIR,
+        InvalidArgument <=> EINVAL,
+        FileTableOverflow <=> ENFILE,
+        TooManyOpenFiles <=> EMFILE,
+        NotATty <=> ENOTTY,
+        TextFileBusy <=> ETXTBSY,
+        FileTooLarge <=> EFBIG,
+        NoSpaceLeftOnDevice <=> ENOSPC,
+        IllegalSeek <=> ESPIPE,
+        ReadOnlyFileSystem <=> EROFS,
+        TooManyLinks <=> EMLINK,
+        BrokenPipe <=> EPIPE,
+        NumberOutOfDomain <=> EDOM,
+        ResultTooLarge <=> ERANGE,
+        NoMessage <=> ENOMSG,
+        IdentifierRemoved <=> EIDRM,
+        ChannelNumberOutOfRange <=> ECHRNG,
+        Level2NotSynchronized <=> EL2NSYNC,
+        Level3Halted <=> EL3HLT,
+        Level3Reset <=> EL3RST,
+        LinkNumberOutOfRange <=> ELNRNG,
+        ProtocolDriverNotAttached <=> EUNATCH,
+        NoCsiStructureAvailable <=> ENOCSI,
+        Level2Halted <=> EL2HLT,
+        Deadlock <=> EDEADLK,
+        NoRecordLocksAvailable <=> ENOLCK,
+        InvalidExchange <=> EBADE,
+        InvalidRequestDescriptor <=> EBADR,
+        ExchangeFull <=> EXFULL,
+        NoAnode <=> ENOANO,
+        InvalidRequestCode <=> EBADRQC,
+        InvalidSlot <=> EBADSLT,
+        BadFontFileFormat <=> EBFONT,
+        DeviceNotAStream <=> ENOSTR,
+        NoDataAvailable <=> ENODATA,
+        TimerExpired <=> ETIME,
+        OutOfStreamsResources <=> ENOSR,
+        MachineNotOnTheNetwork <=> ENONET,
+        PackageNotInstalled <=> ENOPKG,
+        ObjectIsRemote <=> EREMOTE,
+        LinkSevered <=> ENOLINK,
+        AdvertiseError <=> EADV,
+        SrmountError <=> ESRMNT,
+        CommunicationErrorOnSend <=> ECOMM,
+        ProtocolError <=> EPROTO,
+        MultihopAttempted <=> EMULTIHOP,
+        CrossMountPoint <=> EDOTDOT,
+        TryingToReadUnreadableMessage <=> EBADMSG,
+        GivenLogNameNotUnique <=> ENOTUNIQ,
+        FdInvalidForThisOperation <=> EBADFD,
+        RemoteAddressChanged <=> EREMCHG,
+        CantAccessNeededSharedLibrary <=> ELIBACC,
+        AccessingCorruptedSharedLibrary <=> ELIBBAD,
+        LibSectionInAOutCorrupted <=> ELIBSCN,
+        AttemptingToLinkInTooManyLibs <=> ELIBMAX,
+        AttemptingToExecASharedLibrary <=> ELIBEXEC,
+        FunctionNotImplemented <=> ENOSYS,
+        DirectoryNotEmpty <=> ENOTEMPTY,
+        FileOrPathNameTooLong <=> ENAMETOOLONG,
+        TooManySymbolicLinks <=> ELOOP,
+        OperationNotSupportedOnTransportEndpoint <=> EOPNOTSUPP,
+        ProtocolFamilyNotSupported <=> EPFNOSUPPORT,
+        ConnectionResetByPeer <=> ECONNRESET,
+        NoBufferSpaceAvailable <=> ENOBUFS,
+        AddressFamilyNotSupportedByProtocolFamily <=> EAFNOSUPPORT,
+        ProtocolWrongTypeForSocket <=> EPROTOTYPE,
+        SocketOperationOnNonSocket <=> ENOTSOCK,
+        ProtocolNotAvailable <=> ENOPROTOOPT,
+        CantSendAfterSocketShutdown <=> ESHUTDOWN,
+        ConnectionRefused <=> ECONNREFUSED,
+        AddressAlreadyInUse <=> EADDRINUSE,
+        ConnectionAborted <=> ECONNABORTED,
+        NetworkIsUnreachable <=> ENETUNREACH,
+        NetworkInterfaceNotConfigured <=> ENETDOWN,
+        ConnectionTimedOut <=> ETIMEDOUT,
+        HostIsDown <=> EHOSTDOWN,
+        HostIsUnreachable <=> EHOSTUNREACH,
+        ConnectionAlreadyInProgress <=> EINPROGRESS,
+        SocketAlreadyConnected <=> EALREADY,
+        DestinationAddressRequired <=> EDESTADDRREQ,
+        MessageTooLong <=> EMSGSIZE,
+        UnknownProtocol <=> EPROTONOSUPPORT,
+        SocketTypeNotSupported <=> ESOCKTNOSUPPORT,
+        AddressNotAvailable <=> EADDRNOTAVAIL,
+        NetworkDroppedConnectionOnReset <=> ENETRESET,
+        SocketIsAlreadyConnected <=> EISCONN,
+        SocketIsNotConnected <=> ENOTCONN,
+        TooManyReferences <=> ETOOMANYREFS,
+        TooManyUsers <=> EUSERS,
+        DiskQuotaExceeded <=> EDQUOT,
+        StaleNfsFileHandle <=> ESTALE,
+        NoMedium <=> ENOMEDIUM,
+        IllegalSequence <=> EILSEQ,
+        Overflow <=> EOVERFLOW,
+    }
+    one_way: {
+        FileLockingDeadlock => EDEADLK,
+        NotSupported => EOPNOTSUPP,
+    }
+}
+
+impl TryFrom<StandardErrno> for NixErrno {
+    type Error = StandardErrno;
+
+    fn try_from(value: StandardErrno) -> Result<Self, Self::Error> {
+        value.to_nix().ok_or(value)
+    }
+}
+
+impl From<NixErrno> for ErrorCode {
+    /// Map a [`nix::errno::Errno`] into an [`ErrorCode`].
+    ///
+    /// Values without a standard counterpart map to [`ErrorCode::Other`] with the raw
+    /// (platform) error code.
+    fn from(value: NixErrno) -> Self {
+        match StandardErrno::from_nix(value) {
+            Some(standard) => ErrorCode::Standard(standard),
+            None => ErrorCode::Other(Errno(value as i32)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Errno, ErrorCode, StandardErrno};
+    use ::nix::errno::Errno as NixErrno;
+
+    #[test]
+    fn nix_errno_mapping() {
+        assert_eq!(
+            StandardErrno::InvalidArgumentNeg.to_nix(),
+            Some(NixErrno::EINVAL)
+        );
+        assert_eq!(
+            StandardErrno::FunctionNotImplemented.to_nix(),
+            Some(NixErrno::ENOSYS)
+        );
+        assert_eq!(StandardErrno::CaseClash.to_nix(), None);
+        assert_eq!(
+            ErrorCode::from(NixErrno::ENOSYS),
+            ErrorCode::Standard(StandardErrno::FunctionNotImplemented)
+        );
+        assert_eq!(
+            ErrorCode::from(NixErrno::EHWPOISON),
+            ErrorCode::Other(Errno(NixErrno::EHWPOISON as i32))
+        );
+    }
+}
@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Source of time for flow aging.
+//!
+//! Expiry decisions take the current time from a [`Clock`] rather than from [`Instant::now`]
+//! directly, so that tests can substitute a clock they control and exercise expiry logic without
+//! sleeping.
+
+use std::fmt::Debug;
+use std::time::Instant;
+
+/// A source of monotonic time
+pub trait Clock: Debug + Send + Sync {
+    /// The current time
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
@@ -8,7 +8,7 @@ use concurrency::sync::RwLock;
 
 use crate::{AtomicInstant, FlowInfoItem};
 
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
 
 #[derive(Debug, thiserror::Error)]
 pub enum FlowInfoError {
@@ -103,6 +103,32 @@ impl From<FlowStatus> for AtomicFlowStatus {
     }
 }
 
+/// The direction of a packet relative to the flow it belongs to.
+///
+/// For bidirectional flows, the forward direction is the direction of the key the flow
+/// was created with, usually the direction of the first packet of the connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlowDirection {
+    Forward,
+    Reverse,
+}
+
+impl FlowDirection {
+    #[must_use]
+    pub fn reverse(self) -> Self {
+        match self {
+            FlowDirection::Forward => FlowDirection::Reverse,
+            FlowDirection::Reverse => FlowDirection::Forward,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct DirectionCounters {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+}
+
 #[derive(Debug, Default)]
 pub struct FlowInfoLocked {
     // We need this to use downcast to avoid circular dependencies between crates.
@@ -115,6 +141,10 @@ pub struct FlowInfoLocked {
 
 #[derive(Debug)]
 pub struct FlowInfo {
+    created_at: Instant,
+    last_seen: AtomicInstant,
+    // Indexed by FlowDirection
+    counters: [DirectionCounters; 2],
     expires_at: AtomicInstant,
     status: AtomicFlowStatus,
     pub locked: RwLock<FlowInfoLocked>,
@@ -126,13 +156,96 @@ pub struct FlowInfo {
 impl FlowInfo {
     #[must_use]
     pub fn new(expires_at: Instant) -> Self {
+        Self::new_at(Instant::now(), expires_at)
+    }
+
+    /// Create a flow at time `now`, as given by a [`Clock`](crate::Clock).
+    #[must_use]
+    pub fn new_at(now: Instant, expires_at: Instant) -> Self {
         Self {
+            created_at: now,
+            last_seen: AtomicInstant::new(now),
+            counters: Default::default(),
             expires_at: AtomicInstant::new(expires_at),
             status: AtomicFlowStatus::from(FlowStatus::Active),
             locked: RwLock::new(FlowInfoLocked::default()),
         }
     }
 
+    /// The time at which this flow was created.
+    #[must_use]
+    pub fn created_at(&self) -> Instant {
+        self.created_at
+    }
+
+    /// The age of this flow relative to `now`.
+    ///
+    /// Returns a zero duration if `now` is before the creation time.
+    #[must_use]
+    pub fn age(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.created_at)
+    }
+
+    /// The time at which the first packet of this flow was seen.
+    ///
+    /// This is the creation time of the flow.
+    #[must_use]
+    pub fn first_seen(&self) -> Instant {
+        self.created_at
+    }
+
+    /// The time at which the last packet of this flow was seen, or the creation time
+    /// of the flow if no packet was recorded.
+    #[must_use]
+    pub fn last_seen(&self) -> Instant {
+        self.last_seen.load(Ordering::Relaxed)
+    }
+
+    fn counters(&self, direction: FlowDirection) -> &DirectionCounters {
+        match direction {
+            FlowDirection::Forward => &self.counters[0],
+            FlowDirection::Reverse => &self.counters[1],
+        }
+    }
+
+    /// The number of packets recorded for this flow, in both directions.
+    #[must_use]
+    pub fn packets(&self) -> u64 {
+        self.packets_in(FlowDirection::Forward) + self.packets_in(FlowDirection::Reverse)
+    }
+
+    /// The number of bytes recorded for this flow, in both directions.
+    #[must_use]
+    pub fn bytes(&self) -> u64 {
+        self.bytes_in(FlowDirection::Forward) + self.bytes_in(FlowDirection::Reverse)
+    }
+
+    /// The number of packets recorded for this flow in the given direction.
+    #[must_use]
+    pub fn packets_in(&self, direction: FlowDirection) -> u64 {
+        self.counters(direction).packets.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes recorded for this flow in the given direction.
+    #[must_use]
+    pub fn bytes_in(&self, direction: FlowDirection) -> u64 {
+        self.counters(direction).bytes.load(Ordering::Relaxed)
+    }
+
+    /// Account for a packet of `bytes` bytes seen at time `now` in the given direction.
+    ///
+    /// # Thread Safety
+    ///
+    /// This method is thread-safe. Counters are updated atomically, but the packet
+    /// and byte counters are not updated together, so a concurrent reader may see
+    /// one of them updated and not the other.
+    pub fn record_packet(&self, direction: FlowDirection, bytes: u64, now: Instant) {
+        let counters = self.counters(direction);
+        counters.packets.fetch_add(1, Ordering::Relaxed);
+        counters.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.last_seen.store(now, Ordering::Relaxed);
+    }
+
     pub fn expires_at(&self) -> Instant {
         self.expires_at.load(std::sync::atomic::Ordering::Relaxed)
     }
@@ -182,3 +295,29 @@ impl FlowInfo {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_info_counters() {
+        let flow_info = FlowInfo::new(Instant::now() + Duration::from_secs(10));
+        assert_eq!(flow_info.packets(), 0);
+        assert_eq!(flow_info.bytes(), 0);
+        assert_eq!(flow_info.last_seen(), flow_info.first_seen());
+
+        let later = flow_info.first_seen() + Duration::from_secs(1);
+        flow_info.record_packet(FlowDirection::Forward, 100, later);
+        flow_info.record_packet(FlowDirection::Reverse, 1400, later);
+        flow_info.record_packet(FlowDirection::Reverse, 60, later);
+        assert_eq!(flow_info.packets(), 3);
+        assert_eq!(flow_info.bytes(), 1560);
+        assert_eq!(flow_info.packets_in(FlowDirection::Forward), 1);
+        assert_eq!(flow_info.bytes_in(FlowDirection::Forward), 100);
+        assert_eq!(flow_info.packets_in(FlowDirection::Reverse), 2);
+        assert_eq!(flow_info.bytes_in(FlowDirection::Reverse), 1460);
+        assert_eq!(flow_info.last_seen(), later);
+        assert_eq!(flow_info.first_seen(), flow_info.created_at());
+    }
+}
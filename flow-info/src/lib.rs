@@ -2,9 +2,11 @@
 // Copyright Open Network Fabric Authors
 
 pub mod atomic_instant;
+pub mod clock;
 pub mod flow_info;
 pub mod flow_info_item;
 
 pub use atomic_instant::AtomicInstant;
+pub use clock::{Clock, SystemClock};
 pub use flow_info::*;
 pub use flow_info_item::*;
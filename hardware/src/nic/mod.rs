@@ -177,7 +177,7 @@ impl std::fmt::Display for PciDriver {
     }
 }
 
-trait GetDriver {
+pub trait GetDriver {
     /// Get the driver for a device.
     ///
     /// If the device has no driver, returns `Ok(None)`.
@@ -337,3 +337,57 @@ impl BindToVfioPci for PciNic {
         Ok(())
     }
 }
+
+/// Trait for devices which may be returned to a kernel driver after being bound to vfio-pci.
+pub trait RestorePciDriver {
+    /// Errors which may occur when restoring the driver.
+    type Error: std::error::Error;
+    /// Unbind the device from its current driver, clear any driver override, and bind the device
+    /// to `driver`.
+    ///
+    /// If `driver` is `None` (or [`PciDriver::PciePort`]), the device is left unbound.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device could not be unbound or bound to `driver`.
+    fn restore_driver(&mut self, driver: Option<PciDriver>) -> Result<(), Self::Error>;
+}
+
+impl RestorePciDriver for PciNic {
+    type Error = DriverErr;
+
+    fn restore_driver(&mut self, driver: Option<PciDriver>) -> Result<(), DriverErr> {
+        match self.driver() {
+            Ok(Some(current)) if Some(current) == driver => {
+                info!("device {self} is already bound to {current}");
+                return Ok(());
+            }
+            Ok(Some(PciDriver::PciePort) | None) => {}
+            Ok(Some(current)) => {
+                info!("unbinding device {self} from {current}");
+                self.unbind()?;
+            }
+            // no driver link: the device is not bound to anything (e.g., a binding failed midway)
+            Err(DriverErr::Sysfs(SysfsErr::IoError(e))) if e.kind() == ErrorKind::NotFound => {
+                info!("device {self} is currently unbound");
+            }
+            Err(err) => {
+                error!("failed to get device driver: {:?}", err);
+                return Err(err);
+            }
+        }
+        info!("clearing driver override for {self}");
+        // writing an empty line resets the override
+        self.override_file()
+            .map_err(DriverErr::Sysfs)?
+            .write_all(b"\n")
+            .map_err(|e| DriverErr::Sysfs(SysfsErr::IoError(e)))?;
+        match driver {
+            None | Some(PciDriver::PciePort) => {
+                info!("leaving device {self} unbound");
+                Ok(())
+            }
+            Some(driver) => self.bind(driver),
+        }
+    }
+}
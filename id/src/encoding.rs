@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Compact string encodings for [`Id`].
+//!
+//! The canonical (hyphenated) form of a [`Uuid`] is 36 characters long.
+//! This module offers shorter, lossless encodings ([Crockford base32] and [base58]), along with a
+//! truncated (lossy) display form for logs and human consumption.
+//!
+//! [Crockford base32]: https://www.crockford.com/base32.html
+//! [base58]: https://datatracker.ietf.org/doc/html/draft-msporny-base58-03
+
+use crate::Id;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use uuid::Uuid;
+
+/// Crockford base32 alphabet (no `I`, `L`, `O` or `U`)
+const BASE32_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Bitcoin base58 alphabet (no `0`, `O`, `I` or `l`)
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Length of the base32 encoding of an [`Id`]
+pub const BASE32_LEN: usize = 26;
+/// Length of the base58 encoding of an [`Id`]
+pub const BASE58_LEN: usize = 22;
+/// Number of (hex) characters shown by the truncated display form of an [`Id`]
+pub const SHORT_LEN: usize = 8;
+
+/// Errors which may occur when decoding a compact encoding of an [`Id`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IdDecodeError {
+    /// The encoded string does not have the expected length
+    #[error("invalid length {0}")]
+    InvalidLength(usize),
+    /// The encoded string contains a character which is not in the alphabet
+    #[error("invalid character {0:?}")]
+    InvalidCharacter(char),
+    /// The encoded value does not fit in 128 bits
+    #[error("encoded value exceeds 128 bits")]
+    Overflow,
+}
+
+fn encode(mut value: u128, alphabet: &[u8], len: usize) -> String {
+    let base = alphabet.len() as u128;
+    let mut out = vec![alphabet[0]; len];
+    for digit in out.iter_mut().rev() {
+        #[allow(clippy::cast_possible_truncation)] // remainder is smaller than the base
+        let index = (value % base) as usize;
+        *digit = alphabet[index];
+        value /= base;
+    }
+    // all bytes come from the (ASCII) alphabet
+    out.into_iter().map(char::from).collect()
+}
+
+fn decode(
+    encoded: &str,
+    len: usize,
+    base: u128,
+    digit: impl Fn(char) -> Option<u8>,
+) -> Result<u128, IdDecodeError> {
+    let count = encoded.chars().count();
+    if count != len {
+        return Err(IdDecodeError::InvalidLength(count));
+    }
+    encoded.chars().try_fold(0u128, |acc, c| {
+        let d = digit(c).ok_or(IdDecodeError::InvalidCharacter(c))?;
+        acc.checked_mul(base)
+            .and_then(|acc| acc.checked_add(u128::from(d)))
+            .ok_or(IdDecodeError::Overflow)
+    })
+}
+
+fn base32_digit(c: char) -> Option<u8> {
+    // Crockford base32 is case-insensitive and maps ambiguous characters to digits
+    let c = match c.to_ascii_uppercase() {
+        'O' => '0',
+        'I' | 'L' => '1',
+        c => c,
+    };
+    BASE32_ALPHABET
+        .iter()
+        .position(|&a| char::from(a) == c)
+        .and_then(|d| u8::try_from(d).ok())
+}
+
+fn base58_digit(c: char) -> Option<u8> {
+    BASE58_ALPHABET
+        .iter()
+        .position(|&a| char::from(a) == c)
+        .and_then(|d| u8::try_from(d).ok())
+}
+
+impl<T> Id<T> {
+    /// Encode this id in [Crockford base32] (26 characters).
+    ///
+    /// The encoding preserves the ordering of ids.
+    ///
+    /// [Crockford base32]: https://www.crockford.com/base32.html
+    #[must_use]
+    pub fn to_base32(&self) -> String {
+        encode(self.0.as_u128(), BASE32_ALPHABET, BASE32_LEN)
+    }
+
+    /// Decode an id from its [Crockford base32] encoding (case-insensitive).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`IdDecodeError`] if `encoded` is not a valid base32-encoded id.
+    ///
+    /// [Crockford base32]: https://www.crockford.com/base32.html
+    pub fn from_base32(encoded: &str) -> Result<Self, IdDecodeError> {
+        let value = decode(encoded, BASE32_LEN, 32, base32_digit)?;
+        Ok(Self::from_raw(Uuid::from_u128(value)))
+    }
+
+    /// Encode this id in [base58] (22 characters).
+    ///
+    /// The encoding preserves the ordering of ids.
+    ///
+    /// [base58]: https://datatracker.ietf.org/doc/html/draft-msporny-base58-03
+    #[must_use]
+    pub fn to_base58(&self) -> String {
+        encode(self.0.as_u128(), BASE58_ALPHABET, BASE58_LEN)
+    }
+
+    /// Decode an id from its [base58] encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`IdDecodeError`] if `encoded` is not a valid base58-encoded id.
+    ///
+    /// [base58]: https://datatracker.ietf.org/doc/html/draft-msporny-base58-03
+    pub fn from_base58(encoded: &str) -> Result<Self, IdDecodeError> {
+        let value = decode(encoded, BASE58_LEN, 58, base58_digit)?;
+        Ok(Self::from_raw(Uuid::from_u128(value)))
+    }
+
+    /// Get a truncated display form of this id, showing its first [`SHORT_LEN`] hex characters.
+    ///
+    /// This form is lossy and meant for logs and human consumption only.
+    ///
+    /// ```
+    /// # use dataplane_id::Id;
+    /// # use uuid::Uuid;
+    /// let id = Id::<()>::from_raw(Uuid::from_u128(0x8178d539_96b8_40fd_8fbf_402503aa204a));
+    /// assert_eq!(id.short().to_string(), "8178d539");
+    /// ```
+    #[must_use]
+    pub const fn short(&self) -> ShortId<T> {
+        ShortId(self.0, PhantomData)
+    }
+}
+
+/// Truncated display form of an [`Id`], see [`Id::short`]
+pub struct ShortId<T: ?Sized>(Uuid, PhantomData<T>);
+
+impl<T> Display for ShortId<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut buf = Uuid::encode_buffer();
+        let simple = self.0.simple().encode_lower(&mut buf);
+        f.write_str(&simple[..SHORT_LEN])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Id, IdDecodeError, UuidIdGenerator};
+    use uuid::Uuid;
+
+    #[test]
+    fn base32_round_trip() {
+        bolero::check!()
+            .with_generator(UuidIdGenerator)
+            .for_each(|id: &Id<()>| {
+                let encoded = id.to_base32();
+                assert_eq!(encoded.len(), crate::BASE32_LEN);
+                assert_eq!(Id::from_base32(&encoded), Ok(*id));
+                assert_eq!(Id::from_base32(&encoded.to_lowercase()), Ok(*id));
+            });
+    }
+
+    #[test]
+    fn base58_round_trip() {
+        bolero::check!()
+            .with_generator(UuidIdGenerator)
+            .for_each(|id: &Id<()>| {
+                let encoded = id.to_base58();
+                assert_eq!(encoded.len(), crate::BASE58_LEN);
+                assert_eq!(Id::from_base58(&encoded), Ok(*id));
+            });
+    }
+
+    #[test]
+    fn decode_errors() {
+        let max = Id::<()>::from_raw(Uuid::from_u128(u128::MAX));
+        assert_eq!(max.to_base32(), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+        assert_eq!(
+            Id::<()>::from_base32("8ZZZZZZZZZZZZZZZZZZZZZZZZZ"),
+            Err(IdDecodeError::Overflow)
+        );
+        assert_eq!(
+            Id::<()>::from_base32("7ZZ"),
+            Err(IdDecodeError::InvalidLength(3))
+        );
+        assert_eq!(
+            Id::<()>::from_base58("0000000000000000000000"),
+            Err(IdDecodeError::InvalidCharacter('0'))
+        );
+    }
+}
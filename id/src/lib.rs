@@ -7,18 +7,25 @@
 //!
 //! This association helps prevent us from conflating id types while avoiding the need to write a
 //! different `FooId` type for each type which needs an id.
+//!
+//! Besides the canonical [`Uuid`] form, ids can be encoded in shorter forms (see
+//! [`Id::to_base32`], [`Id::to_base58`] and [`Id::short`]).
 
 use core::fmt::{Debug, Formatter};
 use std::cmp::Ordering;
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 
 #[allow(unused_imports)] // re-export
 #[cfg(any(test, feature = "bolero"))]
 pub use contract::*;
 
+mod encoding;
+pub use encoding::{BASE32_LEN, BASE58_LEN, IdDecodeError, SHORT_LEN, ShortId};
+
 /// An abstract, typed ID.
 ///
 /// # Example
@@ -206,11 +213,35 @@ impl<T> Id<T> {
     /// type parameter `T`.
     /// The annotation consumes no space and has no runtime overhead whatsoever.
     /// The only function of `T` is to distinguish this type from other [Id] types.
+    ///
+    /// Ids generated with this method are random.
+    /// Prefer [`Id::new_v7`] for records which should sort chronologically by id (events, audit
+    /// records, configuration generations, ...).
     #[must_use]
     pub fn new() -> Id<T> {
         Id(Uuid::new_v4(), PhantomData)
     }
 
+    /// Generate a new, time-ordered [UUID version 7] `Id<T>`.
+    ///
+    /// Ids generated with this method sort in the order they were generated in (within a process),
+    /// and roughly chronologically across processes.
+    /// This is the recommended kind of id for events and audit records.
+    ///
+    /// [UUID version 7]: https://datatracker.ietf.org/doc/html/rfc9562#section-5.7
+    #[must_use]
+    pub fn new_v7() -> Id<T> {
+        Id(Uuid::now_v7(), PhantomData)
+    }
+
+    /// Get the time at which this id was generated, if it is time-based (e.g. built with
+    /// [`Id::new_v7`]).
+    #[must_use]
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        let (secs, nanos) = self.0.get_timestamp()?.to_unix();
+        SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+    }
+
     /// Strip type safety and return the wrapped (untyped) [Uuid]
     #[must_use]
     pub const fn into_raw(self) -> Uuid {
@@ -330,6 +361,17 @@ mod test {
             });
     }
 
+    #[test]
+    fn test_v7_sorted() {
+        let before = std::time::SystemTime::now();
+        let ids: Vec<Id<()>> = (0..1000).map(|_| Id::new_v7()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        let timestamp = ids[0].timestamp().unwrap();
+        // v7 timestamps have millisecond precision
+        assert!(timestamp + std::time::Duration::from_millis(1) >= before);
+        assert!(Id::<()>::new().timestamp().is_none());
+    }
+
     #[test]
     fn test_static() {
         bolero::check!().with_type().for_each(|x: &String| {
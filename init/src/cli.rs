@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Command line interface of the init program.
+
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use hardware::pci::address::PciAddress;
+
+use crate::error::InitError;
+use crate::hugepages::HugepageSize;
+use crate::state::DEFAULT_STATE_FILE;
+
+/// Prepare the host (network cards and hugepages) for the dataplane.
+#[derive(Debug, Parser)]
+#[command(name = "dataplane-init", version, about, long_about = None)]
+pub(crate) struct Cli {
+    /// What to do (defaults to `bind`).
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// PCI address (`DDDD:BB:DD.F`) of a device to initialize.
+    /// Can be repeated or comma-separated.
+    #[arg(
+        long = "device",
+        short = 'd',
+        value_name = "PCI_ADDRESS",
+        value_delimiter = ',',
+        value_parser = parse_pci_address,
+        global = true
+    )]
+    devices: Vec<PciAddress>,
+
+    /// Dataplane args file to read devices from (the PCI addresses of its `allow` and `interface`
+    /// arguments), in addition to the ones given with `--device`.
+    #[arg(long, env = "DATAPLANE_ARGS_FILE", value_name = "PATH", global = true)]
+    args_file: Option<PathBuf>,
+
+    /// File recording the original state of the host, used to undo the changes.
+    #[arg(
+        long,
+        env = "DATAPLANE_INIT_STATE_FILE",
+        value_name = "PATH",
+        default_value = DEFAULT_STATE_FILE,
+        global = true
+    )]
+    state_file: PathBuf,
+}
+
+/// Modes of operation.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Bind the devices to vfio-pci, after setting up hugepages if requested.
+    Bind(BindArgs),
+    /// Show the driver of the devices and the hugepage reservations.
+    Status,
+    /// Return the devices to their original driver.
+    /// Without devices, undo every recorded change, hugepages included.
+    #[command(alias = "unbind")]
+    Restore,
+}
+
+/// Arguments of the `bind` mode.
+#[derive(Debug, Default, Args)]
+pub(crate) struct BindArgs {
+    /// Number of hugepages to reserve.
+    #[arg(long, value_name = "N")]
+    pub(crate) hugepages: Option<u32>,
+
+    /// Size of the hugepages to reserve and mount.
+    #[arg(long, value_enum, default_value_t)]
+    pub(crate) hugepage_size: HugepageSize,
+
+    /// Where to mount a hugetlbfs for the dataplane, if not mounted already.
+    #[arg(long, value_name = "PATH")]
+    pub(crate) hugepage_mount: Option<PathBuf>,
+}
+
+fn parse_pci_address(value: &str) -> Result<PciAddress, String> {
+    PciAddress::try_from(value).map_err(|e| e.to_string())
+}
+
+impl Cli {
+    /// The mode of operation.
+    pub(crate) fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
+    /// The path of the state file.
+    pub(crate) fn state_file(&self) -> &Path {
+        &self.state_file
+    }
+
+    /// The devices to act upon: those given on the command line, then those of the args file,
+    /// without duplicates.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the args file could not be read or holds invalid PCI addresses.
+    pub(crate) fn devices(&self) -> Result<Vec<PciAddress>, InitError> {
+        let mut devices = self.devices.clone();
+        if let Some(path) = &self.args_file {
+            devices.extend(devices_from_args_file(path)?);
+        }
+        let mut seen = std::collections::HashSet::new();
+        devices.retain(|device| seen.insert(*device));
+        Ok(devices)
+    }
+}
+
+/// Collect the PCI addresses of the `allow` and `interface` arguments of a dataplane args file.
+fn devices_from_args_file(path: &Path) -> Result<Vec<PciAddress>, InitError> {
+    let invalid = |reason: String| InitError::InvalidArgsFile {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let content = std::fs::read_to_string(path).map_err(|source| InitError::ReadArgsFile {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let table: toml::Table = content.parse().map_err(|e| invalid(format!("{e}")))?;
+
+    let mut devices = Vec::new();
+    for (key, value) in &table {
+        let key = key.replace('-', "_");
+        if key != "allow" && key != "interface" {
+            continue;
+        }
+        let values = match value {
+            toml::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let Some(value) = value.as_str() else {
+                return Err(invalid(format!(
+                    "'{key}' takes strings, not {}",
+                    value.type_str()
+                )));
+            };
+            for spec in value.split(',') {
+                let address = if key == "allow" {
+                    Some(spec)
+                } else {
+                    // INTERFACE[=PCI_ADDRESS]
+                    spec.split_once('=')
+                        .map(|(_, address)| address)
+                        .filter(|address| !address.is_empty())
+                };
+                if let Some(address) = address {
+                    devices.push(parse_pci_address(address).map_err(invalid)?);
+                }
+            }
+        }
+    }
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_devices_from_args_file() {
+        let path = std::env::temp_dir().join(format!("init-args-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+                driver = "dpdk"
+                allow = ["0000:02:00.0", "0000:02:00.1"]
+                interface = ["eth0=0000:02:00.1,eth1", "eth2=0000:03:00.0"]
+            "#,
+        )
+        .unwrap();
+        let cli = Cli::parse_from([
+            "dataplane-init",
+            "status",
+            "--device",
+            "0000:01:00.0",
+            "--args-file",
+            path.to_str().unwrap(),
+        ]);
+        let devices: Vec<_> = cli
+            .devices()
+            .unwrap()
+            .into_iter()
+            .map(|device| device.to_string())
+            .collect();
+        assert_eq!(
+            devices,
+            [
+                "0000:01:00.0",
+                "0000:02:00.0",
+                "0000:02:00.1",
+                "0000:03:00.0"
+            ]
+        );
+        assert!(matches!(cli.command(), Some(Command::Status)));
+
+        std::fs::write(&path, "allow = [\"not-a-device\"]").unwrap();
+        assert!(matches!(
+            cli.devices(),
+            Err(InitError::InvalidArgsFile { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
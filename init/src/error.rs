@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Errors of the init program.
+
+use std::path::PathBuf;
+
+use hardware::nic::DriverErr;
+use hardware::pci::address::PciAddress;
+use sysfs::SysfsErr;
+
+use crate::hugepages::HugepageSize;
+
+/// Errors which abort the initialization.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum InitError {
+    /// The dataplane args file could not be read.
+    #[error("failed to read args file {}: {source}", path.display())]
+    ReadArgsFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The dataplane args file could not be understood.
+    #[error("invalid args file {}: {reason}", path.display())]
+    InvalidArgsFile { path: PathBuf, reason: String },
+    /// Neither the command line nor the args file named a device.
+    #[error("no device to initialize: use --device or --args-file")]
+    NoDevice,
+    /// The device could not be found under sysfs.
+    #[error("device {0} not found: {1}")]
+    Device(PciAddress, SysfsErr),
+    /// The device could not be bound to vfio-pci.
+    #[error("failed to bind {0} to vfio-pci: {1}")]
+    Bind(PciAddress, DriverErr),
+    /// The hugepages could not be reserved.
+    #[error("failed to reserve {count} hugepages of {size}: {source}")]
+    ReserveHugepages {
+        count: u64,
+        size: HugepageSize,
+        source: SysfsErr,
+    },
+    /// The hugetlbfs could not be mounted.
+    #[error("failed to mount hugetlbfs at {}: {reason}", path.display())]
+    MountHugetlbfs { path: PathBuf, reason: String },
+    /// The hugetlbfs could not be unmounted.
+    #[error("failed to unmount hugetlbfs at {}: {reason}", path.display())]
+    UnmountHugetlbfs { path: PathBuf, reason: String },
+    /// The state file could not be read, parsed or written.
+    #[error("state file {}: {reason}", path.display())]
+    StateFile { path: PathBuf, reason: String },
+    /// The device could not be returned to its original driver.
+    #[error("failed to restore the original driver of {0}: {1}")]
+    Restore(PciAddress, DriverErr),
+    /// Some changes could not be undone (the failures are logged).
+    #[error("{0} change(s) could not be undone")]
+    RestoreIncomplete(usize),
+}
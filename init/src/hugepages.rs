@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Hugepage reservation and hugetlbfs mounting.
+
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+
+use nix::mount::MsFlags;
+use sysfs::{SysfsErr, SysfsFile, SysfsPath, sysfs_root};
+use tracing::{info, warn};
+
+use crate::error::InitError;
+
+/// Supported hugepage sizes.
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    strum::EnumString,
+    strum::IntoStaticStr,
+)]
+pub(crate) enum HugepageSize {
+    /// 2 MiB pages
+    #[default]
+    #[value(name = "2M")]
+    #[strum(serialize = "2M")]
+    TwoMegabytes,
+    /// 1 GiB pages
+    #[value(name = "1G")]
+    #[strum(serialize = "1G")]
+    OneGigabyte,
+}
+
+impl HugepageSize {
+    /// All the supported sizes.
+    pub(crate) const ALL: [HugepageSize; 2] =
+        [HugepageSize::TwoMegabytes, HugepageSize::OneGigabyte];
+
+    /// The size of a page in KiB, as used in the sysfs directory names.
+    fn kib(self) -> u64 {
+        match self {
+            HugepageSize::TwoMegabytes => 2048,
+            HugepageSize::OneGigabyte => 1024 * 1024,
+        }
+    }
+
+    /// The sysfs directory describing the pages of this size.
+    fn sysfs_dir(self) -> Result<SysfsPath, SysfsErr> {
+        sysfs_root().relative(format!("kernel/mm/hugepages/hugepages-{}kB", self.kib()))
+    }
+}
+
+impl std::fmt::Display for HugepageSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s: &'static str = self.into();
+        f.pad(s)
+    }
+}
+
+/// Hugepage reservation for a page size.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct HugepageStatus {
+    /// Number of reserved pages
+    pub(crate) total: u64,
+    /// Number of reserved pages not in use
+    pub(crate) free: u64,
+}
+
+fn read_counter(dir: &SysfsPath, name: &str) -> Result<u64, SysfsErr> {
+    let mut options = std::fs::OpenOptions::new();
+    options.read(true);
+    let mut content = String::new();
+    SysfsFile::open(dir.relative(name)?, &options)?.read_to_string(&mut content)?;
+    content.trim().parse().map_err(|e| {
+        SysfsErr::IoError(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{dir}/{name}: {e}"),
+        ))
+    })
+}
+
+/// Get the reservation of hugepages of the given size.
+///
+/// # Errors
+///
+/// Returns an error if the kernel does not support this page size.
+pub(crate) fn status(size: HugepageSize) -> Result<HugepageStatus, SysfsErr> {
+    let dir = size.sysfs_dir()?;
+    Ok(HugepageStatus {
+        total: read_counter(&dir, "nr_hugepages")?,
+        free: read_counter(&dir, "free_hugepages")?,
+    })
+}
+
+/// Reserve `count` hugepages of the given size.
+///
+/// The kernel may be unable to reserve all the pages if memory is fragmented: this only warns if
+/// fewer pages end up reserved, leaving the final decision to the dataplane preflight checks.
+///
+/// # Errors
+///
+/// Returns an error if the reservation could not be requested.
+pub(crate) fn reserve(size: HugepageSize, count: u64) -> Result<(), InitError> {
+    let error = |source| InitError::ReserveHugepages {
+        count,
+        size,
+        source,
+    };
+    let dir = size.sysfs_dir().map_err(error)?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    info!("reserving {count} hugepages of {size}");
+    SysfsFile::open(dir.relative("nr_hugepages").map_err(error)?, &options)
+        .map_err(error)?
+        .write_all(count.to_string().as_bytes())
+        .map_err(|e| error(SysfsErr::IoError(e)))?;
+    let reserved = read_counter(&dir, "nr_hugepages").map_err(error)?;
+    if reserved < count {
+        warn!("only {reserved} of {count} hugepages of {size} could be reserved");
+    }
+    Ok(())
+}
+
+/// Mount a hugetlbfs for pages of the given size at `path`, unless one is already mounted there.
+/// Returns true if the filesystem was mounted by this call.
+///
+/// # Errors
+///
+/// Returns an error if the mount point could not be created or the filesystem mounted.
+pub(crate) fn mount(path: &Path, size: HugepageSize) -> Result<bool, InitError> {
+    let error = |reason: String| InitError::MountHugetlbfs {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let mounts = procfs::mounts().map_err(|e| error(e.to_string()))?;
+    if mounts
+        .iter()
+        .any(|mount| mount.fs_vfstype == "hugetlbfs" && Path::new(&mount.fs_file) == path)
+    {
+        info!("hugetlbfs already mounted at {}", path.display());
+        return Ok(false);
+    }
+    std::fs::create_dir_all(path).map_err(|e| error(e.to_string()))?;
+    info!("mounting hugetlbfs ({size} pages) at {}", path.display());
+    nix::mount::mount(
+        Some("hugetlbfs"),
+        path,
+        Some("hugetlbfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some(format!("pagesize={size}").as_str()),
+    )
+    .map_err(|e| error(e.to_string()))?;
+    Ok(true)
+}
+
+/// Unmount the hugetlbfs at `path`.
+///
+/// # Errors
+///
+/// Returns an error if the filesystem could not be unmounted (e.g., because it is in use).
+pub(crate) fn unmount(path: &Path) -> Result<(), InitError> {
+    info!("unmounting hugetlbfs at {}", path.display());
+    nix::mount::umount(path).map_err(|e| InitError::UnmountHugetlbfs {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })
+}
@@ -4,21 +4,267 @@
 #![doc = include_str!("../README.md")]
 #![deny(clippy::pedantic, missing_docs)]
 
-use hardware::nic::{BindToVfioPci, PciNic};
+mod cli;
+mod error;
+mod hugepages;
+mod state;
 
-fn main() {
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+use hardware::nic::{BindToVfioPci, DriverErr, GetDriver, PciDriver, PciNic, RestorePciDriver};
+use hardware::pci::address::PciAddress;
+use tracing::{error, info, warn};
+
+use crate::cli::{BindArgs, Cli, Command};
+use crate::error::InitError;
+use crate::hugepages::HugepageSize;
+use crate::state::State;
+
+/// The changes made by a [`bind`], to be undone if it fails.
+#[derive(Debug, Default)]
+struct Changes {
+    /// The size of the hugepages reserved, the number of pages reserved before, and whether
+    /// that number was recorded as the original one
+    hugepages: Option<(HugepageSize, u64, bool)>,
+    /// The hugetlbfs mount created
+    mount: Option<PathBuf>,
+    /// The devices which may have been bound to vfio-pci
+    devices: Vec<PciAddress>,
+}
+
+/// Set up hugepages as requested, then bind every device to vfio-pci.
+///
+/// The original state of the host is recorded in the state file before each change.
+/// If a step fails, the changes made by this call are undone: the devices are returned to their
+/// original driver, and the hugepage reservation and mount are reverted.
+fn bind(devices: &[PciAddress], args: &BindArgs, state_file: &Path) -> Result<(), InitError> {
+    if devices.is_empty() {
+        return Err(InitError::NoDevice);
+    }
+    let mut state = State::load(state_file)?;
+    let mut changes = Changes::default();
+    if let Err(e) = bind_all(devices, args, &mut state, state_file, &mut changes) {
+        error!("{e}");
+        rollback(&changes, &mut state, state_file);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// The steps of [`bind`]. The changes made are added to `changes`.
+fn bind_all(
+    devices: &[PciAddress],
+    args: &BindArgs,
+    state: &mut State,
+    state_file: &Path,
+    changes: &mut Changes,
+) -> Result<(), InitError> {
+    let size = args.hugepage_size;
+    if let Some(count) = args.hugepages {
+        let previous = hugepages::status(size)
+            .map_err(|source| InitError::ReserveHugepages {
+                count: count.into(),
+                size,
+                source,
+            })?
+            .total;
+        let recorded = state.record_hugepages(size, previous);
+        state.save(state_file)?;
+        changes.hugepages = Some((size, previous, recorded));
+        hugepages::reserve(size, count.into())?;
+    }
+    if let Some(path) = &args.hugepage_mount
+        && hugepages::mount(path, size)?
+    {
+        changes.mount = Some(path.clone());
+        state.record_mount(path);
+        state.save(state_file)?;
+    }
+    changes.devices.reserve(devices.len());
+    for &address in devices {
+        bind_device(address, state, state_file, &mut changes.devices)?;
+        info!("device {address} is bound to vfio-pci");
+    }
+    Ok(())
+}
+
+/// Record the original driver of a device and bind it to vfio-pci.
+/// Devices which this may have changed are added to `touched`.
+fn bind_device(
+    address: PciAddress,
+    state: &mut State,
+    state_file: &Path,
+    touched: &mut Vec<PciAddress>,
+) -> Result<(), InitError> {
+    let mut device = PciNic::new(address).map_err(|e| InitError::Device(address, e))?;
+    let original = match device.driver() {
+        Ok(Some(PciDriver::VfioPci)) => {
+            info!("device {address} is already bound to vfio-pci");
+            return Ok(());
+        }
+        Ok(Some(PciDriver::PciePort) | None) => None,
+        Ok(Some(driver)) => Some(driver),
+        Err(e) => return Err(InitError::Bind(address, e)),
+    };
+    state.record_device(address, original);
+    state.save(state_file)?;
+    touched.push(address);
+    device
+        .bind_to_vfio_pci()
+        .map_err(|e| InitError::Bind(address, e))
+}
+
+/// Return a device to its recorded original driver, and forget about it.
+fn restore_device(address: PciAddress, state: &mut State) -> Result<(), InitError> {
+    let Some(original) = state.original_driver(address) else {
+        warn!("no original driver recorded for {address}: leaving it alone");
+        return Ok(());
+    };
+    let mut device = PciNic::new(address).map_err(|e| InitError::Device(address, e))?;
+    device
+        .restore_driver(original)
+        .map_err(|e| InitError::Restore(address, e))?;
+    state.forget_device(address);
+    Ok(())
+}
+
+/// Undo the changes of a failed [`bind`], most recent first. Failures are only logged: the
+/// changes stay in the state file so that `restore` can be attempted later.
+fn rollback(changes: &Changes, state: &mut State, state_file: &Path) {
+    for &address in changes.devices.iter().rev() {
+        match restore_device(address, state) {
+            Ok(()) => info!("rolled back binding of {address}"),
+            Err(e) => error!("failed to roll back binding of {address}: {e}"),
+        }
+    }
+    if let Some(path) = &changes.mount {
+        match hugepages::unmount(path) {
+            Ok(()) => {
+                info!("rolled back mount of {}", path.display());
+                state.forget_mount(path);
+            }
+            Err(e) => error!("failed to roll back mount of {}: {e}", path.display()),
+        }
+    }
+    if let Some((size, previous, recorded)) = changes.hugepages {
+        match hugepages::reserve(size, previous) {
+            Ok(()) => {
+                info!("rolled back reservation of {size} hugepages");
+                if recorded {
+                    state.forget_hugepages(size);
+                }
+            }
+            Err(e) => error!("failed to roll back reservation of {size} hugepages: {e}"),
+        }
+    }
+    if let Err(e) = state.save(state_file) {
+        error!("{e}");
+    }
+}
+
+/// Return the devices to their original driver. Without devices, undo every recorded change.
+fn restore(devices: &[PciAddress], state_file: &Path) -> Result<(), InitError> {
+    let mut state = State::load(state_file)?;
+    let everything = devices.is_empty();
+    let devices: Vec<_> = if everything {
+        state.devices().collect()
+    } else {
+        devices.to_vec()
+    };
+    let mut failures = 0;
+    for address in devices {
+        match restore_device(address, &mut state) {
+            Ok(()) => info!("restored original driver of {address}"),
+            Err(e) => {
+                error!("{e}");
+                failures += 1;
+            }
+        }
+    }
+    if everything {
+        for path in state.take_mounts() {
+            if let Err(e) = hugepages::unmount(&path) {
+                error!("{e}");
+                failures += 1;
+                state.record_mount(&path);
+            }
+        }
+        for (size, count) in state.take_hugepages() {
+            if let Err(e) = hugepages::reserve(size, count) {
+                error!("{e}");
+                failures += 1;
+                state.record_hugepages(size, count);
+            }
+        }
+    }
+    state.save(state_file)?;
+    if failures > 0 {
+        return Err(InitError::RestoreIncomplete(failures));
+    }
+    Ok(())
+}
+
+/// Print the driver (and recorded original driver) of every device and the hugepage reservations.
+fn status(devices: &[PciAddress], state_file: &Path) -> Result<(), InitError> {
+    let state = State::load(state_file)?;
+    if !devices.is_empty() {
+        println!("{:<14}{:<14}ORIGINAL", "DEVICE", "DRIVER");
+        for &address in devices {
+            let driver = match PciNic::new(address) {
+                Err(_) => "(not found)".to_string(),
+                Ok(device) => match device.driver() {
+                    Ok(Some(driver)) => driver.to_string(),
+                    Ok(None) => "(none)".to_string(),
+                    Err(DriverErr::NotSupported { driver_name }) => driver_name,
+                    Err(e) => format!("(error: {e})"),
+                },
+            };
+            let original = match state.original_driver(address) {
+                None => String::new(),
+                Some(Some(driver)) => driver.to_string(),
+                Some(None) => "(none)".to_string(),
+            };
+            println!("{:<14}{driver:<14}{original}", address.to_string());
+        }
+        println!();
+    }
+    println!("{:<11}{:<8}FREE", "HUGEPAGES", "TOTAL");
+    for size in HugepageSize::ALL {
+        match hugepages::status(size) {
+            Ok(status) => println!("{size:<11}{:<8}{}", status.total, status.free),
+            Err(_) => println!("{size:<11}(unsupported)"),
+        }
+    }
+    Ok(())
+}
+
+fn run(cli: &Cli) -> Result<(), InitError> {
+    let devices = cli.devices()?;
+    let state_file = cli.state_file();
+    match cli.command() {
+        None => bind(&devices, &BindArgs::default(), state_file),
+        Some(Command::Bind(args)) => bind(&devices, args, state_file),
+        Some(Command::Status) => status(&devices, state_file),
+        Some(Command::Restore) => restore(&devices, state_file),
+    }
+}
+
+fn main() -> ExitCode {
     tracing_subscriber::fmt()
         .with_ansi(false)
         .with_file(true)
         .with_level(true)
         .with_line_number(true)
+        .with_writer(std::io::stderr)
         .init();
-    // TODO: proper argument parsing
-    // -- hack add a real command line parser
-    let mut args = std::env::args().skip(1);
-    // -- end hack
-    // TODO: fix unwraps in the next PR.  These can't be properly addressed before the arg parser is done.
-    let address = hardware::pci::address::PciAddress::try_from(args.next().unwrap()).unwrap();
-    let mut device = PciNic::new(address).unwrap();
-    device.bind_to_vfio_pci().unwrap();
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
 }
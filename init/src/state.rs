@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Record of the changes made to the host, so that they can be undone.
+//!
+//! The state file is a plain text file with one change per line:
+//!
+//! ```text
+//! device 0000:02:00.0 i40e
+//! device 0000:02:00.1 -
+//! hugepages 2M 0
+//! mount /dev/hugepages
+//! ```
+//!
+//! `device` lines give the kernel driver a device was bound to before being bound to vfio-pci (`-`
+//! if it was unbound), `hugepages` lines give the number of hugepages reserved before ours, and
+//! `mount` lines give the hugetlbfs mounts we created.
+//!
+//! Only the _first_ known state is recorded: running init twice does not make it forget the
+//! original drivers.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use hardware::nic::PciDriver;
+use hardware::pci::address::PciAddress;
+
+use crate::error::InitError;
+use crate::hugepages::HugepageSize;
+
+/// Default location of the state file. Bindings do not survive reboots, neither should the file.
+pub(crate) const DEFAULT_STATE_FILE: &str = "/run/dataplane/init.state";
+
+/// The original state of the host, as recorded in the state file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct State {
+    /// Devices bound to vfio-pci, with their original driver
+    devices: Vec<(PciAddress, Option<PciDriver>)>,
+    /// Hugepage sizes we reserved pages of, with the original number of pages
+    hugepages: Vec<(HugepageSize, u64)>,
+    /// hugetlbfs mounts we created
+    mounts: Vec<PathBuf>,
+}
+
+impl State {
+    /// Read the state file at `path`. A missing file is an empty state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read or parsed.
+    pub(crate) fn load(path: &Path) -> Result<Self, InitError> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => content.parse().map_err(|reason| InitError::StateFile {
+                path: path.to_path_buf(),
+                reason,
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(InitError::StateFile {
+                path: path.to_path_buf(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    /// Write the state to `path` (atomically), or remove the file if there is nothing left to undo.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be written.
+    pub(crate) fn save(&self, path: &Path) -> Result<(), InitError> {
+        let error = |e: std::io::Error| InitError::StateFile {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        };
+        if self.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(error(e)),
+                _ => Ok(()),
+            };
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(error)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.to_string()).map_err(error)?;
+        std::fs::rename(&tmp, path).map_err(error)
+    }
+
+    /// True if no change is recorded.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.devices.is_empty() && self.hugepages.is_empty() && self.mounts.is_empty()
+    }
+
+    /// The original driver of a device, if it was recorded.
+    pub(crate) fn original_driver(&self, address: PciAddress) -> Option<Option<PciDriver>> {
+        self.devices
+            .iter()
+            .find(|(recorded, _)| *recorded == address)
+            .map(|(_, driver)| *driver)
+    }
+
+    /// The devices with a recorded original driver.
+    pub(crate) fn devices(&self) -> impl Iterator<Item = PciAddress> + '_ {
+        self.devices.iter().map(|(address, _)| *address)
+    }
+
+    /// Record the original driver of a device, unless already known.
+    pub(crate) fn record_device(&mut self, address: PciAddress, driver: Option<PciDriver>) {
+        if self.original_driver(address).is_none() {
+            self.devices.push((address, driver));
+        }
+    }
+
+    /// Forget about a device which was restored.
+    pub(crate) fn forget_device(&mut self, address: PciAddress) {
+        self.devices.retain(|(recorded, _)| *recorded != address);
+    }
+
+    /// Record the original number of hugepages of a size, unless already known.
+    /// Returns true if it was not known.
+    pub(crate) fn record_hugepages(&mut self, size: HugepageSize, count: u64) -> bool {
+        if self.hugepages.iter().any(|(recorded, _)| *recorded == size) {
+            return false;
+        }
+        self.hugepages.push((size, count));
+        true
+    }
+
+    /// Forget about the original number of hugepages of a size, which was restored.
+    pub(crate) fn forget_hugepages(&mut self, size: HugepageSize) {
+        self.hugepages.retain(|(recorded, _)| *recorded != size);
+    }
+
+    /// Take the recorded original numbers of hugepages.
+    pub(crate) fn take_hugepages(&mut self) -> Vec<(HugepageSize, u64)> {
+        std::mem::take(&mut self.hugepages)
+    }
+
+    /// Record a hugetlbfs mount which we created.
+    pub(crate) fn record_mount(&mut self, path: &Path) {
+        if !self.mounts.iter().any(|recorded| recorded == path) {
+            self.mounts.push(path.to_path_buf());
+        }
+    }
+
+    /// Forget about a hugetlbfs mount which was removed.
+    pub(crate) fn forget_mount(&mut self, path: &Path) {
+        self.mounts.retain(|recorded| recorded != path);
+    }
+
+    /// Take the recorded hugetlbfs mounts.
+    pub(crate) fn take_mounts(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.mounts)
+    }
+}
+
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (address, driver) in &self.devices {
+            match driver {
+                Some(driver) => writeln!(f, "device {address} {driver}")?,
+                None => writeln!(f, "device {address} -")?,
+            }
+        }
+        for (size, count) in &self.hugepages {
+            writeln!(f, "hugepages {size} {count}")?;
+        }
+        for path in &self.mounts {
+            writeln!(f, "mount {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for State {
+    type Err = String;
+
+    fn from_str(content: &str) -> Result<Self, String> {
+        let mut state = State::default();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |reason: &str| format!("line {}: {reason}: {line}", number + 1);
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next(), words.next(), words.next()) {
+                (Some("device"), Some(address), Some(driver), None) => {
+                    let address =
+                        PciAddress::try_from(address).map_err(|e| error(&e.to_string()))?;
+                    let driver = match driver {
+                        "-" => None,
+                        driver => {
+                            Some(PciDriver::from_str(driver).map_err(|_| error("unknown driver"))?)
+                        }
+                    };
+                    state.record_device(address, driver);
+                }
+                (Some("hugepages"), Some(size), Some(count), None) => {
+                    let size =
+                        HugepageSize::from_str(size).map_err(|_| error("unknown hugepage size"))?;
+                    let count = count.parse().map_err(|_| error("invalid hugepage count"))?;
+                    state.record_hugepages(size, count);
+                }
+                (Some("mount"), Some(path), None, None) => state.record_mount(Path::new(path)),
+                _ => return Err(error("invalid entry")),
+            }
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_state_round_trip() {
+        let first = PciAddress::try_from("0000:02:00.0").unwrap();
+        let second = PciAddress::try_from("0000:02:00.1").unwrap();
+        let mut state = State::default();
+        state.record_device(first, Some(PciDriver::I40e));
+        state.record_device(second, None);
+        // the original driver is not overwritten by later runs
+        state.record_device(first, Some(PciDriver::VfioPci));
+        assert!(state.record_hugepages(HugepageSize::TwoMegabytes, 0));
+        assert!(!state.record_hugepages(HugepageSize::TwoMegabytes, 8));
+        state.record_mount(Path::new("/dev/hugepages"));
+
+        let content = state.to_string();
+        assert_eq!(
+            content,
+            "device 0000:02:00.0 i40e\ndevice 0000:02:00.1 -\nhugepages 2M 0\nmount /dev/hugepages\n"
+        );
+        let parsed: State = content.parse().unwrap();
+        assert_eq!(parsed, state);
+        assert_eq!(parsed.original_driver(first), Some(Some(PciDriver::I40e)));
+        assert_eq!(parsed.original_driver(second), Some(None));
+
+        assert!("device 0000:02:00.0".parse::<State>().is_err());
+        assert!("device 0000:02:00.0 nope".parse::<State>().is_err());
+    }
+
+    #[test]
+    fn test_state_forget() {
+        let mut state = State::default();
+        state.record_hugepages(HugepageSize::TwoMegabytes, 0);
+        state.record_mount(Path::new("/dev/hugepages"));
+        state.forget_mount(Path::new("/dev/hugepages"));
+        assert!(!state.is_empty());
+        state.forget_hugepages(HugepageSize::TwoMegabytes);
+        assert!(state.is_empty());
+    }
+}
@@ -48,7 +48,7 @@ use net::ipv4::addr::UnicastIpv4Addr;
 use net::pci::PciEbdf;
 use net::route::RouteTableId;
 use net::vxlan::InvalidVni;
-use rekon::{AsRequirement, Create, Op, Reconcile, Remove, Update};
+use rekon::{AsRequirement, Change, Create, Op, Plan, Reconcile, Remove, Update};
 use rtnetlink::packet_route::link::{
     InfoBridge, InfoData, InfoKind, InfoVrf, InfoVxlan, LinkAttribute, LinkFlags, LinkInfo,
     LinkMessage, State,
@@ -596,14 +596,42 @@ impl Reconcile for Manager<Interface> {
     where
         Self: 'a,
     {
-        match observation {
-            None => Some(Op::Create(self.create(requirement).await)),
-            Some(observed) => {
-                if requirement == observed {
-                    return None;
-                }
+        match self.plan(requirement, observation)? {
+            Change::Create(requirement) => Some(Op::Create(self.create(requirement).await)),
+            Change::Update(requirement, observed) => {
                 Some(Op::Update(self.update(requirement, observed).await))
             }
+            Change::Remove(observed) => Some(Op::Remove(self.remove(observed).await)),
+        }
+    }
+}
+
+impl Plan for Manager<Interface> {
+    type Requirement<'a>
+        = &'a InterfaceSpec
+    where
+        Self: 'a;
+    type Observation<'a>
+        = Option<&'a Interface>
+    where
+        Self: 'a;
+    type Changes<'a>
+        = Option<Change<&'a InterfaceSpec, &'a Interface>>
+    where
+        Self: 'a;
+
+    fn plan<'a>(
+        &self,
+        requirement: &'a InterfaceSpec,
+        observation: Option<&'a Interface>,
+    ) -> Self::Changes<'a>
+    where
+        Self: 'a,
+    {
+        match observation {
+            None => Some(Change::Create(requirement)),
+            Some(observed) if requirement == observed => None,
+            Some(observed) => Some(Change::Update(requirement, observed)),
         }
     }
 }
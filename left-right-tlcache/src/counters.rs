@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Counters for thread-local read-handle caches.
+//! Each thread's cache owns its counters. These are only updated by the owning thread, but
+//! are atomic so that they can be read from any thread to aggregate them. To that end, the
+//! counters of every thread are kept in a global registry, per thread-local cache declaration.
+//! When a thread exits, its counters are removed from the registry and added to the retired
+//! counters of the cache, so that the registry does not grow with the number of threads spawned.
+
+use std::iter::Sum;
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A snapshot of the counters of a `ReadHandleCache`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReadHandleCacheStats {
+    /// Number of lookups served from the cache
+    pub hits: u64,
+    /// Number of lookups that required querying the provider
+    pub misses: u64,
+    /// Number of cache entries discarded because they were stale
+    pub invalidations: u64,
+    /// Number of full refreshes of the cache
+    pub refreshes: u64,
+    /// Number of entries evicted because the cache was full
+    pub evictions: u64,
+    /// Number of entries currently in the cache
+    pub entries: u64,
+}
+
+impl ReadHandleCacheStats {
+    /// The ratio of lookups served from the cache, if any lookup was done
+    #[must_use]
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        #[allow(clippy::cast_precision_loss)]
+        (total != 0).then(|| self.hits as f64 / total as f64)
+    }
+}
+
+impl AddAssign for ReadHandleCacheStats {
+    fn add_assign(&mut self, other: Self) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.invalidations += other.invalidations;
+        self.refreshes += other.refreshes;
+        self.evictions += other.evictions;
+        self.entries += other.entries;
+    }
+}
+
+impl Sum for ReadHandleCacheStats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |mut acc, stats| {
+            acc += stats;
+            acc
+        })
+    }
+}
+
+/// Add `n` to a counter that only the owning thread updates
+fn add(counter: &AtomicU64, n: u64) {
+    counter.store(counter.load(Ordering::Relaxed) + n, Ordering::Relaxed);
+}
+
+#[derive(Debug)]
+pub(crate) struct CacheCounters {
+    thread: String,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+    refreshes: AtomicU64,
+    evictions: AtomicU64,
+    entries: AtomicU64,
+}
+
+impl Default for CacheCounters {
+    fn default() -> Self {
+        let current = std::thread::current();
+        let thread = current
+            .name()
+            .map_or_else(|| format!("{:?}", current.id()), ToOwned::to_owned);
+        Self {
+            thread,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+            refreshes: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            entries: AtomicU64::new(0),
+        }
+    }
+}
+
+impl CacheCounters {
+    pub(crate) fn thread(&self) -> &str {
+        &self.thread
+    }
+    pub(crate) fn hit(&self) {
+        add(&self.hits, 1);
+    }
+    pub(crate) fn miss(&self) {
+        add(&self.misses, 1);
+    }
+    pub(crate) fn invalidated(&self, count: usize) {
+        if count > 0 {
+            add(&self.invalidations, count as u64);
+        }
+    }
+    pub(crate) fn refreshed(&self) {
+        add(&self.refreshes, 1);
+    }
+    pub(crate) fn evicted(&self, count: usize) {
+        if count > 0 {
+            add(&self.evictions, count as u64);
+        }
+    }
+    pub(crate) fn set_entries(&self, entries: usize) {
+        self.entries.store(entries as u64, Ordering::Relaxed);
+    }
+    pub(crate) fn stats(&self) -> ReadHandleCacheStats {
+        ReadHandleCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            refreshes: self.refreshes.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: self.entries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The counters of all live threads, for each thread-local cache, and the sum of the counters of
+/// the threads that exited. Caches are told apart by the address of their (static) `LocalKey`,
+/// which is unique per declaration.
+struct Registry {
+    live: Vec<(usize, Arc<CacheCounters>)>,
+    retired: Vec<(usize, ReadHandleCacheStats)>,
+}
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    live: Vec::new(),
+    retired: Vec::new(),
+});
+
+pub(crate) fn register_counters(cache: usize, counters: Arc<CacheCounters>) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    registry.live.push((cache, counters));
+}
+
+/// Remove the counters of a thread from the registry, adding them to the retired counters of
+/// the cache. Retired caches have no entries.
+pub(crate) fn retire_counters(cache: usize, counters: &Arc<CacheCounters>) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(index) = registry
+        .live
+        .iter()
+        .position(|(id, live)| *id == cache && Arc::ptr_eq(live, counters))
+    else {
+        return;
+    };
+    registry.live.swap_remove(index);
+    let stats = ReadHandleCacheStats {
+        entries: 0,
+        ..counters.stats()
+    };
+    match registry.retired.iter_mut().find(|(id, _)| *id == cache) {
+        Some((_, retired)) => *retired += stats,
+        None => registry.retired.push((cache, stats)),
+    }
+}
+
+pub(crate) fn registered_counters(cache: usize) -> Vec<Arc<CacheCounters>> {
+    let registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    registry
+        .live
+        .iter()
+        .filter(|(id, _)| *id == cache)
+        .map(|(_, counters)| Arc::clone(counters))
+        .collect()
+}
+
+/// The sum of the counters of the threads that exited, if any did
+pub(crate) fn retired_stats(cache: usize) -> Option<ReadHandleCacheStats> {
+    let registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    registry
+        .retired
+        .iter()
+        .find(|(id, _)| *id == cache)
+        .map(|(_, stats)| *stats)
+}
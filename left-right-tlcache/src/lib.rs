@@ -28,16 +28,36 @@
 //!   - declaring a thread-local `ReadHandleCache` object
 //!
 //! Note: providers must be Sync since the thread-local caches for distinct threads will poll them.
+//!
+//! A generic provider, [`ReadHandleRegistry`], is available for users that do not need a custom one.
+//! Macro `make_readhandle_provider!` declares a provider type backed by it.
 
-use ahash::RandomState;
 use left_right::{ReadHandle, ReadHandleFactory};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::thread::LocalKey;
 use thiserror::Error;
 
+mod counters;
+mod lru;
+mod refresh;
+mod registry;
+pub use counters::ReadHandleCacheStats;
+use counters::{
+    CacheCounters, register_counters, registered_counters, retire_counters, retired_stats,
+};
+use lru::LruMap;
+pub use refresh::RefreshPolicy;
+use refresh::RefreshState;
+pub use registry::{ReadHandleRegistry, ReadHandleRegistryError};
+
+/// The name under which [`ReadHandleCache::thread_stats`] reports the counters of the threads
+/// that exited
+pub const RETIRED_THREADS: &str = "retired";
+
 pub trait ReadHandleProvider: Sync {
     type Data;
     type Key;
@@ -127,8 +147,12 @@ impl<T: Identity<K>, K: PartialEq> ReadHandleEntry<T, K> {
 }
 
 pub struct ReadHandleCache<K: Hash + Eq + Clone, T> {
-    handles: RefCell<HashMap<K, ReadHandleEntry<T, K>, RandomState>>,
+    handles: RefCell<LruMap<K, ReadHandleEntry<T, K>>>,
     refresh_version: RefCell<u64>, // version when last refresh mas made
+    counters: Arc<CacheCounters>,
+    registered: Cell<Option<usize>>, // id of the cache, once the counters are registered
+    refresh: RefreshState,
+    capacity: Cell<usize>, // usize::MAX means unbounded
 }
 impl<K, T> ReadHandleCache<K, T>
 where
@@ -137,30 +161,135 @@ where
 {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
+        Self::with_refresh_policy(RefreshPolicy::Manual)
+    }
+
+    /// Create a cache that refreshes itself according to the given [`RefreshPolicy`]
+    pub fn with_refresh_policy(policy: RefreshPolicy) -> Self {
         Self {
-            handles: RefCell::new(HashMap::with_hasher(RandomState::with_seed(0))),
+            handles: RefCell::new(LruMap::new()),
             refresh_version: RefCell::new(0),
+            counters: Arc::new(CacheCounters::default()),
+            registered: Cell::new(None),
+            refresh: RefreshState::new(policy),
+            capacity: Cell::new(usize::MAX),
+        }
+    }
+
+    /// Bound the number of entries of the cache. When full, the least recently used entries
+    /// are evicted to make room for new ones.
+    #[must_use]
+    pub fn bounded(self, capacity: usize) -> Self {
+        self.capacity.set(capacity.max(1));
+        self
+    }
+
+    /// Change the maximum number of entries of the cache of the calling thread. `None` makes
+    /// the cache unbounded. Entries in excess are evicted immediately. Since unbounded caches do
+    /// not keep track of the uses of their entries, bounding one evicts the oldest entries first.
+    pub fn set_capacity(thread_local: &'static LocalKey<Self>, capacity: Option<usize>) {
+        thread_local.with(|local| {
+            local
+                .capacity
+                .set(capacity.map_or(usize::MAX, |capacity| capacity.max(1)));
+            let mut handles = local.handles.borrow_mut();
+            local.evict(thread_local, &mut handles, None);
+        });
+    }
+
+    /// Get the maximum number of entries of the cache of the calling thread, if bounded
+    pub fn capacity(thread_local: &'static LocalKey<Self>) -> Option<usize> {
+        thread_local.with(|local| {
+            let capacity = local.capacity.get();
+            (capacity != usize::MAX).then_some(capacity)
+        })
+    }
+
+    /// Evict the least recently used entries in excess of the capacity, sparing the entry
+    /// for the key `keep`, if any.
+    fn evict(
+        &self,
+        thread_local: &'static LocalKey<Self>,
+        handles: &mut LruMap<K, ReadHandleEntry<T, K>>,
+        keep: Option<&K>,
+    ) {
+        let mut evicted = 0;
+        while handles.len() > self.capacity.get() && handles.pop_lru(keep).is_some() {
+            evicted += 1;
+        }
+        if evicted == 0 {
+            return;
+        }
+        let counters = self.counters(thread_local);
+        counters.evicted(evicted);
+        counters.set_entries(handles.len());
+    }
+
+    /// Change the [`RefreshPolicy`] of the cache of the calling thread
+    pub fn set_refresh_policy(thread_local: &'static LocalKey<Self>, policy: RefreshPolicy) {
+        thread_local.with(|local| local.refresh.set_policy(policy));
+    }
+
+    /// Get the [`RefreshPolicy`] of the cache of the calling thread
+    pub fn refresh_policy(thread_local: &'static LocalKey<Self>) -> RefreshPolicy {
+        thread_local.with(|local| local.refresh.policy())
+    }
+
+    /// Get the counters of this thread's cache, making them visible to other threads
+    /// (for aggregation) the first time.
+    fn counters(&self, thread_local: &'static LocalKey<Self>) -> &CacheCounters {
+        if self.registered.get().is_none() {
+            let cache = Self::cache_id(thread_local);
+            self.registered.set(Some(cache));
+            register_counters(cache, Arc::clone(&self.counters));
         }
+        &self.counters
+    }
+
+    /// Identifies a thread-local cache declaration, by the address of its (static) key.
+    fn cache_id(thread_local: &'static LocalKey<Self>) -> usize {
+        std::ptr::from_ref(thread_local) as usize
     }
+
     pub fn get_reader(
         thread_local: &'static LocalKey<Self>,
         key: K,
         provider: &impl ReadHandleProvider<Data = T, Key = K>,
     ) -> Result<Rc<ReadHandle<T>>, ReadHandleCacheError<K>> {
+        // refresh the whole cache first if the policy says so and the provider changed
+        let stale = thread_local.with(|local| {
+            local.refresh.should_check()
+                && *local.refresh_version.borrow() != provider.get_version()
+        });
+        if stale {
+            Self::refresh(thread_local, provider);
+        }
+
         thread_local.with(|local| {
+            let counters = local.counters(thread_local);
             let mut map = local.handles.borrow_mut();
 
             // cache has a valid handle for that key
-            if let Some(entry) = map.get(&key)
-                && entry.is_valid(&key, provider)
-            {
-                return Ok(Rc::clone(&entry.rhandle));
+            if let Some(entry) = map.get(&key) {
+                if entry.is_valid(&key, provider) {
+                    counters.hit();
+                    let rhandle = Rc::clone(&entry.rhandle);
+                    // keeping track of uses is only needed for evictions: spare the cost to
+                    // unbounded caches, whose entries then age by insertion only
+                    if local.capacity.get() != usize::MAX {
+                        map.touch(&key);
+                    }
+                    return Ok(rhandle);
+                }
+                counters.invalidated(1);
             }
+            counters.miss();
 
             // get a factory for the key from the provider to build a fresh handle from it
             // provider returns identity of object and version for entry invalidation
             let (factory, identity, version) = provider.get_factory(&key).ok_or_else(|| {
                 map.remove(&key);
+                counters.set_entries(map.len());
                 ReadHandleCacheError::NotFound(key.clone())
             })?;
 
@@ -168,7 +297,17 @@ where
             let rhandle = factory.handle();
             if rhandle.was_dropped() {
                 // can remove element with key, but also all which point to the same identity
-                map.retain(|_key, entry| entry.identity != identity);
+                // (the entry for the key itself, if any, was already accounted as invalidated)
+                let mut removed = 0;
+                map.retain(|k, entry| {
+                    let keep = entry.identity != identity;
+                    if !keep && *k != key {
+                        removed += 1;
+                    }
+                    keep
+                });
+                counters.invalidated(removed);
+                counters.set_entries(map.len());
                 return Err(ReadHandleCacheError::NotAccessible(key.clone()));
             }
 
@@ -181,11 +320,11 @@ where
             // and avoids having duplicate readhandles for the same T, which should expedite checks with many read handles
             // if T's are accessed by multiple keys.
             if key != identity {
-                map.insert(
-                    identity.clone(),
-                    ReadHandleEntry::new(identity, Rc::clone(&rhandle), version),
-                );
+                let entry = ReadHandleEntry::new(identity.clone(), Rc::clone(&rhandle), version);
+                map.insert(identity, entry);
             }
+            counters.set_entries(map.len());
+            local.evict(thread_local, &mut map, Some(&key));
             Ok(rhandle)
         })
     }
@@ -194,17 +333,51 @@ where
         thread_local.with(|local| {
             local.handles.borrow_mut().clear();
             *local.refresh_version.borrow_mut() = 0;
+            local.counters(thread_local).set_entries(0);
         });
     }
 
     #[allow(unused)]
     fn purge_unreadable(thread_local: &'static LocalKey<Self>) {
         thread_local.with(|local| {
+            let counters = local.counters(thread_local);
             let mut handles = local.handles.borrow_mut();
+            let before = handles.len();
             handles.retain(|_, e| !e.rhandle.was_dropped());
+            counters.invalidated(before - handles.len());
+            counters.set_entries(handles.len());
         });
     }
 
+    /// Get the counters of the cache of the calling thread
+    pub fn stats(thread_local: &'static LocalKey<Self>) -> ReadHandleCacheStats {
+        thread_local.with(|local| local.counters(thread_local).stats())
+    }
+
+    /// Get the counters of the caches of all the live threads that used the cache, along with
+    /// the name of each thread (or its id, if unnamed). The counters of the threads that exited
+    /// are added up under the name [`RETIRED_THREADS`].
+    pub fn thread_stats(
+        thread_local: &'static LocalKey<Self>,
+    ) -> Vec<(String, ReadHandleCacheStats)> {
+        let cache = Self::cache_id(thread_local);
+        registered_counters(cache)
+            .iter()
+            .map(|counters| (counters.thread().to_owned(), counters.stats()))
+            .chain(retired_stats(cache).map(|stats| (RETIRED_THREADS.to_owned(), stats)))
+            .collect()
+    }
+
+    /// Get the counters of the caches of all threads, including those that exited, added up
+    pub fn aggregated_stats(thread_local: &'static LocalKey<Self>) -> ReadHandleCacheStats {
+        let cache = Self::cache_id(thread_local);
+        registered_counters(cache)
+            .iter()
+            .map(|counters| counters.stats())
+            .chain(retired_stats(cache))
+            .sum()
+    }
+
     // Do a full refresh of the cache
     pub fn refresh(
         thread_local: &'static LocalKey<Self>,
@@ -243,28 +416,27 @@ where
             let mut handles = local.handles.borrow_mut();
 
             // purge all unusable readers
+            let before = handles.len();
             handles.retain(|_key, entry| !entry.rhandle.was_dropped());
+            let counters = local.counters(thread_local);
+            counters.invalidated(before - handles.len());
+            counters.refreshed();
 
             // update primaries first and store an Rc of the latest rhandles in a temporary map
             let mut temporary = HashMap::new();
             for (key, factory, id) in primaries {
-                handles
-                    .entry(key.clone())
-                    .and_modify(|e| {
-                        if e.version != version {
-                            *e = ReadHandleEntry::new(
-                                id.clone(),
-                                Rc::new(factory.handle()),
-                                version,
-                            );
-                        }
-                        temporary.insert(id.clone(), Rc::clone(&e.rhandle));
-                    })
-                    .or_insert_with(|| {
-                        let rhandle = Rc::new(factory.handle());
-                        temporary.insert(key, Rc::clone(&rhandle));
-                        ReadHandleEntry::new(id, rhandle, version)
-                    });
+                let rhandle = if let Some(e) = handles.get_mut(&key) {
+                    if e.version != version {
+                        *e = ReadHandleEntry::new(id.clone(), Rc::new(factory.handle()), version);
+                    }
+                    Rc::clone(&e.rhandle)
+                } else {
+                    let rhandle = Rc::new(factory.handle());
+                    let entry = ReadHandleEntry::new(id.clone(), Rc::clone(&rhandle), version);
+                    handles.insert(key, entry);
+                    rhandle
+                };
+                temporary.insert(id, rhandle);
             }
             // update entries for aliases to reuse primaries' handles, using the temporary map
             for (key, _factory, id) in aliases {
@@ -281,6 +453,8 @@ where
             }
 
             *local.refresh_version.borrow_mut() = version;
+            counters.set_entries(handles.len());
+            local.evict(thread_local, &mut handles, None);
         });
     }
 
@@ -310,6 +484,15 @@ where
     }
 }
 
+impl<K: Hash + Eq + Clone, T> Drop for ReadHandleCache<K, T> {
+    fn drop(&mut self) {
+        // the counters of a thread outlive its cache, as part of the retired counters
+        if let Some(cache) = self.registered.get() {
+            retire_counters(cache, &self.counters);
+        }
+    }
+}
+
 /// Create a thread-local `ReadHandleCache` with a given name, to access
 /// `ReadHandle<T>`'s identified with some type of key.
 /// Example:
@@ -325,6 +508,24 @@ where
 /// }
 ///
 /// make_thread_local_readhandle_cache!(MYCACHE, u32, LeftRightWrappedType);
+///
+/// // a cache that checks whether it must be refreshed on every lookup
+/// use dataplane_left_right_tlcache::RefreshPolicy;
+/// make_thread_local_readhandle_cache!(
+///     MYCACHE_AUTO,
+///     u32,
+///     LeftRightWrappedType,
+///     RefreshPolicy::OnVersionChange
+/// );
+///
+/// // a cache with at most 1024 entries
+/// make_thread_local_readhandle_cache!(
+///     MYCACHE_BOUNDED,
+///     u32,
+///     LeftRightWrappedType,
+///     RefreshPolicy::Manual,
+///     1024
+/// );
 /// ```
 #[macro_export]
 macro_rules! make_thread_local_readhandle_cache {
@@ -333,6 +534,18 @@ macro_rules! make_thread_local_readhandle_cache {
             static $name: ReadHandleCache<$key_t, $rhandle_t> = ReadHandleCache::new();
         }
     };
+    ($name:ident, $key_t:ty, $rhandle_t:ty, $policy:expr) => {
+        thread_local! {
+            static $name: ReadHandleCache<$key_t, $rhandle_t> =
+                ReadHandleCache::with_refresh_policy($policy);
+        }
+    };
+    ($name:ident, $key_t:ty, $rhandle_t:ty, $policy:expr, $capacity:expr) => {
+        thread_local! {
+            static $name: ReadHandleCache<$key_t, $rhandle_t> =
+                ReadHandleCache::with_refresh_policy($policy).bounded($capacity);
+        }
+    };
 }
 
 #[cfg(test)]
@@ -776,4 +989,197 @@ mod tests {
         let vec: Vec<(u64, Rc<ReadHandle<TestStruct>>)> = iterator.collect();
         assert_eq!(vec.len() as u64, (NUM_HANDLES - 1) * 2);
     }
+
+    #[serial]
+    #[test]
+    fn test_readhandle_cache_stats() {
+        ReadHandleCache::purge(&TEST_CACHE);
+        let base = ReadHandleCache::stats(&TEST_CACHE);
+        assert_eq!(base.entries, 0);
+
+        let mut provider = TestProvider::new();
+        provider.add_object(1, 1);
+        provider.add_object(2, 2);
+        provider.add_object(6000, 1);
+
+        // first access misses, subsequent ones hit
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        ReadHandleCache::get_reader(&TEST_CACHE, 6000, &provider).unwrap();
+        let stats = ReadHandleCache::stats(&TEST_CACHE);
+        assert_eq!(stats.hits - base.hits, 2);
+        assert_eq!(stats.misses - base.misses, 2);
+        assert_eq!(stats.entries, 2);
+
+        // lookups for unknown keys are misses
+        assert!(ReadHandleCache::get_reader(&TEST_CACHE, 3, &provider).is_err());
+        let stats = ReadHandleCache::stats(&TEST_CACHE);
+        assert_eq!(stats.misses - base.misses, 3);
+
+        // dropping the object invalidates the entry for the key and its alias
+        provider.drop_writer(1);
+        assert!(ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).is_err());
+        let stats = ReadHandleCache::stats(&TEST_CACHE);
+        assert_eq!(stats.invalidations - base.invalidations, 2);
+        assert_eq!(stats.entries, 0);
+
+        // a refresh is accounted only if the provider version changed
+        ReadHandleCache::refresh(&TEST_CACHE, &provider);
+        ReadHandleCache::refresh(&TEST_CACHE, &provider);
+        let stats = ReadHandleCache::stats(&TEST_CACHE);
+        assert_eq!(stats.refreshes - base.refreshes, 1);
+        assert_eq!(stats.entries, 1);
+
+        // counters of other threads are aggregated
+        let before = ReadHandleCache::aggregated_stats(&TEST_CACHE);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                ReadHandleCache::get_reader(&TEST_CACHE, 2, &provider).unwrap();
+                ReadHandleCache::get_reader(&TEST_CACHE, 2, &provider).unwrap();
+            });
+        });
+        let after = ReadHandleCache::aggregated_stats(&TEST_CACHE);
+        assert_eq!(after.hits - before.hits, 1);
+        assert_eq!(after.misses - before.misses, 1);
+        assert!(ReadHandleCache::thread_stats(&TEST_CACHE).len() >= 2);
+
+        // the counters of exited threads are not reported on their own, but kept in the totals
+        std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .name("tlcache-exiting".to_owned())
+                .spawn_scoped(scope, || {
+                    ReadHandleCache::get_reader(&TEST_CACHE, 2, &provider).unwrap();
+                })
+                .unwrap()
+                .join()
+                .unwrap();
+        });
+        let threads = ReadHandleCache::thread_stats(&TEST_CACHE);
+        assert!(!threads.iter().any(|(name, _)| name == "tlcache-exiting"));
+        let (_, retired) = threads
+            .iter()
+            .find(|(name, _)| name == RETIRED_THREADS)
+            .expect("Should report retired threads");
+        assert_eq!(retired.entries, 0);
+        let exited = ReadHandleCache::aggregated_stats(&TEST_CACHE);
+        assert_eq!(exited.misses - after.misses, 1);
+    }
+
+    #[serial]
+    #[test]
+    fn test_readhandle_cache_refresh_policy() {
+        ReadHandleCache::purge(&TEST_CACHE);
+        assert_eq!(
+            ReadHandleCache::refresh_policy(&TEST_CACHE),
+            RefreshPolicy::Manual
+        );
+
+        let mut provider = TestProvider::new();
+        provider.add_object(1, 1);
+        provider.add_object(2, 2);
+        provider.add_object(6000, 1);
+        provider.mod_object(1, "object-1");
+        provider.mod_object(2, "object-2");
+
+        // with the manual policy, looking up a key does not populate other entries
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        TEST_CACHE.with(|local| assert_eq!(local.handles.borrow().len(), 1));
+
+        // on version change, a lookup refreshes the whole cache, including aliases
+        ReadHandleCache::set_refresh_policy(&TEST_CACHE, RefreshPolicy::OnVersionChange);
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        TEST_CACHE.with(|local| assert_eq!(local.handles.borrow().len(), 3));
+        let refreshes = ReadHandleCache::stats(&TEST_CACHE).refreshes;
+
+        // no refresh if the version did not change
+        ReadHandleCache::get_reader(&TEST_CACHE, 2, &provider).unwrap();
+        assert_eq!(ReadHandleCache::stats(&TEST_CACHE).refreshes, refreshes);
+
+        // alias re-assigned: the next lookup of an unrelated key refreshes the cache, so
+        // that the alias points to the new object
+        provider.add_object(6000, 2);
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        assert_eq!(ReadHandleCache::stats(&TEST_CACHE).refreshes, refreshes + 1);
+        TEST_CACHE.with(|local| {
+            let handles = local.handles.borrow();
+            assert_eq!(handles.get(&6000).unwrap().identity, 2);
+        });
+
+        // check every 3 lookups
+        let every = std::num::NonZero::new(3).unwrap();
+        ReadHandleCache::set_refresh_policy(&TEST_CACHE, RefreshPolicy::EveryLookups(every));
+        provider.add_object(7000, 1);
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        assert_eq!(ReadHandleCache::stats(&TEST_CACHE).refreshes, refreshes + 1);
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        assert_eq!(ReadHandleCache::stats(&TEST_CACHE).refreshes, refreshes + 2);
+        TEST_CACHE.with(|local| assert!(local.handles.borrow().contains_key(&7000)));
+
+        // check at most once per (long) interval
+        ReadHandleCache::set_refresh_policy(
+            &TEST_CACHE,
+            RefreshPolicy::EveryInterval(std::time::Duration::from_secs(3600)),
+        );
+        provider.add_object(8000, 1);
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+        assert_eq!(ReadHandleCache::stats(&TEST_CACHE).refreshes, refreshes + 2);
+
+        ReadHandleCache::set_refresh_policy(&TEST_CACHE, RefreshPolicy::Manual);
+    }
+
+    #[serial]
+    #[test]
+    fn test_readhandle_cache_capacity() {
+        ReadHandleCache::purge(&TEST_CACHE);
+        ReadHandleCache::set_capacity(&TEST_CACHE, Some(3));
+        assert_eq!(ReadHandleCache::capacity(&TEST_CACHE), Some(3));
+        let base = ReadHandleCache::stats(&TEST_CACHE);
+
+        let mut provider = TestProvider::new();
+        for id in 1..=5 {
+            provider.add_object(id, id);
+        }
+
+        // fill the cache, then use 1 so that 2 becomes the least recently used entry
+        for id in 1..=3 {
+            ReadHandleCache::get_reader(&TEST_CACHE, id, &provider).unwrap();
+        }
+        ReadHandleCache::get_reader(&TEST_CACHE, 1, &provider).unwrap();
+
+        ReadHandleCache::get_reader(&TEST_CACHE, 4, &provider).unwrap();
+        TEST_CACHE.with(|local| {
+            let handles = local.handles.borrow();
+            assert_eq!(handles.len(), 3);
+            assert!(!handles.contains_key(&2));
+            assert!(handles.contains_key(&1));
+            assert!(handles.contains_key(&4));
+        });
+        let stats = ReadHandleCache::stats(&TEST_CACHE);
+        assert_eq!(stats.evictions - base.evictions, 1);
+        assert_eq!(stats.entries, 3);
+
+        // evicted entries are transparently re-created
+        let h = ReadHandleCache::get_reader(&TEST_CACHE, 2, &provider).unwrap();
+        assert_eq!(h.enter().unwrap().id, 2);
+        assert_eq!(
+            ReadHandleCache::stats(&TEST_CACHE).evictions - base.evictions,
+            2
+        );
+
+        // a refresh does not make the cache exceed its capacity
+        ReadHandleCache::refresh(&TEST_CACHE, &provider);
+        TEST_CACHE.with(|local| assert_eq!(local.handles.borrow().len(), 3));
+
+        // shrinking evicts immediately; unbounding stops evictions
+        ReadHandleCache::set_capacity(&TEST_CACHE, Some(1));
+        TEST_CACHE.with(|local| assert_eq!(local.handles.borrow().len(), 1));
+        ReadHandleCache::set_capacity(&TEST_CACHE, None);
+        assert_eq!(ReadHandleCache::capacity(&TEST_CACHE), None);
+        for id in 1..=5 {
+            ReadHandleCache::get_reader(&TEST_CACHE, id, &provider).unwrap();
+        }
+        TEST_CACHE.with(|local| assert_eq!(local.handles.borrow().len(), 5));
+    }
 }
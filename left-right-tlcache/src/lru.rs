@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! A hash map that keeps its keys ordered by their last use, to evict the least recently used
+//! entries of a bounded `ReadHandleCache`.
+//! Every insertion or use of an entry stamps it with the next value of a logical clock, and keys
+//! are indexed by their stamp. Marking an entry as used and evicting the least recently used entry
+//! are both O(log n).
+
+use ahash::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+pub(crate) struct LruMap<K, V> {
+    entries: HashMap<K, (V, u64), RandomState>,
+    order: BTreeMap<u64, K>, // keys by the value of the clock when last used, oldest first
+    clock: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> LruMap<K, V> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: HashMap::with_hasher(RandomState::with_seed(0)),
+            order: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+    #[cfg(test)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    #[cfg(test)]
+    pub(crate) fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+    /// Get the value for a key, without marking it as used
+    pub(crate) fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|(value, _)| value)
+    }
+    /// Get the value for a key mutably, without marking it as used
+    pub(crate) fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.entries.get_mut(key).map(|(value, _)| value)
+    }
+    /// Mark the entry for a key as the most recently used one
+    pub(crate) fn touch(&mut self, key: &K) {
+        let Some((_, used)) = self.entries.get_mut(key) else {
+            return;
+        };
+        self.clock += 1;
+        self.order.remove(used);
+        *used = self.clock;
+        self.order.insert(self.clock, key.clone());
+    }
+    /// Insert a value as the most recently used one, returning the value it replaced, if any
+    pub(crate) fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.clock += 1;
+        let old = self.entries.insert(key.clone(), (value, self.clock));
+        self.order.insert(self.clock, key);
+        old.map(|(value, used)| {
+            self.order.remove(&used);
+            value
+        })
+    }
+    pub(crate) fn remove(&mut self, key: &K) -> Option<V> {
+        let (value, used) = self.entries.remove(key)?;
+        self.order.remove(&used);
+        Some(value)
+    }
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        let order = &mut self.order;
+        self.entries.retain(|key, (value, used)| {
+            let keep = f(key, value);
+            if !keep {
+                order.remove(used);
+            }
+            keep
+        });
+    }
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, (value, _))| (key, value))
+    }
+    /// Remove the least recently used entry, sparing the one for the key `spare`, if any.
+    /// Returns the key of the entry removed.
+    pub(crate) fn pop_lru(&mut self, spare: Option<&K>) -> Option<K> {
+        let (&used, key) = self.order.iter().find(|(_, key)| Some(*key) != spare)?;
+        let key = key.clone();
+        self.order.remove(&used);
+        self.entries.remove(&key);
+        Some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruMap;
+
+    #[test]
+    fn test_lru_map_order() {
+        let mut map = LruMap::new();
+        for key in 1..=4 {
+            map.insert(key, key * 10);
+        }
+        // use 1, re-insert 2: 3 is now the least recently used entry, then 4
+        map.touch(&1);
+        assert_eq!(map.insert(2, 21), Some(20));
+        assert_eq!(map.pop_lru(None), Some(3));
+        assert_eq!(map.pop_lru(Some(&4)), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&2), Some(&21));
+
+        // removed entries are no longer candidates for eviction
+        map.retain(|key, _| *key != 4);
+        assert_eq!(map.remove(&2), Some(21));
+        assert!(map.is_empty());
+        assert_eq!(map.pop_lru(None), None);
+    }
+}
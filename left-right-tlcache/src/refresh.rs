@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Policies to automatically refresh a thread-local read-handle cache.
+//! By default, a `ReadHandleCache` is only fully refreshed when explicitly asked to. Entries are
+//! validated when looked up, but a thread that keeps looking up the same keys may keep using
+//! handles that a refresh would have replaced. A [`RefreshPolicy`] makes lookups check whether
+//! the provider changed and refresh the cache if so. Checking the version of the provider is
+//! cheap, but not free: policies can limit how often this is done.
+
+use std::cell::Cell;
+use std::num::NonZero;
+use std::time::{Duration, Instant};
+
+/// When to check if a `ReadHandleCache` needs to be refreshed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RefreshPolicy {
+    /// Only refresh when `ReadHandleCache::refresh` is called
+    #[default]
+    Manual,
+    /// Check on every lookup if the version of the provider changed, and refresh if so
+    OnVersionChange,
+    /// Check if the version of the provider changed at most once every N lookups
+    EveryLookups(NonZero<u64>),
+    /// Check if the version of the provider changed at most once per interval
+    EveryInterval(Duration),
+}
+
+/// Per-thread state to apply a [`RefreshPolicy`]
+#[derive(Debug)]
+pub(crate) struct RefreshState {
+    policy: Cell<RefreshPolicy>,
+    lookups: Cell<u64>,
+    last_check: Cell<Instant>,
+}
+
+impl RefreshState {
+    pub(crate) fn new(policy: RefreshPolicy) -> Self {
+        Self {
+            policy: Cell::new(policy),
+            lookups: Cell::new(0),
+            last_check: Cell::new(Instant::now()),
+        }
+    }
+    pub(crate) fn policy(&self) -> RefreshPolicy {
+        self.policy.get()
+    }
+    pub(crate) fn set_policy(&self, policy: RefreshPolicy) {
+        self.policy.set(policy);
+        self.lookups.set(0);
+        self.last_check.set(Instant::now());
+    }
+    /// Tell if the cache should check the version of the provider, on a lookup.
+    pub(crate) fn should_check(&self) -> bool {
+        match self.policy.get() {
+            RefreshPolicy::Manual => false,
+            RefreshPolicy::OnVersionChange => true,
+            RefreshPolicy::EveryLookups(every) => {
+                let lookups = self.lookups.get() + 1;
+                if lookups >= every.get() {
+                    self.lookups.set(0);
+                    true
+                } else {
+                    self.lookups.set(lookups);
+                    false
+                }
+            }
+            RefreshPolicy::EveryInterval(interval) => {
+                let now = Instant::now();
+                if now.saturating_duration_since(self.last_check.get()) >= interval {
+                    self.last_check.set(now);
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,429 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! A generic, keyed registry of left-right objects implementing [`ReadHandleProvider`].
+//! The registry keeps a `ReadHandleFactory<T>` for every object, which can be reached by its
+//! identity or by any number of aliases. The version of the registry is bumped on every change
+//! to the set of objects or aliases, as required by [`ReadHandleProvider::get_version`].
+//!
+//! The registry does not own the `WriteHandle`s of the objects: a `WriteHandle<T, O>` is only
+//! `Send` if T is, while factories are `Send` and `Sync` for any T. Keeping the writers out
+//! makes the registry usable for objects that cannot be sent across threads (e.g. objects with
+//! `Rc`-shared internals). Objects whose writer is dropped can no longer be read, and are
+//! discarded by the caches.
+
+use crate::ReadHandleProvider;
+use ahash::RandomState;
+use left_right::{Absorb, ReadHandleFactory, WriteHandle};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use thiserror::Error;
+
+#[derive(Debug, PartialEq, Error)]
+pub enum ReadHandleRegistryError<K> {
+    #[error("No object is registered with key {0}")]
+    NotFound(K),
+    #[error("Key {0} is already in use")]
+    KeyInUse(K),
+    #[error("Key {0} is not an alias")]
+    NotAlias(K),
+}
+
+/// An object in a [`ReadHandleRegistry`]. Aliases hold a copy of the factory of the object
+/// they refer to.
+struct RegistryEntry<K, T> {
+    id: K,
+    factory: ReadHandleFactory<T>,
+}
+
+/// A keyed registry of `ReadHandleFactory<T>`s, usable as a [`ReadHandleProvider`] for a
+/// [`crate::ReadHandleCache`].
+pub struct ReadHandleRegistry<K, T> {
+    version: u64,
+    entries: HashMap<K, RegistryEntry<K, T>, RandomState>,
+}
+
+impl<K: Debug, T> Debug for ReadHandleRegistry<K, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadHandleRegistry")
+            .field("version", &self.version)
+            .field(
+                "entries",
+                &self
+                    .entries
+                    .iter()
+                    .map(|(key, entry)| (key, &entry.id))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<K, T> Default for ReadHandleRegistry<K, T>
+where
+    K: Hash + Eq + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, T> ReadHandleRegistry<K, T>
+where
+    K: Hash + Eq + Clone,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            version: 0,
+            entries: HashMap::with_hasher(RandomState::with_seed(0)),
+        }
+    }
+
+    fn bump_version(&mut self) {
+        self.version = self.version.wrapping_add(1);
+    }
+
+    /// Get the current version of the registry
+    #[must_use]
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Number of keys (identities and aliases) in the registry
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Tell if some key (identity or alias) is registered
+    #[must_use]
+    pub fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Tell the identity of the object reachable with the given key, if any
+    #[must_use]
+    pub fn identity(&self, key: &K) -> Option<&K> {
+        self.entries.get(key).map(|entry| &entry.id)
+    }
+
+    /// Get the factory of the object reachable with the given key, if any
+    #[must_use]
+    pub fn factory(&self, key: &K) -> Option<&ReadHandleFactory<T>> {
+        self.entries.get(key).map(|entry| &entry.factory)
+    }
+
+    /// Create a new left-right object with the given identity and register it. The caller
+    /// owns the returned `WriteHandle`: the object can be read as long as the latter lives.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the identity is already in use as identity or alias.
+    pub fn add<O>(
+        &mut self,
+        id: K,
+        data: T,
+    ) -> Result<WriteHandle<T, O>, ReadHandleRegistryError<K>>
+    where
+        T: Absorb<O> + Clone,
+    {
+        if self.entries.contains_key(&id) {
+            return Err(ReadHandleRegistryError::KeyInUse(id));
+        }
+        let (writer, _) = left_right::new_from_empty(data);
+        self.add_factory(id, writer.factory())?;
+        Ok(writer)
+    }
+
+    /// Register an object, given a factory for it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the identity is already in use as identity or alias.
+    pub fn add_factory(
+        &mut self,
+        id: K,
+        factory: ReadHandleFactory<T>,
+    ) -> Result<(), ReadHandleRegistryError<K>> {
+        if self.entries.contains_key(&id) {
+            return Err(ReadHandleRegistryError::KeyInUse(id));
+        }
+        let entry = RegistryEntry {
+            id: id.clone(),
+            factory,
+        };
+        self.entries.insert(id, entry);
+        self.bump_version();
+        Ok(())
+    }
+
+    /// Make the object with identity `id` reachable with key `alias` too.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the alias is already in use or if no object has identity `id`.
+    pub fn add_alias(&mut self, alias: K, id: &K) -> Result<(), ReadHandleRegistryError<K>> {
+        if self.entries.contains_key(&alias) {
+            return Err(ReadHandleRegistryError::KeyInUse(alias));
+        }
+        let entry = self
+            .entries
+            .get(id)
+            .filter(|entry| entry.id == *id)
+            .ok_or_else(|| ReadHandleRegistryError::NotFound(id.clone()))?;
+        let entry = RegistryEntry {
+            id: id.clone(),
+            factory: entry.factory.clone(),
+        };
+        self.entries.insert(alias, entry);
+        self.bump_version();
+        Ok(())
+    }
+
+    /// Remove an alias. Identities are not removed by this method.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key is not registered or is not an alias.
+    pub fn remove_alias(&mut self, alias: &K) -> Result<(), ReadHandleRegistryError<K>> {
+        match self.entries.get(alias) {
+            None => Err(ReadHandleRegistryError::NotFound(alias.clone())),
+            Some(entry) if entry.id == *alias => {
+                Err(ReadHandleRegistryError::NotAlias(alias.clone()))
+            }
+            Some(_) => {
+                self.entries.remove(alias);
+                self.bump_version();
+                Ok(())
+            }
+        }
+    }
+
+    /// Remove the object with identity `id`, along with all its aliases. The object remains
+    /// readable by the handles obtained before, as long as its writer lives.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no object has identity `id`.
+    pub fn remove(&mut self, id: &K) -> Result<(), ReadHandleRegistryError<K>> {
+        if self.identity(id) != Some(id) {
+            return Err(ReadHandleRegistryError::NotFound(id.clone()));
+        }
+        self.entries.retain(|_, entry| entry.id != *id);
+        self.bump_version();
+        Ok(())
+    }
+}
+
+impl<K, T> ReadHandleProvider for ReadHandleRegistry<K, T>
+where
+    K: Hash + Eq + Clone + Sync,
+{
+    type Data = T;
+    type Key = K;
+    fn get_factory(
+        &self,
+        key: &Self::Key,
+    ) -> Option<(&ReadHandleFactory<Self::Data>, Self::Key, u64)> {
+        self.entries
+            .get(key)
+            .map(|entry| (&entry.factory, entry.id.clone(), self.version))
+    }
+    fn get_identity(&self, key: &Self::Key) -> Option<Self::Key> {
+        self.entries.get(key).map(|entry| entry.id.clone())
+    }
+    fn get_version(&self) -> u64 {
+        self.version
+    }
+    fn get_iter(
+        &self,
+    ) -> (
+        u64,
+        impl Iterator<Item = (Self::Key, &ReadHandleFactory<Self::Data>, Self::Key)>,
+    ) {
+        let iter = self
+            .entries
+            .iter()
+            .map(|(key, entry)| (key.clone(), &entry.factory, entry.id.clone()));
+        (self.version, iter)
+    }
+}
+
+/// Declare a read-handle provider type backed by a [`ReadHandleRegistry`]. The declared
+/// type derefs to the registry, implements [`ReadHandleProvider`], and, being local to the
+/// declaring crate, can be extended with inherent methods or trait implementations (e.g.
+/// `Absorb`, to make the provider itself a left-right object).
+/// Example:
+/// ```
+/// use dataplane_left_right_tlcache::make_readhandle_provider;
+///
+/// #[derive(Clone)]
+/// struct Counter(u64);
+/// struct Increment;
+/// impl left_right::Absorb<Increment> for Counter {
+///     fn absorb_first(&mut self, _op: &mut Increment, _other: &Self) {
+///         self.0 += 1;
+///     }
+///     fn sync_with(&mut self, first: &Self) {
+///         *self = first.clone();
+///     }
+/// }
+///
+/// make_readhandle_provider!(pub CounterProvider, u32, Counter);
+///
+/// let mut provider = CounterProvider::default();
+/// let mut writer = provider.add::<Increment>(1, Counter(0)).unwrap();
+/// writer.append(Increment).publish();
+/// ```
+#[macro_export]
+macro_rules! make_readhandle_provider {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $key_t:ty, $data_t:ty) => {
+        $(#[$attr])*
+        #[derive(Default, Debug)]
+        $vis struct $name($crate::ReadHandleRegistry<$key_t, $data_t>);
+
+        impl ::std::ops::Deref for $name {
+            type Target = $crate::ReadHandleRegistry<$key_t, $data_t>;
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+        impl ::std::ops::DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+        impl $crate::ReadHandleProvider for $name {
+            type Data = $data_t;
+            type Key = $key_t;
+            fn get_factory(
+                &self,
+                key: &Self::Key,
+            ) -> Option<(&::left_right::ReadHandleFactory<Self::Data>, Self::Key, u64)> {
+                self.0.get_factory(key)
+            }
+            fn get_identity(&self, key: &Self::Key) -> Option<Self::Key> {
+                self.0.get_identity(key)
+            }
+            fn get_version(&self) -> u64 {
+                self.0.get_version()
+            }
+            fn get_iter(
+                &self,
+            ) -> (
+                u64,
+                impl Iterator<
+                    Item = (
+                        Self::Key,
+                        &::left_right::ReadHandleFactory<Self::Data>,
+                        Self::Key,
+                    ),
+                >,
+            ) {
+                self.0.get_iter()
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Identity, ReadHandleCache, ReadHandleCacheError};
+    use std::rc::Rc;
+
+    // Rc makes objects neither Send nor Sync, as a Fib is
+    #[derive(Debug, Clone)]
+    struct Object {
+        id: u32,
+        value: Rc<u32>,
+    }
+    impl Identity<u32> for Object {
+        fn identity(&self) -> u32 {
+            self.id
+        }
+    }
+    struct SetValue(u32);
+    impl Absorb<SetValue> for Object {
+        fn absorb_first(&mut self, op: &mut SetValue, _other: &Self) {
+            self.value = Rc::new(op.0);
+        }
+        fn sync_with(&mut self, first: &Self) {
+            *self = first.clone();
+        }
+    }
+    fn object(id: u32) -> Object {
+        Object {
+            id,
+            value: Rc::new(0),
+        }
+    }
+
+    crate::make_readhandle_provider!(TestRegistry, u32, Object);
+    crate::make_thread_local_readhandle_cache!(REGISTRY_CACHE, u32, Object);
+
+    #[test]
+    fn test_registry_provider() {
+        fn is_sync<P: Sync>(_: &P) {}
+
+        let mut registry = TestRegistry::default();
+        is_sync(&registry);
+        let mut writer1 = registry.add::<SetValue>(1, object(1)).unwrap();
+        let _writer2 = registry.add::<SetValue>(2, object(2)).unwrap();
+        registry.add_alias(100, &1).unwrap();
+        assert_eq!(registry.len(), 3);
+        assert_eq!(registry.identity(&100), Some(&1));
+
+        // keys in use and unknown identities are rejected, without bumping the version
+        let version = registry.version();
+        assert!(matches!(
+            registry.add::<SetValue>(100, object(100)),
+            Err(ReadHandleRegistryError::KeyInUse(100))
+        ));
+        assert_eq!(
+            registry.add_alias(101, &3),
+            Err(ReadHandleRegistryError::NotFound(3))
+        );
+        assert_eq!(
+            registry.add_alias(101, &100),
+            Err(ReadHandleRegistryError::NotFound(100))
+        );
+        assert_eq!(
+            registry.remove_alias(&1),
+            Err(ReadHandleRegistryError::NotAlias(1))
+        );
+        assert_eq!(registry.version(), version);
+
+        // updates are visible through the identity and the alias, which share the handle
+        writer1.append(SetValue(42)).publish();
+        let reader = ReadHandleCache::get_reader(&REGISTRY_CACHE, 1, &*registry).unwrap();
+        assert_eq!(*reader.enter().unwrap().value, 42);
+        let alias = ReadHandleCache::get_reader(&REGISTRY_CACHE, 100, &*registry).unwrap();
+        assert!(Rc::ptr_eq(&reader, &alias));
+
+        // removing an object drops its aliases
+        registry.remove(&1).unwrap();
+        assert!(registry.version() != version);
+        assert!(!registry.contains(&100));
+        assert_eq!(
+            ReadHandleCache::get_reader(&REGISTRY_CACHE, 100, &*registry).unwrap_err(),
+            ReadHandleCacheError::NotFound(100)
+        );
+
+        // objects whose writer is dropped are not accessible
+        registry.add_factory(1, writer1.factory()).unwrap();
+        drop(writer1);
+        assert!(reader.was_dropped());
+        assert_eq!(
+            ReadHandleCache::get_reader(&REGISTRY_CACHE, 1, &*registry).unwrap_err(),
+            ReadHandleCacheError::NotAccessible(1)
+        );
+    }
+}
@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Compares batched lookups against scalar ones, over tries of each backend with a
+//! routing-table-like set of prefixes, for destinations with and without repetitions.
+//! Run with `cargo bench -p dataplane-lpm`.
+
+use dataplane_lpm::prefix::Ipv4Prefix;
+use dataplane_lpm::trie::{LpmTrie, TrieBackend, TrieMap};
+use std::hint::black_box;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+const PREFIXES: usize = 100_000;
+const DESTINATIONS: usize = 1 << 16;
+const BURST: usize = 32;
+const ROUNDS: usize = 20;
+
+/// A trivial deterministic pseudo-random generator, so that runs are comparable
+struct Lcg(u64);
+impl Lcg {
+    fn next(&mut self) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 32) as u32
+    }
+}
+
+fn build_trie(rng: &mut Lcg, backend: TrieBackend) -> LpmTrie<Ipv4Prefix, u32> {
+    let mut trie = LpmTrie::new(backend);
+    trie.insert(Ipv4Prefix::new(Ipv4Addr::UNSPECIFIED, 0).unwrap(), 0);
+    for value in 0..PREFIXES as u32 {
+        let len = 16 + (rng.next() % 17) as u8;
+        let addr = Ipv4Addr::from_bits(rng.next() & (u32::MAX << (32 - u32::from(len))));
+        trie.insert(Ipv4Prefix::new(addr, len).unwrap(), value);
+    }
+    trie
+}
+
+/// Build destinations, each repeated `repeat` times in a row, as packets of the same flow are
+fn build_destinations(rng: &mut Lcg, repeat: usize) -> Vec<Ipv4Addr> {
+    (0..DESTINATIONS / repeat)
+        .flat_map(|_| std::iter::repeat_n(Ipv4Addr::from_bits(rng.next()), repeat))
+        .collect()
+}
+
+fn scalar(trie: &LpmTrie<Ipv4Prefix, u32>, destinations: &[Ipv4Addr]) -> u64 {
+    let mut sum = 0u64;
+    for burst in destinations.chunks(BURST) {
+        for addr in burst {
+            if let Some((_, value)) = trie.lookup(*addr) {
+                sum += u64::from(*value);
+            }
+        }
+    }
+    sum
+}
+
+fn batch(trie: &LpmTrie<Ipv4Prefix, u32>, destinations: &[Ipv4Addr]) -> u64 {
+    let mut sum = 0u64;
+    let mut results = Vec::with_capacity(BURST);
+    for burst in destinations.chunks(BURST) {
+        results.clear();
+        trie.lookup_batch(burst, &mut results);
+        for (_, value) in results.iter().flatten() {
+            sum += u64::from(**value);
+        }
+    }
+    sum
+}
+
+fn measure(f: impl Fn() -> u64) -> (Duration, u64) {
+    let start = Instant::now();
+    let mut sum = 0;
+    for _ in 0..ROUNDS {
+        sum = black_box(f());
+    }
+    (start.elapsed() / ROUNDS as u32, sum)
+}
+
+fn main() {
+    println!("{PREFIXES} prefixes, {DESTINATIONS} destinations, bursts of {BURST}");
+    for backend in [TrieBackend::PrefixMap, TrieBackend::Compressed] {
+        let mut rng = Lcg(0x5eed);
+        let trie = build_trie(&mut rng, backend);
+        for repeat in [1, 4, 16] {
+            let destinations = build_destinations(&mut rng, repeat);
+            let (t_scalar, s_scalar) = measure(|| scalar(&trie, black_box(&destinations)));
+            let (t_batch, s_batch) = measure(|| batch(&trie, black_box(&destinations)));
+            assert_eq!(s_scalar, s_batch, "batched and scalar lookups differ");
+            let per_lookup = |t: Duration| t.as_nanos() as f64 / DESTINATIONS as f64;
+            println!(
+                "{backend:?}, repeat {repeat:>2}: scalar {:>7.1} ns/lookup, batch {:>7.1} ns/lookup",
+                per_lookup(t_scalar),
+                per_lookup(t_batch)
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Selection of the trie implementation at construction time
+
+use crate::prefix::IpPrefix;
+use crate::trie::{CompressedTrie, EitherIter, PrefixMapTrie, TrieMap, TrieMapFactory, TrieStats};
+use std::borrow::Borrow;
+
+/// The implementations of tries to select from when building an [`LpmTrie`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrieBackend {
+    /// [`PrefixMapTrie`]: cheap updates, moderately fast lookups
+    #[default]
+    PrefixMap,
+    /// [`CompressedTrie`]: costly updates, faster and cache-friendly lookups on large tables
+    Compressed,
+}
+
+/// A trie with the implementation selected when building it
+#[derive(Debug, Clone)]
+pub enum LpmTrie<P: IpPrefix, V> {
+    PrefixMap(PrefixMapTrie<P, V>),
+    Compressed(CompressedTrie<P, V>),
+}
+
+impl<P: IpPrefix, V> LpmTrie<P, V> {
+    /// Create an empty trie, with the indicated [`TrieBackend`]
+    #[must_use]
+    pub fn new(backend: TrieBackend) -> Self {
+        match backend {
+            TrieBackend::PrefixMap => LpmTrie::PrefixMap(PrefixMapTrie::create()),
+            TrieBackend::Compressed => LpmTrie::Compressed(CompressedTrie::create()),
+        }
+    }
+
+    /// Tell the [`TrieBackend`] of this trie
+    #[must_use]
+    pub fn backend(&self) -> TrieBackend {
+        match self {
+            LpmTrie::PrefixMap(_) => TrieBackend::PrefixMap,
+            LpmTrie::Compressed(_) => TrieBackend::Compressed,
+        }
+    }
+}
+
+impl<P: IpPrefix, V> Default for LpmTrie<P, V> {
+    fn default() -> Self {
+        Self::new(TrieBackend::default())
+    }
+}
+
+impl<P: IpPrefix, V> TrieMap for LpmTrie<P, V> {
+    type Prefix = P;
+    type Value = V;
+    type Error = std::convert::Infallible;
+
+    fn iter(&self) -> impl Iterator<Item = (&P, &V)> {
+        match self {
+            LpmTrie::PrefixMap(trie) => EitherIter::Left(trie.iter()),
+            LpmTrie::Compressed(trie) => EitherIter::Right(trie.iter()),
+        }
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (&P, &mut V)> {
+        match self {
+            LpmTrie::PrefixMap(trie) => EitherIter::Left(trie.iter_mut()),
+            LpmTrie::Compressed(trie) => EitherIter::Right(trie.iter_mut()),
+        }
+    }
+
+    fn get<B>(&self, prefix: B) -> Option<&V>
+    where
+        B: Borrow<P>,
+    {
+        match self {
+            LpmTrie::PrefixMap(trie) => trie.get(prefix),
+            LpmTrie::Compressed(trie) => trie.get(prefix),
+        }
+    }
+
+    fn get_mut<B>(&mut self, prefix: B) -> Option<&mut V>
+    where
+        B: Borrow<P>,
+    {
+        match self {
+            LpmTrie::PrefixMap(trie) => trie.get_mut(prefix),
+            LpmTrie::Compressed(trie) => trie.get_mut(prefix),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            LpmTrie::PrefixMap(trie) => trie.len(),
+            LpmTrie::Compressed(trie) => trie.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            LpmTrie::PrefixMap(trie) => trie.is_empty(),
+            LpmTrie::Compressed(trie) => trie.is_empty(),
+        }
+    }
+
+    fn insert(&mut self, prefix: P, value: V) -> Option<V> {
+        match self {
+            LpmTrie::PrefixMap(trie) => trie.insert(prefix, value),
+            LpmTrie::Compressed(trie) => trie.insert(prefix, value),
+        }
+    }
+
+    fn remove<B>(&mut self, prefix: B) -> Option<V>
+    where
+        B: Borrow<P>,
+    {
+        match self {
+            LpmTrie::PrefixMap(trie) => trie.remove(prefix),
+            LpmTrie::Compressed(trie) => trie.remove(prefix),
+        }
+    }
+
+    fn lookup<A>(&self, addr: A) -> Option<(&P, &V)>
+    where
+        A: Into<Self::Prefix>,
+    {
+        match self {
+            LpmTrie::PrefixMap(trie) => trie.lookup(addr),
+            LpmTrie::Compressed(trie) => trie.lookup(addr),
+        }
+    }
+
+    fn lookup_batch<'a, A>(&'a self, addrs: &[A], results: &mut Vec<Option<(&'a P, &'a V)>>)
+    where
+        A: Into<Self::Prefix> + Copy + Ord,
+    {
+        match self {
+            LpmTrie::PrefixMap(trie) => trie.lookup_batch(addrs, results),
+            LpmTrie::Compressed(trie) => trie.lookup_batch(addrs, results),
+        }
+    }
+
+    fn covered<B>(&self, prefix: B) -> impl Iterator<Item = (&P, &V)>
+    where
+        B: Borrow<P>,
+    {
+        match self {
+            LpmTrie::PrefixMap(trie) => EitherIter::Left(trie.covered(prefix)),
+            LpmTrie::Compressed(trie) => EitherIter::Right(trie.covered(prefix)),
+        }
+    }
+
+    fn covering<B>(&self, prefix: B) -> impl Iterator<Item = (&P, &V)>
+    where
+        B: Borrow<P>,
+    {
+        match self {
+            LpmTrie::PrefixMap(trie) => EitherIter::Left(trie.covering(prefix)),
+            LpmTrie::Compressed(trie) => EitherIter::Right(trie.covering(prefix)),
+        }
+    }
+
+    fn stats(&self) -> TrieStats {
+        match self {
+            LpmTrie::PrefixMap(trie) => trie.stats(),
+            LpmTrie::Compressed(trie) => trie.stats(),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Batched lookups. Keys are looked up in ascending order, in chunks of up to [`LOOKUP_BATCH`],
+//! so that consecutive lookups walk mostly the same trie nodes, which then remain in cache.
+//! Repeated keys (e.g. addresses of packets of the same flow) are only looked up once. Callers
+//! usually prefetch the values found as the lookups proceed, since they read them right after.
+//! This does not prefetch the trie nodes: tries owning their nodes, like [`CompressedTrie`],
+//! rather interleave the lookups of a batch to prefetch them, see [`TrieMap::lookup_batch`].
+//!
+//! [`CompressedTrie`]: crate::trie::CompressedTrie
+//! [`TrieMap::lookup_batch`]: crate::trie::TrieMap::lookup_batch
+
+use std::ptr;
+
+/// Maximum number of keys looked up together
+pub const LOOKUP_BATCH: usize = 32;
+
+/// Hint the CPU to bring `value` into its caches, without waiting for it
+#[inline]
+pub fn prefetch<T>(value: &T) {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: prefetching is a hint that never faults, whatever the address
+    unsafe {
+        use std::arch::x86_64::{_MM_HINT_T0, _mm_prefetch};
+        _mm_prefetch::<_MM_HINT_T0>(ptr::from_ref(value).cast());
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = ptr::from_ref(value);
+}
+
+/// Look up each of `keys` with `lookup`, appending the results to `results`, in the order of
+/// `keys`. This is the building block of the batched lookups of the tries, exposed for the
+/// users that need to look up keys over several tries (e.g. IPv4 and IPv6 addresses).
+pub fn lookup_batch_with<K, R>(keys: &[K], results: &mut Vec<R>, mut lookup: impl FnMut(K) -> R)
+where
+    K: Copy + Ord,
+    R: Copy,
+{
+    results.reserve(keys.len());
+    for chunk in keys.chunks(LOOKUP_BATCH) {
+        let mut order = [0usize; LOOKUP_BATCH];
+        let order = &mut order[..chunk.len()];
+        for (position, index) in order.iter_mut().enumerate() {
+            *index = position;
+        }
+        order.sort_unstable_by_key(|index| chunk[*index]);
+
+        let mut hits: [Option<R>; LOOKUP_BATCH] = [None; LOOKUP_BATCH];
+        let mut last: Option<(K, R)> = None;
+        for index in order.iter() {
+            let key = chunk[*index];
+            let hit = match last {
+                Some((last_key, hit)) if last_key == key => hit,
+                _ => lookup(key),
+            };
+            last = Some((key, hit));
+            hits[*index] = Some(hit);
+        }
+        results.extend(
+            hits[..chunk.len()]
+                .iter()
+                .map(|hit| hit.unwrap_or_else(|| unreachable!())),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prefix::{Ipv4Prefix, Prefix};
+    use crate::trie::{IpPrefixTrie, PrefixMapTrie, TrieMap, TrieMapFactory};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn test_lookup_batch_with() {
+        let keys: Vec<u32> = (0..96).map(|n| (n * 7) % 10).collect();
+        let mut lookups = 0;
+        let mut results = Vec::new();
+        lookup_batch_with(&keys, &mut results, |key| {
+            lookups += 1;
+            key * 2
+        });
+        let expected: Vec<u32> = keys.iter().map(|key| key * 2).collect();
+        assert_eq!(results, expected);
+        // repeated keys are looked up once per chunk
+        assert_eq!(lookups, 10 * keys.len().div_ceil(LOOKUP_BATCH));
+    }
+
+    #[test]
+    fn test_trie_lookup_batch() {
+        let mut trie: PrefixMapTrie<Ipv4Prefix, u32> = PrefixMapTrie::with_root(0);
+        trie.insert(Ipv4Prefix::new(Ipv4Addr::new(10, 0, 0, 0), 8).unwrap(), 8);
+        trie.insert(Ipv4Prefix::new(Ipv4Addr::new(10, 1, 0, 0), 16).unwrap(), 16);
+        trie.insert(Ipv4Prefix::new(Ipv4Addr::new(10, 1, 1, 0), 24).unwrap(), 24);
+
+        let addrs: Vec<Ipv4Addr> = (0..=255u8)
+            .flat_map(|n| [Ipv4Addr::new(10, n % 3, n, 1), Ipv4Addr::new(n, 1, 1, 1)])
+            .collect();
+        let mut results = Vec::new();
+        trie.lookup_batch(&addrs, &mut results);
+        assert_eq!(results.len(), addrs.len());
+        for (addr, result) in addrs.iter().zip(results) {
+            assert_eq!(result, trie.lookup(*addr), "Mismatch for {addr}");
+        }
+
+        let mut trie = IpPrefixTrie::new();
+        trie.insert(Prefix::expect_from(("10.0.0.0", 8)), 8);
+        trie.insert(Prefix::expect_from(("2001:db8::", 32)), 32);
+        let addrs: Vec<IpAddr> = ["2001:db8::1", "10.1.2.3", "192.168.1.1", "10.1.2.3"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        let mut results = Vec::new();
+        trie.lookup_batch(&addrs, &mut results);
+        let results: Vec<_> = results.iter().map(|r| r.map(|(_, v)| *v)).collect();
+        assert_eq!(results, vec![Some(32), Some(8), None, Some(8)]);
+    }
+}
@@ -0,0 +1,521 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! A compressed trie, in the spirit of DXR (D. Zec, L. Rizzo, M. Mikuc: "DXR: towards a billion
+//! routing lookups per second in software", 2012). The address space is split in 2^16 chunks,
+//! indexed by the leading bits of the addresses. Each chunk either resolves directly to a prefix,
+//! or to a short sorted array of address ranges, in which a binary search finds the prefix.
+//! Lookups thus touch very few cache lines, whatever the number of prefixes, at the expense of
+//! updates: adding or removing a prefix rebuilds all the chunks that the prefix spans.
+
+use crate::prefix::IpPrefix;
+use crate::prefix::ip::Representable;
+use crate::trie::stats::depths;
+use crate::trie::{
+    LOOKUP_BATCH, TrieMap, TrieMapFactory, TrieMapWithDefault, TrieStats, prefetch, truncate,
+};
+use num_traits::{Bounded, NumCast, One, PrimInt, ToPrimitive, Zero};
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+
+/// Number of leading address bits indexing the chunks
+const CHUNK_BITS: u8 = 16;
+
+/// Slot meaning that no prefix matches
+const NO_SLOT: u32 = u32::MAX;
+
+/// Minimum number of unused ranges before compacting them
+const COMPACT_MIN: usize = 4096;
+
+#[derive(Debug, Clone, Copy)]
+struct Chunk {
+    base: u32, /* if len is 0, slot of the prefix of the whole chunk, else offset of its ranges */
+    len: u32,  /* number of ranges of the chunk */
+}
+
+impl Chunk {
+    const EMPTY: Chunk = Chunk {
+        base: NO_SLOT,
+        len: 0,
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct CompressedTrie<P: IpPrefix, V> {
+    entries: Vec<Option<(P, V)>>, /* prefixes and their values, per slot */
+    free: Vec<u32>,               /* slots available for reuse */
+    index: BTreeMap<(P::Repr, u8), u32>, /* slots per network address and length */
+    chunks: Box<[Chunk]>,         /* chunks, indexed by the leading bits of addresses */
+    ranges: Vec<(P::Repr, u32)>,  /* first address of the ranges and their slot */
+    unused: usize,                /* number of ranges no longer used by any chunk */
+}
+
+/// Append the range starting at `start` and resolving to `slot` to `ranges`, merging ranges
+/// resolving to the same slot
+fn push_range<R: PrimInt>(ranges: &mut Vec<(R, u32)>, start: R, slot: u32) {
+    match ranges.last_mut() {
+        Some(last) if last.0 == start => last.1 = slot,
+        Some(last) if last.1 == slot => return,
+        _ => ranges.push((start, slot)),
+    }
+    if let [.., previous, last] = ranges.as_slice() {
+        if previous.1 == last.1 {
+            ranges.pop();
+        }
+    }
+}
+
+fn to_u32(value: usize) -> u32 {
+    u32::try_from(value)
+        .ok()
+        .filter(|value| *value != NO_SLOT)
+        .expect("Too many prefixes or ranges in compressed trie")
+}
+
+impl<P: IpPrefix, V> CompressedTrie<P, V> {
+    fn shift() -> usize {
+        usize::from(P::MAX_LEN - CHUNK_BITS)
+    }
+
+    fn key(prefix: &P) -> (P::Repr, u8) {
+        (prefix.network().to_bits(), prefix.len())
+    }
+
+    fn chunk_of(addr: P::Repr) -> usize {
+        (addr >> Self::shift())
+            .to_usize()
+            .unwrap_or_else(|| unreachable!())
+    }
+
+    fn chunk_start(chunk: usize) -> P::Repr {
+        let chunk: P::Repr = NumCast::from(chunk).unwrap_or_else(|| unreachable!());
+        chunk << Self::shift()
+    }
+
+    /// Find the slot of the prefix matching `addr`, in the ranges of its chunk
+    fn slot(&self, addr: P::Repr, chunk: Chunk) -> u32 {
+        if chunk.len == 0 {
+            chunk.base
+        } else {
+            let ranges = &self.ranges[chunk.base as usize..][..chunk.len as usize];
+            ranges[ranges.partition_point(|(start, _)| *start <= addr) - 1].1
+        }
+    }
+
+    fn prefix(&self, slot: u32) -> &P {
+        self.entry(slot).0
+    }
+
+    fn entry(&self, slot: u32) -> (&P, &V) {
+        let entry = self.entries[slot as usize].as_ref();
+        let (prefix, value) = entry.unwrap_or_else(|| unreachable!());
+        (prefix, value)
+    }
+
+    /// Compute the ranges of a chunk, from the prefixes spanning (part of) it
+    fn chunk_ranges(&self, chunk: usize) -> Vec<(P::Repr, u32)> {
+        let start = Self::chunk_start(chunk);
+        let end = start | (P::Repr::max_value() >> usize::from(CHUNK_BITS));
+
+        // the longest prefix spanning the whole chunk, if any
+        let base = (0..=CHUNK_BITS)
+            .rev()
+            .find_map(|len| {
+                let network = start & !(P::Repr::max_value() >> usize::from(len));
+                self.index.get(&(network, len)).copied()
+            })
+            .unwrap_or(NO_SLOT);
+
+        // the longer prefixes, within the chunk, sorted by address then length: each prefix
+        // comes after the prefixes enclosing it.
+        let mut ranges = vec![(start, base)];
+        let mut enclosing: Vec<(P::Repr, u32)> = Vec::new();
+        for (&(network, _), &slot) in self
+            .index
+            .range((start, CHUNK_BITS + 1)..=(end, P::MAX_LEN))
+        {
+            while let Some(&(last, _)) = enclosing.last().filter(|(last, _)| *last < network) {
+                enclosing.pop();
+                let below = enclosing.last().map_or(base, |(_, slot)| *slot);
+                push_range(&mut ranges, last + P::Repr::one(), below);
+            }
+            push_range(&mut ranges, network, slot);
+            enclosing.push((self.prefix(slot).last_address().to_bits(), slot));
+        }
+        while let Some((last, _)) = enclosing.pop() {
+            if last < end {
+                let below = enclosing.last().map_or(base, |(_, slot)| *slot);
+                push_range(&mut ranges, last + P::Repr::one(), below);
+            }
+        }
+        ranges
+    }
+
+    fn rebuild_chunk(&mut self, chunk: usize) {
+        let ranges = self.chunk_ranges(chunk);
+        let old = self.chunks[chunk];
+        self.unused += old.len as usize;
+        self.chunks[chunk] = if let [(_, slot)] = ranges.as_slice() {
+            Chunk {
+                base: *slot,
+                len: 0,
+            }
+        } else {
+            let base = to_u32(self.ranges.len());
+            self.ranges.extend(ranges);
+            Chunk {
+                base,
+                len: to_u32(self.ranges.len()) - base,
+            }
+        };
+    }
+
+    /// Rebuild the chunks that `prefix` spans, after adding or removing it
+    fn rebuild_chunks(&mut self, prefix: &P) {
+        let first = Self::chunk_of(prefix.network().to_bits());
+        let last = Self::chunk_of(prefix.last_address().to_bits());
+        for chunk in first..=last {
+            self.rebuild_chunk(chunk);
+        }
+        self.compact();
+    }
+
+    /// Drop the ranges no longer used, if they are many
+    fn compact(&mut self) {
+        if self.unused < COMPACT_MIN || self.unused < self.ranges.len() / 2 {
+            return;
+        }
+        let mut ranges = Vec::with_capacity(self.ranges.len() - self.unused);
+        for chunk in self.chunks.iter_mut().filter(|chunk| chunk.len > 0) {
+            let base = to_u32(ranges.len());
+            ranges.extend_from_slice(&self.ranges[chunk.base as usize..][..chunk.len as usize]);
+            chunk.base = base;
+        }
+        self.ranges = ranges;
+        self.unused = 0;
+    }
+}
+
+impl<P: IpPrefix, V> TrieMapFactory<CompressedTrie<P, V>> for CompressedTrie<P, V> {
+    fn create() -> Self {
+        Self {
+            entries: Vec::new(),
+            free: Vec::new(),
+            index: BTreeMap::new(),
+            chunks: vec![Chunk::EMPTY; 1 << CHUNK_BITS].into_boxed_slice(),
+            ranges: Vec::new(),
+            unused: 0,
+        }
+    }
+
+    fn with_capacity(capacity: usize) -> Self {
+        let mut ret = Self::create();
+        ret.entries.reserve(capacity);
+        ret
+    }
+
+    fn with_root(value: V) -> Self {
+        let mut ret = Self::create();
+        ret.insert(P::ROOT, value);
+        ret
+    }
+}
+
+impl<P: IpPrefix, V> Default for CompressedTrie<P, V> {
+    fn default() -> Self {
+        Self::create()
+    }
+}
+
+impl<P: IpPrefix, V> TrieMap for CompressedTrie<P, V> {
+    type Prefix = P;
+    type Value = V;
+    type Error = std::convert::Infallible;
+
+    fn iter(&self) -> impl Iterator<Item = (&P, &V)> {
+        self.index.values().map(|slot| self.entry(*slot))
+    }
+
+    /// Iterate over the prefixes and their mutable values, in no particular order
+    fn iter_mut(&mut self) -> impl Iterator<Item = (&P, &mut V)> {
+        self.entries.iter_mut().flatten().map(|(p, v)| (&*p, v))
+    }
+
+    fn get<B>(&self, prefix: B) -> Option<&V>
+    where
+        B: Borrow<P>,
+    {
+        let slot = self.index.get(&Self::key(prefix.borrow()))?;
+        self.entries[*slot as usize].as_ref().map(|(_, v)| v)
+    }
+
+    fn get_mut<B>(&mut self, prefix: B) -> Option<&mut V>
+    where
+        B: Borrow<P>,
+    {
+        let slot = self.index.get(&Self::key(prefix.borrow()))?;
+        self.entries[*slot as usize].as_mut().map(|(_, v)| v)
+    }
+
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    fn insert(&mut self, prefix: P, value: V) -> Option<V> {
+        let key = Self::key(&prefix);
+        if let Some(slot) = self.index.get(&key) {
+            let entry = self.entries[*slot as usize].as_mut();
+            return entry.map(|(_, v)| std::mem::replace(v, value));
+        }
+        let slot = if let Some(slot) = self.free.pop() {
+            self.entries[slot as usize] = Some((prefix.clone(), value));
+            slot
+        } else {
+            let slot = to_u32(self.entries.len());
+            self.entries.push(Some((prefix.clone(), value)));
+            slot
+        };
+        self.index.insert(key, slot);
+        self.rebuild_chunks(&prefix);
+        None
+    }
+
+    fn remove<B>(&mut self, prefix: B) -> Option<V>
+    where
+        B: Borrow<P>,
+    {
+        let slot = self.index.remove(&Self::key(prefix.borrow()))?;
+        let (prefix, value) = self.entries[slot as usize].take()?;
+        self.free.push(slot);
+        self.rebuild_chunks(&prefix);
+        Some(value)
+    }
+
+    fn lookup<A>(&self, addr: A) -> Option<(&P, &V)>
+    where
+        A: Into<Self::Prefix>,
+    {
+        let addr = addr.into().network().to_bits();
+        let slot = self.slot(addr, self.chunks[Self::chunk_of(addr)]);
+        self.entries
+            .get(slot as usize)?
+            .as_ref()
+            .map(|(p, v)| (p, v))
+    }
+
+    /// Look up the addresses of each chunk of up to [`LOOKUP_BATCH`] of them level by level,
+    /// rather than one after the other: the chunks of all the addresses are prefetched first,
+    /// then their ranges, then the entries they resolve to, so that the cache misses of the
+    /// addresses overlap instead of stalling each lookup in turn.
+    fn lookup_batch<'a, A>(&'a self, addrs: &[A], results: &mut Vec<Option<(&'a P, &'a V)>>)
+    where
+        A: Into<Self::Prefix> + Copy + Ord,
+    {
+        results.reserve(addrs.len());
+        for burst in addrs.chunks(LOOKUP_BATCH) {
+            let mut bits = [P::Repr::zero(); LOOKUP_BATCH];
+            let bits = &mut bits[..burst.len()];
+            for (bits, addr) in bits.iter_mut().zip(burst) {
+                *bits = (*addr).into().network().to_bits();
+                prefetch(&self.chunks[Self::chunk_of(*bits)]);
+            }
+
+            let mut chunks = [Chunk::EMPTY; LOOKUP_BATCH];
+            let chunks = &mut chunks[..burst.len()];
+            for (chunk, bits) in chunks.iter_mut().zip(bits.iter()) {
+                *chunk = self.chunks[Self::chunk_of(*bits)];
+                if chunk.len == 0 {
+                    if let Some(entry) = self.entries.get(chunk.base as usize) {
+                        prefetch(entry);
+                    }
+                } else {
+                    prefetch(&self.ranges[chunk.base as usize]);
+                }
+            }
+
+            let mut slots = [NO_SLOT; LOOKUP_BATCH];
+            let slots = &mut slots[..burst.len()];
+            for ((slot, chunk), bits) in slots.iter_mut().zip(chunks.iter()).zip(bits.iter()) {
+                *slot = self.slot(*bits, *chunk);
+                if chunk.len != 0 {
+                    if let Some(entry) = self.entries.get(*slot as usize) {
+                        prefetch(entry);
+                    }
+                }
+            }
+
+            results.extend(slots.iter().map(|slot| {
+                self.entries
+                    .get(*slot as usize)?
+                    .as_ref()
+                    .map(|(p, v)| (p, v))
+            }));
+        }
+    }
+
+    fn covered<B>(&self, prefix: B) -> impl Iterator<Item = (&P, &V)>
+    where
+        B: Borrow<P>,
+    {
+        // the prefixes with a network address within `prefix` are those covered by it, except
+        // for shorter ones sharing its network address
+        let prefix = prefix.borrow();
+        let first = (prefix.network().to_bits(), prefix.len());
+        let last = (prefix.last_address().to_bits(), P::MAX_LEN);
+        self.index
+            .range(first..=last)
+            .map(|(_, slot)| self.entry(*slot))
+    }
+
+    fn covering<B>(&self, prefix: B) -> impl Iterator<Item = (&P, &V)>
+    where
+        B: Borrow<P>,
+    {
+        let prefix = prefix.borrow().clone();
+        (0..=prefix.len())
+            .rev()
+            .filter_map(move |len| truncate(&prefix, len))
+            .filter_map(|shorter| self.index.get(&Self::key(&shorter)))
+            .map(|slot| self.entry(*slot))
+    }
+
+    /// The nodes are the chunks and the ranges in use
+    fn stats(&self) -> TrieStats {
+        let ranges = self.ranges.len() - self.unused;
+        let index = self.index.len() * size_of::<((P::Repr, u8), u32)>();
+        TrieStats {
+            prefixes: self.len(),
+            nodes: self.chunks.len() + ranges,
+            memory: self.entries.capacity() * size_of::<Option<(P, V)>>()
+                + self.free.capacity() * size_of::<u32>()
+                + index
+                + self.chunks.len() * size_of::<Chunk>()
+                + self.ranges.capacity() * size_of::<(P::Repr, u32)>(),
+            depths: depths(self.iter().map(|(p, _)| p)),
+        }
+    }
+}
+
+pub type CompressedTrieWithDefault<P, V> = TrieMapWithDefault<CompressedTrie<P, V>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prefix::{Ipv4Prefix, Ipv6Prefix};
+    use crate::trie::PrefixMapTrie;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    /// A trivial deterministic pseudo-random generator
+    struct Lcg(u64);
+    impl Lcg {
+        fn next(&mut self) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            self.0
+        }
+    }
+
+    /// Check that a [`CompressedTrie`] finds the same prefixes as a [`PrefixMapTrie`], as
+    /// random prefixes get added then removed
+    fn check_against_prefix_map<P: IpPrefix>(rng: &mut Lcg, bits: fn(&mut Lcg) -> P::Repr)
+    where
+        P::Addr: Copy + Ord,
+    {
+        let mut reference: PrefixMapTrie<P, usize> = PrefixMapTrie::create();
+        let mut compressed: CompressedTrie<P, usize> = CompressedTrie::create();
+        let mut prefixes = Vec::new();
+        for value in 0..2000 {
+            // favour lengths around the chunk boundary
+            let len = match rng.next() % 4 {
+                0 => rng.next() % u64::from(P::MAX_LEN + 1),
+                _ => u64::from(CHUNK_BITS - 4) + rng.next() % 12,
+            };
+            let len = u8::try_from(len).unwrap();
+            let network = bits(rng) & !(P::Repr::max_value() >> usize::from(len));
+            let prefix = P::new(P::Addr::from_bits(network), len).unwrap();
+            assert_eq!(
+                compressed.insert(prefix.clone(), value),
+                reference.insert(prefix.clone(), value)
+            );
+            prefixes.push(prefix);
+        }
+        assert_eq!(compressed.len(), reference.len());
+
+        let check = |compressed: &CompressedTrie<P, usize>, reference: &PrefixMapTrie<P, usize>| {
+            let addrs = prefixes
+                .iter()
+                .flat_map(|p| [p.network(), p.last_address()]);
+            let mut addr_rng = Lcg(7);
+            let addrs: Vec<P::Addr> = addrs
+                .chain((0..2000).map(|_| P::Addr::from_bits(bits(&mut addr_rng))))
+                .collect();
+            for addr in &addrs {
+                assert_eq!(
+                    compressed.lookup(*addr),
+                    reference.lookup(*addr),
+                    "Mismatch for {addr}"
+                );
+            }
+            // batched lookups find the same prefixes, in the order of the addresses
+            let mut results = Vec::new();
+            compressed.lookup_batch(&addrs, &mut results);
+            assert_eq!(results.len(), addrs.len());
+            for (addr, result) in addrs.iter().zip(results) {
+                assert_eq!(result, reference.lookup(*addr), "Batch mismatch for {addr}");
+            }
+        };
+        check(&compressed, &reference);
+
+        for prefix in prefixes.iter().step_by(2) {
+            assert_eq!(compressed.remove(prefix), reference.remove(prefix));
+            assert_eq!(compressed.get(prefix), None);
+        }
+        assert_eq!(compressed.len(), reference.len());
+        check(&compressed, &reference);
+    }
+
+    #[test]
+    fn test_compressed_trie_ipv4() {
+        let mut rng = Lcg(0x1234);
+        check_against_prefix_map::<Ipv4Prefix>(&mut rng, |rng| {
+            u32::try_from(rng.next() >> 32).unwrap()
+        });
+    }
+
+    #[test]
+    fn test_compressed_trie_ipv6() {
+        let mut rng = Lcg(0x5678);
+        check_against_prefix_map::<Ipv6Prefix>(&mut rng, |rng| {
+            (u128::from(rng.next()) << 64) | u128::from(rng.next())
+        });
+    }
+
+    #[test]
+    fn test_compressed_trie_basic() {
+        let mut trie: CompressedTrieWithDefault<Ipv4Prefix, u32> = TrieMapWithDefault::new();
+        let p16 = Ipv4Prefix::new(Ipv4Addr::new(10, 1, 0, 0), 16).unwrap();
+        let p24 = Ipv4Prefix::new(Ipv4Addr::new(10, 1, 1, 0), 24).unwrap();
+        trie.insert(p16, 16);
+        trie.insert(p24, 24);
+        assert_eq!(trie.lookup_wd(Ipv4Addr::new(10, 1, 1, 1)), (&p24, &24));
+        assert_eq!(trie.lookup_wd(Ipv4Addr::new(10, 1, 2, 1)), (&p16, &16));
+        assert_eq!(trie.lookup_wd(Ipv4Addr::new(10, 2, 0, 1)).1, &0);
+        assert_eq!(trie.insert(p24, 25), Some(24));
+        assert_eq!(trie.lookup_wd(Ipv4Addr::new(10, 1, 1, 255)).1, &25);
+        assert_eq!(trie.remove(p16), Some(16));
+        assert_eq!(trie.lookup_wd(Ipv4Addr::new(10, 1, 2, 1)).1, &0);
+        assert_eq!(trie.len(), 2);
+
+        let mut trie: CompressedTrie<Ipv6Prefix, u32> = CompressedTrie::create();
+        let addr: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        assert_eq!(trie.lookup(addr), None);
+        trie.insert(Ipv6Prefix::new(addr, 128).unwrap(), 128);
+        assert_eq!(trie.lookup(addr).map(|(_, v)| *v), Some(128));
+    }
+}
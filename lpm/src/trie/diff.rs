@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Differences between two tries. Tries iterate over their prefixes in the same order, so
+//! their differences are found in a single pass over both, as when merging sorted lists.
+
+use std::cmp::Ordering;
+
+/// A difference between two tries, for a prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrieDiff<P, V> {
+    /// The prefix is only in the second trie, with the given value
+    Added(P, V),
+    /// The prefix is only in the first trie, with the given value
+    Removed(P, V),
+    /// The prefix is in both tries, with distinct values: the old one, then the new one
+    Changed(P, V, V),
+}
+
+impl<P, V> TrieDiff<P, V> {
+    /// Get the prefix that differs
+    pub fn prefix(&self) -> &P {
+        match self {
+            TrieDiff::Added(prefix, _)
+            | TrieDiff::Removed(prefix, _)
+            | TrieDiff::Changed(prefix, _, _) => prefix,
+        }
+    }
+
+    /// Convert the prefix of the difference with `f`
+    pub fn map_prefix<Q>(self, f: impl FnOnce(P) -> Q) -> TrieDiff<Q, V> {
+        match self {
+            TrieDiff::Added(prefix, value) => TrieDiff::Added(f(prefix), value),
+            TrieDiff::Removed(prefix, value) => TrieDiff::Removed(f(prefix), value),
+            TrieDiff::Changed(prefix, old, new) => TrieDiff::Changed(f(prefix), old, new),
+        }
+    }
+}
+
+/// Iterate over the differences between the entries of `old` and those of `new`, both sorted
+/// by `key`
+pub(crate) fn diff_sorted<P, V, K>(
+    old: impl Iterator<Item = (P, V)>,
+    new: impl Iterator<Item = (P, V)>,
+    key: impl Fn(&P) -> K,
+) -> impl Iterator<Item = TrieDiff<P, V>>
+where
+    K: Ord,
+    V: PartialEq,
+{
+    let mut old = old.peekable();
+    let mut new = new.peekable();
+    std::iter::from_fn(move || {
+        loop {
+            let ordering = match (old.peek(), new.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((p, _)), Some((q, _))) => key(p).cmp(&key(q)),
+            };
+            match ordering {
+                Ordering::Less => {
+                    let (prefix, value) = old.next()?;
+                    return Some(TrieDiff::Removed(prefix, value));
+                }
+                Ordering::Greater => {
+                    let (prefix, value) = new.next()?;
+                    return Some(TrieDiff::Added(prefix, value));
+                }
+                Ordering::Equal => {
+                    let (prefix, old_value) = old.next()?;
+                    let (_, new_value) = new.next()?;
+                    if old_value != new_value {
+                        return Some(TrieDiff::Changed(prefix, old_value, new_value));
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prefix::{Ipv4Prefix, Prefix};
+    use crate::trie::{IpPrefixTrie, LpmTrie, TrieBackend, TrieMap};
+
+    #[test]
+    fn test_trie_diff() {
+        let prefix = |p: &str| *Prefix::expect_from(p).get_v4();
+        for (old_backend, new_backend) in [
+            (TrieBackend::PrefixMap, TrieBackend::PrefixMap),
+            (TrieBackend::Compressed, TrieBackend::PrefixMap),
+            (TrieBackend::PrefixMap, TrieBackend::Compressed),
+        ] {
+            let mut old: LpmTrie<Ipv4Prefix, u32> = LpmTrie::new(old_backend);
+            let mut new: LpmTrie<Ipv4Prefix, u32> = LpmTrie::new(new_backend);
+            for (p, value) in [("10.0.0.0/8", 1), ("10.0.0.0/16", 2), ("10.1.0.0/16", 3)] {
+                old.insert(prefix(p), value);
+                new.insert(prefix(p), value);
+            }
+            assert_eq!(old.diff(&new).count(), 0);
+
+            old.insert(prefix("10.0.0.0/24"), 4);
+            new.insert(prefix("0.0.0.0/0"), 0);
+            new.insert(prefix("10.1.0.0/16"), 5);
+            new.insert(prefix("192.168.0.0/16"), 6);
+            let diff: Vec<_> = old.diff(&new).collect();
+            assert_eq!(
+                diff,
+                vec![
+                    TrieDiff::Added(&prefix("0.0.0.0/0"), &0),
+                    TrieDiff::Removed(&prefix("10.0.0.0/24"), &4),
+                    TrieDiff::Changed(&prefix("10.1.0.0/16"), &3, &5),
+                    TrieDiff::Added(&prefix("192.168.0.0/16"), &6),
+                ]
+            );
+        }
+
+        let mut old = IpPrefixTrie::new();
+        let mut new = IpPrefixTrie::with_backend(TrieBackend::Compressed);
+        old.insert(Prefix::expect_from("10.0.0.0/8"), 1);
+        new.insert(Prefix::expect_from("2001:db8::/32"), 2);
+        let diff: Vec<_> = old.diff(&new).map(|d| *d.prefix()).collect();
+        let expected = ["10.0.0.0/8", "2001:db8::/32"].map(Prefix::expect_from);
+        assert_eq!(diff, expected);
+    }
+}
@@ -1,7 +1,9 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright Open Network Fabric Authors
 
+use crate::prefix::ip::Representable;
 use crate::prefix::{IpPrefix, Ipv4Prefix, Ipv6Prefix, Prefix};
+use num_traits::{Bounded, CheckedShr, Zero};
 use std::borrow::Borrow;
 use std::net::IpAddr;
 
@@ -11,6 +13,23 @@ pub use prefix_map_impl::*;
 mod trie_with_default;
 pub use trie_with_default::TrieMapWithDefault;
 
+mod batch;
+pub use batch::{LOOKUP_BATCH, lookup_batch_with, prefetch};
+
+mod compressed;
+pub use compressed::{CompressedTrie, CompressedTrieWithDefault};
+
+mod backend;
+pub use backend::{LpmTrie, TrieBackend};
+
+mod diff;
+pub use diff::TrieDiff;
+use diff::diff_sorted;
+
+mod stats;
+pub use stats::TrieStats;
+use stats::depths;
+
 pub trait TrieMapFactory<T: TrieMap> {
     fn create() -> T;
     fn with_capacity(capacity: usize) -> T;
@@ -40,6 +59,7 @@ pub trait TrieMap {
     where
         B: Borrow<Self::Prefix>;
 
+    /// Iterates over the prefixes, by ascending network address then length
     fn iter(&self) -> impl Iterator<Item = (&Self::Prefix, &Self::Value)>;
     fn iter_mut(&mut self) -> impl Iterator<Item = (&Self::Prefix, &mut Self::Value)>;
     fn is_empty(&self) -> bool;
@@ -53,23 +73,121 @@ pub trait TrieMap {
     where
         A: Into<Self::Prefix>;
 
+    /// Gets the prefixes with the longest match for a batch of addresses, appending them to
+    /// `results` in the order of `addrs`. This is equivalent to calling [`TrieMap::lookup`] for
+    /// each address, only faster when addresses share trie nodes or repeat.
+    ///
+    /// The default implementation looks up the addresses in ascending order and prefetches the
+    /// values found, but not the trie nodes, which it cannot reach. Implementations owning their
+    /// nodes, like [`CompressedTrie`], rather interleave the lookups to prefetch them.
+    fn lookup_batch<'a, A>(
+        &'a self,
+        addrs: &[A],
+        results: &mut Vec<Option<(&'a Self::Prefix, &'a Self::Value)>>,
+    ) where
+        A: Into<Self::Prefix> + Copy + Ord,
+    {
+        lookup_batch_with(addrs, results, |addr| {
+            let hit = self.lookup(addr);
+            if let Some((_, value)) = hit {
+                prefetch(value);
+            }
+            hit
+        });
+    }
+
     fn remove<B>(&mut self, prefix: B) -> Option<Self::Value>
     where
         B: Borrow<Self::Prefix>;
+
+    /// Iterates over the prefixes covered by `prefix`, i.e. `prefix` itself, if present, and
+    /// all the prefixes more specific than it
+    fn covered<B>(&self, prefix: B) -> impl Iterator<Item = (&Self::Prefix, &Self::Value)>
+    where
+        B: Borrow<Self::Prefix>;
+
+    /// Iterates over the prefixes covering `prefix`, i.e. `prefix` itself, if present, and all
+    /// the prefixes less specific than it, from the most specific to the least specific one
+    fn covering<B>(&self, prefix: B) -> impl Iterator<Item = (&Self::Prefix, &Self::Value)>
+    where
+        B: Borrow<Self::Prefix>;
+
+    /// Iterates over the differences between this trie and `other`, by ascending prefix: these
+    /// are the minimal updates turning this trie into `other`
+    fn diff<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<Item = TrieDiff<&'a Self::Prefix, &'a Self::Value>>
+    where
+        Self::Value: PartialEq,
+    {
+        let key = |prefix: &&Self::Prefix| (prefix.network().to_bits(), prefix.len());
+        diff_sorted(self.iter(), other.iter(), key)
+    }
+
+    /// Gets the memory and depth statistics of the trie
+    fn stats(&self) -> TrieStats {
+        TrieStats {
+            prefixes: self.len(),
+            nodes: self.len(),
+            memory: self.len() * size_of::<(Self::Prefix, Self::Value)>(),
+            depths: depths(self.iter().map(|(prefix, _)| prefix)),
+        }
+    }
+}
+
+/// An iterator over either of two iterators with the same items, e.g. to iterate over any of
+/// the backends of an [`LpmTrie`]
+pub(crate) enum EitherIter<L, R> {
+    Left(L),
+    Right(R),
+}
+
+impl<T, L, R> Iterator for EitherIter<L, R>
+where
+    L: Iterator<Item = T>,
+    R: Iterator<Item = T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match self {
+            EitherIter::Left(iter) => iter.next(),
+            EitherIter::Right(iter) => iter.next(),
+        }
+    }
+}
+
+/// Get the prefix of length `len` covering `prefix`, if `len` is not larger than its length
+fn truncate<P: IpPrefix>(prefix: &P, len: u8) -> Option<P> {
+    if len > prefix.len() {
+        return None;
+    }
+    let hostmask = P::Repr::max_value()
+        .checked_shr(u32::from(len))
+        .unwrap_or_else(P::Repr::zero);
+    let network = prefix.network().to_bits() & !hostmask;
+    P::new(P::Addr::from_bits(network), len).ok()
 }
 
 #[derive(Debug, Clone)]
 pub struct IpPrefixTrie<V> {
-    ipv4: PrefixMapTrie<Ipv4Prefix, V>,
-    ipv6: PrefixMapTrie<Ipv6Prefix, V>,
+    ipv4: LpmTrie<Ipv4Prefix, V>,
+    ipv6: LpmTrie<Ipv6Prefix, V>,
 }
 
 impl<V: Clone> IpPrefixTrie<V> {
     #[must_use]
     pub fn new() -> Self {
+        Self::with_backend(TrieBackend::default())
+    }
+
+    /// Create an empty trie, with the indicated [`TrieBackend`]
+    #[must_use]
+    pub fn with_backend(backend: TrieBackend) -> Self {
         Self {
-            ipv4: PrefixMapTrie::create(),
-            ipv6: PrefixMapTrie::create(),
+            ipv4: LpmTrie::new(backend),
+            ipv6: LpmTrie::new(backend),
         }
     }
 
@@ -97,6 +215,94 @@ impl<V: Clone> IpPrefixTrie<V> {
         }
     }
 
+    /// Iterates over the prefixes covered by `prefix`, see [`TrieMap::covered`]
+    pub fn covered(&self, prefix: &Prefix) -> impl Iterator<Item = (Prefix, &V)> {
+        match *prefix {
+            Prefix::IPV4(prefix) => EitherIter::Left(
+                self.ipv4
+                    .covered(prefix)
+                    .map(|(k, v)| (Prefix::IPV4(*k), v)),
+            ),
+            Prefix::IPV6(prefix) => EitherIter::Right(
+                self.ipv6
+                    .covered(prefix)
+                    .map(|(k, v)| (Prefix::IPV6(*k), v)),
+            ),
+        }
+    }
+
+    /// Iterates over the prefixes covering `prefix`, see [`TrieMap::covering`]
+    pub fn covering(&self, prefix: &Prefix) -> impl Iterator<Item = (Prefix, &V)> {
+        match *prefix {
+            Prefix::IPV4(prefix) => EitherIter::Left(
+                self.ipv4
+                    .covering(prefix)
+                    .map(|(k, v)| (Prefix::IPV4(*k), v)),
+            ),
+            Prefix::IPV6(prefix) => EitherIter::Right(
+                self.ipv6
+                    .covering(prefix)
+                    .map(|(k, v)| (Prefix::IPV6(*k), v)),
+            ),
+        }
+    }
+
+    /// Iterates over the differences between this trie and `other`, IPv4 prefixes first, see
+    /// [`TrieMap::diff`]
+    pub fn diff<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = TrieDiff<Prefix, &'a V>>
+    where
+        V: PartialEq,
+    {
+        let ipv4 = self.ipv4.diff(&other.ipv4);
+        let ipv6 = self.ipv6.diff(&other.ipv6);
+        ipv4.map(|diff| diff.map_prefix(|prefix| Prefix::IPV4(*prefix)))
+            .chain(ipv6.map(|diff| diff.map_prefix(|prefix| Prefix::IPV6(*prefix))))
+    }
+
+    /// Batched version of [`IpPrefixTrie::lookup`], appending the results to `results` in the
+    /// order of `addrs`. The IPv4 and IPv6 addresses are looked up in separate batches, with
+    /// [`TrieMap::lookup_batch`] on the trie of their family.
+    pub fn lookup_batch<'a>(
+        &'a self,
+        addrs: &[IpAddr],
+        results: &mut Vec<Option<(Prefix, &'a V)>>,
+    ) {
+        let mut ipv4 = Vec::new();
+        let mut ipv6 = Vec::new();
+        for addr in addrs {
+            match addr {
+                IpAddr::V4(ip) => ipv4.push(*ip),
+                IpAddr::V6(ip) => ipv6.push(*ip),
+            }
+        }
+        let mut hits4 = Vec::with_capacity(ipv4.len());
+        let mut hits6 = Vec::with_capacity(ipv6.len());
+        self.ipv4.lookup_batch(&ipv4, &mut hits4);
+        self.ipv6.lookup_batch(&ipv6, &mut hits6);
+
+        let (mut hits4, mut hits6) = (hits4.into_iter(), hits6.into_iter());
+        results.extend(addrs.iter().map(|addr| {
+            match addr {
+                IpAddr::V4(_) => hits4
+                    .next()
+                    .unwrap_or_else(|| unreachable!())
+                    .map(|(k, v)| (Prefix::IPV4(*k), v)),
+                IpAddr::V6(_) => hits6
+                    .next()
+                    .unwrap_or_else(|| unreachable!())
+                    .map(|(k, v)| (Prefix::IPV6(*k), v)),
+            }
+        }));
+    }
+
+    /// Gets the statistics of the IPv4 and IPv6 tries together, see [`TrieMap::stats`]
+    #[must_use]
+    pub fn stats(&self) -> TrieStats {
+        let mut stats = self.ipv4.stats();
+        stats.merge(&self.ipv6.stats());
+        stats
+    }
+
     pub fn len(&self) -> usize {
         self.ipv4.len() + self.ipv6.len()
     }
@@ -111,3 +317,61 @@ impl<V: Clone> Default for IpPrefixTrie<V> {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted<'a>(iter: impl Iterator<Item = (Prefix, &'a &'static str)>) -> Vec<&'static str> {
+        let mut values: Vec<_> = iter.map(|(_, value)| *value).collect();
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn test_covered_covering() {
+        for backend in [TrieBackend::PrefixMap, TrieBackend::Compressed] {
+            let mut trie = IpPrefixTrie::with_backend(backend);
+            for (prefix, name) in [
+                ("0.0.0.0/0", "root"),
+                ("10.0.0.0/8", "10/8"),
+                ("10.1.0.0/16", "10.1/16"),
+                ("10.1.1.0/24", "10.1.1/24"),
+                ("10.2.0.0/16", "10.2/16"),
+                ("11.0.0.0/8", "11/8"),
+                ("2001:db8::/32", "v6"),
+            ] {
+                trie.insert(Prefix::expect_from(prefix), name);
+            }
+
+            let covered = |prefix: &str| sorted(trie.covered(&Prefix::expect_from(prefix)));
+            assert_eq!(
+                covered("10.0.0.0/8"),
+                ["10.1.1/24", "10.1/16", "10.2/16", "10/8"]
+            );
+            assert_eq!(covered("10.1.0.0/16"), ["10.1.1/24", "10.1/16"]);
+            assert_eq!(covered("10.1.0.0/17"), ["10.1.1/24"]);
+            assert!(covered("12.0.0.0/8").is_empty());
+            assert_eq!(covered("0.0.0.0/0").len(), 6);
+
+            let covering = |prefix: &str| {
+                let prefix = Prefix::expect_from(prefix);
+                trie.covering(&prefix)
+                    .map(|(_, value)| *value)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                covering("10.1.1.0/24"),
+                ["10.1.1/24", "10.1/16", "10/8", "root"]
+            );
+            assert_eq!(
+                covering("10.1.1.128/25"),
+                ["10.1.1/24", "10.1/16", "10/8", "root"]
+            );
+            assert_eq!(covering("10.3.0.0/16"), ["10/8", "root"]);
+            assert_eq!(covering("0.0.0.0/0"), ["root"]);
+            assert_eq!(covering("2001:db8:1::/48"), ["v6"]);
+            assert!(covering("2001:db9::/32").is_empty());
+        }
+    }
+}
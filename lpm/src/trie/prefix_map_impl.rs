@@ -8,7 +8,8 @@ use std::borrow::Borrow;
 use std::default::Default;
 use std::fmt::{Debug, Display};
 
-use crate::trie::{TrieMap, TrieMapFactory, TrieMapWithDefault};
+use crate::trie::stats::{compressed_nodes, depths};
+use crate::trie::{TrieMap, TrieMapFactory, TrieMapWithDefault, TrieStats, truncate};
 
 #[derive(Clone)]
 #[repr(transparent)]
@@ -132,6 +133,39 @@ where
             .get_lpm(&IpPrefixW(addr.into()))
             .map(|(p, v)| (&p.0, v))
     }
+
+    fn covered<B>(&self, prefix: B) -> impl Iterator<Item = (&P, &V)>
+    where
+        B: Borrow<P>,
+    {
+        self.0
+            .children(&IpPrefixW(prefix.borrow().clone()))
+            .map(|(p, v)| (&p.0, v))
+    }
+
+    fn covering<B>(&self, prefix: B) -> impl Iterator<Item = (&P, &V)>
+    where
+        B: Borrow<P>,
+    {
+        let first = self.0.get_lpm(&IpPrefixW(prefix.borrow().clone()));
+        std::iter::successors(first, |(p, _)| {
+            let shorter = truncate(&p.0, p.0.len().checked_sub(1)?)?;
+            self.0.get_lpm(&IpPrefixW(shorter))
+        })
+        .map(|(p, v)| (&p.0, v))
+    }
+
+    /// The nodes are those of the path-compressed binary trie holding the prefixes, each with
+    /// the prefix, an optional value and the indices of both children
+    fn stats(&self) -> TrieStats {
+        let nodes = compressed_nodes(self.iter().map(|(p, _)| p));
+        TrieStats {
+            prefixes: self.len(),
+            nodes,
+            memory: nodes * size_of::<(P, Option<V>, [Option<usize>; 2])>(),
+            depths: depths(self.iter().map(|(p, _)| p)),
+        }
+    }
 }
 
 pub type PrefixMapTrieWithDefault<P, V> = TrieMapWithDefault<PrefixMapTrie<P, V>>;
@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Memory and depth statistics of tries. Prefix tries are path-compressed binary tries: their
+//! nodes are the prefixes, the root, and the branching points between prefixes that do not
+//! cover each other. The depth of a prefix is the number of prefixes of the trie covering it,
+//! i.e. the number of candidates that a lookup for an address of that prefix walks past.
+
+use crate::prefix::IpPrefix;
+use crate::prefix::ip::Representable;
+use crate::trie::truncate;
+use num_traits::PrimInt;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+/// Statistics of a trie
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrieStats {
+    /// Number of prefixes
+    pub prefixes: usize,
+    /// Number of nodes, as laid out by the implementation of the trie
+    pub nodes: usize,
+    /// Memory used by the trie, in bytes. This is an estimate, from the sizes of the nodes, and
+    /// excludes the memory that the values own.
+    pub memory: usize,
+    /// Number of prefixes per depth, the depth of a prefix being the number of other prefixes
+    /// of the trie covering it
+    pub depths: Vec<usize>,
+}
+
+impl TrieStats {
+    /// Tell the largest depth of the prefixes, if any
+    #[must_use]
+    pub fn max_depth(&self) -> Option<usize> {
+        self.depths.iter().rposition(|count| *count > 0)
+    }
+
+    /// Add the statistics of `other` to these, e.g. to account for several tries together
+    pub fn merge(&mut self, other: &TrieStats) {
+        self.prefixes += other.prefixes;
+        self.nodes += other.nodes;
+        self.memory += other.memory;
+        if self.depths.len() < other.depths.len() {
+            self.depths.resize(other.depths.len(), 0);
+        }
+        for (count, other) in self.depths.iter_mut().zip(&other.depths) {
+            *count += other;
+        }
+    }
+}
+
+impl Display for TrieStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} prefixes, {} nodes, {} KiB",
+            self.prefixes,
+            self.nodes,
+            self.memory.div_ceil(1024)
+        )?;
+        if !self.depths.is_empty() {
+            write!(f, ", depths:")?;
+            for (depth, count) in self.depths.iter().enumerate() {
+                write!(f, " {depth}:{count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compute the depth histogram of `prefixes`, sorted by network address then length
+pub(crate) fn depths<'a, P: IpPrefix + 'a>(prefixes: impl Iterator<Item = &'a P>) -> Vec<usize> {
+    let mut depths = Vec::new();
+    let mut enclosing: Vec<P::Repr> = Vec::new(); /* last addresses of the covering prefixes */
+    for prefix in prefixes {
+        let network = prefix.network().to_bits();
+        while enclosing.last().is_some_and(|last| *last < network) {
+            enclosing.pop();
+        }
+        if depths.len() <= enclosing.len() {
+            depths.resize(enclosing.len() + 1, 0);
+        }
+        depths[enclosing.len()] += 1;
+        enclosing.push(prefix.last_address().to_bits());
+    }
+    depths
+}
+
+/// Get the longest prefix covering both `a` and `b`
+fn common<P: IpPrefix>(a: &P, b: &P) -> Option<P> {
+    let diff = a.network().to_bits() ^ b.network().to_bits();
+    let len = u8::try_from(diff.leading_zeros()).unwrap_or(u8::MAX);
+    truncate(a, len.min(a.len()).min(b.len()))
+}
+
+/// Count the nodes of a path-compressed binary trie holding `prefixes`, sorted by network
+/// address then length. The branching points are the longest prefixes covering two
+/// consecutive prefixes, unless the first covers the second.
+pub(crate) fn compressed_nodes<'a, P: IpPrefix + 'a>(
+    prefixes: impl Iterator<Item = &'a P>,
+) -> usize {
+    let key = |prefix: &P| (prefix.network().to_bits(), prefix.len());
+    let mut nodes = BTreeSet::from([key(&P::ROOT)]);
+    let mut previous: Option<&P> = None;
+    for prefix in prefixes {
+        nodes.insert(key(prefix));
+        if let Some(branch) = previous.and_then(|previous| common(previous, prefix)) {
+            nodes.insert(key(&branch));
+        }
+        previous = Some(prefix);
+    }
+    nodes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prefix::{Ipv4Prefix, Prefix};
+    use crate::trie::{IpPrefixTrie, LpmTrie, TrieBackend, TrieMap};
+
+    #[test]
+    fn test_trie_stats() {
+        for backend in [TrieBackend::PrefixMap, TrieBackend::Compressed] {
+            let mut trie: LpmTrie<Ipv4Prefix, u32> = LpmTrie::new(backend);
+            assert_eq!(trie.stats().prefixes, 0);
+            assert_eq!(trie.stats().max_depth(), None);
+            for (n, prefix) in [
+                "10.0.0.0/8",
+                "10.1.0.0/16",
+                "10.1.1.0/24",
+                "10.1.2.0/24",
+                "10.2.0.0/16",
+                "192.168.0.0/16",
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let n = u32::try_from(n).unwrap();
+                trie.insert(*Prefix::expect_from(prefix).get_v4(), n);
+            }
+            let stats = trie.stats();
+            assert_eq!(stats.prefixes, 6);
+            assert_eq!(stats.depths, [2, 2, 2]);
+            assert_eq!(stats.max_depth(), Some(2));
+            assert!(stats.nodes >= stats.prefixes);
+            assert!(stats.memory > 0);
+        }
+
+        // the root, 4 prefixes, and the branching points 10.1.0.0/22 and 10.0.0.0/14
+        let mut trie: LpmTrie<Ipv4Prefix, u32> = LpmTrie::new(TrieBackend::PrefixMap);
+        for prefix in [
+            "10.1.1.0/24",
+            "10.1.2.0/24",
+            "10.2.0.0/16",
+            "192.168.0.0/16",
+        ] {
+            trie.insert(*Prefix::expect_from(prefix).get_v4(), 0);
+        }
+        assert_eq!(trie.stats().nodes, 7);
+
+        let mut trie = IpPrefixTrie::new();
+        trie.insert(Prefix::expect_from("10.0.0.0/8"), 1);
+        trie.insert(Prefix::expect_from("2001:db8::/32"), 2);
+        trie.insert(Prefix::expect_from("2001:db8:1::/48"), 3);
+        let stats = trie.stats();
+        assert_eq!(stats.prefixes, 3);
+        assert_eq!(stats.depths, [2, 1]);
+    }
+}
@@ -2,7 +2,7 @@
 // Copyright Open Network Fabric Authors
 
 use crate::prefix::IpPrefix;
-use crate::trie::{TrieMap, TrieMapFactory};
+use crate::trie::{TrieMap, TrieMapFactory, TrieStats};
 use std::borrow::Borrow;
 use tracing::warn;
 
@@ -128,4 +128,22 @@ impl<T: TrieMap<Value: Default> + TrieMapFactory<T>> TrieMap for TrieMapWithDefa
         }
         self.0.remove(prefix)
     }
+
+    fn covered<B>(&self, prefix: B) -> impl Iterator<Item = (&Self::Prefix, &Self::Value)>
+    where
+        B: Borrow<Self::Prefix>,
+    {
+        self.0.covered(prefix)
+    }
+
+    fn covering<B>(&self, prefix: B) -> impl Iterator<Item = (&Self::Prefix, &Self::Value)>
+    where
+        B: Borrow<Self::Prefix>,
+    {
+        self.0.covering(prefix)
+    }
+
+    fn stats(&self) -> TrieStats {
+        self.0.stats()
+    }
 }
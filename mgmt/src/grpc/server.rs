@@ -4,23 +4,39 @@
 // mgmt/src/grpc/server.rs
 
 use async_trait::async_trait;
+use std::pin::Pin;
 use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_stream::Stream;
 use tonic::{Request, Response, Status};
 use tracing::debug;
 
-use crate::processor::proc::{ConfigChannelRequest, ConfigRequest, ConfigResponse};
+use crate::processor::proc::{
+    ConfigChannelRequest, ConfigRequest, ConfigResponse, InterfaceChange,
+};
 use config::converters::grpc::{
     convert_dataplane_status_to_grpc, convert_gateway_config_from_grpc_with_defaults,
 };
 use config::internal::status::DataplaneStatus;
 use config::{GenId, GwConfig};
+use lpm::prefix::{Prefix, PrefixString};
+use nat::stateful::{NatSession, NatSessionPage, NatSessionQuery};
+use net::packet::VpcDiscriminant;
+use net::vxlan::Vni;
+use pkt_meta::flow_table::{FlowFilter, FlowProto};
+use rekon::Change;
+use routing::rib::watch::{RouteChange, RouteEvent, RouteSubscription};
+use routing::snapshot::RoutingSnapshot;
 use tokio::sync::mpsc::Sender;
 
 // Import proto-generated types
 use gateway_config::{
     ConfigService, ConfigServiceServer, Error, GatewayConfig, GetConfigGenerationRequest,
     GetConfigGenerationResponse, GetConfigRequest, GetDataplaneStatusRequest,
-    GetDataplaneStatusResponse, UpdateConfigRequest, UpdateConfigResponse,
+    GetDataplaneStatusResponse, GetNatSessionsRequest, GetNatSessionsResponse,
+    GetRoutingSnapshotRequest, GetRoutingSnapshotResponse, InterfaceChangeOp, NatSessionProtocol,
+    PlannedInterfaceChange, PreviewConfigRequest, PreviewConfigResponse, RouteChangeEvent,
+    RouteChangeType, UpdateConfigRequest, UpdateConfigResponse, WatchRoutesRequest,
 };
 
 /// Trait for configuration management
@@ -30,8 +46,114 @@ pub trait ConfigManager: Send + Sync {
     async fn get_generation(&self) -> Result<i64, String>;
     async fn apply_config(&self, config: GatewayConfig) -> Result<(), String>;
     async fn get_dataplane_status(&self) -> Result<DataplaneStatus, String>;
+    async fn preview_config(&self, config: GatewayConfig) -> Result<Vec<InterfaceChange>, String>;
+    async fn get_nat_sessions(&self, query: NatSessionQuery) -> Result<NatSessionPage, String>;
+    async fn get_routing_snapshot(&self, vrfid: Option<u32>) -> Result<RoutingSnapshot, String>;
+    async fn subscribe_routes(&self) -> Result<RouteSubscription, String>;
+}
+
+/// Convert a planned interface change to its gRPC representation
+fn convert_interface_change_to_grpc(change: &InterfaceChange) -> PlannedInterfaceChange {
+    let (op, name) = match change {
+        Change::Create(spec) => (InterfaceChangeOp::Create, &spec.name),
+        Change::Update(spec, _) => (InterfaceChangeOp::Update, &spec.name),
+        Change::Remove(observed) => (InterfaceChangeOp::Remove, &observed.name),
+    };
+    PlannedInterfaceChange {
+        op: op as i32,
+        interface: name.to_string(),
+    }
+}
+
+/// Convert a gRPC request for NAT sessions to a session query
+fn convert_nat_session_query_from_grpc(
+    request: &GetNatSessionsRequest,
+) -> Result<NatSessionQuery, String> {
+    let mut filter = FlowFilter::new();
+    if let Some(vni) = request.vni {
+        let vni = Vni::new_checked(vni).map_err(|e| format!("Invalid VNI {vni}: {e}"))?;
+        filter = filter.with_vpcd(VpcDiscriminant::VNI(vni));
+    }
+    if let Some(prefix) = &request.prefix {
+        let prefix = Prefix::try_from(PrefixString(prefix))
+            .map_err(|e| format!("Invalid prefix {prefix}: {e}"))?;
+        filter = filter.with_prefix(prefix);
+    }
+    if let Some(protocol) = request.protocol {
+        let protocol = NatSessionProtocol::try_from(protocol)
+            .map_err(|_| format!("Invalid protocol {protocol}"))?;
+        filter = filter.with_proto(match protocol {
+            NatSessionProtocol::Tcp => FlowProto::Tcp,
+            NatSessionProtocol::Udp => FlowProto::Udp,
+            NatSessionProtocol::Icmp => FlowProto::Icmp,
+        });
+    }
+
+    let mut query = NatSessionQuery::new(filter)
+        .with_offset(usize::try_from(request.offset).unwrap_or(usize::MAX));
+    // No limit in the request: use the default one
+    if request.limit != 0 {
+        query = query.with_limit(usize::try_from(request.limit).unwrap_or(usize::MAX));
+    }
+    Ok(query)
+}
+
+/// Convert a NAT session to its gRPC representation
+fn convert_nat_session_to_grpc(session: &NatSession) -> gateway_config::NatSession {
+    fn vni(vpcd: Option<VpcDiscriminant>) -> Option<u32> {
+        match vpcd? {
+            VpcDiscriminant::VNI(vni) => Some(vni.as_u32()),
+            VpcDiscriminant::MPLS(_) | VpcDiscriminant::QinQ { .. } => None,
+        }
+    }
+    let flow = &session.flow;
+    let protocol = match flow.proto {
+        FlowProto::Tcp => NatSessionProtocol::Tcp,
+        FlowProto::Udp => NatSessionProtocol::Udp,
+        FlowProto::Icmp => NatSessionProtocol::Icmp,
+    };
+    let (src_port, dst_port) = (
+        flow.src_port.or(flow.icmp_id),
+        flow.dst_port.or(flow.icmp_id),
+    );
+    let (translated_src_ip, translated_src_port) = session.translated_src();
+    let (translated_dst_ip, translated_dst_port) = session.translated_dst();
+    gateway_config::NatSession {
+        src_vni: vni(flow.src_vpcd),
+        dst_vni: vni(flow.dst_vpcd),
+        protocol: protocol as i32,
+        src_ip: flow.src_ip.to_string(),
+        dst_ip: flow.dst_ip.to_string(),
+        src_port: src_port.map(u32::from),
+        dst_port: dst_port.map(u32::from),
+        translated_src_ip: translated_src_ip.to_string(),
+        translated_dst_ip: translated_dst_ip.to_string(),
+        translated_src_port: translated_src_port.map(u32::from),
+        translated_dst_port: translated_dst_port.map(u32::from),
+        age: flow.age.try_into().ok(),
+        idle: flow.idle.try_into().ok(),
+        packets: flow.packets,
+        bytes: flow.bytes,
+    }
 }
 
+fn convert_route_event_to_grpc(event: &RouteEvent) -> RouteChangeEvent {
+    let change = match event.change {
+        RouteChange::Add => RouteChangeType::Add,
+        RouteChange::Modify => RouteChangeType::Modify,
+        RouteChange::Del => RouteChangeType::Del,
+    };
+    RouteChangeEvent {
+        vrf_id: event.vrfid,
+        prefix: event.prefix.to_string(),
+        change: change.into(),
+        origin: event.origin.map(|origin| origin.to_string()),
+    }
+}
+
+/// The stream of route changes sent to a subscriber
+type RouteChangeStream = Pin<Box<dyn Stream<Item = Result<RouteChangeEvent, Status>> + Send>>;
+
 /// Implementation of the gRPC server
 pub struct ConfigServiceImpl {
     config_manager: Arc<dyn ConfigManager>,
@@ -109,6 +231,110 @@ impl ConfigService for ConfigServiceImpl {
 
         Ok(Response::new(grpc))
     }
+
+    async fn preview_config(
+        &self,
+        request: Request<PreviewConfigRequest>,
+    ) -> Result<Response<PreviewConfigResponse>, Status> {
+        let preview_request = request.into_inner();
+        let grpc_config = preview_request
+            .config
+            .ok_or_else(|| Status::invalid_argument("Missing config in preview request"))?;
+
+        match self.config_manager.preview_config(grpc_config).await {
+            Ok(changes) => Ok(Response::new(PreviewConfigResponse {
+                error: Error::None as i32,
+                message: format!(
+                    "Applying the configuration would make {} changes",
+                    changes.len()
+                ),
+                changes: changes
+                    .iter()
+                    .map(convert_interface_change_to_grpc)
+                    .collect(),
+            })),
+            Err(e) => Ok(Response::new(PreviewConfigResponse {
+                error: Error::ApplyFailed as i32,
+                message: format!("Failed to preview configuration: {e}"),
+                changes: vec![],
+            })),
+        }
+    }
+
+    async fn get_nat_sessions(
+        &self,
+        request: Request<GetNatSessionsRequest>,
+    ) -> Result<Response<GetNatSessionsResponse>, Status> {
+        let query = convert_nat_session_query_from_grpc(&request.into_inner())
+            .map_err(Status::invalid_argument)?;
+
+        let page = self
+            .config_manager
+            .get_nat_sessions(query)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to get NAT sessions: {e}")))?;
+
+        Ok(Response::new(GetNatSessionsResponse {
+            sessions: page
+                .sessions
+                .iter()
+                .map(convert_nat_session_to_grpc)
+                .collect(),
+            offset: page.offset as u64,
+            total: page.total as u64,
+            next_offset: page.next_offset().map(|offset| offset as u64),
+        }))
+    }
+
+    async fn get_routing_snapshot(
+        &self,
+        request: Request<GetRoutingSnapshotRequest>,
+    ) -> Result<Response<GetRoutingSnapshotResponse>, Status> {
+        let snapshot = self
+            .config_manager
+            .get_routing_snapshot(request.into_inner().vrf_id)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to get routing snapshot: {e}")))?;
+        let snapshot = snapshot
+            .to_json()
+            .map_err(|e| Status::internal(format!("Failed to serialize routing snapshot: {e}")))?;
+
+        Ok(Response::new(GetRoutingSnapshotResponse { snapshot }))
+    }
+
+    type WatchRoutesStream = RouteChangeStream;
+
+    /// Stream the route changes of all VRFs, or of the one requested. The stream fails with
+    /// a data loss error if the subscriber does not keep up and misses changes: it should then
+    /// subscribe again and get a routing snapshot to resynchronize.
+    async fn watch_routes(
+        &self,
+        request: Request<WatchRoutesRequest>,
+    ) -> Result<Response<Self::WatchRoutesStream>, Status> {
+        let vrfid = request.into_inner().vrf_id;
+        let subscription =
+            self.config_manager.subscribe_routes().await.map_err(|e| {
+                Status::internal(format!("Failed to subscribe to route changes: {e}"))
+            })?;
+
+        let stream = futures::stream::unfold(Some(subscription), move |subscription| async move {
+            let mut subscription = subscription?;
+            loop {
+                match subscription.recv().await {
+                    Ok(event) if vrfid.is_some_and(|vrfid| vrfid != event.vrfid) => {}
+                    Ok(event) => {
+                        return Some((Ok(convert_route_event_to_grpc(&event)), Some(subscription)));
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        let status = Status::data_loss(format!("Missed {missed} route changes"));
+                        return Some((Err(status), None));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
 }
 
 /// Basic configuration manager implementation
@@ -212,6 +438,86 @@ impl ConfigManager for BasicConfigManager {
             _ => unreachable!(),
         }
     }
+
+    async fn preview_config(
+        &self,
+        grpc_config: GatewayConfig,
+    ) -> Result<Vec<InterfaceChange>, String> {
+        debug!("Received request to preview config");
+
+        let external_config = convert_gateway_config_from_grpc_with_defaults(&grpc_config)?;
+        let gw_config = Box::new(GwConfig::new(external_config));
+
+        // build a request to the config processor, send it and get the response
+        let (req, rx) = ConfigChannelRequest::new(ConfigRequest::PreviewConfig(gw_config));
+        self.channel_tx
+            .send(req)
+            .await
+            .map_err(|_| "Failure relaying request".to_string())?;
+        let response = rx
+            .await
+            .map_err(|_| "Failure receiving from config processor".to_string())?;
+        match response {
+            ConfigResponse::PreviewConfig(result) => {
+                result.map_err(|e| format!("Failed to preview config: {e}"))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    async fn get_nat_sessions(&self, query: NatSessionQuery) -> Result<NatSessionPage, String> {
+        debug!("Received request to get NAT sessions");
+
+        // build a request to the config processor, send it and get the response
+        let (req, rx) = ConfigChannelRequest::new(ConfigRequest::GetNatSessions(query));
+        self.channel_tx
+            .send(req)
+            .await
+            .map_err(|_| "Failure relaying request".to_string())?;
+        let response = rx
+            .await
+            .map_err(|_| "Failure receiving from config processor".to_string())?;
+        match response {
+            ConfigResponse::GetNatSessions(page) => Ok(*page),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn get_routing_snapshot(&self, vrfid: Option<u32>) -> Result<RoutingSnapshot, String> {
+        debug!("Received request to get routing snapshot");
+
+        // build a request to the config processor, send it and get the response
+        let (req, rx) = ConfigChannelRequest::new(ConfigRequest::GetRoutingSnapshot(vrfid));
+        self.channel_tx
+            .send(req)
+            .await
+            .map_err(|_| "Failure relaying request".to_string())?;
+        let response = rx
+            .await
+            .map_err(|_| "Failure receiving from config processor".to_string())?;
+        match response {
+            ConfigResponse::GetRoutingSnapshot(result) => result.map(|snapshot| *snapshot),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn subscribe_routes(&self) -> Result<RouteSubscription, String> {
+        debug!("Received request to subscribe to route changes");
+
+        // build a request to the config processor, send it and get the response
+        let (req, rx) = ConfigChannelRequest::new(ConfigRequest::SubscribeRoutes);
+        self.channel_tx
+            .send(req)
+            .await
+            .map_err(|_| "Failure relaying request".to_string())?;
+        let response = rx
+            .await
+            .map_err(|_| "Failure receiving from config processor".to_string())?;
+        match response {
+            ConfigResponse::SubscribeRoutes(result) => result,
+            _ => unreachable!(),
+        }
+    }
 }
 
 /// Function to create the gRPC service
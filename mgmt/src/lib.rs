@@ -12,6 +12,10 @@ pub mod processor;
 /* VPC manager */
 pub mod vpc_manager;
 
+/* Test harness for the gRPC service */
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 #[cfg(test)]
 mod tests;
 
@@ -208,6 +208,11 @@ pub(crate) fn generate_router_config(
         generate_router_interfaces_config(internal, kernel_vrfs, &mut router_config)?;
     }
 
+    /* static routes of the underlay are programmed in the RIB, without FRR */
+    for route in &config.external.underlay.static_routes {
+        router_config.add_static_route(route.clone());
+    }
+
     /* set frr config as part of the router config */
     let frr_cfg = internal.render(&genid);
     router_config.set_frr_config(frr_cfg.to_string());
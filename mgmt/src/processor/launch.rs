@@ -2,7 +2,7 @@
 // Copyright Open Network Fabric Authors
 
 use crate::processor::proc::ConfigChannelRequest;
-use crate::processor::proc::ConfigProcessor;
+use crate::processor::proc::{ConfigProcessor, DeviceSender};
 
 use std::fmt::Display;
 use std::io::Error;
@@ -19,6 +19,7 @@ use tokio_stream::Stream;
 use nat::stateful::NatAllocatorWriter;
 use nat::stateless::NatTablesWriter;
 use pkt_meta::dst_vpcd_lookup::VpcDiscTablesWriter;
+use pkt_meta::flow_table::FlowTable;
 use routing::ctl::RouterCtlSender;
 
 use crate::grpc::server::create_config_service;
@@ -176,6 +177,8 @@ pub fn start_mgmt(
     vpcdtablesw: VpcDiscTablesWriter,
     vpcmapw: VpcMapWriter<VpcMapName>,
     vps_stats_store: std::sync::Arc<stats::VpcStatsStore>,
+    device_tx: DeviceSender,
+    nat_sessions: concurrency::sync::Arc<FlowTable>,
 ) -> Result<std::thread::JoinHandle<()>, Error> {
     /* build server address from provided grpc address */
     let server_address = match grpc_addr {
@@ -198,7 +201,7 @@ pub fn start_mgmt(
 
             /* block thread to run gRPC and configuration processor */
             rt.block_on(async {
-                let (processor, tx) = ConfigProcessor::new(
+                let (mut processor, tx) = ConfigProcessor::new(
                     router_ctl,
                     vpcmapw,
                     nattablew,
@@ -206,6 +209,8 @@ pub fn start_mgmt(
                     vpcdtablesw,
                     vps_stats_store,
                 );
+                processor.set_device_sender(device_tx);
+                processor.set_nat_sessions(nat_sessions);
                 spawn(async { processor.run().await });
 
                 // Start the appropriate server based on address type
@@ -4,7 +4,7 @@
 // !Configuration processor
 
 use concurrency::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use tokio::spawn;
 use tokio::sync::mpsc;
@@ -20,18 +20,22 @@ use config::{external::overlay::Overlay, internal::device::tracecfg::TracingConf
 
 use crate::processor::confbuild::internal::build_internal_config;
 use crate::processor::confbuild::router::generate_router_config;
-use nat::stateful::NatAllocatorWriter;
+use nat::stateful::{NatAllocatorWriter, NatSessionPage, NatSessionQuery, query_sessions};
 use nat::stateless::NatTablesWriter;
 use nat::stateless::setup::{build_nat_configuration, validate_nat_configuration};
 use pkt_meta::dst_vpcd_lookup::VpcDiscTablesWriter;
 use pkt_meta::dst_vpcd_lookup::setup::build_dst_vni_lookup_configuration;
+use pkt_meta::flow_table::FlowTable;
 use routing::frr::FrrAppliedConfig;
+use routing::rib::watch::RouteSubscription;
+use routing::snapshot::RoutingSnapshot;
 
 use crate::processor::display::GwConfigDatabaseSummary;
 use crate::processor::gwconfigdb::GwConfigDatabase;
 
-use crate::vpc_manager::{RequiredInformationBase, VpcManager};
-use rekon::{Observe, Reconcile};
+use crate::vpc_manager::{RequiredInformationBase, VpcManager, VpcReconcilePass};
+use interface_manager::interface::InterfaceSpec;
+use rekon::{Change, Observe, ReconcileLoop};
 use tracectl::get_trace_ctl;
 use tracing::{debug, error, info, warn};
 
@@ -51,6 +55,10 @@ pub enum ConfigRequest {
     GetCurrentConfig,
     GetGeneration,
     GetDataplaneStatus,
+    PreviewConfig(Box<GwConfig>),
+    GetNatSessions(NatSessionQuery),
+    GetRoutingSnapshot(Option<u32>),
+    SubscribeRoutes,
 }
 
 /// A response from the `ConfigProcessor`
@@ -60,9 +68,16 @@ pub enum ConfigResponse {
     GetCurrentConfig(Box<Option<GwConfig>>),
     GetGeneration(Option<GenId>),
     GetDataplaneStatus(Box<DataplaneStatus>),
+    PreviewConfig(Result<Vec<InterfaceChange>, ConfigError>),
+    GetNatSessions(Box<NatSessionPage>),
+    GetRoutingSnapshot(Result<Box<RoutingSnapshot>, String>),
+    SubscribeRoutes(Result<RouteSubscription, String>),
 }
 type ConfigResponseChannel = oneshot::Sender<ConfigResponse>;
 
+/// A change to a kernel interface which applying a configuration would make
+pub type InterfaceChange = Change<InterfaceSpec, Interface>;
+
 /// A type that includes a request to the `ConfigProcessor` and a channel to
 /// issue the response back
 pub struct ConfigChannelRequest {
@@ -78,6 +93,10 @@ impl ConfigChannelRequest {
     }
 }
 
+/// Sender of the devices of the interfaces of each applied configuration, identified by their
+/// PCI address, for the driver to attach (hotplug) those it does not have yet
+pub type DeviceSender = std::sync::mpsc::Sender<BTreeSet<String>>;
+
 /// A configuration processor entity. This is the RPC-independent entity responsible for
 /// accepting/rejecting configurations, storing them in the configuration database and
 /// applying them.
@@ -91,6 +110,8 @@ pub(crate) struct ConfigProcessor {
     natallocatorw: NatAllocatorWriter,
     vnitablesw: VpcDiscTablesWriter,
     vpc_stats_store: Arc<VpcStatsStore>,
+    device_tx: Option<DeviceSender>,
+    nat_sessions: Option<Arc<FlowTable>>,
 }
 /// Populate FRR status into the dataplane status structure
 pub async fn populate_status_with_frr(
@@ -99,8 +120,21 @@ pub async fn populate_status_with_frr(
 ) {
     let mut frr = FrrStatus::new();
 
-    if let Ok(Some(FrrAppliedConfig { genid, .. })) = router_ctl.get_frr_applied_config().await {
-        frr = frr.set_applied_config_gen(genid);
+    if let Ok(apply_status) = router_ctl.get_frr_apply_status().await {
+        /* after a rollback, the applied config is the one rolled back to */
+        if let Some(FrrAppliedConfig { genid, .. }) = apply_status.applied {
+            frr = frr.set_applied_config_gen(genid);
+        }
+        frr = frr
+            .set_applied_configs(u32::try_from(apply_status.applied_configs).unwrap_or(u32::MAX))
+            .set_failed_configs(u32::try_from(apply_status.failed_configs).unwrap_or(u32::MAX))
+            .set_rollbacks(u32::try_from(apply_status.rollbacks).unwrap_or(u32::MAX))
+            .set_rollback_failures(
+                u32::try_from(apply_status.rollback_failures).unwrap_or(u32::MAX),
+            );
+        if let Some(genid) = apply_status.last_fail_genid {
+            frr = frr.set_last_failed_config_gen(genid);
+        }
     }
 
     status.set_frr_status(frr);
@@ -142,10 +176,41 @@ impl ConfigProcessor {
             natallocatorw,
             vnitablesw,
             vpc_stats_store,
+            device_tx: None,
+            nat_sessions: None,
         };
         (processor, tx)
     }
 
+    /// Publish the devices of the interfaces of each applied configuration to `device_tx`
+    pub(crate) fn set_device_sender(&mut self, device_tx: DeviceSender) {
+        self.device_tx = Some(device_tx);
+    }
+
+    /// Serve the queries for stateful NAT sessions from `nat_sessions`
+    pub(crate) fn set_nat_sessions(&mut self, nat_sessions: Arc<FlowTable>) {
+        self.nat_sessions = Some(nat_sessions);
+    }
+
+    /// Publish the devices of the interfaces of `config`, if anyone listens
+    fn publish_devices(&self, config: &GwConfig) {
+        let Some(device_tx) = &self.device_tx else {
+            return;
+        };
+        let devices = config
+            .external
+            .underlay
+            .vrf
+            .interfaces
+            .values()
+            .filter_map(|iface| iface.pci.as_ref())
+            .map(ToString::to_string)
+            .collect();
+        if device_tx.send(devices).is_err() {
+            debug!("No driver listens to device updates");
+        }
+    }
+
     /// Main entry point for new configurations
     pub(crate) async fn process_incoming_config(&mut self, mut config: GwConfig) -> ConfigResult {
         let genid = config.genid();
@@ -206,6 +271,7 @@ impl ConfigProcessor {
         }
         config.meta.set_state(genid, true, None);
         self.config_db.set_current_gen(genid);
+        self.publish_devices(&config);
         if !self.config_db.contains(genid) {
             self.config_db.add(config);
         }
@@ -230,6 +296,9 @@ impl ConfigProcessor {
             )
             .await;
         }
+        if let Some(prior) = self.config_db.get(rollback_cfg) {
+            self.publish_devices(prior);
+        }
     }
 
     /// RPC handler: store and apply the provided config
@@ -257,6 +326,20 @@ impl ConfigProcessor {
         ConfigResponse::GetCurrentConfig(cfg)
     }
 
+    /// RPC handler: compute the interface changes which applying the provided config would make,
+    /// without applying it
+    async fn handle_preview_config(&self, mut config: GwConfig) -> ConfigResponse {
+        let genid = config.genid();
+        debug!("Handling preview configuration request. Genid {genid}");
+        let result = async {
+            config.validate()?;
+            let internal = build_internal_config(&config)?;
+            self.vpc_mgr.preview_config(&internal).await
+        }
+        .await;
+        ConfigResponse::PreviewConfig(result)
+    }
+
     /// RPC handler: get dataplane status
     async fn handle_get_dataplane_status(&mut self) -> ConfigResponse {
         let mut status = DataplaneStatus::new();
@@ -284,6 +367,8 @@ impl ConfigProcessor {
             id_of.insert(disc, format!("{disc:?}"));
             let vni = match disc {
                 vpcmap::VpcDiscriminant::VNI(v) => v.as_u32(),
+                // not a VXLAN VPC: report no VNI
+                vpcmap::VpcDiscriminant::MPLS(_) | vpcmap::VpcDiscriminant::QinQ { .. } => 0,
             };
             vni_of.insert(disc, vni);
         }
@@ -342,6 +427,39 @@ impl ConfigProcessor {
         ConfigResponse::GetDataplaneStatus(Box::new(status))
     }
 
+    /// RPC handler: get the stateful NAT sessions matching a query
+    fn handle_get_nat_sessions(&self, query: &NatSessionQuery) -> ConfigResponse {
+        debug!("Handling query for NAT sessions: {query:?}");
+        let page = match &self.nat_sessions {
+            Some(sessions) => query_sessions(sessions, query),
+            None => NatSessionPage::default(),
+        };
+        ConfigResponse::GetNatSessions(Box::new(page))
+    }
+
+    /// RPC handler: get a snapshot of the RIB and FIB of all VRFs, or of one of them
+    async fn handle_get_routing_snapshot(&mut self, vrfid: Option<u32>) -> ConfigResponse {
+        debug!("Handling request for routing snapshot");
+        let snapshot = self
+            .router_ctl
+            .get_snapshot(vrfid)
+            .await
+            .map(Box::new)
+            .map_err(|e| e.to_string());
+        ConfigResponse::GetRoutingSnapshot(snapshot)
+    }
+
+    /// RPC handler: subscribe to the route changes of all VRFs
+    async fn handle_subscribe_routes(&mut self) -> ConfigResponse {
+        debug!("Handling subscription to route changes");
+        let subscription = self
+            .router_ctl
+            .subscribe_routes()
+            .await
+            .map_err(|e| e.to_string());
+        ConfigResponse::SubscribeRoutes(subscription)
+    }
+
     /// Run the configuration processor
     #[allow(unreachable_code)]
     pub async fn run(mut self) {
@@ -359,6 +477,16 @@ impl ConfigProcessor {
                         ConfigRequest::GetDataplaneStatus => {
                             self.handle_get_dataplane_status().await
                         }
+                        ConfigRequest::PreviewConfig(config) => {
+                            self.handle_preview_config(*config).await
+                        }
+                        ConfigRequest::GetNatSessions(query) => {
+                            self.handle_get_nat_sessions(&query)
+                        }
+                        ConfigRequest::GetRoutingSnapshot(vrfid) => {
+                            self.handle_get_routing_snapshot(vrfid).await
+                        }
+                        ConfigRequest::SubscribeRoutes => self.handle_subscribe_routes().await,
                     };
                     if req.reply_tx.send(response).is_err() {
                         warn!("Failed to send reply from config processor: receiver dropped?");
@@ -376,7 +504,7 @@ impl VpcManager<RequiredInformationBase> {
     /// Apply the provided [`InternalConfig`]
     async fn apply_config(&self, internal: &InternalConfig, genid: GenId) -> ConfigResult {
         /* build required information base from internal config */
-        let mut rib: RequiredInformationBase = match internal.try_into() {
+        let rib: RequiredInformationBase = match internal.try_into() {
             Ok(rib) => rib,
             Err(err) => {
                 let msg = format!("Couldn't build required information base: {err}");
@@ -387,17 +515,14 @@ impl VpcManager<RequiredInformationBase> {
 
         debug!("Required information base for genid {genid} is:\n{rib:?}");
 
-        let mut required_passes = 0;
-        while !self
-            .reconcile(&mut rib, &self.observe().await.unwrap())
-            .await
-        {
-            required_passes += 1;
-            if required_passes >= 300 {
-                let msg = "Interface reconciliation not achieved after 300 passes".to_string();
-                error!("{msg}");
-                return Err(ConfigError::FailureApply(msg));
-            }
+        let pass = VpcReconcilePass::new(self.clone(), rib);
+        let mut reconcile = ReconcileLoop::new(pass)
+            .with_max_progress_passes(300)
+            .with_max_failures(1);
+        if let Err(err) = reconcile.converge().await {
+            let msg = format!("Interface reconciliation not achieved: {err}");
+            error!("{msg}");
+            return Err(ConfigError::FailureApply(msg));
         }
         debug!("VPC-manager successfully applied config for genid {genid}");
 
@@ -419,6 +544,19 @@ impl VpcManager<RequiredInformationBase> {
         Ok(())
     }
 
+    /// Compute the interface changes which applying the provided [`InternalConfig`] would make
+    async fn preview_config(
+        &self,
+        internal: &InternalConfig,
+    ) -> Result<Vec<InterfaceChange>, ConfigError> {
+        let rib: RequiredInformationBase = internal.try_into().map_err(|err| {
+            ConfigError::FailureApply(format!("Couldn't build required information base: {err}"))
+        })?;
+        self.preview(&rib).await.map_err(|_| {
+            ConfigError::InternalFailure("Failed to observe interface state".to_string())
+        })
+    }
+
     /// Get the current set of kernel interfaces of type VRF keyed by name
     async fn get_kernel_vrfs(&self) -> Result<HashMap<InterfaceName, Interface>, ConfigError> {
         let obs_rib = self.observe().await.map_err(|_| {
@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Test harness for the management gRPC service.
+//!
+//! [`MgmtTestHarness`] starts a router, a configuration processor operating on in-memory tables
+//! and the gRPC config service on an ephemeral TCP port of the loopback interface, and provides a
+//! client connected to it. Tests can then drive the service through the client and inspect the
+//! tables the configuration processor writes to.
+//!
+//! This lives in this crate (behind feature `testing`) rather than in `test-utils`, since this
+//! crate depends on `test-utils` for its own tests.
+
+use crate::grpc::server::create_config_service;
+use crate::processor::proc::ConfigProcessor;
+use gateway_config::ConfigServiceClient;
+use nat::stateful::{NatAllocatorReader, NatAllocatorWriter};
+use nat::stateless::{NatTablesReader, NatTablesWriter};
+use pkt_meta::dst_vpcd_lookup::{VpcDiscTablesReader, VpcDiscTablesWriter};
+use routing::{Router, RouterParamsBuilder};
+use stats::{VpcMapName, VpcStatsStore};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Channel, Server};
+use tracing::{debug, error};
+use vpcmap::map::{VpcMapReader, VpcMapWriter};
+
+#[derive(Debug, Error)]
+pub enum MgmtTestError {
+    #[error("Failed to start router: {0}")]
+    Router(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to connect to gRPC server: {0}")]
+    Connect(#[from] tonic::transport::Error),
+}
+
+/// Readers for the tables the configuration processor of a [`MgmtTestHarness`] writes to
+pub struct MgmtTestTables {
+    pub vpcmap: VpcMapReader<VpcMapName>,
+    pub nat_tables: NatTablesReader,
+    pub nat_allocator: NatAllocatorReader,
+    pub vpcd_tables: VpcDiscTablesReader,
+    pub vpc_stats: Arc<VpcStatsStore>,
+}
+
+/// A running management service with a connected client.
+/// Must be created from within a tokio runtime.
+pub struct MgmtTestHarness {
+    address: SocketAddr,
+    client: ConfigServiceClient<Channel>,
+    tables: MgmtTestTables,
+    router: Router,
+    sock_dir: PathBuf,
+    shutdown: Option<oneshot::Sender<()>>,
+    server: JoinHandle<()>,
+    processor: JoinHandle<()>,
+}
+
+/// Distinguishes the socket directories of harnesses within a process
+static HARNESS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+impl MgmtTestHarness {
+    /// Start the management service and connect a client to it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the router cannot be started, if the server cannot be bound or if the client
+    /// cannot connect.
+    pub async fn start() -> Result<Self, MgmtTestError> {
+        let count = HARNESS_COUNT.fetch_add(1, Ordering::Relaxed);
+        let sock_dir =
+            std::env::temp_dir().join(format!("mgmt-test-{}-{count}", std::process::id()));
+        std::fs::create_dir_all(&sock_dir)?;
+
+        let router_params = RouterParamsBuilder::default()
+            .name(format!("mgmt-test-{count}"))
+            .cpi_sock_path(sock_dir.join("cpi.sock"))
+            .cli_sock_path(sock_dir.join("cli.sock"))
+            .frr_agent_path(sock_dir.join("frr-agent.sock"))
+            .build()
+            .map_err(|e| MgmtTestError::Router(e.to_string()))?;
+        let router =
+            Router::new(router_params).map_err(|e| MgmtTestError::Router(e.to_string()))?;
+
+        let vpcmapw = VpcMapWriter::<VpcMapName>::new();
+        let nattablesw = NatTablesWriter::new();
+        let natallocatorw = NatAllocatorWriter::new();
+        let vpcdtablesw = VpcDiscTablesWriter::new();
+        let vpc_stats = VpcStatsStore::new();
+        let tables = MgmtTestTables {
+            vpcmap: vpcmapw.get_reader(),
+            nat_tables: nattablesw.get_reader(),
+            nat_allocator: natallocatorw.get_reader(),
+            vpcd_tables: vpcdtablesw.get_reader(),
+            vpc_stats: vpc_stats.clone(),
+        };
+
+        let (processor, tx) = ConfigProcessor::new(
+            router.get_ctl_tx(),
+            vpcmapw,
+            nattablesw,
+            natallocatorw,
+            vpcdtablesw,
+            vpc_stats,
+        );
+        let processor = tokio::spawn(async { processor.run().await });
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let address = listener.local_addr()?;
+        let (shutdown, signal) = oneshot::channel();
+        let server = tokio::spawn(async move {
+            let result = Server::builder()
+                .add_service(create_config_service(tx))
+                .serve_with_incoming_shutdown(TcpIncoming::from(listener), async {
+                    let _ = signal.await;
+                })
+                .await;
+            if let Err(e) = result {
+                error!("Test gRPC server failed: {e}");
+            }
+        });
+        debug!("Test gRPC server listening on {address}");
+
+        let client = ConfigServiceClient::connect(format!("http://{address}")).await?;
+        Ok(Self {
+            address,
+            client,
+            tables,
+            router,
+            sock_dir,
+            shutdown: Some(shutdown),
+            server,
+            processor,
+        })
+    }
+
+    /// The address the gRPC server listens on
+    #[must_use]
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// A client connected to the gRPC server
+    pub fn client(&mut self) -> &mut ConfigServiceClient<Channel> {
+        &mut self.client
+    }
+
+    /// Readers for the tables the configuration processor writes to
+    #[must_use]
+    pub fn tables(&self) -> &MgmtTestTables {
+        &self.tables
+    }
+
+    /// Stop the server gracefully, then the configuration processor and the router.
+    pub async fn stop(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let server = std::mem::replace(&mut self.server, tokio::spawn(async {}));
+        let _ = server.await;
+        // dropping self takes care of the rest
+    }
+}
+
+impl Drop for MgmtTestHarness {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        self.server.abort();
+        self.processor.abort();
+        self.router.stop();
+        let _ = std::fs::remove_dir_all(&self.sock_dir);
+    }
+}
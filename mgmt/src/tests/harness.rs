@@ -0,0 +1,35 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+#[cfg(test)]
+mod test {
+    use crate::testing::MgmtTestHarness;
+    use caps::Capability::CAP_NET_ADMIN;
+    use gateway_config::{GetConfigGenerationRequest, GetConfigRequest};
+    use test_utils::with_caps;
+    use tonic::Code;
+
+    #[tokio::test]
+    #[fixin::wrap(with_caps([CAP_NET_ADMIN]))]
+    async fn test_harness_no_config() {
+        let mut harness = MgmtTestHarness::start()
+            .await
+            .expect("Harness should start");
+
+        /* no config was applied: the service replies, but with errors */
+        let status = harness
+            .client()
+            .get_config_generation(GetConfigGenerationRequest::default())
+            .await
+            .expect_err("No generation without config");
+        assert_eq!(status.code(), Code::Internal);
+        let status = harness
+            .client()
+            .get_config(GetConfigRequest::default())
+            .await
+            .expect_err("No config without config");
+        assert_eq!(status.code(), Code::Internal);
+
+        harness.stop().await;
+    }
+}
@@ -11,6 +11,7 @@ pub mod test {
     use net::eth::mac::Mac;
     use net::interface::Mtu;
     use pkt_meta::dst_vpcd_lookup::VpcDiscTablesWriter;
+    use std::collections::BTreeSet;
     use std::net::IpAddr;
     use std::net::Ipv4Addr;
     use std::str::FromStr;
@@ -307,6 +308,7 @@ pub mod test {
         Underlay {
             vrf: default_vrf,
             vtep: None,
+            static_routes: BTreeSet::new(),
         }
     }
 
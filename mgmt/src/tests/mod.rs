@@ -5,3 +5,6 @@
 
 #[cfg(test)]
 mod mgmt;
+
+#[cfg(test)]
+mod harness;
@@ -10,8 +10,8 @@ use derive_builder::Builder;
 use futures::TryStreamExt;
 use interface_manager::Manager;
 use interface_manager::interface::{
-    BridgePropertiesSpec, InterfaceAssociationSpec, InterfacePropertiesSpec, InterfaceSpecBuilder,
-    MultiIndexInterfaceAssociationSpecMap, MultiIndexInterfaceSpecMap,
+    BridgePropertiesSpec, InterfaceAssociationSpec, InterfacePropertiesSpec, InterfaceSpec,
+    InterfaceSpecBuilder, MultiIndexInterfaceAssociationSpecMap, MultiIndexInterfaceSpecMap,
     MultiIndexVrfPropertiesSpecMap, MultiIndexVtepPropertiesSpecMap, TryFromLinkMessage,
     VrfPropertiesSpec, VtepPropertiesSpec,
 };
@@ -25,7 +25,7 @@ use net::interface::{
 use net::ip::UnicastIpAddr;
 use net::route::RouteTableId;
 use net::vxlan::{Vni, Vxlan};
-use rekon::{Observe, Op, Reconcile, Remove};
+use rekon::{Change, Create, Observe, PassOutcome, Plan, Reconcile, ReconcilePass, Remove, Update};
 use rtnetlink::Handle;
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
@@ -189,6 +189,9 @@ impl Reconcile for VpcManager<RequiredInformationBase> {
         Self: 'a;
 
     /// Returns true if the system is reconciled.
+    ///
+    /// This makes the changes computed by [`Plan::plan`]: the system is reconciled if there are
+    /// none.
     async fn reconcile<'a>(
         &self,
         requirement: &'a mut RequiredInformationBase,
@@ -197,86 +200,157 @@ impl Reconcile for VpcManager<RequiredInformationBase> {
     where
         Self: 'a,
     {
-        let mut reconciled = true;
-        // update the requirements to reflect which interfaces can be associated with which
-        for (_, association) in requirement.associations.iter() {
-            requirement
-                .interfaces
-                .update_by_name(&association.name, |_, _, _, controller, _| {
-                    *controller =
-                        association
-                            .controller_name
-                            .as_ref()
-                            .and_then(|controller_name| {
-                                observation
-                                    .interfaces
-                                    .get_by_name(controller_name)
-                                    .map(|controller| controller.index)
-                            });
-                });
+        let changes = self.plan(requirement, observation);
+        let reconciled = changes.is_empty();
+        let iface_handle = Manager::<Interface>::new(self.handle.clone());
+        for change in changes {
+            let result = match &change {
+                Change::Create(interface) => iface_handle.create(interface).await,
+                Change::Update(interface, observed) => {
+                    iface_handle.update(interface, observed).await
+                }
+                Change::Remove(observed) => iface_handle.remove(observed).await,
+            };
+            if let Err(err) = result {
+                error!("{err:?}");
+            }
         }
+        reconciled
+    }
+}
+
+impl Plan for VpcManager<RequiredInformationBase> {
+    type Requirement<'a>
+        = &'a RequiredInformationBase
+    where
+        Self: 'a;
+    type Observation<'a>
+        = &'a ObservedInformationBase
+    where
+        Self: 'a;
+    type Changes<'a>
+        = Vec<Change<InterfaceSpec, &'a Interface>>
+    where
+        Self: 'a;
 
-        // reconciling the extant interfaces as much as possible
+    /// Returns the interface changes which [`Reconcile::reconcile`] would make.
+    ///
+    /// The returned specs have their controller resolved from the associations of the requirement,
+    /// as `reconcile` would do.
+    fn plan<'a>(
+        &self,
+        requirement: &'a RequiredInformationBase,
+        observation: &'a ObservedInformationBase,
+    ) -> Self::Changes<'a>
+    where
+        Self: 'a,
+    {
         let iface_handle = Manager::<Interface>::new(self.handle.clone());
+        let mut changes = vec![];
+
+        // extant interfaces which are not required (and which we manage)
         for (_, interface) in observation.interfaces.iter() {
-            match requirement.interfaces.get_by_name(&interface.name) {
-                None => match interface.properties {
-                    InterfaceProperties::Other | InterfaceProperties::Pci(_) => {}
-                    _ => {
-                        reconciled = false;
-                        match iface_handle.remove(interface).await {
-                            Ok(()) => {}
-                            Err(err) => {
-                                error!("{err:?}")
-                            }
-                        }
-                    }
-                },
-                Some(requirement) => {
-                    match iface_handle.reconcile(requirement, Some(interface)).await {
-                        None => {}
-                        Some(
-                            Op::Create(Err(err)) | Op::Update(Err(err)) | Op::Remove(Err(err)),
-                        ) => {
-                            reconciled = false;
-                            error!("{err:?}");
-                        }
-                        Some(Op::Create(Ok(()))) => {
-                            reconciled = false;
-                        }
-                        Some(Op::Update(Ok(()))) => {
-                            reconciled = false;
-                        }
-                        Some(Op::Remove(Ok(()))) => {
-                            reconciled = false;
-                        }
-                    }
-                }
+            if requirement
+                .interfaces
+                .get_by_name(&interface.name)
+                .is_none()
+                && !matches!(
+                    interface.properties,
+                    InterfaceProperties::Other | InterfaceProperties::Pci(_)
+                )
+            {
+                changes.push(Change::Remove(interface));
             }
         }
 
-        // go through the requirement list and create anything missing (and reconcile anything out
-        // of sync)
+        // required interfaces which are missing or out of sync
         for (_, interface) in requirement.interfaces.iter() {
-            match iface_handle
-                .reconcile(
-                    interface,
-                    observation.interfaces.get_by_name(&interface.name),
-                )
-                .await
-            {
+            let mut interface = interface.clone();
+            if let Some(association) = requirement.associations.get_by_name(&interface.name) {
+                interface.controller =
+                    association
+                        .controller_name
+                        .as_ref()
+                        .and_then(|controller_name| {
+                            observation
+                                .interfaces
+                                .get_by_name(controller_name)
+                                .map(|controller| controller.index)
+                        });
+            }
+            let observed = observation.interfaces.get_by_name(&interface.name);
+            match iface_handle.plan(&interface, observed) {
                 None => {}
-                Some(Op::Create(Err(err)) | Op::Update(Err(err)) | Op::Remove(Err(err))) => {
-                    reconciled = false;
-                    error!("{err:?}");
-                }
-                _ => {
-                    reconciled = false;
+                Some(Change::Create(_)) => changes.push(Change::Create(interface)),
+                Some(Change::Update(_, observed)) => {
+                    changes.push(Change::Update(interface, observed));
                 }
+                Some(Change::Remove(observed)) => changes.push(Change::Remove(observed)),
             }
         }
 
-        reconciled
+        changes
+    }
+}
+
+/// A pass reconciling the kernel interfaces with a [`RequiredInformationBase`], to be driven
+/// by a [`rekon::ReconcileLoop`]
+pub struct VpcReconcilePass {
+    manager: VpcManager<RequiredInformationBase>,
+    requirement: RequiredInformationBase,
+}
+
+impl VpcReconcilePass {
+    #[must_use]
+    pub fn new(
+        manager: VpcManager<RequiredInformationBase>,
+        requirement: RequiredInformationBase,
+    ) -> Self {
+        Self {
+            manager,
+            requirement,
+        }
+    }
+}
+
+impl ReconcilePass for VpcReconcilePass {
+    type Error = ObservedInformationBaseBuilderError;
+
+    async fn pass(&mut self) -> Result<PassOutcome, Self::Error> {
+        let observation = self.manager.observe().await?;
+        let reconciled = self
+            .manager
+            .reconcile(&mut self.requirement, &observation)
+            .await;
+        Ok(reconciled.into())
+    }
+}
+
+impl VpcManager<RequiredInformationBase> {
+    /// Observe the system and compute the interface changes which reconciling it with
+    /// `requirement` would make, without making them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system could not be observed.
+    pub async fn preview(
+        &self,
+        requirement: &RequiredInformationBase,
+    ) -> Result<Vec<Change<InterfaceSpec, Interface>>, ObservedInformationBaseBuilderError> {
+        let observation = self.observe().await?;
+        let changes = self
+            .plan(requirement, &observation)
+            .into_iter()
+            .map(|change| match change {
+                Change::Create(spec) => Change::Create(spec),
+                Change::Update(spec, observed) => Change::Update(spec, observed.clone()),
+                Change::Remove(observed) => Change::Remove(observed.clone()),
+            })
+            .collect::<Vec<_>>();
+        for change in &changes {
+            debug!("planned interface change: {change:?}");
+        }
+        Ok(changes)
     }
 }
 
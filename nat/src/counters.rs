@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Per-VPC counters for the NAT stages.
+//!
+//! The counters are shared by all instances of the stateless and stateful NAT stages (typically,
+//! one per worker), and are indexed by the source VPC of the packets.
+
+use concurrency::sync::atomic::{AtomicU64, Ordering};
+use concurrency::sync::{Arc, RwLock};
+use net::packet::VpcDiscriminant;
+use std::collections::HashMap;
+
+/// A snapshot of the NAT counters for a VPC
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NatVpcStats {
+    /// Number of packets from the VPC that were translated, with stateless or stateful NAT
+    pub translated_packets: u64,
+    /// Number of stateful NAT sessions initiated from the VPC, currently in the session table
+    pub active_sessions: u64,
+    /// Number of stateful NAT sessions from the VPC that could not be created because no address
+    /// or port was left to allocate
+    pub allocation_failures: u64,
+}
+
+#[derive(Debug, Default)]
+struct VpcCounters {
+    translated_packets: AtomicU64,
+    active_sessions: AtomicU64,
+    allocation_failures: AtomicU64,
+}
+
+/// Per-VPC NAT counters, see [`NatVpcStats`]
+#[derive(Debug)]
+pub struct NatVpcCounters(RwLock<HashMap<VpcDiscriminant, Arc<VpcCounters>>>);
+
+impl NatVpcCounters {
+    #[must_use]
+    pub fn new() -> Self {
+        Self(RwLock::new(HashMap::new()))
+    }
+
+    fn counters(&self, vpcd: VpcDiscriminant) -> Arc<VpcCounters> {
+        if let Some(counters) = self.0.read().unwrap().get(&vpcd) {
+            return counters.clone();
+        }
+        self.0.write().unwrap().entry(vpcd).or_default().clone()
+    }
+
+    /// Account for a packet from `vpcd` that was translated
+    pub(crate) fn record_translation(&self, vpcd: VpcDiscriminant) {
+        if let Some(counters) = self.0.read().unwrap().get(&vpcd) {
+            counters.translated_packets.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.counters(vpcd)
+            .translated_packets
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Account for a new session from `vpcd` that failed, because the pool was exhausted
+    pub(crate) fn record_allocation_failure(&self, vpcd: VpcDiscriminant) {
+        self.counters(vpcd)
+            .allocation_failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Account for a new session from `vpcd`, for as long as the returned [`ActiveSession`] lives
+    pub(crate) fn new_session(&self, vpcd: VpcDiscriminant) -> ActiveSession {
+        let counters = self.counters(vpcd);
+        counters.active_sessions.fetch_add(1, Ordering::Relaxed);
+        ActiveSession(counters)
+    }
+
+    /// Get a snapshot of the counters, for all VPCs seen so far
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<(VpcDiscriminant, NatVpcStats)> {
+        self.0
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(vpcd, counters)| {
+                let stats = NatVpcStats {
+                    translated_packets: counters.translated_packets.load(Ordering::Relaxed),
+                    active_sessions: counters.active_sessions.load(Ordering::Relaxed),
+                    allocation_failures: counters.allocation_failures.load(Ordering::Relaxed),
+                };
+                (*vpcd, stats)
+            })
+            .collect()
+    }
+}
+
+impl Default for NatVpcCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A session accounted for in the active sessions of a VPC, until dropped
+#[derive(Debug)]
+pub(crate) struct ActiveSession(Arc<VpcCounters>);
+
+impl Drop for ActiveSession {
+    fn drop(&mut self) {
+        self.0.active_sessions.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use net::vxlan::Vni;
+
+    #[test]
+    fn test_vpc_counters() {
+        let counters = NatVpcCounters::new();
+        let vpcd1 = VpcDiscriminant::from_vni(Vni::new_checked(100).unwrap());
+        let vpcd2 = VpcDiscriminant::from_vni(Vni::new_checked(200).unwrap());
+
+        counters.record_translation(vpcd1);
+        counters.record_translation(vpcd1);
+        counters.record_allocation_failure(vpcd2);
+        let session1 = counters.new_session(vpcd1);
+        let session2 = counters.new_session(vpcd1);
+        drop(session1);
+
+        let mut snapshot = counters.snapshot();
+        snapshot.sort_by_key(|(vpcd, _)| vpcd.to_string());
+        assert_eq!(
+            snapshot,
+            vec![
+                (
+                    vpcd1,
+                    NatVpcStats {
+                        translated_packets: 2,
+                        active_sessions: 1,
+                        allocation_failures: 0,
+                    }
+                ),
+                (
+                    vpcd2,
+                    NatVpcStats {
+                        translated_packets: 0,
+                        active_sessions: 0,
+                        allocation_failures: 1,
+                    }
+                ),
+            ]
+        );
+
+        drop(session2);
+        assert_eq!(counters.snapshot().len(), 2);
+        assert!(
+            counters
+                .snapshot()
+                .iter()
+                .all(|(_, stats)| stats.active_sessions == 0)
+        );
+    }
+}
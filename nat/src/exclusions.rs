@@ -0,0 +1,217 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! "No-NAT" exceptions: prefixes and ports, within exposed prefixes with NAT, for which traffic
+//! bypasses address translation.
+//!
+//! Exceptions are configured on the private side of the expose objects. For traffic between two
+//! VPCs, exceptions from the exposes of the source VPC apply to the source address and port of the
+//! packets, and exceptions from the exposes of the destination VPC apply to the destination address
+//! and port. Exceptions are looked up by longest prefix match.
+
+use config::external::overlay::vpcpeering::{VpcExpose, VpcExposeNatExclusion};
+use lpm::prefix::Prefix;
+use lpm::trie::IpPrefixTrie;
+use net::buffer::PacketBufferMut;
+use net::headers::{Transport, TryHeaders, TryTransport};
+use net::packet::Packet;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::ops::RangeInclusive;
+
+/// The ports for which NAT does not apply, for a given exception prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ExcludedPorts {
+    All,
+    Ranges(Vec<RangeInclusive<u16>>),
+}
+
+impl ExcludedPorts {
+    fn new(ports: Option<&RangeInclusive<u16>>) -> Self {
+        match ports {
+            None => ExcludedPorts::All,
+            Some(ports) => ExcludedPorts::Ranges(vec![ports.clone()]),
+        }
+    }
+
+    fn merge(&mut self, other: &ExcludedPorts) {
+        match (&mut *self, other) {
+            (ExcludedPorts::All, _) => {}
+            (_, ExcludedPorts::All) => *self = ExcludedPorts::All,
+            (ExcludedPorts::Ranges(ranges), ExcludedPorts::Ranges(other_ranges)) => {
+                ranges.extend(other_ranges.iter().cloned());
+            }
+        }
+    }
+
+    // Packets without ports (ICMP) only match exceptions for all ports
+    fn contains(&self, port: Option<u16>) -> bool {
+        match self {
+            ExcludedPorts::All => true,
+            ExcludedPorts::Ranges(ranges) => {
+                port.is_some_and(|port| ranges.iter().any(|range| range.contains(&port)))
+            }
+        }
+    }
+}
+
+/// "No-NAT" exceptions for one side (source or destination) of the traffic between two VPCs.
+#[derive(Debug, Clone, Default)]
+pub struct NatExclusionTable {
+    // Excluded ports for each exception prefix, as configured
+    rules: BTreeMap<Prefix, ExcludedPorts>,
+    // Excluded ports for each exception prefix, merged with the ports for all prefixes covering it,
+    // so that the longest prefix match is enough to tell whether an address and port are excluded
+    trie: IpPrefixTrie<ExcludedPorts>,
+}
+
+impl PartialEq for NatExclusionTable {
+    fn eq(&self, other: &Self) -> bool {
+        self.rules == other.rules
+    }
+}
+
+impl Eq for NatExclusionTable {}
+
+impl NatExclusionTable {
+    /// Build a [`NatExclusionTable`] from the "no-NAT" exceptions of the given exposes
+    #[must_use]
+    pub fn from_exposes<'a>(exposes: impl Iterator<Item = &'a VpcExpose>) -> Self {
+        Self::from_exclusions(exposes.flat_map(VpcExpose::nat_exclusions))
+    }
+
+    fn from_exclusions<'a>(exclusions: impl Iterator<Item = &'a VpcExposeNatExclusion>) -> Self {
+        let mut rules = BTreeMap::<Prefix, ExcludedPorts>::new();
+        for exclusion in exclusions {
+            let ports = ExcludedPorts::new(exclusion.ports.as_ref());
+            rules
+                .entry(exclusion.prefix)
+                .and_modify(|current| current.merge(&ports))
+                .or_insert(ports);
+        }
+
+        let mut trie = IpPrefixTrie::new();
+        for prefix in rules.keys() {
+            let mut ports = ExcludedPorts::Ranges(Vec::new());
+            rules
+                .iter()
+                .filter(|(other, _)| other.covers(prefix))
+                .for_each(|(_, other_ports)| ports.merge(other_ports));
+            trie.insert(*prefix, ports);
+        }
+        Self { rules, trie }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Tell whether NAT does not apply to `addr` and `port`. Pass `None` as the port for packets
+    /// with no ports, such as ICMP packets: they only match exceptions for all ports.
+    #[must_use]
+    pub fn excludes(&self, addr: IpAddr, port: Option<u16>) -> bool {
+        self.trie
+            .lookup(addr)
+            .is_some_and(|(_, ports)| ports.contains(port))
+    }
+}
+
+/// "No-NAT" exceptions for the traffic from a source VPC to a destination VPC.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NatExclusions {
+    /// Exceptions for the source address and port, from the exposes of the source VPC
+    pub src: NatExclusionTable,
+    /// Exceptions for the destination address and port, from the exposes of the destination VPC
+    pub dst: NatExclusionTable,
+}
+
+impl NatExclusions {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.src.is_empty() && self.dst.is_empty()
+    }
+
+    /// Tell whether source NAT and destination NAT, respectively, do not apply to the given
+    /// addresses and ports.
+    #[must_use]
+    pub fn excludes(&self, src: (IpAddr, Option<u16>), dst: (IpAddr, Option<u16>)) -> (bool, bool) {
+        (
+            self.src.excludes(src.0, src.1),
+            self.dst.excludes(dst.0, dst.1),
+        )
+    }
+}
+
+/// Get the source and destination ports of a packet, if its transport protocol uses ports.
+pub(crate) fn packet_ports<Buf: PacketBufferMut>(
+    packet: &Packet<Buf>,
+) -> (Option<u16>, Option<u16>) {
+    match packet.headers().try_transport() {
+        Some(Transport::Tcp(tcp)) => (
+            Some(tcp.source().as_u16()),
+            Some(tcp.destination().as_u16()),
+        ),
+        Some(Transport::Udp(udp)) => (
+            Some(udp.source().as_u16()),
+            Some(udp.destination().as_u16()),
+        ),
+        _ => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn addr(ip: &str) -> IpAddr {
+        IpAddr::from_str(ip).unwrap()
+    }
+
+    #[test]
+    fn test_exclusion_table() {
+        let expose = VpcExpose::empty()
+            .ip("10.0.0.0/16".into())
+            .ip("1::/64".into())
+            .as_range("2.0.0.0/16".into())
+            .no_nat("10.0.0.0/20".into(), Some(22..=22))
+            .no_nat("10.0.0.0/20".into(), Some(8000..=8999))
+            .no_nat("10.0.1.0/24".into(), None)
+            .no_nat("10.0.2.0/24".into(), Some(443..=443))
+            .no_nat("1::/80".into(), None);
+        let table = NatExclusionTable::from_exposes(std::iter::once(&expose));
+        assert!(!table.is_empty());
+
+        // /20: ports 22 and 8000-8999 only
+        assert!(table.excludes(addr("10.0.3.1"), Some(22)));
+        assert!(table.excludes(addr("10.0.3.1"), Some(8080)));
+        assert!(!table.excludes(addr("10.0.3.1"), Some(80)));
+        assert!(!table.excludes(addr("10.0.3.1"), None));
+
+        // /24 within the /20: all ports
+        assert!(table.excludes(addr("10.0.1.1"), Some(80)));
+        assert!(table.excludes(addr("10.0.1.1"), None));
+
+        // /24 within the /20: ports from the /24 and from the /20
+        assert!(table.excludes(addr("10.0.2.1"), Some(443)));
+        assert!(table.excludes(addr("10.0.2.1"), Some(22)));
+        assert!(!table.excludes(addr("10.0.2.1"), Some(80)));
+
+        // Outside of exceptions
+        assert!(!table.excludes(addr("10.0.16.1"), Some(22)));
+
+        // IPv6
+        assert!(table.excludes(addr("1::1"), Some(80)));
+        assert!(!table.excludes(addr("1::1:0:0:1"), Some(80)));
+
+        let exclusions = NatExclusions {
+            src: table,
+            dst: NatExclusionTable::default(),
+        };
+        assert_eq!(
+            exclusions.excludes((addr("10.0.1.1"), None), (addr("10.0.1.1"), None)),
+            (true, false)
+        );
+    }
+}
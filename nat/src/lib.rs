@@ -21,11 +21,15 @@
 //! - The total number of available (not excluded) private addresses used in an "Expose" object must
 //!   be equal to the total number of publicly exposed addresses in this object.
 
+mod counters;
+mod exclusions;
 mod icmp_error_msg;
 mod port;
 pub mod stateful;
 pub mod stateless;
 
+pub use counters::{NatVpcCounters, NatVpcStats};
+pub use exclusions::{NatExclusionTable, NatExclusions};
 pub use port::NatPort;
 pub use stateful::StatefulNat;
 pub use stateless::StatelessNat;
@@ -4,10 +4,10 @@
 //! NAT allocator trait: a trait to build allocators to manage IP addresses and ports for stateful NAT.
 
 use crate::port::NatPortError;
+use crate::stateful::timeouts::SessionTimeouts;
 use net::ip::NextHeader;
 use pkt_meta::flow_table::FlowKey;
 use std::fmt::Debug;
-use std::time::Duration;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, thiserror::Error)]
 pub enum AllocatorError {
@@ -48,24 +48,24 @@ pub struct AllocationResult<T: Debug> {
     pub dst: Option<T>,
     pub return_src: Option<T>,
     pub return_dst: Option<T>,
-    pub src_flow_idle_timeout: Option<Duration>,
-    pub dst_flow_idle_timeout: Option<Duration>,
+    pub src_flow_timeouts: Option<SessionTimeouts>,
+    pub dst_flow_timeouts: Option<SessionTimeouts>,
 }
 
 impl<T: Debug> AllocationResult<T> {
-    /// Returns the idle timeout for the flow.
+    /// Returns the idle timeouts for the flow.
     ///
     /// # Returns
     ///
-    /// * `Some(Duration)` if at least one of `src_flow_idle_timeout` or `dst_flow_idle_timeout` is set.
-    /// * `None` if both `src_flow_idle_timeout` and `dst_flow_idle_timeout` are `None`.
+    /// * `Some(SessionTimeouts)` if at least one of `src_flow_timeouts` or `dst_flow_timeouts` is set.
+    /// * `None` if both `src_flow_timeouts` and `dst_flow_timeouts` are `None`.
     #[must_use]
-    pub fn idle_timeout(&self) -> Option<Duration> {
-        // Use the minimum of the two timeouts (source/destination).
+    pub fn session_timeouts(&self) -> Option<SessionTimeouts> {
+        // Use the minimum of the two timeouts (source/destination), for each protocol.
         //
         // FIXME: We shouldn't use just one of the two timeouts, but doing otherwise will require
         //        uncoupling entry creation for source and destination NAT.
-        match (self.src_flow_idle_timeout, self.dst_flow_idle_timeout) {
+        match (self.src_flow_timeouts, self.dst_flow_timeouts) {
             (Some(src), Some(dst)) => Some(src.min(dst)),
             (Some(src), None) => Some(src),
             (None, Some(dst)) => Some(dst),
@@ -2,11 +2,13 @@
 // Copyright Open Network Fabric Authors
 
 use crate::stateful::NatDefaultAllocator;
+use crate::stateful::stats::{NatAllocatorCounters, NatAllocatorStats, NatPortUsage};
 use arc_swap::ArcSwapOption;
 use config::ConfigError;
 use config::external::overlay::vpc::Peering;
 use config::external::overlay::vpc::VpcTable;
 use net::packet::VpcDiscriminant;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 #[derive(Debug, PartialEq)]
@@ -43,6 +45,7 @@ impl StatefulNatConfig {
 pub struct NatAllocatorWriter {
     config: StatefulNatConfig,
     allocator: Arc<ArcSwapOption<NatDefaultAllocator>>,
+    counters: Arc<NatAllocatorCounters>,
 }
 
 impl NatAllocatorWriter {
@@ -51,12 +54,23 @@ impl NatAllocatorWriter {
         Self {
             config: StatefulNatConfig::default(),
             allocator: Arc::new(ArcSwapOption::new(None)),
+            counters: Arc::new(NatAllocatorCounters::default()),
         }
     }
 
     #[must_use]
     pub fn get_reader(&self) -> NatAllocatorReader {
-        NatAllocatorReader(self.allocator.clone())
+        NatAllocatorReader {
+            allocator: self.allocator.clone(),
+            counters: self.counters.clone(),
+        }
+    }
+
+    /// Get a snapshot of the allocation failure and session eviction counters, for all readers of
+    /// this writer
+    #[must_use]
+    pub fn stats(&self) -> NatAllocatorStats {
+        self.counters.snapshot()
     }
 
     #[must_use]
@@ -126,11 +140,30 @@ impl Default for NatAllocatorWriter {
 }
 
 #[derive(Debug, Clone)]
-pub struct NatAllocatorReader(Arc<ArcSwapOption<NatDefaultAllocator>>);
+pub struct NatAllocatorReader {
+    allocator: Arc<ArcSwapOption<NatDefaultAllocator>>,
+    counters: Arc<NatAllocatorCounters>,
+}
 
 impl NatAllocatorReader {
     pub fn get(&self) -> Option<Arc<NatDefaultAllocator>> {
-        self.0.load().clone()
+        self.allocator.load().clone()
+    }
+    /// Get a snapshot of the allocation failure and session eviction counters
+    #[must_use]
+    pub fn stats(&self) -> NatAllocatorStats {
+        self.counters.snapshot()
+    }
+    /// Get the usage of the ports available for dynamic source NAT, for each source VPC, with the
+    /// current allocator
+    #[must_use]
+    pub fn port_usage(&self) -> HashMap<VpcDiscriminant, NatPortUsage> {
+        self.get()
+            .map(|allocator| allocator.port_usage())
+            .unwrap_or_default()
+    }
+    pub(crate) fn counters(&self) -> &NatAllocatorCounters {
+        &self.counters
     }
     #[must_use]
     pub fn factory(&self) -> NatAllocatorReaderFactory {
@@ -9,16 +9,19 @@
 //!
 //! See also the architecture diagram at the top of mod.rs.
 
+use super::port_blocks::PortBlockMap;
 use super::{NatIpWithBitmap, port_alloc};
 use crate::port::NatPort;
 use crate::stateful::NatIp;
 use crate::stateful::allocator::AllocatorError;
+use crate::stateful::stats::NatPortUsage;
+use crate::stateful::timeouts::SessionTimeouts;
 use concurrency::sync::{Arc, RwLock, Weak};
 use lpm::prefix::{IpPrefix, Prefix};
 use roaring::RoaringBitmap;
 use std::collections::{BTreeMap, VecDeque};
 use std::net::Ipv6Addr;
-use std::time::Duration;
+use std::ops::RangeInclusive;
 
 ///////////////////////////////////////////////////////////////////////////////
 // IpAllocator
@@ -28,20 +31,44 @@ use std::time::Duration;
 /// object that contains IP availables for a given
 /// [`VpcExpose`](config::external::overlay::vpcpeering::VpcExpose). It can allocate an IP and
 /// (using this IP) a port.
+///
+/// For deterministic NAT, the IP and the ports to allocate from depend on the address to
+/// translate, as per the [`PortBlockMap`].
+///
+/// With endpoint-independent mapping, the allocations for UDP source NAT are shared between all
+/// sessions from the same internal address and port, see [`super::eim`].
 #[derive(Debug, Clone)]
 pub(crate) struct IpAllocator<I: NatIpWithBitmap> {
     pool: Arc<RwLock<NatPool<I>>>,
+    port_blocks: Option<Arc<PortBlockMap>>,
+    endpoint_independent: bool,
 }
 
 impl<I: NatIpWithBitmap> IpAllocator<I> {
     pub(crate) fn new(pool: NatPool<I>) -> Self {
         Self {
             pool: Arc::new(RwLock::new(pool)),
+            port_blocks: None,
+            endpoint_independent: false,
         }
     }
 
-    pub(crate) fn idle_timeout(&self) -> Option<Duration> {
-        Some(self.pool.read().ok()?.idle_timeout())
+    pub(crate) fn with_port_blocks(mut self, port_blocks: Arc<PortBlockMap>) -> Self {
+        self.port_blocks = Some(port_blocks);
+        self
+    }
+
+    pub(crate) fn with_endpoint_independent(mut self) -> Self {
+        self.endpoint_independent = true;
+        self
+    }
+
+    pub(crate) fn endpoint_independent(&self) -> bool {
+        self.endpoint_independent
+    }
+
+    pub(crate) fn session_timeouts(&self) -> Option<SessionTimeouts> {
+        Some(self.pool.read().ok()?.session_timeouts())
     }
 
     pub(crate) fn deep_clone(&self) -> Result<IpAllocator<I>, AllocatorError> {
@@ -49,7 +76,26 @@ impl<I: NatIpWithBitmap> IpAllocator<I> {
             .pool
             .read()
             .map_err(|_| AllocatorError::InternalIssue("Failed to read pool".to_string()))?;
-        Ok(IpAllocator::new((*nat_pool).clone()))
+        Ok(IpAllocator {
+            pool: Arc::new(RwLock::new((*nat_pool).clone())),
+            port_blocks: self.port_blocks.clone(),
+            endpoint_independent: self.endpoint_independent,
+        })
+    }
+
+    /// Remove `ip` from the addresses available for dynamic allocation, for static NAT.
+    pub(crate) fn withhold_ip(&self, ip: I) -> Result<(), AllocatorError> {
+        self.pool.write().unwrap().withhold(ip)
+    }
+
+    /// Get the usage of the ports of the pool
+    pub(crate) fn port_usage(&self) -> NatPortUsage {
+        self.pool.read().unwrap().port_usage()
+    }
+
+    /// Tell whether `other` allocates from the same pool
+    pub(crate) fn shares_pool(&self, other: &IpAllocator<I>) -> bool {
+        Arc::ptr_eq(&self.pool, &other.pool)
     }
 
     fn deallocate_ip(&self, ip: I) {
@@ -99,13 +145,37 @@ impl<I: NatIpWithBitmap> IpAllocator<I> {
         allocated_ips.cleanup();
     }
 
+    // Allocate a port from the block assigned to `addr`, for deterministic NAT
+    fn allocate_from_port_block(
+        &self,
+        port_blocks: &PortBlockMap,
+        addr: I,
+        allow_null: bool,
+    ) -> Result<port_alloc::AllocatedPort<I>, AllocatorError> {
+        let block = port_blocks.lookup(addr.to_ip_addr()).ok_or_else(|| {
+            AllocatorError::InternalIssue(format!("No port block for address {addr}"))
+        })?;
+        let ip = I::try_from_addr(block.ip).map_err(|()| {
+            AllocatorError::InternalIssue("Failed to convert IP address for port block".to_string())
+        })?;
+        self.get_allocated_ip(ip).and_then(|allocated_ip| {
+            allocated_ip.allocate_port_in_range_for_ip(&block.ports, allow_null)
+        })
+    }
+
+    /// Allocate an IP and a port to translate `addr` into.
     pub(crate) fn allocate(
         &self,
+        addr: I,
         allow_null: bool,
     ) -> Result<port_alloc::AllocatedPort<I>, AllocatorError> {
         // FIXME: Should we clean up every time??
         self.cleanup_used_ips();
 
+        if let Some(port_blocks) = &self.port_blocks {
+            return self.allocate_from_port_block(port_blocks, addr, allow_null);
+        }
+
         if let Ok(port) = self.reuse_allocated_ip(allow_null) {
             return Ok(port);
         }
@@ -153,10 +223,10 @@ pub(crate) struct AllocatedIp<I: NatIpWithBitmap> {
 }
 
 impl<I: NatIpWithBitmap> AllocatedIp<I> {
-    fn new(ip: I, ip_allocator: IpAllocator<I>) -> Self {
+    fn new(ip: I, ip_allocator: IpAllocator<I>, port_range: Option<RangeInclusive<u16>>) -> Self {
         Self {
             ip,
-            port_allocator: port_alloc::PortAllocator::new(),
+            port_allocator: port_alloc::PortAllocator::new(port_range),
             ip_allocator,
         }
     }
@@ -169,6 +239,10 @@ impl<I: NatIpWithBitmap> AllocatedIp<I> {
         self.port_allocator.has_free_ports()
     }
 
+    fn ports_in_use(&self) -> u64 {
+        self.port_allocator.ports_in_use()
+    }
+
     pub(crate) fn deallocate_block_for_ip(&self, index: usize) {
         self.port_allocator.deallocate_block(index);
     }
@@ -186,6 +260,15 @@ impl<I: NatIpWithBitmap> AllocatedIp<I> {
     ) -> Result<port_alloc::AllocatedPort<I>, AllocatorError> {
         self.port_allocator.reserve_port(self.clone(), port)
     }
+
+    fn allocate_port_in_range_for_ip(
+        self: Arc<Self>,
+        ports: &RangeInclusive<u16>,
+        allow_null: bool,
+    ) -> Result<port_alloc::AllocatedPort<I>, AllocatorError> {
+        self.port_allocator
+            .allocate_port_in_range(self.clone(), ports, allow_null)
+    }
 }
 
 impl<I: NatIpWithBitmap> Drop for AllocatedIp<I> {
@@ -207,7 +290,11 @@ pub(crate) struct NatPool<I: NatIpWithBitmap> {
     bitmap_mapping: BTreeMap<u32, u128>,
     reverse_bitmap_mapping: BTreeMap<u128, u32>,
     in_use: VecDeque<Weak<AllocatedIp<I>>>,
-    idle_timeout: Duration,
+    // Number of addresses available for dynamic allocation
+    size: u64,
+    session_timeouts: SessionTimeouts,
+    // Range of ports to allocate from, for each IP address of the pool. All ports if None.
+    port_range: Option<RangeInclusive<u16>>,
 }
 
 impl<I: NatIpWithBitmap> NatPool<I> {
@@ -215,14 +302,17 @@ impl<I: NatIpWithBitmap> NatPool<I> {
         bitmap: PoolBitmap,
         bitmap_mapping: BTreeMap<u32, u128>,
         reverse_bitmap_mapping: BTreeMap<u128, u32>,
-        idle_timeout: Duration,
+        session_timeouts: SessionTimeouts,
+        port_range: Option<RangeInclusive<u16>>,
     ) -> Self {
         Self {
+            size: bitmap.0.len(),
             bitmap,
             bitmap_mapping,
             reverse_bitmap_mapping,
             in_use: VecDeque::new(),
-            idle_timeout,
+            session_timeouts,
+            port_range,
         }
     }
 
@@ -234,14 +324,28 @@ impl<I: NatIpWithBitmap> NatPool<I> {
         self.in_use.retain(|ip| ip.upgrade().is_some());
     }
 
-    fn idle_timeout(&self) -> Duration {
-        self.idle_timeout
+    fn session_timeouts(&self) -> SessionTimeouts {
+        self.session_timeouts
     }
 
     fn ips_in_use(&self) -> impl Iterator<Item = &Weak<AllocatedIp<I>>> {
         self.in_use.iter()
     }
 
+    fn port_usage(&self) -> NatPortUsage {
+        let ports_per_ip = self.port_range.as_ref().map_or(1 << 16, |range| {
+            u64::from(range.end().saturating_sub(*range.start())) + 1
+        });
+        NatPortUsage {
+            in_use: self
+                .ips_in_use()
+                .filter_map(Weak::upgrade)
+                .map(|ip| ip.ports_in_use())
+                .sum(),
+            total: self.size * ports_per_ip,
+        }
+    }
+
     fn use_new_ip(
         &mut self,
         ip_allocator: IpAllocator<I>,
@@ -250,7 +354,15 @@ impl<I: NatIpWithBitmap> NatPool<I> {
         let offset = self.bitmap.pop_ip()?;
 
         let ip = I::try_from_offset(offset, &self.bitmap_mapping)?;
-        Ok(AllocatedIp::new(ip, ip_allocator))
+        Ok(AllocatedIp::new(ip, ip_allocator, self.port_range.clone()))
+    }
+
+    fn withhold(&mut self, ip: I) -> Result<(), AllocatorError> {
+        let offset = I::try_to_offset(ip, &self.reverse_bitmap_mapping)?;
+        if self.bitmap.set_ip_allocated(offset) {
+            self.size -= 1;
+        }
+        Ok(())
     }
 
     fn deallocate_from_pool(&mut self, ip: I) {
@@ -282,7 +394,7 @@ impl<I: NatIpWithBitmap> NatPool<I> {
         // drops an AllocatedIp and its reference count goes to 0, but it hasn't called the drop()
         // function to remove the IP from the bitmap in that other thread yet).
         let _ = self.bitmap.set_ip_allocated(offset);
-        let arc_ip = Arc::new(AllocatedIp::new(ip, ip_allocator));
+        let arc_ip = Arc::new(AllocatedIp::new(ip, ip_allocator, self.port_range.clone()));
         self.add_in_use(&arc_ip);
         Ok(arc_ip)
     }
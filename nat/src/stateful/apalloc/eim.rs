@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Endpoint-independent mapping and filtering (RFC 4787), for UDP.
+//!
+//! With endpoint-independent mapping, all sessions from a given internal address and port use the
+//! same public address and port, whatever the remote endpoint. With endpoint-independent
+//! filtering, packets from any remote endpoint to this public address and port are translated back
+//! to the internal address and port, even if the internal endpoint never sent anything to this
+//! remote endpoint. Together, they make UDP hole punching (STUN, WebRTC) work.
+//!
+//! A mapping lives as long as at least one session uses its [`AllocatedPort`].
+
+use super::NatIpWithBitmap;
+use super::port_alloc::{AllocatedPort, WeakAllocatedPort};
+use crate::stateful::allocator::AllocatorError;
+use concurrency::sync::RwLock;
+use net::packet::VpcDiscriminant;
+use std::collections::HashMap;
+
+// Minimum number of mappings in the table before we look for stale entries to drop
+const MIN_CLEANUP_THRESHOLD: usize = 1024;
+
+/// An address and a port, for traffic from a source VPC to a destination VPC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct EimKey<I: NatIpWithBitmap> {
+    src_vpcd: VpcDiscriminant,
+    dst_vpcd: VpcDiscriminant,
+    ip: I,
+    port: u16,
+}
+
+impl<I: NatIpWithBitmap> EimKey<I> {
+    pub(crate) fn new(
+        src_vpcd: VpcDiscriminant,
+        dst_vpcd: VpcDiscriminant,
+        ip: I,
+        port: u16,
+    ) -> Self {
+        Self {
+            src_vpcd,
+            dst_vpcd,
+            ip,
+            port,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EimMapping<I: NatIpWithBitmap> {
+    internal_ip: I,
+    internal_port: u16,
+    allocation: WeakAllocatedPort<I>,
+}
+
+#[derive(Debug)]
+struct EimMaps<I: NatIpWithBitmap> {
+    // Mappings by internal address and port
+    by_internal: HashMap<EimKey<I>, WeakAllocatedPort<I>>,
+    // Mappings by public address and port, with the source and destination VPCs of the traffic
+    // from the internal endpoint
+    by_public: HashMap<EimKey<I>, EimMapping<I>>,
+    cleanup_threshold: usize,
+}
+
+impl<I: NatIpWithBitmap> EimMaps<I> {
+    // Drop the entries for mappings no longer in use, if the table has grown enough since the
+    // last time we did it
+    fn cleanup(&mut self) {
+        if self.by_internal.len() < self.cleanup_threshold {
+            return;
+        }
+        self.by_internal
+            .retain(|_, allocation| allocation.upgrade().is_some());
+        self.by_public
+            .retain(|_, mapping| mapping.allocation.upgrade().is_some());
+        self.cleanup_threshold = MIN_CLEANUP_THRESHOLD.max(self.by_internal.len() * 2);
+    }
+}
+
+/// The endpoint-independent mappings in use, for all peerings
+#[derive(Debug)]
+pub(crate) struct EimTable<I: NatIpWithBitmap>(RwLock<EimMaps<I>>);
+
+impl<I: NatIpWithBitmap> EimTable<I> {
+    pub(crate) fn new() -> Self {
+        Self(RwLock::new(EimMaps {
+            by_internal: HashMap::new(),
+            by_public: HashMap::new(),
+            cleanup_threshold: MIN_CLEANUP_THRESHOLD,
+        }))
+    }
+
+    /// Get the mapping in use for the internal address and port in `key`, or allocate a new one
+    /// with `allocate` and record it.
+    pub(crate) fn get_or_allocate<F>(
+        &self,
+        key: EimKey<I>,
+        allocate: F,
+    ) -> Result<AllocatedPort<I>, AllocatorError>
+    where
+        F: FnOnce() -> Result<AllocatedPort<I>, AllocatorError>,
+    {
+        let existing = self
+            .0
+            .read()
+            .unwrap()
+            .by_internal
+            .get(&key)
+            .and_then(WeakAllocatedPort::upgrade);
+        if let Some(allocation) = existing {
+            return Ok(allocation);
+        }
+
+        let allocation = allocate()?;
+        let mut maps = self.0.write().unwrap();
+        // Another thread may have recorded a mapping for the same internal endpoint in the
+        // meantime: use it, and release our allocation
+        if let Some(existing) = maps
+            .by_internal
+            .get(&key)
+            .and_then(WeakAllocatedPort::upgrade)
+        {
+            return Ok(existing);
+        }
+        maps.cleanup();
+        maps.by_internal.insert(key, allocation.downgrade());
+        maps.by_public.insert(
+            EimKey::new(
+                key.src_vpcd,
+                key.dst_vpcd,
+                allocation.ip(),
+                allocation.port().as_u16(),
+            ),
+            EimMapping {
+                internal_ip: key.ip,
+                internal_port: key.port,
+                allocation: allocation.downgrade(),
+            },
+        );
+        Ok(allocation)
+    }
+
+    /// Get the internal address and port, and the mapping, for the public address and port in
+    /// `key`, if the mapping is still in use.
+    pub(crate) fn lookup_public(&self, key: &EimKey<I>) -> Option<(I, u16, AllocatedPort<I>)> {
+        let maps = self.0.read().unwrap();
+        let mapping = maps.by_public.get(key)?;
+        let allocation = mapping.allocation.upgrade()?;
+        Some((mapping.internal_ip, mapping.internal_port, allocation))
+    }
+}
@@ -60,28 +60,54 @@
 //! [`AllocatedPortBlock`](port_alloc::AllocatedPortBlock) has a back reference to
 //! [`AllocatedIp`](alloc::AllocatedIp), and then the [`IpAllocator`], to deallocate the IP address
 //! when they are dropped.
+//!
+//! With deterministic NAT, the [`IpAllocator`] does not pick IP addresses and port blocks freely,
+//! but follows a fixed mapping from the addresses to translate: see the `port_blocks` submodule.
+//!
+//! Before looking up the pools, the allocator checks the "no-NAT" exceptions for the source and
+//! destination VPCs of the flow, see [`NatExclusions`]: the matching addresses get no allocation,
+//! and are left untranslated.
+//!
+//! UDP source NAT can use endpoint-independent mapping and filtering (RFC 4787), see the `eim`
+//! submodule. All sessions from a given internal address and port share the same allocated public
+//! address and port, and packets from any remote endpoint to this public address and port are
+//! translated back to the internal endpoint, without a source pool for the remote endpoint. This
+//! only applies when the remote endpoint has no stateful source NAT of its own.
+//!
+//! Static NAT bindings and port forwarding rules, see the `static_nat` submodule, apply before
+//! dynamic allocation: for the source of the traffic from their internal address, and for the
+//! destination of the traffic to their public address, from any remote endpoint.
 
 #![allow(clippy::ip_constant)]
 #![allow(rustdoc::private_intra_doc_links)]
 
 use super::allocator::{AllocationResult, AllocatorError};
 use super::{NatAllocator, NatIp};
-use crate::NatPort;
 use crate::stateful::apalloc::alloc::IpAllocator;
+use crate::stateful::apalloc::eim::{EimKey, EimTable};
 pub use crate::stateful::apalloc::natip_with_bitmap::NatIpWithBitmap;
+use crate::stateful::apalloc::port_blocks::PortBlockMap;
+pub use crate::stateful::apalloc::port_blocks::{PortBlock, PortBlockEntry};
+use crate::stateful::apalloc::static_nat::{StaticBinding, StaticNatTable};
+use crate::stateful::stats::NatPortUsage;
+use crate::{NatExclusions, NatPort};
+use concurrency::sync::Arc;
 use net::ip::NextHeader;
 use net::packet::VpcDiscriminant;
 use pkt_meta::flow_table::FlowKey;
 use pkt_meta::flow_table::IpProtoKey;
 use pkt_meta::flow_table::flow_key::IcmpProtoKey;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
 use std::net::{Ipv4Addr, Ipv6Addr};
 
 mod alloc;
+mod eim;
 mod natip_with_bitmap;
 mod port_alloc;
+mod port_blocks;
 mod setup;
+mod static_nat;
 mod test_alloc;
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -173,6 +199,23 @@ impl<I: NatIpWithBitmap, J: NatIpWithBitmap> PoolTable<I, J> {
     fn add_entry(&mut self, key: PoolTableKey<I>, allocator: alloc::IpAllocator<J>) {
         self.0.insert(key, allocator);
     }
+
+    // Add the port usage of the pools, to the usage for the source VPC of each pool. Allocators
+    // are shared between the entries for the different prefixes of a given expose, so we only
+    // account for each pool once.
+    fn add_port_usage(&self, usage: &mut HashMap<VpcDiscriminant, NatPortUsage>) {
+        let mut seen: Vec<&alloc::IpAllocator<J>> = Vec::new();
+        for (key, allocator) in &self.0 {
+            if seen.iter().any(|other| other.shares_pool(allocator)) {
+                continue;
+            }
+            seen.push(allocator);
+            usage
+                .entry(key.src_id)
+                .or_default()
+                .add(allocator.port_usage());
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -182,6 +225,8 @@ impl<I: NatIpWithBitmap, J: NatIpWithBitmap> PoolTable<I, J> {
 /// [`AllocatedIpPort`] is the public type for the object returned by our allocator.
 pub type AllocatedIpPort<I> = port_alloc::AllocatedPort<I>;
 type AllocationMapping<I> = (Option<AllocatedIpPort<I>>, Option<AllocatedIpPort<I>>);
+// Port blocks for deterministic source NAT, with the source and destination VPCs they apply to
+type PeeringPortBlocks = (VpcDiscriminant, VpcDiscriminant, Arc<PortBlockMap>);
 
 impl<I: NatIpWithBitmap> Display for AllocatedIpPort<I> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -205,6 +250,15 @@ pub struct NatDefaultAllocator {
     pools_dst44: PoolTable<Ipv4Addr, Ipv4Addr>,
     pools_src66: PoolTable<Ipv6Addr, Ipv6Addr>,
     pools_dst66: PoolTable<Ipv6Addr, Ipv6Addr>,
+    port_blocks: Vec<PeeringPortBlocks>,
+    // "No-NAT" exceptions, for each pair of source and destination VPCs
+    exclusions: HashMap<(VpcDiscriminant, VpcDiscriminant), NatExclusions>,
+    // Endpoint-independent mappings in use for UDP
+    eim44: EimTable<Ipv4Addr>,
+    eim66: EimTable<Ipv6Addr>,
+    // Static NAT bindings
+    static44: StaticNatTable<Ipv4Addr>,
+    static66: StaticNatTable<Ipv6Addr>,
 }
 
 impl NatAllocator<AllocatedIpPort<Ipv4Addr>, AllocatedIpPort<Ipv6Addr>> for NatDefaultAllocator {
@@ -214,6 +268,12 @@ impl NatAllocator<AllocatedIpPort<Ipv4Addr>, AllocatedIpPort<Ipv6Addr>> for NatD
             pools_dst44: PoolTable::new(),
             pools_src66: PoolTable::new(),
             pools_dst66: PoolTable::new(),
+            port_blocks: Vec::new(),
+            exclusions: HashMap::new(),
+            eim44: EimTable::new(),
+            eim66: EimTable::new(),
+            static44: StaticNatTable::new(),
+            static66: StaticNatTable::new(),
         }
     }
 
@@ -221,92 +281,270 @@ impl NatAllocator<AllocatedIpPort<Ipv4Addr>, AllocatedIpPort<Ipv6Addr>> for NatD
         &self,
         flow_key: &FlowKey,
     ) -> Result<AllocationResult<AllocatedIpPort<Ipv4Addr>>, AllocatorError> {
-        Self::allocate_from_tables(flow_key, &self.pools_src44, &self.pools_dst44)
+        Self::allocate_from_tables(
+            flow_key,
+            &self.pools_src44,
+            &self.pools_dst44,
+            &self.exclusions,
+            &self.eim44,
+            &self.static44,
+        )
     }
 
     fn allocate_v6(
         &self,
         flow_key: &FlowKey,
     ) -> Result<AllocationResult<AllocatedIpPort<Ipv6Addr>>, AllocatorError> {
-        Self::allocate_from_tables(flow_key, &self.pools_src66, &self.pools_dst66)
+        Self::allocate_from_tables(
+            flow_key,
+            &self.pools_src66,
+            &self.pools_dst66,
+            &self.exclusions,
+            &self.eim66,
+            &self.static66,
+        )
     }
 }
 
 impl NatDefaultAllocator {
+    /// Get the mapping for deterministic NAT: the port block assigned to each address translated
+    /// with deterministic source NAT, for all peerings.
+    ///
+    /// Port blocks are the same for TCP, UDP and ICMP (identifiers).
+    pub fn port_blocks(&self) -> impl Iterator<Item = PortBlockEntry> + '_ {
+        self.port_blocks
+            .iter()
+            .flat_map(|(src_vpcd, dst_vpcd, port_blocks)| {
+                port_blocks.iter().map(move |(addr, block)| PortBlockEntry {
+                    src_vpcd: *src_vpcd,
+                    dst_vpcd: *dst_vpcd,
+                    addr,
+                    block,
+                })
+            })
+    }
+
+    /// Get the usage of the ports available for dynamic source NAT, for each source VPC, for all
+    /// protocols and peerings.
+    #[must_use]
+    pub fn port_usage(&self) -> HashMap<VpcDiscriminant, NatPortUsage> {
+        let mut usage = HashMap::new();
+        self.pools_src44.add_port_usage(&mut usage);
+        self.pools_src66.add_port_usage(&mut usage);
+        usage
+    }
+
     fn allocate_from_tables<I: NatIpWithBitmap>(
         flow_key: &FlowKey,
         pools_src: &PoolTable<I, I>,
         pools_dst: &PoolTable<I, I>,
+        exclusions: &HashMap<(VpcDiscriminant, VpcDiscriminant), NatExclusions>,
+        eim: &EimTable<I>,
+        statics: &StaticNatTable<I>,
     ) -> Result<AllocationResult<AllocatedIpPort<I>>, AllocatorError> {
         let next_header = Self::get_next_header(flow_key);
         Self::check_proto(next_header)?;
         let (src_vpc_id, dst_vpc_id) = Self::check_and_get_discriminants(flow_key)?;
+        let src_ip = NatIp::try_from_addr(*flow_key.data().src_ip()).map_err(|()| {
+            AllocatorError::InternalIssue("Failed to convert IP address to Ipv4Addr".to_string())
+        })?;
+        let dst_ip = NatIp::try_from_addr(*flow_key.data().dst_ip()).map_err(|()| {
+            AllocatorError::InternalIssue("Failed to convert IP address to Ipv4Addr".to_string())
+        })?;
+
+        // Addresses matching "no-NAT" exceptions get no address pool, and remain untranslated
+        let (src_excluded, dst_excluded) = exclusions
+            .get(&(src_vpc_id, dst_vpc_id))
+            .map_or((false, false), |exclusions| {
+                Self::check_exclusions(exclusions, flow_key)
+            });
+
+        // Static NAT bindings apply before dynamic allocation: for the source if it is the internal
+        // address of a binding, for the destination if it is the public address of a binding
+        let (src_port, dst_port) = Self::get_ports(flow_key);
+        let static_src = if src_excluded {
+            None
+        } else {
+            statics.lookup_internal(src_vpc_id, dst_vpc_id, src_ip, src_port)
+        };
+        let static_dst = if dst_excluded {
+            None
+        } else {
+            statics.lookup_public(src_vpc_id, dst_vpc_id, dst_ip, dst_port)
+        };
 
         // Get address pools for source
-        let pool_src_opt = pools_src.get_entry(
-            next_header,
-            src_vpc_id,
-            dst_vpc_id,
-            NatIp::try_from_addr(*flow_key.data().src_ip()).map_err(|()| {
-                AllocatorError::InternalIssue(
-                    "Failed to convert IP address to Ipv4Addr".to_string(),
-                )
-            })?,
-        );
+        let pool_src_opt = if src_excluded {
+            None
+        } else {
+            pools_src.get_entry(next_header, src_vpc_id, dst_vpc_id, src_ip)
+        };
 
         // If we could not find an address pool for the source address, this means that the user has
         // not exposed and configured NAT for the source address currently in use. In this case, we
         // do not want to create a new session, even if destination NAT for that packet were valid:
-        // we need to drop the packet instead.
-        if pool_src_opt.is_none() {
-            return Err(AllocatorError::Denied);
+        // we need to drop the packet instead. Unless one of the addresses is exempted from NAT: the
+        // user explicitly allowed the traffic to go through untranslated. Or unless the packet is
+        // addressed to a static binding, or to an endpoint-independent mapping: they accept any
+        // remote endpoint.
+        if pool_src_opt.is_none() && static_dst.is_none() && !src_excluded && !dst_excluded {
+            return Self::allocate_from_eim_mapping(
+                flow_key, pools_dst, eim, src_vpc_id, dst_vpc_id, dst_ip,
+            )?
+            .ok_or(AllocatorError::Denied);
         }
 
         // Get address pools for destination
-        let pool_dst_opt = pools_dst.get_entry(
-            next_header,
-            src_vpc_id,
-            dst_vpc_id,
-            NatIp::try_from_addr(*flow_key.data().dst_ip()).map_err(|()| {
-                AllocatorError::InternalIssue(
-                    "Failed to convert IP address to Ipv4Addr".to_string(),
-                )
-            })?,
-        );
+        let pool_dst_opt = if dst_excluded || static_dst.is_some() {
+            None
+        } else {
+            pools_dst.get_entry(next_header, src_vpc_id, dst_vpc_id, dst_ip)
+        };
 
         // Allocate IP and ports from pools, for source and destination NAT
         let allow_null = matches!(flow_key.data().proto_key_info(), IpProtoKey::Icmp(_));
-        let (src_mapping, dst_mapping) = Self::get_mapping(pool_src_opt, pool_dst_opt, allow_null)?;
+        let pool_src_dynamic = pool_src_opt.filter(|_| static_src.is_none());
+        let eim_key = match (pool_src_dynamic, flow_key.data().proto_key_info()) {
+            (Some(pool_src), IpProtoKey::Udp(udp)) if pool_src.endpoint_independent() => Some(
+                EimKey::new(src_vpc_id, dst_vpc_id, src_ip, udp.src_port.as_u16()),
+            ),
+            _ => None,
+        };
+        let (mut src_mapping, mut dst_mapping) = Self::get_mapping(
+            pool_src_dynamic,
+            pool_dst_opt,
+            src_ip,
+            dst_ip,
+            allow_null,
+            eim,
+            eim_key,
+        )?;
+
+        // Static bindings provide their own mappings, preserving the ports (or ICMP identifiers)
+        // for 1:1 bindings
+        if let Some(binding) = static_src {
+            let port = Self::get_static_port(flow_key, src_port)?;
+            src_mapping = Some(binding.public_mapping(next_header, port)?);
+        }
+        if let Some(binding) = static_dst {
+            let port = Self::get_static_port(flow_key, dst_port)?;
+            dst_mapping = Some(binding.internal_mapping(port)?);
+        }
 
         // Now based on the previous allocation, we need to "reserve" IP and ports for the reverse
-        // path for the flow. First retrieve the relevant address pools.
+        // path for the flow. First retrieve the relevant address pools. Static bindings provide
+        // their own reverse mappings.
 
-        let reverse_pool_src_opt = if let Some(mapping) = &dst_mapping {
+        let reverse_pool_src_opt = if static_dst.is_some() {
+            None
+        } else if let Some(mapping) = &dst_mapping {
             pools_src.get_entry(next_header, dst_vpc_id, src_vpc_id, mapping.ip())
         } else {
             None
         };
 
-        let reverse_pool_dst_opt = if let Some(mapping) = &src_mapping {
+        let reverse_pool_dst_opt = if static_src.is_some() {
+            None
+        } else if let Some(mapping) = &src_mapping {
             pools_dst.get_entry(next_header, dst_vpc_id, src_vpc_id, mapping.ip())
         } else {
             None
         };
 
         // Reserve IP and ports for the reverse path for the flow.
-        let (reverse_src_mapping, reverse_dst_mapping) =
+        let (mut reverse_src_mapping, mut reverse_dst_mapping) =
             Self::get_reverse_mapping(flow_key, reverse_pool_src_opt, reverse_pool_dst_opt)?;
+        if let Some(binding) = static_dst {
+            let port = Self::get_static_port(flow_key, dst_port)?;
+            reverse_src_mapping = Some(binding.public_mapping(next_header, port)?);
+        }
+        if let Some(binding) = static_src {
+            let port = Self::get_static_port(flow_key, src_port)?;
+            reverse_dst_mapping = Some(binding.internal_mapping(port)?);
+        }
 
         Ok(AllocationResult {
             src: src_mapping,
             dst: dst_mapping,
             return_src: reverse_src_mapping,
             return_dst: reverse_dst_mapping,
-            src_flow_idle_timeout: pool_src_opt.and_then(IpAllocator::idle_timeout),
-            dst_flow_idle_timeout: pool_dst_opt.and_then(IpAllocator::idle_timeout),
+            src_flow_timeouts: pool_src_opt.and_then(IpAllocator::session_timeouts),
+            dst_flow_timeouts: static_dst
+                .and_then(StaticBinding::session_timeouts)
+                .or_else(|| pool_dst_opt.and_then(IpAllocator::session_timeouts)),
         })
     }
 
+    // The port (or ICMP query identifier) to preserve with a 1:1 static NAT binding
+    fn get_static_port(flow_key: &FlowKey, port: Option<u16>) -> Result<NatPort, AllocatorError> {
+        match (flow_key.data().proto_key_info(), port) {
+            (IpProtoKey::Icmp(icmp), _) => Ok(NatPort::Identifier(Self::get_icmp_query_id(icmp)?)),
+            (_, Some(port)) => {
+                NatPort::new_port_checked(port).map_err(AllocatorError::PortAllocationFailed)
+            }
+            (_, None) => Err(AllocatorError::InternalIssue(
+                "Missing port for static NAT".to_string(),
+            )),
+        }
+    }
+
+    // Endpoint-independent filtering: if the destination of a UDP flow is the public address and
+    // port of an endpoint-independent mapping in use, translate the destination back to the
+    // internal address and port, whatever the source. Return None for other flows.
+    fn allocate_from_eim_mapping<I: NatIpWithBitmap>(
+        flow_key: &FlowKey,
+        pools_dst: &PoolTable<I, I>,
+        eim: &EimTable<I>,
+        src_vpc_id: VpcDiscriminant,
+        dst_vpc_id: VpcDiscriminant,
+        dst_ip: I,
+    ) -> Result<Option<AllocationResult<AllocatedIpPort<I>>>, AllocatorError> {
+        let IpProtoKey::Udp(udp) = flow_key.data().proto_key_info() else {
+            return Ok(None);
+        };
+        // The mapping was created for traffic from the destination VPC to the source VPC
+        let key = EimKey::new(dst_vpc_id, src_vpc_id, dst_ip, udp.dst_port.as_u16());
+        let Some((internal_ip, internal_port, mapping)) = eim.lookup_public(&key) else {
+            return Ok(None);
+        };
+        let Some(pool_dst) = pools_dst.get_entry(NextHeader::UDP, src_vpc_id, dst_vpc_id, dst_ip)
+        else {
+            return Ok(None);
+        };
+
+        let port = NatPort::new_port_checked(internal_port)
+            .map_err(AllocatorError::PortAllocationFailed)?;
+        let dst_mapping = pool_dst.reserve(internal_ip, port)?;
+
+        Ok(Some(AllocationResult {
+            src: None,
+            dst: Some(dst_mapping),
+            // Replies go out with the public address and port of the mapping
+            return_src: Some(mapping),
+            return_dst: None,
+            src_flow_timeouts: None,
+            dst_flow_timeouts: pool_dst.session_timeouts(),
+        }))
+    }
+
+    // Tell whether the source and the destination, respectively, of the flow match "no-NAT"
+    // exceptions
+    fn check_exclusions(exclusions: &NatExclusions, flow_key: &FlowKey) -> (bool, bool) {
+        let (src_port, dst_port) = Self::get_ports(flow_key);
+        exclusions.excludes(
+            (*flow_key.data().src_ip(), src_port),
+            (*flow_key.data().dst_ip(), dst_port),
+        )
+    }
+
+    fn get_ports(flow_key: &FlowKey) -> (Option<u16>, Option<u16>) {
+        match flow_key.data().proto_key_info() {
+            IpProtoKey::Tcp(tcp) => (Some(tcp.src_port.as_u16()), Some(tcp.dst_port.as_u16())),
+            IpProtoKey::Udp(udp) => (Some(udp.src_port.as_u16()), Some(udp.dst_port.as_u16())),
+            IpProtoKey::Icmp(_) => (None, None),
+        }
+    }
+
     fn check_proto(next_header: NextHeader) -> Result<(), AllocatorError> {
         match next_header {
             NextHeader::TCP | NextHeader::UDP | NextHeader::ICMP | NextHeader::ICMP6 => Ok(()),
@@ -335,7 +573,6 @@ impl NatDefaultAllocator {
             .ok_or(AllocatorError::MissingDiscriminant)?;
 
         // We only support VNIs at the moment
-        #[allow(unreachable_patterns)]
         match (src_vpc_id, dst_vpc_id) {
             (VpcDiscriminant::VNI(_), VpcDiscriminant::VNI(_)) => Ok((src_vpc_id, dst_vpc_id)),
             _ => Err(AllocatorError::UnsupportedDiscriminant),
@@ -345,7 +582,12 @@ impl NatDefaultAllocator {
     fn get_mapping<I: NatIpWithBitmap>(
         pool_src_opt: Option<&alloc::IpAllocator<I>>,
         pool_dst_opt: Option<&alloc::IpAllocator<I>>,
+        src_ip: I,
+        dst_ip: I,
         allow_null: bool,
+        eim: &EimTable<I>,
+        // Set for UDP source NAT with endpoint-independent mapping
+        eim_key: Option<EimKey<I>>,
     ) -> Result<AllocationMapping<I>, AllocatorError> {
         // Allocate IP and ports for source and destination NAT.
         //
@@ -358,13 +600,16 @@ impl NatDefaultAllocator {
         // "port" with the current architecture of the allocator, which means we also allocate a
         // port/identifier value for the src_mapping, even though we'll never use it. (This does not
         // apply to TCP or UDP, for which we need and use both ports).
-        let src_mapping = match pool_src_opt {
-            Some(pool_src) => Some(pool_src.allocate(allow_null)?),
-            None => None,
+        let src_mapping = match (pool_src_opt, eim_key) {
+            (Some(pool_src), Some(key)) => {
+                Some(eim.get_or_allocate(key, || pool_src.allocate(src_ip, allow_null))?)
+            }
+            (Some(pool_src), None) => Some(pool_src.allocate(src_ip, allow_null)?),
+            (None, _) => None,
         };
 
         let dst_mapping = match pool_dst_opt {
-            Some(pool_dst) => Some(pool_dst.allocate(allow_null)?),
+            Some(pool_dst) => Some(pool_dst.allocate(dst_ip, allow_null)?),
             None => None,
         };
 
@@ -16,6 +16,7 @@ use concurrency::concurrency_mode;
 use concurrency::sync::atomic::{AtomicBool, AtomicU16, AtomicUsize};
 use concurrency::sync::{Arc, Mutex, RwLock, Weak};
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::thread::ThreadId;
 
 #[concurrency_mode(std)]
@@ -32,17 +33,24 @@ use shuttle::rand::{Rng, thread_rng};
 /// This index is used to represent the position, initially picked at random, of the block in the
 /// list of all blocks. This is used to (somewhat) randomise the order of port allocation for a
 /// given IP address.
+///
+/// Blocks that do not intersect with the configured port range, if any, are not `allocatable`: we
+/// never pick them for new allocations, although we can still reserve ports from them.
 #[derive(Debug)]
 struct AllocatorPortBlock {
     random_index: u8,
+    allocatable: bool,
     // Candidate for CachePadded
     free: AtomicBool,
 }
 
 impl AllocatorPortBlock {
-    fn new(index: u8) -> Self {
+    fn new(index: u8, port_range: Option<&RangeInclusive<u16>>) -> Self {
+        let base_port = u16::from(index) * 256;
         Self {
             random_index: index,
+            allocatable: port_range
+                .is_none_or(|range| base_port <= *range.end() && base_port + 255 >= *range.start()),
             free: AtomicBool::new(true),
         }
     }
@@ -65,9 +73,14 @@ impl AllocatorPortBlock {
 /// [`PortAllocator`] is a port allocator for a given IP address. In fact, it does not allocate
 /// ports itself, but handles block of ports ([`AllocatedPortBlock`]s) from which the final ports
 /// are effectively allocated.
+///
+/// If a port range is configured, new ports are only allocated within this range. Blocks are then
+/// picked in ascending order rather than at random, so that allocation is deterministic, and each
+/// thread allocates from its own block, which splits the range across workers.
 #[derive(Debug)]
 pub(crate) struct PortAllocator<I: NatIpWithBitmap> {
     blocks: [AllocatorPortBlock; 256],
+    port_range: Option<RangeInclusive<u16>>,
     // TODO: Candidates for CachePadded? Not sure, given that both atomics should be updated at the same time?
     usable_blocks: AtomicU16,
     current_alloc_index: AtomicUsize,
@@ -76,20 +89,33 @@ pub(crate) struct PortAllocator<I: NatIpWithBitmap> {
 }
 
 impl<I: NatIpWithBitmap> PortAllocator<I> {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(port_range: Option<RangeInclusive<u16>>) -> Self {
         let mut base_ports = (0..=255).collect::<Vec<_>>();
 
         // Shuffle the list of port blocks for the port allocator. This way, we can pick blocks in a
         // "random" order when allocating them, and have ports allocated in a "random" order. The
         // quotes denote that this is not completely random: ports are allocated sequentially within
         // a 256-port block.
-        Self::shuffle_slice(&mut base_ports);
-        let blocks = std::array::from_fn(|i| AllocatorPortBlock::new(base_ports[i]));
+        //
+        // When a port range is configured, keep the blocks sorted instead.
+        if port_range.is_none() {
+            Self::shuffle_slice(&mut base_ports);
+        }
+        let blocks: [AllocatorPortBlock; 256] =
+            std::array::from_fn(|i| AllocatorPortBlock::new(base_ports[i], port_range.as_ref()));
+
+        #[allow(clippy::cast_possible_truncation)] // max value is 256
+        let usable_blocks = blocks.iter().filter(|block| block.allocatable).count() as u16;
+        let first_usable_block = blocks
+            .iter()
+            .position(|block| block.allocatable)
+            .unwrap_or(0);
 
         Self {
             blocks,
-            usable_blocks: AtomicU16::new(256),
-            current_alloc_index: AtomicUsize::new(0),
+            port_range,
+            usable_blocks: AtomicU16::new(usable_blocks),
+            current_alloc_index: AtomicUsize::new(first_usable_block),
             thread_blocks: ThreadPortMap::new(),
             allocated_blocks: AllocatedPortBlockMap::new(),
         }
@@ -133,6 +159,11 @@ impl<I: NatIpWithBitmap> PortAllocator<I> {
             || self.has_allocated_blocks_with_free_ports()
     }
 
+    /// Get the number of ports currently allocated or reserved for the IP address
+    pub(crate) fn ports_in_use(&self) -> u64 {
+        self.allocated_blocks.ports_in_use()
+    }
+
     pub(crate) fn deallocate_block(&self, index: usize) {
         // Do not remove from self.allocated_blocks, as that is managed by the allocator when
         // finding a weak reference that won't upgrade. Removing here would require an additional
@@ -143,8 +174,10 @@ impl<I: NatIpWithBitmap> PortAllocator<I> {
         self.blocks[index]
             .free
             .store(true, concurrency::sync::atomic::Ordering::Relaxed);
-        self.usable_blocks
-            .fetch_add(1, concurrency::sync::atomic::Ordering::Relaxed);
+        if self.blocks[index].allocatable {
+            self.usable_blocks
+                .fetch_add(1, concurrency::sync::atomic::Ordering::Relaxed);
+        }
     }
 
     fn has_allocated_blocks_with_free_ports(&self) -> bool {
@@ -157,16 +190,17 @@ impl<I: NatIpWithBitmap> PortAllocator<I> {
         let (index, block) = self
             .cycle_blocks()
             .find(|(_, block)| {
-                // Find the first block for which the atomic compare_exchange succeeds
-                block
-                    .free
-                    .compare_exchange(
-                        true,
-                        false,
-                        concurrency::sync::atomic::Ordering::Relaxed,
-                        concurrency::sync::atomic::Ordering::Relaxed,
-                    )
-                    .is_ok()
+                // Find the first allocatable block for which the atomic compare_exchange succeeds
+                block.allocatable
+                    && block
+                        .free
+                        .compare_exchange(
+                            true,
+                            false,
+                            concurrency::sync::atomic::Ordering::Relaxed,
+                            concurrency::sync::atomic::Ordering::Relaxed,
+                        )
+                        .is_ok()
             })
             .ok_or(AllocatorError::NoPortBlock)?;
         Ok((index, block.to_port_number()))
@@ -190,7 +224,13 @@ impl<I: NatIpWithBitmap> PortAllocator<I> {
         self.usable_blocks
             .fetch_sub(1, concurrency::sync::atomic::Ordering::Relaxed);
 
-        AllocatedPortBlock::new(ip, index, base_port_index, allow_null)
+        AllocatedPortBlock::new(
+            ip,
+            index,
+            base_port_index,
+            allow_null,
+            self.port_range.as_ref(),
+        )
     }
 
     pub(crate) fn allocate_port(
@@ -247,13 +287,16 @@ impl<I: NatIpWithBitmap> PortAllocator<I> {
         port: NatPort,
         allow_null: bool,
     ) -> Result<Arc<AllocatedPortBlock<I>>, AllocatorError> {
-        self.usable_blocks
-            .fetch_sub(1, concurrency::sync::atomic::Ordering::Relaxed);
+        if self.blocks[index].allocatable {
+            self.usable_blocks
+                .fetch_sub(1, concurrency::sync::atomic::Ordering::Relaxed);
+        }
         let block = Arc::new(AllocatedPortBlock::new(
             ip,
             index,
             (port.as_u16() / 256) * 256, // port block base index, discard offset within block
             allow_null,
+            self.port_range.as_ref(),
         )?);
         self.allocated_blocks
             .insert(block.index, Arc::downgrade(&block));
@@ -290,6 +333,33 @@ impl<I: NatIpWithBitmap> PortAllocator<I> {
         let block = self.find_block_for_port(ip, port)?;
         block.reserve_port_from_block(port)
     }
+
+    /// Allocate a port within `ports`, for deterministic NAT. `ports` must not contain port 0.
+    pub(crate) fn allocate_port_in_range(
+        &self,
+        ip: Arc<AllocatedIp<I>>,
+        ports: &RangeInclusive<u16>,
+        allow_null: bool,
+    ) -> Result<AllocatedPort<I>, AllocatorError> {
+        // Try the 256-port blocks covering the range, in order
+        for block_index in ports.start() / 256..=ports.end() / 256 {
+            let first_port = (block_index * 256).max(*ports.start());
+            let port = if allow_null {
+                NatPort::Identifier(first_port)
+            } else {
+                NatPort::new_port_checked(first_port)
+                    .map_err(AllocatorError::PortAllocationFailed)?
+            };
+            let block = self.find_block_for_port(ip.clone(), port)?;
+            let excluded = Bitmap256::outside_range(block.base_port_idx, ports);
+            match block.allocate_port_from_block_excluding(&excluded, allow_null) {
+                Ok(port) => return Ok(port),
+                Err(AllocatorError::NoFreePort(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Err(AllocatorError::NoFreePort(*ports.start()))
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -311,6 +381,8 @@ pub(crate) struct AllocatedPortBlock<I: NatIpWithBitmap> {
     base_port_idx: u16,
     index: usize,
     usage_bitmap: Mutex<Bitmap256>,
+    // Ports outside of the configured port range: never allocated, but they can be reserved
+    excluded_bitmap: Bitmap256,
 }
 
 impl<I: NatIpWithBitmap> AllocatedPortBlock<I> {
@@ -319,12 +391,16 @@ impl<I: NatIpWithBitmap> AllocatedPortBlock<I> {
         index: usize,
         base_port_idx: u16,
         allow_null: bool,
+        port_range: Option<&RangeInclusive<u16>>,
     ) -> Result<Self, AllocatorError> {
         let block = Self {
             ip,
             base_port_idx,
             index,
             usage_bitmap: Mutex::new(Bitmap256::new()),
+            excluded_bitmap: port_range.map_or_else(Bitmap256::new, |range| {
+                Bitmap256::outside_range(base_port_idx, range)
+            }),
         };
         // Port 0 may be reserved, in which case we don't want to use it, so we mark it as not free.
         if !allow_null && block.base_port_idx == 0 {
@@ -347,7 +423,10 @@ impl<I: NatIpWithBitmap> AllocatedPortBlock<I> {
     }
 
     fn is_full(&self) -> bool {
-        self.usage_bitmap.lock().unwrap().bitmap_full()
+        self.usage_bitmap
+            .lock()
+            .unwrap()
+            .bitmap_full(&self.excluded_bitmap)
     }
 
     fn covers(&self, port: NatPort) -> bool {
@@ -356,6 +435,10 @@ impl<I: NatIpWithBitmap> AllocatedPortBlock<I> {
             .is_some_and(|delta| delta < 256)
     }
 
+    fn ports_in_use(&self) -> u64 {
+        self.usage_bitmap.lock().unwrap().count()
+    }
+
     fn deallocate_port_from_block(&self, port: NatPort) -> Result<(), AllocatorError> {
         self.usage_bitmap
             .lock()
@@ -378,12 +461,22 @@ impl<I: NatIpWithBitmap> AllocatedPortBlock<I> {
     fn allocate_port_from_block(
         self: Arc<Self>,
         allow_null: bool,
+    ) -> Result<AllocatedPort<I>, AllocatorError> {
+        let excluded = self.excluded_bitmap.clone();
+        self.allocate_port_from_block_excluding(&excluded, allow_null)
+    }
+
+    // Allocate a port from the block, skipping the ports in the `excluded` bitmap
+    fn allocate_port_from_block_excluding(
+        self: Arc<Self>,
+        excluded: &Bitmap256,
+        allow_null: bool,
     ) -> Result<AllocatedPort<I>, AllocatorError> {
         let bitmap_offset = self
             .usage_bitmap
             .lock()
             .unwrap()
-            .allocate_port_from_bitmap()
+            .allocate_port_from_bitmap(excluded)
             .map_err(|()| AllocatorError::NoFreePort(self.base_port_idx))?;
 
         if allow_null {
@@ -441,35 +534,58 @@ impl<I: NatIpWithBitmap> Drop for AllocatedPortBlock<I> {
 ///
 /// It contains a back reference to its parent [`AllocatedPortBlock`], to deallocate the port when
 /// the [`AllocatedPort`] is dropped.
+///
+/// Clones of an [`AllocatedPort`] share the allocation, and the port is deallocated when the last
+/// clone is dropped. This is how sessions share a mapping, for endpoint-independent mapping.
+#[derive(Debug, Clone)]
+pub struct AllocatedPort<I: NatIpWithBitmap>(Arc<PortAllocation<I>>);
+
 #[derive(Debug)]
-pub struct AllocatedPort<I: NatIpWithBitmap> {
+struct PortAllocation<I: NatIpWithBitmap> {
     port: NatPort,
     block_allocator: Arc<AllocatedPortBlock<I>>,
 }
 
 impl<I: NatIpWithBitmap> AllocatedPort<I> {
     fn new(port: NatPort, block_allocator: Arc<AllocatedPortBlock<I>>) -> Self {
-        Self {
+        Self(Arc::new(PortAllocation {
             port,
             block_allocator,
-        }
+        }))
     }
 
     pub fn port(&self) -> NatPort {
-        self.port
+        self.0.port
     }
 
     pub fn ip(&self) -> I {
-        self.block_allocator.ip()
+        self.0.block_allocator.ip()
+    }
+
+    /// Get a weak reference to the allocation, which does not prevent the port from being
+    /// deallocated
+    pub(crate) fn downgrade(&self) -> WeakAllocatedPort<I> {
+        WeakAllocatedPort(Arc::downgrade(&self.0))
     }
 }
 
-impl<I: NatIpWithBitmap> Drop for AllocatedPort<I> {
+impl<I: NatIpWithBitmap> Drop for PortAllocation<I> {
     fn drop(&mut self) {
         let _ = self.block_allocator.deallocate_port_from_block(self.port);
     }
 }
 
+/// A weak reference to an [`AllocatedPort`]
+#[derive(Debug, Clone)]
+pub(crate) struct WeakAllocatedPort<I: NatIpWithBitmap>(Weak<PortAllocation<I>>);
+
+impl<I: NatIpWithBitmap> WeakAllocatedPort<I> {
+    /// Get the [`AllocatedPort`], if the port is still allocated
+    pub(crate) fn upgrade(&self) -> Option<AllocatedPort<I>> {
+        self.0.upgrade().map(AllocatedPort)
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 // ThreadPortMap
 ///////////////////////////////////////////////////////////////////////////////
@@ -554,6 +670,16 @@ impl<I: NatIpWithBitmap> AllocatedPortBlockMap<I> {
             .any(|block| block.upgrade().is_some_and(|block| !block.is_full()))
     }
 
+    fn ports_in_use(&self) -> u64 {
+        self.0
+            .read()
+            .unwrap()
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|block| block.ports_in_use())
+            .sum()
+    }
+
     fn search_for_block(&self, port: NatPort) -> Option<Arc<AllocatedPortBlock<I>>> {
         let blocks = self.0.read().unwrap();
         blocks
@@ -583,8 +709,31 @@ impl Bitmap256 {
         }
     }
 
-    fn bitmap_full(&self) -> bool {
-        self.first_half == u128::MAX && self.second_half == u128::MAX
+    // Build a bitmap with the bits set for the ports of the block starting at base_port that fall
+    // outside of the given range
+    fn outside_range(base_port: u16, range: &RangeInclusive<u16>) -> Self {
+        let mut bitmap = Self::new();
+        for offset in 0..=255u8 {
+            if !range.contains(&(base_port + u16::from(offset))) {
+                if offset < 128 {
+                    bitmap.first_half |= 1 << offset;
+                } else {
+                    bitmap.second_half |= 1 << (offset - 128);
+                }
+            }
+        }
+        bitmap
+    }
+
+    // Count the ports in use
+    fn count(&self) -> u64 {
+        u64::from(self.first_half.count_ones() + self.second_half.count_ones())
+    }
+
+    // Tell whether all ports are in use, or excluded as per the given bitmap
+    fn bitmap_full(&self, excluded: &Bitmap256) -> bool {
+        self.first_half | excluded.first_half == u128::MAX
+            && self.second_half | excluded.second_half == u128::MAX
     }
 
     // The bitmap is made of two u128, the first one for port values (0)-127, the second one for
@@ -603,16 +752,18 @@ impl Bitmap256 {
     //
     // In the last example above, we have three trailing ones in the first half, telling us that
     // port at 1 << 3 (port number 3) is free.
-    fn allocate_port_from_bitmap(&mut self) -> Result<u16, ()> {
+    //
+    // Ports marked in the excluded bitmap are skipped, as if they were in use.
+    fn allocate_port_from_bitmap(&mut self, excluded: &Bitmap256) -> Result<u16, ()> {
         #[allow(clippy::cast_possible_truncation)] // max value is 128
-        let ones = self.first_half.trailing_ones() as u16;
+        let ones = (self.first_half | excluded.first_half).trailing_ones() as u16;
         if ones < 128 {
             self.first_half |= 1 << ones;
             return Ok(ones);
         }
 
         #[allow(clippy::cast_possible_truncation)] // max value is 128
-        let ones = self.second_half.trailing_ones() as u16;
+        let ones = (self.second_half | excluded.second_half).trailing_ones() as u16;
         if ones < 128 {
             self.second_half |= 1 << ones;
             return Ok(ones + 128);
@@ -623,17 +774,16 @@ impl Bitmap256 {
     }
 
     fn set_bitmap_value(&mut self, port_in_block: u8, value: u128) -> Result<(), ()> {
-        if port_in_block < 128 {
-            if self.first_half & (1 << port_in_block) == value {
-                return Err(());
-            }
-            self.first_half |= value << port_in_block;
+        let (half, offset) = if port_in_block < 128 {
+            (&mut self.first_half, port_in_block)
         } else {
-            if self.second_half & (1 << (port_in_block - 128)) == value {
-                return Err(());
-            }
-            self.second_half |= value << (port_in_block - 128);
+            (&mut self.second_half, port_in_block - 128)
+        };
+        if (*half >> offset) & 1 == value {
+            return Err(());
         }
+        // Clear the bit before setting it to the new value, so that deallocation frees the port
+        *half = (*half & !(1 << offset)) | (value << offset);
         Ok(())
     }
 
@@ -645,3 +795,33 @@ impl Bitmap256 {
         self.set_bitmap_value(port_in_block, 1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Bitmap256;
+
+    #[test]
+    fn test_bitmap_deallocate_reserve() {
+        let none = Bitmap256::new();
+        let mut bitmap = Bitmap256::new();
+        for port in 0..=255u16 {
+            assert_eq!(bitmap.allocate_port_from_bitmap(&none), Ok(port));
+        }
+        assert!(bitmap.bitmap_full(&none));
+
+        // Deallocated ports, in either half, get allocated again
+        for port in [3, 200] {
+            assert_eq!(bitmap.deallocate_port_from_bitmap(port), Ok(()));
+            assert_eq!(bitmap.deallocate_port_from_bitmap(port), Err(()));
+            assert_eq!(bitmap.count(), 255);
+            assert_eq!(bitmap.allocate_port_from_bitmap(&none), Ok(u16::from(port)));
+        }
+
+        // Ports in use cannot be reserved, free ones can
+        assert_eq!(bitmap.reserve_port_from_bitmap(5), Err(()));
+        assert_eq!(bitmap.reserve_port_from_bitmap(130), Err(()));
+        assert_eq!(bitmap.deallocate_port_from_bitmap(130), Ok(()));
+        assert_eq!(bitmap.reserve_port_from_bitmap(130), Ok(()));
+        assert!(bitmap.bitmap_full(&none));
+    }
+}
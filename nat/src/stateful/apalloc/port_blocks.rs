@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Port blocks for deterministic NAT.
+//!
+//! With deterministic NAT, each address to translate gets a fixed block of ports on a fixed public
+//! address, instead of ports allocated dynamically from the whole pool. Addresses to translate and
+//! public addresses are numbered in the order of their prefixes, and address number `n` gets block
+//! number `n % blocks_per_ip` on public address number `n / blocks_per_ip`, where blocks are
+//! consecutive slices of the port range.
+//!
+//! The mapping only depends on the configuration: the translation for any session can be found
+//! from the mapping, without logging each session.
+
+use crate::stateful::allocator::AllocatorError;
+use lpm::prefix::Prefix;
+use net::packet::VpcDiscriminant;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ops::RangeInclusive;
+
+/// A block of ports on a public address, assigned to an address to translate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortBlock {
+    pub ip: IpAddr,
+    pub ports: RangeInclusive<u16>,
+}
+
+impl Display for PortBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ports {}-{}",
+            self.ip,
+            self.ports.start(),
+            self.ports.end()
+        )
+    }
+}
+
+/// An entry of the mapping for deterministic NAT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortBlockEntry {
+    pub src_vpcd: VpcDiscriminant,
+    pub dst_vpcd: VpcDiscriminant,
+    /// The address to translate
+    pub addr: IpAddr,
+    pub block: PortBlock,
+}
+
+impl Display for PortBlockEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> {}: {} -> {}",
+            self.src_vpcd, self.dst_vpcd, self.addr, self.block
+        )
+    }
+}
+
+fn addr_bits(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(addr) => u128::from(addr.to_bits()),
+        IpAddr::V6(addr) => addr.to_bits(),
+    }
+}
+
+// Number of addresses in the prefix, saturated for ::/0
+fn prefix_size(prefix: &Prefix) -> u128 {
+    u128::try_from(prefix.size()).unwrap_or(u128::MAX)
+}
+
+fn addr_count(prefixes: &[Prefix]) -> u128 {
+    prefixes
+        .iter()
+        .map(prefix_size)
+        .fold(0, u128::saturating_add)
+}
+
+// Get the index of `addr` among the addresses covered by `prefixes`
+fn index_of(prefixes: &[Prefix], addr: IpAddr) -> Option<u128> {
+    let mut base = 0_u128;
+    for prefix in prefixes {
+        if prefix.covers_addr(&addr) {
+            return base.checked_add(addr_bits(addr) - addr_bits(prefix.as_address()));
+        }
+        base = base.checked_add(prefix_size(prefix))?;
+    }
+    None
+}
+
+// Get the address at `index` among the addresses covered by `prefixes`
+fn addr_at(prefixes: &[Prefix], mut index: u128) -> Option<IpAddr> {
+    for prefix in prefixes {
+        let size = prefix_size(prefix);
+        if index < size {
+            let bits = addr_bits(prefix.as_address()) + index;
+            return match prefix {
+                Prefix::IPV4(_) => u32::try_from(bits)
+                    .ok()
+                    .map(|bits| IpAddr::V4(Ipv4Addr::from_bits(bits))),
+                Prefix::IPV6(_) => Some(IpAddr::V6(Ipv6Addr::from_bits(bits))),
+            };
+        }
+        index -= size;
+    }
+    None
+}
+
+/// [`PortBlockMap`] assigns port blocks to the addresses to translate for a given
+/// [`VpcExpose`](config::external::overlay::vpcpeering::VpcExpose), for deterministic NAT.
+#[derive(Debug)]
+pub(crate) struct PortBlockMap {
+    // Prefixes of the addresses to translate, in order
+    internal: Vec<Prefix>,
+    // Prefixes of the public addresses, in order
+    public: Vec<Prefix>,
+    port_range: RangeInclusive<u16>,
+    block_size: u16,
+}
+
+impl PortBlockMap {
+    /// Build the mapping from the addresses in `internal` to blocks of `block_size` ports from
+    /// `port_range` (all ports but port 0 if `None`) on the addresses in `public`.
+    ///
+    /// # Errors
+    ///
+    /// [`AllocatorError::InternalIssue`] if there are not enough public addresses and ports to
+    /// assign a block to each address to translate. Configuration validation should prevent this.
+    pub(crate) fn new(
+        internal: &BTreeSet<Prefix>,
+        public: &BTreeSet<Prefix>,
+        port_range: Option<RangeInclusive<u16>>,
+        block_size: u16,
+    ) -> Result<Self, AllocatorError> {
+        let map = Self {
+            internal: internal.iter().copied().collect(),
+            public: public.iter().copied().collect(),
+            port_range: port_range.unwrap_or(1..=u16::MAX),
+            block_size,
+        };
+        let blocks_per_ip = map.blocks_per_ip();
+        if blocks_per_ip == 0
+            || addr_count(&map.internal) > addr_count(&map.public).saturating_mul(blocks_per_ip)
+        {
+            return Err(AllocatorError::InternalIssue(format!(
+                "Not enough public addresses and ports for NAT port blocks of size {block_size}"
+            )));
+        }
+        Ok(map)
+    }
+
+    fn blocks_per_ip(&self) -> u128 {
+        let ports = (u128::from(*self.port_range.end()) + 1)
+            .saturating_sub(u128::from(*self.port_range.start()));
+        ports.checked_div(u128::from(self.block_size)).unwrap_or(0)
+    }
+
+    fn block(&self, index: u128) -> Option<PortBlock> {
+        let blocks_per_ip = self.blocks_per_ip();
+        let ip = addr_at(&self.public, index.checked_div(blocks_per_ip)?)?;
+        let block_index = u16::try_from(index % blocks_per_ip).ok()?;
+        let start = self.port_range.start() + block_index * self.block_size;
+        Some(PortBlock {
+            ip,
+            ports: start..=start + (self.block_size - 1),
+        })
+    }
+
+    /// Get the port block for `addr`, if `addr` is one of the addresses to translate
+    pub(crate) fn lookup(&self, addr: IpAddr) -> Option<PortBlock> {
+        self.block(index_of(&self.internal, addr)?)
+    }
+
+    /// Iterate over the addresses to translate, in order, with their port blocks
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (IpAddr, PortBlock)> + '_ {
+        (0..addr_count(&self.internal))
+            .map_while(|index| Some((addr_at(&self.internal, index)?, self.block(index)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn addr(ip: &str) -> IpAddr {
+        IpAddr::from_str(ip).unwrap()
+    }
+
+    #[test]
+    fn test_port_block_map() {
+        let internal = BTreeSet::from(["1.1.0.0/30".into(), "1.2.0.0/31".into()]);
+        let public = BTreeSet::from(["10.0.0.0/31".into()]);
+
+        // Not enough room: 6 addresses to translate, 2 public addresses with 2 blocks each
+        assert!(PortBlockMap::new(&internal, &public, Some(1024..=1535), 256).is_err());
+        assert!(PortBlockMap::new(&internal, &public, Some(1024..=1535), 0).is_err());
+
+        // 3 blocks per public address, the last 12 ports of the range are never used
+        let map = PortBlockMap::new(&internal, &public, Some(1024..=1335), 100).unwrap();
+        let entries = map.iter().collect::<Vec<_>>();
+        let expected = [
+            ("1.1.0.0", "10.0.0.0", 1024..=1123),
+            ("1.1.0.1", "10.0.0.0", 1124..=1223),
+            ("1.1.0.2", "10.0.0.0", 1224..=1323),
+            ("1.1.0.3", "10.0.0.1", 1024..=1123),
+            ("1.2.0.0", "10.0.0.1", 1124..=1223),
+            ("1.2.0.1", "10.0.0.1", 1224..=1323),
+        ];
+        assert_eq!(entries.len(), expected.len());
+        for ((internal, block), (expected_internal, expected_ip, expected_ports)) in
+            entries.iter().zip(expected)
+        {
+            assert_eq!(*internal, addr(expected_internal));
+            assert_eq!(block.ip, addr(expected_ip));
+            assert_eq!(block.ports, expected_ports);
+            assert_eq!(map.lookup(*internal).as_ref(), Some(block));
+        }
+        assert_eq!(map.lookup(addr("1.1.0.4")), None);
+
+        // All ports but port 0 without a port range
+        let map = PortBlockMap::new(&internal, &public, None, 21845).unwrap();
+        assert_eq!(
+            map.lookup(addr("1.2.0.1")),
+            Some(PortBlock {
+                ip: addr("10.0.0.1"),
+                ports: 43691..=65535,
+            })
+        );
+    }
+}
@@ -3,19 +3,25 @@
 
 use super::NatIpWithBitmap;
 use super::alloc::{IpAllocator, NatPool, PoolBitmap};
-use super::{NatDefaultAllocator, PoolTable, PoolTableKey};
+use super::port_blocks::PortBlockMap;
+use super::static_nat::{StaticBinding, StaticNatTable};
+use super::{NatDefaultAllocator, PeeringPortBlocks, PoolTable, PoolTableKey};
 use crate::stateful::allocator::AllocatorError;
 use crate::stateful::allocator_writer::StatefulNatConfig;
+use crate::stateful::timeouts::SessionTimeouts;
 use crate::stateful::{NatAllocator, NatIp};
+use crate::{NatExclusionTable, NatExclusions};
+use concurrency::sync::Arc;
 use config::ConfigError;
 use config::external::overlay::vpc::Peering;
-use config::external::overlay::vpcpeering::{VpcExpose, VpcManifest};
+use config::external::overlay::vpcpeering::{VpcExpose, VpcExposeStaticNat, VpcManifest};
 use config::utils::collapse_prefixes_peering;
 use lpm::prefix::{IpPrefix, Prefix};
 use net::ip::NextHeader;
 use net::packet::VpcDiscriminant;
 use std::collections::{BTreeMap, BTreeSet};
-use std::time::Duration;
+use std::net::IpAddr;
+use std::ops::RangeInclusive;
 
 impl NatDefaultAllocator {
     /// Build a [`NatDefaultAllocator`] from information collected from a [`VpcTable`] object. This
@@ -58,6 +64,31 @@ impl NatDefaultAllocator {
         // Update table for destination NAT
         self.build_dst_nat_pool_for_expose(&new_peering, src_vpc_id, dst_vpc_id)?;
 
+        // Record static NAT bindings, once the pools for source NAT are built
+        self.add_static_bindings(&new_peering, src_vpc_id, dst_vpc_id)?;
+
+        // Record "no-NAT" exceptions: from local exposes for the source, from remote exposes for
+        // the destination
+        let exclusions = NatExclusions {
+            src: NatExclusionTable::from_exposes(
+                new_peering
+                    .local
+                    .exposes
+                    .iter()
+                    .filter(|e| e.has_stateful_nat()),
+            ),
+            dst: NatExclusionTable::from_exposes(
+                new_peering
+                    .remote
+                    .exposes
+                    .iter()
+                    .filter(|e| e.has_stateful_nat()),
+            ),
+        };
+        if !exclusions.is_empty() {
+            self.exclusions.insert((src_vpc_id, dst_vpc_id), exclusions);
+        }
+
         Ok(())
     }
 
@@ -76,6 +107,7 @@ impl NatDefaultAllocator {
             |expose| &expose.ips,
             &mut self.pools_src44,
             NextHeader::ICMP,
+            Some(&mut self.port_blocks),
         )?;
 
         build_nat_pool_generic(
@@ -87,9 +119,54 @@ impl NatDefaultAllocator {
             |expose| &expose.ips,
             &mut self.pools_src66,
             NextHeader::ICMP6,
+            Some(&mut self.port_blocks),
         )
     }
 
+    fn add_static_bindings(
+        &mut self,
+        peering: &Peering,
+        src_vpc_id: VpcDiscriminant,
+        dst_vpc_id: VpcDiscriminant,
+    ) -> Result<(), AllocatorError> {
+        for expose in peering
+            .local
+            .exposes
+            .iter()
+            .filter(|e| e.has_stateful_nat())
+        {
+            let session_timeouts = session_timeouts_for_expose(expose);
+            for binding in expose.nat_static_bindings() {
+                match (binding.internal, binding.public) {
+                    (IpAddr::V4(internal), IpAddr::V4(public)) => add_static_binding(
+                        &mut self.static44,
+                        &self.pools_src44,
+                        NextHeader::ICMP,
+                        (src_vpc_id, dst_vpc_id),
+                        binding,
+                        (internal, public),
+                        session_timeouts,
+                    )?,
+                    (IpAddr::V6(internal), IpAddr::V6(public)) => add_static_binding(
+                        &mut self.static66,
+                        &self.pools_src66,
+                        NextHeader::ICMP6,
+                        (src_vpc_id, dst_vpc_id),
+                        binding,
+                        (internal, public),
+                        session_timeouts,
+                    )?,
+                    _ => {
+                        return Err(AllocatorError::InternalIssue(format!(
+                            "IP version mismatch for static NAT binding {binding}"
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn build_dst_nat_pool_for_expose(
         &mut self,
         peering: &Peering,
@@ -105,6 +182,7 @@ impl NatDefaultAllocator {
             VpcExpose::as_range_or_empty,
             &mut self.pools_dst44,
             NextHeader::ICMP,
+            None,
         )?;
 
         build_nat_pool_generic(
@@ -116,6 +194,7 @@ impl NatDefaultAllocator {
             VpcExpose::as_range_or_empty,
             &mut self.pools_dst66,
             NextHeader::ICMP6,
+            None,
         )
     }
 }
@@ -133,6 +212,8 @@ fn build_nat_pool_generic<'a, I: NatIpWithBitmap, J: NatIpWithBitmap, F, Iter, G
     target_prefixes_from_expose: H,
     table: &mut PoolTable<I, J>,
     icmp_proto: NextHeader,
+    // Where to record port blocks for deterministic NAT, for source NAT only
+    mut port_blocks: Option<&mut Vec<PeeringPortBlocks>>,
 ) -> Result<(), AllocatorError>
 where
     F: FnOnce(&'a VpcManifest) -> Iter,
@@ -141,12 +222,30 @@ where
     H: Fn(&'a VpcExpose) -> &'a BTreeSet<Prefix>,
 {
     exposes_filter(manifest).try_for_each(|expose| {
-        // We should always have an idle timeout if we process this expose for stateful NAT.
-        let idle_timeout = expose.idle_timeout().unwrap_or_else(|| unreachable!());
+        let session_timeouts = session_timeouts_for_expose(expose);
+        let port_range = expose.nat_port_range().cloned();
 
-        let tcp_ip_allocator =
-            ip_allocator_for_prefixes(original_prefixes_from_expose(expose), idle_timeout)?;
-        let udp_ip_allocator = tcp_ip_allocator.deep_clone()?;
+        let mut tcp_ip_allocator = ip_allocator_for_prefixes(
+            original_prefixes_from_expose(expose),
+            session_timeouts,
+            port_range.clone(),
+        )?;
+        if let (Some(port_blocks), Some(block_size)) =
+            (port_blocks.as_deref_mut(), expose.nat_port_block_size())
+        {
+            let map = Arc::new(PortBlockMap::new(
+                target_prefixes_from_expose(expose),
+                original_prefixes_from_expose(expose),
+                port_range,
+                block_size,
+            )?);
+            tcp_ip_allocator = tcp_ip_allocator.with_port_blocks(map.clone());
+            port_blocks.push((src_vpc_id, dst_vpc_id, map));
+        }
+        let mut udp_ip_allocator = tcp_ip_allocator.deep_clone()?;
+        if expose.nat_endpoint_independent() {
+            udp_ip_allocator = udp_ip_allocator.with_endpoint_independent();
+        }
         let icmp_ip_allocator = tcp_ip_allocator.deep_clone()?;
 
         add_pool_entries(
@@ -162,6 +261,67 @@ where
     })
 }
 
+fn session_timeouts_for_expose(expose: &VpcExpose) -> SessionTimeouts {
+    // We should always have an idle timeout if we process this expose for stateful NAT.
+    let idle_timeout = expose.idle_timeout().unwrap_or_else(|| unreachable!());
+    SessionTimeouts::new(
+        idle_timeout,
+        expose
+            .nat_session_timeouts()
+            .unwrap_or_else(|| unreachable!()),
+    )
+}
+
+// Record a static NAT binding. For 1:1 bindings, withhold the public address from the dynamic pools
+// for the expose; for port forwarding rules, reserve the public port in these pools.
+fn add_static_binding<I: NatIpWithBitmap>(
+    table: &mut StaticNatTable<I>,
+    pools_src: &PoolTable<I, I>,
+    icmp_proto: NextHeader,
+    (src_vpc_id, dst_vpc_id): (VpcDiscriminant, VpcDiscriminant),
+    binding: &VpcExposeStaticNat,
+    (internal, public): (I, I),
+    session_timeouts: SessionTimeouts,
+) -> Result<(), AllocatorError> {
+    let dynamic_allocator = |protocol| {
+        pools_src
+            .get_entry(protocol, src_vpc_id, dst_vpc_id, internal)
+            .ok_or_else(|| {
+                AllocatorError::InternalIssue(format!(
+                    "No address pool for static NAT binding {binding}"
+                ))
+            })
+    };
+    let internal_allocator = ip_allocator_for_prefixes(
+        &BTreeSet::from([Prefix::from(binding.internal)]),
+        session_timeouts,
+        None,
+    )?;
+
+    let static_binding = match binding.ports {
+        None => {
+            for protocol in [NextHeader::TCP, NextHeader::UDP, icmp_proto] {
+                dynamic_allocator(protocol)?.withhold_ip(public)?;
+            }
+            let public_allocator = ip_allocator_for_prefixes(
+                &BTreeSet::from([Prefix::from(binding.public)]),
+                session_timeouts,
+                None,
+            )?;
+            StaticBinding::new_address(internal, public, internal_allocator, public_allocator)
+        }
+        Some((internal_port, public_port)) => StaticBinding::new_port(
+            (internal, internal_port),
+            (public, public_port),
+            internal_allocator,
+            dynamic_allocator(NextHeader::TCP)?,
+            dynamic_allocator(NextHeader::UDP)?,
+        )?,
+    };
+    table.insert(src_vpc_id, dst_vpc_id, static_binding);
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 fn add_pool_entries<I: NatIpWithBitmap, J: NatIpWithBitmap>(
     table: &mut PoolTable<I, J>,
@@ -215,16 +375,18 @@ fn insert_per_proto_entries<I: NatIpWithBitmap, J: NatIpWithBitmap>(
 
 fn ip_allocator_for_prefixes<J: NatIpWithBitmap>(
     prefixes: &BTreeSet<Prefix>,
-    idle_timeout: Duration,
+    session_timeouts: SessionTimeouts,
+    port_range: Option<RangeInclusive<u16>>,
 ) -> Result<IpAllocator<J>, AllocatorError> {
-    let pool = create_natpool(prefixes, idle_timeout)?;
+    let pool = create_natpool(prefixes, session_timeouts, port_range)?;
     let allocator = IpAllocator::new(pool);
     Ok(allocator)
 }
 
 fn create_natpool<J: NatIpWithBitmap>(
     prefixes: &BTreeSet<Prefix>,
-    idle_timeout: Duration,
+    session_timeouts: SessionTimeouts,
+    port_range: Option<RangeInclusive<u16>>,
 ) -> Result<NatPool<J>, AllocatorError> {
     // Build mappings for IPv6 <-> u32 bitmap translation
     let (bitmap_mapping, reverse_bitmap_mapping) = create_ipv6_bitmap_mappings(prefixes)?;
@@ -239,7 +401,8 @@ fn create_natpool<J: NatIpWithBitmap>(
         bitmap,
         bitmap_mapping,
         reverse_bitmap_mapping,
-        idle_timeout,
+        session_timeouts,
+        port_range,
     ))
 }
 
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Static NAT bindings, applied before dynamic allocation.
+//!
+//! A 1:1 binding ties an internal address to a public address: the public address is withheld from
+//! the dynamic pool, and ports (or ICMP identifiers) are preserved. A port forwarding rule ties an
+//! internal address and port to a public address and port, for TCP and UDP: the public port is
+//! reserved in the dynamic pool for as long as the configuration applies, and other ports of the
+//! public address remain available for dynamic allocation.
+//!
+//! In both cases, packets from any remote endpoint to the public address (and port) are accepted
+//! and translated back to the internal address (and port).
+
+use super::NatIpWithBitmap;
+use super::alloc::IpAllocator;
+use super::port_alloc::AllocatedPort;
+use crate::NatPort;
+use crate::stateful::allocator::AllocatorError;
+use crate::stateful::timeouts::SessionTimeouts;
+use concurrency::sync::Arc;
+use net::ip::NextHeader;
+use net::packet::VpcDiscriminant;
+use std::collections::HashMap;
+
+/// The public side of a [`StaticBinding`]
+#[derive(Debug)]
+enum StaticPublic<I: NatIpWithBitmap> {
+    // 1:1 binding: allocator for the public address only
+    Address(IpAllocator<I>),
+    // Port forwarding: the public port, permanently reserved for TCP and UDP in the dynamic pools
+    Port {
+        port: u16,
+        tcp: AllocatedPort<I>,
+        udp: AllocatedPort<I>,
+    },
+}
+
+/// A static NAT binding between an internal address and a public address
+#[derive(Debug)]
+pub(crate) struct StaticBinding<I: NatIpWithBitmap> {
+    internal: I,
+    public: I,
+    // Internal port, for port forwarding
+    internal_port: Option<u16>,
+    // Allocator for the internal address only, to reserve mappings for the reverse direction
+    internal_allocator: IpAllocator<I>,
+    public_side: StaticPublic<I>,
+}
+
+impl<I: NatIpWithBitmap> StaticBinding<I> {
+    /// Create a 1:1 binding. `public_allocator` must only contain the public address.
+    pub(crate) fn new_address(
+        internal: I,
+        public: I,
+        internal_allocator: IpAllocator<I>,
+        public_allocator: IpAllocator<I>,
+    ) -> Self {
+        Self {
+            internal,
+            public,
+            internal_port: None,
+            internal_allocator,
+            public_side: StaticPublic::Address(public_allocator),
+        }
+    }
+
+    /// Create a port forwarding rule, reserving the public port for TCP and UDP with the dynamic
+    /// allocators for the public address.
+    pub(crate) fn new_port(
+        (internal, internal_port): (I, u16),
+        (public, public_port): (I, u16),
+        internal_allocator: IpAllocator<I>,
+        tcp_allocator: &IpAllocator<I>,
+        udp_allocator: &IpAllocator<I>,
+    ) -> Result<Self, AllocatorError> {
+        let port =
+            NatPort::new_port_checked(public_port).map_err(AllocatorError::PortAllocationFailed)?;
+        Ok(Self {
+            internal,
+            public,
+            internal_port: Some(internal_port),
+            internal_allocator,
+            public_side: StaticPublic::Port {
+                port: public_port,
+                tcp: tcp_allocator.reserve(public, port)?,
+                udp: udp_allocator.reserve(public, port)?,
+            },
+        })
+    }
+
+    fn public_port(&self) -> Option<u16> {
+        match &self.public_side {
+            StaticPublic::Address(_) => None,
+            StaticPublic::Port { port, .. } => Some(*port),
+        }
+    }
+
+    /// Get the mapping to translate the internal address into, for `protocol`. `port` is the
+    /// port (or ICMP identifier) to preserve, for 1:1 bindings.
+    pub(crate) fn public_mapping(
+        &self,
+        protocol: NextHeader,
+        port: NatPort,
+    ) -> Result<AllocatedPort<I>, AllocatorError> {
+        match &self.public_side {
+            StaticPublic::Address(allocator) => allocator.reserve(self.public, port),
+            StaticPublic::Port { tcp, .. } if protocol == NextHeader::TCP => Ok(tcp.clone()),
+            StaticPublic::Port { udp, .. } if protocol == NextHeader::UDP => Ok(udp.clone()),
+            StaticPublic::Port { .. } => Err(AllocatorError::UnsupportedProtocol(protocol)),
+        }
+    }
+
+    /// Get the mapping to translate the public address back into. `port` is the port (or ICMP
+    /// identifier) to preserve, for 1:1 bindings.
+    pub(crate) fn internal_mapping(
+        &self,
+        port: NatPort,
+    ) -> Result<AllocatedPort<I>, AllocatorError> {
+        let port = match self.internal_port {
+            Some(internal_port) => NatPort::new_port_checked(internal_port)
+                .map_err(AllocatorError::PortAllocationFailed)?,
+            None => port,
+        };
+        self.internal_allocator.reserve(self.internal, port)
+    }
+
+    pub(crate) fn session_timeouts(&self) -> Option<SessionTimeouts> {
+        self.internal_allocator.session_timeouts()
+    }
+}
+
+// Static bindings for one address: at most one 1:1 binding, and port forwarding rules by port
+#[derive(Debug)]
+struct AddressBindings<I: NatIpWithBitmap> {
+    address: Option<Arc<StaticBinding<I>>>,
+    ports: HashMap<u16, Arc<StaticBinding<I>>>,
+}
+
+impl<I: NatIpWithBitmap> AddressBindings<I> {
+    fn new() -> Self {
+        Self {
+            address: None,
+            ports: HashMap::new(),
+        }
+    }
+
+    // Port forwarding rules take precedence over 1:1 bindings. Packets without ports (ICMP) only
+    // match 1:1 bindings.
+    fn lookup(&self, port: Option<u16>) -> Option<&StaticBinding<I>> {
+        port.and_then(|port| self.ports.get(&port))
+            .or(self.address.as_ref())
+            .map(|binding| &**binding)
+    }
+}
+
+type BindingKey<I> = (VpcDiscriminant, VpcDiscriminant, I);
+
+/// The static NAT bindings, for all peerings, keyed by the source and destination VPCs of the
+/// traffic from the internal side.
+#[derive(Debug)]
+pub(crate) struct StaticNatTable<I: NatIpWithBitmap> {
+    by_internal: HashMap<BindingKey<I>, AddressBindings<I>>,
+    by_public: HashMap<BindingKey<I>, AddressBindings<I>>,
+}
+
+impl<I: NatIpWithBitmap> StaticNatTable<I> {
+    pub(crate) fn new() -> Self {
+        Self {
+            by_internal: HashMap::new(),
+            by_public: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        src_vpcd: VpcDiscriminant,
+        dst_vpcd: VpcDiscriminant,
+        binding: StaticBinding<I>,
+    ) {
+        let (internal, public) = (binding.internal, binding.public);
+        let (internal_port, public_port) = (binding.internal_port, binding.public_port());
+        let binding = Arc::new(binding);
+        for (table, addr, port) in [
+            (&mut self.by_internal, internal, internal_port),
+            (&mut self.by_public, public, public_port),
+        ] {
+            let bindings = table
+                .entry((src_vpcd, dst_vpcd, addr))
+                .or_insert_with(AddressBindings::new);
+            match port {
+                Some(port) => {
+                    bindings.ports.insert(port, binding.clone());
+                }
+                None => bindings.address = Some(binding.clone()),
+            }
+        }
+    }
+
+    /// Get the binding for traffic from `src_vpcd` to `dst_vpcd`, from internal address `addr`
+    /// and port `port`.
+    pub(crate) fn lookup_internal(
+        &self,
+        src_vpcd: VpcDiscriminant,
+        dst_vpcd: VpcDiscriminant,
+        addr: I,
+        port: Option<u16>,
+    ) -> Option<&StaticBinding<I>> {
+        self.by_internal
+            .get(&(src_vpcd, dst_vpcd, addr))
+            .and_then(|bindings| bindings.lookup(port))
+    }
+
+    /// Get the binding for traffic from `src_vpcd` to `dst_vpcd`, to public address `addr` and
+    /// port `port`. The binding is keyed with the VPCs of the traffic from its internal side,
+    /// so `dst_vpcd` is the VPC of the internal address.
+    pub(crate) fn lookup_public(
+        &self,
+        src_vpcd: VpcDiscriminant,
+        dst_vpcd: VpcDiscriminant,
+        addr: I,
+        port: Option<u16>,
+    ) -> Option<&StaticBinding<I>> {
+        self.by_public
+            .get(&(dst_vpcd, src_vpcd, addr))
+            .and_then(|bindings| bindings.lookup(port))
+    }
+}
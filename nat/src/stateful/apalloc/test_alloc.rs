@@ -23,6 +23,7 @@ mod context {
     use net::vxlan::Vni;
     use pkt_meta::flow_table::{IpProtoKey, TcpProtoKey, UdpProtoKey};
     use std::net::{IpAddr, Ipv4Addr};
+    use std::ops::RangeInclusive;
     use std::str::FromStr;
 
     #[allow(unused)]
@@ -97,9 +98,9 @@ mod context {
         .unwrap()
     }
 
-    fn build_context() -> VpcTable {
+    fn build_context(port_range: Option<RangeInclusive<u16>>) -> VpcTable {
         // Exposes and manifests
-        let expose1 = VpcExpose::empty()
+        let mut expose1 = VpcExpose::empty()
             .make_stateful_nat(None)
             .unwrap()
             .ip("1.1.0.0/16".into())
@@ -107,6 +108,9 @@ mod context {
             .ip("1.3.0.0/16".into())
             .as_range("10.1.0.0/30".into())
             .not_as("10.1.0.3/32".into());
+        if let Some(port_range) = port_range {
+            expose1 = expose1.make_nat_port_range(port_range).unwrap();
+        }
         let expose2 = VpcExpose::empty()
             .make_stateful_nat(None)
             .unwrap()
@@ -136,6 +140,10 @@ mod context {
             exposes: vec![expose3, expose4],
         };
 
+        build_vpc_table(manifest1, manifest2)
+    }
+
+    fn build_vpc_table(manifest1: VpcManifest, manifest2: VpcManifest) -> VpcTable {
         // Peerings
         let peering1 = Peering {
             name: "test_peering1".into(),
@@ -167,7 +175,138 @@ mod context {
     }
 
     pub fn build_allocator() -> Result<NatDefaultAllocator, ConfigError> {
-        let vpc_table = build_context();
+        let vpc_table = build_context(None);
+        let config = StatefulNatConfig::new(&vpc_table);
+        NatDefaultAllocator::build_nat_allocator(&config)
+    }
+
+    // Same as build_allocator(), with a port range for the first expose of VPC-1
+    pub fn build_allocator_with_port_range(
+        port_range: RangeInclusive<u16>,
+    ) -> Result<NatDefaultAllocator, ConfigError> {
+        let vpc_table = build_context(Some(port_range));
+        let config = StatefulNatConfig::new(&vpc_table);
+        NatDefaultAllocator::build_nat_allocator(&config)
+    }
+
+    // Build an allocator with deterministic NAT for VPC-1: 1.1.0.0/26 is translated into
+    // 10.1.0.0/31, with blocks of 32 ports from 1024-2047 (32 blocks per public address)
+    pub fn build_allocator_with_port_blocks() -> Result<NatDefaultAllocator, ConfigError> {
+        let expose1 = VpcExpose::empty()
+            .make_stateful_nat(None)
+            .unwrap()
+            .ip("1.1.0.0/26".into())
+            .as_range("10.1.0.0/31".into())
+            .make_nat_port_range(1024..=2047)
+            .unwrap()
+            .make_nat_port_blocks(32)
+            .unwrap();
+        let expose2 = VpcExpose::empty()
+            .make_stateful_nat(None)
+            .unwrap()
+            .ip("3.0.0.0/24".into())
+            .as_range("10.3.0.0/30".into());
+        expose1.validate().unwrap();
+
+        let manifest1 = VpcManifest {
+            name: "VPC-1".into(),
+            exposes: vec![expose1],
+        };
+        let manifest2 = VpcManifest {
+            name: "VPC-2".into(),
+            exposes: vec![expose2],
+        };
+        let vpc_table = build_vpc_table(manifest1, manifest2);
+        let config = StatefulNatConfig::new(&vpc_table);
+        NatDefaultAllocator::build_nat_allocator(&config)
+    }
+
+    // Build an allocator with "no-NAT" exceptions for VPC-1: 1.1.0.0/24 is translated into
+    // 10.1.0.0/30, except for 1.1.0.128/25, and for port 22 on 1.1.0.0/26
+    pub fn build_allocator_with_exclusions() -> Result<NatDefaultAllocator, ConfigError> {
+        let expose1 = VpcExpose::empty()
+            .make_stateful_nat(None)
+            .unwrap()
+            .ip("1.1.0.0/24".into())
+            .as_range("10.1.0.0/30".into())
+            .no_nat("1.1.0.128/25".into(), None)
+            .no_nat("1.1.0.0/26".into(), Some(22..=22));
+        let expose2 = VpcExpose::empty()
+            .make_stateful_nat(None)
+            .unwrap()
+            .ip("3.0.0.0/24".into())
+            .as_range("10.3.0.0/30".into());
+        expose1.validate().unwrap();
+
+        let manifest1 = VpcManifest {
+            name: "VPC-1".into(),
+            exposes: vec![expose1],
+        };
+        let manifest2 = VpcManifest {
+            name: "VPC-2".into(),
+            exposes: vec![expose2],
+        };
+        let vpc_table = build_vpc_table(manifest1, manifest2);
+        let config = StatefulNatConfig::new(&vpc_table);
+        NatDefaultAllocator::build_nat_allocator(&config)
+    }
+
+    pub fn build_allocator_with_static_bindings() -> Result<NatDefaultAllocator, ConfigError> {
+        let expose1 = VpcExpose::empty()
+            .make_stateful_nat(None)
+            .unwrap()
+            .ip("1.1.0.0/24".into())
+            .as_range("10.1.0.0/30".into())
+            .make_nat_static_binding(ipaddr("1.1.0.5"), ipaddr("10.1.0.3"), None)
+            .unwrap()
+            .make_nat_static_binding(ipaddr("1.1.0.6"), ipaddr("10.1.0.0"), Some((80, 8080)))
+            .unwrap();
+        let expose2 = VpcExpose::empty()
+            .make_stateful_nat(None)
+            .unwrap()
+            .ip("3.0.0.0/24".into())
+            .as_range("10.3.0.0/30".into());
+        expose1.validate().unwrap();
+
+        let manifest1 = VpcManifest {
+            name: "VPC-1".into(),
+            exposes: vec![expose1],
+        };
+        let manifest2 = VpcManifest {
+            name: "VPC-2".into(),
+            exposes: vec![expose2],
+        };
+        let vpc_table = build_vpc_table(manifest1, manifest2);
+        let config = StatefulNatConfig::new(&vpc_table);
+        NatDefaultAllocator::build_nat_allocator(&config)
+    }
+
+    pub fn build_allocator_with_endpoint_independent(
+        endpoint_independent: bool,
+    ) -> Result<NatDefaultAllocator, ConfigError> {
+        let mut expose1 = VpcExpose::empty()
+            .make_stateful_nat(None)
+            .unwrap()
+            .ip("1.1.0.0/24".into())
+            .as_range("10.1.0.0/30".into());
+        if endpoint_independent {
+            expose1 = expose1.make_nat_endpoint_independent().unwrap();
+        }
+        let expose2 = VpcExpose::empty()
+            .make_stateful_nat(None)
+            .unwrap()
+            .ip("3.0.0.0/24".into())
+            .as_range("10.3.0.0/30".into());
+
+        let manifest1 = VpcManifest {
+            name: "VPC-1".into(),
+            exposes: vec![expose1],
+        };
+        let manifest2 = VpcManifest {
+            name: "VPC-2".into(),
+            exposes: vec![expose2],
+        };
+        let vpc_table = build_vpc_table(manifest1, manifest2);
         let config = StatefulNatConfig::new(&vpc_table);
         NatDefaultAllocator::build_nat_allocator(&config)
     }
@@ -176,12 +315,15 @@ mod context {
 #[concurrency_mode(std)]
 mod std_tests {
     use super::context::*;
-    use crate::stateful::allocator::NatAllocator;
+    use crate::stateful::allocator::{AllocatorError, NatAllocator};
     use crate::stateful::apalloc::PoolTableKey;
+    use crate::stateful::apalloc::port_alloc::AllocatedPort;
+    use crate::stateful::stats::{NatAllocatorCounters, NatAllocatorStats};
     use concurrency::sync::Arc;
     use concurrency::thread;
     use net::ip::NextHeader;
     use pkt_meta::flow_table::FlowKey;
+    use std::net::{IpAddr, Ipv4Addr};
 
     #[test]
     fn test_build_allocator() {
@@ -468,6 +610,382 @@ mod std_tests {
         assert_eq!(in_use.len(), 1); // 1 allocated, in use
     }
 
+    // Allocate ports with a port range configured for the expose. Ports should be allocated in
+    // ascending order within the range, across port blocks, then from the next IP address, until
+    // the pool is exhausted.
+    #[test]
+    fn test_allocate_port_range() {
+        // 201 ports, spanning over two port blocks (1024-1279, 1280-1535)
+        let port_range = 1100..=1300;
+        let ports_per_ip = port_range.len();
+        let allocator = build_allocator_with_port_range(port_range.clone()).unwrap();
+
+        let flow_key = |i: u16| {
+            FlowKey::uni(
+                Some(vpcd1()),
+                ipaddr("1.1.0.0"),
+                Some(vpcd2()),
+                ipaddr("10.3.0.2"),
+                tcp_proto_key(2000 + i, 5000 + i),
+            )
+        };
+
+        // Keep all allocations alive until the end of the test
+        let mut allocations = Vec::new();
+        for i in 0..3 * ports_per_ip {
+            let i = u16::try_from(i).unwrap();
+            let allocation = allocator.allocate_v4(&flow_key(i)).unwrap();
+            let src = allocation.src.as_ref().unwrap();
+            let ip_index = u8::try_from(usize::from(i) / ports_per_ip).unwrap();
+            let port_offset = u16::try_from(usize::from(i) % ports_per_ip).unwrap();
+            assert_eq!(src.ip(), addr_v4(&format!("10.1.0.{ip_index}")));
+            assert_eq!(src.port().as_u16(), port_range.start() + port_offset);
+
+            // Reservations for the return path are not restricted to the port range
+            assert_eq!(
+                allocation.return_dst.as_ref().unwrap().port().as_u16(),
+                2000 + i
+            );
+            allocations.push(allocation);
+        }
+
+        // All three IP addresses have used all the ports from the range
+        let err = allocator.allocate_v4(&flow_key(1000)).unwrap_err();
+        assert!(matches!(err, AllocatorError::NoFreeIp));
+
+        // Usage for VPC-1 accounts for the three addresses with the port range, for TCP, UDP and
+        // ICMP, and for the eight addresses of the second expose, with all ports
+        let usage = allocator.port_usage()[&vpcd1()];
+        assert_eq!(usage.in_use, 3 * ports_per_ip as u64);
+        assert_eq!(usage.total, (3 * 3 * ports_per_ip + 3 * 8 * 65536) as u64);
+
+        let counters = NatAllocatorCounters::default();
+        counters.record(&err);
+        counters.record(&AllocatorError::NoFreePort(1024));
+        counters.record(&AllocatorError::Denied);
+        assert_eq!(
+            counters.snapshot(),
+            NatAllocatorStats {
+                ip_exhausted: 1,
+                port_exhausted: 1,
+                sessions_evicted: 0,
+            }
+        );
+
+        // Release the allocations, ports from the range are available again
+        drop(allocations);
+        let allocation = allocator.allocate_v4(&flow_key(1000)).unwrap();
+        assert!(port_range.contains(&allocation.src.as_ref().unwrap().port().as_u16()));
+        assert_eq!(allocator.port_usage()[&vpcd1()].in_use, 1);
+    }
+
+    // Allocate ports with deterministic NAT. Each address to translate always gets the same public
+    // address, with ports from its own block only, and the mapping can be exported.
+    #[test]
+    fn test_allocate_port_blocks() {
+        let allocator = build_allocator_with_port_blocks().unwrap();
+
+        let flow_key = |src: &str, i: u16| {
+            FlowKey::uni(
+                Some(vpcd1()),
+                ipaddr(src),
+                Some(vpcd2()),
+                ipaddr("10.3.0.2"),
+                tcp_proto_key(2000 + i, 5000 + i),
+            )
+        };
+
+        // 1.1.0.33 is the address at index 33: second block on the second public address
+        let mut allocations = Vec::new();
+        for i in 0..32 {
+            let allocation = allocator.allocate_v4(&flow_key("1.1.0.33", i)).unwrap();
+            let src = allocation.src.as_ref().unwrap();
+            assert_eq!(src.ip(), addr_v4("10.1.0.1"));
+            assert_eq!(src.port().as_u16(), 1056 + i);
+            allocations.push(allocation);
+        }
+
+        // The block is exhausted, but other addresses still get ports from their own blocks
+        let err = allocator
+            .allocate_v4(&flow_key("1.1.0.33", 32))
+            .unwrap_err();
+        assert!(matches!(err, AllocatorError::NoFreePort(1056)));
+        let allocation = allocator.allocate_v4(&flow_key("1.1.0.2", 0)).unwrap();
+        let src = allocation.src.as_ref().unwrap();
+        assert_eq!(src.ip(), addr_v4("10.1.0.0"));
+        assert_eq!(src.port().as_u16(), 1088);
+
+        // Released ports are available again
+        allocations.remove(5);
+        let allocation = allocator.allocate_v4(&flow_key("1.1.0.33", 32)).unwrap();
+        let src = allocation.src.as_ref().unwrap();
+        assert_eq!(src.ip(), addr_v4("10.1.0.1"));
+        assert_eq!(src.port().as_u16(), 1061);
+
+        // Export the mapping
+        let entries = allocator.port_blocks().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 64);
+        let entry = &entries[33];
+        assert_eq!(entry.src_vpcd, vpcd1());
+        assert_eq!(entry.dst_vpcd, vpcd2());
+        assert_eq!(entry.addr, ipaddr("1.1.0.33"));
+        assert_eq!(entry.block.ip, ipaddr("10.1.0.1"));
+        assert_eq!(entry.block.ports, 1056..=1087);
+        assert_eq!(
+            entry.to_string(),
+            "VNI(100) -> VNI(200): 1.1.0.33 -> 10.1.0.1 ports 1056-1087"
+        );
+    }
+
+    // Flows matching "no-NAT" exceptions get no allocation, in both directions
+    #[test]
+    fn test_allocate_exclusions() {
+        let allocator = build_allocator_with_exclusions().unwrap();
+
+        let flow_key = |src: &str, src_port: u16| {
+            FlowKey::uni(
+                Some(vpcd1()),
+                ipaddr(src),
+                Some(vpcd2()),
+                ipaddr("10.3.0.2"),
+                tcp_proto_key(src_port, 443),
+            )
+        };
+
+        // Excluded for all ports: no source NAT, destination NAT still applies
+        let allocation = allocator.allocate_v4(&flow_key("1.1.0.200", 2000)).unwrap();
+        assert!(allocation.src.is_none());
+        assert!(allocation.dst.is_some());
+
+        // Excluded for port 22 only
+        let allocation = allocator.allocate_v4(&flow_key("1.1.0.5", 22)).unwrap();
+        assert!(allocation.src.is_none());
+        let allocation = allocator.allocate_v4(&flow_key("1.1.0.5", 2000)).unwrap();
+        assert!(allocation.src.is_some());
+
+        // Not excluded
+        let allocation = allocator.allocate_v4(&flow_key("1.1.0.100", 22)).unwrap();
+        assert!(allocation.src.is_some());
+
+        // Traffic to an excluded address is not translated either, and not denied
+        let reverse_flow_key = FlowKey::uni(
+            Some(vpcd2()),
+            ipaddr("10.3.0.2"),
+            Some(vpcd1()),
+            ipaddr("1.1.0.200"),
+            tcp_proto_key(443, 2000),
+        );
+        let allocation = allocator.allocate_v4(&reverse_flow_key).unwrap();
+        assert!(allocation.src.is_none());
+        assert!(allocation.dst.is_none());
+    }
+
+    #[test]
+    fn test_allocate_static_bindings() {
+        let allocator = build_allocator_with_static_bindings().unwrap();
+        let outbound_flow_key = |src: &str, src_port: u16| {
+            FlowKey::uni(
+                Some(vpcd1()),
+                ipaddr(src),
+                Some(vpcd2()),
+                ipaddr("10.3.0.2"),
+                tcp_proto_key(src_port, 443),
+            )
+        };
+        let inbound_flow_key = |dst: &str, dst_port: u16| {
+            FlowKey::uni(
+                Some(vpcd2()),
+                ipaddr("10.3.0.1"),
+                Some(vpcd1()),
+                ipaddr(dst),
+                tcp_proto_key(3000, dst_port),
+            )
+        };
+        let ip_port = |mapping: &AllocatedPort<Ipv4Addr>| (mapping.ip(), mapping.port().as_u16());
+
+        // 1:1 binding, outbound: public address, port preserved
+        let allocation = allocator
+            .allocate_v4(&outbound_flow_key("1.1.0.5", 5000))
+            .unwrap();
+        assert_eq!(
+            ip_port(allocation.src.as_ref().unwrap()),
+            (addr_v4("10.1.0.3"), 5000)
+        );
+        assert_eq!(
+            ip_port(allocation.return_dst.as_ref().unwrap()),
+            (addr_v4("1.1.0.5"), 5000)
+        );
+
+        // 1:1 binding, inbound from any remote endpoint
+        let allocation = allocator
+            .allocate_v4(&inbound_flow_key("10.1.0.3", 22))
+            .unwrap();
+        assert!(allocation.src.is_none());
+        assert_eq!(
+            ip_port(allocation.dst.as_ref().unwrap()),
+            (addr_v4("1.1.0.5"), 22)
+        );
+        assert_eq!(
+            ip_port(allocation.return_src.as_ref().unwrap()),
+            (addr_v4("10.1.0.3"), 22)
+        );
+        assert!(allocation.dst_flow_timeouts.is_some());
+
+        // The public address of the 1:1 binding is not available for dynamic allocation
+        for protocol in [NextHeader::TCP, NextHeader::UDP, NextHeader::ICMP] {
+            let (bitmap, _) = allocator
+                .pools_src44
+                .get_entry(protocol, vpcd1(), vpcd2(), addr_v4("1.1.0.1"))
+                .unwrap()
+                .get_pool_clone_for_tests();
+            assert!(!bitmap.contains(addr_v4_bits("10.1.0.3")));
+            assert!(bitmap.contains(addr_v4_bits("10.1.0.2")));
+        }
+
+        // Port forwarding, inbound: only for the public port
+        let allocation = allocator
+            .allocate_v4(&inbound_flow_key("10.1.0.0", 8080))
+            .unwrap();
+        assert_eq!(
+            ip_port(allocation.dst.as_ref().unwrap()),
+            (addr_v4("1.1.0.6"), 80)
+        );
+        assert_eq!(
+            ip_port(allocation.return_src.as_ref().unwrap()),
+            (addr_v4("10.1.0.0"), 8080)
+        );
+        assert!(matches!(
+            allocator.allocate_v4(&inbound_flow_key("10.1.0.0", 8081)),
+            Err(AllocatorError::Denied)
+        ));
+
+        // Port forwarding, outbound: only from the internal port
+        let allocation = allocator
+            .allocate_v4(&outbound_flow_key("1.1.0.6", 80))
+            .unwrap();
+        assert_eq!(
+            ip_port(allocation.src.as_ref().unwrap()),
+            (addr_v4("10.1.0.0"), 8080)
+        );
+        let allocation = allocator
+            .allocate_v4(&outbound_flow_key("1.1.0.6", 81))
+            .unwrap();
+        assert_ne!(
+            ip_port(allocation.src.as_ref().unwrap()),
+            (addr_v4("10.1.0.0"), 8080)
+        );
+    }
+
+    #[test]
+    fn test_allocate_endpoint_independent() {
+        let flow_key = |src: &str, dst: &str, proto_key| {
+            FlowKey::uni(
+                Some(vpcd1()),
+                ipaddr(src),
+                Some(vpcd2()),
+                ipaddr(dst),
+                proto_key,
+            )
+        };
+        let inbound_flow_key = |src: &str, dst: Ipv4Addr, dst_port: u16| {
+            FlowKey::uni(
+                Some(vpcd2()),
+                ipaddr(src),
+                Some(vpcd1()),
+                IpAddr::V4(dst),
+                udp_proto_key(3478, dst_port),
+            )
+        };
+
+        let allocator = build_allocator_with_endpoint_independent(true).unwrap();
+
+        // Same internal address and port, different destinations: same mapping
+        let allocation1 = allocator
+            .allocate_v4(&flow_key("1.1.0.5", "10.3.0.2", udp_proto_key(5000, 3478)))
+            .unwrap();
+        let allocation2 = allocator
+            .allocate_v4(&flow_key("1.1.0.5", "10.3.0.3", udp_proto_key(5000, 3478)))
+            .unwrap();
+        let mapping = allocation1.src.clone().unwrap();
+        let public_ip = mapping.ip();
+        let public_port = mapping.port().as_u16();
+        assert_eq!(allocation2.src.as_ref().unwrap().ip(), public_ip);
+        assert_eq!(
+            allocation2.src.as_ref().unwrap().port().as_u16(),
+            public_port
+        );
+
+        // Different internal port: different mapping
+        let allocation3 = allocator
+            .allocate_v4(&flow_key("1.1.0.5", "10.3.0.2", udp_proto_key(5001, 3478)))
+            .unwrap();
+        assert_ne!(allocation3.src.unwrap().port().as_u16(), public_port);
+
+        // TCP does not use endpoint-independent mapping
+        let allocation4 = allocator
+            .allocate_v4(&flow_key("1.1.0.5", "10.3.0.2", tcp_proto_key(5000, 3478)))
+            .unwrap();
+        let allocation5 = allocator
+            .allocate_v4(&flow_key("1.1.0.5", "10.3.0.3", tcp_proto_key(5000, 3478)))
+            .unwrap();
+        let (mapping4, mapping5) = (allocation4.src.unwrap(), allocation5.src.unwrap());
+        assert_ne!(
+            (mapping4.ip(), mapping4.port()),
+            (mapping5.ip(), mapping5.port())
+        );
+
+        // Packets from a new remote endpoint to the public address and port are accepted, and
+        // translated back to the internal address and port
+        let allocation = allocator
+            .allocate_v4(&inbound_flow_key("10.3.0.1", public_ip, public_port))
+            .unwrap();
+        assert!(allocation.src.is_none());
+        let dst = allocation.dst.unwrap();
+        assert_eq!(dst.ip(), addr_v4("1.1.0.5"));
+        assert_eq!(dst.port().as_u16(), 5000);
+        let return_src = allocation.return_src.unwrap();
+        assert_eq!(return_src.ip(), public_ip);
+        assert_eq!(return_src.port().as_u16(), public_port);
+        assert!(allocation.dst_flow_timeouts.is_some());
+
+        // Packets to another port are still denied
+        assert!(matches!(
+            allocator.allocate_v4(&inbound_flow_key("10.3.0.1", public_ip, public_port + 1)),
+            Err(AllocatorError::Denied)
+        ));
+
+        // Once no session uses the mapping, it is gone
+        drop((allocation1, allocation2, mapping, return_src));
+        assert!(matches!(
+            allocator.allocate_v4(&inbound_flow_key("10.3.0.1", public_ip, public_port)),
+            Err(AllocatorError::Denied)
+        ));
+
+        // Without endpoint-independent mapping and filtering, mappings depend on the destination
+        // and packets from new remote endpoints are denied
+        let allocator = build_allocator_with_endpoint_independent(false).unwrap();
+        let allocation1 = allocator
+            .allocate_v4(&flow_key("1.1.0.5", "10.3.0.2", udp_proto_key(5000, 3478)))
+            .unwrap();
+        let allocation2 = allocator
+            .allocate_v4(&flow_key("1.1.0.5", "10.3.0.3", udp_proto_key(5000, 3478)))
+            .unwrap();
+        let mapping1 = allocation1.src.unwrap();
+        let mapping2 = allocation2.src.unwrap();
+        assert_ne!(
+            (mapping1.ip(), mapping1.port()),
+            (mapping2.ip(), mapping2.port())
+        );
+        assert!(matches!(
+            allocator.allocate_v4(&inbound_flow_key(
+                "10.3.0.1",
+                mapping1.ip(),
+                mapping1.port().as_u16()
+            )),
+            Err(AllocatorError::Denied)
+        ));
+    }
+
     // This test is NOT a shuttle test. It validates that a basic example with threads works
     // with or without shuttle components (depending on how we compile), as a control test in
     // case shuttle tests do not work. For example, it helped understand that memory usage for
@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Eviction of transitory sessions when the session table is close to full.
+//!
+//! Transitory sessions are TCP sessions whose last packet opened or closed the connection (see
+//! [`SessionClass::TcpTransitory`](super::timeouts::SessionClass::TcpTransitory)): half-open
+//! connections, often left over by scans or SYN floods, and connections being torn down. When the
+//! session table gets close to its capacity, the stateful NAT evicts the least recently used of
+//! them to make room for new sessions, and releases their allocated IPs and ports, rather than
+//! failing to allocate for new flows or letting the table evict established sessions.
+
+use super::{NatFlowState, StatefulNat};
+use concurrency::sync::atomic::Ordering;
+use concurrency::sync::{Arc, Weak};
+use flow_info::{ExtractRef, FlowInfo, FlowStatus};
+use pkt_meta::flow_table::FlowKey;
+use std::collections::VecDeque;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::Instant;
+use tracing::debug;
+
+/// Maximum number of tracked sessions examined when looking for a session to evict
+const MAX_EVICTION_SCAN: usize = 64;
+/// Maximum number of stale entries dropped from the queue each time a session gets tracked
+const MAX_PRUNE: usize = 2;
+/// Maximum number of tracked sessions, for a given [`StatefulNat`] instance
+const MAX_TRACKED: usize = 1 << 16;
+
+// Tell if the session for `flow_info` is transitory
+fn is_transitory(flow_info: &FlowInfo) -> bool {
+    let Ok(value) = flow_info.locked.read() else {
+        return false;
+    };
+    let Some(nat_state) = value.nat_state.as_ref() else {
+        return false;
+    };
+    if let Some(state) = nat_state.extract_ref::<NatFlowState<Ipv4Addr>>() {
+        state.transitory.load(Ordering::Relaxed)
+    } else {
+        nat_state
+            .extract_ref::<NatFlowState<Ipv6Addr>>()
+            .is_some_and(|state| state.transitory.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug)]
+struct TrackedSession {
+    flow_key: FlowKey,
+    flow_info: Weak<FlowInfo>,
+    // Time the last packet of the session was seen at, when the session was queued
+    last_seen: Instant,
+}
+
+impl TrackedSession {
+    // Get the session if it is still active and transitory
+    fn get(&self) -> Option<Arc<FlowInfo>> {
+        let flow_info = self.flow_info.upgrade()?;
+        (flow_info.status() == FlowStatus::Active && is_transitory(&flow_info)).then_some(flow_info)
+    }
+}
+
+/// The transitory sessions created or updated by a [`StatefulNat`] instance, from the least to
+/// the most recently used.
+///
+/// The order is approximate: sessions are queued when they become transitory, and those that have
+/// seen packets since get a second chance when they reach the head of the queue.
+#[derive(Debug, Default)]
+pub(crate) struct TransitorySessions(VecDeque<TrackedSession>);
+
+impl TransitorySessions {
+    /// Track the session for `flow_key`, which just became transitory
+    pub(crate) fn track(&mut self, flow_key: FlowKey, flow_info: &Arc<FlowInfo>) {
+        self.prune();
+        if self.0.len() >= MAX_TRACKED {
+            self.0.pop_front();
+        }
+        self.0.push_back(TrackedSession {
+            flow_key,
+            flow_info: Arc::downgrade(flow_info),
+            last_seen: flow_info.last_seen(),
+        });
+    }
+
+    // Drop a few stale entries from the head of the queue, so that the queue does not keep growing
+    // with sessions that expired or got established while the table was not under pressure.
+    fn prune(&mut self) {
+        for _ in 0..MAX_PRUNE {
+            match self.0.front() {
+                Some(tracked) if tracked.get().is_none() => {
+                    self.0.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Remove and return the least recently used transitory session, if any
+    fn pop_lru(&mut self) -> Option<(FlowKey, Arc<FlowInfo>)> {
+        for _ in 0..MAX_EVICTION_SCAN {
+            let mut tracked = self.0.pop_front()?;
+            let Some(flow_info) = tracked.get() else {
+                continue;
+            };
+            let last_seen = flow_info.last_seen();
+            if last_seen > tracked.last_seen {
+                // Used since it was queued, give it a second chance
+                tracked.last_seen = last_seen;
+                self.0.push_back(tracked);
+                continue;
+            }
+            return Some((tracked.flow_key, flow_info));
+        }
+        None
+    }
+}
+
+impl StatefulNat {
+    // Get the number of sessions to remove for the session table to be back under the threshold
+    // for evicting transitory sessions: 90% of the capacity, if the table is bounded.
+    fn sessions_over_threshold(&self) -> usize {
+        self.sessions.capacity().map_or(0, |capacity| {
+            (self.sessions.len() + 1).saturating_sub(capacity / 10 * 9)
+        })
+    }
+
+    /// Evict up to `count` transitory sessions, if the session table is close to its capacity.
+    pub(crate) fn evict_transitory_sessions(&mut self, count: u64) {
+        // Counting the entries of the table sums the counters of all its partitions, which other
+        // workers keep updating: do it once
+        let mut excess = self.sessions_over_threshold();
+        let mut evicted = 0;
+        while evicted < count && excess > 0 {
+            let Some((flow_key, flow_info)) = self.transitory.pop_lru() else {
+                break;
+            };
+            if self.evict_session(&flow_key, &flow_info) {
+                excess -= 1;
+            }
+            evicted += 1;
+        }
+        if evicted > 0 {
+            self.allocator.counters().record_evictions(evicted);
+        }
+    }
+
+    // Evict a session, and tell if this removed an entry from the session table
+    fn evict_session(&self, flow_key: &FlowKey, flow_info: &Arc<FlowInfo>) -> bool {
+        debug!(
+            "{}: Evicting transitory session {}",
+            self.name(),
+            flow_key.data()
+        );
+        let removed = self
+            .sessions
+            .lookup(flow_key)
+            .is_some_and(|current| Arc::ptr_eq(&current, flow_info))
+            && self.sessions.remove(flow_key).is_some();
+        let _ = flow_info.update_status(FlowStatus::Expired);
+        // Release the allocated IP and port now, rather than when the flow gets reaped
+        if let Ok(mut value) = flow_info.locked.write() {
+            value.nat_state = None;
+        }
+        removed
+    }
+}
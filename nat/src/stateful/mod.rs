@@ -4,21 +4,28 @@
 mod allocator;
 mod allocator_writer;
 pub mod apalloc;
+mod eviction;
 mod natip;
+mod query;
+mod sip;
+mod stats;
 mod test;
+mod timeouts;
 
 use super::NatTranslationData;
+use crate::counters::{ActiveSession, NatVpcCounters};
 use crate::icmp_error_msg::{
     IcmpErrorMsgError, stateful_translate_icmp_inner, validate_checksums_icmp,
 };
 use crate::stateful::allocator::{AllocationResult, AllocatorError, NatAllocator};
-use crate::stateful::allocator_writer::NatAllocatorReader;
 use crate::stateful::apalloc::AllocatedIpPort;
 use crate::stateful::apalloc::{NatDefaultAllocator, NatIpWithBitmap};
 use crate::stateful::natip::NatIp;
-pub use allocator_writer::NatAllocatorWriter;
+use crate::stateful::timeouts::{SessionClass, SessionTimeouts};
+pub use allocator_writer::{NatAllocatorReader, NatAllocatorReaderFactory, NatAllocatorWriter};
 use concurrency::sync::Arc;
-use flow_info::{ExtractRef, FlowInfo};
+use concurrency::sync::atomic::{AtomicBool, Ordering};
+use flow_info::{Clock, ExtractRef, FlowInfo};
 use net::buffer::PacketBufferMut;
 use net::headers::{
     Net, Transport, TryHeaders, TryHeadersMut, TryInnerIp, TryIp, TryIpMut, TryTransportMut,
@@ -27,9 +34,10 @@ use net::packet::{DoneReason, Packet, VpcDiscriminant};
 use pipeline::NetworkFunction;
 use pkt_meta::flow_table::flow_key::{IcmpProtoKey, Uni};
 use pkt_meta::flow_table::{FlowKey, FlowKeyData, FlowTable, IpProtoKey};
+pub use query::{NatSession, NatSessionPage, NatSessionQuery, query_sessions};
+pub use stats::{NatAllocatorStats, NatPortUsage};
 use std::fmt::{Debug, Display};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::time::{Duration, Instant};
 
 #[allow(unused)]
 use tracing::{debug, error, warn};
@@ -67,21 +75,26 @@ pub enum StatefulNatError {
 struct NatFlowState<I: NatIpWithBitmap> {
     src_alloc: Option<AllocatedIpPort<I>>,
     dst_alloc: Option<AllocatedIpPort<I>>,
-    idle_timeout: Duration,
+    session_timeouts: SessionTimeouts,
+    // Whether the last packet of the session was a TCP packet opening or closing the connection
+    transitory: AtomicBool,
+    // Accounts for the session in the counters of the VPC that initiated it, for the state of the
+    // forward flow only
+    _session: Option<ActiveSession>,
 }
 
 impl<I: NatIpWithBitmap> Display for NatFlowState<I> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "({} {})[{}s]",
+            "({} {})[{}]",
             self.src_alloc
                 .as_ref()
                 .map_or(String::new(), |a| a.ip().to_string()),
             self.dst_alloc
                 .as_ref()
                 .map_or(String::new(), |a| a.ip().to_string()),
-            self.idle_timeout.as_secs()
+            self.session_timeouts
         )
     }
 }
@@ -93,6 +106,9 @@ pub struct StatefulNat {
     name: String,
     sessions: Arc<FlowTable>,
     allocator: NatAllocatorReader,
+    sip_dialogs: sip::SipDialogs,
+    transitory: eviction::TransitorySessions,
+    counters: Arc<NatVpcCounters>,
 }
 
 #[allow(clippy::new_without_default)]
@@ -107,6 +123,9 @@ impl StatefulNat {
                 name: name.to_string(),
                 sessions: Arc::new(FlowTable::default()),
                 allocator: allocator_reader,
+                sip_dialogs: sip::SipDialogs::default(),
+                transitory: eviction::TransitorySessions::default(),
+                counters: Arc::new(NatVpcCounters::new()),
             },
             allocator_writer,
         )
@@ -119,9 +138,39 @@ impl StatefulNat {
             name: name.to_string(),
             sessions: Arc::new(FlowTable::default()),
             allocator,
+            sip_dialogs: sip::SipDialogs::default(),
+            transitory: eviction::TransitorySessions::default(),
+            counters: Arc::new(NatVpcCounters::new()),
         }
     }
 
+    /// Use `sessions` as the session table, for example to share it with the flow lookup stage,
+    /// or to query the sessions with [`query_sessions`].
+    ///
+    /// This replaces the session table, and should be called before processing any packet.
+    #[must_use]
+    pub fn with_sessions(mut self, sessions: Arc<FlowTable>) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    /// Use `counters` for the per-VPC counters, for example to share them with other NAT stages
+    /// and export them as metrics.
+    #[must_use]
+    pub fn with_counters(mut self, counters: Arc<NatVpcCounters>) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    /// Use `clock` as the source of time for session timeouts, instead of the system clock.
+    ///
+    /// This replaces the session table, and should be called before processing any packet.
+    #[must_use]
+    pub fn with_clock(mut self, clock: std::sync::Arc<dyn Clock>) -> Self {
+        self.sessions = Arc::new(FlowTable::default().with_clock(clock));
+        self
+    }
+
     /// Get the name of this instance
     #[must_use]
     pub fn name(&self) -> &String {
@@ -148,16 +197,36 @@ impl StatefulNat {
     }
 
     // Look up for a session for a packet, based on attached flow key.
-    // On success, update session timeout.
+    // On success, update session timeout, based on the protocol (and for TCP, on the flags) of the
+    // packet, and return the translation data with the idle timeouts for the session.
     fn lookup_session<I: NatIpWithBitmap, Buf: PacketBufferMut>(
-        packet: &mut Packet<Buf>,
-    ) -> Option<NatTranslationData> {
-        let flow_info = packet.get_meta_mut().flow_info.as_mut()?;
-        let value = flow_info.locked.read().unwrap();
-        let state = value.nat_state.as_ref()?.extract_ref::<NatFlowState<I>>()?;
-        flow_info.extend_expiry(state.idle_timeout).ok()?;
-        let translation_data = Self::get_translation_info(&state.src_alloc, &state.dst_alloc);
-        Some(translation_data)
+        &mut self,
+        packet: &Packet<Buf>,
+        flow_key: &FlowKey,
+    ) -> Option<(NatTranslationData, SessionTimeouts)> {
+        let class = SessionClass::from_packet(packet);
+        let flow_info = packet.get_meta().flow_info.as_ref()?;
+        let (translation_data, session_timeouts, became_transitory) = {
+            let value = flow_info.locked.read().unwrap();
+            let state = value.nat_state.as_ref()?.extract_ref::<NatFlowState<I>>()?;
+            flow_info
+                .extend_expiry(state.session_timeouts.get(class))
+                .ok()?;
+            let transitory = class == SessionClass::TcpTransitory;
+            let was_transitory = state.transitory.load(Ordering::Relaxed);
+            if transitory != was_transitory {
+                state.transitory.store(transitory, Ordering::Relaxed);
+            }
+            (
+                Self::get_translation_info(&state.src_alloc, &state.dst_alloc),
+                state.session_timeouts,
+                transitory && !was_transitory,
+            )
+        };
+        if became_transitory {
+            self.transitory.track(*flow_key, flow_info);
+        }
+        Some((translation_data, session_timeouts))
     }
 
     // Look up for a session by passing the parameters that make up a flow key.
@@ -172,7 +241,8 @@ impl StatefulNat {
         dst_vpcd: VpcDiscriminant,
         dst_ip: IpAddr,
         proto_key_info: IpProtoKey,
-    ) -> Option<(NatTranslationData, Duration)> {
+    ) -> Option<(NatTranslationData, std::time::Duration)> {
+        let class = SessionClass::from_proto_key(&proto_key_info);
         let flow_key = FlowKey::uni(
             Some(src_vpcd),
             src_ip,
@@ -184,19 +254,16 @@ impl StatefulNat {
         let value = flow_info.locked.read().unwrap();
         let state = value.nat_state.as_ref()?.extract_ref::<NatFlowState<I>>()?;
         let translation_data = Self::get_translation_info(&state.src_alloc, &state.dst_alloc);
-        Some((translation_data, state.idle_timeout))
+        Some((translation_data, state.session_timeouts.get(class)))
     }
 
+    // Create a session for `flow_key`, with the idle timeout for a packet of class `class`.
     fn create_session<I: NatIpWithBitmap>(
         &mut self,
         flow_key: &FlowKey,
         state: NatFlowState<I>,
-        idle_timeout: Duration,
+        class: SessionClass,
     ) {
-        fn session_timeout_time(timeout: Duration) -> Instant {
-            Instant::now() + timeout
-        }
-
         debug!(
             "{}: Creating new flow session entry: {} -> {}",
             self.name(),
@@ -204,10 +271,19 @@ impl StatefulNat {
             state
         );
 
-        let flow_info = FlowInfo::new(session_timeout_time(idle_timeout));
+        let now = self.sessions.now();
+        let transitory = class == SessionClass::TcpTransitory;
+        state.transitory.store(transitory, Ordering::Relaxed);
+        let flow_info = Arc::new(FlowInfo::new_at(
+            now,
+            now + state.session_timeouts.get(class),
+        ));
         flow_info.locked.write().unwrap().nat_state = Some(Box::new(state));
 
-        self.sessions.insert(*flow_key, flow_info);
+        self.sessions.reinsert(*flow_key, &flow_info);
+        if transitory {
+            self.transitory.track(*flow_key, &flow_info);
+        }
     }
 
     #[allow(clippy::unnecessary_wraps)]
@@ -236,7 +312,7 @@ impl StatefulNat {
                 .try_transport_mut()
                 .ok_or(StatefulNatError::BadTransportHeader)?;
             match transport {
-                Transport::Tcp(_) | Transport::Udp(_) => {
+                Transport::Tcp(_) | Transport::Udp(_) | Transport::Sctp(_) => {
                     transport
                         .try_set_source(
                             target_src_port.try_into().map_err(|_| {
@@ -262,7 +338,7 @@ impl StatefulNat {
                 .try_transport_mut()
                 .ok_or(StatefulNatError::BadTransportHeader)?;
             match transport {
-                Transport::Tcp(_) | Transport::Udp(_) => {
+                Transport::Tcp(_) | Transport::Udp(_) | Transport::Sctp(_) => {
                     transport
                         .try_set_destination(
                             target_dst_port.try_into().map_err(|_| {
@@ -295,17 +371,22 @@ impl StatefulNat {
 
     fn new_states_from_alloc<I: NatIpWithBitmap>(
         alloc: AllocationResult<AllocatedIpPort<I>>,
-        idle_timeout: Duration,
+        session_timeouts: SessionTimeouts,
+        session: ActiveSession,
     ) -> (NatFlowState<I>, NatFlowState<I>) {
         let forward_state = NatFlowState {
             src_alloc: alloc.src,
             dst_alloc: alloc.dst,
-            idle_timeout,
+            session_timeouts,
+            transitory: AtomicBool::new(false),
+            _session: Some(session),
         };
         let reverse_state = NatFlowState {
             src_alloc: alloc.return_src,
             dst_alloc: alloc.return_dst,
-            idle_timeout,
+            session_timeouts,
+            transitory: AtomicBool::new(false),
+            _session: None,
         };
         (forward_state, reverse_state)
     }
@@ -524,8 +605,10 @@ impl StatefulNat {
         dst_vpc_id: VpcDiscriminant,
     ) -> Result<bool, StatefulNatError> {
         // Hot path: if we have a session, directly translate the address already
-        if let Some(state) = Self::lookup_session::<I, Buf>(packet) {
-            return Self::stateful_translate::<Buf>(packet, &state).and(Ok(true));
+        if let Some((state, session_timeouts)) = self.lookup_session::<I, Buf>(packet, flow_key) {
+            Self::stateful_translate::<Buf>(packet, &state)?;
+            self.sip_alg::<Buf, I>(packet, flow_key, &state, session_timeouts);
+            return Ok(true);
         }
 
         match self.deal_with_icmp_error_msg::<Buf, I>(packet, flow_key) {
@@ -540,26 +623,45 @@ impl StatefulNat {
             return Err(StatefulNatError::NoAllocator);
         };
 
+        // Make room for the new sessions if the session table is close to full. Do it before
+        // allocating, as evicted sessions release their IPs and ports.
+        self.evict_transitory_sessions(2);
+
         // Else, if we need NAT for this packet, create a new session and translate the address
-        let alloc =
-            I::allocate(allocator, flow_key).map_err(StatefulNatError::AllocationFailure)?;
+        let alloc = I::allocate(allocator, flow_key).map_err(|e| {
+            self.allocator.counters().record(&e);
+            if matches!(
+                e,
+                AllocatorError::NoFreeIp
+                    | AllocatorError::NoPortBlock
+                    | AllocatorError::NoFreePort(_)
+            ) {
+                self.counters.record_allocation_failure(src_vpc_id);
+            }
+            StatefulNatError::AllocationFailure(e)
+        })?;
 
         if alloc.src.is_none() && alloc.dst.is_none() {
             // No NAT for this tuple, leave the packet unchanged - Do not drop it
             return Ok(false);
         }
         // Given that at least one of alloc.src or alloc.dst is set, we should always have at
-        // least one timeout set.
-        let idle_timeout = alloc.idle_timeout().unwrap_or_else(|| unreachable!());
+        // least one set of timeouts.
+        let session_timeouts = alloc.session_timeouts().unwrap_or_else(|| unreachable!());
+        let class = SessionClass::from_packet(packet);
 
         let translation_info = Self::get_translation_info(&alloc.src, &alloc.dst);
         let reverse_flow_key = Self::new_reverse_session(flow_key, &alloc, src_vpc_id, dst_vpc_id)?;
-        let (forward_state, reverse_state) = Self::new_states_from_alloc(alloc, idle_timeout);
+        let session = self.counters.new_session(src_vpc_id);
+        let (forward_state, reverse_state) =
+            Self::new_states_from_alloc(alloc, session_timeouts, session);
 
-        self.create_session(flow_key, forward_state, idle_timeout);
-        self.create_session(&reverse_flow_key, reverse_state, idle_timeout);
+        self.create_session(flow_key, forward_state, class);
+        self.create_session(&reverse_flow_key, reverse_state, class);
 
-        Self::stateful_translate::<Buf>(packet, &translation_info).and(Ok(true))
+        Self::stateful_translate::<Buf>(packet, &translation_info)?;
+        self.sip_alg::<Buf, I>(packet, flow_key, &translation_info, session_timeouts);
+        Ok(true)
     }
 
     fn nat_packet<Buf: PacketBufferMut>(
@@ -605,6 +707,7 @@ impl StatefulNat {
             }
             Ok(true) => {
                 packet.get_meta_mut().set_checksum_refresh(true);
+                self.counters.record_translation(src_vpc_id);
             }
             Ok(false) => {}
         }
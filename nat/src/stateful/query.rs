@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Read-only queries over the stateful NAT sessions.
+//!
+//! Queries work on a snapshot of the session table (see [`FlowTable::snapshot`]), and never hold
+//! the table locks while building the results. Matching sessions are sorted by flow key, so that
+//! the results can be fetched page by page.
+
+use super::{NatFlowState, StatefulNat};
+use crate::NatPort;
+use crate::stateful::apalloc::NatIpWithBitmap;
+use flow_info::{ExtractRef, FlowInfo};
+use pkt_meta::flow_table::{FlowEntry, FlowFilter, FlowTable};
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// A query for stateful NAT sessions
+#[derive(Debug, Clone)]
+pub struct NatSessionQuery {
+    /// Select the sessions for the flows matching this filter, on their original tuple
+    pub filter: FlowFilter,
+    /// Number of matching sessions to skip
+    pub offset: usize,
+    /// Maximum number of sessions to return
+    pub limit: usize,
+}
+
+impl NatSessionQuery {
+    /// Default maximum number of sessions returned by a query
+    pub const DEFAULT_LIMIT: usize = 100;
+
+    #[must_use]
+    pub fn new(filter: FlowFilter) -> Self {
+        Self {
+            filter,
+            offset: 0,
+            limit: Self::DEFAULT_LIMIT,
+        }
+    }
+    #[must_use]
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+    #[must_use]
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+impl Default for NatSessionQuery {
+    fn default() -> Self {
+        Self::new(FlowFilter::default())
+    }
+}
+
+/// A snapshot of a stateful NAT session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NatSession {
+    /// The flow, with its original tuple, its age and its counters
+    pub flow: FlowEntry,
+    pub translated_src_ip: Option<IpAddr>,
+    pub translated_dst_ip: Option<IpAddr>,
+    /// Translated source port, or ICMP identifier
+    pub translated_src_port: Option<u16>,
+    /// Translated destination port, or ICMP identifier
+    pub translated_dst_port: Option<u16>,
+}
+
+impl NatSession {
+    fn new<I: NatIpWithBitmap>(flow: FlowEntry, state: &NatFlowState<I>) -> Self {
+        let translation = StatefulNat::get_translation_info(&state.src_alloc, &state.dst_alloc);
+        Self {
+            flow,
+            translated_src_ip: translation.src_addr,
+            translated_dst_ip: translation.dst_addr,
+            translated_src_port: translation.src_port.map(NatPort::as_u16),
+            translated_dst_port: translation.dst_port.map(NatPort::as_u16),
+        }
+    }
+
+    fn from_flow_info(flow: FlowEntry, flow_info: &FlowInfo) -> Option<Self> {
+        let value = flow_info.locked.read().ok()?;
+        let nat_state = value.nat_state.as_ref()?;
+        if let Some(state) = nat_state.extract_ref::<NatFlowState<Ipv4Addr>>() {
+            Some(Self::new(flow, state))
+        } else {
+            let state = nat_state.extract_ref::<NatFlowState<Ipv6Addr>>()?;
+            Some(Self::new(flow, state))
+        }
+    }
+
+    /// Get the source address and port (or ICMP identifier) of the flow after translation
+    #[must_use]
+    pub fn translated_src(&self) -> (IpAddr, Option<u16>) {
+        (
+            self.translated_src_ip.unwrap_or(self.flow.src_ip),
+            self.translated_src_port
+                .or(self.flow.src_port)
+                .or(self.flow.icmp_id),
+        )
+    }
+
+    /// Get the destination address and port (or ICMP identifier) of the flow after translation
+    #[must_use]
+    pub fn translated_dst(&self) -> (IpAddr, Option<u16>) {
+        (
+            self.translated_dst_ip.unwrap_or(self.flow.dst_ip),
+            self.translated_dst_port
+                .or(self.flow.dst_port)
+                .or(self.flow.icmp_id),
+        )
+    }
+}
+
+fn fmt_endpoint((ip, port): (IpAddr, Option<u16>)) -> String {
+    match port {
+        Some(port) => format!("{ip}:{port}"),
+        None => format!("{ip}"),
+    }
+}
+
+impl Display for NatSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\n    translated: ({}, {})",
+            self.flow,
+            fmt_endpoint(self.translated_src()),
+            fmt_endpoint(self.translated_dst())
+        )
+    }
+}
+
+/// A page of results for a [`NatSessionQuery`]
+#[derive(Debug, Clone, Default)]
+pub struct NatSessionPage {
+    pub sessions: Vec<NatSession>,
+    /// Offset of the first session of this page among all the matching sessions
+    pub offset: usize,
+    /// Total number of matching sessions
+    pub total: usize,
+}
+
+impl NatSessionPage {
+    /// Get the offset to use in the query for the next page, if any
+    #[must_use]
+    pub fn next_offset(&self) -> Option<usize> {
+        let next = self.offset + self.sessions.len();
+        (next < self.total).then_some(next)
+    }
+}
+
+impl Display for NatSessionPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for session in &self.sessions {
+            writeln!(f, "{session}")?;
+        }
+        if self.sessions.is_empty() {
+            write!(f, "No sessions ({} in total)", self.total)?;
+        } else {
+            write!(
+                f,
+                "Sessions {}-{} of {}",
+                self.offset + 1,
+                self.offset + self.sessions.len(),
+                self.total
+            )?;
+        }
+        if let Some(next) = self.next_offset() {
+            write!(f, " (next page at offset {next})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the stateful NAT sessions from `sessions` that match `query`.
+///
+/// Flows with no NAT state in the table are ignored.
+///
+/// # Panics
+///
+/// Panics if this thread already holds the read lock on a partition of the table, or if a
+/// partition lock is poisoned.
+#[must_use]
+pub fn query_sessions(sessions: &FlowTable, query: &NatSessionQuery) -> NatSessionPage {
+    let now = sessions.now();
+    let mut flows = sessions.snapshot(&query.filter);
+    flows.sort_unstable_by(|(key1, _), (key2, _)| key1.cmp(key2));
+
+    let mut page = NatSessionPage {
+        offset: query.offset,
+        ..NatSessionPage::default()
+    };
+    for (flow_key, flow_info) in &flows {
+        let flow = FlowEntry::new(flow_key, flow_info, now);
+        let Some(session) = NatSession::from_flow_info(flow, flow_info) else {
+            continue;
+        };
+        if page.total >= query.offset && page.sessions.len() < query.limit {
+            page.sessions.push(session);
+        }
+        page.total += 1;
+    }
+    page
+}
@@ -0,0 +1,741 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Application-level gateway (ALG) for the Session Initiation Protocol ([SIP]) over UDP.
+//!
+//! SIP user agents advertise their own addresses in the signalling messages they send: in the
+//! `Via` and `Contact` headers, to receive responses and subsequent requests, and in the session
+//! descriptions ([SDP]) carried in the message bodies, to receive the media (RTP) streams. Behind
+//! source NAT, these addresses are private and the peer cannot reach them. For SIP messages going
+//! through stateful NAT, the ALG:
+//!
+//! - Rewrites the address of the user agent behind the NAT in the SIP headers and SDP bodies: the
+//!   original address is replaced with the translated one in outgoing messages, and the other way
+//!   around in incoming messages.
+//! - Allocates translated ports for the media streams that the user agent offers or accepts, and
+//!   advertises them in its session descriptions.
+//! - Opens pinholes in the session table for these media streams once the session descriptions of
+//!   both sides are known, so that RTP packets get translated in both directions.
+//!
+//! Limitations: SIP over TCP, multipart bodies, and destination NAT for the media streams are not
+//! supported. Media streams from the offer and from the answer are paired by their position in the
+//! session descriptions, as mandated by RFC 3264. RTCP ports are not handled.
+//!
+//! [SIP]: https://datatracker.ietf.org/doc/html/rfc3261
+//! [SDP]: https://datatracker.ietf.org/doc/html/rfc4566
+
+use super::{NatFlowState, StatefulNat};
+use crate::NatTranslationData;
+use crate::stateful::allocator::AllocatorError;
+use crate::stateful::apalloc::{AllocatedIpPort, NatIpWithBitmap};
+use crate::stateful::natip::NatIp;
+use crate::stateful::timeouts::{SessionClass, SessionTimeouts};
+use concurrency::sync::atomic::AtomicBool;
+use flow_info::{ExtractMut, ExtractRef, FlowInfoItem};
+use net::buffer::PacketBufferMut;
+use net::packet::{Packet, VpcDiscriminant};
+use net::udp::UdpPort;
+use pkt_meta::flow_table::{FlowKey, IpProtoKey, UdpProtoKey};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+use tracing::debug;
+
+/// Well-known port for SIP over UDP
+pub(crate) const SIP_PORT: u16 = 5060;
+
+/// Maximum number of SIP dialogs tracked by a [`StatefulNat`] instance
+const MAX_SIP_DIALOGS: usize = 4096;
+
+///////////////////////////////////////////////////////////////////////////////
+// SIP message rewriting
+///////////////////////////////////////////////////////////////////////////////
+
+/// A SIP message, with the addresses rewritten
+#[derive(Debug)]
+struct SipRewrite {
+    payload: Vec<u8>,
+    call_id: String,
+    /// Media endpoints advertised in the session description of the original message, if any, in
+    /// the order of the media descriptions
+    media: Option<Vec<SocketAddr>>,
+}
+
+// Rewrite the SIP message in payload, replacing endpoint `from` with endpoint `to` in the Via and
+// Contact headers and address `from.ip()` with `to.ip()` in the session description.
+//
+// For each media stream advertised with address `from.ip()`, `media_port` is called with the
+// index of the media description and the advertised endpoint. It returns the port to advertise
+// instead, if any.
+//
+// Returns None if payload is not a SIP message, or if it has no Call-ID.
+fn rewrite_message(
+    payload: &[u8],
+    from: SocketAddr,
+    to: SocketAddr,
+    media_port: impl FnMut(usize, SocketAddr) -> Option<u16>,
+) -> Option<SipRewrite> {
+    let text = std::str::from_utf8(payload).ok()?;
+    let (head, body) = text.split_once("\r\n\r\n")?;
+    let mut lines = head.split("\r\n");
+    let start_line = lines.next()?;
+    if !start_line.starts_with("SIP/2.0 ") && !start_line.ends_with(" SIP/2.0") {
+        return None;
+    }
+
+    let mut call_id = None;
+    let mut is_sdp = false;
+    let mut headers = Vec::new();
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            headers.push(line.to_string());
+            continue;
+        };
+        // Header names are case-insensitive, and most have a compact form
+        match name.trim().to_ascii_lowercase().as_str() {
+            "call-id" | "i" => {
+                call_id = Some(value.trim().to_string());
+                headers.push(line.to_string());
+            }
+            "content-type" | "c" => {
+                is_sdp = value.trim().eq_ignore_ascii_case("application/sdp");
+                headers.push(line.to_string());
+            }
+            "via" | "v" | "contact" | "m" => {
+                headers.push(format!("{name}:{}", replace_endpoint(value, from, to)));
+            }
+            // Updated below, once we know the length of the new body
+            "content-length" | "l" => {}
+            _ => headers.push(line.to_string()),
+        }
+    }
+    let call_id = call_id?;
+
+    let (body, media) = if is_sdp {
+        let (body, media) = rewrite_sdp(body, from.ip(), to.ip(), media_port);
+        (body, Some(media))
+    } else {
+        (body.to_string(), None)
+    };
+
+    let mut message = String::with_capacity(payload.len() + 64);
+    message.push_str(start_line);
+    message.push_str("\r\n");
+    for header in headers {
+        message.push_str(&header);
+        message.push_str("\r\n");
+    }
+    let _ = write!(message, "Content-Length: {}\r\n\r\n", body.len());
+    message.push_str(&body);
+
+    Some(SipRewrite {
+        payload: message.into_bytes(),
+        call_id,
+        media,
+    })
+}
+
+// Format an address as it appears in the host part of SIP URIs and Via headers
+fn sip_host(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{ip}]"),
+    }
+}
+
+// Replace all occurrences of endpoint `from` with endpoint `to` in a header value. Occurrences of
+// the address of `from` with another port are left untouched.
+fn replace_endpoint(value: &str, from: SocketAddr, to: SocketAddr) -> String {
+    let from_host = sip_host(from.ip());
+    let to_host = sip_host(to.ip());
+    let mut result = String::with_capacity(value.len() + 16);
+    let mut rest = value;
+    while let Some(position) = rest.find(&from_host) {
+        let (before, after) = rest.split_at(position);
+        let after = &after[from_host.len()..];
+        result.push_str(before);
+
+        // Make sure we matched a full address, and not "10.0.0.1" in "10.0.0.10", for example.
+        // IPv6 addresses are enclosed in brackets, so they always match fully.
+        let is_address_char = |c: char| c.is_ascii_digit() || c == '.';
+        let partial_match = from.is_ipv4()
+            && (before.chars().next_back().is_some_and(is_address_char)
+                || after.chars().next().is_some_and(is_address_char));
+        if partial_match {
+            result.push_str(&from_host);
+            rest = after;
+            continue;
+        }
+
+        let port_digits = after
+            .strip_prefix(':')
+            .map(|port| port.chars().take_while(char::is_ascii_digit).count());
+        match port_digits {
+            Some(digits) if digits > 0 => {
+                if after[1..=digits].parse::<u16>().ok() != Some(from.port()) {
+                    // Same address, but for another endpoint
+                    result.push_str(&from_host);
+                    rest = after;
+                    continue;
+                }
+                let _ = write!(result, "{to_host}:{}", to.port());
+                rest = &after[1 + digits..];
+            }
+            _ => {
+                // No port, the default port for SIP is implied
+                if from.port() != SIP_PORT {
+                    result.push_str(&from_host);
+                } else if to.port() == SIP_PORT {
+                    result.push_str(&to_host);
+                } else {
+                    let _ = write!(result, "{to_host}:{}", to.port());
+                }
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// Rewrite a session description, replacing address `from` with `to` in the origin (o=) and
+// connection (c=) fields, and the ports of the media descriptions (m=) with address `from`, as
+// returned by `media_port`.
+//
+// Returns the new session description and the endpoints of the media streams of the original one.
+fn rewrite_sdp(
+    body: &str,
+    from: IpAddr,
+    to: IpAddr,
+    mut media_port: impl FnMut(usize, SocketAddr) -> Option<u16>,
+) -> (String, Vec<SocketAddr>) {
+    let lines: Vec<&str> = body.lines().collect();
+    let media = sdp_media_endpoints(&lines);
+    let from = from.to_string();
+    let to = to.to_string();
+
+    let mut result = String::with_capacity(body.len() + 16);
+    let mut media_index = 0;
+    for line in lines {
+        if let Some(fields) = line.strip_prefix("c=").or(line.strip_prefix("o=")) {
+            // The connection address is the last field of both c= and o= lines
+            match fields.rsplit_once(' ') {
+                Some((head, address)) if address == from => {
+                    let _ = write!(result, "{}{head} {to}", &line[..2]);
+                }
+                _ => result.push_str(line),
+            }
+        } else if let Some(fields) = line.strip_prefix("m=") {
+            let endpoint = media.get(media_index).copied();
+            media_index += 1;
+            let mut fields = fields.splitn(3, ' ');
+            let (Some(kind), Some(_port), Some(tail)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                result.push_str(line);
+                result.push_str("\r\n");
+                continue;
+            };
+            match endpoint
+                .filter(|endpoint| endpoint.ip().to_string() == from && endpoint.port() != 0)
+                .and_then(|endpoint| media_port(media_index - 1, endpoint))
+            {
+                Some(port) => {
+                    let _ = write!(result, "m={kind} {port} {tail}");
+                }
+                None => result.push_str(line),
+            }
+        } else {
+            result.push_str(line);
+        }
+        result.push_str("\r\n");
+    }
+    (result, media)
+}
+
+// Get the endpoints of the media streams described in a session description. The address for a
+// media stream is the one from the media-level connection field, if any, or the one from the
+// session-level connection field otherwise.
+//
+// Media descriptions we fail to parse get an unspecified endpoint, to preserve the pairing between
+// the media descriptions of the offer and the answer.
+fn sdp_media_endpoints(lines: &[&str]) -> Vec<SocketAddr> {
+    let connection_address = |line: &str| -> Option<IpAddr> {
+        let mut fields = line.strip_prefix("c=")?.split(' ');
+        let (_net_type, _addr_type, address) = (fields.next()?, fields.next()?, fields.next()?);
+        address.parse().ok()
+    };
+    let unspecified = SocketAddr::from(([0, 0, 0, 0], 0));
+
+    let mut session_address = None;
+    // Port and media-level address, for each media description
+    let mut media: Vec<(Option<u16>, Option<IpAddr>)> = Vec::new();
+    for line in lines {
+        if let Some(fields) = line.strip_prefix("m=") {
+            // Ignore port counts ("port/count")
+            let port = fields
+                .split(' ')
+                .nth(1)
+                .and_then(|port| port.split('/').next())
+                .and_then(|port| port.parse().ok());
+            media.push((port, None));
+        } else if let Some(address) = connection_address(line) {
+            match media.last_mut() {
+                Some((_, media_address)) => *media_address = Some(address),
+                None => session_address = Some(address),
+            }
+        }
+    }
+    media
+        .into_iter()
+        .map(
+            |(port, address)| match (port, address.or(session_address)) {
+                (Some(port), Some(address)) => SocketAddr::new(address, port),
+                _ => unspecified,
+            },
+        )
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// SIP dialogs
+///////////////////////////////////////////////////////////////////////////////
+
+/// A media stream of the user agent behind the NAT
+#[derive(Debug)]
+struct LocalMedia<I: NatIpWithBitmap> {
+    /// Endpoint advertised by the user agent
+    original: SocketAddr,
+    /// Translated port advertised to the peer
+    port: u16,
+    /// Allocations for the source NAT of the stream, and for the reverse path, until we open the
+    /// pinholes
+    alloc: Option<(AllocatedIpPort<I>, AllocatedIpPort<I>)>,
+}
+
+/// Media streams negotiated within a SIP dialog, identified by its Call-ID
+#[derive(Debug)]
+struct SipDialog<I: NatIpWithBitmap> {
+    expiry: Instant,
+    // VPC of the user agent behind the NAT, and VPC of the peer
+    private_vpcd: VpcDiscriminant,
+    public_vpcd: VpcDiscriminant,
+    local: Vec<Option<LocalMedia<I>>>,
+    remote: Vec<SocketAddr>,
+}
+
+/// The SIP dialogs tracked by the ALG, for IPv4 or IPv6 (they are type-erased)
+#[derive(Debug, Default)]
+pub(crate) struct SipDialogs(HashMap<String, Box<dyn FlowInfoItem>>);
+
+impl SipDialogs {
+    fn get_mut<I: NatIpWithBitmap>(&mut self, call_id: &str) -> Option<&mut SipDialog<I>> {
+        self.0.get_mut(call_id)?.extract_mut::<SipDialog<I>>()
+    }
+
+    fn get_or_insert<I: NatIpWithBitmap>(
+        &mut self,
+        call_id: &str,
+        now: Instant,
+        private_vpcd: VpcDiscriminant,
+        public_vpcd: VpcDiscriminant,
+    ) -> Option<&mut SipDialog<I>> {
+        if self.get_mut::<I>(call_id).is_none() {
+            self.0.retain(|_, dialog| {
+                dialog
+                    .extract_ref::<SipDialog<I>>()
+                    .is_none_or(|dialog| dialog.expiry > now)
+            });
+            if self.0.len() >= MAX_SIP_DIALOGS {
+                return None;
+            }
+            let dialog = SipDialog::<I> {
+                expiry: now,
+                private_vpcd,
+                public_vpcd,
+                local: Vec::new(),
+                remote: Vec::new(),
+            };
+            self.0.insert(call_id.to_string(), Box::new(dialog));
+        }
+        self.get_mut(call_id)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// StatefulNat integration
+///////////////////////////////////////////////////////////////////////////////
+
+impl StatefulNat {
+    /// Run the SIP ALG on a packet that we just translated with `translation`, if it is a SIP
+    /// message. Failures are not fatal: the packet is then forwarded without further changes.
+    pub(super) fn sip_alg<Buf: PacketBufferMut, I: NatIpWithBitmap>(
+        &mut self,
+        packet: &mut Packet<Buf>,
+        flow_key: &FlowKey,
+        translation: &NatTranslationData,
+        session_timeouts: SessionTimeouts,
+    ) {
+        let data = flow_key.data();
+        let IpProtoKey::Udp(ports) = data.proto_key_info() else {
+            return;
+        };
+        let (src_port, dst_port) = (ports.src_port.as_u16(), ports.dst_port.as_u16());
+        if src_port != SIP_PORT && dst_port != SIP_PORT {
+            return;
+        }
+        let (Some(src_vpcd), Some(dst_vpcd)) = (data.src_vpcd(), data.dst_vpcd()) else {
+            return;
+        };
+
+        // Outgoing messages, from the user agent behind the NAT, get their source translated.
+        // Incoming messages, sent to the translated address, get their destination translated.
+        let (from, to, outgoing) = match translation {
+            NatTranslationData {
+                src_addr: Some(ip),
+                src_port: Some(port),
+                ..
+            } => (
+                SocketAddr::new(*data.src_ip(), src_port),
+                SocketAddr::new(*ip, port.as_u16()),
+                true,
+            ),
+            NatTranslationData {
+                dst_addr: Some(ip),
+                dst_port: Some(port),
+                ..
+            } => (
+                SocketAddr::new(*data.dst_ip(), dst_port),
+                SocketAddr::new(*ip, port.as_u16()),
+                false,
+            ),
+            _ => return,
+        };
+        let (private_vpcd, public_vpcd, peer_ip) = if outgoing {
+            (src_vpcd, dst_vpcd, *data.dst_ip())
+        } else {
+            (dst_vpcd, src_vpcd, *data.src_ip())
+        };
+        if packet.payload().num_segments() > 1 {
+            return;
+        }
+
+        // Find the Call-ID first, to reuse the ports we already advertised for this dialog, for
+        // retransmissions or for an updated offer
+        let Some(call_id) = rewrite_message(packet.payload().as_ref(), from, to, |_, _| None)
+            .map(|rewrite| rewrite.call_id)
+        else {
+            return;
+        };
+        let now = self.sessions.now();
+        let mut dialog_local = self
+            .sip_dialogs
+            .get_mut::<I>(&call_id)
+            .map(|dialog| std::mem::take(&mut dialog.local))
+            .unwrap_or_default();
+
+        let Some(rewrite) = rewrite_message(packet.payload().as_ref(), from, to, |index, media| {
+            if !outgoing {
+                return None;
+            }
+            if let Some(Some(local)) = dialog_local.get(index)
+                && local.original == media
+            {
+                return Some(local.port);
+            }
+            let (src, return_dst) =
+                self.allocate_media::<I>(media, peer_ip, private_vpcd, public_vpcd)?;
+            let port = src.port().as_u16();
+            if dialog_local.len() <= index {
+                dialog_local.resize_with(index + 1, || None);
+            }
+            dialog_local[index] = Some(LocalMedia {
+                original: media,
+                port,
+                alloc: Some((src, return_dst)),
+            });
+            Some(port)
+        }) else {
+            return;
+        };
+
+        if let Err(e) = packet.set_udp_payload(&rewrite.payload) {
+            debug!("{}: SIP ALG failed to rewrite message: {e}", self.name());
+            return;
+        }
+
+        let Some(dialog) =
+            self.sip_dialogs
+                .get_or_insert::<I>(&call_id, now, private_vpcd, public_vpcd)
+        else {
+            debug!(
+                "{}: Too many SIP dialogs, not tracking {call_id}",
+                self.name()
+            );
+            return;
+        };
+        dialog.expiry = now + session_timeouts.udp();
+        dialog.local = dialog_local;
+        if let (false, Some(media)) = (outgoing, rewrite.media) {
+            dialog.remote = media;
+        }
+        self.open_media_pinholes::<I>(&call_id, session_timeouts);
+    }
+
+    // Open pinholes for the media streams of a dialog for which we know both endpoints, and which
+    // we have not processed yet
+    fn open_media_pinholes<I: NatIpWithBitmap>(
+        &mut self,
+        call_id: &str,
+        session_timeouts: SessionTimeouts,
+    ) {
+        let Some(dialog) = self.sip_dialogs.get_mut::<I>(call_id) else {
+            return;
+        };
+        let pinholes = dialog
+            .local
+            .iter_mut()
+            .zip(dialog.remote.iter())
+            .filter_map(|(local, remote)| {
+                let local = local.as_mut()?;
+                if remote.port() == 0 || remote.ip().is_unspecified() {
+                    return None;
+                }
+                Some((local.original, *remote, local.alloc.take()?))
+            })
+            .collect::<Vec<_>>();
+        let (private_vpcd, public_vpcd) = (dialog.private_vpcd, dialog.public_vpcd);
+        for (original, remote, (src, return_dst)) in pinholes {
+            self.open_media_pinhole(
+                original,
+                remote,
+                src,
+                return_dst,
+                private_vpcd,
+                public_vpcd,
+                session_timeouts,
+            );
+        }
+    }
+
+    // Allocate a translated address and port for a media stream of the user agent behind the NAT,
+    // and reserve the original endpoint for the reverse path. We don't know the endpoint of the
+    // peer for the media stream yet: use the address of the SIP peer to look up the NAT pools.
+    fn allocate_media<I: NatIpWithBitmap>(
+        &self,
+        media: SocketAddr,
+        peer_ip: IpAddr,
+        private_vpcd: VpcDiscriminant,
+        public_vpcd: VpcDiscriminant,
+    ) -> Option<(AllocatedIpPort<I>, AllocatedIpPort<I>)> {
+        let allocator = self.allocator.get()?;
+        let port = UdpPort::new_checked(media.port()).ok()?;
+        let flow_key = FlowKey::uni(
+            Some(private_vpcd),
+            media.ip(),
+            Some(public_vpcd),
+            peer_ip,
+            IpProtoKey::Udp(UdpProtoKey {
+                src_port: port,
+                dst_port: port,
+            }),
+        );
+        let alloc = I::allocate(allocator, &flow_key)
+            .inspect_err(|e: &AllocatorError| {
+                self.allocator.counters().record(e);
+                debug!(
+                    "{}: SIP ALG failed to allocate for {media}: {e}",
+                    self.name()
+                );
+            })
+            .ok()?;
+        Some((alloc.src?, alloc.return_dst?))
+    }
+
+    // Create the sessions for a media stream between endpoint original, translated into the
+    // endpoint from allocation src, and endpoint remote.
+    #[allow(clippy::too_many_arguments)]
+    fn open_media_pinhole<I: NatIpWithBitmap>(
+        &mut self,
+        original: SocketAddr,
+        remote: SocketAddr,
+        src: AllocatedIpPort<I>,
+        return_dst: AllocatedIpPort<I>,
+        private_vpcd: VpcDiscriminant,
+        public_vpcd: VpcDiscriminant,
+        session_timeouts: SessionTimeouts,
+    ) {
+        let (Ok(original_port), Ok(remote_port), Ok(translated_port)) = (
+            UdpPort::new_checked(original.port()),
+            UdpPort::new_checked(remote.port()),
+            UdpPort::new_checked(src.port().as_u16()),
+        ) else {
+            return;
+        };
+        let forward_key = FlowKey::uni(
+            Some(private_vpcd),
+            original.ip(),
+            Some(public_vpcd),
+            remote.ip(),
+            IpProtoKey::Udp(UdpProtoKey {
+                src_port: original_port,
+                dst_port: remote_port,
+            }),
+        );
+        let reverse_key = FlowKey::uni(
+            Some(public_vpcd),
+            remote.ip(),
+            Some(private_vpcd),
+            src.ip().to_ip_addr(),
+            IpProtoKey::Udp(UdpProtoKey {
+                src_port: remote_port,
+                dst_port: translated_port,
+            }),
+        );
+        let forward_state = NatFlowState {
+            src_alloc: Some(src),
+            dst_alloc: None,
+            session_timeouts,
+            transitory: AtomicBool::new(false),
+            _session: Some(self.counters.new_session(private_vpcd)),
+        };
+        let reverse_state = NatFlowState {
+            src_alloc: None,
+            dst_alloc: Some(return_dst),
+            session_timeouts,
+            transitory: AtomicBool::new(false),
+            _session: None,
+        };
+        self.create_session(&forward_key, forward_state, SessionClass::Udp);
+        self.create_session(&reverse_key, reverse_state, SessionClass::Udp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn endpoint(s: &str) -> SocketAddr {
+        SocketAddr::from_str(s).unwrap()
+    }
+
+    const INVITE: &str = "INVITE sip:bob@192.0.2.10 SIP/2.0\r\n\
+        Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds\r\n\
+        Max-Forwards: 70\r\n\
+        To: Bob <sip:bob@192.0.2.10>\r\n\
+        From: Alice <sip:alice@10.0.0.1>;tag=1928301774\r\n\
+        Call-ID: a84b4c76e66710@10.0.0.10\r\n\
+        CSeq: 314159 INVITE\r\n\
+        Contact: <sip:alice@10.0.0.1>\r\n\
+        Content-Type: application/sdp\r\n\
+        Content-Length: 142\r\n\
+        \r\n\
+        v=0\r\n\
+        o=alice 2890844526 2890844526 IN IP4 10.0.0.1\r\n\
+        s=-\r\n\
+        c=IN IP4 10.0.0.1\r\n\
+        t=0 0\r\n\
+        m=audio 49170 RTP/AVP 0\r\n\
+        m=video 51372 RTP/AVP 31\r\n\
+        c=IN IP4 10.0.0.10\r\n";
+
+    #[test]
+    fn test_rewrite_outgoing_invite() {
+        let from = endpoint("10.0.0.1:5060");
+        let to = endpoint("203.0.113.1:1024");
+        let mut offered = Vec::new();
+        let rewrite = rewrite_message(INVITE.as_bytes(), from, to, |index, media| {
+            offered.push((index, media));
+            Some(20000)
+        })
+        .unwrap();
+        let message = String::from_utf8(rewrite.payload).unwrap();
+        let (head, body) = message.split_once("\r\n\r\n").unwrap();
+
+        assert_eq!(rewrite.call_id, "a84b4c76e66710@10.0.0.10");
+        assert!(head.contains("Via: SIP/2.0/UDP 203.0.113.1:1024;branch=z9hG4bK776asdhds\r\n"));
+        // Implicit default port gets replaced with the translated port
+        assert!(head.contains("Contact: <sip:alice@203.0.113.1:1024>\r\n"));
+        // Only Via and Contact headers are rewritten
+        assert!(head.contains("From: Alice <sip:alice@10.0.0.1>;tag=1928301774\r\n"));
+        // Call-ID is an identifier, not an address, and 10.0.0.10 is a different address
+        assert!(head.contains("Call-ID: a84b4c76e66710@10.0.0.10\r\n"));
+        assert!(head.ends_with(&format!("Content-Length: {}", body.len())));
+
+        assert!(body.contains("o=alice 2890844526 2890844526 IN IP4 203.0.113.1\r\n"));
+        assert!(body.contains("c=IN IP4 203.0.113.1\r\n"));
+        assert!(body.contains("m=audio 20000 RTP/AVP 0\r\n"));
+        // Media stream with another address is left untouched
+        assert!(body.contains("m=video 51372 RTP/AVP 31\r\nc=IN IP4 10.0.0.10\r\n"));
+
+        assert_eq!(offered, vec![(0, endpoint("10.0.0.1:49170"))]);
+        assert_eq!(
+            rewrite.media.unwrap(),
+            vec![endpoint("10.0.0.1:49170"), endpoint("10.0.0.10:51372")]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_incoming_response() {
+        let response = "SIP/2.0 200 OK\r\n\
+            v: SIP/2.0/UDP 203.0.113.1:1024;branch=z9hG4bK776asdhds;received=203.0.113.1\r\n\
+            i: a84b4c76e66710@10.0.0.10\r\n\
+            c: application/sdp\r\n\
+            l: 0\r\n\
+            \r\n\
+            v=0\r\n\
+            c=IN IP4 192.0.2.10\r\n\
+            m=audio 3456 RTP/AVP 0\r\n";
+        let from = endpoint("203.0.113.1:1024");
+        let to = endpoint("10.0.0.1:5060");
+        let rewrite = rewrite_message(response.as_bytes(), from, to, |_, _| {
+            unreachable!("no media with the translated address")
+        })
+        .unwrap();
+        let message = String::from_utf8(rewrite.payload).unwrap();
+
+        // The address in the received parameter has no port, and the port for the Via header is
+        // not the default one: leave it alone.
+        assert!(message.starts_with(
+            "SIP/2.0 200 OK\r\n\
+            v: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds;received=203.0.113.1\r\n"
+        ));
+        assert_eq!(rewrite.call_id, "a84b4c76e66710@10.0.0.10");
+        assert_eq!(rewrite.media.unwrap(), vec![endpoint("192.0.2.10:3456")]);
+    }
+
+    #[test]
+    fn test_not_sip() {
+        let from = endpoint("10.0.0.1:5060");
+        let to = endpoint("203.0.113.1:1024");
+        for payload in [
+            &b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"[..],
+            b"\xff\xfe\x00",
+            // No Call-ID
+            b"OPTIONS sip:bob@192.0.2.10 SIP/2.0\r\nVia: SIP/2.0/UDP 10.0.0.1\r\n\r\n",
+        ] {
+            assert!(rewrite_message(payload, from, to, |_, _| None).is_none());
+        }
+    }
+
+    #[test]
+    fn test_replace_endpoint_ipv6() {
+        let from = endpoint("[fd00::1]:5062");
+        let to = endpoint("[2001:db8::1]:2000");
+        assert_eq!(
+            replace_endpoint(" <sip:alice@[fd00::1]:5062>;expires=60", from, to),
+            " <sip:alice@[2001:db8::1]:2000>;expires=60"
+        );
+        // Different port, or implicit default port: a different endpoint
+        assert_eq!(
+            replace_endpoint(
+                " <sip:alice@[fd00::1]:5063>, <sip:alice@[fd00::1]>",
+                from,
+                to
+            ),
+            " <sip:alice@[fd00::1]:5063>, <sip:alice@[fd00::1]>"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Counters for the stateful NAT allocator and sessions.
+//! They are shared by all the readers of a given [`NatAllocatorWriter`](super::NatAllocatorWriter),
+//! and survive allocator updates, so that exhaustion can be monitored over time.
+
+use crate::stateful::allocator::AllocatorError;
+use concurrency::sync::atomic::{AtomicU64, Ordering};
+
+/// A snapshot of the counters of the stateful NAT allocator
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NatAllocatorStats {
+    /// Number of allocations that failed because no IP address with free ports was left in the pool
+    pub ip_exhausted: u64,
+    /// Number of allocations that failed because no port was left for the selected IP address, in
+    /// the configured port range if any
+    pub port_exhausted: u64,
+    /// Number of transitory sessions evicted to make room for new sessions, because the session
+    /// table was close to its capacity
+    pub sessions_evicted: u64,
+}
+
+/// The usage of the ports available for stateful source NAT
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NatPortUsage {
+    /// Number of ports (or ICMP identifiers) currently allocated or reserved
+    pub in_use: u64,
+    /// Number of ports (or ICMP identifiers) available for dynamic allocation, in total
+    pub total: u64,
+}
+
+impl NatPortUsage {
+    pub(crate) fn add(&mut self, other: NatPortUsage) {
+        self.in_use += other.in_use;
+        self.total += other.total;
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct NatAllocatorCounters {
+    ip_exhausted: AtomicU64,
+    port_exhausted: AtomicU64,
+    sessions_evicted: AtomicU64,
+}
+
+impl NatAllocatorCounters {
+    /// Account for a failed allocation
+    pub(crate) fn record(&self, error: &AllocatorError) {
+        match error {
+            AllocatorError::NoFreeIp => {
+                self.ip_exhausted.fetch_add(1, Ordering::Relaxed);
+            }
+            AllocatorError::NoPortBlock | AllocatorError::NoFreePort(_) => {
+                self.port_exhausted.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    /// Account for sessions evicted from the session table
+    pub(crate) fn record_evictions(&self, count: u64) {
+        self.sessions_evicted.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> NatAllocatorStats {
+        NatAllocatorStats {
+            ip_exhausted: self.ip_exhausted.load(Ordering::Relaxed),
+            port_exhausted: self.port_exhausted.load(Ordering::Relaxed),
+            sessions_evicted: self.sessions_evicted.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -8,7 +8,7 @@ mod tests {
     use config::external::overlay::Overlay;
     use config::external::overlay::vpc::{Vpc, VpcTable};
     use config::external::overlay::vpcpeering::{
-        VpcExpose, VpcManifest, VpcPeering, VpcPeeringTable,
+        NatSessionTimeouts, VpcExpose, VpcManifest, VpcPeering, VpcPeeringTable,
     };
     use config::external::underlay::Underlay;
     use config::internal::device::DeviceConfig;
@@ -24,21 +24,31 @@ mod tests {
     use net::udp::{TruncatedUdp, UdpPort};
 
     use crate::StatefulNat;
+    use crate::stateful::{NatSessionQuery, query_sessions};
+    use concurrency::sync::Arc;
+    use lpm::prefix::Prefix;
 
     use net::buffer::{PacketBufferMut, TestBuffer};
     use net::eth::mac::Mac;
     use net::headers::{
-        EmbeddedTransport, TryEmbeddedTransport as _, TryIcmp4, TryInnerIpv4, TryIpv4, TryUdp,
+        EmbeddedTransport, TryEmbeddedTransport as _, TryIcmp4, TryInnerIpv4, TryIpv4, TryIpv4Mut,
+        TryTcpMut, TryUdp,
     };
+    use net::ipv4::addr::UnicastIpv4Addr;
     use net::packet::test_utils::{
         IcmpEchoDirection, build_test_icmp4_destination_unreachable_packet, build_test_icmp4_echo,
-        build_test_udp_ipv4_frame,
+        build_test_ipv4_packet_with_transport, build_test_udp_ipv4_frame,
     };
     use net::packet::{DoneReason, Packet, VpcDiscriminant};
+    use net::tcp::port::TcpPort;
     use net::vxlan::Vni;
     use pipeline::NetworkFunction;
     use pkt_meta::flow_table::flow_key::Uni;
-    use pkt_meta::flow_table::{FlowKey, FlowTable, IpProtoKey, UdpProtoKey};
+    use pkt_meta::flow_table::{
+        ExpirationsNF, FlowFilter, FlowKey, FlowTable, IpProtoKey, LookupNF, TcpProtoKey,
+        UdpProtoKey,
+    };
+    use std::collections::BTreeSet;
     use std::net::{IpAddr, Ipv4Addr};
     use std::str::FromStr;
     use std::time::Duration;
@@ -77,6 +87,7 @@ mod tests {
         let underlay = Underlay {
             vrf: vrf_config,
             vtep: None,
+            static_routes: BTreeSet::new(),
         };
 
         let mut external_builder = ExternalConfigBuilder::default();
@@ -616,6 +627,282 @@ mod tests {
         assert_eq!(done_reason, Some(DoneReason::Filtered));
     }
 
+    #[test]
+    #[traced_test]
+    fn test_session_timeouts() {
+        let mut overlay = build_overlay_2vpcs_unidirectional_nat();
+        // Override the UDP timeout for source NAT from VPC-1
+        let expose121 = VpcExpose::empty()
+            .make_stateful_nat(Some(FIVE_MINUTES))
+            .unwrap()
+            .make_nat_session_timeouts(NatSessionTimeouts {
+                udp: Some(ONE_MINUTE),
+                ..NatSessionTimeouts::default()
+            })
+            .unwrap()
+            .ip("1.1.0.0/16".into())
+            .as_range("2.2.0.0/16".into());
+        let mut manifest12 = VpcManifest::new("VPC-1");
+        manifest12.add_expose(expose121).unwrap();
+        let mut manifest21 = VpcManifest::new("VPC-2");
+        manifest21.add_expose(VpcExpose::empty()).unwrap();
+        overlay.peering_table = VpcPeeringTable::new();
+        overlay
+            .peering_table
+            .add(VpcPeering::new("VPC-1--VPC-2", manifest12, manifest21))
+            .unwrap();
+
+        let mut config = build_sample_config(overlay);
+        config.validate().unwrap();
+        let (mut nat, mut allocator) = StatefulNat::new("test-nat");
+        allocator
+            .update_allocator(&config.external.overlay.vpc_table)
+            .unwrap();
+
+        let (orig_src, orig_dst) = ("1.1.2.3", "5.0.0.5");
+        let (output_src, _, output_src_port, _, done_reason) =
+            check_packet(&mut nat, vni(100), vni(200), orig_src, orig_dst, 9998, 443);
+        assert_eq!(output_src, addr_v4("2.2.0.0"));
+        assert_eq!(done_reason, None);
+
+        let Some((_, idle_timeout)) = nat.get_session::<Ipv4Addr>(
+            VpcDiscriminant::VNI(vni(100)),
+            IpAddr::from_str(orig_src).unwrap(),
+            VpcDiscriminant::VNI(vni(200)),
+            IpAddr::from_str(orig_dst).unwrap(),
+            IpProtoKey::Udp(UdpProtoKey {
+                src_port: UdpPort::new_checked(9998).unwrap(),
+                dst_port: UdpPort::new_checked(443).unwrap(),
+            }),
+        ) else {
+            unreachable!()
+        };
+        assert_eq!(idle_timeout, ONE_MINUTE);
+        // Reverse path
+        let Some((_, idle_timeout)) = nat.get_session::<Ipv4Addr>(
+            VpcDiscriminant::VNI(vni(200)),
+            IpAddr::from_str(orig_dst).unwrap(),
+            VpcDiscriminant::VNI(vni(100)),
+            IpAddr::from_str("2.2.0.0").unwrap(),
+            IpProtoKey::Udp(UdpProtoKey {
+                src_port: UdpPort::new_checked(443).unwrap(),
+                dst_port: UdpPort::new_checked(output_src_port).unwrap(),
+            }),
+        ) else {
+            unreachable!()
+        };
+        assert_eq!(idle_timeout, ONE_MINUTE);
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_query_sessions() {
+        let mut config = build_sample_config(build_overlay_2vpcs());
+        config.validate().unwrap();
+        let (mut nat, mut allocator) = StatefulNat::new("test-nat");
+        allocator
+            .update_allocator(&config.external.overlay.vpc_table)
+            .unwrap();
+
+        // Two flows, each with a session for each direction
+        let mut translations = Vec::new();
+        for sport in [9998, 9999] {
+            let (output_src, output_dst, output_src_port, output_dst_port, done_reason) =
+                check_packet(
+                    &mut nat,
+                    vni(100),
+                    vni(200),
+                    "1.1.2.3",
+                    "3.3.3.3",
+                    sport,
+                    443,
+                );
+            assert_eq!(done_reason, None);
+            translations.push((
+                (IpAddr::from(output_src), Some(output_src_port)),
+                (IpAddr::from(output_dst), Some(output_dst_port)),
+            ));
+        }
+
+        let page = query_sessions(nat.sessions(), &NatSessionQuery::default());
+        assert_eq!(page.total, 4);
+        assert_eq!(page.sessions.len(), 4);
+        assert_eq!(page.next_offset(), None);
+
+        // Only keep the forward sessions
+        let filter = FlowFilter::new().with_prefix(Prefix::expect_from(("1.1.0.0", 16)));
+        let page = query_sessions(nat.sessions(), &NatSessionQuery::new(filter.clone()));
+        assert_eq!(page.total, 2);
+        for (session, (sport, (translated_src, translated_dst))) in page
+            .sessions
+            .iter()
+            .zip([9998, 9999].into_iter().zip(translations))
+        {
+            assert_eq!(session.flow.src_ip, IpAddr::from(addr_v4("1.1.2.3")));
+            assert_eq!(session.flow.src_port, Some(sport));
+            assert_eq!(session.translated_src(), translated_src);
+            assert_eq!(session.translated_dst(), translated_dst);
+        }
+
+        // Pagination
+        let query = NatSessionQuery::new(filter).with_limit(1);
+        let first = query_sessions(nat.sessions(), &query);
+        assert_eq!(first.sessions.len(), 1);
+        assert_eq!(first.next_offset(), Some(1));
+        let second = query_sessions(nat.sessions(), &query.with_offset(1));
+        assert_eq!(second.sessions.len(), 1);
+        assert_eq!(second.next_offset(), None);
+        assert_ne!(first.sessions[0], second.sessions[0]);
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_sessions_shared_with_flow_lookup() {
+        let mut config = build_sample_config(build_overlay_2vpcs());
+        config.validate().unwrap();
+        let (nat, mut allocator) = StatefulNat::new("test-nat");
+        allocator
+            .update_allocator(&config.external.overlay.vpc_table)
+            .unwrap();
+        let flow_table = Arc::new(FlowTable::default());
+        let mut nat = nat.with_sessions(flow_table.clone());
+        let mut lookup = LookupNF::new(flow_table.clone());
+        let mut expirations = ExpirationsNF::new(flow_table.clone());
+
+        // Run the packet through the flow lookup, stateful NAT and expiration stages, and return
+        // its translated source port
+        let mut translate = |sport: u16| -> u16 {
+            let mut packet: Packet<TestBuffer> = build_test_udp_ipv4_frame(
+                Mac([0x2, 0, 0, 0, 0, 1]),
+                Mac([0x2, 0, 0, 0, 0, 2]),
+                "1.1.2.3",
+                "3.3.3.3",
+                sport,
+                443,
+            );
+            packet.get_meta_mut().set_nat(true);
+            packet.get_meta_mut().src_vpcd = Some(VpcDiscriminant::VNI(vni(100)));
+            packet.get_meta_mut().dst_vpcd = Some(VpcDiscriminant::VNI(vni(200)));
+            let looked_up: Vec<_> = lookup.process(vec![packet].into_iter()).collect();
+            let translated: Vec<_> = nat.process(looked_up.into_iter()).collect();
+            let packets_out: Vec<_> = expirations.process(translated.into_iter()).collect();
+            assert_eq!(packets_out[0].get_done(), None);
+            packets_out[0].try_udp().unwrap().source().into()
+        };
+
+        // The second packet of the flow finds the session that the first one created, through the
+        // flow info attached by the flow lookup stage, and gets the same translation
+        let first = translate(9998);
+        assert_eq!(flow_table.len(), 2);
+        assert_eq!(translate(9998), first);
+        assert_eq!(flow_table.len(), 2);
+
+        // A new flow gets new sessions
+        translate(9999);
+        assert_eq!(flow_table.len(), 4);
+    }
+
+    fn check_tcp_packet(
+        nat: &mut StatefulNat,
+        src_ip: &str,
+        dst_ip: &str,
+        sport: u16,
+        syn: bool,
+    ) -> Option<DoneReason> {
+        let mut packet = build_test_ipv4_packet_with_transport(64, Some(NextHeader::TCP)).unwrap();
+        let ipv4 = packet.try_ipv4_mut().unwrap();
+        ipv4.set_source(UnicastIpv4Addr::new(addr_v4(src_ip)).unwrap());
+        ipv4.set_destination(addr_v4(dst_ip));
+        let tcp = packet.try_tcp_mut().unwrap();
+        tcp.set_source(TcpPort::new_checked(sport).unwrap());
+        tcp.set_destination(TcpPort::new_checked(443).unwrap());
+        tcp.set_syn(syn);
+        packet.get_meta_mut().set_nat(true);
+        packet.get_meta_mut().src_vpcd = Some(VpcDiscriminant::VNI(vni(100)));
+        packet.get_meta_mut().dst_vpcd = Some(VpcDiscriminant::VNI(vni(200)));
+
+        flow_lookup(nat.sessions(), &mut packet);
+
+        let packets_out: Vec<_> = nat.process(vec![packet].into_iter()).collect();
+        packets_out[0].get_done()
+    }
+
+    fn tcp_session_exists(nat: &StatefulNat, src_ip: &str, dst_ip: &str, sport: u16) -> bool {
+        nat.get_session::<Ipv4Addr>(
+            VpcDiscriminant::VNI(vni(100)),
+            IpAddr::from_str(src_ip).unwrap(),
+            VpcDiscriminant::VNI(vni(200)),
+            IpAddr::from_str(dst_ip).unwrap(),
+            IpProtoKey::Tcp(TcpProtoKey {
+                src_port: TcpPort::new_checked(sport).unwrap(),
+                dst_port: TcpPort::new_checked(443).unwrap(),
+            }),
+        )
+        .is_some()
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_evict_transitory_sessions() {
+        let mut config = build_sample_config(build_overlay_2vpcs());
+        config.validate().unwrap();
+        let (nat, mut allocator) = StatefulNat::new("test-nat");
+        allocator
+            .update_allocator(&config.external.overlay.vpc_table)
+            .unwrap();
+        // Transitory sessions get evicted when the table holds 9 sessions or more
+        let mut nat = nat.with_sessions(Arc::new(FlowTable::with_capacity(16, 10)));
+        let (orig_src, orig_dst) = ("1.1.2.3", "3.3.3.3");
+
+        // One established connection, then half-open connections, each with two sessions
+        assert_eq!(
+            check_tcp_packet(&mut nat, orig_src, orig_dst, 1000, false),
+            None
+        );
+        for sport in 2000..2004 {
+            assert_eq!(
+                check_tcp_packet(&mut nat, orig_src, orig_dst, sport, true),
+                None
+            );
+        }
+        assert_eq!(nat.sessions().len(), 10);
+        assert_eq!(allocator.stats().sessions_evicted, 0);
+
+        // A new connection evicts the sessions of the oldest half-open connection
+        assert_eq!(
+            check_tcp_packet(&mut nat, orig_src, orig_dst, 3000, true),
+            None
+        );
+        assert_eq!(nat.sessions().len(), 10);
+        assert_eq!(allocator.stats().sessions_evicted, 2);
+        assert_eq!(nat.sessions().evictions(), 0);
+        assert!(!tcp_session_exists(&nat, orig_src, orig_dst, 2000));
+        assert!(tcp_session_exists(&nat, orig_src, orig_dst, 2001));
+        assert!(tcp_session_exists(&nat, orig_src, orig_dst, 3000));
+        assert!(tcp_session_exists(&nat, orig_src, orig_dst, 1000));
+
+        // New established connections evict the remaining half-open connections
+        for sport in 4000..4004 {
+            assert_eq!(
+                check_tcp_packet(&mut nat, orig_src, orig_dst, sport, false),
+                None
+            );
+        }
+        assert_eq!(nat.sessions().len(), 10);
+        assert_eq!(allocator.stats().sessions_evicted, 10);
+        assert_eq!(nat.sessions().evictions(), 0);
+        assert!(!tcp_session_exists(&nat, orig_src, orig_dst, 3000));
+        assert!(tcp_session_exists(&nat, orig_src, orig_dst, 1000));
+
+        // Established sessions are never evicted by the stateful NAT, but by the table itself
+        assert_eq!(
+            check_tcp_packet(&mut nat, orig_src, orig_dst, 4004, false),
+            None
+        );
+        assert_eq!(allocator.stats().sessions_evicted, 10);
+        assert!(nat.sessions().evictions() > 0);
+    }
+
     fn check_packet_icmp_echo(
         nat: &mut StatefulNat,
         src_vni: Vni,
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Idle timeouts for stateful NAT sessions, per protocol.
+
+use config::external::overlay::vpcpeering::NatSessionTimeouts;
+use net::buffer::PacketBufferMut;
+use net::headers::{Transport, TryHeaders, TryTransport};
+use net::packet::Packet;
+use std::fmt::Display;
+use std::time::Duration;
+
+/// The category of a packet for a session, which determines the idle timeout to apply to the
+/// session after the packet has been processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SessionClass {
+    TcpEstablished,
+    /// TCP packet opening or closing the connection (SYN, FIN or RST flag set)
+    TcpTransitory,
+    Udp,
+    Icmp,
+}
+
+impl SessionClass {
+    /// Get the class of a packet, based on its transport header.
+    pub(crate) fn from_packet<Buf: PacketBufferMut>(packet: &Packet<Buf>) -> Self {
+        match packet.headers().try_transport() {
+            Some(Transport::Tcp(tcp)) if tcp.syn() || tcp.fin() || tcp.rst() => {
+                SessionClass::TcpTransitory
+            }
+            Some(Transport::Tcp(_)) => SessionClass::TcpEstablished,
+            Some(Transport::Udp(_)) => SessionClass::Udp,
+            // We only create sessions for TCP, UDP and ICMP
+            _ => SessionClass::Icmp,
+        }
+    }
+
+    /// Get the class of a flow, based on its protocol only. TCP flows are assumed established.
+    #[cfg(test)]
+    pub(crate) fn from_proto_key(proto_key: &pkt_meta::flow_table::IpProtoKey) -> Self {
+        use pkt_meta::flow_table::IpProtoKey;
+        match proto_key {
+            IpProtoKey::Tcp(_) => SessionClass::TcpEstablished,
+            IpProtoKey::Udp(_) => SessionClass::Udp,
+            IpProtoKey::Icmp(_) => SessionClass::Icmp,
+        }
+    }
+}
+
+/// Idle timeouts for the sessions created from a NAT pool, for each [`SessionClass`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionTimeouts {
+    tcp_established: Duration,
+    tcp_transitory: Duration,
+    udp: Duration,
+    icmp: Duration,
+}
+
+impl SessionTimeouts {
+    /// Create a new [`SessionTimeouts`] using `idle_timeout` for all protocols, unless overridden
+    /// in `session_timeouts`.
+    pub(crate) fn new(idle_timeout: Duration, session_timeouts: &NatSessionTimeouts) -> Self {
+        Self {
+            tcp_established: session_timeouts.tcp_established.unwrap_or(idle_timeout),
+            tcp_transitory: session_timeouts.tcp_transitory.unwrap_or(idle_timeout),
+            udp: session_timeouts.udp.unwrap_or(idle_timeout),
+            icmp: session_timeouts.icmp.unwrap_or(idle_timeout),
+        }
+    }
+
+    /// Get the idle timeout for a session, for the given class of packet
+    pub(crate) fn get(&self, class: SessionClass) -> Duration {
+        match class {
+            SessionClass::TcpEstablished => self.tcp_established,
+            SessionClass::TcpTransitory => self.tcp_transitory,
+            SessionClass::Udp => self.udp,
+            SessionClass::Icmp => self.icmp,
+        }
+    }
+
+    /// Get the idle timeout for UDP sessions
+    pub(crate) fn udp(&self) -> Duration {
+        self.udp
+    }
+
+    /// Combine two sets of timeouts, keeping the minimum value for each class
+    #[must_use]
+    pub(crate) fn min(self, other: Self) -> Self {
+        Self {
+            tcp_established: self.tcp_established.min(other.tcp_established),
+            tcp_transitory: self.tcp_transitory.min(other.tcp_transitory),
+            udp: self.udp.min(other.udp),
+            icmp: self.icmp.min(other.icmp),
+        }
+    }
+}
+
+impl Display for SessionTimeouts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "tcp {}s/{}s, udp {}s, icmp {}s",
+            self.tcp_established.as_secs(),
+            self.tcp_transitory.as_secs(),
+            self.udp.as_secs(),
+            self.icmp.as_secs()
+        )
+    }
+}
@@ -8,10 +8,13 @@ pub mod setup;
 mod test;
 
 use crate::NatTranslationData;
+use crate::counters::NatVpcCounters;
+use crate::exclusions::packet_ports;
 use crate::icmp_error_msg::{
     IcmpErrorMsgError, stateful_translate_icmp_inner, validate_checksums_icmp,
 };
 pub use crate::stateless::natrw::{NatTablesReader, NatTablesWriter}; // re-export
+use concurrency::sync::Arc;
 use net::buffer::PacketBufferMut;
 use net::headers::{Net, TryHeadersMut, TryInnerIp, TryIpMut};
 use net::ipv4::UnicastIpv4Addr;
@@ -102,6 +105,7 @@ fn map_ip_nat(
 pub struct StatelessNat {
     name: String,
     tablesr: NatTablesReader,
+    counters: Arc<NatVpcCounters>,
 }
 
 #[allow(clippy::new_without_default)]
@@ -116,6 +120,7 @@ impl StatelessNat {
             Self {
                 name: name.to_string(),
                 tablesr,
+                counters: Arc::new(NatVpcCounters::new()),
             },
             tablesw,
         )
@@ -126,9 +131,18 @@ impl StatelessNat {
         Self {
             name: name.to_string(),
             tablesr,
+            counters: Arc::new(NatVpcCounters::new()),
         }
     }
 
+    /// Use `counters` for the per-VPC counters, for example to share them with other NAT stages
+    /// and export them as metrics.
+    #[must_use]
+    pub fn with_counters(mut self, counters: Arc<NatVpcCounters>) -> Self {
+        self.counters = counters;
+        self
+    }
+
     /// Get the name of this instance
     #[must_use]
     pub fn name(&self) -> &String {
@@ -257,21 +271,41 @@ impl StatelessNat {
     ) -> Result<bool, StatelessNatError> {
         let nfi = self.name();
 
-        // Get IP header
-        let Some(net) = packet.headers_mut().try_ip_mut() else {
-            error!("{nfi}: Failed to get IP headers!");
-            return Err(StatelessNatError::NoIpHeader);
-        };
-
         // Get NAT tables
         let Some(table) = nat_tables.get_table(src_vni) else {
             error!("{nfi}: Can't find NAT tables for VNI {src_vni}");
             return Err(StatelessNatError::MissingTable(src_vni));
         };
 
-        let (src_ranges, dst_ranges) =
+        // Get "no-NAT" exceptions, if any, with the ports to check them against
+        let exclusions = table
+            .exclusions
+            .get(&dst_vni)
+            .map(|exclusions| (exclusions, packet_ports(packet)));
+
+        // Get IP header
+        let Some(net) = packet.headers_mut().try_ip_mut() else {
+            error!("{nfi}: Failed to get IP headers!");
+            return Err(StatelessNatError::NoIpHeader);
+        };
+
+        let (mut src_ranges, mut dst_ranges) =
             table.find_nat_ranges(net.src_addr(), net.dst_addr(), dst_vni);
 
+        // Leave addresses matching "no-NAT" exceptions untranslated
+        if let Some((exclusions, (src_port, dst_port))) = exclusions {
+            let (src_excluded, dst_excluded) =
+                exclusions.excludes((net.src_addr(), src_port), (net.dst_addr(), dst_port));
+            if src_excluded {
+                debug!("{nfi}: Source {} excluded from NAT", net.src_addr());
+                src_ranges = None;
+            }
+            if dst_excluded {
+                debug!("{nfi}: Destination {} excluded from NAT", net.dst_addr());
+                dst_ranges = None;
+            }
+        }
+
         // will set to true if packet is modified
         let mut modified = false;
         if let Some(ranges_src) = src_ranges {
@@ -327,6 +361,8 @@ impl StatelessNat {
                 packet.get_meta_mut().set_nat(false);
                 if modified {
                     packet.get_meta_mut().set_checksum_refresh(true);
+                    self.counters
+                        .record_translation(VpcDiscriminant::VNI(src_vni));
                     debug!("{nfi}: Packet was NAT'ed");
                 } else {
                     debug!("{nfi}: No NAT translation needed");
@@ -11,6 +11,7 @@ pub mod range_builder;
 pub mod tables;
 
 use crate::stateless::{NatTableValue, NatTables, PerVniTable};
+use crate::{NatExclusionTable, NatExclusions};
 use config::external::overlay::vpc::{Peering, VpcTable};
 use config::external::overlay::vpcpeering::VpcExpose;
 use config::utils::{ConfigUtilError, collapse_prefixes_peering};
@@ -96,6 +97,16 @@ impl PerVniTable {
                 })
             })?;
 
+        // "No-NAT" exceptions: from local exposes for the source, from remote exposes for the
+        // destination
+        let exclusions = NatExclusions {
+            src: NatExclusionTable::from_exposes(new_peering.local.stateless_nat_exposes()),
+            dst: NatExclusionTable::from_exposes(new_peering.remote.stateless_nat_exposes()),
+        };
+        if !exclusions.is_empty() {
+            self.exclusions.insert(dst_vni, exclusions);
+        }
+
         Ok(())
     }
 }
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright Open Network Fabric Authors
 
+use crate::NatExclusions;
 use ahash::RandomState;
 use net::vxlan::Vni;
 use std::collections::{BTreeMap, HashMap};
@@ -55,6 +56,8 @@ pub struct PerVniTable {
     pub vni: Vni,
     pub dst_nat: NatRuleTable,
     pub src_nat: HashMap<Vni, NatRuleTable>,
+    /// "No-NAT" exceptions, for each destination VNI
+    pub exclusions: HashMap<Vni, NatExclusions>,
 }
 
 impl PerVniTable {
@@ -65,6 +68,7 @@ impl PerVniTable {
             vni,
             dst_nat: NatRuleTable::new(),
             src_nat: HashMap::new(),
+            exclusions: HashMap::new(),
         }
     }
 
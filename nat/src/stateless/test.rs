@@ -34,6 +34,7 @@ mod tests {
     use net::packet::{DoneReason, Packet, VpcDiscriminant};
     use net::vxlan::Vni;
     use pipeline::NetworkFunction;
+    use std::collections::BTreeSet;
     use std::net::Ipv4Addr;
     use std::str::FromStr;
     use tracing_test::traced_test;
@@ -419,7 +420,8 @@ mod tests {
             .make_stateless_nat()
             .unwrap()
             .ip("192.168.100.0/24".into())
-            .as_range("34.34.34.0/24".into());
+            .as_range("34.34.34.0/24".into())
+            .no_nat("192.168.100.128/25".into(), None);
         let expose431 = VpcExpose::empty()
             .make_stateless_nat()
             .unwrap()
@@ -498,6 +500,7 @@ mod tests {
         let underlay = Underlay {
             vrf: vrf_config,
             vtep: None,
+            static_routes: BTreeSet::new(),
         };
 
         let mut external_builder = ExternalConfigBuilder::default();
@@ -689,5 +692,19 @@ mod tests {
         assert_eq!(output_src, orig_dst);
         assert_eq!(output_dst, orig_src);
         assert_eq!(done_reason, None);
+
+        // expose341 <-> expose431 (no-NAT exception)
+        let (orig_src, orig_dst) = (addr_v4("192.168.100.200"), addr_v4("4.4.0.43"));
+        let (output_src, output_dst, done_reason) =
+            check_packet(&mut nat, vni(300), vni(400), orig_src, orig_dst);
+        assert_eq!(output_src, orig_src);
+        assert_eq!(output_dst, orig_dst);
+        assert_eq!(done_reason, None);
+        // Reverse path
+        let (output_src, output_dst, done_reason) =
+            check_packet(&mut nat, vni(400), vni(300), orig_dst, orig_src);
+        assert_eq!(output_src, orig_dst);
+        assert_eq!(output_dst, orig_src);
+        assert_eq!(done_reason, None);
     }
 }
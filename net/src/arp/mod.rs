@@ -0,0 +1,394 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [ARP][RFC826] types and parsing.
+//!
+//! Only ARP for IPv4 over ethernet is supported, which is the only form of ARP we expect to see.
+//!
+//! [RFC826]: https://datatracker.ietf.org/doc/html/rfc826
+
+use crate::eth::ethtype::EthType;
+use crate::eth::mac::Mac;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use core::fmt::{Display, Formatter};
+use core::num::NonZero;
+use derive_builder::Builder;
+use std::net::Ipv4Addr;
+use tracing::trace;
+
+/// The operation of an [`Arp`] packet.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+pub struct ArpOperation(u16);
+
+impl ArpOperation {
+    /// ARP request ("who has")
+    pub const REQUEST: ArpOperation = ArpOperation(1);
+    /// ARP reply ("is at")
+    pub const REPLY: ArpOperation = ArpOperation(2);
+
+    /// Map a raw value to an [`ArpOperation`]
+    #[must_use]
+    pub const fn new(raw: u16) -> ArpOperation {
+        ArpOperation(raw)
+    }
+
+    /// Get the raw value of the operation
+    #[must_use]
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
+}
+
+impl Display for ArpOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            ArpOperation::REQUEST => write!(f, "request"),
+            ArpOperation::REPLY => write!(f, "reply"),
+            ArpOperation(other) => write!(f, "operation {other}"),
+        }
+    }
+}
+
+/// An [ARP] packet, for IPv4 over ethernet.
+///
+/// Use [`ArpBuilder`] to create one.
+///
+/// [ARP]: https://en.wikipedia.org/wiki/Address_Resolution_Protocol
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Builder)]
+pub struct Arp {
+    /// The operation (request or reply)
+    operation: ArpOperation,
+    /// The MAC address of the sender
+    sender_mac: Mac,
+    /// The IP address of the sender
+    sender_ip: Ipv4Addr,
+    /// The MAC address of the target (zero in requests, which is the default)
+    #[builder(default = Mac([0; 6]))]
+    target_mac: Mac,
+    /// The IP address of the target
+    target_ip: Ipv4Addr,
+}
+
+impl Arp {
+    /// The length of an [`Arp`] packet.
+    ///
+    /// Naming for consistency with other headers.
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(28).unwrap();
+
+    /// Hardware type for ethernet
+    const HARDWARE_ETHERNET: u16 = 1;
+    /// Length of a MAC address
+    const HARDWARE_LENGTH: u8 = 6;
+    /// Length of an IPv4 address
+    const PROTOCOL_LENGTH: u8 = 4;
+
+    /// Get the operation of this packet.
+    #[must_use]
+    pub const fn operation(&self) -> ArpOperation {
+        self.operation
+    }
+
+    /// Get the MAC address of the sender.
+    #[must_use]
+    pub const fn sender_mac(&self) -> Mac {
+        self.sender_mac
+    }
+
+    /// Get the IP address of the sender.
+    #[must_use]
+    pub const fn sender_ip(&self) -> Ipv4Addr {
+        self.sender_ip
+    }
+
+    /// Get the MAC address of the target.
+    #[must_use]
+    pub const fn target_mac(&self) -> Mac {
+        self.target_mac
+    }
+
+    /// Get the IP address of the target.
+    #[must_use]
+    pub const fn target_ip(&self) -> Ipv4Addr {
+        self.target_ip
+    }
+
+    /// Set the operation of this packet.
+    pub fn set_operation(&mut self, operation: ArpOperation) -> &mut Arp {
+        self.operation = operation;
+        self
+    }
+
+    /// Set the MAC address of the sender.
+    pub fn set_sender_mac(&mut self, mac: Mac) -> &mut Arp {
+        self.sender_mac = mac;
+        self
+    }
+
+    /// Set the IP address of the sender.
+    pub fn set_sender_ip(&mut self, ip: Ipv4Addr) -> &mut Arp {
+        self.sender_ip = ip;
+        self
+    }
+
+    /// Set the MAC address of the target.
+    pub fn set_target_mac(&mut self, mac: Mac) -> &mut Arp {
+        self.target_mac = mac;
+        self
+    }
+
+    /// Set the IP address of the target.
+    pub fn set_target_ip(&mut self, ip: Ipv4Addr) -> &mut Arp {
+        self.target_ip = ip;
+        self
+    }
+
+    /// Tell if this is a gratuitous ARP, i.e., an announcement of the sender's own address.
+    #[must_use]
+    pub fn is_gratuitous(&self) -> bool {
+        self.sender_ip == self.target_ip
+    }
+
+    /// Build the reply to this request, telling that the target IP address is at `mac`.
+    #[must_use]
+    pub fn reply(&self, mac: Mac) -> Arp {
+        Arp {
+            operation: ArpOperation::REPLY,
+            sender_mac: mac,
+            sender_ip: self.target_ip,
+            target_mac: self.sender_mac,
+            target_ip: self.sender_ip,
+        }
+    }
+}
+
+/// Errors which may occur when parsing an [`Arp`] packet.
+#[derive(Debug, thiserror::Error)]
+pub enum ArpError {
+    /// Only ethernet hardware addresses are supported.
+    #[error("unsupported ARP hardware type {0}")]
+    UnsupportedHardwareType(u16),
+    /// Only IPv4 protocol addresses are supported.
+    #[error("unsupported ARP protocol type {0:?}")]
+    UnsupportedProtocolType(EthType),
+    /// The address lengths do not match those of ethernet and IPv4.
+    #[error("invalid ARP address lengths (hardware: {hardware}, protocol: {protocol})")]
+    InvalidAddressLength {
+        /// The length of the hardware addresses
+        hardware: u8,
+        /// The length of the protocol addresses
+        protocol: u8,
+    },
+}
+
+impl Parse for Arp {
+    type Error = ArpError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < Arp::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Arp::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let hardware = u16::from_be_bytes([buf[0], buf[1]]);
+        if hardware != Arp::HARDWARE_ETHERNET {
+            trace!("Received ARP packet with hardware type {hardware}");
+            return Err(ParseError::Invalid(ArpError::UnsupportedHardwareType(
+                hardware,
+            )));
+        }
+        let protocol = EthType::new_from_be_bytes([buf[2], buf[3]]);
+        if protocol != EthType::IPV4 {
+            trace!("Received ARP packet with protocol type {protocol:?}");
+            return Err(ParseError::Invalid(ArpError::UnsupportedProtocolType(
+                protocol,
+            )));
+        }
+        if buf[4] != Arp::HARDWARE_LENGTH || buf[5] != Arp::PROTOCOL_LENGTH {
+            return Err(ParseError::Invalid(ArpError::InvalidAddressLength {
+                hardware: buf[4],
+                protocol: buf[5],
+            }));
+        }
+        let mac = |offset: usize| {
+            let mut mac = [0u8; 6];
+            mac.copy_from_slice(&buf[offset..offset + 6]);
+            Mac(mac)
+        };
+        let ip = |offset: usize| {
+            Ipv4Addr::new(
+                buf[offset],
+                buf[offset + 1],
+                buf[offset + 2],
+                buf[offset + 3],
+            )
+        };
+        let arp = Arp {
+            operation: ArpOperation(u16::from_be_bytes([buf[6], buf[7]])),
+            sender_mac: mac(8),
+            sender_ip: ip(14),
+            target_mac: mac(18),
+            target_ip: ip(24),
+        };
+        Ok((arp, Arp::MIN_LENGTH))
+    }
+}
+
+impl DeParse for Arp {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        Arp::MIN_LENGTH
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        if buf.len() < Arp::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: Arp::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        buf[0..2].copy_from_slice(&Arp::HARDWARE_ETHERNET.to_be_bytes());
+        buf[2..4].copy_from_slice(&EthType::IPV4.as_u16().to_be_bytes());
+        buf[4] = Arp::HARDWARE_LENGTH;
+        buf[5] = Arp::PROTOCOL_LENGTH;
+        buf[6..8].copy_from_slice(&self.operation.0.to_be_bytes());
+        buf[8..14].copy_from_slice(&self.sender_mac.0);
+        buf[14..18].copy_from_slice(&self.sender_ip.octets());
+        buf[18..24].copy_from_slice(&self.target_mac.0);
+        buf[24..28].copy_from_slice(&self.target_ip.octets());
+        Ok(Arp::MIN_LENGTH)
+    }
+}
+
+#[cfg(any(test, feature = "bolero"))]
+mod contract {
+    use crate::arp::Arp;
+    use bolero::{Driver, TypeGenerator};
+    use std::net::Ipv4Addr;
+
+    impl TypeGenerator for Arp {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            Some(Arp {
+                operation: driver.produce()?,
+                sender_mac: driver.produce()?,
+                sender_ip: Ipv4Addr::from(driver.produce::<[u8; 4]>()?),
+                target_mac: driver.produce()?,
+                target_ip: Ipv4Addr::from(driver.produce::<[u8; 4]>()?),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arp::{Arp, ArpBuilder, ArpError, ArpOperation};
+    use crate::buffer::TestBuffer;
+    use crate::eth::Eth;
+    use crate::eth::ethtype::EthType;
+    use crate::eth::mac::{DestinationMac, Mac, SourceMac};
+    use crate::headers::{HeadersBuilder, TryArp, TryIp};
+    use crate::packet::Packet;
+    use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, Parse, ParseError};
+    use std::net::Ipv4Addr;
+
+    const MIN_LENGTH_USIZE: usize = 28;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!().with_type().for_each(|arp: &Arp| {
+            let mut buf = [0u8; MIN_LENGTH_USIZE];
+            let bytes_written = arp.deparse(&mut buf).unwrap_or_else(|_| unreachable!());
+            assert_eq!(bytes_written, Arp::MIN_LENGTH);
+            let (parsed, bytes_parsed) = Arp::parse(&buf).unwrap();
+            assert_eq!(parsed, *arp);
+            assert_eq!(bytes_parsed, Arp::MIN_LENGTH);
+        });
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; MIN_LENGTH_USIZE]| {
+                let (parsed, bytes_parsed) = match Arp::parse(slice) {
+                    Ok((parsed, bytes_parsed)) => (parsed, bytes_parsed),
+                    Err(ParseError::Invalid(ArpError::UnsupportedHardwareType(hardware))) => {
+                        assert_eq!(u16::from_be_bytes([slice[0], slice[1]]), hardware);
+                        assert_ne!(hardware, 1);
+                        return;
+                    }
+                    Err(ParseError::Invalid(ArpError::UnsupportedProtocolType(protocol))) => {
+                        assert_ne!(protocol, EthType::IPV4);
+                        return;
+                    }
+                    Err(ParseError::Invalid(ArpError::InvalidAddressLength {
+                        hardware,
+                        protocol,
+                    })) => {
+                        assert!(hardware != 6 || protocol != 4);
+                        return;
+                    }
+                    Err(ParseError::Length(_) | ParseError::BufferTooLong(_)) => unreachable!(),
+                };
+                let mut write_back_buffer = [0u8; MIN_LENGTH_USIZE];
+                let bytes_written = parsed
+                    .deparse(&mut write_back_buffer)
+                    .unwrap_or_else(|_| unreachable!());
+                assert_eq!(bytes_written, bytes_parsed);
+                assert_eq!(&write_back_buffer, slice);
+            });
+    }
+
+    #[test]
+    fn write_to_insufficient_buffer_fails_gracefully() {
+        bolero::check!().with_type().for_each(|arp: &Arp| {
+            let mut too_small_buffer = [0u8; MIN_LENGTH_USIZE - 1];
+            match arp.deparse(&mut too_small_buffer) {
+                Err(DeParseError::Length(e)) => {
+                    assert_eq!(e.expected, Arp::MIN_LENGTH.into_non_zero_usize());
+                    assert_eq!(e.actual, too_small_buffer.len());
+                }
+                _ => unreachable!(),
+            }
+        });
+    }
+
+    #[test]
+    fn build_and_reply() {
+        let request = ArpBuilder::default()
+            .operation(ArpOperation::REQUEST)
+            .sender_mac(Mac([0x2, 0, 0, 0, 0, 1]))
+            .sender_ip(Ipv4Addr::new(10, 0, 0, 1))
+            .target_ip(Ipv4Addr::new(10, 0, 0, 2))
+            .build()
+            .unwrap();
+        assert_eq!(request.target_mac(), Mac([0; 6]));
+        assert!(!request.is_gratuitous());
+        assert!(ArpBuilder::default().build().is_err());
+
+        let reply = request.reply(Mac([0x2, 0, 0, 0, 0, 2]));
+        assert_eq!(reply.operation(), ArpOperation::REPLY);
+        assert_eq!(reply.sender_ip(), request.target_ip());
+        assert_eq!(reply.target_ip(), request.sender_ip());
+        assert_eq!(reply.target_mac(), request.sender_mac());
+
+        let headers = HeadersBuilder::default()
+            .eth(Some(Eth::new(
+                SourceMac::new(reply.sender_mac()).unwrap(),
+                DestinationMac::new(reply.target_mac()).unwrap(),
+                EthType::ARP,
+            )))
+            .arp(Some(reply))
+            .build()
+            .unwrap();
+        let mut buffer = TestBuffer::new();
+        headers.deparse(buffer.as_mut()).unwrap();
+        let packet = Packet::new(buffer).unwrap();
+        assert_eq!(packet.try_arp(), Some(&reply));
+        assert!(packet.try_ip().is_none());
+    }
+}
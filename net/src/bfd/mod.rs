@@ -0,0 +1,555 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [BFD][RFC5880] control packets.
+//!
+//! BFD control packets are the payload of UDP datagrams to port [`BfdControl::UDP_PORT`] (for
+//! single-hop sessions, see [RFC5881]). They are not parsed as part of the
+//! [`Headers`](crate::headers::Headers) of a packet.
+//!
+//! Authentication is not supported: packets with the authentication bit set are rejected.
+//!
+//! [RFC5880]: https://datatracker.ietf.org/doc/html/rfc5880#section-4.1
+//! [RFC5881]: https://datatracker.ietf.org/doc/html/rfc5881
+
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use core::fmt::{Display, Formatter};
+use core::num::NonZero;
+use std::time::Duration;
+
+/// The state of a BFD session, as advertised in a [`BfdControl`] packet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BfdState {
+    /// The session is administratively down
+    AdminDown = 0,
+    /// The session is down, or has just been created
+    Down = 1,
+    /// The local system is communicating with the remote system, and wants the session up
+    Init = 2,
+    /// The session is up
+    Up = 3,
+}
+
+/// The diagnostic code of a [`BfdControl`] packet: the reason for the last change of state of the
+/// session.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BfdDiag {
+    /// No diagnostic
+    None,
+    /// Control detection time expired
+    DetectionTimeExpired,
+    /// Echo function failed
+    EchoFailed,
+    /// Neighbor signaled session down
+    NeighborDown,
+    /// Forwarding plane reset
+    ForwardingPlaneReset,
+    /// Path down
+    PathDown,
+    /// Concatenated path down
+    ConcatenatedPathDown,
+    /// Administratively down
+    AdminDown,
+    /// Reverse concatenated path down
+    ReverseConcatenatedPathDown,
+    /// Any other (reserved) value
+    Reserved(u8),
+}
+
+/// A BFD control packet, without authentication section.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BfdControl {
+    diag: BfdDiag,
+    state: BfdState,
+    poll: bool,
+    final_: bool,
+    cpi: bool,
+    demand: bool,
+    detect_mult: NonZero<u8>,
+    my_discriminator: NonZero<u32>,
+    your_discriminator: u32,
+    desired_min_tx: u32,
+    required_min_rx: u32,
+    required_min_echo_rx: u32,
+}
+
+/// Errors which may occur when parsing a [`BfdControl`] packet.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BfdError {
+    /// The version is not 1.
+    #[error("invalid BFD version {0}")]
+    InvalidVersion(u8),
+    /// The length field is smaller than the minimum length, or larger than the packet.
+    #[error("invalid BFD length {0}")]
+    InvalidLength(u8),
+    /// The detection time multiplier is zero.
+    #[error("zero BFD detection time multiplier")]
+    ZeroDetectMult,
+    /// The multipoint bit is set.
+    #[error("BFD multipoint bit set")]
+    Multipoint,
+    /// The local discriminator of the sender is zero.
+    #[error("zero BFD discriminator")]
+    ZeroDiscriminator,
+    /// The packet is authenticated, which is not supported.
+    #[error("BFD authentication is not supported")]
+    Authentication,
+}
+
+impl BfdDiag {
+    fn from_raw(diag: u8) -> BfdDiag {
+        match diag {
+            0 => BfdDiag::None,
+            1 => BfdDiag::DetectionTimeExpired,
+            2 => BfdDiag::EchoFailed,
+            3 => BfdDiag::NeighborDown,
+            4 => BfdDiag::ForwardingPlaneReset,
+            5 => BfdDiag::PathDown,
+            6 => BfdDiag::ConcatenatedPathDown,
+            7 => BfdDiag::AdminDown,
+            8 => BfdDiag::ReverseConcatenatedPathDown,
+            other => BfdDiag::Reserved(other),
+        }
+    }
+
+    /// Get the value of this diagnostic code (5 bits)
+    #[must_use]
+    pub const fn as_u8(self) -> u8 {
+        match self {
+            BfdDiag::None => 0,
+            BfdDiag::DetectionTimeExpired => 1,
+            BfdDiag::EchoFailed => 2,
+            BfdDiag::NeighborDown => 3,
+            BfdDiag::ForwardingPlaneReset => 4,
+            BfdDiag::PathDown => 5,
+            BfdDiag::ConcatenatedPathDown => 6,
+            BfdDiag::AdminDown => 7,
+            BfdDiag::ReverseConcatenatedPathDown => 8,
+            BfdDiag::Reserved(other) => other & 0x1f,
+        }
+    }
+}
+
+impl BfdState {
+    fn from_raw(state: u8) -> BfdState {
+        match state & 0x03 {
+            0 => BfdState::AdminDown,
+            1 => BfdState::Down,
+            2 => BfdState::Init,
+            _ => BfdState::Up,
+        }
+    }
+}
+
+// Convert a duration to microseconds, saturating at the largest value representable on the wire
+fn micros(duration: Duration) -> u32 {
+    u32::try_from(duration.as_micros()).unwrap_or(u32::MAX)
+}
+
+impl BfdControl {
+    /// The UDP destination port of single-hop BFD control packets
+    pub const UDP_PORT: u16 = 3784;
+
+    /// The first UDP source port to use for BFD control packets
+    pub const MIN_SOURCE_PORT: u16 = 49152;
+
+    /// The IP TTL (or hop limit) of single-hop BFD control packets. Packets received with
+    /// another TTL must be discarded.
+    pub const TTL: u8 = 255;
+
+    /// Length of a BFD control packet without authentication section
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const LENGTH: NonZero<u16> = NonZero::new(24).unwrap();
+
+    const VERSION: u8 = 1;
+
+    const FLAG_POLL: u8 = 0x20;
+    const FLAG_FINAL: u8 = 0x10;
+    const FLAG_CPI: u8 = 0x08;
+    const FLAG_AUTH: u8 = 0x04;
+    const FLAG_DEMAND: u8 = 0x02;
+    const FLAG_MULTIPOINT: u8 = 0x01;
+
+    /// Create a new [`BfdControl`] packet, with no diagnostic, no flag set and no interval.
+    #[must_use]
+    pub const fn new(
+        state: BfdState,
+        detect_mult: NonZero<u8>,
+        my_discriminator: NonZero<u32>,
+        your_discriminator: u32,
+    ) -> BfdControl {
+        BfdControl {
+            diag: BfdDiag::None,
+            state,
+            poll: false,
+            final_: false,
+            cpi: false,
+            demand: false,
+            detect_mult,
+            my_discriminator,
+            your_discriminator,
+            desired_min_tx: 0,
+            required_min_rx: 0,
+            required_min_echo_rx: 0,
+        }
+    }
+
+    /// Get the diagnostic code of this packet
+    #[must_use]
+    pub const fn diag(&self) -> BfdDiag {
+        self.diag
+    }
+
+    /// Set the diagnostic code of this packet
+    pub const fn set_diag(&mut self, diag: BfdDiag) -> &mut BfdControl {
+        self.diag = diag;
+        self
+    }
+
+    /// Get the state of the session, as seen by the sender
+    #[must_use]
+    pub const fn state(&self) -> BfdState {
+        self.state
+    }
+
+    /// Tell if the poll bit is set: the sender requests verification of connectivity or of a
+    /// change of parameters.
+    #[must_use]
+    pub const fn poll(&self) -> bool {
+        self.poll
+    }
+
+    /// Set the poll bit
+    pub const fn set_poll(&mut self, poll: bool) -> &mut BfdControl {
+        self.poll = poll;
+        self
+    }
+
+    /// Tell if the final bit is set: this packet responds to a packet with the poll bit set.
+    #[must_use]
+    pub const fn final_(&self) -> bool {
+        self.final_
+    }
+
+    /// Set the final bit
+    pub const fn set_final(&mut self, final_: bool) -> &mut BfdControl {
+        self.final_ = final_;
+        self
+    }
+
+    /// Tell if the control plane independent bit is set
+    #[must_use]
+    pub const fn cpi(&self) -> bool {
+        self.cpi
+    }
+
+    /// Set the control plane independent bit
+    pub const fn set_cpi(&mut self, cpi: bool) -> &mut BfdControl {
+        self.cpi = cpi;
+        self
+    }
+
+    /// Tell if the demand bit is set
+    #[must_use]
+    pub const fn demand(&self) -> bool {
+        self.demand
+    }
+
+    /// Get the detection time multiplier of the sender
+    #[must_use]
+    pub const fn detect_mult(&self) -> NonZero<u8> {
+        self.detect_mult
+    }
+
+    /// Get the discriminator of the session, for the sender
+    #[must_use]
+    pub const fn my_discriminator(&self) -> NonZero<u32> {
+        self.my_discriminator
+    }
+
+    /// Get the discriminator of the session, for the receiver, or zero if unknown to the sender
+    #[must_use]
+    pub const fn your_discriminator(&self) -> u32 {
+        self.your_discriminator
+    }
+
+    /// Get the minimum interval the sender would like to use to transmit control packets
+    #[must_use]
+    pub fn desired_min_tx(&self) -> Duration {
+        Duration::from_micros(self.desired_min_tx.into())
+    }
+
+    /// Set the minimum interval the sender would like to use to transmit control packets
+    pub fn set_desired_min_tx(&mut self, interval: Duration) -> &mut BfdControl {
+        self.desired_min_tx = micros(interval);
+        self
+    }
+
+    /// Get the minimum interval between received control packets that the sender supports
+    #[must_use]
+    pub fn required_min_rx(&self) -> Duration {
+        Duration::from_micros(self.required_min_rx.into())
+    }
+
+    /// Set the minimum interval between received control packets that the sender supports
+    pub fn set_required_min_rx(&mut self, interval: Duration) -> &mut BfdControl {
+        self.required_min_rx = micros(interval);
+        self
+    }
+
+    /// Get the minimum interval between received echo packets that the sender supports, zero if
+    /// it does not support the echo function
+    #[must_use]
+    pub fn required_min_echo_rx(&self) -> Duration {
+        Duration::from_micros(self.required_min_echo_rx.into())
+    }
+}
+
+impl Parse for BfdControl {
+    type Error = BfdError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < BfdControl::LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: BfdControl::LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let version = buf[0] >> 5;
+        if version != BfdControl::VERSION {
+            return Err(ParseError::Invalid(BfdError::InvalidVersion(version)));
+        }
+        let flags = buf[1];
+        if flags & BfdControl::FLAG_AUTH != 0 {
+            return Err(ParseError::Invalid(BfdError::Authentication));
+        }
+        if flags & BfdControl::FLAG_MULTIPOINT != 0 {
+            return Err(ParseError::Invalid(BfdError::Multipoint));
+        }
+        let length = buf[3];
+        if u16::from(length) != BfdControl::LENGTH.get() {
+            return Err(ParseError::Invalid(BfdError::InvalidLength(length)));
+        }
+        let detect_mult =
+            NonZero::new(buf[2]).ok_or(ParseError::Invalid(BfdError::ZeroDetectMult))?;
+        let word = |offset: usize| {
+            u32::from_be_bytes([
+                buf[offset],
+                buf[offset + 1],
+                buf[offset + 2],
+                buf[offset + 3],
+            ])
+        };
+        let my_discriminator =
+            NonZero::new(word(4)).ok_or(ParseError::Invalid(BfdError::ZeroDiscriminator))?;
+        let control = BfdControl {
+            diag: BfdDiag::from_raw(buf[0] & 0x1f),
+            state: BfdState::from_raw(flags >> 6),
+            poll: flags & BfdControl::FLAG_POLL != 0,
+            final_: flags & BfdControl::FLAG_FINAL != 0,
+            cpi: flags & BfdControl::FLAG_CPI != 0,
+            demand: flags & BfdControl::FLAG_DEMAND != 0,
+            detect_mult,
+            my_discriminator,
+            your_discriminator: word(8),
+            desired_min_tx: word(12),
+            required_min_rx: word(16),
+            required_min_echo_rx: word(20),
+        };
+        Ok((control, BfdControl::LENGTH))
+    }
+}
+
+impl DeParse for BfdControl {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        BfdControl::LENGTH
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        if buf.len() < BfdControl::LENGTH.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: BfdControl::LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let mut flags = (self.state as u8) << 6;
+        for (set, flag) in [
+            (self.poll, BfdControl::FLAG_POLL),
+            (self.final_, BfdControl::FLAG_FINAL),
+            (self.cpi, BfdControl::FLAG_CPI),
+            (self.demand, BfdControl::FLAG_DEMAND),
+        ] {
+            if set {
+                flags |= flag;
+            }
+        }
+        buf[0] = (BfdControl::VERSION << 5) | self.diag.as_u8();
+        buf[1] = flags;
+        buf[2] = self.detect_mult.get();
+        #[allow(clippy::cast_possible_truncation)] // the length is 24
+        let length = BfdControl::LENGTH.get() as u8;
+        buf[3] = length;
+        buf[4..8].copy_from_slice(&self.my_discriminator.get().to_be_bytes());
+        buf[8..12].copy_from_slice(&self.your_discriminator.to_be_bytes());
+        buf[12..16].copy_from_slice(&self.desired_min_tx.to_be_bytes());
+        buf[16..20].copy_from_slice(&self.required_min_rx.to_be_bytes());
+        buf[20..24].copy_from_slice(&self.required_min_echo_rx.to_be_bytes());
+        Ok(BfdControl::LENGTH)
+    }
+}
+
+impl Display for BfdState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BfdState::AdminDown => write!(f, "admin-down"),
+            BfdState::Down => write!(f, "down"),
+            BfdState::Init => write!(f, "init"),
+            BfdState::Up => write!(f, "up"),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "bolero"))]
+mod contract {
+    use crate::bfd::{BfdControl, BfdDiag, BfdState};
+    use bolero::{Driver, TypeGenerator};
+    use core::num::NonZero;
+
+    impl TypeGenerator for BfdControl {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            let state = match driver.produce::<u8>()? % 4 {
+                0 => BfdState::AdminDown,
+                1 => BfdState::Down,
+                2 => BfdState::Init,
+                _ => BfdState::Up,
+            };
+            let mut control = BfdControl::new(
+                state,
+                NonZero::new(driver.produce()?)?,
+                NonZero::new(driver.produce()?)?,
+                driver.produce()?,
+            );
+            control
+                .set_diag(BfdDiag::from_raw(driver.produce::<u8>()? & 0x1f))
+                .set_poll(driver.produce()?)
+                .set_final(driver.produce()?)
+                .set_cpi(driver.produce()?);
+            control.demand = driver.produce()?;
+            control.desired_min_tx = driver.produce()?;
+            control.required_min_rx = driver.produce()?;
+            control.required_min_echo_rx = driver.produce()?;
+            Some(control)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bfd::{BfdControl, BfdDiag, BfdError, BfdState};
+    use crate::parse::{DeParse, Parse, ParseError};
+    use core::num::NonZero;
+    use std::time::Duration;
+
+    const LENGTH_USIZE: usize = 24;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!()
+            .with_type()
+            .for_each(|control: &BfdControl| {
+                let mut buf = [0u8; LENGTH_USIZE];
+                let bytes_written = control.deparse(&mut buf).unwrap_or_else(|_| unreachable!());
+                assert_eq!(bytes_written, control.size());
+                let (parsed, bytes_parsed) = BfdControl::parse(&buf).unwrap();
+                assert_eq!(parsed, *control);
+                assert_eq!(bytes_parsed, control.size());
+            });
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; LENGTH_USIZE]| {
+                let Ok((parsed, bytes_parsed)) = BfdControl::parse(slice) else {
+                    return;
+                };
+                let mut write_back_buffer = [0u8; LENGTH_USIZE];
+                let bytes_written = parsed
+                    .deparse(&mut write_back_buffer)
+                    .unwrap_or_else(|_| unreachable!());
+                assert_eq!(bytes_written, bytes_parsed);
+                assert_eq!(write_back_buffer, *slice);
+            });
+    }
+
+    #[test]
+    fn parse_control_packet() {
+        let mut control = BfdControl::new(
+            BfdState::Up,
+            NonZero::new(3).unwrap(),
+            NonZero::new(0x1234_5678).unwrap(),
+            0x9abc_def0,
+        );
+        control
+            .set_diag(BfdDiag::NeighborDown)
+            .set_poll(true)
+            .set_desired_min_tx(Duration::from_millis(100))
+            .set_required_min_rx(Duration::from_millis(300));
+        let mut buf = [0u8; LENGTH_USIZE];
+        control.deparse(&mut buf).unwrap();
+        assert_eq!(buf[..4], [0x23, 0xe0, 3, 24]);
+        // 100 ms in microseconds
+        assert_eq!(buf[12..16], 100_000u32.to_be_bytes());
+
+        let (parsed, _) = BfdControl::parse(&buf).unwrap();
+        assert_eq!(parsed.state(), BfdState::Up);
+        assert_eq!(parsed.diag(), BfdDiag::NeighborDown);
+        assert!(parsed.poll());
+        assert!(!parsed.final_());
+        assert_eq!(parsed.your_discriminator(), 0x9abc_def0);
+        assert_eq!(parsed.required_min_rx(), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn parse_of_invalid_control_packet_fails_gracefully() {
+        let control = BfdControl::new(
+            BfdState::Down,
+            NonZero::new(3).unwrap(),
+            NonZero::new(1).unwrap(),
+            0,
+        );
+        let mut buf = [0u8; LENGTH_USIZE];
+        control.deparse(&mut buf).unwrap();
+        assert!(matches!(
+            BfdControl::parse(&buf[..20]),
+            Err(ParseError::Length(_))
+        ));
+        let mut bad = buf;
+        bad[0] = 0x40; // version 2
+        assert!(matches!(
+            BfdControl::parse(&bad),
+            Err(ParseError::Invalid(BfdError::InvalidVersion(2)))
+        ));
+        let mut bad = buf;
+        bad[1] |= 0x04; // authentication
+        assert!(matches!(
+            BfdControl::parse(&bad),
+            Err(ParseError::Invalid(BfdError::Authentication))
+        ));
+        let mut bad = buf;
+        bad[2] = 0;
+        assert!(matches!(
+            BfdControl::parse(&bad),
+            Err(ParseError::Invalid(BfdError::ZeroDetectMult))
+        ));
+        let mut bad = buf;
+        bad[4..8].copy_from_slice(&[0; 4]);
+        assert!(matches!(
+            BfdControl::parse(&bad),
+            Err(ParseError::Invalid(BfdError::ZeroDiscriminator))
+        ));
+    }
+}
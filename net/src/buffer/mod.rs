@@ -19,18 +19,29 @@ impl<T> PacketBuffer for T where T: AsRef<[u8]> + Headroom + Debug + 'static {}
 
 /// Super trait representing the abstract operations which may be performed on mutable a packet buffer.
 pub trait PacketBufferMut:
-    PacketBuffer + AsMut<[u8]> + Prepend + Send + TrimFromStart + TrimFromEnd + Headroom + Tailroom
+    PacketBuffer
+    + AsMut<[u8]>
+    + Prepend
+    + Append
+    + Send
+    + TrimFromStart
+    + TrimFromEnd
+    + Headroom
+    + Tailroom
+    + Segments
 {
 }
 impl<T> PacketBufferMut for T where
     T: PacketBuffer
         + AsMut<[u8]>
         + Prepend
+        + Append
         + Send
         + TrimFromStart
         + TrimFromEnd
         + Headroom
         + Tailroom
+        + Segments
 {
 }
 
@@ -101,6 +112,62 @@ pub trait TrimFromEnd {
     fn trim_from_end(&mut self, len: u16) -> Result<&mut [u8], Self::Error>;
 }
 
+/// Trait representing a packet buffer whose data may be split over a chain of segments, as for
+/// DPDK multi-segment mbufs holding jumbo frames.
+///
+/// The [`AsRef<[u8]>`] and [`AsMut<[u8]>`] views of a packet buffer only cover its first segment,
+/// where the headers of the packet are expected to be found.
+/// The default methods describe a buffer made of a single segment.
+pub trait Segments: AsRef<[u8]> + AsMut<[u8]> {
+    /// Get the number of segments of the buffer.
+    fn num_segments(&self) -> u16 {
+        1
+    }
+
+    /// Iterate over the data of the segments of the buffer, in order, first segment included.
+    fn segments(&self) -> impl Iterator<Item = &[u8]> {
+        core::iter::once(self.as_ref())
+    }
+
+    /// Get a mutable reference to the data of the segment with index `index`, if any.
+    fn segment_mut(&mut self, index: u16) -> Option<&mut [u8]> {
+        if index == 0 {
+            Some(self.as_mut())
+        } else {
+            None
+        }
+    }
+
+    /// Get the length of the data of the buffer, over all its segments.
+    fn data_len(&self) -> usize {
+        self.segments().map(<[u8]>::len).sum()
+    }
+
+    /// Copy the data of the buffer starting at `offset` into `dst`, across segments, without
+    /// linearizing the buffer.
+    ///
+    /// Returns the number of bytes copied, which is less than the length of `dst` if the buffer
+    /// does not hold enough data past `offset`.
+    fn read(&self, offset: usize, dst: &mut [u8]) -> usize {
+        let mut skip = offset;
+        let mut copied = 0;
+        for segment in self.segments() {
+            if copied == dst.len() {
+                break;
+            }
+            if skip >= segment.len() {
+                skip -= segment.len();
+                continue;
+            }
+            let len = (segment.len() - skip).min(dst.len() - copied);
+            dst[copied..copied + len].copy_from_slice(&segment[skip..skip + len]);
+            copied += len;
+            skip = 0;
+        }
+        copied
+    }
+}
+
 /// Error indicating that there is not enough headroom in a memory buffer for the requested
 /// operation.
 #[non_exhaustive]
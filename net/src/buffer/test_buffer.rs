@@ -8,7 +8,7 @@ pub use contract::*;
 
 use crate::buffer::{
     Append, Headroom, MemoryBufferNotLongEnough, NotEnoughHeadRoom, NotEnoughTailRoom, Prepend,
-    Tailroom, TrimFromEnd, TrimFromStart,
+    Segments, Tailroom, TrimFromEnd, TrimFromStart,
 };
 use tracing::trace;
 
@@ -22,11 +22,14 @@ use crate::buffer::PacketBuffer;
 ///
 /// The core function of this structure is to facilitate testing by "faking" many useful properties
 /// of a real DPDK mbuf (without the need to spin up a full EAL).
+/// As for mbufs, the data of a `TestBuffer` may be split over a chain of segments (see
+/// [`TestBuffer::from_segments`]).
 #[derive(Debug, Clone)]
 pub struct TestBuffer {
     buffer: Vec<u8>,
     headroom: u16,
     tailroom: u16,
+    next: Option<Box<TestBuffer>>,
 }
 
 impl Drop for TestBuffer {
@@ -63,6 +66,7 @@ impl TestBuffer {
             buffer,
             headroom,
             tailroom,
+            next: None,
         }
     }
 
@@ -77,6 +81,31 @@ impl TestBuffer {
             buffer,
             headroom: TestBuffer::HEADROOM,
             tailroom: TestBuffer::TAILROOM,
+            next: None,
+        }
+    }
+
+    /// Create a new multi-segment `TestBuffer`, with one segment for each of the given slices of
+    /// octets.
+    ///
+    /// Only the first segment is seen through the [`AsRef<[u8]>`] view of the buffer.
+    #[must_use]
+    pub fn from_segments(segments: &[&[u8]]) -> TestBuffer {
+        let Some((first, rest)) = segments.split_first() else {
+            return TestBuffer::from_raw_data(&[]);
+        };
+        let mut buffer = TestBuffer::from_raw_data(first);
+        if !rest.is_empty() {
+            buffer.next = Some(Box::new(TestBuffer::from_segments(rest)));
+        }
+        buffer
+    }
+
+    /// Get the last segment of the buffer.
+    fn last_segment_mut(&mut self) -> &mut TestBuffer {
+        match self.next {
+            Some(ref mut next) => next.last_segment_mut(),
+            None => self,
         }
     }
 }
@@ -111,7 +140,11 @@ impl Headroom for TestBuffer {
 
 impl Tailroom for TestBuffer {
     fn tailroom(&self) -> u16 {
-        self.tailroom
+        // data is appended to the last segment
+        match &self.next {
+            Some(next) => next.tailroom(),
+            None => self.tailroom,
+        }
     }
 }
 
@@ -129,11 +162,12 @@ impl Prepend for TestBuffer {
 impl Append for TestBuffer {
     type Error = NotEnoughTailRoom;
     fn append(&mut self, len: u16) -> Result<&mut [u8], Self::Error> {
-        if self.tailroom < len {
+        let last = self.last_segment_mut();
+        if last.tailroom < len {
             return Err(NotEnoughTailRoom);
         }
-        self.tailroom -= len;
-        Ok(self.as_mut())
+        last.tailroom -= len;
+        Ok(last.as_mut())
     }
 }
 
@@ -159,14 +193,49 @@ impl TrimFromEnd for TestBuffer {
         debug_assert!(
             (self.headroom + self.tailroom) as usize + self.as_ref().len() == self.buffer.len()
         );
-        if (self.headroom + self.tailroom + len) as usize > self.buffer.len() {
+        if usize::from(len) > self.data_len() {
             return Err(MemoryBufferNotLongEnough);
         }
-        self.tailroom += len;
+        let next_len = self.next.as_deref().map_or(0, Segments::data_len);
+        match &mut self.next {
+            // the trimmed data is held by the following segments only
+            Some(next) if usize::from(len) < next_len => {
+                next.trim_from_end(len)?;
+            }
+            // release the following segments, and trim the rest from this one
+            _ => {
+                #[allow(clippy::cast_possible_truncation)] // bounded by len
+                let rest = len - next_len as u16;
+                self.next = None;
+                self.tailroom += rest;
+            }
+        }
         Ok(self.as_mut())
     }
 }
 
+impl Segments for TestBuffer {
+    fn num_segments(&self) -> u16 {
+        1 + self.next.as_deref().map_or(0, Segments::num_segments)
+    }
+
+    fn segments(&self) -> impl Iterator<Item = &[u8]> {
+        let mut segment = Some(self);
+        core::iter::from_fn(move || {
+            let current = segment?;
+            segment = current.next.as_deref();
+            Some(current.as_ref())
+        })
+    }
+
+    fn segment_mut(&mut self, index: u16) -> Option<&mut [u8]> {
+        if index == 0 {
+            return Some(self.as_mut());
+        }
+        self.next.as_mut()?.segment_mut(index - 1)
+    }
+}
+
 #[cfg(any(test, feature = "bolero"))]
 mod contract {
     use crate::buffer::TestBuffer;
@@ -315,3 +384,38 @@ mod contract {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::{Append, Segments, TestBuffer, TrimFromEnd};
+
+    #[test]
+    fn multi_segment_buffer() {
+        let mut buffer = TestBuffer::from_segments(&[&[0, 1, 2], &[3, 4], &[5, 6, 7]]);
+        assert_eq!(buffer.num_segments(), 3);
+        assert_eq!(buffer.as_ref(), &[0, 1, 2]);
+        assert_eq!(buffer.data_len(), 8);
+
+        let mut data = [0u8; 4];
+        assert_eq!(buffer.read(2, &mut data), 4);
+        assert_eq!(data, [2, 3, 4, 5]);
+        assert_eq!(buffer.read(6, &mut data), 2);
+        assert_eq!(data[..2], [6, 7]);
+
+        buffer.segment_mut(1).unwrap()[0] = 42;
+        assert!(buffer.segment_mut(3).is_none());
+
+        // trimming across segments releases the segments left empty
+        buffer.trim_from_end(4).unwrap();
+        assert_eq!(buffer.num_segments(), 2);
+        assert_eq!(
+            buffer.segments().collect::<Vec<_>>(),
+            [&[0u8, 1, 2][..], &[42u8][..]]
+        );
+        assert!(buffer.trim_from_end(5).is_err());
+
+        // data is appended to the last segment
+        assert_eq!(buffer.append(2).unwrap().len(), 3);
+        assert_eq!(buffer.data_len(), 6);
+    }
+}
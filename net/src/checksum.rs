@@ -3,8 +3,35 @@
 
 //! Traits for checksum calculation and manipulation
 
+use crate::headers::Net;
+use crate::ip::NextHeader;
 use std::fmt::Debug;
 
+/// Compute the ones' complement sum of the IP pseudo-header of a transport segment of `len` bytes
+/// carried in `net`, folded to 16 bits but _not_ complemented.
+///
+/// This is the value that NICs computing TCP and UDP checksums on transmission expect to find in
+/// the checksum field of the transport header.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)] // the sum is folded to 16 bits
+pub(crate) fn pseudo_header_sum(net: &Net, protocol: NextHeader, len: usize) -> u16 {
+    let words = |bytes: &[u8]| {
+        bytes
+            .chunks_exact(2)
+            .map(|word| u64::from(u16::from_be_bytes([word[0], word[1]])))
+            .sum::<u64>()
+    };
+    let mut sum = match net {
+        Net::Ipv4(ip) => words(&ip.0.source) + words(&ip.0.destination),
+        Net::Ipv6(ip) => words(&ip.0.source) + words(&ip.0.destination),
+    };
+    sum += u64::from(protocol.as_u8()) + len as u64;
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
 /// A trait for checksum calculation and manipulation.
 ///
 /// This trait is used to calculate and manipulate checksums in various headers.
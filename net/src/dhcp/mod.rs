@@ -0,0 +1,576 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [DHCP][RFC2131] message types and parsing.
+//!
+//! DHCP messages are not part of the [`Headers`] of a packet: they are parsed on demand from the
+//! payload of UDP datagrams (see [`Packet::dhcp`]).
+//!
+//! [`Headers`]: crate::headers::Headers
+//! [RFC2131]: https://datatracker.ietf.org/doc/html/rfc2131#section-2
+
+mod option;
+
+use crate::buffer::PacketBufferMut;
+use crate::eth::mac::Mac;
+use crate::headers::TryUdp;
+use crate::packet::Packet;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use crate::udp::port::UdpPort;
+use core::num::NonZero;
+pub use option::{DhcpMessageType, DhcpOption, RelayAgentSubOption};
+use std::net::Ipv4Addr;
+use tracing::debug;
+
+/// The operation of a [`Dhcp`] message (the BOOTP `op` field).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum DhcpOp {
+    /// Message from a client to a server
+    Request = 1,
+    /// Message from a server to a client
+    Reply = 2,
+}
+
+impl TryFrom<u8> for DhcpOp {
+    type Error = DhcpError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(DhcpOp::Request),
+            2 => Ok(DhcpOp::Reply),
+            _ => Err(DhcpError::InvalidOp(value)),
+        }
+    }
+}
+
+/// A DHCP message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dhcp {
+    op: DhcpOp,
+    htype: u8,
+    hlen: u8,
+    hops: u8,
+    xid: u32,
+    secs: u16,
+    flags: u16,
+    ciaddr: Ipv4Addr,
+    yiaddr: Ipv4Addr,
+    siaddr: Ipv4Addr,
+    giaddr: Ipv4Addr,
+    chaddr: [u8; 16],
+    sname: [u8; 64],
+    file: [u8; 128],
+    options: Vec<DhcpOption>,
+}
+
+/// Errors which may occur when parsing a [`Dhcp`] message.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DhcpError {
+    /// The op field is neither a request nor a reply.
+    #[error("invalid DHCP op {0}")]
+    InvalidOp(u8),
+    /// The options are not preceded by the DHCP magic cookie.
+    #[error("invalid DHCP magic cookie {0:#010x}")]
+    InvalidMagicCookie(u32),
+    /// An option overflows the message.
+    #[error("malformed DHCP options")]
+    MalformedOptions,
+    /// The length of an option is invalid for its code.
+    #[error("invalid length {len} for DHCP option {code}")]
+    InvalidOptionLength {
+        /// Code of the option
+        code: u8,
+        /// Length of the data of the option
+        len: usize,
+    },
+    /// Unknown message type.
+    #[error("invalid DHCP message type {0}")]
+    InvalidMessageType(u8),
+}
+
+impl Dhcp {
+    /// UDP port of DHCP servers (and relay agents).
+    #[allow(unsafe_code)] // const-eval and trivially safe
+    pub const SERVER_PORT: UdpPort = unsafe { UdpPort::new_unchecked(67) };
+
+    /// UDP port of DHCP clients.
+    #[allow(unsafe_code)] // const-eval and trivially safe
+    pub const CLIENT_PORT: UdpPort = unsafe { UdpPort::new_unchecked(68) };
+
+    /// The length of a [`Dhcp`] message without options (the fixed BOOTP fields and the magic
+    /// cookie).
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(240).unwrap();
+
+    /// The magic cookie marking the start of the options.
+    const MAGIC_COOKIE: u32 = 0x6382_5363;
+    /// Hardware type of ethernet
+    const HTYPE_ETHERNET: u8 = 1;
+    /// Broadcast bit of the flags field
+    const FLAG_BROADCAST: u16 = 0x8000;
+
+    /// Create a new message for a client with an ethernet address, without options.
+    #[must_use]
+    pub fn new(op: DhcpOp, xid: u32, client: Mac) -> Dhcp {
+        let mut chaddr = [0u8; 16];
+        chaddr[..6].copy_from_slice(&client.0);
+        Dhcp {
+            op,
+            htype: Dhcp::HTYPE_ETHERNET,
+            hlen: 6,
+            hops: 0,
+            xid,
+            secs: 0,
+            flags: 0,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: Ipv4Addr::UNSPECIFIED,
+            chaddr,
+            sname: [0u8; 64],
+            file: [0u8; 128],
+            options: Vec::new(),
+        }
+    }
+
+    /// Get the operation of this message.
+    #[must_use]
+    pub const fn op(&self) -> DhcpOp {
+        self.op
+    }
+
+    /// Get the hardware address type of the client.
+    #[must_use]
+    pub const fn htype(&self) -> u8 {
+        self.htype
+    }
+
+    /// Get the number of relay agents this message went through.
+    #[must_use]
+    pub const fn hops(&self) -> u8 {
+        self.hops
+    }
+
+    /// Set the number of relay agents this message went through.
+    pub const fn set_hops(&mut self, hops: u8) -> &mut Dhcp {
+        self.hops = hops;
+        self
+    }
+
+    /// Get the transaction ID of this message.
+    #[must_use]
+    pub const fn xid(&self) -> u32 {
+        self.xid
+    }
+
+    /// Get the number of seconds elapsed since the client began the acquisition of its address.
+    #[must_use]
+    pub const fn secs(&self) -> u16 {
+        self.secs
+    }
+
+    /// Tell if the client requested the replies to be broadcast.
+    #[must_use]
+    pub const fn broadcast(&self) -> bool {
+        self.flags & Dhcp::FLAG_BROADCAST != 0
+    }
+
+    /// Set or clear the broadcast flag.
+    pub const fn set_broadcast(&mut self, broadcast: bool) -> &mut Dhcp {
+        if broadcast {
+            self.flags |= Dhcp::FLAG_BROADCAST;
+        } else {
+            self.flags &= !Dhcp::FLAG_BROADCAST;
+        }
+        self
+    }
+
+    /// Get the current address of the client (`ciaddr`).
+    #[must_use]
+    pub const fn client_addr(&self) -> Ipv4Addr {
+        self.ciaddr
+    }
+
+    /// Set the current address of the client (`ciaddr`).
+    pub const fn set_client_addr(&mut self, addr: Ipv4Addr) -> &mut Dhcp {
+        self.ciaddr = addr;
+        self
+    }
+
+    /// Get the address assigned to the client (`yiaddr`).
+    #[must_use]
+    pub const fn your_addr(&self) -> Ipv4Addr {
+        self.yiaddr
+    }
+
+    /// Set the address assigned to the client (`yiaddr`).
+    pub const fn set_your_addr(&mut self, addr: Ipv4Addr) -> &mut Dhcp {
+        self.yiaddr = addr;
+        self
+    }
+
+    /// Get the address of the next server to use in bootstrap (`siaddr`).
+    #[must_use]
+    pub const fn server_addr(&self) -> Ipv4Addr {
+        self.siaddr
+    }
+
+    /// Get the address of the relay agent (`giaddr`).
+    #[must_use]
+    pub const fn relay_addr(&self) -> Ipv4Addr {
+        self.giaddr
+    }
+
+    /// Set the address of the relay agent (`giaddr`).
+    pub const fn set_relay_addr(&mut self, addr: Ipv4Addr) -> &mut Dhcp {
+        self.giaddr = addr;
+        self
+    }
+
+    /// Get the hardware address of the client (`chaddr`), as long as given by the `hlen` field
+    /// (at most 16 bytes).
+    #[must_use]
+    pub fn client_hw_addr(&self) -> &[u8] {
+        &self.chaddr[..usize::from(self.hlen).min(self.chaddr.len())]
+    }
+
+    /// Get the ethernet address of the client, if the client has one.
+    #[must_use]
+    pub fn client_mac(&self) -> Option<Mac> {
+        if self.htype != Dhcp::HTYPE_ETHERNET || self.hlen != 6 {
+            return None;
+        }
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&self.chaddr[..6]);
+        Some(Mac(mac))
+    }
+
+    /// Get the options of this message.
+    #[must_use]
+    pub fn options(&self) -> &[DhcpOption] {
+        &self.options
+    }
+
+    /// Get the options of this message, for modification.
+    #[must_use]
+    pub fn options_mut(&mut self) -> &mut Vec<DhcpOption> {
+        &mut self.options
+    }
+
+    /// Get the first option with the given `code`, if any.
+    #[must_use]
+    pub fn option(&self, code: u8) -> Option<&DhcpOption> {
+        self.options.iter().find(|option| option.code() == code)
+    }
+
+    /// Get the type of this message, if it has a message type option.
+    #[must_use]
+    pub fn message_type(&self) -> Option<DhcpMessageType> {
+        self.options.iter().find_map(|option| match option {
+            DhcpOption::MessageType(message_type) => Some(*message_type),
+            _ => None,
+        })
+    }
+
+    /// Length of the message on the wire, in bytes, including the end option
+    fn len(&self) -> usize {
+        Dhcp::MIN_LENGTH.into_non_zero_usize().get()
+            + self.options.iter().map(DhcpOption::len).sum::<usize>()
+            + 1
+    }
+}
+
+impl Parse for Dhcp {
+    type Error = DhcpError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() > u16::MAX as usize {
+            return Err(ParseError::BufferTooLong(buf.len()));
+        }
+        if buf.len() < Dhcp::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Dhcp::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let op = DhcpOp::try_from(buf[0]).map_err(ParseError::Invalid)?;
+        let addr = |offset: usize| {
+            Ipv4Addr::new(
+                buf[offset],
+                buf[offset + 1],
+                buf[offset + 2],
+                buf[offset + 3],
+            )
+        };
+        let cookie = u32::from_be_bytes([buf[236], buf[237], buf[238], buf[239]]);
+        if cookie != Dhcp::MAGIC_COOKIE {
+            debug!("DHCP message with invalid magic cookie {cookie:#010x}");
+            return Err(ParseError::Invalid(DhcpError::InvalidMagicCookie(cookie)));
+        }
+        let mut dhcp = Dhcp {
+            op,
+            htype: buf[1],
+            hlen: buf[2],
+            hops: buf[3],
+            xid: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            secs: u16::from_be_bytes([buf[8], buf[9]]),
+            flags: u16::from_be_bytes([buf[10], buf[11]]),
+            ciaddr: addr(12),
+            yiaddr: addr(16),
+            siaddr: addr(20),
+            giaddr: addr(24),
+            chaddr: [0u8; 16],
+            sname: [0u8; 64],
+            file: [0u8; 128],
+            options: Vec::new(),
+        };
+        dhcp.chaddr.copy_from_slice(&buf[28..44]);
+        dhcp.sname.copy_from_slice(&buf[44..108]);
+        dhcp.file.copy_from_slice(&buf[108..236]);
+
+        // options run until the end option, or until the end of the buffer
+        let mut offset = Dhcp::MIN_LENGTH.into_non_zero_usize().get();
+        while let Some(&code) = buf.get(offset) {
+            offset += 1;
+            match code {
+                DhcpOption::PAD => continue,
+                DhcpOption::END => break,
+                _ => {}
+            }
+            let Some(&len) = buf.get(offset) else {
+                return Err(ParseError::Invalid(DhcpError::MalformedOptions));
+            };
+            offset += 1;
+            let Some(data) = buf.get(offset..offset + usize::from(len)) else {
+                return Err(ParseError::Invalid(DhcpError::MalformedOptions));
+            };
+            dhcp.options
+                .push(DhcpOption::parse(code, data).map_err(ParseError::Invalid)?);
+            offset += usize::from(len);
+        }
+        #[allow(clippy::cast_possible_truncation)] // buffer length checked above
+        let consumed = NonZero::new(offset as u16).unwrap_or_else(|| unreachable!());
+        Ok((dhcp, consumed))
+    }
+}
+
+impl DeParse for Dhcp {
+    type Error = ();
+
+    /// The size of the message, or [`u16::MAX`] if its options are too long to be written.
+    fn size(&self) -> NonZero<u16> {
+        let len = u16::try_from(self.len()).unwrap_or(u16::MAX);
+        NonZero::new(len).unwrap_or_else(|| unreachable!())
+    }
+
+    /// Write the message to `buf`, followed by the end option.
+    ///
+    /// The message is not padded to the 300 bytes minimum length of BOOTP messages.
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let len = self.len();
+        if len > usize::from(u16::MAX) || !self.options.iter().all(DhcpOption::is_valid) {
+            return Err(DeParseError::Invalid(()));
+        }
+        let size = self.size();
+        if buf.len() < len {
+            return Err(DeParseError::Length(LengthError {
+                expected: size.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        buf[0] = self.op as u8;
+        buf[1] = self.htype;
+        buf[2] = self.hlen;
+        buf[3] = self.hops;
+        buf[4..8].copy_from_slice(&self.xid.to_be_bytes());
+        buf[8..10].copy_from_slice(&self.secs.to_be_bytes());
+        buf[10..12].copy_from_slice(&self.flags.to_be_bytes());
+        buf[12..16].copy_from_slice(&self.ciaddr.octets());
+        buf[16..20].copy_from_slice(&self.yiaddr.octets());
+        buf[20..24].copy_from_slice(&self.siaddr.octets());
+        buf[24..28].copy_from_slice(&self.giaddr.octets());
+        buf[28..44].copy_from_slice(&self.chaddr);
+        buf[44..108].copy_from_slice(&self.sname);
+        buf[108..236].copy_from_slice(&self.file);
+        buf[236..240].copy_from_slice(&Dhcp::MAGIC_COOKIE.to_be_bytes());
+        let mut offset = Dhcp::MIN_LENGTH.into_non_zero_usize().get();
+        for option in &self.options {
+            option.write(&mut buf[offset..]);
+            offset += option.len();
+        }
+        buf[offset] = DhcpOption::END;
+        debug_assert_eq!(offset + 1, len);
+        Ok(size)
+    }
+}
+
+impl<Buf: PacketBufferMut> Packet<Buf> {
+    /// Parse the payload of this packet as a [`Dhcp`] message, if the packet is a UDP datagram
+    /// from or to a DHCP port.
+    ///
+    /// Returns `None` if the packet is not a DHCP packet, or if the message fails to parse.
+    #[must_use]
+    pub fn dhcp(&self) -> Option<Dhcp> {
+        let udp = self.try_udp()?;
+        let ports = [Dhcp::SERVER_PORT, Dhcp::CLIENT_PORT];
+        if !ports.contains(&udp.source()) && !ports.contains(&udp.destination()) {
+            return None;
+        }
+        Dhcp::parse(self.payload().as_ref())
+            .map_err(|e| debug!("failed to parse DHCP message: {e:?}"))
+            .map(|(dhcp, _)| dhcp)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dhcp::{Dhcp, DhcpError, DhcpMessageType, DhcpOp, DhcpOption, RelayAgentSubOption};
+    use crate::eth::mac::Mac;
+    use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, Parse, ParseError};
+    use std::net::Ipv4Addr;
+
+    /// A DHCPDISCOVER message from a client with a host name, padded to 300 bytes.
+    fn discover() -> Vec<u8> {
+        let mut buf = vec![0u8; 300];
+        buf[..4].copy_from_slice(&[1, 1, 6, 0]);
+        buf[4..8].copy_from_slice(&0x1234_5678_u32.to_be_bytes());
+        buf[10] = 0x80;
+        buf[28..34].copy_from_slice(&[2, 0, 0, 0, 0, 1]);
+        buf[236..240].copy_from_slice(&[99, 130, 83, 99]);
+        let options = [
+            &[53, 1, 1][..],
+            &[55, 3, 1, 3, 6],
+            &[0, 0],
+            &[12, 4, b'h', b'o', b's', b't'],
+            &[255],
+        ];
+        let mut offset = 240;
+        for option in options {
+            buf[offset..offset + option.len()].copy_from_slice(option);
+            offset += option.len();
+        }
+        buf
+    }
+
+    #[test]
+    fn parse_discover() {
+        let buf = discover();
+        let (dhcp, consumed) = Dhcp::parse(&buf).unwrap();
+        assert_eq!(consumed.get(), 240 + 3 + 5 + 2 + 6 + 1);
+        assert_eq!(dhcp.op(), DhcpOp::Request);
+        assert_eq!(dhcp.xid(), 0x1234_5678);
+        assert!(dhcp.broadcast());
+        assert_eq!(dhcp.client_mac(), Some(Mac([2, 0, 0, 0, 0, 1])));
+        assert_eq!(dhcp.message_type(), Some(DhcpMessageType::Discover));
+        assert_eq!(
+            dhcp.options(),
+            &[
+                DhcpOption::MessageType(DhcpMessageType::Discover),
+                DhcpOption::ParameterRequestList(vec![1, 3, 6]),
+                DhcpOption::HostName(b"host".to_vec()),
+            ]
+        );
+
+        // written back without the pad options
+        let mut written = vec![0u8; dhcp.size().into_non_zero_usize().get()];
+        dhcp.deparse(&mut written).unwrap();
+        let expected = [&buf[..248], &buf[250..257]].concat();
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn relay_agent_round_trip() {
+        let mut dhcp = Dhcp::new(DhcpOp::Request, 7, Mac([2, 0, 0, 0, 0, 1]));
+        dhcp.set_hops(1)
+            .set_relay_addr(Ipv4Addr::new(192, 168, 1, 1));
+        dhcp.options_mut().extend([
+            DhcpOption::MessageType(DhcpMessageType::Request),
+            DhcpOption::RequestedIpAddress(Ipv4Addr::new(192, 168, 1, 10)),
+            DhcpOption::Router(vec![Ipv4Addr::new(192, 168, 1, 1)]),
+            DhcpOption::RelayAgentInformation(vec![
+                RelayAgentSubOption::new(RelayAgentSubOption::CIRCUIT_ID, b"eth0".to_vec())
+                    .unwrap(),
+                RelayAgentSubOption::new(RelayAgentSubOption::REMOTE_ID, vec![1, 2]).unwrap(),
+            ]),
+            DhcpOption::Other {
+                code: 119,
+                data: vec![3, b'f', b'o', b'o', 0],
+            },
+        ]);
+        let mut buf = vec![0u8; dhcp.size().into_non_zero_usize().get()];
+        assert_eq!(dhcp.deparse(&mut buf).unwrap(), dhcp.size());
+        let (parsed, consumed) = Dhcp::parse(&buf).unwrap();
+        assert_eq!(parsed, dhcp);
+        assert_eq!(consumed, dhcp.size());
+        assert_eq!(parsed.relay_addr(), Ipv4Addr::new(192, 168, 1, 1));
+
+        assert!(matches!(
+            dhcp.deparse(&mut buf[..250]),
+            Err(DeParseError::Length(_))
+        ));
+        dhcp.options_mut().push(DhcpOption::Router(vec![]));
+        assert!(matches!(
+            dhcp.deparse(&mut buf),
+            Err(DeParseError::Invalid(()))
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let buf = discover();
+        assert!(matches!(
+            Dhcp::parse(&buf[..239]),
+            Err(ParseError::Length(_))
+        ));
+        let mut bad = buf.clone();
+        bad[0] = 3;
+        assert!(matches!(
+            Dhcp::parse(&bad),
+            Err(ParseError::Invalid(DhcpError::InvalidOp(3)))
+        ));
+        let mut bad = buf.clone();
+        bad[236] = 0;
+        assert!(matches!(
+            Dhcp::parse(&bad),
+            Err(ParseError::Invalid(DhcpError::InvalidMagicCookie(_)))
+        ));
+        let mut bad = buf.clone();
+        bad[242] = 9;
+        assert!(matches!(
+            Dhcp::parse(&bad),
+            Err(ParseError::Invalid(DhcpError::InvalidMessageType(9)))
+        ));
+        let mut bad = buf.clone();
+        bad[241] = 2;
+        assert!(matches!(
+            Dhcp::parse(&bad),
+            Err(ParseError::Invalid(DhcpError::InvalidOptionLength {
+                code: 53,
+                len: 2
+            }))
+        ));
+        // an option overflowing the message
+        assert!(matches!(
+            Dhcp::parse(&buf[..252]),
+            Err(ParseError::Invalid(DhcpError::MalformedOptions))
+        ));
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!().with_type().for_each(|slice: &[u8; 300]| {
+            let mut slice = *slice;
+            slice[0] = 1 + (slice[0] & 1);
+            slice[236..240].copy_from_slice(&[99, 130, 83, 99]);
+            let Ok((parsed, _)) = Dhcp::parse(&slice) else {
+                return;
+            };
+            let mut buf = vec![0u8; parsed.size().into_non_zero_usize().get()];
+            parsed.deparse(&mut buf).unwrap();
+            let (reparsed, _) = Dhcp::parse(&buf).unwrap();
+            assert_eq!(reparsed, parsed);
+        });
+    }
+}
@@ -0,0 +1,344 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [DHCP options][RFC2132].
+//!
+//! [RFC2132]: https://datatracker.ietf.org/doc/html/rfc2132
+
+use crate::dhcp::DhcpError;
+use std::net::Ipv4Addr;
+
+/// The type of a DHCP message, carried in the [`DhcpOption::MessageType`] option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum DhcpMessageType {
+    /// Client broadcast to locate available servers
+    Discover = 1,
+    /// Server to client, in response to a discover message, with an offer of parameters
+    Offer = 2,
+    /// Client message to request offered parameters, or to extend a lease
+    Request = 3,
+    /// Client to server, indicating that the offered address is already in use
+    Decline = 4,
+    /// Server to client, with configuration parameters and committed address
+    Ack = 5,
+    /// Server to client, refusing the request of the client
+    Nak = 6,
+    /// Client to server, relinquishing the address of the client
+    Release = 7,
+    /// Client to server, asking for local configuration parameters only
+    Inform = 8,
+}
+
+impl TryFrom<u8> for DhcpMessageType {
+    type Error = DhcpError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => DhcpMessageType::Discover,
+            2 => DhcpMessageType::Offer,
+            3 => DhcpMessageType::Request,
+            4 => DhcpMessageType::Decline,
+            5 => DhcpMessageType::Ack,
+            6 => DhcpMessageType::Nak,
+            7 => DhcpMessageType::Release,
+            8 => DhcpMessageType::Inform,
+            _ => return Err(DhcpError::InvalidMessageType(value)),
+        })
+    }
+}
+
+/// A sub-option of the [relay agent information][RFC3046] option.
+///
+/// [RFC3046]: https://datatracker.ietf.org/doc/html/rfc3046#section-2.0
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RelayAgentSubOption {
+    code: u8,
+    data: Vec<u8>,
+}
+
+impl RelayAgentSubOption {
+    /// Code of the agent circuit ID sub-option
+    pub const CIRCUIT_ID: u8 = 1;
+    /// Code of the agent remote ID sub-option
+    pub const REMOTE_ID: u8 = 2;
+
+    /// Create a new sub-option.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DhcpError::InvalidOptionLength`] if `data` is longer than 255 bytes.
+    pub fn new(code: u8, data: Vec<u8>) -> Result<RelayAgentSubOption, DhcpError> {
+        if data.len() > usize::from(u8::MAX) {
+            return Err(DhcpError::InvalidOptionLength {
+                code: DhcpOption::RELAY_AGENT_INFORMATION,
+                len: data.len(),
+            });
+        }
+        Ok(RelayAgentSubOption { code, data })
+    }
+
+    /// Get the code of this sub-option.
+    #[must_use]
+    pub const fn code(&self) -> u8 {
+        self.code
+    }
+
+    /// Get the data of this sub-option.
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Length of the sub-option on the wire, in bytes
+    fn len(&self) -> usize {
+        2 + self.data.len()
+    }
+}
+
+/// A DHCP option.
+///
+/// Options with a known code are parsed to their typed variant; any other option is kept as
+/// [`DhcpOption::Other`] (which should not be used for known codes).
+/// The pad and end options are not represented: they are skipped on parsing, and the end option
+/// is added when writing a message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DhcpOption {
+    /// Subnet mask of the client (option 1)
+    SubnetMask(Ipv4Addr),
+    /// Routers on the subnet of the client, in order of preference (option 3)
+    Router(Vec<Ipv4Addr>),
+    /// DNS servers available to the client, in order of preference (option 6)
+    DomainNameServer(Vec<Ipv4Addr>),
+    /// Name of the client (option 12)
+    HostName(Vec<u8>),
+    /// Domain name the client should use for DNS resolution (option 15)
+    DomainName(Vec<u8>),
+    /// Address requested by the client (option 50)
+    RequestedIpAddress(Ipv4Addr),
+    /// Lease time of the address, in seconds (option 51)
+    LeaseTime(u32),
+    /// Type of the message (option 53)
+    MessageType(DhcpMessageType),
+    /// Address of the server (option 54)
+    ServerIdentifier(Ipv4Addr),
+    /// Codes of the options requested by the client (option 55)
+    ParameterRequestList(Vec<u8>),
+    /// Time until the client should renew its lease, in seconds (option 58)
+    RenewalTime(u32),
+    /// Time until the client should rebind its lease, in seconds (option 59)
+    RebindingTime(u32),
+    /// Unique identifier of the client (option 61)
+    ClientIdentifier(Vec<u8>),
+    /// Information added by a relay agent (option 82)
+    RelayAgentInformation(Vec<RelayAgentSubOption>),
+    /// Any other option
+    Other {
+        /// Code of the option (neither 0 nor 255)
+        code: u8,
+        /// Data of the option
+        data: Vec<u8>,
+    },
+}
+
+impl DhcpOption {
+    /// Code of the pad option
+    pub const PAD: u8 = 0;
+    /// Code of the subnet mask option
+    pub const SUBNET_MASK: u8 = 1;
+    /// Code of the router option
+    pub const ROUTER: u8 = 3;
+    /// Code of the domain name server option
+    pub const DOMAIN_NAME_SERVER: u8 = 6;
+    /// Code of the host name option
+    pub const HOST_NAME: u8 = 12;
+    /// Code of the domain name option
+    pub const DOMAIN_NAME: u8 = 15;
+    /// Code of the requested IP address option
+    pub const REQUESTED_IP_ADDRESS: u8 = 50;
+    /// Code of the lease time option
+    pub const LEASE_TIME: u8 = 51;
+    /// Code of the message type option
+    pub const MESSAGE_TYPE: u8 = 53;
+    /// Code of the server identifier option
+    pub const SERVER_IDENTIFIER: u8 = 54;
+    /// Code of the parameter request list option
+    pub const PARAMETER_REQUEST_LIST: u8 = 55;
+    /// Code of the renewal time option
+    pub const RENEWAL_TIME: u8 = 58;
+    /// Code of the rebinding time option
+    pub const REBINDING_TIME: u8 = 59;
+    /// Code of the client identifier option
+    pub const CLIENT_IDENTIFIER: u8 = 61;
+    /// Code of the relay agent information option
+    pub const RELAY_AGENT_INFORMATION: u8 = 82;
+    /// Code of the end option
+    pub const END: u8 = 255;
+
+    /// Get the code of this option.
+    #[must_use]
+    pub const fn code(&self) -> u8 {
+        match self {
+            DhcpOption::SubnetMask(_) => DhcpOption::SUBNET_MASK,
+            DhcpOption::Router(_) => DhcpOption::ROUTER,
+            DhcpOption::DomainNameServer(_) => DhcpOption::DOMAIN_NAME_SERVER,
+            DhcpOption::HostName(_) => DhcpOption::HOST_NAME,
+            DhcpOption::DomainName(_) => DhcpOption::DOMAIN_NAME,
+            DhcpOption::RequestedIpAddress(_) => DhcpOption::REQUESTED_IP_ADDRESS,
+            DhcpOption::LeaseTime(_) => DhcpOption::LEASE_TIME,
+            DhcpOption::MessageType(_) => DhcpOption::MESSAGE_TYPE,
+            DhcpOption::ServerIdentifier(_) => DhcpOption::SERVER_IDENTIFIER,
+            DhcpOption::ParameterRequestList(_) => DhcpOption::PARAMETER_REQUEST_LIST,
+            DhcpOption::RenewalTime(_) => DhcpOption::RENEWAL_TIME,
+            DhcpOption::RebindingTime(_) => DhcpOption::REBINDING_TIME,
+            DhcpOption::ClientIdentifier(_) => DhcpOption::CLIENT_IDENTIFIER,
+            DhcpOption::RelayAgentInformation(_) => DhcpOption::RELAY_AGENT_INFORMATION,
+            DhcpOption::Other { code, .. } => *code,
+        }
+    }
+
+    /// Parse the `data` of an option with the given `code`.
+    pub(crate) fn parse(code: u8, data: &[u8]) -> Result<DhcpOption, DhcpError> {
+        let invalid_length = || DhcpError::InvalidOptionLength {
+            code,
+            len: data.len(),
+        };
+        let addr = || -> Result<Ipv4Addr, DhcpError> {
+            let bytes: [u8; 4] = data.try_into().map_err(|_| invalid_length())?;
+            Ok(Ipv4Addr::from(bytes))
+        };
+        let addrs = || -> Result<Vec<Ipv4Addr>, DhcpError> {
+            if data.is_empty() || !data.len().is_multiple_of(4) {
+                return Err(invalid_length());
+            }
+            Ok(data
+                .chunks_exact(4)
+                .map(|chunk| Ipv4Addr::new(chunk[0], chunk[1], chunk[2], chunk[3]))
+                .collect())
+        };
+        let time = || -> Result<u32, DhcpError> {
+            let bytes: [u8; 4] = data.try_into().map_err(|_| invalid_length())?;
+            Ok(u32::from_be_bytes(bytes))
+        };
+        Ok(match code {
+            DhcpOption::SUBNET_MASK => DhcpOption::SubnetMask(addr()?),
+            DhcpOption::ROUTER => DhcpOption::Router(addrs()?),
+            DhcpOption::DOMAIN_NAME_SERVER => DhcpOption::DomainNameServer(addrs()?),
+            DhcpOption::HOST_NAME => DhcpOption::HostName(data.to_vec()),
+            DhcpOption::DOMAIN_NAME => DhcpOption::DomainName(data.to_vec()),
+            DhcpOption::REQUESTED_IP_ADDRESS => DhcpOption::RequestedIpAddress(addr()?),
+            DhcpOption::LEASE_TIME => DhcpOption::LeaseTime(time()?),
+            DhcpOption::MESSAGE_TYPE => match data {
+                [message_type] => {
+                    DhcpOption::MessageType(DhcpMessageType::try_from(*message_type)?)
+                }
+                _ => return Err(invalid_length()),
+            },
+            DhcpOption::SERVER_IDENTIFIER => DhcpOption::ServerIdentifier(addr()?),
+            DhcpOption::PARAMETER_REQUEST_LIST => DhcpOption::ParameterRequestList(data.to_vec()),
+            DhcpOption::RENEWAL_TIME => DhcpOption::RenewalTime(time()?),
+            DhcpOption::REBINDING_TIME => DhcpOption::RebindingTime(time()?),
+            DhcpOption::CLIENT_IDENTIFIER => DhcpOption::ClientIdentifier(data.to_vec()),
+            DhcpOption::RELAY_AGENT_INFORMATION => {
+                let mut sub_options = Vec::new();
+                let mut rest = data;
+                while let [code, len, tail @ ..] = rest {
+                    let Some(sub_data) = tail.get(..usize::from(*len)) else {
+                        return Err(DhcpError::MalformedOptions);
+                    };
+                    sub_options.push(RelayAgentSubOption {
+                        code: *code,
+                        data: sub_data.to_vec(),
+                    });
+                    rest = &tail[usize::from(*len)..];
+                }
+                if !rest.is_empty() {
+                    return Err(DhcpError::MalformedOptions);
+                }
+                DhcpOption::RelayAgentInformation(sub_options)
+            }
+            _ => DhcpOption::Other {
+                code,
+                data: data.to_vec(),
+            },
+        })
+    }
+
+    /// Length of the data of the option, in bytes
+    fn data_len(&self) -> usize {
+        match self {
+            DhcpOption::SubnetMask(_)
+            | DhcpOption::RequestedIpAddress(_)
+            | DhcpOption::ServerIdentifier(_)
+            | DhcpOption::LeaseTime(_)
+            | DhcpOption::RenewalTime(_)
+            | DhcpOption::RebindingTime(_) => 4,
+            DhcpOption::MessageType(_) => 1,
+            DhcpOption::Router(addrs) | DhcpOption::DomainNameServer(addrs) => addrs.len() * 4,
+            DhcpOption::HostName(data)
+            | DhcpOption::DomainName(data)
+            | DhcpOption::ParameterRequestList(data)
+            | DhcpOption::ClientIdentifier(data)
+            | DhcpOption::Other { data, .. } => data.len(),
+            DhcpOption::RelayAgentInformation(sub_options) => {
+                sub_options.iter().map(RelayAgentSubOption::len).sum()
+            }
+        }
+    }
+
+    /// Length of the option on the wire, in bytes
+    pub(crate) fn len(&self) -> usize {
+        2 + self.data_len()
+    }
+
+    /// Returns true if this option can be written: its data must fit the one-byte length field,
+    /// address lists must not be empty, and [`DhcpOption::Other`] must not use the code of the pad
+    /// or end options.
+    pub(crate) fn is_valid(&self) -> bool {
+        let valid = match self {
+            DhcpOption::Router(addrs) | DhcpOption::DomainNameServer(addrs) => !addrs.is_empty(),
+            DhcpOption::Other { code, .. } => !matches!(*code, DhcpOption::PAD | DhcpOption::END),
+            _ => true,
+        };
+        valid && self.data_len() <= usize::from(u8::MAX)
+    }
+
+    /// Write the option to `buf`, which must be at least [`DhcpOption::len`] bytes long.
+    pub(crate) fn write(&self, buf: &mut [u8]) {
+        buf[0] = self.code();
+        #[allow(clippy::cast_possible_truncation)] // checked by is_valid
+        let len = self.data_len() as u8;
+        buf[1] = len;
+        let data = &mut buf[2..self.len()];
+        match self {
+            DhcpOption::SubnetMask(addr)
+            | DhcpOption::RequestedIpAddress(addr)
+            | DhcpOption::ServerIdentifier(addr) => data.copy_from_slice(&addr.octets()),
+            DhcpOption::LeaseTime(time)
+            | DhcpOption::RenewalTime(time)
+            | DhcpOption::RebindingTime(time) => data.copy_from_slice(&time.to_be_bytes()),
+            DhcpOption::MessageType(message_type) => data[0] = *message_type as u8,
+            DhcpOption::Router(addrs) | DhcpOption::DomainNameServer(addrs) => {
+                for (chunk, addr) in data.chunks_exact_mut(4).zip(addrs) {
+                    chunk.copy_from_slice(&addr.octets());
+                }
+            }
+            DhcpOption::HostName(bytes)
+            | DhcpOption::DomainName(bytes)
+            | DhcpOption::ParameterRequestList(bytes)
+            | DhcpOption::ClientIdentifier(bytes)
+            | DhcpOption::Other { data: bytes, .. } => data.copy_from_slice(bytes),
+            DhcpOption::RelayAgentInformation(sub_options) => {
+                let mut offset = 0;
+                for sub_option in sub_options {
+                    data[offset] = sub_option.code;
+                    #[allow(clippy::cast_possible_truncation)] // checked on creation
+                    let sub_len = sub_option.data.len() as u8;
+                    data[offset + 1] = sub_len;
+                    data[offset + 2..offset + sub_option.len()].copy_from_slice(&sub_option.data);
+                    offset += sub_option.len();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,613 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [DHCPv6][RFC8415] message types and parsing.
+//!
+//! DHCPv6 messages are not part of the [`Headers`] of a packet: they are parsed on demand from
+//! the payload of UDP datagrams (see [`Packet::dhcpv6`]).
+//!
+//! [`Headers`]: crate::headers::Headers
+//! [RFC8415]: https://datatracker.ietf.org/doc/html/rfc8415#section-8
+
+mod option;
+
+use crate::buffer::PacketBufferMut;
+use crate::headers::TryUdp;
+use crate::packet::Packet;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use crate::udp::port::UdpPort;
+use core::num::NonZero;
+pub use option::Dhcpv6Option;
+use std::net::Ipv6Addr;
+use tracing::debug;
+
+/// The type of a DHCPv6 message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Dhcpv6MessageType {
+    /// Client message to locate servers
+    Solicit = 1,
+    /// Server message indicating that it is available, in response to a solicit message
+    Advertise = 2,
+    /// Client message to request configuration parameters from a server
+    Request = 3,
+    /// Client message to check that its addresses are still appropriate for its link
+    Confirm = 4,
+    /// Client message to extend its leases, to the server which provided them
+    Renew = 5,
+    /// Client message to extend its leases, to any server
+    Rebind = 6,
+    /// Server message with the leases and configuration parameters of the client
+    Reply = 7,
+    /// Client message indicating that it no longer uses its leases
+    Release = 8,
+    /// Client message indicating that its addresses are already in use on the link
+    Decline = 9,
+    /// Server message informing the client that it has new or updated configuration parameters
+    Reconfigure = 10,
+    /// Client message to request configuration parameters without leases
+    InformationRequest = 11,
+    /// Relay agent message forwarding a message to a server
+    RelayForw = 12,
+    /// Server message to a relay agent, with a message to deliver to a client
+    RelayRepl = 13,
+}
+
+impl Dhcpv6MessageType {
+    /// Tell if this is the type of a relay agent/server message ([`Dhcpv6Relay`]).
+    #[must_use]
+    pub const fn is_relay(self) -> bool {
+        matches!(
+            self,
+            Dhcpv6MessageType::RelayForw | Dhcpv6MessageType::RelayRepl
+        )
+    }
+}
+
+impl TryFrom<u8> for Dhcpv6MessageType {
+    type Error = Dhcpv6Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => Dhcpv6MessageType::Solicit,
+            2 => Dhcpv6MessageType::Advertise,
+            3 => Dhcpv6MessageType::Request,
+            4 => Dhcpv6MessageType::Confirm,
+            5 => Dhcpv6MessageType::Renew,
+            6 => Dhcpv6MessageType::Rebind,
+            7 => Dhcpv6MessageType::Reply,
+            8 => Dhcpv6MessageType::Release,
+            9 => Dhcpv6MessageType::Decline,
+            10 => Dhcpv6MessageType::Reconfigure,
+            11 => Dhcpv6MessageType::InformationRequest,
+            12 => Dhcpv6MessageType::RelayForw,
+            13 => Dhcpv6MessageType::RelayRepl,
+            _ => return Err(Dhcpv6Error::InvalidMessageType(value)),
+        })
+    }
+}
+
+/// Errors which may occur when creating or parsing a [`Dhcpv6`] message.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Dhcpv6Error {
+    /// Unknown message type, or message type not matching the message format.
+    #[error("invalid DHCPv6 message type {0}")]
+    InvalidMessageType(u8),
+    /// The transaction ID is a 24-bit number.
+    #[error("invalid DHCPv6 transaction ID {0:#x}")]
+    InvalidTransactionId(u32),
+    /// An option overflows the message.
+    #[error("malformed DHCPv6 options")]
+    MalformedOptions,
+    /// The length of an option is invalid for its code.
+    #[error("invalid length {len} for DHCPv6 option {code}")]
+    InvalidOptionLength {
+        /// Code of the option
+        code: u16,
+        /// Length of the data of the option
+        len: usize,
+    },
+    /// Relay messages are nested deeper than the maximum hop count.
+    #[error("DHCPv6 relay messages nested too deep")]
+    TooDeep,
+}
+
+/// A DHCPv6 message exchanged between a client and a server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dhcpv6Message {
+    msg_type: Dhcpv6MessageType,
+    transaction_id: u32,
+    options: Vec<Dhcpv6Option>,
+}
+
+/// A DHCPv6 message exchanged between a relay agent and a server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dhcpv6Relay {
+    msg_type: Dhcpv6MessageType,
+    hop_count: u8,
+    link_address: Ipv6Addr,
+    peer_address: Ipv6Addr,
+    options: Vec<Dhcpv6Option>,
+}
+
+/// A DHCPv6 message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dhcpv6 {
+    /// A client/server message
+    Message(Dhcpv6Message),
+    /// A relay agent/server message
+    Relay(Dhcpv6Relay),
+}
+
+impl Dhcpv6Message {
+    /// The length of a [`Dhcpv6Message`] without options.
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(4).unwrap();
+
+    /// The maximum value of a transaction ID.
+    pub const MAX_TRANSACTION_ID: u32 = 0x00ff_ffff;
+
+    /// Create a new message without options.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Dhcpv6Error::InvalidMessageType`] if `msg_type` is the type of a relay message,
+    /// or [`Dhcpv6Error::InvalidTransactionId`] if `transaction_id` does not fit 24 bits.
+    pub fn new(
+        msg_type: Dhcpv6MessageType,
+        transaction_id: u32,
+    ) -> Result<Dhcpv6Message, Dhcpv6Error> {
+        if msg_type.is_relay() {
+            return Err(Dhcpv6Error::InvalidMessageType(msg_type as u8));
+        }
+        if transaction_id > Dhcpv6Message::MAX_TRANSACTION_ID {
+            return Err(Dhcpv6Error::InvalidTransactionId(transaction_id));
+        }
+        Ok(Dhcpv6Message {
+            msg_type,
+            transaction_id,
+            options: Vec::new(),
+        })
+    }
+
+    /// Get the type of this message.
+    #[must_use]
+    pub const fn msg_type(&self) -> Dhcpv6MessageType {
+        self.msg_type
+    }
+
+    /// Get the (24-bit) transaction ID of this message.
+    #[must_use]
+    pub const fn transaction_id(&self) -> u32 {
+        self.transaction_id
+    }
+}
+
+impl Dhcpv6Relay {
+    /// The length of a [`Dhcpv6Relay`] message without options.
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(34).unwrap();
+
+    /// The maximum number of relay agents a message may go through.
+    pub const HOP_COUNT_LIMIT: u8 = 32;
+
+    /// Create a new relay message without options.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Dhcpv6Error::InvalidMessageType`] if `msg_type` is not the type of a relay
+    /// message.
+    pub fn new(
+        msg_type: Dhcpv6MessageType,
+        hop_count: u8,
+        link_address: Ipv6Addr,
+        peer_address: Ipv6Addr,
+    ) -> Result<Dhcpv6Relay, Dhcpv6Error> {
+        if !msg_type.is_relay() {
+            return Err(Dhcpv6Error::InvalidMessageType(msg_type as u8));
+        }
+        Ok(Dhcpv6Relay {
+            msg_type,
+            hop_count,
+            link_address,
+            peer_address,
+            options: Vec::new(),
+        })
+    }
+
+    /// Get the type of this message.
+    #[must_use]
+    pub const fn msg_type(&self) -> Dhcpv6MessageType {
+        self.msg_type
+    }
+
+    /// Get the number of relay agents this message went through.
+    #[must_use]
+    pub const fn hop_count(&self) -> u8 {
+        self.hop_count
+    }
+
+    /// Get the address identifying the link of the client.
+    #[must_use]
+    pub const fn link_address(&self) -> Ipv6Addr {
+        self.link_address
+    }
+
+    /// Get the address of the client or relay agent the message was received from (or is to be
+    /// relayed to).
+    #[must_use]
+    pub const fn peer_address(&self) -> Ipv6Addr {
+        self.peer_address
+    }
+
+    /// Get the relayed message, if this message has a relay message option.
+    #[must_use]
+    pub fn relayed(&self) -> Option<&Dhcpv6> {
+        self.options.iter().find_map(|option| match option {
+            Dhcpv6Option::RelayMessage(message) => Some(message.as_ref()),
+            _ => None,
+        })
+    }
+}
+
+impl Dhcpv6 {
+    /// UDP port of DHCPv6 servers and relay agents.
+    #[allow(unsafe_code)] // const-eval and trivially safe
+    pub const SERVER_PORT: UdpPort = unsafe { UdpPort::new_unchecked(547) };
+
+    /// UDP port of DHCPv6 clients.
+    #[allow(unsafe_code)] // const-eval and trivially safe
+    pub const CLIENT_PORT: UdpPort = unsafe { UdpPort::new_unchecked(546) };
+
+    /// Get the type of this message.
+    #[must_use]
+    pub const fn msg_type(&self) -> Dhcpv6MessageType {
+        match self {
+            Dhcpv6::Message(message) => message.msg_type,
+            Dhcpv6::Relay(relay) => relay.msg_type,
+        }
+    }
+
+    /// Get the options of this message.
+    #[must_use]
+    pub fn options(&self) -> &[Dhcpv6Option] {
+        match self {
+            Dhcpv6::Message(message) => &message.options,
+            Dhcpv6::Relay(relay) => &relay.options,
+        }
+    }
+
+    /// Get the options of this message, for modification.
+    #[must_use]
+    pub fn options_mut(&mut self) -> &mut Vec<Dhcpv6Option> {
+        match self {
+            Dhcpv6::Message(message) => &mut message.options,
+            Dhcpv6::Relay(relay) => &mut relay.options,
+        }
+    }
+
+    /// Get the first option with the given `code`, if any.
+    #[must_use]
+    pub fn option(&self, code: u16) -> Option<&Dhcpv6Option> {
+        self.options().iter().find(|option| option.code() == code)
+    }
+
+    /// Length of the fixed part of the message, in bytes
+    fn header_len(&self) -> usize {
+        match self {
+            Dhcpv6::Message(_) => Dhcpv6Message::MIN_LENGTH,
+            Dhcpv6::Relay(_) => Dhcpv6Relay::MIN_LENGTH,
+        }
+        .into_non_zero_usize()
+        .get()
+    }
+
+    /// Length of the message on the wire, in bytes
+    fn len(&self) -> usize {
+        self.header_len() + self.options().iter().map(Dhcpv6Option::len).sum::<usize>()
+    }
+
+    /// Returns true if the message and all its options can be written.
+    fn is_valid(&self) -> bool {
+        self.len() <= usize::from(u16::MAX) && self.options().iter().all(Dhcpv6Option::is_valid)
+    }
+
+    /// Write the message to `buf`, which must be at least [`Dhcpv6::len`] bytes long.
+    fn write(&self, buf: &mut [u8]) {
+        buf[0] = self.msg_type() as u8;
+        match self {
+            Dhcpv6::Message(message) => {
+                buf[1..4].copy_from_slice(&message.transaction_id.to_be_bytes()[1..]);
+            }
+            Dhcpv6::Relay(relay) => {
+                buf[1] = relay.hop_count;
+                buf[2..18].copy_from_slice(&relay.link_address.octets());
+                buf[18..34].copy_from_slice(&relay.peer_address.octets());
+            }
+        }
+        let mut offset = self.header_len();
+        for option in self.options() {
+            option.write(&mut buf[offset..]);
+            offset += option.len();
+        }
+        debug_assert_eq!(offset, self.len());
+    }
+
+    /// Parse a message filling `buf`, `depth` being the number of relay messages it is nested in.
+    fn parse_nested(buf: &[u8], depth: u8) -> Result<Dhcpv6, Dhcpv6Error> {
+        if depth > Dhcpv6Relay::HOP_COUNT_LIMIT {
+            return Err(Dhcpv6Error::TooDeep);
+        }
+        // lengths are checked by the caller for the outer message
+        let Some(&msg_type) = buf.first() else {
+            return Err(Dhcpv6Error::MalformedOptions);
+        };
+        let msg_type = Dhcpv6MessageType::try_from(msg_type)?;
+        let header_len = if msg_type.is_relay() {
+            Dhcpv6Relay::MIN_LENGTH
+        } else {
+            Dhcpv6Message::MIN_LENGTH
+        }
+        .into_non_zero_usize()
+        .get();
+        if buf.len() < header_len {
+            return Err(Dhcpv6Error::MalformedOptions);
+        }
+        let mut options = Vec::new();
+        let mut rest = &buf[header_len..];
+        while !rest.is_empty() {
+            let [code_hi, code_lo, len_hi, len_lo, tail @ ..] = rest else {
+                return Err(Dhcpv6Error::MalformedOptions);
+            };
+            let code = u16::from_be_bytes([*code_hi, *code_lo]);
+            let len = usize::from(u16::from_be_bytes([*len_hi, *len_lo]));
+            let Some(data) = tail.get(..len) else {
+                return Err(Dhcpv6Error::MalformedOptions);
+            };
+            options.push(Dhcpv6Option::parse(code, data, depth)?);
+            rest = &tail[len..];
+        }
+        let address = |offset: usize| {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&buf[offset..offset + 16]);
+            Ipv6Addr::from(octets)
+        };
+        Ok(if msg_type.is_relay() {
+            Dhcpv6::Relay(Dhcpv6Relay {
+                msg_type,
+                hop_count: buf[1],
+                link_address: address(2),
+                peer_address: address(18),
+                options,
+            })
+        } else {
+            Dhcpv6::Message(Dhcpv6Message {
+                msg_type,
+                transaction_id: u32::from_be_bytes([0, buf[1], buf[2], buf[3]]),
+                options,
+            })
+        })
+    }
+}
+
+impl Parse for Dhcpv6 {
+    type Error = Dhcpv6Error;
+
+    /// Parse a message; the options of the message run until the end of `buf`.
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() > u16::MAX as usize {
+            return Err(ParseError::BufferTooLong(buf.len()));
+        }
+        let relay = buf
+            .first()
+            .and_then(|&msg_type| Dhcpv6MessageType::try_from(msg_type).ok())
+            .is_some_and(Dhcpv6MessageType::is_relay);
+        let expected = if relay {
+            Dhcpv6Relay::MIN_LENGTH
+        } else {
+            Dhcpv6Message::MIN_LENGTH
+        };
+        if buf.len() < expected.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: expected.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let dhcpv6 = Dhcpv6::parse_nested(buf, 0).map_err(ParseError::Invalid)?;
+        #[allow(clippy::cast_possible_truncation)] // buffer length checked above
+        let consumed = NonZero::new(buf.len() as u16).unwrap_or_else(|| unreachable!());
+        Ok((dhcpv6, consumed))
+    }
+}
+
+impl DeParse for Dhcpv6 {
+    type Error = ();
+
+    /// The size of the message, or [`u16::MAX`] if its options are too long to be written.
+    fn size(&self) -> NonZero<u16> {
+        let len = u16::try_from(self.len()).unwrap_or(u16::MAX);
+        NonZero::new(len).unwrap_or_else(|| unreachable!())
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        if !self.is_valid() {
+            return Err(DeParseError::Invalid(()));
+        }
+        let size = self.size();
+        if buf.len() < size.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: size.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        self.write(buf);
+        Ok(size)
+    }
+}
+
+impl<Buf: PacketBufferMut> Packet<Buf> {
+    /// Parse the payload of this packet as a [`Dhcpv6`] message, if the packet is a UDP datagram
+    /// from or to a DHCPv6 port.
+    ///
+    /// Returns `None` if the packet is not a DHCPv6 packet, or if the message fails to parse.
+    #[must_use]
+    pub fn dhcpv6(&self) -> Option<Dhcpv6> {
+        let udp = self.try_udp()?;
+        let ports = [Dhcpv6::SERVER_PORT, Dhcpv6::CLIENT_PORT];
+        if !ports.contains(&udp.source()) && !ports.contains(&udp.destination()) {
+            return None;
+        }
+        Dhcpv6::parse(self.payload().as_ref())
+            .map_err(|e| debug!("failed to parse DHCPv6 message: {e:?}"))
+            .map(|(dhcpv6, _)| dhcpv6)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dhcpv6::{
+        Dhcpv6, Dhcpv6Error, Dhcpv6Message, Dhcpv6MessageType, Dhcpv6Option, Dhcpv6Relay,
+    };
+    use crate::parse::{DeParse, IntoNonZeroUSize, Parse, ParseError};
+    use std::net::Ipv6Addr;
+
+    /// A solicit message with a client ID, an option request and an elapsed time.
+    const SOLICIT: [u8; 28] = [
+        1, 0x12, 0x34, 0x56, // type, transaction ID
+        0, 1, 0, 4, 0, 3, 0, 1, // client ID
+        0, 6, 0, 4, 0, 23, 0, 24, // option request
+        0, 8, 0, 2, 0, 0, // elapsed time
+        0, 0, // truncated option
+    ];
+
+    #[test]
+    fn parse_solicit() {
+        let buf = &SOLICIT[..26];
+        let (parsed, consumed) = Dhcpv6::parse(buf).unwrap();
+        assert_eq!(consumed.into_non_zero_usize().get(), buf.len());
+        let Dhcpv6::Message(message) = &parsed else {
+            unreachable!()
+        };
+        assert_eq!(message.msg_type(), Dhcpv6MessageType::Solicit);
+        assert_eq!(message.transaction_id(), 0x12_3456);
+        assert_eq!(
+            parsed.options(),
+            &[
+                Dhcpv6Option::ClientId(vec![0, 3, 0, 1]),
+                Dhcpv6Option::OptionRequest(vec![23, 24]),
+                Dhcpv6Option::ElapsedTime(0),
+            ]
+        );
+        let mut written = [0u8; 26];
+        assert_eq!(parsed.deparse(&mut written).unwrap(), consumed);
+        assert_eq!(&written, buf);
+
+        assert!(matches!(
+            Dhcpv6::parse(&SOLICIT),
+            Err(ParseError::Invalid(Dhcpv6Error::MalformedOptions))
+        ));
+    }
+
+    #[test]
+    fn relay_round_trip() {
+        let mut solicit = Dhcpv6::parse(&SOLICIT[..26]).unwrap().0;
+        solicit
+            .options_mut()
+            .push(Dhcpv6Option::DnsServers(vec![Ipv6Addr::LOCALHOST]));
+        let mut relay = Dhcpv6::Relay(
+            Dhcpv6Relay::new(
+                Dhcpv6MessageType::RelayForw,
+                0,
+                "2001:db8::1".parse().unwrap(),
+                "fe80::1".parse().unwrap(),
+            )
+            .unwrap(),
+        );
+        relay.options_mut().extend([
+            Dhcpv6Option::InterfaceId(b"eth0".to_vec()),
+            Dhcpv6Option::RelayMessage(Box::new(solicit.clone())),
+            Dhcpv6Option::Other {
+                code: 37,
+                data: vec![0, 0, 0, 9, 1, 2],
+            },
+        ]);
+        let mut buf = vec![0u8; relay.size().into_non_zero_usize().get()];
+        assert_eq!(relay.deparse(&mut buf).unwrap(), relay.size());
+        let (parsed, consumed) = Dhcpv6::parse(&buf).unwrap();
+        assert_eq!(parsed, relay);
+        assert_eq!(consumed, relay.size());
+        let Dhcpv6::Relay(parsed) = parsed else {
+            unreachable!()
+        };
+        assert_eq!(
+            parsed.peer_address(),
+            "fe80::1".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(parsed.relayed(), Some(&solicit));
+    }
+
+    #[test]
+    fn invalid_messages() {
+        assert_eq!(
+            Dhcpv6Message::new(Dhcpv6MessageType::Solicit, 0x0100_0000).unwrap_err(),
+            Dhcpv6Error::InvalidTransactionId(0x0100_0000)
+        );
+        assert_eq!(
+            Dhcpv6Message::new(Dhcpv6MessageType::RelayRepl, 1).unwrap_err(),
+            Dhcpv6Error::InvalidMessageType(13)
+        );
+        assert!(matches!(
+            Dhcpv6::parse(&[12; 20]),
+            Err(ParseError::Length(_))
+        ));
+        assert!(matches!(
+            Dhcpv6::parse(&[14, 0, 0, 0]),
+            Err(ParseError::Invalid(Dhcpv6Error::InvalidMessageType(14)))
+        ));
+        assert!(matches!(
+            Dhcpv6::parse(&[1, 0, 0, 0, 0, 8, 0, 1, 0]),
+            Err(ParseError::Invalid(Dhcpv6Error::InvalidOptionLength {
+                code: 8,
+                len: 1
+            }))
+        ));
+
+        // relay messages nested over the hop count limit
+        let mut message =
+            Dhcpv6::Message(Dhcpv6Message::new(Dhcpv6MessageType::Solicit, 1).unwrap());
+        for _ in 0..=Dhcpv6Relay::HOP_COUNT_LIMIT {
+            let mut relay = Dhcpv6::Relay(
+                Dhcpv6Relay::new(
+                    Dhcpv6MessageType::RelayForw,
+                    0,
+                    Ipv6Addr::UNSPECIFIED,
+                    Ipv6Addr::UNSPECIFIED,
+                )
+                .unwrap(),
+            );
+            relay
+                .options_mut()
+                .push(Dhcpv6Option::RelayMessage(Box::new(message)));
+            message = relay;
+        }
+        let mut buf = vec![0u8; message.size().into_non_zero_usize().get()];
+        message.deparse(&mut buf).unwrap();
+        assert!(matches!(
+            Dhcpv6::parse(&buf),
+            Err(ParseError::Invalid(Dhcpv6Error::TooDeep))
+        ));
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!().with_type().for_each(|slice: &[u8; 64]| {
+            let Ok((parsed, _)) = Dhcpv6::parse(slice) else {
+                return;
+            };
+            let mut buf = [0u8; 64];
+            assert_eq!(parsed.deparse(&mut buf).unwrap().get(), 64);
+            assert_eq!(&buf, slice);
+        });
+    }
+}
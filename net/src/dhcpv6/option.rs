@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [DHCPv6 options][RFC8415].
+//!
+//! [RFC8415]: https://datatracker.ietf.org/doc/html/rfc8415#section-21
+
+use crate::dhcpv6::{Dhcpv6, Dhcpv6Error};
+use std::net::Ipv6Addr;
+
+/// A DHCPv6 option.
+///
+/// Options with a known code are parsed to their typed variant; any other option is kept as
+/// [`Dhcpv6Option::Other`] (which should not be used for known codes).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dhcpv6Option {
+    /// DUID of the client (option 1)
+    ClientId(Vec<u8>),
+    /// DUID of the server (option 2)
+    ServerId(Vec<u8>),
+    /// Codes of the options requested by the client (option 6)
+    OptionRequest(Vec<u16>),
+    /// Time since the client began the exchange, in hundredths of a second (option 8)
+    ElapsedTime(u16),
+    /// The message relayed by a relay agent (option 9)
+    RelayMessage(Box<Dhcpv6>),
+    /// Identifier of the interface on which a relay agent received the message (option 18)
+    InterfaceId(Vec<u8>),
+    /// DNS recursive name servers available to the client (option 23)
+    DnsServers(Vec<Ipv6Addr>),
+    /// Any other option
+    Other {
+        /// Code of the option
+        code: u16,
+        /// Data of the option
+        data: Vec<u8>,
+    },
+}
+
+impl Dhcpv6Option {
+    /// Code of the client identifier option
+    pub const CLIENT_ID: u16 = 1;
+    /// Code of the server identifier option
+    pub const SERVER_ID: u16 = 2;
+    /// Code of the option request option
+    pub const OPTION_REQUEST: u16 = 6;
+    /// Code of the elapsed time option
+    pub const ELAPSED_TIME: u16 = 8;
+    /// Code of the relay message option
+    pub const RELAY_MESSAGE: u16 = 9;
+    /// Code of the interface ID option
+    pub const INTERFACE_ID: u16 = 18;
+    /// Code of the DNS recursive name server option
+    pub const DNS_SERVERS: u16 = 23;
+
+    /// Get the code of this option.
+    #[must_use]
+    pub const fn code(&self) -> u16 {
+        match self {
+            Dhcpv6Option::ClientId(_) => Dhcpv6Option::CLIENT_ID,
+            Dhcpv6Option::ServerId(_) => Dhcpv6Option::SERVER_ID,
+            Dhcpv6Option::OptionRequest(_) => Dhcpv6Option::OPTION_REQUEST,
+            Dhcpv6Option::ElapsedTime(_) => Dhcpv6Option::ELAPSED_TIME,
+            Dhcpv6Option::RelayMessage(_) => Dhcpv6Option::RELAY_MESSAGE,
+            Dhcpv6Option::InterfaceId(_) => Dhcpv6Option::INTERFACE_ID,
+            Dhcpv6Option::DnsServers(_) => Dhcpv6Option::DNS_SERVERS,
+            Dhcpv6Option::Other { code, .. } => *code,
+        }
+    }
+
+    /// Parse the `data` of an option with the given `code`, `depth` being the number of relay
+    /// messages the option is nested in.
+    pub(crate) fn parse(code: u16, data: &[u8], depth: u8) -> Result<Dhcpv6Option, Dhcpv6Error> {
+        let invalid_length = || Dhcpv6Error::InvalidOptionLength {
+            code,
+            len: data.len(),
+        };
+        Ok(match code {
+            Dhcpv6Option::CLIENT_ID => Dhcpv6Option::ClientId(data.to_vec()),
+            Dhcpv6Option::SERVER_ID => Dhcpv6Option::ServerId(data.to_vec()),
+            Dhcpv6Option::OPTION_REQUEST => {
+                if !data.len().is_multiple_of(2) {
+                    return Err(invalid_length());
+                }
+                Dhcpv6Option::OptionRequest(
+                    data.chunks_exact(2)
+                        .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+                        .collect(),
+                )
+            }
+            Dhcpv6Option::ELAPSED_TIME => {
+                let bytes: [u8; 2] = data.try_into().map_err(|_| invalid_length())?;
+                Dhcpv6Option::ElapsedTime(u16::from_be_bytes(bytes))
+            }
+            Dhcpv6Option::RELAY_MESSAGE => {
+                Dhcpv6Option::RelayMessage(Box::new(Dhcpv6::parse_nested(data, depth + 1)?))
+            }
+            Dhcpv6Option::INTERFACE_ID => Dhcpv6Option::InterfaceId(data.to_vec()),
+            Dhcpv6Option::DNS_SERVERS => {
+                if !data.len().is_multiple_of(16) {
+                    return Err(invalid_length());
+                }
+                Dhcpv6Option::DnsServers(
+                    data.chunks_exact(16)
+                        .map(|chunk| {
+                            let mut octets = [0u8; 16];
+                            octets.copy_from_slice(chunk);
+                            Ipv6Addr::from(octets)
+                        })
+                        .collect(),
+                )
+            }
+            _ => Dhcpv6Option::Other {
+                code,
+                data: data.to_vec(),
+            },
+        })
+    }
+
+    /// Length of the data of the option, in bytes
+    fn data_len(&self) -> usize {
+        match self {
+            Dhcpv6Option::ClientId(data)
+            | Dhcpv6Option::ServerId(data)
+            | Dhcpv6Option::InterfaceId(data)
+            | Dhcpv6Option::Other { data, .. } => data.len(),
+            Dhcpv6Option::OptionRequest(codes) => codes.len() * 2,
+            Dhcpv6Option::ElapsedTime(_) => 2,
+            Dhcpv6Option::RelayMessage(message) => message.len(),
+            Dhcpv6Option::DnsServers(addrs) => addrs.len() * 16,
+        }
+    }
+
+    /// Length of the option on the wire, in bytes
+    pub(crate) fn len(&self) -> usize {
+        4 + self.data_len()
+    }
+
+    /// Returns true if this option can be written: its data must fit the two-byte length field.
+    pub(crate) fn is_valid(&self) -> bool {
+        let valid = match self {
+            Dhcpv6Option::RelayMessage(message) => message.is_valid(),
+            _ => true,
+        };
+        valid && self.data_len() <= usize::from(u16::MAX)
+    }
+
+    /// Write the option to `buf`, which must be at least [`Dhcpv6Option::len`] bytes long.
+    pub(crate) fn write(&self, buf: &mut [u8]) {
+        buf[0..2].copy_from_slice(&self.code().to_be_bytes());
+        #[allow(clippy::cast_possible_truncation)] // checked by is_valid
+        let len = self.data_len() as u16;
+        buf[2..4].copy_from_slice(&len.to_be_bytes());
+        let data = &mut buf[4..self.len()];
+        match self {
+            Dhcpv6Option::ClientId(bytes)
+            | Dhcpv6Option::ServerId(bytes)
+            | Dhcpv6Option::InterfaceId(bytes)
+            | Dhcpv6Option::Other { data: bytes, .. } => data.copy_from_slice(bytes),
+            Dhcpv6Option::OptionRequest(codes) => {
+                for (chunk, code) in data.chunks_exact_mut(2).zip(codes) {
+                    chunk.copy_from_slice(&code.to_be_bytes());
+                }
+            }
+            Dhcpv6Option::ElapsedTime(time) => data.copy_from_slice(&time.to_be_bytes()),
+            Dhcpv6Option::RelayMessage(message) => message.write(data),
+            Dhcpv6Option::DnsServers(addrs) => {
+                for (chunk, addr) in data.chunks_exact_mut(16).zip(addrs) {
+                    chunk.copy_from_slice(&addr.octets());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [IPsec ESP][RFC4303] header type and logic.
+//!
+//! We do not terminate IPsec: only the security parameter index (SPI) and the sequence number,
+//! which are sent in the clear, are parsed, so that flows can be classified (and load-balanced)
+//! by SPI. The rest of the packet, encrypted, is left in the payload.
+//!
+//! [RFC4303]: https://datatracker.ietf.org/doc/html/rfc4303#section-2
+
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use core::fmt::{Display, Formatter};
+use core::num::NonZero;
+
+/// A security parameter index, identifying an IPsec security association.
+///
+/// This is found in both [`Esp`] and [`IpAuth`](crate::ip_auth::IpAuth) headers.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct Spi(NonZero<u32>);
+
+impl Spi {
+    /// Create a new [`Spi`].
+    ///
+    /// Returns `None` if `spi` is zero, which is reserved and must not be sent on the wire.
+    #[must_use]
+    pub const fn new(spi: u32) -> Option<Spi> {
+        match NonZero::new(spi) {
+            Some(spi) => Some(Spi(spi)),
+            None => None,
+        }
+    }
+
+    /// Get the value of this [`Spi`]
+    #[must_use]
+    pub const fn as_u32(self) -> u32 {
+        self.0.get()
+    }
+}
+
+impl Display for Spi {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#010x}", self.as_u32())
+    }
+}
+
+/// An IPsec encapsulating security payload (ESP) header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Esp {
+    spi: Spi,
+    sequence_number: u32,
+}
+
+/// Errors which may occur when parsing an [`Esp`] header.
+#[derive(Debug, thiserror::Error)]
+pub enum EspError {
+    /// The SPI is zero, which is reserved.
+    #[error("zero SPI")]
+    ZeroSpi,
+}
+
+impl Esp {
+    /// Length of the (clear) ESP header
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const LENGTH: NonZero<u16> = NonZero::new(8).unwrap();
+
+    /// Create a new [`Esp`] header.
+    #[must_use]
+    pub const fn new(spi: Spi, sequence_number: u32) -> Esp {
+        Esp {
+            spi,
+            sequence_number,
+        }
+    }
+
+    /// Get the security parameter index of this header
+    #[must_use]
+    pub const fn spi(&self) -> Spi {
+        self.spi
+    }
+
+    /// Set the security parameter index of this header
+    pub const fn set_spi(&mut self, spi: Spi) -> &mut Esp {
+        self.spi = spi;
+        self
+    }
+
+    /// Get the sequence number of this header
+    #[must_use]
+    pub const fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    /// Set the sequence number of this header
+    pub const fn set_sequence_number(&mut self, sequence_number: u32) -> &mut Esp {
+        self.sequence_number = sequence_number;
+        self
+    }
+}
+
+impl Parse for Esp {
+    type Error = EspError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < Esp::LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Esp::LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let spi = Spi::new(u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]))
+            .ok_or(ParseError::Invalid(EspError::ZeroSpi))?;
+        let sequence_number = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        Ok((Esp::new(spi, sequence_number), Esp::LENGTH))
+    }
+}
+
+impl DeParse for Esp {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        Esp::LENGTH
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        if buf.len() < Esp::LENGTH.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: Esp::LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        buf[..4].copy_from_slice(&self.spi.as_u32().to_be_bytes());
+        buf[4..8].copy_from_slice(&self.sequence_number.to_be_bytes());
+        Ok(Esp::LENGTH)
+    }
+}
+
+#[cfg(any(test, feature = "bolero"))]
+mod contract {
+    use crate::esp::{Esp, Spi};
+    use bolero::{Driver, TypeGenerator};
+
+    impl TypeGenerator for Spi {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            Spi::new(driver.produce()?)
+        }
+    }
+
+    impl TypeGenerator for Esp {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            Some(Esp::new(driver.produce()?, driver.produce()?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::esp::{Esp, EspError, Spi};
+    use crate::eth::mac::Mac;
+    use crate::headers::{TryEsp, TryIpAuth, TryTransport};
+    use crate::ip::NextHeader;
+    use crate::ipv4::Ipv4;
+    use crate::ipv4::addr::UnicastIpv4Addr;
+    use crate::packet::{Packet, PacketBuilder};
+    use crate::parse::{DeParse, IntoNonZeroUSize, Parse, ParseError};
+    use etherparse::{IpAuthHeader, IpNumber};
+    use std::net::Ipv4Addr;
+
+    const LENGTH_USIZE: usize = 8;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!().with_type().for_each(|esp: &Esp| {
+            let mut buf = [0u8; LENGTH_USIZE];
+            let bytes_written = esp.deparse(&mut buf).unwrap_or_else(|_| unreachable!());
+            assert_eq!(bytes_written, esp.size());
+            let (parsed, bytes_parsed) = Esp::parse(&buf).unwrap();
+            assert_eq!(parsed, *esp);
+            assert_eq!(bytes_parsed, esp.size());
+        });
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; LENGTH_USIZE]| {
+                let (parsed, bytes_parsed) = match Esp::parse(slice) {
+                    Ok((parsed, bytes_parsed)) => (parsed, bytes_parsed),
+                    Err(ParseError::Invalid(EspError::ZeroSpi)) => {
+                        assert_eq!(slice[..4], [0; 4]);
+                        return;
+                    }
+                    Err(ParseError::Length(_) | ParseError::BufferTooLong(_)) => unreachable!(),
+                };
+                let mut write_back_buffer = [0u8; LENGTH_USIZE];
+                let bytes_written = parsed
+                    .deparse(&mut write_back_buffer)
+                    .unwrap_or_else(|_| unreachable!());
+                assert_eq!(bytes_written, bytes_parsed);
+                assert_eq!(write_back_buffer, *slice);
+            });
+    }
+
+    fn ipv4(next_header: NextHeader) -> Ipv4 {
+        let mut ip = Ipv4::default();
+        ip.set_source(UnicastIpv4Addr::new(Ipv4Addr::new(10, 0, 0, 1)).unwrap())
+            .set_destination(Ipv4Addr::new(10, 0, 0, 2))
+            .set_next_header(next_header);
+        ip
+    }
+
+    fn packet(ip: Ipv4, payload: Vec<u8>) -> Packet<TestBuffer> {
+        PacketBuilder::new()
+            .eth(Mac([0x2, 0, 0, 0, 0, 1]), Mac([0x2, 0, 0, 0, 0, 2]))
+            .ipv4(ip)
+            .payload(payload)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_esp_in_ipv4() {
+        let esp = Esp::new(Spi::new(0x1234_5678).unwrap(), 42);
+        let mut payload = vec![0u8; esp.size().into_non_zero_usize().get()];
+        esp.deparse(&mut payload).unwrap();
+        payload.extend_from_slice(&[0xaa; 32]); // encrypted data
+        let packet = packet(ipv4(NextHeader::ESP), payload);
+
+        assert_eq!(packet.try_esp(), Some(&esp));
+        assert_eq!(packet.try_esp().unwrap().spi().as_u32(), 0x1234_5678);
+        assert!(packet.try_transport().is_none());
+        assert_eq!(packet.payload().as_ref(), &[0xaa; 32]);
+    }
+
+    #[test]
+    fn parse_esp_after_ip_auth() {
+        let auth =
+            IpAuthHeader::new(IpNumber::ENCAPSULATING_SECURITY_PAYLOAD, 0x100, 7, &[0; 4]).unwrap();
+        let esp = Esp::new(Spi::new(0x200).unwrap(), 8);
+        let mut payload = auth.to_bytes().to_vec();
+        let auth_len = payload.len();
+        payload.resize(auth_len + LENGTH_USIZE, 0);
+        esp.deparse(&mut payload[auth_len..]).unwrap();
+        let packet = packet(ipv4(NextHeader::AUTH), payload);
+
+        let auth = packet.try_ip_auth().unwrap();
+        assert_eq!(auth.spi(), Spi::new(0x100));
+        assert_eq!(auth.sequence_number(), 7);
+        assert_eq!(packet.try_esp(), Some(&esp));
+        assert_eq!(packet.payload_len(), 0);
+    }
+
+    #[test]
+    fn zero_spi_is_not_parsed() {
+        let payload = vec![0u8; 16];
+        let packet = packet(ipv4(NextHeader::ESP), payload);
+        assert!(packet.try_esp().is_none());
+        assert_eq!(packet.payload_len(), 16);
+    }
+}
@@ -35,6 +35,18 @@ impl EthType {
     pub const VLAN_DOUBLE_TAGGED: EthType = EthType(EtherType::VLAN_DOUBLE_TAGGED_FRAME);
     /// Ethernet type for [QinQ (aka provider bridging)](https://en.wikipedia.org/wiki/IEEE_802.1ad)
     pub const VLAN_QINQ: EthType = EthType(EtherType::PROVIDER_BRIDGING);
+    /// Ethernet type for ethernet frames carried in [GRE](https://datatracker.ietf.org/doc/html/rfc1701)
+    pub const TRANSPARENT_ETHERNET_BRIDGING: EthType = EthType(EtherType(0x6558));
+    /// Ethernet type for [PPPoE discovery](https://datatracker.ietf.org/doc/html/rfc2516#section-5)
+    pub const PPPOE_DISCOVERY: EthType = EthType(EtherType(0x8863));
+    /// Ethernet type for [PPPoE session](https://datatracker.ietf.org/doc/html/rfc2516#section-6)
+    pub const PPPOE_SESSION: EthType = EthType(EtherType(0x8864));
+    /// Ethernet type for [MPLS](https://datatracker.ietf.org/doc/html/rfc3032) (unicast)
+    pub const MPLS: EthType = EthType(EtherType(0x8847));
+    /// Ethernet type for [LLDP](https://en.wikipedia.org/wiki/Link_Layer_Discovery_Protocol)
+    pub const LLDP: EthType = EthType(EtherType(0x88cc));
+    /// Ethernet type for [NSH](https://datatracker.ietf.org/doc/html/rfc8300)
+    pub const NSH: EthType = EthType(EtherType(0x894f));
 
     /// Map a raw (native-endian) u16 into an [`EthType`]
     #[must_use]
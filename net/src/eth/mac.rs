@@ -5,6 +5,7 @@
 
 use arrayvec::ArrayVec;
 use std::fmt::{Debug, Display, LowerHex, UpperHex};
+use std::net::IpAddr;
 
 /// A [MAC Address] type.
 ///
@@ -169,6 +170,36 @@ impl Mac {
     pub fn is_valid(&self) -> bool {
         !self.is_zero()
     }
+
+    /// Returns the multicast [`Mac`] that frames sent to the IP multicast group `group` are
+    /// addressed to ([RFC 1112] for IPv4, [RFC 2464] for IPv6), or `None` if `group` is not a
+    /// multicast address.
+    ///
+    /// [RFC 1112]: https://datatracker.ietf.org/doc/html/rfc1112#section-6.4
+    /// [RFC 2464]: https://datatracker.ietf.org/doc/html/rfc2464#section-7
+    #[must_use]
+    pub fn from_multicast_group(group: IpAddr) -> Option<Mac> {
+        match group {
+            IpAddr::V4(group) if group.is_multicast() => {
+                let octets = group.octets();
+                Some(Mac([
+                    0x01,
+                    0x00,
+                    0x5e,
+                    octets[1] & 0x7f,
+                    octets[2],
+                    octets[3],
+                ]))
+            }
+            IpAddr::V6(group) if group.is_multicast() => {
+                let octets = group.octets();
+                Some(Mac([
+                    0x33, 0x33, octets[12], octets[13], octets[14], octets[15],
+                ]))
+            }
+            IpAddr::V4(_) | IpAddr::V6(_) => None,
+        }
+    }
 }
 
 impl Display for Mac {
@@ -493,6 +524,26 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn mac_from_multicast_group() {
+        let mac = |group: &str| Mac::from_multicast_group(group.parse().unwrap());
+        assert_eq!(
+            mac("239.129.2.3"),
+            Some(Mac([0x01, 0x00, 0x5e, 0x01, 0x02, 0x03]))
+        );
+        assert_eq!(
+            mac("224.0.0.251"),
+            Some(Mac([0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb]))
+        );
+        assert_eq!(
+            mac("ff02::1:ff00:1234"),
+            Some(Mac([0x33, 0x33, 0xff, 0x00, 0x12, 0x34]))
+        );
+        assert_eq!(mac("10.0.0.1"), None);
+        assert_eq!(mac("2001:db8::1"), None);
+        assert!(mac("ff05::2").is_some_and(|mac| mac.is_multicast()));
+    }
+
     #[test]
     fn mac_from_string_invalid_octet() {
         let result = Mac::try_from("00:00:00:00:00:000");
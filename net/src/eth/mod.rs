@@ -6,6 +6,7 @@
 pub mod ethtype;
 pub mod mac;
 
+use crate::arp::Arp;
 use crate::eth::ethtype::EthType;
 use crate::eth::mac::{
     DestinationMac, DestinationMacAddressError, Mac, SourceMac, SourceMacAddressError,
@@ -13,7 +14,10 @@ use crate::eth::mac::{
 use crate::headers::Header;
 use crate::ipv4::Ipv4;
 use crate::ipv6::Ipv6;
+use crate::mpls::Mpls;
+use crate::nsh::Nsh;
 use crate::parse::{DeParse, DeParseError, LengthError, Parse, ParseError, Reader};
+use crate::pppoe::Pppoe;
 use crate::vlan::Vlan;
 use etherparse::{EtherType, Ethernet2Header};
 use std::num::NonZero;
@@ -201,6 +205,39 @@ pub(crate) fn parse_from_ethertype(ether_type: EtherType, cursor: &mut Reader) -
             })
             .map(|(vlan, _)| EthNext::Vlan(vlan))
             .ok(),
+        EtherType::ARP => cursor
+            .parse::<Arp>()
+            .map_err(|e| {
+                debug!("failed to parse arp: {:?}", e);
+            })
+            .map(|(arp, _)| EthNext::Arp(arp))
+            .ok(),
+        ether_type
+            if ether_type == EthType::PPPOE_DISCOVERY.0
+                || ether_type == EthType::PPPOE_SESSION.0 =>
+        {
+            cursor
+                .parse::<Pppoe>()
+                .map_err(|e| {
+                    debug!("failed to parse pppoe: {:?}", e);
+                })
+                .map(|(pppoe, _)| EthNext::Pppoe(pppoe))
+                .ok()
+        }
+        ether_type if ether_type == EthType::NSH.0 => cursor
+            .parse::<Nsh>()
+            .map_err(|e| {
+                debug!("failed to parse nsh: {:?}", e);
+            })
+            .map(|(nsh, _)| EthNext::Nsh(nsh))
+            .ok(),
+        ether_type if ether_type == EthType::MPLS.0 => cursor
+            .parse::<Mpls>()
+            .map_err(|e| {
+                debug!("failed to parse mpls: {:?}", e);
+            })
+            .map(|(mpls, _)| EthNext::Mpls(mpls))
+            .ok(),
         _ => {
             trace!("unsupported ether type: {:?}", ether_type);
             None
@@ -212,6 +249,10 @@ pub(crate) enum EthNext {
     Vlan(Vlan),
     Ipv4(Ipv4),
     Ipv6(Ipv6),
+    Arp(Arp),
+    Pppoe(Pppoe),
+    Nsh(Nsh),
+    Mpls(Mpls),
 }
 
 impl From<EthNext> for Header {
@@ -220,6 +261,10 @@ impl From<EthNext> for Header {
             EthNext::Vlan(x) => Header::Vlan(x),
             EthNext::Ipv4(x) => Header::Ipv4(x),
             EthNext::Ipv6(x) => Header::Ipv6(x),
+            EthNext::Arp(x) => Header::Arp(x),
+            EthNext::Pppoe(x) => Header::Pppoe(x),
+            EthNext::Nsh(x) => Header::Nsh(x),
+            EthNext::Mpls(x) => Header::Mpls(x),
         }
     }
 }
@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+use crate::headers::{Headers, TryGeneve, TryIp, TryTransportMut};
+use tracing::warn;
+
+/// Configuration for [`GeneveEncap`] operation
+///
+/// This struct is a safety measure designed to check that the enclosed [`Headers`] really do
+/// describe a Geneve packet.
+pub struct GeneveEncap {
+    headers: Headers,
+}
+
+impl AsRef<Headers> for GeneveEncap {
+    fn as_ref(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+/// Errors which may occur when encapsulating a packet with Geneve headers.
+#[derive(Debug, thiserror::Error)]
+pub enum GeneveEncapError {
+    /// supplied headers have no IP layer
+    #[error("supplied headers have no IP layer")]
+    Ip,
+    /// supplied headers have no Geneve layer
+    #[error("supplied headers have no Geneve layer")]
+    Geneve,
+}
+
+impl GeneveEncap {
+    /// Create a new [`GeneveEncap`] configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GeneveEncapError`] if the supplied [`Headers`] are not a legal Geneve header.
+    pub fn new(mut headers: Headers) -> Result<GeneveEncap, GeneveEncapError> {
+        if headers.try_transport_mut().is_some() {
+            headers.transport.take();
+            warn!("BUG: should not provide transport header; it will be ignored");
+        }
+        match (headers.try_ip(), headers.try_geneve()) {
+            (None, _) => Err(GeneveEncapError::Ip),
+            (_, None) => Err(GeneveEncapError::Geneve),
+            (Some(_), Some(_)) => Ok(Self { headers }),
+        }
+    }
+
+    /// Get the headers to be used to fill in the Geneve parameters on encap.
+    #[must_use]
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
@@ -0,0 +1,495 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [Geneve][RFC8926] types and parsing.
+//!
+//! [RFC8926]: https://datatracker.ietf.org/doc/html/rfc8926#section-3.4
+
+mod encap;
+
+use crate::eth::ethtype::EthType;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use crate::udp::port::UdpPort;
+use crate::vxlan::{InvalidVni, Vni};
+use core::num::NonZero;
+pub use encap::{GeneveEncap, GeneveEncapError};
+use tracing::trace;
+
+/// A [Geneve] header, with its options.
+///
+/// [Geneve]: https://en.wikipedia.org/wiki/Generic_Network_Virtualization_Encapsulation
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Geneve {
+    vni: Vni,
+    protocol: EthType,
+    oam: bool,
+    options: Vec<GeneveOption>,
+}
+
+/// A TLV option of a [`Geneve`] header.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GeneveOption {
+    class: u16,
+    option_type: u8,
+    data: Vec<u8>,
+}
+
+impl Geneve {
+    /// UDP port on which we expect to receive Geneve packets.  The standard requires 6081.
+    #[allow(unsafe_code)] // const-eval and trivially safe
+    pub const PORT: UdpPort = unsafe { UdpPort::new_unchecked(6081) };
+
+    /// The minimum length of a [`Geneve`] header (without options).
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(8).unwrap();
+
+    /// The maximum length of the options of a [`Geneve`] header (the length field is 6-bit, in
+    /// multiples of 4 bytes).
+    pub const MAX_OPTIONS_LENGTH: u16 = 63 * 4;
+
+    /// The only version of Geneve defined so far.
+    const VERSION: u8 = 0;
+    /// OAM packet bit, in the second byte of the header
+    const FLAG_OAM: u8 = 0b1000_0000;
+    /// Critical options present bit, in the second byte of the header
+    const FLAG_CRITICAL: u8 = 0b0100_0000;
+
+    /// Create a new Geneve header without options, carrying ethernet frames.
+    #[must_use]
+    pub fn new(vni: Vni) -> Geneve {
+        Geneve {
+            vni,
+            protocol: EthType::TRANSPARENT_ETHERNET_BRIDGING,
+            oam: false,
+            options: Vec::new(),
+        }
+    }
+
+    /// Get the [`Vni`] of this header.
+    #[must_use]
+    pub const fn vni(&self) -> Vni {
+        self.vni
+    }
+
+    /// Set the [`Vni`] of this header.
+    pub const fn set_vni(&mut self, vni: Vni) -> &mut Geneve {
+        self.vni = vni;
+        self
+    }
+
+    /// Get the protocol type of the payload of this header.
+    #[must_use]
+    pub const fn protocol(&self) -> EthType {
+        self.protocol
+    }
+
+    /// Set the protocol type of the payload of this header.
+    pub const fn set_protocol(&mut self, protocol: EthType) -> &mut Geneve {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Tell if this header is for an OAM (control) packet.
+    #[must_use]
+    pub const fn oam(&self) -> bool {
+        self.oam
+    }
+
+    /// Mark this header as being for an OAM (control) packet, or not.
+    pub const fn set_oam(&mut self, oam: bool) -> &mut Geneve {
+        self.oam = oam;
+        self
+    }
+
+    /// Tell if any option of this header is critical.
+    ///
+    /// This is the value of the "critical options present" bit of the header.
+    #[must_use]
+    pub fn critical(&self) -> bool {
+        self.options.iter().any(GeneveOption::critical)
+    }
+
+    /// Get the options of this header.
+    #[must_use]
+    pub fn options(&self) -> &[GeneveOption] {
+        &self.options
+    }
+
+    /// Add an option to this header.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeneveError::OptionsTooLong`] if the options would not fit in the header.
+    pub fn push_option(&mut self, option: GeneveOption) -> Result<&mut Geneve, GeneveError> {
+        if self.options_len() + option.len() > Geneve::MAX_OPTIONS_LENGTH {
+            return Err(GeneveError::OptionsTooLong);
+        }
+        self.options.push(option);
+        Ok(self)
+    }
+
+    /// Remove all the options of this header.
+    pub fn clear_options(&mut self) -> &mut Geneve {
+        self.options.clear();
+        self
+    }
+
+    /// Length of the options, in bytes
+    fn options_len(&self) -> u16 {
+        self.options.iter().map(GeneveOption::len).sum()
+    }
+}
+
+impl GeneveOption {
+    /// The maximum length of the data of an option (the length field is 5-bit, in multiples of
+    /// 4 bytes).
+    pub const MAX_DATA_LENGTH: usize = 31 * 4;
+
+    /// The bit of the option type which marks an option as critical.
+    const CRITICAL: u8 = 0b1000_0000;
+
+    /// Create a new option.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeneveError::InvalidOptionLength`] if the length of `data` is not a multiple of 4
+    /// or is larger than [`GeneveOption::MAX_DATA_LENGTH`].
+    pub fn new(class: u16, option_type: u8, data: Vec<u8>) -> Result<GeneveOption, GeneveError> {
+        if !data.len().is_multiple_of(4) || data.len() > GeneveOption::MAX_DATA_LENGTH {
+            return Err(GeneveError::InvalidOptionLength(data.len()));
+        }
+        Ok(GeneveOption {
+            class,
+            option_type,
+            data,
+        })
+    }
+
+    /// Get the class (namespace) of this option.
+    #[must_use]
+    pub const fn class(&self) -> u16 {
+        self.class
+    }
+
+    /// Get the type of this option, including its critical bit.
+    #[must_use]
+    pub const fn option_type(&self) -> u8 {
+        self.option_type
+    }
+
+    /// Tell if this option is critical, i.e., if a receiver which does not understand it must drop
+    /// the packet.
+    #[must_use]
+    pub const fn critical(&self) -> bool {
+        self.option_type & GeneveOption::CRITICAL != 0
+    }
+
+    /// Get the data of this option.
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Length of the option on the wire, in bytes
+    #[allow(clippy::cast_possible_truncation)] // data length checked on creation
+    fn len(&self) -> u16 {
+        4 + self.data.len() as u16
+    }
+}
+
+/// Errors which may occur when creating or parsing a [`Geneve`] header.
+#[derive(Debug, thiserror::Error)]
+pub enum GeneveError {
+    /// [`Vni`] is a non-zero, 24-bit number.
+    #[error(transparent)]
+    InvalidVni(InvalidVni),
+    /// Only version 0 of Geneve is defined.
+    #[error("unsupported Geneve version {0}")]
+    UnsupportedVersion(u8),
+    /// The options of the header are inconsistent with the length of the header.
+    #[error("Malformed options")]
+    MalformedOptions,
+    /// The data of an option must be a multiple of 4 bytes, and at most
+    /// [`GeneveOption::MAX_DATA_LENGTH`] bytes.
+    #[error("Invalid option length {0}")]
+    InvalidOptionLength(usize),
+    /// The options do not fit in the header.
+    #[error("Options too long")]
+    OptionsTooLong,
+}
+
+impl Parse for Geneve {
+    type Error = GeneveError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < Geneve::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Geneve::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let version = buf[0] >> 6;
+        if version != Geneve::VERSION {
+            trace!("Received Geneve header with version {version}");
+            return Err(ParseError::Invalid(GeneveError::UnsupportedVersion(
+                version,
+            )));
+        }
+        let options_len = usize::from(buf[0] & 0b0011_1111) * 4;
+        let len = Geneve::MIN_LENGTH.into_non_zero_usize().get() + options_len;
+        if buf.len() < len {
+            return Err(ParseError::Length(LengthError {
+                expected: NonZero::new(len).unwrap_or_else(|| unreachable!()),
+                actual: buf.len(),
+            }));
+        }
+        // the reserved bits are ignored on receipt, and the critical bit is derived from options
+        let oam = buf[1] & Geneve::FLAG_OAM != 0;
+        let protocol = EthType::new_from_be_bytes([buf[2], buf[3]]);
+        let raw_vni = u32::from_be_bytes([0, buf[4], buf[5], buf[6]]);
+        let vni = Vni::new_checked(raw_vni)
+            .map_err(|e| ParseError::Invalid(GeneveError::InvalidVni(e)))?;
+
+        let mut options = Vec::new();
+        let mut rest = &buf[Geneve::MIN_LENGTH.into_non_zero_usize().get()..len];
+        while !rest.is_empty() {
+            if rest.len() < 4 {
+                return Err(ParseError::Invalid(GeneveError::MalformedOptions));
+            }
+            let data_len = usize::from(rest[3] & 0b0001_1111) * 4;
+            let Some(data) = rest.get(4..4 + data_len) else {
+                trace!("Received Geneve option overflowing the options length");
+                return Err(ParseError::Invalid(GeneveError::MalformedOptions));
+            };
+            options.push(GeneveOption {
+                class: u16::from_be_bytes([rest[0], rest[1]]),
+                option_type: rest[2],
+                data: data.to_vec(),
+            });
+            rest = &rest[4 + data_len..];
+        }
+        let geneve = Geneve {
+            vni,
+            protocol,
+            oam,
+            options,
+        };
+        #[allow(clippy::cast_possible_truncation)] // at most 8 + 252
+        let len = NonZero::new(len as u16).unwrap_or_else(|| unreachable!());
+        Ok((geneve, len))
+    }
+}
+
+impl DeParse for Geneve {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        Geneve::MIN_LENGTH
+            .checked_add(self.options_len())
+            .unwrap_or_else(|| unreachable!())
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let size = self.size();
+        if buf.len() < size.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: size.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        #[allow(clippy::cast_possible_truncation)] // options length checked on push
+        let options_len = (self.options_len() / 4) as u8;
+        buf[0] = (Geneve::VERSION << 6) | options_len;
+        buf[1] = 0;
+        if self.oam {
+            buf[1] |= Geneve::FLAG_OAM;
+        }
+        if self.critical() {
+            buf[1] |= Geneve::FLAG_CRITICAL;
+        }
+        buf[2..4].copy_from_slice(&self.protocol.as_u16().to_be_bytes());
+        buf[4..8].copy_from_slice(&(self.vni.as_u32() << 8).to_be_bytes());
+        let mut offset = Geneve::MIN_LENGTH.into_non_zero_usize().get();
+        for option in &self.options {
+            buf[offset..offset + 2].copy_from_slice(&option.class.to_be_bytes());
+            buf[offset + 2] = option.option_type;
+            #[allow(clippy::cast_possible_truncation)] // data length checked on creation
+            let data_len = (option.data.len() / 4) as u8;
+            buf[offset + 3] = data_len;
+            offset += 4;
+            buf[offset..offset + option.data.len()].copy_from_slice(&option.data);
+            offset += option.data.len();
+        }
+        debug_assert_eq!(offset, size.into_non_zero_usize().get());
+        Ok(size)
+    }
+}
+
+#[cfg(any(test, feature = "bolero"))]
+mod contract {
+    use crate::geneve::{Geneve, GeneveOption};
+    use bolero::{Driver, TypeGenerator};
+
+    impl TypeGenerator for GeneveOption {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            let words = driver.produce::<usize>()? % (GeneveOption::MAX_DATA_LENGTH / 4 + 1);
+            let data = (0..words * 4)
+                .map(|_| driver.produce::<u8>())
+                .collect::<Option<Vec<_>>>()?;
+            GeneveOption::new(driver.produce()?, driver.produce()?, data).ok()
+        }
+    }
+
+    impl TypeGenerator for Geneve {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            let mut geneve = Geneve::new(driver.produce()?);
+            geneve.set_protocol(driver.produce()?);
+            geneve.set_oam(driver.produce()?);
+            let options = driver.produce::<usize>()? % 4;
+            for _ in 0..options {
+                // options which would not fit are skipped
+                let _ = geneve.push_option(driver.produce()?);
+            }
+            Some(geneve)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::eth::Eth;
+    use crate::eth::ethtype::EthType;
+    use crate::eth::mac::{DestinationMac, Mac, SourceMac};
+    use crate::geneve::{Geneve, GeneveEncap, GeneveError, GeneveOption};
+    use crate::headers::{HeadersBuilder, Net, TryGeneve, TryUdp};
+    use crate::ip::NextHeader;
+    use crate::ipv4::{Ipv4, UnicastIpv4Addr};
+    use crate::packet::Packet;
+    use crate::packet::test_utils::build_test_ipv4_packet_with_transport;
+    use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, Parse, ParseError};
+    use crate::udp::UdpEncap;
+    use crate::vxlan::Vni;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!().with_type().for_each(|geneve: &Geneve| {
+            let mut buf = vec![0u8; geneve.size().into_non_zero_usize().get()];
+            let bytes_written = geneve.deparse(&mut buf).unwrap_or_else(|_| unreachable!());
+            assert_eq!(bytes_written, geneve.size());
+            let (parsed, bytes_parsed) = Geneve::parse(&buf).unwrap();
+            assert_eq!(parsed, *geneve);
+            assert_eq!(bytes_parsed, geneve.size());
+            assert_eq!(buf[1] & Geneve::FLAG_CRITICAL != 0, geneve.critical());
+        });
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!().with_type().for_each(|slice: &[u8; 64]| {
+            let (parsed, bytes_parsed) = match Geneve::parse(slice) {
+                Ok((parsed, bytes_parsed)) => (parsed, bytes_parsed),
+                Err(ParseError::Invalid(GeneveError::UnsupportedVersion(version))) => {
+                    assert_eq!(slice[0] >> 6, version);
+                    return;
+                }
+                Err(ParseError::Length(e)) => {
+                    assert!(e.expected.get() > slice.len());
+                    return;
+                }
+                Err(
+                    ParseError::Invalid(GeneveError::InvalidVni(_) | GeneveError::MalformedOptions)
+                    | ParseError::BufferTooLong(_),
+                ) => return,
+                Err(ParseError::Invalid(e)) => unreachable!("{e:?}"),
+            };
+            let mut write_back_buffer = [0u8; 64];
+            let bytes_written = parsed
+                .deparse(&mut write_back_buffer)
+                .unwrap_or_else(|_| unreachable!());
+            assert_eq!(bytes_written, bytes_parsed);
+            let (reparsed, _) = Geneve::parse(&write_back_buffer).unwrap();
+            assert_eq!(reparsed, parsed);
+        });
+    }
+
+    #[test]
+    fn write_to_insufficient_buffer_fails_gracefully() {
+        bolero::check!().with_type().for_each(|geneve: &Geneve| {
+            let mut too_small_buffer = vec![0u8; geneve.size().into_non_zero_usize().get() - 1];
+            match geneve.deparse(&mut too_small_buffer) {
+                Err(DeParseError::Length(e)) => {
+                    assert_eq!(e.expected, geneve.size().into_non_zero_usize());
+                    assert_eq!(e.actual, too_small_buffer.len());
+                }
+                _ => unreachable!(),
+            }
+        });
+    }
+
+    #[test]
+    fn options() {
+        assert!(matches!(
+            GeneveOption::new(0x0102, 3, vec![0; 3]),
+            Err(GeneveError::InvalidOptionLength(3))
+        ));
+        let mut geneve = Geneve::new(Vni::new_checked(42).unwrap());
+        geneve
+            .push_option(GeneveOption::new(0x0102, 3, vec![1, 2, 3, 4]).unwrap())
+            .unwrap();
+        assert!(!geneve.critical());
+        geneve
+            .push_option(GeneveOption::new(0x0102, 0x83, vec![]).unwrap())
+            .unwrap();
+        assert!(geneve.critical());
+        assert_eq!(geneve.size().get(), 8 + 8 + 4);
+
+        // two options of 124 bytes and one of 4 bytes fill the 252 bytes available
+        let big = GeneveOption::new(0, 0, vec![0; 120]).unwrap();
+        let empty = GeneveOption::new(0, 0, vec![]).unwrap();
+        geneve.clear_options();
+        geneve.push_option(big.clone()).unwrap();
+        geneve.push_option(big).unwrap();
+        geneve.push_option(empty.clone()).unwrap();
+        assert_eq!(geneve.size().get(), 8 + Geneve::MAX_OPTIONS_LENGTH);
+        assert!(matches!(
+            geneve.push_option(empty),
+            Err(GeneveError::OptionsTooLong)
+        ));
+    }
+
+    #[test]
+    fn encap_decap() {
+        let mut packet = build_test_ipv4_packet_with_transport(64, Some(NextHeader::UDP)).unwrap();
+        let inner = packet.get_headers().clone();
+
+        let mut geneve = Geneve::new(Vni::new_checked(42).unwrap());
+        geneve
+            .push_option(GeneveOption::new(0x0102, 0x80, vec![1, 2, 3, 4]).unwrap())
+            .unwrap();
+        let mut ipv4 = Ipv4::default();
+        ipv4.set_source(UnicastIpv4Addr::new(Ipv4Addr::new(10, 0, 0, 1)).unwrap());
+        ipv4.set_destination(Ipv4Addr::new(10, 0, 0, 2));
+        ipv4.set_next_header(NextHeader::UDP);
+        let outer = HeadersBuilder::default()
+            .eth(Some(Eth::new(
+                SourceMac::new(Mac([0x2, 0, 0, 0, 0, 3])).unwrap(),
+                DestinationMac::new(Mac([0x2, 0, 0, 0, 0, 4])).unwrap(),
+                EthType::IPV4,
+            )))
+            .net(Some(Net::Ipv4(ipv4)))
+            .udp_encap(Some(UdpEncap::Geneve(geneve.clone())))
+            .build()
+            .unwrap();
+        packet
+            .geneve_encap(&GeneveEncap::new(outer).unwrap())
+            .unwrap();
+
+        let mut packet = Packet::new(packet.serialize().unwrap()).unwrap();
+        assert_eq!(packet.try_udp().unwrap().destination(), Geneve::PORT);
+        assert_eq!(packet.try_geneve(), Some(&geneve));
+        assert_eq!(packet.geneve_decap().unwrap().unwrap(), geneve);
+        assert_eq!(packet.get_headers(), &inner);
+        assert!(packet.geneve_decap().is_none());
+    }
+}
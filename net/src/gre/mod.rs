@@ -0,0 +1,410 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [GRE][RFC2784] types and parsing, including the key and sequence number
+//! [extensions][RFC2890].
+//!
+//! [RFC2784]: https://datatracker.ietf.org/doc/html/rfc2784#section-2
+//! [RFC2890]: https://datatracker.ietf.org/doc/html/rfc2890#section-2
+
+use crate::eth::ethtype::EthType;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use core::num::NonZero;
+use tracing::trace;
+
+/// A [GRE] header
+///
+/// The optional fields are present in the header if and only if they are `Some`.
+///
+/// [GRE]: https://en.wikipedia.org/wiki/Generic_Routing_Encapsulation
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+pub struct Gre {
+    protocol: EthType,
+    checksum: Option<u16>,
+    key: Option<u32>,
+    sequence: Option<u32>,
+}
+
+impl Gre {
+    /// The minimum length of a [`Gre`] header (no optional field).
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(4).unwrap();
+
+    /// The maximum length of a [`Gre`] header (checksum, key and sequence number).
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MAX_LENGTH: NonZero<u16> = NonZero::new(16).unwrap();
+
+    /// Checksum present bit
+    const FLAG_CHECKSUM: u8 = 0b1000_0000;
+    /// Key present bit ([RFC2890](https://datatracker.ietf.org/doc/html/rfc2890#section-2))
+    const FLAG_KEY: u8 = 0b0010_0000;
+    /// Sequence number present bit
+    /// ([RFC2890](https://datatracker.ietf.org/doc/html/rfc2890#section-2))
+    const FLAG_SEQUENCE: u8 = 0b0001_0000;
+
+    /// Bits of the first byte which must be zero.
+    ///
+    /// These are the routing present, strict source route and recursion control fields of
+    /// [RFC1701](https://datatracker.ietf.org/doc/html/rfc1701), which
+    /// [RFC2784](https://datatracker.ietf.org/doc/html/rfc2784#section-2.1) deprecates:
+    ///
+    /// > A receiver MUST discard a packet where any of bits 1-5 are non-zero, unless that receiver
+    /// > implements RFC 1701.
+    const RESERVED0_FLAGS: u8 = 0b0100_1111;
+
+    /// Bits of the second byte which must be zero (the remaining bits are the version).
+    const RESERVED1_FLAGS: u8 = 0b1111_1000;
+
+    /// Create a new GRE header carrying `protocol`, without any optional field.
+    #[must_use]
+    pub fn new(protocol: EthType) -> Gre {
+        Gre {
+            protocol,
+            checksum: None,
+            key: None,
+            sequence: None,
+        }
+    }
+
+    /// Get the protocol type of the payload of this header.
+    #[must_use]
+    pub const fn protocol(&self) -> EthType {
+        self.protocol
+    }
+
+    /// Set the protocol type of the payload of this header.
+    pub const fn set_protocol(&mut self, protocol: EthType) -> &mut Gre {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Get the checksum field of this header, if present.
+    ///
+    /// The checksum is carried as is: it is neither validated on parse nor computed on deparse.
+    #[must_use]
+    pub const fn checksum(&self) -> Option<u16> {
+        self.checksum
+    }
+
+    /// Set (or remove, with `None`) the checksum field of this header.
+    pub const fn set_checksum(&mut self, checksum: Option<u16>) -> &mut Gre {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Get the key of this header, if present.
+    #[must_use]
+    pub const fn key(&self) -> Option<u32> {
+        self.key
+    }
+
+    /// Set (or remove, with `None`) the key of this header.
+    pub const fn set_key(&mut self, key: Option<u32>) -> &mut Gre {
+        self.key = key;
+        self
+    }
+
+    /// Get the sequence number of this header, if present.
+    #[must_use]
+    pub const fn sequence(&self) -> Option<u32> {
+        self.sequence
+    }
+
+    /// Set (or remove, with `None`) the sequence number of this header.
+    pub const fn set_sequence(&mut self, sequence: Option<u32>) -> &mut Gre {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Length of the header with the given flags byte
+    fn len_for_flags(flags: u8) -> u16 {
+        let optional = [Gre::FLAG_CHECKSUM, Gre::FLAG_KEY, Gre::FLAG_SEQUENCE]
+            .iter()
+            .filter(|&&flag| flags & flag != 0)
+            .count();
+        #[allow(clippy::cast_possible_truncation)] // at most 3
+        let optional = optional as u16;
+        Gre::MIN_LENGTH.get() + 4 * optional
+    }
+
+    fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.checksum.is_some() {
+            flags |= Gre::FLAG_CHECKSUM;
+        }
+        if self.key.is_some() {
+            flags |= Gre::FLAG_KEY;
+        }
+        if self.sequence.is_some() {
+            flags |= Gre::FLAG_SEQUENCE;
+        }
+        flags
+    }
+}
+
+/// Errors which may occur when parsing a [`Gre`] header.
+#[derive(Debug, thiserror::Error)]
+pub enum GreError {
+    /// Only version 0 of GRE is supported (version 1 is the enhanced GRE of PPTP).
+    #[error("unsupported GRE version {0}")]
+    UnsupportedVersion(u8),
+    /// [The GRE spec] requires receivers to discard packets with the RFC1701 routing and
+    /// recursion control fields set.
+    ///
+    /// [The GRE spec]: https://datatracker.ietf.org/doc/html/rfc2784#section-2.1
+    #[error("Reserved bits set")]
+    ReservedBitsSet,
+}
+
+impl Parse for Gre {
+    type Error = GreError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < Gre::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Gre::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let version = buf[1] & !Gre::RESERVED1_FLAGS;
+        if version != 0 {
+            trace!("Received GRE header with version {version}");
+            return Err(ParseError::Invalid(GreError::UnsupportedVersion(version)));
+        }
+        if buf[0] & Gre::RESERVED0_FLAGS != 0 || buf[1] & Gre::RESERVED1_FLAGS != 0 {
+            trace!("Received GRE header with reserved bits set.");
+            return Err(ParseError::Invalid(GreError::ReservedBitsSet));
+        }
+        let len = Gre::len_for_flags(buf[0]);
+        #[allow(clippy::unwrap_used)] // at least MIN_LENGTH
+        let len = NonZero::new(len).unwrap();
+        if buf.len() < len.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: len.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let mut gre = Gre::new(EthType::new_from_be_bytes([buf[2], buf[3]]));
+        let mut fields = buf[4..len.into_non_zero_usize().get()].chunks_exact(4);
+        // length checked above, so the chunks are there and of the right size
+        let mut next_field = || -> [u8; 4] {
+            fields
+                .next()
+                .and_then(|chunk| chunk.try_into().ok())
+                .unwrap_or_else(|| unreachable!())
+        };
+        if buf[0] & Gre::FLAG_CHECKSUM != 0 {
+            let field = next_field();
+            // the second half of the field is reserved and ignored on receipt
+            gre.checksum = Some(u16::from_be_bytes([field[0], field[1]]));
+        }
+        if buf[0] & Gre::FLAG_KEY != 0 {
+            gre.key = Some(u32::from_be_bytes(next_field()));
+        }
+        if buf[0] & Gre::FLAG_SEQUENCE != 0 {
+            gre.sequence = Some(u32::from_be_bytes(next_field()));
+        }
+        Ok((gre, len))
+    }
+}
+
+impl DeParse for Gre {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        #[allow(clippy::unwrap_used)] // at least MIN_LENGTH
+        NonZero::new(Gre::len_for_flags(self.flags())).unwrap()
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let size = self.size();
+        if buf.len() < size.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: size.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        buf[0] = self.flags();
+        buf[1] = 0; // version 0, reserved bits zero
+        buf[2..4].copy_from_slice(&self.protocol.as_u16().to_be_bytes());
+        let mut offset = 4;
+        if let Some(checksum) = self.checksum {
+            buf[offset..offset + 2].copy_from_slice(&checksum.to_be_bytes());
+            buf[offset + 2..offset + 4].copy_from_slice(&[0, 0]);
+            offset += 4;
+        }
+        for field in [self.key, self.sequence].into_iter().flatten() {
+            buf[offset..offset + 4].copy_from_slice(&field.to_be_bytes());
+            offset += 4;
+        }
+        debug_assert_eq!(offset, size.into_non_zero_usize().get());
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::eth::ethtype::EthType;
+    use crate::gre::{Gre, GreError};
+    use crate::headers::{TryEth, TryGre, TryHeaders, TryIpv4, TryUdp};
+    use crate::packet::Packet;
+    use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, Parse, ParseError};
+    use etherparse::{EtherType, Ethernet2Header, IpNumber, Ipv4Header, UdpHeader};
+    const MAX_LENGTH_USIZE: usize = 16;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!().with_type().for_each(|gre: &Gre| {
+            let mut buf = [0u8; MAX_LENGTH_USIZE];
+            let bytes_written = gre.deparse(&mut buf).unwrap_or_else(|_| unreachable!());
+            assert_eq!(bytes_written, gre.size());
+            let (parsed, bytes_parsed) = Gre::parse(&buf).unwrap();
+            assert_eq!(parsed, *gre);
+            assert_eq!(bytes_parsed, gre.size());
+        });
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; MAX_LENGTH_USIZE]| {
+                let (parsed, bytes_parsed) = match Gre::parse(slice) {
+                    Ok((parsed, bytes_parsed)) => (parsed, bytes_parsed),
+                    Err(ParseError::Invalid(GreError::UnsupportedVersion(version))) => {
+                        assert_eq!(slice[1] & 0b111, version);
+                        assert_ne!(version, 0);
+                        return;
+                    }
+                    Err(ParseError::Invalid(GreError::ReservedBitsSet)) => {
+                        assert!(slice[0] & 0b0100_1111 != 0 || slice[1] & 0b1111_1000 != 0);
+                        return;
+                    }
+                    Err(ParseError::Length(_) | ParseError::BufferTooLong(_)) => unreachable!(),
+                };
+                let mut write_back_buffer = [0u8; MAX_LENGTH_USIZE];
+                let bytes_written = parsed
+                    .deparse(&mut write_back_buffer)
+                    .unwrap_or_else(|_| unreachable!());
+                assert_eq!(bytes_written, bytes_parsed);
+                assert_eq!(write_back_buffer[..2], slice[..2]);
+                assert_eq!(write_back_buffer[2..4], slice[2..4]);
+                let (reparsed, _) = Gre::parse(&write_back_buffer).unwrap();
+                assert_eq!(reparsed, parsed);
+            });
+    }
+
+    #[test]
+    fn write_to_insufficient_buffer_fails_gracefully() {
+        bolero::check!().with_type().for_each(|gre: &Gre| {
+            let mut too_small_buffer = vec![0u8; gre.size().into_non_zero_usize().get() - 1];
+            match gre.deparse(&mut too_small_buffer) {
+                Err(DeParseError::Length(e)) => {
+                    assert_eq!(e.expected, gre.size().into_non_zero_usize());
+                    assert_eq!(e.actual, too_small_buffer.len());
+                }
+                _ => unreachable!(),
+            }
+        });
+    }
+
+    #[test]
+    fn parse_of_truncated_optional_fields_fails_gracefully() {
+        let mut gre = Gre::new(EthType::IPV4);
+        gre.set_key(Some(0x1234_5678)).set_sequence(Some(42));
+        let mut buf = [0u8; MAX_LENGTH_USIZE];
+        gre.deparse(&mut buf).unwrap();
+        match Gre::parse(&buf[..8]) {
+            Err(ParseError::Length(e)) => {
+                assert_eq!(e.expected, gre.size().into_non_zero_usize());
+                assert_eq!(e.actual, 8);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Build an ethernet frame with an IPv4 / GRE (with a key) outer header, wrapping an
+    /// IPv4 / UDP packet.
+    fn gre_frame(protocol: EthType, inner: &[u8]) -> Vec<u8> {
+        let mut gre = Gre::new(protocol);
+        gre.set_key(Some(7));
+        let mut gre_bytes = vec![0u8; gre.size().into_non_zero_usize().get()];
+        gre.deparse(&mut gre_bytes).unwrap();
+        #[allow(clippy::cast_possible_truncation)]
+        let outer_ip = Ipv4Header::new(
+            (gre_bytes.len() + inner.len()) as u16,
+            64,
+            IpNumber::GRE,
+            [192, 168, 0, 1],
+            [192, 168, 0, 2],
+        )
+        .unwrap();
+        let eth = Ethernet2Header {
+            source: [2, 0, 0, 0, 0, 1],
+            destination: [2, 0, 0, 0, 0, 2],
+            ether_type: EtherType::IPV4,
+        };
+        let mut frame = Vec::new();
+        eth.write(&mut frame).unwrap();
+        outer_ip.write(&mut frame).unwrap();
+        frame.extend_from_slice(&gre_bytes);
+        frame.extend_from_slice(inner);
+        frame
+    }
+
+    fn inner_ipv4_udp() -> Vec<u8> {
+        let udp = UdpHeader {
+            source_port: 1234,
+            destination_port: 5678,
+            length: 8,
+            checksum: 0,
+        };
+        let ip = Ipv4Header::new(8, 64, IpNumber::UDP, [10, 0, 0, 1], [10, 0, 0, 2]).unwrap();
+        let mut bytes = Vec::new();
+        ip.write(&mut bytes).unwrap();
+        udp.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decap_ip_payload() {
+        let frame = gre_frame(EthType::IPV4, &inner_ipv4_udp());
+        let mut packet = Packet::new(TestBuffer::from_raw_data(&frame)).unwrap();
+        assert_eq!(packet.try_gre().unwrap().key(), Some(7));
+        assert!(packet.try_udp().is_none());
+
+        let gre = packet.gre_decap().unwrap().unwrap();
+        assert_eq!(gre.protocol(), EthType::IPV4);
+        assert!(packet.try_gre().is_none());
+        assert_eq!(
+            packet.headers().try_ipv4().unwrap().destination(),
+            std::net::Ipv4Addr::new(10, 0, 0, 2)
+        );
+        assert_eq!(packet.try_udp().unwrap().destination().as_u16(), 5678);
+        assert_eq!(packet.payload_len(), 0);
+        // the outer ethernet header is kept, with the protocol of the inner packet
+        assert_eq!(packet.try_eth().unwrap().ether_type(), EthType::IPV4);
+        assert!(packet.gre_decap().is_none());
+    }
+
+    #[test]
+    fn decap_ethernet_payload() {
+        let eth = Ethernet2Header {
+            source: [2, 0, 0, 0, 0, 3],
+            destination: [2, 0, 0, 0, 0, 4],
+            ether_type: EtherType::IPV4,
+        };
+        let mut inner = Vec::new();
+        eth.write(&mut inner).unwrap();
+        inner.extend_from_slice(&inner_ipv4_udp());
+        let frame = gre_frame(EthType::TRANSPARENT_ETHERNET_BRIDGING, &inner);
+        let mut packet = Packet::new(TestBuffer::from_raw_data(&frame)).unwrap();
+
+        let gre = packet.gre_decap().unwrap().unwrap();
+        assert_eq!(gre.protocol(), EthType::TRANSPARENT_ETHERNET_BRIDGING);
+        assert_eq!(packet.try_udp().unwrap().source().as_u16(), 1234);
+        assert_eq!(packet.payload_len(), 0);
+    }
+}
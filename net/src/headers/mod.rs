@@ -4,9 +4,13 @@
 //! Definition of [`Headers`] and related methods and types.
 #![allow(missing_docs, clippy::pedantic)] // temporary
 
-use crate::checksum::Checksum;
+use crate::arp::Arp;
+use crate::checksum::{Checksum, pseudo_header_sum};
+use crate::esp::Esp;
 use crate::eth::ethtype::EthType;
 use crate::eth::{Eth, EthError};
+use crate::geneve::Geneve;
+use crate::gre::Gre;
 use crate::icmp_any::{IcmpAny, IcmpAnyMut};
 use crate::icmp4::Icmp4;
 use crate::icmp6::{Icmp6, Icmp6ChecksumPayload};
@@ -14,19 +18,24 @@ use crate::impl_from_for_enum;
 use crate::ip::{NextHeader, UnicastIpAddr};
 use crate::ip_auth::IpAuth;
 use crate::ipv4::Ipv4;
-use crate::ipv6::{Ipv6, Ipv6Ext};
+use crate::ipv6::{Ipv6, Ipv6Ext, Ipv6Srh, SrhError};
+use crate::mpls::{Mpls, MplsLabel};
+use crate::nsh::Nsh;
+use crate::packet::ChecksumOffload;
 use crate::parse::{
     DeParse, DeParseError, IllegalBufferLength, IntoNonZeroUSize, LengthError, Parse, ParseError,
     Reader, Writer,
 };
+use crate::pppoe::Pppoe;
+use crate::sctp::{Sctp, SctpPort};
 use crate::tcp::{Tcp, TcpChecksumPayload, TcpPort};
 use crate::udp::{Udp, UdpChecksumPayload, UdpEncap, UdpPort};
-use crate::vlan::{Pcp, Vid, Vlan};
-use crate::vxlan::Vxlan;
+use crate::vlan::{Pcp, Vid, Vlan, VlanTagKind};
+use crate::vxlan::{Vxlan, VxlanGpe};
 use arrayvec::ArrayVec;
 use core::fmt::Debug;
 use derive_builder::Builder;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr};
 use std::num::NonZero;
 use tracing::{debug, error, trace};
 
@@ -37,16 +46,24 @@ mod embedded;
 pub use embedded::*;
 
 const MAX_VLANS: usize = 4;
-const MAX_NET_EXTENSIONS: usize = 2;
+const MAX_MPLS_LABELS: usize = 8;
+const MAX_NET_EXTENSIONS: usize = 3;
 
 // TODO: remove `pub` from all fields
 #[derive(Debug, PartialEq, Eq, Clone, Default, Builder)]
 #[builder(default)]
 pub struct Headers {
     pub eth: Option<Eth>,
+    /// The VLAN stack, outermost tag first.
     pub vlan: ArrayVec<Vlan, MAX_VLANS>,
+    pub pppoe: Option<Pppoe>,
+    pub nsh: Option<Nsh>,
+    /// The MPLS label stack, outermost entry first.
+    pub mpls: ArrayVec<Mpls, MAX_MPLS_LABELS>,
+    pub arp: Option<Arp>,
     pub net: Option<Net>,
     pub net_ext: ArrayVec<NetExt, MAX_NET_EXTENSIONS>,
+    pub gre: Option<Gre>,
     pub transport: Option<Transport>,
     pub udp_encap: Option<UdpEncap>,
     pub embedded_ip: Option<EmbeddedHeaders>,
@@ -139,6 +156,28 @@ impl DeParse for Net {
 pub enum NetExt {
     IpAuth(IpAuth),
     Ipv6Ext(Ipv6Ext),
+    /// Always the last extension, as what follows is encrypted
+    Esp(Esp),
+}
+
+impl DeParse for NetExt {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        match self {
+            NetExt::IpAuth(auth) => auth.size(),
+            NetExt::Ipv6Ext(ext) => ext.size(),
+            NetExt::Esp(esp) => esp.size(),
+        }
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        match self {
+            NetExt::IpAuth(auth) => auth.deparse(buf),
+            NetExt::Ipv6Ext(ext) => ext.deparse(buf),
+            NetExt::Esp(esp) => esp.deparse(buf),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
@@ -155,6 +194,7 @@ pub enum Transport {
     Udp(Udp),
     Icmp4(Icmp4),
     Icmp6(Icmp6),
+    Sctp(Sctp),
 }
 
 impl Net {
@@ -169,6 +209,26 @@ impl Net {
 }
 
 impl Transport {
+    /// Seed the checksum of a TCP or UDP header with the sum of the IP pseudo-header, for the
+    /// hardware to complete it on transmission. `payload_len` is the length of the transport
+    /// payload.
+    pub(crate) fn seed_checksum(&mut self, net: &Net, payload_len: usize) {
+        let len = usize::from(self.size().get()) + payload_len;
+        match self {
+            Transport::Tcp(tcp) => {
+                let sum = pseudo_header_sum(net, NextHeader::TCP, len);
+                tcp.set_checksum(sum.into())
+                    .unwrap_or_else(|()| unreachable!()); // Setting TCP checksum never fails
+            }
+            Transport::Udp(udp) => {
+                let sum = pseudo_header_sum(net, NextHeader::UDP, len);
+                udp.set_checksum(sum.into())
+                    .unwrap_or_else(|()| unreachable!()); // Setting UDP checksum never fails
+            }
+            Transport::Icmp4(_) | Transport::Icmp6(_) | Transport::Sctp(_) => {}
+        }
+    }
+
     pub(crate) fn update_checksum(
         &mut self,
         net: &Net,
@@ -184,6 +244,9 @@ impl Transport {
                 udp.update_checksum(&UdpChecksumPayload::new(net, payload.as_ref()))
                     .unwrap_or_else(|()| unreachable!()); // Updating UDP checksum never fails
             }
+            (_, Transport::Sctp(sctp)) => {
+                sctp.update_checksum(payload.as_ref());
+            }
             (Net::Ipv4(_), Transport::Icmp4(icmp4)) => {
                 if icmp4.is_error_message() && embedded_headers.is_some() {
                     let checksum_payload =
@@ -230,6 +293,7 @@ impl Transport {
             Transport::Udp(udp) => udp.size(),
             Transport::Icmp4(icmp4) => icmp4.size(),
             Transport::Icmp6(icmpv6) => icmpv6.size(),
+            Transport::Sctp(sctp) => sctp.size(),
         }
     }
 
@@ -246,6 +310,9 @@ impl Transport {
             Transport::Udp(udp) => {
                 udp.set_source(UdpPort::new(port));
             }
+            Transport::Sctp(sctp) => {
+                sctp.set_source(SctpPort::new(port));
+            }
             _ => {
                 return Err(TransportError::UnsupportedPort);
             }
@@ -266,6 +333,9 @@ impl Transport {
             Transport::Udp(udp) => {
                 udp.set_destination(UdpPort::new(port));
             }
+            Transport::Sctp(sctp) => {
+                sctp.set_destination(SctpPort::new(port));
+            }
             _ => {
                 return Err(TransportError::UnsupportedPort);
             }
@@ -295,6 +365,7 @@ impl DeParse for Transport {
             Transport::Udp(x) => x.size(),
             Transport::Icmp4(x) => x.size(),
             Transport::Icmp6(x) => x.size(),
+            Transport::Sctp(x) => x.size(),
         }
     }
 
@@ -304,6 +375,7 @@ impl DeParse for Transport {
             Transport::Udp(x) => x.deparse(buf),
             Transport::Icmp4(x) => x.deparse(buf),
             Transport::Icmp6(x) => x.deparse(buf),
+            Transport::Sctp(x) => x.deparse(buf),
         }
     }
 }
@@ -312,14 +384,21 @@ impl DeParse for Transport {
 pub enum Header {
     Eth(Eth),
     Vlan(Vlan),
+    Pppoe(Pppoe),
+    Nsh(Nsh),
+    Mpls(Mpls),
+    Arp(Arp),
     Ipv4(Ipv4),
     Ipv6(Ipv6),
     Tcp(Tcp),
     Udp(Udp),
     Icmp4(Icmp4),
     Icmp6(Icmp6),
+    Sctp(Sctp),
     IpAuth(IpAuth),
     IpV6Ext(Ipv6Ext), // TODO: break out nested enum.  Nesting is counter productive here
+    Esp(Esp),
+    Gre(Gre),
     Encap(UdpEncap),
     EmbeddedIp(EmbeddedHeaders),
 }
@@ -327,27 +406,25 @@ pub enum Header {
 impl Header {
     fn parse_payload(&self, cursor: &mut Reader) -> Option<Header> {
         use Header::{
-            EmbeddedIp, Encap, Eth, Icmp4, Icmp6, IpAuth, IpV6Ext, Ipv4, Ipv6, Tcp, Udp, Vlan,
+            Arp, EmbeddedIp, Encap, Esp, Eth, Gre, Icmp4, Icmp6, IpAuth, IpV6Ext, Ipv4, Ipv6, Mpls,
+            Nsh, Pppoe, Sctp, Tcp, Udp, Vlan,
         };
         match self {
             Eth(eth) => eth.parse_payload(cursor).map(Header::from),
             Vlan(vlan) => vlan.parse_payload(cursor).map(Header::from),
+            Pppoe(pppoe) => pppoe.parse_payload(cursor).map(Header::from),
+            Nsh(nsh) => nsh.parse_payload(cursor).map(Header::from),
+            Mpls(mpls) => mpls.parse_payload(cursor).map(Header::from),
             Ipv4(ipv4) => ipv4.parse_payload(cursor).map(Header::from),
             Ipv6(ipv6) => ipv6.parse_payload(cursor).map(Header::from),
             IpAuth(auth) => auth.parse_payload(cursor).map(Header::from),
-            IpV6Ext(ext) => {
-                if let Ipv6(ipv6) = self {
-                    ext.parse_payload(ipv6.next_header(), cursor)
-                        .map(Header::from)
-                } else {
-                    debug!("ipv6 extension header outside ipv6 header");
-                    None
-                }
-            }
+            IpV6Ext(ext) => ext.parse_payload(cursor).map(Header::from),
             Icmp4(icmp4) => icmp4.parse_payload(cursor).map(Header::from),
             Icmp6(icmp6) => icmp6.parse_payload(cursor).map(Header::from),
             Udp(udp) => udp.parse_payload(cursor).map(Header::from),
-            Encap(_) | Tcp(_) | EmbeddedIp(_) => None,
+            // the payload of GRE is only parsed on decapsulation, and the payload of ESP is
+            // encrypted
+            Arp(_) | Gre(_) | Esp(_) | Encap(_) | Tcp(_) | Sctp(_) | EmbeddedIp(_) => None,
         }
     }
 }
@@ -359,49 +436,89 @@ impl Parse for Headers {
         let mut cursor =
             Reader::new(buf).map_err(|IllegalBufferLength(len)| ParseError::BufferTooLong(len))?;
         let (eth, _) = cursor.parse::<Eth>()?;
-        let mut this = Headers {
-            eth: Some(eth.clone()),
-            net: None,
-            transport: None,
-            vlan: ArrayVec::default(),
-            net_ext: ArrayVec::default(),
-            udp_encap: None,
-            embedded_ip: None,
+        let mut this = Headers::default();
+        this.parse_from(Header::Eth(eth), &mut cursor);
+        #[allow(unsafe_code, clippy::cast_possible_truncation)] // Non zero checked by parse impl
+        let consumed = unsafe {
+            NonZero::new_unchecked((cursor.inner.len() - cursor.remaining as usize) as u16)
         };
-        let mut prior = Header::Eth(eth);
+        Ok((this, consumed))
+    }
+}
+
+impl Headers {
+    /// Parse the headers of `buf` as the payload of the (already parsed) `eth` header.
+    ///
+    /// This is used to parse packets without an ethernet header of their own (e.g., IP in GRE),
+    /// in which case `eth` holds the ethertype of the payload.
+    /// Returns the parsed headers and the number of bytes they take.
+    pub(crate) fn parse_with_eth(
+        eth: Eth,
+        buf: &[u8],
+    ) -> Result<(Headers, u16), ParseError<EthError>> {
+        let mut cursor =
+            Reader::new(buf).map_err(|IllegalBufferLength(len)| ParseError::BufferTooLong(len))?;
+        let mut this = Headers::default();
+        this.parse_from(Header::Eth(eth), &mut cursor);
+        #[allow(clippy::cast_possible_truncation)] // length bounded on cursor creation
+        let consumed = (cursor.inner.len() - cursor.remaining as usize) as u16;
+        Ok((this, consumed))
+    }
+
+    /// Store `prior` and parse the headers following it from `cursor`.
+    fn parse_from(&mut self, mut prior: Header, cursor: &mut Reader) {
         loop {
-            let header = prior.parse_payload(&mut cursor);
+            let header = prior.parse_payload(cursor);
             match prior {
-                Header::Eth(eth) => this.eth = Some(eth),
-                Header::Ipv4(ip) => this.net = Some(Net::Ipv4(ip)),
-                Header::Ipv6(ip) => this.net = Some(Net::Ipv6(ip)),
-                Header::Tcp(tcp) => this.transport = Some(Transport::Tcp(tcp)),
-                Header::Udp(udp) => this.transport = Some(Transport::Udp(udp)),
-                Header::Icmp4(icmp4) => this.transport = Some(Transport::Icmp4(icmp4)),
-                Header::Icmp6(icmp6) => this.transport = Some(Transport::Icmp6(icmp6)),
-                Header::Encap(encap) => this.udp_encap = Some(encap),
+                Header::Eth(eth) => self.eth = Some(eth),
+                Header::Pppoe(pppoe) => self.pppoe = Some(pppoe),
+                Header::Nsh(nsh) => self.nsh = Some(nsh),
+                Header::Arp(arp) => self.arp = Some(arp),
+                Header::Ipv4(ip) => self.net = Some(Net::Ipv4(ip)),
+                Header::Ipv6(ip) => self.net = Some(Net::Ipv6(ip)),
+                Header::Tcp(tcp) => self.transport = Some(Transport::Tcp(tcp)),
+                Header::Udp(udp) => self.transport = Some(Transport::Udp(udp)),
+                Header::Icmp4(icmp4) => self.transport = Some(Transport::Icmp4(icmp4)),
+                Header::Icmp6(icmp6) => self.transport = Some(Transport::Icmp6(icmp6)),
+                Header::Sctp(sctp) => self.transport = Some(Transport::Sctp(sctp)),
+                Header::Encap(encap) => self.udp_encap = Some(encap),
+                Header::Gre(gre) => self.gre = Some(gre),
                 Header::Vlan(vlan) => {
-                    if this.vlan.len() < MAX_VLANS {
-                        this.vlan.push(vlan);
+                    if self.vlan.len() < MAX_VLANS {
+                        self.vlan.push(vlan);
+                    } else {
+                        break;
+                    }
+                }
+                Header::Mpls(mpls) => {
+                    if self.mpls.len() < MAX_MPLS_LABELS {
+                        self.mpls.push(mpls);
                     } else {
                         break;
                     }
                 }
                 Header::IpAuth(auth) => {
-                    if this.net_ext.len() < MAX_NET_EXTENSIONS {
-                        this.net_ext.push(NetExt::IpAuth(auth));
+                    if self.net_ext.len() < MAX_NET_EXTENSIONS {
+                        self.net_ext.push(NetExt::IpAuth(auth));
                     } else {
                         break;
                     }
                 }
                 Header::IpV6Ext(ext) => {
-                    if this.net_ext.len() < MAX_NET_EXTENSIONS {
-                        this.net_ext.push(NetExt::Ipv6Ext(ext));
+                    if self.net_ext.len() < MAX_NET_EXTENSIONS {
+                        self.net_ext.push(NetExt::Ipv6Ext(ext));
+                    } else {
+                        break;
+                    }
+                }
+                Header::Esp(esp) => {
+                    if self.net_ext.len() < MAX_NET_EXTENSIONS {
+                        self.net_ext.push(NetExt::Esp(esp));
                     } else {
                         break;
                     }
                 }
-                Header::EmbeddedIp(embedded) => this.embedded_ip = Some(embedded),
+                Header::EmbeddedIp(embedded) => self.embedded_ip = Some(embedded),
             }
             match header {
                 None => {
@@ -412,11 +529,6 @@ impl Parse for Headers {
                 }
             }
         }
-        #[allow(unsafe_code, clippy::cast_possible_truncation)] // Non zero checked by parse impl
-        let consumed = unsafe {
-            NonZero::new_unchecked((cursor.inner.len() - cursor.remaining as usize) as u16)
-        };
-        Ok((this, consumed))
     }
 }
 
@@ -424,9 +536,12 @@ impl DeParse for Headers {
     type Error = ();
 
     fn size(&self) -> NonZero<u16> {
-        // TODO(blocking): Deal with ip{v4,v6} extensions
         let eth = self.eth.as_ref().map(|x| x.size().get()).unwrap_or(0);
         let vlan = self.vlan.iter().map(|v| v.size().get()).sum::<u16>();
+        let pppoe = self.pppoe.as_ref().map_or(0, |pppoe| pppoe.size().get());
+        let nsh = self.nsh.as_ref().map_or(0, |nsh| nsh.size().get());
+        let mpls = self.mpls.iter().map(|m| m.size().get()).sum::<u16>();
+        let arp = self.arp.as_ref().map_or(0, |arp| arp.size().get());
         let net = match self.net {
             None => {
                 debug_assert!(self.transport.is_none());
@@ -434,6 +549,8 @@ impl DeParse for Headers {
             }
             Some(ref n) => n.size().get(),
         };
+        let net_ext = self.net_ext.iter().map(|ext| ext.size().get()).sum::<u16>();
+        let gre = self.gre.as_ref().map_or(0, |gre| gre.size().get());
         let transport = match self.transport {
             None => 0,
             Some(ref t) => t.size().get(),
@@ -441,17 +558,19 @@ impl DeParse for Headers {
         let encap = match self.udp_encap {
             None => 0,
             Some(UdpEncap::Vxlan(vxlan)) => vxlan.size().get(),
+            Some(UdpEncap::VxlanGpe(gpe)) => gpe.size().get(),
+            Some(UdpEncap::Geneve(ref geneve)) => geneve.size().get(),
         };
         let embedded_ip = self
             .embedded_ip
             .as_ref()
             .map_or(0, |embedded_header| embedded_header.size().get());
-        NonZero::new(eth + vlan + net + transport + encap + embedded_ip)
+        let link = eth + vlan + pppoe + nsh + mpls;
+        NonZero::new(link + arp + net + net_ext + gre + transport + encap + embedded_ip)
             .unwrap_or_else(|| unreachable!())
     }
 
     fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
-        // TODO(blocking): Deal with ip{v4,v6} extensions
         let len = buf.len();
         if len < self.size().into_non_zero_usize().get() {
             return Err(DeParseError::Length(LengthError {
@@ -467,9 +586,21 @@ impl DeParse for Headers {
                 cursor.write(eth)?;
             }
         }
-        for vlan in self.vlan.iter().rev() {
+        for vlan in &self.vlan {
             cursor.write(vlan)?;
         }
+        if let Some(ref pppoe) = self.pppoe {
+            cursor.write(pppoe)?;
+        }
+        if let Some(ref nsh) = self.nsh {
+            cursor.write(nsh)?;
+        }
+        for mpls in &self.mpls {
+            cursor.write(mpls)?;
+        }
+        if let Some(ref arp) = self.arp {
+            cursor.write(arp)?;
+        }
         match self.net {
             None => {
                 debug_assert!(self.transport.is_none());
@@ -484,6 +615,14 @@ impl DeParse for Headers {
             }
         }
 
+        for ext in &self.net_ext {
+            cursor.write(ext)?;
+        }
+
+        if let Some(ref gre) = self.gre {
+            cursor.write(gre)?;
+        }
+
         match self.transport {
             None => {
                 #[allow(clippy::cast_possible_truncation)] // length bounded on cursor creation
@@ -497,12 +636,15 @@ impl DeParse for Headers {
             }
         }
 
-        if let Some(UdpEncap::Vxlan(ref vxlan)) = self.udp_encap {
-            if matches!(self.transport, Some(Transport::Udp(_))) {
-                cursor.write(vxlan)?;
-            } else {
+        if let Some(ref encap) = self.udp_encap {
+            if !matches!(self.transport, Some(Transport::Udp(_))) {
                 return Err(DeParseError::Invalid(()));
             }
+            match encap {
+                UdpEncap::Vxlan(vxlan) => cursor.write(vxlan)?,
+                UdpEncap::VxlanGpe(gpe) => cursor.write(gpe)?,
+                UdpEncap::Geneve(geneve) => cursor.write(geneve)?,
+            };
         }
 
         if let Some(ref embedded_ip) = self.embedded_ip {
@@ -530,6 +672,22 @@ pub enum PushVlanError {
     NoEthernetHeader,
     #[error("Header already has as many VLAN headers as parser can support (max is {MAX_VLANS})")]
     TooManyVlans,
+    #[error("can't push a customer vlan tag above a service vlan tag")]
+    CustomerAboveService,
+}
+
+/// Errors which may occur when validating the VLAN stack of a [`Headers`].
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidVlanStack {
+    /// VLAN headers are present without an ethernet header.
+    #[error("vlan headers without an ethernet header")]
+    NoEthernetHeader,
+    /// The vlan tag at the given index (outermost first) is not introduced by a VLAN ethtype.
+    #[error("vlan tag {index} is introduced by ethtype {ethtype:?}")]
+    UnexpectedEthType { index: usize, ethtype: EthType },
+    /// A service tag follows a customer tag.
+    #[error("service vlan tag {0} follows a customer vlan tag")]
+    ServiceAfterCustomer(usize),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -538,6 +696,19 @@ pub enum PopVlanError {
     NoEthernetHeader,
 }
 
+/// Errors which may occur when pushing or popping MPLS label stack entries.
+#[derive(Debug, thiserror::Error)]
+pub enum MplsStackError {
+    #[error("can't push or pop mpls labels without an ethernet header")]
+    NoEthernetHeader,
+    #[error(
+        "Header already has as many MPLS labels as parser can support (max is {MAX_MPLS_LABELS})"
+    )]
+    TooManyLabels,
+    #[error("can't pop the last mpls label without an IP header below it")]
+    NoNetHeader,
+}
+
 impl Headers {
     /// Create a new [`Headers`] with the supplied `Eth` header.
     pub fn new() -> Headers {
@@ -568,19 +739,36 @@ impl Headers {
     #[allow(unsafe_code)]
     #[allow(dead_code)]
     unsafe fn push_vlan_header_unchecked(&mut self, vlan: Vlan) -> Result<(), PushVlanError> {
-        if self.vlan.len() < MAX_VLANS {
-            self.vlan.push(vlan);
-            Ok(())
-        } else {
-            Err(PushVlanError::TooManyVlans)
-        }
+        self.vlan
+            .try_insert(0, vlan)
+            .map_err(|_| PushVlanError::TooManyVlans)
     }
 
-    /// Push a vlan header onto the VLAN stack of this [`Headers`].
+    /// Push a (customer) vlan header onto the VLAN stack of this [`Headers`].
+    ///
+    /// The new header becomes the outermost tag, and the `eth` field has its [`EthType`] adjusted
+    /// to [`EthType::VLAN`].
     ///
-    /// This method will ensure that the `eth` field has its [`EthType`] adjusted to
-    /// [`EthType::VLAN`] if there are no [`Vlan`]s on the stack at the time this method was called.
+    /// # Errors
+    ///
+    /// Returns [`PushVlanError::CustomerAboveService`] if the current outermost tag is a service
+    /// tag, as customer tags must be below service tags.
     pub fn push_vlan(&mut self, vid: Vid) -> Result<(), PushVlanError> {
+        if self.outer_vlan_kind() == Some(VlanTagKind::Service) {
+            return Err(PushVlanError::CustomerAboveService);
+        }
+        self.push_tag(vid, VlanTagKind::Customer)
+    }
+
+    /// Push an 802.1ad service vlan header onto the VLAN stack of this [`Headers`].
+    ///
+    /// The new header becomes the outermost tag, and the `eth` field has its [`EthType`] adjusted
+    /// to [`EthType::VLAN_QINQ`].
+    pub fn push_s_vlan(&mut self, vid: Vid) -> Result<(), PushVlanError> {
+        self.push_tag(vid, VlanTagKind::Service)
+    }
+
+    fn push_tag(&mut self, vid: Vid, kind: VlanTagKind) -> Result<(), PushVlanError> {
         if self.vlan.len() >= MAX_VLANS {
             return Err(PushVlanError::TooManyVlans);
         }
@@ -588,15 +776,16 @@ impl Headers {
             None => Err(PushVlanError::NoEthernetHeader),
             Some(eth) => {
                 let old_eth_type = eth.ether_type();
-                eth.set_ether_type(EthType::VLAN);
+                eth.set_ether_type(kind.ethtype());
                 let new_vlan_header = Vlan::new(vid, old_eth_type, Pcp::default(), false);
-                self.vlan.push(new_vlan_header);
-                Ok(())
+                self.vlan
+                    .try_insert(0, new_vlan_header)
+                    .map_err(|_| PushVlanError::TooManyVlans)
             }
         }
     }
 
-    /// Pop a vlan header from the stack.
+    /// Pop the outermost vlan header from the stack.
     ///
     /// Returns [`None`] if no [`Vlan`]s are on the stack.
     ///
@@ -607,7 +796,7 @@ impl Headers {
     pub fn pop_vlan(&mut self) -> Result<Option<Vlan>, PopVlanError> {
         match &mut self.eth {
             None => Err(PopVlanError::NoEthernetHeader),
-            Some(eth) => match self.vlan.pop() {
+            Some(eth) => match self.vlan.pop_at(0) {
                 None => Ok(None),
                 Some(vlan) => {
                     eth.set_ether_type(vlan.inner_ethtype());
@@ -617,18 +806,291 @@ impl Headers {
         }
     }
 
-    /// update the checksums of the headers
-    pub(crate) fn update_checksums(&mut self, payload: impl AsRef<[u8]>) {
-        let is_vxlan = self.try_vxlan().is_some();
+    /// Get the kind of the vlan tag at `index` in the stack (outermost first).
+    ///
+    /// Returns [`None`] if there is no such tag, or if it is not introduced by a VLAN ethtype.
+    pub fn vlan_kind(&self, index: usize) -> Option<VlanTagKind> {
+        let ethtype = match index {
+            0 => self.eth.as_ref()?.ether_type(),
+            _ => self.vlan.get(index - 1)?.inner_ethtype(),
+        };
+        self.vlan.get(index)?;
+        VlanTagKind::from_ethtype(ethtype)
+    }
+
+    /// Get the kind of the outermost vlan tag, if any.
+    pub fn outer_vlan_kind(&self) -> Option<VlanTagKind> {
+        self.vlan_kind(0)
+    }
+
+    /// Get the outermost vlan tag, if any.
+    pub fn outer_vlan(&self) -> Option<&Vlan> {
+        self.vlan.first()
+    }
+
+    /// Get a mutable reference to the outermost vlan tag, if any.
+    ///
+    /// This allows rewriting the outer tag only, leaving any inner tag untouched.
+    pub fn outer_vlan_mut(&mut self) -> Option<&mut Vlan> {
+        self.vlan.first_mut()
+    }
+
+    /// Rewrite the [`Vid`] of the outermost vlan tag, returning the previous one.
+    ///
+    /// Returns [`None`] (and does nothing) if there is no vlan tag.
+    pub fn set_outer_vid(&mut self, vid: Vid) -> Option<Vid> {
+        let vlan = self.outer_vlan_mut()?;
+        let old = vlan.vid();
+        vlan.set_vid(vid);
+        Some(old)
+    }
+
+    /// Get the 802.1ad service tag (S-VLAN), if the outermost tag is one.
+    pub fn s_vlan(&self) -> Option<&Vlan> {
+        match self.outer_vlan_kind()? {
+            VlanTagKind::Service => self.vlan.first(),
+            VlanTagKind::Customer => None,
+        }
+    }
+
+    /// Get the outermost customer tag (C-VLAN), if any.
+    pub fn c_vlan(&self) -> Option<&Vlan> {
+        (0..self.vlan.len())
+            .find(|&i| self.vlan_kind(i) == Some(VlanTagKind::Customer))
+            .and_then(|i| self.vlan.get(i))
+    }
+
+    /// Check that the VLAN stack is well-formed, that is, that each tag is introduced by a VLAN
+    /// ethtype, and that service tags (if any) are all above customer tags (if any).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidVlanStack`] describing the first issue found in the stack.
+    pub fn validate_vlans(&self) -> Result<(), InvalidVlanStack> {
+        if self.vlan.is_empty() {
+            return Ok(());
+        }
+        let Some(eth) = &self.eth else {
+            return Err(InvalidVlanStack::NoEthernetHeader);
+        };
+        let mut seen_customer = false;
+        for index in 0..self.vlan.len() {
+            let Some(kind) = self.vlan_kind(index) else {
+                let ethtype = match index {
+                    0 => eth.ether_type(),
+                    _ => self.vlan[index - 1].inner_ethtype(),
+                };
+                return Err(InvalidVlanStack::UnexpectedEthType { index, ethtype });
+            };
+            match kind {
+                VlanTagKind::Customer => seen_customer = true,
+                VlanTagKind::Service if seen_customer => {
+                    return Err(InvalidVlanStack::ServiceAfterCustomer(index));
+                }
+                VlanTagKind::Service => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the ethtype introducing the payload of the link layer headers, that is, the ethtype of
+    /// the innermost vlan tag if any, or the ethtype of the `eth` header otherwise.
+    fn set_link_payload_ethtype(&mut self, ethtype: EthType) -> Result<(), MplsStackError> {
+        let Some(eth) = &mut self.eth else {
+            return Err(MplsStackError::NoEthernetHeader);
+        };
+        match self.vlan.last_mut() {
+            Some(vlan) => {
+                vlan.set_inner_ethtype(ethtype);
+            }
+            None => {
+                eth.set_ether_type(ethtype);
+            }
+        }
+        Ok(())
+    }
+
+    /// Push an MPLS label stack entry with the given `label` and `ttl` on top of the label stack.
+    ///
+    /// The entry is marked as bottom of stack if the stack was empty, in which case the ethtype
+    /// preceding the stack is set to [`EthType::MPLS`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`MplsStackError`] if there is no ethernet header or if the stack is full, in
+    /// which case the [`Headers`] is not modified.
+    pub fn push_mpls(&mut self, label: MplsLabel, ttl: u8) -> Result<(), MplsStackError> {
+        if self.mpls.is_full() {
+            return Err(MplsStackError::TooManyLabels);
+        }
+        let mut mpls = Mpls::new(label, ttl);
+        if self.mpls.is_empty() {
+            self.set_link_payload_ethtype(EthType::MPLS)?;
+            mpls.set_bos(true);
+        }
+        self.mpls.insert(0, mpls);
+        Ok(())
+    }
+
+    /// Pop the outermost MPLS label stack entry.
+    ///
+    /// Returns [`None`] if the label stack is empty. When popping the last entry, the ethtype
+    /// preceding the stack is set to the ethtype of the IP header below it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`MplsStackError`] if the last entry is to be popped but there is no IP header
+    /// to forward below it, in which case the [`Headers`] is not modified.
+    pub fn pop_mpls(&mut self) -> Result<Option<Mpls>, MplsStackError> {
+        match self.mpls.len() {
+            0 => Ok(None),
+            1 => {
+                let ethtype = match self.net {
+                    Some(Net::Ipv4(_)) => EthType::IPV4,
+                    Some(Net::Ipv6(_)) => EthType::IPV6,
+                    None => return Err(MplsStackError::NoNetHeader),
+                };
+                self.set_link_payload_ethtype(ethtype)?;
+                Ok(self.mpls.pop())
+            }
+            _ => Ok(self.mpls.pop_at(0)),
+        }
+    }
+
+    /// Get a mutable reference to the outermost MPLS label stack entry, if any.
+    pub fn outer_mpls_mut(&mut self) -> Option<&mut Mpls> {
+        self.mpls.first_mut()
+    }
+
+    /// Get the segment routing header of an IPv6 packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SrhError::Missing`] if the packet is not IPv6 or has no routing header, or
+    /// another [`SrhError`] if its routing header is not a valid segment routing header.
+    pub fn srh(&self) -> Result<Ipv6Srh, SrhError> {
+        let Some(Net::Ipv6(_)) = &self.net else {
+            return Err(SrhError::Missing);
+        };
+        self.net_ext
+            .iter()
+            .filter_map(|ext| match ext {
+                NetExt::Ipv6Ext(ext) => Some(ext.srh()),
+                _ => None,
+            })
+            .find(|srh| *srh != Err(SrhError::Missing))
+            .unwrap_or(Err(SrhError::Missing))
+    }
+
+    /// Move to the next segment of the segment routing header of an IPv6 packet: decrement its
+    /// segments left and set the destination address of the packet to the new active segment,
+    /// which is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SrhError::NoSegmentsLeft`] if the packet is at the end of its segment list, or
+    /// another [`SrhError`] if the packet has no valid segment routing header (see
+    /// [`Headers::srh`]). In this case, the headers are not modified.
+    pub fn srv6_advance(&mut self) -> Result<Ipv6Addr, SrhError> {
+        let mut srh = self.srh()?;
+        let segments_left = srh
+            .segments_left()
+            .checked_sub(1)
+            .ok_or(SrhError::NoSegmentsLeft)?;
+        srh.set_segments_left(segments_left)?;
+        let Some(ext) = self.net_ext.iter_mut().find_map(|ext| match ext {
+            NetExt::Ipv6Ext(ext) if ext.srh().is_ok() => Some(ext),
+            _ => None,
+        }) else {
+            unreachable!("srh was found");
+        };
+        ext.set_srh(&srh)?;
+        let active = srh.active_segment();
+        if let Some(Net::Ipv6(ipv6)) = &mut self.net {
+            ipv6.set_destination(active);
+        }
+        Ok(active)
+    }
+
+    /// Get the protocol of the payload of an IPv6 packet, past its extension headers.
+    pub(crate) fn ipv6_payload_protocol(&self) -> Option<NextHeader> {
+        let Some(Net::Ipv6(ipv6)) = &self.net else {
+            return None;
+        };
+        match self.net_ext.last() {
+            None => Some(ipv6.next_header()),
+            Some(NetExt::Ipv6Ext(ext)) => ext.next_header(),
+            Some(_) => None,
+        }
+    }
+
+    /// Get the checksums among `offload` which hardware can compute for these headers.
+    ///
+    /// The hardware is told the lengths of the link and network headers (see
+    /// [`Headers::offload_lengths`]), so the network header must directly follow the ethernet
+    /// header and its VLAN tags. The transport checksum can only be offloaded for TCP, and for
+    /// UDP when it does not carry an encapsulation.
+    #[must_use]
+    pub fn offloadable_checksums(&self, offload: ChecksumOffload) -> ChecksumOffload {
+        if self.eth.is_none()
+            || self.pppoe.is_some()
+            || self.nsh.is_some()
+            || !self.mpls.is_empty()
+            || self.gre.is_some()
+        {
+            return ChecksumOffload::empty();
+        }
+        let mut offloadable = ChecksumOffload::empty();
+        if let Some(Net::Ipv4(_)) = self.net {
+            offloadable |= ChecksumOffload::IPV4;
+        }
+        match (&self.transport, &self.udp_encap) {
+            (Some(Transport::Tcp(_)), _) => offloadable |= ChecksumOffload::TCP,
+            (Some(Transport::Udp(_)), None) => offloadable |= ChecksumOffload::UDP,
+            _ => {}
+        }
+        offload & offloadable
+    }
+
+    /// Get the lengths of the link headers (ethernet and VLAN tags) and of the network headers (IP
+    /// and its extensions), which hardware needs to compute the checksums offloaded to it.
+    #[must_use]
+    pub fn offload_lengths(&self) -> (u16, u16) {
+        let link = self.eth.as_ref().map_or(0, |eth| eth.size().get())
+            + self.vlan.iter().map(|vlan| vlan.size().get()).sum::<u16>();
+        let net = self.net.as_ref().map_or(0, |net| net.size().get())
+            + self.net_ext.iter().map(|ext| ext.size().get()).sum::<u16>();
+        (link, net)
+    }
+
+    /// Tell if the checksum of the transport header is to be computed by hardware, given the
+    /// `offload` flags.
+    pub(crate) fn transport_checksum_offloaded(&self, offload: ChecksumOffload) -> bool {
+        self.offloadable_checksums(offload)
+            .intersects(ChecksumOffload::TCP | ChecksumOffload::UDP)
+    }
+
+    /// update the checksums of the headers. The checksums to be computed by hardware, as requested
+    /// by `offload`, are prepared for it instead: the IPv4 checksum is zeroed, and the transport
+    /// checksum is seeded with the sum of the pseudo-header.
+    pub(crate) fn update_checksums(&mut self, payload: impl AsRef<[u8]>, offload: ChecksumOffload) {
+        let is_udp_encap = self.udp_encap.is_some();
+        let offload = self.offloadable_checksums(offload);
 
         let Some(net) = self.net.as_mut() else {
             trace!("no network header: can't update checksum");
             return;
         };
-        net.update_checksum();
+        match net {
+            Net::Ipv4(ip) if offload.contains(ChecksumOffload::IPV4) => {
+                ip.set_checksum(0.into())
+                    .unwrap_or_else(|()| unreachable!()); // Setting IPv4 checksum never fails
+            }
+            _ => net.update_checksum(),
+        }
 
-        if is_vxlan {
-            // Only recompute checksum if it is not VXLAN
+        if is_udp_encap {
+            // Only recompute checksum if it is not VXLAN or Geneve
             return;
         }
 
@@ -652,6 +1114,10 @@ impl Headers {
             trace!("no transport header: can't update checksum");
             return;
         };
+        if offload.intersects(ChecksumOffload::TCP | ChecksumOffload::UDP) {
+            transport.seed_checksum(net, payload.as_ref().len());
+            return;
+        }
         transport.update_checksum(net, self.embedded_ip.as_ref(), payload.as_ref());
     }
 }
@@ -678,6 +1144,72 @@ impl TryEthMut for Headers {
     }
 }
 
+// ARP traits
+
+pub trait TryArp {
+    fn try_arp(&self) -> Option<&Arp>;
+}
+
+pub trait TryArpMut {
+    fn try_arp_mut(&mut self) -> Option<&mut Arp>;
+}
+
+impl TryArp for Headers {
+    fn try_arp(&self) -> Option<&Arp> {
+        self.arp.as_ref()
+    }
+}
+
+impl TryArpMut for Headers {
+    fn try_arp_mut(&mut self) -> Option<&mut Arp> {
+        self.arp.as_mut()
+    }
+}
+
+// PPPoE traits
+
+pub trait TryPppoe {
+    fn try_pppoe(&self) -> Option<&Pppoe>;
+}
+
+pub trait TryPppoeMut {
+    fn try_pppoe_mut(&mut self) -> Option<&mut Pppoe>;
+}
+
+impl TryPppoe for Headers {
+    fn try_pppoe(&self) -> Option<&Pppoe> {
+        self.pppoe.as_ref()
+    }
+}
+
+impl TryPppoeMut for Headers {
+    fn try_pppoe_mut(&mut self) -> Option<&mut Pppoe> {
+        self.pppoe.as_mut()
+    }
+}
+
+// NSH traits
+
+pub trait TryNsh {
+    fn try_nsh(&self) -> Option<&Nsh>;
+}
+
+pub trait TryNshMut {
+    fn try_nsh_mut(&mut self) -> Option<&mut Nsh>;
+}
+
+impl TryNsh for Headers {
+    fn try_nsh(&self) -> Option<&Nsh> {
+        self.nsh.as_ref()
+    }
+}
+
+impl TryNshMut for Headers {
+    fn try_nsh_mut(&mut self) -> Option<&mut Nsh> {
+        self.nsh.as_mut()
+    }
+}
+
 // Ipv4 traits
 
 pub trait TryIpv4 {
@@ -756,6 +1288,47 @@ impl TryIpMut for Headers {
     }
 }
 
+// IPsec traits
+
+pub trait TryIpAuth {
+    fn try_ip_auth(&self) -> Option<&IpAuth>;
+}
+
+pub trait TryEsp {
+    fn try_esp(&self) -> Option<&Esp>;
+}
+
+pub trait TryEspMut {
+    fn try_esp_mut(&mut self) -> Option<&mut Esp>;
+}
+
+impl TryIpAuth for Headers {
+    fn try_ip_auth(&self) -> Option<&IpAuth> {
+        self.net_ext.iter().find_map(|ext| match ext {
+            NetExt::IpAuth(auth) => Some(auth),
+            _ => None,
+        })
+    }
+}
+
+impl TryEsp for Headers {
+    fn try_esp(&self) -> Option<&Esp> {
+        match self.net_ext.last() {
+            Some(NetExt::Esp(esp)) => Some(esp),
+            _ => None,
+        }
+    }
+}
+
+impl TryEspMut for Headers {
+    fn try_esp_mut(&mut self) -> Option<&mut Esp> {
+        match self.net_ext.last_mut() {
+            Some(NetExt::Esp(esp)) => Some(esp),
+            _ => None,
+        }
+    }
+}
+
 // Tcp traits
 
 pub trait TryTcp {
@@ -812,6 +1385,34 @@ impl TryUdpMut for Headers {
     }
 }
 
+// SCTP traits
+
+pub trait TrySctp {
+    fn try_sctp(&self) -> Option<&Sctp>;
+}
+
+pub trait TrySctpMut {
+    fn try_sctp_mut(&mut self) -> Option<&mut Sctp>;
+}
+
+impl TrySctp for Headers {
+    fn try_sctp(&self) -> Option<&Sctp> {
+        match &self.transport {
+            Some(Transport::Sctp(header)) => Some(header),
+            _ => None,
+        }
+    }
+}
+
+impl TrySctpMut for Headers {
+    fn try_sctp_mut(&mut self) -> Option<&mut Sctp> {
+        match &mut self.transport {
+            Some(Transport::Sctp(header)) => Some(header),
+            _ => None,
+        }
+    }
+}
+
 // ICMPv4 traits
 
 pub trait TryIcmp4 {
@@ -920,6 +1521,28 @@ impl TryTransportMut for Headers {
     }
 }
 
+// Gre traits
+
+pub trait TryGre {
+    fn try_gre(&self) -> Option<&Gre>;
+}
+
+pub trait TryGreMut {
+    fn try_gre_mut(&mut self) -> Option<&mut Gre>;
+}
+
+impl TryGre for Headers {
+    fn try_gre(&self) -> Option<&Gre> {
+        self.gre.as_ref()
+    }
+}
+
+impl TryGreMut for Headers {
+    fn try_gre_mut(&mut self) -> Option<&mut Gre> {
+        self.gre.as_mut()
+    }
+}
+
 // Vxlan traits
 
 pub trait TryVxlan {
@@ -948,18 +1571,81 @@ impl TryVxlanMut for Headers {
     }
 }
 
+// Vxlan-GPE traits
+
+pub trait TryVxlanGpe {
+    fn try_vxlan_gpe(&self) -> Option<&VxlanGpe>;
+}
+
+pub trait TryVxlanGpeMut {
+    fn try_vxlan_gpe_mut(&mut self) -> Option<&mut VxlanGpe>;
+}
+
+impl TryVxlanGpe for Headers {
+    fn try_vxlan_gpe(&self) -> Option<&VxlanGpe> {
+        match &self.udp_encap {
+            Some(UdpEncap::VxlanGpe(gpe)) => Some(gpe),
+            _ => None,
+        }
+    }
+}
+
+impl TryVxlanGpeMut for Headers {
+    fn try_vxlan_gpe_mut(&mut self) -> Option<&mut VxlanGpe> {
+        match &mut self.udp_encap {
+            Some(UdpEncap::VxlanGpe(gpe)) => Some(gpe),
+            _ => None,
+        }
+    }
+}
+
+// Geneve traits
+
+pub trait TryGeneve {
+    fn try_geneve(&self) -> Option<&Geneve>;
+}
+
+pub trait TryGeneveMut {
+    fn try_geneve_mut(&mut self) -> Option<&mut Geneve>;
+}
+
+impl TryGeneve for Headers {
+    fn try_geneve(&self) -> Option<&Geneve> {
+        match &self.udp_encap {
+            Some(UdpEncap::Geneve(geneve)) => Some(geneve),
+            _ => None,
+        }
+    }
+}
+
+impl TryGeneveMut for Headers {
+    fn try_geneve_mut(&mut self) -> Option<&mut Geneve> {
+        match &mut self.udp_encap {
+            Some(UdpEncap::Geneve(geneve)) => Some(geneve),
+            _ => None,
+        }
+    }
+}
+
 impl_from_for_enum![
     Header,
     Eth(Eth),
     Vlan(Vlan),
+    Pppoe(Pppoe),
+    Nsh(Nsh),
+    Mpls(Mpls),
+    Arp(Arp),
     Ipv4(Ipv4),
     Ipv6(Ipv6),
     Tcp(Tcp),
     Udp(Udp),
     Icmp4(Icmp4),
     Icmp6(Icmp6),
+    Sctp(Sctp),
     IpAuth(IpAuth),
     IpV6Ext(Ipv6Ext),
+    Esp(Esp),
+    Gre(Gre),
     Encap(UdpEncap),
     EmbeddedIp(EmbeddedHeaders),
 ];
@@ -980,6 +1666,7 @@ impl From<Transport> for Header {
             Transport::Udp(x) => Header::from(x),
             Transport::Icmp4(x) => Header::from(x),
             Transport::Icmp6(x) => Header::from(x),
+            Transport::Sctp(x) => Header::from(x),
         }
     }
 }
@@ -990,19 +1677,40 @@ impl From<Vxlan> for Header {
     }
 }
 
+impl From<VxlanGpe> for Header {
+    fn from(value: VxlanGpe) -> Self {
+        Header::Encap(UdpEncap::VxlanGpe(value))
+    }
+}
+
+impl From<Geneve> for Header {
+    fn from(value: Geneve) -> Self {
+        Header::Encap(UdpEncap::Geneve(value))
+    }
+}
+
 pub trait AbstractHeaders:
     Debug
     + TryEth
+    + TryPppoe
+    + TryNsh
+    + TryArp
     + TryIpv4
     + TryIpv6
     + TryIp
+    + TryIpAuth
+    + TryEsp
     + TryTcp
     + TryUdp
+    + TrySctp
     + TryIcmp4
     + TryIcmp6
     + TryIcmpAny
     + TryTransport
+    + TryGre
     + TryVxlan
+    + TryVxlanGpe
+    + TryGeneve
     + DeParse
 {
 }
@@ -1010,16 +1718,25 @@ pub trait AbstractHeaders:
 impl<T> AbstractHeaders for T where
     T: Debug
         + TryEth
+        + TryPppoe
+        + TryNsh
+        + TryArp
         + TryIpv4
         + TryIpv6
         + TryIp
+        + TryIpAuth
+        + TryEsp
         + TryTcp
         + TryUdp
+        + TrySctp
         + TryIcmp4
         + TryIcmp6
         + TryIcmpAny
         + TryTransport
+        + TryGre
         + TryVxlan
+        + TryVxlanGpe
+        + TryGeneve
         + DeParse
 {
 }
@@ -1027,32 +1744,48 @@ impl<T> AbstractHeaders for T where
 pub trait AbstractHeadersMut:
     AbstractHeaders
     + TryEthMut
+    + TryPppoeMut
+    + TryNshMut
+    + TryArpMut
     + TryIpv4Mut
     + TryIpv6Mut
     + TryIpMut
+    + TryEspMut
     + TryTcpMut
     + TryUdpMut
+    + TrySctpMut
     + TryIcmp4Mut
     + TryIcmp6Mut
     + TryIcmpAnyMut
     + TryTransportMut
+    + TryGreMut
     + TryVxlanMut
+    + TryVxlanGpeMut
+    + TryGeneveMut
 {
 }
 
 impl<T> AbstractHeadersMut for T where
     T: AbstractHeaders
         + TryEthMut
+        + TryPppoeMut
+        + TryNshMut
+        + TryArpMut
         + TryIpv4Mut
         + TryIpv6Mut
         + TryIpMut
+        + TryEspMut
         + TryTcpMut
         + TryUdpMut
+        + TrySctpMut
         + TryIcmp4Mut
         + TryIcmp6Mut
         + TryIcmpAnyMut
         + TryTransportMut
+        + TryGreMut
         + TryVxlanMut
+        + TryVxlanGpeMut
+        + TryGeneveMut
 {
 }
 
@@ -1073,6 +1806,33 @@ where
     }
 }
 
+impl<T> TryPppoe for T
+where
+    T: TryHeaders,
+{
+    fn try_pppoe(&self) -> Option<&Pppoe> {
+        self.headers().try_pppoe()
+    }
+}
+
+impl<T> TryNsh for T
+where
+    T: TryHeaders,
+{
+    fn try_nsh(&self) -> Option<&Nsh> {
+        self.headers().try_nsh()
+    }
+}
+
+impl<T> TryArp for T
+where
+    T: TryHeaders,
+{
+    fn try_arp(&self) -> Option<&Arp> {
+        self.headers().try_arp()
+    }
+}
+
 impl<T> TryIpv4 for T
 where
     T: TryHeaders,
@@ -1118,6 +1878,15 @@ where
     }
 }
 
+impl<T> TrySctp for T
+where
+    T: TryHeaders,
+{
+    fn try_sctp(&self) -> Option<&Sctp> {
+        self.headers().try_sctp()
+    }
+}
+
 impl<T> TryIcmp4 for T
 where
     T: TryHeaders,
@@ -1145,6 +1914,15 @@ where
     }
 }
 
+impl<T> TryGre for T
+where
+    T: TryHeaders,
+{
+    fn try_gre(&self) -> Option<&Gre> {
+        self.headers().try_gre()
+    }
+}
+
 impl<T> TryVxlan for T
 where
     T: TryHeaders,
@@ -1163,6 +1941,33 @@ where
     }
 }
 
+impl<T> TryPppoeMut for T
+where
+    T: TryHeadersMut,
+{
+    fn try_pppoe_mut(&mut self) -> Option<&mut Pppoe> {
+        self.headers_mut().try_pppoe_mut()
+    }
+}
+
+impl<T> TryNshMut for T
+where
+    T: TryHeadersMut,
+{
+    fn try_nsh_mut(&mut self) -> Option<&mut Nsh> {
+        self.headers_mut().try_nsh_mut()
+    }
+}
+
+impl<T> TryArpMut for T
+where
+    T: TryHeadersMut,
+{
+    fn try_arp_mut(&mut self) -> Option<&mut Arp> {
+        self.headers_mut().try_arp_mut()
+    }
+}
+
 impl<T> TryIpv4Mut for T
 where
     T: TryHeadersMut,
@@ -1208,6 +2013,15 @@ where
     }
 }
 
+impl<T> TrySctpMut for T
+where
+    T: TryHeadersMut,
+{
+    fn try_sctp_mut(&mut self) -> Option<&mut Sctp> {
+        self.headers_mut().try_sctp_mut()
+    }
+}
+
 impl<T> TryIcmp4Mut for T
 where
     T: TryHeadersMut,
@@ -1253,6 +2067,15 @@ where
     }
 }
 
+impl<T> TryGreMut for T
+where
+    T: TryHeadersMut,
+{
+    fn try_gre_mut(&mut self) -> Option<&mut Gre> {
+        self.headers_mut().try_gre_mut()
+    }
+}
+
 impl<T> TryVxlanMut for T
 where
     T: TryHeadersMut,
@@ -1262,6 +2085,69 @@ where
     }
 }
 
+impl<T> TryVxlanGpe for T
+where
+    T: TryHeaders,
+{
+    fn try_vxlan_gpe(&self) -> Option<&VxlanGpe> {
+        self.headers().try_vxlan_gpe()
+    }
+}
+
+impl<T> TryVxlanGpeMut for T
+where
+    T: TryHeadersMut,
+{
+    fn try_vxlan_gpe_mut(&mut self) -> Option<&mut VxlanGpe> {
+        self.headers_mut().try_vxlan_gpe_mut()
+    }
+}
+
+impl<T> TryIpAuth for T
+where
+    T: TryHeaders,
+{
+    fn try_ip_auth(&self) -> Option<&IpAuth> {
+        self.headers().try_ip_auth()
+    }
+}
+
+impl<T> TryEsp for T
+where
+    T: TryHeaders,
+{
+    fn try_esp(&self) -> Option<&Esp> {
+        self.headers().try_esp()
+    }
+}
+
+impl<T> TryGeneve for T
+where
+    T: TryHeaders,
+{
+    fn try_geneve(&self) -> Option<&Geneve> {
+        self.headers().try_geneve()
+    }
+}
+
+impl<T> TryEspMut for T
+where
+    T: TryHeadersMut,
+{
+    fn try_esp_mut(&mut self) -> Option<&mut Esp> {
+        self.headers_mut().try_esp_mut()
+    }
+}
+
+impl<T> TryGeneveMut for T
+where
+    T: TryHeadersMut,
+{
+    fn try_geneve_mut(&mut self) -> Option<&mut Geneve> {
+        self.headers_mut().try_geneve_mut()
+    }
+}
+
 #[cfg(any(test, feature = "bolero"))]
 mod contract {
     use crate::eth::ethtype::CommonEthType;
@@ -1343,8 +2229,13 @@ mod contract {
                             let headers = Headers {
                                 eth: Some(eth),
                                 vlan: Default::default(),
+                                pppoe: None,
+                                nsh: None,
+                                mpls: Default::default(),
+                                arp: None,
                                 net: Some(Net::Ipv4(ipv4)),
                                 net_ext: Default::default(),
+                                gre: None,
                                 transport: Some(Transport::Tcp(tcp)),
                                 udp_encap: None,
                                 embedded_ip: None,
@@ -1362,8 +2253,13 @@ mod contract {
                             let headers = Headers {
                                 eth: Some(eth),
                                 vlan: Default::default(),
+                                pppoe: None,
+                                nsh: None,
+                                mpls: Default::default(),
+                                arp: None,
                                 net: Some(Net::Ipv4(ipv4)),
                                 net_ext: Default::default(),
+                                gre: None,
                                 transport: Some(Transport::Udp(udp)),
                                 udp_encap,
                                 embedded_ip: None,
@@ -1375,8 +2271,13 @@ mod contract {
                             let headers = Headers {
                                 eth: Some(eth),
                                 vlan: ArrayVec::default(),
+                                pppoe: None,
+                                nsh: None,
+                                mpls: ArrayVec::default(),
+                                arp: None,
                                 net: Some(Net::Ipv4(ipv4)),
                                 net_ext: Default::default(),
+                                gre: None,
                                 transport: Some(Transport::Icmp4(icmp)),
                                 udp_encap: None,
                                 embedded_ip: None,
@@ -1395,8 +2296,13 @@ mod contract {
                             let headers = Headers {
                                 eth: Some(eth),
                                 vlan: Default::default(),
+                                pppoe: None,
+                                nsh: None,
+                                mpls: Default::default(),
+                                arp: None,
                                 net: Some(Net::Ipv6(ipv6)),
                                 net_ext: Default::default(),
+                                gre: None,
                                 transport: Some(Transport::Tcp(tcp)),
                                 udp_encap: None,
                                 embedded_ip: None,
@@ -1414,8 +2320,13 @@ mod contract {
                             let headers = Headers {
                                 eth: Some(eth),
                                 vlan: Default::default(),
+                                pppoe: None,
+                                nsh: None,
+                                mpls: Default::default(),
+                                arp: None,
                                 net: Some(Net::Ipv6(ipv6)),
                                 net_ext: Default::default(),
+                                gre: None,
                                 transport: Some(Transport::Udp(udp)),
                                 udp_encap,
                                 embedded_ip: None,
@@ -1427,8 +2338,13 @@ mod contract {
                             let headers = Headers {
                                 eth: Some(eth),
                                 vlan: Default::default(),
+                                pppoe: None,
+                                nsh: None,
+                                mpls: Default::default(),
+                                arp: None,
                                 net: Some(Net::Ipv6(ipv6)),
                                 net_ext: Default::default(),
+                                gre: None,
                                 transport: Some(Transport::Icmp6(icmp6)),
                                 udp_encap: None,
                                 embedded_ip: None,
@@ -1492,6 +2408,62 @@ mod test {
             .for_each(parse_back_test)
     }
 
+    #[test]
+    fn qinq_vlan_stack() {
+        let vid = |vid| Vid::new(vid).unwrap();
+        let mut headers = sample::ipv4_tcp();
+        headers.push_vlan(vid(100)).unwrap();
+        headers.push_s_vlan(vid(10)).unwrap();
+        assert!(matches!(
+            headers.push_vlan(vid(200)),
+            Err(PushVlanError::CustomerAboveService)
+        ));
+        headers.validate_vlans().unwrap();
+        assert_eq!(
+            headers.eth.as_ref().unwrap().ether_type(),
+            EthType::VLAN_QINQ
+        );
+        assert_eq!(headers.s_vlan().unwrap().vid(), vid(10));
+        assert_eq!(headers.c_vlan().unwrap().vid(), vid(100));
+        parse_back_test(&headers);
+
+        // the outer tag is on the wire first
+        let mut buffer = [0_u8; 1024];
+        headers.deparse(&mut buffer).unwrap();
+        assert_eq!(buffer[12..=13], EthType::VLAN_QINQ.as_u16().to_be_bytes());
+        assert_eq!(buffer[14..=15], 10_u16.to_be_bytes());
+        assert_eq!(buffer[16..=17], EthType::VLAN.as_u16().to_be_bytes());
+        assert_eq!(buffer[18..=19], 100_u16.to_be_bytes());
+
+        assert_eq!(headers.set_outer_vid(vid(20)), Some(vid(10)));
+        assert_eq!(headers.s_vlan().unwrap().vid(), vid(20));
+        assert_eq!(headers.c_vlan().unwrap().vid(), vid(100));
+
+        let outer = headers.pop_vlan().unwrap().unwrap();
+        assert_eq!(outer.vid(), vid(20));
+        assert_eq!(headers.outer_vlan_kind(), Some(VlanTagKind::Customer));
+        assert!(headers.s_vlan().is_none());
+        headers.pop_vlan().unwrap().unwrap();
+        assert_eq!(headers.eth.as_ref().unwrap().ether_type(), EthType::IPV4);
+        assert_eq!(headers.set_outer_vid(vid(20)), None);
+
+        // service tag below a customer tag
+        headers.eth.as_mut().unwrap().set_ether_type(EthType::VLAN);
+        headers.vlan.push(Vlan::new(
+            vid(100),
+            EthType::VLAN_QINQ,
+            Pcp::default(),
+            false,
+        ));
+        headers
+            .vlan
+            .push(Vlan::new(vid(10), EthType::IPV4, Pcp::default(), false));
+        assert!(matches!(
+            headers.validate_vlans(),
+            Err(InvalidVlanStack::ServiceAfterCustomer(1))
+        ));
+    }
+
     mod sample {
         use crate::checksum::Checksum;
         use crate::eth::Eth;
@@ -1686,7 +2658,7 @@ mod test {
             }
         }
 
-        headers.update_checksums([]);
+        headers.update_checksums([], ChecksumOffload::empty());
 
         match &headers.transport {
             None => {}
@@ -1870,7 +2842,7 @@ mod test {
         ];
         for comparison in comparisons {
             let mut headers = sample::ipv4_tcp();
-            headers.update_checksums(comparison.payload);
+            headers.update_checksums(comparison.payload, ChecksumOffload::empty());
             match &headers.net {
                 Some(net) => match net {
                     Net::Ipv4(ipv4) => {
@@ -1923,7 +2895,7 @@ mod test {
         ];
         for comparison in comparisons {
             let mut headers = sample::ipv4_udp();
-            headers.update_checksums(comparison.payload);
+            headers.update_checksums(comparison.payload, ChecksumOffload::empty());
             match &headers.net {
                 Some(net) => match net {
                     Net::Ipv4(ipv4) => {
@@ -1976,7 +2948,7 @@ mod test {
         ];
         for comparison in comparisons {
             let mut headers = sample::ipv4_icmp();
-            headers.update_checksums(comparison.payload);
+            headers.update_checksums(comparison.payload, ChecksumOffload::empty());
             match &headers.net {
                 Some(net) => {
                     if let Net::Ipv4(ipv4) = net {
@@ -2024,7 +2996,7 @@ mod test {
         ];
         for comparison in comparisons {
             let mut headers = sample::ipv6_tcp();
-            headers.update_checksums(comparison.payload);
+            headers.update_checksums(comparison.payload, ChecksumOffload::empty());
             match (headers.net, headers.transport) {
                 (Some(net), Some(Transport::Tcp(tcp))) => {
                     assert_eq!(tcp.checksum().unwrap(), comparison.good_tcp);
@@ -2062,7 +3034,7 @@ mod test {
         ];
         for comparison in comparisons {
             let mut headers = sample::ipv6_udp();
-            headers.update_checksums(comparison.payload);
+            headers.update_checksums(comparison.payload, ChecksumOffload::empty());
             match (headers.net, headers.transport) {
                 (Some(net), Some(Transport::Udp(udp))) => {
                     assert_eq!(udp.checksum().unwrap(), comparison.good_udp);
@@ -2100,7 +3072,7 @@ mod test {
         ];
         for comparison in comparisons {
             let mut headers = sample::ipv6_icmp();
-            headers.update_checksums(comparison.payload);
+            headers.update_checksums(comparison.payload, ChecksumOffload::empty());
             match (headers.net, headers.transport) {
                 (Some(Net::Ipv6(ipv6)), Some(Transport::Icmp6(icmp))) => {
                     assert_eq!(icmp.checksum().unwrap(), comparison.good_icmp);
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Generation of `ICMPv4` error messages, as per [RFC 792] and [RFC 1812].
+//!
+//! [RFC 792]: https://datatracker.ietf.org/doc/html/rfc792
+//! [RFC 1812]: https://datatracker.ietf.org/doc/html/rfc1812#section-4.3.2
+
+use crate::buffer::{Append, PacketBufferMut};
+use crate::headers::{Net, TryIp};
+use crate::icmp_any::{IcmpErrorMessageError, prepare_error_message};
+use crate::icmp4::Icmp4;
+use crate::ipv4::Ipv4;
+use crate::ipv4::addr::UnicastIpv4Addr;
+use crate::packet::Packet;
+use crate::parse::DeParse;
+use etherparse::icmpv4::{DestUnreachableHeader, TimeExceededCode};
+use etherparse::{Icmpv4Header, Icmpv4Type};
+
+/// Maximum length of an `ICMPv4` error message, IP header included ([RFC 1812]).
+///
+/// [RFC 1812]: https://datatracker.ietf.org/doc/html/rfc1812#section-4.3.2.3
+pub const ERROR_MESSAGE_MAX_LEN: usize = 576;
+
+/// TTL of the generated `ICMPv4` error messages
+pub const ERROR_MESSAGE_TTL: u8 = 64;
+
+/// Build an `ICMPv4` Destination Unreachable message in `buffer`, in response to `offending`.
+///
+/// The message is sent from `source` to the source of `offending`.
+///
+/// # Errors
+///
+/// Returns an [`IcmpErrorMessageError`] if no error message should be sent in response to
+/// `offending` (see [`error_message`]), or if the message can't be built in `buffer`.
+pub fn destination_unreachable<Buf: PacketBufferMut, Out: PacketBufferMut + Append>(
+    offending: &Packet<Buf>,
+    source: UnicastIpv4Addr,
+    code: DestUnreachableHeader,
+    buffer: Out,
+) -> Result<Packet<Out>, IcmpErrorMessageError> {
+    error_message(
+        offending,
+        source,
+        Icmpv4Type::DestinationUnreachable(code),
+        buffer,
+    )
+}
+
+/// Build an `ICMPv4` Time Exceeded message in `buffer`, in response to `offending`.
+///
+/// The message is sent from `source` to the source of `offending`.
+///
+/// # Errors
+///
+/// Returns an [`IcmpErrorMessageError`] if no error message should be sent in response to
+/// `offending` (see [`error_message`]), or if the message can't be built in `buffer`.
+pub fn time_exceeded<Buf: PacketBufferMut, Out: PacketBufferMut + Append>(
+    offending: &Packet<Buf>,
+    source: UnicastIpv4Addr,
+    code: TimeExceededCode,
+    buffer: Out,
+) -> Result<Packet<Out>, IcmpErrorMessageError> {
+    error_message(offending, source, Icmpv4Type::TimeExceeded(code), buffer)
+}
+
+/// Build an `ICMPv4` "Fragmentation Needed and Don't Fragment was Set" message in `buffer`, in
+/// response to `offending`, which is larger than the `mtu` of the next hop.
+///
+/// The message is sent from `source` to the source of `offending`.
+///
+/// # Errors
+///
+/// Returns an [`IcmpErrorMessageError`] if no error message should be sent in response to
+/// `offending` (see [`error_message`]), or if the message can't be built in `buffer`.
+pub fn packet_too_big<Buf: PacketBufferMut, Out: PacketBufferMut + Append>(
+    offending: &Packet<Buf>,
+    source: UnicastIpv4Addr,
+    mtu: u16,
+    buffer: Out,
+) -> Result<Packet<Out>, IcmpErrorMessageError> {
+    error_message(
+        offending,
+        source,
+        Icmpv4Type::DestinationUnreachable(DestUnreachableHeader::FragmentationNeeded {
+            next_hop_mtu: mtu,
+        }),
+        buffer,
+    )
+}
+
+/// Build an `ICMPv4` error message of type `icmp_type` in `buffer`, in response to `offending`.
+///
+/// The message embeds as much of the IP datagram of `offending` as fits in
+/// [`ERROR_MESSAGE_MAX_LEN`] bytes.
+///
+/// # Errors
+///
+/// Returns an [`IcmpErrorMessageError`] if `icmp_type` is not an error type, or if no error
+/// message should be sent in response to `offending`, that is, if `offending`:
+///
+/// * is not an IPv4 packet,
+/// * is itself an ICMP error message,
+/// * is a non-first fragment,
+/// * was sent to a multicast or broadcast address, at the link or network layer.
+///
+/// Also returns an error if the message can't be built in `buffer`.
+pub fn error_message<Buf: PacketBufferMut, Out: PacketBufferMut + Append>(
+    offending: &Packet<Buf>,
+    source: UnicastIpv4Addr,
+    icmp_type: Icmpv4Type,
+    buffer: Out,
+) -> Result<Packet<Out>, IcmpErrorMessageError> {
+    let icmp = Icmp4(Icmpv4Header::new(icmp_type));
+    if !icmp.is_error_message() {
+        return Err(IcmpErrorMessageError::NotAnError);
+    }
+    let Some(Net::Ipv4(offending_ip)) = offending.try_ip() else {
+        return Err(IcmpErrorMessageError::NoIp);
+    };
+    if offending_ip.fragment_offset().value() != 0 {
+        return Err(IcmpErrorMessageError::NonFirstFragment);
+    }
+    let destination = offending_ip.destination();
+    if destination.is_broadcast() || destination.is_multicast() {
+        return Err(IcmpErrorMessageError::NonUnicastDestination);
+    }
+
+    let mut ip = Ipv4::default();
+    ip.set_source(source)
+        .set_destination(offending_ip.source().inner())
+        .set_ttl(ERROR_MESSAGE_TTL);
+
+    let max_len = ERROR_MESSAGE_MAX_LEN - ip.header_len() - usize::from(icmp.size().get());
+    let (builder, datagram) = prepare_error_message(offending, max_len)?;
+    Ok(builder
+        .ipv4(ip)
+        .icmp4(icmp)
+        .payload(datagram)
+        .build_in(buffer)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::eth::mac::Mac;
+    use crate::headers::{
+        EmbeddedTransport, Net, TryEmbeddedHeaders, TryEmbeddedTransport, TryIcmp4, TryInnerIp,
+        TryIp,
+    };
+    use crate::icmp_any::IcmpErrorMessageError;
+    use crate::icmp4::{destination_unreachable, error_message, packet_too_big, time_exceeded};
+    use crate::ipv4::Ipv4;
+    use crate::ipv4::addr::UnicastIpv4Addr;
+    use crate::packet::{Packet, PacketBuilder};
+    use crate::udp::{TruncatedUdp, Udp, UdpPort};
+    use etherparse::Icmpv4Type;
+    use etherparse::icmpv4::{DestUnreachableHeader, TimeExceededCode};
+    use std::net::Ipv4Addr;
+
+    const ROUTER: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+    fn offending(destination: Ipv4Addr, payload_len: usize) -> Packet<TestBuffer> {
+        let mut ip = Ipv4::default();
+        ip.set_source(UnicastIpv4Addr::new(Ipv4Addr::new(1, 2, 3, 4)).unwrap())
+            .set_destination(destination)
+            .set_ttl(1);
+        PacketBuilder::new()
+            .eth(Mac([0x2, 0, 0, 0, 0, 1]), Mac([0x2, 0, 0, 0, 0, 2]))
+            .ipv4(ip)
+            .udp(Udp::new(1234.try_into().unwrap(), 53.try_into().unwrap()))
+            .payload(vec![0xaa; payload_len])
+            .build()
+            .unwrap()
+    }
+
+    fn router() -> UnicastIpv4Addr {
+        UnicastIpv4Addr::new(ROUTER).unwrap()
+    }
+
+    #[test]
+    fn time_exceeded_embeds_offending_headers() {
+        let offending = offending(Ipv4Addr::new(5, 6, 7, 8), 16);
+        let mut message = time_exceeded(
+            &offending,
+            router(),
+            TimeExceededCode::TtlExceededInTransit,
+            TestBuffer::new(),
+        )
+        .unwrap();
+
+        let Some(Net::Ipv4(ip)) = message.try_ip() else {
+            panic!("expected ipv4 header");
+        };
+        assert_eq!(ip.source().inner(), ROUTER);
+        assert_eq!(ip.destination(), Ipv4Addr::new(1, 2, 3, 4));
+        assert_eq!(
+            message.try_icmp4().unwrap().icmp_type(),
+            &Icmpv4Type::TimeExceeded(TimeExceededCode::TtlExceededInTransit)
+        );
+
+        let embedded = message.embedded_headers().unwrap();
+        let Some(Net::Ipv4(inner_ip)) = embedded.try_inner_ip() else {
+            panic!("expected embedded ipv4 header");
+        };
+        assert_eq!(inner_ip.destination(), Ipv4Addr::new(5, 6, 7, 8));
+        let Some(EmbeddedTransport::Udp(TruncatedUdp::FullHeader(udp))) =
+            embedded.try_embedded_transport()
+        else {
+            panic!("expected embedded udp header");
+        };
+        assert_eq!(udp.destination(), UdpPort::new_checked(53).unwrap());
+        assert_eq!(message.payload().as_ref(), &[0xaa; 16]);
+
+        // checksums are already valid
+        let before = message.try_icmp4().cloned();
+        message.update_checksums();
+        assert_eq!(message.try_icmp4().cloned(), before);
+    }
+
+    #[test]
+    fn error_message_length() {
+        let offending = offending(Ipv4Addr::new(5, 6, 7, 8), 1000);
+        let message = packet_too_big(&offending, router(), 1000, TestBuffer::new()).unwrap();
+        assert_eq!(
+            message.try_icmp4().unwrap().icmp_type(),
+            &Icmpv4Type::DestinationUnreachable(DestUnreachableHeader::FragmentationNeeded {
+                next_hop_mtu: 1000
+            })
+        );
+        let Some(Net::Ipv4(ip)) = message.try_ip() else {
+            panic!("expected ipv4 header");
+        };
+        assert_eq!(usize::from(ip.total_len()), super::ERROR_MESSAGE_MAX_LEN);
+    }
+
+    #[test]
+    fn suppressed_error_messages() {
+        let broadcast = offending(Ipv4Addr::BROADCAST, 0);
+        assert!(matches!(
+            destination_unreachable(
+                &broadcast,
+                router(),
+                DestUnreachableHeader::Host,
+                TestBuffer::new()
+            ),
+            Err(IcmpErrorMessageError::NonUnicastDestination)
+        ));
+
+        let offending = offending(Ipv4Addr::new(5, 6, 7, 8), 0);
+        let message = destination_unreachable(
+            &offending,
+            router(),
+            DestUnreachableHeader::Host,
+            TestBuffer::new(),
+        )
+        .unwrap();
+        assert!(matches!(
+            time_exceeded(
+                &message,
+                router(),
+                TimeExceededCode::TtlExceededInTransit,
+                TestBuffer::new()
+            ),
+            Err(IcmpErrorMessageError::ErrorMessage)
+        ));
+
+        assert!(matches!(
+            error_message(
+                &offending,
+                router(),
+                Icmpv4Type::EchoReply(etherparse::IcmpEchoHeader { id: 1, seq: 1 }),
+                TestBuffer::new()
+            ),
+            Err(IcmpErrorMessageError::NotAnError)
+        ));
+    }
+}
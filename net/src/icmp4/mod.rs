@@ -4,9 +4,11 @@
 //! `ICMPv4` header type and logic.
 
 mod checksum;
+mod error_message;
 mod truncated;
 
 pub use checksum::*;
+pub use error_message::*;
 pub use truncated::*;
 
 use crate::headers::{AbstractEmbeddedHeaders, EmbeddedHeaders, EmbeddedIpVersion};
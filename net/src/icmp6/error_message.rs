@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Generation of `ICMPv6` error messages, as per [RFC 4443].
+//!
+//! [RFC 4443]: https://datatracker.ietf.org/doc/html/rfc4443#section-2.4
+
+use crate::buffer::{Append, PacketBufferMut};
+use crate::headers::{Net, TryIp};
+use crate::icmp_any::{IcmpErrorMessageError, prepare_error_message};
+use crate::icmp6::Icmp6;
+use crate::ipv6::Ipv6;
+use crate::ipv6::addr::UnicastIpv6Addr;
+use crate::packet::Packet;
+use crate::parse::DeParse;
+use etherparse::icmpv6::{DestUnreachableCode, TimeExceededCode};
+use etherparse::{Icmpv6Header, Icmpv6Type};
+
+/// Maximum length of an `ICMPv6` error message, IP header included: the minimum IPv6 MTU
+/// ([RFC 4443]).
+///
+/// [RFC 4443]: https://datatracker.ietf.org/doc/html/rfc4443#section-2.4
+pub const ERROR_MESSAGE_MAX_LEN: usize = 1280;
+
+/// Hop limit of the generated `ICMPv6` error messages
+pub const ERROR_MESSAGE_HOP_LIMIT: u8 = 64;
+
+/// Build an `ICMPv6` Destination Unreachable message in `buffer`, in response to `offending`.
+///
+/// The message is sent from `source` to the source of `offending`.
+///
+/// # Errors
+///
+/// Returns an [`IcmpErrorMessageError`] if no error message should be sent in response to
+/// `offending` (see [`error_message`]), or if the message can't be built in `buffer`.
+pub fn destination_unreachable<Buf: PacketBufferMut, Out: PacketBufferMut + Append>(
+    offending: &Packet<Buf>,
+    source: UnicastIpv6Addr,
+    code: DestUnreachableCode,
+    buffer: Out,
+) -> Result<Packet<Out>, IcmpErrorMessageError> {
+    error_message(
+        offending,
+        source,
+        Icmpv6Type::DestinationUnreachable(code),
+        buffer,
+    )
+}
+
+/// Build an `ICMPv6` Time Exceeded message in `buffer`, in response to `offending`.
+///
+/// The message is sent from `source` to the source of `offending`.
+///
+/// # Errors
+///
+/// Returns an [`IcmpErrorMessageError`] if no error message should be sent in response to
+/// `offending` (see [`error_message`]), or if the message can't be built in `buffer`.
+pub fn time_exceeded<Buf: PacketBufferMut, Out: PacketBufferMut + Append>(
+    offending: &Packet<Buf>,
+    source: UnicastIpv6Addr,
+    code: TimeExceededCode,
+    buffer: Out,
+) -> Result<Packet<Out>, IcmpErrorMessageError> {
+    error_message(offending, source, Icmpv6Type::TimeExceeded(code), buffer)
+}
+
+/// Build an `ICMPv6` Packet Too Big message in `buffer`, in response to `offending`, which is
+/// larger than the `mtu` of the next hop.
+///
+/// The message is sent from `source` to the source of `offending`.
+///
+/// # Errors
+///
+/// Returns an [`IcmpErrorMessageError`] if no error message should be sent in response to
+/// `offending` (see [`error_message`]), or if the message can't be built in `buffer`.
+pub fn packet_too_big<Buf: PacketBufferMut, Out: PacketBufferMut + Append>(
+    offending: &Packet<Buf>,
+    source: UnicastIpv6Addr,
+    mtu: u32,
+    buffer: Out,
+) -> Result<Packet<Out>, IcmpErrorMessageError> {
+    error_message(offending, source, Icmpv6Type::PacketTooBig { mtu }, buffer)
+}
+
+/// Build an `ICMPv6` error message of type `icmp_type` in `buffer`, in response to `offending`.
+///
+/// The message embeds as much of the IP datagram of `offending` as fits in
+/// [`ERROR_MESSAGE_MAX_LEN`] bytes.
+///
+/// # Errors
+///
+/// Returns an [`IcmpErrorMessageError`] if `icmp_type` is not an error type, or if no error
+/// message should be sent in response to `offending`, that is, if `offending`:
+///
+/// * is not an IPv6 packet,
+/// * is itself an ICMP error message,
+/// * was sent to a multicast address, at the link or network layer.
+///
+/// Note that RFC 4443 allows Packet Too Big and some Parameter Problem messages in response to
+/// multicast packets: we don't send them either.
+///
+/// Also returns an error if the message can't be built in `buffer`.
+pub fn error_message<Buf: PacketBufferMut, Out: PacketBufferMut + Append>(
+    offending: &Packet<Buf>,
+    source: UnicastIpv6Addr,
+    icmp_type: Icmpv6Type,
+    buffer: Out,
+) -> Result<Packet<Out>, IcmpErrorMessageError> {
+    let icmp = Icmp6(Icmpv6Header::new(icmp_type));
+    if !icmp.is_error_message() {
+        return Err(IcmpErrorMessageError::NotAnError);
+    }
+    let Some(Net::Ipv6(offending_ip)) = offending.try_ip() else {
+        return Err(IcmpErrorMessageError::NoIp);
+    };
+    if offending_ip.destination().is_multicast() {
+        return Err(IcmpErrorMessageError::NonUnicastDestination);
+    }
+
+    let mut ip = Ipv6::default();
+    ip.set_source(source)
+        .set_destination(offending_ip.source().inner())
+        .set_hop_limit(ERROR_MESSAGE_HOP_LIMIT);
+
+    let max_len = ERROR_MESSAGE_MAX_LEN - usize::from(ip.size().get() + icmp.size().get());
+    let (builder, datagram) = prepare_error_message(offending, max_len)?;
+    Ok(builder
+        .ipv6(ip)
+        .icmp6(icmp)
+        .payload(datagram)
+        .build_in(buffer)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::eth::mac::Mac;
+    use crate::headers::{Net, TryEmbeddedHeaders, TryIcmp6, TryInnerIp, TryIp};
+    use crate::icmp_any::IcmpErrorMessageError;
+    use crate::icmp6::{destination_unreachable, packet_too_big, time_exceeded};
+    use crate::ipv6::Ipv6;
+    use crate::ipv6::addr::UnicastIpv6Addr;
+    use crate::packet::{Packet, PacketBuilder};
+    use crate::udp::Udp;
+    use etherparse::Icmpv6Type;
+    use etherparse::icmpv6::{DestUnreachableCode, TimeExceededCode};
+    use std::net::Ipv6Addr;
+
+    const ROUTER: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    const HOST: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+
+    fn offending(destination: Ipv6Addr, payload_len: usize) -> Packet<TestBuffer> {
+        let mut ip = Ipv6::default();
+        ip.set_source(UnicastIpv6Addr::new(HOST).unwrap())
+            .set_destination(destination)
+            .set_hop_limit(1);
+        PacketBuilder::new()
+            .eth(Mac([0x2, 0, 0, 0, 0, 1]), Mac([0x2, 0, 0, 0, 0, 2]))
+            .ipv6(ip)
+            .udp(Udp::new(1234.try_into().unwrap(), 53.try_into().unwrap()))
+            .payload(vec![0xaa; payload_len])
+            .build()
+            .unwrap()
+    }
+
+    fn router() -> UnicastIpv6Addr {
+        UnicastIpv6Addr::new(ROUTER).unwrap()
+    }
+
+    #[test]
+    fn time_exceeded_embeds_offending_headers() {
+        let destination = Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 1);
+        let offending = offending(destination, 16);
+        let mut message = time_exceeded(
+            &offending,
+            router(),
+            TimeExceededCode::HopLimitExceeded,
+            TestBuffer::new(),
+        )
+        .unwrap();
+
+        let Some(Net::Ipv6(ip)) = message.try_ip() else {
+            panic!("expected ipv6 header");
+        };
+        assert_eq!(ip.source().inner(), ROUTER);
+        assert_eq!(ip.destination(), HOST);
+        assert_eq!(
+            message.try_icmp6().unwrap().icmp_type(),
+            &Icmpv6Type::TimeExceeded(TimeExceededCode::HopLimitExceeded)
+        );
+        let Some(Net::Ipv6(inner_ip)) = message.embedded_headers().unwrap().try_inner_ip() else {
+            panic!("expected embedded ipv6 header");
+        };
+        assert_eq!(inner_ip.destination(), destination);
+
+        // checksums are already valid
+        let before = message.try_icmp6().cloned();
+        message.update_checksums();
+        assert_eq!(message.try_icmp6().cloned(), before);
+    }
+
+    #[test]
+    fn error_message_length() {
+        let offending = offending(Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 1), 1400);
+        let message = packet_too_big(&offending, router(), 1280, TestBuffer::new()).unwrap();
+        assert_eq!(
+            message.try_icmp6().unwrap().icmp_type(),
+            &Icmpv6Type::PacketTooBig { mtu: 1280 }
+        );
+        assert_eq!(
+            usize::from(message.total_len()),
+            super::ERROR_MESSAGE_MAX_LEN + usize::from(crate::eth::Eth::HEADER_LEN.get())
+        );
+    }
+
+    #[test]
+    fn suppressed_error_messages() {
+        let multicast = offending(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1), 0);
+        assert!(matches!(
+            destination_unreachable(
+                &multicast,
+                router(),
+                DestUnreachableCode::NoRoute,
+                TestBuffer::new()
+            ),
+            Err(IcmpErrorMessageError::NonUnicastDestination)
+        ));
+
+        let offending = offending(Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 1), 0);
+        let message = destination_unreachable(
+            &offending,
+            router(),
+            DestUnreachableCode::NoRoute,
+            TestBuffer::new(),
+        )
+        .unwrap();
+        assert!(matches!(
+            time_exceeded(
+                &message,
+                router(),
+                TimeExceededCode::HopLimitExceeded,
+                TestBuffer::new()
+            ),
+            Err(IcmpErrorMessageError::ErrorMessage)
+        ));
+    }
+}
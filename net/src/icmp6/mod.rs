@@ -4,9 +4,11 @@
 //! `Icmp6` header type and logic.
 
 mod checksum;
+mod error_message;
 mod truncated;
 
 pub use checksum::*;
+pub use error_message::*;
 pub use truncated::*;
 
 use crate::headers::{AbstractEmbeddedHeaders, EmbeddedHeaders, EmbeddedIpVersion};
@@ -45,6 +47,25 @@ impl Icmp6 {
         &mut self.0.icmp_type
     }
 
+    /// Returns true if the message is a neighbor solicitation
+    #[must_use]
+    pub fn is_neighbor_solicitation(&self) -> bool {
+        matches!(self.icmp_type(), Icmpv6Type::NeighborSolicitation)
+    }
+
+    /// Returns a neighbor advertisement header (RFC 4861, 4.4) with the given flags. The target
+    /// address and the options of the message are carried in the payload.
+    #[must_use]
+    pub fn neighbor_advertisement(router: bool, solicited: bool, r#override: bool) -> Self {
+        Icmp6(Icmpv6Header::new(Icmpv6Type::NeighborAdvertisement(
+            etherparse::icmpv6::NeighborAdvertisementHeader {
+                router,
+                solicited,
+                r#override,
+            },
+        )))
+    }
+
     /// Returns true if the ICMP type is a query message
     #[must_use]
     pub fn is_query_message(&self) -> bool {
@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Common logic to generate ICMP error messages in response to an offending packet.
+
+use crate::buffer::{PacketBufferMut, Segments};
+use crate::icmp_any::IcmpAny;
+use crate::packet::{Packet, PacketBuilder, PacketBuilderError};
+use crate::parse::DeParse;
+use crate::vlan::VlanTagKind;
+
+/// Reasons for which an ICMP error message is not generated in response to a packet.
+#[derive(Debug, thiserror::Error)]
+pub enum IcmpErrorMessageError {
+    /// The requested ICMP type is not an error message type.
+    #[error("icmp type is not an error message type")]
+    NotAnError,
+    /// The offending packet has no Ethernet header.
+    #[error("offending packet has no ethernet header")]
+    NoEth,
+    /// The offending packet has no IP header of the expected version.
+    #[error("offending packet has no matching ip header")]
+    NoIp,
+    /// The offending packet is itself an ICMP error message.
+    #[error("offending packet is an icmp error message")]
+    ErrorMessage,
+    /// The offending packet is a non-first fragment.
+    #[error("offending packet is a non-first fragment")]
+    NonFirstFragment,
+    /// The offending packet was sent to a broadcast or multicast address.
+    #[error("offending packet has a non-unicast destination")]
+    NonUnicastDestination,
+    /// The error message could not be built.
+    #[error(transparent)]
+    Build(#[from] PacketBuilderError),
+}
+
+/// Prepare a [`PacketBuilder`] for an ICMP error message in response to `offending`, with the
+/// link layer of the reply to `offending`.
+///
+/// Also returns the datagram to embed in the message: the IP header of `offending` and whatever
+/// follows, truncated to `max_len` bytes.
+/// Note that the datagram is built from the current headers of `offending`, so it reflects any
+/// change applied to them.
+pub(crate) fn prepare_error_message<Buf: PacketBufferMut>(
+    offending: &Packet<Buf>,
+    max_len: usize,
+) -> Result<(PacketBuilder, Vec<u8>), IcmpErrorMessageError> {
+    let headers = offending.get_headers();
+    let eth = headers.eth.as_ref().ok_or(IcmpErrorMessageError::NoEth)?;
+    let destination = eth.destination().inner();
+    if destination.is_broadcast() || destination.is_multicast() {
+        return Err(IcmpErrorMessageError::NonUnicastDestination);
+    }
+    if let Some(transport) = &headers.transport
+        && IcmpAny::try_from(transport).is_ok_and(|icmp| icmp.is_error_message())
+    {
+        return Err(IcmpErrorMessageError::ErrorMessage);
+    }
+
+    let mut builder = PacketBuilder::new().eth(destination, eth.source().inner());
+    for (index, vlan) in headers.vlan.iter().enumerate() {
+        builder = match headers.vlan_kind(index) {
+            Some(VlanTagKind::Service) => builder.s_vlan(vlan.vid()),
+            _ => builder.vlan(vlan.vid()),
+        };
+    }
+
+    let mut ip_headers = headers.clone();
+    ip_headers.eth = None;
+    ip_headers.vlan.clear();
+    if ip_headers.net.is_none() {
+        return Err(IcmpErrorMessageError::NoIp);
+    }
+    let header_len = usize::from(ip_headers.size().get());
+    let mut datagram = vec![0; header_len];
+    ip_headers
+        .deparse(&mut datagram)
+        .unwrap_or_else(|e| unreachable!("{e:?}"));
+    for segment in offending.payload().segments() {
+        if datagram.len() >= max_len {
+            break;
+        }
+        datagram.extend_from_slice(segment);
+    }
+    datagram.truncate(max_len);
+
+    Ok((builder, datagram))
+}
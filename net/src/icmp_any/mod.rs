@@ -8,9 +8,12 @@ use crate::icmp4::Icmp4;
 use crate::icmp6::Icmp6;
 
 mod checksum;
+mod error_message;
 mod truncated;
 
 pub use checksum::*;
+pub use error_message::IcmpErrorMessageError;
+pub(crate) use error_message::prepare_error_message;
 pub use truncated::*;
 
 /// Error type for [`IcmpAny`] and [`IcmpAnyMut`]
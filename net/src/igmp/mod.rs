@@ -0,0 +1,499 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [IGMPv2][RFC2236] and [IGMPv3][RFC3376] message types and parsing.
+//!
+//! IGMP messages are not part of the [`Headers`] of a packet: they are parsed on demand from the
+//! payload of IPv4 packets (see [`Packet::igmp`]).
+//!
+//! [`Headers`]: crate::headers::Headers
+//! [RFC2236]: https://datatracker.ietf.org/doc/html/rfc2236#section-2
+//! [RFC3376]: https://datatracker.ietf.org/doc/html/rfc3376#section-4
+
+mod record;
+
+use crate::buffer::PacketBufferMut;
+use crate::headers::{TryIpv4, TryTransport};
+use crate::packet::Packet;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use core::num::NonZero;
+use etherparse::IpNumber;
+pub(crate) use record::{GroupAddress, decode_exponential};
+pub use record::{GroupRecord, GroupRecordError, GroupRecordType, SourceQuery};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use tracing::debug;
+
+/// An IGMP membership query.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IgmpQuery {
+    max_resp_code: u8,
+    group: Ipv4Addr,
+    v3: Option<SourceQuery<Ipv4Addr>>,
+}
+
+/// The version of an [`IgmpQuery`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IgmpVersion {
+    /// IGMPv1 query (8 bytes, no maximum response time)
+    V1,
+    /// IGMPv2 query (8 bytes)
+    V2,
+    /// IGMPv3 query (12 bytes or more)
+    V3,
+}
+
+/// An IGMP message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Igmp {
+    /// Membership query, of any version
+    Query(IgmpQuery),
+    /// IGMPv1 membership report, for the given group
+    V1Report(Ipv4Addr),
+    /// IGMPv2 membership report, for the given group
+    V2Report(Ipv4Addr),
+    /// IGMPv2 leave group message, for the given group
+    Leave(Ipv4Addr),
+    /// IGMPv3 membership report
+    V3Report(Vec<GroupRecord<Ipv4Addr>>),
+}
+
+/// Errors which may occur when parsing an [`Igmp`] message.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IgmpError {
+    /// Unknown message type.
+    #[error("invalid IGMP message type {0:#04x}")]
+    InvalidType(u8),
+    /// The length of the message is invalid for its type.
+    #[error("invalid IGMP message length {0}")]
+    InvalidLength(usize),
+    /// The checksum of the message is invalid.
+    #[error("invalid IGMP checksum")]
+    InvalidChecksum,
+    /// A group record of a report is invalid.
+    #[error(transparent)]
+    Record(#[from] GroupRecordError),
+}
+
+impl IgmpQuery {
+    /// Create a new query, of version 2 if `v3` is `None`, or of version 3 otherwise.
+    ///
+    /// `group` is the unspecified address for general queries.
+    #[must_use]
+    pub fn new(max_resp_code: u8, group: Ipv4Addr, v3: Option<SourceQuery<Ipv4Addr>>) -> IgmpQuery {
+        IgmpQuery {
+            max_resp_code,
+            group,
+            v3,
+        }
+    }
+
+    /// Get the version of this query.
+    #[must_use]
+    pub const fn version(&self) -> IgmpVersion {
+        match (&self.v3, self.max_resp_code) {
+            (Some(_), _) => IgmpVersion::V3,
+            (None, 0) => IgmpVersion::V1,
+            (None, _) => IgmpVersion::V2,
+        }
+    }
+
+    /// Get the maximum response code of this query.
+    #[must_use]
+    pub const fn max_resp_code(&self) -> u8 {
+        self.max_resp_code
+    }
+
+    /// Get the maximum time allowed before sending a report, decoded from the maximum response
+    /// code.
+    #[must_use]
+    pub fn max_resp_time(&self) -> Duration {
+        let tenths = match self.v3 {
+            Some(_) => decode_exponential(self.max_resp_code.into(), 8),
+            None => u32::from(self.max_resp_code),
+        };
+        Duration::from_millis(u64::from(tenths) * 100)
+    }
+
+    /// Get the group of a group-specific query, or the unspecified address for a general query.
+    #[must_use]
+    pub const fn group(&self) -> Ipv4Addr {
+        self.group
+    }
+
+    /// Tell if this is a general query (for all groups).
+    #[must_use]
+    pub fn is_general(&self) -> bool {
+        self.group.is_unspecified()
+    }
+
+    /// Get the fields specific to IGMPv3 queries, if this is an IGMPv3 query.
+    #[must_use]
+    pub const fn v3(&self) -> Option<&SourceQuery<Ipv4Addr>> {
+        self.v3.as_ref()
+    }
+}
+
+impl Igmp {
+    /// Type of membership queries
+    pub const TYPE_QUERY: u8 = 0x11;
+    /// Type of IGMPv1 membership reports
+    pub const TYPE_V1_REPORT: u8 = 0x12;
+    /// Type of IGMPv2 membership reports
+    pub const TYPE_V2_REPORT: u8 = 0x16;
+    /// Type of IGMPv2 leave group messages
+    pub const TYPE_LEAVE: u8 = 0x17;
+    /// Type of IGMPv3 membership reports
+    pub const TYPE_V3_REPORT: u8 = 0x22;
+
+    /// The length of the shortest [`Igmp`] messages.
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(8).unwrap();
+
+    /// Length of IGMPv3 queries without sources
+    const V3_QUERY_MIN_LENGTH: usize = 12;
+
+    /// Get the type of this message.
+    #[must_use]
+    pub const fn message_type(&self) -> u8 {
+        match self {
+            Igmp::Query(_) => Igmp::TYPE_QUERY,
+            Igmp::V1Report(_) => Igmp::TYPE_V1_REPORT,
+            Igmp::V2Report(_) => Igmp::TYPE_V2_REPORT,
+            Igmp::Leave(_) => Igmp::TYPE_LEAVE,
+            Igmp::V3Report(_) => Igmp::TYPE_V3_REPORT,
+        }
+    }
+
+    /// Length of the message on the wire, in bytes
+    fn len(&self) -> usize {
+        let min_len = Igmp::MIN_LENGTH.into_non_zero_usize().get();
+        match self {
+            Igmp::Query(query) => min_len + query.v3.as_ref().map_or(0, SourceQuery::len),
+            Igmp::V1Report(_) | Igmp::V2Report(_) | Igmp::Leave(_) => min_len,
+            Igmp::V3Report(records) => {
+                min_len + records.iter().map(GroupRecord::len).sum::<usize>()
+            }
+        }
+    }
+
+    /// Returns true if the message can be written.
+    fn is_valid(&self) -> bool {
+        let valid = match self {
+            Igmp::Query(query) => query
+                .v3
+                .as_ref()
+                .is_none_or(|v3| v3.sources().len() <= usize::from(u16::MAX)),
+            Igmp::V3Report(records) => {
+                records.len() <= usize::from(u16::MAX) && records.iter().all(GroupRecord::is_valid)
+            }
+            Igmp::V1Report(_) | Igmp::V2Report(_) | Igmp::Leave(_) => true,
+        };
+        valid && self.len() <= usize::from(u16::MAX)
+    }
+}
+
+/// Compute the internet checksum of `buf` ([RFC1071]).
+///
+/// [RFC1071]: https://datatracker.ietf.org/doc/html/rfc1071
+fn checksum(buf: &[u8]) -> u16 {
+    let mut sum: u32 = buf
+        .chunks(2)
+        .map(|chunk| {
+            u32::from(u16::from_be_bytes([
+                chunk[0],
+                chunk.get(1).copied().unwrap_or(0),
+            ]))
+        })
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    #[allow(clippy::cast_possible_truncation)] // folded above
+    let sum = sum as u16;
+    !sum
+}
+
+impl Parse for Igmp {
+    type Error = IgmpError;
+
+    /// Parse an IGMP message filling `buf`.
+    ///
+    /// The version of queries is determined by their length, so `buf` must not include any
+    /// padding following the message.
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() > u16::MAX as usize {
+            return Err(ParseError::BufferTooLong(buf.len()));
+        }
+        let min_len = Igmp::MIN_LENGTH.into_non_zero_usize().get();
+        if buf.len() < min_len {
+            return Err(ParseError::Length(LengthError {
+                expected: Igmp::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        if checksum(buf) != 0 {
+            debug!("IGMP message with invalid checksum");
+            return Err(ParseError::Invalid(IgmpError::InvalidChecksum));
+        }
+        let group = Ipv4Addr::new(buf[4], buf[5], buf[6], buf[7]);
+        let fixed_len = |igmp: Igmp| {
+            if buf.len() == min_len {
+                Ok(igmp)
+            } else {
+                Err(IgmpError::InvalidLength(buf.len()))
+            }
+        };
+        let igmp = match buf[0] {
+            Igmp::TYPE_QUERY => {
+                let v3 = match buf.len() {
+                    8 => None,
+                    len if len >= Igmp::V3_QUERY_MIN_LENGTH => Some(
+                        SourceQuery::parse(&buf[min_len..])
+                            .ok_or(ParseError::Invalid(IgmpError::InvalidLength(len)))?,
+                    ),
+                    len => return Err(ParseError::Invalid(IgmpError::InvalidLength(len))),
+                };
+                Ok(Igmp::Query(IgmpQuery {
+                    max_resp_code: buf[1],
+                    group,
+                    v3,
+                }))
+            }
+            Igmp::TYPE_V1_REPORT => fixed_len(Igmp::V1Report(group)),
+            Igmp::TYPE_V2_REPORT => fixed_len(Igmp::V2Report(group)),
+            Igmp::TYPE_LEAVE => fixed_len(Igmp::Leave(group)),
+            Igmp::TYPE_V3_REPORT => {
+                let count = usize::from(u16::from_be_bytes([buf[6], buf[7]]));
+                let mut records = Vec::with_capacity(count.min(buf.len() / 8));
+                let mut rest = &buf[min_len..];
+                for _ in 0..count {
+                    let (record, len) =
+                        GroupRecord::parse(rest).map_err(|e| ParseError::Invalid(e.into()))?;
+                    records.push(record);
+                    rest = &rest[len..];
+                }
+                if rest.is_empty() {
+                    Ok(Igmp::V3Report(records))
+                } else {
+                    Err(IgmpError::InvalidLength(buf.len()))
+                }
+            }
+            other => Err(IgmpError::InvalidType(other)),
+        }
+        .map_err(ParseError::Invalid)?;
+        #[allow(clippy::cast_possible_truncation)] // buffer length checked above
+        let consumed = NonZero::new(buf.len() as u16).unwrap_or_else(|| unreachable!());
+        Ok((igmp, consumed))
+    }
+}
+
+impl DeParse for Igmp {
+    type Error = ();
+
+    /// The size of the message, or [`u16::MAX`] if it is too long to be written.
+    fn size(&self) -> NonZero<u16> {
+        let len = u16::try_from(self.len()).unwrap_or(u16::MAX);
+        NonZero::new(len).unwrap_or_else(|| unreachable!())
+    }
+
+    /// Write the message to `buf`, with its checksum.
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        if !self.is_valid() {
+            return Err(DeParseError::Invalid(()));
+        }
+        let size = self.size();
+        let len = size.into_non_zero_usize().get();
+        if buf.len() < len {
+            return Err(DeParseError::Length(LengthError {
+                expected: size.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        buf[0] = self.message_type();
+        buf[1..4].fill(0);
+        let min_len = Igmp::MIN_LENGTH.into_non_zero_usize().get();
+        match self {
+            Igmp::Query(query) => {
+                buf[1] = query.max_resp_code;
+                buf[4..8].copy_from_slice(&query.group.octets());
+                if let Some(v3) = &query.v3 {
+                    v3.write(&mut buf[min_len..]);
+                }
+            }
+            Igmp::V1Report(group) | Igmp::V2Report(group) | Igmp::Leave(group) => {
+                buf[4..8].copy_from_slice(&group.octets());
+            }
+            Igmp::V3Report(records) => {
+                buf[4..6].fill(0);
+                #[allow(clippy::cast_possible_truncation)] // checked by is_valid
+                let count = records.len() as u16;
+                buf[6..8].copy_from_slice(&count.to_be_bytes());
+                let mut offset = min_len;
+                for record in records {
+                    record.write(&mut buf[offset..]);
+                    offset += record.len();
+                }
+            }
+        }
+        let checksum = checksum(&buf[..len]);
+        buf[2..4].copy_from_slice(&checksum.to_be_bytes());
+        Ok(size)
+    }
+}
+
+impl<Buf: PacketBufferMut> Packet<Buf> {
+    /// Parse the payload of this packet as an [`Igmp`] message, if the packet is an IPv4 packet
+    /// with the IGMP protocol.
+    ///
+    /// Returns `None` if the packet is not an IGMP packet, or if the message fails to parse.
+    #[must_use]
+    pub fn igmp(&self) -> Option<Igmp> {
+        let ipv4 = self.try_ipv4()?;
+        if ipv4.protocol() != IpNumber::IGMP || self.try_transport().is_some() {
+            return None;
+        }
+        // ignore the padding of short ethernet frames
+        let len = usize::from(ipv4.total_len()).checked_sub(ipv4.header_len())?;
+        let payload = self.payload().as_ref().get(..len)?;
+        Igmp::parse(payload)
+            .map_err(|e| debug!("failed to parse IGMP message: {e:?}"))
+            .map(|(igmp, _)| igmp)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::igmp::{
+        GroupRecord, GroupRecordError, GroupRecordType, Igmp, IgmpError, IgmpQuery, IgmpVersion,
+        SourceQuery, checksum,
+    };
+    use crate::parse::{DeParse, IntoNonZeroUSize, Parse, ParseError};
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    const GROUP: Ipv4Addr = Ipv4Addr::new(239, 1, 2, 3);
+    const SOURCE: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+    fn set_checksum(buf: &mut [u8]) {
+        buf[2..4].fill(0);
+        let checksum = checksum(buf);
+        buf[2..4].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    fn write(igmp: &Igmp) -> Vec<u8> {
+        let mut buf = vec![0u8; igmp.size().into_non_zero_usize().get()];
+        assert_eq!(igmp.deparse(&mut buf).unwrap(), igmp.size());
+        buf
+    }
+
+    #[test]
+    fn v2_messages() {
+        // general query with 10 seconds max response time, from a capture
+        let query = [0x11, 0x64, 0xee, 0x9b, 0, 0, 0, 0];
+        let (igmp, _) = Igmp::parse(&query).unwrap();
+        let Igmp::Query(parsed) = &igmp else {
+            unreachable!()
+        };
+        assert_eq!(parsed.version(), IgmpVersion::V2);
+        assert!(parsed.is_general());
+        assert_eq!(parsed.max_resp_time(), Duration::from_secs(10));
+        assert_eq!(write(&igmp), query);
+
+        for igmp in [
+            Igmp::V2Report(GROUP),
+            Igmp::Leave(GROUP),
+            Igmp::V1Report(GROUP),
+        ] {
+            let buf = write(&igmp);
+            assert_eq!(buf.len(), 8);
+            assert_eq!(Igmp::parse(&buf).unwrap().0, igmp);
+        }
+    }
+
+    #[test]
+    fn v3_messages() {
+        let query = Igmp::Query(IgmpQuery::new(
+            0x8a,
+            GROUP,
+            Some(SourceQuery::new(false, 2, 125, vec![SOURCE])),
+        ));
+        let buf = write(&query);
+        assert_eq!(buf.len(), 16);
+        let (parsed, _) = Igmp::parse(&buf).unwrap();
+        assert_eq!(parsed, query);
+        let Igmp::Query(parsed) = parsed else {
+            unreachable!()
+        };
+        assert_eq!(parsed.version(), IgmpVersion::V3);
+        // mantissa 0xa, exponent 0: (0x10 | 0xa) << 3 = 208 tenths of second
+        assert_eq!(parsed.max_resp_time(), Duration::from_millis(20_800));
+        assert_eq!(parsed.v3().unwrap().query_interval(), 125);
+
+        let report = Igmp::V3Report(vec![
+            GroupRecord::new(GroupRecordType::ChangeToExclude, GROUP, vec![]),
+            GroupRecord::new(GroupRecordType::ModeIsInclude, GROUP, vec![SOURCE, SOURCE]),
+            GroupRecord::new(GroupRecordType::ChangeToInclude, GROUP, vec![]),
+        ]);
+        let buf = write(&report);
+        assert_eq!(buf.len(), 8 + 8 + 16 + 8);
+        let (parsed, _) = Igmp::parse(&buf).unwrap();
+        assert_eq!(parsed, report);
+        let Igmp::V3Report(records) = parsed else {
+            unreachable!()
+        };
+        let joins: Vec<_> = records.iter().map(GroupRecord::is_join).collect();
+        assert_eq!(joins, [true, true, false]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut buf = write(&Igmp::V2Report(GROUP));
+        assert!(matches!(Igmp::parse(&buf[..7]), Err(ParseError::Length(_))));
+        buf[7] ^= 1;
+        assert!(matches!(
+            Igmp::parse(&buf),
+            Err(ParseError::Invalid(IgmpError::InvalidChecksum))
+        ));
+
+        let report = Igmp::V3Report(vec![GroupRecord::new(
+            GroupRecordType::AllowNewSources,
+            GROUP,
+            vec![SOURCE],
+        )]);
+        let mut buf = write(&report);
+        buf[8] = 7;
+        set_checksum(&mut buf);
+        assert!(matches!(
+            Igmp::parse(&buf),
+            Err(ParseError::Invalid(IgmpError::Record(
+                GroupRecordError::InvalidRecordType(7)
+            )))
+        ));
+        buf[8] = GroupRecordType::AllowNewSources as u8;
+        buf[11] = 2;
+        set_checksum(&mut buf);
+        assert!(matches!(
+            Igmp::parse(&buf),
+            Err(ParseError::Invalid(IgmpError::Record(
+                GroupRecordError::Truncated
+            )))
+        ));
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!().with_type().for_each(|slice: &[u8; 32]| {
+            let mut buf = *slice;
+            set_checksum(&mut buf);
+            let Ok((parsed, consumed)) = Igmp::parse(&buf) else {
+                return;
+            };
+            assert_eq!(consumed.into_non_zero_usize().get(), buf.len());
+            // reserved fields are not preserved
+            let (reparsed, _) = Igmp::parse(&write(&parsed)).unwrap();
+            assert_eq!(reparsed, parsed);
+        });
+    }
+}
@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Group records and source-specific query fields, common to [IGMPv3][RFC3376] and
+//! [MLDv2][RFC3810].
+//!
+//! [RFC3376]: https://datatracker.ietf.org/doc/html/rfc3376#section-4.2.4
+//! [RFC3810]: https://datatracker.ietf.org/doc/html/rfc3810#section-5.2.4
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Multicast addresses which may be carried in group records and queries.
+pub(crate) trait GroupAddress: Copy {
+    /// Length of the address on the wire, in bytes
+    const LEN: usize;
+    /// Read an address from the first [`GroupAddress::LEN`] bytes of `buf`.
+    fn read(buf: &[u8]) -> Self;
+    /// Write the address to the first [`GroupAddress::LEN`] bytes of `buf`.
+    fn write(&self, buf: &mut [u8]);
+}
+
+impl GroupAddress for Ipv4Addr {
+    const LEN: usize = 4;
+
+    fn read(buf: &[u8]) -> Self {
+        Ipv4Addr::new(buf[0], buf[1], buf[2], buf[3])
+    }
+
+    fn write(&self, buf: &mut [u8]) {
+        buf[..4].copy_from_slice(&self.octets());
+    }
+}
+
+impl GroupAddress for Ipv6Addr {
+    const LEN: usize = 16;
+
+    fn read(buf: &[u8]) -> Self {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&buf[..16]);
+        Ipv6Addr::from(octets)
+    }
+
+    fn write(&self, buf: &mut [u8]) {
+        buf[..16].copy_from_slice(&self.octets());
+    }
+}
+
+/// Read a list of `count` addresses from `buf`, returning `None` if `buf` is too short.
+pub(crate) fn read_sources<A: GroupAddress>(buf: &[u8], count: usize) -> Option<Vec<A>> {
+    let sources = buf.get(..count * A::LEN)?;
+    Some(sources.chunks_exact(A::LEN).map(A::read).collect())
+}
+
+/// Write `sources` to `buf`, and return the number of bytes written.
+pub(crate) fn write_sources<A: GroupAddress>(buf: &mut [u8], sources: &[A]) -> usize {
+    for (chunk, source) in buf.chunks_exact_mut(A::LEN).zip(sources) {
+        source.write(chunk);
+    }
+    sources.len() * A::LEN
+}
+
+/// Errors which may occur when parsing a [`GroupRecord`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum GroupRecordError {
+    /// The record overflows the message.
+    #[error("truncated group record")]
+    Truncated,
+    /// Unknown record type.
+    #[error("invalid group record type {0}")]
+    InvalidRecordType(u8),
+}
+
+/// The type of a [`GroupRecord`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum GroupRecordType {
+    /// Current state: the sender listens to the sources of the record only
+    ModeIsInclude = 1,
+    /// Current state: the sender listens to all sources except those of the record
+    ModeIsExclude = 2,
+    /// Filter mode change: the sender now listens to the sources of the record only
+    ChangeToInclude = 3,
+    /// Filter mode change: the sender now listens to all sources except those of the record
+    ChangeToExclude = 4,
+    /// Source list change: the sender now also listens to the sources of the record
+    AllowNewSources = 5,
+    /// Source list change: the sender no longer listens to the sources of the record
+    BlockOldSources = 6,
+}
+
+impl TryFrom<u8> for GroupRecordType {
+    type Error = GroupRecordError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => GroupRecordType::ModeIsInclude,
+            2 => GroupRecordType::ModeIsExclude,
+            3 => GroupRecordType::ChangeToInclude,
+            4 => GroupRecordType::ChangeToExclude,
+            5 => GroupRecordType::AllowNewSources,
+            6 => GroupRecordType::BlockOldSources,
+            _ => return Err(GroupRecordError::InvalidRecordType(value)),
+        })
+    }
+}
+
+/// A group record of an IGMPv3 or MLDv2 report, describing the membership of the sender to one
+/// multicast group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GroupRecord<A> {
+    record_type: GroupRecordType,
+    group: A,
+    sources: Vec<A>,
+    aux_data: Vec<u8>,
+}
+
+impl<A> GroupRecord<A> {
+    /// Create a new group record, without auxiliary data.
+    #[must_use]
+    pub fn new(record_type: GroupRecordType, group: A, sources: Vec<A>) -> GroupRecord<A> {
+        GroupRecord {
+            record_type,
+            group,
+            sources,
+            aux_data: Vec::new(),
+        }
+    }
+
+    /// Get the type of this record.
+    #[must_use]
+    pub const fn record_type(&self) -> GroupRecordType {
+        self.record_type
+    }
+
+    /// Get the multicast group this record applies to.
+    #[must_use]
+    pub const fn group(&self) -> &A {
+        &self.group
+    }
+
+    /// Get the sources of this record.
+    #[must_use]
+    pub fn sources(&self) -> &[A] {
+        &self.sources
+    }
+
+    /// Get the auxiliary data of this record (unused by IGMPv3 and MLDv2).
+    #[must_use]
+    pub fn aux_data(&self) -> &[u8] {
+        &self.aux_data
+    }
+
+    /// Tell if, after this record, the sender listens to the group from at least some sources
+    /// (i.e., this is not a record for leaving the group).
+    ///
+    /// An empty source list in include mode means that the sender left the group, whereas an
+    /// empty source list in exclude mode means that the sender listens to all sources.
+    #[must_use]
+    pub fn is_join(&self) -> bool {
+        match self.record_type {
+            GroupRecordType::ModeIsInclude
+            | GroupRecordType::ChangeToInclude
+            | GroupRecordType::AllowNewSources => !self.sources.is_empty(),
+            GroupRecordType::ModeIsExclude | GroupRecordType::ChangeToExclude => true,
+            GroupRecordType::BlockOldSources => false,
+        }
+    }
+}
+
+impl<A: GroupAddress> GroupRecord<A> {
+    /// Length of the fixed part of a record, in bytes
+    const FIXED_LEN: usize = 4;
+
+    /// Length of the record on the wire, in bytes
+    pub(crate) fn len(&self) -> usize {
+        GroupRecord::<A>::FIXED_LEN + A::LEN * (1 + self.sources.len()) + self.aux_data.len()
+    }
+
+    /// Returns true if the record can be written: the number of sources must fit 16 bits, and the
+    /// auxiliary data must be a multiple of 4 bytes, of at most 255 words.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.sources.len() <= usize::from(u16::MAX)
+            && self.aux_data.len().is_multiple_of(4)
+            && self.aux_data.len() / 4 <= usize::from(u8::MAX)
+    }
+
+    /// Parse a record at the start of `buf`, and return it with its length.
+    pub(crate) fn parse(buf: &[u8]) -> Result<(GroupRecord<A>, usize), GroupRecordError> {
+        let [record_type, aux_len, count_hi, count_lo, rest @ ..] = buf else {
+            return Err(GroupRecordError::Truncated);
+        };
+        let record_type = GroupRecordType::try_from(*record_type)?;
+        let aux_len = usize::from(*aux_len) * 4;
+        let count = usize::from(u16::from_be_bytes([*count_hi, *count_lo]));
+        if rest.len() < A::LEN {
+            return Err(GroupRecordError::Truncated);
+        }
+        let group = A::read(rest);
+        let rest = &rest[A::LEN..];
+        let sources = read_sources(rest, count).ok_or(GroupRecordError::Truncated)?;
+        let aux_data = rest
+            .get(count * A::LEN..count * A::LEN + aux_len)
+            .ok_or(GroupRecordError::Truncated)?
+            .to_vec();
+        let record = GroupRecord {
+            record_type,
+            group,
+            sources,
+            aux_data,
+        };
+        let len = record.len();
+        Ok((record, len))
+    }
+
+    /// Write the record to `buf`, which must be at least [`GroupRecord::len`] bytes long.
+    pub(crate) fn write(&self, buf: &mut [u8]) {
+        buf[0] = self.record_type as u8;
+        #[allow(clippy::cast_possible_truncation)] // checked by is_valid
+        let aux_len = (self.aux_data.len() / 4) as u8;
+        buf[1] = aux_len;
+        #[allow(clippy::cast_possible_truncation)] // checked by is_valid
+        let count = self.sources.len() as u16;
+        buf[2..4].copy_from_slice(&count.to_be_bytes());
+        let mut offset = GroupRecord::<A>::FIXED_LEN;
+        self.group.write(&mut buf[offset..]);
+        offset += A::LEN;
+        offset += write_sources(&mut buf[offset..], &self.sources);
+        buf[offset..offset + self.aux_data.len()].copy_from_slice(&self.aux_data);
+    }
+}
+
+/// The fields of IGMPv3 and MLDv2 queries which are absent from the queries of former versions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceQuery<A> {
+    suppress_router_processing: bool,
+    robustness: u8,
+    query_interval_code: u8,
+    sources: Vec<A>,
+}
+
+impl<A> SourceQuery<A> {
+    /// Create new query fields.
+    ///
+    /// Only the 3 lowest bits of `robustness` are kept.
+    #[must_use]
+    pub fn new(
+        suppress_router_processing: bool,
+        robustness: u8,
+        query_interval_code: u8,
+        sources: Vec<A>,
+    ) -> SourceQuery<A> {
+        SourceQuery {
+            suppress_router_processing,
+            robustness: robustness & 0b111,
+            query_interval_code,
+            sources,
+        }
+    }
+
+    /// Tell if routers receiving the query must suppress their timer updates ("S" flag).
+    #[must_use]
+    pub const fn suppress_router_processing(&self) -> bool {
+        self.suppress_router_processing
+    }
+
+    /// Get the robustness variable of the querier ("QRV" field).
+    #[must_use]
+    pub const fn robustness(&self) -> u8 {
+        self.robustness
+    }
+
+    /// Get the query interval code of the querier ("QQIC" field).
+    #[must_use]
+    pub const fn query_interval_code(&self) -> u8 {
+        self.query_interval_code
+    }
+
+    /// Get the query interval of the querier, in seconds, decoded from its query interval code.
+    #[must_use]
+    pub fn query_interval(&self) -> u32 {
+        decode_exponential(self.query_interval_code.into(), 8)
+    }
+
+    /// Get the sources of a group-and-source-specific query.
+    #[must_use]
+    pub fn sources(&self) -> &[A] {
+        &self.sources
+    }
+}
+
+impl<A: GroupAddress> SourceQuery<A> {
+    /// Length of the fields on the wire, in bytes
+    pub(crate) fn len(&self) -> usize {
+        4 + A::LEN * self.sources.len()
+    }
+
+    /// Parse the fields at the start of `buf`, which must hold exactly the fields.
+    pub(crate) fn parse(buf: &[u8]) -> Option<SourceQuery<A>> {
+        let [flags, query_interval_code, count_hi, count_lo, rest @ ..] = buf else {
+            return None;
+        };
+        let count = usize::from(u16::from_be_bytes([*count_hi, *count_lo]));
+        if rest.len() != count * A::LEN {
+            return None;
+        }
+        Some(SourceQuery {
+            suppress_router_processing: flags & 0b1000 != 0,
+            robustness: flags & 0b111,
+            query_interval_code: *query_interval_code,
+            sources: read_sources(rest, count)?,
+        })
+    }
+
+    /// Write the fields to `buf`, which must be at least [`SourceQuery::len`] bytes long.
+    pub(crate) fn write(&self, buf: &mut [u8]) {
+        buf[0] = (u8::from(self.suppress_router_processing) << 3) | self.robustness;
+        buf[1] = self.query_interval_code;
+        #[allow(clippy::cast_possible_truncation)] // checked by the caller
+        let count = self.sources.len() as u16;
+        buf[2..4].copy_from_slice(&count.to_be_bytes());
+        write_sources(&mut buf[4..], &self.sources);
+    }
+}
+
+/// Decode a code (maximum response code, or query interval code) which uses a floating point
+/// representation for values above `1 << (bits - 1)`: the highest bit is set, followed by 3 bits
+/// of exponent and `bits - 4` bits of mantissa.
+pub(crate) fn decode_exponential(code: u16, bits: u32) -> u32 {
+    let threshold = 1u16 << (bits - 1);
+    if code < threshold {
+        return u32::from(code);
+    }
+    let mantissa_bits = bits - 4;
+    let mantissa = u32::from(code) & ((1 << mantissa_bits) - 1);
+    let exponent = (u32::from(code) >> mantissa_bits) & 0b111;
+    (mantissa | (1 << mantissa_bits)) << (exponent + 3)
+}
@@ -42,6 +42,24 @@ impl NextHeader {
     /// ICMP6 next header
     pub const ICMP6: NextHeader = NextHeader(IpNumber::IPV6_ICMP);
 
+    /// GRE next header
+    pub const GRE: NextHeader = NextHeader(IpNumber::GRE);
+
+    /// SCTP next header
+    pub const SCTP: NextHeader = NextHeader(IpNumber::SCTP);
+
+    /// IPsec authentication header next header
+    pub const AUTH: NextHeader = NextHeader(IpNumber::AUTHENTICATION_HEADER);
+
+    /// IPsec encapsulating security payload next header
+    pub const ESP: NextHeader = NextHeader(IpNumber::ENCAPSULATING_SECURITY_PAYLOAD);
+
+    /// IPv4 encapsulation next header
+    pub const IPV4: NextHeader = NextHeader(IpNumber::IPV4);
+
+    /// IPv6 encapsulation next header
+    pub const IPV6: NextHeader = NextHeader(IpNumber::IPV6);
+
     /// Get the inner (wrapped) `etherparse` [`IpNumber`] type
     pub(crate) fn inner(self) -> IpNumber {
         self.0
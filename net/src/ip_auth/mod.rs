@@ -3,11 +3,16 @@
 
 //! IP authentication header type and logic.
 
+use crate::esp::{Esp, Spi};
+use crate::gre::Gre;
 use crate::headers::{EmbeddedHeader, Header};
 use crate::icmp4::Icmp4;
 use crate::icmp6::Icmp6;
 use crate::impl_from_for_enum;
-use crate::parse::{Parse, ParseError, ParseHeader, Reader};
+use crate::parse::{
+    DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError, ParseHeader, Reader,
+};
+use crate::sctp::Sctp;
 use crate::tcp::{Tcp, TruncatedTcp};
 use crate::udp::{TruncatedUdp, Udp};
 use etherparse::{IpAuthHeader, IpNumber};
@@ -21,6 +26,20 @@ use tracing::{debug, trace};
 pub struct IpAuth(Box<IpAuthHeader>);
 
 impl IpAuth {
+    /// Get the security parameter index of this header.
+    ///
+    /// Returns `None` if the SPI is zero, which is reserved and must not be sent on the wire.
+    #[must_use]
+    pub fn spi(&self) -> Option<Spi> {
+        Spi::new(self.0.spi)
+    }
+
+    /// Get the sequence number of this header
+    #[must_use]
+    pub fn sequence_number(&self) -> u32 {
+        self.0.sequence_number
+    }
+
     /// Parse the payload of the IP authentication header.
     ///
     /// # Returns
@@ -37,6 +56,9 @@ impl IpAuth {
                 debug!("nested ip auth header");
                 cursor.parse_header::<IpAuth, IpAuthNext>()
             }
+            IpNumber::ENCAPSULATING_SECURITY_PAYLOAD => cursor.parse_header::<Esp, IpAuthNext>(),
+            IpNumber::GRE => cursor.parse_header::<Gre, IpAuthNext>(),
+            IpNumber::SCTP => cursor.parse_header::<Sctp, IpAuthNext>(),
             _ => {
                 trace!("unsupported protocol: {:?}", self.0.next_header);
                 None
@@ -89,12 +111,36 @@ impl Parse for IpAuth {
     }
 }
 
+impl DeParse for IpAuth {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        #[allow(clippy::cast_possible_truncation)] // header has bounded size
+        NonZero::new(self.0.header_len() as u16).unwrap_or_else(|| unreachable!())
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let len = buf.len();
+        if len < self.size().into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: self.size().into_non_zero_usize(),
+                actual: len,
+            }));
+        }
+        buf[..self.size().into_non_zero_usize().get()].copy_from_slice(&self.0.to_bytes());
+        Ok(self.size())
+    }
+}
+
 pub(crate) enum IpAuthNext {
     Tcp(Tcp),
     Udp(Udp),
     Icmp4(Icmp4),
     Icmp6(Icmp6),
     IpAuth(IpAuth),
+    Esp(Esp),
+    Gre(Gre),
+    Sctp(Sctp),
 }
 
 impl_from_for_enum![
@@ -103,7 +149,10 @@ impl_from_for_enum![
     Udp(Udp),
     Icmp4(Icmp4),
     Icmp6(Icmp6),
-    IpAuth(IpAuth)
+    IpAuth(IpAuth),
+    Esp(Esp),
+    Gre(Gre),
+    Sctp(Sctp)
 ];
 
 impl From<IpAuthNext> for Header {
@@ -114,6 +163,9 @@ impl From<IpAuthNext> for Header {
             IpAuthNext::Icmp4(x) => Header::Icmp4(x),
             IpAuthNext::Icmp6(x) => Header::Icmp6(x),
             IpAuthNext::IpAuth(x) => Header::IpAuth(x),
+            IpAuthNext::Esp(x) => Header::Esp(x),
+            IpAuthNext::Gre(x) => Header::Gre(x),
+            IpAuthNext::Sctp(x) => Header::Sctp(x),
         }
     }
 }
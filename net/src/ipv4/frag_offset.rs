@@ -40,6 +40,12 @@ impl FragOffset {
             IpFragOffset::try_new(raw).map_err(|e| IllegalFragOffset::TooBig(e.actual))?,
         ))
     }
+
+    /// Get the raw (13-bit) value of this [`FragOffset`]
+    #[must_use]
+    pub fn value(self) -> u16 {
+        self.0.value()
+    }
 }
 
 #[cfg(any(test, feature = "bolero"))]
@@ -3,6 +3,8 @@
 
 //! Ipv4 Address type and manipulation
 
+use crate::esp::Esp;
+use crate::gre::Gre;
 use crate::headers::{EmbeddedHeader, Header};
 use crate::icmp4::{Icmp4, TruncatedIcmp4};
 use crate::impl_from_for_enum;
@@ -15,6 +17,7 @@ use crate::ipv4::frag_offset::FragOffset;
 use crate::parse::{
     DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError, ParseHeader, Reader,
 };
+use crate::sctp::Sctp;
 use crate::tcp::{Tcp, TruncatedTcp};
 use crate::udp::{TruncatedUdp, Udp};
 use etherparse::{IpDscp, IpEcn, IpFragOffset, IpNumber, Ipv4Header};
@@ -152,6 +155,12 @@ impl Ipv4 {
         self.0.fragment_offset
     }
 
+    /// Returns true if this packet is a fragment of a packet, but not the first one.
+    /// The payload of such a packet does not start with a header.
+    fn is_non_first_fragment(&self) -> bool {
+        self.0.fragment_offset.value() != 0
+    }
+
     /// Return the headers "identification".
     /// See [IP fragmentation]
     ///
@@ -294,13 +303,20 @@ impl Ipv4 {
     /// # Returns
     ///
     /// * `Some(Ipv4Next)` if the payload is a supported protocol
-    /// * `None` if the payload is not a supported protocol
+    /// * `None` if the payload is not a supported protocol, or if this is a non-first fragment
     pub(crate) fn parse_payload(&self, cursor: &mut Reader) -> Option<Ipv4Next> {
+        if self.is_non_first_fragment() {
+            trace!("payload is a non-first fragment");
+            return None;
+        }
         match self.0.protocol {
             IpNumber::TCP => cursor.parse_header::<Tcp, Ipv4Next>(),
             IpNumber::UDP => cursor.parse_header::<Udp, Ipv4Next>(),
             IpNumber::ICMP => cursor.parse_header::<Icmp4, Ipv4Next>(),
             IpNumber::AUTHENTICATION_HEADER => cursor.parse_header::<IpAuth, Ipv4Next>(),
+            IpNumber::ENCAPSULATING_SECURITY_PAYLOAD => cursor.parse_header::<Esp, Ipv4Next>(),
+            IpNumber::GRE => cursor.parse_header::<Gre, Ipv4Next>(),
+            IpNumber::SCTP => cursor.parse_header::<Sctp, Ipv4Next>(),
             _ => {
                 trace!("unsupported protocol: {:?}", self.0.protocol);
                 None
@@ -313,8 +329,12 @@ impl Ipv4 {
     /// # Returns
     ///
     /// * `Some(EmbeddedIpv4Next)` if the payload is a supported protocol
-    /// * `None` if the payload is not a supported protocol
+    /// * `None` if the payload is not a supported protocol, or if this is a non-first fragment
     pub(crate) fn parse_embedded_payload(&self, cursor: &mut Reader) -> Option<EmbeddedIpv4Next> {
+        if self.is_non_first_fragment() {
+            trace!("payload is a non-first fragment");
+            return None;
+        }
         match self.0.protocol {
             IpNumber::TCP => cursor.parse_header::<TruncatedTcp, EmbeddedIpv4Next>(),
             IpNumber::UDP => cursor.parse_header::<TruncatedUdp, EmbeddedIpv4Next>(),
@@ -398,9 +418,21 @@ pub(crate) enum Ipv4Next {
     Udp(Udp),
     Icmp4(Icmp4),
     IpAuth(IpAuth),
+    Esp(Esp),
+    Gre(Gre),
+    Sctp(Sctp),
 }
 
-impl_from_for_enum![Ipv4Next, Tcp(Tcp), Udp(Udp), Icmp4(Icmp4), IpAuth(IpAuth)];
+impl_from_for_enum![
+    Ipv4Next,
+    Tcp(Tcp),
+    Udp(Udp),
+    Icmp4(Icmp4),
+    IpAuth(IpAuth),
+    Esp(Esp),
+    Gre(Gre),
+    Sctp(Sctp)
+];
 
 impl From<Ipv4Next> for Header {
     fn from(value: Ipv4Next) -> Self {
@@ -409,6 +441,9 @@ impl From<Ipv4Next> for Header {
             Ipv4Next::Udp(x) => Header::Udp(x),
             Ipv4Next::Icmp4(x) => Header::Icmp4(x),
             Ipv4Next::IpAuth(x) => Header::IpAuth(x),
+            Ipv4Next::Esp(x) => Header::Esp(x),
+            Ipv4Next::Gre(x) => Header::Gre(x),
+            Ipv4Next::Sctp(x) => Header::Sctp(x),
         }
     }
 }
@@ -443,6 +478,7 @@ impl From<EmbeddedIpv4Next> for EmbeddedHeader {
 mod contract {
     use crate::ip::NextHeader;
     use crate::ipv4::Ipv4;
+    use crate::ipv4::frag_offset::FragOffset;
     use bolero::generator::bolero_generator::bounded::BoundedValue;
     use bolero::{Driver, TypeGenerator, ValueGenerator};
     use etherparse::Ipv4Header;
@@ -489,7 +525,9 @@ mod contract {
                 .set_dont_fragment(u.produce()?)
                 .set_more_fragments(u.produce()?)
                 .set_identification(u.produce()?)
-                .set_fragment_offset(u.produce()?);
+                // the payload is expected to start with a `NextHeader` header, so this can only be
+                // the first fragment of a packet
+                .set_fragment_offset(FragOffset::MIN);
             header
                 .set_payload_len(u16::gen_bounded(
                     u,
@@ -513,7 +551,9 @@ mod contract {
         ///
         /// Unfortunately, the current implementation does not cover [`Ipv4::options`].
         fn generate<D: Driver>(u: &mut D) -> Option<Self> {
-            GenWithNextHeader(u.produce()?).generate(u)
+            let mut header = GenWithNextHeader(u.produce()?).generate(u)?;
+            header.set_fragment_offset(u.produce()?);
+            Some(header)
         }
     }
 }
@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! IPv6 [fragment header][RFC8200] type and logic.
+//!
+//! [RFC8200]: https://datatracker.ietf.org/doc/html/rfc8200#section-4.5
+
+use crate::ip::NextHeader;
+use crate::ipv4::frag_offset::FragOffset;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use core::convert::Infallible;
+use etherparse::Ipv6FragmentHeader;
+use std::num::NonZero;
+
+/// An IPv6 fragment extension header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+pub struct Ipv6Fragment {
+    next_header: NextHeader,
+    offset: FragOffset,
+    more_fragments: bool,
+    identification: u32,
+}
+
+impl Ipv6Fragment {
+    /// The length (in bytes) of an [`Ipv6Fragment`] header.
+    #[allow(clippy::unwrap_used)] // safe due to const eval
+    pub const LEN: NonZero<u16> = NonZero::new(8).unwrap();
+
+    /// Create a new [`Ipv6Fragment`] header.
+    #[must_use]
+    pub fn new(
+        next_header: NextHeader,
+        offset: FragOffset,
+        more_fragments: bool,
+        identification: u32,
+    ) -> Ipv6Fragment {
+        Ipv6Fragment {
+            next_header,
+            offset,
+            more_fragments,
+            identification,
+        }
+    }
+
+    /// Get the protocol of the fragmentable part of the original packet.
+    #[must_use]
+    pub fn next_header(&self) -> NextHeader {
+        self.next_header
+    }
+
+    /// Get the offset of this fragment (in units of 8 bytes) in the fragmentable part of the
+    /// original packet.
+    #[must_use]
+    pub fn offset(&self) -> FragOffset {
+        self.offset
+    }
+
+    /// Get the offset of this fragment (in bytes) in the fragmentable part of the original packet.
+    #[must_use]
+    pub fn byte_offset(&self) -> u16 {
+        // 13-bit value, so the multiplication can't overflow
+        self.offset.value() * 8
+    }
+
+    /// Returns true if the "more fragments" flag is set (i.e., this is not the last fragment).
+    #[must_use]
+    pub fn more_fragments(&self) -> bool {
+        self.more_fragments
+    }
+
+    /// Get the identification of the original packet.
+    #[must_use]
+    pub fn identification(&self) -> u32 {
+        self.identification
+    }
+
+    /// Returns true if this header is an [atomic fragment], that is, if the packet holds the whole
+    /// fragmentable part of the original packet and needs no reassembly.
+    ///
+    /// [atomic fragment]: https://datatracker.ietf.org/doc/html/rfc6946
+    #[must_use]
+    pub fn is_atomic(&self) -> bool {
+        self.offset == FragOffset::MIN && !self.more_fragments
+    }
+
+    /// Set the protocol of the fragmentable part of the original packet.
+    pub fn set_next_header(&mut self, next_header: NextHeader) -> &mut Self {
+        self.next_header = next_header;
+        self
+    }
+
+    /// Set the offset of this fragment (in units of 8 bytes).
+    pub fn set_offset(&mut self, offset: FragOffset) -> &mut Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Set the "more fragments" flag.
+    pub fn set_more_fragments(&mut self, more_fragments: bool) -> &mut Self {
+        self.more_fragments = more_fragments;
+        self
+    }
+
+    /// Set the identification of the original packet.
+    pub fn set_identification(&mut self, identification: u32) -> &mut Self {
+        self.identification = identification;
+        self
+    }
+}
+
+impl From<&Ipv6FragmentHeader> for Ipv6Fragment {
+    fn from(header: &Ipv6FragmentHeader) -> Self {
+        Ipv6Fragment {
+            next_header: NextHeader::from(header.next_header),
+            offset: FragOffset(header.fragment_offset),
+            more_fragments: header.more_fragments,
+            identification: header.identification,
+        }
+    }
+}
+
+impl From<Ipv6Fragment> for Ipv6FragmentHeader {
+    fn from(fragment: Ipv6Fragment) -> Self {
+        Ipv6FragmentHeader::new(
+            fragment.next_header.inner(),
+            fragment.offset.0,
+            fragment.more_fragments,
+            fragment.identification,
+        )
+    }
+}
+
+impl Parse for Ipv6Fragment {
+    /// The fragment header has a fixed size and no invalid field values, so only length errors may
+    /// occur.
+    type Error = Infallible;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < Ipv6Fragment::LEN.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Ipv6Fragment::LEN.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let offset_and_flags = u16::from_be_bytes([buf[2], buf[3]]);
+        let fragment = Ipv6Fragment {
+            next_header: NextHeader::new(buf[0]),
+            offset: FragOffset::new(offset_and_flags >> 3).unwrap_or_else(|e| unreachable!("{e}")),
+            more_fragments: offset_and_flags & 1 == 1,
+            identification: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+        };
+        Ok((fragment, Ipv6Fragment::LEN))
+    }
+}
+
+impl DeParse for Ipv6Fragment {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        Ipv6Fragment::LEN
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let len = buf.len();
+        if len < self.size().into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: self.size().into_non_zero_usize(),
+                actual: len,
+            }));
+        }
+        let offset_and_flags = (self.offset.value() << 3) | u16::from(self.more_fragments);
+        buf[0] = self.next_header.as_u8();
+        buf[1] = 0;
+        buf[2..4].copy_from_slice(&offset_and_flags.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.identification.to_be_bytes());
+        Ok(self.size())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ipv6::fragment::Ipv6Fragment;
+    use crate::parse::{DeParse, IntoNonZeroUSize, Parse, ParseError};
+    use etherparse::Ipv6FragmentHeader;
+
+    const LEN: usize = Ipv6Fragment::LEN.get() as usize;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!()
+            .with_type()
+            .for_each(|input: &Ipv6Fragment| {
+                let mut buffer = [0u8; LEN];
+                let consumed = input.deparse(&mut buffer).unwrap();
+                assert_eq!(consumed.into_non_zero_usize().get(), buffer.len());
+                let (parse_back, consumed2) = Ipv6Fragment::parse(&buffer).unwrap();
+                assert_eq!(input, &parse_back);
+                assert_eq!(consumed, consumed2);
+            });
+    }
+
+    #[test]
+    fn parse_matches_etherparse() {
+        bolero::check!().with_type().for_each(|slice: &[u8; LEN]| {
+            let (parsed, consumed) = Ipv6Fragment::parse(slice).unwrap();
+            assert_eq!(consumed.into_non_zero_usize().get(), LEN);
+            let (reference, _) = Ipv6FragmentHeader::from_slice(slice).unwrap();
+            assert_eq!(parsed, Ipv6Fragment::from(&reference));
+            let mut slice2 = [0u8; LEN];
+            parsed.deparse(&mut slice2).unwrap();
+            // the reserved bits are not preserved
+            assert_eq!(slice2, reference.to_bytes());
+        });
+    }
+
+    #[test]
+    fn too_short_buffer_parse_fails_gracefully() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; LEN - 1]| {
+                for i in 0..slice.len() {
+                    match Ipv6Fragment::parse(&slice[..i]) {
+                        Err(ParseError::Length(e)) => {
+                            assert_eq!(e.expected, Ipv6Fragment::LEN.into_non_zero_usize());
+                            assert_eq!(e.actual, i);
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            });
+    }
+}
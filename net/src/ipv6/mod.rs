@@ -3,6 +3,8 @@
 
 //! Ipv6 Address type and manipulation
 
+use crate::esp::Esp;
+use crate::gre::Gre;
 use crate::headers::{EmbeddedHeader, Header};
 use crate::icmp6::{Icmp6, TruncatedIcmp6};
 use crate::impl_from_for_enum;
@@ -14,6 +16,7 @@ use crate::parse::{
     DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError, ParseHeader,
     ParseWith, Reader,
 };
+use crate::sctp::Sctp;
 use crate::tcp::{Tcp, TruncatedTcp};
 use crate::udp::{TruncatedUdp, Udp};
 use etherparse::{IpNumber, Ipv6Extensions, Ipv6Header};
@@ -23,6 +26,11 @@ use tracing::{debug, trace};
 
 pub mod addr;
 pub mod flow_label;
+pub mod fragment;
+pub mod srh;
+
+pub use fragment::Ipv6Fragment;
+pub use srh::{Ipv6Srh, SrhError};
 
 #[cfg(any(test, feature = "bolero"))]
 pub use contract::*;
@@ -89,6 +97,12 @@ impl Ipv6 {
         FlowLabel::new(self.0.flow_label.value()).unwrap_or_else(|_| unreachable!())
     }
 
+    /// Get the length (in bytes) of the payload of this header, including the extension headers.
+    #[must_use]
+    pub fn payload_length(&self) -> u16 {
+        self.0.payload_length
+    }
+
     /// Set the source ip address of this header
     pub fn set_source(&mut self, source: UnicastIpv6Addr) -> &mut Self {
         self.0.source = source.inner().octets();
@@ -198,6 +212,9 @@ impl Ipv6 {
             IpNumber::UDP => cursor.parse_header::<Udp, Ipv6Next>(),
             IpNumber::IPV6_ICMP => cursor.parse_header::<Icmp6, Ipv6Next>(),
             IpNumber::AUTHENTICATION_HEADER => cursor.parse_header::<IpAuth, Ipv6Next>(),
+            IpNumber::ENCAPSULATING_SECURITY_PAYLOAD => cursor.parse_header::<Esp, Ipv6Next>(),
+            IpNumber::GRE => cursor.parse_header::<Gre, Ipv6Next>(),
+            IpNumber::SCTP => cursor.parse_header::<Sctp, Ipv6Next>(),
             IpNumber::IPV6_HEADER_HOP_BY_HOP
             | IpNumber::IPV6_ROUTE_HEADER
             | IpNumber::IPV6_FRAGMENTATION_HEADER
@@ -310,7 +327,10 @@ pub(crate) enum Ipv6Next {
     Udp(Udp),
     Icmp6(Icmp6),
     IpAuth(IpAuth),
+    Esp(Esp),
     Ipv6Ext(Ipv6Ext),
+    Gre(Gre),
+    Sctp(Sctp),
 }
 
 impl_from_for_enum![
@@ -319,7 +339,10 @@ impl_from_for_enum![
     Udp(Udp),
     Icmp6(Icmp6),
     IpAuth(IpAuth),
-    Ipv6Ext(Ipv6Ext)
+    Esp(Esp),
+    Ipv6Ext(Ipv6Ext),
+    Gre(Gre),
+    Sctp(Sctp)
 ];
 
 pub(crate) enum EmbeddedIpv6Next {
@@ -344,6 +367,8 @@ impl_from_for_enum![
 /// TODO: break this into multiple types (one per each header type).
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Ipv6Ext {
+    /// The protocol number of the first header in `inner`
+    ip_number: IpNumber,
     inner: Box<Ipv6Extensions>,
 }
 
@@ -370,39 +395,142 @@ impl ParseWith for Ipv6Ext {
         #[allow(clippy::cast_possible_truncation)] // buffer length bounded above
         let consumed =
             NonZero::new((buf.len() - rest.len()) as u16).ok_or_else(|| unreachable!())?;
-        Ok((Self { inner }, consumed))
+        Ok((Self { ip_number, inner }, consumed))
+    }
+}
+
+impl DeParse for Ipv6Ext {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        #[allow(clippy::cast_possible_truncation)] // bounded by the length of the parsed buffer
+        NonZero::new(self.inner.header_len() as u16).unwrap_or_else(|| unreachable!())
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let len = buf.len();
+        if len < self.size().into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: self.size().into_non_zero_usize(),
+                actual: len,
+            }));
+        }
+        self.inner
+            .write(&mut &mut buf[..], self.ip_number)
+            .map_err(|e| {
+                debug!("failed to write ipv6 extension headers: {e:?}");
+                DeParseError::Invalid(())
+            })?;
+        Ok(self.size())
     }
 }
 
 impl Ipv6Ext {
+    /// Get the protocol number of the first header of this extension header chain (i.e., the
+    /// next header value of the preceding header).
+    #[must_use]
+    pub fn first_header(&self) -> NextHeader {
+        NextHeader::from(self.ip_number)
+    }
+
+    /// Get the protocol of the payload following this extension header chain, if the chain is
+    /// consistent.
+    #[must_use]
+    pub fn next_header(&self) -> Option<NextHeader> {
+        self.inner
+            .next_header(self.ip_number)
+            .map(NextHeader::from)
+            .ok()
+    }
+
+    /// Get the [`Ipv6Fragment`] header of this extension header chain, if any.
+    #[must_use]
+    pub fn fragment(&self) -> Option<Ipv6Fragment> {
+        self.inner.fragment.as_ref().map(Ipv6Fragment::from)
+    }
+
+    /// Get the segment routing header of this extension header chain, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SrhError::Missing`] if the chain has no routing header, or another [`SrhError`]
+    /// if the routing header is not a valid segment routing header.
+    pub fn srh(&self) -> Result<Ipv6Srh, SrhError> {
+        let routing = self.inner.routing.as_ref().ok_or(SrhError::Missing)?;
+        Ipv6Srh::from_routing_payload(routing.routing.payload())
+    }
+
+    /// Replace the segment routing header of this extension header chain.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SrhError::Missing`] if the chain has no routing header, or
+    /// [`SrhError::InvalidTlvLength`] if the new header can't be encoded.
+    pub fn set_srh(&mut self, srh: &Ipv6Srh) -> Result<(), SrhError> {
+        let routing = self.inner.routing.as_mut().ok_or(SrhError::Missing)?;
+        let payload = srh.to_routing_payload()?;
+        routing
+            .routing
+            .set_payload(&payload)
+            .map_err(|_| SrhError::InvalidTlvLength(srh.tlvs_len()))
+    }
+
+    /// Returns true if the payload following this extension header chain is a (non-first)
+    /// fragment of a packet, and thus does not start with a header.
+    fn is_non_first_fragment(&self) -> bool {
+        self.fragment().is_some_and(|frag| frag.byte_offset() != 0)
+    }
+
+    /// Remove the [`Ipv6Fragment`] header from this extension header chain.
+    ///
+    /// The next header values of the chain are updated to skip the fragment header, and so is the
+    /// protocol number of the first header of the chain (see [`Ipv6Ext::first_header`]), which the
+    /// caller must propagate to the preceding header.
+    /// The chain may be left empty, in which case it should be removed from the packet.
+    pub(crate) fn remove_fragment(&mut self) -> Option<Ipv6Fragment> {
+        let fragment = self.fragment()?;
+        self.inner.fragment = None;
+        self.ip_number = self.inner.set_next_headers(fragment.next_header().inner());
+        Some(fragment)
+    }
+
+    /// Returns true if this extension header chain holds no header.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
     /// Parse the payload of this extension header.
     ///
     /// # Returns
     ///
     /// * `Some(Ipv6ExtNext)` variant if the payload was successfully parsed as a next header.
-    /// * `None` if the next header is not supported.
-    pub(crate) fn parse_payload(
-        &self,
-        first_ip_number: NextHeader,
-        cursor: &mut Reader,
-    ) -> Option<Ipv6ExtNext> {
+    /// * `None` if the next header is not supported, or if the payload is a non-first fragment.
+    pub(crate) fn parse_payload(&self, cursor: &mut Reader) -> Option<Ipv6ExtNext> {
         use etherparse::ip_number::{
-            AUTHENTICATION_HEADER, IPV6_DESTINATION_OPTIONS, IPV6_FRAGMENTATION_HEADER,
-            IPV6_HEADER_HOP_BY_HOP, IPV6_ICMP, IPV6_ROUTE_HEADER, TCP, UDP,
+            AUTHENTICATION_HEADER, ENCAPSULATING_SECURITY_PAYLOAD, GRE, IPV6_DESTINATION_OPTIONS,
+            IPV6_FRAGMENTATION_HEADER, IPV6_HEADER_HOP_BY_HOP, IPV6_ICMP, IPV6_ROUTE_HEADER, SCTP,
+            TCP, UDP,
         };
+        if self.is_non_first_fragment() {
+            trace!("payload is a non-first fragment");
+            return None;
+        }
         let next_header = self
             .inner
-            .next_header(first_ip_number.inner())
+            .next_header(self.ip_number)
             .map_err(|e| debug!("failed to parse: {e:?}"))
             .ok()?;
         match next_header {
             TCP => cursor.parse_header::<Tcp, Ipv6ExtNext>(),
             UDP => cursor.parse_header::<Udp, Ipv6ExtNext>(),
             IPV6_ICMP => cursor.parse_header::<Icmp6, Ipv6ExtNext>(),
+            GRE => cursor.parse_header::<Gre, Ipv6ExtNext>(),
+            SCTP => cursor.parse_header::<Sctp, Ipv6ExtNext>(),
             AUTHENTICATION_HEADER => {
                 debug!("nested ip auth header");
                 cursor.parse_header::<IpAuth, Ipv6ExtNext>()
             }
+            ENCAPSULATING_SECURITY_PAYLOAD => cursor.parse_header::<Esp, Ipv6ExtNext>(),
             IPV6_HEADER_HOP_BY_HOP
             | IPV6_ROUTE_HEADER
             | IPV6_FRAGMENTATION_HEADER
@@ -421,19 +549,22 @@ impl Ipv6Ext {
     /// # Returns
     ///
     /// * `Some(EmbeddedIpv6ExtNext)` variant if the payload was successfully parsed as a next header.
-    /// * `None` if the next header is not supported.
+    /// * `None` if the next header is not supported, or if the payload is a non-first fragment.
     pub(crate) fn parse_embedded_payload(
         &self,
-        first_ip_number: NextHeader,
         cursor: &mut Reader,
     ) -> Option<EmbeddedIpv6ExtNext> {
         use etherparse::ip_number::{
             AUTHENTICATION_HEADER, IPV6_DESTINATION_OPTIONS, IPV6_FRAGMENTATION_HEADER,
             IPV6_HEADER_HOP_BY_HOP, IPV6_ICMP, IPV6_ROUTE_HEADER, TCP, UDP,
         };
+        if self.is_non_first_fragment() {
+            trace!("payload is a non-first fragment");
+            return None;
+        }
         let next_header = self
             .inner
-            .next_header(first_ip_number.inner())
+            .next_header(self.ip_number)
             .map_err(|e| debug!("failed to parse: {e:?}"))
             .ok()?;
         match next_header {
@@ -463,7 +594,10 @@ pub(crate) enum Ipv6ExtNext {
     Udp(Udp),
     Icmp6(Icmp6),
     IpAuth(IpAuth),
+    Esp(Esp),
     Ipv6Ext(Ipv6Ext),
+    Gre(Gre),
+    Sctp(Sctp),
 }
 
 impl_from_for_enum![
@@ -472,7 +606,10 @@ impl_from_for_enum![
     Udp(Udp),
     Icmp6(Icmp6),
     IpAuth(IpAuth),
-    Ipv6Ext(Ipv6Ext)
+    Esp(Esp),
+    Ipv6Ext(Ipv6Ext),
+    Gre(Gre),
+    Sctp(Sctp)
 ];
 
 impl From<Ipv6Next> for Header {
@@ -482,7 +619,10 @@ impl From<Ipv6Next> for Header {
             Ipv6Next::Udp(x) => Header::Udp(x),
             Ipv6Next::Icmp6(x) => Header::Icmp6(x),
             Ipv6Next::IpAuth(x) => Header::IpAuth(x),
+            Ipv6Next::Esp(x) => Header::Esp(x),
             Ipv6Next::Ipv6Ext(x) => Header::IpV6Ext(x),
+            Ipv6Next::Gre(x) => Header::Gre(x),
+            Ipv6Next::Sctp(x) => Header::Sctp(x),
         }
     }
 }
@@ -494,7 +634,10 @@ impl From<Ipv6ExtNext> for Header {
             Ipv6ExtNext::Udp(x) => Header::Udp(x),
             Ipv6ExtNext::Icmp6(x) => Header::Icmp6(x),
             Ipv6ExtNext::IpAuth(x) => Header::IpAuth(x),
+            Ipv6ExtNext::Esp(x) => Header::Esp(x),
             Ipv6ExtNext::Ipv6Ext(x) => Header::IpV6Ext(x),
+            Ipv6ExtNext::Gre(x) => Header::Gre(x),
+            Ipv6ExtNext::Sctp(x) => Header::Sctp(x),
         }
     }
 }
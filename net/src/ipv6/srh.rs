@@ -0,0 +1,345 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! IPv6 [segment routing header][RFC8754] (SRH) type and logic.
+//!
+//! The SRH is a routing extension header, and is kept as such in the [`Ipv6Ext`] chain of a
+//! packet: [`Ipv6Srh`] is a typed view of it, see [`Ipv6Ext::srh`] and [`Ipv6Ext::set_srh`].
+//!
+//! [RFC8754]: https://datatracker.ietf.org/doc/html/rfc8754#section-2
+//! [`Ipv6Ext`]: crate::ipv6::Ipv6Ext
+//! [`Ipv6Ext::srh`]: crate::ipv6::Ipv6Ext::srh
+//! [`Ipv6Ext::set_srh`]: crate::ipv6::Ipv6Ext::set_srh
+
+use std::net::Ipv6Addr;
+
+/// Errors which may occur when building or parsing an [`Ipv6Srh`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SrhError {
+    /// The extension header chain has no segment routing header.
+    #[error("no segment routing header")]
+    Missing,
+    /// The segment list has no segment left to visit.
+    #[error("no segments left")]
+    NoSegmentsLeft,
+    /// The routing header is not a segment routing header.
+    #[error("routing type {0} is not a segment routing header")]
+    NotSrh(u8),
+    /// The header is too short for its fields or segment list.
+    #[error("truncated segment routing header")]
+    Truncated,
+    /// The segment list is empty or has more than 256 segments.
+    #[error("invalid number of segments ({0})")]
+    InvalidSegmentCount(usize),
+    /// Segments left points beyond the last entry of the segment list.
+    #[error("segments left ({segments_left}) exceeds last entry ({last_entry})")]
+    InvalidSegmentsLeft { segments_left: u8, last_entry: u8 },
+    /// The TLVs do not fill the header up to a multiple of 8 bytes, or the header is too long.
+    #[error("invalid length of TLVs ({0})")]
+    InvalidTlvLength(usize),
+}
+
+/// An IPv6 segment routing header.
+///
+/// The segment list is encoded in reverse order: `segments()[0]` is the last segment of the path,
+/// and the active segment is `segments()[segments_left]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv6Srh {
+    segments_left: u8,
+    flags: u8,
+    tag: u16,
+    segments: Vec<Ipv6Addr>,
+    tlvs: Vec<u8>,
+}
+
+impl Ipv6Srh {
+    /// The routing type of segment routing headers
+    pub const ROUTING_TYPE: u8 = 4;
+    /// The length of the fields of the routing header payload preceding the segment list
+    const FIXED_LEN: usize = 6;
+
+    /// Create a new [`Ipv6Srh`] with the given segment list (in reverse order), the first
+    /// segment of the path (the last of the list) being active.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SrhError::InvalidSegmentCount`] if the segment list is empty or too long.
+    pub fn new(segments: Vec<Ipv6Addr>) -> Result<Self, SrhError> {
+        let Some(last_entry) = segments
+            .len()
+            .checked_sub(1)
+            .and_then(|n| u8::try_from(n).ok())
+        else {
+            return Err(SrhError::InvalidSegmentCount(segments.len()));
+        };
+        Ok(Self {
+            segments_left: last_entry,
+            flags: 0,
+            tag: 0,
+            segments,
+            tlvs: vec![],
+        })
+    }
+
+    /// Get the number of segments left to visit
+    #[must_use]
+    pub fn segments_left(&self) -> u8 {
+        self.segments_left
+    }
+
+    /// Get the index of the last element of the segment list
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)] // checked on creation
+    pub fn last_entry(&self) -> u8 {
+        (self.segments.len() - 1) as u8
+    }
+
+    /// Get the flags of this header
+    #[must_use]
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Get the tag of this header
+    #[must_use]
+    pub fn tag(&self) -> u16 {
+        self.tag
+    }
+
+    /// Get the segment list, in reverse order
+    #[must_use]
+    pub fn segments(&self) -> &[Ipv6Addr] {
+        &self.segments
+    }
+
+    /// Get the length of the TLVs following the segment list
+    #[must_use]
+    pub fn tlvs_len(&self) -> usize {
+        self.tlvs.len()
+    }
+
+    /// Get the active segment, that is, the segment at index `segments_left` of the list
+    #[must_use]
+    pub fn active_segment(&self) -> Ipv6Addr {
+        self.segments[usize::from(self.segments_left)]
+    }
+
+    /// Set the number of segments left to visit
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SrhError::InvalidSegmentsLeft`] if `segments_left` exceeds the last entry.
+    pub fn set_segments_left(&mut self, segments_left: u8) -> Result<&mut Self, SrhError> {
+        if segments_left > self.last_entry() {
+            return Err(SrhError::InvalidSegmentsLeft {
+                segments_left,
+                last_entry: self.last_entry(),
+            });
+        }
+        self.segments_left = segments_left;
+        Ok(self)
+    }
+
+    /// Set the flags of this header
+    pub fn set_flags(&mut self, flags: u8) -> &mut Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the tag of this header
+    pub fn set_tag(&mut self, tag: u16) -> &mut Self {
+        self.tag = tag;
+        self
+    }
+
+    /// Parse an [`Ipv6Srh`] from the payload of a routing header, that is, the bytes following
+    /// its next header and length fields.
+    pub(crate) fn from_routing_payload(payload: &[u8]) -> Result<Self, SrhError> {
+        let Some(fixed) = payload.first_chunk::<{ Ipv6Srh::FIXED_LEN }>() else {
+            return Err(SrhError::Truncated);
+        };
+        let [
+            routing_type,
+            segments_left,
+            last_entry,
+            flags,
+            tag_hi,
+            tag_lo,
+        ] = *fixed;
+        if routing_type != Ipv6Srh::ROUTING_TYPE {
+            return Err(SrhError::NotSrh(routing_type));
+        }
+        if segments_left > last_entry {
+            return Err(SrhError::InvalidSegmentsLeft {
+                segments_left,
+                last_entry,
+            });
+        }
+        let num_segments = usize::from(last_entry) + 1;
+        let tlvs_start = Ipv6Srh::FIXED_LEN + 16 * num_segments;
+        let Some(list) = payload.get(Ipv6Srh::FIXED_LEN..tlvs_start) else {
+            return Err(SrhError::Truncated);
+        };
+        let segments = list
+            .chunks_exact(16)
+            .map(|chunk| {
+                let octets: [u8; 16] = chunk.try_into().unwrap_or_else(|_| unreachable!());
+                Ipv6Addr::from(octets)
+            })
+            .collect();
+        Ok(Self {
+            segments_left,
+            flags,
+            tag: u16::from_be_bytes([tag_hi, tag_lo]),
+            segments,
+            tlvs: payload[tlvs_start..].to_vec(),
+        })
+    }
+
+    /// Encode this [`Ipv6Srh`] as the payload of a routing header
+    pub(crate) fn to_routing_payload(&self) -> Result<Vec<u8>, SrhError> {
+        if self.tlvs.len() % 8 != 0 {
+            return Err(SrhError::InvalidTlvLength(self.tlvs.len()));
+        }
+        let mut payload =
+            Vec::with_capacity(Ipv6Srh::FIXED_LEN + 16 * self.segments.len() + self.tlvs.len());
+        payload.extend_from_slice(&[
+            Ipv6Srh::ROUTING_TYPE,
+            self.segments_left,
+            self.last_entry(),
+            self.flags,
+        ]);
+        payload.extend_from_slice(&self.tag.to_be_bytes());
+        for segment in &self.segments {
+            payload.extend_from_slice(&segment.octets());
+        }
+        payload.extend_from_slice(&self.tlvs);
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn addr(address: &str) -> Ipv6Addr {
+        Ipv6Addr::from_str(address).unwrap()
+    }
+
+    #[test]
+    fn srh_payload_back_and_forth() {
+        let segments = vec![addr("fc00::3"), addr("fc00::2"), addr("fc00::1")];
+        let mut srh = Ipv6Srh::new(segments.clone()).unwrap();
+        assert_eq!(srh.segments_left(), 2);
+        assert_eq!(srh.last_entry(), 2);
+        assert_eq!(srh.active_segment(), addr("fc00::1"));
+        srh.set_segments_left(1).unwrap().set_tag(0x1234);
+        assert_eq!(srh.active_segment(), addr("fc00::2"));
+
+        let payload = srh.to_routing_payload().unwrap();
+        assert_eq!(payload.len(), 6 + 3 * 16);
+        assert_eq!(payload[..6], [4, 1, 2, 0, 0x12, 0x34]);
+        assert_eq!(Ipv6Srh::from_routing_payload(&payload), Ok(srh.clone()));
+
+        // malformed headers are rejected
+        assert_eq!(
+            Ipv6Srh::from_routing_payload(&payload[..40]),
+            Err(SrhError::Truncated)
+        );
+        let mut bad = payload.clone();
+        bad[0] = 0;
+        assert_eq!(
+            Ipv6Srh::from_routing_payload(&bad),
+            Err(SrhError::NotSrh(0))
+        );
+        bad[0] = 4;
+        bad[1] = 3;
+        assert!(matches!(
+            Ipv6Srh::from_routing_payload(&bad),
+            Err(SrhError::InvalidSegmentsLeft { .. })
+        ));
+        assert!(srh.set_segments_left(3).is_err());
+        assert_eq!(Ipv6Srh::new(vec![]), Err(SrhError::InvalidSegmentCount(0)));
+    }
+
+    #[test]
+    fn srv6_advance_and_decap() {
+        use crate::buffer::TestBuffer;
+        use crate::eth::Eth;
+        use crate::eth::ethtype::EthType;
+        use crate::eth::mac::{DestinationMac, Mac, SourceMac};
+        use crate::headers::{TryIpv4, TryIpv6};
+        use crate::packet::Packet;
+        use crate::parse::DeParse;
+        use etherparse::{
+            IpNumber, Ipv4Header, Ipv6FlowLabel, Ipv6Header, Ipv6RawExtHeader, UdpHeader,
+        };
+
+        let eth = |ethtype| {
+            Eth::new(
+                SourceMac::new(Mac([2, 0, 0, 0, 0, 1])).unwrap(),
+                DestinationMac::new(Mac([2, 0, 0, 0, 0, 2])).unwrap(),
+                ethtype,
+            )
+        };
+        let udp = UdpHeader {
+            source_port: 1234,
+            destination_port: 5678,
+            length: 8,
+            checksum: 0,
+        };
+        let ip = Ipv4Header::new(8, 64, IpNumber::UDP, [10, 0, 0, 1], [10, 0, 0, 2]).unwrap();
+        let mut srh = Ipv6Srh::new(vec![addr("fc00::1"), addr("fc00::2")]).unwrap();
+        srh.set_segments_left(1).unwrap();
+        let routing =
+            Ipv6RawExtHeader::new_raw(IpNumber::IPV4, &srh.to_routing_payload().unwrap()).unwrap();
+        #[allow(clippy::cast_possible_truncation)] // test values
+        let outer = Ipv6Header {
+            traffic_class: 0,
+            flow_label: Ipv6FlowLabel::ZERO,
+            payload_length: (routing.header_len() + 20 + 8) as u16,
+            next_header: IpNumber::IPV6_ROUTE_HEADER,
+            hop_limit: 64,
+            source: addr("fc00::100").octets(),
+            destination: addr("fc00::2").octets(),
+        };
+
+        // an IPv4 packet in SRv6, with one segment left
+        let mut frame = vec![0u8; 14];
+        eth(EthType::IPV6).deparse(&mut frame).unwrap();
+        outer.write(&mut frame).unwrap();
+        routing.write(&mut frame).unwrap();
+        ip.write(&mut frame).unwrap();
+        udp.write(&mut frame).unwrap();
+
+        // the inner packet
+        let mut inner = vec![0u8; 14];
+        eth(EthType::IPV4).deparse(&mut inner).unwrap();
+        ip.write(&mut inner).unwrap();
+        udp.write(&mut inner).unwrap();
+
+        let mut packet = Packet::new(TestBuffer::from_raw_data(&frame)).unwrap();
+        assert_eq!(packet.srh(), Ok(srh));
+
+        // move to the last segment
+        assert_eq!(packet.srv6_advance(), Ok(addr("fc00::1")));
+        assert_eq!(packet.try_ipv6().unwrap().destination(), addr("fc00::1"));
+        assert_eq!(packet.srh().unwrap().segments_left(), 0);
+        assert_eq!(packet.srv6_advance(), Err(SrhError::NoSegmentsLeft));
+
+        // decapsulate the inner packet
+        let removed = packet.ipv6_decap().unwrap().unwrap();
+        assert_eq!(removed.destination(), addr("fc00::1"));
+        assert!(packet.try_ipv6().is_none());
+        assert_eq!(
+            packet.try_ipv4().unwrap().destination(),
+            std::net::Ipv4Addr::new(10, 0, 0, 2)
+        );
+        assert_eq!(packet.srh(), Err(SrhError::Missing));
+        assert!(packet.ipv6_decap().is_none());
+        let mut buf = vec![0u8; inner.len()];
+        packet.get_headers().deparse(&mut buf).unwrap();
+        assert_eq!(buf, inner);
+    }
+}
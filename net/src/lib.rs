@@ -15,22 +15,38 @@
 #![allow(clippy::should_panic_without_expect)] // we panic in contract checks with simple unwrap()
 
 pub mod addr_parse_error;
+pub mod arp;
+pub mod bfd;
 pub mod buffer;
 pub mod checksum;
+pub mod dhcp;
+pub mod dhcpv6;
+pub mod esp;
 pub mod eth;
+pub mod geneve;
+pub mod gre;
 pub mod headers;
 pub mod icmp4;
 pub mod icmp6;
 pub mod icmp_any;
+pub mod igmp;
 pub mod interface;
 pub mod ip;
 pub mod ip_auth;
 pub mod ipv4;
 pub mod ipv6;
+pub mod lldp;
+pub mod mld;
+pub mod mpls;
+pub mod nsh;
 pub mod packet;
 pub mod parse;
+pub mod pcap;
 pub mod pci;
+pub mod pppoe;
+pub mod reassembly;
 pub mod route;
+pub mod sctp;
 pub mod tcp;
 pub mod udp;
 pub mod vlan;
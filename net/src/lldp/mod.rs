@@ -0,0 +1,557 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [LLDP][IEEE 802.1AB] data units, made of TLVs (type, length, value).
+//!
+//! LLDP data units are the payload of ethernet frames of type [`EthType::LLDP`], they are not
+//! parsed as part of the [`Headers`](crate::headers::Headers) of a packet.
+//!
+//! [IEEE 802.1AB]: https://en.wikipedia.org/wiki/Link_Layer_Discovery_Protocol
+
+use crate::eth::ethtype::EthType;
+use crate::eth::mac::Mac;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use core::fmt::{Display, Formatter};
+use core::num::NonZero;
+use tracing::trace;
+
+/// The chassis id of an [`Lldpdu`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChassisId {
+    /// A MAC address of the chassis
+    MacAddress(Mac),
+    /// A locally assigned name
+    Local(String),
+    /// Any other subtype, carried as is
+    Other {
+        /// The chassis id subtype
+        subtype: u8,
+        /// The raw chassis id
+        id: Vec<u8>,
+    },
+}
+
+/// The port id of an [`Lldpdu`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PortId {
+    /// The MAC address of the port
+    MacAddress(Mac),
+    /// The name of the interface
+    InterfaceName(String),
+    /// A locally assigned name
+    Local(String),
+    /// Any other subtype, carried as is
+    Other {
+        /// The port id subtype
+        subtype: u8,
+        /// The raw port id
+        id: Vec<u8>,
+    },
+}
+
+/// An optional TLV of an [`Lldpdu`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LldpTlv {
+    /// Description of the port
+    PortDescription(String),
+    /// Name of the system
+    SystemName(String),
+    /// Description of the system
+    SystemDescription(String),
+    /// Capabilities of the system
+    SystemCapabilities {
+        /// Bitmap of the capabilities of the system
+        available: u16,
+        /// Bitmap of the enabled capabilities of the system
+        enabled: u16,
+    },
+    /// Any other TLV (management address, organizationally specific, ...), carried as is
+    Other {
+        /// The type of the TLV (7 bits)
+        tlv_type: u8,
+        /// The value of the TLV
+        value: Vec<u8>,
+    },
+}
+
+/// An LLDP data unit.
+///
+/// The chassis id, port id and TTL TLVs are mandatory and always come first, in this order.
+/// Any optional TLV follows, and the end of the data unit is marked by an end TLV.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lldpdu {
+    chassis_id: ChassisId,
+    port_id: PortId,
+    ttl: u16,
+    tlvs: Vec<LldpTlv>,
+}
+
+/// Errors which may occur when parsing an [`Lldpdu`].
+#[derive(Debug, thiserror::Error)]
+pub enum LldpError {
+    /// One of the mandatory TLVs is missing (or misplaced).
+    #[error("missing mandatory TLV of type {0}")]
+    MissingTlv(u8),
+    /// A TLV has an invalid length for its type.
+    #[error("invalid length {length} for TLV of type {tlv_type}")]
+    InvalidLength {
+        /// The type of the TLV
+        tlv_type: u8,
+        /// The length of the TLV
+        length: u16,
+    },
+}
+
+impl ChassisId {
+    const SUBTYPE_MAC_ADDRESS: u8 = 4;
+    const SUBTYPE_LOCAL: u8 = 7;
+
+    fn subtype(&self) -> u8 {
+        match self {
+            ChassisId::MacAddress(_) => ChassisId::SUBTYPE_MAC_ADDRESS,
+            ChassisId::Local(_) => ChassisId::SUBTYPE_LOCAL,
+            ChassisId::Other { subtype, .. } => *subtype,
+        }
+    }
+
+    fn id(&self) -> &[u8] {
+        match self {
+            ChassisId::MacAddress(mac) => &mac.0,
+            ChassisId::Local(name) => name.as_bytes(),
+            ChassisId::Other { id, .. } => id,
+        }
+    }
+
+    fn from_raw(subtype: u8, id: &[u8]) -> ChassisId {
+        match (subtype, <[u8; 6]>::try_from(id), core::str::from_utf8(id)) {
+            (ChassisId::SUBTYPE_MAC_ADDRESS, Ok(mac), _) => ChassisId::MacAddress(Mac(mac)),
+            (ChassisId::SUBTYPE_LOCAL, _, Ok(name)) => ChassisId::Local(name.to_owned()),
+            _ => ChassisId::Other {
+                subtype,
+                id: id.to_vec(),
+            },
+        }
+    }
+}
+
+impl PortId {
+    const SUBTYPE_MAC_ADDRESS: u8 = 3;
+    const SUBTYPE_INTERFACE_NAME: u8 = 5;
+    const SUBTYPE_LOCAL: u8 = 7;
+
+    fn subtype(&self) -> u8 {
+        match self {
+            PortId::MacAddress(_) => PortId::SUBTYPE_MAC_ADDRESS,
+            PortId::InterfaceName(_) => PortId::SUBTYPE_INTERFACE_NAME,
+            PortId::Local(_) => PortId::SUBTYPE_LOCAL,
+            PortId::Other { subtype, .. } => *subtype,
+        }
+    }
+
+    fn id(&self) -> &[u8] {
+        match self {
+            PortId::MacAddress(mac) => &mac.0,
+            PortId::InterfaceName(name) | PortId::Local(name) => name.as_bytes(),
+            PortId::Other { id, .. } => id,
+        }
+    }
+
+    fn from_raw(subtype: u8, id: &[u8]) -> PortId {
+        match (subtype, <[u8; 6]>::try_from(id), core::str::from_utf8(id)) {
+            (PortId::SUBTYPE_MAC_ADDRESS, Ok(mac), _) => PortId::MacAddress(Mac(mac)),
+            (PortId::SUBTYPE_INTERFACE_NAME, _, Ok(name)) => PortId::InterfaceName(name.to_owned()),
+            (PortId::SUBTYPE_LOCAL, _, Ok(name)) => PortId::Local(name.to_owned()),
+            _ => PortId::Other {
+                subtype,
+                id: id.to_vec(),
+            },
+        }
+    }
+}
+
+impl LldpTlv {
+    fn tlv_type(&self) -> u8 {
+        match self {
+            LldpTlv::PortDescription(_) => Lldpdu::TYPE_PORT_DESCRIPTION,
+            LldpTlv::SystemName(_) => Lldpdu::TYPE_SYSTEM_NAME,
+            LldpTlv::SystemDescription(_) => Lldpdu::TYPE_SYSTEM_DESCRIPTION,
+            LldpTlv::SystemCapabilities { .. } => Lldpdu::TYPE_SYSTEM_CAPABILITIES,
+            LldpTlv::Other { tlv_type, .. } => *tlv_type,
+        }
+    }
+
+    fn value(&self) -> Vec<u8> {
+        match self {
+            LldpTlv::PortDescription(text)
+            | LldpTlv::SystemName(text)
+            | LldpTlv::SystemDescription(text) => text.as_bytes().to_vec(),
+            LldpTlv::SystemCapabilities { available, enabled } => {
+                [available.to_be_bytes(), enabled.to_be_bytes()].concat()
+            }
+            LldpTlv::Other { value, .. } => value.clone(),
+        }
+    }
+
+    fn from_raw(tlv_type: u8, value: &[u8]) -> LldpTlv {
+        let text = core::str::from_utf8(value).map(str::to_owned);
+        match (tlv_type, text) {
+            (Lldpdu::TYPE_PORT_DESCRIPTION, Ok(text)) => LldpTlv::PortDescription(text),
+            (Lldpdu::TYPE_SYSTEM_NAME, Ok(text)) => LldpTlv::SystemName(text),
+            (Lldpdu::TYPE_SYSTEM_DESCRIPTION, Ok(text)) => LldpTlv::SystemDescription(text),
+            (Lldpdu::TYPE_SYSTEM_CAPABILITIES, _) if value.len() == 4 => {
+                LldpTlv::SystemCapabilities {
+                    available: u16::from_be_bytes([value[0], value[1]]),
+                    enabled: u16::from_be_bytes([value[2], value[3]]),
+                }
+            }
+            _ => LldpTlv::Other {
+                tlv_type,
+                value: value.to_vec(),
+            },
+        }
+    }
+}
+
+impl Lldpdu {
+    /// The destination [`Mac`] of LLDP frames (nearest bridge).
+    pub const DESTINATION: Mac = Mac([0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e]);
+
+    /// The minimum length of an [`Lldpdu`]: the mandatory TLVs, each with a 1 byte value, and the
+    /// end TLV.
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(14).unwrap();
+
+    /// The maximum length of the value of a TLV (9 bits)
+    pub const MAX_VALUE_LENGTH: usize = 511;
+
+    const TYPE_END: u8 = 0;
+    const TYPE_CHASSIS_ID: u8 = 1;
+    const TYPE_PORT_ID: u8 = 2;
+    const TYPE_TTL: u8 = 3;
+    const TYPE_PORT_DESCRIPTION: u8 = 4;
+    const TYPE_SYSTEM_NAME: u8 = 5;
+    const TYPE_SYSTEM_DESCRIPTION: u8 = 6;
+    const TYPE_SYSTEM_CAPABILITIES: u8 = 7;
+
+    /// Length of the header of a TLV
+    const TLV_HEADER_LENGTH: usize = 2;
+
+    /// Create a new [`Lldpdu`] with the mandatory TLVs and no optional TLV.
+    ///
+    /// A `ttl` of zero tells the neighbors to forget about the sender.
+    #[must_use]
+    pub fn new(chassis_id: ChassisId, port_id: PortId, ttl: u16) -> Lldpdu {
+        Lldpdu {
+            chassis_id,
+            port_id,
+            ttl,
+            tlvs: Vec::new(),
+        }
+    }
+
+    /// Add an optional TLV to this data unit.
+    #[must_use]
+    pub fn with_tlv(mut self, tlv: LldpTlv) -> Lldpdu {
+        self.tlvs.push(tlv);
+        self
+    }
+
+    /// Get the chassis id of the sender.
+    #[must_use]
+    pub const fn chassis_id(&self) -> &ChassisId {
+        &self.chassis_id
+    }
+
+    /// Get the port id of the sender.
+    #[must_use]
+    pub const fn port_id(&self) -> &PortId {
+        &self.port_id
+    }
+
+    /// Get the number of seconds for which the information of this data unit is valid.
+    #[must_use]
+    pub const fn ttl(&self) -> u16 {
+        self.ttl
+    }
+
+    /// Get the optional TLVs of this data unit.
+    #[must_use]
+    pub fn tlvs(&self) -> &[LldpTlv] {
+        &self.tlvs
+    }
+
+    /// Get the system name of the sender, if any.
+    #[must_use]
+    pub fn system_name(&self) -> Option<&str> {
+        self.tlvs.iter().find_map(|tlv| match tlv {
+            LldpTlv::SystemName(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Get the description of the port of the sender, if any.
+    #[must_use]
+    pub fn port_description(&self) -> Option<&str> {
+        self.tlvs.iter().find_map(|tlv| match tlv {
+            LldpTlv::PortDescription(description) => Some(description.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Iterate over the (type, value) of all the TLVs of this data unit, end TLV included.
+    fn raw_tlvs(&self) -> impl Iterator<Item = (u8, Vec<u8>)> + '_ {
+        let chassis_id = [&[self.chassis_id.subtype()], self.chassis_id.id()].concat();
+        let port_id = [&[self.port_id.subtype()], self.port_id.id()].concat();
+        [
+            (Lldpdu::TYPE_CHASSIS_ID, chassis_id),
+            (Lldpdu::TYPE_PORT_ID, port_id),
+            (Lldpdu::TYPE_TTL, self.ttl.to_be_bytes().to_vec()),
+        ]
+        .into_iter()
+        .chain(self.tlvs.iter().map(|tlv| (tlv.tlv_type(), tlv.value())))
+        .chain([(Lldpdu::TYPE_END, Vec::new())])
+    }
+
+    /// Read the next TLV of `buf` at `offset`, returning its type, its value and the offset of the
+    /// following TLV.
+    fn next_tlv(buf: &[u8], offset: usize) -> Result<(u8, &[u8], usize), ParseError<LldpError>> {
+        let header_end = offset + Lldpdu::TLV_HEADER_LENGTH;
+        let Some(header) = buf.get(offset..header_end) else {
+            return Err(ParseError::Length(LengthError {
+                expected: NonZero::new(header_end).unwrap_or_else(|| unreachable!()),
+                actual: buf.len(),
+            }));
+        };
+        let tlv_type = header[0] >> 1;
+        let length = u16::from_be_bytes([header[0] & 0x01, header[1]]);
+        let value_end = header_end + usize::from(length);
+        let Some(value) = buf.get(header_end..value_end) else {
+            return Err(ParseError::Length(LengthError {
+                expected: NonZero::new(value_end).unwrap_or_else(|| unreachable!()),
+                actual: buf.len(),
+            }));
+        };
+        Ok((tlv_type, value, value_end))
+    }
+
+    /// Read the next TLV of `buf` at `offset`, which must be of type `expected` and have a value
+    /// of at least `min_length` bytes.
+    fn next_mandatory_tlv(
+        buf: &[u8],
+        offset: usize,
+        expected: u8,
+        min_length: usize,
+    ) -> Result<(&[u8], usize), ParseError<LldpError>> {
+        let (tlv_type, value, next) = Lldpdu::next_tlv(buf, offset)?;
+        if tlv_type != expected {
+            return Err(ParseError::Invalid(LldpError::MissingTlv(expected)));
+        }
+        if value.len() < min_length {
+            #[allow(clippy::cast_possible_truncation)] // length is 9 bits
+            let length = value.len() as u16;
+            return Err(ParseError::Invalid(LldpError::InvalidLength {
+                tlv_type,
+                length,
+            }));
+        }
+        Ok((value, next))
+    }
+}
+
+impl Parse for Lldpdu {
+    type Error = LldpError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() > u16::MAX as usize {
+            return Err(ParseError::BufferTooLong(buf.len()));
+        }
+        let (chassis_id, offset) = Lldpdu::next_mandatory_tlv(buf, 0, Lldpdu::TYPE_CHASSIS_ID, 2)?;
+        let (port_id, offset) = Lldpdu::next_mandatory_tlv(buf, offset, Lldpdu::TYPE_PORT_ID, 2)?;
+        let (ttl, mut offset) = Lldpdu::next_mandatory_tlv(buf, offset, Lldpdu::TYPE_TTL, 2)?;
+        let mut lldpdu = Lldpdu::new(
+            ChassisId::from_raw(chassis_id[0], &chassis_id[1..]),
+            PortId::from_raw(port_id[0], &port_id[1..]),
+            u16::from_be_bytes([ttl[0], ttl[1]]),
+        );
+        loop {
+            let (tlv_type, value, next) = Lldpdu::next_tlv(buf, offset)?;
+            offset = next;
+            match tlv_type {
+                Lldpdu::TYPE_END => break,
+                Lldpdu::TYPE_CHASSIS_ID | Lldpdu::TYPE_PORT_ID | Lldpdu::TYPE_TTL => {
+                    trace!("Ignoring duplicate mandatory LLDP TLV of type {tlv_type}");
+                }
+                _ => lldpdu.tlvs.push(LldpTlv::from_raw(tlv_type, value)),
+            }
+        }
+        #[allow(clippy::cast_possible_truncation)] // buffer length bounded above
+        let consumed = NonZero::new(offset as u16).unwrap_or_else(|| unreachable!());
+        Ok((lldpdu, consumed))
+    }
+}
+
+impl DeParse for Lldpdu {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        let size: usize = self
+            .raw_tlvs()
+            .map(|(_, value)| Lldpdu::TLV_HEADER_LENGTH + value.len())
+            .sum();
+        #[allow(clippy::cast_possible_truncation)] // we don't expect data units of 64kiB
+        NonZero::new(size as u16).unwrap_or_else(|| unreachable!())
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let size = self.size();
+        if buf.len() < size.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: size.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let mut offset = 0;
+        for (tlv_type, value) in self.raw_tlvs() {
+            if value.len() > Lldpdu::MAX_VALUE_LENGTH {
+                return Err(DeParseError::Invalid(()));
+            }
+            #[allow(clippy::cast_possible_truncation)] // checked above
+            let header = ((u16::from(tlv_type) << 9) | value.len() as u16).to_be_bytes();
+            buf[offset..offset + Lldpdu::TLV_HEADER_LENGTH].copy_from_slice(&header);
+            offset += Lldpdu::TLV_HEADER_LENGTH;
+            buf[offset..offset + value.len()].copy_from_slice(&value);
+            offset += value.len();
+        }
+        debug_assert_eq!(offset, size.into_non_zero_usize().get());
+        Ok(size)
+    }
+}
+
+impl Display for ChassisId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ChassisId::MacAddress(mac) => write!(f, "{mac}"),
+            ChassisId::Local(name) => write!(f, "{name}"),
+            ChassisId::Other { subtype, id } => write!(f, "({subtype}) {id:02x?}"),
+        }
+    }
+}
+
+impl Display for PortId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PortId::MacAddress(mac) => write!(f, "{mac}"),
+            PortId::InterfaceName(name) | PortId::Local(name) => write!(f, "{name}"),
+            PortId::Other { subtype, id } => write!(f, "({subtype}) {id:02x?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::eth::ethtype::EthType;
+    use crate::eth::mac::Mac;
+    use crate::headers::TryEth;
+    use crate::lldp::{ChassisId, LldpError, LldpTlv, Lldpdu, PortId};
+    use crate::packet::{Packet, PacketBuilder};
+    use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, Parse, ParseError};
+
+    const SOURCE: Mac = Mac([0x2, 0, 0, 0, 0, 1]);
+
+    fn lldpdu() -> Lldpdu {
+        Lldpdu::new(
+            ChassisId::MacAddress(SOURCE),
+            PortId::InterfaceName("eth0".to_string()),
+            120,
+        )
+        .with_tlv(LldpTlv::PortDescription("uplink".to_string()))
+        .with_tlv(LldpTlv::SystemName("gateway-1".to_string()))
+        .with_tlv(LldpTlv::SystemCapabilities {
+            available: 0x14,
+            enabled: 0x10,
+        })
+        .with_tlv(LldpTlv::Other {
+            tlv_type: 127,
+            value: vec![0x00, 0x12, 0x0f, 0x01, 0x03, 0x6c, 0x00, 0x00, 0x10],
+        })
+    }
+
+    #[test]
+    fn parse_back() {
+        let lldpdu = lldpdu();
+        let mut buf = vec![0u8; lldpdu.size().into_non_zero_usize().get()];
+        let bytes_written = lldpdu.deparse(&mut buf).unwrap();
+        assert_eq!(bytes_written, lldpdu.size());
+        // chassis id TLV: type 1, length 7, subtype 4 (mac address)
+        assert_eq!(buf[..3], [0x02, 0x07, 0x04]);
+        // end TLV
+        assert_eq!(buf[buf.len() - 2..], [0, 0]);
+
+        let (parsed, bytes_parsed) = Lldpdu::parse(&buf).unwrap();
+        assert_eq!(parsed, lldpdu);
+        assert_eq!(bytes_parsed, lldpdu.size());
+        assert_eq!(parsed.system_name(), Some("gateway-1"));
+        assert_eq!(parsed.port_description(), Some("uplink"));
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!().with_type().for_each(|slice: &[u8; 128]| {
+            let Ok((parsed, bytes_parsed)) = Lldpdu::parse(slice) else {
+                return;
+            };
+            let mut write_back_buffer = [0u8; 128];
+            let bytes_written = parsed
+                .deparse(&mut write_back_buffer)
+                .unwrap_or_else(|e| unreachable!("{e:?}"));
+            assert!(bytes_written <= bytes_parsed);
+            let (reparsed, _) = Lldpdu::parse(&write_back_buffer).unwrap();
+            assert_eq!(reparsed, parsed);
+        });
+    }
+
+    #[test]
+    fn parse_of_invalid_lldpdu_fails_gracefully() {
+        let mut buf = vec![0u8; lldpdu().size().into_non_zero_usize().get()];
+        lldpdu().deparse(&mut buf).unwrap();
+        // missing end TLV
+        assert!(matches!(
+            Lldpdu::parse(&buf[..buf.len() - 2]),
+            Err(ParseError::Length(_))
+        ));
+        // port id first
+        assert!(matches!(
+            Lldpdu::parse(&buf[9..]),
+            Err(ParseError::Invalid(LldpError::MissingTlv(1)))
+        ));
+        // value too long to be represented
+        let too_long = Lldpdu::new(
+            ChassisId::Local("x".repeat(Lldpdu::MAX_VALUE_LENGTH)),
+            PortId::Local("eth0".to_string()),
+            120,
+        );
+        let mut buf = vec![0u8; too_long.size().into_non_zero_usize().get()];
+        assert!(matches!(
+            too_long.deparse(&mut buf),
+            Err(DeParseError::Invalid(()))
+        ));
+    }
+
+    #[test]
+    fn lldp_frame() {
+        let lldpdu = lldpdu();
+        let mut payload = vec![0u8; lldpdu.size().into_non_zero_usize().get()];
+        lldpdu.deparse(&mut payload).unwrap();
+        let frame = PacketBuilder::new()
+            .eth(SOURCE, Lldpdu::DESTINATION)
+            .ether_type(EthType::LLDP)
+            .payload(payload)
+            .build_bytes()
+            .unwrap();
+        let packet = Packet::new(TestBuffer::from_raw_data(&frame)).unwrap();
+        assert_eq!(packet.try_eth().unwrap().ether_type(), EthType::LLDP);
+        let (parsed, _) = Lldpdu::parse(packet.payload().as_ref()).unwrap();
+        assert_eq!(parsed, lldpdu);
+    }
+}
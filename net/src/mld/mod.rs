@@ -0,0 +1,450 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [MLDv1][RFC2710] and [MLDv2][RFC3810] message types and parsing.
+//!
+//! MLD messages are ICMPv6 messages of types unknown to the [`Icmp6`] header, so they are parsed
+//! on demand from ICMPv6 packets (see [`Packet::mld`]).
+//! The checksum of MLD messages covers an IPv6 pseudo-header, so it is neither validated on
+//! parsing nor computed when writing a message: use the checksum methods of [`Icmp6`] instead.
+//!
+//! [`Icmp6`]: crate::icmp6::Icmp6
+//! [RFC2710]: https://datatracker.ietf.org/doc/html/rfc2710#section-3
+//! [RFC3810]: https://datatracker.ietf.org/doc/html/rfc3810#section-5
+
+use crate::buffer::PacketBufferMut;
+use crate::headers::TryIcmp6;
+use crate::igmp::{GroupAddress, GroupRecord, GroupRecordError, SourceQuery, decode_exponential};
+use crate::packet::Packet;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use core::num::NonZero;
+use etherparse::Icmpv6Type;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+use tracing::debug;
+
+/// An MLD multicast listener query.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MldQuery {
+    max_resp_code: u16,
+    group: Ipv6Addr,
+    v2: Option<SourceQuery<Ipv6Addr>>,
+}
+
+/// The version of an [`MldQuery`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MldVersion {
+    /// MLDv1 query (24 bytes)
+    V1,
+    /// MLDv2 query (28 bytes or more)
+    V2,
+}
+
+/// An MLD message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Mld {
+    /// Multicast listener query, of any version
+    Query(MldQuery),
+    /// MLDv1 multicast listener report, for the given group
+    V1Report(Ipv6Addr),
+    /// MLDv1 multicast listener done message, for the given group
+    Done(Ipv6Addr),
+    /// MLDv2 multicast listener report
+    V2Report(Vec<GroupRecord<Ipv6Addr>>),
+}
+
+/// Errors which may occur when parsing an [`Mld`] message.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MldError {
+    /// Not an MLD message type.
+    #[error("invalid MLD message type {0}")]
+    InvalidType(u8),
+    /// The length of the message is invalid for its type.
+    #[error("invalid MLD message length {0}")]
+    InvalidLength(usize),
+    /// A multicast address record of a report is invalid.
+    #[error(transparent)]
+    Record(#[from] GroupRecordError),
+}
+
+impl MldQuery {
+    /// Create a new query, of version 1 if `v2` is `None`, or of version 2 otherwise.
+    ///
+    /// `group` is the unspecified address for general queries.
+    #[must_use]
+    pub fn new(max_resp_code: u16, group: Ipv6Addr, v2: Option<SourceQuery<Ipv6Addr>>) -> MldQuery {
+        MldQuery {
+            max_resp_code,
+            group,
+            v2,
+        }
+    }
+
+    /// Get the version of this query.
+    #[must_use]
+    pub const fn version(&self) -> MldVersion {
+        match self.v2 {
+            Some(_) => MldVersion::V2,
+            None => MldVersion::V1,
+        }
+    }
+
+    /// Get the maximum response code of this query.
+    #[must_use]
+    pub const fn max_resp_code(&self) -> u16 {
+        self.max_resp_code
+    }
+
+    /// Get the maximum time allowed before sending a report, decoded from the maximum response
+    /// code.
+    #[must_use]
+    pub fn max_resp_time(&self) -> Duration {
+        let millis = match self.v2 {
+            Some(_) => decode_exponential(self.max_resp_code, 16),
+            None => u32::from(self.max_resp_code),
+        };
+        Duration::from_millis(u64::from(millis))
+    }
+
+    /// Get the group of a group-specific query, or the unspecified address for a general query.
+    #[must_use]
+    pub const fn group(&self) -> Ipv6Addr {
+        self.group
+    }
+
+    /// Tell if this is a general query (for all groups).
+    #[must_use]
+    pub fn is_general(&self) -> bool {
+        self.group.is_unspecified()
+    }
+
+    /// Get the fields specific to MLDv2 queries, if this is an MLDv2 query.
+    #[must_use]
+    pub const fn v2(&self) -> Option<&SourceQuery<Ipv6Addr>> {
+        self.v2.as_ref()
+    }
+}
+
+impl Mld {
+    /// ICMPv6 type of multicast listener queries
+    pub const TYPE_QUERY: u8 = 130;
+    /// ICMPv6 type of MLDv1 multicast listener reports
+    pub const TYPE_V1_REPORT: u8 = 131;
+    /// ICMPv6 type of MLDv1 multicast listener done messages
+    pub const TYPE_DONE: u8 = 132;
+    /// ICMPv6 type of MLDv2 multicast listener reports
+    pub const TYPE_V2_REPORT: u8 = 143;
+
+    /// The length of the shortest [`Mld`] messages (MLDv2 reports without records).
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(8).unwrap();
+
+    /// Length of MLDv1 messages, and offset of the source query fields of MLDv2 queries
+    const V1_LENGTH: usize = 24;
+
+    /// Tell if `icmp_type` is the ICMPv6 type of an MLD message.
+    #[must_use]
+    pub const fn is_mld_type(icmp_type: u8) -> bool {
+        matches!(
+            icmp_type,
+            Mld::TYPE_QUERY | Mld::TYPE_V1_REPORT | Mld::TYPE_DONE | Mld::TYPE_V2_REPORT
+        )
+    }
+
+    /// Get the ICMPv6 type of this message.
+    #[must_use]
+    pub const fn message_type(&self) -> u8 {
+        match self {
+            Mld::Query(_) => Mld::TYPE_QUERY,
+            Mld::V1Report(_) => Mld::TYPE_V1_REPORT,
+            Mld::Done(_) => Mld::TYPE_DONE,
+            Mld::V2Report(_) => Mld::TYPE_V2_REPORT,
+        }
+    }
+
+    /// Length of the message on the wire, in bytes
+    fn len(&self) -> usize {
+        match self {
+            Mld::Query(query) => Mld::V1_LENGTH + query.v2.as_ref().map_or(0, SourceQuery::len),
+            Mld::V1Report(_) | Mld::Done(_) => Mld::V1_LENGTH,
+            Mld::V2Report(records) => {
+                Mld::MIN_LENGTH.into_non_zero_usize().get()
+                    + records.iter().map(GroupRecord::len).sum::<usize>()
+            }
+        }
+    }
+
+    /// Returns true if the message can be written.
+    fn is_valid(&self) -> bool {
+        let valid = match self {
+            Mld::Query(query) => query
+                .v2
+                .as_ref()
+                .is_none_or(|v2| v2.sources().len() <= usize::from(u16::MAX)),
+            Mld::V2Report(records) => {
+                records.len() <= usize::from(u16::MAX) && records.iter().all(GroupRecord::is_valid)
+            }
+            Mld::V1Report(_) | Mld::Done(_) => true,
+        };
+        valid && self.len() <= usize::from(u16::MAX)
+    }
+}
+
+impl Parse for Mld {
+    type Error = MldError;
+
+    /// Parse an MLD message (starting with its ICMPv6 type) filling `buf`.
+    ///
+    /// The version of queries is determined by their length, so `buf` must not include any
+    /// padding following the message.
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() > u16::MAX as usize {
+            return Err(ParseError::BufferTooLong(buf.len()));
+        }
+        let min_len = Mld::MIN_LENGTH.into_non_zero_usize().get();
+        if buf.len() < min_len {
+            return Err(ParseError::Length(LengthError {
+                expected: Mld::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let invalid_length = || ParseError::Invalid(MldError::InvalidLength(buf.len()));
+        let group = || {
+            buf.get(8..Mld::V1_LENGTH)
+                .map(Ipv6Addr::read)
+                .ok_or_else(invalid_length)
+        };
+        let fixed_len = |mld: fn(Ipv6Addr) -> Mld| {
+            if buf.len() == Mld::V1_LENGTH {
+                Ok(mld(group()?))
+            } else {
+                Err(invalid_length())
+            }
+        };
+        let mld = match buf[0] {
+            Mld::TYPE_QUERY => {
+                let v2 = match buf.len() {
+                    Mld::V1_LENGTH => None,
+                    len if len > Mld::V1_LENGTH => Some(
+                        SourceQuery::parse(&buf[Mld::V1_LENGTH..]).ok_or_else(invalid_length)?,
+                    ),
+                    _ => return Err(invalid_length()),
+                };
+                Mld::Query(MldQuery {
+                    max_resp_code: u16::from_be_bytes([buf[4], buf[5]]),
+                    group: group()?,
+                    v2,
+                })
+            }
+            Mld::TYPE_V1_REPORT => fixed_len(Mld::V1Report)?,
+            Mld::TYPE_DONE => fixed_len(Mld::Done)?,
+            Mld::TYPE_V2_REPORT => {
+                let count = usize::from(u16::from_be_bytes([buf[6], buf[7]]));
+                let mut records = Vec::with_capacity(count.min(buf.len() / 20));
+                let mut rest = &buf[min_len..];
+                for _ in 0..count {
+                    let (record, len) =
+                        GroupRecord::parse(rest).map_err(|e| ParseError::Invalid(e.into()))?;
+                    records.push(record);
+                    rest = &rest[len..];
+                }
+                if !rest.is_empty() {
+                    return Err(invalid_length());
+                }
+                Mld::V2Report(records)
+            }
+            other => return Err(ParseError::Invalid(MldError::InvalidType(other))),
+        };
+        #[allow(clippy::cast_possible_truncation)] // buffer length checked above
+        let consumed = NonZero::new(buf.len() as u16).unwrap_or_else(|| unreachable!());
+        Ok((mld, consumed))
+    }
+}
+
+impl DeParse for Mld {
+    type Error = ();
+
+    /// The size of the message, or [`u16::MAX`] if it is too long to be written.
+    fn size(&self) -> NonZero<u16> {
+        let len = u16::try_from(self.len()).unwrap_or(u16::MAX);
+        NonZero::new(len).unwrap_or_else(|| unreachable!())
+    }
+
+    /// Write the message to `buf`, with a zero checksum.
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        if !self.is_valid() {
+            return Err(DeParseError::Invalid(()));
+        }
+        let size = self.size();
+        if buf.len() < size.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: size.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        buf[0] = self.message_type();
+        buf[1..8].fill(0);
+        match self {
+            Mld::Query(query) => {
+                buf[4..6].copy_from_slice(&query.max_resp_code.to_be_bytes());
+                query.group.write(&mut buf[8..]);
+                if let Some(v2) = &query.v2 {
+                    v2.write(&mut buf[Mld::V1_LENGTH..]);
+                }
+            }
+            Mld::V1Report(group) | Mld::Done(group) => group.write(&mut buf[8..]),
+            Mld::V2Report(records) => {
+                #[allow(clippy::cast_possible_truncation)] // checked by is_valid
+                let count = records.len() as u16;
+                buf[6..8].copy_from_slice(&count.to_be_bytes());
+                let mut offset = Mld::MIN_LENGTH.into_non_zero_usize().get();
+                for record in records {
+                    record.write(&mut buf[offset..]);
+                    offset += record.len();
+                }
+            }
+        }
+        Ok(size)
+    }
+}
+
+impl<Buf: PacketBufferMut> Packet<Buf> {
+    /// Parse this packet as an [`Mld`] message, if the packet is an ICMPv6 packet with an MLD
+    /// message type.
+    ///
+    /// Returns `None` if the packet is not an MLD packet, or if the message fails to parse.
+    #[must_use]
+    pub fn mld(&self) -> Option<Mld> {
+        let Icmpv6Type::Unknown {
+            type_u8,
+            code_u8,
+            bytes5to8,
+        } = self.try_icmp6()?.icmp_type()
+        else {
+            return None;
+        };
+        if !Mld::is_mld_type(type_u8) {
+            return None;
+        }
+        // rebuild the message, as the first bytes are held by the icmp6 header
+        let payload = self.payload().as_ref();
+        let mut buf = Vec::with_capacity(8 + payload.len());
+        buf.extend_from_slice(&[type_u8, code_u8, 0, 0]);
+        buf.extend_from_slice(&bytes5to8);
+        buf.extend_from_slice(payload);
+        Mld::parse(&buf)
+            .map_err(|e| debug!("failed to parse MLD message: {e:?}"))
+            .map(|(mld, _)| mld)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::igmp::{GroupRecord, GroupRecordError, GroupRecordType, SourceQuery};
+    use crate::mld::{Mld, MldError, MldQuery, MldVersion};
+    use crate::parse::{DeParse, IntoNonZeroUSize, Parse, ParseError};
+    use std::net::Ipv6Addr;
+    use std::time::Duration;
+
+    const GROUP: Ipv6Addr = Ipv6Addr::new(0xff05, 0, 0, 0, 0, 0, 0, 0x1234);
+    const SOURCE: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+
+    fn write(mld: &Mld) -> Vec<u8> {
+        let mut buf = vec![0u8; mld.size().into_non_zero_usize().get()];
+        assert_eq!(mld.deparse(&mut buf).unwrap(), mld.size());
+        buf
+    }
+
+    #[test]
+    fn v1_messages() {
+        let query = Mld::Query(MldQuery::new(10_000, Ipv6Addr::UNSPECIFIED, None));
+        let buf = write(&query);
+        assert_eq!(buf.len(), 24);
+        assert_eq!(buf[..6], [130, 0, 0, 0, 0x27, 0x10]);
+        let (parsed, _) = Mld::parse(&buf).unwrap();
+        assert_eq!(parsed, query);
+        let Mld::Query(parsed) = parsed else {
+            unreachable!()
+        };
+        assert_eq!(parsed.version(), MldVersion::V1);
+        assert!(parsed.is_general());
+        assert_eq!(parsed.max_resp_time(), Duration::from_secs(10));
+
+        for mld in [Mld::V1Report(GROUP), Mld::Done(GROUP)] {
+            let buf = write(&mld);
+            assert_eq!(buf.len(), 24);
+            assert_eq!(Mld::parse(&buf).unwrap().0, mld);
+        }
+    }
+
+    #[test]
+    fn v2_messages() {
+        let query = Mld::Query(MldQuery::new(
+            0x8000,
+            GROUP,
+            Some(SourceQuery::new(true, 2, 125, vec![SOURCE])),
+        ));
+        let buf = write(&query);
+        assert_eq!(buf.len(), 28 + 16);
+        let (parsed, _) = Mld::parse(&buf).unwrap();
+        assert_eq!(parsed, query);
+        let Mld::Query(parsed) = parsed else {
+            unreachable!()
+        };
+        assert_eq!(parsed.version(), MldVersion::V2);
+        // mantissa 0, exponent 0: 0x1000 << 3 milliseconds
+        assert_eq!(parsed.max_resp_time(), Duration::from_millis(32_768));
+        assert!(parsed.v2().unwrap().suppress_router_processing());
+
+        let report = Mld::V2Report(vec![
+            GroupRecord::new(GroupRecordType::ChangeToExclude, GROUP, vec![]),
+            GroupRecord::new(GroupRecordType::BlockOldSources, GROUP, vec![SOURCE]),
+        ]);
+        let buf = write(&report);
+        assert_eq!(buf.len(), 8 + 20 + 36);
+        assert_eq!(Mld::parse(&buf).unwrap().0, report);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let buf = write(&Mld::Done(GROUP));
+        assert!(matches!(Mld::parse(&buf[..7]), Err(ParseError::Length(_))));
+        assert!(matches!(
+            Mld::parse(&buf[..20]),
+            Err(ParseError::Invalid(MldError::InvalidLength(20)))
+        ));
+        let mut bad = buf.clone();
+        bad[0] = 135;
+        assert!(matches!(
+            Mld::parse(&bad),
+            Err(ParseError::Invalid(MldError::InvalidType(135)))
+        ));
+        let report = Mld::V2Report(vec![GroupRecord::new(
+            GroupRecordType::ModeIsInclude,
+            GROUP,
+            vec![SOURCE],
+        )]);
+        let buf = write(&report);
+        assert!(matches!(
+            Mld::parse(&buf[..buf.len() - 1]),
+            Err(ParseError::Invalid(MldError::Record(
+                GroupRecordError::Truncated
+            )))
+        ));
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!().with_type().for_each(|slice: &[u8; 48]| {
+            for len in [24, 28, 44, 48] {
+                let Ok((parsed, _)) = Mld::parse(&slice[..len]) else {
+                    continue;
+                };
+                // reserved fields are not preserved
+                let (reparsed, _) = Mld::parse(&write(&parsed)).unwrap();
+                assert_eq!(reparsed, parsed);
+            }
+        });
+    }
+}
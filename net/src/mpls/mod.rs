@@ -0,0 +1,423 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! MPLS labels and label stack entries.
+
+use crate::eth::ethtype::EthType;
+use crate::eth::{EthNext, parse_from_ethertype};
+use crate::parse::{
+    DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError, Reader,
+};
+#[allow(unused_imports)] // conditional re-export
+#[cfg(any(test, feature = "bolero"))]
+pub use contract::*;
+use core::convert::Infallible;
+use core::fmt::{Debug, Display, Formatter};
+use core::num::NonZero;
+use tracing::trace;
+
+/// An [MPLS][RFC3032] label.
+///
+/// A label is a 20-bit value carried in a label stack entry.
+/// Values `0` through `15` are reserved by <cite>[RFC3032]</cite> for special purposes (explicit
+/// null, router alert, entropy label indicator, ...).
+/// They are legal on the wire and are therefore accepted by [`MplsLabel::new`], but see
+/// [`MplsLabel::is_reserved`].
+///
+/// [RFC3032]: https://datatracker.ietf.org/doc/html/rfc3032#section-2.1
+#[derive(
+    Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+#[serde(try_from = "u32", into = "u32")]
+#[repr(transparent)]
+pub struct MplsLabel(u32);
+
+/// Errors that can occur when converting a `u32` to an [`MplsLabel`]
+#[must_use]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, thiserror::Error, serde::Deserialize, serde::Serialize,
+)]
+pub enum InvalidMplsLabel {
+    /// The value does not fit in 20 bits.
+    #[error("The value {0} is too large to be an MPLS label (max is {MAX})", MAX = MplsLabel::MAX)]
+    TooLarge(u32),
+}
+
+impl MplsLabel {
+    /// The maximum legal [`MplsLabel`] value (2<sup>20</sup> - 1).
+    pub const MAX: u32 = 0x000F_FFFF;
+    /// The first [`MplsLabel`] value which is not reserved for special purposes.
+    pub const MIN_UNRESERVED: u32 = 16;
+
+    /// Create a new [`MplsLabel`] from a `u32`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidMplsLabel`] error if the value is greater than [`MplsLabel::MAX`].
+    pub const fn new(label: u32) -> Result<MplsLabel, InvalidMplsLabel> {
+        if label > MplsLabel::MAX {
+            Err(InvalidMplsLabel::TooLarge(label))
+        } else {
+            Ok(MplsLabel(label))
+        }
+    }
+
+    /// Get the value of the [`MplsLabel`] as a `u32`.
+    #[must_use]
+    pub const fn as_u32(self) -> u32 {
+        self.0
+    }
+
+    /// Returns true if the label is one of the special-purpose labels (`0` to `15`).
+    #[must_use]
+    pub const fn is_reserved(self) -> bool {
+        self.0 < MplsLabel::MIN_UNRESERVED
+    }
+}
+
+impl Display for MplsLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Debug for MplsLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl From<MplsLabel> for u32 {
+    fn from(label: MplsLabel) -> u32 {
+        label.as_u32()
+    }
+}
+
+impl TryFrom<u32> for MplsLabel {
+    type Error = InvalidMplsLabel;
+
+    fn try_from(label: u32) -> Result<MplsLabel, Self::Error> {
+        MplsLabel::new(label)
+    }
+}
+
+/// Errors that can occur when setting the traffic class of an [`Mpls`] label stack entry
+#[must_use]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("The value {0} is too large to be an MPLS traffic class (max is 7)")]
+pub struct InvalidMplsTc(pub u8);
+
+/// An [MPLS label stack entry][RFC3032].
+///
+/// Labeled packets carry a stack of such entries (outermost first) between the ethernet header
+/// ([`EthType::MPLS`]) and the payload.
+/// The payload of the bottom of stack entry is expected to be an IPv4 or IPv6 packet, which is
+/// told apart by the version nibble of its first byte.
+///
+/// [RFC3032]: https://datatracker.ietf.org/doc/html/rfc3032#section-2.1
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Mpls {
+    label: MplsLabel,
+    tc: u8,
+    bos: bool,
+    ttl: u8,
+}
+
+impl Mpls {
+    /// The length of a label stack entry
+    #[allow(clippy::unwrap_used)] // safe due to const eval
+    pub const LENGTH: NonZero<u16> = NonZero::new(4).unwrap();
+    /// The maximum legal traffic class (3 bits)
+    pub const MAX_TC: u8 = 7;
+
+    /// Create a new label stack entry with the given label and ttl, a traffic class of 0 and the
+    /// bottom of stack bit cleared.
+    #[must_use]
+    pub const fn new(label: MplsLabel, ttl: u8) -> Mpls {
+        Mpls {
+            label,
+            tc: 0,
+            bos: false,
+            ttl,
+        }
+    }
+
+    /// Get the label of this entry
+    #[must_use]
+    pub const fn label(&self) -> MplsLabel {
+        self.label
+    }
+
+    /// Get the traffic class of this entry
+    #[must_use]
+    pub const fn tc(&self) -> u8 {
+        self.tc
+    }
+
+    /// Returns true if this entry is the bottom of the label stack
+    #[must_use]
+    pub const fn bos(&self) -> bool {
+        self.bos
+    }
+
+    /// Get the time to live of this entry
+    #[must_use]
+    pub const fn ttl(&self) -> u8 {
+        self.ttl
+    }
+
+    /// Set the label of this entry
+    pub fn set_label(&mut self, label: MplsLabel) -> &mut Self {
+        self.label = label;
+        self
+    }
+
+    /// Set the traffic class of this entry
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidMplsTc`] error if `tc` is greater than [`Mpls::MAX_TC`].
+    pub fn set_tc(&mut self, tc: u8) -> Result<&mut Self, InvalidMplsTc> {
+        if tc > Mpls::MAX_TC {
+            return Err(InvalidMplsTc(tc));
+        }
+        self.tc = tc;
+        Ok(self)
+    }
+
+    /// Set or clear the bottom of stack bit of this entry
+    pub fn set_bos(&mut self, bos: bool) -> &mut Self {
+        self.bos = bos;
+        self
+    }
+
+    /// Set the time to live of this entry
+    pub fn set_ttl(&mut self, ttl: u8) -> &mut Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Parse the header following this entry: another entry if this one is not the bottom of
+    /// the stack, or the IP packet identified by its version nibble otherwise.
+    pub(crate) fn parse_payload(&self, cursor: &mut Reader) -> Option<EthNext> {
+        if !self.bos {
+            return cursor
+                .parse::<Mpls>()
+                .map_err(|e| {
+                    trace!("failed to parse mpls label stack entry: {e:?}");
+                })
+                .map(|(mpls, _)| EthNext::Mpls(mpls))
+                .ok();
+        }
+        let first = cursor
+            .inner
+            .get(cursor.inner.len() - cursor.remaining as usize)?;
+        match first >> 4 {
+            4 => parse_from_ethertype(EthType::IPV4.0, cursor),
+            6 => parse_from_ethertype(EthType::IPV6.0, cursor),
+            version => {
+                trace!("unsupported payload below mpls label stack (version nibble {version})");
+                None
+            }
+        }
+    }
+}
+
+impl Parse for Mpls {
+    type Error = Infallible;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        let Some(bytes) = buf.first_chunk::<4>() else {
+            return Err(ParseError::Length(LengthError {
+                expected: Mpls::LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        };
+        let raw = u32::from_be_bytes(*bytes);
+        let mpls = Mpls {
+            label: MplsLabel(raw >> 12),
+            #[allow(clippy::cast_possible_truncation)] // masked to 3 bits
+            tc: ((raw >> 9) & 0x7) as u8,
+            bos: raw & 0x100 != 0,
+            ttl: bytes[3],
+        };
+        Ok((mpls, Mpls::LENGTH))
+    }
+}
+
+impl DeParse for Mpls {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        Mpls::LENGTH
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let Some(bytes) = buf.first_chunk_mut::<4>() else {
+            return Err(DeParseError::Length(LengthError {
+                expected: Mpls::LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        };
+        let raw = (self.label.0 << 12)
+            | (u32::from(self.tc) << 9)
+            | (u32::from(self.bos) << 8)
+            | u32::from(self.ttl);
+        *bytes = raw.to_be_bytes();
+        Ok(Mpls::LENGTH)
+    }
+}
+
+#[cfg(any(test, feature = "bolero"))]
+mod contract {
+    use crate::mpls::{Mpls, MplsLabel};
+    use bolero::{Driver, TypeGenerator};
+
+    impl TypeGenerator for MplsLabel {
+        fn generate<D: Driver>(u: &mut D) -> Option<Self> {
+            let raw = u.produce::<u32>()? & MplsLabel::MAX;
+            Some(MplsLabel::new(raw).unwrap_or_else(|e| unreachable!("{e:?}")))
+        }
+    }
+
+    impl TypeGenerator for Mpls {
+        fn generate<D: Driver>(u: &mut D) -> Option<Self> {
+            let mut mpls = Mpls::new(u.produce()?, u.produce()?);
+            mpls.set_tc(u.produce::<u8>()? & Mpls::MAX_TC)
+                .ok()?
+                .set_bos(u.produce()?);
+            Some(mpls)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn zero_is_a_reserved_label() {
+        let label = MplsLabel::new(0).unwrap();
+        assert!(label.is_reserved());
+    }
+
+    #[test]
+    fn min_unreserved_is_not_reserved() {
+        let label = MplsLabel::new(MplsLabel::MIN_UNRESERVED).unwrap();
+        assert!(!label.is_reserved());
+    }
+
+    #[test]
+    fn max_is_a_legal_label() {
+        assert_eq!(
+            MplsLabel::new(MplsLabel::MAX).unwrap().as_u32(),
+            MplsLabel::MAX
+        );
+    }
+
+    #[test]
+    fn max_plus_one_is_not_a_legal_label() {
+        assert_eq!(
+            MplsLabel::new(MplsLabel::MAX + 1).unwrap_err(),
+            InvalidMplsLabel::TooLarge(MplsLabel::MAX + 1)
+        );
+    }
+
+    #[test]
+    fn arbitrary_value_complies_with_contract() {
+        bolero::check!()
+            .with_type()
+            .cloned()
+            .for_each(|label: MplsLabel| {
+                assert!(label.as_u32() <= MplsLabel::MAX);
+            });
+    }
+
+    #[test]
+    fn parse_back() {
+        bolero::check!().with_type().for_each(|mpls: &Mpls| {
+            let mut buf = [0u8; 4];
+            let bytes_written = mpls.deparse(&mut buf).unwrap_or_else(|_| unreachable!());
+            assert_eq!(bytes_written, Mpls::LENGTH);
+            let (parsed, bytes_parsed) = Mpls::parse(&buf).unwrap();
+            assert_eq!(parsed, *mpls);
+            assert_eq!(bytes_parsed, Mpls::LENGTH);
+        });
+    }
+
+    #[test]
+    fn label_stack_entry_layout() {
+        let mut mpls = Mpls::new(MplsLabel::new(0x12345).unwrap(), 64);
+        mpls.set_tc(5).unwrap().set_bos(true);
+        let mut buf = [0u8; 4];
+        mpls.deparse(&mut buf).unwrap();
+        assert_eq!(buf, [0x12, 0x34, 0x5b, 64]);
+        assert_eq!(mpls.set_tc(Mpls::MAX_TC + 1), Err(InvalidMplsTc(8)));
+        assert!(Mpls::parse(&buf[..3]).is_err());
+    }
+
+    #[test]
+    fn parse_push_and_pop_label_stack() {
+        use crate::buffer::TestBuffer;
+        use crate::eth::Eth;
+        use crate::eth::mac::{DestinationMac, Mac, SourceMac};
+        use crate::headers::TryIpv4;
+        use crate::packet::Packet;
+        use etherparse::{IpNumber, Ipv4Header, UdpHeader};
+
+        let label = |value| MplsLabel::new(value).unwrap();
+        let eth = |ethtype| {
+            Eth::new(
+                SourceMac::new(Mac([2, 0, 0, 0, 0, 1])).unwrap(),
+                DestinationMac::new(Mac([2, 0, 0, 0, 0, 2])).unwrap(),
+                ethtype,
+            )
+        };
+        let udp = UdpHeader {
+            source_port: 1234,
+            destination_port: 5678,
+            length: 8,
+            checksum: 0,
+        };
+        let ip = Ipv4Header::new(8, 64, IpNumber::UDP, [10, 0, 0, 1], [10, 0, 0, 2]).unwrap();
+        let mut inner = Mpls::new(label(200), 63);
+        inner.set_bos(true);
+        let outer = Mpls::new(label(100), 62);
+
+        // a frame with two labels
+        let mut frame = vec![0u8; 14 + 8];
+        eth(EthType::MPLS).deparse(&mut frame).unwrap();
+        outer.deparse(&mut frame[14..]).unwrap();
+        inner.deparse(&mut frame[18..]).unwrap();
+        ip.write(&mut frame).unwrap();
+        udp.write(&mut frame).unwrap();
+
+        // the same frame without labels
+        let mut unlabeled = vec![0u8; 14];
+        eth(EthType::IPV4).deparse(&mut unlabeled).unwrap();
+        ip.write(&mut unlabeled).unwrap();
+        udp.write(&mut unlabeled).unwrap();
+
+        let mut packet = Packet::new(TestBuffer::from_raw_data(&frame)).unwrap();
+        assert_eq!(packet.mpls_labels(), &[outer, inner]);
+        assert_eq!(
+            packet.try_ipv4().unwrap().destination(),
+            std::net::Ipv4Addr::new(10, 0, 0, 2)
+        );
+
+        // pop both labels
+        assert_eq!(packet.pop_mpls().unwrap(), Some(outer));
+        assert_eq!(packet.pop_mpls().unwrap(), Some(inner));
+        assert_eq!(packet.pop_mpls().unwrap(), None);
+        let mut buf = vec![0u8; unlabeled.len()];
+        packet.get_headers().deparse(&mut buf).unwrap();
+        assert_eq!(buf, unlabeled);
+
+        // push them back
+        packet.push_mpls(label(200), 63).unwrap();
+        packet.push_mpls(label(100), 62).unwrap();
+        let mut buf = vec![0u8; frame.len()];
+        packet.get_headers().deparse(&mut buf).unwrap();
+        assert_eq!(buf, frame);
+    }
+}
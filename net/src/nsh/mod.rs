@@ -0,0 +1,648 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [Network service header][RFC8300] (NSH) types and parsing.
+//!
+//! The NSH carries the service function path of a packet (its service path identifier and
+//! service index) along with optional metadata, so that service function chaining information can
+//! travel between dataplane instances, over ethernet ([`EthType::NSH`]) or in
+//! [`VxlanGpe`](crate::vxlan::VxlanGpe) tunnels.
+//!
+//! [RFC8300]: https://datatracker.ietf.org/doc/html/rfc8300#section-2
+
+use crate::eth::ethtype::EthType;
+use crate::eth::{EthNext, parse_from_ethertype};
+use crate::parse::{
+    DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError, Reader,
+};
+use core::fmt::{Display, Formatter};
+use core::num::NonZero;
+use tracing::trace;
+
+/// The protocol of the payload of an [`Nsh`] header.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+pub struct NshNextProtocol(pub u8);
+
+impl NshNextProtocol {
+    /// IPv4 payload
+    pub const IPV4: NshNextProtocol = NshNextProtocol(1);
+    /// IPv6 payload
+    pub const IPV6: NshNextProtocol = NshNextProtocol(2);
+    /// Ethernet payload
+    pub const ETHERNET: NshNextProtocol = NshNextProtocol(3);
+    /// NSH payload
+    pub const NSH: NshNextProtocol = NshNextProtocol(4);
+    /// MPLS payload
+    pub const MPLS: NshNextProtocol = NshNextProtocol(5);
+
+    /// Get the [`EthType`] matching this protocol, if known.
+    #[must_use]
+    pub const fn ethtype(self) -> Option<EthType> {
+        match self {
+            NshNextProtocol::IPV4 => Some(EthType::IPV4),
+            NshNextProtocol::IPV6 => Some(EthType::IPV6),
+            NshNextProtocol::ETHERNET => Some(EthType::TRANSPARENT_ETHERNET_BRIDGING),
+            NshNextProtocol::NSH => Some(EthType::NSH),
+            NshNextProtocol::MPLS => Some(EthType::MPLS),
+            _ => None,
+        }
+    }
+}
+
+/// A service path identifier, the 24-bit identifier of a service function path.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct ServicePathId(u32);
+
+impl ServicePathId {
+    /// The maximum legal [`ServicePathId`] value (2<sup>24</sup> - 1).
+    pub const MAX: u32 = 0x00ff_ffff;
+
+    /// Create a new [`ServicePathId`].
+    ///
+    /// Returns `None` if `spi` does not fit in 24 bits.
+    #[must_use]
+    pub const fn new(spi: u32) -> Option<ServicePathId> {
+        if spi > ServicePathId::MAX {
+            return None;
+        }
+        Some(ServicePathId(spi))
+    }
+
+    /// Get the value of this [`ServicePathId`]
+    #[must_use]
+    pub const fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl Display for ServicePathId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A variable-length context header (TLV) of an [`NshContext::Md2`] context.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NshTlv {
+    /// The scope of `tlv_type`
+    pub class: u16,
+    /// The type of the metadata
+    pub tlv_type: u8,
+    /// The metadata, of at most [`NshTlv::MAX_VALUE_LENGTH`] bytes
+    pub value: Vec<u8>,
+}
+
+impl NshTlv {
+    /// The maximum length of the value of a context header (7 bits)
+    pub const MAX_VALUE_LENGTH: usize = 127;
+
+    /// Length of the header of a TLV
+    const HEADER_LENGTH: usize = 4;
+
+    /// The length of this context header on the wire, value padding included.
+    fn size(&self) -> usize {
+        NshTlv::HEADER_LENGTH + self.value.len().next_multiple_of(4)
+    }
+}
+
+/// The context headers of an [`Nsh`] header, determined by its metadata (MD) type.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NshContext {
+    /// MD type 1: a fixed-length context header of 16 bytes
+    Md1([u8; 16]),
+    /// MD type 2: variable-length context headers, possibly none
+    Md2(Vec<NshTlv>),
+    /// Any other MD type, with its context headers carried as is
+    Other {
+        /// The MD type (4 bits)
+        md_type: u8,
+        /// The raw context headers, a multiple of 4 bytes long
+        context: Vec<u8>,
+    },
+}
+
+impl NshContext {
+    const MD_TYPE_1: u8 = 1;
+    const MD_TYPE_2: u8 = 2;
+
+    /// Get the metadata type of this context.
+    #[must_use]
+    pub const fn md_type(&self) -> u8 {
+        match self {
+            NshContext::Md1(_) => NshContext::MD_TYPE_1,
+            NshContext::Md2(_) => NshContext::MD_TYPE_2,
+            NshContext::Other { md_type, .. } => *md_type,
+        }
+    }
+
+    /// The length of these context headers on the wire.
+    fn size(&self) -> usize {
+        match self {
+            NshContext::Md1(context) => context.len(),
+            NshContext::Md2(tlvs) => tlvs.iter().map(NshTlv::size).sum(),
+            NshContext::Other { context, .. } => context.len(),
+        }
+    }
+
+    /// Check that these context headers can be represented on the wire.
+    fn validate(&self) -> Result<(), NshError> {
+        let valid = match self {
+            NshContext::Md1(_) => true,
+            NshContext::Md2(tlvs) => tlvs
+                .iter()
+                .all(|tlv| tlv.value.len() <= NshTlv::MAX_VALUE_LENGTH),
+            NshContext::Other { md_type, context } => {
+                *md_type <= 0x0f
+                    && *md_type != NshContext::MD_TYPE_1
+                    && *md_type != NshContext::MD_TYPE_2
+                    && context.len().is_multiple_of(4)
+            }
+        };
+        if !valid || Nsh::BASE_LENGTH + self.size() > Nsh::MAX_LENGTH {
+            return Err(NshError::InvalidContext);
+        }
+        Ok(())
+    }
+
+    /// Parse the variable-length context headers of `buf`.
+    fn parse_tlvs(mut buf: &[u8]) -> Result<Vec<NshTlv>, NshError> {
+        let mut tlvs = Vec::new();
+        while !buf.is_empty() {
+            let Some(header) = buf.get(..NshTlv::HEADER_LENGTH) else {
+                return Err(NshError::InvalidContext);
+            };
+            let len = usize::from(header[3] & 0x7f);
+            let end = NshTlv::HEADER_LENGTH + len.next_multiple_of(4);
+            let Some(value) = buf.get(NshTlv::HEADER_LENGTH..NshTlv::HEADER_LENGTH + len) else {
+                return Err(NshError::InvalidContext);
+            };
+            tlvs.push(NshTlv {
+                class: u16::from_be_bytes([header[0], header[1]]),
+                tlv_type: header[2],
+                value: value.to_vec(),
+            });
+            buf = buf.get(end..).ok_or(NshError::InvalidContext)?;
+        }
+        Ok(tlvs)
+    }
+}
+
+/// A [network service header][RFC8300].
+///
+/// [RFC8300]: https://datatracker.ietf.org/doc/html/rfc8300#section-2
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Nsh {
+    oam: bool,
+    ttl: u8,
+    spi: ServicePathId,
+    service_index: u8,
+    next_protocol: NshNextProtocol,
+    context: NshContext,
+}
+
+/// Errors which may occur when parsing or building an [`Nsh`] header.
+#[derive(Debug, thiserror::Error)]
+pub enum NshError {
+    /// Only version 0 of NSH is defined.
+    #[error("unsupported NSH version {0}")]
+    UnsupportedVersion(u8),
+    /// The length field is too small for the header, or does not match its MD type.
+    #[error("invalid NSH length {length} for MD type {md_type}")]
+    InvalidLength {
+        /// The length field, in units of 4 bytes
+        length: u8,
+        /// The MD type of the header
+        md_type: u8,
+    },
+    /// The context headers are malformed, or can't be represented on the wire.
+    #[error("invalid NSH context headers")]
+    InvalidContext,
+    /// The TTL does not fit in 6 bits.
+    #[error("invalid NSH TTL {0}")]
+    InvalidTtl(u8),
+}
+
+impl Nsh {
+    /// The minimum length of an [`Nsh`] header: base and service path headers, with no context
+    /// header (MD type 2).
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(8).unwrap();
+
+    /// The maximum TTL of an [`Nsh`] header (6 bits), which is also the default TTL.
+    pub const MAX_TTL: u8 = 63;
+
+    /// Length of the base and service path headers
+    const BASE_LENGTH: usize = 8;
+
+    /// Maximum length of a header (6 bits length field, in units of 4 bytes)
+    const MAX_LENGTH: usize = 0x3f * 4;
+
+    /// Create a new [`Nsh`] header for service path `spi` at service index `service_index`, with no
+    /// context header (MD type 2).
+    #[must_use]
+    pub const fn new(spi: ServicePathId, service_index: u8, next_protocol: NshNextProtocol) -> Nsh {
+        Nsh {
+            oam: false,
+            ttl: Nsh::MAX_TTL,
+            spi,
+            service_index,
+            next_protocol,
+            context: NshContext::Md2(Vec::new()),
+        }
+    }
+
+    /// Tell if the packet is an OAM packet.
+    #[must_use]
+    pub const fn oam(&self) -> bool {
+        self.oam
+    }
+
+    /// Set the OAM flag of this header.
+    pub const fn set_oam(&mut self, oam: bool) -> &mut Nsh {
+        self.oam = oam;
+        self
+    }
+
+    /// Get the number of service function forwarder hops the packet may still go through.
+    #[must_use]
+    pub const fn ttl(&self) -> u8 {
+        self.ttl
+    }
+
+    /// Set the TTL of this header.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NshError::InvalidTtl`] if `ttl` exceeds [`Nsh::MAX_TTL`].
+    pub fn set_ttl(&mut self, ttl: u8) -> Result<&mut Nsh, NshError> {
+        if ttl > Nsh::MAX_TTL {
+            return Err(NshError::InvalidTtl(ttl));
+        }
+        self.ttl = ttl;
+        Ok(self)
+    }
+
+    /// Get the service path identifier of this header.
+    #[must_use]
+    pub const fn spi(&self) -> ServicePathId {
+        self.spi
+    }
+
+    /// Set the service path identifier of this header.
+    pub const fn set_spi(&mut self, spi: ServicePathId) -> &mut Nsh {
+        self.spi = spi;
+        self
+    }
+
+    /// Get the service index of this header, the location of the packet in its service path.
+    #[must_use]
+    pub const fn service_index(&self) -> u8 {
+        self.service_index
+    }
+
+    /// Set the service index of this header.
+    pub const fn set_service_index(&mut self, service_index: u8) -> &mut Nsh {
+        self.service_index = service_index;
+        self
+    }
+
+    /// Get the protocol of the payload of this header.
+    #[must_use]
+    pub const fn next_protocol(&self) -> NshNextProtocol {
+        self.next_protocol
+    }
+
+    /// Set the protocol of the payload of this header.
+    pub const fn set_next_protocol(&mut self, next_protocol: NshNextProtocol) -> &mut Nsh {
+        self.next_protocol = next_protocol;
+        self
+    }
+
+    /// Get the context headers of this header.
+    #[must_use]
+    pub const fn context(&self) -> &NshContext {
+        &self.context
+    }
+
+    /// Set the context headers of this header.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NshError::InvalidContext`] if the context headers can't be represented on the
+    /// wire (value of a TLV too long, header too long, ...).
+    pub fn set_context(&mut self, context: NshContext) -> Result<&mut Nsh, NshError> {
+        context.validate()?;
+        self.context = context;
+        Ok(self)
+    }
+
+    /// Parse the payload of this header.
+    ///
+    /// Only IPv4 and IPv6 payloads are parsed: other payloads (ethernet frames in particular) are
+    /// left in the payload of the packet.
+    pub(crate) fn parse_payload(&self, cursor: &mut Reader) -> Option<EthNext> {
+        match self.next_protocol {
+            NshNextProtocol::IPV4 => parse_from_ethertype(EthType::IPV4.0, cursor),
+            NshNextProtocol::IPV6 => parse_from_ethertype(EthType::IPV6.0, cursor),
+            NshNextProtocol::MPLS => parse_from_ethertype(EthType::MPLS.0, cursor),
+            _ => None,
+        }
+    }
+}
+
+impl Parse for Nsh {
+    type Error = NshError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < Nsh::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Nsh::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let version = buf[0] >> 6;
+        if version != 0 {
+            trace!("Received NSH header with version {version}");
+            return Err(ParseError::Invalid(NshError::UnsupportedVersion(version)));
+        }
+        let length = buf[1] & 0x3f;
+        let md_type = buf[2] & 0x0f;
+        let size = usize::from(length) * 4;
+        let invalid_length = ParseError::Invalid(NshError::InvalidLength { length, md_type });
+        if size < Nsh::BASE_LENGTH {
+            return Err(invalid_length);
+        }
+        let Some(context) = buf.get(Nsh::BASE_LENGTH..size) else {
+            return Err(ParseError::Length(LengthError {
+                expected: NonZero::new(size).unwrap_or_else(|| unreachable!()),
+                actual: buf.len(),
+            }));
+        };
+        let context = match md_type {
+            NshContext::MD_TYPE_1 => {
+                NshContext::Md1(context.try_into().map_err(|_| invalid_length)?)
+            }
+            NshContext::MD_TYPE_2 => {
+                NshContext::Md2(NshContext::parse_tlvs(context).map_err(ParseError::Invalid)?)
+            }
+            _ => NshContext::Other {
+                md_type,
+                context: context.to_vec(),
+            },
+        };
+        let spi = ServicePathId(u32::from_be_bytes([0, buf[4], buf[5], buf[6]]));
+        let header = Nsh {
+            oam: buf[0] & 0x20 != 0,
+            ttl: ((buf[0] & 0x0f) << 2) | (buf[1] >> 6),
+            spi,
+            service_index: buf[7],
+            next_protocol: NshNextProtocol(buf[3]),
+            context,
+        };
+        #[allow(clippy::cast_possible_truncation)] // at most 252 bytes
+        let consumed = NonZero::new(size as u16).unwrap_or_else(|| unreachable!());
+        Ok((header, consumed))
+    }
+}
+
+impl DeParse for Nsh {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        #[allow(clippy::cast_possible_truncation)] // bounded by the length field on validation
+        NonZero::new((Nsh::BASE_LENGTH + self.context.size()) as u16)
+            .unwrap_or_else(|| unreachable!())
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let size = self.size();
+        let len = size.into_non_zero_usize().get();
+        if buf.len() < len {
+            return Err(DeParseError::Length(LengthError {
+                expected: size.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        if self.context.validate().is_err() || self.ttl > Nsh::MAX_TTL {
+            return Err(DeParseError::Invalid(()));
+        }
+        #[allow(clippy::cast_possible_truncation)] // checked on validation
+        let length = (len / 4) as u8;
+        buf[0] = (u8::from(self.oam) << 5) | (self.ttl >> 2);
+        buf[1] = (self.ttl << 6) | length;
+        buf[2] = self.context.md_type();
+        buf[3] = self.next_protocol.0;
+        buf[4..7].copy_from_slice(&self.spi.0.to_be_bytes()[1..]);
+        buf[7] = self.service_index;
+        match &self.context {
+            NshContext::Md1(context) => buf[8..len].copy_from_slice(context),
+            NshContext::Other { context, .. } => buf[8..len].copy_from_slice(context),
+            NshContext::Md2(tlvs) => {
+                let mut offset = Nsh::BASE_LENGTH;
+                for tlv in tlvs {
+                    let end = offset + tlv.size();
+                    buf[offset..offset + 2].copy_from_slice(&tlv.class.to_be_bytes());
+                    buf[offset + 2] = tlv.tlv_type;
+                    #[allow(clippy::cast_possible_truncation)] // checked on validation
+                    let value_len = tlv.value.len() as u8;
+                    buf[offset + 3] = value_len;
+                    let value_start = offset + NshTlv::HEADER_LENGTH;
+                    buf[value_start..value_start + tlv.value.len()].copy_from_slice(&tlv.value);
+                    buf[value_start + tlv.value.len()..end].fill(0);
+                    offset = end;
+                }
+            }
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(any(test, feature = "bolero"))]
+mod contract {
+    use crate::nsh::{Nsh, NshContext, NshTlv, ServicePathId};
+    use bolero::{Driver, TypeGenerator};
+
+    impl TypeGenerator for ServicePathId {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            ServicePathId::new(driver.produce::<u32>()? & ServicePathId::MAX)
+        }
+    }
+
+    impl TypeGenerator for NshTlv {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            let len = usize::from(driver.produce::<u8>()? % 16);
+            let mut value = Vec::with_capacity(len);
+            for _ in 0..len {
+                value.push(driver.produce()?);
+            }
+            Some(NshTlv {
+                class: driver.produce()?,
+                tlv_type: driver.produce()?,
+                value,
+            })
+        }
+    }
+
+    impl TypeGenerator for NshContext {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            if driver.produce()? {
+                return Some(NshContext::Md1(driver.produce()?));
+            }
+            let mut tlvs = Vec::new();
+            for _ in 0..driver.produce::<u8>()? % 4 {
+                tlvs.push(driver.produce()?);
+            }
+            Some(NshContext::Md2(tlvs))
+        }
+    }
+
+    impl TypeGenerator for Nsh {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            let mut nsh = Nsh::new(driver.produce()?, driver.produce()?, driver.produce()?);
+            nsh.set_oam(driver.produce()?)
+                .set_ttl(driver.produce::<u8>()? % (Nsh::MAX_TTL + 1))
+                .ok()?
+                .set_context(driver.produce()?)
+                .ok()?;
+            Some(nsh)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::eth::Eth;
+    use crate::eth::ethtype::EthType;
+    use crate::eth::mac::{DestinationMac, Mac, SourceMac};
+    use crate::headers::{TryIpv4, TryNsh, TryUdp};
+    use crate::nsh::{Nsh, NshContext, NshError, NshNextProtocol, NshTlv, ServicePathId};
+    use crate::packet::Packet;
+    use crate::parse::{DeParse, IntoNonZeroUSize, Parse, ParseError};
+    use etherparse::{IpNumber, Ipv4Header, UdpHeader};
+
+    const MAX_LENGTH_USIZE: usize = 252;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!().with_type().for_each(|nsh: &Nsh| {
+            let mut buf = [0u8; MAX_LENGTH_USIZE];
+            let bytes_written = nsh.deparse(&mut buf).unwrap_or_else(|_| unreachable!());
+            assert_eq!(bytes_written, nsh.size());
+            let (parsed, bytes_parsed) = Nsh::parse(&buf).unwrap();
+            assert_eq!(parsed, *nsh);
+            assert_eq!(bytes_parsed, nsh.size());
+        });
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; MAX_LENGTH_USIZE]| {
+                let Ok((parsed, bytes_parsed)) = Nsh::parse(slice) else {
+                    return;
+                };
+                let mut write_back_buffer = [0u8; MAX_LENGTH_USIZE];
+                let bytes_written = parsed
+                    .deparse(&mut write_back_buffer)
+                    .unwrap_or_else(|e| unreachable!("{e:?}"));
+                assert_eq!(bytes_written, bytes_parsed);
+                let (reparsed, _) = Nsh::parse(&write_back_buffer).unwrap();
+                assert_eq!(reparsed, parsed);
+            });
+    }
+
+    #[test]
+    fn parse_of_invalid_header_fails_gracefully() {
+        let mut nsh = Nsh::new(ServicePathId::new(42).unwrap(), 255, NshNextProtocol::IPV4);
+        nsh.set_context(NshContext::Md1([7; 16])).unwrap();
+        let mut buf = [0u8; 24];
+        nsh.deparse(&mut buf).unwrap();
+        assert_eq!(buf[..8], [0x0f, 0xc6, 0x01, 0x01, 0, 0, 42, 255]);
+        match Nsh::parse(&buf[..20]) {
+            Err(ParseError::Length(e)) => {
+                assert_eq!(e.expected.get(), 24);
+                assert_eq!(e.actual, 20);
+            }
+            _ => unreachable!(),
+        }
+        // MD type 1 with a wrong length
+        buf[1] = 0xc2;
+        assert!(matches!(
+            Nsh::parse(&buf),
+            Err(ParseError::Invalid(NshError::InvalidLength {
+                length: 2,
+                md_type: 1
+            }))
+        ));
+        // unsupported version
+        buf[0] |= 0x40;
+        assert!(matches!(
+            Nsh::parse(&buf),
+            Err(ParseError::Invalid(NshError::UnsupportedVersion(1)))
+        ));
+
+        assert!(ServicePathId::new(ServicePathId::MAX + 1).is_none());
+        assert!(nsh.set_ttl(Nsh::MAX_TTL + 1).is_err());
+        let too_long = NshTlv {
+            class: 0,
+            tlv_type: 0,
+            value: vec![0; NshTlv::MAX_VALUE_LENGTH + 1],
+        };
+        assert!(matches!(
+            nsh.set_context(NshContext::Md2(vec![too_long])),
+            Err(NshError::InvalidContext)
+        ));
+    }
+
+    #[test]
+    fn parse_ipv4_over_nsh() {
+        let udp = UdpHeader {
+            source_port: 1234,
+            destination_port: 5678,
+            length: 8,
+            checksum: 0,
+        };
+        let ip = Ipv4Header::new(8, 64, IpNumber::UDP, [10, 0, 0, 1], [10, 0, 0, 2]).unwrap();
+        let mut nsh = Nsh::new(
+            ServicePathId::new(0x1234).unwrap(),
+            254,
+            NshNextProtocol::IPV4,
+        );
+        nsh.set_context(NshContext::Md2(vec![NshTlv {
+            class: 0x0101,
+            tlv_type: 3,
+            value: vec![0xaa; 5],
+        }]))
+        .unwrap();
+        let eth = Eth::new(
+            SourceMac::new(Mac([2, 0, 0, 0, 0, 1])).unwrap(),
+            DestinationMac::new(Mac([2, 0, 0, 0, 0, 2])).unwrap(),
+            EthType::NSH,
+        );
+        let mut frame = vec![0u8; (eth.size().get() + nsh.size().get()).into()];
+        let eth_len = eth.deparse(&mut frame).unwrap().into_non_zero_usize().get();
+        nsh.deparse(&mut frame[eth_len..]).unwrap();
+        ip.write(&mut frame).unwrap();
+        udp.write(&mut frame).unwrap();
+
+        let packet = Packet::new(TestBuffer::from_raw_data(&frame)).unwrap();
+        let parsed = packet.try_nsh().unwrap();
+        assert_eq!(parsed, &nsh);
+        assert_eq!(parsed.size().get(), 20);
+        assert_eq!(
+            packet.headers().try_ipv4().unwrap().destination(),
+            std::net::Ipv4Addr::new(10, 0, 0, 2)
+        );
+        assert_eq!(packet.try_udp().unwrap().destination().as_u16(), 5678);
+        assert_eq!(packet.payload_len(), 0);
+
+        let mut buf = vec![0u8; frame.len()];
+        packet.get_headers().deparse(&mut buf).unwrap();
+        assert_eq!(buf, frame);
+    }
+}
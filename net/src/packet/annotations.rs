@@ -0,0 +1,213 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Typed annotations attached to the metadata of a [`Packet`](crate::packet::Packet).
+//!
+//! Stages may attach structured metadata to a packet (ingress VPC, policy verdict, trace id, ...)
+//! without adding a dedicated field to [`PacketMeta`](crate::packet::PacketMeta) for each of
+//! them. Each kind of annotation is identified by a zero-sized tag type implementing
+//! [`Annotation`], in the same way [`Id<T>`](id::Id) is tagged by `T`:
+//!
+//! ```
+//! # use dataplane_net::packet::{Annotation, Annotations};
+//! /// Identifier of the trace a packet belongs to
+//! struct TraceId;
+//!
+//! impl Annotation for TraceId {
+//!     type Value = u64;
+//! }
+//!
+//! let mut annotations = Annotations::default();
+//! annotations.insert::<TraceId>(42);
+//! assert_eq!(annotations.get::<TraceId>(), Some(&42));
+//! ```
+
+use std::any::{Any, TypeId, type_name};
+use std::fmt::{Debug, Formatter};
+
+/// A tag type identifying a kind of packet annotation.
+///
+/// The tag is never instantiated, it only selects the entry of [`Annotations`] and the type of
+/// the [`Value`](Annotation::Value) stored in it.
+pub trait Annotation: 'static {
+    /// The type of the value of the annotation
+    type Value: Any + Clone + Debug + Send + Sync;
+}
+
+/// Type-erased value of an annotation
+trait AnnotationValue: Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+    fn clone_box(&self) -> Box<dyn AnnotationValue>;
+}
+
+impl<T: Any + Clone + Debug + Send + Sync> AnnotationValue for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+    fn clone_box(&self) -> Box<dyn AnnotationValue> {
+        Box::new(self.clone())
+    }
+}
+
+struct Entry {
+    tag: TypeId,
+    name: &'static str,
+    value: Box<dyn AnnotationValue>,
+}
+
+impl Clone for Entry {
+    fn clone(&self) -> Self {
+        Entry {
+            tag: self.tag,
+            name: self.name,
+            value: self.value.clone_box(),
+        }
+    }
+}
+
+/// A map of typed annotations, keyed by [`Annotation`] tags.
+///
+/// Packets usually carry few annotations, if any: they are kept in a vector, which does not
+/// allocate until the first annotation is inserted.
+#[derive(Clone, Default)]
+pub struct Annotations(Vec<Entry>);
+
+impl Annotations {
+    fn position<A: Annotation>(&self) -> Option<usize> {
+        let tag = TypeId::of::<A>();
+        self.0.iter().position(|entry| entry.tag == tag)
+    }
+
+    /// Attach annotation `A` with `value`, returning the previous value of `A` if any.
+    pub fn insert<A: Annotation>(&mut self, value: A::Value) -> Option<A::Value> {
+        let previous = self.remove::<A>();
+        self.0.push(Entry {
+            tag: TypeId::of::<A>(),
+            name: type_name::<A>(),
+            value: Box::new(value),
+        });
+        previous
+    }
+
+    /// Get the value of annotation `A`, if attached.
+    #[must_use]
+    pub fn get<A: Annotation>(&self) -> Option<&A::Value> {
+        self.position::<A>()
+            .and_then(|index| self.0[index].value.as_any().downcast_ref())
+    }
+
+    /// Get a mutable reference to the value of annotation `A`, if attached.
+    #[must_use]
+    pub fn get_mut<A: Annotation>(&mut self) -> Option<&mut A::Value> {
+        self.position::<A>()
+            .and_then(|index| self.0[index].value.as_any_mut().downcast_mut())
+    }
+
+    /// Detach annotation `A`, returning its value if it was attached.
+    pub fn remove<A: Annotation>(&mut self) -> Option<A::Value> {
+        let entry = self.0.swap_remove(self.position::<A>()?);
+        entry.value.into_any().downcast().ok().map(|value| *value)
+    }
+
+    /// Tell if annotation `A` is attached.
+    #[must_use]
+    pub fn contains<A: Annotation>(&self) -> bool {
+        self.position::<A>().is_some()
+    }
+
+    /// Get the number of attached annotations.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Tell if no annotation is attached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Detach all annotations.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl Debug for Annotations {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|entry| (entry.name, &entry.value)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Annotation, Annotations};
+
+    struct IngressVpc;
+    impl Annotation for IngressVpc {
+        type Value = u32;
+    }
+
+    struct TraceId;
+    impl Annotation for TraceId {
+        type Value = u32;
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Verdict {
+        Allow,
+        Deny(String),
+    }
+    struct PolicyVerdict;
+    impl Annotation for PolicyVerdict {
+        type Value = Verdict;
+    }
+
+    #[test]
+    fn annotations_are_keyed_by_tag() {
+        let mut annotations = Annotations::default();
+        assert!(annotations.is_empty());
+        assert_eq!(annotations.insert::<IngressVpc>(1), None);
+        assert_eq!(annotations.insert::<TraceId>(2), None);
+        assert_eq!(annotations.insert::<PolicyVerdict>(Verdict::Allow), None);
+        assert_eq!(annotations.len(), 3);
+
+        // same value type, distinct tags
+        assert_eq!(annotations.get::<IngressVpc>(), Some(&1));
+        assert_eq!(annotations.get::<TraceId>(), Some(&2));
+
+        assert_eq!(
+            annotations.insert::<PolicyVerdict>(Verdict::Deny("acl".to_string())),
+            Some(Verdict::Allow)
+        );
+        assert_eq!(annotations.len(), 3);
+
+        *annotations.get_mut::<TraceId>().unwrap() += 1;
+        let cloned = annotations.clone();
+        assert_eq!(annotations.remove::<TraceId>(), Some(3));
+        assert!(!annotations.contains::<TraceId>());
+        assert_eq!(annotations.remove::<TraceId>(), None);
+        assert_eq!(cloned.get::<TraceId>(), Some(&3));
+        assert_eq!(
+            cloned.get::<PolicyVerdict>(),
+            Some(&Verdict::Deny("acl".to_string()))
+        );
+
+        let debug = format!("{cloned:?}");
+        assert!(debug.contains("TraceId"));
+        assert!(debug.contains("Deny(\"acl\")"));
+
+        annotations.clear();
+        assert!(annotations.is_empty());
+    }
+}
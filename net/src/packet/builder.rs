@@ -0,0 +1,652 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Fluent construction of [`Packet`]s from their layers.
+
+use crate::buffer::{Append, PacketBufferMut, TrimFromEnd};
+use crate::eth::mac::{DestinationMac, Mac, SourceMac};
+use crate::eth::{Eth, EthError, ethtype::EthType};
+use crate::geneve::Geneve;
+use crate::headers::{Headers, InvalidVlanStack, Net, Transport};
+use crate::icmp4::Icmp4;
+use crate::icmp6::Icmp6;
+use crate::ip::NextHeader;
+use crate::ipv4::Ipv4;
+use crate::ipv6::Ipv6;
+use crate::packet::{ChecksumOffload, Packet};
+use crate::parse::{DeParse, ParseError};
+use crate::tcp::Tcp;
+use crate::udp::{Udp, UdpEncap};
+use crate::vlan::{Pcp, Vid, Vlan, VlanTagKind};
+use crate::vxlan::{Vxlan, VxlanGpe};
+use std::num::NonZero;
+
+#[cfg(any(doc, test, feature = "test_buffer"))]
+use crate::buffer::TestBuffer;
+
+/// Errors which may occur when building a [`Packet`] with a [`PacketBuilder`].
+#[derive(Debug, thiserror::Error)]
+pub enum PacketBuilderError {
+    /// No Ethernet header was supplied.
+    #[error("missing ethernet header")]
+    MissingEth,
+    /// One of the [`Mac`] addresses of the Ethernet header is invalid.
+    #[error(transparent)]
+    InvalidEth(#[from] EthError),
+    /// The frame has no network header and no ether type was supplied.
+    #[error("missing ether type for a frame without network header")]
+    MissingEtherType,
+    /// More VLAN headers than the parser supports were supplied.
+    #[error("too many vlan headers: {0}")]
+    TooManyVlans(usize),
+    /// The VLAN headers are not correctly ordered.
+    #[error(transparent)]
+    InvalidVlans(#[from] InvalidVlanStack),
+    /// A transport header was supplied without a network header.
+    #[error("transport header without network header")]
+    MissingNet,
+    /// The ICMP version does not match the IP version of the network header.
+    #[error("icmp version does not match ip version")]
+    IcmpVersionMismatch,
+    /// A UDP encapsulation was supplied without a UDP header.
+    #[error("udp encapsulation without udp header")]
+    MissingUdp,
+    /// The resulting frame would be too long.
+    #[error("packet too long: {0} bytes")]
+    TooLong(usize),
+    /// The buffer supplied to [`PacketBuilder::build_in`] does not have enough room.
+    #[error("not enough room in buffer for {0} bytes")]
+    NotEnoughRoom(usize),
+    /// The resulting frame does not parse back.
+    #[error(transparent)]
+    Invalid(ParseError<EthError>),
+}
+
+/// A fluent builder for [`Packet`]s.
+///
+/// Layers are supplied from the outermost to the innermost, and are only checked for consistency
+/// when the packet is built.
+/// At this point the builder fills in the fields linking the layers together: ether types, IP next
+/// header, IP and UDP lengths, and checksums.
+/// Other fields (addresses, ports, TTL, ...) are taken as supplied.
+///
+/// The checksum of the UDP header is left untouched when the packet carries a UDP encapsulation,
+/// as VXLAN and Geneve recommend a zero checksum.
+#[derive(Debug, Clone, Default)]
+pub struct PacketBuilder {
+    eth: Option<(Mac, Mac)>,
+    ether_type: Option<EthType>,
+    vlans: Vec<(Vid, VlanTagKind)>,
+    net: Option<Net>,
+    transport: Option<Transport>,
+    udp_encap: Option<UdpEncap>,
+    payload: Vec<u8>,
+}
+
+impl PacketBuilder {
+    /// Create a new, empty, [`PacketBuilder`].
+    #[must_use]
+    pub fn new() -> PacketBuilder {
+        PacketBuilder::default()
+    }
+
+    /// Set the source and destination [`Mac`]s of the Ethernet header.
+    #[must_use]
+    pub fn eth(mut self, source: Mac, destination: Mac) -> PacketBuilder {
+        self.eth = Some((source, destination));
+        self
+    }
+
+    /// Set the ether type of a frame which has no network header.
+    ///
+    /// This is ignored if a network header is supplied.
+    #[must_use]
+    pub fn ether_type(mut self, ether_type: EthType) -> PacketBuilder {
+        self.ether_type = Some(ether_type);
+        self
+    }
+
+    /// Push a (customer) VLAN header below the Ethernet header and any previously pushed VLAN
+    /// header.
+    #[must_use]
+    pub fn vlan(mut self, vid: Vid) -> PacketBuilder {
+        self.vlans.push((vid, VlanTagKind::Customer));
+        self
+    }
+
+    /// Push an 802.1ad service VLAN header below the Ethernet header and any previously pushed
+    /// VLAN header.
+    ///
+    /// Service VLAN headers must be pushed before customer VLAN headers.
+    #[must_use]
+    pub fn s_vlan(mut self, vid: Vid) -> PacketBuilder {
+        self.vlans.push((vid, VlanTagKind::Service));
+        self
+    }
+
+    /// Set an IPv4 network header.
+    #[must_use]
+    pub fn ipv4(mut self, ipv4: Ipv4) -> PacketBuilder {
+        self.net = Some(Net::Ipv4(ipv4));
+        self
+    }
+
+    /// Set an IPv6 network header.
+    #[must_use]
+    pub fn ipv6(mut self, ipv6: Ipv6) -> PacketBuilder {
+        self.net = Some(Net::Ipv6(ipv6));
+        self
+    }
+
+    /// Set a TCP transport header.
+    #[must_use]
+    pub fn tcp(mut self, tcp: Tcp) -> PacketBuilder {
+        self.transport = Some(Transport::Tcp(tcp));
+        self
+    }
+
+    /// Set a UDP transport header.
+    #[must_use]
+    pub fn udp(mut self, udp: Udp) -> PacketBuilder {
+        self.transport = Some(Transport::Udp(udp));
+        self
+    }
+
+    /// Set an `ICMPv4` transport header.
+    #[must_use]
+    pub fn icmp4(mut self, icmp4: Icmp4) -> PacketBuilder {
+        self.transport = Some(Transport::Icmp4(icmp4));
+        self
+    }
+
+    /// Set an `ICMPv6` transport header.
+    #[must_use]
+    pub fn icmp6(mut self, icmp6: Icmp6) -> PacketBuilder {
+        self.transport = Some(Transport::Icmp6(icmp6));
+        self
+    }
+
+    /// Set a [`Vxlan`] encapsulation header (requires a UDP header).
+    #[must_use]
+    pub fn vxlan(mut self, vxlan: Vxlan) -> PacketBuilder {
+        self.udp_encap = Some(UdpEncap::Vxlan(vxlan));
+        self
+    }
+
+    /// Set a [`VxlanGpe`] encapsulation header (requires a UDP header).
+    #[must_use]
+    pub fn vxlan_gpe(mut self, gpe: VxlanGpe) -> PacketBuilder {
+        self.udp_encap = Some(UdpEncap::VxlanGpe(gpe));
+        self
+    }
+
+    /// Set a [`Geneve`] encapsulation header (requires a UDP header).
+    #[must_use]
+    pub fn geneve(mut self, geneve: Geneve) -> PacketBuilder {
+        self.udp_encap = Some(UdpEncap::Geneve(geneve));
+        self
+    }
+
+    /// Set the payload following the innermost header.
+    ///
+    /// For encapsulated packets, this is the inner frame (which may itself come from a
+    /// [`PacketBuilder`], see [`PacketBuilder::build_bytes`]).
+    #[must_use]
+    pub fn payload(mut self, payload: impl Into<Vec<u8>>) -> PacketBuilder {
+        self.payload = payload.into();
+        self
+    }
+
+    /// Validate the layers and assemble them into [`Headers`].
+    fn headers(self) -> Result<(Headers, Vec<u8>), PacketBuilderError> {
+        let (source, destination) = self.eth.ok_or(PacketBuilderError::MissingEth)?;
+        let source = SourceMac::new(source).map_err(EthError::InvalidSource)?;
+        let destination = DestinationMac::new(destination).map_err(EthError::InvalidDestination)?;
+
+        let mut headers = Headers::new();
+
+        // link layer
+        let ether_type = match &self.net {
+            Some(Net::Ipv4(_)) => EthType::IPV4,
+            Some(Net::Ipv6(_)) => EthType::IPV6,
+            None => self
+                .ether_type
+                .ok_or(PacketBuilderError::MissingEtherType)?,
+        };
+        let outer_ether_type = match self.vlans.first() {
+            Some((_, kind)) => kind.ethtype(),
+            None => ether_type,
+        };
+        headers.set_eth(Eth::new(source, destination, outer_ether_type));
+        let num_vlans = self.vlans.len();
+        for (i, &(vid, _)) in self.vlans.iter().enumerate() {
+            let inner = match self.vlans.get(i + 1) {
+                Some((_, kind)) => kind.ethtype(),
+                None => ether_type,
+            };
+            headers
+                .vlan
+                .try_push(Vlan::new(vid, inner, Pcp::default(), false))
+                .map_err(|_| PacketBuilderError::TooManyVlans(num_vlans))?;
+        }
+        headers.validate_vlans()?;
+
+        // upper layers
+        let mut net = match self.net {
+            Some(net) => net,
+            None if self.transport.is_some() => return Err(PacketBuilderError::MissingNet),
+            None => {
+                if self.udp_encap.is_some() {
+                    return Err(PacketBuilderError::MissingUdp);
+                }
+                return Ok((headers, self.payload));
+            }
+        };
+        let encap_len = self.udp_encap.as_ref().map_or(0, |encap| match encap {
+            UdpEncap::Vxlan(vxlan) => vxlan.size().get(),
+            UdpEncap::VxlanGpe(gpe) => gpe.size().get(),
+            UdpEncap::Geneve(geneve) => geneve.size().get(),
+        });
+        let mut transport = self.transport;
+        if self.udp_encap.is_some() && !matches!(transport, Some(Transport::Udp(_))) {
+            return Err(PacketBuilderError::MissingUdp);
+        }
+        let transport_len = transport.as_ref().map_or(0, |t| t.size().get());
+        let ip_payload_len =
+            usize::from(transport_len) + usize::from(encap_len) + self.payload.len();
+        let Ok(ip_payload_len) = u16::try_from(ip_payload_len) else {
+            return Err(PacketBuilderError::TooLong(ip_payload_len));
+        };
+
+        if let Some(transport) = transport.as_mut() {
+            let next_header = match (&net, &mut *transport) {
+                (_, Transport::Tcp(_)) => NextHeader::TCP,
+                (_, Transport::Udp(udp)) => {
+                    // never zero: the UDP header is part of the length
+                    let length = NonZero::new(ip_payload_len).unwrap_or_else(|| unreachable!());
+                    #[allow(unsafe_code)] // length includes the UDP header
+                    unsafe {
+                        udp.set_length(length);
+                    }
+                    NextHeader::UDP
+                }
+                (_, Transport::Sctp(_)) => NextHeader::SCTP,
+                (Net::Ipv4(_), Transport::Icmp4(_)) => NextHeader::ICMP,
+                (Net::Ipv6(_), Transport::Icmp6(_)) => NextHeader::ICMP6,
+                (Net::Ipv4(_), Transport::Icmp6(_)) | (Net::Ipv6(_), Transport::Icmp4(_)) => {
+                    return Err(PacketBuilderError::IcmpVersionMismatch);
+                }
+            };
+            match &mut net {
+                Net::Ipv4(ipv4) => {
+                    ipv4.set_next_header(next_header);
+                }
+                Net::Ipv6(ipv6) => {
+                    ipv6.set_next_header(next_header);
+                }
+            }
+        }
+        match &mut net {
+            Net::Ipv4(ipv4) => ipv4.set_payload_len(ip_payload_len).map_err(|_| {
+                PacketBuilderError::TooLong(usize::from(ip_payload_len) + ipv4.header_len())
+            })?,
+            Net::Ipv6(ipv6) => {
+                ipv6.set_payload_length(ip_payload_len);
+            }
+        }
+
+        headers.net = Some(net);
+        headers.transport = transport;
+        headers.udp_encap = self.udp_encap;
+        headers.update_checksums(&self.payload, ChecksumOffload::empty());
+        Ok((headers, self.payload))
+    }
+
+    /// Build the frame and return its bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PacketBuilderError`] if the supplied layers are inconsistent.
+    pub fn build_bytes(self) -> Result<Vec<u8>, PacketBuilderError> {
+        let (headers, payload) = self.headers()?;
+        let header_len = usize::from(headers.size().get());
+        let total_len = header_len + payload.len();
+        if u16::try_from(total_len).is_err() {
+            return Err(PacketBuilderError::TooLong(total_len));
+        }
+        let mut bytes = vec![0; total_len];
+        headers
+            .deparse(&mut bytes[..header_len])
+            .unwrap_or_else(|e| unreachable!("{e:?}"));
+        bytes[header_len..].copy_from_slice(&payload);
+        Ok(bytes)
+    }
+
+    /// Build the frame in `buffer`, replacing its current content, and parse it as a [`Packet`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PacketBuilderError`] if the supplied layers are inconsistent, or if `buffer`
+    /// is too small to hold the frame.
+    pub fn build_in<Buf: PacketBufferMut + Append>(
+        self,
+        mut buffer: Buf,
+    ) -> Result<Packet<Buf>, PacketBuilderError> {
+        let bytes = self.build_bytes()?;
+        #[allow(clippy::cast_possible_truncation)] // checked in build_bytes
+        let len = bytes.len() as u16;
+        #[allow(clippy::cast_possible_truncation)] // buffers are shorter than 2^16 bytes
+        let current_len = buffer.as_ref().len() as u16;
+        buffer
+            .trim_from_end(current_len)
+            .unwrap_or_else(|e| unreachable!("{e:?}"));
+        buffer
+            .append(len)
+            .map_err(|_| PacketBuilderError::NotEnoughRoom(bytes.len()))?
+            .copy_from_slice(&bytes);
+        Packet::new(buffer).map_err(|invalid| PacketBuilderError::Invalid(invalid.error))
+    }
+
+    /// Build the frame as a [`Packet`] backed by a [`TestBuffer`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PacketBuilderError`] if the supplied layers are inconsistent.
+    #[cfg(any(doc, test, feature = "test_buffer"))]
+    pub fn build(self) -> Result<Packet<TestBuffer>, PacketBuilderError> {
+        let bytes = self.build_bytes()?;
+        Packet::new(TestBuffer::from_raw_data(&bytes))
+            .map_err(|invalid| PacketBuilderError::Invalid(invalid.error))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::checksum::Checksum;
+    use crate::eth::ethtype::EthType;
+    use crate::eth::mac::Mac;
+    use crate::headers::{
+        Net, TryEth, TryHeaders, TryIp, TryIpv4, TryIpv4Mut, TryTransport, TryUdp, TryVxlan,
+    };
+    use crate::icmp6::Icmp6;
+    use crate::ip::NextHeader;
+    use crate::ipv4::Ipv4;
+    use crate::ipv4::addr::UnicastIpv4Addr;
+    use crate::ipv6::Ipv6;
+    use crate::packet::{
+        ChecksumOffload, Packet, PacketBuilder, PacketBuilderError, SetPayloadError,
+    };
+    use crate::parse::DeParse;
+    use crate::tcp::Tcp;
+    use crate::udp::Udp;
+    use crate::vlan::Vid;
+    use crate::vxlan::{Vni, Vxlan};
+    use etherparse::{Icmpv6Header, Icmpv6Type};
+    use std::net::Ipv4Addr;
+
+    const SRC_MAC: Mac = Mac([0x2, 0, 0, 0, 0, 1]);
+    const DST_MAC: Mac = Mac([0x2, 0, 0, 0, 0, 2]);
+
+    fn ipv4() -> Ipv4 {
+        let mut ipv4 = Ipv4::default();
+        ipv4.set_source(UnicastIpv4Addr::new(Ipv4Addr::new(1, 2, 3, 4)).unwrap());
+        ipv4.set_destination(Ipv4Addr::new(5, 6, 7, 8));
+        ipv4.set_ttl(64);
+        ipv4
+    }
+
+    #[test]
+    fn tcp_over_vlan() {
+        let mut tcp = Tcp::default();
+        tcp.set_source(123.try_into().unwrap());
+        tcp.set_destination(456.try_into().unwrap());
+        let packet = PacketBuilder::new()
+            .eth(SRC_MAC, DST_MAC)
+            .vlan(Vid::new(10).unwrap())
+            .vlan(Vid::new(20).unwrap())
+            .ipv4(ipv4())
+            .tcp(tcp)
+            .payload([1, 2, 3, 4])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            packet.headers().try_eth().unwrap().ether_type(),
+            EthType::VLAN
+        );
+        let Some(Net::Ipv4(ip)) = packet.headers().try_ip() else {
+            panic!("expected ipv4 header");
+        };
+        assert_eq!(ip.protocol(), NextHeader::TCP.0);
+        assert_eq!(packet.payload().as_ref(), &[1, 2, 3, 4]);
+        assert_eq!(
+            ip.total_len(),
+            packet.total_len() - packet.headers().try_eth().unwrap().size().get() - 8
+        );
+
+        // checksums are already valid
+        let bytes = PacketBuilder::new()
+            .eth(SRC_MAC, DST_MAC)
+            .ipv4(ipv4())
+            .tcp(Tcp::default())
+            .build_bytes()
+            .unwrap();
+        let mut packet = Packet::new(crate::buffer::TestBuffer::from_raw_data(&bytes)).unwrap();
+        let before = packet.headers().try_transport().cloned();
+        packet.update_checksums();
+        assert_eq!(packet.headers().try_transport().cloned(), before);
+    }
+
+    #[test]
+    fn checksum_offload() {
+        let bytes = PacketBuilder::new()
+            .eth(SRC_MAC, DST_MAC)
+            .ipv4(ipv4())
+            .udp(Udp::new(1234.try_into().unwrap(), 5678.try_into().unwrap()))
+            .payload([1, 2, 3, 4])
+            .build_bytes()
+            .unwrap();
+        let mut packet = Packet::new(crate::buffer::TestBuffer::from_raw_data(&bytes)).unwrap();
+        let ip_checksum = packet.try_ipv4().unwrap().checksum();
+        let udp_checksum = packet.try_udp().unwrap().checksum();
+        packet
+            .try_ipv4_mut()
+            .unwrap()
+            .set_destination(Ipv4Addr::new(9, 9, 9, 9));
+
+        // offloaded checksums are prepared for the hardware to compute them: the IPv4 checksum
+        // is zeroed and the UDP checksum is seeded with the sum of the pseudo-header, which is
+        // 0x0102 + 0x0304 + 0x0909 + 0x0909 + 17 (protocol) + 12 (length) = 0x1635
+        let offload = ChecksumOffload::IPV4 | ChecksumOffload::UDP;
+        packet.get_meta_mut().set_checksum_offload(offload);
+        packet.update_checksums();
+        assert_eq!(packet.try_ipv4().unwrap().checksum(), Some(0.into()));
+        assert_eq!(packet.try_udp().unwrap().checksum(), Some(0x1635.into()));
+        assert_eq!(packet.headers().offload_lengths(), (14, 20));
+
+        // offloading TCP checksums has no effect on UDP
+        packet
+            .get_meta_mut()
+            .set_checksum_offload(ChecksumOffload::TCP);
+        packet.update_checksums();
+        assert_ne!(packet.try_ipv4().unwrap().checksum(), ip_checksum);
+        assert_ne!(packet.try_udp().unwrap().checksum(), udp_checksum);
+        let ipv4 = packet.try_ipv4().unwrap();
+        assert_eq!(ipv4.checksum(), ipv4.compute_checksum(&()).ok());
+    }
+
+    #[test]
+    fn qinq() {
+        let packet = PacketBuilder::new()
+            .eth(SRC_MAC, DST_MAC)
+            .s_vlan(Vid::new(10).unwrap())
+            .vlan(Vid::new(20).unwrap())
+            .ipv4(ipv4())
+            .build()
+            .unwrap();
+        assert_eq!(
+            packet.headers().try_eth().unwrap().ether_type(),
+            EthType::VLAN_QINQ
+        );
+        assert_eq!(
+            packet.get_headers().s_vlan().unwrap().vid(),
+            Vid::new(10).unwrap()
+        );
+        assert_eq!(
+            packet.get_headers().c_vlan().unwrap().vid(),
+            Vid::new(20).unwrap()
+        );
+
+        assert!(matches!(
+            PacketBuilder::new()
+                .eth(SRC_MAC, DST_MAC)
+                .vlan(Vid::new(20).unwrap())
+                .s_vlan(Vid::new(10).unwrap())
+                .ipv4(ipv4())
+                .build(),
+            Err(PacketBuilderError::InvalidVlans(_))
+        ));
+    }
+
+    #[test]
+    fn vxlan_encap() {
+        let inner = PacketBuilder::new()
+            .eth(SRC_MAC, DST_MAC)
+            .ipv4(ipv4())
+            .build_bytes()
+            .unwrap();
+        let packet = PacketBuilder::new()
+            .eth(SRC_MAC, DST_MAC)
+            .ipv4(ipv4())
+            .udp(Udp::new(1234.try_into().unwrap(), Vxlan::PORT))
+            .vxlan(Vxlan::new(Vni::new_checked(42).unwrap()))
+            .payload(inner.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            packet.headers().try_vxlan().unwrap().vni(),
+            Vni::new_checked(42).unwrap()
+        );
+        #[allow(clippy::cast_possible_truncation)] // test values
+        let udp_len = (Udp::MIN_LENGTH.get() as usize + 8 + inner.len()) as u16;
+        assert_eq!(packet.headers().try_udp().unwrap().length().get(), udp_len);
+        assert_eq!(packet.payload().as_ref(), inner.as_slice());
+    }
+
+    #[test]
+    fn ipv6_icmp6() {
+        let mut ipv6 = Ipv6::default();
+        ipv6.set_hop_limit(64);
+        let icmp6 = Icmp6(Icmpv6Header::new(Icmpv6Type::EchoRequest(
+            etherparse::IcmpEchoHeader { id: 1, seq: 2 },
+        )));
+        let packet = PacketBuilder::new()
+            .eth(SRC_MAC, DST_MAC)
+            .ipv6(ipv6)
+            .icmp6(icmp6)
+            .build()
+            .unwrap();
+        let Some(Net::Ipv6(ip)) = packet.headers().try_ip() else {
+            panic!("expected ipv6 header");
+        };
+        assert_eq!(ip.next_header(), NextHeader::ICMP6);
+        assert_eq!(ip.payload_length(), 8);
+    }
+
+    #[test]
+    fn invalid_layers() {
+        assert!(matches!(
+            PacketBuilder::new().ipv4(ipv4()).build(),
+            Err(PacketBuilderError::MissingEth)
+        ));
+        assert!(matches!(
+            PacketBuilder::new().eth(DST_MAC, Mac::ZERO).build(),
+            Err(PacketBuilderError::InvalidEth(_))
+        ));
+        assert!(matches!(
+            PacketBuilder::new().eth(SRC_MAC, DST_MAC).build(),
+            Err(PacketBuilderError::MissingEtherType)
+        ));
+        assert!(matches!(
+            PacketBuilder::new()
+                .eth(SRC_MAC, DST_MAC)
+                .tcp(Tcp::default())
+                .build(),
+            Err(PacketBuilderError::MissingNet)
+        ));
+        assert!(matches!(
+            PacketBuilder::new()
+                .eth(SRC_MAC, DST_MAC)
+                .ipv4(ipv4())
+                .icmp6(Icmp6(Icmpv6Header::new(Icmpv6Type::EchoRequest(
+                    etherparse::IcmpEchoHeader { id: 1, seq: 2 },
+                ))))
+                .build(),
+            Err(PacketBuilderError::IcmpVersionMismatch)
+        ));
+        assert!(matches!(
+            PacketBuilder::new()
+                .eth(SRC_MAC, DST_MAC)
+                .ipv4(ipv4())
+                .tcp(Tcp::default())
+                .vxlan(Vxlan::new(Vni::new_checked(42).unwrap()))
+                .build(),
+            Err(PacketBuilderError::MissingUdp)
+        ));
+        let mut builder = PacketBuilder::new().eth(SRC_MAC, DST_MAC).ipv4(ipv4());
+        for vid in 1..=5 {
+            builder = builder.vlan(Vid::new(vid).unwrap());
+        }
+        assert!(matches!(
+            builder.build(),
+            Err(PacketBuilderError::TooManyVlans(5))
+        ));
+        assert!(matches!(
+            PacketBuilder::new()
+                .eth(SRC_MAC, DST_MAC)
+                .ipv4(ipv4())
+                .payload(vec![0; usize::from(u16::MAX)])
+                .build(),
+            Err(PacketBuilderError::TooLong(_))
+        ));
+    }
+
+    #[test]
+    fn set_udp_payload() {
+        let mut packet = PacketBuilder::new()
+            .eth(SRC_MAC, DST_MAC)
+            .ipv4(ipv4())
+            .udp(Udp::new(1234.try_into().unwrap(), 5678.try_into().unwrap()))
+            .payload([1, 2, 3, 4])
+            .build()
+            .unwrap();
+
+        for payload in [&[5, 6, 7, 8, 9, 10][..], &[11], &[]] {
+            packet.set_udp_payload(payload).unwrap();
+            assert_eq!(packet.payload().as_ref(), payload);
+            assert_eq!(
+                usize::from(packet.try_udp().unwrap().length().get()),
+                8 + payload.len()
+            );
+            assert_eq!(
+                usize::from(packet.try_ipv4().unwrap().total_len()),
+                20 + 8 + payload.len()
+            );
+        }
+
+        // the packet parses back with the new lengths
+        packet.update_checksums();
+        let buffer = packet.serialize().unwrap();
+        let packet = Packet::new(buffer).unwrap();
+        assert_eq!(packet.payload_len(), 0);
+
+        let mut packet = PacketBuilder::new()
+            .eth(SRC_MAC, DST_MAC)
+            .ipv4(ipv4())
+            .tcp(Tcp::default())
+            .build()
+            .unwrap();
+        assert_eq!(packet.set_udp_payload(&[1]), Err(SetPayloadError::NotUdp));
+    }
+}
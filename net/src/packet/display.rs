@@ -3,12 +3,18 @@
 
 //! Display of Packets
 
+use crate::arp::Arp;
 use crate::eth::Eth;
+use crate::gre::Gre;
 use crate::headers::{Headers, Net, Transport};
 use crate::icmp4::Icmp4;
 use crate::icmp6::Icmp6;
 use crate::ipv4::Ipv4;
 use crate::ipv6::Ipv6;
+use crate::mpls::Mpls;
+use crate::nsh::Nsh;
+use crate::pppoe::Pppoe;
+use crate::sctp::Sctp;
 use crate::tcp::Tcp;
 use crate::udp::{Udp, UdpEncap};
 
@@ -74,6 +80,72 @@ impl Display for Net {
         }
     }
 }
+impl Display for Pppoe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "  PPPoE: code: {:#04x} session: {:#06x} length: {}",
+            self.code().0,
+            self.session_id(),
+            self.length()
+        )?;
+        if let Some(protocol) = self.protocol() {
+            write!(f, " protocol: {:#06x}", protocol.0)?;
+        }
+        writeln!(f)
+    }
+}
+impl Display for Mpls {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "  MPLS: label: {} tc: {} ttl: {}{}",
+            self.label(),
+            self.tc(),
+            self.ttl(),
+            if self.bos() { " (bos)" } else { "" }
+        )
+    }
+}
+impl Display for Nsh {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "  NSH: spi: {} si: {} ttl: {} md-type: {} next: {}{}",
+            self.spi(),
+            self.service_index(),
+            self.ttl(),
+            self.context().md_type(),
+            self.next_protocol().0,
+            if self.oam() { " (OAM)" } else { "" }
+        )
+    }
+}
+impl Display for Arp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "  ARP : {} {} ({}) -> {} ({})",
+            self.operation(),
+            self.sender_ip(),
+            self.sender_mac(),
+            self.target_ip(),
+            self.target_mac(),
+        )
+    }
+}
+impl Display for Gre {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "  GRE: protocol: {:#06x}", self.protocol().as_u16())?;
+        if let Some(key) = self.key() {
+            write!(f, " key: {key}")?;
+        }
+        if let Some(sequence) = self.sequence() {
+            write!(f, " seq: {sequence}")?;
+        }
+        writeln!(f)
+    }
+}
 impl Display for Icmp4 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "  ICMP:")?;
@@ -104,6 +176,18 @@ impl Display for Udp {
         )
     }
 }
+impl Display for Sctp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "  SCTP: {} -> {} verification-tag: {:#010x} checksum: {}",
+            self.source().as_u16(),
+            self.destination().as_u16(),
+            self.verification_tag(),
+            self.checksum()
+        )
+    }
+}
 impl Tcp {
     fn flags_as_string(&self) -> String {
         let mut flags = String::with_capacity(8 * 3);
@@ -164,6 +248,7 @@ impl Display for Transport {
             Transport::Icmp6(x) => x.fmt(f),
             Transport::Udp(x) => x.fmt(f),
             Transport::Tcp(x) => x.fmt(f),
+            Transport::Sctp(x) => x.fmt(f),
         }
     }
 }
@@ -172,6 +257,18 @@ impl Display for UdpEncap {
         write!(f, "  ENCAP:")?;
         match self {
             UdpEncap::Vxlan(vxlan) => writeln!(f, "  vxlan, vni={}", vxlan.vni()),
+            UdpEncap::VxlanGpe(gpe) => writeln!(
+                f,
+                "  vxlan-gpe, vni={} protocol={}",
+                gpe.vni(),
+                gpe.protocol().0
+            ),
+            UdpEncap::Geneve(geneve) => writeln!(
+                f,
+                "  geneve, vni={} options={}",
+                geneve.vni(),
+                geneve.options().len()
+            ),
         }
     }
 }
@@ -183,9 +280,24 @@ impl Display for Headers {
         if let Some(eth) = &self.eth {
             write!(f, "{eth}")?;
         }
+        if let Some(pppoe) = &self.pppoe {
+            write!(f, "{pppoe}")?;
+        }
+        if let Some(nsh) = &self.nsh {
+            write!(f, "{nsh}")?;
+        }
+        for mpls in &self.mpls {
+            write!(f, "{mpls}")?;
+        }
+        if let Some(arp) = &self.arp {
+            write!(f, "{arp}")?;
+        }
         if let Some(net) = &self.net {
             write!(f, "{net}")?;
         }
+        if let Some(gre) = &self.gre {
+            write!(f, "{gre}")?;
+        }
         if let Some(transport) = &self.transport {
             write!(f, "{transport}")?;
         }
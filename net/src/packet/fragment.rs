@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! IPv4 fragmentation of packets
+
+use crate::buffer::{Append, PacketBufferMut, Prepend, Segments, TrimFromEnd};
+use crate::headers::{Headers, Net};
+use crate::ipv4::frag_offset::FragOffset;
+use crate::packet::Packet;
+use crate::parse::DeParse;
+
+/// Errors which may occur when fragmenting a [`Packet`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FragmentError {
+    /// Only IPv4 packets can be fragmented on their way.
+    #[error("not an ipv4 packet")]
+    NotIpv4,
+    /// The packet does not fit the MTU, but its "don't fragment" flag is set.
+    #[error("packet too big ({size} bytes, mtu {mtu}) with \"don't fragment\" flag set")]
+    DontFragment {
+        /// Length of the IP packet
+        size: u16,
+        /// The MTU the packet has to fit
+        mtu: u16,
+    },
+    /// The MTU is too small to hold a fragment (including the upper layer headers of the packet,
+    /// for the first fragment).
+    #[error("mtu {0} too small to fragment packet")]
+    MtuTooSmall(u16),
+    /// The offset of a fragment would exceed the maximum fragment offset.
+    #[error("fragment offset out of range")]
+    InvalidOffset,
+    /// No buffer could be obtained for a fragment, or the buffer has not enough room for it.
+    #[error("no buffer available for fragment")]
+    NoBuffer,
+}
+
+impl<Buf: PacketBufferMut + Append> Packet<Buf> {
+    /// Fragment this IPv4 packet, so that the IP length of each fragment fits `mtu`.
+    ///
+    /// The packet is returned unmodified (as the only fragment) if it already fits `mtu`.
+    /// Otherwise, the checksums of the packet are updated (unless the packet is itself a fragment),
+    /// and its headers following the IPv4 header become part of the data of the first fragment: the
+    /// fragments only have ethernet, VLAN and IPv4 headers.
+    ///
+    /// The first fragment reuses the buffer of this packet, which may be made of several segments
+    /// (see [`Segments`]): the data of the other fragments is read across segments.
+    /// The buffers of the other fragments are obtained from `new_buffer`, which must return empty
+    /// buffers with enough headroom for the headers of the fragments and enough tailroom for their
+    /// data.
+    /// The IPv4 options, if any, are copied to all fragments.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FragmentError`] if the packet is not an IPv4 packet, if it can't be fragmented,
+    /// or if `new_buffer` fails to provide buffers.
+    pub fn fragment(
+        mut self,
+        mtu: u16,
+        mut new_buffer: impl FnMut() -> Option<Buf>,
+    ) -> Result<Vec<Packet<Buf>>, FragmentError> {
+        let Some(Net::Ipv4(ipv4)) = &self.headers.net else {
+            return Err(FragmentError::NotIpv4);
+        };
+        let ip_len = ipv4.size().get();
+        let l3_start = self.headers.eth.as_ref().map_or(0, |eth| eth.size().get())
+            + self
+                .headers
+                .vlan
+                .iter()
+                .map(|vlan| vlan.size().get())
+                .sum::<u16>();
+        let headers_len = self.headers.size().get();
+        let size = headers_len - l3_start + self.payload_len();
+        if size <= mtu {
+            return Ok(vec![self]);
+        }
+        if ipv4.dont_fragment() {
+            return Err(FragmentError::DontFragment { size, mtu });
+        }
+        // all fragments but the last must carry a multiple of 8 bytes of data
+        let chunk = mtu.saturating_sub(ip_len) & !7;
+        // the headers following the ip header go in the first fragment
+        let upper_len = headers_len - l3_start - ip_len;
+        if chunk == 0 || chunk < upper_len {
+            return Err(FragmentError::MtuTooSmall(mtu));
+        }
+        let base_offset = ipv4.fragment_offset().value();
+        let more_fragments = ipv4.more_fragments();
+
+        // the transport checksum of a packet which is already a fragment covers data we don't have
+        if base_offset == 0 && !more_fragments {
+            self.update_checksums();
+        }
+        let mut header_bytes = vec![0u8; usize::from(headers_len)];
+        self.headers
+            .deparse(&mut header_bytes)
+            .unwrap_or_else(|e| unreachable!("{e:?}"));
+        let template = Headers {
+            eth: self.headers.eth.clone(),
+            vlan: self.headers.vlan.clone(),
+            net: self.headers.net.clone(),
+            ..Headers::default()
+        };
+
+        let first_len = chunk - upper_len;
+        let mut others = Vec::new();
+        let mut offset = chunk;
+        let payload_len = self.payload_len();
+        for start in (first_len..payload_len).step_by(usize::from(chunk)) {
+            let len = chunk.min(payload_len - start);
+            let mut buf = new_buffer().ok_or(FragmentError::NoBuffer)?;
+            let data = buf.append(len).map_err(|_| FragmentError::NoBuffer)?;
+            self.payload.read(usize::from(start), data);
+            let mut headers = template.clone();
+            let last = offset + len == size - ip_len;
+            set_fragment(
+                &mut headers,
+                base_offset,
+                offset,
+                len,
+                more_fragments || !last,
+            )?;
+            others.push(Packet {
+                headers,
+                payload: buf,
+                meta: self.meta.clone(),
+            });
+            offset += len;
+        }
+
+        let trim = payload_len - first_len;
+        self.payload
+            .trim_from_end(trim)
+            .unwrap_or_else(|e| unreachable!("{e:?}"));
+        self.payload
+            .prepend(upper_len)
+            .map_err(|_| FragmentError::NoBuffer)?
+            .copy_from_slice(&header_bytes[usize::from(l3_start + ip_len)..]);
+        self.headers = template;
+        set_fragment(&mut self.headers, base_offset, 0, chunk, true)?;
+        let mut fragments = Vec::with_capacity(1 + others.len());
+        fragments.push(self);
+        fragments.append(&mut others);
+        Ok(fragments)
+    }
+}
+
+/// Set the IPv4 header of a fragment, holding `len` bytes at `offset` (in bytes) in the data of
+/// the original packet, which starts at `base_offset` (in units of 8 bytes).
+fn set_fragment(
+    headers: &mut Headers,
+    base_offset: u16,
+    offset: u16,
+    len: u16,
+    more_fragments: bool,
+) -> Result<(), FragmentError> {
+    let Some(Net::Ipv4(ipv4)) = &mut headers.net else {
+        unreachable!()
+    };
+    let offset =
+        FragOffset::new(base_offset + offset / 8).map_err(|_| FragmentError::InvalidOffset)?;
+    ipv4.set_fragment_offset(offset)
+        .set_more_fragments(more_fragments)
+        .set_payload_len(len)
+        .unwrap_or_else(|e| unreachable!("{e:?}"));
+    if let Some(net) = headers.net.as_mut() {
+        net.update_checksum();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::{Segments, TestBuffer};
+    use crate::headers::{TryIpv4, TryUdp};
+    use crate::packet::Packet;
+    use crate::packet::fragment::FragmentError;
+    use crate::reassembly::{Ipv4Reassembler, ReassemblyConfig};
+    use etherparse::{EtherType, Ethernet2Header, IpNumber, Ipv4Header, UdpHeader};
+    use std::time::Instant;
+
+    /// An ethernet frame with an IPv4/UDP packet of `len` bytes of UDP payload.
+    fn frame(len: u8, dont_fragment: bool) -> Vec<u8> {
+        let payload: Vec<u8> = (0..len).collect();
+        let eth = Ethernet2Header {
+            source: [2, 0, 0, 0, 0, 1],
+            destination: [2, 0, 0, 0, 0, 2],
+            ether_type: EtherType::IPV4,
+        };
+        let mut ip = Ipv4Header::new(
+            UdpHeader::LEN_U16 + u16::from(len),
+            64,
+            IpNumber::UDP,
+            [10, 0, 0, 1],
+            [10, 0, 0, 2],
+        )
+        .unwrap();
+        ip.dont_fragment = dont_fragment;
+        ip.identification = 42;
+        let udp = UdpHeader::with_ipv4_checksum(1234, 5678, &ip, &payload).unwrap();
+        let mut frame = Vec::new();
+        eth.write(&mut frame).unwrap();
+        ip.write(&mut frame).unwrap();
+        udp.write(&mut frame).unwrap();
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    fn new_buffer() -> Option<TestBuffer> {
+        Some(TestBuffer::from_raw_data(&[]))
+    }
+
+    #[test]
+    fn fits_mtu() {
+        let packet = Packet::new(TestBuffer::from_raw_data(&frame(100, true))).unwrap();
+        let fragments = packet.fragment(128, new_buffer).unwrap();
+        assert_eq!(fragments.len(), 1);
+        assert!(!fragments[0].try_ipv4().unwrap().more_fragments());
+        assert!(fragments[0].try_udp().is_some());
+    }
+
+    #[test]
+    fn dont_fragment() {
+        let packet = Packet::new(TestBuffer::from_raw_data(&frame(100, true))).unwrap();
+        assert_eq!(
+            packet.fragment(68, new_buffer).unwrap_err(),
+            FragmentError::DontFragment { size: 128, mtu: 68 }
+        );
+        let packet = Packet::new(TestBuffer::from_raw_data(&frame(100, false))).unwrap();
+        assert_eq!(
+            packet.fragment(24, new_buffer).unwrap_err(),
+            FragmentError::MtuTooSmall(24)
+        );
+    }
+
+    #[test]
+    fn fragment_and_reassemble() {
+        let original = frame(100, false);
+        let packet = Packet::new(TestBuffer::from_raw_data(&original)).unwrap();
+        let fragments = packet.fragment(70, new_buffer).unwrap();
+        // 108 bytes of ip payload, in chunks of 48 bytes
+        let expected = [(0, 48, true), (6, 48, true), (12, 12, false)];
+        assert_eq!(fragments.len(), expected.len());
+        let fragments: Vec<_> = fragments
+            .into_iter()
+            .zip(expected)
+            .map(|(fragment, (offset, len, more_fragments))| {
+                let ipv4 = fragment.try_ipv4().unwrap();
+                assert_eq!(ipv4.fragment_offset().value(), offset);
+                assert_eq!(ipv4.total_len(), 20 + len);
+                assert_eq!(ipv4.more_fragments(), more_fragments);
+                assert_eq!(ipv4.identification(), 42);
+                assert!(fragment.try_udp().is_none());
+                Packet::new(fragment.serialize().unwrap()).unwrap()
+            })
+            .collect();
+        assert!(fragments[0].try_udp().is_some());
+        assert!(fragments[1].try_udp().is_none());
+
+        let now = Instant::now();
+        let mut reassembler = Ipv4Reassembler::new(ReassemblyConfig::default());
+        let mut fragments = fragments.into_iter().rev();
+        assert!(
+            reassembler
+                .reassemble(fragments.next().unwrap(), now)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            reassembler
+                .reassemble(fragments.next().unwrap(), now)
+                .unwrap()
+                .is_none()
+        );
+        let packet = reassembler
+            .reassemble(fragments.next().unwrap(), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(reassembler.pending(), 0);
+        assert_eq!(packet.try_udp().unwrap().destination().as_u16(), 5678);
+        assert_eq!(packet.serialize().unwrap().as_ref(), original.as_slice());
+    }
+
+    #[test]
+    fn fragment_multi_segment_packet() {
+        let original = frame(100, false);
+        let segments: [&[u8]; 3] = [&original[..60], &original[60..100], &original[100..]];
+        let packet = Packet::new(TestBuffer::from_segments(&segments)).unwrap();
+        assert_eq!(packet.payload().num_segments(), 3);
+        assert_eq!(packet.payload_len(), 100);
+
+        let fragments = packet.fragment(70, new_buffer).unwrap();
+        assert_eq!(fragments.len(), 3);
+        let now = Instant::now();
+        let mut reassembler = Ipv4Reassembler::new(ReassemblyConfig::default());
+        let mut reassembled = None;
+        for fragment in fragments {
+            let fragment = Packet::new(fragment.serialize().unwrap()).unwrap();
+            reassembled = reassembler.reassemble(fragment, now).unwrap();
+        }
+        let buffer = reassembled.unwrap().serialize().unwrap();
+        assert_eq!(buffer.segments().collect::<Vec<_>>().concat(), original);
+    }
+}
@@ -3,43 +3,127 @@
 
 //! Module to compute packet hashes
 
-use crate::headers::{Net, Transport, TryHeaders, TryIp, TryTransport};
+use crate::headers::{Net, Transport, TryEsp, TryHeaders, TryIp, TryTransport};
 use crate::packet::Packet;
 use crate::{buffer::PacketBufferMut, headers::TryEth};
 use ahash::AHasher;
 use std::hash::{Hash, Hasher};
 
+/// The set of header fields that [`Packet::hash_ip_fields`] hashes over
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct HashFields {
+    pub src_ip: bool,
+    pub dst_ip: bool,
+    pub protocol: bool,
+    /// Source port of TCP, UDP and SCTP packets
+    pub src_port: bool,
+    /// Destination port of TCP, UDP and SCTP packets. The SPI of ESP packets is hashed if
+    /// either port is selected.
+    pub dst_port: bool,
+}
+
+impl HashFields {
+    /// Source and destination addresses, protocol, source and destination ports
+    pub const FIVE_TUPLE: HashFields = HashFields {
+        src_ip: true,
+        dst_ip: true,
+        protocol: true,
+        src_port: true,
+        dst_port: true,
+    };
+    /// Source and destination addresses, protocol
+    pub const THREE_TUPLE: HashFields = HashFields {
+        src_ip: true,
+        dst_ip: true,
+        protocol: true,
+        src_port: false,
+        dst_port: false,
+    };
+    /// Source and destination addresses only
+    pub const TWO_TUPLE: HashFields = HashFields {
+        src_ip: true,
+        dst_ip: true,
+        protocol: false,
+        src_port: false,
+        dst_port: false,
+    };
+
+    fn any_port(self) -> bool {
+        self.src_port || self.dst_port
+    }
+}
+
+impl Default for HashFields {
+    fn default() -> Self {
+        Self::FIVE_TUPLE
+    }
+}
+
+fn hash_ports<P: Hash, H: Hasher>(fields: HashFields, source: P, destination: P, state: &mut H) {
+    if fields.src_port {
+        source.hash(state);
+    }
+    if fields.dst_port {
+        destination.hash(state);
+    }
+}
+
 impl<Buf: PacketBufferMut> Packet<Buf> {
     #[allow(unused)]
     /// Computes a hash over a `Packet` object if it contains an ipv4 or ipv6 packet,
     /// using invariant fields of the ip header and common transport headers,
-    /// if present, using the specified Hasher.
+    /// or the SPI of ESP packets, if present, using the specified Hasher.
     pub fn hash_ip<H: Hasher>(&self, state: &mut H) {
+        self.hash_ip_fields(HashFields::FIVE_TUPLE, state);
+    }
+
+    /// Same as [`Packet::hash_ip`], but only hashes over the header fields selected in `fields`.
+    pub fn hash_ip_fields<H: Hasher>(&self, fields: HashFields, state: &mut H) {
         if let Some(ip) = self.headers().try_ip() {
             match ip {
                 Net::Ipv4(ipv4) => {
-                    ipv4.source().hash(state);
-                    ipv4.destination().hash(state);
-                    ipv4.protocol().hash(state);
+                    if fields.src_ip {
+                        ipv4.source().hash(state);
+                    }
+                    if fields.dst_ip {
+                        ipv4.destination().hash(state);
+                    }
+                    if fields.protocol {
+                        ipv4.protocol().hash(state);
+                    }
                 }
                 Net::Ipv6(ipv6) => {
-                    ipv6.source().hash(state);
-                    ipv6.destination().hash(state);
-                    ipv6.next_header().hash(state);
+                    if fields.src_ip {
+                        ipv6.source().hash(state);
+                    }
+                    if fields.dst_ip {
+                        ipv6.destination().hash(state);
+                    }
+                    if fields.protocol {
+                        ipv6.next_header().hash(state);
+                    }
                 }
             }
+            if !fields.any_port() {
+                return;
+            }
             if let Some(transport) = self.headers().try_transport() {
                 match transport {
                     Transport::Tcp(tcp) => {
-                        tcp.source().hash(state);
-                        tcp.destination().hash(state);
+                        hash_ports(fields, tcp.source(), tcp.destination(), state);
                     }
                     Transport::Udp(udp) => {
-                        udp.source().hash(state);
-                        udp.destination().hash(state);
+                        hash_ports(fields, udp.source(), udp.destination(), state);
+                    }
+                    Transport::Sctp(sctp) => {
+                        hash_ports(fields, sctp.source(), sctp.destination(), state);
                     }
                     &Transport::Icmp4(_) | &Transport::Icmp6(_) => {}
                 }
+            } else if let Some(esp) = self.headers().try_esp() {
+                // encrypted traffic: use the security association instead of the ports
+                esp.spi().hash(state);
             }
         }
     }
@@ -68,6 +152,14 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
         hasher.finish() % u64::from(last - first + 1) + u64::from(first)
     }
 
+    /// Computes a hash over the header fields selected in `fields`, for path selection.
+    #[must_use]
+    pub fn packet_hash_fields(&self, fields: HashFields) -> u64 {
+        let mut hasher = AHasher::default();
+        self.hash_ip_fields(fields, &mut hasher);
+        hasher.finish()
+    }
+
     #[allow(unused)]
     /// Uses the `hash_l2_frame` `Packet` method to provide a hash in the range \[49152,65535\] suitable
     /// as UDP source port for vxlan-encapsulated packets, as recommended by RFC7348.
@@ -82,8 +174,8 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
 #[cfg(test)]
 mod tests {
     use crate::buffer::{PacketBufferMut, TestBuffer};
-    use crate::packet::Packet;
     use crate::packet::test_utils::*;
+    use crate::packet::{HashFields, Packet};
     use ahash::AHasher;
     use ordermap::OrderMap;
     use std::collections::BTreeMap;
@@ -152,6 +244,44 @@ mod tests {
         assert_eq!(fingerprint, reference);
     }
 
+    #[test]
+    fn test_hash_fields() {
+        let packet1 = build_test_udp_ipv4_packet("10.0.0.1", "10.0.0.2", 1000, 80);
+        let packet2 = build_test_udp_ipv4_packet("10.0.0.1", "10.0.0.2", 2000, 80);
+        let packet3 = build_test_udp_ipv4_packet("10.0.0.3", "10.0.0.2", 1000, 80);
+
+        // the 5-tuple hash is the hash used by default
+        assert_eq!(
+            packet1.packet_hash_fields(HashFields::FIVE_TUPLE),
+            hash_ip_packet(&packet1)
+        );
+        assert_ne!(
+            packet1.packet_hash_fields(HashFields::FIVE_TUPLE),
+            packet2.packet_hash_fields(HashFields::FIVE_TUPLE)
+        );
+        // ports are ignored in the 3-tuple hash
+        assert_eq!(
+            packet1.packet_hash_fields(HashFields::THREE_TUPLE),
+            packet2.packet_hash_fields(HashFields::THREE_TUPLE)
+        );
+        assert_ne!(
+            packet1.packet_hash_fields(HashFields::THREE_TUPLE),
+            packet3.packet_hash_fields(HashFields::THREE_TUPLE)
+        );
+        // only the destination port selected
+        let dst_only = HashFields {
+            src_ip: false,
+            dst_ip: false,
+            protocol: false,
+            src_port: false,
+            dst_port: true,
+        };
+        assert_eq!(
+            packet1.packet_hash_fields(dst_only),
+            packet3.packet_hash_fields(dst_only)
+        );
+    }
+
     #[test]
     #[allow(clippy::cast_precision_loss)]
     fn test_hash_bounds() {
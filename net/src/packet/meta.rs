@@ -4,6 +4,9 @@
 #![allow(missing_docs)] // TODO
 
 use crate::interface::InterfaceIndex;
+use crate::mpls::MplsLabel;
+use crate::packet::Annotations;
+use crate::vlan::Vid;
 use crate::vxlan::Vni;
 use bitflags::bitflags;
 use concurrency::sync::Arc;
@@ -27,6 +30,10 @@ impl PortIndex {
     pub const fn new(value: u16) -> Self {
         Self(value)
     }
+    #[must_use]
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
 }
 impl Display for PortIndex {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -52,7 +59,12 @@ impl BridgeDomain {
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Ord, PartialOrd, Serialize)]
 #[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
 pub enum VpcDiscriminant {
+    /// VXLAN network identifier
     VNI(Vni),
+    /// MPLS (service) label
+    MPLS(MplsLabel),
+    /// 802.1ad (outer, inner) VLAN pair
+    QinQ { outer: Vid, inner: Vid },
 }
 
 impl VpcDiscriminant {
@@ -60,6 +72,14 @@ impl VpcDiscriminant {
     pub fn from_vni(vni: Vni) -> Self {
         Self::VNI(vni)
     }
+    #[must_use]
+    pub fn from_mpls_label(label: MplsLabel) -> Self {
+        Self::MPLS(label)
+    }
+    #[must_use]
+    pub fn from_vlans(outer: Vid, inner: Vid) -> Self {
+        Self::QinQ { outer, inner }
+    }
 }
 impl AsRef<VpcDiscriminant> for VpcDiscriminant {
     fn as_ref(&self) -> &VpcDiscriminant {
@@ -73,12 +93,36 @@ impl From<Vni> for VpcDiscriminant {
     }
 }
 
+impl From<MplsLabel> for VpcDiscriminant {
+    fn from(label: MplsLabel) -> Self {
+        Self::MPLS(label)
+    }
+}
+
+impl From<(Vid, Vid)> for VpcDiscriminant {
+    fn from((outer, inner): (Vid, Vid)) -> Self {
+        Self::QinQ { outer, inner }
+    }
+}
+
 impl TryFrom<VpcDiscriminant> for Vni {
     type Error = ();
 
     fn try_from(value: VpcDiscriminant) -> Result<Self, Self::Error> {
         match value {
             VpcDiscriminant::VNI(vni) => Ok(vni),
+            _ => Err(()),
+        }
+    }
+}
+
+impl TryFrom<VpcDiscriminant> for MplsLabel {
+    type Error = ();
+
+    fn try_from(value: VpcDiscriminant) -> Result<Self, Self::Error> {
+        match value {
+            VpcDiscriminant::MPLS(label) => Ok(label),
+            _ => Err(()),
         }
     }
 }
@@ -87,6 +131,8 @@ impl Display for VpcDiscriminant {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             VpcDiscriminant::VNI(vni) => write!(f, "VNI({vni})"),
+            VpcDiscriminant::MPLS(label) => write!(f, "MPLS({label})"),
+            VpcDiscriminant::QinQ { outer, inner } => write!(f, "QinQ({outer}.{inner})"),
         }
     }
 }
@@ -131,10 +177,27 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// The checksums to be computed by hardware on transmission, which
+    /// [`Packet::update_checksums`](crate::packet::Packet::update_checksums) prepares for it
+    /// instead of computing them.
+    ///
+    /// These flags are set by the driver, for the packets sent on a port with the matching
+    /// transmit offloads enabled, and the driver requests the computation of the checksums from
+    /// the NIC.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct ChecksumOffload: u8 {
+        const IPV4 = 0b0000_0001; /* IPv4 header checksum */
+        const TCP  = 0b0000_0010; /* TCP checksum */
+        const UDP  = 0b0000_0100; /* UDP checksum */
+    }
+}
+
 #[allow(unused)]
 #[derive(Debug, Default, Clone)]
 pub struct PacketMeta {
     flags: MetaFlags,
+    checksum_offload: ChecksumOffload, /* checksums to be computed by hardware on transmission */
     pub iport: Option<PortIndex>, /* ingress port index - set on rx by driver, read by ingress */
     pub iif: Option<InterfaceIndex>, /* incoming interface: set by ingress stage */
     pub oif: Option<InterfaceIndex>, /* outgoing interface - set by IO manager for outgoing traffic or forwarding functions */
@@ -146,6 +209,7 @@ pub struct PacketMeta {
     pub src_vpcd: Option<VpcDiscriminant>, /* the vpc discriminant of a received encapsulated packet */
     pub dst_vpcd: Option<VpcDiscriminant>, /* the vpc discriminant of a packet to be (or already) re-encapsulated by the gateway */
     pub flow_info: Option<Arc<FlowInfo>>, /* flow specific information that can be looked up in the flow table */
+    pub annotations: Annotations, /* typed annotations attached by stages (ingress vpc, trace id, ...) */
 }
 impl PacketMeta {
     #[must_use]
@@ -196,6 +260,13 @@ impl PacketMeta {
         }
     }
     #[must_use]
+    pub fn checksum_offload(&self) -> ChecksumOffload {
+        self.checksum_offload
+    }
+    pub fn set_checksum_offload(&mut self, offload: ChecksumOffload) {
+        self.checksum_offload = offload;
+    }
+    #[must_use]
     pub fn keep(&self) -> bool {
         self.flags.contains(MetaFlags::KEEP)
     }
@@ -3,7 +3,10 @@
 
 //! Packet struct and methods
 
+mod annotations;
+mod builder;
 mod display;
+mod fragment;
 mod hash;
 mod meta;
 
@@ -13,23 +16,38 @@ pub use contract::*;
 #[cfg(any(doc, test, feature = "test_buffer"))]
 pub mod test_utils;
 
-use crate::buffer::{Headroom, PacketBufferMut, Prepend, Tailroom, TrimFromStart};
+use crate::buffer::{
+    Append, Headroom, PacketBufferMut, Prepend, Segments, Tailroom, TrimFromEnd, TrimFromStart,
+};
 use crate::eth::Eth;
 use crate::eth::EthError;
+use crate::eth::ethtype::EthType;
+use crate::geneve::{Geneve, GeneveEncap};
+use crate::gre::Gre;
 use crate::headers::{
     AbstractEmbeddedHeaders, AbstractEmbeddedHeadersMut, AbstractHeaders, AbstractHeadersMut,
-    Headers, Net, Transport, TryEmbeddedHeaders, TryEmbeddedHeadersMut, TryHeaders, TryHeadersMut,
-    TryIpMut, TryVxlan,
+    Headers, MplsStackError, Net, Transport, TryEmbeddedHeaders, TryEmbeddedHeadersMut, TryGeneve,
+    TryGre, TryHeaders, TryHeadersMut, TryIpMut, TryVxlan, TryVxlanGpe,
 };
+use crate::ip::NextHeader;
+use crate::ipv6::{Ipv6, Ipv6Srh, SrhError};
+use crate::mpls::{Mpls, MplsLabel};
 use crate::parse::{DeParse, Parse, ParseError};
-use crate::udp::{Udp, UdpChecksum};
+use crate::udp::{Udp, UdpChecksum, UdpEncap, UdpPort};
 
 use crate::checksum::Checksum;
-use crate::vxlan::{Vxlan, VxlanEncap};
+use crate::vxlan::{Vxlan, VxlanEncap, VxlanGpe};
+#[allow(unused_imports)] // re-export
+pub use annotations::*;
+#[allow(unused_imports)] // re-export
+pub use builder::*;
+#[allow(unused_imports)] // re-export
+pub use fragment::*;
 #[allow(unused_imports)] // re-export
 pub use hash::*;
 #[allow(unused_imports)] // re-export
 pub use meta::*;
+use std::net::Ipv6Addr;
 use std::num::NonZero;
 
 pub mod utils;
@@ -49,12 +67,32 @@ pub struct InvalidPacket<Buf: PacketBufferMut> {
     #[allow(unused)]
     mbuf: Buf,
     #[source]
-    error: ParseError<EthError>,
+    pub(crate) error: ParseError<EthError>,
+}
+
+/// Errors which may occur when replacing the payload of a [`Packet`]
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum SetPayloadError {
+    /// The packet is not a UDP packet, or it carries a UDP encapsulation
+    #[error("not a plain UDP packet")]
+    NotUdp,
+    /// The payload of the packet is split over several segments
+    #[error("multi-segment payload")]
+    MultiSegment,
+    /// The new payload would exceed the maximum length of the packet
+    #[error("payload too long: {0} bytes")]
+    TooLong(usize),
+    /// The buffer does not have enough tailroom to grow the payload
+    #[error("not enough tailroom")]
+    NotEnoughTailroom,
 }
 
 impl<Buf: PacketBufferMut> Packet<Buf> {
     /// Map a `PacketBufferMut` to a `Packet` if the buffer contains a valid ethernet packet.
     ///
+    /// The headers are parsed from the first segment of the buffer (see [`Segments`]); the
+    /// following segments, if any, are left untouched as part of the payload.
+    ///
     /// # Errors
     ///
     /// Returns an [`InvalidPacket`] error the buffer does not parse as an ethernet frame.
@@ -85,15 +123,66 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
         self.headers.set_eth(eth);
     }
 
+    /// Get the MPLS label stack of this packet, outermost entry first
+    #[must_use]
+    pub fn mpls_labels(&self) -> &[Mpls] {
+        &self.headers.mpls
+    }
+
+    /// Get a mutable reference to the outermost MPLS label stack entry, if any
+    pub fn outer_mpls_mut(&mut self) -> Option<&mut Mpls> {
+        self.headers.outer_mpls_mut()
+    }
+
+    /// Get the segment routing header of this packet. See [`Headers::srh`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`SrhError`] if the packet has no valid segment routing header.
+    pub fn srh(&self) -> Result<Ipv6Srh, SrhError> {
+        self.headers.srh()
+    }
+
+    /// Move to the next segment of the segment routing header of this packet.
+    /// See [`Headers::srv6_advance`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`SrhError`] if the packet has no valid segment routing header or no segment
+    /// left.
+    pub fn srv6_advance(&mut self) -> Result<Ipv6Addr, SrhError> {
+        self.headers.srv6_advance()
+    }
+
+    /// Push an MPLS label on top of the label stack of this packet.
+    /// See [`Headers::push_mpls`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`MplsStackError`] if the label can't be pushed.
+    pub fn push_mpls(&mut self, label: MplsLabel, ttl: u8) -> Result<(), MplsStackError> {
+        self.headers.push_mpls(label, ttl)
+    }
+
+    /// Pop the outermost MPLS label of this packet. See [`Headers::pop_mpls`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`MplsStackError`] if the label can't be popped.
+    pub fn pop_mpls(&mut self) -> Result<Option<Mpls>, MplsStackError> {
+        self.headers.pop_mpls()
+    }
+
     /// Get the length of the packet's payload
     ///
     /// # Note
     ///
     /// Manipulating the parsed headers _does not_ change the length returned by this method.
+    /// The length covers all the segments of the payload.
     #[allow(clippy::cast_possible_truncation)] // checked in ctor
     #[must_use]
     pub fn payload_len(&self) -> u16 {
-        self.payload.as_ref().len() as u16
+        self.payload.data_len() as u16
     }
 
     /// Get the length of the packet's current headers.
@@ -173,9 +262,136 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
         }
     }
 
-    /// Encapsulate the packet in the supplied [`Vxlan`] [`Headers`]
+    /// If the [`Packet`] is [`Gre`], then this method
+    ///
+    /// 1. strips the outer headers
+    /// 2. parses the inner headers
+    /// 3. adjusts the `Buf` to start at the beginning of the inner packet.
+    /// 4. mutates self to use the newly parsed headers
+    /// 5. returns the (now removed) [`Gre`] header.
+    ///
+    /// If the GRE payload is an ethernet frame ([`EthType::TRANSPARENT_ETHERNET_BRIDGING`]), it
+    /// replaces the outer headers entirely, as for [`Packet::vxlan_decap`].
+    /// Otherwise (e.g., IP in GRE), the outer ethernet header is kept, with its ethertype set to
+    /// the protocol of the GRE payload, and the outer VLAN tags are removed.
+    ///
+    /// # Errors
+    ///
+    /// * returns `None` (and does not modify `self`) if the packet is not [`Gre`].
+    /// * returns `Some(Err(ParseError<EthError>))` if the inner packet cannot be parsed as a legal
+    ///   frame.  In this case, `self` will not be modified.
+    pub fn gre_decap(&mut self) -> Option<Result<Gre, ParseError<EthError>>> {
+        let gre = *self.headers.try_gre()?;
+        Some(self.decap_payload(gre.protocol()).map(|_| gre))
+    }
+
+    /// If the [`Packet`] is an IPv6 packet carrying an IPv4 or IPv6 packet (e.g., at the end of
+    /// an SRv6 path), then this method
+    ///
+    /// 1. strips the outer IPv6 header and its extension headers
+    /// 2. parses the inner headers
+    /// 3. adjusts the `Buf` to start at the beginning of the inner packet.
+    /// 4. mutates self to use the newly parsed headers
+    /// 5. returns the (now removed) outer [`Ipv6`] header.
+    ///
+    /// The outer ethernet header is kept, with its ethertype set to the inner protocol, as for
+    /// [`Packet::gre_decap`].
+    ///
+    /// # Errors
+    ///
+    /// * returns `None` (and does not modify `self`) if the packet is not IPv6, or if its payload
+    ///   is not an IP packet.
+    /// * returns `Some(Err(ParseError<EthError>))` if the inner packet cannot be parsed as a legal
+    ///   frame.  In this case, `self` will not be modified.
+    pub fn ipv6_decap(&mut self) -> Option<Result<Ipv6, ParseError<EthError>>> {
+        let protocol = match self.headers.ipv6_payload_protocol()? {
+            NextHeader::IPV4 => EthType::IPV4,
+            NextHeader::IPV6 => EthType::IPV6,
+            _ => return None,
+        };
+        Some(self.decap_payload(protocol).map(|outer| match outer.net {
+            Some(Net::Ipv6(ipv6)) => ipv6,
+            _ => unreachable!(),
+        }))
+    }
+
+    /// If the [`Packet`] is [`Geneve`], then this method
+    ///
+    /// 1. strips the outer headers
+    /// 2. parses the inner headers
+    /// 3. adjusts the `Buf` to start at the beginning of the inner packet.
+    /// 4. mutates self to use the newly parsed headers
+    /// 5. returns the (now removed) [`Geneve`] header.
+    ///
+    /// The payload is handled according to the protocol of the [`Geneve`] header, as in
+    /// [`Packet::gre_decap`].
+    ///
+    /// # Errors
+    ///
+    /// * returns `None` (and does not modify `self`) if the packet is not [`Geneve`].
+    /// * returns `Some(Err(ParseError<EthError>))` if the inner packet cannot be parsed as a legal
+    ///   frame.  In this case, `self` will not be modified.
+    pub fn geneve_decap(&mut self) -> Option<Result<Geneve, ParseError<EthError>>> {
+        let protocol = self.headers.try_geneve()?.protocol();
+        Some(
+            self.decap_payload(protocol)
+                .map(|outer| match outer.udp_encap {
+                    Some(UdpEncap::Geneve(geneve)) => geneve,
+                    _ => unreachable!(),
+                }),
+        )
+    }
+
+    /// If the [`Packet`] is [`VxlanGpe`], then this method
+    ///
+    /// 1. strips the outer headers
+    /// 2. parses the inner headers
+    /// 3. adjusts the `Buf` to start at the beginning of the inner packet.
+    /// 4. mutates self to use the newly parsed headers
+    /// 5. returns the (now removed) [`VxlanGpe`] header.
+    ///
+    /// The payload is handled according to the protocol of the [`VxlanGpe`] header, as in
+    /// [`Packet::gre_decap`].
+    ///
+    /// # Errors
+    ///
+    /// * returns `None` (and does not modify `self`) if the packet is not [`VxlanGpe`], or if the
+    ///   protocol of its payload is unknown.
+    /// * returns `Some(Err(ParseError<EthError>))` if the inner packet cannot be parsed as a legal
+    ///   frame.  In this case, `self` will not be modified.
+    pub fn vxlan_gpe_decap(&mut self) -> Option<Result<VxlanGpe, ParseError<EthError>>> {
+        let gpe = *self.headers.try_vxlan_gpe()?;
+        let protocol = gpe.protocol().ethtype()?;
+        Some(self.decap_payload(protocol).map(|_| gpe))
+    }
+
+    /// Replace the headers with those parsed from the payload, which is of type `protocol`, and
+    /// return the previous (outer) headers.
+    ///
+    /// Ethernet payloads replace the outer headers entirely.
+    /// Other payloads are parsed as the payload of the outer ethernet header.
+    fn decap_payload(&mut self, protocol: EthType) -> Result<Headers, ParseError<EthError>> {
+        let (headers, consumed) = if protocol == EthType::TRANSPARENT_ETHERNET_BRIDGING {
+            Headers::parse(self.payload.as_ref())
+                .map(|(headers, consumed)| (headers, consumed.get()))?
+        } else {
+            let Some(mut eth) = self.headers.eth.clone() else {
+                unreachable!("packets are always parsed from an ethernet header");
+            };
+            eth.set_ether_type(protocol);
+            Headers::parse_with_eth(eth, self.payload.as_ref())?
+        };
+        self.payload
+            .trim_from_start(consumed)
+            .unwrap_or_else(|e| unreachable!("{e:?}"));
+        Ok(std::mem::replace(&mut self.headers, headers))
+    }
+
+    /// Encapsulate the packet in the supplied [`Vxlan`] (or [`VxlanGpe`]) [`Headers`]
     ///
     /// * The supplied [`Headers`] will be validated to ensure they form a VXLAN header.
+    /// * VXLAN-GPE headers are sent to [`VxlanGpe::PORT`].  As the whole frame is encapsulated,
+    ///   their protocol should be [`VxlanGpeProtocol::ETHERNET`](crate::vxlan::VxlanGpeProtocol).
     /// * If the supplied headers describe an IPv4 encapsulation, then the IPv4 checksum will be
     ///   updated.
     /// * The IPv4 / IPv6 headers will be updated to correctly describe the length of the packet.
@@ -189,9 +405,51 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
     ///
     /// This method will panic if the resulting mbuf has a UDP length field longer than 2^16
     /// bytes.
-    /// This is extremely unlikely in that the maximum mbuf length is far less than that, and we
-    /// don't currently support multi-segment packets.
+    /// This is extremely unlikely in that the maximum frame length (jumbo frames included) is far
+    /// less than that.
     pub fn vxlan_encap(&mut self, params: &VxlanEncap) -> Result<(), <Buf as Prepend>::Error> {
+        let port = match params.headers().udp_encap {
+            Some(UdpEncap::VxlanGpe(_)) => VxlanGpe::PORT,
+            _ => Vxlan::PORT,
+        };
+        self.udp_encap(params.headers().clone(), Vxlan::MIN_LENGTH.get(), port)
+    }
+
+    /// Encapsulate the packet in the supplied [`Geneve`] [`Headers`]
+    ///
+    /// * The supplied [`Headers`] will be validated to ensure they form a Geneve header.
+    /// * If the supplied headers describe an IPv4 encapsulation, then the IPv4 checksum will be
+    ///   updated.
+    /// * The IPv4 / IPv6 headers will be updated to correctly describe the length of the packet.
+    ///
+    /// # Errors
+    ///
+    /// If the buffer is unable to prepend the supplied [`Headers`], this method will return a
+    /// `<Buf as Prepend>::PrependFailed` `Err` variant.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the resulting mbuf has a UDP length field longer than 2^16
+    /// bytes (see [`Packet::vxlan_encap`]).
+    pub fn geneve_encap(&mut self, params: &GeneveEncap) -> Result<(), <Buf as Prepend>::Error> {
+        let headers = params.headers().clone();
+        let encap_len = headers
+            .try_geneve()
+            .unwrap_or_else(|| unreachable!()) // checked in GeneveEncap::new
+            .size()
+            .get();
+        self.udp_encap(headers, encap_len, Geneve::PORT)
+    }
+
+    /// Prepend the current headers to the payload, and replace them with `headers`, which have a
+    /// UDP encapsulation header of length `encap_len` sent to `port`.
+    /// The UDP header is built here.
+    fn udp_encap(
+        &mut self,
+        mut headers: Headers,
+        encap_len: u16,
+        port: UdpPort,
+    ) -> Result<(), <Buf as Prepend>::Error> {
         // refresh checksums if told to. N.B. this is DISABLED as the (single) caller does this.
         // TODO: decide if this should be done here or not.
         #[allow(clippy::overly_complex_bool_expr)]
@@ -205,8 +463,7 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
             .deparse(buf)
             .unwrap_or_else(|e| unreachable!("{e:?}", e = e));
 
-        let len = self.payload.as_ref().len()
-            + (Udp::MIN_LENGTH.get() + Vxlan::MIN_LENGTH.get()) as usize;
+        let len = self.payload.data_len() + (Udp::MIN_LENGTH.get() + encap_len) as usize;
         assert!(
             u16::try_from(len).is_ok(),
             "encap would result in frame larger than 2^16 bytes"
@@ -218,8 +475,8 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
             .try_into()
             .unwrap_or_else(|_| unreachable!());
 
-        // build UDP header for Vxlan, setting ports, length and checksum.
-        let mut udp = Udp::new(udp_src_port, Vxlan::PORT);
+        // build UDP header for the encapsulation, setting ports, length and checksum.
+        let mut udp = Udp::new(udp_src_port, port);
         #[allow(clippy::cast_possible_truncation)] // checked
         let udp_len = NonZero::new(len as u16).unwrap_or_else(|| unreachable!());
         #[allow(unsafe_code)] // sound usage due to length check
@@ -227,11 +484,10 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
             udp.set_length(udp_len);
         }
 
-        // the VXLAN spec says that the checksum SHOULD be zero
+        // the VXLAN spec says that the checksum SHOULD be zero, and Geneve allows it
         udp.set_checksum(UdpChecksum::ZERO)
             .unwrap_or_else(|()| unreachable!()); // setting UDP checksum never fails
 
-        let mut headers = params.headers().clone();
         headers.transport = Some(Transport::Udp(udp));
         match headers.try_ip_mut() {
             None => unreachable!(),
@@ -250,9 +506,100 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
         Ok(())
     }
 
+    /// Replace the payload of a UDP packet with `payload`, growing or shrinking the buffer as
+    /// needed.
+    ///
+    /// The UDP length and the IP payload length are updated to account for the new payload.
+    /// Checksums are not: see [`Packet::update_checksums`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SetPayloadError`] if the packet is not a UDP packet (or if it carries a UDP
+    /// encapsulation), if its payload is not contiguous, or if the buffer cannot hold the new
+    /// payload. The packet is left unchanged in that case.
+    pub fn set_udp_payload(&mut self, payload: &[u8]) -> Result<(), SetPayloadError> {
+        if self.headers.udp_encap.is_some() {
+            return Err(SetPayloadError::NotUdp);
+        }
+        let Some(Transport::Udp(udp)) = &self.headers.transport else {
+            return Err(SetPayloadError::NotUdp);
+        };
+        if self.payload.num_segments() > 1 {
+            return Err(SetPayloadError::MultiSegment);
+        }
+        let old_len = self.payload.data_len();
+        let new_len = payload.len();
+        // Length of the headers covered by the length fields, in addition to the payload
+        let udp_overhead = usize::from(udp.length().get()).saturating_sub(old_len);
+        let (ip_overhead, ip_header_len) = match &self.headers.net {
+            Some(Net::Ipv4(ipv4)) => (
+                usize::from(ipv4.total_len()).saturating_sub(ipv4.header_len() + old_len),
+                ipv4.header_len(),
+            ),
+            Some(Net::Ipv6(ipv6)) => (
+                usize::from(ipv6.payload_length()).saturating_sub(old_len),
+                0,
+            ),
+            None => return Err(SetPayloadError::NotUdp),
+        };
+        let too_long = || SetPayloadError::TooLong(new_len);
+        let udp_len = u16::try_from(udp_overhead + new_len).map_err(|_| too_long())?;
+        // For IPv4, check that the total length, including the header, fits
+        u16::try_from(ip_header_len + ip_overhead + new_len).map_err(|_| too_long())?;
+        #[allow(clippy::cast_possible_truncation)] // checked just above
+        let ip_payload_len = (ip_overhead + new_len) as u16;
+
+        if new_len > old_len {
+            let grow = u16::try_from(new_len - old_len).map_err(|_| too_long())?;
+            self.payload
+                .append(grow)
+                .map_err(|_| SetPayloadError::NotEnoughTailroom)?;
+        } else {
+            #[allow(clippy::cast_possible_truncation)] // shorter than the current payload
+            self.payload
+                .trim_from_end((old_len - new_len) as u16)
+                .unwrap_or_else(|e| unreachable!("{e:?}"));
+        }
+        self.payload.as_mut()[..new_len].copy_from_slice(payload);
+
+        if let Some(Transport::Udp(udp)) = &mut self.headers.transport {
+            let udp_len = NonZero::new(udp_len).unwrap_or_else(|| unreachable!());
+            #[allow(unsafe_code)] // sound: at least the UDP header length, as it was before
+            unsafe {
+                udp.set_length(udp_len);
+            }
+        }
+        match &mut self.headers.net {
+            Some(Net::Ipv4(ipv4)) => {
+                ipv4.set_payload_len(ip_payload_len)
+                    .unwrap_or_else(|e| unreachable!("{e:?}"));
+            }
+            Some(Net::Ipv6(ipv6)) => {
+                ipv6.set_payload_length(ip_payload_len);
+            }
+            None => unreachable!(),
+        }
+        Ok(())
+    }
+
     /// Update the network and transport checksums based on the current headers.
+    ///
+    /// Checksums flagged for hardware offload in the packet's [`ChecksumOffload`] metadata, if the
+    /// headers allow it (see [`Headers::offloadable_checksums`]), are prepared for the hardware
+    /// instead: the IPv4 checksum is zeroed, and the transport checksum is seeded with the sum of
+    /// the pseudo-header. The driver is responsible for requesting their computation on
+    /// transmission.
+    ///
+    /// The transport checksum is computed over contiguous data: for multi-segment payloads, the
+    /// segments are gathered into a temporary copy, unless that checksum is offloaded.
     pub fn update_checksums(&mut self) -> &mut Self {
-        self.headers.update_checksums(&self.payload);
+        let offload = self.get_meta().checksum_offload();
+        if self.payload.num_segments() > 1 && !self.headers.transport_checksum_offloaded(offload) {
+            let payload = self.payload.segments().collect::<Vec<_>>().concat();
+            self.headers.update_checksums(payload, offload);
+        } else {
+            self.headers.update_checksums(&self.payload, offload);
+        }
         self.get_meta_mut().set_checksum_refresh(false);
         self
     }
@@ -265,6 +612,17 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
     /// serialize.
     pub fn serialize(mut self) -> Result<Buf, <Buf as Prepend>::Error> {
         self.update_checksums();
+        self.deparse_headers()
+    }
+
+    /// Write the packet's [`Headers`] back to its buffer, as they are (i.e., without updating the
+    /// checksums).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Prepend::Error`] error if the packet does not have enough headroom to
+    /// serialize.
+    pub(crate) fn deparse_headers(mut self) -> Result<Buf, <Buf as Prepend>::Error> {
         let needed = self.headers.size().get();
         let buf = self.payload.prepend(needed)?;
         self.headers
@@ -380,6 +738,11 @@ impl<Buf: PacketBufferMut> Packet<Buf> {
     pub(crate) fn get_headers(&self) -> &Headers {
         &self.headers
     }
+
+    /// Get a mutable reference to the headers of this `Packet`
+    pub(crate) fn get_headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
 }
 
 #[cfg(any(test, feature = "bolero"))]
@@ -482,8 +845,13 @@ pub mod contract {
                     Headers {
                         eth: Some(eth),
                         vlan: ArrayVec::default(),
+                        pppoe: None,
+                        nsh: None,
+                        mpls: ArrayVec::default(),
+                        arp: None,
                         net: Some(Net::Ipv4(ipv4)),
                         net_ext: ArrayVec::default(),
+                        gre: None,
                         transport: Some(Transport::Icmp4(icmp4)),
                         udp_encap: None,
                         embedded_ip: Some(embedded_ip),
@@ -500,8 +868,13 @@ pub mod contract {
                     Headers {
                         eth: Some(eth),
                         vlan: ArrayVec::default(),
+                        pppoe: None,
+                        nsh: None,
+                        mpls: ArrayVec::default(),
+                        arp: None,
                         net: Some(Net::Ipv6(ipv6)),
                         net_ext: ArrayVec::default(),
+                        gre: None,
                         transport: Some(Transport::Icmp6(icmp6)),
                         udp_encap: None,
                         embedded_ip: Some(embedded_ip),
@@ -23,7 +23,7 @@ use crate::ipv4::Ipv4;
 use crate::ipv4::addr::UnicastIpv4Addr;
 use crate::ipv6::Ipv6;
 use crate::ipv6::addr::UnicastIpv6Addr;
-use crate::packet::{InvalidPacket, Packet};
+use crate::packet::{InvalidPacket, Packet, PacketBuilder};
 use crate::parse::DeParse;
 use crate::tcp::{Tcp, TcpChecksumPayload, TruncatedTcp};
 use crate::udp::port::UdpPort;
@@ -52,61 +52,38 @@ pub fn build_test_ipv4_packet_with_transport(
     ttl: u8,
     transport_type: Option<NextHeader>,
 ) -> Result<Packet<TestBuffer>, InvalidPacket<TestBuffer>> {
-    let mut headers = HeadersBuilder::default();
+    let mut ipv4 = Ipv4::default();
+    ipv4.set_source(UnicastIpv4Addr::new(Ipv4Addr::new(1, 2, 3, 4)).unwrap());
+    ipv4.set_destination(Ipv4Addr::new(5, 6, 7, 8));
+    ipv4.set_ttl(ttl);
+    let builder = PacketBuilder::new()
+        .eth(Mac([0x2, 0, 0, 0, 0, 1]), Mac([0x2, 0, 0, 0, 0, 2]))
+        .ipv4(ipv4);
 
-    let mut transport = match transport_type {
+    let builder = match transport_type {
         Some(NextHeader::TCP) => {
             let mut tcp = Tcp::default();
             tcp.set_source(123.try_into().unwrap());
             tcp.set_destination(456.try_into().unwrap());
             tcp.set_syn(true);
             tcp.set_sequence_number(1);
-            Some(Transport::Tcp(tcp))
+            builder.tcp(tcp)
         }
 
         Some(NextHeader::UDP) => {
             let mut udp = Udp::default();
             udp.set_source(123.try_into().unwrap());
             udp.set_destination(456.try_into().unwrap());
-            Some(Transport::Udp(udp))
+            builder.udp(udp)
         }
 
         Some(transport_type) => panic!(
             "build_test_ipv4_packet_with_transport: Unsupported transport type: {transport_type:?}"
         ),
-        None => None,
+        None => builder,
     };
 
-    headers.eth(Some(Eth::new(
-        SourceMac::new(Mac([0x2, 0, 0, 0, 0, 1])).unwrap(),
-        DestinationMac::new(Mac([0x2, 0, 0, 0, 0, 2])).unwrap(),
-        EthType::IPV4,
-    )));
-    let mut ipv4 = Ipv4::default();
-    ipv4.set_source(UnicastIpv4Addr::new(Ipv4Addr::new(1, 2, 3, 4)).unwrap());
-    ipv4.set_destination(Ipv4Addr::new(5, 6, 7, 8));
-    ipv4.set_ttl(ttl);
-    if let Some(transport) = transport.as_ref() {
-        ipv4.set_payload_len(transport.size().get()).unwrap();
-        if let Some(transport_type) = transport_type {
-            ipv4.set_next_header(transport_type);
-        } else {
-            unreachable!("build_test_ipv4_packet_with_transport: Transport type is None here");
-        }
-    }
-
-    let net = Net::Ipv4(ipv4);
-
-    if let Some(transport) = transport.as_mut() {
-        transport.update_checksum(&net, None, []);
-    }
-
-    headers.net(Some(net));
-    headers.transport(transport);
-    let headers = headers.build().unwrap();
-    let mut buffer: TestBuffer = TestBuffer::new();
-    headers.deparse(buffer.as_mut()).unwrap();
-    Packet::new(buffer)
+    Ok(builder.build().unwrap())
 }
 
 #[must_use]
@@ -187,23 +164,17 @@ pub fn build_test_udp_ipv4_packet(
 /// The Ethernet source and destination MAC addresses are 0x02:00:00:00:00:01 and 0x02:00:00:00:00:02
 /// respectively.
 pub fn build_test_ipv6_packet(ttl: u8) -> Result<Packet<TestBuffer>, InvalidPacket<TestBuffer>> {
-    let mut headers = HeadersBuilder::default();
-    headers.eth(Some(Eth::new(
-        SourceMac::new(Mac([0x2, 0, 0, 0, 0, 1])).unwrap(),
-        DestinationMac::new(Mac([0x2, 0, 0, 0, 0, 2])).unwrap(),
-        EthType::IPV6,
-    )));
     let mut ipv6 = Ipv6::default();
     // To construct an Ipv6Addr from a string, use FromStr or "::1.2.3.4".parse()
     ipv6.set_source(UnicastIpv6Addr::new("::1.2.3.4".parse::<Ipv6Addr>().unwrap()).unwrap());
     ipv6.set_destination("::5.6.7.8".parse::<Ipv6Addr>().unwrap());
     ipv6.set_hop_limit(ttl);
-    headers.net(Some(Net::Ipv6(ipv6)));
 
-    let headers = headers.build().unwrap();
-    let mut buffer: TestBuffer = TestBuffer::new();
-    headers.deparse(buffer.as_mut()).unwrap();
-    Packet::new(buffer)
+    Ok(PacketBuilder::new()
+        .eth(Mac([0x2, 0, 0, 0, 0, 1]), Mac([0x2, 0, 0, 0, 0, 2]))
+        .ipv6(ipv6)
+        .build()
+        .unwrap())
 }
 
 #[must_use]
@@ -358,15 +329,6 @@ pub fn build_test_icmp4_echo(
     identifier: u16,
     direction: IcmpEchoDirection,
 ) -> Result<Packet<TestBuffer>, InvalidPacket<TestBuffer>> {
-    let mut headers = HeadersBuilder::default();
-
-    // Ethernet
-    headers.eth(Some(Eth::new(
-        SourceMac::new(Mac([0x2, 0, 0, 0, 0, 1])).unwrap(),
-        DestinationMac::new(Mac([0x2, 0, 0, 0, 0, 2])).unwrap(),
-        EthType::IPV4,
-    )));
-
     // ICMP Echo header
     let echo_header = IcmpEchoHeader {
         id: identifier,
@@ -383,22 +345,11 @@ pub fn build_test_icmp4_echo(
     ipv4.set_source(UnicastIpv4Addr::new(src_ip).unwrap());
     ipv4.set_destination(dst_ip);
     ipv4.set_ttl(8);
-    ipv4.set_next_header(NextHeader::ICMP);
-    ipv4.set_payload_len(icmp.size().get()).unwrap();
-    ipv4.update_checksum(&()).unwrap();
-    let net = Net::Ipv4(ipv4);
-
-    // Update ICMP checksum
-    let mut icmp_transport = Transport::Icmp4(icmp);
-    icmp_transport.update_checksum(&net, None, []);
 
-    // Build headers
-    headers.net(Some(net));
-    headers.transport(Some(icmp_transport));
-    let headers = headers.build().unwrap();
-
-    // Create packet
-    let mut buffer: TestBuffer = TestBuffer::new();
-    headers.deparse(buffer.as_mut()).unwrap();
-    Packet::new(buffer)
+    Ok(PacketBuilder::new()
+        .eth(Mac([0x2, 0, 0, 0, 0, 1]), Mac([0x2, 0, 0, 0, 0, 2]))
+        .ipv4(ipv4)
+        .icmp4(icmp)
+        .build()
+        .unwrap())
 }
@@ -0,0 +1,655 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Reading and writing of packet captures, in the [pcap] and [pcapng] file formats.
+//!
+//! [`PcapReader`] reads the records of either format (detected from the magic number of the file),
+//! and [`PcapWriter`] writes (classic) pcap files of ethernet frames, with microsecond timestamps.
+//!
+//! [pcap]: https://datatracker.ietf.org/doc/draft-ietf-opsawg-pcap/
+//! [pcapng]: https://datatracker.ietf.org/doc/draft-ietf-opsawg-pcapng/
+
+use crate::buffer::{PacketBufferMut, Segments};
+use crate::eth::EthError;
+use crate::packet::Packet;
+use crate::parse::ParseError;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+use tracing::trace;
+
+#[cfg(any(doc, test, feature = "test_buffer"))]
+use crate::buffer::TestBuffer;
+
+/// The link type of captures of ethernet frames.
+pub const LINKTYPE_ETHERNET: u32 = 1;
+
+/// Errors which may occur when reading or writing a packet capture.
+#[derive(Debug, thiserror::Error)]
+pub enum PcapError {
+    /// Reading from or writing to the underlying file failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The file is neither a pcap nor a pcapng file.
+    #[error("not a pcap or pcapng file (magic {0:#010x})")]
+    InvalidMagic(u32),
+    /// The capture ends in the middle of a header or record.
+    #[error("truncated capture")]
+    Truncated,
+    /// A pcapng block is malformed.
+    #[error("invalid pcapng block of type {block_type:#x} and length {length}")]
+    InvalidBlock {
+        /// The type of the block
+        block_type: u32,
+        /// The total length of the block
+        length: u32,
+    },
+    /// A pcapng packet block refers to an interface which has not been described.
+    #[error("packet captured on unknown pcapng interface {0}")]
+    UnknownInterface(u32),
+    /// The record is not an ethernet frame.
+    #[error("unsupported link type {0}")]
+    UnsupportedLinkType(u32),
+    /// The record does not parse as an ethernet frame.
+    #[error("invalid packet: {0}")]
+    InvalidPacket(ParseError<EthError>),
+    /// The headers of a packet could not be written back to its buffer.
+    #[error("not enough headroom to serialize packet")]
+    NoRoom,
+}
+
+/// A frame read from a packet capture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PcapRecord {
+    /// The time of capture, since the UNIX epoch
+    pub timestamp: Duration,
+    /// The link type of the frame (see [`LINKTYPE_ETHERNET`])
+    pub link_type: u32,
+    /// The length of the frame on the wire, which may exceed the captured length
+    pub original_len: u32,
+    /// The captured bytes of the frame
+    pub data: Vec<u8>,
+}
+
+impl PcapRecord {
+    /// Parse the captured frame as a [`Packet`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PcapError`] if the record is not a valid ethernet frame.
+    #[cfg(any(doc, test, feature = "test_buffer"))]
+    pub fn packet(&self) -> Result<Packet<TestBuffer>, PcapError> {
+        if self.link_type != LINKTYPE_ETHERNET {
+            return Err(PcapError::UnsupportedLinkType(self.link_type));
+        }
+        Packet::new(TestBuffer::from_raw_data(&self.data))
+            .map_err(|invalid| PcapError::InvalidPacket(invalid.error))
+    }
+}
+
+/// Magic number of pcap files with microsecond timestamps
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+/// Magic number of pcap files with nanosecond timestamps
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+/// Length of the header of pcap files, after the magic number
+const PCAP_HEADER_LEN: usize = 20;
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const PCAPNG_OPTION_END: u16 = 0;
+const PCAPNG_OPTION_TSRESOL: u16 = 9;
+/// Upper bound on the length of pcapng blocks, not to allocate unreasonable amounts of memory when
+/// reading garbage
+const PCAPNG_MAX_BLOCK_LEN: u32 = 1 << 24;
+
+/// An interface described in a pcapng section
+#[derive(Debug)]
+struct PcapNgInterface {
+    link_type: u32,
+    units_per_second: u64,
+}
+
+#[derive(Debug)]
+enum Format {
+    Pcap {
+        big_endian: bool,
+        units_per_second: u64,
+        link_type: u32,
+    },
+    PcapNg {
+        big_endian: bool,
+        interfaces: Vec<PcapNgInterface>,
+    },
+}
+
+fn read_u16(bytes: &[u8], offset: usize, big_endian: bool) -> u16 {
+    let bytes = [bytes[offset], bytes[offset + 1]];
+    if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let bytes = [
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ];
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// Convert a timestamp in units of `1 / units_per_second` seconds to a [`Duration`].
+fn timestamp(units: u64, units_per_second: u64) -> Duration {
+    #[allow(clippy::cast_possible_truncation)] // less than a second
+    let nanos = (u128::from(units % units_per_second) * 1_000_000_000
+        / u128::from(units_per_second)) as u32;
+    Duration::new(units / units_per_second, nanos)
+}
+
+/// Reader of the records of a pcap or pcapng capture.
+#[derive(Debug)]
+pub struct PcapReader<R: Read> {
+    reader: R,
+    format: Format,
+}
+
+impl PcapReader<BufReader<File>> {
+    /// Open the pcap or pcapng file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PcapError`] if the file can't be opened or is not a packet capture.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, PcapError> {
+        PcapReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Create a new [`PcapReader`], reading the header of the capture from `reader`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PcapError`] if the header can't be read, or is not a pcap or pcapng header.
+    pub fn new(mut reader: R) -> Result<Self, PcapError> {
+        let magic = Self::read_array::<4>(&mut reader)?.ok_or(PcapError::Truncated)?;
+        let format = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAPNG_SECTION_HEADER, _) => {
+                let mut format = Format::PcapNg {
+                    big_endian: false,
+                    interfaces: Vec::new(),
+                };
+                Self::read_section_header(&mut reader, &mut format)?;
+                format
+            }
+            (PCAP_MAGIC_MICROS | PCAP_MAGIC_NANOS, _)
+            | (_, PCAP_MAGIC_MICROS | PCAP_MAGIC_NANOS) => {
+                let big_endian = matches!(
+                    u32::from_be_bytes(magic),
+                    PCAP_MAGIC_MICROS | PCAP_MAGIC_NANOS
+                );
+                let nanos = read_u32(&magic, 0, big_endian) == PCAP_MAGIC_NANOS;
+                let header = Self::read_array::<PCAP_HEADER_LEN>(&mut reader)?
+                    .ok_or(PcapError::Truncated)?;
+                Format::Pcap {
+                    big_endian,
+                    units_per_second: if nanos { 1_000_000_000 } else { 1_000_000 },
+                    link_type: read_u32(&header, 16, big_endian),
+                }
+            }
+            (magic, _) => return Err(PcapError::InvalidMagic(magic)),
+        };
+        trace!("Reading capture: {format:?}");
+        Ok(PcapReader { reader, format })
+    }
+
+    /// Read `N` bytes, or nothing if the end of the capture has been reached.
+    fn read_array<const N: usize>(reader: &mut R) -> Result<Option<[u8; N]>, PcapError> {
+        let mut buf = [0u8; N];
+        let mut filled = 0;
+        while filled < N {
+            match reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(PcapError::Truncated),
+                Ok(len) => filled += len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Some(buf))
+    }
+
+    /// Read exactly `len` bytes.
+    fn read_vec(reader: &mut R, len: usize) -> Result<Vec<u8>, PcapError> {
+        let mut buf = vec![0u8; len];
+        reader.read_exact(&mut buf).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => PcapError::Truncated,
+            _ => e.into(),
+        })?;
+        Ok(buf)
+    }
+
+    /// Read the rest of a pcapng section header block, whose type has already been read, and
+    /// start a new section.
+    fn read_section_header(reader: &mut R, format: &mut Format) -> Result<(), PcapError> {
+        let header = Self::read_array::<8>(reader)?.ok_or(PcapError::Truncated)?;
+        let big_endian = match read_u32(&header, 4, false) {
+            PCAPNG_BYTE_ORDER_MAGIC => false,
+            magic if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+            _ => {
+                return Err(PcapError::InvalidBlock {
+                    block_type: PCAPNG_SECTION_HEADER,
+                    length: read_u32(&header, 0, false),
+                });
+            }
+        };
+        let length = read_u32(&header, 0, big_endian);
+        if !(28..=PCAPNG_MAX_BLOCK_LEN).contains(&length) || length % 4 != 0 {
+            return Err(PcapError::InvalidBlock {
+                block_type: PCAPNG_SECTION_HEADER,
+                length,
+            });
+        }
+        // skip version, section length and options
+        Self::read_vec(reader, length as usize - 12)?;
+        *format = Format::PcapNg {
+            big_endian,
+            interfaces: Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// Parse the body of a pcapng interface description block.
+    fn interface(body: &[u8], big_endian: bool) -> Option<PcapNgInterface> {
+        let link_type = u32::from(read_u16(body, 0, big_endian));
+        let mut units_per_second = 1_000_000;
+        let mut offset = 8;
+        while offset + 4 <= body.len() {
+            let code = read_u16(body, offset, big_endian);
+            let len = usize::from(read_u16(body, offset + 2, big_endian));
+            let value = body.get(offset + 4..offset + 4 + len)?;
+            match code {
+                PCAPNG_OPTION_END => break,
+                PCAPNG_OPTION_TSRESOL if len == 1 => {
+                    let exponent = u32::from(value[0] & 0x7f);
+                    units_per_second = if value[0] & 0x80 == 0 {
+                        10u64.checked_pow(exponent)?
+                    } else {
+                        2u64.checked_pow(exponent)?
+                    };
+                }
+                _ => {}
+            }
+            offset += 4 + len.next_multiple_of(4);
+        }
+        Some(PcapNgInterface {
+            link_type,
+            units_per_second,
+        })
+    }
+
+    fn next_pcap_record(&mut self) -> Result<Option<PcapRecord>, PcapError> {
+        let Format::Pcap {
+            big_endian,
+            units_per_second,
+            link_type,
+        } = self.format
+        else {
+            unreachable!()
+        };
+        let Some(header) = Self::read_array::<16>(&mut self.reader)? else {
+            return Ok(None);
+        };
+        let seconds = read_u32(&header, 0, big_endian);
+        let fraction = read_u32(&header, 4, big_endian);
+        let captured_len = read_u32(&header, 8, big_endian);
+        let original_len = read_u32(&header, 12, big_endian);
+        let data = Self::read_vec(&mut self.reader, captured_len as usize)?;
+        Ok(Some(PcapRecord {
+            timestamp: Duration::from_secs(seconds.into())
+                + timestamp(fraction.into(), units_per_second),
+            link_type,
+            original_len,
+            data,
+        }))
+    }
+
+    fn next_pcapng_record(&mut self) -> Result<Option<PcapRecord>, PcapError> {
+        loop {
+            let Some(block_type) = Self::read_array::<4>(&mut self.reader)? else {
+                return Ok(None);
+            };
+            if u32::from_le_bytes(block_type) == PCAPNG_SECTION_HEADER {
+                Self::read_section_header(&mut self.reader, &mut self.format)?;
+                continue;
+            }
+            let Format::PcapNg {
+                big_endian,
+                ref mut interfaces,
+            } = self.format
+            else {
+                unreachable!()
+            };
+            let block_type = read_u32(&block_type, 0, big_endian);
+            let length = Self::read_array::<4>(&mut self.reader)?.ok_or(PcapError::Truncated)?;
+            let length = read_u32(&length, 0, big_endian);
+            let invalid = PcapError::InvalidBlock { block_type, length };
+            if !(12..=PCAPNG_MAX_BLOCK_LEN).contains(&length) || length % 4 != 0 {
+                return Err(invalid);
+            }
+            // the block body is followed by a copy of the block length
+            let block = Self::read_vec(&mut self.reader, length as usize - 8)?;
+            let body = &block[..block.len() - 4];
+            match block_type {
+                PCAPNG_INTERFACE_DESCRIPTION if body.len() >= 8 => {
+                    interfaces.push(Self::interface(body, big_endian).ok_or(invalid)?);
+                }
+                PCAPNG_ENHANCED_PACKET if body.len() >= 20 => {
+                    let interface_id = read_u32(body, 0, big_endian);
+                    let interface = interfaces
+                        .get(interface_id as usize)
+                        .ok_or(PcapError::UnknownInterface(interface_id))?;
+                    let units = (u64::from(read_u32(body, 4, big_endian)) << 32)
+                        | u64::from(read_u32(body, 8, big_endian));
+                    let captured_len = read_u32(body, 12, big_endian) as usize;
+                    let data = body.get(20..20 + captured_len).ok_or(invalid)?;
+                    return Ok(Some(PcapRecord {
+                        timestamp: timestamp(units, interface.units_per_second),
+                        link_type: interface.link_type,
+                        original_len: read_u32(body, 16, big_endian),
+                        data: data.to_vec(),
+                    }));
+                }
+                PCAPNG_SIMPLE_PACKET if body.len() >= 4 => {
+                    let interface = interfaces.first().ok_or(PcapError::UnknownInterface(0))?;
+                    let original_len = read_u32(body, 0, big_endian);
+                    let data = &body[4..];
+                    let captured_len = data.len().min(original_len as usize);
+                    return Ok(Some(PcapRecord {
+                        timestamp: Duration::ZERO,
+                        link_type: interface.link_type,
+                        original_len,
+                        data: data[..captured_len].to_vec(),
+                    }));
+                }
+                PCAPNG_INTERFACE_DESCRIPTION | PCAPNG_ENHANCED_PACKET | PCAPNG_SIMPLE_PACKET => {
+                    return Err(invalid);
+                }
+                _ => trace!("Skipping pcapng block of type {block_type:#x}"),
+            }
+        }
+    }
+
+    /// Read the next record of the capture, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PcapError`] if the record can't be read or is malformed.
+    pub fn next_record(&mut self) -> Result<Option<PcapRecord>, PcapError> {
+        match self.format {
+            Format::Pcap { .. } => self.next_pcap_record(),
+            Format::PcapNg { .. } => self.next_pcapng_record(),
+        }
+    }
+
+    /// Iterate over the records of the capture, parsed as [`Packet`]s.
+    #[cfg(any(doc, test, feature = "test_buffer"))]
+    pub fn packets(self) -> impl Iterator<Item = Result<Packet<TestBuffer>, PcapError>> {
+        self.map(|record| record.and_then(|record| record.packet()))
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = Result<PcapRecord, PcapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Writer of (classic) pcap captures of ethernet frames.
+#[derive(Debug)]
+pub struct PcapWriter<W: Write> {
+    writer: W,
+}
+
+impl PcapWriter<BufWriter<File>> {
+    /// Create (or truncate) the pcap file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PcapError`] if the file can't be created.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, PcapError> {
+        PcapWriter::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// The maximum length of the captured frames
+    pub const SNAPLEN: u32 = 65535;
+
+    /// Create a new [`PcapWriter`], writing the header of the capture to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PcapError`] if the header can't be written.
+    pub fn new(mut writer: W) -> Result<Self, PcapError> {
+        let mut header = Vec::with_capacity(4 + PCAP_HEADER_LEN);
+        header.extend_from_slice(&PCAP_MAGIC_MICROS.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes()); // major version
+        header.extend_from_slice(&4u16.to_le_bytes()); // minor version
+        header.extend_from_slice(&0i32.to_le_bytes()); // time zone
+        header.extend_from_slice(&0u32.to_le_bytes()); // timestamp accuracy
+        header.extend_from_slice(&Self::SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        writer.write_all(&header)?;
+        Ok(PcapWriter { writer })
+    }
+
+    /// Write a record for the ethernet frame made of the concatenation of `segments`, captured
+    /// at `timestamp` (since the UNIX epoch).
+    fn write_segments<'a>(
+        &mut self,
+        timestamp: Duration,
+        segments: impl Iterator<Item = &'a [u8]> + Clone,
+    ) -> Result<(), PcapError> {
+        let len: usize = segments.clone().map(<[u8]>::len).sum();
+        let original_len = u32::try_from(len).unwrap_or(u32::MAX);
+        let captured_len = original_len.min(Self::SNAPLEN);
+        #[allow(clippy::cast_possible_truncation)] // pcap timestamps wrap in 2106
+        let seconds = timestamp.as_secs() as u32;
+        let mut header = [0u8; 16];
+        header[..4].copy_from_slice(&seconds.to_le_bytes());
+        header[4..8].copy_from_slice(&timestamp.subsec_micros().to_le_bytes());
+        header[8..12].copy_from_slice(&captured_len.to_le_bytes());
+        header[12..].copy_from_slice(&original_len.to_le_bytes());
+        self.writer.write_all(&header)?;
+        let mut remaining = captured_len as usize;
+        for segment in segments {
+            let len = segment.len().min(remaining);
+            self.writer.write_all(&segment[..len])?;
+            remaining -= len;
+        }
+        Ok(())
+    }
+
+    /// Write a record for the ethernet frame `frame`, captured at `timestamp` (since the UNIX
+    /// epoch).
+    ///
+    /// Frames longer than [`Self::SNAPLEN`] are truncated.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PcapError`] if the record can't be written.
+    pub fn write_frame(&mut self, timestamp: Duration, frame: &[u8]) -> Result<(), PcapError> {
+        self.write_segments(timestamp, core::iter::once(frame))
+    }
+
+    /// Serialize `packet` and write it as a record captured at `timestamp` (since the UNIX epoch).
+    ///
+    /// The buffer of the serialized packet is returned, so that it can be reused.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PcapError`] if the packet can't be serialized or the record can't be written.
+    pub fn write_packet<Buf: PacketBufferMut>(
+        &mut self,
+        timestamp: Duration,
+        packet: Packet<Buf>,
+    ) -> Result<Buf, PcapError> {
+        let buf = packet.serialize().map_err(|_| PcapError::NoRoom)?;
+        self.write_segments(timestamp, buf.segments().collect::<Vec<_>>().into_iter())?;
+        Ok(buf)
+    }
+
+    /// Flush the records written so far.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PcapError`] if flushing the underlying writer fails.
+    pub fn flush(&mut self) -> Result<(), PcapError> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// Get the underlying writer back.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::eth::mac::Mac;
+    use crate::headers::{TryIpv4, TryUdp};
+    use crate::ipv4::Ipv4;
+    use crate::ipv4::addr::UnicastIpv4Addr;
+    use crate::packet::{Packet, PacketBuilder};
+    use crate::pcap::{LINKTYPE_ETHERNET, PcapError, PcapReader, PcapRecord, PcapWriter};
+    use crate::udp::{Udp, UdpPort};
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn packet(port: u16) -> Packet<TestBuffer> {
+        let mut ip = Ipv4::default();
+        ip.set_source(UnicastIpv4Addr::new(Ipv4Addr::new(10, 0, 0, 1)).unwrap())
+            .set_destination(Ipv4Addr::new(10, 0, 0, 2));
+        let udp = Udp::new(
+            UdpPort::new_checked(1234).unwrap(),
+            UdpPort::new_checked(port).unwrap(),
+        );
+        PacketBuilder::new()
+            .eth(Mac([0x2, 0, 0, 0, 0, 1]), Mac([0x2, 0, 0, 0, 0, 2]))
+            .ipv4(ip)
+            .udp(udp)
+            .payload(vec![0xab; 32])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let mut writer = PcapWriter::new(Vec::new()).unwrap();
+        let mut frames = Vec::new();
+        for (i, port) in (0..).zip([53, 123, 5000]) {
+            let timestamp = Duration::from_micros(1_700_000_000_000_000 + i);
+            let buf = writer.write_packet(timestamp, packet(port)).unwrap();
+            frames.push((timestamp, buf.as_ref().to_vec()));
+        }
+        let capture = writer.into_inner();
+
+        let records: Vec<_> = PcapReader::new(capture.as_slice())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records.len(), frames.len());
+        for (record, (timestamp, frame)) in records.iter().zip(&frames) {
+            assert_eq!(record.timestamp, *timestamp);
+            assert_eq!(record.link_type, LINKTYPE_ETHERNET);
+            assert_eq!(record.original_len as usize, frame.len());
+            assert_eq!(&record.data, frame);
+        }
+
+        let ports: Vec<_> = PcapReader::new(capture.as_slice())
+            .unwrap()
+            .packets()
+            .map(|packet| packet.unwrap().try_udp().unwrap().destination().as_u16())
+            .collect();
+        assert_eq!(ports, [53, 123, 5000]);
+    }
+
+    #[test]
+    fn read_pcapng() {
+        let frame = packet(53).serialize().unwrap().as_ref().to_vec();
+        let mut capture = Vec::new();
+        // section header block (big endian)
+        capture.extend_from_slice(&[0x0a, 0x0d, 0x0d, 0x0a, 0, 0, 0, 28]);
+        capture.extend_from_slice(&[0x1a, 0x2b, 0x3c, 0x4d, 0, 1, 0, 0]);
+        capture.extend_from_slice(&[0xff; 8]); // unknown section length
+        capture.extend_from_slice(&[0, 0, 0, 28]);
+        // interface description block, with nanosecond timestamps
+        capture.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 32, 0, 1, 0, 0, 0, 0, 0xff, 0xff]);
+        capture.extend_from_slice(&[0, 9, 0, 1, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32]);
+        // some block we don't care about
+        capture.extend_from_slice(&[0, 0, 0, 5, 0, 0, 0, 16, 1, 2, 3, 4, 0, 0, 0, 16]);
+        // enhanced packet block
+        let padded_len = frame.len().next_multiple_of(4);
+        let block_len = u32::try_from(32 + padded_len).unwrap();
+        let frame_len = u32::try_from(frame.len()).unwrap();
+        let timestamp: u64 = 1_700_000_000_123_456_789;
+        capture.extend_from_slice(&6u32.to_be_bytes());
+        capture.extend_from_slice(&block_len.to_be_bytes());
+        capture.extend_from_slice(&0u32.to_be_bytes());
+        capture.extend_from_slice(&timestamp.to_be_bytes()); // high then low 32 bits
+        capture.extend_from_slice(&frame_len.to_be_bytes());
+        capture.extend_from_slice(&frame_len.to_be_bytes());
+        capture.extend_from_slice(&frame);
+        capture.resize(capture.len() + padded_len - frame.len(), 0);
+        capture.extend_from_slice(&block_len.to_be_bytes());
+
+        let mut reader = PcapReader::new(capture.as_slice()).unwrap();
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(
+            record,
+            PcapRecord {
+                timestamp: Duration::new(1_700_000_000, 123_456_789),
+                link_type: LINKTYPE_ETHERNET,
+                original_len: frame_len,
+                data: frame,
+            }
+        );
+        let packet = record.packet().unwrap();
+        assert_eq!(
+            packet.try_ipv4().unwrap().destination(),
+            Ipv4Addr::new(10, 0, 0, 2)
+        );
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn read_invalid_capture() {
+        assert!(matches!(
+            PcapReader::new([0u8; 24].as_slice()),
+            Err(PcapError::InvalidMagic(0))
+        ));
+        let mut writer = PcapWriter::new(Vec::new()).unwrap();
+        writer.write_frame(Duration::ZERO, &[0xff; 8]).unwrap();
+        let capture = writer.into_inner();
+        let mut reader = PcapReader::new(&capture[..capture.len() - 1]).unwrap();
+        assert!(matches!(reader.next_record(), Err(PcapError::Truncated)));
+
+        let mut reader = PcapReader::new(capture.as_slice()).unwrap();
+        let record = reader.next_record().unwrap().unwrap();
+        assert!(matches!(record.packet(), Err(PcapError::InvalidPacket(_))));
+    }
+}
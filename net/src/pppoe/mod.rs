@@ -0,0 +1,426 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [PPPoE][RFC2516] types and parsing.
+//!
+//! We only parse the PPPoE header (and the PPP protocol field of session packets) so that
+//! PPPoE-encapsulated traffic can be classified and forwarded.
+//! The tags of discovery packets are left in the payload.
+//!
+//! [RFC2516]: https://datatracker.ietf.org/doc/html/rfc2516#section-4
+
+use crate::eth::ethtype::EthType;
+use crate::eth::{EthNext, parse_from_ethertype};
+use crate::parse::{
+    DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError, Reader,
+};
+use core::num::NonZero;
+use tracing::trace;
+
+/// The code of a [`Pppoe`] header.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+pub struct PppoeCode(pub u8);
+
+impl PppoeCode {
+    /// Session data
+    pub const SESSION: PppoeCode = PppoeCode(0x00);
+    /// PPPoE Active Discovery Offer
+    pub const PADO: PppoeCode = PppoeCode(0x07);
+    /// PPPoE Active Discovery Initiation
+    pub const PADI: PppoeCode = PppoeCode(0x09);
+    /// PPPoE Active Discovery Request
+    pub const PADR: PppoeCode = PppoeCode(0x19);
+    /// PPPoE Active Discovery Session-confirmation
+    pub const PADS: PppoeCode = PppoeCode(0x65);
+    /// PPPoE Active Discovery Terminate
+    pub const PADT: PppoeCode = PppoeCode(0xa7);
+}
+
+/// The protocol field of the PPP frame carried in a PPPoE session packet.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+pub struct PppProtocol(pub u16);
+
+impl PppProtocol {
+    /// IPv4 payload
+    pub const IPV4: PppProtocol = PppProtocol(0x0021);
+    /// IPv6 payload
+    pub const IPV6: PppProtocol = PppProtocol(0x0057);
+    /// IP control protocol
+    pub const IPCP: PppProtocol = PppProtocol(0x8021);
+    /// IPv6 control protocol
+    pub const IPV6CP: PppProtocol = PppProtocol(0x8057);
+    /// Link control protocol
+    pub const LCP: PppProtocol = PppProtocol(0xc021);
+
+    /// Get the [`EthType`] of the payload described by this protocol, if it is a network protocol
+    /// we know how to parse.
+    #[must_use]
+    pub const fn ethtype(self) -> Option<EthType> {
+        match self {
+            PppProtocol::IPV4 => Some(EthType::IPV4),
+            PppProtocol::IPV6 => Some(EthType::IPV6),
+            _ => None,
+        }
+    }
+}
+
+/// A [PPPoE] header.
+///
+/// Session packets ([`PppoeCode::SESSION`]) carry the protocol of their PPP frame, which is
+/// part of the header for our purposes.
+/// Discovery packets carry none.
+///
+/// [PPPoE]: https://en.wikipedia.org/wiki/Point-to-Point_Protocol_over_Ethernet
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Pppoe {
+    code: PppoeCode,
+    session_id: u16,
+    length: u16,
+    protocol: Option<PppProtocol>,
+}
+
+impl Pppoe {
+    /// The minimum length of a [`Pppoe`] header (discovery packets).
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(6).unwrap();
+
+    /// The length of a [`Pppoe`] header of a session packet, PPP protocol included.
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const SESSION_LENGTH: NonZero<u16> = NonZero::new(8).unwrap();
+
+    /// The only version and type defined for PPPoE (both 1, in the same byte)
+    const VERSION_TYPE: u8 = 0x11;
+
+    /// Length of the PPP protocol field
+    const PROTOCOL_LENGTH: u16 = 2;
+
+    /// Create the header of a PPPoE session packet carrying a PPP frame of type `protocol`.
+    ///
+    /// The `length` is set for an empty PPP payload, see [`Pppoe::set_payload_len`].
+    #[must_use]
+    pub const fn session(session_id: u16, protocol: PppProtocol) -> Pppoe {
+        Pppoe {
+            code: PppoeCode::SESSION,
+            session_id,
+            length: Pppoe::PROTOCOL_LENGTH,
+            protocol: Some(protocol),
+        }
+    }
+
+    /// Create the header of a PPPoE discovery packet.
+    ///
+    /// Returns `None` if `code` is [`PppoeCode::SESSION`].
+    #[must_use]
+    pub const fn discovery(code: PppoeCode, session_id: u16) -> Option<Pppoe> {
+        if code.0 == PppoeCode::SESSION.0 {
+            return None;
+        }
+        Some(Pppoe {
+            code,
+            session_id,
+            length: 0,
+            protocol: None,
+        })
+    }
+
+    /// Get the code of this header.
+    #[must_use]
+    pub const fn code(&self) -> PppoeCode {
+        self.code
+    }
+
+    /// Tell if this is the header of a session (as opposed to discovery) packet.
+    #[must_use]
+    pub const fn is_session(&self) -> bool {
+        self.protocol.is_some()
+    }
+
+    /// Get the session id of this header.
+    #[must_use]
+    pub const fn session_id(&self) -> u16 {
+        self.session_id
+    }
+
+    /// Set the session id of this header.
+    pub const fn set_session_id(&mut self, session_id: u16) -> &mut Pppoe {
+        self.session_id = session_id;
+        self
+    }
+
+    /// Get the length field of this header.
+    ///
+    /// This is the length of the PPPoE payload, which includes the PPP protocol field of session
+    /// packets.
+    #[must_use]
+    pub const fn length(&self) -> u16 {
+        self.length
+    }
+
+    /// Set the length field of this header from the length of the payload which follows it.
+    ///
+    /// For session packets, the PPP protocol field is accounted for.
+    ///
+    /// # Errors
+    ///
+    /// Returns the resulting length if it does not fit in the length field.
+    pub fn set_payload_len(&mut self, payload_len: u16) -> Result<&mut Pppoe, usize> {
+        let protocol_len = if self.is_session() {
+            Pppoe::PROTOCOL_LENGTH
+        } else {
+            0
+        };
+        self.length = payload_len
+            .checked_add(protocol_len)
+            .ok_or_else(|| usize::from(payload_len) + usize::from(protocol_len))?;
+        Ok(self)
+    }
+
+    /// Get the protocol of the PPP frame of session packets.
+    #[must_use]
+    pub const fn protocol(&self) -> Option<PppProtocol> {
+        self.protocol
+    }
+
+    /// Set the protocol of the PPP frame of a session packet.
+    ///
+    /// This is a no-op for discovery packets.
+    pub const fn set_protocol(&mut self, protocol: PppProtocol) -> &mut Pppoe {
+        if self.protocol.is_some() {
+            self.protocol = Some(protocol);
+        }
+        self
+    }
+
+    /// Get the [`EthType`] of the ethernet (or VLAN) header which carries this header.
+    #[must_use]
+    pub const fn ethtype(&self) -> EthType {
+        if self.is_session() {
+            EthType::PPPOE_SESSION
+        } else {
+            EthType::PPPOE_DISCOVERY
+        }
+    }
+
+    /// Parse the payload of this header.
+    ///
+    /// Only the IPv4 and IPv6 payloads of session packets are parsed.
+    pub(crate) fn parse_payload(&self, cursor: &mut Reader) -> Option<EthNext> {
+        let ethtype = self.protocol?.ethtype()?;
+        parse_from_ethertype(ethtype.0, cursor)
+    }
+}
+
+/// Errors which may occur when parsing a [`Pppoe`] header.
+#[derive(Debug, thiserror::Error)]
+pub enum PppoeError {
+    /// Only version 1, type 1 of PPPoE is defined.
+    #[error("unsupported PPPoE version and type {0:#04x}")]
+    UnsupportedVersion(u8),
+}
+
+impl Parse for Pppoe {
+    type Error = PppoeError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < Pppoe::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Pppoe::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        if buf[0] != Pppoe::VERSION_TYPE {
+            trace!(
+                "Received PPPoE header with version and type {:#04x}",
+                buf[0]
+            );
+            return Err(ParseError::Invalid(PppoeError::UnsupportedVersion(buf[0])));
+        }
+        let code = PppoeCode(buf[1]);
+        let session_id = u16::from_be_bytes([buf[2], buf[3]]);
+        let length = u16::from_be_bytes([buf[4], buf[5]]);
+        if code != PppoeCode::SESSION {
+            let header = Pppoe {
+                code,
+                session_id,
+                length,
+                protocol: None,
+            };
+            return Ok((header, Pppoe::MIN_LENGTH));
+        }
+        if buf.len() < Pppoe::SESSION_LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Pppoe::SESSION_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let header = Pppoe {
+            code,
+            session_id,
+            length,
+            protocol: Some(PppProtocol(u16::from_be_bytes([buf[6], buf[7]]))),
+        };
+        Ok((header, Pppoe::SESSION_LENGTH))
+    }
+}
+
+impl DeParse for Pppoe {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        if self.is_session() {
+            Pppoe::SESSION_LENGTH
+        } else {
+            Pppoe::MIN_LENGTH
+        }
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let size = self.size();
+        if buf.len() < size.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: size.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        buf[0] = Pppoe::VERSION_TYPE;
+        buf[1] = self.code.0;
+        buf[2..4].copy_from_slice(&self.session_id.to_be_bytes());
+        buf[4..6].copy_from_slice(&self.length.to_be_bytes());
+        if let Some(protocol) = self.protocol {
+            buf[6..8].copy_from_slice(&protocol.0.to_be_bytes());
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(any(test, feature = "bolero"))]
+mod contract {
+    use crate::pppoe::{Pppoe, PppoeCode};
+    use bolero::{Driver, TypeGenerator};
+
+    impl TypeGenerator for Pppoe {
+        fn generate<D: Driver>(driver: &mut D) -> Option<Self> {
+            let code: PppoeCode = driver.produce()?;
+            let protocol = if code == PppoeCode::SESSION {
+                Some(driver.produce()?)
+            } else {
+                None
+            };
+            Some(Pppoe {
+                code,
+                session_id: driver.produce()?,
+                length: driver.produce()?,
+                protocol,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::eth::Eth;
+    use crate::eth::mac::{DestinationMac, Mac, SourceMac};
+    use crate::headers::{TryIpv4, TryPppoe, TryUdp};
+    use crate::packet::Packet;
+    use crate::parse::{DeParse, IntoNonZeroUSize, Parse, ParseError};
+    use crate::pppoe::{PppProtocol, Pppoe, PppoeCode, PppoeError};
+    use etherparse::{IpNumber, Ipv4Header, UdpHeader};
+
+    const MAX_LENGTH_USIZE: usize = 8;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!().with_type().for_each(|pppoe: &Pppoe| {
+            let mut buf = [0u8; MAX_LENGTH_USIZE];
+            let bytes_written = pppoe.deparse(&mut buf).unwrap_or_else(|_| unreachable!());
+            assert_eq!(bytes_written, pppoe.size());
+            let (parsed, bytes_parsed) = Pppoe::parse(&buf).unwrap();
+            assert_eq!(parsed, *pppoe);
+            assert_eq!(bytes_parsed, pppoe.size());
+        });
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; MAX_LENGTH_USIZE]| {
+                let (parsed, bytes_parsed) = match Pppoe::parse(slice) {
+                    Ok((parsed, bytes_parsed)) => (parsed, bytes_parsed),
+                    Err(ParseError::Invalid(PppoeError::UnsupportedVersion(version))) => {
+                        assert_eq!(slice[0], version);
+                        assert_ne!(version, 0x11);
+                        return;
+                    }
+                    Err(ParseError::Length(_) | ParseError::BufferTooLong(_)) => unreachable!(),
+                };
+                assert_eq!(parsed.is_session(), slice[1] == 0);
+                let mut write_back_buffer = [0u8; MAX_LENGTH_USIZE];
+                let bytes_written = parsed
+                    .deparse(&mut write_back_buffer)
+                    .unwrap_or_else(|_| unreachable!());
+                assert_eq!(bytes_written, bytes_parsed);
+                let len = bytes_parsed.into_non_zero_usize().get();
+                assert_eq!(write_back_buffer[..len], slice[..len]);
+            });
+    }
+
+    #[test]
+    fn parse_of_truncated_session_header_fails_gracefully() {
+        let pppoe = Pppoe::session(1, PppProtocol::IPV4);
+        let mut buf = [0u8; MAX_LENGTH_USIZE];
+        pppoe.deparse(&mut buf).unwrap();
+        match Pppoe::parse(&buf[..6]) {
+            Err(ParseError::Length(e)) => {
+                assert_eq!(e.expected, Pppoe::SESSION_LENGTH.into_non_zero_usize());
+                assert_eq!(e.actual, 6);
+            }
+            _ => unreachable!(),
+        }
+        assert!(Pppoe::discovery(PppoeCode::SESSION, 1).is_none());
+    }
+
+    #[test]
+    fn parse_ipv4_over_pppoe_session() {
+        let udp = UdpHeader {
+            source_port: 1234,
+            destination_port: 5678,
+            length: 8,
+            checksum: 0,
+        };
+        let ip = Ipv4Header::new(8, 64, IpNumber::UDP, [10, 0, 0, 1], [10, 0, 0, 2]).unwrap();
+        let mut pppoe = Pppoe::session(0x4242, PppProtocol::IPV4);
+        let payload_len = u16::try_from(ip.header_len() + UdpHeader::LEN).unwrap();
+        pppoe.set_payload_len(payload_len).unwrap();
+        let eth = Eth::new(
+            SourceMac::new(Mac([2, 0, 0, 0, 0, 1])).unwrap(),
+            DestinationMac::new(Mac([2, 0, 0, 0, 0, 2])).unwrap(),
+            pppoe.ethtype(),
+        );
+        let mut frame = vec![0u8; (eth.size().get() + pppoe.size().get()).into()];
+        let eth_len = eth.deparse(&mut frame).unwrap().into_non_zero_usize().get();
+        pppoe.deparse(&mut frame[eth_len..]).unwrap();
+        ip.write(&mut frame).unwrap();
+        udp.write(&mut frame).unwrap();
+
+        let packet = Packet::new(TestBuffer::from_raw_data(&frame)).unwrap();
+        let parsed = packet.try_pppoe().unwrap();
+        assert_eq!(parsed, &pppoe);
+        assert_eq!(parsed.length(), 30);
+        assert_eq!(
+            packet.headers().try_ipv4().unwrap().destination(),
+            std::net::Ipv4Addr::new(10, 0, 0, 2)
+        );
+        assert_eq!(packet.try_udp().unwrap().destination().as_u16(), 5678);
+        assert_eq!(packet.payload_len(), 0);
+
+        let mut buf = vec![0u8; frame.len()];
+        packet.get_headers().deparse(&mut buf).unwrap();
+        assert_eq!(buf, frame);
+    }
+}
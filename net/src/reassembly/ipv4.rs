@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Reassembly of fragmented IPv4 packets.
+
+use crate::buffer::{Append, PacketBufferMut, Segments};
+use crate::headers::Net;
+use crate::ipv4::frag_offset::FragOffset;
+use crate::packet::Packet;
+use crate::parse::DeParse;
+use crate::reassembly::{
+    FragmentCache, FragmentData, FragmentPosition, Fragments, ReassemblyConfig, ReassemblyError,
+};
+use etherparse::IpNumber;
+use std::net::Ipv4Addr;
+use std::time::Instant;
+use tracing::debug;
+
+/// The fields identifying the fragments of a same IPv4 packet (see [RFC791]).
+///
+/// [RFC791]: https://datatracker.ietf.org/doc/html/rfc791#section-2.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Ipv4FragmentKey {
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    identification: u16,
+    protocol: IpNumber,
+}
+
+/// A reassembly cache for IPv4 packets.
+///
+/// Fragments are identified by their source and destination addresses, their protocol, and the
+/// identification field of their IPv4 header.
+/// The reassembled packet is built in the buffer of its first fragment, which must have enough
+/// tailroom to hold the rest of the packet.
+pub struct Ipv4Reassembler<Buf: PacketBufferMut> {
+    cache: FragmentCache<Ipv4FragmentKey, Buf>,
+}
+
+impl<Buf: PacketBufferMut> Ipv4Reassembler<Buf> {
+    /// Create a new (empty) [`Ipv4Reassembler`].
+    #[must_use]
+    pub fn new(config: ReassemblyConfig) -> Ipv4Reassembler<Buf> {
+        Ipv4Reassembler {
+            cache: FragmentCache::new(config),
+        }
+    }
+
+    /// Get the number of packets under reassembly.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Get the number of fragment bytes held in the cache.
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        self.cache.bytes()
+    }
+
+    /// Drop the packets whose reassembly timed out at time `now`, and return their number.
+    ///
+    /// Timed out packets are also dropped when the cache is full, but this method should be called
+    /// periodically to release their memory.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        self.cache.expire(now)
+    }
+}
+
+impl<Buf: PacketBufferMut + Append> Ipv4Reassembler<Buf> {
+    /// Submit `packet` for reassembly, at time `now`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(packet))` with the unmodified packet if it is not an IPv4 fragment.
+    /// * `Ok(Some(packet))` with the reassembled packet if `packet` was its last missing fragment.
+    ///   The headers of the reassembled packet are parsed again from the reassembled data.
+    /// * `Ok(None)` if `packet` is held in the cache until the other fragments are received.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ReassemblyError`] if the fragment is invalid or can't be held in the cache.
+    /// The fragment is dropped.
+    pub fn reassemble(
+        &mut self,
+        packet: Packet<Buf>,
+        now: Instant,
+    ) -> Result<Option<Packet<Buf>>, ReassemblyError> {
+        let Some(Net::Ipv4(ipv4)) = &packet.get_headers().net else {
+            return Ok(Some(packet));
+        };
+        let offset = ipv4.fragment_offset().value();
+        if offset == 0 && !ipv4.more_fragments() {
+            return Ok(Some(packet));
+        }
+        let len = usize::from(ipv4.total_len())
+            .checked_sub(ipv4.header_len())
+            .ok_or(ReassemblyError::InvalidLength(0))?;
+        let key = Ipv4FragmentKey {
+            source: ipv4.source().inner(),
+            destination: ipv4.destination(),
+            identification: ipv4.identification(),
+            protocol: ipv4.protocol(),
+        };
+        let position = FragmentPosition {
+            offset: usize::from(offset) * 8,
+            len,
+            more_fragments: ipv4.more_fragments(),
+        };
+        let data = if position.offset == 0 {
+            FragmentData::First(packet)
+        } else {
+            let mut data = vec![0; position.len];
+            let payload = packet.payload();
+            if payload.read(0, &mut data) < position.len {
+                return Err(ReassemblyError::InvalidLength(payload.data_len()));
+            }
+            FragmentData::Rest(data)
+        };
+        match self.cache.insert(key, position, data, now)? {
+            None => Ok(None),
+            Some(fragments) => Self::build(fragments, key).map(Some),
+        }
+    }
+
+    /// Build the reassembled packet from its `fragments`.
+    fn build(
+        mut fragments: Fragments<Buf>,
+        key: Ipv4FragmentKey,
+    ) -> Result<Packet<Buf>, ReassemblyError> {
+        let total = fragments.total;
+        let payload_len = u16::try_from(total).map_err(|_| ReassemblyError::TooLarge)?;
+        let headers = fragments.first.get_headers_mut();
+        let Some(Net::Ipv4(ipv4)) = &mut headers.net else {
+            unreachable!()
+        };
+        ipv4.set_fragment_offset(FragOffset::MIN)
+            .set_more_fragments(false)
+            .set_payload_len(payload_len)
+            .map_err(|_| ReassemblyError::TooLarge)?;
+        let ip_len = ipv4.size().get();
+        if let Some(net) = headers.net.as_mut() {
+            net.update_checksum();
+        }
+        debug!(
+            "reassembled ipv4 packet (id {id:#x}, {total} bytes)",
+            id = key.identification
+        );
+
+        // offset of the fragmentable part in the buffer
+        let start = headers.eth.as_ref().map_or(0, |eth| eth.size().get())
+            + headers
+                .vlan
+                .iter()
+                .map(|vlan| vlan.size().get())
+                .sum::<u16>()
+            + ip_len;
+        fragments.rebuild(usize::from(start))
+    }
+}
@@ -0,0 +1,317 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Reassembly of fragmented IPv6 packets.
+
+use crate::buffer::{Append, PacketBufferMut, Segments};
+use crate::headers::{Net, NetExt};
+use crate::ipv6::Ipv6Fragment;
+use crate::packet::Packet;
+use crate::parse::DeParse;
+use crate::reassembly::{
+    FragmentCache, FragmentData, FragmentPosition, Fragments, ReassemblyConfig, ReassemblyError,
+};
+use std::net::Ipv6Addr;
+use std::time::Instant;
+use tracing::debug;
+
+/// The fields identifying the fragments of a same IPv6 packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Ipv6FragmentKey {
+    source: Ipv6Addr,
+    destination: Ipv6Addr,
+    identification: u32,
+}
+
+/// A reassembly cache for IPv6 packets.
+///
+/// Fragments are identified by their source and destination addresses and by the identification
+/// of their fragment header.
+/// The reassembled packet is built in the buffer of its first fragment, which must have enough
+/// tailroom to hold the rest of the packet.
+pub struct Ipv6Reassembler<Buf: PacketBufferMut> {
+    cache: FragmentCache<Ipv6FragmentKey, Buf>,
+}
+
+impl<Buf: PacketBufferMut> Ipv6Reassembler<Buf> {
+    /// Create a new (empty) [`Ipv6Reassembler`].
+    #[must_use]
+    pub fn new(config: ReassemblyConfig) -> Ipv6Reassembler<Buf> {
+        Ipv6Reassembler {
+            cache: FragmentCache::new(config),
+        }
+    }
+
+    /// Get the number of packets under reassembly.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Get the number of fragment bytes held in the cache.
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        self.cache.bytes()
+    }
+
+    /// Drop the packets whose reassembly timed out at time `now`, and return their number.
+    ///
+    /// Timed out packets are also dropped when the cache is full, but this method should be called
+    /// periodically to release their memory.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        self.cache.expire(now)
+    }
+}
+
+impl<Buf: PacketBufferMut + Append> Ipv6Reassembler<Buf> {
+    /// Submit `packet` for reassembly, at time `now`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(packet))` with the unmodified packet if it is not an IPv6 fragment, or if it is
+    ///   an atomic fragment (see [`Ipv6Fragment::is_atomic`]).
+    /// * `Ok(Some(packet))` with the reassembled packet if `packet` was its last missing fragment.
+    ///   The fragment header is removed from the reassembled packet, and its headers are parsed
+    ///   again from the reassembled data.
+    /// * `Ok(None)` if `packet` is held in the cache until the other fragments are received.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ReassemblyError`] if the fragment is invalid or can't be held in the cache.
+    /// The fragment is dropped.
+    pub fn reassemble(
+        &mut self,
+        packet: Packet<Buf>,
+        now: Instant,
+    ) -> Result<Option<Packet<Buf>>, ReassemblyError> {
+        let headers = packet.get_headers();
+        let Some(Net::Ipv6(ipv6)) = &headers.net else {
+            return Ok(Some(packet));
+        };
+        let Some((index, fragment)) =
+            headers
+                .net_ext
+                .iter()
+                .enumerate()
+                .find_map(|(index, ext)| match ext {
+                    NetExt::Ipv6Ext(ext) => ext.fragment().map(|fragment| (index, fragment)),
+                    NetExt::IpAuth(_) | NetExt::Esp(_) => None,
+                })
+        else {
+            return Ok(Some(packet));
+        };
+        if fragment.is_atomic() {
+            return Ok(Some(packet));
+        }
+        // the fragment header must be part of the first extension headers, so that we know how to
+        // remove it from the reassembled packet
+        if index != 0 {
+            debug!("unsupported ipv6 fragment header position");
+            return Err(ReassemblyError::Unsupported);
+        }
+        let ext_len: u16 = headers.net_ext.iter().map(|ext| ext.size().get()).sum();
+        let len = ipv6
+            .payload_length()
+            .checked_sub(ext_len)
+            .ok_or(ReassemblyError::InvalidLength(0))?;
+        let key = Ipv6FragmentKey {
+            source: ipv6.source().inner(),
+            destination: ipv6.destination(),
+            identification: fragment.identification(),
+        };
+        let position = FragmentPosition {
+            offset: usize::from(fragment.byte_offset()),
+            len: usize::from(len),
+            more_fragments: fragment.more_fragments(),
+        };
+        let data = if position.offset == 0 {
+            FragmentData::First(packet)
+        } else {
+            let mut data = vec![0; position.len];
+            let payload = packet.payload();
+            if payload.read(0, &mut data) < position.len {
+                return Err(ReassemblyError::InvalidLength(payload.data_len()));
+            }
+            FragmentData::Rest(data)
+        };
+        match self.cache.insert(key, position, data, now)? {
+            None => Ok(None),
+            Some(fragments) => Self::build(fragments, fragment).map(Some),
+        }
+    }
+
+    /// Build the reassembled packet from its `fragments`, `fragment` being the header of the last
+    /// received one.
+    fn build(
+        mut fragments: Fragments<Buf>,
+        fragment: Ipv6Fragment,
+    ) -> Result<Packet<Buf>, ReassemblyError> {
+        let total = fragments.total;
+        let headers = fragments.first.get_headers_mut();
+        let Some(NetExt::Ipv6Ext(ext)) = headers.net_ext.first_mut() else {
+            unreachable!()
+        };
+        ext.remove_fragment();
+        let first_header = ext.first_header();
+        if ext.is_empty() {
+            headers.net_ext.remove(0);
+        }
+        let ext_len: u16 = headers.net_ext.iter().map(|ext| ext.size().get()).sum();
+        let payload_length =
+            u16::try_from(total + usize::from(ext_len)).map_err(|_| ReassemblyError::TooLarge)?;
+        let Some(Net::Ipv6(ipv6)) = &mut headers.net else {
+            unreachable!()
+        };
+        ipv6.set_next_header(first_header)
+            .set_payload_length(payload_length);
+        debug!(
+            "reassembled ipv6 packet (id {id:#x}, {total} bytes)",
+            id = fragment.identification()
+        );
+
+        // offset of the fragmentable part in the buffer
+        let start = headers.eth.as_ref().map_or(0, |eth| eth.size().get())
+            + headers
+                .vlan
+                .iter()
+                .map(|vlan| vlan.size().get())
+                .sum::<u16>()
+            + ipv6.size().get()
+            + ext_len;
+        fragments.rebuild(usize::from(start))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::headers::{TryIpv6, TryUdp};
+    use crate::ip::NextHeader;
+    use crate::ipv4::frag_offset::FragOffset;
+    use crate::ipv6::Ipv6Fragment;
+    use crate::packet::Packet;
+    use crate::parse::DeParse;
+    use crate::reassembly::{Ipv6Reassembler, ReassemblyConfig, ReassemblyError};
+    use etherparse::{EtherType, Ethernet2Header, IpNumber, Ipv6Header, UdpHeader};
+    use std::time::Instant;
+
+    const SOURCE: [u8; 16] = [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    const DESTINATION: [u8; 16] = [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+
+    fn frame(next_header: IpNumber, ip_payload: &[u8]) -> Vec<u8> {
+        let eth = Ethernet2Header {
+            source: [2, 0, 0, 0, 0, 1],
+            destination: [2, 0, 0, 0, 0, 2],
+            ether_type: EtherType::IPV6,
+        };
+        #[allow(clippy::cast_possible_truncation)]
+        let ip = Ipv6Header {
+            traffic_class: 0,
+            flow_label: 0.try_into().unwrap(),
+            payload_length: ip_payload.len() as u16,
+            next_header,
+            hop_limit: 64,
+            source: SOURCE,
+            destination: DESTINATION,
+        };
+        let mut frame = Vec::new();
+        eth.write(&mut frame).unwrap();
+        ip.write(&mut frame).unwrap();
+        frame.extend_from_slice(ip_payload);
+        frame
+    }
+
+    /// A UDP datagram with 72 bytes of payload.
+    fn udp_datagram() -> Vec<u8> {
+        let payload: Vec<u8> = (0..72).collect();
+        let ip = Ipv6Header {
+            source: SOURCE,
+            destination: DESTINATION,
+            ..Default::default()
+        };
+        let udp = UdpHeader::with_ipv6_checksum(1234, 5678, &ip, &payload).unwrap();
+        let mut bytes = Vec::new();
+        udp.write(&mut bytes).unwrap();
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    fn fragment(datagram: &[u8], offset: u16, len: u16, id: u32) -> Packet<TestBuffer> {
+        let end = usize::from(offset + len);
+        let header = Ipv6Fragment::new(
+            NextHeader::UDP,
+            FragOffset::new(offset / 8).unwrap(),
+            end < datagram.len(),
+            id,
+        );
+        let mut ip_payload = vec![0u8; Ipv6Fragment::LEN.get() as usize];
+        header.deparse(&mut ip_payload).unwrap();
+        ip_payload.extend_from_slice(&datagram[usize::from(offset)..end]);
+        let frame = frame(IpNumber::IPV6_FRAGMENTATION_HEADER, &ip_payload);
+        Packet::new(TestBuffer::from_raw_data(&frame)).unwrap()
+    }
+
+    #[test]
+    fn not_fragmented() {
+        let mut reassembler = Ipv6Reassembler::new(ReassemblyConfig::default());
+        let packet = Packet::new(TestBuffer::from_raw_data(&frame(
+            IpNumber::UDP,
+            &udp_datagram(),
+        )))
+        .unwrap();
+        let expected = packet.clone();
+        let packet = reassembler.reassemble(packet, Instant::now()).unwrap();
+        assert_eq!(packet.unwrap().get_headers(), expected.get_headers());
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn reassemble_out_of_order() {
+        let now = Instant::now();
+        let datagram = udp_datagram();
+        let mut reassembler = Ipv6Reassembler::new(ReassemblyConfig::default());
+        let first = fragment(&datagram, 0, 32, 7);
+        assert_eq!(first.try_udp().unwrap().source().as_u16(), 1234);
+        let last = fragment(&datagram, 64, 16, 7);
+        assert!(last.try_udp().is_none());
+        assert!(reassembler.reassemble(last, now).unwrap().is_none());
+        assert!(reassembler.reassemble(first, now).unwrap().is_none());
+        // a fragment of another packet
+        let other = fragment(&datagram, 32, 32, 8);
+        assert!(reassembler.reassemble(other, now).unwrap().is_none());
+        assert_eq!(reassembler.pending(), 2);
+        assert_eq!(reassembler.bytes(), 80);
+
+        let middle = fragment(&datagram, 32, 32, 7);
+        let packet = reassembler.reassemble(middle, now).unwrap().unwrap();
+        assert_eq!(reassembler.pending(), 1);
+        assert_eq!(reassembler.bytes(), 32);
+        assert_eq!(packet.try_ipv6().unwrap().next_header(), NextHeader::UDP);
+        assert_eq!(packet.try_ipv6().unwrap().payload_length(), 80);
+        assert!(packet.get_headers().net_ext.is_empty());
+        let udp = packet.try_udp().unwrap();
+        assert_eq!(udp.source().as_u16(), 1234);
+        assert_eq!(udp.destination().as_u16(), 5678);
+        assert_eq!(packet.payload().as_ref(), &datagram[8..]);
+        assert_eq!(
+            packet.serialize().unwrap().as_ref(),
+            frame(IpNumber::UDP, &datagram).as_slice()
+        );
+    }
+
+    #[test]
+    fn overlapping_fragments() {
+        let now = Instant::now();
+        let datagram = udp_datagram();
+        let mut reassembler = Ipv6Reassembler::new(ReassemblyConfig::default());
+        let first = fragment(&datagram, 0, 32, 7);
+        assert!(reassembler.reassemble(first, now).unwrap().is_none());
+        let overlapping = fragment(&datagram, 24, 56, 7);
+        assert_eq!(
+            reassembler.reassemble(overlapping, now).unwrap_err(),
+            ReassemblyError::Overlap
+        );
+        assert_eq!(reassembler.pending(), 0);
+        assert_eq!(reassembler.bytes(), 0);
+    }
+}
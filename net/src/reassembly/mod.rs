@@ -0,0 +1,439 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Reassembly of fragmented IP packets.
+//!
+//! Fragments are held in a cache until all the fragments of the original packet have been
+//! received.
+//! The cache is bounded, both in number of packets under reassembly and in number of bytes held,
+//! and incomplete packets are dropped after a timeout.
+//!
+//! Stages which need to see whole packets (e.g., stateful NAT, which needs the transport ports)
+//! should reassemble fragments beforehand.
+
+mod ipv4;
+mod ipv6;
+
+pub use ipv4::*;
+pub use ipv6::*;
+
+use crate::buffer::{Append, PacketBufferMut, Segments, TrimFromEnd};
+use crate::packet::Packet;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Configuration of a reassembly cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReassemblyConfig {
+    /// Maximum number of packets under reassembly at any time.
+    pub max_datagrams: usize,
+    /// Maximum number of fragment bytes held in the cache at any time.
+    pub max_bytes: usize,
+    /// Time after which an incomplete packet is dropped from the cache.
+    pub timeout: Duration,
+}
+
+impl Default for ReassemblyConfig {
+    /// The default configuration, with the 60 seconds timeout of [RFC8200].
+    ///
+    /// [RFC8200]: https://datatracker.ietf.org/doc/html/rfc8200#section-4.5
+    fn default() -> Self {
+        ReassemblyConfig {
+            max_datagrams: 1024,
+            max_bytes: 4 * 1024 * 1024,
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Errors which may occur when reassembling a packet.
+///
+/// The fragment passed to the reassembly cache is dropped in all cases.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ReassemblyError {
+    /// The fragment is empty, or is not the last fragment and its length is not a multiple of 8.
+    #[error("invalid fragment length: {0}")]
+    InvalidLength(usize),
+    /// The reassembled packet would exceed the maximum packet size.
+    /// The whole packet is dropped.
+    #[error("reassembled packet too large")]
+    TooLarge,
+    /// The fragment overlaps with previously received fragments (see [RFC5722]).
+    /// The whole packet is dropped.
+    ///
+    /// [RFC5722]: https://datatracker.ietf.org/doc/html/rfc5722
+    #[error("overlapping fragment")]
+    Overlap,
+    /// The fragment is inconsistent with the length of the packet given by its last fragment.
+    /// The whole packet is dropped.
+    #[error("fragment inconsistent with packet length")]
+    InconsistentLength,
+    /// The cache is full.
+    #[error("reassembly cache full")]
+    CacheFull,
+    /// The headers of the fragment are not supported for reassembly.
+    #[error("unsupported fragment headers")]
+    Unsupported,
+    /// There is not enough room in the buffer of the first fragment to hold the reassembled packet.
+    #[error("not enough room in buffer to reassemble packet")]
+    NoRoom,
+}
+
+/// The data of a fragment.
+enum FragmentData<Buf: PacketBufferMut> {
+    /// The first fragment, with the headers of the original packet.
+    First(Packet<Buf>),
+    /// The contents of any other fragment.
+    Rest(Vec<u8>),
+}
+
+/// The position of a fragment in the fragmentable part of the original packet.
+#[derive(Debug, Clone, Copy)]
+struct FragmentPosition {
+    /// Offset of the fragment (in bytes).
+    offset: usize,
+    /// Length of the fragment (in bytes).
+    len: usize,
+    /// Whether more fragments follow this one.
+    more_fragments: bool,
+}
+
+impl FragmentPosition {
+    fn end(&self) -> usize {
+        self.offset + self.len
+    }
+}
+
+/// All the fragments of a packet, ready for reassembly.
+struct Fragments<Buf: PacketBufferMut> {
+    /// The first fragment.
+    first: Packet<Buf>,
+    /// The length of the fragmentable data in the first fragment.
+    first_len: usize,
+    /// The contents of the other fragments, in order.
+    rest: Vec<Vec<u8>>,
+    /// The length of the fragmentable part of the packet.
+    total: usize,
+}
+
+impl<Buf: PacketBufferMut + Append> Fragments<Buf> {
+    /// Write the headers of the first fragment back to its buffer, append the data of the other
+    /// fragments, and parse the resulting packet.
+    ///
+    /// The headers of the first fragment must have been updated for the reassembled packet, and
+    /// `start` is the offset of the fragmentable data in the buffer once these headers are written.
+    fn rebuild(self, start: usize) -> Result<Packet<Buf>, ReassemblyError> {
+        let Fragments {
+            first,
+            first_len,
+            rest,
+            ..
+        } = self;
+        let meta = first.meta.clone();
+        let mut buf = first
+            .deparse_headers()
+            .map_err(|_| ReassemblyError::NoRoom)?;
+        let end = start + first_len;
+        let padding = buf
+            .data_len()
+            .checked_sub(end)
+            .ok_or(ReassemblyError::InvalidLength(first_len))?;
+        if padding > 0 {
+            #[allow(clippy::cast_possible_truncation)] // bounded by the buffer length
+            buf.trim_from_end(padding as u16)
+                .unwrap_or_else(|e| unreachable!("{e:?}"));
+        }
+        for data in rest {
+            #[allow(clippy::cast_possible_truncation)] // fragment length bounded by the ip length
+            let appended = buf
+                .append(data.len() as u16)
+                .map_err(|_| ReassemblyError::NoRoom)?;
+            // the appended bytes are at the end of the (last segment of the) buffer
+            let at = appended.len() - data.len();
+            appended[at..].copy_from_slice(&data);
+        }
+        let mut packet = Packet::new(buf).map_err(|_| ReassemblyError::Unsupported)?;
+        packet.meta = meta;
+        Ok(packet)
+    }
+}
+
+/// A packet under reassembly.
+struct PartialDatagram<Buf: PacketBufferMut> {
+    created: Instant,
+    first: Option<(Packet<Buf>, usize)>,
+    rest: BTreeMap<usize, Vec<u8>>,
+    /// Received byte ranges (start to end), including the first fragment
+    ranges: BTreeMap<usize, usize>,
+    /// Number of bytes received
+    received: usize,
+    /// Length of the fragmentable part of the original packet, once the last fragment is received
+    total: Option<usize>,
+}
+
+impl<Buf: PacketBufferMut> PartialDatagram<Buf> {
+    fn new(now: Instant) -> Self {
+        PartialDatagram {
+            created: now,
+            first: None,
+            rest: BTreeMap::new(),
+            ranges: BTreeMap::new(),
+            received: 0,
+            total: None,
+        }
+    }
+
+    fn overlaps(&self, position: FragmentPosition) -> bool {
+        let before = self.ranges.range(..=position.offset).next_back();
+        let after = self.ranges.range(position.offset..).next();
+        before.is_some_and(|(_, &end)| end > position.offset)
+            || after.is_some_and(|(&start, _)| start < position.end())
+    }
+
+    fn is_consistent(&self, position: FragmentPosition) -> bool {
+        match (self.total, position.more_fragments) {
+            (Some(total), true) => position.end() <= total,
+            (Some(total), false) => position.end() == total,
+            (None, true) => true,
+            (None, false) => self
+                .ranges
+                .last_key_value()
+                .is_none_or(|(_, &end)| end <= position.end()),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.first.is_some() && self.total == Some(self.received)
+    }
+}
+
+/// A bounded cache of packets under reassembly, keyed by `K`.
+struct FragmentCache<K, Buf: PacketBufferMut> {
+    config: ReassemblyConfig,
+    datagrams: HashMap<K, PartialDatagram<Buf>>,
+    bytes: usize,
+}
+
+impl<K: Hash + Eq + Copy, Buf: PacketBufferMut> FragmentCache<K, Buf> {
+    /// The maximum length of the fragmentable part of a packet.
+    const MAX_LEN: usize = u16::MAX as usize;
+
+    fn new(config: ReassemblyConfig) -> Self {
+        FragmentCache {
+            config,
+            datagrams: HashMap::new(),
+            bytes: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.datagrams.len()
+    }
+
+    fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    fn is_expired(&self, datagram: &PartialDatagram<Buf>, now: Instant) -> bool {
+        now.saturating_duration_since(datagram.created) >= self.config.timeout
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(datagram) = self.datagrams.remove(key) {
+            self.bytes -= datagram.received;
+        }
+    }
+
+    /// Drop the packets whose reassembly timed out, and return their number.
+    fn expire(&mut self, now: Instant) -> usize {
+        let before = self.datagrams.len();
+        let timeout = self.config.timeout;
+        let mut freed = 0;
+        self.datagrams.retain(|_, datagram| {
+            let keep = now.saturating_duration_since(datagram.created) < timeout;
+            if !keep {
+                freed += datagram.received;
+            }
+            keep
+        });
+        self.bytes -= freed;
+        let expired = before - self.datagrams.len();
+        if expired > 0 {
+            debug!("dropped {expired} incomplete packet(s) after reassembly timeout");
+        }
+        expired
+    }
+
+    /// Returns true if the cache has room for `len` more bytes (and one more packet if `new`).
+    fn has_room(&self, len: usize, new: bool) -> bool {
+        self.bytes + len <= self.config.max_bytes
+            && (!new || self.datagrams.len() < self.config.max_datagrams)
+    }
+
+    /// Add a fragment of the packet identified by `key` to the cache.
+    ///
+    /// Returns the fragments of the packet once they have all been received.
+    fn insert(
+        &mut self,
+        key: K,
+        position: FragmentPosition,
+        data: FragmentData<Buf>,
+        now: Instant,
+    ) -> Result<Option<Fragments<Buf>>, ReassemblyError> {
+        if position.len == 0 || (position.more_fragments && !position.len.is_multiple_of(8)) {
+            return Err(ReassemblyError::InvalidLength(position.len));
+        }
+        if position.end() > Self::MAX_LEN {
+            self.remove(&key);
+            return Err(ReassemblyError::TooLarge);
+        }
+        if self
+            .datagrams
+            .get(&key)
+            .is_some_and(|datagram| self.is_expired(datagram, now))
+        {
+            self.remove(&key);
+        }
+        let new = !self.datagrams.contains_key(&key);
+        if !self.has_room(position.len, new) {
+            self.expire(now);
+            if !self.has_room(position.len, new) {
+                return Err(ReassemblyError::CacheFull);
+            }
+        }
+        let datagram = self
+            .datagrams
+            .entry(key)
+            .or_insert_with(|| PartialDatagram::new(now));
+        if datagram.overlaps(position) {
+            self.remove(&key);
+            return Err(ReassemblyError::Overlap);
+        }
+        if !datagram.is_consistent(position) {
+            self.remove(&key);
+            return Err(ReassemblyError::InconsistentLength);
+        }
+        if !position.more_fragments {
+            datagram.total = Some(position.end());
+        }
+        datagram.ranges.insert(position.offset, position.end());
+        datagram.received += position.len;
+        self.bytes += position.len;
+        match data {
+            FragmentData::First(packet) => datagram.first = Some((packet, position.len)),
+            FragmentData::Rest(data) => {
+                datagram.rest.insert(position.offset, data);
+            }
+        }
+        if !datagram.is_complete() {
+            return Ok(None);
+        }
+        let datagram = self
+            .datagrams
+            .remove(&key)
+            .unwrap_or_else(|| unreachable!());
+        self.bytes -= datagram.received;
+        let (first, first_len) = datagram.first.unwrap_or_else(|| unreachable!());
+        Ok(Some(Fragments {
+            first,
+            first_len,
+            rest: datagram.rest.into_values().collect(),
+            total: datagram.received,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::TestBuffer;
+    use crate::reassembly::{
+        FragmentCache, FragmentData, FragmentPosition, ReassemblyConfig, ReassemblyError,
+    };
+    use std::time::{Duration, Instant};
+
+    fn rest(offset: usize, len: usize, more_fragments: bool) -> (FragmentPosition, Vec<u8>) {
+        let position = FragmentPosition {
+            offset,
+            len,
+            more_fragments,
+        };
+        #[allow(clippy::cast_possible_truncation)] // test data
+        (position, (offset..offset + len).map(|i| i as u8).collect())
+    }
+
+    fn insert(
+        cache: &mut FragmentCache<u32, TestBuffer>,
+        key: u32,
+        (position, data): (FragmentPosition, Vec<u8>),
+        now: Instant,
+    ) -> Result<bool, ReassemblyError> {
+        cache
+            .insert(key, position, FragmentData::Rest(data), now)
+            .map(|done| done.is_some())
+    }
+
+    #[test]
+    fn overlap_drops_datagram() {
+        let now = Instant::now();
+        let mut cache = FragmentCache::<u32, TestBuffer>::new(ReassemblyConfig::default());
+        assert_eq!(insert(&mut cache, 1, rest(16, 16, true), now), Ok(false));
+        assert_eq!(insert(&mut cache, 1, rest(48, 8, false), now), Ok(false));
+        assert_eq!(cache.bytes(), 24);
+        assert_eq!(
+            insert(&mut cache, 1, rest(24, 16, true), now),
+            Err(ReassemblyError::Overlap)
+        );
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.bytes(), 0);
+    }
+
+    #[test]
+    fn invalid_lengths() {
+        let now = Instant::now();
+        let mut cache = FragmentCache::<u32, TestBuffer>::new(ReassemblyConfig::default());
+        assert_eq!(
+            insert(&mut cache, 1, rest(16, 12, true), now),
+            Err(ReassemblyError::InvalidLength(12))
+        );
+        assert_eq!(
+            insert(&mut cache, 1, rest(65528, 16, false), now),
+            Err(ReassemblyError::TooLarge)
+        );
+        assert_eq!(insert(&mut cache, 1, rest(16, 16, false), now), Ok(false));
+        assert_eq!(
+            insert(&mut cache, 1, rest(32, 8, true), now),
+            Err(ReassemblyError::InconsistentLength)
+        );
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn bounded_and_expiring() {
+        let now = Instant::now();
+        let config = ReassemblyConfig {
+            max_datagrams: 2,
+            max_bytes: 64,
+            timeout: Duration::from_secs(1),
+        };
+        let mut cache = FragmentCache::<u32, TestBuffer>::new(config);
+        assert_eq!(insert(&mut cache, 1, rest(8, 8, true), now), Ok(false));
+        assert_eq!(insert(&mut cache, 2, rest(8, 8, true), now), Ok(false));
+        assert_eq!(
+            insert(&mut cache, 3, rest(8, 8, true), now),
+            Err(ReassemblyError::CacheFull)
+        );
+        assert_eq!(
+            insert(&mut cache, 1, rest(16, 56, true), now),
+            Err(ReassemblyError::CacheFull)
+        );
+        let later = now + Duration::from_secs(1);
+        assert_eq!(insert(&mut cache, 3, rest(8, 8, true), later), Ok(false));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.bytes(), 8);
+        assert_eq!(cache.expire(later + Duration::from_secs(1)), 1);
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.bytes(), 0);
+    }
+}
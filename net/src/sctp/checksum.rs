@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! SCTP checksum type and methods
+//!
+//! Unlike the other transport protocols, SCTP does not use the internet checksum but a
+//! [CRC32c][RFC9260] over the whole packet, so it does not implement
+//! [`Checksum`](crate::checksum::Checksum), whose checksums are 16-bit wide.
+//!
+//! [RFC9260]: https://datatracker.ietf.org/doc/html/rfc9260#appendix-A
+
+use crate::sctp::Sctp;
+use core::fmt::{Display, Formatter};
+
+/// A [`Sctp`] checksum (CRC32c)
+#[repr(transparent)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct SctpChecksum(pub(crate) u32);
+
+impl SctpChecksum {
+    /// Map a raw value to a [`SctpChecksum`]
+    #[must_use]
+    pub const fn new(raw: u32) -> SctpChecksum {
+        SctpChecksum(raw)
+    }
+}
+
+impl Display for SctpChecksum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#010X}", self.0)
+    }
+}
+
+impl From<u32> for SctpChecksum {
+    fn from(raw: u32) -> Self {
+        Self::new(raw)
+    }
+}
+
+impl From<SctpChecksum> for u32 {
+    fn from(checksum: SctpChecksum) -> Self {
+        checksum.0
+    }
+}
+
+/// Errors which may occur when validating a [`Sctp`] checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("checksum mismatch: expected {expected}, actual {actual}")]
+pub struct SctpChecksumError {
+    /// The expected (computed) checksum.
+    pub expected: SctpChecksum,
+    /// The actual checksum in the header.
+    pub actual: SctpChecksum,
+}
+
+/// Reflected CRC32c (Castagnoli) polynomial
+const POLYNOMIAL: u32 = 0x82F6_3B78;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        #[allow(clippy::cast_possible_truncation)] // i < 256
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ POLYNOMIAL
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Accumulate `bytes` into a running (non-inverted) CRC32c value.
+fn crc32c_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for &byte in bytes {
+        crc = TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+impl Sctp {
+    /// Get the checksum field of the header.
+    #[must_use]
+    pub const fn checksum(&self) -> SctpChecksum {
+        self.checksum
+    }
+
+    /// Set the checksum field of the header.
+    ///
+    /// The validity of the checksum is not checked.
+    pub fn set_checksum(&mut self, checksum: SctpChecksum) -> &mut Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Compute the checksum of the packet made of this header and `payload` (the chunks).
+    ///
+    /// This method _does not_ update the checksum field.
+    #[must_use]
+    pub fn compute_checksum(&self, payload: &[u8]) -> SctpChecksum {
+        // the checksum is computed with the checksum field set to zero
+        let mut header = [0u8; Sctp::MIN_LENGTH.get() as usize];
+        self.write_without_checksum(&mut header);
+        let crc = crc32c_update(u32::MAX, &header);
+        SctpChecksum(!crc32c_update(crc, payload))
+    }
+
+    /// Validate the checksum of the header against `payload`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SctpChecksumError`] if the checksum in the header is incorrect.
+    pub fn validate_checksum(&self, payload: &[u8]) -> Result<SctpChecksum, SctpChecksumError> {
+        let expected = self.compute_checksum(payload);
+        if expected == self.checksum {
+            Ok(expected)
+        } else {
+            Err(SctpChecksumError {
+                expected,
+                actual: self.checksum,
+            })
+        }
+    }
+
+    /// Compute the checksum of the packet made of this header and `payload`, and store it in the
+    /// header.
+    pub fn update_checksum(&mut self, payload: &[u8]) -> &mut Self {
+        let checksum = self.compute_checksum(payload);
+        self.set_checksum(checksum)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sctp::checksum::crc32c_update;
+
+    #[test]
+    fn crc32c_check_values() {
+        // check values from RFC 3720, appendix B.4, and the usual "123456789" check
+        assert_eq!(!crc32c_update(u32::MAX, &[0u8; 32]), 0x8A91_36AA);
+        assert_eq!(!crc32c_update(u32::MAX, &[0xFFu8; 32]), 0x62A8_AB43);
+        assert_eq!(!crc32c_update(u32::MAX, b"123456789"), 0xE306_9283);
+    }
+}
@@ -0,0 +1,239 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [SCTP][RFC9260] common header type and logic.
+//!
+//! Only the common header is parsed: the chunks are left in the payload of the packet.
+//!
+//! [RFC9260]: https://datatracker.ietf.org/doc/html/rfc9260#section-3.1
+
+mod checksum;
+pub mod port;
+
+pub use checksum::*;
+pub use port::*;
+
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use std::num::NonZero;
+
+/// An SCTP common header.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+pub struct Sctp {
+    source: SctpPort,
+    destination: SctpPort,
+    verification_tag: u32,
+    checksum: SctpChecksum,
+}
+
+impl Sctp {
+    /// The minimum length of a valid SCTP header (technically also the maximum length).
+    /// The name choice here is for consistency with other header types.
+    #[allow(clippy::unwrap_used)] // safe due to const-eval
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(12).unwrap();
+
+    /// Create a new SCTP header, with a zero checksum.
+    #[must_use]
+    pub fn new(source: SctpPort, destination: SctpPort, verification_tag: u32) -> Sctp {
+        Sctp {
+            source,
+            destination,
+            verification_tag,
+            checksum: SctpChecksum::default(),
+        }
+    }
+
+    /// Get the header's source port
+    #[must_use]
+    pub const fn source(&self) -> SctpPort {
+        self.source
+    }
+
+    /// Get the header's dest port
+    #[must_use]
+    pub const fn destination(&self) -> SctpPort {
+        self.destination
+    }
+
+    /// Get the header's verification tag
+    #[must_use]
+    pub const fn verification_tag(&self) -> u32 {
+        self.verification_tag
+    }
+
+    /// Set the source port.
+    pub fn set_source(&mut self, port: SctpPort) -> &mut Self {
+        self.source = port;
+        self
+    }
+
+    /// Set the destination port.
+    pub fn set_destination(&mut self, port: SctpPort) -> &mut Self {
+        self.destination = port;
+        self
+    }
+
+    /// Set the verification tag.
+    pub fn set_verification_tag(&mut self, verification_tag: u32) -> &mut Self {
+        self.verification_tag = verification_tag;
+        self
+    }
+
+    /// Write the header to the first [`Sctp::MIN_LENGTH`] bytes of `buf`, with a zero checksum.
+    fn write_without_checksum(&self, buf: &mut [u8]) {
+        buf[0..2].copy_from_slice(&self.source.as_u16().to_be_bytes());
+        buf[2..4].copy_from_slice(&self.destination.as_u16().to_be_bytes());
+        buf[4..8].copy_from_slice(&self.verification_tag.to_be_bytes());
+        buf[8..12].copy_from_slice(&[0; 4]);
+    }
+}
+
+/// Errors which may occur when parsing an SCTP header
+#[derive(Debug, thiserror::Error)]
+pub enum SctpParseError {
+    /// Zero is not a legal sctp port
+    #[error("zero source port")]
+    ZeroSourcePort,
+    /// Zero is not a legal sctp port
+    #[error("zero destination port")]
+    ZeroDestinationPort,
+}
+
+impl Parse for Sctp {
+    type Error = SctpParseError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < Sctp::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: Sctp::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let source = SctpPort::new_checked(u16::from_be_bytes([buf[0], buf[1]]))
+            .map_err(|_| ParseError::Invalid(SctpParseError::ZeroSourcePort))?;
+        let destination = SctpPort::new_checked(u16::from_be_bytes([buf[2], buf[3]]))
+            .map_err(|_| ParseError::Invalid(SctpParseError::ZeroDestinationPort))?;
+        let sctp = Sctp {
+            source,
+            destination,
+            verification_tag: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            // the CRC32c is transmitted least significant byte first
+            checksum: SctpChecksum(u32::from_le_bytes([buf[8], buf[9], buf[10], buf[11]])),
+        };
+        Ok((sctp, Sctp::MIN_LENGTH))
+    }
+}
+
+impl DeParse for Sctp {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        Sctp::MIN_LENGTH
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        let len = buf.len();
+        if len < self.size().into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: self.size().into_non_zero_usize(),
+                actual: len,
+            }));
+        }
+        self.write_without_checksum(buf);
+        buf[8..12].copy_from_slice(&self.checksum.0.to_le_bytes());
+        Ok(self.size())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse::{DeParse, IntoNonZeroUSize, Parse, ParseError};
+    use crate::sctp::{Sctp, SctpChecksum, SctpParseError, SctpPort};
+
+    const MIN_LENGTH_USIZE: usize = 12;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!().with_type().for_each(|input: &Sctp| {
+            let mut buffer = [0u8; MIN_LENGTH_USIZE];
+            let consumed = match input.deparse(&mut buffer) {
+                Ok(consumed) => consumed,
+                Err(err) => {
+                    unreachable!("failed to write sctp: {err:?}");
+                }
+            };
+            assert_eq!(consumed.into_non_zero_usize().get(), buffer.len());
+            let (parse_back, consumed2) = Sctp::parse(&buffer).unwrap();
+            assert_eq!(input, &parse_back);
+            assert_eq!(consumed, consumed2);
+        });
+    }
+
+    #[test]
+    fn parse_arbitrary_bytes() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; MIN_LENGTH_USIZE]| {
+                let (parsed, bytes_read) = match Sctp::parse(slice) {
+                    Ok(x) => x,
+                    Err(ParseError::Invalid(SctpParseError::ZeroSourcePort)) => {
+                        assert_eq!(slice[0..=1], [0, 0]);
+                        return;
+                    }
+                    Err(ParseError::Invalid(SctpParseError::ZeroDestinationPort)) => {
+                        assert_eq!(slice[2..=3], [0, 0]);
+                        return;
+                    }
+                    Err(ParseError::Length(_) | ParseError::BufferTooLong(_)) => unreachable!(),
+                };
+                let mut slice2 = [0u8; MIN_LENGTH_USIZE];
+                let bytes_written = parsed.deparse(&mut slice2).unwrap_or_else(|e| {
+                    unreachable!("{e:?}");
+                });
+                assert_eq!(bytes_read, bytes_written);
+                assert_eq!(slice, &slice2);
+            });
+    }
+
+    #[test]
+    fn too_short_buffer_parse_fails_gracefully() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; MIN_LENGTH_USIZE - 1]| {
+                for i in 0..slice.len() {
+                    match Sctp::parse(&slice[..i]) {
+                        Err(ParseError::Length(e)) => {
+                            assert_eq!(e.expected, Sctp::MIN_LENGTH.into_non_zero_usize());
+                            assert_eq!(e.actual, i);
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            });
+    }
+
+    #[test]
+    fn checksum() {
+        // INIT chunk from 5000 to 80
+        const PACKET: [u8; 32] = [
+            0x13, 0x88, 0x00, 0x50, 0x11, 0x22, 0x33, 0x44, 0x07, 0x5d, 0xdd, 0x92, 0x01, 0x00,
+            0x00, 0x14, 0xde, 0xad, 0xbe, 0xef, 0x00, 0x00, 0xff, 0xff, 0x00, 0x0a, 0x00, 0x0a,
+            0x00, 0x00, 0x00, 0x01,
+        ];
+        let (mut sctp, consumed) = Sctp::parse(&PACKET).unwrap();
+        let chunks = &PACKET[consumed.into_non_zero_usize().get()..];
+        assert_eq!(sctp.source(), SctpPort::new_checked(5000).unwrap());
+        assert_eq!(sctp.destination(), SctpPort::new_checked(80).unwrap());
+        assert_eq!(sctp.verification_tag(), 0x1122_3344);
+        assert_eq!(
+            sctp.validate_checksum(chunks),
+            Ok(SctpChecksum::new(0x92DD_5D07))
+        );
+
+        sctp.set_destination(SctpPort::new_checked(8080).unwrap());
+        let err = sctp.validate_checksum(chunks).unwrap_err();
+        assert_eq!(err.actual, SctpChecksum::new(0x92DD_5D07));
+        sctp.update_checksum(chunks);
+        assert_eq!(sctp.validate_checksum(chunks), Ok(err.expected));
+    }
+}
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! SCTP port type and parsing logic.
+
+use std::num::NonZero;
+
+/// Transparent wrapper type for sctp ports.
+///
+/// Zero overhead beyond that imposed by `NonZero<u16>`, i.e., only the non-zero check, which is
+/// required anyway.
+#[repr(transparent)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+#[allow(clippy::unsafe_derive_deserialize)] // both try_from and into u16 are safe for this type
+#[derive(
+    Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "u16", into = "u16")]
+pub struct SctpPort(NonZero<u16>);
+
+/// Errors which may occur in the creation or parsing of a [`SctpPort`].
+#[repr(transparent)]
+#[derive(
+    Debug,
+    thiserror::Error,
+    serde::Serialize,
+    serde::Deserialize,
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+)]
+pub enum SctpPortError {
+    /// The spec forbids the use of port zero.
+    #[error("port must be non-zero")]
+    Zero,
+}
+
+impl SctpPort {
+    /// Create a [`SctpPort`].
+    #[must_use]
+    pub const fn new(port: NonZero<u16>) -> SctpPort {
+        SctpPort(port)
+    }
+
+    /// Create a [`SctpPort`].
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the submitted raw port number is zero.
+    pub const fn new_checked(port: u16) -> Result<SctpPort, SctpPortError> {
+        match NonZero::new(port) {
+            None => Err(SctpPortError::Zero),
+            Some(port) => Ok(SctpPort(port)),
+        }
+    }
+
+    /// Get the value of a [`SctpPort`] as a u16
+    #[must_use]
+    pub fn as_u16(self) -> u16 {
+        self.0.get()
+    }
+}
+
+impl From<SctpPort> for u16 {
+    fn from(port: SctpPort) -> Self {
+        port.0.get()
+    }
+}
+
+impl TryFrom<u16> for SctpPort {
+    type Error = SctpPortError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Self::new_checked(value)
+    }
+}
+
+impl From<SctpPort> for NonZero<u16> {
+    fn from(port: SctpPort) -> Self {
+        port.0
+    }
+}
+
+impl From<NonZero<u16>> for SctpPort {
+    fn from(port: NonZero<u16>) -> Self {
+        Self(port)
+    }
+}
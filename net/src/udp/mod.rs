@@ -11,12 +11,13 @@ pub use checksum::*;
 pub use port::*;
 pub use truncated::*;
 
+use crate::geneve::Geneve;
 use crate::ipv4::Ipv4;
 use crate::ipv6::Ipv6;
 use crate::parse::{
     DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError, Reader,
 };
-use crate::vxlan::{Vni, Vxlan};
+use crate::vxlan::{Vni, Vxlan, VxlanGpe};
 use etherparse::UdpHeader;
 use std::num::NonZero;
 use tracing::debug;
@@ -27,23 +28,36 @@ pub struct Udp(UdpHeader);
 
 /// A UDP encapsulation.
 ///
-/// At this point we only support VXLAN, but Geneve and others can be added as needed.
+/// At this point we support VXLAN, VXLAN-GPE and Geneve, others can be added as needed.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UdpEncap {
     /// A VXLAN header in a UDP packet
     Vxlan(Vxlan),
+    /// A VXLAN-GPE header in a UDP packet
+    VxlanGpe(VxlanGpe),
+    /// A Geneve header in a UDP packet
+    Geneve(Geneve),
 }
 
 impl UdpEncap {
     /// Get the `Vni` of an encapsulation if it is `Vxlan`
     #[must_use]
     pub fn vxlan_vni(&self) -> Option<Vni> {
-        #[allow(unreachable_patterns)]
         match self {
             UdpEncap::Vxlan(vxlan) => Some(vxlan.vni()),
             _ => None,
         }
     }
+
+    /// Get the `Vni` of an encapsulation, whatever the encapsulation
+    #[must_use]
+    pub fn vni(&self) -> Vni {
+        match self {
+            UdpEncap::Vxlan(vxlan) => vxlan.vni(),
+            UdpEncap::VxlanGpe(gpe) => gpe.vni(),
+            UdpEncap::Geneve(geneve) => geneve.vni(),
+        }
+    }
 }
 
 impl Udp {
@@ -169,6 +183,20 @@ impl Udp {
                 };
                 Some(UdpEncap::Vxlan(vxlan))
             }
+            VxlanGpe::PORT => match cursor.parse::<VxlanGpe>() {
+                Ok((gpe, _)) => Some(UdpEncap::VxlanGpe(gpe)),
+                Err(e) => {
+                    debug!("vxlan-gpe parse error: {e:?}");
+                    None
+                }
+            },
+            Geneve::PORT => match cursor.parse::<Geneve>() {
+                Ok((geneve, _)) => Some(UdpEncap::Geneve(geneve)),
+                Err(e) => {
+                    debug!("geneve parse error: {e:?}");
+                    None
+                }
+            },
             _ => None,
         }
     }
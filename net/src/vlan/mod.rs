@@ -224,9 +224,46 @@ impl From<Pcp> for u8 {
     }
 }
 
+/// The kind of a VLAN tag, as given by the ethtype (TPID) of the header which precedes it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VlanTagKind {
+    /// An [802.1ad] service tag (S-VLAN), pushed by a provider bridge.
+    ///
+    /// [802.1ad]: https://en.wikipedia.org/wiki/IEEE_802.1ad
+    Service,
+    /// An [802.1Q] customer tag (C-VLAN).
+    ///
+    /// [802.1Q]: https://en.wikipedia.org/wiki/IEEE_802.1Q
+    Customer,
+}
+
+impl VlanTagKind {
+    /// Get the kind of VLAN tag introduced by `ethtype`, if any.
+    ///
+    /// The pre-standard [`EthType::VLAN_DOUBLE_TAGGED`] ethtype maps to [`VlanTagKind::Service`].
+    #[must_use]
+    pub fn from_ethtype(ethtype: EthType) -> Option<VlanTagKind> {
+        match ethtype {
+            EthType::VLAN_QINQ | EthType::VLAN_DOUBLE_TAGGED => Some(VlanTagKind::Service),
+            EthType::VLAN => Some(VlanTagKind::Customer),
+            _ => None,
+        }
+    }
+
+    /// Get the ethtype used to introduce this kind of VLAN tag.
+    #[must_use]
+    pub const fn ethtype(self) -> EthType {
+        match self {
+            VlanTagKind::Service => EthType::VLAN_QINQ,
+            VlanTagKind::Customer => EthType::VLAN,
+        }
+    }
+}
+
 /// A VLAN header.
 ///
-/// This may represent 802.1Q or 802.1AD (the outer ethtype is not stored in this struct)
+/// This may represent 802.1Q or 802.1AD (the outer ethtype is not stored in this struct, see
+/// [`VlanTagKind`])
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Vlan(SingleVlanHeader);
 
@@ -1,13 +1,13 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright Open Network Fabric Authors
 
-use crate::headers::{Headers, TryIp, TryTransportMut, TryVxlan};
+use crate::headers::{Headers, TryIp, TryTransportMut, TryVxlan, TryVxlanGpe};
 use tracing::{error, warn};
 
 /// Configuration for [`VxlanEncap`] operation
 ///
 /// This struct is a safety measure designed to check that the enclosed [`Headers`] really do
-/// describe a vxlan packet.
+/// describe a vxlan (or vxlan-gpe) packet.
 pub struct VxlanEncap {
     headers: Headers,
 }
@@ -27,8 +27,8 @@ pub enum VxlanEncapError {
     /// supplied headers have no UDP layer
     #[error("supplied headers have no UDP layer")]
     Udp,
-    /// supplied headers have no VXLAN layer
-    #[error("supplied headers have no VXLAN layer")]
+    /// supplied headers have no VXLAN or VXLAN-GPE layer
+    #[error("supplied headers have no VXLAN or VXLAN-GPE layer")]
     Vxlan,
 }
 
@@ -43,10 +43,14 @@ impl VxlanEncap {
             headers.transport.take();
             warn!("BUG: should not provide transport header; it will be ignored");
         }
-        match (headers.try_ip(), headers.try_vxlan()) {
-            (None, _) => Err(VxlanEncapError::Ip),
-            (_, None) => Err(VxlanEncapError::Vxlan),
-            (Some(_), Some(_)) => Ok(Self { headers }),
+        match (
+            headers.try_ip(),
+            headers.try_vxlan(),
+            headers.try_vxlan_gpe(),
+        ) {
+            (None, _, _) => Err(VxlanEncapError::Ip),
+            (_, None, None) => Err(VxlanEncapError::Vxlan),
+            (Some(_), _, _) => Ok(Self { headers }),
         }
     }
 
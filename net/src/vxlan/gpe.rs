@@ -0,0 +1,355 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! [VXLAN-GPE][draft] (Generic Protocol Extension) header.
+//!
+//! [draft]: https://datatracker.ietf.org/doc/html/draft-ietf-nvo3-vxlan-gpe-13#section-3
+
+use crate::eth::ethtype::EthType;
+use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
+use crate::udp::port::UdpPort;
+use crate::vxlan::{InvalidVni, Vni};
+use core::num::NonZero;
+use tracing::trace;
+
+/// A [VXLAN-GPE][draft] header.
+///
+/// Unlike [`Vxlan`](crate::vxlan::Vxlan) headers, which always carry ethernet frames, VXLAN-GPE
+/// headers carry a [`VxlanGpeProtocol`] field describing their payload.
+///
+/// [draft]: https://datatracker.ietf.org/doc/html/draft-ietf-nvo3-vxlan-gpe-13#section-3
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+pub struct VxlanGpe {
+    vni: Vni,
+    protocol: VxlanGpeProtocol,
+    bum: bool,
+    oam: bool,
+}
+
+/// The next protocol field of a [`VxlanGpe`] header.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[cfg_attr(any(test, feature = "bolero"), derive(bolero::TypeGenerator))]
+pub struct VxlanGpeProtocol(pub u8);
+
+impl VxlanGpeProtocol {
+    /// IPv4 payload
+    pub const IPV4: VxlanGpeProtocol = VxlanGpeProtocol(1);
+    /// IPv6 payload
+    pub const IPV6: VxlanGpeProtocol = VxlanGpeProtocol(2);
+    /// Ethernet payload
+    pub const ETHERNET: VxlanGpeProtocol = VxlanGpeProtocol(3);
+    /// Network Service Header payload
+    pub const NSH: VxlanGpeProtocol = VxlanGpeProtocol(4);
+    /// MPLS payload
+    pub const MPLS: VxlanGpeProtocol = VxlanGpeProtocol(5);
+
+    /// Get the [`EthType`] of the payload described by this protocol, if any.
+    ///
+    /// Ethernet payloads map to [`EthType::TRANSPARENT_ETHERNET_BRIDGING`].
+    #[must_use]
+    pub const fn ethtype(self) -> Option<EthType> {
+        match self {
+            VxlanGpeProtocol::IPV4 => Some(EthType::IPV4),
+            VxlanGpeProtocol::IPV6 => Some(EthType::IPV6),
+            VxlanGpeProtocol::ETHERNET => Some(EthType::TRANSPARENT_ETHERNET_BRIDGING),
+            VxlanGpeProtocol::NSH => Some(EthType::NSH),
+            VxlanGpeProtocol::MPLS => Some(EthType::MPLS),
+            _ => None,
+        }
+    }
+}
+
+impl VxlanGpe {
+    /// UDP port on which we expect to receive VXLAN-GPE frames.  The draft requires 4790.
+    #[allow(unsafe_code)] // const-eval and trivially safe
+    pub const PORT: UdpPort = unsafe { UdpPort::new_unchecked(4790) };
+
+    /// The minimum (and maximum) length of a [`VxlanGpe`] header.
+    #[allow(clippy::unwrap_used)] // trivially safe const expression
+    pub const MIN_LENGTH: NonZero<u16> = NonZero::new(8).unwrap();
+
+    /// The only version of VXLAN-GPE defined so far.
+    const VERSION: u8 = 0;
+    /// Version field, in the first byte of the header
+    const VERSION_MASK: u8 = 0b0011_0000;
+    /// VNI present bit, which must be set
+    const FLAG_I: u8 = 0b0000_1000;
+    /// Next protocol present bit, which must be set
+    const FLAG_P: u8 = 0b0000_0100;
+    /// Broadcast, unknown unicast or multicast (BUM) traffic bit
+    const FLAG_B: u8 = 0b0000_0010;
+    /// OAM packet bit
+    const FLAG_O: u8 = 0b0000_0001;
+
+    /// Create a new VXLAN-GPE header.
+    #[must_use]
+    pub fn new(vni: Vni, protocol: VxlanGpeProtocol) -> VxlanGpe {
+        VxlanGpe {
+            vni,
+            protocol,
+            bum: false,
+            oam: false,
+        }
+    }
+
+    /// Get the [`Vni`] of this header.
+    #[must_use]
+    pub const fn vni(&self) -> Vni {
+        self.vni
+    }
+
+    /// Set the [`Vni`] of this header.
+    pub const fn set_vni(&mut self, vni: Vni) -> &mut VxlanGpe {
+        self.vni = vni;
+        self
+    }
+
+    /// Get the protocol of the payload of this header.
+    #[must_use]
+    pub const fn protocol(&self) -> VxlanGpeProtocol {
+        self.protocol
+    }
+
+    /// Set the protocol of the payload of this header.
+    pub const fn set_protocol(&mut self, protocol: VxlanGpeProtocol) -> &mut VxlanGpe {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Tell if this header is for broadcast, unknown unicast or multicast traffic.
+    #[must_use]
+    pub const fn bum(&self) -> bool {
+        self.bum
+    }
+
+    /// Mark this header as being for broadcast, unknown unicast or multicast traffic, or not.
+    pub const fn set_bum(&mut self, bum: bool) -> &mut VxlanGpe {
+        self.bum = bum;
+        self
+    }
+
+    /// Tell if this header is for an OAM (control) packet.
+    #[must_use]
+    pub const fn oam(&self) -> bool {
+        self.oam
+    }
+
+    /// Mark this header as being for an OAM (control) packet, or not.
+    pub const fn set_oam(&mut self, oam: bool) -> &mut VxlanGpe {
+        self.oam = oam;
+        self
+    }
+}
+
+/// Errors which may occur when parsing a [`VxlanGpe`] header.
+#[derive(Debug, thiserror::Error)]
+pub enum VxlanGpeError {
+    /// [`Vni`] is a non-zero, 24-bit number.
+    #[error(transparent)]
+    InvalidVni(InvalidVni),
+    /// The I and P flags must be set in VXLAN-GPE headers.
+    #[error("Required bit unset")]
+    RequiredBitUnset,
+    /// Only version 0 of VXLAN-GPE is supported.
+    #[error("Unsupported VXLAN-GPE version {0}")]
+    UnsupportedVersion(u8),
+}
+
+impl Parse for VxlanGpe {
+    type Error = VxlanGpeError;
+
+    fn parse(buf: &[u8]) -> Result<(Self, NonZero<u16>), ParseError<Self::Error>> {
+        if buf.len() < VxlanGpe::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(ParseError::Length(LengthError {
+                expected: VxlanGpe::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let flags = buf[0];
+        let version = (flags & VxlanGpe::VERSION_MASK) >> 4;
+        if version != VxlanGpe::VERSION {
+            return Err(ParseError::Invalid(VxlanGpeError::UnsupportedVersion(
+                version,
+            )));
+        }
+        let required = VxlanGpe::FLAG_I | VxlanGpe::FLAG_P;
+        if flags & required != required {
+            return Err(ParseError::Invalid(VxlanGpeError::RequiredBitUnset));
+        }
+        if flags & 0b1100_0000 != 0 || buf[1..=2] != [0, 0] || buf[7] != 0 {
+            // reserved fields must be ignored on receipt
+            trace!("Received VXLAN-GPE header with reserved bits set.");
+        }
+        let raw_vni = u32::from_be_bytes([0, buf[4], buf[5], buf[6]]);
+        let vni = Vni::new_checked(raw_vni)
+            .map_err(|e| ParseError::Invalid(VxlanGpeError::InvalidVni(e)))?;
+        let header = VxlanGpe {
+            vni,
+            protocol: VxlanGpeProtocol(buf[3]),
+            bum: flags & VxlanGpe::FLAG_B != 0,
+            oam: flags & VxlanGpe::FLAG_O != 0,
+        };
+        Ok((header, VxlanGpe::MIN_LENGTH))
+    }
+}
+
+impl DeParse for VxlanGpe {
+    type Error = ();
+
+    fn size(&self) -> NonZero<u16> {
+        VxlanGpe::MIN_LENGTH
+    }
+
+    fn deparse(&self, buf: &mut [u8]) -> Result<NonZero<u16>, DeParseError<Self::Error>> {
+        if buf.len() < VxlanGpe::MIN_LENGTH.into_non_zero_usize().get() {
+            return Err(DeParseError::Length(LengthError {
+                expected: VxlanGpe::MIN_LENGTH.into_non_zero_usize(),
+                actual: buf.len(),
+            }));
+        }
+        let mut flags = (VxlanGpe::VERSION << 4) | VxlanGpe::FLAG_I | VxlanGpe::FLAG_P;
+        if self.bum {
+            flags |= VxlanGpe::FLAG_B;
+        }
+        if self.oam {
+            flags |= VxlanGpe::FLAG_O;
+        }
+        buf[0] = flags;
+        buf[1..=2].copy_from_slice(&[0, 0]);
+        buf[3] = self.protocol.0;
+        buf[4..=7].copy_from_slice(&(self.vni.as_u32() << 8).to_be_bytes());
+        Ok(VxlanGpe::MIN_LENGTH)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::eth::Eth;
+    use crate::eth::ethtype::EthType;
+    use crate::eth::mac::Mac;
+    use crate::headers::{Net, TryEth, TryIp, TryUdp, TryVxlanGpe};
+    use crate::ipv4::Ipv4;
+    use crate::ipv4::addr::UnicastIpv4Addr;
+    use crate::packet::PacketBuilder;
+    use crate::parse::{DeParse, IntoNonZeroUSize, Parse, ParseError};
+    use crate::udp::Udp;
+    use crate::vxlan::{Vni, VxlanGpe, VxlanGpeError, VxlanGpeProtocol};
+    use std::net::Ipv4Addr;
+    const MIN_LENGTH_USIZE: usize = 8;
+
+    #[test]
+    fn parse_back() {
+        bolero::check!().with_type().for_each(|gpe: &VxlanGpe| {
+            let mut buf = [0u8; MIN_LENGTH_USIZE];
+            let bytes_written = gpe.deparse(&mut buf).unwrap_or_else(|_| unreachable!());
+            assert_eq!(bytes_written, VxlanGpe::MIN_LENGTH);
+            let (parsed, bytes_parsed) = VxlanGpe::parse(&buf).unwrap();
+            assert_eq!(parsed, *gpe);
+            assert_eq!(bytes_parsed, VxlanGpe::MIN_LENGTH);
+        });
+    }
+
+    #[test]
+    fn parse_ipv4_payload() {
+        let buf = [0x0c, 0, 0, 0x01, 0x00, 0x00, 0x2a, 0];
+        let (gpe, _) = VxlanGpe::parse(&buf).unwrap();
+        assert_eq!(gpe.vni(), Vni::new_checked(42).unwrap());
+        assert_eq!(gpe.protocol(), VxlanGpeProtocol::IPV4);
+        assert_eq!(gpe.protocol().ethtype(), Some(EthType::IPV4));
+        assert!(!gpe.bum());
+        assert!(!gpe.oam());
+
+        // P flag unset: this is a plain VXLAN header
+        let buf = [0x08, 0, 0, 0, 0x00, 0x00, 0x2a, 0];
+        assert!(matches!(
+            VxlanGpe::parse(&buf),
+            Err(ParseError::Invalid(VxlanGpeError::RequiredBitUnset))
+        ));
+        let buf = [0x1c, 0, 0, 0x01, 0x00, 0x00, 0x2a, 0];
+        assert!(matches!(
+            VxlanGpe::parse(&buf),
+            Err(ParseError::Invalid(VxlanGpeError::UnsupportedVersion(1)))
+        ));
+    }
+
+    #[test]
+    fn decap_ipv4_payload() {
+        let inner_ip = |destination| {
+            let mut ip = Ipv4::default();
+            ip.set_source(UnicastIpv4Addr::new(Ipv4Addr::new(192, 168, 0, 1)).unwrap())
+                .set_destination(destination)
+                .set_ttl(64);
+            ip
+        };
+        let outer_ip = inner_ip(Ipv4Addr::new(192, 168, 0, 2));
+        let inner_frame = PacketBuilder::new()
+            .eth(Mac([0x2, 0, 0, 0, 0, 1]), Mac([0x2, 0, 0, 0, 0, 2]))
+            .ipv4(inner_ip(Ipv4Addr::new(10, 0, 0, 1)))
+            .build_bytes()
+            .unwrap();
+        let mut packet = PacketBuilder::new()
+            .eth(Mac([0x2, 0, 0, 0, 0, 1]), Mac([0x2, 0, 0, 0, 0, 2]))
+            .ipv4(outer_ip)
+            .udp(Udp::new(1234.try_into().unwrap(), VxlanGpe::PORT))
+            .vxlan_gpe(VxlanGpe::new(
+                Vni::new_checked(42).unwrap(),
+                VxlanGpeProtocol::IPV4,
+            ))
+            .payload(&inner_frame[Eth::HEADER_LEN.into_non_zero_usize().get()..])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            packet.try_vxlan_gpe().unwrap().protocol(),
+            VxlanGpeProtocol::IPV4
+        );
+        let gpe = packet.vxlan_gpe_decap().unwrap().unwrap();
+        assert_eq!(gpe.vni(), Vni::new_checked(42).unwrap());
+        assert!(packet.try_udp().is_none());
+        let Some(Net::Ipv4(ip)) = packet.try_ip() else {
+            panic!("expected inner ipv4 header");
+        };
+        assert_eq!(ip.destination(), Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(packet.try_eth().unwrap().ether_type(), EthType::IPV4);
+    }
+
+    #[test]
+    fn parse_noise() {
+        bolero::check!()
+            .with_type()
+            .for_each(|slice: &[u8; MIN_LENGTH_USIZE]| {
+                let (parsed, bytes_parsed) = match VxlanGpe::parse(slice) {
+                    Ok((parsed, bytes_parsed)) => (parsed, bytes_parsed),
+                    Err(ParseError::Invalid(VxlanGpeError::InvalidVni(_))) => {
+                        assert_eq!(&slice[4..=6], &[0, 0, 0]);
+                        return;
+                    }
+                    Err(ParseError::Invalid(VxlanGpeError::UnsupportedVersion(v))) => {
+                        assert_ne!(v, 0);
+                        return;
+                    }
+                    Err(ParseError::Invalid(VxlanGpeError::RequiredBitUnset)) => {
+                        assert_ne!(slice[0] & 0b1100, 0b1100);
+                        return;
+                    }
+                    Err(e) => unreachable!("{e:?}"),
+                };
+                assert_eq!(bytes_parsed, VxlanGpe::MIN_LENGTH);
+                let mut write_back_buffer = [0u8; MIN_LENGTH_USIZE];
+                let bytes_written = parsed
+                    .deparse(&mut write_back_buffer)
+                    .unwrap_or_else(|_| unreachable!());
+                assert_eq!(bytes_written, VxlanGpe::MIN_LENGTH);
+                // reserved fields are cleared, others are preserved
+                assert_eq!(write_back_buffer[0], slice[0] & 0b0011_1111);
+                assert_eq!(&write_back_buffer[1..=2], &[0, 0]);
+                assert_eq!(
+                    &write_back_buffer[3..bytes_written.into_non_zero_usize().get() - 1],
+                    &slice[3..bytes_written.into_non_zero_usize().get() - 1]
+                );
+                assert_eq!(write_back_buffer[7], 0);
+            });
+    }
+}
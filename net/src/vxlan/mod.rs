@@ -6,12 +6,14 @@
 //! [RFC7348]: https://datatracker.ietf.org/doc/html/rfc7348#section-5
 
 mod encap;
+mod gpe;
 mod vni;
 
 use crate::parse::{DeParse, DeParseError, IntoNonZeroUSize, LengthError, Parse, ParseError};
 use crate::udp::port::UdpPort;
 use core::num::NonZero;
 pub use encap::{VxlanEncap, VxlanEncapError};
+pub use gpe::{VxlanGpe, VxlanGpeError, VxlanGpeProtocol};
 use tracing::trace;
 pub use vni::{InvalidVni, Vni};
 
@@ -0,0 +1,177 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+use crate::{DynNetworkFunction, NetworkFunction, nf_dyn};
+use dyn_iter::IntoDynIterator;
+use net::buffer::PacketBufferMut;
+use net::packet::Packet;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Network function that sends each packet to one of several network functions, the branches
+/// (typically [`DynPipeline`]s), depending on the class that a classifier gives to the packet,
+/// and merges their outputs.
+///
+/// Packets of a class without a branch go to the default branch, if any, or else get through
+/// unchanged. Packets are processed by batches: the input is split per branch, then each
+/// branch processes its own packets. The order of packets is preserved within a branch, but
+/// not across branches.
+///
+/// # Example
+///
+/// ```rust
+/// use dataplane_pipeline::{Branch, DynPipeline};
+/// use dataplane_pipeline::sample_nfs::{BroadcastMacs, DecrementTtl};
+/// use net::buffer::TestBuffer;
+/// use net::headers::TryIpv4;
+/// use net::packet::Packet;
+///
+/// // Decrement the TTL of IPv4 packets, and broadcast the other ones
+/// let branch = Branch::new(|packet: &Packet<TestBuffer>| Some(packet.try_ipv4().is_some()))
+///     .add_branch(true, DecrementTtl)
+///     .add_branch(false, BroadcastMacs);
+/// let pipeline = DynPipeline::<TestBuffer>::new().add_stage(branch);
+/// ```
+///
+/// [`DynPipeline`]: crate::DynPipeline
+pub struct Branch<Buf: PacketBufferMut, K, F> {
+    classifier: F,
+    index: HashMap<K, usize>, /* index of the branch for each class */
+    branches: Vec<Box<dyn DynNetworkFunction<Buf>>>,
+    default: Option<usize>, /* index of the default branch */
+}
+
+impl<Buf, K, F> Branch<Buf, K, F>
+where
+    Buf: PacketBufferMut + 'static,
+    K: Eq + Hash,
+    F: FnMut(&Packet<Buf>) -> Option<K>,
+{
+    /// Create a [`Branch`] with the given classifier. It has no branches yet, so packets get
+    /// through unchanged until some get added.
+    #[must_use]
+    pub fn new(classifier: F) -> Self {
+        Self {
+            classifier,
+            index: HashMap::new(),
+            branches: Vec::new(),
+            default: None,
+        }
+    }
+
+    fn add(&mut self, index: Option<usize>, nf: Box<dyn DynNetworkFunction<Buf>>) -> usize {
+        if let Some(index) = index {
+            self.branches[index] = nf;
+            index
+        } else {
+            self.branches.push(nf);
+            self.branches.len() - 1
+        }
+    }
+
+    /// Send the packets of class `class` to `nf`, replacing the branch of that class, if any.
+    #[must_use]
+    pub fn add_branch<NF: NetworkFunction<Buf> + 'static>(mut self, class: K, nf: NF) -> Self {
+        let index = self.add(self.index.get(&class).copied(), nf_dyn(nf));
+        self.index.insert(class, index);
+        self
+    }
+
+    /// Send the packets of classes without a branch, and those without a class, to `nf`,
+    /// replacing the default branch, if any.
+    #[must_use]
+    pub fn default_branch<NF: NetworkFunction<Buf> + 'static>(mut self, nf: NF) -> Self {
+        self.default = Some(self.add(self.default, nf_dyn(nf)));
+        self
+    }
+}
+
+impl<Buf, K, F> NetworkFunction<Buf> for Branch<Buf, K, F>
+where
+    Buf: PacketBufferMut + 'static,
+    K: Eq + Hash,
+    F: FnMut(&Packet<Buf>) -> Option<K>,
+{
+    fn process<'a, Input: Iterator<Item = Packet<Buf>> + 'a>(
+        &'a mut self,
+        input: Input,
+    ) -> impl Iterator<Item = Packet<Buf>> + 'a {
+        let mut batches: Vec<Vec<Packet<Buf>>> = std::iter::repeat_with(Vec::new)
+            .take(self.branches.len())
+            .collect();
+        let mut unchanged = Vec::new();
+        for packet in input {
+            let branch = (self.classifier)(&packet)
+                .and_then(|class| self.index.get(&class).copied())
+                .or(self.default);
+            match branch {
+                Some(branch) => batches[branch].push(packet),
+                None => unchanged.push(packet),
+            }
+        }
+        let outputs = self
+            .branches
+            .iter_mut()
+            .zip(batches)
+            .filter(|(_, batch)| !batch.is_empty())
+            .flat_map(|(nf, batch)| nf.process_dyn(batch.into_iter().into_dyn_iter()));
+        unchanged.into_iter().chain(outputs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use net::buffer::TestBuffer;
+    use net::eth::mac::{DestinationMac, Mac};
+    use net::headers::{TryEth, TryIpv4};
+    use net::packet::Packet;
+    use net::packet::test_utils::build_test_ipv4_packet;
+
+    use crate::sample_nfs::{BroadcastMacs, DecrementTtl};
+    use crate::{Branch, DynPipeline, NetworkFunction};
+
+    fn ttl(packet: &Packet<TestBuffer>) -> u8 {
+        packet.try_ipv4().unwrap().ttl()
+    }
+
+    fn is_broadcast(packet: &Packet<TestBuffer>) -> bool {
+        packet.try_eth().unwrap().destination() == DestinationMac::new(Mac::BROADCAST).unwrap()
+    }
+
+    #[test]
+    fn branch_by_class() {
+        // packets are classified by their TTL modulo 3
+        let mut branch = Branch::new(|packet: &Packet<TestBuffer>| Some(ttl(packet) % 3))
+            .add_branch(
+                0,
+                DynPipeline::new()
+                    .add_stage(DecrementTtl)
+                    .add_stage(DecrementTtl),
+            )
+            .add_branch(1, BroadcastMacs);
+
+        let packets: Vec<_> = [30, 31, 32, 33]
+            .into_iter()
+            .map(|ttl| build_test_ipv4_packet(ttl).unwrap())
+            .collect();
+        let mut out: Vec<_> = branch.process(packets.into_iter()).collect();
+        out.sort_by_key(ttl);
+        let ttls: Vec<_> = out.iter().map(ttl).collect();
+        assert_eq!(ttls, [28, 31, 31, 32]);
+        assert_eq!(out[1..3].iter().filter(|p| is_broadcast(p)).count(), 1);
+        assert!(!is_broadcast(&out[3]));
+
+        // packets without a branch go to the default branch, once there is one
+        let mut branch = branch.default_branch(DecrementTtl);
+        let packets = vec![build_test_ipv4_packet(32).unwrap()];
+        let out: Vec<_> = branch.process(packets.into_iter()).collect();
+        assert_eq!(ttl(&out[0]), 31);
+
+        // branches get replaced
+        let mut branch = branch.add_branch(1, DecrementTtl);
+        let packets = vec![build_test_ipv4_packet(31).unwrap()];
+        let out: Vec<_> = branch.process(packets.into_iter()).collect();
+        assert_eq!(ttl(&out[0]), 30);
+        assert!(!is_broadcast(&out[0]));
+    }
+}
@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Per-stage packet counters.
+//!
+//! A [`DynPipeline`] built with [`DynPipeline::with_counters`] accounts the packets that enter
+//! and leave each of its stages. Each pipeline instance (e.g. one per worker) has its own
+//! counters, so that workers do not contend on them, and all of them are kept in a shared
+//! [`PipelineCounters`], which sums them per stage name.
+//!
+//! Only the pipeline instance owning counters updates them, so that they are incremented with
+//! plain loads and stores rather than atomic read-modify-write operations. Readers may thus see
+//! them slightly behind.
+//!
+//! [`DynPipeline`]: crate::DynPipeline
+//! [`DynPipeline::with_counters`]: crate::DynPipeline::with_counters
+
+use net::buffer::PacketBufferMut;
+use net::packet::{DoneReason, Packet};
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Tell if a packet was marked to be dropped, as opposed to delivered or punted to the kernel
+fn is_dropped<Buf: PacketBufferMut>(packet: &Packet<Buf>) -> bool {
+    packet
+        .get_done()
+        .is_some_and(|reason| !matches!(reason, DoneReason::Local | DoneReason::Delivered))
+}
+
+/// Add `n` to a counter that a single thread updates
+fn add(counter: &AtomicU64, n: u64) {
+    counter.store(counter.load(Ordering::Relaxed) + n, Ordering::Relaxed);
+}
+
+/// The counters of a stage of a pipeline instance
+#[derive(Debug, Default)]
+pub(crate) struct StageCounters {
+    rx: AtomicU64,      /* packets entering the stage */
+    tx: AtomicU64,      /* packets leaving the stage */
+    drops: AtomicU64,   /* packets dropped by the stage, accounted at the end of each burst */
+    live_rx: AtomicU64, /* packets of the current burst entering the stage, not marked as dropped */
+    live_tx: AtomicU64, /* packets of the current burst leaving the stage, not marked as dropped */
+}
+
+impl StageCounters {
+    pub(crate) fn count_rx<Buf: PacketBufferMut>(&self, packet: &Packet<Buf>) {
+        add(&self.rx, 1);
+        if !is_dropped(packet) {
+            add(&self.live_rx, 1);
+        }
+    }
+
+    pub(crate) fn count_tx<Buf: PacketBufferMut>(&self, packet: &Packet<Buf>) {
+        add(&self.tx, 1);
+        if !is_dropped(packet) {
+            add(&self.live_tx, 1);
+        }
+    }
+
+    /// Account the packets of the burst that the stage dropped: those that entered it live and
+    /// that it either marked to be dropped or removed from the stream. Frames that the stage
+    /// injects only offset the drops of the burst they belong to.
+    fn end_burst(&self) {
+        let live_rx = self.live_rx.load(Ordering::Relaxed);
+        let live_tx = self.live_tx.load(Ordering::Relaxed);
+        self.live_rx.store(0, Ordering::Relaxed);
+        self.live_tx.store(0, Ordering::Relaxed);
+        add(&self.drops, live_rx.saturating_sub(live_tx));
+    }
+}
+
+/// The output of a stage for a burst of packets, counting the packets leaving the stage, and the
+/// packets that it dropped once the burst is over
+pub(crate) struct CountedOutput<'a, I> {
+    output: I,
+    counters: &'a StageCounters,
+}
+
+impl<'a, I> CountedOutput<'a, I> {
+    pub(crate) fn new(output: I, counters: &'a StageCounters) -> Self {
+        Self { output, counters }
+    }
+}
+
+impl<Buf: PacketBufferMut, I: Iterator<Item = Packet<Buf>>> Iterator for CountedOutput<'_, I> {
+    type Item = Packet<Buf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let packet = self.output.next()?;
+        self.counters.count_tx(&packet);
+        Some(packet)
+    }
+}
+
+impl<I> Drop for CountedOutput<'_, I> {
+    fn drop(&mut self) {
+        self.counters.end_burst();
+    }
+}
+
+/// The packet counts of a stage, summed over all the pipeline instances
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageStats {
+    /// Number of packets that entered the stage
+    pub packets_in: u64,
+    /// Number of packets that left the stage
+    pub packets_out: u64,
+    /// Number of packets that the stage dropped: packets that it marked to be dropped, or that
+    /// it removed from the stream
+    pub drops: u64,
+}
+
+impl StageStats {
+    fn add(&mut self, counters: &StageCounters) {
+        // the counters may be updated while they are read, so they may be slightly off
+        self.packets_in += counters.rx.load(Ordering::Relaxed);
+        self.packets_out += counters.tx.load(Ordering::Relaxed);
+        self.drops += counters.drops.load(Ordering::Relaxed);
+    }
+}
+
+/// The counters of the stages of all the instances of a pipeline, by stage name. This is shared
+/// by the pipeline instances and the readers of the counters (e.g. metrics or the CLI).
+#[derive(Debug, Default)]
+pub struct PipelineCounters {
+    stages: Mutex<Vec<(String, Arc<StageCounters>)>>,
+}
+
+impl PipelineCounters {
+    /// Create an empty set of counters
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register new counters for a stage of a pipeline instance
+    pub(crate) fn register(&self, name: &str) -> Arc<StageCounters> {
+        let counters = Arc::new(StageCounters::default());
+        if let Ok(mut stages) = self.stages.lock() {
+            stages.push((name.to_string(), counters.clone()));
+        }
+        counters
+    }
+
+    /// Get the packet counts of each stage, in the order of the stages in the pipeline
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<(String, StageStats)> {
+        let mut snapshot: Vec<(String, StageStats)> = Vec::new();
+        let Ok(stages) = self.stages.lock() else {
+            return snapshot;
+        };
+        for (name, counters) in stages.iter() {
+            if let Some((_, stats)) = snapshot.iter_mut().find(|(n, _)| n == name) {
+                stats.add(counters);
+            } else {
+                let mut stats = StageStats::default();
+                stats.add(counters);
+                snapshot.push((name.clone(), stats));
+            }
+        }
+        snapshot
+    }
+
+    /// Get the packet counts of the stage `name`, if there is such a stage
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<StageStats> {
+        self.snapshot()
+            .into_iter()
+            .find_map(|(n, stats)| (n == name).then_some(stats))
+    }
+}
+
+impl Display for StageStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>12} {:>12} {:>12}",
+            self.packets_in, self.packets_out, self.drops
+        )
+    }
+}
+
+impl Display for PipelineCounters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>12} {:>12} {:>12}",
+            "stage", "in", "out", "drops"
+        )?;
+        for (name, stats) in self.snapshot() {
+            writeln!(f, "{name:<24} {stats}")?;
+        }
+        Ok(())
+    }
+}
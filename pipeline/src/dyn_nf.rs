@@ -29,6 +29,18 @@ pub trait DynNetworkFunction<Buf: PacketBufferMut>: Any {
     /// type.  However, if you only have a dynamic iterator, you can use this method to process the
     /// packets.
     fn process_dyn<'a>(&'a mut self, input: DynIter<'a, Packet<Buf>>) -> DynIter<'a, Packet<Buf>>;
+
+    /// The name of the network function, used to name the pipeline stages running it. This
+    /// defaults to the name of its type, without its path and generic parameters.
+    fn name(&self) -> &'static str {
+        short_type_name(std::any::type_name::<Self>())
+    }
+}
+
+/// Strip the path and generic parameters of a type name
+fn short_type_name(name: &'static str) -> &'static str {
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
 }
 
 pub(crate) struct DynNetworkFunctionImpl<Buf: PacketBufferMut, NF: NetworkFunction<Buf> + 'static> {
@@ -69,4 +81,8 @@ impl<Buf: PacketBufferMut, NF: NetworkFunction<Buf>> DynNetworkFunction<Buf>
     fn process_dyn<'a>(&'a mut self, input: DynIter<'a, Packet<Buf>>) -> DynIter<'a, Packet<Buf>> {
         self.nf.process(input).into_dyn_iter()
     }
+
+    fn name(&self) -> &'static str {
+        short_type_name(std::any::type_name::<NF>())
+    }
 }
@@ -0,0 +1,334 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Per-stage latency histograms.
+//!
+//! A [`DynPipeline`] built with [`DynPipeline::with_latencies`] measures the time that each of
+//! its stages takes to produce each of its output packets, excluding the time spent in the
+//! previous stages, and records it in a histogram. As for the [`PipelineCounters`], each
+//! pipeline instance has its own histograms, and all of them are kept in a shared
+//! [`PipelineLatencies`], which merges them per stage name.
+//!
+//! The histograms are HDR-style: values are recorded in buckets whose width grows with the
+//! value, so that the relative error is bounded (1/16th) over the whole range, with a fixed
+//! number of buckets. Measuring the time takes a few clock reads per packet and per stage,
+//! which is why the instrumentation is optional.
+//!
+//! [`DynPipeline`]: crate::DynPipeline
+//! [`DynPipeline::with_latencies`]: crate::DynPipeline::with_latencies
+//! [`PipelineCounters`]: crate::PipelineCounters
+
+use dyn_iter::{DynIter, IntoDynIterator};
+use std::cell::Cell;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Number of bits of the values kept exactly: there are `1 << SUB_BITS` buckets per power of 2
+const SUB_BITS: u32 = 4;
+const SUB: usize = 1 << SUB_BITS;
+/// Largest exponent of the buckets: values of 2^36 ns (about 68 s) and above share the last one
+const MAX_EXPONENT: u32 = 32;
+const BUCKETS: usize = (MAX_EXPONENT as usize + 1) * SUB + SUB;
+
+/// Index of the bucket of `value`
+fn bucket(value: u64) -> usize {
+    if value < SUB as u64 {
+        #[allow(clippy::cast_possible_truncation)] // value < SUB
+        return value as usize;
+    }
+    let exponent = (63 - value.leading_zeros() - SUB_BITS).min(MAX_EXPONENT);
+    #[allow(clippy::cast_possible_truncation)] // the mantissa is below 2 * SUB
+    let mantissa = (value >> exponent).min(2 * SUB as u64 - 1) as usize;
+    exponent as usize * SUB + mantissa
+}
+
+/// Smallest value and width of bucket `index`
+fn bucket_range(index: usize) -> (u64, u64) {
+    if index < SUB {
+        return (index as u64, 1);
+    }
+    let exponent = index / SUB - 1;
+    let mantissa = (index % SUB + SUB) as u64;
+    (mantissa << exponent, 1 << exponent)
+}
+
+/// Value representing bucket `index`: the middle of the bucket
+fn bucket_value(index: usize) -> u64 {
+    let (low, width) = bucket_range(index);
+    low + (width - 1) / 2
+}
+
+/// A histogram of latencies, in nanoseconds
+#[derive(Debug)]
+pub(crate) struct LatencyHistogram {
+    counts: Box<[AtomicU64]>,
+    sum: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            counts: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub(crate) fn record(&self, nanos: u64) {
+        self.counts[bucket(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    fn add_to(&self, snapshot: &mut LatencySnapshot) {
+        for (count, bucket) in snapshot.counts.iter_mut().zip(self.counts.iter()) {
+            *count += bucket.load(Ordering::Relaxed);
+        }
+        snapshot.sum += self.sum.load(Ordering::Relaxed);
+    }
+}
+
+/// Nanoseconds elapsed since `start`
+fn elapsed_nanos(start: Instant) -> u64 {
+    u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Measures the latency of a stage of a pipeline instance
+#[derive(Debug)]
+pub(crate) struct StageTimer {
+    histogram: Arc<LatencyHistogram>,
+    upstream: Cell<u64>, /* time spent pulling packets from the previous stages */
+}
+
+impl StageTimer {
+    pub(crate) fn new(histogram: Arc<LatencyHistogram>) -> Self {
+        Self {
+            histogram,
+            upstream: Cell::new(0),
+        }
+    }
+
+    /// Wrap the input of the stage, to measure the time spent pulling packets from it
+    pub(crate) fn time_input<'a, T: 'a>(&'a self, mut input: DynIter<'a, T>) -> DynIter<'a, T> {
+        let upstream = &self.upstream;
+        std::iter::from_fn(move || {
+            let start = Instant::now();
+            let item = input.next();
+            upstream.set(upstream.get().saturating_add(elapsed_nanos(start)));
+            item
+        })
+        .into_dyn_iter()
+    }
+
+    /// Wrap the output of the stage, to record the time taken to produce each output packet,
+    /// minus the time spent pulling packets from the input wrapped with [`Self::time_input`]
+    pub(crate) fn time_output<'a, T: 'a>(&'a self, mut output: DynIter<'a, T>) -> DynIter<'a, T> {
+        let upstream = &self.upstream;
+        std::iter::from_fn(move || {
+            upstream.set(0);
+            let start = Instant::now();
+            let item = output.next()?;
+            let spent = elapsed_nanos(start).saturating_sub(upstream.get());
+            self.histogram.record(spent);
+            Some(item)
+        })
+        .into_dyn_iter()
+    }
+}
+
+/// The latencies of a stage, merged over all the pipeline instances
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    counts: Vec<u64>,
+    sum: u64,
+}
+
+impl Default for LatencySnapshot {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; BUCKETS],
+            sum: 0,
+        }
+    }
+}
+
+impl LatencySnapshot {
+    /// Number of latencies recorded
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Sum of the latencies recorded, in nanoseconds
+    #[must_use]
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    /// Iterate over the non-empty buckets, as the value representing the bucket, in
+    /// nanoseconds, and the number of latencies in it, by increasing value
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(index, count)| (bucket_value(index), *count))
+    }
+
+    /// Get the latency, in nanoseconds, below which are the fraction `quantile` of the latencies
+    /// recorded (e.g. 0.99 for the 99th percentile), or 0 if none was recorded
+    #[must_use]
+    pub fn quantile(&self, quantile: f64) -> u64 {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        #[allow(clippy::cast_precision_loss)]
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count() as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (value, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return value;
+            }
+        }
+        0
+    }
+
+    /// Get the latencies recorded since `earlier`, an older snapshot of the same histograms
+    #[must_use]
+    pub fn since(&self, earlier: &LatencySnapshot) -> LatencySnapshot {
+        LatencySnapshot {
+            counts: self
+                .counts
+                .iter()
+                .zip(earlier.counts.iter())
+                .map(|(count, earlier)| count.saturating_sub(*earlier))
+                .collect(),
+            sum: self.sum.saturating_sub(earlier.sum),
+        }
+    }
+}
+
+/// The latency histograms of the stages of all the instances of a pipeline, by stage name. This
+/// is shared by the pipeline instances and the readers of the histograms (e.g. metrics).
+#[derive(Debug, Default)]
+pub struct PipelineLatencies {
+    stages: Mutex<Vec<(String, Arc<LatencyHistogram>)>>,
+}
+
+impl PipelineLatencies {
+    /// Create an empty set of histograms
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new histogram for a stage of a pipeline instance
+    pub(crate) fn register(&self, name: &str) -> Arc<LatencyHistogram> {
+        let histogram = Arc::new(LatencyHistogram::default());
+        if let Ok(mut stages) = self.stages.lock() {
+            stages.push((name.to_string(), histogram.clone()));
+        }
+        histogram
+    }
+
+    /// Get the latencies of each stage, in the order of the stages in the pipeline
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<(String, LatencySnapshot)> {
+        let mut snapshot: Vec<(String, LatencySnapshot)> = Vec::new();
+        let Ok(stages) = self.stages.lock() else {
+            return snapshot;
+        };
+        for (name, histogram) in stages.iter() {
+            if let Some((_, latencies)) = snapshot.iter_mut().find(|(n, _)| n == name) {
+                histogram.add_to(latencies);
+            } else {
+                let mut latencies = LatencySnapshot::default();
+                histogram.add_to(&mut latencies);
+                snapshot.push((name.clone(), latencies));
+            }
+        }
+        snapshot
+    }
+
+    /// Get the latencies of the stage `name`, if there is such a stage
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<LatencySnapshot> {
+        self.snapshot()
+            .into_iter()
+            .find_map(|(n, latencies)| (n == name).then_some(latencies))
+    }
+}
+
+impl Display for LatencySnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>12} {:>10} {:>10} {:>10} {:>10}",
+            self.count(),
+            self.quantile(0.5),
+            self.quantile(0.9),
+            self.quantile(0.99),
+            self.quantile(1.0)
+        )
+    }
+}
+
+impl Display for PipelineLatencies {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>12} {:>10} {:>10} {:>10} {:>10}",
+            "stage", "packets", "p50(ns)", "p90(ns)", "p99(ns)", "max(ns)"
+        )?;
+        for (name, latencies) in self.snapshot() {
+            writeln!(f, "{name:<24} {latencies}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn latency_buckets() {
+        let mut last = 0;
+        for value in (0..100_000).chain([u64::MAX / 2, u64::MAX]) {
+            let index = bucket(value);
+            assert!(index >= last && index < BUCKETS, "bad bucket for {value}");
+            last = index;
+            let (low, width) = bucket_range(index);
+            if index < BUCKETS - 1 {
+                assert!(low <= value && value < low + width, "{value} not in bucket");
+                // the relative error is bounded
+                assert!(width == 1 || width * SUB as u64 <= low);
+            }
+        }
+
+        let histogram = LatencyHistogram::default();
+        for value in 1..=1000 {
+            histogram.record(value);
+        }
+        let mut latencies = LatencySnapshot::default();
+        histogram.add_to(&mut latencies);
+        assert_eq!(latencies.count(), 1000);
+        assert_eq!(latencies.sum(), 500_500);
+        for (quantile, expected) in [(0.0, 1), (0.5, 500), (0.99, 990), (1.0, 1000)] {
+            let value = latencies.quantile(quantile);
+            assert!(
+                value.abs_diff(expected) <= expected / 16,
+                "{quantile}: {value}"
+            );
+        }
+
+        let earlier = latencies.clone();
+        histogram.record(10);
+        let mut latencies = LatencySnapshot::default();
+        histogram.add_to(&mut latencies);
+        let delta = latencies.since(&earlier);
+        assert_eq!(delta.buckets().collect::<Vec<_>>(), vec![(10, 1)]);
+        assert_eq!(delta.sum(), 10);
+    }
+}
@@ -0,0 +1,90 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+use std::fmt::Display;
+
+/// Description of a stage of a [`DynPipeline`]
+///
+/// [`DynPipeline`]: crate::DynPipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageInfo {
+    /// Position of the stage in the pipeline, from 0
+    pub position: usize,
+    /// Id of the stage
+    pub id: String,
+    /// Name of the stage, unique in the pipeline
+    pub name: String,
+    /// Name of the network function of the stage, see [`DynNetworkFunction::name`]
+    ///
+    /// [`DynNetworkFunction::name`]: crate::DynNetworkFunction::name
+    pub nf: &'static str,
+    /// Whether the packets of the stage are counted, see [`PipelineCounters`]
+    ///
+    /// [`PipelineCounters`]: crate::PipelineCounters
+    pub counted: bool,
+    /// Whether the latency of the stage is measured, see [`PipelineLatencies`]
+    ///
+    /// [`PipelineLatencies`]: crate::PipelineLatencies
+    pub timed: bool,
+}
+
+/// Description of the stages of a [`DynPipeline`], in order. Unlike the pipeline, this can be
+/// shared with other threads, e.g. to display the pipelines of the workers.
+///
+/// [`DynPipeline`]: crate::DynPipeline
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipelineLayout(pub(crate) Vec<StageInfo>);
+
+impl PipelineLayout {
+    /// Iterate over the stages, in order
+    pub fn stages(&self) -> impl Iterator<Item = &StageInfo> {
+        self.0.iter()
+    }
+
+    /// Get the stage named `name`, if any
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&StageInfo> {
+        self.0.iter().find(|stage| stage.name == name)
+    }
+
+    /// Tell the number of stages
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Tell if there are no stages
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Display for StageInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>3} {:<24} {:<24} {:<8} {:<8} {}",
+            self.position,
+            self.name,
+            self.nf,
+            if self.counted { "yes" } else { "no" },
+            if self.timed { "yes" } else { "no" },
+            self.id
+        )
+    }
+}
+
+impl Display for PipelineLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:>3} {:<24} {:<24} {:<8} {:<8} id",
+            "#", "stage", "function", "counted", "timed"
+        )?;
+        for stage in self.stages() {
+            writeln!(f, "{stage}")?;
+        }
+        Ok(())
+    }
+}
@@ -104,21 +104,36 @@
 //! example.
 //!
 
+mod branch;
+mod counters;
 mod dyn_nf;
+mod latency;
+mod layout;
 mod pipeline;
+mod punt;
 /// Sample network functions
 pub mod sample_nfs;
 mod static_nf;
+mod trace;
 
 #[cfg(test)]
 pub(crate) mod test_utils;
 
+pub use branch::Branch;
+pub use counters::{PipelineCounters, StageStats};
 #[allow(unused)]
 pub use dyn_nf::{DynNetworkFunction, nf_dyn};
+pub use latency::{LatencySnapshot, PipelineLatencies};
+pub use layout::{PipelineLayout, StageInfo};
 #[allow(unused)]
 pub use pipeline::{DynPipeline, StageId};
+pub use punt::{
+    Punt, PuntReason, PuntReceiver, PuntSender, PuntStats, Punted, PuntedPacket, RateLimit, punt,
+    punt_channel,
+};
 #[allow(unused)]
 pub use static_nf::{NetworkFunction, StaticChain};
+pub use trace::{MAX_PACKET_TRACES, PacketTrace, PacketTracer, TraceFilter, TraceHop, TraceId};
 
 #[cfg(test)]
 mod test {
@@ -3,18 +3,81 @@
 
 #![allow(clippy::missing_errors_doc)]
 
+use crate::counters::{CountedOutput, PipelineCounters, StageCounters};
 use crate::dyn_nf::DynNetworkFunctionImpl;
-use crate::{DynNetworkFunction, NetworkFunction, nf_dyn};
+use crate::latency::{PipelineLatencies, StageTimer};
+use crate::trace::PacketTracer;
+use crate::{DynNetworkFunction, NetworkFunction, PipelineLayout, StageInfo, nf_dyn};
 use dyn_iter::{DynIter, IntoDynIterator};
 use id::Id;
 use net::buffer::PacketBufferMut;
 use net::packet::Packet;
 use ordermap::OrderMap;
 use std::any::Any;
+use std::sync::Arc;
 
 /// A type that represents an Id for a stage or NF
 pub type StageId<Buf> = Id<Box<dyn DynNetworkFunction<Buf>>>;
 
+/// A stage of a [`DynPipeline`]
+struct Stage<Buf: PacketBufferMut> {
+    name: String,
+    nf: Box<dyn DynNetworkFunction<Buf>>,
+    counters: Option<Arc<StageCounters>>,
+    timer: Option<StageTimer>,
+}
+
+impl<Buf: PacketBufferMut> Stage<Buf> {
+    fn process_dyn<'a>(
+        &'a mut self,
+        input: DynIter<'a, Packet<Buf>>,
+        tracer: Option<&'a PacketTracer<Buf>>,
+    ) -> DynIter<'a, Packet<Buf>> {
+        let Stage {
+            name,
+            nf,
+            counters,
+            timer,
+        } = self;
+        let name = name.as_str();
+        let input = match tracer {
+            None => input,
+            Some(tracer) => input
+                .inspect(move |packet| tracer.enter(packet, name))
+                .into_dyn_iter(),
+        };
+        let counters = counters.as_deref();
+        let output = match timer.as_ref() {
+            None => Self::process_counted(nf, counters, input),
+            Some(timer) => {
+                let input = timer.time_input(input);
+                timer.time_output(Self::process_counted(nf, counters, input))
+            }
+        };
+        match tracer {
+            None => output,
+            Some(tracer) => output
+                .inspect(move |packet| tracer.leave(packet))
+                .into_dyn_iter(),
+        }
+    }
+
+    fn process_counted<'a>(
+        nf: &'a mut Box<dyn DynNetworkFunction<Buf>>,
+        counters: Option<&'a StageCounters>,
+        input: DynIter<'a, Packet<Buf>>,
+    ) -> DynIter<'a, Packet<Buf>> {
+        match counters {
+            None => nf.process_dyn(input),
+            Some(counters) => {
+                let input = input.inspect(move |packet| counters.count_rx(packet));
+                let output = nf.process_dyn(input.into_dyn_iter());
+                CountedOutput::new(output, counters).into_dyn_iter()
+            }
+        }
+    }
+}
+
 /// A dynamic pipeline that can be updated at runtime.
 ///
 /// This struct is used to create a dynamic pipeline that can be updated at runtime.
@@ -24,7 +87,10 @@ pub type StageId<Buf> = Id<Box<dyn DynNetworkFunction<Buf>>>;
 /// [`DynNetworkFunction`]
 #[derive(Default)]
 pub struct DynPipeline<Buf: PacketBufferMut> {
-    nfs: OrderMap<StageId<Buf>, Box<dyn DynNetworkFunction<Buf>>>,
+    nfs: OrderMap<StageId<Buf>, Stage<Buf>>,
+    counters: Option<Arc<PipelineCounters>>,
+    latencies: Option<Arc<PipelineLatencies>>,
+    tracer: Option<Arc<PacketTracer<Buf>>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -39,7 +105,62 @@ impl<Buf: PacketBufferMut> DynPipeline<Buf> {
     pub fn new() -> Self {
         Self {
             nfs: OrderMap::new(),
+            counters: None,
+            latencies: None,
+            tracer: None,
+        }
+    }
+
+    /// Account the packets entering and leaving each stage of the pipeline in `counters`, under
+    /// the name of the stage. Stages are named after the type of their network function, with a
+    /// suffix if several stages have the same type (e.g. `IpForwarder`, `IpForwarder-2`).
+    ///
+    /// The pipelines built the same way (e.g. one per worker) can share the same `counters`,
+    /// which then sum the packets of all of them.
+    #[must_use]
+    pub fn with_counters(mut self, counters: Arc<PipelineCounters>) -> Self {
+        for stage in self.nfs.values_mut() {
+            stage.counters = Some(counters.register(&stage.name));
+        }
+        self.counters = Some(counters);
+        self
+    }
+
+    /// Record the latency of each stage of the pipeline in `latencies`, under the name of the
+    /// stage: the time that the stage takes to produce each of its output packets, excluding the
+    /// time spent in the previous stages. See [`PipelineLatencies`].
+    ///
+    /// As for [`DynPipeline::with_counters`], the pipelines built the same way can share the
+    /// same `latencies`.
+    #[must_use]
+    pub fn with_latencies(mut self, latencies: Arc<PipelineLatencies>) -> Self {
+        for stage in self.nfs.values_mut() {
+            stage.timer = Some(StageTimer::new(latencies.register(&stage.name)));
+        }
+        self.latencies = Some(latencies);
+        self
+    }
+
+    /// Trace the packets that `tracer` selects through the stages of the pipeline, once the
+    /// tracer is started. See [`PacketTracer`].
+    ///
+    /// The pipelines built the same way can share the same `tracer`.
+    #[must_use]
+    pub fn with_tracer(mut self, tracer: Arc<PacketTracer<Buf>>) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Get a name for a new stage, from `base`, not used by any other stage of the pipeline
+    fn stage_name(&self, base: &str) -> String {
+        let taken = |name: &str| self.nfs.values().any(|stage| stage.name == name);
+        let mut name = base.to_string();
+        let mut n = 1;
+        while taken(&name) {
+            n += 1;
+            name = format!("{base}-{n}");
         }
+        name
     }
 
     /// Add a static network function to the pipeline.
@@ -51,6 +172,20 @@ impl<Buf: PacketBufferMut> DynPipeline<Buf> {
         self.add_stage_dyn(nf_dyn(nf))
     }
 
+    /// Add a static network function to the pipeline, with a specific stage name.
+    ///
+    /// Stage names are unique in a pipeline: if the name is taken, a suffix is added to it (e.g.
+    /// `ingress-2`). Stages added without a name are named after their network function.
+    #[must_use]
+    pub fn add_named_stage<NF: NetworkFunction<Buf> + 'static>(
+        mut self,
+        name: &str,
+        nf: NF,
+    ) -> Self {
+        let _ = self.internal_add_stage_dyn_with_id(StageId::<Buf>::new(), Some(name), nf_dyn(nf));
+        self
+    }
+
     /// Add a static network function to the pipeline using a specific stage id.
     ///
     /// This method takes a [`NetworkFunction`] and adds it to the pipeline.
@@ -75,7 +210,16 @@ impl<Buf: PacketBufferMut> DynPipeline<Buf> {
     #[allow(unused)]
     #[must_use]
     pub fn add_stage_dyn(mut self, nf: Box<dyn DynNetworkFunction<Buf>>) -> Self {
-        self.internal_add_stage_dyn_with_id(StageId::<Buf>::new(), nf);
+        self.internal_add_stage_dyn_with_id(StageId::<Buf>::new(), None, nf);
+        self
+    }
+
+    /// Add a dynamic network function to the pipeline, with a specific stage name.
+    ///
+    /// See [`DynPipeline::add_named_stage`] for the naming of the stages.
+    #[must_use]
+    pub fn add_named_stage_dyn(mut self, name: &str, nf: Box<dyn DynNetworkFunction<Buf>>) -> Self {
+        let _ = self.internal_add_stage_dyn_with_id(StageId::<Buf>::new(), Some(name), nf);
         self
     }
 
@@ -108,12 +252,13 @@ impl<Buf: PacketBufferMut> DynPipeline<Buf> {
         id: StageId<Buf>,
         nf: Box<dyn DynNetworkFunction<Buf>>,
     ) -> Result<&mut Self, PipelineError> {
-        self.internal_add_stage_dyn_with_id(id, nf)
+        self.internal_add_stage_dyn_with_id(id, None, nf)
     }
 
     fn internal_add_stage_dyn_with_id(
         &mut self,
         id: StageId<Buf>,
+        name: Option<&str>,
         nf: Box<dyn DynNetworkFunction<Buf>>,
     ) -> Result<&mut Self, PipelineError> {
         // FIXME(mvachhar): There seems to be no method to insert and error if the key already exists.
@@ -125,7 +270,22 @@ impl<Buf: PacketBufferMut> DynPipeline<Buf> {
         if self.nfs.get(&id).is_some() {
             Err(PipelineError::DuplicateStageId(id.to_string()))
         } else {
-            self.nfs.insert(id, nf);
+            let name = self.stage_name(name.unwrap_or(nf.name()));
+            let counters = self
+                .counters
+                .as_ref()
+                .map(|counters| counters.register(&name));
+            let timer = self
+                .latencies
+                .as_ref()
+                .map(|latencies| StageTimer::new(latencies.register(&name)));
+            let stage = Stage {
+                name,
+                nf,
+                counters,
+                timer,
+            };
+            self.nfs.insert(id, stage);
             Ok(self)
         }
     }
@@ -160,15 +320,73 @@ impl<Buf: PacketBufferMut> DynPipeline<Buf> {
     pub fn get_stage_dyn_by_id<T: DynNetworkFunction<Buf>>(&self, id: &StageId<Buf>) -> Option<&T> {
         self.nfs
             .get(id)
-            .and_then(|nf| (&**nf as &dyn Any).downcast_ref::<T>())
+            .and_then(|stage| (&*stage.nf as &dyn Any).downcast_ref::<T>())
+    }
+
+    fn stage_info(position: usize, id: &StageId<Buf>, stage: &Stage<Buf>) -> StageInfo {
+        StageInfo {
+            position,
+            id: id.to_string(),
+            name: stage.name.clone(),
+            nf: stage.nf.name(),
+            counted: stage.counters.is_some(),
+            timed: stage.timer.is_some(),
+        }
+    }
+
+    /// Iterate over the stages of the pipeline, in order
+    pub fn stages(&self) -> impl Iterator<Item = StageInfo> {
+        self.nfs
+            .iter()
+            .enumerate()
+            .map(|(position, (id, stage))| Self::stage_info(position, id, stage))
+    }
+
+    /// Get the description of a stage by stage id.
+    #[must_use]
+    pub fn get_stage_info(&self, id: &StageId<Buf>) -> Option<StageInfo> {
+        let (position, id, stage) = self.nfs.get_full(id)?;
+        Some(Self::stage_info(position, id, stage))
+    }
+
+    /// Get the id of a stage by stage name.
+    #[must_use]
+    pub fn get_stage_id(&self, name: &str) -> Option<StageId<Buf>> {
+        self.nfs
+            .iter()
+            .find_map(|(id, stage)| (stage.name == name).then_some(*id))
+    }
+
+    /// Get the description of all the stages of the pipeline, to share with other threads.
+    #[must_use]
+    pub fn layout(&self) -> PipelineLayout {
+        PipelineLayout(self.stages().collect())
     }
 }
 
 impl<Buf: PacketBufferMut> DynNetworkFunction<Buf> for DynPipeline<Buf> {
     fn process_dyn<'a>(&'a mut self, input: DynIter<'a, Packet<Buf>>) -> DynIter<'a, Packet<Buf>> {
-        self.nfs
-            .values_mut()
-            .fold(input, move |input, nf| nf.process_dyn(input))
+        let DynPipeline { nfs, tracer, .. } = self;
+        let Some(tracer) = tracer.as_deref().filter(|tracer| tracer.is_tracing()) else {
+            return nfs
+                .values_mut()
+                .fold(input, move |input, stage| stage.process_dyn(input, None))
+                .into_dyn_iter();
+        };
+        let input = input
+            .map(move |mut packet| {
+                tracer.select(&mut packet);
+                packet
+            })
+            .into_dyn_iter();
+        nfs.values_mut()
+            .fold(input, move |input, stage| {
+                stage.process_dyn(input, Some(tracer))
+            })
+            .map(move |mut packet| {
+                tracer.finish(&mut packet);
+                packet
+            })
             .into_dyn_iter()
     }
 }
@@ -190,10 +408,15 @@ mod test {
     use net::headers::{Net, TryEth, TryIp, TryIpv4};
 
     use crate::dyn_nf::DynNetworkFunctionImpl;
-    use crate::sample_nfs::DecrementTtl;
+    use crate::sample_nfs::{DecrementTtl, Passthrough};
     use crate::test_utils::DynStageGenerator;
-    use crate::{DynNetworkFunction, DynPipeline, NetworkFunction, StageId};
+    use crate::{
+        DynNetworkFunction, DynPipeline, NetworkFunction, PacketTracer, PipelineCounters,
+        PipelineLatencies, StageId, TraceHop,
+    };
     use net::packet::test_utils::build_test_ipv4_packet;
+    use net::packet::{DoneReason, Packet};
+    use std::sync::Arc;
 
     type TestStageId = StageId<TestBuffer>;
 
@@ -323,4 +546,217 @@ mod test {
             );
         assert!(stage.is_some());
     }
+
+    #[test]
+    fn stage_counters() {
+        let counters = Arc::new(PipelineCounters::new());
+        let build = || {
+            DynPipeline::<TestBuffer>::new()
+                .add_stage(Passthrough)
+                .with_counters(counters.clone())
+                .add_stage(DecrementTtl)
+                .add_stage(Passthrough)
+        };
+        let mut pipeline1 = build();
+        let mut pipeline2 = build();
+
+        // a packet with a TTL of 0 is removed by DecrementTtl
+        let packets = vec![
+            build_test_ipv4_packet(10).unwrap(),
+            build_test_ipv4_packet(0).unwrap(),
+        ];
+        assert_eq!(pipeline1.process(packets.into_iter()).count(), 1);
+
+        // a packet marked to be dropped is not dropped again by the next stages
+        let mut packet: Packet<TestBuffer> = build_test_ipv4_packet(10).unwrap();
+        packet.done(DoneReason::Filtered);
+        assert_eq!(pipeline2.process(std::iter::once(packet)).count(), 1);
+
+        let stages: Vec<_> = counters.snapshot().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(stages, ["Passthrough", "DecrementTtl", "Passthrough-2"]);
+        let stats = counters.get("DecrementTtl").unwrap();
+        assert_eq!(
+            (stats.packets_in, stats.packets_out, stats.drops),
+            (3, 2, 1)
+        );
+        let stats = counters.get("Passthrough-2").unwrap();
+        assert_eq!(
+            (stats.packets_in, stats.packets_out, stats.drops),
+            (2, 2, 0)
+        );
+        assert!(counters.get("InspectHeaders").is_none());
+    }
+
+    /// Network function removing the packets with a TTL of 0 and duplicating the others
+    struct DuplicateLive;
+
+    impl NetworkFunction<TestBuffer> for DuplicateLive {
+        fn process<'a, Input: Iterator<Item = Packet<TestBuffer>> + 'a>(
+            &'a mut self,
+            input: Input,
+        ) -> impl Iterator<Item = Packet<TestBuffer>> + 'a {
+            input
+                .filter(|packet| packet.try_ipv4().is_some_and(|ipv4| ipv4.ttl() > 0))
+                .flat_map(|packet| [packet.clone(), packet])
+        }
+    }
+
+    #[test]
+    fn stage_counters_with_injection() {
+        let counters = Arc::new(PipelineCounters::new());
+        let mut pipeline = DynPipeline::<TestBuffer>::new()
+            .add_stage(DuplicateLive)
+            .with_counters(counters.clone());
+
+        // the packets injected in a burst do not hide the drops of another one
+        let dropped = vec![
+            build_test_ipv4_packet(0).unwrap(),
+            build_test_ipv4_packet(0).unwrap(),
+        ];
+        assert_eq!(pipeline.process(dropped.into_iter()).count(), 0);
+        let duplicated = vec![
+            build_test_ipv4_packet(10).unwrap(),
+            build_test_ipv4_packet(10).unwrap(),
+        ];
+        assert_eq!(pipeline.process(duplicated.into_iter()).count(), 4);
+
+        let stats = counters.get("DuplicateLive").unwrap();
+        assert_eq!(
+            (stats.packets_in, stats.packets_out, stats.drops),
+            (4, 4, 2)
+        );
+    }
+
+    #[test]
+    fn packet_tracing() {
+        let tracer = Arc::new(PacketTracer::new());
+        let mut pipeline = DynPipeline::<TestBuffer>::new()
+            .add_stage(Passthrough)
+            .add_stage(DecrementTtl)
+            .with_tracer(tracer.clone());
+        let hop = |stage: &str, left| TraceHop {
+            stage: stage.to_string(),
+            left,
+            done: None,
+        };
+
+        // a packet with a TTL of 0 is removed by DecrementTtl
+        tracer.start(2, None);
+        let packets = vec![
+            build_test_ipv4_packet(10).unwrap(),
+            build_test_ipv4_packet(0).unwrap(),
+            build_test_ipv4_packet(20).unwrap(),
+        ];
+        let packets_out: Vec<_> = pipeline.process(packets.into_iter()).collect();
+        assert_eq!(packets_out.len(), 2);
+        assert!(
+            packets_out
+                .iter()
+                .all(|p| p.get_meta().annotations.is_empty())
+        );
+        assert!(!tracer.is_started());
+
+        let traces = tracer.traces();
+        assert_eq!(traces.len(), 2);
+        assert!(traces[0].finished);
+        assert_eq!(
+            traces[0].hops,
+            [hop("Passthrough", true), hop("DecrementTtl", true)]
+        );
+        assert!(!traces[1].finished);
+        assert_eq!(
+            traces[1].hops,
+            [hop("Passthrough", true), hop("DecrementTtl", false)]
+        );
+
+        // only the packets matching the filter are traced
+        tracer.stop();
+        tracer.clear();
+        tracer.start(
+            5,
+            Some(Box::new(|packet: &Packet<TestBuffer>| {
+                packet.try_ipv4().is_some_and(|ipv4| ipv4.ttl() == 20)
+            })),
+        );
+        let packets = vec![
+            build_test_ipv4_packet(10).unwrap(),
+            build_test_ipv4_packet(20).unwrap(),
+        ];
+        assert_eq!(pipeline.process(packets.into_iter()).count(), 2);
+        let traces = tracer.traces();
+        assert_eq!(traces.len(), 1);
+        assert!(traces[0].finished);
+        assert!(tracer.is_started());
+    }
+
+    #[test]
+    fn stage_latencies() {
+        let latencies = Arc::new(PipelineLatencies::new());
+        let mut pipeline = DynPipeline::<TestBuffer>::new()
+            .add_stage(Passthrough)
+            .with_latencies(latencies.clone())
+            .add_stage(DecrementTtl);
+        let timed: Vec<_> = pipeline.stages().map(|stage| stage.timed).collect();
+        assert_eq!(timed, [true, true]);
+
+        // a packet with a TTL of 0 is removed by DecrementTtl
+        let packets = vec![
+            build_test_ipv4_packet(10).unwrap(),
+            build_test_ipv4_packet(0).unwrap(),
+            build_test_ipv4_packet(20).unwrap(),
+        ];
+        assert_eq!(pipeline.process(packets.into_iter()).count(), 2);
+
+        // one latency is recorded per packet out of each stage
+        let stages: Vec<_> = latencies
+            .snapshot()
+            .into_iter()
+            .map(|(name, latencies)| (name, latencies.count()))
+            .collect();
+        assert_eq!(
+            stages,
+            [
+                ("Passthrough".to_string(), 3),
+                ("DecrementTtl".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn stage_introspection() {
+        let test_stage_id = TestStageId::new();
+        let mut pipeline = DynPipeline::<TestBuffer>::new()
+            .add_named_stage("ingress", Passthrough)
+            .add_stage(DecrementTtl)
+            .add_named_stage("ingress", Passthrough)
+            .with_counters(Arc::new(PipelineCounters::new()));
+        pipeline
+            .add_stage_with_id(test_stage_id, DecrementTtl)
+            .unwrap();
+
+        let stages: Vec<_> = pipeline
+            .stages()
+            .map(|stage| (stage.position, stage.name, stage.nf))
+            .collect();
+        assert_eq!(
+            stages,
+            [
+                (0, "ingress".to_string(), "Passthrough"),
+                (1, "DecrementTtl".to_string(), "DecrementTtl"),
+                (2, "ingress-2".to_string(), "Passthrough"),
+                (3, "DecrementTtl-2".to_string(), "DecrementTtl"),
+            ]
+        );
+
+        let info = pipeline.get_stage_info(&test_stage_id).unwrap();
+        assert_eq!(info.position, 3);
+        assert_eq!(info.id, test_stage_id.to_string());
+        assert!(info.counted);
+        assert_eq!(pipeline.get_stage_id("DecrementTtl-2"), Some(test_stage_id));
+        assert_eq!(pipeline.get_stage_id("egress"), None);
+
+        let layout = pipeline.layout();
+        assert_eq!(layout.len(), 4);
+        assert_eq!(layout.get("ingress-2").unwrap().position, 2);
+    }
 }
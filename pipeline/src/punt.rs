@@ -0,0 +1,352 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! The punt path: packets that a network function diverts to the control plane.
+//!
+//! Network functions mark the packets to punt with [`punt`], with a [`PuntReason`]. A [`Punt`]
+//! stage, later in the pipeline, takes them out of the pipeline and sends them over a punt
+//! channel (see [`punt_channel`]) to the control plane, which gets them from the
+//! [`PuntReceiver`]. The rate of punted packets is limited, so that the control plane can't be
+//! flooded by the dataplane, and so is the number of packets waiting in the channel: the
+//! packets over the limits are dropped.
+//!
+//! ```
+//! use dataplane_pipeline::{DynPipeline, NetworkFunction, Punt, PuntReason, RateLimit};
+//! use dataplane_pipeline::{punt, punt_channel};
+//! use net::buffer::TestBuffer;
+//! use net::packet::test_utils::build_test_ipv4_packet;
+//!
+//! let (sender, receiver) = punt_channel::<TestBuffer>(16, RateLimit::new(1000, 10));
+//! let mut pipeline = DynPipeline::new().add_stage(Punt::new("punt", sender));
+//!
+//! let mut packet = build_test_ipv4_packet(64).unwrap();
+//! punt(&mut packet, PuntReason::Local);
+//! assert_eq!(pipeline.process(std::iter::once(packet)).count(), 0);
+//! assert_eq!(receiver.try_recv().map(|punted| punted.reason), Some(PuntReason::Local));
+//! ```
+
+use crate::NetworkFunction;
+use net::buffer::PacketBufferMut;
+use net::packet::{Annotation, DoneReason, Packet};
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::trace;
+
+/// The reason why a packet is punted to the control plane
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PuntReason {
+    /// ARP request or reply
+    Arp,
+    /// IPv6 neighbor discovery message
+    NeighborDiscovery,
+    /// BFD control packet
+    Bfd,
+    /// LLDP frame
+    Lldp,
+    /// Packet addressed to the gateway itself
+    Local,
+    /// Packet for a destination that the dataplane does not know how to reach
+    UnknownDestination,
+}
+
+impl Display for PuntReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            PuntReason::Arp => "ARP",
+            PuntReason::NeighborDiscovery => "ND",
+            PuntReason::Bfd => "BFD",
+            PuntReason::Lldp => "LLDP",
+            PuntReason::Local => "local",
+            PuntReason::UnknownDestination => "unknown destination",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+/// Annotation of the packets to punt, with the reason why they are punted
+pub struct Punted;
+
+impl Annotation for Punted {
+    type Value = PuntReason;
+}
+
+/// Mark `packet` to be punted to the control plane for `reason`. The packet is marked as done
+/// ([`DoneReason::Local`]), so that the next stages leave it alone, until a [`Punt`] stage
+/// sends it over the punt channel.
+pub fn punt<Buf: PacketBufferMut>(packet: &mut Packet<Buf>, reason: PuntReason) {
+    packet.done(DoneReason::Local);
+    packet.get_meta_mut().annotations.insert::<Punted>(reason);
+}
+
+/// The limits of the rate of the punted packets, as a token bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Number of packets per second, on average
+    pub rate: u32,
+    /// Number of packets that can be punted in a row, above the average rate
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Create a rate limit of `rate` packets per second, with bursts of up to `burst` packets
+    #[must_use]
+    pub fn new(rate: u32, burst: u32) -> Self {
+        Self { rate, burst }
+    }
+}
+
+/// A token bucket, filled at the rate of a [`RateLimit`]
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.burst),
+            last: Instant::now(),
+        }
+    }
+
+    /// Take a token, if there is one
+    fn take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last);
+        self.last = now;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * f64::from(self.limit.rate))
+            .min(f64::from(self.limit.burst));
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// The counters of a punt channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PuntStats {
+    /// Number of packets sent to the control plane
+    pub punted: u64,
+    /// Number of packets dropped because they exceeded the rate limit
+    pub rate_limited: u64,
+    /// Number of packets dropped because the channel was full, or closed
+    pub queue_full: u64,
+}
+
+#[derive(Debug, Default)]
+struct PuntCounters {
+    punted: AtomicU64,
+    rate_limited: AtomicU64,
+    queue_full: AtomicU64,
+}
+
+/// A packet punted to the control plane
+#[derive(Debug)]
+pub struct PuntedPacket<Buf: PacketBufferMut> {
+    /// The reason why the packet was punted
+    pub reason: PuntReason,
+    /// The packet
+    pub packet: Packet<Buf>,
+}
+
+/// The sending side of a punt channel. This can be cloned, e.g. for the [`Punt`] stages of the
+/// pipelines of all the workers, which then share the rate limit.
+#[derive(Debug)]
+pub struct PuntSender<Buf: PacketBufferMut> {
+    channel: SyncSender<PuntedPacket<Buf>>,
+    bucket: Arc<Mutex<TokenBucket>>,
+    counters: Arc<PuntCounters>,
+}
+
+impl<Buf: PacketBufferMut> Clone for PuntSender<Buf> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            bucket: self.bucket.clone(),
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+impl<Buf: PacketBufferMut> PuntSender<Buf> {
+    /// Send `packet` to the control plane, unless this exceeds the rate limit or the channel is
+    /// full, in which case the packet is dropped. Tell if the packet was sent.
+    #[must_use]
+    pub fn send(&self, packet: Packet<Buf>, reason: PuntReason) -> bool {
+        let allowed = self
+            .bucket
+            .lock()
+            .is_ok_and(|mut bucket| bucket.take(Instant::now()));
+        if !allowed {
+            self.counters.rate_limited.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        match self.channel.try_send(PuntedPacket { reason, packet }) {
+            Ok(()) => {
+                self.counters.punted.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                self.counters.queue_full.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Get the counters of the punt channel
+    #[must_use]
+    pub fn stats(&self) -> PuntStats {
+        PuntStats {
+            punted: self.counters.punted.load(Ordering::Relaxed),
+            rate_limited: self.counters.rate_limited.load(Ordering::Relaxed),
+            queue_full: self.counters.queue_full.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The receiving side of a punt channel, for the control plane
+#[derive(Debug)]
+pub struct PuntReceiver<Buf: PacketBufferMut> {
+    channel: Receiver<PuntedPacket<Buf>>,
+}
+
+impl<Buf: PacketBufferMut> PuntReceiver<Buf> {
+    /// Get the next punted packet, if any, without waiting
+    #[must_use]
+    pub fn try_recv(&self) -> Option<PuntedPacket<Buf>> {
+        self.channel.try_recv().ok()
+    }
+
+    /// Wait for the next punted packet. Returns `None` once all the senders are gone.
+    #[must_use]
+    pub fn recv(&self) -> Option<PuntedPacket<Buf>> {
+        self.channel.recv().ok()
+    }
+}
+
+/// Create a punt channel holding up to `capacity` packets, with the rate of packets limited by
+/// `limit`
+#[must_use]
+pub fn punt_channel<Buf: PacketBufferMut>(
+    capacity: usize,
+    limit: RateLimit,
+) -> (PuntSender<Buf>, PuntReceiver<Buf>) {
+    let (sender, receiver) = sync_channel(capacity);
+    let sender = PuntSender {
+        channel: sender,
+        bucket: Arc::new(Mutex::new(TokenBucket::new(limit))),
+        counters: Arc::new(PuntCounters::default()),
+    };
+    (sender, PuntReceiver { channel: receiver })
+}
+
+/// Network function that takes the packets marked with [`punt`] out of the pipeline, to send
+/// them over a punt channel. Other packets pass through.
+pub struct Punt<Buf: PacketBufferMut> {
+    name: String,
+    sender: PuntSender<Buf>,
+}
+
+impl<Buf: PacketBufferMut> Punt<Buf> {
+    /// Create a [`Punt`] stage, sending the punted packets to `sender`
+    #[must_use]
+    pub fn new(name: &str, sender: PuntSender<Buf>) -> Self {
+        Self {
+            name: name.to_string(),
+            sender,
+        }
+    }
+}
+
+impl<Buf: PacketBufferMut> NetworkFunction<Buf> for Punt<Buf> {
+    fn process<'a, Input: Iterator<Item = Packet<Buf>> + 'a>(
+        &'a mut self,
+        input: Input,
+    ) -> impl Iterator<Item = Packet<Buf>> + 'a {
+        input.filter_map(move |mut packet| {
+            let Some(reason) = packet.get_meta_mut().annotations.remove::<Punted>() else {
+                return Some(packet);
+            };
+            if !self.sender.send(packet, reason) {
+                trace!("{}: dropped punted packet ({reason})", self.name);
+            }
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sample_nfs::Passthrough;
+    use crate::{DynPipeline, NetworkFunction};
+    use net::buffer::TestBuffer;
+    use net::packet::test_utils::build_test_ipv4_packet;
+    use std::time::Duration;
+
+    #[test]
+    fn token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::new(10, 2));
+        bucket.last = start;
+        assert!(bucket.take(start));
+        assert!(bucket.take(start));
+        assert!(!bucket.take(start));
+        // one token every 100ms, up to the burst
+        assert!(bucket.take(start + Duration::from_millis(100)));
+        assert!(!bucket.take(start + Duration::from_millis(150)));
+        let later = start + Duration::from_secs(10);
+        assert!(bucket.take(later));
+        assert!(bucket.take(later));
+        assert!(!bucket.take(later));
+    }
+
+    #[test]
+    fn punt_stage() {
+        let (sender, receiver) = punt_channel::<TestBuffer>(2, RateLimit::new(1, 3));
+        let mut pipeline = DynPipeline::new()
+            .add_stage(Passthrough)
+            .add_stage(Punt::new("punt", sender.clone()));
+
+        // 1 packet passes through, 4 are punted: 2 fill the channel, 1 more exceeds its
+        // capacity, and the last one exceeds the rate limit
+        let reasons = [None, Some(PuntReason::Arp), Some(PuntReason::Bfd)];
+        let reasons = reasons.into_iter().chain([Some(PuntReason::Local); 2]);
+        let packets = reasons.map(|reason| {
+            let mut packet = build_test_ipv4_packet(64).unwrap();
+            if let Some(reason) = reason {
+                punt(&mut packet, reason);
+            }
+            packet
+        });
+        let packets_out: Vec<_> = pipeline.process(packets).collect();
+        assert_eq!(packets_out.len(), 1);
+        assert!(!packets_out[0].is_done());
+
+        let punted: Vec<_> = std::iter::from_fn(|| receiver.try_recv())
+            .map(|punted| (punted.reason, punted.packet.get_done()))
+            .collect();
+        assert_eq!(
+            punted,
+            [
+                (PuntReason::Arp, Some(DoneReason::Local)),
+                (PuntReason::Bfd, Some(DoneReason::Local))
+            ]
+        );
+        assert_eq!(
+            sender.stats(),
+            PuntStats {
+                punted: 2,
+                rate_limited: 1,
+                queue_full: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Per-packet tracing through the stages of a pipeline.
+//!
+//! A [`DynPipeline`] built with [`DynPipeline::with_tracer`] can trace the packets selected by
+//! the filter of its [`PacketTracer`], once started: each selected packet gets a [`TraceId`]
+//! annotation, and the tracer records each stage it traverses, with the verdict of the stage
+//! on it. Traces are logged when the packets leave the pipeline, and the most recent ones are
+//! kept in the tracer, to be retrieved (e.g. over the CLI).
+//!
+//! This is a debugging aid: while traces are collected, every packet is checked for the
+//! annotation at each stage, and each stage traversed by a traced packet takes a lock.
+//!
+//! [`DynPipeline`]: crate::DynPipeline
+//! [`DynPipeline::with_tracer`]: crate::DynPipeline::with_tracer
+
+use arc_swap::ArcSwapOption;
+use net::buffer::PacketBufferMut;
+use net::packet::{Annotation, DoneReason, Packet};
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tracectl::{custom_target, tdebug};
+
+const PKT_TRACE_TARGET: &str = "pkt-trace";
+custom_target!(PKT_TRACE_TARGET, LevelFilter::DEBUG, &["pipeline"]);
+
+/// Maximum number of traces kept by a [`PacketTracer`]. Older traces are discarded.
+pub const MAX_PACKET_TRACES: usize = 64;
+
+/// Annotation of the packets traced by a [`PacketTracer`], with the id of their trace
+pub struct TraceId;
+
+impl Annotation for TraceId {
+    type Value = u64;
+}
+
+/// A filter selecting the packets to trace
+pub type TraceFilter<Buf> = Box<dyn Fn(&Packet<Buf>) -> bool + Send + Sync>;
+
+/// A stage traversed by a traced packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceHop {
+    /// Name of the stage
+    pub stage: String,
+    /// Whether the packet left the stage. It did not if the stage consumed it.
+    pub left: bool,
+    /// The verdict on the packet when it left the stage
+    pub done: Option<DoneReason>,
+}
+
+/// The trace of a packet through a pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketTrace {
+    /// Id of the trace, see [`TraceId`]
+    pub id: u64,
+    /// Dump of the packet, as it entered the pipeline
+    pub packet: String,
+    /// The stages traversed by the packet, in order
+    pub hops: Vec<TraceHop>,
+    /// Whether the packet left the pipeline
+    pub finished: bool,
+    /// The verdict on the packet when it left the pipeline
+    pub done: Option<DoneReason>,
+}
+
+/// Selects the packets to trace and collects their traces. This is shared by the instances of
+/// a pipeline (e.g. one per worker) and the readers of the traces.
+pub struct PacketTracer<Buf: PacketBufferMut> {
+    remaining: AtomicU64,   /* number of packets still to select */
+    in_flight: AtomicUsize, /* number of traced packets that did not leave the pipeline */
+    next_id: AtomicU64,
+    filter: ArcSwapOption<TraceFilter<Buf>>,
+    traces: Mutex<VecDeque<PacketTrace>>,
+}
+
+impl<Buf: PacketBufferMut> Default for PacketTracer<Buf> {
+    fn default() -> Self {
+        Self {
+            remaining: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+            next_id: AtomicU64::new(1),
+            filter: ArcSwapOption::empty(),
+            traces: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl<Buf: PacketBufferMut> PacketTracer<Buf> {
+    /// Create a tracer, not tracing any packet until started
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trace the next `count` packets matching `filter`, or any packets if there is no filter.
+    /// This replaces the filter and count of a previous start.
+    pub fn start(&self, count: u64, filter: Option<TraceFilter<Buf>>) {
+        self.filter.store(filter.map(Arc::new));
+        self.remaining.store(count, Ordering::Relaxed);
+    }
+
+    /// Stop selecting packets to trace. The traces of the packets in the pipelines, if any,
+    /// are not completed.
+    pub fn stop(&self) {
+        self.remaining.store(0, Ordering::Relaxed);
+        self.in_flight.store(0, Ordering::Relaxed);
+    }
+
+    /// Tell if the tracer still has packets to select
+    #[must_use]
+    pub fn is_started(&self) -> bool {
+        self.remaining.load(Ordering::Relaxed) > 0
+    }
+
+    /// Tell if the stages of the pipelines have to look for traced packets
+    pub(crate) fn is_tracing(&self) -> bool {
+        self.is_started() || self.in_flight.load(Ordering::Relaxed) > 0
+    }
+
+    /// Get the traces collected, oldest first
+    #[must_use]
+    pub fn traces(&self) -> Vec<PacketTrace> {
+        self.traces
+            .lock()
+            .map(|traces| traces.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Discard the traces collected
+    pub fn clear(&self) {
+        if let Ok(mut traces) = self.traces.lock() {
+            traces.clear();
+        }
+    }
+
+    fn update(&self, id: u64, update: impl FnOnce(&mut PacketTrace)) {
+        let Ok(mut traces) = self.traces.lock() else {
+            return;
+        };
+        if let Some(trace) = traces.iter_mut().rev().find(|trace| trace.id == id) {
+            update(trace);
+        }
+    }
+
+    /// Select `packet` to be traced if it matches the filter and there are packets left to select
+    pub(crate) fn select(&self, packet: &mut Packet<Buf>) {
+        if !self.is_started() {
+            return;
+        }
+        let filter = self.filter.load_full();
+        if filter.is_some_and(|filter| !(*filter)(packet)) {
+            return;
+        }
+        // another worker may have selected the last packet meanwhile
+        let selected = self
+            .remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        if selected.is_err() {
+            return;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let trace = PacketTrace {
+            id,
+            packet: packet.to_string(),
+            hops: Vec::new(),
+            finished: false,
+            done: packet.get_done(),
+        };
+        packet.get_meta_mut().annotations.insert::<TraceId>(id);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut traces) = self.traces.lock() {
+            if traces.len() == MAX_PACKET_TRACES {
+                traces.pop_front();
+            }
+            traces.push_back(trace);
+        }
+    }
+
+    /// Record that `packet`, if traced, enters `stage`
+    pub(crate) fn enter(&self, packet: &Packet<Buf>, stage: &str) {
+        if let Some(id) = packet.get_meta().annotations.get::<TraceId>() {
+            self.update(*id, |trace| {
+                trace.hops.push(TraceHop {
+                    stage: stage.to_string(),
+                    left: false,
+                    done: None,
+                });
+            });
+        }
+    }
+
+    /// Record that `packet`, if traced, leaves the last stage it entered
+    pub(crate) fn leave(&self, packet: &Packet<Buf>) {
+        if let Some(id) = packet.get_meta().annotations.get::<TraceId>() {
+            self.update(*id, |trace| {
+                if let Some(hop) = trace.hops.last_mut() {
+                    hop.left = true;
+                    hop.done = packet.get_done();
+                }
+            });
+        }
+    }
+
+    /// Complete the trace of `packet`, if traced, as it leaves the pipeline
+    pub(crate) fn finish(&self, packet: &mut Packet<Buf>) {
+        let Some(id) = packet.get_meta_mut().annotations.remove::<TraceId>() else {
+            return;
+        };
+        let _ = self
+            .in_flight
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        self.update(id, |trace| {
+            trace.finished = true;
+            trace.done = packet.get_done();
+            tdebug!(PKT_TRACE_TARGET, "{trace}");
+        });
+    }
+}
+
+impl Display for TraceHop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.left, self.done) {
+            (false, _) => write!(f, "{}: consumed", self.stage),
+            (true, None) => write!(f, "{}: passed", self.stage),
+            (true, Some(reason)) => write!(f, "{}: done ({reason:?})", self.stage),
+        }
+    }
+}
+
+impl Display for PacketTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "trace {}:", self.id)?;
+        writeln!(f, "{}", self.packet)?;
+        for (position, hop) in self.hops.iter().enumerate() {
+            writeln!(f, "  {position:>3} {hop}")?;
+        }
+        match (self.finished, self.done) {
+            (false, _) => writeln!(f, "  verdict: none, the packet did not leave the pipeline"),
+            (true, None) => writeln!(f, "  verdict: none"),
+            (true, Some(reason)) => writeln!(f, "  verdict: {reason:?}"),
+        }
+    }
+}
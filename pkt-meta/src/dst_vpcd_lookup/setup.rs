@@ -32,6 +32,12 @@ fn process_peering(
                 .dst_vpcds
                 .insert(*prefix, VpcDiscriminant::VNI(remote_vni));
         }
+        // Addresses exempted from NAT are reachable without translation
+        for exclusion in expose.nat_exclusions() {
+            table
+                .dst_vpcds
+                .insert(exclusion.prefix, VpcDiscriminant::VNI(remote_vni));
+        }
     });
     Ok(())
 }
@@ -141,7 +147,8 @@ mod tests {
             .as_range("5.5.0.0/17".into())
             .as_range("5.6.0.0/17".into())
             .not_as("5.6.0.0/24".into())
-            .not_as("5.6.8.0/24".into());
+            .not_as("5.6.8.0/24".into())
+            .no_nat("10.0.3.0/24".into(), None);
 
         let manifest2 = VpcManifest {
             name: "VPC-2".into(),
@@ -227,6 +234,26 @@ mod tests {
             None
         );
 
+        // Addresses exempted from NAT are reachable, other private addresses are not
+        assert_eq!(
+            vpcd_tables
+                .tables_by_discriminant
+                .get(&VpcDiscriminant::VNI(vni1))
+                .unwrap()
+                .dst_vpcds
+                .lookup("10.0.3.1".parse::<IpAddr>().unwrap()),
+            Some((Prefix::from("10.0.3.0/24"), &VpcDiscriminant::VNI(vni2)))
+        );
+        assert_eq!(
+            vpcd_tables
+                .tables_by_discriminant
+                .get(&VpcDiscriminant::VNI(vni1))
+                .unwrap()
+                .dst_vpcds
+                .lookup("10.0.4.1".parse::<IpAddr>().unwrap()),
+            None
+        );
+
         // Make sure dst VNI lookup for non-NAT stuff works
         assert_eq!(
             vpcd_tables
@@ -6,6 +6,7 @@ use std::net::IpAddr;
 use std::num::NonZero;
 
 use etherparse::{Icmpv4Type, Icmpv6Type};
+use flow_info::FlowDirection;
 use net::buffer::PacketBufferMut;
 use net::headers::{
     EmbeddedTransport, Transport, TryEmbeddedHeaders, TryEmbeddedTransport, TryHeaders, TryInnerIp,
@@ -360,6 +361,23 @@ impl IpProtoKey {
     }
 }
 
+impl IpProtoKey {
+    // Unlike `==`, which is symmetric on ports, tell if both keys have the same ports
+    // in the same order.
+    fn same_orientation(&self, other: &Self) -> bool {
+        match (self, other) {
+            (IpProtoKey::Tcp(a), IpProtoKey::Tcp(b)) => {
+                a.src_port == b.src_port && a.dst_port == b.dst_port
+            }
+            (IpProtoKey::Udp(a), IpProtoKey::Udp(b)) => {
+                a.src_port == b.src_port && a.dst_port == b.dst_port
+            }
+            (IpProtoKey::Icmp(a), IpProtoKey::Icmp(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
 impl SrcLeqDst for IpProtoKey {
     fn src_leq_dst(&self) -> bool {
         match self {
@@ -479,6 +497,29 @@ impl FlowKeyData {
         }
     }
 
+    /// Normalize the flow key data so that both directions of a connection yield the
+    /// same value.
+    ///
+    /// Returns the normalized data, along with the direction of `self` relative to it.
+    #[must_use]
+    pub fn normalize(&self) -> (Self, FlowDirection) {
+        if self.src_leq_dst() {
+            (*self, FlowDirection::Forward)
+        } else {
+            (self.reverse(), FlowDirection::Reverse)
+        }
+    }
+
+    /// Tell if `self` and `other` describe the same flow in the same direction.
+    #[must_use]
+    pub fn same_orientation(&self, other: &Self) -> bool {
+        self.src_vpcd == other.src_vpcd
+            && self.dst_vpcd == other.dst_vpcd
+            && self.src_ip == other.src_ip
+            && self.dst_ip == other.dst_ip
+            && self.proto_key_info.same_orientation(&other.proto_key_info)
+    }
+
     /// Creates a new flow key with src and dst swapped
     #[must_use]
     pub fn reverse(&self) -> Self {
@@ -577,6 +618,32 @@ impl FlowKey {
         ))
     }
 
+    /// Normalize the flow key so that, for bidirectional keys, both directions of a
+    /// connection yield the same value. Unidirectional keys are left untouched.
+    ///
+    /// Returns the normalized key, along with the direction of `self` relative to it.
+    #[must_use]
+    pub fn normalize(&self) -> (FlowKey, FlowDirection) {
+        match self {
+            FlowKey::Bidirectional(data) => {
+                let (data, direction) = data.normalize();
+                (FlowKey::Bidirectional(data), direction)
+            }
+            FlowKey::Unidirectional(_) => (*self, FlowDirection::Forward),
+        }
+    }
+
+    /// The direction of a packet with this key relative to a flow that was created with
+    /// key `origin`, assuming both keys match.
+    #[must_use]
+    pub fn direction_relative_to(&self, origin: &FlowKey) -> FlowDirection {
+        if self.data().same_orientation(origin.data()) {
+            FlowDirection::Forward
+        } else {
+            FlowDirection::Reverse
+        }
+    }
+
     // Creates the flow key with src and dst swapped
     #[must_use]
     pub fn reverse(&self) -> FlowKey {
@@ -654,8 +721,8 @@ fn flow_key_data_from_packet<Buf: PacketBufferMut>(packet: &Packet<Buf>) -> Opti
         }),
         Transport::Icmp4(icmp) => IpProtoKey::Icmp(IcmpProtoKey::new_icmp_v4(packet, icmp)),
         Transport::Icmp6(icmp) => IpProtoKey::Icmp(IcmpProtoKey::new_icmp_v6(packet, icmp)),
-        #[allow(unreachable_patterns)]
-        _ => return None,
+        // TODO: flow keys for SCTP
+        Transport::Sctp(_) => return None,
     };
 
     let src_vpcd = packet.meta.src_vpcd;
@@ -994,6 +1061,36 @@ mod tests {
         assert_eq!(flow_key_2, flow_key_2);
     }
 
+    #[test]
+    fn test_flow_key_normalize() {
+        bolero::check!()
+            .with_type::<FlowKeyData>()
+            .for_each(|data| {
+                // Keys that compare the same way in both directions have no canonical form
+                if data.src_leq_dst() && data.reverse().src_leq_dst() {
+                    return;
+                }
+                let forward = FlowKey::Bidirectional(*data);
+                let reverse = forward.reverse();
+                let (normalized_fwd, dir_fwd) = forward.normalize();
+                let (normalized_rev, dir_rev) = reverse.normalize();
+                assert!(
+                    normalized_fwd
+                        .data()
+                        .same_orientation(normalized_rev.data())
+                );
+                assert_eq!(dir_fwd, dir_rev.reverse());
+                assert_eq!(
+                    forward.direction_relative_to(&forward),
+                    FlowDirection::Forward
+                );
+                assert_eq!(
+                    reverse.direction_relative_to(&forward),
+                    FlowDirection::Reverse
+                );
+            });
+    }
+
     #[test]
     fn test_flow_key_reverse() {
         let flow_key = FlowKey::uni(
@@ -1193,6 +1290,7 @@ mod tests {
                     }
                     _ => Some(IpProtoKey::Icmp(IcmpProtoKey::Unsupported)),
                 },
+                Transport::Sctp(_) => None,
             };
             if let Some(proto) = proto {
                 let (flow_key, mut packet) = if bidi {
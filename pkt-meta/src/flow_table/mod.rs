@@ -5,6 +5,7 @@ mod display;
 pub mod flow_key;
 pub mod nf_expirations;
 pub mod nf_lookup;
+pub mod query;
 pub mod table;
 mod thread_local_pq;
 
@@ -18,6 +19,7 @@ pub use ::flow_info::atomic_instant::AtomicInstant;
 pub use ::flow_info::*;
 pub use nf_expirations::ExpirationsNF;
 pub use nf_lookup::LookupNF;
+pub use query::{FlowEntry, FlowFilter, FlowProto};
 
 use tracectl::trace_target;
 trace_target!("flow-table", LevelFilter::INFO, &["pipeline"]);
@@ -6,7 +6,7 @@
 use tracing::debug;
 
 use concurrency::sync::Arc;
-use flow_info::ExtractRef;
+use flow_info::{ExtractRef, FlowDirection};
 use net::buffer::PacketBufferMut;
 use net::packet::{Packet, VpcDiscriminant};
 use pipeline::NetworkFunction;
@@ -36,7 +36,17 @@ impl<Buf: PacketBufferMut> NetworkFunction<Buf> for LookupNF {
             let Some(flow_key) = flow_key else {
                 return packet.enforce();
             };
-            if let Some(flow_info) = self.flow_table.lookup(&flow_key) {
+            // Unidirectional flows take precedence, then look for a bidirectional flow
+            // matching either direction of the packet
+            let found = self
+                .flow_table
+                .lookup(&flow_key)
+                .map(|flow_info| (flow_info, FlowDirection::Forward))
+                .or_else(|| {
+                    self.flow_table
+                        .lookup_with_direction(&FlowKey::Bidirectional(*flow_key.data()))
+                });
+            if let Some((flow_info, direction)) = found {
                 debug!(
                     "LookupNF: Tagging packet with flow info for flow key {:?}",
                     flow_key
@@ -51,6 +61,7 @@ impl<Buf: PacketBufferMut> NetworkFunction<Buf> for LookupNF {
                         packet.meta.dst_vpcd = Some(*dst_vpcd);
                     }
                 }
+                flow_info.record_packet(direction, u64::from(packet.total_len()), self.flow_table.now());
                 packet.meta.flow_info = Some(flow_info);
             }
             packet.enforce()
@@ -104,7 +115,9 @@ mod test {
         // Ensure packet is tagged
         let mut output_iter = lookup_nf.process(std::iter::once(packet));
         let output = output_iter.next().unwrap();
-        assert!(output.meta.flow_info.is_some());
+        let flow_info = output.meta.flow_info.as_ref().unwrap();
+        assert_eq!(flow_info.packets(), 1);
+        assert_eq!(flow_info.bytes(), u64::from(output.total_len()));
         assert_eq!(output.meta.dst_vpcd, Some(dst_vpcd));
     }
 }
@@ -0,0 +1,423 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Read-only queries over the flow table.
+//!
+//! Queries take a snapshot of the matching entries shard by shard and release all the
+//! table locks before handing the results to the caller, so that a slow consumer (e.g. the CLI)
+//! never holds up the datapath.
+
+use serde::Serialize;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use concurrency::sync::Arc;
+use lpm::prefix::Prefix;
+use net::packet::VpcDiscriminant;
+
+use crate::flow_table::flow_key::IcmpProtoKey;
+use crate::flow_table::{FlowInfo, FlowKey, FlowStatus, FlowTable, IpProtoKey};
+
+/// The transport protocol of a flow, as used in flow queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FlowProto {
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+impl From<&IpProtoKey> for FlowProto {
+    fn from(key: &IpProtoKey) -> Self {
+        match key {
+            IpProtoKey::Tcp(_) => FlowProto::Tcp,
+            IpProtoKey::Udp(_) => FlowProto::Udp,
+            IpProtoKey::Icmp(_) => FlowProto::Icmp,
+        }
+    }
+}
+
+impl Display for FlowProto {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlowProto::Tcp => write!(f, "TCP"),
+            FlowProto::Udp => write!(f, "UDP"),
+            FlowProto::Icmp => write!(f, "ICMP"),
+        }
+    }
+}
+
+/// A filter to select flows from the flow table.
+///
+/// All the criteria that are set must match for a flow to be selected. A default filter
+/// matches every active flow.
+#[derive(Debug, Clone, Default)]
+pub struct FlowFilter {
+    /// Match flows whose source or destination VPC is this discriminant
+    pub vpcd: Option<VpcDiscriminant>,
+    /// Match flows whose source or destination address is covered by this prefix
+    pub prefix: Option<Prefix>,
+    /// Match flows of this protocol
+    pub proto: Option<FlowProto>,
+    /// Match flows that are at least this old
+    pub min_age: Option<Duration>,
+}
+
+impl FlowFilter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[must_use]
+    pub fn with_vpcd(mut self, vpcd: VpcDiscriminant) -> Self {
+        self.vpcd = Some(vpcd);
+        self
+    }
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: Prefix) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+    #[must_use]
+    pub fn with_proto(mut self, proto: FlowProto) -> Self {
+        self.proto = Some(proto);
+        self
+    }
+    #[must_use]
+    pub fn with_min_age(mut self, min_age: Duration) -> Self {
+        self.min_age = Some(min_age);
+        self
+    }
+
+    /// Tell if the flow with the given key and info matches this filter at time `now`.
+    /// Flows that are not active never match.
+    #[must_use]
+    pub fn matches(&self, flow_key: &FlowKey, flow_info: &FlowInfo, now: Instant) -> bool {
+        if flow_info.status() != FlowStatus::Active {
+            return false;
+        }
+        let data = flow_key.data();
+        if let Some(vpcd) = self.vpcd
+            && data.src_vpcd() != Some(vpcd)
+            && data.dst_vpcd() != Some(vpcd)
+        {
+            return false;
+        }
+        if let Some(prefix) = &self.prefix
+            && !prefix.covers_addr(data.src_ip())
+            && !prefix.covers_addr(data.dst_ip())
+        {
+            return false;
+        }
+        if let Some(proto) = self.proto
+            && FlowProto::from(data.proto_key_info()) != proto
+        {
+            return false;
+        }
+        if let Some(min_age) = self.min_age
+            && flow_info.age(now) < min_age
+        {
+            return false;
+        }
+        true
+    }
+}
+
+/// A serializable snapshot of a flow table entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlowEntry {
+    pub bidirectional: bool,
+    pub src_vpcd: Option<VpcDiscriminant>,
+    pub dst_vpcd: Option<VpcDiscriminant>,
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub proto: FlowProto,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+    pub icmp_id: Option<u16>,
+    pub age: Duration,
+    pub idle: Duration,
+    pub expires_in: Duration,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl FlowEntry {
+    /// Build a [`FlowEntry`] from a flow key and its info, with ages relative to `now`.
+    #[must_use]
+    pub fn new(flow_key: &FlowKey, flow_info: &FlowInfo, now: Instant) -> Self {
+        let data = flow_key.data();
+        let proto_key = data.proto_key_info();
+        let (src_port, dst_port, icmp_id) = match proto_key {
+            IpProtoKey::Tcp(tcp) => (
+                Some(tcp.src_port.as_u16()),
+                Some(tcp.dst_port.as_u16()),
+                None,
+            ),
+            IpProtoKey::Udp(udp) => (
+                Some(udp.src_port.as_u16()),
+                Some(udp.dst_port.as_u16()),
+                None,
+            ),
+            IpProtoKey::Icmp(IcmpProtoKey::QueryMsgData(id)) => (None, None, Some(*id)),
+            IpProtoKey::Icmp(_) => (None, None, None),
+        };
+        Self {
+            bidirectional: matches!(flow_key, FlowKey::Bidirectional(_)),
+            src_vpcd: data.src_vpcd(),
+            dst_vpcd: data.dst_vpcd(),
+            src_ip: *data.src_ip(),
+            dst_ip: *data.dst_ip(),
+            proto: FlowProto::from(proto_key),
+            src_port,
+            dst_port,
+            icmp_id,
+            age: flow_info.age(now),
+            idle: now.saturating_duration_since(flow_info.last_seen()),
+            expires_in: flow_info.expires_at().saturating_duration_since(now),
+            packets: flow_info.packets(),
+            bytes: flow_info.bytes(),
+        }
+    }
+}
+
+fn fmt_endpoint(ip: &IpAddr, port: Option<u16>) -> String {
+    match port {
+        Some(port) => format!("{ip}:{port}"),
+        None => format!("{ip}"),
+    }
+}
+
+impl Display for FlowEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let vpcd = |v: Option<VpcDiscriminant>| {
+            v.as_ref().map_or(String::new(), VpcDiscriminant::to_string)
+        };
+        write!(
+            f,
+            "{} VPCs({}->{}) [proto: {}] ({}, {})",
+            if self.bidirectional { "bidi" } else { "uni " },
+            vpcd(self.src_vpcd),
+            vpcd(self.dst_vpcd),
+            self.proto,
+            fmt_endpoint(&self.src_ip, self.src_port),
+            fmt_endpoint(&self.dst_ip, self.dst_port),
+        )?;
+        if let Some(id) = self.icmp_id {
+            write!(f, " id:{id}")?;
+        }
+        write!(
+            f,
+            " pkts: {} bytes: {} age: {}s idle: {}s expires in: {}s",
+            self.packets,
+            self.bytes,
+            self.age.as_secs(),
+            self.idle.as_secs(),
+            self.expires_in.as_secs()
+        )
+    }
+}
+
+impl FlowTable {
+    /// Collect the keys and infos of all the flows matching the filter.
+    ///
+    /// The table is walked one partition and one shard at a time and no lock is held
+    /// when this method returns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
+    #[must_use]
+    pub fn snapshot(&self, filter: &FlowFilter) -> Vec<(FlowKey, Arc<FlowInfo>)> {
+        let now = self.now();
+        let mut flows = Vec::new();
+        for partition in &self.partitions {
+            let table = partition.table.read();
+            flows.extend(table.iter().filter_map(|entry| {
+                let flow_info = entry.value().upgrade()?;
+                filter
+                    .matches(entry.key(), &flow_info, now)
+                    .then(|| (*entry.key(), flow_info))
+            }));
+        }
+        flows
+    }
+
+    /// Call `f` on every flow matching the filter.
+    ///
+    /// `f` is called without any table lock held, so it may safely access the table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
+    pub fn for_each_flow<F>(&self, filter: &FlowFilter, mut f: F)
+    where
+        F: FnMut(&FlowKey, &Arc<FlowInfo>),
+    {
+        for (flow_key, flow_info) in self.snapshot(filter) {
+            f(&flow_key, &flow_info);
+        }
+    }
+
+    /// Dump all the flows matching the filter as serializable [`FlowEntry`]s.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
+    #[must_use]
+    pub fn query(&self, filter: &FlowFilter) -> Vec<FlowEntry> {
+        let now = self.now();
+        self.snapshot(filter)
+            .iter()
+            .map(|(flow_key, flow_info)| FlowEntry::new(flow_key, flow_info, now))
+            .collect()
+    }
+
+    /// Number of active flows in the table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
+    #[must_use]
+    pub fn active_len(&self) -> usize {
+        self.partitions
+            .iter()
+            .map(|partition| {
+                let table = partition.table.read();
+                table
+                    .iter()
+                    .filter(|entry| {
+                        entry
+                            .value()
+                            .upgrade()
+                            .is_some_and(|info| info.status() == FlowStatus::Active)
+                    })
+                    .count()
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_table::{TcpProtoKey, UdpProtoKey};
+    use net::tcp::TcpPort;
+    use net::udp::UdpPort;
+    use net::vxlan::Vni;
+
+    fn vpcd(vni: u32) -> VpcDiscriminant {
+        VpcDiscriminant::VNI(Vni::new_checked(vni).unwrap())
+    }
+
+    fn tcp_key(src_vni: u32, src_ip: &str, dst_vni: u32, dst_ip: &str) -> FlowKey {
+        FlowKey::uni(
+            Some(vpcd(src_vni)),
+            src_ip.parse().unwrap(),
+            Some(vpcd(dst_vni)),
+            dst_ip.parse().unwrap(),
+            IpProtoKey::Tcp(TcpProtoKey {
+                src_port: TcpPort::new_checked(1025).unwrap(),
+                dst_port: TcpPort::new_checked(80).unwrap(),
+            }),
+        )
+    }
+
+    fn udp_key(src_vni: u32, src_ip: &str, dst_vni: u32, dst_ip: &str) -> FlowKey {
+        FlowKey::bidi(
+            Some(vpcd(src_vni)),
+            src_ip.parse().unwrap(),
+            Some(vpcd(dst_vni)),
+            dst_ip.parse().unwrap(),
+            IpProtoKey::Udp(UdpProtoKey {
+                src_port: UdpPort::new_checked(5353).unwrap(),
+                dst_port: UdpPort::new_checked(53).unwrap(),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_flow_table_query_filters() {
+        let flow_table = FlowTable::default();
+        let expires_at = Instant::now() + Duration::from_secs(60);
+        flow_table.insert(
+            tcp_key(1, "10.0.0.1", 2, "20.0.0.1"),
+            FlowInfo::new(expires_at),
+        );
+        flow_table.insert(
+            tcp_key(3, "10.0.1.1", 4, "30.0.0.1"),
+            FlowInfo::new(expires_at),
+        );
+        flow_table.insert(
+            udp_key(1, "10.0.2.1", 4, "40.0.0.1"),
+            FlowInfo::new(expires_at),
+        );
+
+        assert_eq!(flow_table.query(&FlowFilter::new()).len(), 3);
+        assert_eq!(flow_table.active_len(), 3);
+
+        let by_vpc = flow_table.query(&FlowFilter::new().with_vpcd(vpcd(4)));
+        assert_eq!(by_vpc.len(), 2);
+
+        let by_proto = flow_table.query(&FlowFilter::new().with_proto(FlowProto::Udp));
+        assert_eq!(by_proto.len(), 1);
+        assert!(by_proto[0].bidirectional);
+        assert_eq!(by_proto[0].dst_port, Some(53));
+
+        let prefix = Prefix::expect_from(("10.0.0.0", 24));
+        let by_prefix = flow_table.query(&FlowFilter::new().with_prefix(prefix));
+        assert_eq!(by_prefix.len(), 1);
+        assert_eq!(by_prefix[0].src_ip, "10.0.0.1".parse::<IpAddr>().unwrap());
+
+        let combined = FlowFilter::new()
+            .with_vpcd(vpcd(1))
+            .with_proto(FlowProto::Tcp);
+        assert_eq!(flow_table.query(&combined).len(), 1);
+
+        let too_young = FlowFilter::new().with_min_age(Duration::from_secs(3600));
+        assert!(flow_table.query(&too_young).is_empty());
+    }
+
+    #[test]
+    fn test_flow_table_query_skips_expired() {
+        let flow_table = FlowTable::default();
+        let flow_key = tcp_key(1, "10.0.0.1", 2, "20.0.0.1");
+        flow_table.insert(flow_key, FlowInfo::new(Instant::now()));
+        assert_eq!(flow_table.query(&FlowFilter::new()).len(), 1);
+
+        flow_table.reap_all_expired();
+        assert!(flow_table.query(&FlowFilter::new()).is_empty());
+        assert_eq!(flow_table.active_len(), 0);
+    }
+
+    #[test]
+    fn test_flow_table_for_each_can_access_table() {
+        let flow_table = FlowTable::default();
+        let flow_key = tcp_key(1, "10.0.0.1", 2, "20.0.0.1");
+        flow_table.insert(
+            flow_key,
+            FlowInfo::new(Instant::now() + Duration::from_secs(60)),
+        );
+        // removing from within the callback must not deadlock
+        flow_table.for_each_flow(&FlowFilter::new(), |key, _| {
+            assert!(flow_table.remove(key).is_some());
+        });
+        assert!(flow_table.lookup(&flow_key).is_none());
+    }
+
+    #[test]
+    fn test_flow_entry_display() {
+        let flow_key = tcp_key(1, "10.0.0.1", 2, "20.0.0.1");
+        let now = Instant::now();
+        let flow_info = FlowInfo::new(now + Duration::from_secs(60));
+        let entry = FlowEntry::new(&flow_key, &flow_info, now);
+        let output = entry.to_string();
+        assert!(output.contains("10.0.0.1:1025"), "{output}");
+        assert!(output.contains("20.0.0.1:80"), "{output}");
+        assert!(output.contains("[proto: TCP]"), "{output}");
+    }
+}
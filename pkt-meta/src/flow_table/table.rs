@@ -5,29 +5,76 @@ use ahash::RandomState;
 use dashmap::DashMap;
 use std::borrow::Borrow;
 use std::fmt::Debug;
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
 use std::time::Instant;
 use tracing::{debug, error};
 
-use concurrency::sync::{Arc, RwLock, RwLockReadGuard, Weak};
+use concurrency::rcu::Rcu;
+use concurrency::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use concurrency::sync::{Arc, RwLock, Weak};
 
 use crate::flow_table::thread_local_pq::{PQAction, ThreadLocalPriorityQueue};
-use crate::flow_table::{FlowInfo, FlowKey, FlowStatus};
+use crate::flow_table::{Clock, FlowDirection, FlowInfo, FlowKey, FlowStatus, SystemClock};
 
 #[derive(Debug, thiserror::Error)]
 pub enum FlowTableError {
     #[error("Invalid number of shards: {0}. Must be a power of two.")]
     InvalidShardCount(usize),
+    #[error("Invalid number of partitions: {0}. Must be a power of two.")]
+    InvalidPartitionCount(usize),
 }
 
 type PriorityQueue = ThreadLocalPriorityQueue<FlowKey, Arc<FlowInfo>>;
-type Table = DashMap<FlowKey, Weak<FlowInfo>, RandomState>;
+pub(crate) type Table = DashMap<FlowKey, Weak<FlowInfo>, RandomState>;
+
+/// Default number of partitions of a flow table
+pub const DEFAULT_PARTITIONS: usize = 16;
+
+/// A partition of the flow table.
+///
+/// Each flow key is owned by exactly one partition, selected from the hash of the key.
+/// Operations on a flow only touch the partition that owns it, so that workers handling
+/// different flows do not contend on a single lock or counter.
+///
+/// Lookups reach the table of the partition through an RCU cell, without taking any partition
+/// lock: the only synchronization left on the read path is the shared lock of the shard of the
+/// table holding the key, which only waits for a writer on that same shard.
+#[derive(Debug)]
+pub(crate) struct Partition {
+    pub(crate) table: Rcu<Table>,
+    // Taken for reading by the operations adding or removing entries, and for writing when
+    // resharding the partition, so that no entry is added or removed while the table is copied
+    resize: RwLock<()>,
+    // Number of entries in this partition, including those of flows that are gone
+    // but not yet removed
+    entries: AtomicUsize,
+}
+
+impl Partition {
+    fn new(num_shards: usize) -> Self {
+        Self {
+            table: Rcu::new(Table::with_hasher_and_shard_amount(
+                hasher_state().clone(),
+                num_shards,
+            )),
+            resize: RwLock::new(()),
+            entries: AtomicUsize::new(0),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct FlowTable {
-    // TODO(mvachhar) move this to a cross beam sharded lock
-    pub(crate) table: RwLock<Table>,
+    pub(crate) partitions: Box<[Partition]>,
     pub(crate) priority_queue: PriorityQueue,
+    // Maximum number of entries in the table, usize::MAX if unbounded
+    capacity: AtomicUsize,
+    // Number of flows evicted to make room for new ones
+    evictions: AtomicU64,
+    // Number of stale entries purged to make room for new flows
+    purges: AtomicU64,
+    // Source of time for expiry decisions
+    clock: std::sync::Arc<dyn Clock>,
 }
 
 impl Default for FlowTable {
@@ -42,49 +89,196 @@ fn hasher_state() -> &'static RandomState {
     HASHER_STATE.get_or_init(|| RandomState::with_seeds(0, 0, 0, 0))
 }
 
+// Number of shards of each partition for a table with `num_shards` shards in total.
+// A `DashMap` needs at least two shards.
+fn shards_per_partition(num_partitions: usize, num_shards: usize) -> usize {
+    (num_shards / num_partitions).max(2)
+}
+
 impl FlowTable {
+    /// Create a flow table with `num_shards` shards in total, spread over
+    /// [`DEFAULT_PARTITIONS`] partitions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_shards` is not a power of two.
     #[must_use]
     pub fn new(num_shards: usize) -> Self {
+        assert!(
+            num_shards.is_power_of_two(),
+            "Invalid number of shards: {num_shards}"
+        );
+        let num_partitions = DEFAULT_PARTITIONS.min(num_shards);
+        Self::build(num_partitions, num_shards)
+    }
+
+    /// Create a flow table with `num_partitions` partitions and `num_shards` shards in total.
+    ///
+    /// A good choice for the number of partitions is the number of workers using the table,
+    /// rounded up to a power of two.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of partitions or shards is not a power of two.
+    pub fn with_partitions(
+        num_partitions: usize,
+        num_shards: usize,
+    ) -> Result<Self, FlowTableError> {
+        if !num_partitions.is_power_of_two() {
+            return Err(FlowTableError::InvalidPartitionCount(num_partitions));
+        }
+        if !num_shards.is_power_of_two() {
+            return Err(FlowTableError::InvalidShardCount(num_shards));
+        }
+        Ok(Self::build(num_partitions, num_shards))
+    }
+
+    fn build(num_partitions: usize, num_shards: usize) -> Self {
+        let shards = shards_per_partition(num_partitions, num_shards);
         Self {
-            table: RwLock::new(Table::with_hasher_and_shard_amount(
-                hasher_state().clone(),
-                num_shards,
-            )),
+            partitions: (0..num_partitions)
+                .map(|_| Partition::new(shards))
+                .collect(),
             priority_queue: PriorityQueue::new(),
+            capacity: AtomicUsize::new(usize::MAX),
+            evictions: AtomicU64::new(0),
+            purges: AtomicU64::new(0),
+            clock: std::sync::Arc::new(SystemClock),
+        }
+    }
+
+    /// Use `clock` as the source of time for expiring flows, instead of the system clock.
+    ///
+    /// This is mostly useful in tests, to advance time on demand.
+    #[must_use]
+    pub fn with_clock(mut self, clock: std::sync::Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The current time, according to the clock of the table.
+    ///
+    /// Flows inserted in the table should have their expiry time computed from this time.
+    #[must_use]
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Create a flow table that holds at most `capacity` entries.
+    ///
+    /// When the table is full, inserting a new flow evicts the flow that is closest to
+    /// expiring among the flows inserted by the current thread. Since expiry is extended on
+    /// activity, this approximates least-recently-used eviction.
+    #[must_use]
+    pub fn with_capacity(num_shards: usize, capacity: usize) -> Self {
+        let table = Self::new(num_shards);
+        table.set_capacity(Some(capacity));
+        table
+    }
+
+    /// Set the maximum number of entries in the table, or remove the bound with `None`.
+    ///
+    /// Lowering the capacity below the current number of entries does not evict anything
+    /// immediately: entries are evicted as new flows get inserted.
+    pub fn set_capacity(&self, capacity: Option<usize>) {
+        self.capacity
+            .store(capacity.unwrap_or(usize::MAX), Ordering::Relaxed);
+    }
+
+    /// The maximum number of entries in the table, if bounded.
+    #[must_use]
+    pub fn capacity(&self) -> Option<usize> {
+        match self.capacity.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            capacity => Some(capacity),
         }
     }
 
+    /// The number of entries in the table.
+    ///
+    /// This includes the entries of flows that expired but that have not been removed
+    /// from the table yet.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.partitions
+            .iter()
+            .map(|partition| partition.entries.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Tell if the table has no entries.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of flows evicted from the table because it was full.
+    #[must_use]
+    pub fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+
+    /// The number of entries of flows that were gone, purged from the table because it was full.
+    ///
+    /// These are not counted as evictions: the flows had already expired or been removed.
+    #[must_use]
+    pub fn purges(&self) -> u64 {
+        self.purges.load(Ordering::Relaxed)
+    }
+
+    /// The number of partitions of the table.
+    #[must_use]
+    pub fn num_partitions(&self) -> usize {
+        self.partitions.len()
+    }
+
+    fn partition<Q>(&self, flow_key: &Q) -> &Partition
+    where
+        Q: Hash + ?Sized,
+    {
+        let hash = hasher_state().hash_one(flow_key);
+        // The number of partitions is a power of two, keep the low bits of the hash
+        #[allow(clippy::cast_possible_truncation)]
+        let index = (hash as usize) & (self.partitions.len() - 1);
+        &self.partitions[index]
+    }
+
     /// Reshard the flow table into the given number of shards.
     ///
+    /// Partitions are resharded one at a time: while a partition is being resharded, flows
+    /// can still be looked up in it, but adding or removing flows waits for the end of the
+    /// resharding of the partition.
+    ///
     /// # Errors
     ///
     /// Returns an error if the number of shards is not a power of two.
     ///
     /// # Panics
     ///
-    /// Panics if this thread already holds the read lock on the table or
-    /// if the table lock is poisoned.
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
     pub fn reshard(&self, num_shards: usize) -> Result<(), FlowTableError> {
         if !num_shards.is_power_of_two() {
             return Err(FlowTableError::InvalidShardCount(num_shards));
         }
+        let shards = shards_per_partition(self.partitions.len(), num_shards);
         debug!(
-            "reshard: Resharding flow table from {} shards into {} shards",
-            self.table.read().unwrap().shards().len(),
-            num_shards
+            "reshard: Resharding {} flow table partitions into {shards} shards each",
+            self.partitions.len(),
         );
-        let mut locked_table = self.table.write().unwrap();
-        let new_table =
-            DashMap::with_hasher_and_shard_amount(locked_table.hasher().clone(), num_shards);
-        let old_table = std::mem::replace(&mut *locked_table, new_table);
-
-        // Move all entries from the old table to the new table using raw_api
-        for shard_lock in old_table.into_shards() {
-            let mut shard = shard_lock.write();
-            let drain_iter = shard.drain();
-            for (k, v) in drain_iter {
-                locked_table.insert(k, v.into_inner());
-            }
+        for partition in &self.partitions {
+            let _resize = partition.resize.write().unwrap();
+            let new_table = {
+                let table = partition.table.read();
+                let new_table =
+                    DashMap::with_hasher_and_shard_amount(table.hasher().clone(), shards);
+                for entry in table.iter() {
+                    new_table.insert(*entry.key(), entry.value().clone());
+                }
+                new_table
+            };
+            // Lookups move to the new table, the old one goes once they are all done with it
+            partition.table.update(new_table);
         }
         Ok(())
     }
@@ -97,8 +291,8 @@ impl FlowTable {
     ///
     /// # Panics
     ///
-    /// Panics if this thread already holds the read lock on the table or
-    /// if the table lock is poisoned.
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
     pub fn insert(&self, flow_key: FlowKey, flow_info: FlowInfo) -> Option<Arc<FlowInfo>> {
         debug!("insert: Inserting flow key {:?}", flow_key);
         let val = Arc::new(flow_info);
@@ -116,17 +310,31 @@ impl FlowTable {
     ///
     /// # Panics
     ///
-    /// Panics if this thread already holds the read lock on the table or
-    /// if the table lock is poisoned.
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
     pub fn reinsert(&self, flow_key: FlowKey, flow_info: &Arc<FlowInfo>) -> Option<Arc<FlowInfo>> {
         debug!("reinsert: Re-inserting flow key {:?}", flow_key);
         self.insert_common(flow_key, flow_info)
     }
 
     fn insert_common(&self, flow_key: FlowKey, val: &Arc<FlowInfo>) -> Option<Arc<FlowInfo>> {
-        let table = self.table.read().unwrap();
+        let partition = self.partition(&flow_key);
+        // Eviction may need to lock another partition, do it before locking ours. Counting the
+        // entries sums the counters of all the partitions, skip it for unbounded tables.
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        if capacity != usize::MAX
+            && self.len() >= capacity
+            && !partition.table.read().contains_key(&flow_key)
+        {
+            self.evict_one();
+        }
+        let _resize = partition.resize.read().unwrap();
+        let table = partition.table.read();
         let expires_at = val.expires_at();
         let result = table.insert(flow_key, Arc::downgrade(val));
+        if result.is_none() {
+            partition.entries.fetch_add(1, Ordering::Relaxed);
+        }
         self.priority_queue.push(flow_key, val.clone(), expires_at);
         let ret = match result {
             Some(w) => w.upgrade(),
@@ -144,54 +352,147 @@ impl FlowTable {
         Some(ret)
     }
 
+    /// Evict one flow to make room for a new one.
+    ///
+    /// The victim is the flow closest to expiring in the priority queue of the current thread.
+    /// Queued flows whose entry was already removed or replaced are skipped. If this thread has
+    /// no flows left, the entries of all flows that are gone are purged instead.
+    fn evict_one(&self) {
+        while let Some((flow_key, flow_info)) = self.priority_queue.pop_first() {
+            let partition = self.partition(&flow_key);
+            let _resize = partition.resize.read().unwrap();
+            let removed = partition.table.read().remove_if(&flow_key, |_, weak| {
+                weak.upgrade()
+                    .is_some_and(|current| Arc::ptr_eq(&current, &flow_info))
+            });
+            if removed.is_none() {
+                continue;
+            }
+            debug!("evict_one: Evicted flow key {flow_key:?}");
+            partition.entries.fetch_sub(1, Ordering::Relaxed);
+            Self::do_reap(flow_key, flow_info);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let mut purged = 0;
+        for partition in &self.partitions {
+            let _resize = partition.resize.read().unwrap();
+            let table = partition.table.read();
+            let before = table.len();
+            table.retain(|_, weak| {
+                weak.upgrade()
+                    .is_some_and(|flow_info| flow_info.status() != FlowStatus::Expired)
+            });
+            let after = table.len();
+            partition.entries.store(after, Ordering::Relaxed);
+            purged += before.saturating_sub(after);
+        }
+        debug!("evict_one: Purged {purged} stale entries");
+        self.purges
+            .fetch_add(u64::try_from(purged).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
     /// Lookup a flow in the table.
     ///
     /// # Panics
     ///
-    /// Panics if this thread already holds the read lock on the table or
-    /// if the table lock is poisoned.
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
     pub fn lookup<Q>(&self, flow_key: &Q) -> Option<Arc<FlowInfo>>
     where
         FlowKey: Borrow<Q>,
         Q: Hash + Eq + ?Sized + Debug,
     {
         debug!("lookup: Looking up flow key {:?}", flow_key);
-        let table = self.table.read().unwrap();
+        let partition = self.partition(flow_key);
+        let table = partition.table.read();
         let item = table.get(flow_key)?.upgrade();
         let Some(item) = item else {
             debug!(
                 "lookup: Removing flow key {:?}, found empty weak reference",
                 flow_key
             );
-            Self::remove_with_read_lock(&table, flow_key);
+            Self::remove_stale(partition, &table, flow_key);
             return None;
         };
         if item.status() == FlowStatus::Expired {
             debug!("lookup: Flow key {:?} is expired, removing", flow_key);
-            Self::remove_with_read_lock(&table, flow_key);
+            Self::remove_stale(partition, &table, flow_key);
             return None;
         }
         Some(item)
     }
 
+    /// Lookup a flow in the table and tell the direction of `flow_key` relative to the
+    /// key the flow was inserted with.
+    ///
+    /// This is mostly useful for bidirectional keys, where both directions of a connection
+    /// match the same flow: packets matching the inserted key are in the forward direction,
+    /// the others in the reverse direction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
+    pub fn lookup_with_direction(
+        &self,
+        flow_key: &FlowKey,
+    ) -> Option<(Arc<FlowInfo>, FlowDirection)> {
+        debug!("lookup_with_direction: Looking up flow key {flow_key:?}");
+        let partition = self.partition(flow_key);
+        let table = partition.table.read();
+        let (item, direction) = {
+            let entry = table.get(flow_key)?;
+            (
+                entry.value().upgrade(),
+                flow_key.direction_relative_to(entry.key()),
+            )
+        };
+        match item {
+            Some(item) if item.status() != FlowStatus::Expired => Some((item, direction)),
+            _ => {
+                debug!("lookup_with_direction: Flow key {flow_key:?} is gone, removing");
+                Self::remove_stale(partition, &table, flow_key);
+                None
+            }
+        }
+    }
+
     /// Remove a flow from the table.
     ///
     /// # Panics
     ///
-    /// Panics if this thread already holds the read lock on the table or
-    /// if the table lock is poisoned.
+    /// Panics if this thread already holds the read lock on a partition or
+    /// if a partition lock is poisoned.
     pub fn remove<Q>(&self, flow_key: &Q) -> Option<(FlowKey, Arc<FlowInfo>)>
     where
         FlowKey: Borrow<Q>,
         Q: Hash + Eq + ?Sized + Debug,
     {
         debug!("remove: Removing flow key {:?}", flow_key);
-        let table = self.table.read().unwrap();
-        Self::remove_with_read_lock(&table, flow_key)
+        let partition = self.partition(flow_key);
+        let _resize = partition.resize.read().unwrap();
+        let table = partition.table.read();
+        Self::remove_entry(partition, &table, flow_key)
+    }
+
+    // Remove the entry of a flow found gone during a lookup. Lookups never wait: if the
+    // partition is being resharded, the entry is left for later.
+    fn remove_stale<Q>(partition: &Partition, table: &Table, flow_key: &Q)
+    where
+        FlowKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + Debug,
+    {
+        if let Ok(_resize) = partition.resize.try_read() {
+            Self::remove_entry(partition, table, flow_key);
+        }
     }
 
-    fn remove_with_read_lock<Q>(
-        table: &RwLockReadGuard<DashMap<FlowKey, Weak<FlowInfo>, RandomState>>,
+    // Remove the entry of a flow. The caller must hold the resize lock of the partition.
+    fn remove_entry<Q>(
+        partition: &Partition,
+        table: &Table,
         flow_key: &Q,
     ) -> Option<(FlowKey, Arc<FlowInfo>)>
     where
@@ -200,6 +501,7 @@ impl FlowTable {
     {
         let result = table.remove(flow_key);
         let (k, w) = result?;
+        partition.entries.fetch_sub(1, Ordering::Relaxed);
         let old_val = w.upgrade()?;
         if old_val.status() == FlowStatus::Expired {
             return None;
@@ -255,18 +557,17 @@ impl FlowTable {
     /// Panics if any lock acquired by this method is poisoned.
     pub fn reap_expired(&self) -> usize {
         self.priority_queue
-            .reap_expired(Self::decide_expiry, Self::do_reap)
+            .reap_expired(&self.now(), Self::decide_expiry, Self::do_reap)
     }
 
     pub fn reap_all_expired(&self) -> usize {
-        self.priority_queue
-            .reap_all_expired(Self::decide_expiry, Self::do_reap)
+        self.reap_all_expired_with_time(&self.now())
     }
 
-    #[cfg(all(test, feature = "shuttle"))]
+    /// Reap the entries of all threads which expired at time `time`.
     pub fn reap_all_expired_with_time(&self, time: &Instant) -> usize {
         self.priority_queue
-            .reap_all_expired_with_time(time, Self::decide_expiry, Self::do_reap)
+            .reap_all_expired(time, Self::decide_expiry, Self::do_reap)
     }
 }
 
@@ -369,6 +670,193 @@ mod tests {
             );
         }
 
+        #[test]
+        fn test_flow_table_timeout_mock_clock() {
+            let clock = test_utils::clock::MockClock::new();
+            let flow_table = FlowTable::default().with_clock(clock.shared());
+            let flow_key = tcp_flow_key(0);
+            let now = flow_table.now();
+            flow_table.insert(
+                flow_key,
+                FlowInfo::new_at(now, now + Duration::from_secs(30)),
+            );
+
+            clock.advance(Duration::from_secs(29));
+            assert_eq!(flow_table.reap_expired(), 0);
+            assert!(flow_table.lookup(&flow_key).is_some());
+
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(flow_table.reap_expired(), 1);
+            assert!(flow_table.lookup(&flow_key).is_none());
+        }
+
+        fn tcp_flow_key(i: u16) -> FlowKey {
+            FlowKey::Unidirectional(FlowKeyData::new(
+                Some(VpcDiscriminant::VNI(Vni::new_checked(1).unwrap())),
+                "10.0.0.1".parse::<IpAddr>().unwrap(),
+                Some(VpcDiscriminant::VNI(Vni::new_checked(2).unwrap())),
+                "10.0.0.2".parse::<IpAddr>().unwrap(),
+                IpProtoKey::Tcp(TcpProtoKey {
+                    src_port: TcpPort::new_checked(1000 + i).unwrap(),
+                    dst_port: TcpPort::new_checked(80).unwrap(),
+                }),
+            ))
+        }
+
+        #[test]
+        fn test_flow_table_capacity_eviction() {
+            let now = Instant::now();
+            let flow_table = FlowTable::with_capacity(16, 4);
+            assert_eq!(flow_table.capacity(), Some(4));
+
+            // flows inserted first expire first, so they are evicted first
+            for i in 0..4 {
+                let expires_at = now + Duration::from_secs(10 + u64::from(i));
+                flow_table.insert(tcp_flow_key(i), FlowInfo::new(expires_at));
+            }
+            assert_eq!(flow_table.len(), 4);
+            assert_eq!(flow_table.evictions(), 0);
+
+            // re-inserting an existing key does not evict
+            let flow_info = flow_table.lookup(&tcp_flow_key(3)).unwrap();
+            flow_table.reinsert(tcp_flow_key(3), &flow_info);
+            assert_eq!(flow_table.evictions(), 0);
+
+            let expires_at = now + Duration::from_secs(30);
+            flow_table.insert(tcp_flow_key(4), FlowInfo::new(expires_at));
+            assert_eq!(flow_table.len(), 4);
+            assert_eq!(flow_table.evictions(), 1);
+            assert!(flow_table.lookup(&tcp_flow_key(0)).is_none());
+            for i in 1..=4 {
+                assert!(flow_table.lookup(&tcp_flow_key(i)).is_some());
+            }
+        }
+
+        #[test]
+        fn test_flow_table_eviction_keeps_held_flows_consistent() {
+            let now = Instant::now();
+            let flow_table = FlowTable::with_capacity(16, 1);
+            flow_table.insert(
+                tcp_flow_key(0),
+                FlowInfo::new(now + Duration::from_secs(10)),
+            );
+            let held = flow_table.lookup(&tcp_flow_key(0)).unwrap();
+            flow_table.insert(
+                tcp_flow_key(1),
+                FlowInfo::new(now + Duration::from_secs(10)),
+            );
+            // holders of an evicted flow can tell it is no longer valid
+            assert_eq!(held.status(), FlowStatus::Expired);
+            assert_eq!(flow_table.len(), 1);
+        }
+
+        #[test]
+        fn test_flow_table_eviction_skips_removed_flows() {
+            let now = Instant::now();
+            let flow_table = FlowTable::with_capacity(16, 2);
+            for i in 0..2 {
+                let expires_at = now + Duration::from_secs(10 + u64::from(i));
+                flow_table.insert(tcp_flow_key(i), FlowInfo::new(expires_at));
+            }
+            // the removed flow remains queued, first to expire
+            assert!(flow_table.remove(&tcp_flow_key(0)).is_some());
+            let expires_at = now + Duration::from_secs(30);
+            flow_table.insert(tcp_flow_key(2), FlowInfo::new(expires_at));
+            assert_eq!(flow_table.evictions(), 0);
+
+            // the next flow to expire that is still in the table is evicted instead
+            flow_table.insert(tcp_flow_key(3), FlowInfo::new(expires_at));
+            assert_eq!(flow_table.len(), 2);
+            assert_eq!(flow_table.evictions(), 1);
+            assert!(flow_table.lookup(&tcp_flow_key(1)).is_none());
+            assert!(flow_table.lookup(&tcp_flow_key(2)).is_some());
+            assert!(flow_table.lookup(&tcp_flow_key(3)).is_some());
+        }
+
+        #[test]
+        fn test_flow_table_eviction_purges_stale_entries() {
+            let flow_table = FlowTable::with_capacity(16, 2);
+            flow_table.insert(tcp_flow_key(0), FlowInfo::new(Instant::now()));
+            flow_table.insert(tcp_flow_key(1), FlowInfo::new(Instant::now()));
+            thread::sleep(Duration::from_millis(10));
+            flow_table.reap_all_expired();
+            // reaped flows leave stale entries behind until the table needs room
+            assert_eq!(flow_table.len(), 2);
+
+            let expires_at = Instant::now() + Duration::from_secs(10);
+            flow_table.insert(tcp_flow_key(2), FlowInfo::new(expires_at));
+            assert_eq!(flow_table.len(), 1);
+            assert_eq!(flow_table.purges(), 2);
+            assert_eq!(flow_table.evictions(), 0);
+            assert!(flow_table.lookup(&tcp_flow_key(2)).is_some());
+        }
+
+        #[test]
+        fn test_flow_table_lookup_with_direction() {
+            let flow_table = FlowTable::default();
+            let data = *tcp_flow_key(0).data();
+            let forward = FlowKey::Bidirectional(data);
+            let reverse = forward.reverse();
+            flow_table.insert(
+                forward,
+                FlowInfo::new(Instant::now() + Duration::from_secs(10)),
+            );
+
+            let (fwd_info, fwd_dir) = flow_table.lookup_with_direction(&forward).unwrap();
+            let (rev_info, rev_dir) = flow_table.lookup_with_direction(&reverse).unwrap();
+            assert!(Arc::ptr_eq(&fwd_info, &rev_info));
+            assert_eq!(fwd_dir, FlowDirection::Forward);
+            assert_eq!(rev_dir, FlowDirection::Reverse);
+
+            let now = Instant::now();
+            fwd_info.record_packet(fwd_dir, 100, now);
+            rev_info.record_packet(rev_dir, 1000, now);
+            assert_eq!(fwd_info.bytes_in(FlowDirection::Forward), 100);
+            assert_eq!(fwd_info.bytes_in(FlowDirection::Reverse), 1000);
+
+            // unidirectional keys do not match bidirectional flows
+            assert!(
+                flow_table
+                    .lookup_with_direction(&FlowKey::Unidirectional(data))
+                    .is_none()
+            );
+        }
+
+        #[test]
+        fn test_flow_table_partitions() {
+            assert!(matches!(
+                FlowTable::with_partitions(3, 64),
+                Err(FlowTableError::InvalidPartitionCount(3))
+            ));
+            assert!(matches!(
+                FlowTable::with_partitions(4, 63),
+                Err(FlowTableError::InvalidShardCount(63))
+            ));
+
+            let flow_table = FlowTable::with_partitions(4, 64).unwrap();
+            assert_eq!(flow_table.num_partitions(), 4);
+            let expires_at = Instant::now() + Duration::from_secs(10);
+            for i in 0..64 {
+                flow_table.insert(tcp_flow_key(i), FlowInfo::new(expires_at));
+            }
+            assert_eq!(flow_table.len(), 64);
+            // keys are spread over the partitions
+            assert!(
+                flow_table
+                    .partitions
+                    .iter()
+                    .all(|partition| partition.entries.load(Ordering::Relaxed) > 0)
+            );
+
+            flow_table.reshard(16).unwrap();
+            assert_eq!(flow_table.len(), 64);
+            for i in 0..64 {
+                assert!(flow_table.lookup(&tcp_flow_key(i)).is_some());
+                assert!(flow_table.remove(&tcp_flow_key(i)).is_some());
+            }
+            assert!(flow_table.is_empty());
+        }
+
         #[test]
         fn test_flow_table_expire_bolero() {
             let flow_table = FlowTable::default();
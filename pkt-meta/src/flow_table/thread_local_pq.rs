@@ -127,7 +127,26 @@ where
             .map(|expires_at| expires_at.0)
     }
 
-    /// Reap expired entries from the priority queue.
+    /// Remove and return the entry that expires first from the priority queue
+    /// of the current thread.
+    ///
+    /// # Thread Safety
+    ///
+    /// This method is thread-safe but should not be called if the current thread is
+    /// holding a lock on any element in the priority queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any lock acquired by this method is poisoned.
+    pub fn pop_first(&self) -> Option<(K, V)> {
+        let pq = self.get_pq_lock();
+        pq.write()
+            .unwrap()
+            .pop()
+            .map(|(entry, _)| (entry.key, entry.value))
+    }
+
+    /// Reap the entries which expired at time `now` from the priority queue.
     ///
     /// # Thread Safety
     ///
@@ -139,15 +158,17 @@ where
     /// Panics if any lock acquired by this method is poisoned.
     pub fn reap_expired(
         &self,
+        now: &Instant,
         on_expired: impl Fn(&Instant, &K, &V) -> PQAction,
         on_reaped: impl Fn(K, V),
     ) -> usize {
         let pql = self.get_pq_lock();
         let mut pq = pql.write().unwrap();
-        Self::reap_expired_locked(&mut pq, &on_expired, &on_reaped)
+        Self::reap_expired_locked_with_time(&mut pq, now, &on_expired, &on_reaped)
     }
 
-    /// Reap expired entries from all priority queues (regardless of current thread)
+    /// Reap the entries which expired at time `now` from all priority queues (regardless of
+    /// current thread)
     ///
     /// # Thread Safety
     ///
@@ -158,25 +179,6 @@ where
     ///
     /// Panics if any lock acquired by this method is poisoned.
     pub fn reap_all_expired(
-        &self,
-        on_expired: impl Fn(&Instant, &K, &V) -> PQAction,
-        on_reaped: impl Fn(K, V),
-    ) -> usize {
-        self.reap_all_expired_with_time_internal(&Instant::now(), &on_expired, &on_reaped)
-    }
-
-    #[allow(unused)] // This is unused for now if shuttle is not enabled
-    #[cfg(test)]
-    pub fn reap_all_expired_with_time(
-        &self,
-        now: &Instant,
-        on_expired: impl Fn(&Instant, &K, &V) -> PQAction,
-        on_reaped: impl Fn(K, V),
-    ) -> usize {
-        self.reap_all_expired_with_time_internal(now, &on_expired, &on_reaped)
-    }
-
-    fn reap_all_expired_with_time_internal(
         &self,
         now: &Instant,
         on_expired: impl Fn(&Instant, &K, &V) -> PQAction,
@@ -191,16 +193,6 @@ where
         count
     }
 
-    fn reap_expired_locked(
-        pq: &mut concurrency::sync::RwLockWriteGuard<
-            PriorityQueue<Entry<K, V>, Priority, RandomState>,
-        >,
-        on_expired: impl Fn(&Instant, &K, &V) -> PQAction,
-        on_reaped: impl Fn(K, V),
-    ) -> usize {
-        Self::reap_expired_locked_with_time(pq, &Instant::now(), on_expired, on_reaped)
-    }
-
     fn reap_expired_locked_with_time(
         pq: &mut concurrency::sync::RwLockWriteGuard<
             PriorityQueue<Entry<K, V>, Priority, RandomState>,
@@ -0,0 +1,463 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! A generic driver for reconciliation loops.
+//!
+//! Reconcilers built on the traits of this crate only move the system _closer_ to its required
+//! state, so they need to be called repeatedly: observe, compute what differs, reconcile, and start
+//! again until nothing is left to do.
+//! [`ReconcileLoop`] runs those passes, backs off exponentially (with jitter) when they fail,
+//! periodically re-checks converged systems to catch drift, and can be woken up early through a
+//! [`Trigger`] (e.g., when a netlink event reports a change).
+
+use concurrency::async_sync::Notify;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// The result of a single successful reconciliation pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassOutcome {
+    /// The observed state matched the requirement: nothing was done.
+    Converged,
+    /// Changes were made, another pass is needed to check whether they were sufficient.
+    Progress,
+}
+
+impl From<bool> for PassOutcome {
+    /// Convert the "converged" flag returned by many reconcilers.
+    fn from(converged: bool) -> Self {
+        if converged {
+            PassOutcome::Converged
+        } else {
+            PassOutcome::Progress
+        }
+    }
+}
+
+/// A single observe → diff → reconcile pass, as driven by a [`ReconcileLoop`].
+///
+/// This is typically implemented on a small struct holding the reconciler and the requirement,
+/// so that the pass can call [`Observe::observe`](crate::Observe::observe) and then
+/// [`Reconcile::reconcile`](crate::Reconcile::reconcile) with whatever glue those need.
+pub trait ReconcilePass {
+    /// The error reported by a failed pass.
+    type Error: Display;
+
+    /// Run one pass.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state could not be observed or reconciled. The loop will try again
+    /// after a backoff delay.
+    fn pass(&mut self) -> impl Future<Output = Result<PassOutcome, Self::Error>> + Send;
+}
+
+/// A [`ReconcilePass`] which can also tell what it would do, without doing it.
+///
+/// This is typically implemented by observing the system and calling
+/// [`Plan::plan`](crate::Plan::plan).
+pub trait PlanPass: ReconcilePass {
+    /// The changes which a pass would make.
+    type Plan;
+
+    /// Compute the changes which the next pass would make.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state could not be observed.
+    fn plan(&mut self) -> impl Future<Output = Result<Self::Plan, Self::Error>> + Send;
+}
+
+/// Exponential backoff parameters for failed passes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Delay after the first failure
+    pub min: Duration,
+    /// Upper bound of the delay, jitter included
+    pub max: Duration,
+    /// Growth factor of the delay for each consecutive failure
+    pub multiplier: u32,
+    /// Fraction (between 0 and 1) by which delays are randomly stretched or shrunk
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            min: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            multiplier: 2,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl Backoff {
+    /// The delay before the next pass, after `failures` consecutive failures (at least one).
+    #[must_use]
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = self.multiplier.saturating_pow(failures.saturating_sub(1));
+        let delay = self.min.saturating_mul(factor).min(self.max);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+        let scale = 1.0 + jitter * (2.0 * rand::random::<f64>() - 1.0);
+        delay.mul_f64(scale).min(self.max)
+    }
+}
+
+/// A handle to wake up a [`ReconcileLoop`] and have it run a pass immediately.
+///
+/// Triggers are coalesced: firing several times while a pass is running results in a single
+/// additional pass.
+#[derive(Debug, Clone, Default)]
+pub struct Trigger(Arc<Notify>);
+
+impl Trigger {
+    /// Request an immediate reconciliation pass.
+    pub fn fire(&self) {
+        self.0.notify_one();
+    }
+}
+
+/// Counters describing the activity of a [`ReconcileLoop`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoopStats {
+    /// Number of passes run
+    pub passes: u64,
+    /// Number of passes which found the system converged
+    pub converged: u64,
+    /// Number of failed passes, including runs of passes which did not converge
+    pub failures: u64,
+    /// Number of failures since the last successful pass
+    pub consecutive_failures: u32,
+    /// Number of passes started early because of a [`Trigger`]
+    pub triggered: u64,
+    /// The error reported by the last failure, if any
+    pub last_error: Option<String>,
+}
+
+/// The error returned by [`ReconcileLoop::converge`].
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+#[error("reconciliation gave up after {failures} consecutive failures, last error: {last_error}")]
+pub struct ConvergeError {
+    /// Number of consecutive failures
+    pub failures: u32,
+    /// The error reported by the last failure
+    pub last_error: String,
+}
+
+/// Drives a [`ReconcilePass`] until (and while) the system is converged.
+pub struct ReconcileLoop<P> {
+    pass: P,
+    backoff: Backoff,
+    resync: Duration,
+    max_progress_passes: u32,
+    max_failures: Option<u32>,
+    trigger: Trigger,
+    progress_passes: u32,
+    stats: LoopStats,
+}
+
+impl<P: ReconcilePass> ReconcileLoop<P> {
+    /// Create a loop driving `pass` with default settings.
+    #[must_use]
+    pub fn new(pass: P) -> Self {
+        Self {
+            pass,
+            backoff: Backoff::default(),
+            resync: Duration::from_secs(60),
+            max_progress_passes: 300,
+            max_failures: None,
+            trigger: Trigger::default(),
+            progress_passes: 0,
+            stats: LoopStats::default(),
+        }
+    }
+
+    /// Set the backoff parameters used after failures.
+    #[must_use]
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set the interval at which a converged system is checked again.
+    #[must_use]
+    pub fn with_resync(mut self, resync: Duration) -> Self {
+        self.resync = resync;
+        self
+    }
+
+    /// Set how many passes in a row may make progress before the run is counted as a failure.
+    /// This prevents a reconciler which never converges from spinning without delay.
+    #[must_use]
+    pub fn with_max_progress_passes(mut self, passes: u32) -> Self {
+        self.max_progress_passes = passes;
+        self
+    }
+
+    /// Set after how many consecutive failures [`converge`](Self::converge) gives up.
+    /// By default it never does.
+    #[must_use]
+    pub fn with_max_failures(mut self, failures: u32) -> Self {
+        self.max_failures = Some(failures);
+        self
+    }
+
+    /// Get a handle to request immediate passes.
+    #[must_use]
+    pub fn trigger(&self) -> Trigger {
+        self.trigger.clone()
+    }
+
+    /// Get the activity counters of the loop.
+    #[must_use]
+    pub fn stats(&self) -> &LoopStats {
+        &self.stats
+    }
+
+    /// Get the driven pass.
+    #[must_use]
+    pub fn pass(&self) -> &P {
+        &self.pass
+    }
+
+    /// Get the driven pass, mutably (e.g., to change its requirement).
+    pub fn pass_mut(&mut self) -> &mut P {
+        &mut self.pass
+    }
+
+    /// Consume the loop and return the driven pass.
+    pub fn into_pass(self) -> P {
+        self.pass
+    }
+
+    /// Compute the changes which the next pass would make, without running it (dry run).
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the pass if the state could not be observed.
+    pub async fn plan(&mut self) -> Result<P::Plan, P::Error>
+    where
+        P: PlanPass,
+    {
+        self.pass.plan().await
+    }
+
+    fn fail(&mut self, error: String) -> Duration {
+        self.progress_passes = 0;
+        self.stats.failures += 1;
+        self.stats.consecutive_failures = self.stats.consecutive_failures.saturating_add(1);
+        let delay = self.backoff.delay(self.stats.consecutive_failures);
+        warn!(
+            "reconciliation failed ({} in a row), retrying in {delay:?}: {error}",
+            self.stats.consecutive_failures
+        );
+        self.stats.last_error = Some(error);
+        delay
+    }
+
+    /// Run a single pass, and return its outcome (none on failure) and the delay before the next.
+    async fn step(&mut self) -> (Option<PassOutcome>, Duration) {
+        self.stats.passes += 1;
+        match self.pass.pass().await {
+            Ok(PassOutcome::Converged) => {
+                debug!("reconciliation converged");
+                self.progress_passes = 0;
+                self.stats.converged += 1;
+                self.stats.consecutive_failures = 0;
+                (Some(PassOutcome::Converged), self.resync)
+            }
+            Ok(PassOutcome::Progress) => {
+                self.progress_passes += 1;
+                if self.progress_passes >= self.max_progress_passes {
+                    let error = format!("not converged after {} passes", self.progress_passes);
+                    (None, self.fail(error))
+                } else {
+                    self.stats.consecutive_failures = 0;
+                    (Some(PassOutcome::Progress), Duration::ZERO)
+                }
+            }
+            Err(e) => (None, self.fail(e.to_string())),
+        }
+    }
+
+    /// Wait for `delay` to elapse or for the trigger to fire, whichever comes first.
+    async fn wait(&mut self, delay: Duration) {
+        if delay.is_zero() {
+            tokio::task::yield_now().await;
+            return;
+        }
+        tokio::select! {
+            () = self.trigger.0.notified() => self.stats.triggered += 1,
+            () = tokio::time::sleep(delay) => {},
+        }
+    }
+
+    /// Run passes until one finds the system converged.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ConvergeError`] once the number of consecutive failures reaches the limit set
+    /// with [`with_max_failures`](Self::with_max_failures).
+    pub async fn converge(&mut self) -> Result<(), ConvergeError> {
+        loop {
+            let (outcome, delay) = self.step().await;
+            if outcome == Some(PassOutcome::Converged) {
+                return Ok(());
+            }
+            if let Some(max) = self.max_failures
+                && self.stats.consecutive_failures >= max
+            {
+                return Err(ConvergeError {
+                    failures: self.stats.consecutive_failures,
+                    last_error: self.stats.last_error.clone().unwrap_or_default(),
+                });
+            }
+            self.wait(delay).await;
+        }
+    }
+
+    /// Keep the system converged until `shutdown` completes.
+    ///
+    /// Failures never stop the loop: they are counted in the [stats](Self::stats) and retried
+    /// after a backoff delay.
+    pub async fn run_until(&mut self, shutdown: impl Future<Output = ()>) {
+        let mut shutdown = std::pin::pin!(shutdown);
+        loop {
+            let (_, delay) = self.step().await;
+            tokio::select! {
+                () = &mut shutdown => return,
+                () = self.wait(delay) => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A pass which fails a given number of times, then needs a given number of passes to converge
+    struct Scripted {
+        failures: u32,
+        progress: u32,
+    }
+
+    impl ReconcilePass for Scripted {
+        type Error = String;
+        async fn pass(&mut self) -> Result<PassOutcome, String> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err("boom".to_string());
+            }
+            if self.progress > 0 {
+                self.progress -= 1;
+                return Ok(PassOutcome::Progress);
+            }
+            Ok(PassOutcome::Converged)
+        }
+    }
+
+    impl PlanPass for Scripted {
+        /// The number of changes left to make
+        type Plan = u32;
+        async fn plan(&mut self) -> Result<u32, String> {
+            if self.failures > 0 {
+                return Err("boom".to_string());
+            }
+            Ok(self.progress)
+        }
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff {
+            min: Duration::from_millis(10),
+            max: Duration::from_secs(1),
+            multiplier: 2,
+            jitter: 0.0,
+        };
+        assert_eq!(backoff.delay(1), Duration::from_millis(10));
+        assert_eq!(backoff.delay(3), Duration::from_millis(40));
+        assert_eq!(backoff.delay(100), Duration::from_secs(1));
+
+        let jittered = Backoff {
+            jitter: 0.5,
+            ..backoff
+        };
+        for _ in 0..100 {
+            let delay = jittered.delay(2);
+            assert!(delay >= Duration::from_millis(10) && delay <= Duration::from_millis(30));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_converge() {
+        let mut driver = ReconcileLoop::new(Scripted {
+            failures: 3,
+            progress: 2,
+        });
+        driver.converge().await.unwrap();
+        let stats = driver.stats();
+        assert_eq!(stats.passes, 6);
+        assert_eq!(stats.failures, 3);
+        assert_eq!(stats.consecutive_failures, 0);
+        assert_eq!(stats.converged, 1);
+        assert_eq!(stats.last_error.as_deref(), Some("boom"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_converge_gives_up() {
+        let mut driver = ReconcileLoop::new(Scripted {
+            failures: 0,
+            progress: u32::MAX,
+        })
+        .with_max_progress_passes(5)
+        .with_max_failures(2);
+        let err = driver.converge().await.unwrap_err();
+        assert_eq!(err.failures, 2);
+        assert_eq!(driver.stats().passes, 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_plan_is_dry() {
+        let mut driver = ReconcileLoop::new(Scripted {
+            failures: 0,
+            progress: 2,
+        });
+        assert_eq!(driver.plan().await, Ok(2));
+        assert_eq!(driver.plan().await, Ok(2));
+        assert_eq!(driver.stats().passes, 0);
+        driver.converge().await.unwrap();
+        assert_eq!(driver.plan().await, Ok(0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_trigger() {
+        let mut driver = ReconcileLoop::new(Scripted {
+            failures: 0,
+            progress: 0,
+        })
+        .with_resync(Duration::from_secs(3600));
+        let trigger = driver.trigger();
+        let shutdown = async {
+            for _ in 0..3 {
+                tokio::task::yield_now().await;
+                trigger.fire();
+                tokio::task::yield_now().await;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        };
+        driver.run_until(shutdown).await;
+        let stats = driver.stats();
+        assert_eq!(stats.triggered, 3);
+        assert_eq!(stats.passes, 4);
+        assert_eq!(stats.converged, 4);
+    }
+}
@@ -1,6 +1,14 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright Open Network Fabric Authors
 
+mod driver;
+mod plan;
+
+pub use driver::{
+    Backoff, ConvergeError, LoopStats, PassOutcome, PlanPass, ReconcileLoop, ReconcilePass, Trigger,
+};
+pub use plan::{Change, Plan};
+
 /// `Observe` is a trait that can be implemented for whatever struct is intended to collect or
 /// measure data present in an external system.
 ///
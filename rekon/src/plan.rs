@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Dry-run support: compute what a reconciliation would do without doing it.
+
+/// A change which reconciliation would make to an external resource.
+///
+/// `Req` is the requirement which a resource is created or updated from, and `Obs` is the
+/// observation of the resource which is updated or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<Req, Obs> {
+    /// The resource is required but was not observed: it would be created.
+    Create(Req),
+    /// The resource was observed but does not meet its requirement: it would be updated.
+    Update(Req, Obs),
+    /// The resource was observed but is not required: it would be removed.
+    Remove(Obs),
+}
+
+impl<Req, Obs> Change<Req, Obs> {
+    /// The requirement of a created or updated resource.
+    pub fn requirement(&self) -> Option<&Req> {
+        match self {
+            Change::Create(requirement) | Change::Update(requirement, _) => Some(requirement),
+            Change::Remove(_) => None,
+        }
+    }
+
+    /// The observation of an updated or removed resource.
+    pub fn observation(&self) -> Option<&Obs> {
+        match self {
+            Change::Update(_, observation) | Change::Remove(observation) => Some(observation),
+            Change::Create(_) => None,
+        }
+    }
+}
+
+/// `Plan` is the side-effect free counterpart of [`Reconcile`](crate::Reconcile): it computes
+/// which [`Create`](crate::Create), [`Update`](crate::Update) and [`Remove`](crate::Remove)
+/// operations reconciling an observation with a requirement would perform, without performing
+/// them.
+///
+/// The motivating use case is to let operators preview what applying a configuration would
+/// change on the host.
+pub trait Plan {
+    /// The data required to create the resource.
+    ///
+    /// This is a [GAT] parameterized over a lifetime `'a where Self: 'a`.
+    /// Thus, it is fair game to use simple references, or `Option<&'a Whatever>` or something more
+    /// complex here.
+    ///
+    /// [GAT]: https://rust-lang.github.io/generic-associated-types-initiative/explainer/motivation.html
+    type Requirement<'a>
+    where
+        Self: 'a;
+
+    /// The returned data type of the observation.
+    ///
+    /// This is a [GAT] parameterized over a lifetime `'a where Self: 'a`.
+    /// Thus, it is fair game to use simple references, or `Option<&'a Whatever>` or something more
+    /// complex here.
+    ///
+    /// [GAT]: https://rust-lang.github.io/generic-associated-types-initiative/explainer/motivation.html
+    type Observation<'a>
+    where
+        Self: 'a;
+
+    /// The planned operations.
+    /// Often this is `Option<Change<..>>` for a single resource, or `Vec<Change<..>>` for a
+    /// collection of resources.
+    type Changes<'a>
+    where
+        Self: 'a;
+
+    /// Compute the operations which reconciling `observation` with `requirement` would perform.
+    ///
+    /// # Contract
+    ///
+    /// Implementations must not mutate the state of the external system.
+    /// The plan should match what a single call to `reconcile` would do given the same arguments.
+    fn plan<'a>(
+        &self,
+        requirement: Self::Requirement<'a>,
+        observation: Self::Observation<'a>,
+    ) -> Self::Changes<'a>
+    where
+        Self: 'a;
+}
@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! State shared by the packet processing workers to answer ARP requests on behalf of other
+//! hosts (proxy ARP) and to announce the addresses of the interfaces with gratuitous ARP and
+//! unsolicited neighbor advertisements. Addresses are announced once when they appear on an
+//! interface that is up, and again when the interface comes back up (e.g. on failover) or when
+//! re-announcing them is explicitly requested.
+
+use lpm::prefix::{IpPrefixCovering, Ipv4Prefix};
+use net::interface::InterfaceIndex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Interval between two checks for addresses to announce
+pub const ANNOUNCE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The prefixes to answer ARP requests for, per interface
+pub type ProxyArpConfig = BTreeMap<InterfaceIndex, BTreeSet<Ipv4Prefix>>;
+
+#[derive(Debug, Default)]
+struct ArpNdState {
+    proxy: ProxyArpConfig,
+    announced: BTreeSet<(InterfaceIndex, IpAddr)>,
+    last_check: Option<Instant>,
+}
+
+/// A handle to the shared proxy ARP and address announcement state. Cloning the handle does
+/// not clone the state.
+#[derive(Clone, Debug, Default)]
+pub struct ArpNdAgent(Arc<Mutex<ArpNdState>>);
+
+impl ArpNdAgent {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ArpNdState> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Set the prefixes to answer ARP requests for, replacing the current ones
+    pub fn configure_proxy(&self, config: &ProxyArpConfig) {
+        let mut state = self.lock();
+        if state.proxy != *config {
+            debug!("Proxy ARP is now enabled on {} interface(s)", config.len());
+            state.proxy.clone_from(config);
+        }
+    }
+
+    /// Get the current proxy ARP configuration
+    #[must_use]
+    pub fn proxy_config(&self) -> ProxyArpConfig {
+        self.lock().proxy.clone()
+    }
+
+    /// Tell if ARP requests for `target` received on interface `ifindex` must be answered
+    #[must_use]
+    pub fn proxies(&self, ifindex: InterfaceIndex, target: Ipv4Addr) -> bool {
+        let state = self.lock();
+        state
+            .proxy
+            .get(&ifindex)
+            .is_some_and(|prefixes| prefixes.iter().any(|prefix| prefix.covers(&target)))
+    }
+
+    /// Request the addresses of interface `ifindex` to be announced again
+    pub fn reannounce(&self, ifindex: InterfaceIndex) {
+        let mut state = self.lock();
+        state
+            .announced
+            .retain(|(announced, _)| *announced != ifindex);
+    }
+
+    /// Get the addresses to announce at `now`. Addresses are checked at most once every
+    /// [`ANNOUNCE_CHECK_INTERVAL`], by calling `current` to get the addresses of the interfaces
+    /// that are up. Returns the ones that have not been announced yet, and forgets the
+    /// announced addresses that are no longer current, so that they get announced again if
+    /// they come back.
+    pub fn announcements<F>(&self, now: Instant, current: F) -> Vec<(InterfaceIndex, IpAddr)>
+    where
+        F: FnOnce() -> BTreeSet<(InterfaceIndex, IpAddr)>,
+    {
+        let mut state = self.lock();
+        let due = state
+            .last_check
+            .is_none_or(|last| now.saturating_duration_since(last) >= ANNOUNCE_CHECK_INTERVAL);
+        if !due {
+            return Vec::new();
+        }
+        state.last_check = Some(now);
+        let current = current();
+        let new: Vec<_> = current.difference(&state.announced).copied().collect();
+        state.announced = current;
+        new
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ifindex(index: u32) -> InterfaceIndex {
+        InterfaceIndex::try_new(index).unwrap()
+    }
+
+    #[test]
+    fn test_proxy_arp() {
+        let agent = ArpNdAgent::new();
+        let target: Ipv4Addr = "10.0.0.5".parse().unwrap();
+        assert!(!agent.proxies(ifindex(2), target));
+
+        let config =
+            ProxyArpConfig::from([(ifindex(2), BTreeSet::from(["10.0.0.0/24".parse().unwrap()]))]);
+        agent.clone().configure_proxy(&config);
+        assert!(agent.proxies(ifindex(2), target));
+        assert!(!agent.proxies(ifindex(3), target));
+        assert!(!agent.proxies(ifindex(2), "10.0.1.5".parse().unwrap()));
+
+        agent.configure_proxy(&ProxyArpConfig::new());
+        assert!(!agent.proxies(ifindex(2), target));
+    }
+
+    #[test]
+    fn test_announcements() {
+        let agent = ArpNdAgent::new();
+        let addr1: IpAddr = "10.0.0.1".parse().unwrap();
+        let addr2: IpAddr = "2001:db8::1".parse().unwrap();
+        let now = Instant::now();
+
+        let current = BTreeSet::from([(ifindex(2), addr1), (ifindex(3), addr2)]);
+        let announced = agent.announcements(now, || current.clone());
+        assert_eq!(announced, [(ifindex(2), addr1), (ifindex(3), addr2)]);
+
+        // not checked again before the interval
+        let later = now + ANNOUNCE_CHECK_INTERVAL / 2;
+        assert!(agent.announcements(later, || unreachable!()).is_empty());
+
+        // nothing new
+        let later = now + ANNOUNCE_CHECK_INTERVAL;
+        assert!(agent.announcements(later, || current.clone()).is_empty());
+
+        // interface 3 goes down, then back up
+        let later = later + ANNOUNCE_CHECK_INTERVAL;
+        let down = BTreeSet::from([(ifindex(2), addr1)]);
+        assert!(agent.announcements(later, || down).is_empty());
+        let later = later + ANNOUNCE_CHECK_INTERVAL;
+        let announced = agent.announcements(later, || current.clone());
+        assert_eq!(announced, [(ifindex(3), addr2)]);
+
+        // explicit request
+        agent.reannounce(ifindex(2));
+        let later = later + ANNOUNCE_CHECK_INTERVAL;
+        let announced = agent.announcements(later, || current.clone());
+        assert_eq!(announced, [(ifindex(2), addr1)]);
+    }
+}
@@ -4,5 +4,6 @@
 //! Adjacency table module
 
 pub mod adjacency;
+pub mod arpnd;
 pub mod atablerw;
 pub mod resolver;
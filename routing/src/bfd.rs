@@ -0,0 +1,648 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Single-hop BFD sessions (RFC 5880, RFC 5881), used to detect the failure of
+//! next-hops quickly.
+//!
+//! The table of sessions is shared between the packet processing workers, which
+//! receive the BFD control packets, the BFD timer thread of the dataplane, which
+//! emits the control packets and runs the detection timers, and the routing
+//! thread, which withdraws the next-hops of the sessions that went down from the
+//! FIBs.
+
+use crate::rib::nexthop::NhopKey;
+use arc_swap::ArcSwap;
+use net::bfd::{BfdControl, BfdDiag, BfdState};
+use net::interface::InterfaceIndex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::num::NonZero;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Default interval to send and receive BFD control packets
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Default detection multiplier. With the default intervals, the failure of a
+/// neighbor is detected within 300 ms.
+#[allow(clippy::unwrap_used)] // trivially safe const expression
+pub const DEFAULT_DETECT_MULT: NonZero<u8> = NonZero::new(3).unwrap();
+
+/// Minimum interval to send control packets while a session is not up (RFC 5880, 6.8.3)
+const SLOW_TX_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The configuration of a single-hop BFD session
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BfdSessionConfig {
+    /// Address of the neighbor
+    pub peer: IpAddr,
+    /// Local address, used as source of the control packets
+    pub local: IpAddr,
+    /// Interface to reach the neighbor
+    pub ifindex: InterfaceIndex,
+    /// Desired minimum interval between transmitted control packets
+    pub tx_interval: Duration,
+    /// Required minimum interval between received control packets
+    pub rx_interval: Duration,
+    /// Detection multiplier
+    pub detect_mult: NonZero<u8>,
+}
+
+impl BfdSessionConfig {
+    #[must_use]
+    pub fn new(peer: IpAddr, local: IpAddr, ifindex: InterfaceIndex) -> Self {
+        Self {
+            peer,
+            local,
+            ifindex,
+            tx_interval: DEFAULT_INTERVAL,
+            rx_interval: DEFAULT_INTERVAL,
+            detect_mult: DEFAULT_DETECT_MULT,
+        }
+    }
+    #[must_use]
+    pub fn with_intervals(mut self, tx_interval: Duration, rx_interval: Duration) -> Self {
+        self.tx_interval = tx_interval;
+        self.rx_interval = rx_interval;
+        self
+    }
+    #[must_use]
+    pub fn with_detect_mult(mut self, detect_mult: NonZero<u8>) -> Self {
+        self.detect_mult = detect_mult;
+        self
+    }
+    fn key(&self) -> BfdSessionKey {
+        (self.peer, self.ifindex)
+    }
+}
+
+/// A BFD session is identified by the address of the neighbor and the interface to reach it
+pub type BfdSessionKey = (IpAddr, InterfaceIndex);
+
+/// A BFD session and its state
+#[derive(Clone, Debug)]
+pub struct BfdSession {
+    config: BfdSessionConfig,
+    state: BfdState,
+    diag: BfdDiag,
+    local_disc: NonZero<u32>,
+    remote_state: BfdState,
+    remote_disc: u32,
+    remote_min_rx: Duration,
+    remote_min_tx: Duration,
+    remote_detect_mult: u8,
+    last_rx: Option<Instant>,
+    next_tx: Option<Instant>,
+    tx_count: u32,
+    send_final: bool,
+    withdrawn: bool,
+}
+
+impl BfdSession {
+    fn new(config: BfdSessionConfig, local_disc: NonZero<u32>) -> Self {
+        Self {
+            config,
+            state: BfdState::Down,
+            diag: BfdDiag::None,
+            local_disc,
+            remote_state: BfdState::Down,
+            remote_disc: 0,
+            remote_min_rx: Duration::from_micros(1),
+            remote_min_tx: Duration::ZERO,
+            remote_detect_mult: 0,
+            last_rx: None,
+            next_tx: None,
+            tx_count: 0,
+            send_final: false,
+            withdrawn: false,
+        }
+    }
+    #[must_use]
+    pub fn config(&self) -> &BfdSessionConfig {
+        &self.config
+    }
+    #[must_use]
+    pub fn state(&self) -> BfdState {
+        self.state
+    }
+    #[must_use]
+    pub fn diag(&self) -> BfdDiag {
+        self.diag
+    }
+    #[must_use]
+    pub fn remote_state(&self) -> BfdState {
+        self.remote_state
+    }
+    #[must_use]
+    pub fn local_discriminator(&self) -> NonZero<u32> {
+        self.local_disc
+    }
+    #[must_use]
+    pub fn remote_discriminator(&self) -> u32 {
+        self.remote_disc
+    }
+    /// Tell if the next-hop monitored by this session should be withdrawn: this is the case
+    /// if the session went down after having been up.
+    #[must_use]
+    pub fn is_withdrawn(&self) -> bool {
+        self.withdrawn
+    }
+
+    /// The UDP source port of the control packets of this session (RFC 5881, 4)
+    fn src_port(&self) -> u16 {
+        #[allow(clippy::cast_possible_truncation)] // the remainder fits in a u16
+        let offset = (self.local_disc.get() % 16384) as u16;
+        BfdControl::MIN_SOURCE_PORT + offset
+    }
+
+    /// The interval at which we advertise to send control packets (RFC 5880, 6.8.3)
+    fn desired_min_tx(&self) -> Duration {
+        if self.state == BfdState::Up {
+            self.config.tx_interval
+        } else {
+            self.config.tx_interval.max(SLOW_TX_INTERVAL)
+        }
+    }
+
+    /// The interval between control packets sent, with jitter (RFC 5880, 6.8.7)
+    fn tx_interval(&mut self) -> Duration {
+        let interval = self.desired_min_tx().max(self.remote_min_rx);
+        let max = if self.config.detect_mult.get() == 1 {
+            90
+        } else {
+            100
+        };
+        self.tx_count = self.tx_count.wrapping_add(1);
+        let seed = self
+            .local_disc
+            .get()
+            .wrapping_mul(2_654_435_761)
+            .wrapping_add(self.tx_count.wrapping_mul(40_503));
+        let percent = 75 + seed % (max - 75 + 1);
+        interval * percent / 100
+    }
+
+    /// The detection time, in asynchronous mode (RFC 5880, 6.8.4)
+    fn detection_time(&self) -> Duration {
+        self.config.rx_interval.max(self.remote_min_tx) * u32::from(self.remote_detect_mult)
+    }
+
+    fn set_state(&mut self, state: BfdState, diag: BfdDiag, now: Instant) {
+        if self.state == state {
+            return;
+        }
+        info!(
+            "BFD session with {} on ifindex {} changed state: {} -> {} ({:?})",
+            self.config.peer, self.config.ifindex, self.state, state, diag
+        );
+        self.state = state;
+        self.diag = diag;
+        /* advertise the change without waiting for the next periodic transmission */
+        self.next_tx = Some(now);
+    }
+
+    /// Process a received control packet (RFC 5880, 6.8.6)
+    fn rx(&mut self, control: &BfdControl, now: Instant) {
+        let your_disc = control.your_discriminator();
+        if your_disc == 0 && !matches!(control.state(), BfdState::Down | BfdState::AdminDown) {
+            return;
+        }
+        if your_disc != 0 && your_disc != self.local_disc.get() {
+            return;
+        }
+        self.remote_disc = control.my_discriminator().get();
+        self.remote_state = control.state();
+        self.remote_min_rx = control.required_min_rx();
+        self.remote_min_tx = control.desired_min_tx();
+        self.remote_detect_mult = control.detect_mult().get();
+        self.last_rx = Some(now);
+
+        if control.state() == BfdState::AdminDown {
+            /* the neighbor does not want the session: this is not a failure (RFC 5882, 3.2) */
+            self.set_state(BfdState::Down, BfdDiag::NeighborDown, now);
+            self.withdrawn = false;
+        } else {
+            match (self.state, control.state()) {
+                (BfdState::Down, BfdState::Down) => {
+                    self.set_state(BfdState::Init, BfdDiag::None, now);
+                }
+                (BfdState::Down, BfdState::Init)
+                | (BfdState::Init, BfdState::Init | BfdState::Up) => {
+                    self.set_state(BfdState::Up, BfdDiag::None, now);
+                    self.withdrawn = false;
+                }
+                (BfdState::Up, BfdState::Down) => {
+                    self.set_state(BfdState::Down, BfdDiag::NeighborDown, now);
+                    self.withdrawn = true;
+                }
+                _ => {}
+            }
+        }
+        if control.poll() {
+            self.send_final = true;
+            self.next_tx = Some(now);
+        }
+    }
+
+    /// Run the detection timer (RFC 5880, 6.8.4)
+    fn check_detection(&mut self, now: Instant) {
+        if !matches!(self.state, BfdState::Init | BfdState::Up) {
+            return;
+        }
+        let Some(last_rx) = self.last_rx else {
+            return;
+        };
+        if now.saturating_duration_since(last_rx) <= self.detection_time() {
+            return;
+        }
+        if self.state == BfdState::Up {
+            self.withdrawn = true;
+        }
+        self.set_state(BfdState::Down, BfdDiag::DetectionTimeExpired, now);
+        self.remote_state = BfdState::Down;
+        self.remote_disc = 0;
+        self.remote_min_rx = Duration::from_micros(1);
+    }
+
+    /// Build the control packet to send at `now`, if one is due
+    fn tx(&mut self, now: Instant) -> Option<BfdControl> {
+        if self.remote_min_rx.is_zero() && !self.send_final {
+            /* the neighbor asked us not to send periodic control packets */
+            return None;
+        }
+        if self.next_tx.is_some_and(|next| next > now) {
+            return None;
+        }
+        let mut control = BfdControl::new(
+            self.state,
+            self.config.detect_mult,
+            self.local_disc,
+            self.remote_disc,
+        );
+        control
+            .set_diag(self.diag)
+            .set_final(self.send_final)
+            .set_desired_min_tx(self.desired_min_tx())
+            .set_required_min_rx(self.config.rx_interval);
+        self.send_final = false;
+        self.next_tx = Some(now + self.tx_interval());
+        Some(control)
+    }
+}
+
+/// A control packet to send for some BFD session
+#[derive(Clone, Debug)]
+pub struct BfdTx {
+    pub peer: IpAddr,
+    pub local: IpAddr,
+    pub ifindex: InterfaceIndex,
+    pub src_port: u16,
+    pub control: BfdControl,
+}
+
+/// The sessions, by key. Each session has its own lock, for the packet processing workers not to
+/// contend with each other nor with the timers for the sessions they do not receive packets for.
+type BfdSessionMap = BTreeMap<BfdSessionKey, Arc<Mutex<BfdSession>>>;
+
+fn lock_session(session: &Mutex<BfdSession>) -> MutexGuard<'_, BfdSession> {
+    session.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The table of BFD sessions
+#[derive(Debug, Default)]
+pub struct BfdSessionTable {
+    /// The sessions, replaced as a whole when the configuration changes, so that looking up a
+    /// session on reception does not need to lock the table
+    sessions: ArcSwap<BfdSessionMap>,
+    /// The last local discriminator allocated. The lock also serializes configuration changes.
+    last_disc: Mutex<u32>,
+    generation: AtomicU64,
+    waker: Mutex<Option<mio::Waker>>,
+}
+
+impl BfdSessionTable {
+    /// Signal that the set of withdrawn next-hops changed
+    fn bump(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+        let waker = self.waker.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(waker) = waker.as_ref() {
+            if let Err(e) = waker.wake() {
+                warn!("Failed to wake routing thread: {e}");
+            }
+        }
+    }
+}
+
+/// Allocate a local discriminator not in `used`
+fn next_disc(last_disc: &mut u32, used: &BTreeSet<u32>) -> NonZero<u32> {
+    loop {
+        *last_disc = last_disc.wrapping_add(1);
+        if let Some(disc) = NonZero::new(*last_disc) {
+            if !used.contains(&disc.get()) {
+                return disc;
+            }
+        }
+    }
+}
+
+/// A handle to a shared [`BfdSessionTable`]. Cloning the handle does not clone the table.
+#[derive(Clone, Debug, Default)]
+pub struct BfdSessions(Arc<BfdSessionTable>);
+
+impl BfdSessions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a waker to notify when the set of withdrawn next-hops changes
+    pub(crate) fn set_waker(&self, waker: mio::Waker) {
+        *self.0.waker.lock().unwrap_or_else(PoisonError::into_inner) = Some(waker);
+    }
+
+    /// Set the sessions to run. Existing sessions keep their state; sessions not in
+    /// `configs` are removed.
+    pub fn configure(&self, configs: &[BfdSessionConfig]) {
+        let mut last_disc = self
+            .0
+            .last_disc
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let current = self.0.sessions.load_full();
+        let keys: BTreeSet<_> = configs.iter().map(BfdSessionConfig::key).collect();
+        let mut changed = false;
+        for (key, session) in current.iter() {
+            if !keys.contains(key) {
+                debug!("Removing BFD session with {} on ifindex {}", key.0, key.1);
+                changed |= lock_session(session).withdrawn;
+            }
+        }
+        let mut used: BTreeSet<u32> = current
+            .values()
+            .map(|session| lock_session(session).local_disc.get())
+            .collect();
+        let mut sessions = BfdSessionMap::new();
+        for config in configs {
+            if let Some(session) = current.get(&config.key()) {
+                lock_session(session).config = config.clone();
+                sessions.insert(config.key(), session.clone());
+            } else {
+                debug!(
+                    "Creating BFD session with {} on ifindex {}",
+                    config.peer, config.ifindex
+                );
+                let disc = next_disc(&mut last_disc, &used);
+                used.insert(disc.get());
+                let session = BfdSession::new(config.clone(), disc);
+                sessions.insert(config.key(), Arc::new(Mutex::new(session)));
+            }
+        }
+        self.0.sessions.store(Arc::new(sessions));
+        if changed {
+            self.0.bump();
+        }
+    }
+
+    /// Process a control packet received on interface `ifindex`. Returns true if the packet
+    /// belongs to a configured session.
+    ///
+    /// This only locks the session the packet belongs to.
+    pub fn rx(
+        &self,
+        ifindex: InterfaceIndex,
+        source: IpAddr,
+        destination: IpAddr,
+        control: &BfdControl,
+        now: Instant,
+    ) -> bool {
+        let sessions = self.0.sessions.load();
+        let Some(session) = sessions.get(&(source, ifindex)) else {
+            return false;
+        };
+        let mut session = lock_session(session);
+        if session.config.local != destination {
+            return false;
+        }
+        let withdrawn = session.withdrawn;
+        session.rx(control, now);
+        let changed = session.withdrawn != withdrawn;
+        drop(session);
+        if changed {
+            self.0.bump();
+        }
+        true
+    }
+
+    /// Run the detection timers of all sessions and get the control packets to send at `now`
+    #[must_use]
+    pub fn poll(&self, now: Instant) -> Vec<BfdTx> {
+        let mut changed = false;
+        let mut out = Vec::new();
+        for session in self.0.sessions.load().values() {
+            let mut session = lock_session(session);
+            let withdrawn = session.withdrawn;
+            session.check_detection(now);
+            changed |= session.withdrawn != withdrawn;
+            if let Some(control) = session.tx(now) {
+                out.push(BfdTx {
+                    peer: session.config.peer,
+                    local: session.config.local,
+                    ifindex: session.config.ifindex,
+                    src_port: session.src_port(),
+                    control,
+                });
+            }
+        }
+        if changed {
+            self.0.bump();
+        }
+        out
+    }
+
+    /// Get the keys of the sessions whose next-hops should be withdrawn
+    #[must_use]
+    pub fn withdrawn(&self) -> BTreeSet<BfdSessionKey> {
+        self.0
+            .sessions
+            .load()
+            .iter()
+            .filter(|(_, session)| lock_session(session).withdrawn)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Get a counter that changes every time the set of withdrawn next-hops changes
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.0.generation.load(Ordering::Acquire)
+    }
+
+    /// Get a copy of all sessions
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<BfdSession> {
+        self.0
+            .sessions
+            .load()
+            .values()
+            .map(|session| lock_session(session).clone())
+            .collect()
+    }
+}
+
+/// Tell if the next-hop with the given key is monitored by one of the `withdrawn` sessions
+pub(crate) fn nhop_is_withdrawn(withdrawn: &BTreeSet<BfdSessionKey>, key: &NhopKey) -> bool {
+    withdrawn.iter().any(|(peer, ifindex)| {
+        key.address == Some(*peer) && key.ifindex.is_none_or(|index| index == *ifindex)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn addr(a: &str) -> IpAddr {
+        IpAddr::from_str(a).unwrap()
+    }
+
+    fn build_sessions() -> (BfdSessions, BfdSessionKey) {
+        let sessions = BfdSessions::new();
+        let ifindex = InterfaceIndex::try_new(2).unwrap();
+        let config = BfdSessionConfig::new(addr("10.0.0.2"), addr("10.0.0.1"), ifindex);
+        sessions.configure(&[config]);
+        (sessions, (addr("10.0.0.2"), ifindex))
+    }
+
+    fn peer_control(state: BfdState, your_disc: u32) -> BfdControl {
+        let mut control = BfdControl::new(
+            state,
+            DEFAULT_DETECT_MULT,
+            NonZero::new(1000).unwrap(),
+            your_disc,
+        );
+        control
+            .set_desired_min_tx(DEFAULT_INTERVAL)
+            .set_required_min_rx(DEFAULT_INTERVAL);
+        control
+    }
+
+    fn rx(sessions: &BfdSessions, key: BfdSessionKey, control: &BfdControl, now: Instant) {
+        assert!(sessions.rx(key.1, key.0, addr("10.0.0.1"), control, now));
+    }
+
+    fn state(sessions: &BfdSessions) -> BfdState {
+        sessions.snapshot()[0].state()
+    }
+
+    // bring the session up with a three-way handshake and return the local discriminator
+    fn bring_up(sessions: &BfdSessions, key: BfdSessionKey, now: Instant) -> u32 {
+        let tx = sessions.poll(now);
+        assert_eq!(tx.len(), 1);
+        assert_eq!(tx[0].control.state(), BfdState::Down);
+        assert!(tx[0].src_port >= BfdControl::MIN_SOURCE_PORT);
+        let disc = tx[0].control.my_discriminator().get();
+
+        rx(sessions, key, &peer_control(BfdState::Down, 0), now);
+        assert_eq!(state(sessions), BfdState::Init);
+        rx(sessions, key, &peer_control(BfdState::Up, disc), now);
+        assert_eq!(state(sessions), BfdState::Up);
+        disc
+    }
+
+    #[test]
+    fn test_bfd_session_up() {
+        let (sessions, key) = build_sessions();
+        let now = Instant::now();
+        bring_up(&sessions, key, now);
+        assert!(sessions.withdrawn().is_empty());
+
+        /* the change of state is advertised immediately */
+        let tx = sessions.poll(now);
+        assert_eq!(tx.len(), 1);
+        assert_eq!(tx[0].control.state(), BfdState::Up);
+        assert_eq!(tx[0].control.your_discriminator(), 1000);
+
+        /* next packet is sent within the negotiated interval, with jitter */
+        assert!(sessions.poll(now + Duration::from_millis(70)).is_empty());
+        assert_eq!(sessions.poll(now + DEFAULT_INTERVAL).len(), 1);
+
+        /* packets for other destinations or interfaces are not ours */
+        let control = peer_control(BfdState::Up, 1);
+        assert!(!sessions.rx(key.1, key.0, addr("10.0.0.3"), &control, now));
+        let other = InterfaceIndex::try_new(3).unwrap();
+        assert!(!sessions.rx(other, key.0, addr("10.0.0.1"), &control, now));
+    }
+
+    #[test]
+    fn test_bfd_detection_timeout() {
+        let (sessions, key) = build_sessions();
+        let now = Instant::now();
+        bring_up(&sessions, key, now);
+        let generation = sessions.generation();
+
+        /* within the detection time */
+        let _ = sessions.poll(now + Duration::from_millis(300));
+        assert_eq!(state(&sessions), BfdState::Up);
+
+        /* detection time expired */
+        let _ = sessions.poll(now + Duration::from_millis(301));
+        let session = &sessions.snapshot()[0];
+        assert_eq!(session.state(), BfdState::Down);
+        assert_eq!(session.diag(), BfdDiag::DetectionTimeExpired);
+        assert_eq!(sessions.withdrawn(), BTreeSet::from([key]));
+        assert_ne!(sessions.generation(), generation);
+
+        /* the session comes back up */
+        let later = now + Duration::from_secs(1);
+        bring_up(&sessions, key, later);
+        assert!(sessions.withdrawn().is_empty());
+    }
+
+    #[test]
+    fn test_bfd_neighbor_down() {
+        let (sessions, key) = build_sessions();
+        let now = Instant::now();
+        let disc = bring_up(&sessions, key, now);
+
+        /* administratively down is not a failure */
+        rx(
+            &sessions,
+            key,
+            &peer_control(BfdState::AdminDown, disc),
+            now,
+        );
+        assert_eq!(state(&sessions), BfdState::Down);
+        assert!(sessions.withdrawn().is_empty());
+
+        /* neighbor signaled down */
+        bring_up(&sessions, key, now);
+        rx(&sessions, key, &peer_control(BfdState::Down, disc), now);
+        assert_eq!(state(&sessions), BfdState::Down);
+        assert_eq!(sessions.withdrawn(), BTreeSet::from([key]));
+
+        /* removing the session restores the next-hop */
+        let generation = sessions.generation();
+        sessions.configure(&[]);
+        assert!(sessions.snapshot().is_empty());
+        assert!(sessions.withdrawn().is_empty());
+        assert_ne!(sessions.generation(), generation);
+    }
+
+    #[test]
+    fn test_bfd_nhop_is_withdrawn() {
+        let ifindex = InterfaceIndex::try_new(2).unwrap();
+        let withdrawn = BTreeSet::from([(addr("10.0.0.2"), ifindex)]);
+        let mut key = NhopKey::with_address(&addr("10.0.0.2"));
+        assert!(nhop_is_withdrawn(&withdrawn, &key));
+        key.ifindex = Some(ifindex);
+        assert!(nhop_is_withdrawn(&withdrawn, &key));
+        key.ifindex = Some(InterfaceIndex::try_new(3).unwrap());
+        assert!(!nhop_is_withdrawn(&withdrawn, &key));
+        assert!(!nhop_is_withdrawn(
+            &withdrawn,
+            &NhopKey::with_address(&addr("10.0.0.3"))
+        ));
+    }
+}
@@ -6,8 +6,8 @@
 #![allow(clippy::unnecessary_wraps)]
 
 use crate::cpi::rpc_send_control;
-use crate::display::IfTableAddress;
 use crate::display::{FibGroups, FibViewV4, FibViewV6};
+use crate::display::{IfTableAddress, IfTableUrpf};
 use crate::display::{VrfV4Nexthops, VrfV6Nexthops, VrfViewV4, VrfViewV6};
 use crate::fib::fibtype::{FibRouteV4Filter, FibRouteV6Filter};
 use crate::revent::ROUTER_EVENTS;
@@ -17,10 +17,15 @@ use crate::rib::vrftable::VrfTable;
 use crate::rio::Rio;
 use crate::routingdb::RoutingDb;
 
-use cli::cliproto::{CliAction, CliError, CliRequest, CliResponse, CliSerialize, RouteProtocol};
+use cli::cliproto::{
+    CliAction, CliError, CliRequest, CliResponse, CliSerialize, RequestArgs, RouteProtocol,
+};
 use lpm::prefix::{Ipv4Prefix, Ipv6Prefix};
 use net::vxlan::Vni;
+use std::collections::HashMap;
 use std::os::unix::net::SocketAddr;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Instant;
 use tracing::{error, trace};
 
 use tracectl::{get_trace_ctl, trace_target};
@@ -360,6 +365,81 @@ fn show_ip_fib_groups(
     }
 }
 
+fn show_snapshot(request: CliRequest, db: &RoutingDb) -> Result<CliResponse, CliError> {
+    if let Some(vrfid) = request.args.vrfid {
+        if db.vrftable.get_vrf(vrfid).is_err() {
+            return Err(CliError::NotFound(format!("VRF with id {vrfid}")));
+        }
+    }
+    let snapshot = db.snapshot(request.args.vrfid);
+    match snapshot.to_json() {
+        Ok(json) => Ok(CliResponse::from_request_ok(request, format!("\n{json}"))),
+        Err(e) => {
+            error!("Failed to serialize routing snapshot: {e}");
+            Err(CliError::InternalError)
+        }
+    }
+}
+
+fn show_mroutes(request: CliRequest, db: &RoutingDb) -> Result<CliResponse, CliError> {
+    let Some(mroutes) = db.mroutew.enter() else {
+        return Err(CliError::InternalError);
+    };
+    let members = db.mcast_members.snapshot(Instant::now());
+    Ok(CliResponse::from_request_ok(
+        request,
+        format!("\n{}\n{members}", *mroutes),
+    ))
+}
+
+fn show_lldp_neighbors(request: CliRequest, db: &RoutingDb) -> Result<CliResponse, CliError> {
+    let mut neighbors = db.lldp.snapshot(Instant::now());
+    if let Some(ifname) = &request.args.ifname {
+        let Some(iftable) = db.iftw.enter() else {
+            return Err(CliError::InternalError);
+        };
+        let Some(iface) = iftable.values().find(|iface| &iface.name == ifname) else {
+            return Err(CliError::NotFound(format!("Interface {ifname}")));
+        };
+        neighbors.retain_ifindex(iface.ifindex);
+    }
+    Ok(CliResponse::from_request_ok(
+        request,
+        format!("\n{neighbors}"),
+    ))
+}
+
+/// A handler for cli requests about state that is not kept in the routing database,
+/// such as the state of the packet processing stages.
+pub type CliHandler = Box<dyn Fn(&RequestArgs) -> Result<String, CliError> + Send + Sync>;
+
+/// A handle to the shared table of [`CliHandler`]s, indexed by [`CliAction`].
+/// Cloning the handle does not clone the table.
+#[derive(Clone, Default)]
+pub struct CliHandlers(Arc<RwLock<HashMap<u16, CliHandler>>>);
+
+impl CliHandlers {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `handler` to serve the requests for `action`, replacing any previous handler
+    pub fn register(&self, action: CliAction, handler: CliHandler) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(action as u16, handler);
+    }
+
+    /// Serve `request` with the registered handler for its action, if any
+    fn handle(&self, request: &CliRequest) -> Option<Result<String, CliError>> {
+        let handlers = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        let handler = handlers.get(&(request.action.clone() as u16))?;
+        Some(handler(&request.args))
+    }
+}
+
 fn do_handle_cli_request(
     request: CliRequest,
     db: &RoutingDb,
@@ -422,6 +502,14 @@ fn do_handle_cli_request(
                 CliResponse::from_request_fail(request, CliError::InternalError)
             }
         }
+        CliAction::ShowRouterUrpf => {
+            if let Some(iftable) = db.iftw.enter() {
+                let urpf = IfTableUrpf(&iftable, &db.urpf_drops);
+                CliResponse::from_request_ok(request, format!("\n{urpf}"))
+            } else {
+                CliResponse::from_request_fail(request, CliError::InternalError)
+            }
+        }
         CliAction::ShowRouterVrfs => return show_vrfs(request, db),
         CliAction::ShowRouterEvpnRmacStore => {
             let rmac_store = &db.rmac_store;
@@ -462,7 +550,14 @@ fn do_handle_cli_request(
         CliAction::ShowRouterIpv6FibGroups => {
             return show_ip_fib_groups(request, db, false);
         }
-        _ => Err(CliError::NotSupported("Not implemented yet".to_owned()))?,
+        CliAction::ShowRouterSnapshot => return show_snapshot(request, db),
+        CliAction::ShowRouterMroutes => return show_mroutes(request, db),
+        CliAction::ShowLldpNeighbors => return show_lldp_neighbors(request, db),
+        _ => match db.cli_handlers.handle(&request) {
+            Some(Ok(out)) => CliResponse::from_request_ok(request, format!("\n{out}")),
+            Some(Err(e)) => return Err(e),
+            None => Err(CliError::NotSupported("Not implemented yet".to_owned()))?,
+        },
     };
     Ok(response)
 }
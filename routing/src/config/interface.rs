@@ -14,6 +14,26 @@ use crate::interfaces::iftablerw::IfTableWriter;
 use crate::interfaces::interface::{AttachConfig, Attachment};
 use crate::interfaces::interface::{Interface, RouterInterfaceConfig};
 use crate::rib::VrfTable;
+
+impl RouterConfig {
+    ///////////////////////////////////////////////////////////////////////////////////
+    /// Get the config to apply for an interface: interfaces that don't set their uRPF
+    /// mode get that of the VRF they are attached to, if any.
+    ///////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn effective_interface_config(
+        &self,
+        cfg: &RouterInterfaceConfig,
+    ) -> RouterInterfaceConfig {
+        let mut cfg = cfg.clone();
+        if cfg.urpf.is_none() {
+            if let Some(AttachConfig::VRF(vrfid)) = cfg.attach_cfg {
+                cfg.urpf = self.vrf_urpf.get(&vrfid).copied();
+            }
+        }
+        cfg
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////////////
 /// Structure used to summarize a plan for reconfiguring [`Interface`]s
 ///////////////////////////////////////////////////////////////////////////////////////
@@ -39,8 +59,9 @@ impl ReconfigInterfacePlan {
         for iface in iftable.values() {
             let ifindex = iface.ifindex;
             if let Some(cfg) = config.get_interface(ifindex) {
-                if iface.as_config() != *cfg {
-                    to_modify.push(cfg.clone());
+                let cfg = config.effective_interface_config(cfg);
+                if iface.as_config() != cfg {
+                    to_modify.push(cfg);
                 } else {
                     to_keep.push(ifindex);
                 }
@@ -50,7 +71,7 @@ impl ReconfigInterfacePlan {
         }
         for cfg in config.interfaces() {
             if !iftable.contains(cfg.ifindex.into()) {
-                to_add.push(cfg.clone());
+                to_add.push(config.effective_interface_config(cfg));
             }
         }
         ReconfigInterfacePlan {
@@ -133,6 +154,7 @@ impl Interface {
             iftype: self.iftype.clone(),
             admin_state: self.admin_state,
             mtu: self.mtu,
+            urpf: self.urpf,
             attach_cfg: self
                 .attachment
                 .as_ref()
@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Router multicast configuration. Static mroutes are programmed directly in the multicast
+//! routing table used by the dataplane, without the intervention of FRR.
+
+use crate::RouterError;
+use crate::config::RouterConfig;
+use crate::mcast::MrouteKey;
+use crate::routingdb::RoutingDb;
+use std::collections::BTreeSet;
+use tracing::debug;
+
+impl RouterConfig {
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Check the mroutes of this config: groups must be multicast addresses, sources
+    /// unicast ones of the same family, mroutes must refer to configured vrfs, and
+    /// there can't be multiple mroutes with the same key.
+    //////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn validate_mroutes(&self) -> Result<(), RouterError> {
+        let keys: BTreeSet<MrouteKey> = self.mroutes.iter().map(|mroute| mroute.key).collect();
+        if keys.len() != self.mroutes.len() {
+            return Err(RouterError::InvalidConfig("Duplicated mroute"));
+        }
+        for mroute in &self.mroutes {
+            let key = &mroute.key;
+            if !key.group.is_multicast() {
+                return Err(RouterError::InvalidConfig("Invalid multicast group"));
+            }
+            if let Some(source) = key.source {
+                if source.is_multicast()
+                    || source.is_unspecified()
+                    || source.is_ipv4() != key.group.is_ipv4()
+                {
+                    return Err(RouterError::InvalidConfig("Invalid multicast source"));
+                }
+            }
+            if key.vrfid != 0 && !self.vrfs.contains_key(&key.vrfid) {
+                return Err(RouterError::InvalidConfig("Mroute refers to unknown vrf"));
+            }
+            if mroute.iif.is_some_and(|iif| mroute.oifs.contains(&iif)) {
+                return Err(RouterError::InvalidConfig("Mroute iif is also an oif"));
+            }
+        }
+        Ok(())
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Program the mroutes of this config, if they changed since the previously
+    /// applied config. The table is replaced as a whole and published once.
+    //////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn apply_mroutes(&self, db: &mut RoutingDb) {
+        let current = db.config.as_ref().map(|config| &config.mroutes);
+        if current == Some(&self.mroutes) {
+            return;
+        }
+        db.mroutew.clear(false);
+        for mroute in &self.mroutes {
+            debug!("Adding mroute {mroute}");
+            db.mroutew.add_mroute(mroute.clone(), false);
+        }
+        db.mroutew.publish();
+    }
+}
@@ -6,17 +6,31 @@
 #![allow(unused)]
 
 mod interface;
+mod mcast;
+mod srv6;
+mod statics;
 mod vrf;
 mod vtep;
 
 use crate::RouterError;
+use crate::atable::arpnd::ProxyArpConfig;
+use crate::bfd::BfdSessionConfig;
 use crate::evpn::Vtep;
+use crate::fib::ecmp::EcmpConfig;
+use crate::fib::urpf::UrpfMode;
 use crate::interfaces::iftable::IfTable;
 use crate::interfaces::interface::RouterInterfaceConfig;
+use crate::mcast::Mroute;
 use crate::rib::VrfTable;
-use crate::rib::vrf::{RouterVrfConfig, VrfId};
+use crate::rib::aggregate::Aggregate;
+use crate::rib::distance::AdminDistances;
+use crate::rib::policy::RoutePolicyTable;
+use crate::rib::vrf::{RouteOrigin, RouterVrfConfig, VrfId};
 use crate::routingdb::RoutingDb;
+use crate::srv6::LocalSid;
 use config::GenId;
+use config::internal::routing::statics::StaticRoute;
+use lpm::prefix::Ipv4Prefix;
 use net::interface::InterfaceIndex;
 use net::vxlan::Vni;
 use std::collections::{BTreeMap, BTreeSet};
@@ -38,6 +52,16 @@ pub struct RouterConfig {
     vrfs: BTreeMap<VrfId, RouterVrfConfig>,
     interfaces: BTreeMap<InterfaceIndex, RouterInterfaceConfig>,
     vtep: Option<Vtep>,
+    ecmp: Option<EcmpConfig>,
+    bfd: Vec<BfdSessionConfig>,
+    static_routes: BTreeSet<StaticRoute>,
+    policies: RoutePolicyTable,
+    admin_distances: AdminDistances,
+    aggregates: BTreeMap<VrfId, BTreeSet<Aggregate>>,
+    local_sids: BTreeSet<LocalSid>,
+    mroutes: BTreeSet<Mroute>,
+    proxy_arp: ProxyArpConfig,
+    vrf_urpf: BTreeMap<VrfId, UrpfMode>,
     frr_cfg: Option<FrrConfig>,
 }
 
@@ -49,6 +73,16 @@ impl RouterConfig {
             vrfs: BTreeMap::new(),
             interfaces: BTreeMap::new(),
             vtep: None,
+            ecmp: None,
+            bfd: Vec::new(),
+            static_routes: BTreeSet::new(),
+            policies: RoutePolicyTable::new(),
+            admin_distances: AdminDistances::new(),
+            aggregates: BTreeMap::new(),
+            local_sids: BTreeSet::new(),
+            mroutes: BTreeSet::new(),
+            proxy_arp: ProxyArpConfig::new(),
+            vrf_urpf: BTreeMap::new(),
             frr_cfg: None,
         }
     }
@@ -64,6 +98,41 @@ impl RouterConfig {
     pub fn set_vtep(&mut self, vtep: Vtep) {
         self.vtep = Some(vtep);
     }
+    pub fn set_ecmp(&mut self, ecmp: EcmpConfig) {
+        self.ecmp = Some(ecmp);
+    }
+    pub fn add_bfd_session(&mut self, session: BfdSessionConfig) {
+        self.bfd.push(session);
+    }
+    pub fn add_static_route(&mut self, route: StaticRoute) {
+        self.static_routes.insert(route);
+    }
+    /// N.B. policies only apply to the routes received after they are set
+    pub fn set_route_policies(&mut self, policies: RoutePolicyTable) {
+        self.policies = policies;
+    }
+    /// Set the administrative distance of the routes learnt from FRR for a protocol.
+    /// N.B. distances only apply to the routes received after they are set
+    pub fn set_admin_distance(&mut self, origin: RouteOrigin, distance: u8) {
+        self.admin_distances.insert(origin, distance);
+    }
+    pub fn add_aggregate(&mut self, vrfid: VrfId, aggregate: Aggregate) {
+        self.aggregates.entry(vrfid).or_default().insert(aggregate);
+    }
+    pub fn add_local_sid(&mut self, local_sid: LocalSid) {
+        self.local_sids.insert(local_sid);
+    }
+    pub fn add_mroute(&mut self, mroute: Mroute) {
+        self.mroutes.insert(mroute);
+    }
+    /// Answer the ARP requests received on interface `ifindex` for addresses in `prefix`
+    pub fn add_proxy_arp(&mut self, ifindex: InterfaceIndex, prefix: Ipv4Prefix) {
+        self.proxy_arp.entry(ifindex).or_default().insert(prefix);
+    }
+    /// Set the uRPF mode of the interfaces attached to VRF `vrfid` that don't set their own
+    pub fn set_vrf_urpf(&mut self, vrfid: VrfId, mode: UrpfMode) {
+        self.vrf_urpf.insert(vrfid, mode);
+    }
     pub fn set_frr_config(&mut self, frr_cfg: FrrConfig) {
         self.frr_cfg = Some(frr_cfg);
     }
@@ -91,6 +160,54 @@ impl RouterConfig {
                 return Err(RouterError::InvalidConfig("Vtep is not set up"));
             }
         }
+        // Check bfd sessions
+        if self
+            .bfd
+            .iter()
+            .any(|session| session.peer.is_ipv4() != session.local.is_ipv4())
+        {
+            return Err(RouterError::InvalidConfig(
+                "BFD session with mismatched address families",
+            ));
+        }
+        // Check static routes
+        for route in &self.static_routes {
+            if route.next_hop_vrf.is_some() {
+                return Err(RouterError::InvalidConfig(
+                    "Static route with next-hop vrf is not supported",
+                ));
+            }
+            if let Err(e) = route.validate() {
+                error!("{e}");
+                return Err(RouterError::InvalidConfig("Invalid static route"));
+            }
+        }
+        // Check route policies
+        self.policies.validate()?;
+        // Check aggregates
+        for aggregates in self.aggregates.values() {
+            Aggregate::validate_set(aggregates)?;
+        }
+        // Check local SIDs
+        self.validate_local_sids()?;
+        // Check mroutes
+        self.validate_mroutes()?;
+        // Check proxy ARP
+        if self
+            .proxy_arp
+            .keys()
+            .any(|ifindex| !self.interfaces.contains_key(ifindex))
+        {
+            return Err(RouterError::InvalidConfig("Proxy ARP on unknown interface"));
+        }
+        // Check uRPF
+        if self
+            .vrf_urpf
+            .keys()
+            .any(|vrfid| *vrfid != 0 && !self.vrfs.contains_key(vrfid))
+        {
+            return Err(RouterError::InvalidConfig("uRPF mode for unknown vrf"));
+        }
         Ok(())
     }
 }
@@ -159,6 +276,34 @@ impl RouterConfig {
         if let Some(vtep) = &self.vtep {
             vtep.apply(db);
         }
+        if let Some(ecmp) = self.ecmp {
+            db.vrftable
+                .values_mut()
+                .filter(|vrf| vrf.get_ecmp() != Some(ecmp))
+                .for_each(|vrf| vrf.set_ecmp(ecmp));
+        }
+        db.bfd.configure(&self.bfd);
+        db.arpnd.configure_proxy(&self.proxy_arp);
+        db.macip.set_vnis(
+            self.vrfs()
+                .filter_map(|vrf| Some((vrf.vrfid, vrf.vni?)))
+                .collect(),
+        );
+        if db.policies != self.policies {
+            db.policies = self.policies.clone();
+        }
+        for vrf in db.vrftable.values_mut() {
+            if *vrf.get_admin_distances() != self.admin_distances {
+                vrf.set_admin_distances(&self.admin_distances);
+            }
+            let aggregates = self.aggregates.get(&vrf.vrfid).cloned().unwrap_or_default();
+            if vrf.get_aggregates() != aggregates {
+                vrf.set_aggregates(&aggregates);
+            }
+        }
+        self.apply_static_routes(db)?;
+        self.apply_local_sids(db);
+        self.apply_mroutes(db);
         debug!("Successfully applied router config for generation {genid}");
         self.verify(&db)?;
         Ok(())
@@ -213,7 +358,7 @@ impl RouterConfig {
     fn verify_interfaces(&self, db: &RoutingDb) -> Result<(), RouterError> {
         let iftable = &*db.iftw.enter().unwrap_or_else(|| unreachable!());
         for ifconfig in self.interfaces() {
-            Self::verify_interface(ifconfig, iftable)?;
+            Self::verify_interface(&self.effective_interface_config(ifconfig), iftable)?;
         }
         Ok(())
     }
@@ -230,7 +375,7 @@ impl RouterConfig {
 #[cfg(test)]
 #[rustfmt::skip]
 mod tests {
-    use std::net::IpAddr;
+    use std::net::{IpAddr, Ipv6Addr};
     use std::str::FromStr;
     use tracing_test::traced_test;
     use tracing::debug;
@@ -247,6 +392,15 @@ use crate::{config::RouterConfig, evpn::Vtep, interfaces::interface::{AttachConf
     use crate::interfaces::iftablerw::IfTableWriter;
     use crate::fib::fibtable::FibTableWriter;
     use crate::atable::resolver::AtResolver;
+    use crate::lfib::lfibrw::LfibWriter;
+    use crate::mcast::mroutetablerw::MrouteTableWriter;
+    use crate::mcast::{Mroute, MrouteKey};
+    use crate::srv6::sidtablerw::SidTableWriter;
+    use crate::srv6::{LocalSid, SidBehavior};
+    use crate::fib::urpf::UrpfMode;
+    use crate::rib::vrf::RouteOrigin;
+    use config::internal::routing::statics::StaticRoute;
+    use lpm::prefix::Prefix;
 
 
     fn mk_vni(vni: u32) -> Vni {
@@ -324,7 +478,10 @@ use crate::{config::RouterConfig, evpn::Vtep, interfaces::interface::{AttachConf
         let (iftw, _iftr) = IfTableWriter::new();
         let (fibtw, _fibtr) = FibTableWriter::new();
         let (_resolver, atabler) = AtResolver::new(false);
-        RoutingDb::new(fibtw, iftw, atabler)
+        let (lfibw, _lfibr) = LfibWriter::new();
+        let (sidtablew, _sidtabler) = SidTableWriter::new();
+        let (mroutew, _mroutetabler) = MrouteTableWriter::new();
+        RoutingDb::new(fibtw, iftw, atabler, lfibw, sidtablew, mroutew)
     }
     fn test_apply_config(config: &RouterConfig, db: &mut RoutingDb) -> Result<(), RouterError> {
         config.apply(db)?;
@@ -456,4 +613,203 @@ use crate::{config::RouterConfig, evpn::Vtep, interfaces::interface::{AttachConf
         ifconfig.set_attach_cfg(None);
         test_apply_config(&config, &mut db).expect("Should succeed");
     }
+
+    #[traced_test]
+    #[test]
+    fn test_config_static_routes() {
+        let mut db = create_routing_database();
+        let mut config = build_router_config();
+        let ecmp = Prefix::expect_from(("192.168.1.0", 24));
+        let drop = Prefix::expect_from(("192.168.2.0", 24));
+        config.add_static_route(StaticRoute::new(ecmp).nhop_addr(IpAddr::from_str("10.0.0.1").unwrap()));
+        config.add_static_route(StaticRoute::new(ecmp).nhop_iface("Eth1".to_string()));
+        config.add_static_route(StaticRoute::new(drop).nhop_blackhole().distance(200));
+        test_apply_config(&config, &mut db).expect("Should succeed");
+
+        let vrf0 = db.vrftable.get_default_vrf();
+        let route = vrf0.get_route(ecmp).expect("Should find route");
+        assert_eq!(route.origin, RouteOrigin::Static);
+        assert_eq!(route.distance, 1);
+        assert_eq!(route.s_nhops.len(), 2);
+        let route = vrf0.get_route(drop).expect("Should find route");
+        assert_eq!(route.distance, 200);
+        db.set_config(config);
+
+        debug!("━━━━━━━━ Test: Remove static routes");
+        let mut config = build_router_config();
+        config.genid = 2;
+        config.add_static_route(StaticRoute::new(drop).nhop_blackhole().distance(200));
+        test_apply_config(&config, &mut db).expect("Should succeed");
+        let vrf0 = db.vrftable.get_default_vrf();
+        assert!(vrf0.get_route(ecmp).is_none());
+        assert!(vrf0.get_route(drop).is_some());
+
+        debug!("━━━━━━━━ Test: Static route via unknown interface");
+        db.set_config(config);
+        let mut config = build_router_config();
+        config.genid = 3;
+        config.add_static_route(StaticRoute::new(ecmp).nhop_iface("unknown".to_string()));
+        let result = test_apply_config(&config, &mut db);
+        assert!(result.is_err_and(|e| matches!(e, RouterError::InvalidConfig(_))));
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_config_local_sids() {
+        let mut db = create_routing_database();
+        let mut config = build_router_config();
+        let sid = |address: &str| Ipv6Addr::from_str(address).unwrap();
+        let end = LocalSid::new(sid("fc00:0:1::"), SidBehavior::End);
+        let dt4 = LocalSid::new(sid("fc00:0:1::100"), SidBehavior::EndDt4(100));
+        config.add_local_sid(end.clone());
+        config.add_local_sid(dt4.clone());
+        test_apply_config(&config, &mut db).expect("Should succeed");
+        {
+            let sidtable = db.sidtablew.enter().unwrap();
+            assert_eq!(sidtable.len(), 2);
+            assert_eq!(sidtable.get_sid(&end.sid), Some(&end));
+            assert_eq!(sidtable.get_sid(&dt4.sid), Some(&dt4));
+        }
+        db.set_config(config);
+
+        debug!("━━━━━━━━ Test: Replace local SIDs");
+        let mut config = build_router_config();
+        config.genid = 2;
+        let dt6 = LocalSid::new(sid("fc00:0:1::200"), SidBehavior::EndDt6(101));
+        config.add_local_sid(dt6.clone());
+        test_apply_config(&config, &mut db).expect("Should succeed");
+        {
+            let sidtable = db.sidtablew.enter().unwrap();
+            assert_eq!(sidtable.len(), 1);
+            assert_eq!(sidtable.get_sid(&dt6.sid), Some(&dt6));
+        }
+        db.set_config(config);
+
+        debug!("━━━━━━━━ Test: Invalid local SIDs");
+        let invalid = [
+            vec![LocalSid::new(sid("fc00:0:1::"), SidBehavior::EndDt4(999))],
+            vec![LocalSid::new(sid("ff02::1"), SidBehavior::End)],
+            vec![end.clone(), LocalSid::new(end.sid, SidBehavior::EndDt6(100))],
+        ];
+        for local_sids in invalid {
+            let mut config = build_router_config();
+            config.genid = 3;
+            local_sids.into_iter().for_each(|local| config.add_local_sid(local));
+            let result = test_apply_config(&config, &mut db);
+            assert!(result.is_err_and(|e| matches!(e, RouterError::InvalidConfig(_))));
+        }
+        assert_eq!(db.sidtablew.enter().unwrap().len(), 1);
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_config_mroutes() {
+        let mut db = create_routing_database();
+        let mut config = build_router_config();
+        let addr = |address: &str| IpAddr::from_str(address).unwrap();
+        let eth0 = InterfaceIndex::try_new(10).unwrap();
+        let eth1 = InterfaceIndex::try_new(11).unwrap();
+        let star_g = Mroute::new(MrouteKey::new(100, None, addr("239.1.1.1"))).with_oif(eth0);
+        let s_g = Mroute::new(MrouteKey::new(0, Some(addr("10.0.0.1")), addr("239.1.1.1")))
+            .with_iif(eth0)
+            .with_oif(eth1);
+        config.add_mroute(star_g.clone());
+        config.add_mroute(s_g.clone());
+        test_apply_config(&config, &mut db).expect("Should succeed");
+        {
+            let mroutes = db.mroutew.enter().unwrap();
+            assert_eq!(mroutes.len(), 2);
+            assert_eq!(mroutes.get_mroute(&star_g.key), Some(&star_g));
+            assert_eq!(mroutes.get_mroute(&s_g.key), Some(&s_g));
+        }
+        db.set_config(config);
+
+        debug!("━━━━━━━━ Test: Replace mroutes");
+        let mut config = build_router_config();
+        config.genid = 2;
+        let v6 = Mroute::new(MrouteKey::new(101, None, addr("ff05::1:3"))).with_oif(eth1);
+        config.add_mroute(v6.clone());
+        test_apply_config(&config, &mut db).expect("Should succeed");
+        {
+            let mroutes = db.mroutew.enter().unwrap();
+            assert_eq!(mroutes.len(), 1);
+            assert_eq!(mroutes.get_mroute(&v6.key), Some(&v6));
+        }
+        db.set_config(config);
+
+        debug!("━━━━━━━━ Test: Invalid mroutes");
+        let invalid = [
+            vec![Mroute::new(MrouteKey::new(0, None, addr("10.1.1.1")))],
+            vec![Mroute::new(MrouteKey::new(0, Some(addr("2001:db8::1")), addr("239.1.1.1")))],
+            vec![Mroute::new(MrouteKey::new(999, None, addr("239.1.1.1")))],
+            vec![Mroute::new(MrouteKey::new(0, None, addr("239.1.1.1")))
+                .with_iif(eth0)
+                .with_oif(eth0)],
+            vec![star_g.clone(), star_g.clone().with_oif(eth1)],
+        ];
+        for mroutes in invalid {
+            let mut config = build_router_config();
+            config.genid = 3;
+            mroutes.into_iter().for_each(|mroute| config.add_mroute(mroute));
+            let result = test_apply_config(&config, &mut db);
+            assert!(result.is_err_and(|e| matches!(e, RouterError::InvalidConfig(_))));
+        }
+        assert_eq!(db.mroutew.enter().unwrap().len(), 1);
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_config_proxy_arp() {
+        let mut db = create_routing_database();
+        let mut config = build_router_config();
+        let eth0 = InterfaceIndex::try_new(10).unwrap();
+        config.add_proxy_arp(eth0, "10.0.0.0/24".parse().unwrap());
+        test_apply_config(&config, &mut db).expect("Should succeed");
+        assert!(db.arpnd.proxies(eth0, "10.0.0.5".parse().unwrap()));
+        assert!(!db.arpnd.proxies(eth0, "10.0.1.5".parse().unwrap()));
+        db.set_config(config);
+
+        debug!("━━━━━━━━ Test: Proxy ARP on unknown interface");
+        let mut config = build_router_config();
+        config.genid = 2;
+        config.add_proxy_arp(InterfaceIndex::try_new(999).unwrap(), "10.0.0.0/24".parse().unwrap());
+        let result = test_apply_config(&config, &mut db);
+        assert!(result.is_err_and(|e| matches!(e, RouterError::InvalidConfig(_))));
+        assert!(db.arpnd.proxies(eth0, "10.0.0.5".parse().unwrap()));
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_config_vrf_urpf() {
+        let mut db = create_routing_database();
+        let eth0 = InterfaceIndex::try_new(10).unwrap();
+        let eth1 = InterfaceIndex::try_new(11).unwrap();
+        let urpf = |db: &RoutingDb, ifindex| {
+            db.iftw.enter().unwrap().get_interface(ifindex).unwrap().urpf
+        };
+
+        // Eth0 is attached to vrf 100 and gets its mode. Eth1 is not attached
+        let mut config = build_router_config();
+        config.set_vrf_urpf(100, UrpfMode::Loose);
+        test_apply_config(&config, &mut db).expect("Should succeed");
+        assert_eq!(urpf(&db, eth0), Some(UrpfMode::Loose));
+        assert_eq!(urpf(&db, eth1), None);
+        db.set_config(config);
+
+        debug!("━━━━━━━━ Test: Change the mode of the vrf");
+        let mut config = build_router_config();
+        config.genid = 2;
+        config.set_vrf_urpf(100, UrpfMode::Strict);
+        test_apply_config(&config, &mut db).expect("Should succeed");
+        assert_eq!(urpf(&db, eth0), Some(UrpfMode::Strict));
+        db.set_config(config);
+
+        debug!("━━━━━━━━ Test: uRPF mode for unknown vrf");
+        let mut config = build_router_config();
+        config.genid = 3;
+        config.set_vrf_urpf(999, UrpfMode::Strict);
+        let result = test_apply_config(&config, &mut db);
+        assert!(result.is_err_and(|e| matches!(e, RouterError::InvalidConfig(_))));
+        assert_eq!(urpf(&db, eth0), Some(UrpfMode::Strict));
+    }
 }
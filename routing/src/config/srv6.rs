@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Router SRv6 configuration. Local SIDs are programmed directly in the local SID
+//! table used by the dataplane, without the intervention of FRR.
+
+use crate::RouterError;
+use crate::config::RouterConfig;
+use crate::routingdb::RoutingDb;
+use crate::srv6::SidBehavior;
+use std::collections::BTreeSet;
+use std::net::Ipv6Addr;
+use tracing::debug;
+
+impl RouterConfig {
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Check the local SIDs of this config: SIDs must be unicast, can't be bound to
+    /// multiple behaviors, and decapsulation behaviors must refer to configured vrfs.
+    //////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn validate_local_sids(&self) -> Result<(), RouterError> {
+        let sids: BTreeSet<Ipv6Addr> = self.local_sids.iter().map(|local| local.sid).collect();
+        if sids.len() != self.local_sids.len() {
+            return Err(RouterError::InvalidConfig("SID with multiple behaviors"));
+        }
+        if sids
+            .iter()
+            .any(|sid| sid.is_unspecified() || sid.is_multicast())
+        {
+            return Err(RouterError::InvalidConfig("Invalid SID"));
+        }
+        for local in &self.local_sids {
+            match local.behavior {
+                SidBehavior::End => {}
+                SidBehavior::EndDt4(vrfid) | SidBehavior::EndDt6(vrfid) => {
+                    if vrfid != 0 && !self.vrfs.contains_key(&vrfid) {
+                        return Err(RouterError::InvalidConfig("SID refers to unknown vrf"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Program the local SIDs of this config, if they changed since the previously
+    /// applied config. The table is replaced as a whole and published once.
+    //////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn apply_local_sids(&self, db: &mut RoutingDb) {
+        let current = db.config.as_ref().map(|config| &config.local_sids);
+        if current == Some(&self.local_sids) {
+            return;
+        }
+        db.sidtablew.clear(false);
+        for local in &self.local_sids {
+            debug!("Adding local SID {} ({})", local.sid, local.behavior);
+            db.sidtablew.add_sid(local.clone(), false);
+        }
+        db.sidtablew.publish();
+    }
+}
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Router static route configuration. Static routes are programmed directly
+//! in the RIB of the default VRF, without the intervention of FRR.
+
+use crate::RouterError;
+use crate::config::RouterConfig;
+use crate::interfaces::iftable::IfTable;
+use crate::rib::distance::RouteSource;
+use crate::rib::nexthop::{FwAction, NhopKey};
+use crate::rib::vrf::{Route, RouteNhop, RouteOrigin};
+use crate::routingdb::RoutingDb;
+use config::internal::routing::statics::{StaticRoute, StaticRouteNhop};
+use lpm::prefix::Prefix;
+use std::collections::BTreeMap;
+use tracing::{debug, error};
+
+/// Administrative distance of static routes, if not explicitly configured
+const DEFAULT_STATIC_DISTANCE: u8 = 1;
+
+/// Group static routes by prefix
+fn group_by_prefix<'a>(
+    routes: impl Iterator<Item = &'a StaticRoute>,
+) -> BTreeMap<Prefix, Vec<&'a StaticRoute>> {
+    let mut grouped: BTreeMap<Prefix, Vec<&StaticRoute>> = BTreeMap::new();
+    for route in routes {
+        grouped.entry(route.prefix).or_default().push(route);
+    }
+    grouped
+}
+
+impl RouteNhop {
+    /// Build a [`RouteNhop`] from the next-hop of a static route, looking up interfaces by name
+    fn from_static_nhop(nhop: &StaticRouteNhop, iftable: &IfTable) -> Result<Self, RouterError> {
+        let lookup = |ifname: &String| {
+            iftable
+                .values()
+                .find(|iface| &iface.name == ifname)
+                .map(|iface| iface.ifindex)
+                .ok_or(RouterError::InvalidConfig(
+                    "Unknown interface in static route",
+                ))
+        };
+        let origin = RouteOrigin::Static;
+        let key = match nhop {
+            StaticRouteNhop::Unset => {
+                return Err(RouterError::InvalidConfig("Static route has no next-hop"));
+            }
+            StaticRouteNhop::Address(address) => {
+                NhopKey::new(origin, Some(*address), None, None, FwAction::Forward, None)
+            }
+            StaticRouteNhop::Interface(ifname) => NhopKey::new(
+                origin,
+                None,
+                Some(lookup(ifname)?),
+                None,
+                FwAction::Forward,
+                Some(ifname.clone()),
+            ),
+            StaticRouteNhop::AddressInterface(address, ifname) => NhopKey::new(
+                origin,
+                Some(*address),
+                Some(lookup(ifname)?),
+                None,
+                FwAction::Forward,
+                Some(ifname.clone()),
+            ),
+            StaticRouteNhop::Null0 | StaticRouteNhop::Blackhole | StaticRouteNhop::Reject => {
+                NhopKey::with_drop()
+            }
+        };
+        Ok(RouteNhop { vrfid: 0, key })
+    }
+}
+
+impl RouterConfig {
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Program the static routes of this config in the default VRF, removing the
+    /// static routes of the previously applied config that are no longer configured.
+    /// Only the routes with the lowest distance are installed for each prefix, and
+    /// they are only selected over the routes learnt from FRR if preferred to them.
+    //////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn apply_static_routes(&self, db: &mut RoutingDb) -> Result<(), RouterError> {
+        let wanted = group_by_prefix(self.static_routes.iter());
+        let current = db
+            .config
+            .as_ref()
+            .map(|config| group_by_prefix(config.static_routes.iter()))
+            .unwrap_or_default();
+
+        let iftable = db.iftw.enter().unwrap_or_else(|| unreachable!());
+        let vrf0 = db.vrftable.get_default_vrf_mut();
+        let mut changed = false;
+
+        // remove static routes no longer configured
+        let source = RouteSource::Protocol(RouteOrigin::Static);
+        for prefix in current.keys().filter(|p| !wanted.contains_key(p)) {
+            if vrf0.has_route_from(*prefix, source) {
+                debug!("Removing static route to {prefix}");
+                vrf0.del_route_from(*prefix, source, None, &db.rmac_store);
+                changed = true;
+            }
+        }
+
+        // add or replace static routes that changed
+        for (prefix, routes) in &wanted {
+            let installed = vrf0.has_route_from(*prefix, source);
+            if installed && current.get(prefix) == Some(routes) {
+                continue;
+            }
+            let distance = routes
+                .iter()
+                .map(|r| r.distance.unwrap_or(DEFAULT_STATIC_DISTANCE))
+                .min()
+                .unwrap_or(DEFAULT_STATIC_DISTANCE);
+            let mut nhops = Vec::with_capacity(routes.len());
+            for r in routes
+                .iter()
+                .filter(|r| r.distance.unwrap_or(DEFAULT_STATIC_DISTANCE) == distance)
+            {
+                nhops.push(
+                    RouteNhop::from_static_nhop(&r.next_hop, &iftable).inspect_err(|e| {
+                        error!(
+                            "Failed to build next-hop {:?} for {prefix}: {e}",
+                            r.next_hop
+                        )
+                    })?,
+                );
+            }
+            let route = Route {
+                origin: RouteOrigin::Static,
+                distance,
+                ..Default::default()
+            };
+            debug!(
+                "Adding static route to {prefix} with {} next-hop(s)",
+                nhops.len()
+            );
+            vrf0.add_route_complete(prefix, route, &nhops, None, &db.rmac_store);
+            changed = true;
+        }
+
+        // other vrfs may resolve over the static routes of the default vrf
+        if changed {
+            db.vrftable.refresh_non_default_fibs(&db.rmac_store);
+        }
+        Ok(())
+    }
+}
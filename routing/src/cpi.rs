@@ -4,6 +4,7 @@
 //! Main processing functions of the Control-plane interface (CPI)
 
 use crate::evpn::RmacEntry;
+use crate::lfib::Lsp as LfibLsp;
 use crate::revent::{ROUTER_EVENTS, RouterEvent, revent};
 use crate::rio::Rio;
 use crate::routingdb::RoutingDb;
@@ -18,7 +19,10 @@ use std::os::unix::net::SocketAddr;
 use std::process;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use net::eth::mac::Mac;
 use net::interface::InterfaceIndex;
+use net::mpls::MplsLabel;
+use net::vxlan::Vni;
 #[allow(unused)]
 use tracing::{debug, error, info, trace, warn};
 
@@ -43,6 +47,7 @@ impl StatsRow {
 pub(crate) enum CpiStatus {
     #[default]
     NotConnected, /* FRR has not connected -- or we're not attending it */
+    Disconnected, /* FRR had connected but we no longer hear from it */
     Incompatible, /* FRR has attempted to connect but we use incompatible RPC versions */
     Connected,    /* FRR has connected normally */
     FrrRestarted, /* FRR has reconnected: it has restarted */
@@ -89,6 +94,10 @@ pub(crate) struct CpiStats {
     pub(crate) del_ifaddr: StatsRow,
     pub(crate) add_rmac: StatsRow,
     pub(crate) del_rmac: StatsRow,
+    pub(crate) add_lsp: StatsRow,
+    pub(crate) del_lsp: StatsRow,
+    pub(crate) add_macip: StatsRow,
+    pub(crate) del_macip: StatsRow,
 
     // control - keepalives
     pub(crate) control_rx: u64,
@@ -200,19 +209,20 @@ impl RpcOperation for IpRoute {
         let rmac_store = &db.rmac_store;
         let vrftable = &mut db.vrftable;
         let iftabler = &db.iftw.as_iftable_reader();
+        let policies = &db.policies;
 
         if self.vrfid != 0 && (is_evpn_route(self) || nonlocal_nhop(self)) {
             let Ok((vrf, vrf0)) = vrftable.get_with_default_mut(self.vrfid) else {
                 error!("Unable to get vrf with id {}", self.vrfid);
                 return RpcResultCode::Failure;
             };
-            vrf.add_route_rpc(self, Some(vrf0), rmac_store, iftabler);
+            vrf.add_route_rpc(self, Some(vrf0), rmac_store, iftabler, policies);
         } else {
             let Ok(vrf0) = vrftable.get_vrf_mut(self.vrfid) else {
                 error!("Unable to find VRF with id {}", self.vrfid);
                 return RpcResultCode::Failure;
             };
-            vrf0.add_route_rpc(self, None, rmac_store, iftabler);
+            vrf0.add_route_rpc(self, None, rmac_store, iftabler, policies);
             vrftable.refresh_non_default_fibs(rmac_store);
         }
         RpcResultCode::Ok
@@ -262,6 +272,44 @@ impl RpcOperation for Rmac {
     }
 }
 
+impl RpcOperation for MacIp {
+    type ObjectStore = RoutingDb;
+    fn add(&self, db: &mut Self::ObjectStore) -> RpcResultCode {
+        let Ok(vni) = Vni::new_checked(self.vni) else {
+            error!("Failed to store MAC/IP binding {self}: invalid vni");
+            return RpcResultCode::Failure;
+        };
+        db.macip.add(vni, self.address, Mac::from(self.mac.bytes()));
+        RpcResultCode::Ok
+    }
+    fn del(&self, db: &mut Self::ObjectStore) -> RpcResultCode {
+        let Ok(vni) = Vni::new_checked(self.vni) else {
+            return RpcResultCode::Failure;
+        };
+        db.macip.del(vni, self.address, Mac::from(self.mac.bytes()));
+        RpcResultCode::Ok
+    }
+}
+
+impl RpcOperation for Lsp {
+    type ObjectStore = RoutingDb;
+    fn add(&self, db: &mut Self::ObjectStore) -> RpcResultCode {
+        let Ok(lsp) = LfibLsp::try_from(self) else {
+            error!("Failed to store LSP {self}");
+            return RpcResultCode::Failure;
+        };
+        db.lfibw.add_lsp(lsp, true);
+        RpcResultCode::Ok
+    }
+    fn del(&self, db: &mut Self::ObjectStore) -> RpcResultCode {
+        let Ok(in_label) = MplsLabel::new(self.in_label) else {
+            return RpcResultCode::Failure;
+        };
+        db.lfibw.del_lsp(in_label, true);
+        RpcResultCode::Ok
+    }
+}
+
 impl RpcOperation for IfAddress {
     type ObjectStore = RoutingDb;
     fn add(&self, db: &mut Self::ObjectStore) -> RpcResultCode {
@@ -363,6 +411,16 @@ fn update_stats(
             RpcOp::Del => stats.del_route.incr(res_code),
             _ => unreachable!(),
         },
+        Some(RpcObject::Lsp(_)) => match op {
+            RpcOp::Add | RpcOp::Update => stats.add_lsp.incr(res_code),
+            RpcOp::Del => stats.del_lsp.incr(res_code),
+            _ => unreachable!(),
+        },
+        Some(RpcObject::MacIp(_)) => match op {
+            RpcOp::Add => stats.add_macip.incr(res_code),
+            RpcOp::Del => stats.del_macip.incr(res_code),
+            _ => unreachable!(),
+        },
         Some(RpcObject::ConnectInfo(_)) => stats.connect.incr(res_code),
     }
 }
@@ -413,6 +471,16 @@ fn handle_request(rio: &mut Rio, peer: &SocketAddr, req: &RpcRequest, db: &mut R
             RpcOp::Del => route.del(db),
             _ => RpcResultCode::InvalidRequest,
         },
+        Some(RpcObject::Lsp(lsp)) => match op {
+            RpcOp::Add | RpcOp::Update => lsp.add(db),
+            RpcOp::Del => lsp.del(db),
+            _ => RpcResultCode::InvalidRequest,
+        },
+        Some(RpcObject::MacIp(macip)) => match op {
+            RpcOp::Add => macip.add(db),
+            RpcOp::Del => macip.del(db),
+            _ => RpcResultCode::InvalidRequest,
+        },
         Some(RpcObject::ConnectInfo(conninfo)) => match op {
             RpcOp::Connect => {
                 let res = conninfo.connect(&mut rio.cpistats, peer);
@@ -460,6 +528,10 @@ pub fn process_rx_data(rio: &mut Rio, peer: &SocketAddr, data: &[u8], db: &mut R
     trace!("CPI: recvd {} bytes from {}...", data.len(), peer.pretty());
     let mut buf_rx = Bytes::copy_from_slice(data); // TODO: avoid this copy
     rio.cpistats.last_msg_rx = Some(Local::now());
+    if rio.cpistats.status == CpiStatus::Disconnected {
+        info!("CPI: heard again from {}", peer.pretty());
+        rio.cpistats.status.change(CpiStatus::Connected);
+    }
     match RpcMsg::decode(&mut buf_rx) {
         Ok(msg) => handle_rpc_msg(rio, peer, &msg, db),
         Err(e) => {
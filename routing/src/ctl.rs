@@ -14,10 +14,13 @@ use tracing::{debug, error, info, warn};
 
 use crate::RouterError;
 use crate::config::RouterConfig;
-use crate::frr::frrmi::FrrAppliedConfig;
+use crate::frr::frrmi::{FrrAppliedConfig, FrrApplyStatus};
 use crate::revent::{ROUTER_EVENTS, RouterEvent, revent};
+use crate::rib::vrf::VrfId;
+use crate::rib::watch::RouteSubscription;
 use crate::rio::{CPSOCK, Rio};
 use crate::routingdb::RoutingDb;
+use crate::snapshot::RoutingSnapshot;
 
 pub(crate) type RouterCtlReplyTx = AsyncSender<RouterCtlReply>;
 
@@ -25,6 +28,9 @@ pub(crate) type RouterCtlReplyTx = AsyncSender<RouterCtlReply>;
 pub enum RouterCtlReply {
     Result(Result<(), RouterError>),
     FrrConfig(Option<FrrAppliedConfig>),
+    FrrStatus(FrrApplyStatus),
+    Snapshot(Box<RoutingSnapshot>),
+    RouteSubscription(Option<RouteSubscription>),
 }
 
 #[repr(transparent)]
@@ -49,6 +55,9 @@ pub enum RouterCtlMsg {
     GuardedUnlock,
     Configure(RouterConfig, RouterCtlReplyTx),
     GetFrrAppliedConfig(RouterCtlReplyTx),
+    GetFrrApplyStatus(RouterCtlReplyTx),
+    GetSnapshot(Option<VrfId>, RouterCtlReplyTx),
+    SubscribeRoutes(RouterCtlReplyTx),
 }
 
 // An object to send control messages to the router
@@ -132,6 +141,54 @@ impl RouterCtlSender {
         };
         Ok(frr_cfg)
     }
+    pub async fn get_frr_apply_status(&mut self) -> Result<FrrApplyStatus, RouterError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let msg = RouterCtlMsg::GetFrrApplyStatus(reply_tx);
+        self.0
+            .send(msg)
+            .await
+            .map_err(|_| RouterError::Internal("Failed to send get FRR apply status"))?;
+        let reply = reply_rx
+            .await
+            .map_err(|_| RouterError::Internal("Failed to receive reply for get FRR status"))?;
+        let RouterCtlReply::FrrStatus(status) = reply else {
+            unreachable!()
+        };
+        Ok(status)
+    }
+    pub async fn get_snapshot(
+        &mut self,
+        vrfid: Option<VrfId>,
+    ) -> Result<RoutingSnapshot, RouterError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let msg = RouterCtlMsg::GetSnapshot(vrfid, reply_tx);
+        self.0
+            .send(msg)
+            .await
+            .map_err(|_| RouterError::Internal("Failed to send get snapshot"))?;
+        let reply = reply_rx
+            .await
+            .map_err(|_| RouterError::Internal("Failed to receive reply for get snapshot"))?;
+        let RouterCtlReply::Snapshot(snapshot) = reply else {
+            unreachable!()
+        };
+        Ok(*snapshot)
+    }
+    pub async fn subscribe_routes(&mut self) -> Result<RouteSubscription, RouterError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let msg = RouterCtlMsg::SubscribeRoutes(reply_tx);
+        self.0
+            .send(msg)
+            .await
+            .map_err(|_| RouterError::Internal("Failed to send route subscription request"))?;
+        let reply = reply_rx
+            .await
+            .map_err(|_| RouterError::Internal("Failed to receive route subscription"))?;
+        let RouterCtlReply::RouteSubscription(subscription) = reply else {
+            unreachable!()
+        };
+        subscription.ok_or(RouterError::Internal("Route changes are not watched"))
+    }
 }
 
 /// Handle a lock request for the indicated CPI
@@ -208,6 +265,36 @@ fn handle_get_frr_applied_config(rio: &Rio, reply_to: RouterCtlReplyTx) {
         });
 }
 
+/// Handle get FRR apply status
+fn handle_get_frr_apply_status(rio: &Rio, reply_to: RouterCtlReplyTx) {
+    let status = rio.frrmi.get_apply_status();
+    let _ = reply_to
+        .send(RouterCtlReply::FrrStatus(status))
+        .map_err(|e| {
+            error!("Fatal: could not reply to get FRR apply status request: {e:?}");
+        });
+}
+
+/// Handle get routing snapshot
+fn handle_get_snapshot(db: &RoutingDb, vrfid: Option<VrfId>, reply_to: RouterCtlReplyTx) {
+    let snapshot = db.snapshot(vrfid);
+    let _ = reply_to
+        .send(RouterCtlReply::Snapshot(Box::new(snapshot)))
+        .map_err(|e| {
+            error!("Fatal: could not reply to get snapshot request: {e:?}");
+        });
+}
+
+/// Handle a subscription to route changes
+fn handle_subscribe_routes(db: &RoutingDb, reply_to: RouterCtlReplyTx) {
+    let subscription = db.vrftable.subscribe_routes();
+    let _ = reply_to
+        .send(RouterCtlReply::RouteSubscription(subscription))
+        .map_err(|e| {
+            error!("Fatal: could not reply to route subscription request: {e:?}");
+        });
+}
+
 /// Handle a request from the control channel
 pub(crate) fn handle_ctl_msg(rio: &mut Rio, db: &mut RoutingDb) {
     match rio.ctl_rx.try_recv() {
@@ -224,6 +311,9 @@ pub(crate) fn handle_ctl_msg(rio: &mut Rio, db: &mut RoutingDb) {
         Ok(RouterCtlMsg::GetFrrAppliedConfig(reply_to)) => {
             handle_get_frr_applied_config(rio, reply_to)
         }
+        Ok(RouterCtlMsg::GetFrrApplyStatus(reply_to)) => handle_get_frr_apply_status(rio, reply_to),
+        Ok(RouterCtlMsg::GetSnapshot(vrfid, reply_to)) => handle_get_snapshot(db, vrfid, reply_to),
+        Ok(RouterCtlMsg::SubscribeRoutes(reply_to)) => handle_subscribe_routes(db, reply_to),
         Err(TryRecvError::Empty) => {}
         Err(e) => {
             error!("Error receiving from ctl channel {e:?}");
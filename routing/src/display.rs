@@ -17,6 +17,7 @@ use crate::cpi::{CpiStats, CpiStatus, StatsRow};
 use crate::fib::fibgroupstore::FibRoute;
 use crate::fib::fibobjects::{EgressObject, FibEntry, FibGroup, PktInstruction};
 use crate::fib::fibtype::{Fib, FibKey};
+use crate::fib::urpf::UrpfDrops;
 use crate::frr::frrmi::{FrrAppliedConfig, Frrmi, FrrmiStats};
 
 use crate::rib::VrfTable;
@@ -28,6 +29,11 @@ use crate::interfaces::iftable::IfTable;
 use crate::interfaces::interface::Attachment;
 use crate::interfaces::interface::{IfDataDot1q, IfDataEthernet};
 use crate::interfaces::interface::{IfState, IfType, Interface};
+use crate::interfaces::lldp::{LldpNeighbor, LldpNeighborTable};
+use crate::lfib::{LabelOp, Lfib, Lsp, LspNhop};
+use crate::mcast::snooping::McastMemberTable;
+use crate::mcast::{Mroute, MrouteKey, MrouteTable};
+use crate::srv6::{LocalSid, SidBehavior, SidTable};
 
 use crate::evpn::{RmacEntry, RmacStore, Vtep};
 use crate::pretty_utils::{Heading, line};
@@ -260,6 +266,12 @@ impl Display for Vrf {
         )?;
         fmt_vrf_trie(f, "Ipv4", &self.routesv4, |_| true)?;
         fmt_vrf_trie(f, "Ipv6", &self.routesv6, |_| true)?;
+        if !self.alternates.is_empty() {
+            Heading(format!("Alternate routes ({})", self.alternates.len())).fmt(f)?;
+            for (prefix, route) in self.alternates.iter() {
+                write!(f, " {}  {prefix} {route}", route.flags)?;
+            }
+        }
         self.nhstore.fmt(f)
     }
 }
@@ -556,6 +568,87 @@ impl Display for IfTableAddress<'_> {
     }
 }
 
+//========================= uRPF ================================//
+pub struct IfTableUrpf<'a>(pub &'a IfTable, pub &'a UrpfDrops);
+
+macro_rules! INTERFACE_URPF_FMT {
+    () => {
+        " {:<16} {:>8} {:<8} {:>12}"
+    };
+}
+impl Display for IfTableUrpf<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let drops = self.1.snapshot();
+        Heading("uRPF".to_string()).fmt(f)?;
+        writeln!(
+            f,
+            "{}",
+            format_args!(INTERFACE_URPF_FMT!(), "name", "ifindex", "mode", "drops")
+        )?;
+        for iface in self.0.values() {
+            let mode = iface.urpf.map_or("--".to_string(), |mode| mode.to_string());
+            let dropped = drops.get(&iface.ifindex).copied().unwrap_or_default();
+            writeln!(
+                f,
+                "{}",
+                format_args!(
+                    INTERFACE_URPF_FMT!(),
+                    iface.name,
+                    iface.ifindex.to_string(),
+                    mode,
+                    dropped
+                )
+            )?;
+        }
+        Ok(())
+    }
+}
+
+//========================= LLDP neighbors ================================//
+macro_rules! LLDP_NEIGH_TBL_FMT {
+    () => {
+        " {:<10} {:<24} {:<20} {:<20} {:>6} {}"
+    };
+}
+fn fmt_lldp_neighbor_heading(f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+        f,
+        "{}",
+        format_args!(
+            LLDP_NEIGH_TBL_FMT!(),
+            "ifindex", "chassis-id", "port-id", "system-name", "ttl", "port-description"
+        )
+    )
+}
+
+impl Display for LldpNeighbor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            format_args!(
+                LLDP_NEIGH_TBL_FMT!(),
+                self.ifindex,
+                self.chassis_id.to_string(),
+                self.port_id.to_string(),
+                self.system_name.as_deref().unwrap_or("--"),
+                self.ttl,
+                self.port_description.as_deref().unwrap_or("--")
+            )
+        )
+    }
+}
+impl Display for LldpNeighborTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Heading(format!("LLDP neighbors ({})", self.len())).fmt(f)?;
+        fmt_lldp_neighbor_heading(f)?;
+        for neighbor in self.values() {
+            writeln!(f, "{neighbor}")?;
+        }
+        Ok(())
+    }
+}
+
 //========================= Rmac Store ================================//
 macro_rules! RMAC_TBL_FMT {
     () => {
@@ -638,6 +731,130 @@ impl Display for AdjacencyTable {
     }
 }
 
+//========================= Lfib ================================//
+impl Display for LabelOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LabelOp::Swap(labels) => {
+                write!(f, "swap")?;
+                for label in labels {
+                    write!(f, " {label}")?;
+                }
+                Ok(())
+            }
+            LabelOp::Pop => write!(f, "pop"),
+            LabelOp::PopLookup(vrfid) => write!(f, "pop, lookup in vrf {vrfid}"),
+        }
+    }
+}
+impl Display for LspNhop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.op)?;
+        if let Some(address) = self.address {
+            write!(f, " via {address}")?;
+        }
+        if let Some(ifindex) = self.ifindex {
+            write!(f, " ifindex {ifindex}")?;
+        }
+        Ok(())
+    }
+}
+impl Display for Lsp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, " {} ({:?})", self.in_label, self.origin)?;
+        for nhop in &self.nhops {
+            writeln!(f, "    {nhop}")?;
+        }
+        Ok(())
+    }
+}
+impl Display for Lfib {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Heading(format!("LFIB ({})", self.len())).fmt(f)?;
+        let mut lsps: Vec<&Lsp> = self.values().collect();
+        lsps.sort_by_key(|lsp| lsp.in_label);
+        for lsp in lsps {
+            write!(f, "{lsp}")?;
+        }
+        Ok(())
+    }
+}
+
+//========================= SRv6 local SIDs ================================//
+impl Display for SidBehavior {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SidBehavior::End => write!(f, "End"),
+            SidBehavior::EndDt4(vrfid) => write!(f, "End.DT4 vrf {vrfid}"),
+            SidBehavior::EndDt6(vrfid) => write!(f, "End.DT6 vrf {vrfid}"),
+        }
+    }
+}
+impl Display for LocalSid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, " {} {}", self.sid, self.behavior)
+    }
+}
+impl Display for SidTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Heading(format!("Local SIDs ({})", self.len())).fmt(f)?;
+        let mut sids: Vec<&LocalSid> = self.values().collect();
+        sids.sort_by_key(|local| local.sid);
+        for local in sids {
+            write!(f, "{local}")?;
+        }
+        Ok(())
+    }
+}
+
+//========================= Mroutes ================================//
+impl Display for MrouteKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.source {
+            Some(source) => write!(f, "({source}, {})", self.group)?,
+            None => write!(f, "(*, {})", self.group)?,
+        }
+        write!(f, " vrf {}", self.vrfid)
+    }
+}
+impl Display for Mroute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.key)?;
+        if let Some(iif) = self.iif {
+            write!(f, " iif {iif}")?;
+        }
+        write!(f, " oifs:")?;
+        for oif in &self.oifs {
+            write!(f, " {oif}")?;
+        }
+        Ok(())
+    }
+}
+impl Display for MrouteTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Heading(format!("Mroutes ({})", self.len())).fmt(f)?;
+        let mut mroutes: Vec<&Mroute> = self.values().collect();
+        mroutes.sort_by_key(|mroute| mroute.key);
+        for mroute in mroutes {
+            writeln!(f, " {mroute}")?;
+        }
+        Ok(())
+    }
+}
+impl Display for McastMemberTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Heading(format!("Snooped groups ({})", self.len())).fmt(f)?;
+        for (vrfid, group, members) in self.iter() {
+            write!(f, " {group} vrf {vrfid} members:")?;
+            for ifindex in members {
+                write!(f, " {ifindex}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
 //========================= Fib ================================//
 impl Display for FibKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -688,11 +905,15 @@ impl Display for FibRoute {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(
             f,
-            "via {} groups, {} entries:",
+            "via {} groups{}, {} entries:",
             self.num_groups(),
+            if self.is_shared() { " (shared)" } else { "" },
             self.len()
         )?;
-        for group in self.iter() {
+        for (group, weight) in self.iter_weighted() {
+            if weight > 1 {
+                writeln!(f, "     weight {weight}")?;
+            }
             group.fmt(f)?;
         }
         Ok(())
@@ -747,6 +968,7 @@ impl<F: for<'a> Fn(&'a (&Ipv4Prefix, &FibRoute)) -> bool> Display for FibViewV4<
 
         fmt_vrf_oneline(&self.vrf, f)?;
         Heading(format!("Ipv4 FIB ({total_entries} destinations)")).fmt(f)?;
+        writeln!(f, "  trie: {}", fibr.get_v4_trie().stats())?;
         for (prefix, route) in rt_iter {
             write!(f, "  {prefix:?} {route}")?;
             displayed += 1;
@@ -784,6 +1006,7 @@ impl<F: for<'a> Fn(&'a (&Ipv6Prefix, &FibRoute)) -> bool> Display for FibViewV6<
 
         fmt_vrf_oneline(&self.vrf, f)?;
         Heading(format!("Ipv6 FIB ({total_entries} destinations)")).fmt(f)?;
+        writeln!(f, "  trie: {}", fibr.get_v6_trie().stats())?;
         for (prefix, route) in rt_iter {
             write!(f, "  {prefix:?} {route}")?;
             displayed += 1;
@@ -900,6 +1123,7 @@ impl Display for CpiStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             CpiStatus::NotConnected => write!(f, "Not-connected"),
+            CpiStatus::Disconnected => write!(f, "Disconnected"),
             CpiStatus::Incompatible => write!(f, "Incompatible"),
             CpiStatus::Connected => write!(f, "Connected"),
             CpiStatus::FrrRestarted => write!(f, "Frr-restarted"),
@@ -947,6 +1171,12 @@ impl Display for CpiStats {
 
         fmt_stats_row(f, "Add rmac", &self.add_rmac)?;
         fmt_stats_row(f, "Del rmac", &self.del_rmac)?;
+
+        fmt_stats_row(f, "Add lsp", &self.add_lsp)?;
+        fmt_stats_row(f, "Del lsp", &self.del_lsp)?;
+
+        fmt_stats_row(f, "Add macip", &self.add_macip)?;
+        fmt_stats_row(f, "Del macip", &self.del_macip)?;
         Ok(())
     }
 }
@@ -984,12 +1214,20 @@ impl Display for FrrmiStats {
             .map(|genid| genid.to_string())
             .unwrap_or_else(|| "none".to_string());
 
+        let last_rollback_genid = self
+            .last_rollback_genid
+            .map(|genid| genid.to_string())
+            .unwrap_or_else(|| "none".to_string());
+
         writeln!(f, " Last connection : {last_conn_time}")?;
         writeln!(f, " Last disconnect : {last_disconn_time}")?;
         writeln!(f, " Last cfg applied: {last_ok_genid} {last_ok_t}")?;
         writeln!(f, " Last cfg failure: {last_fail_genid} {last_fail_t}")?;
         writeln!(f, " Configs applied : {}", self.apply_oks)?;
         writeln!(f, " Configs failed  : {}", self.apply_failures)?;
+        writeln!(f, " Last rollback to: {last_rollback_genid}")?;
+        writeln!(f, " Rollbacks       : {}", self.rollbacks)?;
+        writeln!(f, " Rollbacks failed: {}", self.rollback_failures)?;
         Ok(())
     }
 }
@@ -24,6 +24,9 @@ pub enum RouterError {
     #[error("Invalid VNI value: {0}")]
     VniInvalid(u32),
 
+    #[error("Invalid MPLS label: {0}")]
+    MplsLabelInvalid(u32),
+
     #[error("An interface with ifindex {0} already exists")]
     InterfaceExists(InterfaceIndex),
 
@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Submodule to implement a table of the MAC/IP bindings advertised in EVPN type-2 routes,
+//! which FRR conveys over the CPI. The table is shared with the packet processing workers,
+//! which use it to answer ARP and ND requests locally instead of flooding them into the VXLAN
+//! fabric (ARP/ND suppression).
+
+use crate::rib::vrf::VrfId;
+use net::eth::mac::Mac;
+use net::vxlan::Vni;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tracing::debug;
+
+#[derive(Debug, Default)]
+struct MacIpTable {
+    bindings: HashMap<(Vni, IpAddr), Mac>,
+    vnis: BTreeMap<VrfId, Vni>,
+}
+
+/// A handle to the shared table of EVPN MAC/IP bindings. Cloning the handle does not clone
+/// the table.
+#[derive(Clone, Debug, Default)]
+pub struct MacIpBindings(Arc<Mutex<MacIpTable>>);
+
+impl MacIpBindings {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MacIpTable> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Set the VNIs of the VRFs, to look up the bindings of the requests received on the
+    /// interfaces attached to them
+    pub fn set_vnis(&self, vnis: BTreeMap<VrfId, Vni>) {
+        self.lock().vnis = vnis;
+    }
+
+    /// Add (or update) the binding of `address` to `mac` in `vni`
+    pub fn add(&self, vni: Vni, address: IpAddr, mac: Mac) {
+        if self.lock().bindings.insert((vni, address), mac).is_none() {
+            debug!("Registered MAC/IP binding, vni={vni} ip={address} mac={mac}");
+        }
+    }
+
+    /// Remove the binding of `address` in `vni`. The mac address must match (sanity)
+    pub fn del(&self, vni: Vni, address: IpAddr, mac: Mac) {
+        let mut table = self.lock();
+        if table.bindings.get(&(vni, address)) == Some(&mac) {
+            table.bindings.remove(&(vni, address));
+            debug!("Removed MAC/IP binding, vni={vni} ip={address} mac={mac}");
+        }
+    }
+
+    /// Look up the mac address bound to `address` in the VNI of VRF `vrfid`
+    #[must_use]
+    pub fn lookup(&self, vrfid: VrfId, address: IpAddr) -> Option<Mac> {
+        let table = self.lock();
+        let vni = table.vnis.get(&vrfid)?;
+        table.bindings.get(&(*vni, address)).copied()
+    }
+
+    /// Number of MAC/IP bindings
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().bindings.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().bindings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_macip_bindings() {
+        let bindings = MacIpBindings::new();
+        let vni = Vni::new_checked(3000).unwrap();
+        let address: IpAddr = "192.168.1.10".parse().unwrap();
+        let mac = Mac::from([0x02, 0, 0, 0, 0, 0x10]);
+
+        bindings.add(vni, address, mac);
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings.lookup(1, address), None, "VRF has no vni");
+
+        bindings.clone().set_vnis(BTreeMap::from([(1, vni)]));
+        assert_eq!(bindings.lookup(1, address), Some(mac));
+        assert_eq!(bindings.lookup(2, address), None);
+        assert_eq!(bindings.lookup(1, "192.168.1.11".parse().unwrap()), None);
+
+        // removal requires the mac to match
+        bindings.del(vni, address, Mac::from([0x02, 0, 0, 0, 0, 0x11]));
+        assert_eq!(bindings.lookup(1, address), Some(mac));
+        bindings.del(vni, address, mac);
+        assert!(bindings.is_empty());
+    }
+}
@@ -3,9 +3,11 @@
 
 //! EVPN-related state
 
+pub mod macip;
 pub mod rmac;
 pub mod vtep;
 
+pub use macip::MacIpBindings;
 pub use rmac::RmacEntry;
 pub use rmac::RmacStore;
 pub use vtep::Vtep;
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Selection of the next-hop to forward a packet over, for routes with multiple next-hops (ECMP)
+
+use ahash::AHasher;
+use net::buffer::PacketBufferMut;
+use net::packet::{HashFields, Packet};
+use std::hash::{Hash, Hasher};
+
+use crate::fib::fibgroupstore::FibRoute;
+use crate::fib::fibobjects::FibEntry;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+/// How a [`FibEntry`] is picked out of those of a [`FibRoute`], given the hash of a packet
+pub enum EcmpMode {
+    #[default]
+    /// The hash, modulo the number of entries, is the index of the entry.
+    /// Removing an entry remaps most flows.
+    Modulo,
+    /// Rendezvous hashing: the hash is combined with the next-hop of each entry and the entry
+    /// with the highest score is picked. Removing an entry only remaps the flows that used it,
+    /// and adding one only remaps the flows that now pick it. Entries of weighted groups get
+    /// one score per unit of weight. The cost is linear in the (weighted) number of entries.
+    Consistent,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+/// The ECMP configuration of a [`Fib`]
+///
+/// [`Fib`]: crate::fib::fibtype::Fib
+pub struct EcmpConfig {
+    pub fields: HashFields,
+    pub mode: EcmpMode,
+}
+
+impl EcmpConfig {
+    #[must_use]
+    pub fn new(fields: HashFields, mode: EcmpMode) -> Self {
+        Self { fields, mode }
+    }
+
+    /// Select the [`FibEntry`] of a [`FibRoute`] to forward a [`Packet`] with.
+    /// The route must have at least one entry.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn select<'a, Buf: PacketBufferMut>(
+        &self,
+        route: &'a FibRoute,
+        packet: &Packet<Buf>,
+    ) -> &'a FibEntry {
+        let num_entries = route.len();
+        if num_entries == 1 {
+            return route.get_fibentry(0);
+        }
+        let hash = packet.packet_hash_fields(self.fields);
+        match self.mode {
+            EcmpMode::Modulo => route.get_fibentry((hash % num_entries as u64) as usize),
+            EcmpMode::Consistent => route
+                .iter_weighted()
+                .flat_map(|(group, weight)| {
+                    group
+                        .iter()
+                        .flat_map(move |entry| (0..weight).map(move |replica| (entry, replica)))
+                })
+                .max_by_key(|(entry, replica)| rendezvous_score(hash, entry, *replica))
+                .map(|(entry, _)| entry)
+                .unwrap_or_else(|| unreachable!()),
+        }
+    }
+}
+
+/// Score of (a replica of) an entry for a flow with hash `hash`, for rendezvous hashing
+fn rendezvous_score(hash: u64, entry: &FibEntry, replica: u16) -> u64 {
+    let mut hasher = AHasher::default();
+    hash.hash(&mut hasher);
+    entry.hash_nexthop(&mut hasher);
+    replica.hash(&mut hasher);
+    hasher.finish()
+}
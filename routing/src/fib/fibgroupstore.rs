@@ -20,8 +20,15 @@
 //! something like `Arc<AtomicPtr<FibGroup>>`.
 //!
 //! **NOTE**: Throughout the documentation it is assumed that the structure is wrapped in left-right.
+//!
+//! The same mechanism is used for next-hop groups (see [`NhopGroupStore`]): the (weighted)
+//! `FibGroup`s of a next-hop group are shared by all the `FibRoute`s using it, so that replacing
+//! the members of the group changes all of those routes at once.
+//!
+//! [`NhopGroupStore`]: crate::fib::nhgroup::NhopGroupStore
 
 use crate::fib::fibobjects::{FibEntry, FibGroup};
+use crate::fib::nhgroup::NhopGroupId;
 use crate::rib::nexthop::NhopKey;
 use ahash::RandomState;
 use std::cell::UnsafeCell;
@@ -36,6 +43,12 @@ use tracing::{debug, error, warn};
 pub enum FibError {
     #[error("Failed to find fibgroup for nh key {0:?}")]
     NoFibGroup(NhopKey),
+    #[error("Failed to find next-hop group {0}")]
+    NoNhopGroup(NhopGroupId),
+    #[error("Invalid next-hop group {0}: {1}")]
+    InvalidNhopGroup(NhopGroupId, &'static str),
+    #[error("Next-hop group {0} is in use")]
+    NhopGroupInUse(NhopGroupId),
 }
 
 #[derive(Debug, Default)]
@@ -85,7 +98,7 @@ impl FibGroupStore {
     /// Safety: Only the left-right writer should use this method.
     ////////////////////////////////////////////////////////////////////////////////
     #[must_use]
-    fn get_ref(&self, key: &NhopKey) -> Option<Rc<UnsafeCell<FibGroup>>> {
+    pub(super) fn get_ref(&self, key: &NhopKey) -> Option<Rc<UnsafeCell<FibGroup>>> {
         self.0.get(key).map(|group| Rc::clone(group))
     }
 
@@ -151,39 +164,93 @@ impl FibGroupStore {
     }
 }
 
+/// A shared reference to a `FibGroup`, with the weight of its entries
 #[derive(Debug, Clone)]
-pub struct FibRoute(Vec<Rc<UnsafeCell<FibGroup>>>);
+pub(crate) struct WeightedFibGroup {
+    group: Rc<UnsafeCell<FibGroup>>,
+    weight: u16,
+}
+impl WeightedFibGroup {
+    #[must_use]
+    pub(crate) fn new(group: Rc<UnsafeCell<FibGroup>>, weight: u16) -> Self {
+        Self { group, weight }
+    }
+}
+
+/// The `FibGroup`s of a `FibRoute`: either its own or those of a next-hop group,
+/// shared with the other routes using that group.
+#[derive(Debug, Clone)]
+enum FibRouteGroups {
+    Own(Vec<WeightedFibGroup>),
+    Shared(Rc<UnsafeCell<Vec<WeightedFibGroup>>>),
+}
+
+#[derive(Debug, Clone)]
+pub struct FibRoute(FibRouteGroups);
 impl FibRoute {
     #[must_use]
     pub(crate) fn new() -> Self {
-        Self(vec![])
+        Self(FibRouteGroups::Own(vec![]))
     }
     #[must_use]
     pub fn with_fibgroup(fg_ref: Rc<UnsafeCell<FibGroup>>) -> Self {
-        Self(vec![fg_ref])
+        Self(FibRouteGroups::Own(vec![WeightedFibGroup::new(fg_ref, 1)]))
+    }
+    #[must_use]
+    pub(crate) fn with_nhop_group(nhg_ref: Rc<UnsafeCell<Vec<WeightedFibGroup>>>) -> Self {
+        Self(FibRouteGroups::Shared(nhg_ref))
     }
 
     #[cfg(test)]
     /// Add a reference to a `FibGroup` to a `FibRoute`
     pub(crate) fn add_fibgroup_ref(&mut self, fg_ref: Rc<UnsafeCell<FibGroup>>) {
-        self.0.push(fg_ref);
+        if let FibRouteGroups::Own(groups) = &mut self.0 {
+            groups.push(WeightedFibGroup::new(fg_ref, 1));
+        }
+    }
+
+    #[cfg(test)]
+    /// Remove the last reference to a `FibGroup` from a `FibRoute`
+    pub(crate) fn pop_fibgroup_ref(&mut self) {
+        if let FibRouteGroups::Own(groups) = &mut self.0 {
+            groups.pop();
+        }
+    }
+
+    /// The weighted `FibGroup`s of a `FibRoute`
+    fn groups(&self) -> &[WeightedFibGroup] {
+        match &self.0 {
+            FibRouteGroups::Own(groups) => groups,
+            FibRouteGroups::Shared(groups) => unsafe { &*groups.get() },
+        }
+    }
+
+    /////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Tells if a `FibRoute` uses a next-hop group
+    /////////////////////////////////////////////////////////////////////////////////////////////////
+    #[must_use]
+    pub(crate) fn is_shared(&self) -> bool {
+        matches!(self.0, FibRouteGroups::Shared(_))
     }
 
     /////////////////////////////////////////////////////////////////////////////////////////////////
-    /// Tells the total number of `FibEntry`s in a `FibRoute`, as the sum of the lengths of its `FibGroup`s
+    /// Tells the total number of `FibEntry`s in a `FibRoute`, as the sum of the lengths of its `FibGroup`s.
+    /// Every entry counts as many times as the weight of its `FibGroup`.
     /////////////////////////////////////////////////////////////////////////////////////////////////
     #[must_use]
     pub(crate) fn len(&self) -> usize {
-        self.0
-            .iter()
-            .fold(0, |val, g| unsafe { val + (&*g.get()).len() })
+        self.groups().iter().fold(0, |val, g| unsafe {
+            val + (&*g.group.get()).len() * usize::from(g.weight)
+        })
     }
 
     #[allow(unused)]
     #[must_use]
     pub(crate) fn has_entries(&self) -> bool {
         // empty vector returns false
-        self.0.iter().any(|g| unsafe { !(&*g.get()).is_empty() })
+        self.groups()
+            .iter()
+            .any(|g| unsafe { !(&*g.group.get()).is_empty() })
     }
 
     /////////////////////////////////////////////////////////////////////////////////////////////////
@@ -191,7 +258,7 @@ impl FibRoute {
     /////////////////////////////////////////////////////////////////////////////////////////////////
     #[must_use]
     pub(crate) fn num_groups(&self) -> usize {
-        self.0.len()
+        self.groups().len()
     }
 
     ////////////////////////////////////////////////////////////////////////////////////////////////
@@ -210,6 +277,9 @@ impl FibRoute {
     /// to a real one, in the corresponding fibgroup, as shown in the next example for 4 groups.
     /// 0 1 2 3 4 | 5 6 7 | 8 9 | 10 11 12 | virtual entry indices
     /// 0 1 2 3 4 | 0 1 2 | 0 1 | 0  1  2  | real    entry indices within each group.
+    /// Groups with a weight w > 1 span w times their number of entries, so that each of their
+    /// entries is picked w times as often: with a weight of 2, the group of 2 entries above maps
+    /// virtual indices 8 9 10 11 onto real indices 0 1 0 1.
     ///
     /// Panics:
     ///   This method will panic if index is >= FibRoute::len(). This is so to keep this method
@@ -218,12 +288,13 @@ impl FibRoute {
     #[must_use]
     pub(crate) fn get_fibentry(&self, index: usize) -> &FibEntry {
         let mut index = index;
-        for g in self.0.iter() {
-            let group = unsafe { &*g.get() };
-            if index < group.len() {
-                return &group.entries[index];
+        for g in self.groups() {
+            let group = unsafe { &*g.group.get() };
+            let span = group.len() * usize::from(g.weight);
+            if index < span {
+                return &group.entries[index % group.len()];
             }
-            index -= group.len();
+            index -= span;
         }
         // There cannot exist a route without fib groups.
         // Fibgroups must have at least one entry.
@@ -235,7 +306,14 @@ impl FibRoute {
     /// Provide iterator over the `FibGroups` that a `Fibroute` refers to
     ////////////////////////////////////////////////////////////////////////////////////////////////
     pub(crate) fn iter(&self) -> impl Iterator<Item = &FibGroup> {
-        unsafe { self.0.iter().map(|group| &*group.get()) }
+        self.iter_weighted().map(|(group, _)| group)
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Provide iterator over the `FibGroups` that a `Fibroute` refers to, with their weights
+    ////////////////////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn iter_weighted(&self) -> impl Iterator<Item = (&FibGroup, u16)> {
+        unsafe { self.groups().iter().map(|g| (&*g.group.get(), g.weight)) }
     }
 
     #[must_use]
@@ -244,14 +322,14 @@ impl FibRoute {
     /// Fails if it can't find a FibGroup for any of the `NhopKey`s.
     ///////////////////////////////////////////////////////////////////////////////////
     pub(super) fn from_nhopkeys(store: &FibGroupStore, keys: &[NhopKey]) -> Result<Self, FibError> {
-        let mut route = FibRoute::new();
+        let mut groups = Vec::with_capacity(keys.len());
         for key in keys {
             let fg_ref = store
                 .get_ref(key)
                 .ok_or_else(|| FibError::NoFibGroup(key.clone()))?;
-            route.0.push(fg_ref);
+            groups.push(WeightedFibGroup::new(fg_ref, 1));
         }
-        Ok(route)
+        Ok(FibRoute(FibRouteGroups::Own(groups)))
     }
 }
 
@@ -312,7 +390,7 @@ pub mod tests {
         // create fibroute to have a shared ref to that fibgroup
         let mut fibroute = FibRoute::new();
         fibroute.add_fibgroup_ref(store.get_ref(&nhkey).unwrap());
-        assert_eq!(fibroute.0.len(), 1);
+        assert_eq!(fibroute.num_groups(), 1);
         assert_eq!(fibroute.len(), 1);
 
         // create a second fibroute object sharing the same fibgroup
@@ -378,7 +456,7 @@ pub mod tests {
         fibroute.add_fibgroup_ref(store.get_ref(&key2).unwrap());
         fibroute.add_fibgroup_ref(store.get_ref(&key3).unwrap());
         fibroute.add_fibgroup_ref(store.get_ref(&key4).unwrap());
-        assert_eq!(fibroute.0.len(), 4); // 4 fibgroups
+        assert_eq!(fibroute.num_groups(), 4); // 4 fibgroups
         assert_eq!(fibroute.len(), g1.len() + g2.len() + g3.len() + g4.len());
 
         // select one entry in the fibroute by index and check that it is correct
@@ -402,12 +480,12 @@ pub mod tests {
         assert_eq!(store.purge(), 0);
 
         // remove last fibgroup from route and remove it: should be removed
-        fibroute.0.pop();
+        fibroute.pop_fibgroup_ref();
         store.del(&key4);
         assert_eq!(store.len(), (4 + 1) - 1);
 
         // remove another one
-        fibroute.0.pop();
+        fibroute.pop_fibgroup_ref();
         assert_eq!(store.purge(), 1); // one should be purged
 
         // drop the route
@@ -459,11 +537,12 @@ pub mod tests {
         )
         .unwrap();
 
-        assert_eq!(fibroute.0.len(), 4);
-        assert_eq!(unsafe { &*fibroute.0[0].get() }, store.get(&key1).unwrap());
-        assert_eq!(unsafe { &*fibroute.0[1].get() }, store.get(&key2).unwrap());
-        assert_eq!(unsafe { &*fibroute.0[2].get() }, store.get(&key3).unwrap());
-        assert_eq!(unsafe { &*fibroute.0[3].get() }, store.get(&key4).unwrap());
+        assert_eq!(fibroute.num_groups(), 4);
+        let groups: Vec<&FibGroup> = fibroute.iter().collect();
+        assert_eq!(groups[0], store.get(&key1).unwrap());
+        assert_eq!(groups[1], store.get(&key2).unwrap());
+        assert_eq!(groups[2], store.get(&key3).unwrap());
+        assert_eq!(groups[3], store.get(&key4).unwrap());
 
         println!("{fibroute:#?}");
 
@@ -7,6 +7,7 @@ use net::vxlan::Vni;
 
 use crate::rib::encapsulation::Encapsulation;
 use net::interface::InterfaceIndex;
+use std::hash::{Hash, Hasher};
 use std::net::IpAddr;
 
 #[derive(Debug, Default, Clone, Ord, PartialOrd, Eq, PartialEq)]
@@ -207,6 +208,34 @@ impl FibEntry {
         }
         None
     }
+    /// Hash the fields of this entry that identify where it sends packets: interfaces, next-hop
+    /// addresses and encapsulations. Unlike a full hash, this is stable across updates that do
+    /// not change the next-hop, like the resolution of the destination MAC of a vxlan encap.
+    pub(crate) fn hash_nexthop<H: Hasher>(&self, state: &mut H) {
+        for inst in &self.instructions {
+            match inst {
+                PktInstruction::Drop => 0u8.hash(state),
+                PktInstruction::Local(ifindex) => {
+                    1u8.hash(state);
+                    ifindex.hash(state);
+                }
+                PktInstruction::Encap(Encapsulation::Vxlan(vxlan)) => {
+                    2u8.hash(state);
+                    vxlan.vni.hash(state);
+                    vxlan.remote.hash(state);
+                }
+                PktInstruction::Encap(Encapsulation::Mpls(label)) => {
+                    3u8.hash(state);
+                    label.hash(state);
+                }
+                PktInstruction::Egress(egress) => {
+                    4u8.hash(state);
+                    egress.ifindex.hash(state);
+                    egress.address.hash(state);
+                }
+            }
+        }
+    }
     pub fn is_vxlan_with_vni(&self, vni: Vni) -> bool {
         for inst in &self.instructions {
             match inst {
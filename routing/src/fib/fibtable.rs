@@ -10,64 +10,50 @@ use crate::{
 };
 
 use left_right::{Absorb, ReadGuard, ReadHandle, ReadHandleFactory, WriteHandle};
+use left_right_tlcache::make_readhandle_provider;
 use net::vxlan::Vni;
-use std::collections::BTreeMap;
 use std::rc::Rc;
-use std::sync::Arc;
 #[allow(unused)]
 use tracing::{debug, error, info, warn};
 
-#[derive(Debug)]
-struct FibTableEntry {
-    id: FibKey,
-    factory: FibReaderFactory,
-}
-impl FibTableEntry {
-    const fn new(id: FibKey, factory: FibReaderFactory) -> Self {
-        Self { id, factory }
-    }
-}
-
-#[derive(Default, Debug)]
-pub struct FibTable {
-    version: u64,
-    entries: BTreeMap<FibKey, Arc<FibTableEntry>>,
-}
+// The registry only keeps factories, which are Sync even if Fibs are not (they use Rc internally)
+make_readhandle_provider!(pub FibTable, FibKey, Fib);
 
 impl FibTable {
     /// Register a `Fib` by adding a `FibReaderFactory` for it
     fn add_fib(&mut self, id: FibKey, factory: FibReaderFactory) {
         info!("Registering Fib with id {id} in the FibTable");
-        self.entries
-            .insert(id, Arc::new(FibTableEntry::new(id, factory)));
+        if let Err(e) = self.add_factory(id, factory.0) {
+            error!("Failed to register Fib with id {id}: {e}");
+        }
     }
-    /// Delete a `Fib`, by unregistering a `FibReaderFactory` for it
+    /// Delete a `Fib`, by unregistering a `FibReaderFactory` for it, along with its aliases
     fn del_fib(&mut self, id: &FibKey) {
         info!("Unregistering Fib with id {id} from the FibTable");
-        self.entries.remove(id);
+        if let Err(e) = self.remove(id) {
+            warn!("Failed to unregister Fib with id {id}: {e}");
+        }
     }
     /// Register an existing `Fib` with a given [`Vni`].
     /// This allows looking up a Fib (`FibReaderFactory`) from a [`Vni`]
     fn register_by_vni(&mut self, id: &FibKey, vni: Vni) {
-        if let Some(entry) = self.get_entry(id) {
-            self.entries
-                .insert(FibKey::from_vni(vni), Arc::clone(entry));
-            info!("Registering Fib with id {id} in the FibTable with vni {vni}");
-        } else {
-            error!("Failed to register Fib {id} with vni {vni}: no fib with id {id} found");
+        let key = FibKey::from_vni(vni);
+        // a vni may be re-assigned to another fib
+        if self.identity(&key).is_some_and(|current| current != id) {
+            self.unregister_vni(vni);
+        }
+        match self.add_alias(key, id) {
+            Ok(()) => info!("Registering Fib with id {id} in the FibTable with vni {vni}"),
+            Err(ReadHandleRegistryError::KeyInUse(_)) => {}
+            Err(e) => error!("Failed to register Fib {id} with vni {vni}: {e}"),
         }
     }
     /// Remove any entry keyed by a [`Vni`]
     fn unregister_vni(&mut self, vni: Vni) {
         let key = FibKey::from_vni(vni);
-        info!("Unregistered key = {key} from the FibTable");
-        self.entries.remove(&key);
-    }
-
-    /// Get the entry for the fib with the given [`FibKey`]
-    #[must_use]
-    fn get_entry(&self, key: &FibKey) -> Option<&Arc<FibTableEntry>> {
-        self.entries.get(key)
+        if self.remove_alias(&key).is_ok() {
+            info!("Unregistered key = {key} from the FibTable");
+        }
     }
 
     /// Get a [`FibReader`] for the fib with the given [`FibKey`]. This method should only
@@ -75,14 +61,8 @@ impl FibTable {
     #[must_use]
     #[cfg(test)]
     pub fn get_fib(&self, key: &FibKey) -> Option<FibReader> {
-        self.get_entry(key).map(|entry| entry.factory.handle())
-    }
-
-    /// Number of entries in this table
-    #[must_use]
-    #[cfg(test)]
-    pub(crate) fn len(&self) -> usize {
-        self.entries.len()
+        self.factory(key)
+            .map(|factory| FibReader::new(factory.handle()))
     }
 }
 
@@ -95,7 +75,6 @@ enum FibTableChange {
 
 impl Absorb<FibTableChange> for FibTable {
     fn absorb_first(&mut self, change: &mut FibTableChange, _: &Self) {
-        self.version = self.version.wrapping_add(1);
         match change {
             FibTableChange::Add((id, factory)) => self.add_fib(*id, factory.clone()),
             FibTableChange::Del(id) => self.del_fib(id),
@@ -184,38 +163,16 @@ unsafe impl Send for FibTableWriter {}
 // declare thread-local cache for fibtable
 use crate::fib::fibtype::Fib;
 use left_right_tlcache::make_thread_local_readhandle_cache;
-use left_right_tlcache::{ReadHandleCache, ReadHandleProvider};
-make_thread_local_readhandle_cache!(FIBTABLE_CACHE, FibKey, Fib);
-
-impl ReadHandleProvider for FibTable {
-    type Data = Fib;
-    type Key = FibKey;
-    fn get_factory(
-        &self,
-        key: &Self::Key,
-    ) -> Option<(&ReadHandleFactory<Self::Data>, Self::Key, u64)> {
-        let entry = self.get_entry(key)?.as_ref();
-        let factory = entry.factory.as_ref();
-        Some((factory, entry.id, self.version))
-    }
-    fn get_version(&self) -> u64 {
-        self.version
-    }
-    fn get_identity(&self, key: &Self::Key) -> Option<Self::Key> {
-        self.get_entry(key).map(|entry| entry.id)
-    }
-    fn get_iter(
-        &self,
-    ) -> (
-        u64,
-        impl Iterator<Item = (FibKey, &ReadHandleFactory<Fib>, FibKey)>,
-    ) {
-        let iter = self
-            .entries
-            .iter()
-            .map(|(key, entry)| (*key, &*entry.factory.as_ref(), entry.as_ref().id));
-        (self.version, iter)
-    }
+use left_right_tlcache::{
+    ReadHandleCache, ReadHandleCacheStats, ReadHandleRegistryError, RefreshPolicy,
+};
+// fibs (and their aliases) only change on configuration changes: refresh on version change
+make_thread_local_readhandle_cache!(FIBTABLE_CACHE, FibKey, Fib, RefreshPolicy::OnVersionChange);
+
+/// Get the counters of the thread-local fib caches, for every thread that used them.
+#[must_use]
+pub fn fibtable_cache_stats() -> Vec<(String, ReadHandleCacheStats)> {
+    ReadHandleCache::thread_stats(&FIBTABLE_CACHE)
 }
 
 impl FibTableReader {
@@ -12,14 +12,17 @@ use std::rc::Rc;
 use std::{hash::Hash, sync::atomic::AtomicBool};
 
 use lpm::prefix::{Ipv4Prefix, Ipv6Prefix, Prefix};
-use lpm::trie::{PrefixMapTrie, TrieMap, TrieMapFactory};
+use lpm::trie::{LOOKUP_BATCH, PrefixMapTrie, TrieMap, TrieMapFactory};
+use lpm::trie::{lookup_batch_with, prefetch};
 use net::buffer::PacketBufferMut;
 use net::packet::Packet;
 use net::vxlan::Vni;
 
 use crate::evpn::Vtep;
+use crate::fib::ecmp::EcmpConfig;
 use crate::fib::fibgroupstore::{FibGroupStore, FibRoute};
 use crate::fib::fibobjects::{FibEntry, FibGroup};
+use crate::fib::nhgroup::{NhopGroup, NhopGroupId, NhopGroupStore};
 use crate::rib::nexthop::NhopKey;
 use crate::rib::vrf::VrfId;
 
@@ -58,7 +61,9 @@ pub struct Fib {
     routesv4: PrefixMapTrie<Ipv4Prefix, FibRoute>,
     routesv6: PrefixMapTrie<Ipv6Prefix, FibRoute>,
     groupstore: FibGroupStore,
+    nhgroups: NhopGroupStore,
     vtep: Vtep,
+    ecmp: EcmpConfig,
     valid: AtomicBool,
 }
 impl Hash for Fib {
@@ -82,7 +87,9 @@ impl Default for Fib {
             routesv4: PrefixMapTrie::create(),
             routesv6: PrefixMapTrie::create(),
             groupstore: FibGroupStore::new(),
+            nhgroups: NhopGroupStore::new(),
             vtep: Vtep::new(),
+            ecmp: EcmpConfig::default(),
             valid: AtomicBool::new(true),
         };
         // default route
@@ -93,6 +100,9 @@ impl Default for Fib {
     }
 }
 
+/// Maximum number of destinations sorted together by [`Fib::lpm_batch`]
+pub const FIB_LOOKUP_BATCH: usize = LOOKUP_BATCH;
+
 pub type FibRouteV4Filter = Box<dyn Fn(&(&Ipv4Prefix, &FibRoute)) -> bool>;
 pub type FibRouteV6Filter = Box<dyn Fn(&(&Ipv6Prefix, &FibRoute)) -> bool>;
 
@@ -138,6 +148,33 @@ impl Fib {
         }
     }
 
+    /// Add a [`FibRoute`] using the next-hop group with the given id
+    fn add_fibroute_nhop_group(&mut self, prefix: Prefix, id: NhopGroupId) {
+        match self.nhgroups.get_ref(id) {
+            Some(nhg_ref) => {
+                self.add_fibroute(prefix, FibRoute::with_nhop_group(nhg_ref));
+            }
+            None => error!("Failed to build fibroute for {prefix}: no next-hop group {id}"),
+        }
+    }
+
+    /// Add a next-hop group or replace the members of an existing one
+    fn add_nhop_group(&mut self, id: NhopGroupId, group: &NhopGroup) {
+        if let Err(e) = self.nhgroups.add_mod(id, group, &self.groupstore) {
+            error!("Failed to add next-hop group {id}: {e}");
+        }
+    }
+
+    /// Delete a next-hop group
+    fn del_nhop_group(&mut self, id: NhopGroupId) {
+        match self.nhgroups.del(id) {
+            Ok(()) => {
+                self.groupstore.purge();
+            }
+            Err(e) => error!("Failed to delete next-hop group {id}: {e}"),
+        }
+    }
+
     /// Delete the [`FibRoute`] for a prefix
     fn del_fibroute(&mut self, prefix: Prefix) {
         let removed = match prefix {
@@ -191,6 +228,18 @@ impl Fib {
         &self.vtep
     }
 
+    /// Set the [`EcmpConfig`] for this [`Fib`]
+    fn set_ecmp(&mut self, ecmp: EcmpConfig) {
+        self.ecmp = ecmp;
+        info!("ECMP for fib {} set to {ecmp:?}", self.get_id());
+    }
+
+    /// Get the [`EcmpConfig`] for this [`Fib`]
+    #[must_use]
+    pub fn get_ecmp(&self) -> &EcmpConfig {
+        &self.ecmp
+    }
+
     /// Tell the number of IPv4 routes in this [`Fib`]
     #[must_use]
     pub fn len_v4(&self) -> usize {
@@ -209,6 +258,12 @@ impl Fib {
         self.groupstore.len()
     }
 
+    /// Tell the number of next-hop groups in this [`Fib`]
+    #[must_use]
+    pub fn len_nhop_groups(&self) -> usize {
+        self.nhgroups.len()
+    }
+
     /// Iterate over IPv4 routes/entries
     pub fn iter_v4(&self) -> impl Iterator<Item = (&Ipv4Prefix, &FibRoute)> {
         self.routesv4.iter()
@@ -251,6 +306,19 @@ impl Fib {
         }
     }
 
+    /// Do lpm lookups for a batch of destinations, appending the prefix hit and the [`FibRoute`]
+    /// of each to `results`, in the order of `targets`. Destinations are looked up in address
+    /// order, in chunks of up to [`FIB_LOOKUP_BATCH`], so that consecutive lookups walk mostly
+    /// the same trie nodes, which remain in cache, and the [`FibRoute`]s hit are prefetched.
+    /// Repeated destinations (e.g. packets of the same flow) are only looked up once.
+    pub fn lpm_batch<'a>(&'a self, targets: &[IpAddr], results: &mut Vec<(Prefix, &'a FibRoute)>) {
+        lookup_batch_with(targets, results, |target| {
+            let hit = self.lpm_with_prefix(&target);
+            prefetch(hit.1);
+            hit
+        });
+    }
+
     /// Identical to `lpm_with_prefix`, but without reporting the prefix hit
     #[must_use]
     pub fn lpm(&self, target: &IpAddr) -> &FibRoute {
@@ -261,30 +329,36 @@ impl Fib {
     /// Given a [`Packet`], uses [`Self::lpm()`] to retrieve the [`FibRoute`] to forward a packet.
     /// However, instead of returning the entire [`FibRoute`], returns a single [`FibEntry`] out of
     /// those in the `FibGroup`s that make up the [`FibRoute`]. The entry selected is chosen by
-    /// computing a hash on the invariant header fields of the IP and L4 headers.
-    #[allow(clippy::cast_possible_truncation)]
+    /// computing a hash on the invariant header fields of the IP and L4 headers, as set in the
+    /// [`EcmpConfig`] of this [`Fib`].
     pub fn lpm_entry_prefix<Buf: PacketBufferMut>(
         &self,
         packet: &Packet<Buf>,
     ) -> (Prefix, &FibEntry) {
         if let Some(destination) = packet.ip_destination() {
             let (prefix, route) = self.lpm_with_prefix(&destination);
-            let num_entries = route.len();
-            if num_entries == 0 {
-                let bad = "Warning, hit route without fibgroups/entries. This is a bug.";
-                warn!("{bad}");
-                panic!("{bad}");
-            }
-            let mut entry_index = 0;
-            if num_entries > 1 {
-                entry_index = packet.packet_hash_ecmp(0, (num_entries - 1) as u8);
-            }
-            (prefix, route.get_fibentry(entry_index as usize))
+            (prefix, self.select_entry(route, packet))
         } else {
             error!("Failed to get destination IP address!");
             unreachable!()
         }
     }
+
+    /// Select the [`FibEntry`] of a [`FibRoute`] of this [`Fib`] to forward a [`Packet`], as
+    /// [`Self::lpm_entry_prefix`] does. This is meant to be used with the routes returned by
+    /// [`Self::lpm_batch`].
+    pub fn select_entry<'a, Buf: PacketBufferMut>(
+        &self,
+        route: &'a FibRoute,
+        packet: &Packet<Buf>,
+    ) -> &'a FibEntry {
+        if route.len() == 0 {
+            let bad = "Warning, hit route without fibgroups/entries. This is a bug.";
+            warn!("{bad}");
+            panic!("{bad}");
+        }
+        self.ecmp.select(route, packet)
+    }
 }
 
 #[derive(Debug)]
@@ -292,8 +366,12 @@ enum FibChange {
     RegisterFibGroup((NhopKey, FibGroup)),
     UnregisterFibGroup(NhopKey),
     AddFibRoute((Prefix, Vec<NhopKey>)),
+    AddFibRouteNhopGroup((Prefix, NhopGroupId)),
     DelFibRoute(Prefix),
+    AddNhopGroup((NhopGroupId, NhopGroup)),
+    DelNhopGroup(NhopGroupId),
     SetVtep(Vtep),
+    SetEcmp(EcmpConfig),
     Invalidate,
 }
 
@@ -307,8 +385,14 @@ impl Absorb<FibChange> for Fib {
                 self.groupstore.del(key);
             }
             FibChange::AddFibRoute((prefix, keys)) => self.build_add_fibroute(*prefix, keys),
+            FibChange::AddFibRouteNhopGroup((prefix, id)) => {
+                self.add_fibroute_nhop_group(*prefix, *id);
+            }
             FibChange::DelFibRoute(prefix) => self.del_fibroute(*prefix),
+            FibChange::AddNhopGroup((id, group)) => self.add_nhop_group(*id, group),
+            FibChange::DelNhopGroup(id) => self.del_nhop_group(*id),
             FibChange::SetVtep(vtep) => self.set_vtep(vtep),
+            FibChange::SetEcmp(ecmp) => self.set_ecmp(*ecmp),
             FibChange::Invalidate => {
                 self.valid.store(false, std::sync::atomic::Ordering::SeqCst);
             }
@@ -366,9 +450,38 @@ impl FibWriter {
             self.0.publish();
         }
     }
-    pub fn del_fibroute(&mut self, prefix: Prefix) {
+    /// Add a route to `prefix` using the next-hop group `id`, which must exist
+    pub fn add_fibroute_nhop_group(&mut self, prefix: Prefix, id: NhopGroupId, publish: bool) {
+        self.0.append(FibChange::AddFibRouteNhopGroup((prefix, id)));
+        if publish {
+            self.0.publish();
+        }
+    }
+    /// Add a next-hop group, or replace the next-hops of an existing one. The fibgroups
+    /// of the next-hops must have been registered. Replacing a group updates all the
+    /// routes that use it at once.
+    pub fn add_nhop_group(&mut self, id: NhopGroupId, group: &NhopGroup, publish: bool) {
+        if group.is_empty() {
+            error!("Rejected next-hop group {id}: no next-hops provided");
+            return;
+        }
+        self.0.append(FibChange::AddNhopGroup((id, group.clone())));
+        if publish {
+            self.0.publish();
+        }
+    }
+    /// Delete a next-hop group. Groups still in use by some route are not deleted.
+    pub fn del_nhop_group(&mut self, id: NhopGroupId, publish: bool) {
+        self.0.append(FibChange::DelNhopGroup(id));
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn del_fibroute(&mut self, prefix: Prefix, publish: bool) {
         self.0.append(FibChange::DelFibRoute(prefix));
-        self.0.publish();
+        if publish {
+            self.0.publish();
+        }
     }
     pub fn set_vtep(&mut self, vtep: Vtep) {
         self.0.append(FibChange::SetVtep(vtep));
@@ -378,6 +491,14 @@ impl FibWriter {
         let fib = self.enter().unwrap_or_else(|| unreachable!());
         fib.vtep.clone()
     }
+    pub fn set_ecmp(&mut self, ecmp: EcmpConfig) {
+        self.0.append(FibChange::SetEcmp(ecmp));
+        self.0.publish();
+    }
+    pub fn get_ecmp(&self) -> EcmpConfig {
+        let fib = self.enter().unwrap_or_else(|| unreachable!());
+        fib.ecmp
+    }
     pub fn publish(&mut self) {
         self.0.publish();
     }
@@ -3,10 +3,14 @@
 
 //! The Fib module
 
+pub mod ecmp;
 pub mod fibgroupstore;
 pub mod fibobjects;
 pub mod fibtable;
 pub mod fibtype;
+pub mod nhgroup;
+pub mod urpf;
+
 mod test;
 
 use tracectl::trace_target;
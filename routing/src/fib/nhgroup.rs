@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Next-hop groups. A next-hop group is a set of next-hops, each with a weight, identified
+//! by a [`NhopGroupId`]. Routes may refer to a next-hop group instead of providing their
+//! next-hops. All the routes using a group share it: this saves memory and allows changing
+//! the next-hops of all of them at once (and atomically for readers) by replacing the group.
+//! Next-hops with a higher weight get a proportionally larger share of the traffic.
+
+use crate::fib::fibgroupstore::{FibError, FibGroupStore, WeightedFibGroup};
+use crate::rib::nexthop::NhopKey;
+use ahash::RandomState;
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[allow(unused)]
+use tracing::{debug, error, warn};
+
+/// Every next-hop group is identified by a numerical id
+pub type NhopGroupId = u32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A member of a [`NhopGroup`]: a next-hop and its weight
+pub struct NhopGroupMember {
+    pub key: NhopKey,
+    pub weight: u16,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// A next-hop group: a set of weighted next-hops, which must have registered fibgroups
+pub struct NhopGroup {
+    members: Vec<NhopGroupMember>,
+}
+
+impl NhopGroup {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a next-hop with some weight to a [`NhopGroup`]. Weights must be non-zero.
+    pub fn add(&mut self, key: NhopKey, weight: u16) {
+        self.members.push(NhopGroupMember { key, weight });
+    }
+    #[must_use]
+    pub fn members(&self) -> &[NhopGroupMember] {
+        &self.members
+    }
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// The weights of the members of a [`NhopGroup`], divided by their greatest common divisor.
+    /// This keeps the relative weights while lowering the cost of weighted consistent hashing.
+    fn reduced_weights(&self) -> impl Iterator<Item = u16> + '_ {
+        let gcd = self
+            .members
+            .iter()
+            .fold(0, |gcd, member| gcd_u16(gcd, member.weight));
+        self.members.iter().map(move |member| member.weight / gcd)
+    }
+}
+
+fn gcd_u16(a: u16, b: u16) -> u16 {
+    if b == 0 { a } else { gcd_u16(b, a % b) }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// A store of next-hop groups. The weighted `FibGroup`s of each group are behind
+/// an `Rc<UnsafeCell>` shared with the `FibRoute`s using the group. As for the
+/// `FibGroupStore`, this is only sound if wrapped in left-right.
+////////////////////////////////////////////////////////////////////////////////
+#[derive(Debug, Default)]
+pub(crate) struct NhopGroupStore(
+    HashMap<NhopGroupId, Rc<UnsafeCell<Vec<WeightedFibGroup>>>, RandomState>,
+);
+
+impl NhopGroupStore {
+    #[must_use]
+    pub(crate) fn new() -> Self {
+        Self(HashMap::with_hasher(RandomState::with_seed(0)))
+    }
+    #[must_use]
+    #[allow(clippy::len_without_is_empty)]
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    /// Add a next-hop group or replace the members of an existing one. Replacing a
+    /// group changes all the routes using it. Fails without changing anything if a
+    /// member has a zero weight or no registered fibgroup.
+    /// Safety: Only the left-right writer should use this method.
+    ////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn add_mod(
+        &mut self,
+        id: NhopGroupId,
+        group: &NhopGroup,
+        fibgroups: &FibGroupStore,
+    ) -> Result<(), FibError> {
+        if group.is_empty() {
+            return Err(FibError::InvalidNhopGroup(id, "no next-hops"));
+        }
+        if group.members.iter().any(|member| member.weight == 0) {
+            return Err(FibError::InvalidNhopGroup(id, "zero weight"));
+        }
+        let mut members = Vec::with_capacity(group.len());
+        for (member, weight) in group.members.iter().zip(group.reduced_weights()) {
+            let fg_ref = fibgroups
+                .get_ref(&member.key)
+                .ok_or_else(|| FibError::NoFibGroup(member.key.clone()))?;
+            members.push(WeightedFibGroup::new(fg_ref, weight));
+        }
+        if let Some(shared) = self.0.get(&id) {
+            unsafe {
+                *shared.get() = members;
+            }
+        } else {
+            self.0.insert(id, Rc::new(UnsafeCell::new(members)));
+        }
+        Ok(())
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    /// Get a refcounted reference to the members of a next-hop group, for a `FibRoute`
+    /// to use it.
+    /// Safety: Only the left-right writer should use this method.
+    ////////////////////////////////////////////////////////////////////////////////
+    #[must_use]
+    pub(crate) fn get_ref(&self, id: NhopGroupId) -> Option<Rc<UnsafeCell<Vec<WeightedFibGroup>>>> {
+        self.0.get(&id).map(Rc::clone)
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    /// Remove a next-hop group. Groups still used by some route can't be removed.
+    /// Safety: Only the left-right writer should use this method.
+    ////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn del(&mut self, id: NhopGroupId) -> Result<(), FibError> {
+        match self.0.get(&id) {
+            None => Err(FibError::NoNhopGroup(id)),
+            Some(shared) if Rc::strong_count(shared) > 1 => Err(FibError::NhopGroupInUse(id)),
+            Some(_) => {
+                self.0.remove(&id);
+                debug!("Deleted next-hop group {id}");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fib::fibgroupstore::FibRoute;
+    use crate::fib::fibgroupstore::tests::{build_fib_entry_egress, build_fibgroup};
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    fn key(address: &str) -> NhopKey {
+        NhopKey::with_address(&IpAddr::from_str(address).unwrap())
+    }
+
+    #[test]
+    fn test_nhop_group_weights() {
+        let mut fibgroups = FibGroupStore::new();
+        let e1 = build_fib_entry_egress(1, "10.0.1.1", "eth1");
+        let e2 = build_fib_entry_egress(2, "10.0.2.1", "eth2");
+        fibgroups.add_mod_group(&key("8.0.0.1"), build_fibgroup(&[e1.clone()]));
+        fibgroups.add_mod_group(&key("8.0.0.2"), build_fibgroup(&[e2.clone()]));
+
+        // weights 20 and 60 are reduced to 1 and 3
+        let mut group = NhopGroup::new();
+        group.add(key("8.0.0.1"), 20);
+        group.add(key("8.0.0.2"), 60);
+        let mut store = NhopGroupStore::new();
+        store.add_mod(1, &group, &fibgroups).unwrap();
+
+        let route = FibRoute::with_nhop_group(store.get_ref(1).unwrap());
+        assert!(route.is_shared());
+        assert_eq!(route.num_groups(), 2);
+        assert_eq!(route.len(), 4);
+        assert_eq!(route.get_fibentry(0), &e1);
+        for index in 1..4 {
+            assert_eq!(route.get_fibentry(index), &e2);
+        }
+
+        // invalid groups are rejected and leave the existing group untouched
+        let mut bad = NhopGroup::new();
+        bad.add(key("8.0.0.1"), 0);
+        assert_eq!(
+            store.add_mod(1, &bad, &fibgroups),
+            Err(FibError::InvalidNhopGroup(1, "zero weight"))
+        );
+        let mut bad = NhopGroup::new();
+        bad.add(key("8.0.0.3"), 1);
+        assert_eq!(
+            store.add_mod(1, &bad, &fibgroups),
+            Err(FibError::NoFibGroup(key("8.0.0.3")))
+        );
+        assert_eq!(route.len(), 4);
+    }
+
+    #[test]
+    fn test_nhop_group_replace_and_delete() {
+        let mut fibgroups = FibGroupStore::new();
+        let e1 = build_fib_entry_egress(1, "10.0.1.1", "eth1");
+        let e2 = build_fib_entry_egress(2, "10.0.2.1", "eth2");
+        fibgroups.add_mod_group(&key("8.0.0.1"), build_fibgroup(&[e1.clone()]));
+        fibgroups.add_mod_group(&key("8.0.0.2"), build_fibgroup(&[e2.clone()]));
+
+        let mut group = NhopGroup::new();
+        group.add(key("8.0.0.1"), 1);
+        let mut store = NhopGroupStore::new();
+        store.add_mod(7, &group, &fibgroups).unwrap();
+
+        // two routes sharing the group
+        let route1 = FibRoute::with_nhop_group(store.get_ref(7).unwrap());
+        let route2 = FibRoute::with_nhop_group(store.get_ref(7).unwrap());
+        assert_eq!(route1.get_fibentry(0), &e1);
+
+        // replacing the group changes both routes
+        let mut group = NhopGroup::new();
+        group.add(key("8.0.0.2"), 1);
+        store.add_mod(7, &group, &fibgroups).unwrap();
+        assert_eq!(route1.get_fibentry(0), &e2);
+        assert_eq!(route2.get_fibentry(0), &e2);
+
+        // the group can only be removed once unused
+        assert_eq!(store.del(7), Err(FibError::NhopGroupInUse(7)));
+        drop(route1);
+        drop(route2);
+        assert_eq!(store.del(7), Ok(()));
+        assert_eq!(store.len(), 0);
+        assert_eq!(store.del(7), Err(FibError::NoNhopGroup(7)));
+    }
+}
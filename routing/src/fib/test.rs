@@ -8,17 +8,20 @@ use concurrency::concurrency_mode;
 
 #[concurrency_mode(std)]
 mod tests {
+    use crate::fib::ecmp::{EcmpConfig, EcmpMode};
     use crate::fib::fibobjects::FibEntry;
     use crate::fib::fibobjects::FibGroup;
     use crate::fib::fibobjects::PktInstruction;
     use crate::fib::fibtable::FibTableWriter;
     use crate::fib::fibtype::FibKey;
     use crate::fib::fibtype::FibWriter;
+    use crate::fib::nhgroup::NhopGroup;
+    use crate::fib::urpf::UrpfMode;
     use crate::rib::nexthop::NhopKey;
 
     use net::ip::NextHeader;
-    use net::packet::Packet;
-    use net::packet::test_utils::build_test_ipv4_packet_with_transport;
+    use net::packet::test_utils::{build_test_ipv4_packet_with_transport, build_test_ipv6_packet};
+    use net::packet::{HashFields, Packet};
     use net::udp::UdpPort;
     use net::{buffer::TestBuffer, interface::InterfaceIndex};
 
@@ -105,6 +108,309 @@ mod tests {
             .unwrap();
     }
 
+    // Forward packets for flows with udp destination ports 1..=1000 and tell the interface each
+    // flow is sent over
+    fn ecmp_flows(fibw: &FibWriter) -> Vec<InterfaceIndex> {
+        let mut packet = test_packet();
+        let fib = fibw.enter().unwrap();
+        (1..=1000)
+            .map(|port| {
+                packet
+                    .set_udp_destination_port(UdpPort::new_checked(port).unwrap())
+                    .unwrap();
+                let (_, entry) = fib.lpm_entry_prefix(&packet);
+                get_entry_interface_index(entry)
+            })
+            .collect()
+    }
+
+    // Build a fib with a route with 4 next-hops, forward flows, remove the next-hop over eth2
+    // and forward the same flows again. Tell the flows before and after the removal.
+    fn ecmp_remove_member(ecmp: EcmpConfig) -> (Vec<InterfaceIndex>, Vec<InterfaceIndex>) {
+        let (mut fibw, _fibr) = FibWriter::new(FibKey::Id(0));
+        fibw.set_ecmp(ecmp);
+        let prefix = Prefix::from("192.168.1.0/24");
+        let nhkey = NhopKey::with_address(&IpAddr::from_str("7.0.0.1").unwrap());
+        let e1 = build_fib_entry_egress(1, "10.0.1.1", "eth1");
+        let e2 = build_fib_entry_egress(2, "10.0.2.1", "eth2");
+        let e3 = build_fib_entry_egress(3, "10.0.3.1", "eth3");
+        let e4 = build_fib_entry_egress(4, "10.0.4.1", "eth4");
+
+        let fibgroup = build_fibgroup(&[e1.clone(), e2, e3.clone(), e4.clone()]);
+        fibw.register_fibgroup(&nhkey, &fibgroup, false);
+        fibw.add_fibroute(prefix, vec![nhkey.clone()], true);
+        let before = ecmp_flows(&fibw);
+
+        let fibgroup = build_fibgroup(&[e1, e3, e4]);
+        fibw.register_fibgroup(&nhkey, &fibgroup, true);
+        let after = ecmp_flows(&fibw);
+        (before, after)
+    }
+
+    #[test]
+    fn test_ecmp_consistent_hashing() {
+        let removed = InterfaceIndex::try_new(2).unwrap();
+        let ecmp = EcmpConfig::new(HashFields::FIVE_TUPLE, EcmpMode::Consistent);
+        let (before, after) = ecmp_remove_member(ecmp);
+
+        // all next-hops get some flows
+        for ifindex in 1..=4 {
+            let ifindex = InterfaceIndex::try_new(ifindex).unwrap();
+            assert!(before.contains(&ifindex));
+        }
+        // only the flows over the removed next-hop are remapped
+        for (b, a) in before.iter().zip(after.iter()) {
+            assert_ne!(*a, removed);
+            if *b != removed {
+                assert_eq!(a, b);
+            }
+        }
+
+        // with modulo hashing, many more flows are remapped
+        let (before_mod, after_mod) = ecmp_remove_member(EcmpConfig::default());
+        let moved = |before: &[InterfaceIndex], after: &[InterfaceIndex]| {
+            before.iter().zip(after).filter(|(b, a)| b != a).count()
+        };
+        let moved_consistent = moved(&before, &after);
+        assert_eq!(
+            moved_consistent,
+            before.iter().filter(|i| **i == removed).count()
+        );
+        assert!(moved(&before_mod, &after_mod) > moved_consistent);
+    }
+
+    #[test]
+    fn test_ecmp_hash_fields() {
+        // flows only differ by their ports: with a 3-tuple hash, they all use the same next-hop
+        let ecmp = EcmpConfig::new(HashFields::THREE_TUPLE, EcmpMode::Consistent);
+        let (before, after) = ecmp_remove_member(ecmp);
+        assert!(before.iter().all(|ifindex| *ifindex == before[0]));
+        assert!(after.iter().all(|ifindex| *ifindex == after[0]));
+
+        // with a 5-tuple hash, they are spread
+        let (before, _) = ecmp_remove_member(EcmpConfig::default());
+        assert!(before.iter().any(|ifindex| *ifindex != before[0]));
+    }
+
+    #[test]
+    fn test_fib_nhop_groups() {
+        let (mut fibw, fibr) = FibWriter::new(FibKey::Id(0));
+        fibw.set_ecmp(EcmpConfig::new(
+            HashFields::FIVE_TUPLE,
+            EcmpMode::Consistent,
+        ));
+        let key1 = NhopKey::with_address(&IpAddr::from_str("7.0.0.1").unwrap());
+        let key2 = NhopKey::with_address(&IpAddr::from_str("7.0.0.2").unwrap());
+        let e1 = build_fib_entry_egress(1, "10.0.1.1", "eth1");
+        let e2 = build_fib_entry_egress(2, "10.0.2.1", "eth2");
+        fibw.register_fibgroup(&key1, &build_fibgroup(&[e1]), false);
+        fibw.register_fibgroup(&key2, &build_fibgroup(&[e2.clone()]), false);
+
+        // two prefixes share a group where the second next-hop weighs 3 times the first
+        let mut group = NhopGroup::new();
+        group.add(key1.clone(), 1);
+        group.add(key2.clone(), 3);
+        let prefix1 = Prefix::from("192.168.1.0/24");
+        let prefix2 = Prefix::from("192.168.2.0/24");
+        fibw.add_nhop_group(1, &group, false);
+        fibw.add_fibroute_nhop_group(prefix1, 1, false);
+        fibw.add_fibroute_nhop_group(prefix2, 1, true);
+        assert_eq!(fibw.enter().unwrap().len_nhop_groups(), 1);
+
+        // flows are spread according to the weights
+        let flows = ecmp_flows(&fibw);
+        let count = |ifindex: u32| {
+            let ifindex = InterfaceIndex::try_new(ifindex).unwrap();
+            flows.iter().filter(|i| **i == ifindex).count()
+        };
+        assert_eq!(count(1) + count(2), flows.len());
+        assert!(count(1) > 0);
+        assert!(count(2) > 2 * count(1));
+
+        // replacing the group updates all the routes using it
+        let mut group = NhopGroup::new();
+        group.add(key2, 1);
+        fibw.add_nhop_group(1, &group, true);
+        assert!(
+            ecmp_flows(&fibw)
+                .iter()
+                .all(|i| *i == InterfaceIndex::try_new(2).unwrap())
+        );
+        {
+            let route = fibr
+                .lpm_route(IpAddr::from_str("192.168.2.1").unwrap())
+                .unwrap();
+            assert_eq!(route.len(), 1);
+            assert_eq!(route.get_fibentry(0), &e2);
+        }
+
+        // the group can only be deleted once no route uses it
+        fibw.del_nhop_group(1, true);
+        assert_eq!(fibw.enter().unwrap().len_nhop_groups(), 1);
+        fibw.del_fibroute(prefix1, false);
+        fibw.del_fibroute(prefix2, false);
+        fibw.del_nhop_group(1, true);
+        assert_eq!(fibw.enter().unwrap().len_nhop_groups(), 0);
+    }
+
+    // Forward IPv6 packets to 1000 hosts of 2001:db8:1::/64 and tell the interface each one
+    // is sent over
+    fn ecmp_flows_v6(fibw: &FibWriter) -> Vec<InterfaceIndex> {
+        let mut packet = build_test_ipv6_packet(64).unwrap();
+        let fib = fibw.enter().unwrap();
+        (1..=1000u16)
+            .map(|host| {
+                let destination = IpAddr::from_str(&format!("2001:db8:1::{host:x}")).unwrap();
+                packet.set_ip_destination(destination).unwrap();
+                let (prefix, entry) = fib.lpm_entry_prefix(&packet);
+                assert_eq!(prefix, Prefix::from("2001:db8:1::/64"));
+                get_entry_interface_index(entry)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fib_ipv6() {
+        let (mut fibw, fibr) = FibWriter::new(FibKey::Id(0));
+        fibw.set_ecmp(EcmpConfig::new(
+            HashFields::FIVE_TUPLE,
+            EcmpMode::Consistent,
+        ));
+
+        // destinations without a route hit the default route of their family
+        let destination = IpAddr::from_str("2001:db8:1::1").unwrap();
+        let (hit, _) = fibr.lpm_route_with_prefix(destination).unwrap();
+        assert_eq!(hit, Prefix::root_v6());
+
+        // add a route via 3 link-local next-hops, each scoped by its interface
+        let prefix = Prefix::from("2001:db8:1::/64");
+        let nhkey = NhopKey::with_address(&IpAddr::from_str("2001:db8:ff::1").unwrap());
+        let e1 = build_fib_entry_egress(1, "fe80::1", "eth1");
+        let e2 = build_fib_entry_egress(2, "fe80::1", "eth2");
+        let e3 = build_fib_entry_egress(3, "fe80::3", "eth3");
+        let fibgroup = build_fibgroup(&[e1.clone(), e2, e3.clone()]);
+        fibw.register_fibgroup(&nhkey, &fibgroup, false);
+        fibw.add_fibroute(prefix, vec![nhkey.clone()], true);
+        assert_eq!(fibw.enter().unwrap().len_v6(), 2);
+        {
+            let (hit, route) = fibr.lpm_route_with_prefix(destination).unwrap();
+            assert_eq!(hit, prefix);
+            assert_eq!(route.len(), 3);
+            assert_eq!(route.get_fibentry(0), &e1);
+            // IPv4 lookups are not affected
+            drop(route);
+            let (hit, _) = fibr
+                .lpm_route_with_prefix(IpAddr::from_str("10.0.0.1").unwrap())
+                .unwrap();
+            assert_eq!(hit, Prefix::root_v4());
+        }
+
+        // flows are spread over all next-hops, including those sharing an address
+        let before = ecmp_flows_v6(&fibw);
+        for ifindex in 1..=3 {
+            assert!(before.contains(&InterfaceIndex::try_new(ifindex).unwrap()));
+        }
+
+        // removing a next-hop only remaps its flows
+        let removed = InterfaceIndex::try_new(2).unwrap();
+        fibw.register_fibgroup(&nhkey, &build_fibgroup(&[e1, e3]), true);
+        let after = ecmp_flows_v6(&fibw);
+        for (b, a) in before.iter().zip(after.iter()) {
+            assert_ne!(*a, removed);
+            if *b != removed {
+                assert_eq!(a, b);
+            }
+        }
+
+        // deleting the route falls back to the IPv6 default
+        fibw.del_fibroute(prefix, true);
+        let (hit, _) = fibr.lpm_route_with_prefix(destination).unwrap();
+        assert_eq!(hit, Prefix::root_v6());
+    }
+
+    #[test]
+    fn test_fib_urpf() {
+        let (mut fibw, _fibr) = FibWriter::new(FibKey::Id(0));
+        let eth1 = InterfaceIndex::try_new(1).unwrap();
+        let eth2 = InterfaceIndex::try_new(2).unwrap();
+
+        // 10.0.1.0/24 is reachable via eth1, 10.0.9.0/24 is a drop route
+        let nhkey = NhopKey::with_address(&IpAddr::from_str("10.0.1.1").unwrap());
+        let entry = build_fib_entry_egress(1, "10.0.1.1", "eth1");
+        fibw.register_fibgroup(&nhkey, &build_fibgroup(&[entry]), false);
+        fibw.add_fibroute(Prefix::from("10.0.1.0/24"), vec![nhkey], false);
+        let drop_key = NhopKey::with_address(&IpAddr::from_str("10.0.9.1").unwrap());
+        let drop_group = FibGroup::with_entry(FibEntry::drop_fibentry());
+        fibw.register_fibgroup(&drop_key, &drop_group, false);
+        fibw.add_fibroute(Prefix::from("10.0.9.0/24"), vec![drop_key], true);
+
+        let fib = fibw.enter().unwrap();
+        let source = IpAddr::from_str("10.0.1.5").unwrap();
+        assert!(fib.urpf_check(source, eth1, UrpfMode::Strict));
+        assert!(!fib.urpf_check(source, eth2, UrpfMode::Strict));
+        assert!(fib.urpf_check(source, eth2, UrpfMode::Loose));
+
+        // sources only covered by a drop route or the default route fail both checks
+        for source in ["10.0.9.5", "10.0.2.5", "2001:db8::1"] {
+            let source = IpAddr::from_str(source).unwrap();
+            assert!(!fib.urpf_check(source, eth1, UrpfMode::Strict));
+            assert!(!fib.urpf_check(source, eth1, UrpfMode::Loose));
+        }
+
+        // unspecified and link-local sources are not checked
+        for source in ["0.0.0.0", "169.254.0.1", "fe80::1"] {
+            let source = IpAddr::from_str(source).unwrap();
+            assert!(fib.urpf_check(source, eth2, UrpfMode::Strict));
+        }
+    }
+
+    #[test]
+    fn test_fib_lpm_batch() {
+        let (mut fibw, _fibr) = FibWriter::new(FibKey::Id(0));
+        let nhkey = NhopKey::with_address(&IpAddr::from_str("7.0.0.1").unwrap());
+        let e1 = build_fib_entry_egress(1, "10.0.1.1", "eth1");
+        fibw.register_fibgroup(&nhkey, &build_fibgroup(&[e1]), false);
+        for prefix in [
+            "192.168.0.0/16",
+            "192.168.1.0/24",
+            "10.0.0.0/8",
+            "2001:db8::/32",
+        ] {
+            fibw.add_fibroute(Prefix::from(prefix), vec![nhkey.clone()], false);
+        }
+        fibw.publish();
+
+        // more destinations than a batch, mixing families, unsorted and with repetitions
+        let targets: Vec<IpAddr> = (0..100u32)
+            .map(|n| match n % 5 {
+                0 => IpAddr::from_str(&format!("192.168.{}.1", n % 3)).unwrap(),
+                1 => IpAddr::from_str(&format!("10.{n}.0.1")).unwrap(),
+                2 => IpAddr::from_str("2001:db8::1").unwrap(),
+                3 => IpAddr::from_str(&format!("2001:db9::{n:x}")).unwrap(),
+                _ => IpAddr::from_str(&format!("172.16.0.{n}")).unwrap(),
+            })
+            .collect();
+
+        let fib = fibw.enter().unwrap();
+        let mut results = Vec::new();
+        fib.lpm_batch(&targets, &mut results);
+        assert_eq!(results.len(), targets.len());
+        for (target, (prefix, route)) in targets.iter().zip(results.iter()) {
+            let (expected, expected_route) = fib.lpm_with_prefix(target);
+            assert_eq!(*prefix, expected);
+            assert!(std::ptr::eq(*route, expected_route));
+        }
+        assert_eq!(results[0].0, Prefix::from("192.168.0.0/16"));
+        assert_eq!(results[10].0, Prefix::from("192.168.1.0/24"));
+        assert_eq!(results[3].0, Prefix::root_v6());
+
+        // results are appended
+        fib.lpm_batch(&targets[..3], &mut results);
+        assert_eq!(results.len(), targets.len() + 3);
+        fib.lpm_batch(&[], &mut results);
+        assert_eq!(results.len(), targets.len() + 3);
+    }
+
     // Test the concurrency of a SINGLE fib. NUM_WORKERS workers perform LPM lookups on a single FIB for
     // a test packet while another thread fuzzes the route to forward the packet, by removing the route
     // or aggressively changing the fibgroup (and fib entries) used for the prefix of that route.
@@ -219,7 +525,7 @@ mod tests {
                 fibw.publish();
             }
             if updates % 101 == 0 {
-                fibw.del_fibroute(prefix);
+                fibw.del_fibroute(prefix, true);
                 fibw.publish();
                 route_deletions += 1;
             }
@@ -338,7 +644,7 @@ mod tests {
                     fibw.add_fibroute(prefix, vec![nhkey.clone()], true);
                 }
                 if updates % 150 == 0 {
-                    fibw.del_fibroute(prefix);
+                    fibw.del_fibroute(prefix, true);
                     fibw.publish();
                 }
             }
@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Unicast reverse path forwarding (uRPF, RFC 3704). Packets are checked against the fib of
+//! the VRF of the interface they are received on: in strict mode, the route back to their
+//! source must go through that interface; in loose mode, there must be some route back to their
+//! source, other than a default or a drop route. The packets failing the check are dropped and
+//! counted per interface.
+
+use crate::fib::fibobjects::PktInstruction;
+use crate::fib::fibtype::Fib;
+use net::interface::InterfaceIndex;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// The uRPF mode of an interface
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UrpfMode {
+    /// The route to the source must go through the interface the packet is received on
+    Strict,
+    /// There must be a (non-default) route to the source, through any interface
+    Loose,
+}
+
+impl Display for UrpfMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrpfMode::Strict => write!(f, "strict"),
+            UrpfMode::Loose => write!(f, "loose"),
+        }
+    }
+}
+
+impl Fib {
+    /// Check a packet from `source`, received on interface `iif`, in the given [`UrpfMode`].
+    /// Unspecified and link-local sources are not checked, as they are not routed.
+    #[must_use]
+    pub fn urpf_check(&self, source: IpAddr, iif: InterfaceIndex, mode: UrpfMode) -> bool {
+        let link_local = match source {
+            IpAddr::V4(source) => source.is_link_local(),
+            IpAddr::V6(source) => source.is_unicast_link_local(),
+        };
+        if source.is_unspecified() || link_local {
+            return true;
+        }
+        let (prefix, route) = self.lpm_with_prefix(&source);
+        if prefix.length() == 0 {
+            return false;
+        }
+        let mut instructions = route
+            .iter()
+            .flat_map(|group| group.iter())
+            .flat_map(|entry| entry.iter());
+        match mode {
+            UrpfMode::Strict => instructions.any(|inst| match inst {
+                PktInstruction::Egress(egress) => *egress.ifindex() == Some(iif),
+                _ => false,
+            }),
+            UrpfMode::Loose => instructions.any(|inst| !matches!(inst, PktInstruction::Drop)),
+        }
+    }
+}
+
+/// A handle to the shared counters of the packets dropped by uRPF checks, per interface.
+/// Cloning the handle does not clone the counters.
+#[derive(Clone, Debug, Default)]
+pub struct UrpfDrops(Arc<Mutex<BTreeMap<InterfaceIndex, u64>>>);
+
+impl UrpfDrops {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<InterfaceIndex, u64>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Count `count` packets dropped on interface `ifindex`
+    pub fn add(&self, ifindex: InterfaceIndex, count: u64) {
+        *self.lock().entry(ifindex).or_default() += count;
+    }
+
+    /// Get the number of packets dropped on interface `ifindex`
+    #[must_use]
+    pub fn get(&self, ifindex: InterfaceIndex) -> u64 {
+        self.lock().get(&ifindex).copied().unwrap_or_default()
+    }
+
+    /// Get a copy of the counters
+    #[must_use]
+    pub fn snapshot(&self) -> BTreeMap<InterfaceIndex, u64> {
+        self.lock().clone()
+    }
+}
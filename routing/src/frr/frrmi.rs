@@ -36,6 +36,9 @@ pub(crate) enum FrrErr {
 
     #[error("Busy")]
     IOBusy,
+
+    #[error("Invalid config: {0}")]
+    InvalidConfig(&'static str),
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
@@ -43,6 +46,9 @@ pub(crate) enum FrrErr {
 ///   * Interface to frr-agent based on unix stream sockets.
 ///   * The `Frrmi` accepts requests that get queued into it and attempts to send them to the
 ///     to the frr-agent, dealing with connections and the reception of responses.
+///   * Configs are applied as transactions: they are validated before being queued, the response
+///     of frr-agent is verified and, if a config fails to apply, the last config successfully
+///     applied is re-applied (rolled back to).
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 #[derive(Default)]
 pub(crate) struct Frrmi {
@@ -68,6 +74,17 @@ impl FrrAppliedConfig {
     }
 }
 
+/// The status of the application of FRR configs, as reported to the management
+#[derive(Clone, Debug, Default)]
+pub struct FrrApplyStatus {
+    pub applied: Option<FrrAppliedConfig>, /* config currently applied, if known */
+    pub applied_configs: u64,              /* number of configs applied successfully */
+    pub failed_configs: u64,               /* number of configs that failed to apply */
+    pub rollbacks: u64,                    /* number of successful rollbacks */
+    pub rollback_failures: u64,            /* number of rollbacks that failed */
+    pub last_fail_genid: Option<GenId>,    /* genid of the most recent config that failed */
+}
+
 /// Stats for the `Frrmi`
 #[derive(Default)]
 pub(crate) struct FrrmiStats {
@@ -79,12 +96,16 @@ pub(crate) struct FrrmiStats {
     pub(crate) last_fail_time: Option<DateTime<Local>>, /* time when last config failed */
     pub(crate) apply_oks: u64,                 /* number of configs applied successfully */
     pub(crate) apply_failures: u64,            /* number of times applying a config failed */
+    pub(crate) last_rollback_genid: Option<GenId>, /* genid of the config last rolled back to */
+    pub(crate) rollbacks: u64,                 /* number of successful rollbacks */
+    pub(crate) rollback_failures: u64,         /* number of rollbacks that failed */
 }
 
 pub(crate) struct FrrmiRequest {
     genid: GenId,    /* gen id this frr-config corresponds to */
     cfg: FrrConfig,  /* confif to frr-agent is a string */
     max_retries: u8, /* max number of times to retry configuration on failure */
+    rollback: bool,  /* true if the request rolls back to a previously applied config */
 }
 
 const CLEAN_CONFIG: &'static str = "! Empty config";
@@ -95,11 +116,28 @@ impl FrrmiRequest {
             genid,
             cfg,
             max_retries,
+            rollback: false,
         }
     }
     pub(crate) fn blank() -> Self {
         FrrmiRequest::new(0, CLEAN_CONFIG.to_string(), 0)
     }
+    fn rollback(applied: &FrrAppliedConfig) -> Self {
+        Self {
+            rollback: true,
+            ..FrrmiRequest::new(applied.genid, applied.cfg.clone(), 0)
+        }
+    }
+    /// Validate the config of a request before it gets sent to frr-agent
+    pub(crate) fn validate(&self) -> Result<(), FrrErr> {
+        if self.cfg.trim().is_empty() {
+            return Err(FrrErr::InvalidConfig("config is empty"));
+        }
+        if self.cfg.contains('\0') {
+            return Err(FrrErr::InvalidConfig("config contains NUL characters"));
+        }
+        Ok(())
+    }
 }
 
 pub(crate) struct FrrmiResponse {
@@ -186,6 +224,17 @@ impl Frrmi {
     pub fn get_applied_cfg(&self) -> &Option<FrrAppliedConfig> {
         &self.applied_cfg
     }
+    #[must_use]
+    pub fn get_apply_status(&self) -> FrrApplyStatus {
+        FrrApplyStatus {
+            applied: self.applied_cfg.clone(),
+            applied_configs: self.stats.apply_oks,
+            failed_configs: self.stats.apply_failures,
+            rollbacks: self.stats.rollbacks,
+            rollback_failures: self.stats.rollback_failures,
+            last_fail_genid: self.stats.last_fail_genid,
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
@@ -193,9 +242,15 @@ impl Frrmi {
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 impl Frrmi {
     ///////////////////////////////////////////////////////////////////////////////////////////////////
-    /// Queue a request (tail) to be serviced by the frr-agent
+    /// Queue a request (tail) to be serviced by the frr-agent. Requests with invalid configs are
+    /// not queued and are accounted as failures.
     ///////////////////////////////////////////////////////////////////////////////////////////////////
     pub(crate) fn queue_request(&mut self, req: FrrmiRequest) {
+        if let Err(e) = req.validate() {
+            error!("Won't apply FRR configuration for gen {}: {e}", req.genid);
+            self.apply_failed(req.genid);
+            return;
+        }
         debug!("Queued request to configure FRR (gen: {})", req.genid);
         self.requests.clear();
         self.requests.push_back(req);
@@ -224,18 +279,18 @@ impl Frrmi {
     /// retrying a config, we clean-up the current config with a simpler one. For this reason,
     /// all requests are currently set with a max-retries of 0.
     ///////////////////////////////////////////////////////////////////////////////////////////////////
-    pub(crate) fn config_retry(&mut self, mut request: FrrmiRequest) {
+    pub(crate) fn config_retry(&mut self, mut request: FrrmiRequest) -> bool {
         let genid = request.genid;
 
         // give up after exhausting number of attempts
         if request.max_retries == 0 {
             warn!("Ran out of attempts to config FRR for gen {genid}");
-            return;
+            return false;
         }
         // if new configs have arrived, don't try to reapply a config
         if !self.requests.is_empty() {
             warn!("Skipping config of FRR for gen {genid}: newer configs exist");
-            return;
+            return false;
         }
         warn!("Will retry FRR config for gen {genid}...");
         request.max_retries -= 1;
@@ -247,6 +302,28 @@ impl Frrmi {
         if false {
             self.requests.push_front(FrrmiRequest::blank());
         }
+        true
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Schedule the re-application of the last config successfully applied, after the config for
+    /// gen `genid` failed to apply. Nothing is done if newer configs have arrived, since these will
+    /// replace the failed config anyway.
+    ///////////////////////////////////////////////////////////////////////////////////////////////////
+    fn config_rollback(&mut self, genid: GenId) {
+        if !self.requests.is_empty() {
+            warn!("Not rolling back FRR config for gen {genid}: newer configs exist");
+            return;
+        }
+        let Some(applied) = &self.applied_cfg else {
+            warn!("Can't roll back FRR config for gen {genid}: no config was applied before");
+            return;
+        };
+        warn!(
+            "Will roll back FRR config for gen {genid} to gen {}",
+            applied.genid
+        );
+        self.requests.push_front(FrrmiRequest::rollback(applied));
     }
 }
 
@@ -368,29 +445,52 @@ impl Frrmi {
         };
         let reqgen = request.genid;
         let respgen = response.genid;
-        if respgen != reqgen {
+
+        // verify the response: it must be a success and correspond to the request
+        let verified = respgen == reqgen;
+        if !verified {
             warn!("Response genid {respgen} does not match the expected {reqgen}");
         }
-
-        if response.is_success() {
-            info!("Frr configuration successfully applied for gen {respgen}");
+        let out = response.get_response_data();
+
+        if request.rollback {
+            if response.is_success() && verified {
+                info!("Frr configuration successfully rolled back to gen {reqgen}");
+                self.stats.last_rollback_genid = Some(reqgen);
+                self.stats.rollbacks += 1;
+                revent!(RouterEvent::FrrConfigRollbackSuccess(reqgen));
+            } else {
+                error!("Failed to roll back FRR configuration to gen {reqgen}: {out}");
+                self.stats.rollback_failures += 1;
+                // the config that FRR runs is no longer known
+                self.clear_applied_cfg();
+                revent!(RouterEvent::FrrConfigRollbackFailure(reqgen));
+            }
+        } else if response.is_success() && verified {
+            info!("Frr configuration successfully applied for gen {reqgen}");
             self.stats.last_ok_time = Some(Local::now());
-            self.stats.last_ok_genid = Some(response.genid);
+            self.stats.last_ok_genid = Some(reqgen);
             self.stats.apply_oks += 1;
             self.applied_cfg = Some(FrrAppliedConfig::new(request.genid, request.cfg));
-            revent!(RouterEvent::FrrConfigApplySuccess(response.genid));
+            revent!(RouterEvent::FrrConfigApplySuccess(reqgen));
         } else {
-            self.stats.last_fail_time = Some(Local::now());
-            self.stats.last_fail_genid = Some(response.genid);
-            self.stats.apply_failures += 1;
-            let out = response.get_response_data();
-            error!("Failed to apply FRR configuration for gen {respgen}: {out}");
-            revent!(RouterEvent::FrrConfigApplyFailure(response.genid));
-            self.config_retry(request);
+            error!("Failed to apply FRR configuration for gen {reqgen}: {out}");
+            self.apply_failed(reqgen);
+            if !self.config_retry(request) {
+                self.config_rollback(reqgen);
+            }
         }
         // cancel timeout
         self.timeout.take();
     }
+
+    /// Account for the failure to apply the config for gen `genid`
+    fn apply_failed(&mut self, genid: GenId) {
+        self.stats.last_fail_time = Some(Local::now());
+        self.stats.last_fail_genid = Some(genid);
+        self.stats.apply_failures += 1;
+        revent!(RouterEvent::FrrConfigApplyFailure(genid));
+    }
 }
 
 #[derive(Default)]
@@ -490,3 +590,58 @@ impl IoBuffer {
         Ok(FrrmiResponse { genid, data })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serve the next queued request as frr-agent would, responding with `data`
+    fn serve(frrmi: &mut Frrmi, data: &str) -> GenId {
+        let request = frrmi.requests.pop_front().expect("No request queued");
+        let genid = request.genid;
+        frrmi.inservice = Some(request);
+        let data = data.to_string();
+        frrmi.process_response(FrrmiResponse { genid, data });
+        genid
+    }
+
+    #[test]
+    fn test_frrmi_rollback() {
+        let mut frrmi = Frrmi::new("/tmp/nonexistent");
+
+        // invalid configs are not queued
+        frrmi.queue_request(FrrmiRequest::new(1, " \n".to_string(), 0));
+        assert!(frrmi.requests.is_empty());
+        assert_eq!(frrmi.get_stats().apply_failures, 1);
+
+        // first config applies; a failure without previous config can't be rolled back
+        frrmi.queue_request(FrrmiRequest::new(2, "! gen 2".to_string(), 0));
+        assert_eq!(serve(&mut frrmi, "Ok"), 2);
+        frrmi.clear_applied_cfg();
+        frrmi.queue_request(FrrmiRequest::new(3, "! gen 3".to_string(), 0));
+        serve(&mut frrmi, "Error");
+        assert!(frrmi.requests.is_empty());
+        frrmi.queue_request(FrrmiRequest::new(4, "! gen 4".to_string(), 0));
+        serve(&mut frrmi, "Ok");
+
+        // a failed config gets rolled back to the last one applied
+        frrmi.queue_request(FrrmiRequest::new(5, "! gen 5".to_string(), 0));
+        serve(&mut frrmi, "Error");
+        assert_eq!(frrmi.requests.len(), 1);
+        assert!(frrmi.requests[0].rollback);
+        assert_eq!(serve(&mut frrmi, "Ok"), 4);
+        let status = frrmi.get_apply_status();
+        assert_eq!(status.applied.map(|c| c.cfg), Some("! gen 4".to_string()));
+        assert_eq!((status.applied_configs, status.failed_configs), (2, 3));
+        assert_eq!(status.rollbacks, 1);
+        assert_eq!(status.last_fail_genid, Some(5));
+
+        // a failed rollback leaves the applied config unknown and is not rolled back
+        frrmi.queue_request(FrrmiRequest::new(6, "! gen 6".to_string(), 0));
+        serve(&mut frrmi, "Error");
+        serve(&mut frrmi, "Error");
+        assert!(frrmi.requests.is_empty());
+        assert!(frrmi.get_applied_cfg().is_none());
+        assert_eq!(frrmi.get_apply_status().rollback_failures, 1);
+    }
+}
@@ -8,4 +8,4 @@ pub(crate) mod frrmi;
 pub mod renderer;
 mod test;
 
-pub use frrmi::FrrAppliedConfig;
+pub use frrmi::{FrrAppliedConfig, FrrApplyStatus};
@@ -13,6 +13,7 @@ impl Rendered for StaticRouteNhop {
         match self {
             StaticRouteNhop::Interface(ifname) => ifname.to_string(),
             StaticRouteNhop::Address(address) => format!("{address}"),
+            StaticRouteNhop::AddressInterface(address, ifname) => format!("{address} {ifname}"),
             StaticRouteNhop::Null0 => "Null0".to_string(),
             StaticRouteNhop::Reject => "reject".to_string(),
             StaticRouteNhop::Blackhole => "blackhole".to_string(),
@@ -46,6 +47,9 @@ impl Render for StaticRoute {
         if let Some(tag) = &self.tag {
             statement += format!(" tag {tag}").as_ref();
         }
+        if let Some(distance) = &self.distance {
+            statement += format!(" {distance}").as_ref();
+        }
         config += statement;
         config
     }
@@ -78,6 +82,16 @@ pub mod tests {
 
         let route = StaticRoute::new(Prefix::expect_from(("192.168.4.0", 29))).nhop_reject();
         print!("{}", route.render(&()));
+
+        let route = StaticRoute::new(Prefix::expect_from(("192.168.5.0", 24)))
+            .nhop_addr_iface(
+                IpAddr::from_str("10.0.0.1").expect("Bad address"),
+                "eth1".to_owned(),
+            )
+            .distance(200);
+        let rendered = route.render(&()).to_string();
+        print!("{rendered}");
+        assert!(rendered.contains("ip route 192.168.5.0/24 10.0.0.1 eth1 200"));
     }
 
     pub fn build_static_routes() -> BTreeSet<StaticRoute> {
@@ -6,6 +6,7 @@
 #![allow(clippy::collapsible_if)]
 
 use crate::fib::fibtype::FibKey;
+use crate::fib::urpf::UrpfMode;
 use crate::rib::vrf::VrfId;
 use net::eth::mac::Mac;
 use net::interface::{InterfaceIndex, Mtu};
@@ -89,6 +90,7 @@ pub struct RouterInterfaceConfig {
     pub admin_state: IfState,        /* admin state */
     pub attach_cfg: Option<AttachConfig>, /* attach config */
     pub mtu: Option<Mtu>,
+    pub urpf: Option<UrpfMode>, /* uRPF check of received packets */
 }
 impl RouterInterfaceConfig {
     pub fn new(name: &str, ifindex: InterfaceIndex) -> Self {
@@ -100,6 +102,7 @@ impl RouterInterfaceConfig {
             admin_state: IfState::Up,
             attach_cfg: None,
             mtu: None,
+            urpf: None,
         }
     }
     pub fn set_name(&mut self, name: &str) {
@@ -120,6 +123,9 @@ impl RouterInterfaceConfig {
     pub fn set_mtu(&mut self, mtu: Option<Mtu>) {
         self.mtu = mtu;
     }
+    pub fn set_urpf(&mut self, urpf: Option<UrpfMode>) {
+        self.urpf = urpf;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -131,6 +137,7 @@ pub struct Interface {
     pub iftype: IfType,
     pub admin_state: IfState,
     pub mtu: Option<Mtu>,
+    pub urpf: Option<UrpfMode>,
     /* -- state -- */
     pub oper_state: IfState,
     pub addresses: HashSet<IfAddress>,
@@ -150,6 +157,7 @@ impl Interface {
             iftype: config.iftype.clone(),
             admin_state: config.admin_state,
             mtu: config.mtu,
+            urpf: config.urpf,
             oper_state: IfState::Unknown,
             addresses: HashSet::new(),
             attachment: None,
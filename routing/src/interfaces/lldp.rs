@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Table of the neighbors discovered with LLDP, shared between the
+//! packet processing workers, which learn them, and the CLI.
+
+use net::interface::InterfaceIndex;
+use net::lldp::{ChassisId, Lldpdu, PortId};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// A neighbor discovered with LLDP on some interface
+#[derive(Clone, Debug)]
+pub struct LldpNeighbor {
+    pub ifindex: InterfaceIndex,
+    pub chassis_id: ChassisId,
+    pub port_id: PortId,
+    pub system_name: Option<String>,
+    pub port_description: Option<String>,
+    pub ttl: u16,
+    pub last_seen: Instant,
+}
+
+impl LldpNeighbor {
+    /// Tell if the information about this neighbor is no longer valid at `now`
+    #[must_use]
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_seen) >= Duration::from_secs(self.ttl.into())
+    }
+}
+
+type LldpNeighborKey = (InterfaceIndex, ChassisId, PortId);
+
+/// The table of LLDP neighbors
+#[derive(Clone, Debug, Default)]
+pub struct LldpNeighborTable {
+    neighbors: BTreeMap<LldpNeighborKey, LldpNeighbor>,
+}
+
+impl LldpNeighborTable {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.neighbors.len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.neighbors.is_empty()
+    }
+    pub fn values(&self) -> impl Iterator<Item = &LldpNeighbor> {
+        self.neighbors.values()
+    }
+    /// Keep only the neighbors learnt on the interface with index `ifindex`
+    pub fn retain_ifindex(&mut self, ifindex: InterfaceIndex) {
+        self.neighbors.retain(|(index, _, _), _| *index == ifindex);
+    }
+    fn purge(&mut self, now: Instant) {
+        self.neighbors
+            .retain(|_, neighbor| !neighbor.is_expired(now));
+    }
+}
+
+/// A handle to a shared [`LldpNeighborTable`]. Cloning the handle does not clone the table.
+#[derive(Clone, Debug, Default)]
+pub struct LldpNeighbors(Arc<Mutex<LldpNeighborTable>>);
+
+impl LldpNeighbors {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LldpNeighborTable> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Learn (or refresh) the neighbor advertised by `lldpdu`, received on interface `ifindex`.
+    /// A TTL of zero removes the neighbor.
+    pub fn update(&self, ifindex: InterfaceIndex, lldpdu: &Lldpdu, now: Instant) {
+        let key = (
+            ifindex,
+            lldpdu.chassis_id().clone(),
+            lldpdu.port_id().clone(),
+        );
+        let mut table = self.lock();
+        if lldpdu.ttl() == 0 {
+            if table.neighbors.remove(&key).is_some() {
+                debug!(
+                    "LLDP neighbor {} on ifindex {ifindex} is shutting down",
+                    lldpdu.chassis_id()
+                );
+            }
+            return;
+        }
+        let neighbor = LldpNeighbor {
+            ifindex,
+            chassis_id: key.1.clone(),
+            port_id: key.2.clone(),
+            system_name: lldpdu.system_name().map(str::to_owned),
+            port_description: lldpdu.port_description().map(str::to_owned),
+            ttl: lldpdu.ttl(),
+            last_seen: now,
+        };
+        if table.neighbors.insert(key, neighbor).is_none() {
+            debug!(
+                "New LLDP neighbor {} on ifindex {ifindex}",
+                lldpdu.chassis_id()
+            );
+        }
+    }
+
+    /// Get a copy of the table of neighbors that have not expired at `now`
+    #[must_use]
+    pub fn snapshot(&self, now: Instant) -> LldpNeighborTable {
+        let mut table = self.lock();
+        table.purge(now);
+        table.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use net::lldp::LldpTlv;
+
+    fn lldpdu(ttl: u16) -> Lldpdu {
+        Lldpdu::new(
+            ChassisId::Local("spine-1".to_string()),
+            PortId::InterfaceName("swp1".to_string()),
+            ttl,
+        )
+        .with_tlv(LldpTlv::SystemName("spine-1".to_string()))
+    }
+
+    #[test]
+    fn test_lldp_neighbors() {
+        let neighbors = LldpNeighbors::new();
+        let ifindex = InterfaceIndex::try_new(2).unwrap();
+        let now = Instant::now();
+
+        neighbors.update(ifindex, &lldpdu(120), now);
+        neighbors.update(ifindex, &lldpdu(120), now);
+        let table = neighbors.snapshot(now);
+        assert_eq!(table.len(), 1);
+        let neighbor = table.values().next().unwrap();
+        assert_eq!(neighbor.system_name.as_deref(), Some("spine-1"));
+
+        /* filtering on another interface */
+        let mut filtered = table.clone();
+        filtered.retain_ifindex(InterfaceIndex::try_new(3).unwrap());
+        assert!(filtered.is_empty());
+
+        /* expiry */
+        assert!(
+            neighbors
+                .snapshot(now + Duration::from_secs(120))
+                .is_empty()
+        );
+
+        /* explicit removal with ttl 0 */
+        neighbors.update(ifindex, &lldpdu(120), now);
+        neighbors.update(ifindex, &lldpdu(0), now);
+        assert!(neighbors.snapshot(now).is_empty());
+    }
+}
@@ -6,6 +6,7 @@
 pub mod iftable;
 pub mod iftablerw;
 pub mod interface;
+pub mod lldp;
 
 #[cfg(test)]
 pub mod tests {
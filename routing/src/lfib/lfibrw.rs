@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Label forwarding information base left-right
+
+use crate::lfib::{Lfib, Lsp};
+use left_right::{Absorb, ReadGuard, ReadHandle, ReadHandleFactory, WriteHandle};
+use net::mpls::MplsLabel;
+
+enum LfibChange {
+    Add(Lsp),
+    Del(MplsLabel),
+    Clear,
+}
+
+impl Absorb<LfibChange> for Lfib {
+    fn absorb_first(&mut self, change: &mut LfibChange, _: &Self) {
+        match change {
+            LfibChange::Add(lsp) => self.add_lsp(lsp.clone()),
+            LfibChange::Del(in_label) => self.del_lsp(*in_label),
+            LfibChange::Clear => self.clear(),
+        }
+    }
+    fn drop_first(self: Box<Self>) {}
+    fn sync_with(&mut self, first: &Self) {
+        *self = first.clone();
+    }
+}
+
+pub struct LfibWriter(WriteHandle<Lfib, LfibChange>);
+impl LfibWriter {
+    #[must_use]
+    pub fn new() -> (LfibWriter, LfibReader) {
+        let (w, r) = left_right::new_from_empty::<Lfib, LfibChange>(Lfib::new());
+        (LfibWriter(w), LfibReader(r))
+    }
+    #[must_use]
+    pub fn as_lfib_reader(&self) -> LfibReader {
+        LfibReader::new(self.0.clone())
+    }
+    pub fn enter(&self) -> Option<ReadGuard<'_, Lfib>> {
+        self.0.enter()
+    }
+    pub fn add_lsp(&mut self, lsp: Lsp, publish: bool) {
+        self.0.append(LfibChange::Add(lsp));
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn del_lsp(&mut self, in_label: MplsLabel, publish: bool) {
+        self.0.append(LfibChange::Del(in_label));
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn clear(&mut self, publish: bool) {
+        self.0.append(LfibChange::Clear);
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn publish(&mut self) {
+        self.0.publish();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LfibReader(ReadHandle<Lfib>);
+impl LfibReader {
+    pub fn new(rhandle: ReadHandle<Lfib>) -> Self {
+        LfibReader(rhandle)
+    }
+    pub fn enter(&self) -> Option<ReadGuard<'_, Lfib>> {
+        self.0.enter()
+    }
+    pub fn factory(&self) -> LfibReaderFactory {
+        LfibReaderFactory(self.0.factory())
+    }
+}
+
+#[derive(Debug)]
+pub struct LfibReaderFactory(ReadHandleFactory<Lfib>);
+impl LfibReaderFactory {
+    #[must_use]
+    pub fn handle(&self) -> LfibReader {
+        LfibReader(self.0.handle())
+    }
+}
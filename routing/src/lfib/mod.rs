@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Label forwarding information base (LFIB). The LFIB tells how to forward MPLS-labeled
+//! packets depending on their outermost (incoming) label. Its entries are the label-switched
+//! paths (LSPs) that FRR sets up with LDP or segment routing.
+
+pub mod lfibrw;
+
+use crate::rib::vrf::VrfId;
+use ahash::RandomState;
+use net::interface::InterfaceIndex;
+use net::mpls::MplsLabel;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The protocol that set up an [`Lsp`]
+pub enum LspOrigin {
+    Static,
+    Ldp,
+    Bgp,
+    OspfSr,
+    IsisSr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The operation to perform on the label stack of the packets forwarded via an [`LspNhop`]
+pub enum LabelOp {
+    /// Replace the incoming label with the given labels, outermost first
+    Swap(Vec<MplsLabel>),
+    /// Pop the incoming label and forward the packet to the next-hop (penultimate hop popping)
+    Pop,
+    /// Pop the incoming label and route the packet in the given VRF (egress of the LSP)
+    PopLookup(VrfId),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A next-hop of an [`Lsp`]. Next-hops of [`LabelOp::PopLookup`] operations need
+/// no address nor interface, since packets are routed in a VRF.
+pub struct LspNhop {
+    pub op: LabelOp,
+    pub address: Option<IpAddr>,
+    pub ifindex: Option<InterfaceIndex>,
+}
+
+impl LspNhop {
+    #[must_use]
+    pub fn new(op: LabelOp, address: Option<IpAddr>, ifindex: Option<InterfaceIndex>) -> Self {
+        Self {
+            op,
+            address,
+            ifindex,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A label-switched path: the forwarding entry for packets with some incoming label
+pub struct Lsp {
+    pub in_label: MplsLabel,
+    pub origin: LspOrigin,
+    pub nhops: Vec<LspNhop>,
+}
+
+impl Lsp {
+    #[must_use]
+    pub fn new(in_label: MplsLabel, origin: LspOrigin) -> Self {
+        Self {
+            in_label,
+            origin,
+            nhops: vec![],
+        }
+    }
+    pub fn add_nhop(&mut self, nhop: LspNhop) {
+        self.nhops.push(nhop);
+    }
+
+    /// Select the next-hop to forward a packet with the given hash, if the [`Lsp`]
+    /// has multiple (ECMP) next-hops.
+    #[must_use]
+    pub fn select_nhop(&self, hash: u64) -> Option<&LspNhop> {
+        match self.nhops.len() {
+            0 => None,
+            1 => self.nhops.first(),
+            n => self.nhops.get((hash % n as u64) as usize),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// The label forwarding information base: the [`Lsp`]s, by incoming label
+pub struct Lfib(HashMap<MplsLabel, Lsp, RandomState>);
+
+#[allow(clippy::new_without_default)]
+impl Lfib {
+    #[must_use]
+    pub fn new() -> Self {
+        Self(HashMap::with_hasher(RandomState::with_seed(0)))
+    }
+    /// Add an [`Lsp`], replacing the one for the same incoming label, if any
+    pub fn add_lsp(&mut self, lsp: Lsp) {
+        self.0.insert(lsp.in_label, lsp);
+    }
+    pub fn del_lsp(&mut self, in_label: MplsLabel) {
+        self.0.remove(&in_label);
+    }
+    #[must_use]
+    pub fn get_lsp(&self, in_label: MplsLabel) -> Option<&Lsp> {
+        self.0.get(&in_label)
+    }
+    pub fn values(&self) -> impl Iterator<Item = &Lsp> {
+        self.0.values()
+    }
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lfibrw::LfibWriter;
+    use super::*;
+    use std::str::FromStr;
+
+    fn label(value: u32) -> MplsLabel {
+        MplsLabel::new(value).unwrap()
+    }
+
+    #[test]
+    fn test_lfib_add_del() {
+        let (mut lfibw, lfibr) = LfibWriter::new();
+
+        let mut lsp = Lsp::new(label(100), LspOrigin::Ldp);
+        let ifindex = InterfaceIndex::try_new(2).unwrap();
+        for address in ["10.0.0.1", "10.0.0.2"] {
+            lsp.add_nhop(LspNhop::new(
+                LabelOp::Swap(vec![label(200)]),
+                Some(IpAddr::from_str(address).unwrap()),
+                Some(ifindex),
+            ));
+        }
+        lfibw.add_lsp(lsp.clone(), false);
+        lfibw.add_lsp(Lsp::new(label(101), LspOrigin::IsisSr), false);
+        assert!(lfibr.enter().unwrap().is_empty());
+
+        lfibw.publish();
+        {
+            let lfib = lfibr.enter().unwrap();
+            assert_eq!(lfib.len(), 2);
+            let found = lfib.get_lsp(label(100)).unwrap();
+            assert_eq!(found, &lsp);
+            assert_eq!(found.select_nhop(3), Some(&lsp.nhops[1]));
+            assert_eq!(lfib.get_lsp(label(101)).unwrap().select_nhop(3), None);
+        }
+
+        // a reader from the factory sees the same table
+        let lfibr2 = lfibr.factory().handle();
+        lfibw.del_lsp(label(100), true);
+        assert!(lfibr2.enter().unwrap().get_lsp(label(100)).is_none());
+        assert_eq!(lfibr2.enter().unwrap().len(), 1);
+
+        lfibw.clear(true);
+        assert!(lfibr.enter().unwrap().is_empty());
+    }
+}
@@ -8,6 +8,7 @@
 #![allow(clippy::similar_names)]
 
 pub mod atable;
+pub mod bfd;
 pub mod cli;
 pub mod config;
 mod cpi;
@@ -20,6 +21,8 @@ pub mod evpn;
 pub mod fib;
 pub mod frr;
 pub mod interfaces;
+pub mod lfib;
+pub mod mcast;
 pub mod pretty_utils;
 #[macro_use]
 pub(crate) mod revent;
@@ -29,10 +32,12 @@ pub mod rio;
 mod router;
 pub mod routingdb;
 mod rpc_adapt;
+pub mod snapshot;
+pub mod srv6;
 
 // re-exports
 pub use errors::RouterError;
-pub use router::{Router, RouterParams, RouterParamsBuilder};
+pub use router::{DEFAULT_FLOW_TABLE_CAPACITY, Router, RouterParams, RouterParamsBuilder};
 
 // crate-wide target
 use tracectl::trace_target;
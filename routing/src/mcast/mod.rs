@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Multicast routing table. A multicast route (mroute) tells the interfaces that the packets
+//! sent to some multicast group, by any source (*,G) or by a specific one (S,G), have to be
+//! replicated to. Mroutes are programmed from the router configuration; the receivers learnt
+//! with IGMP/MLD snooping (see [`snooping`]) are added to them by the dataplane.
+
+pub mod mroutetablerw;
+pub mod snooping;
+
+use crate::rib::vrf::VrfId;
+use ahash::RandomState;
+use net::interface::InterfaceIndex;
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The key of an [`Mroute`]: a (S,G) pair, or (*,G) if there is no source
+pub struct MrouteKey {
+    pub vrfid: VrfId,
+    pub source: Option<IpAddr>,
+    pub group: IpAddr,
+}
+
+impl MrouteKey {
+    #[must_use]
+    pub fn new(vrfid: VrfId, source: Option<IpAddr>, group: IpAddr) -> Self {
+        Self {
+            vrfid,
+            source,
+            group,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A multicast route
+pub struct Mroute {
+    pub key: MrouteKey,
+    /// The interface packets must be received on (RPF interface), if they must
+    pub iif: Option<InterfaceIndex>,
+    /// The interfaces to replicate the packets to
+    pub oifs: BTreeSet<InterfaceIndex>,
+}
+
+impl Mroute {
+    #[must_use]
+    pub fn new(key: MrouteKey) -> Self {
+        Self {
+            key,
+            iif: None,
+            oifs: BTreeSet::new(),
+        }
+    }
+    #[must_use]
+    pub fn with_iif(mut self, iif: InterfaceIndex) -> Self {
+        self.iif = Some(iif);
+        self
+    }
+    #[must_use]
+    pub fn with_oif(mut self, oif: InterfaceIndex) -> Self {
+        self.oifs.insert(oif);
+        self
+    }
+    /// Tell if packets received on interface `iif` pass the RPF check of this mroute
+    #[must_use]
+    pub fn rpf_check(&self, iif: Option<InterfaceIndex>) -> bool {
+        self.iif.is_none() || self.iif == iif
+    }
+}
+
+#[derive(Clone, Debug)]
+/// The table of [`Mroute`]s, by [`MrouteKey`]
+pub struct MrouteTable(HashMap<MrouteKey, Mroute, RandomState>);
+
+#[allow(clippy::new_without_default)]
+impl MrouteTable {
+    #[must_use]
+    pub fn new() -> Self {
+        Self(HashMap::with_hasher(RandomState::with_seed(0)))
+    }
+    /// Add an [`Mroute`], replacing the one with the same key, if any
+    pub fn add_mroute(&mut self, mroute: Mroute) {
+        self.0.insert(mroute.key, mroute);
+    }
+    pub fn del_mroute(&mut self, key: &MrouteKey) {
+        self.0.remove(key);
+    }
+    #[must_use]
+    pub fn get_mroute(&self, key: &MrouteKey) -> Option<&Mroute> {
+        self.0.get(key)
+    }
+    /// Look up the [`Mroute`] for a packet sent by `source` to `group` in a VRF:
+    /// the (S,G) entry if there is one, or the (*,G) one otherwise.
+    #[must_use]
+    pub fn lookup(&self, vrfid: VrfId, source: IpAddr, group: IpAddr) -> Option<&Mroute> {
+        self.get_mroute(&MrouteKey::new(vrfid, Some(source), group))
+            .or_else(|| self.get_mroute(&MrouteKey::new(vrfid, None, group)))
+    }
+    pub fn values(&self) -> impl Iterator<Item = &Mroute> {
+        self.0.values()
+    }
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mroutetablerw::MrouteTableWriter;
+    use super::*;
+
+    fn ifindex(index: u32) -> InterfaceIndex {
+        InterfaceIndex::try_new(index).unwrap()
+    }
+    fn addr(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn test_mroute_table_lookup() {
+        let (mut mroutew, mroutetabler) = MrouteTableWriter::new();
+        let group = addr("239.1.1.1");
+        let source = addr("10.0.0.1");
+
+        let star_g = Mroute::new(MrouteKey::new(0, None, group))
+            .with_oif(ifindex(2))
+            .with_oif(ifindex(3));
+        let s_g = Mroute::new(MrouteKey::new(0, Some(source), group))
+            .with_iif(ifindex(1))
+            .with_oif(ifindex(4));
+        mroutew.add_mroute(star_g.clone(), false);
+        mroutew.add_mroute(s_g.clone(), false);
+        assert!(mroutetabler.enter().unwrap().is_empty());
+
+        mroutew.publish();
+        {
+            let mroutes = mroutetabler.enter().unwrap();
+            assert_eq!(mroutes.len(), 2);
+            assert_eq!(mroutes.lookup(0, source, group), Some(&s_g));
+            assert_eq!(mroutes.lookup(0, addr("10.0.0.2"), group), Some(&star_g));
+            assert!(mroutes.lookup(1, source, group).is_none());
+            assert!(mroutes.lookup(0, source, addr("239.1.1.2")).is_none());
+        }
+
+        // rpf check
+        assert!(s_g.rpf_check(Some(ifindex(1))));
+        assert!(!s_g.rpf_check(Some(ifindex(2))));
+        assert!(!s_g.rpf_check(None));
+        assert!(star_g.rpf_check(Some(ifindex(2))));
+
+        // removing the (S,G) entry, seen by a reader from the factory
+        let mroutetabler2 = mroutetabler.factory().handle();
+        mroutew.del_mroute(s_g.key, true);
+        assert_eq!(
+            mroutetabler2.enter().unwrap().lookup(0, source, group),
+            Some(&star_g)
+        );
+
+        mroutew.clear(true);
+        assert!(mroutetabler.enter().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Multicast routing table left-right
+
+use crate::mcast::{Mroute, MrouteKey, MrouteTable};
+use left_right::{Absorb, ReadGuard, ReadHandle, ReadHandleFactory, WriteHandle};
+
+enum MrouteTableChange {
+    Add(Mroute),
+    Del(MrouteKey),
+    Clear,
+}
+
+impl Absorb<MrouteTableChange> for MrouteTable {
+    fn absorb_first(&mut self, change: &mut MrouteTableChange, _: &Self) {
+        match change {
+            MrouteTableChange::Add(mroute) => self.add_mroute(mroute.clone()),
+            MrouteTableChange::Del(key) => self.del_mroute(key),
+            MrouteTableChange::Clear => self.clear(),
+        }
+    }
+    fn drop_first(self: Box<Self>) {}
+    fn sync_with(&mut self, first: &Self) {
+        *self = first.clone();
+    }
+}
+
+pub struct MrouteTableWriter(WriteHandle<MrouteTable, MrouteTableChange>);
+impl MrouteTableWriter {
+    #[must_use]
+    pub fn new() -> (MrouteTableWriter, MrouteTableReader) {
+        let (w, r) =
+            left_right::new_from_empty::<MrouteTable, MrouteTableChange>(MrouteTable::new());
+        (MrouteTableWriter(w), MrouteTableReader(r))
+    }
+    #[must_use]
+    pub fn as_mroutetable_reader(&self) -> MrouteTableReader {
+        MrouteTableReader::new(self.0.clone())
+    }
+    pub fn enter(&self) -> Option<ReadGuard<'_, MrouteTable>> {
+        self.0.enter()
+    }
+    pub fn add_mroute(&mut self, mroute: Mroute, publish: bool) {
+        self.0.append(MrouteTableChange::Add(mroute));
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn del_mroute(&mut self, key: MrouteKey, publish: bool) {
+        self.0.append(MrouteTableChange::Del(key));
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn clear(&mut self, publish: bool) {
+        self.0.append(MrouteTableChange::Clear);
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn publish(&mut self) {
+        self.0.publish();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MrouteTableReader(ReadHandle<MrouteTable>);
+impl MrouteTableReader {
+    pub fn new(rhandle: ReadHandle<MrouteTable>) -> Self {
+        MrouteTableReader(rhandle)
+    }
+    pub fn enter(&self) -> Option<ReadGuard<'_, MrouteTable>> {
+        self.0.enter()
+    }
+    pub fn factory(&self) -> MrouteTableReaderFactory {
+        MrouteTableReaderFactory(self.0.factory())
+    }
+}
+
+#[derive(Debug)]
+pub struct MrouteTableReaderFactory(ReadHandleFactory<MrouteTable>);
+impl MrouteTableReaderFactory {
+    #[must_use]
+    pub fn handle(&self) -> MrouteTableReader {
+        MrouteTableReader(self.0.handle())
+    }
+}
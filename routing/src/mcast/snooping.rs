@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Table of the multicast group members learnt with IGMP/MLD snooping, shared between the
+//! packet processing workers, which learn from the reports and replicate, and the CLI.
+//! Members are tracked per interface: a group is forwarded to an interface as long as some
+//! host on it has reported its membership within the last [`MEMBERSHIP_INTERVAL`].
+
+use crate::rib::vrf::VrfId;
+use net::interface::InterfaceIndex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Time after which a membership that is not reported again expires (the default group
+/// membership interval of RFC 3376 and RFC 3810)
+pub const MEMBERSHIP_INTERVAL: Duration = Duration::from_secs(260);
+
+/// The table of snooped group members: the interfaces with members of each group, with the
+/// time of their last report
+#[derive(Clone, Debug, Default)]
+pub struct McastMemberTable(BTreeMap<(VrfId, IpAddr), BTreeMap<InterfaceIndex, Instant>>);
+
+impl McastMemberTable {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// Iterate over the groups, with their VRF and the interfaces they have members on
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (VrfId, IpAddr, impl Iterator<Item = InterfaceIndex>)> {
+        self.0
+            .iter()
+            .map(|((vrfid, group), members)| (*vrfid, *group, members.keys().copied()))
+    }
+    fn purge(&mut self, now: Instant) {
+        self.0.retain(|_, members| {
+            members.retain(|_, last_seen| {
+                now.saturating_duration_since(*last_seen) < MEMBERSHIP_INTERVAL
+            });
+            !members.is_empty()
+        });
+    }
+}
+
+/// A handle to a shared [`McastMemberTable`]. Cloning the handle does not clone the table.
+#[derive(Clone, Debug, Default)]
+pub struct McastMembers(Arc<Mutex<McastMemberTable>>);
+
+impl McastMembers {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, McastMemberTable> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Learn (or refresh) a member of `group` in VRF `vrfid`, on interface `ifindex`
+    pub fn join(&self, vrfid: VrfId, group: IpAddr, ifindex: InterfaceIndex, now: Instant) {
+        let mut table = self.lock();
+        let members = table.0.entry((vrfid, group)).or_default();
+        if members.insert(ifindex, now).is_none() {
+            debug!("New member of group {group} (vrf {vrfid}) on ifindex {ifindex}");
+        }
+    }
+
+    /// Forget the members of `group` in VRF `vrfid` on interface `ifindex`. Interfaces are
+    /// assumed to connect a single host: the group is no longer forwarded on the interface.
+    pub fn leave(&self, vrfid: VrfId, group: IpAddr, ifindex: InterfaceIndex) {
+        let mut table = self.lock();
+        let Some(members) = table.0.get_mut(&(vrfid, group)) else {
+            return;
+        };
+        if members.remove(&ifindex).is_some() {
+            debug!("Member of group {group} (vrf {vrfid}) on ifindex {ifindex} left");
+        }
+        if members.is_empty() {
+            table.0.remove(&(vrfid, group));
+        }
+    }
+
+    /// Get the interfaces with members of `group` in VRF `vrfid` that have not expired at `now`
+    #[must_use]
+    pub fn members(&self, vrfid: VrfId, group: IpAddr, now: Instant) -> BTreeSet<InterfaceIndex> {
+        let table = self.lock();
+        table
+            .0
+            .get(&(vrfid, group))
+            .map(|members| {
+                members
+                    .iter()
+                    .filter(|(_, last_seen)| {
+                        now.saturating_duration_since(**last_seen) < MEMBERSHIP_INTERVAL
+                    })
+                    .map(|(ifindex, _)| *ifindex)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get a copy of the table of members that have not expired at `now`
+    #[must_use]
+    pub fn snapshot(&self, now: Instant) -> McastMemberTable {
+        let mut table = self.lock();
+        table.purge(now);
+        table.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ifindex(index: u32) -> InterfaceIndex {
+        InterfaceIndex::try_new(index).unwrap()
+    }
+
+    #[test]
+    fn test_mcast_members() {
+        let members = McastMembers::new();
+        let group: IpAddr = "239.1.1.1".parse().unwrap();
+        let now = Instant::now();
+
+        members.join(0, group, ifindex(2), now);
+        members.join(0, group, ifindex(3), now);
+        members.join(1, group, ifindex(4), now);
+        assert_eq!(
+            members.members(0, group, now),
+            BTreeSet::from([ifindex(2), ifindex(3)])
+        );
+        assert_eq!(members.snapshot(now).len(), 2);
+
+        // leaving
+        members.leave(0, group, ifindex(2));
+        assert_eq!(members.members(0, group, now), BTreeSet::from([ifindex(3)]));
+        members.leave(1, group, ifindex(4));
+        assert!(members.members(1, group, now).is_empty());
+        assert_eq!(members.snapshot(now).len(), 1);
+
+        // expiration, unless refreshed
+        let later = now + MEMBERSHIP_INTERVAL / 2;
+        members.join(0, group, ifindex(5), later);
+        let expiry = now + MEMBERSHIP_INTERVAL;
+        assert_eq!(
+            members.members(0, group, expiry),
+            BTreeSet::from([ifindex(5)])
+        );
+        let snapshot = members.snapshot(expiry);
+        let groups: Vec<_> = snapshot
+            .iter()
+            .map(|(vrfid, group, members)| (vrfid, group, members.collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(groups, [(0, group, vec![ifindex(5)])]);
+        assert!(members.snapshot(later + MEMBERSHIP_INTERVAL).is_empty());
+    }
+}
@@ -25,6 +25,8 @@ pub enum RouterEvent {
     FrrConfigApplyRequested(GenId),
     FrrConfigApplySuccess(GenId),
     FrrConfigApplyFailure(GenId),
+    FrrConfigRollbackSuccess(GenId),
+    FrrConfigRollbackFailure(GenId),
 }
 
 impl Display for RouterEvent {
@@ -57,6 +59,12 @@ impl Display for RouterEvent {
             RouterEvent::FrrConfigApplyFailure(genid) => {
                 write!(f, "FRR configuration for generation {genid} FAILED")?
             }
+            RouterEvent::FrrConfigRollbackSuccess(genid) => {
+                write!(f, "FRR configuration rolled back to generation {genid}")?
+            }
+            RouterEvent::FrrConfigRollbackFailure(genid) => {
+                write!(f, "FRR configuration rollback to generation {genid} FAILED")?
+            }
         }
         Ok(())
     }
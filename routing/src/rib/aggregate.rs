@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Route aggregation. An aggregate summarizes in the FIB the routes of a [`Vrf`] that
+//! are more specific than a given prefix (its contributors). The RIB is left untouched:
+//! aggregation only determines what gets installed in the FIB.
+
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::debug;
+
+use super::nexthop::NhopKey;
+use super::vrf::Vrf;
+use crate::RouterError;
+use lpm::prefix::Prefix;
+
+//////////////////////////////////////////////////////////////////////////////////
+/// An [`Aggregate`] is a summary prefix for the routes of a [`Vrf`] it covers.
+/// While the [`Vrf`] has routes more specific than the summary prefix, a route to
+/// the summary prefix is installed in the FIB, with the next-hops shared by most of
+/// those routes. If `summary_only` is set, the more specific routes with those
+/// next-hops are not installed in the FIB (they are suppressed). N.B. destinations
+/// within the summary prefix not covered by any route are forwarded as per the summary.
+/// A route to the summary prefix itself in the RIB always takes precedence over the
+/// summary, in which case no route is suppressed.
+//////////////////////////////////////////////////////////////////////////////////
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Aggregate {
+    pub prefix: Prefix,
+    pub summary_only: bool,
+}
+
+impl Aggregate {
+    #[must_use]
+    pub fn new(prefix: Prefix) -> Self {
+        Self {
+            prefix,
+            summary_only: false,
+        }
+    }
+    #[must_use]
+    pub fn summary_only(mut self) -> Self {
+        self.summary_only = true;
+        self
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Validate a set of aggregates for a [`Vrf`]. Aggregates may not be default
+    /// routes, nor cover one another.
+    //////////////////////////////////////////////////////////////////////////////////
+    pub fn validate_set(aggregates: &BTreeSet<Aggregate>) -> Result<(), RouterError> {
+        if aggregates.iter().any(|a| a.prefix.is_root()) {
+            return Err(RouterError::InvalidConfig("Aggregate for a default route"));
+        }
+        for a in aggregates {
+            if aggregates
+                .iter()
+                .any(|b| a != b && a.prefix.covers(&b.prefix))
+            {
+                return Err(RouterError::InvalidConfig("Overlapping aggregates"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The state of an [`Aggregate`] in a [`Vrf`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct AggregateState {
+    summary: Option<Vec<NhopKey>>, /* next-hops of the summary route, if installed */
+    suppressed: BTreeSet<Prefix>,  /* contributors not installed in the FIB */
+}
+
+impl AggregateState {
+    pub(crate) fn is_suppressed(&self, prefix: &Prefix) -> bool {
+        self.suppressed.contains(prefix)
+    }
+}
+
+impl Vrf {
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Set the [`Aggregate`]s of a [`Vrf`], updating its FIB accordingly
+    //////////////////////////////////////////////////////////////////////////////////
+    pub fn set_aggregates(&mut self, aggregates: &BTreeSet<Aggregate>) {
+        debug!("Updating aggregates of VRF {}...", self.name);
+        let removed: Vec<Aggregate> = self
+            .aggregates
+            .keys()
+            .filter(|a| !aggregates.contains(a))
+            .copied()
+            .collect();
+        for aggregate in &removed {
+            self.withdraw_aggregate(aggregate);
+        }
+        for aggregate in aggregates {
+            if !self.aggregates.contains_key(aggregate) {
+                self.aggregates
+                    .insert(*aggregate, AggregateState::default());
+                self.refresh_aggregate(aggregate, None);
+            }
+        }
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Get the [`Aggregate`]s of a [`Vrf`]
+    //////////////////////////////////////////////////////////////////////////////////
+    #[must_use]
+    pub fn get_aggregates(&self) -> BTreeSet<Aggregate> {
+        self.aggregates.keys().copied().collect()
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Get the [`Aggregate`] covering some prefix (or equal to it), if any
+    //////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn covering_aggregate(&self, prefix: &Prefix) -> Option<Aggregate> {
+        self.aggregates
+            .keys()
+            .find(|a| a.prefix.covers(prefix))
+            .copied()
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Tell if the route to some prefix is suppressed by an [`Aggregate`]
+    //////////////////////////////////////////////////////////////////////////////////
+    #[must_use]
+    pub fn is_suppressed(&self, prefix: &Prefix) -> bool {
+        self.aggregates
+            .values()
+            .any(|state| state.is_suppressed(prefix))
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Get the next-hops of the summary route of an [`Aggregate`], if installed
+    //////////////////////////////////////////////////////////////////////////////////
+    #[must_use]
+    pub fn get_summary(&self, aggregate: &Aggregate) -> Option<&Vec<NhopKey>> {
+        self.aggregates.get(aggregate)?.summary.as_ref()
+    }
+
+    /// Get the contributors of an aggregate, grouped by the next-hops they'd be installed with
+    fn aggregate_contributors(&self, aggregate: &Aggregate) -> BTreeMap<Vec<NhopKey>, Vec<Prefix>> {
+        let v4 = self.routesv4.iter().map(|(p, r)| (Prefix::from(*p), r));
+        let v6 = self.routesv6.iter().map(|(p, r)| (Prefix::from(*p), r));
+        let mut groups: BTreeMap<Vec<NhopKey>, Vec<Prefix>> = BTreeMap::new();
+        for (prefix, route) in v4
+            .chain(v6)
+            .filter(|(p, _)| *p != aggregate.prefix && aggregate.prefix.covers(p))
+        {
+            groups
+                .entry(route.fib_nhop_keys())
+                .or_default()
+                .push(prefix);
+        }
+        groups
+    }
+
+    /// Compute the state an aggregate should have given the routes in the RIB
+    fn aggregate_state(&self, aggregate: &Aggregate) -> AggregateState {
+        if self.get_route(aggregate.prefix).is_some() {
+            return AggregateState::default();
+        }
+        // pick the next-hops shared by most contributors. On a tie, the first group wins.
+        let mut best: Option<(Vec<NhopKey>, Vec<Prefix>)> = None;
+        for (keys, prefixes) in self.aggregate_contributors(aggregate) {
+            if best.as_ref().is_none_or(|(_, b)| prefixes.len() > b.len()) {
+                best = Some((keys, prefixes));
+            }
+        }
+        match best {
+            None => AggregateState::default(),
+            Some((keys, prefixes)) => AggregateState {
+                summary: Some(keys),
+                suppressed: if aggregate.summary_only {
+                    prefixes.into_iter().collect()
+                } else {
+                    BTreeSet::new()
+                },
+            },
+        }
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Recompute the state of an [`Aggregate`] and update the FIB with the changes.
+    /// `changed` is the prefix covered by the aggregate whose route was just added or
+    /// replaced in the RIB, if any, which is installed in the FIB unless suppressed.
+    //////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn refresh_aggregate(&mut self, aggregate: &Aggregate, changed: Option<&Prefix>) {
+        let Some(old) = self.aggregates.get(aggregate).cloned() else {
+            return;
+        };
+        let new = self.aggregate_state(aggregate);
+        let mut install: Vec<(Prefix, Vec<NhopKey>)> = old
+            .suppressed
+            .difference(&new.suppressed)
+            .chain(changed.filter(|p| !new.suppressed.contains(*p)))
+            .filter_map(|p| self.get_route(*p).map(|r| (*p, r.fib_nhop_keys())))
+            .collect();
+        if new.summary.is_some() && (new.summary != old.summary || changed.is_some()) {
+            if let Some(keys) = &new.summary {
+                install.push((aggregate.prefix, keys.clone()));
+            }
+        }
+
+        if let Some(fibw) = &mut self.fibw {
+            let exact = install.iter().any(|(p, _)| *p == aggregate.prefix);
+            if old.summary.is_some() && new.summary.is_none() && !exact {
+                fibw.del_fibroute(aggregate.prefix, false);
+            }
+            for prefix in new.suppressed.difference(&old.suppressed) {
+                fibw.del_fibroute(*prefix, false);
+            }
+            for (prefix, keys) in install {
+                fibw.add_fibroute(prefix, keys, false);
+            }
+            fibw.publish();
+        }
+        if new != old {
+            debug!(
+                "Aggregate {} of VRF {}: summary installed: {}, suppressed {} routes",
+                aggregate.prefix,
+                self.name,
+                new.summary.is_some(),
+                new.suppressed.len()
+            );
+        }
+        self.aggregates.insert(*aggregate, new);
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////
+    /// Refresh all the [`Aggregate`]s of a [`Vrf`]
+    //////////////////////////////////////////////////////////////////////////////////
+    pub(crate) fn refresh_aggregates(&mut self) {
+        let aggregates: Vec<Aggregate> = self.aggregates.keys().copied().collect();
+        for aggregate in &aggregates {
+            self.refresh_aggregate(aggregate, None);
+        }
+    }
+
+    /// Remove an aggregate, restoring the routes it suppressed and removing its summary
+    fn withdraw_aggregate(&mut self, aggregate: &Aggregate) {
+        let Some(state) = self.aggregates.remove(aggregate) else {
+            return;
+        };
+        let restore: Vec<(Prefix, Vec<NhopKey>)> = state
+            .suppressed
+            .iter()
+            .filter_map(|p| self.get_route(*p).map(|r| (*p, r.fib_nhop_keys())))
+            .collect();
+        if let Some(fibw) = &mut self.fibw {
+            if state.summary.is_some() {
+                fibw.del_fibroute(aggregate.prefix, false);
+            }
+            for (prefix, keys) in restore {
+                fibw.add_fibroute(prefix, keys, false);
+            }
+            fibw.publish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evpn::RmacStore;
+    use crate::fib::fibtype::{FibKey, FibWriter};
+    use crate::rib::vrf::tests::{build_test_nhop, build_test_route, mk_addr};
+    use crate::rib::vrf::{RouteNhop, RouteOrigin, RouterVrfConfig};
+
+    fn add_route(vrf: &mut Vrf, prefix: &str, nhop: &RouteNhop, rstore: &RmacStore) {
+        let route = build_test_route(RouteOrigin::Bgp, 20, 0);
+        let prefix = Prefix::expect_from(prefix);
+        vrf.add_route_complete(&prefix, route, &[nhop.clone()], None, rstore);
+    }
+
+    #[test]
+    fn test_aggregate_validation() {
+        let mut aggregates = BTreeSet::new();
+        aggregates.insert(Aggregate::new(Prefix::expect_from("192.168.0.0/16")));
+        aggregates.insert(Aggregate::new(Prefix::expect_from("2001:db8::/32")).summary_only());
+        assert!(Aggregate::validate_set(&aggregates).is_ok());
+
+        aggregates.insert(Aggregate::new(Prefix::expect_from("192.168.1.0/24")));
+        assert!(Aggregate::validate_set(&aggregates).is_err());
+
+        let mut aggregates = BTreeSet::new();
+        aggregates.insert(Aggregate::new(Prefix::root_v4()));
+        assert!(Aggregate::validate_set(&aggregates).is_err());
+    }
+
+    #[test]
+    fn test_aggregate_summary_only() {
+        let rstore = RmacStore::new();
+        let mut vrf = Vrf::new(&RouterVrfConfig::new(0, "default"));
+        let (fibw, fibr) = FibWriter::new(FibKey::Id(0));
+        vrf.set_fibw(fibw);
+        let lpm = |address: &str| fibr.lpm_route_with_prefix(mk_addr(address)).unwrap().0;
+
+        let nhop_a = build_test_nhop(Some("10.0.0.1"), Some(1), 0, None);
+        let nhop_b = build_test_nhop(Some("10.0.1.1"), Some(2), 0, None);
+        add_route(&mut vrf, "192.168.0.0/24", &nhop_a, &rstore);
+        add_route(&mut vrf, "192.168.1.0/24", &nhop_a, &rstore);
+        add_route(&mut vrf, "192.168.2.0/24", &nhop_b, &rstore);
+
+        // summarize: the routes via nhop_a are suppressed, the summary forwards like them
+        let aggregate = Aggregate::new(Prefix::expect_from("192.168.0.0/16")).summary_only();
+        vrf.set_aggregates(&BTreeSet::from([aggregate]));
+        assert_eq!(vrf.get_summary(&aggregate), Some(&vec![nhop_a.key.clone()]));
+        assert!(vrf.is_suppressed(&Prefix::expect_from("192.168.0.0/24")));
+        assert!(vrf.is_suppressed(&Prefix::expect_from("192.168.1.0/24")));
+        assert!(!vrf.is_suppressed(&Prefix::expect_from("192.168.2.0/24")));
+        assert_eq!(lpm("192.168.0.1"), aggregate.prefix);
+        assert_eq!(lpm("192.168.2.1"), Prefix::expect_from("192.168.2.0/24"));
+        assert_eq!(lpm("192.168.3.1"), aggregate.prefix);
+        assert_eq!(fibr.enter().unwrap().len_v4(), 3);
+
+        // nhop_b becomes the most common next-hop: the routes via nhop_a get reinstalled
+        add_route(&mut vrf, "192.168.3.0/24", &nhop_b, &rstore);
+        add_route(&mut vrf, "192.168.4.0/24", &nhop_b, &rstore);
+        assert_eq!(vrf.get_summary(&aggregate), Some(&vec![nhop_b.key.clone()]));
+        assert_eq!(lpm("192.168.0.1"), Prefix::expect_from("192.168.0.0/24"));
+        assert_eq!(lpm("192.168.2.1"), aggregate.prefix);
+        assert_eq!(lpm("192.168.4.1"), aggregate.prefix);
+
+        // a route to the aggregate prefix takes precedence over the summary
+        add_route(&mut vrf, "192.168.0.0/16", &nhop_a, &rstore);
+        assert!(vrf.get_summary(&aggregate).is_none());
+        assert!(!vrf.is_suppressed(&Prefix::expect_from("192.168.2.0/24")));
+        assert_eq!(lpm("192.168.2.1"), Prefix::expect_from("192.168.2.0/24"));
+        assert_eq!(lpm("192.168.5.1"), aggregate.prefix);
+        vrf.del_route(aggregate.prefix, None, &rstore);
+        assert_eq!(vrf.get_summary(&aggregate), Some(&vec![nhop_b.key.clone()]));
+        assert_eq!(lpm("192.168.2.1"), aggregate.prefix);
+
+        // removing the aggregate restores all the routes and withdraws the summary
+        vrf.set_aggregates(&BTreeSet::new());
+        assert!(vrf.get_aggregates().is_empty());
+        assert_eq!(lpm("192.168.2.1"), Prefix::expect_from("192.168.2.0/24"));
+        assert_eq!(lpm("192.168.5.1"), Prefix::root_v4());
+    }
+
+    #[test]
+    fn test_aggregate_withdrawn_without_contributors() {
+        let rstore = RmacStore::new();
+        let mut vrf = Vrf::new(&RouterVrfConfig::new(0, "default"));
+        let (fibw, fibr) = FibWriter::new(FibKey::Id(0));
+        vrf.set_fibw(fibw);
+        let lpm = |address: &str| fibr.lpm_route_with_prefix(mk_addr(address)).unwrap().0;
+
+        // without contributors, there's no summary
+        let aggregate = Aggregate::new(Prefix::expect_from("2001:db8::/32"));
+        vrf.set_aggregates(&BTreeSet::from([aggregate]));
+        assert!(vrf.get_summary(&aggregate).is_none());
+        assert_eq!(lpm("2001:db8::1"), Prefix::root_v6());
+
+        // contributors are not suppressed unless summary-only
+        let nhop = build_test_nhop(Some("fe80::1"), Some(1), 0, None);
+        add_route(&mut vrf, "2001:db8:1::/48", &nhop, &rstore);
+        assert_eq!(vrf.get_summary(&aggregate), Some(&vec![nhop.key.clone()]));
+        assert!(!vrf.is_suppressed(&Prefix::expect_from("2001:db8:1::/48")));
+        assert_eq!(lpm("2001:db8:1::1"), Prefix::expect_from("2001:db8:1::/48"));
+        assert_eq!(lpm("2001:db8:2::1"), aggregate.prefix);
+
+        // the summary goes away with the last contributor
+        vrf.del_route(Prefix::expect_from("2001:db8:1::/48"), None, &rstore);
+        assert!(vrf.get_summary(&aggregate).is_none());
+        assert_eq!(lpm("2001:db8:2::1"), Prefix::root_v6());
+    }
+}
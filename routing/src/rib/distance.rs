@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Administrative distance. A [`Vrf`] may hold routes to the same prefix from distinct sources,
+//! e.g. a static route from the configuration and a BGP route learnt from FRR. Only the
+//! preferred one, with the lowest administrative distance, is selected and installed in the
+//! FIB. The others are kept aside as alternates, and the preferred alternate gets selected when
+//! the selected route is withdrawn.
+//!
+//! [`Vrf`]: crate::rib::Vrf
+
+use super::vrf::{Route, RouteFlags, RouteNhop, RouteOrigin};
+use lpm::prefix::Prefix;
+use std::collections::BTreeMap;
+
+/// The administrative distances to set to the routes learnt from FRR, per protocol. Routes from
+/// protocols without an entry keep the distance set by FRR.
+pub type AdminDistances = BTreeMap<RouteOrigin, u8>;
+
+/// The source of a [`Route`]. Routes from the same source replace each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouteSource {
+    /// Routes learnt from FRR, of any protocol: FRR already selects a single route per prefix
+    Frr,
+    /// Routes of a protocol, added by other means (e.g. the static routes of the configuration)
+    Protocol(RouteOrigin),
+}
+
+impl Route {
+    /// Get the [`RouteSource`] of a route
+    #[must_use]
+    pub fn source(&self) -> RouteSource {
+        if self.flags.contains(RouteFlags::FRR) {
+            RouteSource::Frr
+        } else {
+            RouteSource::Protocol(self.origin)
+        }
+    }
+
+    /// Tell if a route is preferred over `other`: the lowest distance wins and, for equal
+    /// distances, the order of the protocols in [`RouteOrigin`] decides.
+    #[must_use]
+    pub fn is_preferred_over(&self, other: &Route) -> bool {
+        (self.distance, self.origin) < (other.distance, other.origin)
+    }
+}
+
+/// A route kept aside, along with the next-hops to install it with
+#[derive(Debug, Clone)]
+pub(crate) struct Alternate {
+    pub(crate) route: Route,
+    pub(crate) nhops: Vec<RouteNhop>,
+}
+
+/// The alternate routes of a [`Vrf`], per prefix. There is at most one alternate route per
+/// prefix and [`RouteSource`], and a prefix only has alternate routes if it has a selected one.
+///
+/// [`Vrf`]: crate::rib::Vrf
+#[derive(Debug, Default)]
+pub(crate) struct Alternates(BTreeMap<Prefix, Vec<Alternate>>);
+
+impl Alternates {
+    /// Keep a route to `prefix` aside, replacing the one from the same source, if any
+    pub(crate) fn insert(&mut self, prefix: Prefix, alternate: Alternate) {
+        let alternates = self.0.entry(prefix).or_default();
+        let source = alternate.route.source();
+        alternates.retain(|a| a.route.source() != source);
+        alternates.push(alternate);
+    }
+
+    /// Remove the alternate route to `prefix` from `source`. Returns true if there was one.
+    pub(crate) fn remove(&mut self, prefix: &Prefix, source: RouteSource) -> bool {
+        let Some(alternates) = self.0.get_mut(prefix) else {
+            return false;
+        };
+        let len = alternates.len();
+        alternates.retain(|a| a.route.source() != source);
+        let removed = alternates.len() != len;
+        if alternates.is_empty() {
+            self.0.remove(prefix);
+        }
+        removed
+    }
+
+    /// Tell if there is an alternate route to `prefix` from `source`
+    pub(crate) fn contains(&self, prefix: &Prefix, source: RouteSource) -> bool {
+        self.get(prefix).any(|route| route.source() == source)
+    }
+
+    /// Get the preferred alternate route to `prefix`, if any
+    pub(crate) fn best(&self, prefix: &Prefix) -> Option<&Alternate> {
+        self.0.get(prefix)?.iter().reduce(|best, a| {
+            if a.route.is_preferred_over(&best.route) {
+                a
+            } else {
+                best
+            }
+        })
+    }
+
+    /// Take the preferred alternate route to `prefix`, if any
+    pub(crate) fn take_best(&mut self, prefix: &Prefix) -> Option<Alternate> {
+        let source = self.best(prefix)?.route.source();
+        let alternates = self.0.get_mut(prefix)?;
+        let index = alternates.iter().position(|a| a.route.source() == source)?;
+        let best = alternates.swap_remove(index);
+        if alternates.is_empty() {
+            self.0.remove(prefix);
+        }
+        Some(best)
+    }
+
+    /// Iterate over the alternate routes to `prefix`
+    pub(crate) fn get(&self, prefix: &Prefix) -> impl Iterator<Item = &Route> {
+        self.0.get(prefix).into_iter().flatten().map(|a| &a.route)
+    }
+
+    /// Iterate over all the alternate routes
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Prefix, &Route)> {
+        self.0
+            .iter()
+            .flat_map(|(prefix, alternates)| alternates.iter().map(move |a| (prefix, &a.route)))
+    }
+
+    /// Set/unset the stale flag of all the alternate routes
+    pub(crate) fn set_stale(&mut self, value: bool) {
+        self.0
+            .values_mut()
+            .flatten()
+            .for_each(|a| a.route.set_stale(value));
+    }
+
+    /// Remove all the alternate routes marked as stale
+    pub(crate) fn remove_stale(&mut self) {
+        self.0.retain(|_, alternates| {
+            alternates.retain(|a| !a.route.is_stale());
+            !alternates.is_empty()
+        });
+    }
+
+    /// Number of alternate routes
+    pub(crate) fn len(&self) -> usize {
+        self.0.values().map(Vec::len).sum()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rib::vrf::tests::build_test_route;
+
+    fn alternate(origin: RouteOrigin, distance: u8) -> Alternate {
+        Alternate {
+            route: build_test_route(origin, distance, 0),
+            nhops: vec![RouteNhop::default()],
+        }
+    }
+
+    #[test]
+    fn test_route_preference() {
+        let connected = build_test_route(RouteOrigin::Connected, 0, 0);
+        let ospf = build_test_route(RouteOrigin::Ospf, 110, 0);
+        let bgp = build_test_route(RouteOrigin::Bgp, 20, 0);
+        assert!(connected.is_preferred_over(&bgp));
+        assert!(bgp.is_preferred_over(&ospf));
+        assert!(!ospf.is_preferred_over(&bgp));
+
+        // ties are broken by protocol
+        let ospf = build_test_route(RouteOrigin::Ospf, 20, 0);
+        assert!(ospf.is_preferred_over(&bgp));
+        assert!(!bgp.is_preferred_over(&ospf));
+
+        // FRR routes share a single source, whatever their protocol
+        let mut frr = bgp.clone();
+        frr.flags.insert(RouteFlags::FRR);
+        assert_eq!(frr.source(), RouteSource::Frr);
+        assert_eq!(bgp.source(), RouteSource::Protocol(RouteOrigin::Bgp));
+    }
+
+    #[test]
+    fn test_alternates() {
+        let prefix = Prefix::expect_from("10.0.0.0/24");
+        let mut alternates = Alternates::default();
+        assert!(alternates.best(&prefix).is_none());
+
+        alternates.insert(prefix, alternate(RouteOrigin::Bgp, 200));
+        alternates.insert(prefix, alternate(RouteOrigin::Ospf, 110));
+        assert_eq!(alternates.len(), 2);
+        let static_source = RouteSource::Protocol(RouteOrigin::Static);
+        assert!(!alternates.contains(&prefix, static_source));
+
+        // an alternate from the same source is replaced
+        alternates.insert(prefix, alternate(RouteOrigin::Bgp, 20));
+        assert_eq!(alternates.len(), 2);
+        assert_eq!(alternates.best(&prefix).unwrap().route.distance, 20);
+
+        let best = alternates.take_best(&prefix).unwrap();
+        assert_eq!(best.route.origin, RouteOrigin::Bgp);
+        let ospf_source = RouteSource::Protocol(RouteOrigin::Ospf);
+        assert!(alternates.contains(&prefix, ospf_source));
+
+        // stale alternates are removed
+        alternates.set_stale(true);
+        alternates.remove_stale();
+        assert_eq!(alternates.len(), 0);
+        assert!(!alternates.remove(&prefix, ospf_source));
+    }
+}
@@ -3,11 +3,15 @@
 
 //! RIB state
 
+pub mod aggregate;
+pub mod distance;
 pub mod encapsulation;
 pub mod nexthop;
+pub mod policy;
 pub mod rib2fib;
 pub mod vrf;
 pub mod vrftable;
+pub mod watch;
 
 // re-exports
 pub use vrf::Vrf;
@@ -19,11 +19,11 @@ use std::net::IpAddr;
 use std::option::Option;
 
 use net::interface::InterfaceIndex;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 #[cfg(test)]
 use std::str::FromStr;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 use tracectl::trace_target;
 trace_target!("next-hops", LevelFilter::WARN, &["routing-full"]);
@@ -41,6 +41,7 @@ pub struct Nhop {
     pub(crate) resolvers: RefCell<Vec<Rc<Nhop>>>,
     pub(crate) instructions: RefCell<Vec<PktInstruction>>,
     pub(crate) fibgroup: RefCell<FibGroup>,
+    pub(crate) alive: Cell<bool>,
 }
 
 #[derive(Debug, Default, Copy, Clone, Hash, Eq, PartialEq, PartialOrd, Ord)]
@@ -96,6 +97,15 @@ impl NhopKey {
             ifname: None,
         }
     }
+    //////////////////////////////////////////////////////////////////
+    /// Tell if the address of a next-hop key is an IPv6 link-local address.
+    /// Such addresses only have meaning on a link: next-hops using them
+    /// need an interface and can't be resolved with a routing table.
+    //////////////////////////////////////////////////////////////////
+    #[must_use]
+    pub fn is_link_local(&self) -> bool {
+        matches!(self.address, Some(IpAddr::V6(a)) if a.is_unicast_link_local())
+    }
     #[cfg(test)]
     pub fn expect_from(address: &str) -> Self {
         Self {
@@ -172,9 +182,19 @@ impl Nhop {
             resolvers: RefCell::new(Vec::new()),
             instructions: RefCell::new(Vec::with_capacity(2)),
             fibgroup: RefCell::new(FibGroup::new()),
+            alive: Cell::new(true),
         }
     }
 
+    //////////////////////////////////////////////////////////////////
+    /// Tell if a next-hop is usable for forwarding. A next-hop is no
+    /// longer usable if a BFD session detected the failure of its peer.
+    //////////////////////////////////////////////////////////////////
+    #[must_use]
+    pub fn is_alive(&self) -> bool {
+        self.alive.get()
+    }
+
     //////////////////////////////////////////////////////////////////////////////////////////////////////
     /// Store a reference to some Nhop 'resolver' in the current next-hop Self.
     /// Note well:
@@ -203,6 +223,11 @@ impl Nhop {
         let Some(a) = self.key.address else {
             return; /* done */
         };
+        if self.key.is_link_local() {
+            warn!("Can't resolve link-local next-hop {a}: no interface");
+            self.resolvers.replace(Vec::new());
+            return;
+        }
         let mut resolvers = Vec::new();
         debug!("Resolving {a} with vrf '{}'({})", vrf.name, vrf.vrfid);
         let (prefix, route) = vrf.lpm(a);
@@ -375,6 +400,26 @@ impl NhopStore {
         self.iter().for_each(|nhop| nhop.lazy_resolve(vrf));
     }
     ///////////////////////////////////////////////////////////////////
+    /// Update the liveness of all next-hops, given a predicate telling
+    /// if the next-hop with some key is down. Returns true if the liveness
+    /// of any next-hop changed.
+    ///////////////////////////////////////////////////////////////////
+    pub fn update_liveness(&self, is_down: impl Fn(&NhopKey) -> bool) -> bool {
+        let mut changed = false;
+        for nhop in self.iter() {
+            let alive = !is_down(&nhop.key);
+            if nhop.alive.replace(alive) != alive {
+                debug!(
+                    "Next-hop {} is now {}",
+                    nhop.key,
+                    if alive { "alive" } else { "down" }
+                );
+                changed = true;
+            }
+        }
+        changed
+    }
+    ///////////////////////////////////////////////////////////////////
     /// Rebuild the fibgroup for every next-hop. This method visits every next-hop and
     /// rebuilds its fibgroup. It returns a vector with only those next-hops whose
     /// fibgroup changed. We return a Vector and not an iterator to force the rebuild
@@ -0,0 +1,419 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Route policies (route-maps) applied to the routes received over the CPI,
+//! before they get installed in the RIB and the FIB.
+
+use crate::RouterError;
+use crate::rib::vrf::{Route, RouteOrigin, VrfId};
+use lpm::prefix::Prefix;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use tracing::{debug, error};
+
+/// A standard BGP community (RFC 1997), as `ASN:VALUE`
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Community(u32);
+
+impl Community {
+    #[must_use]
+    pub fn new(asn: u16, value: u16) -> Self {
+        Self((u32::from(asn) << 16) | u32::from(value))
+    }
+    #[must_use]
+    pub fn asn(&self) -> u16 {
+        (self.0 >> 16) as u16
+    }
+    #[must_use]
+    pub fn value(&self) -> u16 {
+        (self.0 & 0xffff) as u16
+    }
+}
+impl From<u32> for Community {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+impl Display for Community {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.asn(), self.value())
+    }
+}
+
+/// Whether prefix-list rules and policy entries permit or deny what they match
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PolicyAction {
+    Permit,
+    Deny,
+}
+
+/// A rule of a [`PrefixList`]. A rule without a prefix matches any prefix. A rule with
+/// a prefix matches the prefixes it covers whose length is within `ge` and `le`, or
+/// that prefix only if no length range is given.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrefixListRule {
+    pub action: PolicyAction,
+    pub prefix: Option<Prefix>,
+    pub ge: Option<u8>,
+    pub le: Option<u8>,
+}
+
+impl PrefixListRule {
+    #[must_use]
+    pub fn new(action: PolicyAction, prefix: Option<Prefix>) -> Self {
+        Self {
+            action,
+            prefix,
+            ge: None,
+            le: None,
+        }
+    }
+    #[must_use]
+    pub fn with_ge(mut self, ge: u8) -> Self {
+        self.ge = Some(ge);
+        self
+    }
+    #[must_use]
+    pub fn with_le(mut self, le: u8) -> Self {
+        self.le = Some(le);
+        self
+    }
+    fn matches(&self, prefix: &Prefix) -> bool {
+        if let Some(rule_prefix) = &self.prefix {
+            if !rule_prefix.covers(prefix) {
+                return false;
+            }
+            if self.ge.is_none() && self.le.is_none() {
+                return rule_prefix.length() == prefix.length();
+            }
+        }
+        let len = prefix.length();
+        self.ge.is_none_or(|ge| len >= ge) && self.le.is_none_or(|le| len <= le)
+    }
+}
+
+/// A named list of [`PrefixListRule`]s, evaluated in sequence order. The first rule
+/// that matches a prefix decides; prefixes matching no rule are denied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrefixList {
+    pub name: String,
+    rules: BTreeMap<u32, PrefixListRule>,
+}
+
+impl PrefixList {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            rules: BTreeMap::new(),
+        }
+    }
+    pub fn add_rule(&mut self, seq: u32, rule: PrefixListRule) {
+        self.rules.insert(seq, rule);
+    }
+    #[must_use]
+    pub fn permits(&self, prefix: &Prefix) -> bool {
+        self.rules
+            .values()
+            .find(|rule| rule.matches(prefix))
+            .is_some_and(|rule| rule.action == PolicyAction::Permit)
+    }
+}
+
+/// A condition that a route must meet for a [`PolicyEntry`] to apply to it
+#[derive(Clone, Debug, PartialEq)]
+pub enum PolicyMatch {
+    PrefixList(String),
+    Community(Community),
+    Origin(RouteOrigin),
+}
+
+/// A modification that a [`PolicyEntry`] applies to the routes it permits
+#[derive(Clone, Debug, PartialEq)]
+pub enum PolicySet {
+    Metric(u32),
+    Reject,
+}
+
+/// An entry of a [`RoutePolicy`]. It applies to the routes meeting all of its matches.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolicyEntry {
+    pub action: PolicyAction,
+    pub matches: Vec<PolicyMatch>,
+    pub sets: Vec<PolicySet>,
+}
+
+impl PolicyEntry {
+    #[must_use]
+    pub fn new(action: PolicyAction) -> Self {
+        Self {
+            action,
+            matches: vec![],
+            sets: vec![],
+        }
+    }
+    #[must_use]
+    pub fn add_match(mut self, m: PolicyMatch) -> Self {
+        self.matches.push(m);
+        self
+    }
+    #[must_use]
+    pub fn add_set(mut self, set: PolicySet) -> Self {
+        self.sets.push(set);
+        self
+    }
+}
+
+/// The outcome of evaluating a route against a [`RoutePolicy`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PolicyVerdict {
+    Accept,
+    Reject,
+}
+
+/// A named policy (route-map): a list of [`PolicyEntry`]s evaluated in sequence order.
+/// The first entry that applies to a route decides; routes no entry applies to are rejected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoutePolicy {
+    pub name: String,
+    entries: BTreeMap<u32, PolicyEntry>,
+}
+
+impl RoutePolicy {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            entries: BTreeMap::new(),
+        }
+    }
+    pub fn add_entry(&mut self, seq: u32, entry: PolicyEntry) {
+        self.entries.insert(seq, entry);
+    }
+    fn evaluate(
+        &self,
+        lists: &BTreeMap<String, PrefixList>,
+        prefix: &Prefix,
+        route: &mut Route,
+        communities: &[Community],
+    ) -> PolicyVerdict {
+        let matches = |m: &PolicyMatch| match m {
+            PolicyMatch::PrefixList(name) => lists.get(name).is_some_and(|l| l.permits(prefix)),
+            PolicyMatch::Community(community) => communities.contains(community),
+            PolicyMatch::Origin(origin) => route.origin == *origin,
+        };
+        let Some((seq, entry)) = self
+            .entries
+            .iter()
+            .find(|(_, entry)| entry.matches.iter().all(matches))
+        else {
+            return PolicyVerdict::Reject;
+        };
+        debug!(
+            "Route to {prefix} matches entry {seq} of policy {}",
+            self.name
+        );
+        if entry.action == PolicyAction::Deny {
+            return PolicyVerdict::Reject;
+        }
+        for set in &entry.sets {
+            match set {
+                PolicySet::Metric(metric) => route.metric = *metric,
+                PolicySet::Reject => return PolicyVerdict::Reject,
+            }
+        }
+        PolicyVerdict::Accept
+    }
+}
+
+/// The prefix lists and policies known to the router, and the policy used to import
+/// routes in each VRF. Routes are accepted as is in VRFs without an import policy.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoutePolicyTable {
+    prefix_lists: BTreeMap<String, PrefixList>,
+    policies: BTreeMap<String, RoutePolicy>,
+    imports: BTreeMap<VrfId, String>,
+}
+
+impl RoutePolicyTable {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add_prefix_list(&mut self, list: PrefixList) {
+        self.prefix_lists.insert(list.name.clone(), list);
+    }
+    pub fn add_policy(&mut self, policy: RoutePolicy) {
+        self.policies.insert(policy.name.clone(), policy);
+    }
+    pub fn set_import_policy(&mut self, vrfid: VrfId, policy: &str) {
+        self.imports.insert(vrfid, policy.to_owned());
+    }
+
+    /// Check that the policies only refer to existing prefix lists and policies
+    pub fn validate(&self) -> Result<(), RouterError> {
+        for (vrfid, name) in &self.imports {
+            if !self.policies.contains_key(name) {
+                error!("Import policy {name} of vrf {vrfid} does not exist");
+                return Err(RouterError::InvalidConfig("Unknown import policy"));
+            }
+        }
+        for policy in self.policies.values() {
+            let matches = policy.entries.values().flat_map(|entry| &entry.matches);
+            for m in matches {
+                if let PolicyMatch::PrefixList(name) = m {
+                    if !self.prefix_lists.contains_key(name) {
+                        error!(
+                            "Policy {} refers to unknown prefix list {name}",
+                            policy.name
+                        );
+                        return Err(RouterError::InvalidConfig("Unknown prefix list in policy"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluate the import policy of the VRF with id `vrfid`, if any, for a route to
+    /// `prefix` carrying the given `communities`. The policy may modify the `route`.
+    #[must_use]
+    pub fn import(
+        &self,
+        vrfid: VrfId,
+        prefix: &Prefix,
+        route: &mut Route,
+        communities: &[Community],
+    ) -> PolicyVerdict {
+        let Some(policy) = self.imports.get(&vrfid).and_then(|n| self.policies.get(n)) else {
+            return PolicyVerdict::Accept;
+        };
+        policy.evaluate(&self.prefix_lists, prefix, route, communities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bgp_route() -> Route {
+        Route {
+            origin: RouteOrigin::Bgp,
+            metric: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prefix_list() {
+        let mut list = PrefixList::new("PL");
+        let rule = PrefixListRule::new(
+            PolicyAction::Deny,
+            Some(Prefix::expect_from(("10.1.0.0", 16))),
+        )
+        .with_le(32);
+        list.add_rule(5, rule);
+        let rule = PrefixListRule::new(
+            PolicyAction::Permit,
+            Some(Prefix::expect_from(("10.0.0.0", 8))),
+        )
+        .with_ge(16)
+        .with_le(24);
+        list.add_rule(10, rule);
+        let rule = PrefixListRule::new(
+            PolicyAction::Permit,
+            Some(Prefix::expect_from(("0.0.0.0", 0))),
+        );
+        list.add_rule(20, rule);
+
+        assert!(list.permits(&Prefix::expect_from(("10.2.0.0", 16))));
+        assert!(list.permits(&Prefix::expect_from(("10.2.3.0", 24))));
+        assert!(list.permits(&Prefix::expect_from(("0.0.0.0", 0))));
+        assert!(!list.permits(&Prefix::expect_from(("10.1.3.0", 24))));
+        assert!(!list.permits(&Prefix::expect_from(("10.2.3.4", 32))));
+        assert!(!list.permits(&Prefix::expect_from(("10.0.0.0", 8))));
+        assert!(!list.permits(&Prefix::expect_from(("192.168.0.0", 16))));
+    }
+
+    #[test]
+    fn test_import_policy() {
+        let mut table = RoutePolicyTable::new();
+        let mut list = PrefixList::new("CUSTOMERS");
+        list.add_rule(
+            1,
+            PrefixListRule::new(
+                PolicyAction::Permit,
+                Some(Prefix::expect_from(("10.0.0.0", 8))),
+            )
+            .with_le(32),
+        );
+        table.add_prefix_list(list);
+
+        let mut policy = RoutePolicy::new("IMPORT");
+        let entry = PolicyEntry::new(PolicyAction::Permit)
+            .add_match(PolicyMatch::Community(Community::new(65000, 666)))
+            .add_set(PolicySet::Reject);
+        policy.add_entry(10, entry);
+        let entry = PolicyEntry::new(PolicyAction::Permit)
+            .add_match(PolicyMatch::PrefixList("CUSTOMERS".to_string()))
+            .add_match(PolicyMatch::Origin(RouteOrigin::Bgp))
+            .add_set(PolicySet::Metric(100));
+        policy.add_entry(20, entry);
+        table.add_policy(policy);
+
+        // no policy for vrf 0 yet: routes are accepted as is
+        let prefix = Prefix::expect_from(("10.1.0.0", 16));
+        let mut route = bgp_route();
+        assert_eq!(
+            table.import(0, &prefix, &mut route, &[]),
+            PolicyVerdict::Accept
+        );
+        assert_eq!(route.metric, 10);
+
+        table.set_import_policy(0, "IMPORT");
+        table.validate().expect("Should be valid");
+        assert_eq!(
+            table.import(0, &prefix, &mut route, &[]),
+            PolicyVerdict::Accept
+        );
+        assert_eq!(route.metric, 100);
+
+        let blackholed = [Community::new(65000, 666)];
+        let mut route = bgp_route();
+        assert_eq!(
+            table.import(0, &prefix, &mut route, &blackholed),
+            PolicyVerdict::Reject
+        );
+
+        // implicit deny
+        let mut route = bgp_route();
+        route.origin = RouteOrigin::Ospf;
+        assert_eq!(
+            table.import(0, &prefix, &mut route, &[]),
+            PolicyVerdict::Reject
+        );
+        let prefix = Prefix::expect_from(("192.168.0.0", 16));
+        let mut route = bgp_route();
+        assert_eq!(
+            table.import(0, &prefix, &mut route, &[]),
+            PolicyVerdict::Reject
+        );
+    }
+
+    #[test]
+    fn test_policy_validation() {
+        let mut table = RoutePolicyTable::new();
+        table.set_import_policy(0, "MISSING");
+        assert!(table.validate().is_err());
+
+        let mut policy = RoutePolicy::new("MISSING");
+        let entry = PolicyEntry::new(PolicyAction::Deny)
+            .add_match(PolicyMatch::PrefixList("NOPE".to_string()));
+        policy.add_entry(1, entry);
+        table.add_policy(policy);
+        assert!(table.validate().is_err());
+
+        table.add_prefix_list(PrefixList::new("NOPE"));
+        assert!(table.validate().is_ok());
+    }
+}
@@ -48,7 +48,14 @@ impl Nhop {
             instructions.push(PktInstruction::Egress(egress));
             return instructions;
         }
-        if self.key.ifindex.is_some() {
+        if self.key.ifindex.is_none() && self.key.is_link_local() {
+            /* link-local next-hops are unusable without an interface */
+            instructions.push(PktInstruction::Drop);
+            return instructions;
+        }
+        // N.B. a next-hop with an address and no interface gets the interface from its
+        // resolvers when squashing, but keeps its address as the gateway to use
+        if self.key.ifindex.is_some() || self.key.address.is_some() {
             let egress =
                 EgressObject::new(self.key.ifindex, self.key.address, self.key.ifname.clone());
             instructions.push(PktInstruction::Egress(egress));
@@ -87,8 +94,12 @@ impl Nhop {
             entry.squash(); /* squash entry before committing it to the group */
             fibgroup.add(entry); /* add fib entry to group */
         } else {
+            // skip the resolvers that are down, unless all are
+            let all_down = resolvers.iter().all(|resolver| !resolver.is_alive());
             for resolver in resolvers.iter() {
-                resolver.build_nhop_fibgroup_rec(fibgroup, entry.clone());
+                if all_down || resolver.is_alive() {
+                    resolver.build_nhop_fibgroup_rec(fibgroup, entry.clone());
+                }
             }
         }
     }
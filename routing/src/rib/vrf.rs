@@ -4,6 +4,7 @@
 //! VRF module to store Ipv4 and Ipv6 routing tables
 
 use bitflags::bitflags;
+use std::collections::{BTreeMap, BTreeSet};
 use std::hash::Hash;
 use std::iter::Filter;
 use std::net::IpAddr;
@@ -13,8 +14,13 @@ use tracing::debug;
 #[cfg(test)]
 use crate::pretty_utils::Frame;
 
+use super::aggregate::{Aggregate, AggregateState};
+use super::distance::{AdminDistances, Alternate, Alternates, RouteSource};
 use super::nexthop::{FwAction, Nhop, NhopKey, NhopStore};
+use super::watch::{RouteChange, RouteEvent, RouteWatch};
+use crate::bfd::{BfdSessionKey, nhop_is_withdrawn};
 use crate::evpn::{RmacStore, Vtep};
+use crate::fib::ecmp::EcmpConfig;
 use crate::fib::fibtype::{FibKey, FibReader, FibWriter};
 use lpm::prefix::{Ipv4Prefix, Ipv6Prefix, Prefix};
 use lpm::trie::{PrefixMapTrie, TrieMap, TrieMapFactory};
@@ -43,6 +49,7 @@ bitflags! {
     #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
     pub struct RouteFlags: u8 {
         const STALE = 0b0000_0001; /* the route is stale, if set */
+        const FRR   = 0b0000_0010; /* the route was learnt from FRR, if set */
     }
 }
 
@@ -89,6 +96,16 @@ impl Route {
             self.flags.remove(RouteFlags::STALE);
         }
     }
+    /// Get the keys of the next-hops of a route to install in the FIB. Next-hops that are
+    /// not alive are left out, unless none is.
+    pub(crate) fn fib_nhop_keys(&self) -> Vec<NhopKey> {
+        let all_down = self.s_nhops.iter().all(|shim| !shim.rc.is_alive());
+        self.s_nhops
+            .iter()
+            .filter(|shim| all_down || shim.rc.is_alive())
+            .map(|shim| shim.rc.key.clone())
+            .collect()
+    }
     pub fn is_preset_drop_route(&self) -> bool {
         self.origin == RouteOrigin::Other
             && self.s_nhops.len() == 1
@@ -132,6 +149,10 @@ pub struct Vrf {
     pub(crate) nhstore: NhopStore,
     pub(crate) vni: Option<Vni>,
     pub(crate) fibw: Option<FibWriter>,
+    pub(crate) aggregates: BTreeMap<Aggregate, AggregateState>,
+    pub(crate) alternates: Alternates,
+    pub(crate) admin_distances: AdminDistances,
+    pub(crate) watch: Option<RouteWatch>,
 }
 
 //////////////////////////////////////////////////////////////////////////////////
@@ -197,6 +218,10 @@ impl Vrf {
             routesv6,
             nhstore: NhopStore::new(),
             fibw: None,
+            aggregates: BTreeMap::new(),
+            alternates: Alternates::default(),
+            admin_distances: AdminDistances::new(),
+            watch: None,
         };
 
         /* add default routes with default next-hop with action DROP */
@@ -247,6 +272,43 @@ impl Vrf {
         self.fibw = Some(fibw);
     }
 
+    ////////////////////////////////////////////////////////////////////////
+    /// Set the [`RouteWatch`] to notify the route changes of a [`Vrf`] to
+    /////////////////////////////////////////////////////////////////////////
+    pub fn set_watch(&mut self, watch: RouteWatch) {
+        self.watch = Some(watch);
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    /// Set the administrative distances of the routes learnt from FRR. These
+    /// apply to the routes learnt after the change.
+    /////////////////////////////////////////////////////////////////////////
+    pub fn set_admin_distances(&mut self, distances: &AdminDistances) {
+        debug!("Updating administrative distances for VRF {}...", self.name);
+        self.admin_distances.clone_from(distances);
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    /// Get the administrative distances of the routes learnt from FRR
+    /////////////////////////////////////////////////////////////////////////
+    pub fn get_admin_distances(&self) -> &AdminDistances {
+        &self.admin_distances
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    /// Notify a change of the route to some prefix, if watched
+    /////////////////////////////////////////////////////////////////////////
+    fn notify(&self, prefix: &Prefix, change: RouteChange, origin: Option<RouteOrigin>) {
+        if let Some(watch) = &self.watch {
+            watch.emit(RouteEvent {
+                vrfid: self.vrfid,
+                prefix: *prefix,
+                change,
+                origin,
+            });
+        }
+    }
+
     ////////////////////////////////////////////////////////////////////////
     /// Get a fibreader for the fib associated to this [`Vrf`]
     /////////////////////////////////////////////////////////////////////////
@@ -337,6 +399,24 @@ impl Vrf {
         self.fibw.as_ref().map(|fibw| fibw.get_vtep().clone())
     }
 
+    /////////////////////////////////////////////////////////////////////////
+    /// Set the [`EcmpConfig`] for a [`Vrf`], which determines how packets are
+    /// spread over the next-hops of routes with multiple next-hops.
+    /////////////////////////////////////////////////////////////////////////
+    pub fn set_ecmp(&mut self, ecmp: EcmpConfig) {
+        if let Some(ref mut fibw) = self.fibw {
+            debug!("Updating ECMP config for VRF {}...", self.name);
+            fibw.set_ecmp(ecmp);
+        }
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    /// Get the [`EcmpConfig`] for a [`Vrf`]. N.B: this gets the value currently visible by readers
+    //////////////////////////////////////////////////////////////////////////////////////
+    pub fn get_ecmp(&self) -> Option<EcmpConfig> {
+        self.fibw.as_ref().map(FibWriter::get_ecmp)
+    }
+
     #[inline]
     #[must_use]
     /////////////////////////////////////////////////////////////////////////
@@ -449,7 +529,79 @@ impl Vrf {
         }
     }
 
+    /////////////////////////////////////////////////////////////////////////
+    /// Build the [`Alternate`] to keep a selected route aside
+    /////////////////////////////////////////////////////////////////////////
+    fn as_alternate(&self, route: &Route) -> Alternate {
+        let nhops = route
+            .s_nhops
+            .iter()
+            .map(|shim| RouteNhop {
+                vrfid: shim.ext_vrf.unwrap_or(self.vrfid),
+                key: shim.rc.key.clone(),
+            })
+            .collect();
+        let route = Route {
+            s_nhops: Vec::with_capacity(1),
+            ..route.clone()
+        };
+        Alternate { route, nhops }
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Add a route to a prefix and install it in the FIB if it is the preferred one. A route
+    /// replaces the one from the same source. Routes from other sources are kept aside if they
+    /// are not preferred over it, or keep it aside otherwise.
+    ////////////////////////////////////////////////////////////////////////////////////////////////
     pub fn add_route_complete(
+        &mut self,
+        prefix: &Prefix,
+        route: Route,
+        nhops: &[RouteNhop],
+        vrf0: Option<&Vrf>,
+        rstore: &RmacStore,
+    ) {
+        let source = route.source();
+        self.alternates.remove(prefix, source);
+        let selected = self
+            .get_route(*prefix)
+            .filter(|selected| !selected.is_preset_drop_route());
+        match selected {
+            Some(selected) if selected.source() != source => {
+                if !route.is_preferred_over(selected) {
+                    debug!(
+                        "vrf {}: keeping {} route to {prefix} aside",
+                        self.name, route.origin
+                    );
+                    let nhops = nhops.to_vec();
+                    self.alternates.insert(*prefix, Alternate { route, nhops });
+                    return;
+                }
+                let selected = self.as_alternate(selected);
+                self.alternates.insert(*prefix, selected);
+            }
+            _ => {
+                // the route replaces the selected one, but an alternate may be preferred
+                if self
+                    .alternates
+                    .best(prefix)
+                    .is_some_and(|best| best.route.is_preferred_over(&route))
+                {
+                    let best = self
+                        .alternates
+                        .take_best(prefix)
+                        .unwrap_or_else(|| unreachable!());
+                    let nhops = nhops.to_vec();
+                    self.alternates.insert(*prefix, Alternate { route, nhops });
+                    self.install_route(prefix, best.route, &best.nhops, vrf0, rstore);
+                    return;
+                }
+            }
+        }
+        self.install_route(prefix, route, nhops, vrf0, rstore);
+    }
+
+    fn install_route(
         &mut self,
         prefix: &Prefix,
         mut route: Route,
@@ -470,34 +622,89 @@ impl Vrf {
             }
         }
 
-        // update fib
+        // update fib. Routes covered by an aggregate get installed when refreshing it.
+        let aggregate = self.covering_aggregate(prefix);
         if let Some(fibw) = &mut self.fibw {
-            let mut nhkeys = Vec::with_capacity(route.s_nhops.len());
             for shim in &route.s_nhops {
                 if shim.rc.as_ref().set_fibgroup(rstore) {
                     let fibgroup = &*shim.rc.as_ref().fibgroup.borrow();
                     fibw.register_fibgroup(&shim.rc.key, fibgroup, false);
                 }
-                nhkeys.push(shim.rc.key.clone());
             }
-            fibw.add_fibroute(*prefix, nhkeys, true);
+            if aggregate.is_none() {
+                fibw.add_fibroute(*prefix, route.fib_nhop_keys(), true);
+            }
         }
 
         // store the route in this vrf
+        let origin = route.origin;
         let prior = match prefix {
             Prefix::IPV4(p) => self.routesv4.insert(*p, route),
             Prefix::IPV6(p) => self.routesv6.insert(*p, route),
         };
 
         // if we happen to replace a route, unregister its next-hops
+        let change = match &prior {
+            Some(prior) if !prior.is_preset_drop_route() => RouteChange::Modify,
+            _ => RouteChange::Add,
+        };
         if let Some(mut prior) = prior {
             self.deregister_shared_nexthops(&mut prior);
         }
+        self.notify(prefix, change, Some(origin));
+
+        // update the aggregate covering the route, if any
+        if let Some(aggregate) = aggregate {
+            self.refresh_aggregate(&aggregate, Some(prefix));
+        }
 
         // refresh this FIB
         self.refresh_fib(rstore, vrf0);
     }
 
+    ////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Update the liveness of the next-hops of a `Vrf` given the set of BFD sessions that are
+    /// `withdrawn`, and remove the next-hops that are down from the `Fib` (or restore those that
+    /// came back up). `resvrf_changed` tells if the liveness of the next-hops of the resolving
+    /// `Vrf` changed. Returns true if the liveness of any next-hop of this `Vrf` changed.
+    ////////////////////////////////////////////////////////////////////////////////////////////////
+    pub fn update_nhop_liveness(
+        &mut self,
+        withdrawn: &BTreeSet<BfdSessionKey>,
+        rstore: &RmacStore,
+        resvrf: Option<&Vrf>,
+        resvrf_changed: bool,
+    ) -> bool {
+        let changed = self
+            .nhstore
+            .update_liveness(|key| nhop_is_withdrawn(withdrawn, key));
+        if !changed && !resvrf_changed {
+            return false;
+        }
+        debug!("Liveness of next-hops changed in VRF {}", self.name);
+
+        // rebuild the fibgroups of the next-hops resolving over the ones that changed
+        self.refresh_fib(rstore, resvrf);
+
+        // update the routes with multiple next-hops, some of which may have changed
+        // routes suppressed by an aggregate are left out, and aggregates refreshed after.
+        if let Some(fibw) = &mut self.fibw {
+            let aggregates = &self.aggregates;
+            let suppressed = |p: &Prefix| aggregates.values().any(|state| state.is_suppressed(p));
+            let v4 = self.routesv4.iter().map(|(p, r)| (Prefix::from(*p), r));
+            let v6 = self.routesv6.iter().map(|(p, r)| (Prefix::from(*p), r));
+            for (prefix, route) in v4
+                .chain(v6)
+                .filter(|(p, r)| r.s_nhops.len() > 1 && !suppressed(p))
+            {
+                fibw.add_fibroute(prefix, route.fib_nhop_keys(), false);
+            }
+            fibw.publish();
+        }
+        self.refresh_aggregates();
+        changed
+    }
+
     /////////////////////////////////////////////////////////////////////////
     // Route removal
     /////////////////////////////////////////////////////////////////////////
@@ -535,17 +742,74 @@ impl Vrf {
         }
     }
     pub fn del_route(&mut self, prefix: Prefix, vrf0: Option<&Vrf>, rstore: &RmacStore) {
+        // fall back to the preferred alternate route, if any
+        if let Some(best) = self.alternates.take_best(&prefix) {
+            debug!(
+                "vrf {}: falling back to {} route to {prefix}",
+                self.name, best.route.origin
+            );
+            self.install_route(&prefix, best.route, &best.nhops, vrf0, rstore);
+            return;
+        }
+        let existed = self
+            .get_route(prefix)
+            .is_some_and(|route| !route.is_preset_drop_route());
         match prefix {
             Prefix::IPV4(p) => self.del_route_v4(p),
             Prefix::IPV6(p) => self.del_route_v6(p),
         }
         if let Some(fibw) = &mut self.fibw {
-            fibw.del_fibroute(prefix);
+            fibw.del_fibroute(prefix, true);
+        }
+        if existed {
+            self.notify(&prefix, RouteChange::Del, None);
+        }
+        if let Some(aggregate) = self.covering_aggregate(&prefix) {
+            self.refresh_aggregate(&aggregate, Some(&prefix));
         }
         self.check_deletion();
         self.refresh_fib(rstore, vrf0);
     }
 
+    /////////////////////////////////////////////////////////////////////////
+    /// Remove the route to a prefix from the given [`RouteSource`], whether
+    /// it is selected or kept aside
+    /////////////////////////////////////////////////////////////////////////
+    pub fn del_route_from(
+        &mut self,
+        prefix: Prefix,
+        source: RouteSource,
+        vrf0: Option<&Vrf>,
+        rstore: &RmacStore,
+    ) {
+        if self.alternates.remove(&prefix, source) {
+            return;
+        }
+        if self
+            .get_route(prefix)
+            .is_some_and(|route| route.source() == source)
+        {
+            self.del_route(prefix, vrf0, rstore);
+        }
+    }
+
+    /////////////////////////////////////////////////////////////////////////
+    /// Tell if there is a route to a prefix from the given [`RouteSource`],
+    /// whether it is selected or kept aside
+    /////////////////////////////////////////////////////////////////////////
+    pub fn has_route_from(&self, prefix: Prefix, source: RouteSource) -> bool {
+        self.get_route(prefix)
+            .is_some_and(|route| route.source() == source)
+            || self.alternates.contains(&prefix, source)
+    }
+
+    /////////////////////////////////////////////////////////////////////////
+    /// Iterate over the routes to a prefix kept aside, which are not installed
+    /////////////////////////////////////////////////////////////////////////
+    pub fn get_alternate_routes(&self, prefix: Prefix) -> impl Iterator<Item = &Route> {
+        self.alternates.get(&prefix)
+    }
+
     /////////////////////////////////////////////////////////////////////////
     // Route retrieval
     /////////////////////////////////////////////////////////////////////////
@@ -666,6 +930,8 @@ impl Vrf {
                 **prefix != Ipv6Prefix::default() && !route.is_preset_drop_route()
             })
             .for_each(|(_, route)| route.set_stale(value));
+
+        self.alternates.set_stale(value);
     }
 
     /////////////////////////////////////////////////////////////////////////
@@ -716,6 +982,7 @@ impl Vrf {
     /////////////////////////////////////////////////////////////////////////
     pub fn remove_stale_routes(&mut self, vrf0: Option<&Vrf>, rstore: &RmacStore) {
         debug!("Removing stale routes from vrf {}..", self.name);
+        self.alternates.remove_stale();
         self.remove_stale_routes_v4(vrf0, rstore);
         self.remove_stale_routes_v6(vrf0, rstore);
     }
@@ -731,6 +998,7 @@ pub mod tests {
     use crate::rib::vrf::VrfId;
     use crate::rib::nexthop::{FwAction, NhopKey};
     use crate::rib::encapsulation::{Encapsulation, VxlanEncapsulation};
+    use crate::fib::fibobjects::{EgressObject, PktInstruction};
 
     #[test]
     fn test_vrf_build() {
@@ -1092,6 +1360,118 @@ pub mod tests {
 
     }
 
+    #[test]
+    fn test_vrf_ipv6_nhops() {
+        let rstore = RmacStore::new();
+        let vrf_cfg = RouterVrfConfig::new(0, "default");
+        let mut vrf = Vrf::new(&vrf_cfg);
+
+        /* connected route */
+        let prefix = Prefix::expect_from("2001:db8:ff::/64");
+        let nhop = build_test_nhop(None, Some(3), 0, None);
+        vrf.add_route(&prefix, build_test_route(RouteOrigin::Connected, 0, 0), &[nhop], None);
+
+        /* ECMP route via link-local next-hops, scoped by their interfaces */
+        let ecmp = Prefix::expect_from("2001:db8:10::/48");
+        let n1 = build_test_nhop(Some("fe80::1"), Some(1), 0, None);
+        let n2 = build_test_nhop(Some("fe80::2"), Some(2), 0, None);
+        vrf.add_route(&ecmp, build_test_route(RouteOrigin::Bgp, 20, 0), &[n1, n2], None);
+
+        /* recursive route via a global next-hop */
+        let recursive = Prefix::expect_from("2001:db8:20::/48");
+        let n3 = build_test_nhop(Some("2001:db8:ff::1"), None, 0, None);
+        vrf.add_route(&recursive, build_test_route(RouteOrigin::Static, 1, 0), &[n3], None);
+
+        /* route via a link-local next-hop without interface: it's unusable */
+        let unscoped = Prefix::expect_from("2001:db8:30::/48");
+        let n4 = build_test_nhop(Some("fe80::4"), None, 0, None);
+        vrf.add_route(&unscoped, build_test_route(RouteOrigin::Static, 1, 0), &[n4], None);
+
+        vrf.nhstore.lazy_resolve_all(&vrf);
+        vrf.nhstore.resolve_nhop_instructions(&rstore);
+        vrf.nhstore.rebuild_fibgroups(&rstore);
+        vrf.dump(Some("VRF with IPv6 routes"));
+
+        let egress = |ifindex: u32, address: &str| {
+            PktInstruction::Egress(EgressObject::new(InterfaceIndex::try_new(ifindex).ok(), Some(mk_addr(address)), None))
+        };
+        let instructions = |route: &Route| -> Vec<Vec<PktInstruction>> {
+            route.s_nhops.iter()
+                .flat_map(|shim| shim.rc.fibgroup.borrow().iter().map(|e| e.instructions.clone()).collect::<Vec<_>>())
+                .collect()
+        };
+
+        let (hit, route) = vrf.lpm(mk_addr("2001:db8:10::1"));
+        assert_eq!(hit, ecmp);
+        let paths = instructions(route);
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&vec![egress(1, "fe80::1")]));
+        assert!(paths.contains(&vec![egress(2, "fe80::2")]));
+
+        let (hit, route) = vrf.lpm(mk_addr("2001:db8:20::1"));
+        assert_eq!(hit, recursive);
+        assert_eq!(instructions(route), vec![vec![egress(3, "2001:db8:ff::1")]]);
+
+        let (hit, route) = vrf.lpm(mk_addr("2001:db8:30::1"));
+        assert_eq!(hit, unscoped);
+        assert!(route.s_nhops[0].rc.resolvers.borrow().is_empty());
+        assert_eq!(instructions(route), vec![vec![PktInstruction::Drop]]);
+
+        /* IPv6 destinations not covered use the IPv6 default (drop) route */
+        let (hit, route) = vrf.lpm(mk_addr("2001:db8:40::1"));
+        assert_eq!(hit, Prefix::root_v6());
+        assert_eq!(instructions(route), vec![vec![PktInstruction::Drop]]);
+    }
+
+    #[test]
+    fn test_vrf_admin_distance() {
+        let rstore = RmacStore::new();
+        let mut vrf = Vrf::new(&RouterVrfConfig::new(0, "default"));
+        let prefix = Prefix::expect_from("192.168.0.0/16");
+        let frr_route = |origin, distance| {
+            let mut route = build_test_route(origin, distance, 0);
+            route.flags.insert(RouteFlags::FRR);
+            route
+        };
+        let n1 = build_test_nhop(Some("10.0.0.1"), Some(1), 0, None);
+        let n2 = build_test_nhop(Some("10.0.0.2"), Some(2), 0, None);
+        let static_source = RouteSource::Protocol(RouteOrigin::Static);
+
+        /* a static route is preferred over an OSPF route learnt from FRR */
+        vrf.add_route_complete(&prefix, frr_route(RouteOrigin::Ospf, 110), &[n1.clone()], None, &rstore);
+        vrf.add_route_complete(&prefix, build_test_route(RouteOrigin::Static, 100, 0), &[n2], None, &rstore);
+        assert_eq!(vrf.get_route(prefix).unwrap().origin, RouteOrigin::Static);
+        assert_eq!(vrf.get_alternate_routes(prefix).count(), 1);
+
+        /* FRR replaces its route with a BGP one, which gets selected */
+        vrf.add_route_complete(&prefix, frr_route(RouteOrigin::Bgp, 20), &[n1.clone()], None, &rstore);
+        let route = vrf.get_route(prefix).unwrap();
+        assert_eq!(route.origin, RouteOrigin::Bgp);
+        assert_eq!(route.s_nhops[0].rc.key.address, Some(mk_addr("10.0.0.1")));
+        let alternates: Vec<_> = vrf.get_alternate_routes(prefix).map(|r| r.origin).collect();
+        assert_eq!(alternates, [RouteOrigin::Static]);
+
+        /* withdrawing the BGP route falls back to the static route, with its next-hop */
+        vrf.del_route_from(prefix, RouteSource::Frr, None, &rstore);
+        let route = vrf.get_route(prefix).unwrap();
+        assert_eq!(route.origin, RouteOrigin::Static);
+        assert_eq!(route.s_nhops[0].rc.key.address, Some(mk_addr("10.0.0.2")));
+        assert!(vrf.has_route_from(prefix, static_source));
+        assert!(!vrf.has_route_from(prefix, RouteSource::Frr));
+
+        /* a route kept aside is removed without affecting the selected one */
+        vrf.add_route_complete(&prefix, frr_route(RouteOrigin::Ospf, 110), &[n1], None, &rstore);
+        assert_eq!(vrf.get_route(prefix).unwrap().origin, RouteOrigin::Static);
+        vrf.del_route_from(prefix, RouteSource::Frr, None, &rstore);
+        assert_eq!(vrf.get_route(prefix).unwrap().origin, RouteOrigin::Static);
+        assert_eq!(vrf.get_alternate_routes(prefix).count(), 0);
+
+        /* removing the last route to the prefix */
+        vrf.del_route_from(prefix, static_source, None, &rstore);
+        assert!(vrf.get_route(prefix).is_none());
+        check_vrf_is_empty(&vrf);
+    }
+
     // build a sample VRF used for testing
     pub fn build_test_vrf() -> Vrf {
         let vrf_cfg = RouterVrfConfig::new(0, "default");
@@ -5,18 +5,20 @@
 //! and optionally identified by a Vni. A vrf table always has a default vrf.
 
 use crate::RouterError;
+use crate::bfd::BfdSessionKey;
 use crate::evpn::RmacStore;
 use crate::fib::fibtable::FibTableWriter;
 use crate::fib::fibtype::FibKey;
 use crate::interfaces::iftablerw::IfTableWriter;
 use crate::rib::vrf::{RouterVrfConfig, Vrf, VrfId};
+use crate::rib::watch::{RouteSubscription, RouteWatch};
 
 #[cfg(test)]
 use crate::rib::vrf::VrfStatus;
 
 use ahash::RandomState;
 use net::vxlan::Vni;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use tracing::{debug, error};
 
@@ -24,6 +26,7 @@ pub struct VrfTable {
     by_id: HashMap<VrfId, Vrf, RandomState>,
     by_vni: HashMap<Vni, VrfId, RandomState>,
     fibtablew: FibTableWriter,
+    watch: Option<RouteWatch>,
 }
 
 #[allow(clippy::new_without_default)]
@@ -38,6 +41,7 @@ impl VrfTable {
             by_id: HashMap::with_hasher(RandomState::with_seed(0)),
             by_vni: HashMap::with_hasher(RandomState::with_seed(0)),
             fibtablew,
+            watch: None,
         };
         /* create default vrf: this can't fail */
         let _ = vrftable.add_vrf(&RouterVrfConfig::new(0, "default"));
@@ -75,6 +79,11 @@ impl VrfTable {
         let fibw = self.fibtablew.add_fib(vrf.vrfid, vrf.vni);
         vrf.set_fibw(fibw);
 
+        /* notify route changes */
+        if let Some(watch) = &self.watch {
+            vrf.set_watch(watch.clone());
+        }
+
         /* store */
         self.by_id.entry(vrfid).or_insert(vrf);
         if let Some(vni) = config.vni {
@@ -399,6 +408,24 @@ impl VrfTable {
         }
     }
 
+    //////////////////////////////////////////////////////////////////
+    /// Set the [`RouteWatch`] to notify route changes to, for all vrfs
+    //////////////////////////////////////////////////////////////////
+    pub fn set_watch(&mut self, watch: RouteWatch) {
+        self.by_id
+            .values_mut()
+            .for_each(|vrf| vrf.set_watch(watch.clone()));
+        self.watch = Some(watch);
+    }
+
+    //////////////////////////////////////////////////////////////////
+    /// Subscribe to the route changes of all vrfs, if a [`RouteWatch`] is set
+    //////////////////////////////////////////////////////////////////
+    #[must_use]
+    pub fn subscribe_routes(&self) -> Option<RouteSubscription> {
+        self.watch.as_ref().map(RouteWatch::subscribe)
+    }
+
     /////////////////////////////////////////////////////////////////////////
     // Set/unset stale flag for all routes in all vrfs
     /////////////////////////////////////////////////////////////////////////
@@ -421,6 +448,24 @@ impl VrfTable {
         // remove stale routes from default vrf
         vrf0.remove_stale_routes(None, rstore);
     }
+
+    /////////////////////////////////////////////////////////////////////////
+    /// Withdraw from the fibs of all vrfs the next-hops monitored by BFD
+    /// sessions that are `withdrawn`, and restore the others.
+    /////////////////////////////////////////////////////////////////////////
+    pub fn update_nhop_liveness(
+        &mut self,
+        withdrawn: &BTreeSet<BfdSessionKey>,
+        rstore: &RmacStore,
+    ) {
+        let (vrfs, vrf0) = self.values_mut_except_default();
+
+        // default vrf first, since other vrfs may resolve over its next-hops
+        let changed = vrf0.update_nhop_liveness(withdrawn, rstore, None, false);
+        for vrf in vrfs {
+            vrf.update_nhop_liveness(withdrawn, rstore, Some(vrf0), changed);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -930,4 +975,28 @@ mod tests {
     fn test_vrf_fibgroup_2() {
         test_vrf_fibgroup(build_test_vrf_nhops_partially_resolved());
     }
+
+    #[test]
+    fn test_vrf_nhop_liveness() {
+        let rstore = build_sample_rmac_store();
+        let mut vrf = build_test_vrf();
+        vrf.nhstore.lazy_resolve_all(&vrf);
+        vrf.nhstore.resolve_nhop_instructions(&rstore);
+        vrf.nhstore.rebuild_fibgroups(&rstore);
+
+        let destination = mk_addr("192.168.0.1");
+        let fibgroup_len = |vrf: &Vrf| vrf.lpm(destination).1.s_nhops[0].rc.fibgroup.borrow().len();
+        assert_eq!(fibgroup_len(&vrf), 4);
+
+        /* BFD session to 10.0.0.5 goes down: paths via 10.0.0.5 are withdrawn */
+        let ifindex = InterfaceIndex::try_new(2).unwrap();
+        let withdrawn = BTreeSet::from([(mk_addr("10.0.0.5"), ifindex)]);
+        assert!(vrf.update_nhop_liveness(&withdrawn, &rstore, None, false));
+        assert_eq!(fibgroup_len(&vrf), 2);
+        assert!(!vrf.update_nhop_liveness(&withdrawn, &rstore, None, false));
+
+        /* BFD session comes back up */
+        assert!(vrf.update_nhop_liveness(&BTreeSet::new(), &rstore, None, false));
+        assert_eq!(fibgroup_len(&vrf), 4);
+    }
 }
@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Route change notifications. External controllers and tests can subscribe to a [`RouteWatch`]
+//! to learn about the routes added to, modified in and removed from the RIB, e.g. to track
+//! convergence. Events are broadcast to all subscribers. A subscriber that does not keep up
+//! loses the oldest events (its receiver reports that it lagged) and may resynchronize from a
+//! [`crate::snapshot::RoutingSnapshot`].
+
+use crate::rib::vrf::{RouteOrigin, VrfId};
+use lpm::prefix::Prefix;
+use tokio::sync::broadcast;
+
+/// Number of events buffered for each subscriber
+const ROUTE_WATCH_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The kind of change of a route
+pub enum RouteChange {
+    Add,
+    Modify,
+    Del,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A change of the route to some prefix in some VRF
+pub struct RouteEvent {
+    pub vrfid: VrfId,
+    pub prefix: Prefix,
+    pub change: RouteChange,
+    /// The origin of the route added or modified, `None` for deletions
+    pub origin: Option<RouteOrigin>,
+}
+
+/// The receiving end of a subscription to a [`RouteWatch`]
+pub type RouteSubscription = broadcast::Receiver<RouteEvent>;
+
+#[derive(Clone, Debug)]
+/// A source of [`RouteEvent`]s, shared by the VRFs, which emit them, and the subscribers
+pub struct RouteWatch(broadcast::Sender<RouteEvent>);
+
+#[allow(clippy::new_without_default)]
+impl RouteWatch {
+    #[must_use]
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(ROUTE_WATCH_CAPACITY);
+        Self(sender)
+    }
+
+    /// Subscribe to route changes. Only the changes that happen after subscribing are received.
+    #[must_use]
+    pub fn subscribe(&self) -> RouteSubscription {
+        self.0.subscribe()
+    }
+
+    /// Tell the number of subscribers
+    #[must_use]
+    pub fn subscribers(&self) -> usize {
+        self.0.receiver_count()
+    }
+
+    /// Emit a [`RouteEvent`] to all subscribers, if any
+    pub(crate) fn emit(&self, event: RouteEvent) {
+        if self.0.receiver_count() > 0 {
+            let _ = self.0.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evpn::RmacStore;
+    use crate::rib::vrf::tests::{build_test_nhop, build_test_route};
+    use crate::rib::vrf::{RouterVrfConfig, Vrf};
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    #[test]
+    fn test_route_watch() {
+        let rstore = RmacStore::new();
+        let mut vrf = Vrf::new(&RouterVrfConfig::new(1, "vrf1"));
+        let watch = RouteWatch::new();
+        vrf.set_watch(watch.clone());
+
+        // nothing is emitted without subscribers
+        let prefix = Prefix::expect_from("10.0.0.0/24");
+        let nhop = build_test_nhop(None, Some(1), 0, None);
+        let route = build_test_route(RouteOrigin::Static, 1, 1);
+        vrf.add_route_complete(&prefix, route, &[nhop.clone()], None, &rstore);
+
+        let mut sub = watch.subscribe();
+        assert_eq!(watch.subscribers(), 1);
+        let route = build_test_route(RouteOrigin::Static, 1, 0);
+        vrf.add_route_complete(&prefix, route, &[nhop.clone()], None, &rstore);
+        let other = Prefix::expect_from("10.0.1.0/24");
+        let route = build_test_route(RouteOrigin::Bgp, 20, 0);
+        vrf.add_route_complete(&other, route, &[nhop], None, &rstore);
+        vrf.del_route(prefix, None, &rstore);
+        // deleting a route that does not exist is not a change
+        vrf.del_route(prefix, None, &rstore);
+
+        let event = |prefix, change, origin| RouteEvent {
+            vrfid: 1,
+            prefix,
+            change,
+            origin,
+        };
+        assert_eq!(
+            sub.try_recv(),
+            Ok(event(
+                prefix,
+                RouteChange::Modify,
+                Some(RouteOrigin::Static)
+            ))
+        );
+        assert_eq!(
+            sub.try_recv(),
+            Ok(event(other, RouteChange::Add, Some(RouteOrigin::Bgp)))
+        );
+        assert_eq!(sub.try_recv(), Ok(event(prefix, RouteChange::Del, None)));
+        assert_eq!(sub.try_recv(), Err(TryRecvError::Empty));
+    }
+}
@@ -5,23 +5,34 @@
 
 #![allow(clippy::items_after_statements)]
 
-use crate::cli::handle_cli_request;
+use crate::atable::arpnd::ArpNdAgent;
+use crate::bfd::{BfdSessionKey, BfdSessions};
+use crate::cli::{CliHandlers, handle_cli_request};
 use crate::config::FrrConfig;
 use crate::cpi::{CpiStats, process_rx_data, rpc_send_control};
 use crate::ctl::{RouterCtlMsg, RouterCtlSender, handle_ctl_msg};
 use crate::errors::RouterError;
+use crate::evpn::MacIpBindings;
 use crate::fib::fibtable::FibTableWriter;
+use crate::fib::urpf::UrpfDrops;
 use crate::frr::frrmi::{FrrErr, Frrmi, FrrmiRequest};
 use crate::interfaces::iftablerw::IfTableWriter;
+use crate::interfaces::lldp::LldpNeighbors;
+use crate::lfib::lfibrw::LfibWriter;
+use crate::mcast::mroutetablerw::MrouteTableWriter;
+use crate::mcast::snooping::McastMembers;
 use crate::revent::{ROUTER_EVENTS, RouterEvent};
+use crate::rib::watch::RouteWatch;
 use crate::routingdb::RoutingDb;
+use crate::srv6::sidtablerw::SidTableWriter;
 use crate::{atable::atablerw::AtableReader, cpi::CpiStatus};
 
 use cli::cliproto::{CliRequest, CliSerialize};
 use dplane_rpc::socks::RpcCachedSock;
 
 use mio::unix::SourceFd;
-use mio::{Events, Interest, Poll, Token};
+use mio::{Events, Interest, Poll, Token, Waker};
+use std::collections::BTreeSet;
 use std::fs;
 use std::os::fd::AsRawFd;
 use std::os::unix::fs::PermissionsExt;
@@ -73,10 +84,20 @@ pub const DEFAULT_DP_UX_PATH: &str = "/var/run/frr/hh/dataplane.sock";
 pub const DEFAULT_DP_UX_PATH_CLI: &str = "/var/run/dataplane/cli.sock";
 pub const DEFAULT_FRR_AGENT_PATH: &str = "/var/run/frr/frr-agent.sock";
 
+/// Time during which the routes learnt over the CPI are kept (as stale) when FRR goes away or
+/// restarts, for it to reconnect and re-advertise them before they get removed.
+pub const DEFAULT_CPI_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Time without hearing from FRR (it sends keepalives) after which we consider the CPI down.
+/// This should be a few times the period of the keepalives of FRR.
+pub const DEFAULT_CPI_HOLD_TIME: Duration = Duration::from_secs(30);
+
 pub struct RioConf {
     pub cpi_sock_path: Option<String>,
     pub cli_sock_path: Option<String>,
     pub frrmi_sock_path: Option<String>,
+    pub cpi_grace_period: Duration,
+    pub cpi_hold_time: Duration,
 }
 impl Default for RioConf {
     fn default() -> Self {
@@ -84,6 +105,8 @@ impl Default for RioConf {
             cpi_sock_path: Some(DEFAULT_DP_UX_PATH.to_string()),
             cli_sock_path: Some(DEFAULT_DP_UX_PATH_CLI.to_string()),
             frrmi_sock_path: Some(DEFAULT_FRR_AGENT_PATH.to_string()),
+            cpi_grace_period: DEFAULT_CPI_GRACE_PERIOD,
+            cpi_hold_time: DEFAULT_CPI_HOLD_TIME,
         }
     }
 }
@@ -104,6 +127,7 @@ fn open_unix_sock(path: &String) -> Result<UnixDatagram, RouterError> {
 pub(crate) const CPSOCK: Token = Token(0);
 pub(crate) const CLISOCK: Token = Token(1);
 pub(crate) const FRRMISOCK: Token = Token(2);
+pub(crate) const BFDWAKE: Token = Token(3);
 /// `Rio` is the router IO loop state
 pub(crate) struct Rio {
     pub(crate) run: bool,
@@ -117,7 +141,12 @@ pub(crate) struct Rio {
     pub(crate) ctl_tx: Sender<RouterCtlMsg>,
     pub(crate) ctl_rx: Receiver<RouterCtlMsg>,
     pub(crate) cpistats: CpiStats,
+    cpi_grace_period: Duration,
+    cpi_hold_time: Duration,
+    cpi_down: bool,
     stale_timeout: Option<Instant>,
+    bfd_generation: u64,
+    bfd_withdrawn: BTreeSet<BfdSessionKey>,
 }
 impl Rio {
     fn new(conf: &RioConf) -> Result<Rio, RouterError> {
@@ -185,7 +214,12 @@ impl Rio {
             ctl_tx,
             ctl_rx,
             cpistats: CpiStats::new(),
+            cpi_grace_period: conf.cpi_grace_period,
+            cpi_hold_time: conf.cpi_hold_time,
+            cpi_down: false,
             stale_timeout: None,
+            bfd_generation: 0,
+            bfd_withdrawn: BTreeSet::new(),
         })
     }
     pub(crate) fn register(&self, token: Token, fd: i32, interests: Interest) {
@@ -277,10 +311,20 @@ impl Rio {
     pub(crate) fn cpi_status_check(&mut self, db: &mut RoutingDb) {
         match self.cpistats.status {
             CpiStatus::NotConnected => {}
-            CpiStatus::Connected => {}
+            CpiStatus::Disconnected => {}
+            CpiStatus::Connected => {
+                if self.cpi_down {
+                    /* FRR came back without restarting: it still has the routes we kept */
+                    info!("FRR is back. Preserving the routes learnt before it went away");
+                    self.cpi_down = false;
+                    self.stale_timeout = None;
+                    db.vrftable.set_stale(false);
+                }
+            }
             CpiStatus::Incompatible => {}
             CpiStatus::FrrRestarted => {
                 warn!("FRR appears to have restarted!!!...");
+                self.cpi_down = false;
                 db.vrftable.remove_deleting_vrfs(&mut db.iftw);
                 db.vrftable.set_stale(true);
                 self.set_stale_timeout();
@@ -299,10 +343,29 @@ impl Rio {
             }
         }
     }
+    /// Check if FRR is still talking to us. If it is not, keep the routes learnt from it,
+    /// marked as stale, for the grace period: FRR may reconnect and re-advertise them,
+    /// or come back without having lost them.
+    fn check_cpi_liveness(&mut self, db: &mut RoutingDb) {
+        if self.cpistats.status != CpiStatus::Connected || self.cpi_down {
+            return;
+        }
+        let idle = self
+            .cpistats
+            .last_msg_rx
+            .and_then(|last| (chrono::Local::now() - last).to_std().ok());
+        let hold_time = self.cpi_hold_time;
+        if idle.is_some_and(|idle| idle > hold_time) {
+            warn!("Nothing heard from FRR over the CPI for {hold_time:?}. Is it gone?");
+            self.cpi_down = true;
+            self.cpistats.status.change(CpiStatus::Disconnected);
+            db.vrftable.set_stale(true);
+            self.set_stale_timeout();
+        }
+    }
     fn set_stale_timeout(&mut self) {
-        let duration = 60;
-        debug!("Set stale timeout ({duration} seconds)");
-        let duration = Duration::from_secs(duration);
+        let duration = self.cpi_grace_period;
+        debug!("Set stale timeout ({} seconds)", duration.as_secs());
         self.stale_timeout = Instant::now().checked_add(duration);
     }
     fn check_stale_timeout(&mut self, db: &mut RoutingDb) {
@@ -316,6 +379,19 @@ impl Rio {
             db.vrftable.remove_deleted_vrfs(&mut db.iftw);
         }
     }
+    fn check_bfd(&mut self, db: &mut RoutingDb) {
+        let generation = db.bfd.generation();
+        if generation != self.bfd_generation {
+            self.bfd_generation = generation;
+            self.bfd_withdrawn = db.bfd.withdrawn();
+            debug!("BFD: {} next-hop(s) withdrawn", self.bfd_withdrawn.len());
+        } else if self.bfd_withdrawn.is_empty() {
+            return;
+        }
+        /* next-hops may have been added since the last change: always check while some are down */
+        db.vrftable
+            .update_nhop_liveness(&self.bfd_withdrawn, &db.rmac_store);
+    }
 }
 
 #[allow(clippy::missing_errors_doc)]
@@ -324,10 +400,26 @@ pub fn start_rio(
     fibtw: FibTableWriter,
     iftw: IfTableWriter,
     atabler: AtableReader,
+    lfibw: LfibWriter,
+    sidtablew: SidTableWriter,
+    mroutew: MrouteTableWriter,
+    mcast_members: McastMembers,
+    lldp: LldpNeighbors,
+    bfd: BfdSessions,
+    arpnd: ArpNdAgent,
+    macip: MacIpBindings,
+    urpf_drops: UrpfDrops,
+    route_watch: RouteWatch,
+    cli_handlers: CliHandlers,
 ) -> Result<RioHandle, RouterError> {
     let mut rio = Rio::new(conf)?;
     let ctl_tx = rio.ctl_tx.clone();
 
+    /* let BFD wake us up when the liveness of next-hops changes */
+    let waker = Waker::new(rio.poller.registry(), BFDWAKE)
+        .map_err(|_| RouterError::Internal("Failed to create BFD waker"))?;
+    bfd.set_waker(waker);
+
     /* router IO loop */
     let rio_loop = move || {
         info!("CPI: Listening at {}.", &rio.cp_sock_path);
@@ -337,7 +429,15 @@ pub fn start_rio(
         let mut buf = vec![0; 1024];
 
         /* create routing database: this is fully owned by the CPI */
-        let mut db = RoutingDb::new(fibtw, iftw, atabler);
+        let mut db = RoutingDb::new(fibtw, iftw, atabler, lfibw, sidtablew, mroutew);
+        db.mcast_members = mcast_members;
+        db.lldp = lldp;
+        db.bfd = bfd;
+        db.arpnd = arpnd;
+        db.macip = macip;
+        db.urpf_drops = urpf_drops;
+        db.vrftable.set_watch(route_watch);
+        db.cli_handlers = cli_handlers;
 
         revent!(RouterEvent::Started);
 
@@ -421,9 +521,15 @@ pub fn start_rio(
                 }
             }
 
+            /* check if FRR went away, keeping its routes for the grace period */
+            rio.check_cpi_liveness(&mut db);
+
             /* check stale timeout. If expired, remove stale routes */
             rio.check_stale_timeout(&mut db);
 
+            /* withdraw or restore next-hops monitored by BFD */
+            rio.check_bfd(&mut db);
+
             /* handle control-channel messages */
             handle_ctl_msg(&mut rio, &mut db);
         }
@@ -441,11 +547,22 @@ pub fn start_rio(
 
 #[cfg(test)]
 mod tests {
+    use crate::atable::arpnd::ArpNdAgent;
     use crate::atable::atablerw::AtableWriter;
+    use crate::bfd::BfdSessions;
+    use crate::cli::CliHandlers;
     use crate::errors::RouterError;
+    use crate::evpn::MacIpBindings;
     use crate::fib::fibtable::FibTableWriter;
+    use crate::fib::urpf::UrpfDrops;
     use crate::interfaces::iftablerw::IfTableWriter;
-    use crate::rio::{RioConf, start_rio};
+    use crate::interfaces::lldp::LldpNeighbors;
+    use crate::lfib::lfibrw::LfibWriter;
+    use crate::mcast::mroutetablerw::MrouteTableWriter;
+    use crate::mcast::snooping::McastMembers;
+    use crate::rib::watch::RouteWatch;
+    use crate::rio::{DEFAULT_CPI_GRACE_PERIOD, DEFAULT_CPI_HOLD_TIME, RioConf, start_rio};
+    use crate::srv6::sidtablerw::SidTableWriter;
     use std::thread;
     use std::time::Duration;
 
@@ -461,6 +578,8 @@ mod tests {
             cpi_sock_path: Some(cpi_bind_addr),
             cli_sock_path: Some(cli_bind_addr),
             frrmi_sock_path: Some(frra_path),
+            cpi_grace_period: DEFAULT_CPI_GRACE_PERIOD,
+            cpi_hold_time: DEFAULT_CPI_HOLD_TIME,
         };
 
         /* create interface table */
@@ -472,8 +591,34 @@ mod tests {
         /* create atable */
         let (_atablew, atabler) = AtableWriter::new();
 
+        /* create lfib */
+        let (lfibw, _lfibr) = LfibWriter::new();
+
+        /* create local SID table */
+        let (sidtablew, _sidtabler) = SidTableWriter::new();
+
+        /* create multicast routing table */
+        let (mroutew, _mroutetabler) = MrouteTableWriter::new();
+
         /* start CPI */
-        let mut cpi = start_rio(&conf, fibtw, iftw, atabler).expect("Should succeed");
+        let mut cpi = start_rio(
+            &conf,
+            fibtw,
+            iftw,
+            atabler,
+            lfibw,
+            sidtablew,
+            mroutew,
+            McastMembers::new(),
+            LldpNeighbors::new(),
+            BfdSessions::new(),
+            ArpNdAgent::new(),
+            MacIpBindings::new(),
+            UrpfDrops::new(),
+            RouteWatch::new(),
+            CliHandlers::new(),
+        )
+        .expect("Should succeed");
         thread::sleep(Duration::from_secs(3));
         assert_eq!(cpi.finish(), Ok(()));
     }
@@ -484,6 +629,8 @@ mod tests {
             cpi_sock_path: Some("/nonexistent/hh_dataplane.sock".to_string()),
             cli_sock_path: None,
             frrmi_sock_path: None,
+            cpi_grace_period: DEFAULT_CPI_GRACE_PERIOD,
+            cpi_hold_time: DEFAULT_CPI_HOLD_TIME,
         };
 
         /* create interface table */
@@ -495,8 +642,33 @@ mod tests {
         /* create atable */
         let (_atablew, atabler) = AtableWriter::new();
 
+        /* create lfib */
+        let (lfibw, _lfibr) = LfibWriter::new();
+
+        /* create local SID table */
+        let (sidtablew, _sidtabler) = SidTableWriter::new();
+
+        /* create multicast routing table */
+        let (mroutew, _mroutetabler) = MrouteTableWriter::new();
+
         /* start router IO */
-        let rio = start_rio(&conf, fibtw, iftw, atabler);
+        let rio = start_rio(
+            &conf,
+            fibtw,
+            iftw,
+            atabler,
+            lfibw,
+            sidtablew,
+            mroutew,
+            McastMembers::new(),
+            LldpNeighbors::new(),
+            BfdSessions::new(),
+            ArpNdAgent::new(),
+            MacIpBindings::new(),
+            UrpfDrops::new(),
+            RouteWatch::new(),
+            CliHandlers::new(),
+        );
         assert!(rio.is_err_and(|e| matches!(e, RouterError::InvalidPath(_))));
     }
 }
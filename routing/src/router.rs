@@ -3,24 +3,43 @@
 
 //! Module that implements a router instance
 
+use cli::cliproto::CliAction;
 use derive_builder::Builder;
 use std::fmt::Display;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 use tracing::{debug, error};
 
+use crate::atable::arpnd::ArpNdAgent;
 use crate::atable::atablerw::{AtableReader, AtableReaderFactory};
 use crate::atable::resolver::AtResolver;
+use crate::bfd::BfdSessions;
+use crate::cli::{CliHandler, CliHandlers};
 use crate::ctl::RouterCtlSender;
 use crate::errors::RouterError;
+use crate::evpn::MacIpBindings;
 use crate::fib::fibtable::{FibTableReader, FibTableReaderFactory, FibTableWriter};
+use crate::fib::urpf::UrpfDrops;
 use crate::interfaces::iftablerw::{IfTableReader, IfTableReaderFactory, IfTableWriter};
+use crate::interfaces::lldp::LldpNeighbors;
+use crate::lfib::lfibrw::{LfibReader, LfibReaderFactory, LfibWriter};
+use crate::mcast::mroutetablerw::{MrouteTableReader, MrouteTableReaderFactory, MrouteTableWriter};
+use crate::mcast::snooping::McastMembers;
+use crate::rib::watch::{RouteSubscription, RouteWatch};
 use crate::rio::{RioConf, RioHandle, start_rio};
+use crate::srv6::sidtablerw::{SidTableReader, SidTableReaderFactory, SidTableWriter};
 
+use crate::rio::DEFAULT_CPI_GRACE_PERIOD;
+use crate::rio::DEFAULT_CPI_HOLD_TIME;
 use crate::rio::DEFAULT_DP_UX_PATH;
 use crate::rio::DEFAULT_DP_UX_PATH_CLI;
 use crate::rio::DEFAULT_FRR_AGENT_PATH;
 
+/// Default maximum number of flows in the flow table of the pipelines, which holds the stateful
+/// NAT sessions
+pub const DEFAULT_FLOW_TABLE_CAPACITY: usize = 1 << 20;
+
 /// Struct to configure router object. N.B we derive a builder type `RouterConfig`
 /// and provide defaults for each field.
 #[derive(Builder, Debug)]
@@ -39,6 +58,22 @@ pub struct RouterParams {
 
     #[builder(setter(into), default = DEFAULT_FRR_AGENT_PATH.to_string().into())]
     pub frr_agent_path: PathBuf,
+
+    /// Time to keep the routes learnt from FRR when it goes away or restarts
+    #[builder(default = DEFAULT_CPI_GRACE_PERIOD)]
+    pub cpi_grace_period: Duration,
+
+    /// Time without hearing from FRR after which the CPI is considered down
+    #[builder(default = DEFAULT_CPI_HOLD_TIME)]
+    pub cpi_hold_time: Duration,
+
+    /// Whether to measure the latency of each stage of the pipelines
+    #[builder(default)]
+    pub stage_latencies: bool,
+
+    /// Maximum number of flows in the flow table of the pipelines, if bounded
+    #[builder(default = Some(DEFAULT_FLOW_TABLE_CAPACITY))]
+    pub flow_table_capacity: Option<usize>,
 }
 
 impl Display for RouterParams {
@@ -47,7 +82,9 @@ impl Display for RouterParams {
         writeln!(f, "  name     : {}", self.name)?;
         writeln!(f, "  CPI path : {}", self.cpi_sock_path.display())?;
         writeln!(f, "  CLI path : {}", self.cli_sock_path.display())?;
-        writeln!(f, "  FRR-agent: {}", self.frr_agent_path.display())
+        writeln!(f, "  FRR-agent: {}", self.frr_agent_path.display())?;
+        writeln!(f, "  CPI grace: {}s", self.cpi_grace_period.as_secs())?;
+        writeln!(f, "  CPI hold : {}s", self.cpi_hold_time.as_secs())
     }
 }
 
@@ -59,6 +96,17 @@ pub struct Router {
     rio_handle: RioHandle,
     iftr: IfTableReader,
     fibtr: FibTableReader,
+    lfibr: LfibReader,
+    sidtabler: SidTableReader,
+    mroutetabler: MrouteTableReader,
+    mcast_members: McastMembers,
+    lldp: LldpNeighbors,
+    bfd: BfdSessions,
+    arpnd: ArpNdAgent,
+    macip: MacIpBindings,
+    urpf_drops: UrpfDrops,
+    route_watch: RouteWatch,
+    cli_handlers: CliHandlers,
 }
 
 // Build the router IO configuration from the router configuration
@@ -85,6 +133,8 @@ fn init_router(params: &RouterParams) -> Result<RioConf, RouterError> {
                 .ok_or(RouterError::InvalidPath("(frr-agent path)".to_string()))?
                 .to_owned(),
         ),
+        cpi_grace_period: params.cpi_grace_period,
+        cpi_hold_time: params.cpi_hold_time,
     })
 }
 
@@ -103,12 +153,57 @@ impl Router {
         debug!("{name}: Creating FIB table...");
         let (fibtw, fibtr) = FibTableWriter::new();
 
+        debug!("{name}: Creating LFIB...");
+        let (lfibw, lfibr) = LfibWriter::new();
+
+        debug!("{name}: Creating SRv6 local SID table...");
+        let (sidtablew, sidtabler) = SidTableWriter::new();
+
+        debug!("{name}: Creating multicast routing table...");
+        let (mroutew, mroutetabler) = MrouteTableWriter::new();
+        let mcast_members = McastMembers::new();
+
         debug!("{name}: Creating Adjacency resolver...");
         let (mut resolver, atabler) = AtResolver::new(true);
         resolver.start(3);
 
+        debug!("{name}: Creating LLDP neighbor table...");
+        let lldp = LldpNeighbors::new();
+
+        debug!("{name}: Creating BFD session table...");
+        let bfd = BfdSessions::new();
+
+        debug!("{name}: Creating proxy ARP and address announcement state...");
+        let arpnd = ArpNdAgent::new();
+
+        debug!("{name}: Creating EVPN MAC/IP binding table...");
+        let macip = MacIpBindings::new();
+
+        debug!("{name}: Creating uRPF drop counters...");
+        let urpf_drops = UrpfDrops::new();
+
+        debug!("{name}: Creating route watch...");
+        let route_watch = RouteWatch::new();
+
         debug!("{name}: Starting router IO...");
-        let rio_handle = start_rio(&rioconf, fibtw, iftw, atabler)?;
+        let cli_handlers = CliHandlers::new();
+        let rio_handle = start_rio(
+            &rioconf,
+            fibtw,
+            iftw,
+            atabler,
+            lfibw,
+            sidtablew,
+            mroutew,
+            mcast_members.clone(),
+            lldp.clone(),
+            bfd.clone(),
+            arpnd.clone(),
+            macip.clone(),
+            urpf_drops.clone(),
+            route_watch.clone(),
+            cli_handlers.clone(),
+        )?;
 
         debug!("{name}: Successfully started with parameters:\n{params}");
         let router = Router {
@@ -118,6 +213,17 @@ impl Router {
             rio_handle,
             iftr,
             fibtr,
+            lfibr,
+            sidtabler,
+            mroutetabler,
+            mcast_members,
+            lldp,
+            bfd,
+            arpnd,
+            macip,
+            urpf_drops,
+            route_watch,
+            cli_handlers,
         };
         Ok(router)
     }
@@ -164,6 +270,77 @@ impl Router {
         self.fibtr.factory()
     }
 
+    #[must_use]
+    pub fn get_lfibr(&self) -> LfibReader {
+        self.lfibr.clone()
+    }
+
+    #[must_use]
+    pub fn get_lfibr_factory(&self) -> LfibReaderFactory {
+        self.lfibr.factory()
+    }
+
+    #[must_use]
+    pub fn get_sidtabler(&self) -> SidTableReader {
+        self.sidtabler.clone()
+    }
+
+    #[must_use]
+    pub fn get_sidtabler_factory(&self) -> SidTableReaderFactory {
+        self.sidtabler.factory()
+    }
+
+    #[must_use]
+    pub fn get_mroutetabler(&self) -> MrouteTableReader {
+        self.mroutetabler.clone()
+    }
+
+    #[must_use]
+    pub fn get_mroutetabler_factory(&self) -> MrouteTableReaderFactory {
+        self.mroutetabler.factory()
+    }
+
+    #[must_use]
+    pub fn get_mcast_members(&self) -> McastMembers {
+        self.mcast_members.clone()
+    }
+
+    #[must_use]
+    pub fn get_lldp_neighbors(&self) -> LldpNeighbors {
+        self.lldp.clone()
+    }
+
+    #[must_use]
+    pub fn get_bfd_sessions(&self) -> BfdSessions {
+        self.bfd.clone()
+    }
+
+    #[must_use]
+    pub fn get_arpnd_agent(&self) -> ArpNdAgent {
+        self.arpnd.clone()
+    }
+
+    #[must_use]
+    pub fn get_macip_bindings(&self) -> MacIpBindings {
+        self.macip.clone()
+    }
+
+    #[must_use]
+    pub fn get_urpf_drops(&self) -> UrpfDrops {
+        self.urpf_drops.clone()
+    }
+
+    /// Subscribe to the changes of the routes of all VRFs
+    #[must_use]
+    pub fn subscribe_routes(&self) -> RouteSubscription {
+        self.route_watch.subscribe()
+    }
+
+    /// Register `handler` to serve the cli requests for `action`
+    pub fn register_cli_handler(&self, action: CliAction, handler: CliHandler) {
+        self.cli_handlers.register(action, handler);
+    }
+
     #[must_use]
     pub fn get_ctl_tx(&self) -> RouterCtlSender {
         self.rio_handle.get_ctl_tx()
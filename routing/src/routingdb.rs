@@ -3,12 +3,22 @@
 
 //! Routing database keeps most of the routing information in memory
 
+use crate::atable::arpnd::ArpNdAgent;
 use crate::atable::atablerw::AtableReader;
+use crate::bfd::BfdSessions;
+use crate::cli::CliHandlers;
 use crate::config::RouterConfig;
-use crate::evpn::{RmacStore, Vtep};
+use crate::evpn::{MacIpBindings, RmacStore, Vtep};
 use crate::fib::fibtable::FibTableWriter;
+use crate::fib::urpf::UrpfDrops;
 use crate::interfaces::iftablerw::IfTableWriter;
+use crate::interfaces::lldp::LldpNeighbors;
+use crate::lfib::lfibrw::LfibWriter;
+use crate::mcast::mroutetablerw::MrouteTableWriter;
+use crate::mcast::snooping::McastMembers;
+use crate::rib::policy::RoutePolicyTable;
 use crate::rib::vrftable::VrfTable;
+use crate::srv6::sidtablerw::SidTableWriter;
 use tracing::debug;
 
 /// Routing database
@@ -18,19 +28,48 @@ pub struct RoutingDb {
     pub vtep: Vtep,
     pub atabler: AtableReader,
     pub iftw: IfTableWriter,
+    pub lfibw: LfibWriter,
+    pub sidtablew: SidTableWriter,
+    pub mroutew: MrouteTableWriter,
+    pub mcast_members: McastMembers,
+    pub lldp: LldpNeighbors,
+    pub bfd: BfdSessions,
+    pub arpnd: ArpNdAgent,
+    pub macip: MacIpBindings,
+    pub urpf_drops: UrpfDrops,
+    pub policies: RoutePolicyTable,
+    pub cli_handlers: CliHandlers,
     pub config: Option<RouterConfig>,
 }
 
 #[allow(clippy::new_without_default)]
 impl RoutingDb {
     #[must_use]
-    pub fn new(fibtable: FibTableWriter, iftw: IfTableWriter, atabler: AtableReader) -> Self {
+    pub fn new(
+        fibtable: FibTableWriter,
+        iftw: IfTableWriter,
+        atabler: AtableReader,
+        lfibw: LfibWriter,
+        sidtablew: SidTableWriter,
+        mroutew: MrouteTableWriter,
+    ) -> Self {
         Self {
             vrftable: VrfTable::new(fibtable),
             rmac_store: RmacStore::new(),
             vtep: Vtep::new(),
             atabler,
             iftw,
+            lfibw,
+            sidtablew,
+            mroutew,
+            mcast_members: McastMembers::new(),
+            lldp: LldpNeighbors::new(),
+            bfd: BfdSessions::new(),
+            arpnd: ArpNdAgent::new(),
+            macip: MacIpBindings::new(),
+            urpf_drops: UrpfDrops::new(),
+            policies: RoutePolicyTable::new(),
+            cli_handlers: CliHandlers::new(),
             config: None,
         }
     }
@@ -12,19 +12,24 @@
 use crate::errors::RouterError;
 use crate::evpn::{RmacEntry, RmacStore};
 use crate::interfaces::iftablerw::IfTableReader;
+use crate::lfib::{LabelOp, Lsp, LspNhop, LspOrigin};
+use crate::rib::distance::RouteSource;
 use crate::rib::encapsulation::{Encapsulation, VxlanEncapsulation};
 use crate::rib::nexthop::{FwAction, NhopKey};
+use crate::rib::policy::{PolicyVerdict, RoutePolicyTable};
 use crate::rib::vrf::{Route, RouteFlags, RouteNhop, RouteOrigin, Vrf};
 
 use dplane_rpc::msg::{
-    ForwardAction, IpRoute, NextHop, NextHopEncap, Rmac, RouteTableId, RouteType, VxlanEncap,
+    ForwardAction, IpRoute, LspNextHop, LspType, NextHop, NextHopEncap, Rmac, RouteTableId,
+    RouteType, VxlanEncap,
 };
 use lpm::prefix::Prefix;
 use net::eth::mac::Mac;
 use net::interface::InterfaceIndex;
+use net::mpls::MplsLabel;
 use net::vxlan::Vni;
 use std::net::{IpAddr, Ipv4Addr};
-use tracing::{error, warn};
+use tracing::{debug, error, warn};
 
 impl From<RouteType> for RouteOrigin {
     fn from(value: RouteType) -> Self {
@@ -96,6 +101,75 @@ impl TryFrom<&Rmac> for RmacEntry {
     }
 }
 
+impl From<LspType> for LspOrigin {
+    fn from(value: LspType) -> Self {
+        match value {
+            LspType::Static => LspOrigin::Static,
+            LspType::Ldp => LspOrigin::Ldp,
+            LspType::Bgp => LspOrigin::Bgp,
+            LspType::OspfSr => LspOrigin::OspfSr,
+            LspType::IsisSr => LspOrigin::IsisSr,
+        }
+    }
+}
+
+/// The implicit null label, which FRR uses as outgoing label to request penultimate hop popping
+const IMPLICIT_NULL: u32 = 3;
+
+fn mpls_label_from_rpc(label: u32) -> Result<MplsLabel, RouterError> {
+    MplsLabel::new(label).map_err(|_| {
+        error!("Received invalid MPLS label {label}");
+        RouterError::MplsLabelInvalid(label)
+    })
+}
+
+impl TryFrom<&LspNextHop> for LspNhop {
+    type Error = RouterError;
+
+    fn try_from(nh: &LspNextHop) -> Result<Self, Self::Error> {
+        let ifindex = nh
+            .ifindex
+            .map(|i| {
+                InterfaceIndex::try_new(i).map_err(|e| {
+                    error!("unable to build LSP next hop: {e}");
+                    RouterError::Internal("0 is not a valid interface index")
+                })
+            })
+            .transpose()?;
+        // next-hops without address nor interface are the egress of the LSP: packets are
+        // routed in the VRF of the next-hop once the label is popped
+        let op = if nh.address.is_none() && ifindex.is_none() {
+            LabelOp::PopLookup(nh.vrfid)
+        } else if nh.out_labels.is_empty() || nh.out_labels == [IMPLICIT_NULL] {
+            LabelOp::Pop
+        } else {
+            let labels = nh
+                .out_labels
+                .iter()
+                .map(|label| mpls_label_from_rpc(*label))
+                .collect::<Result<Vec<_>, _>>()?;
+            LabelOp::Swap(labels)
+        };
+        Ok(LspNhop::new(op, nh.address, ifindex))
+    }
+}
+
+impl TryFrom<&dplane_rpc::msg::Lsp> for Lsp {
+    type Error = RouterError;
+
+    fn try_from(value: &dplane_rpc::msg::Lsp) -> Result<Self, Self::Error> {
+        let in_label = mpls_label_from_rpc(value.in_label)?;
+        let mut lsp = Lsp::new(in_label, LspOrigin::from(value.ltype));
+        for nhop in &value.nhops {
+            match LspNhop::try_from(nhop) {
+                Ok(nh) => lsp.add_nhop(nh),
+                Err(e) => error!("Omitting next-hop in LSP for label {in_label}: {e}"),
+            }
+        }
+        Ok(lsp)
+    }
+}
+
 impl RouteNhop {
     #[tracing::instrument(level = "debug")]
     fn from_rpc_nhop(
@@ -158,7 +232,7 @@ impl Route {
         };
 
         Route {
-            flags: RouteFlags::default(),
+            flags: RouteFlags::FRR,
             origin,
             distance: r.distance,
             metric: r.metric,
@@ -183,6 +257,7 @@ impl Vrf {
         vrf0: Option<&Vrf>,
         rstore: &RmacStore,
         iftabler: &IfTableReader,
+        policies: &RoutePolicyTable,
     ) {
         let Ok(prefix) = Prefix::try_from((iproute.prefix, iproute.prefix_len)) else {
             error!(
@@ -198,7 +273,22 @@ impl Vrf {
             }
         }
 
-        let route = Route::from_iproute(&prefix, iproute);
+        let mut route = Route::from_iproute(&prefix, iproute);
+        if let Some(distance) = self.admin_distances.get(&route.origin) {
+            route.distance = *distance;
+        }
+
+        // N.B. routes from the CPI do not carry communities
+        if policies.import(self.vrfid, &prefix, &mut route, &[]) == PolicyVerdict::Reject {
+            debug!(
+                "Route to {prefix} rejected by import policy of vrf {}",
+                self.vrfid
+            );
+            // the route may replace one that was accepted: remove it
+            self.del_route_from(prefix, RouteSource::Frr, vrf0, rstore);
+            return;
+        }
+
         let mut nhops = Vec::with_capacity(iproute.nhops.len());
         for nhop in &iproute.nhops {
             match RouteNhop::from_rpc_nhop(nhop, route.origin, iftabler) {
@@ -217,6 +307,6 @@ impl Vrf {
             );
             return;
         };
-        self.del_route(prefix, vrf0, rstore);
+        self.del_route_from(prefix, RouteSource::Frr, vrf0, rstore);
     }
 }
@@ -0,0 +1,262 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Snapshots of the RIB and FIB. A [`RoutingSnapshot`] is a plain copy of the routes and
+//! forwarding entries of the VRFs at some point in time. It can be serialized (e.g. to JSON)
+//! for offline diffing or to be included in support bundles. Prefixes, encapsulations and
+//! instructions are kept in their textual form, and everything is sorted, so that two
+//! snapshots of the same state are identical.
+
+use crate::fib::fibgroupstore::FibRoute;
+use crate::rib::vrf::{Route, Vrf, VrfId};
+use crate::rib::vrftable::VrfTable;
+use crate::routingdb::RoutingDb;
+use config::GenId;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A next-hop of a route
+pub struct NhopSnapshot {
+    pub address: Option<IpAddr>,
+    pub ifindex: Option<u32>,
+    pub ifname: Option<String>,
+    pub encap: Option<String>,
+    pub fwaction: String,
+    /// The vrf where the next-hop is resolved, if not the vrf of the route
+    pub ext_vrf: Option<VrfId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A route of the RIB
+pub struct RouteSnapshot {
+    pub prefix: String,
+    pub origin: String,
+    pub distance: u8,
+    pub metric: u32,
+    pub nhops: Vec<NhopSnapshot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A group of FIB entries of a FIB route, with its weight. Each entry is the list of
+/// instructions applied to the packets using it.
+pub struct FibGroupSnapshot {
+    pub weight: u16,
+    pub entries: Vec<Vec<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A route of the FIB
+pub struct FibRouteSnapshot {
+    pub prefix: String,
+    pub groups: Vec<FibGroupSnapshot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// The routes and FIB entries of a VRF. The FIB is `None` if the VRF has none.
+pub struct VrfSnapshot {
+    pub vrfid: VrfId,
+    pub name: String,
+    pub vni: Option<u32>,
+    pub routes: Vec<RouteSnapshot>,
+    pub fib: Option<Vec<FibRouteSnapshot>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// A snapshot of the RIB and FIB of some or all the VRFs, sorted by [`VrfId`]
+pub struct RoutingSnapshot {
+    /// The generation of the config applied when the snapshot was taken, if any
+    pub genid: Option<GenId>,
+    pub vrfs: Vec<VrfSnapshot>,
+}
+
+impl RouteSnapshot {
+    fn new(prefix: String, route: &Route) -> Self {
+        let nhops = route
+            .s_nhops
+            .iter()
+            .map(|shim| {
+                let key = &shim.rc.key;
+                NhopSnapshot {
+                    address: key.address,
+                    ifindex: key.ifindex.map(|ifindex| ifindex.to_u32()),
+                    ifname: key.ifname.clone(),
+                    encap: key.encap.as_ref().map(ToString::to_string),
+                    fwaction: format!("{:?}", key.fwaction),
+                    ext_vrf: shim.ext_vrf,
+                }
+            })
+            .collect();
+        Self {
+            prefix,
+            origin: route.origin.to_string(),
+            distance: route.distance,
+            metric: route.metric,
+            nhops,
+        }
+    }
+}
+
+impl FibRouteSnapshot {
+    fn new(prefix: String, route: &FibRoute) -> Self {
+        let groups = route
+            .iter_weighted()
+            .map(|(group, weight)| FibGroupSnapshot {
+                weight,
+                entries: group
+                    .iter()
+                    .map(|entry| entry.iter().map(ToString::to_string).collect())
+                    .collect(),
+            })
+            .collect();
+        Self { prefix, groups }
+    }
+}
+
+impl VrfSnapshot {
+    /// Take a snapshot of the routes and FIB of a [`Vrf`]
+    #[must_use]
+    pub fn new(vrf: &Vrf) -> Self {
+        let mut routes_v4: Vec<_> = vrf.iter_v4().collect();
+        routes_v4.sort_by_key(|(prefix, _)| **prefix);
+        let mut routes_v6: Vec<_> = vrf.iter_v6().collect();
+        routes_v6.sort_by_key(|(prefix, _)| **prefix);
+        let routes = routes_v4
+            .into_iter()
+            .map(|(prefix, route)| RouteSnapshot::new(prefix.to_string(), route))
+            .chain(
+                routes_v6
+                    .into_iter()
+                    .map(|(prefix, route)| RouteSnapshot::new(prefix.to_string(), route)),
+            )
+            .collect();
+
+        let fib = vrf.fibw.as_ref().and_then(|fibw| fibw.enter()).map(|fib| {
+            let mut fib_v4: Vec<_> = fib.iter_v4().collect();
+            fib_v4.sort_by_key(|(prefix, _)| **prefix);
+            let mut fib_v6: Vec<_> = fib.iter_v6().collect();
+            fib_v6.sort_by_key(|(prefix, _)| **prefix);
+            fib_v4
+                .into_iter()
+                .map(|(prefix, route)| FibRouteSnapshot::new(prefix.to_string(), route))
+                .chain(
+                    fib_v6
+                        .into_iter()
+                        .map(|(prefix, route)| FibRouteSnapshot::new(prefix.to_string(), route)),
+                )
+                .collect()
+        });
+
+        Self {
+            vrfid: vrf.vrfid,
+            name: vrf.name.clone(),
+            vni: vrf.vni.map(|vni| vni.as_u32()),
+            routes,
+            fib,
+        }
+    }
+}
+
+impl RoutingSnapshot {
+    /// Take a snapshot of the VRFs in a [`VrfTable`], or only of the one with the given
+    /// [`VrfId`], if any.
+    #[must_use]
+    pub fn new(vrftable: &VrfTable, vrfid: Option<VrfId>, genid: Option<GenId>) -> Self {
+        let mut vrfs: Vec<VrfSnapshot> = vrftable
+            .values()
+            .filter(|vrf| vrfid.is_none_or(|vrfid| vrf.vrfid == vrfid))
+            .map(VrfSnapshot::new)
+            .collect();
+        vrfs.sort_by_key(|vrf| vrf.vrfid);
+        Self { genid, vrfs }
+    }
+
+    /// Serialize a [`RoutingSnapshot`] as pretty-printed JSON
+    ///
+    /// # Errors
+    ///
+    /// Fails if the snapshot can't be serialized.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Deserialize a [`RoutingSnapshot`] from JSON, e.g. to compare it with another one
+    ///
+    /// # Errors
+    ///
+    /// Fails if the input is not a valid JSON snapshot.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl RoutingDb {
+    /// Take a [`RoutingSnapshot`] of all the VRFs, or only of the one with the given [`VrfId`]
+    #[must_use]
+    pub fn snapshot(&self, vrfid: Option<VrfId>) -> RoutingSnapshot {
+        RoutingSnapshot::new(&self.vrftable, vrfid, self.current_config())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evpn::RmacStore;
+    use crate::fib::fibtype::{FibKey, FibWriter};
+    use crate::rib::encapsulation::Encapsulation;
+    use crate::rib::vrf::tests::{build_test_nhop, build_test_route};
+    use crate::rib::vrf::{RouteOrigin, RouterVrfConfig};
+    use lpm::prefix::Prefix;
+
+    #[test]
+    fn test_vrf_snapshot_json() {
+        let rstore = RmacStore::new();
+        let mut vrf = Vrf::new(&RouterVrfConfig::new(0, "default"));
+        let (fibw, _fibr) = FibWriter::new(FibKey::Id(0));
+        vrf.set_fibw(fibw);
+
+        let connected = build_test_nhop(None, Some(1), 0, None);
+        let prefix = Prefix::expect_from("10.0.0.0/24");
+        let route = build_test_route(RouteOrigin::Connected, 0, 1);
+        vrf.add_route_complete(&prefix, route, &[connected], None, &rstore);
+        let nhop = build_test_nhop(Some("10.0.0.1"), None, 0, Some(Encapsulation::Mpls(100)));
+        let prefix = Prefix::expect_from("192.168.0.0/16");
+        let route = build_test_route(RouteOrigin::Bgp, 20, 0);
+        vrf.add_route_complete(&prefix, route, &[nhop], None, &rstore);
+
+        let snapshot = VrfSnapshot::new(&vrf);
+        assert_eq!(snapshot.vrfid, 0);
+        let prefixes: Vec<&str> = snapshot.routes.iter().map(|r| r.prefix.as_str()).collect();
+        assert_eq!(
+            prefixes,
+            ["0.0.0.0/0", "10.0.0.0/24", "192.168.0.0/16", "::/0"]
+        );
+        let bgp = &snapshot.routes[2];
+        assert_eq!(bgp.distance, 20);
+        assert_eq!(bgp.nhops.len(), 1);
+        assert_eq!(bgp.nhops[0].address, Some("10.0.0.1".parse().unwrap()));
+        assert!(bgp.nhops[0].encap.is_some());
+
+        // the fib has the same destinations, the bgp route resolving via the connected one
+        let fib = snapshot.fib.as_ref().unwrap();
+        let prefixes: Vec<&str> = fib.iter().map(|r| r.prefix.as_str()).collect();
+        assert_eq!(
+            prefixes,
+            ["0.0.0.0/0", "10.0.0.0/24", "192.168.0.0/16", "::/0"]
+        );
+        assert!(!fib[2].groups.is_empty());
+
+        // same state, same snapshot
+        assert_eq!(VrfSnapshot::new(&vrf), snapshot);
+
+        // back and forth from json
+        let snapshot = RoutingSnapshot {
+            genid: Some(3),
+            vrfs: vec![snapshot],
+        };
+        let json = snapshot.to_json().unwrap();
+        assert!(json.contains("\"192.168.0.0/16\""));
+        assert_eq!(RoutingSnapshot::from_json(&json).unwrap(), snapshot);
+        assert!(RoutingSnapshot::from_json("{}").is_err());
+    }
+}
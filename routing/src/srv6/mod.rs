@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! SRv6 local SID table. Local SIDs are the IPv6 addresses (segment identifiers) for which the
+//! dataplane performs some SRv6 behavior, instead of routing the packets addressed to them.
+//! The table is programmed from the router configuration.
+
+pub mod sidtablerw;
+
+use crate::rib::vrf::VrfId;
+use ahash::RandomState;
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The behavior bound to a [`LocalSid`], as defined in RFC 8986
+pub enum SidBehavior {
+    /// Move to the next segment of the segment routing header and route the packet to it
+    End,
+    /// Decapsulate an IPv4 packet at the end of its path and route it in the given VRF
+    EndDt4(VrfId),
+    /// Decapsulate an IPv6 packet at the end of its path and route it in the given VRF
+    EndDt6(VrfId),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A local SID: a segment identifier and the behavior for packets addressed to it
+pub struct LocalSid {
+    pub sid: Ipv6Addr,
+    pub behavior: SidBehavior,
+}
+
+impl LocalSid {
+    #[must_use]
+    pub fn new(sid: Ipv6Addr, behavior: SidBehavior) -> Self {
+        Self { sid, behavior }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// The table of [`LocalSid`]s, by SID
+pub struct SidTable(HashMap<Ipv6Addr, LocalSid, RandomState>);
+
+#[allow(clippy::new_without_default)]
+impl SidTable {
+    #[must_use]
+    pub fn new() -> Self {
+        Self(HashMap::with_hasher(RandomState::with_seed(0)))
+    }
+    /// Add a [`LocalSid`], replacing the one for the same SID, if any
+    pub fn add_sid(&mut self, local_sid: LocalSid) {
+        self.0.insert(local_sid.sid, local_sid);
+    }
+    pub fn del_sid(&mut self, sid: &Ipv6Addr) {
+        self.0.remove(sid);
+    }
+    #[must_use]
+    pub fn get_sid(&self, sid: &Ipv6Addr) -> Option<&LocalSid> {
+        self.0.get(sid)
+    }
+    pub fn values(&self) -> impl Iterator<Item = &LocalSid> {
+        self.0.values()
+    }
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sidtablerw::SidTableWriter;
+    use super::*;
+    use std::str::FromStr;
+
+    fn sid(address: &str) -> Ipv6Addr {
+        Ipv6Addr::from_str(address).unwrap()
+    }
+
+    #[test]
+    fn test_sidtable_add_del() {
+        let (mut sidtablew, sidtabler) = SidTableWriter::new();
+
+        let end = LocalSid::new(sid("fc00:0:1::"), SidBehavior::End);
+        let dt4 = LocalSid::new(sid("fc00:0:1::100"), SidBehavior::EndDt4(1));
+        sidtablew.add_sid(end.clone(), false);
+        sidtablew.add_sid(dt4.clone(), false);
+        assert!(sidtabler.enter().unwrap().is_empty());
+
+        sidtablew.publish();
+        {
+            let sidtable = sidtabler.enter().unwrap();
+            assert_eq!(sidtable.len(), 2);
+            assert_eq!(sidtable.get_sid(&end.sid), Some(&end));
+            assert_eq!(sidtable.get_sid(&dt4.sid), Some(&dt4));
+        }
+
+        // replacing the behavior of a SID, seen by a reader from the factory
+        let sidtabler2 = sidtabler.factory().handle();
+        let dt6 = LocalSid::new(dt4.sid, SidBehavior::EndDt6(2));
+        sidtablew.add_sid(dt6.clone(), true);
+        assert_eq!(sidtabler2.enter().unwrap().get_sid(&dt4.sid), Some(&dt6));
+
+        sidtablew.del_sid(end.sid, true);
+        assert!(sidtabler2.enter().unwrap().get_sid(&end.sid).is_none());
+        assert_eq!(sidtabler2.enter().unwrap().len(), 1);
+
+        sidtablew.clear(true);
+        assert!(sidtabler.enter().unwrap().is_empty());
+    }
+}
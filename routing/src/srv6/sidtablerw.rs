@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! SRv6 local SID table left-right
+
+use crate::srv6::{LocalSid, SidTable};
+use left_right::{Absorb, ReadGuard, ReadHandle, ReadHandleFactory, WriteHandle};
+use std::net::Ipv6Addr;
+
+enum SidTableChange {
+    Add(LocalSid),
+    Del(Ipv6Addr),
+    Clear,
+}
+
+impl Absorb<SidTableChange> for SidTable {
+    fn absorb_first(&mut self, change: &mut SidTableChange, _: &Self) {
+        match change {
+            SidTableChange::Add(local_sid) => self.add_sid(local_sid.clone()),
+            SidTableChange::Del(sid) => self.del_sid(sid),
+            SidTableChange::Clear => self.clear(),
+        }
+    }
+    fn drop_first(self: Box<Self>) {}
+    fn sync_with(&mut self, first: &Self) {
+        *self = first.clone();
+    }
+}
+
+pub struct SidTableWriter(WriteHandle<SidTable, SidTableChange>);
+impl SidTableWriter {
+    #[must_use]
+    pub fn new() -> (SidTableWriter, SidTableReader) {
+        let (w, r) = left_right::new_from_empty::<SidTable, SidTableChange>(SidTable::new());
+        (SidTableWriter(w), SidTableReader(r))
+    }
+    #[must_use]
+    pub fn as_sidtable_reader(&self) -> SidTableReader {
+        SidTableReader::new(self.0.clone())
+    }
+    pub fn enter(&self) -> Option<ReadGuard<'_, SidTable>> {
+        self.0.enter()
+    }
+    pub fn add_sid(&mut self, local_sid: LocalSid, publish: bool) {
+        self.0.append(SidTableChange::Add(local_sid));
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn del_sid(&mut self, sid: Ipv6Addr, publish: bool) {
+        self.0.append(SidTableChange::Del(sid));
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn clear(&mut self, publish: bool) {
+        self.0.append(SidTableChange::Clear);
+        if publish {
+            self.0.publish();
+        }
+    }
+    pub fn publish(&mut self) {
+        self.0.publish();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SidTableReader(ReadHandle<SidTable>);
+impl SidTableReader {
+    pub fn new(rhandle: ReadHandle<SidTable>) -> Self {
+        SidTableReader(rhandle)
+    }
+    pub fn enter(&self) -> Option<ReadGuard<'_, SidTable>> {
+        self.0.enter()
+    }
+    pub fn factory(&self) -> SidTableReaderFactory {
+        SidTableReaderFactory(self.0.factory())
+    }
+}
+
+#[derive(Debug)]
+pub struct SidTableReaderFactory(ReadHandleFactory<SidTable>);
+impl SidTableReaderFactory {
+    #[must_use]
+    pub fn handle(&self) -> SidTableReader {
+        SidTableReader(self.0.handle())
+    }
+}
@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Exposes counters maintained by other components, such as network functions, as metrics.
+
+use crate::register::Registered;
+use crate::{MetricSpec, Register};
+use metrics::Unit;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// A function returning the current values of a set of counters, each with the id of the metric
+/// to export it as
+pub type CountersSource = Box<dyn Fn() -> Vec<(&'static str, u64)> + Send + Sync>;
+
+/// Metrics for a set of counters provided by a [`CountersSource`].
+/// Metrics are registered lazily, the first time each counter shows up.
+pub struct ExternalCounters {
+    name: String,
+    source: CountersSource,
+    counters: HashMap<&'static str, Registered<metrics::Counter>>,
+}
+
+impl Debug for ExternalCounters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExternalCounters")
+            .field("name", &self.name)
+            .field("counters", &self.counters)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ExternalCounters {
+    #[must_use]
+    pub fn new(name: &str, source: CountersSource) -> ExternalCounters {
+        ExternalCounters {
+            name: name.to_string(),
+            source,
+            counters: HashMap::new(),
+        }
+    }
+
+    /// The name of the component providing the counters
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Copy the current values of the counters to the metrics.
+    pub fn update(&mut self) {
+        for (id, value) in (self.source)() {
+            self.counters
+                .entry(id)
+                .or_insert_with(|| {
+                    let labels = vec![("source".to_string(), self.name.clone())];
+                    MetricSpec::new(id, Unit::Count, labels).register()
+                })
+                .metric
+                .absolute(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn external_counters_are_registered_lazily() {
+        let mut counters = ExternalCounters::new(
+            "stateful-nat",
+            Box::new(|| {
+                vec![
+                    ("nat_session_eviction_count", 3),
+                    ("nat_port_exhausted_count", 1),
+                ]
+            }),
+        );
+        assert_eq!(counters.name(), "stateful-nat");
+        assert!(counters.counters.is_empty());
+        counters.update();
+        assert_eq!(counters.counters.len(), 2);
+        counters.update();
+        assert_eq!(counters.counters.len(), 2);
+    }
+}
@@ -6,7 +6,7 @@
 
 use crate::rate::{HashMapSmoothing, SavitzkyGolayFilter};
 use net::packet::Packet;
-use pipeline::NetworkFunction;
+use pipeline::{NetworkFunction, PipelineCounters, PipelineLatencies};
 
 use concurrency::sync::Arc;
 use kanal::ReceiveError;
@@ -14,9 +14,14 @@ use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 use vpcmap::VpcDiscriminant;
 use vpcmap::map::VpcMapReader;
+use vpcmap::usage::VpcUsage;
 
 use crate::vpc_stats::VpcStatsStore;
-use crate::{RegisteredVpcMetrics, Specification, VpcMetricsSpec};
+use crate::{
+    CacheStatsSource, CountersSource, ExternalCounters, NatMetrics, NatStatsSource, NicMetrics,
+    NicStatsSource, PipelineLatencyMetrics, PipelineMetrics, ReadHandleCacheMetrics,
+    RegisteredVpcMetrics, Specification, VpcMetricsSpec, VpcUsageMetrics,
+};
 use net::buffer::PacketBufferMut;
 use rand::RngCore;
 use serde::Serialize;
@@ -77,6 +82,20 @@ pub struct StatsCollector {
     /// Reader for the VPC map.  This reader is used to determine the VPCs that are currently
     /// known to the system.
     vpcmap_r: VpcMapReader<VpcMapName>,
+    /// Metrics for the thread-local read-handle caches.
+    caches: Vec<ReadHandleCacheMetrics>,
+    /// Metrics for the counters of other components, such as network functions.
+    counters: Vec<ExternalCounters>,
+    /// Per-VPC metrics for the NAT stages.
+    nat: Vec<NatMetrics>,
+    /// Per-port metrics for the NICs.
+    nics: Vec<NicMetrics>,
+    /// Per-stage metrics for the pipelines.
+    pipelines: Vec<PipelineMetrics>,
+    /// Per-stage latency histograms for the pipelines.
+    latencies: Vec<PipelineLatencyMetrics>,
+    /// Per-discriminant metrics for the lookups of VPC discriminants.
+    usage: Vec<VpcUsageMetrics>,
     /// A MPSC channel receiver for collecting stats from other threads.
     updates: PacketStatsReader,
     /// Shared store for snapshots/rates usable by gRPC, CLI, etc.
@@ -147,6 +166,13 @@ impl StatsCollector {
             outstanding,
             submitted: SavitzkyGolayFilter::new(Self::TIME_TICK),
             vpcmap_r,
+            caches: Vec::new(),
+            counters: Vec::new(),
+            nat: Vec::new(),
+            nics: Vec::new(),
+            pipelines: Vec::new(),
+            latencies: Vec::new(),
+            usage: Vec::new(),
             updates,
             vpc_store,
         };
@@ -154,6 +180,41 @@ impl StatsCollector {
         (stats, writer, store_clone)
     }
 
+    /// Export the counters of a thread-local read-handle cache, which `source` provides.
+    pub fn add_cache_metrics(&mut self, name: &str, source: CacheStatsSource) {
+        self.caches.push(ReadHandleCacheMetrics::new(name, source));
+    }
+
+    /// Export the counters of the component `name`, which `source` provides.
+    pub fn add_counters(&mut self, name: &str, source: CountersSource) {
+        self.counters.push(ExternalCounters::new(name, source));
+    }
+
+    /// Export the per-VPC counters of the NAT stages, which `source` provides.
+    pub fn add_nat_metrics(&mut self, source: NatStatsSource) {
+        self.nat.push(NatMetrics::new(source));
+    }
+
+    /// Export the per-port counters of the NICs, which `source` provides.
+    pub fn add_nic_metrics(&mut self, source: NicStatsSource) {
+        self.nics.push(NicMetrics::new(source));
+    }
+
+    /// Export the per-stage counters of the pipelines sharing `counters`.
+    pub fn add_pipeline_metrics(&mut self, counters: std::sync::Arc<PipelineCounters>) {
+        self.pipelines.push(PipelineMetrics::new(counters));
+    }
+
+    /// Export the per-stage latencies of the pipelines sharing `latencies`.
+    pub fn add_latency_metrics(&mut self, latencies: std::sync::Arc<PipelineLatencies>) {
+        self.latencies.push(PipelineLatencyMetrics::new(latencies));
+    }
+
+    /// Export the per-discriminant lookup counters of `usage`.
+    pub fn add_usage_metrics(&mut self, usage: std::sync::Arc<VpcUsage>) {
+        self.usage.push(VpcUsageMetrics::new(usage));
+    }
+
     /// Update the list of VPCs known to the stats collector (sync snapshot; no awaits).
     #[tracing::instrument(level = "debug")]
     fn refresh(&mut self) -> impl Iterator<Item = (VpcDiscriminant, RegisteredVpcMetrics)> {
@@ -209,6 +270,27 @@ impl StatsCollector {
     /// Calculate updated stats and submit any expired entries to the SG filter.
     #[tracing::instrument(level = "trace")]
     async fn update(&mut self, update: Option<MetricsUpdate>) {
+        for cache in &mut self.caches {
+            cache.update();
+        }
+        for counters in &mut self.counters {
+            counters.update();
+        }
+        for nat in &mut self.nat {
+            nat.update();
+        }
+        for nics in &mut self.nics {
+            nics.update();
+        }
+        for pipeline in &mut self.pipelines {
+            pipeline.update();
+        }
+        for latencies in &mut self.latencies {
+            latencies.update();
+        }
+        for usage in &mut self.usage {
+            usage.update();
+        }
         if let Some(update) = update {
             // Refresh Prometheus registrations based on the current VPC snapshot.
             self.metrics = self.refresh().collect();
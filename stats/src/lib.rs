@@ -3,17 +3,29 @@
 
 // SCRATCH
 
+mod counters;
 mod dpstats;
+mod nat;
+mod nic;
 mod rate;
 mod register;
 mod spec;
+mod stages;
+mod tlcache;
+mod usage;
 mod vpc;
 mod vpc_stats;
 
+pub use counters::*;
 pub use dpstats::*;
+pub use nat::*;
+pub use nic::*;
 pub use rate::*;
 pub use register::*;
 pub use spec::*;
+pub use stages::*;
+pub use tlcache::*;
+pub use usage::*;
 pub use vpc::*;
 pub use vpc_stats::*;
 
@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Exposes the per-VPC counters of the NAT stages as metrics.
+
+use crate::register::Registered;
+use crate::{MetricSpec, Register};
+use metrics::Unit;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Instant;
+use vpcmap::VpcDiscriminant;
+
+/// The NAT counters for a VPC, as provided by a [`NatStatsSource`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VpcNatStats {
+    /// Number of packets from the VPC that were translated
+    pub translated_packets: u64,
+    /// Number of stateful NAT sessions initiated from the VPC, currently active
+    pub active_sessions: u64,
+    /// Number of sessions from the VPC that could not be created, for lack of addresses or ports
+    pub allocation_failures: u64,
+    /// Number of ports (or ICMP identifiers) in use for source NAT for the VPC
+    pub ports_in_use: u64,
+    /// Number of ports (or ICMP identifiers) available for source NAT for the VPC, in total
+    pub ports_total: u64,
+}
+
+/// A function returning the current NAT counters for each VPC
+pub type NatStatsSource = Box<dyn Fn() -> Vec<(VpcDiscriminant, VpcNatStats)> + Send + Sync>;
+
+#[derive(Debug, Serialize)]
+pub struct RegisteredNatMetrics {
+    pub translations: Registered<metrics::Counter>,
+    pub translation_rate: Registered<metrics::Gauge>,
+    pub active_sessions: Registered<metrics::Gauge>,
+    pub allocation_failures: Registered<metrics::Counter>,
+    pub port_utilization: Registered<metrics::Gauge>,
+    #[serde(skip)]
+    last: Option<(Instant, u64)>,
+}
+
+impl RegisteredNatMetrics {
+    fn new(labels: &[(String, String)]) -> RegisteredNatMetrics {
+        let spec = |id: &str, unit: Unit| MetricSpec::new(id, unit, labels.to_vec());
+        RegisteredNatMetrics {
+            translations: spec("nat_translation_count", Unit::Count).register(),
+            translation_rate: spec("nat_translation_rate", Unit::CountPerSecond).register(),
+            active_sessions: spec("nat_active_sessions", Unit::Count).register(),
+            allocation_failures: spec("nat_allocation_failure_count", Unit::Count).register(),
+            port_utilization: spec("nat_port_utilization", Unit::Percent).register(),
+            last: None,
+        }
+    }
+
+    // Compute the translation rate over the time elapsed since the last update
+    #[allow(clippy::cast_precision_loss)]
+    fn rate(&mut self, now: Instant, translated_packets: u64) -> f64 {
+        let rate = match self.last {
+            Some((then, count)) if now > then => {
+                translated_packets.saturating_sub(count) as f64
+                    / now.duration_since(then).as_secs_f64()
+            }
+            _ => 0.0,
+        };
+        self.last = Some((now, translated_packets));
+        rate
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn set(&mut self, now: Instant, stats: &VpcNatStats) {
+        let rate = self.rate(now, stats.translated_packets);
+        self.translations.metric.absolute(stats.translated_packets);
+        self.translation_rate.metric.set(rate);
+        self.active_sessions
+            .metric
+            .set(stats.active_sessions as f64);
+        self.allocation_failures
+            .metric
+            .absolute(stats.allocation_failures);
+        self.port_utilization.metric.set(if stats.ports_total == 0 {
+            0.0
+        } else {
+            100.0 * stats.ports_in_use as f64 / stats.ports_total as f64
+        });
+    }
+}
+
+/// Metrics for the NAT stages, per VPC.
+/// Metrics are registered lazily, as VPCs show up in the counters provided by the source.
+pub struct NatMetrics {
+    source: NatStatsSource,
+    vpcs: HashMap<VpcDiscriminant, RegisteredNatMetrics>,
+}
+
+impl Debug for NatMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NatMetrics")
+            .field("vpcs", &self.vpcs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NatMetrics {
+    #[must_use]
+    pub fn new(source: NatStatsSource) -> NatMetrics {
+        NatMetrics {
+            source,
+            vpcs: HashMap::new(),
+        }
+    }
+
+    /// Copy the current values of the counters to the metrics.
+    pub fn update(&mut self) {
+        self.update_at(Instant::now());
+    }
+
+    fn update_at(&mut self, now: Instant) {
+        for (disc, stats) in (self.source)() {
+            self.vpcs
+                .entry(disc)
+                .or_insert_with(|| {
+                    RegisteredNatMetrics::new(&[("vpcd".to_string(), disc.to_string())])
+                })
+                .set(now, &stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use net::vxlan::Vni;
+    use std::time::Duration;
+
+    #[test]
+    fn nat_metrics_are_registered_per_vpc() {
+        let vpcd1 = VpcDiscriminant::from_vni(Vni::new_checked(100).unwrap());
+        let vpcd2 = VpcDiscriminant::from_vni(Vni::new_checked(200).unwrap());
+        let mut metrics = NatMetrics::new(Box::new(move || {
+            let stats = VpcNatStats {
+                translated_packets: 50,
+                active_sessions: 2,
+                allocation_failures: 1,
+                ports_in_use: 10,
+                ports_total: 1000,
+            };
+            vec![(vpcd1, stats), (vpcd2, VpcNatStats::default())]
+        }));
+        assert!(metrics.vpcs.is_empty());
+        metrics.update();
+        assert_eq!(metrics.vpcs.len(), 2);
+        metrics.update();
+        assert_eq!(metrics.vpcs.len(), 2);
+    }
+
+    #[test]
+    fn nat_translation_rate() {
+        let mut metrics = RegisteredNatMetrics::new(&[]);
+        let start = Instant::now();
+        assert!(metrics.rate(start, 100) < f64::EPSILON);
+        let rate = metrics.rate(start + Duration::from_secs(2), 300);
+        assert!((rate - 100.0).abs() < f64::EPSILON);
+        // Counters going backwards (after a restart of the source) do not make a negative rate
+        let rate = metrics.rate(start + Duration::from_secs(3), 0);
+        assert!(rate.abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Exposes the counters of the NIC ports, including their extended statistics, as metrics.
+
+use crate::register::Registered;
+use crate::{MetricSpec, Register};
+use metrics::Unit;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// The counters of a NIC port, as provided by a [`NicStatsSource`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NicPortStats {
+    /// Number of packets received
+    pub rx_packets: u64,
+    /// Number of packets transmitted
+    pub tx_packets: u64,
+    /// Number of bytes received
+    pub rx_bytes: u64,
+    /// Number of bytes transmitted
+    pub tx_bytes: u64,
+    /// Number of packets dropped by the NIC, because the receive queues were full
+    pub rx_missed: u64,
+    /// Number of erroneous packets received
+    pub rx_errors: u64,
+    /// Number of packets which failed to be transmitted
+    pub tx_errors: u64,
+    /// Number of packets dropped for lack of buffers to receive them
+    pub rx_nombuf: u64,
+    /// The extended statistics of the port, by name. These depend on the driver of the NIC.
+    pub xstats: Vec<(String, u64)>,
+}
+
+/// A function returning the current counters of each NIC port, by port name
+pub type NicStatsSource = Box<dyn Fn() -> Vec<(String, NicPortStats)> + Send + Sync>;
+
+#[derive(Debug, Serialize)]
+pub struct RegisteredNicMetrics {
+    pub rx_packets: Registered<metrics::Counter>,
+    pub tx_packets: Registered<metrics::Counter>,
+    pub rx_bytes: Registered<metrics::Counter>,
+    pub tx_bytes: Registered<metrics::Counter>,
+    pub rx_missed: Registered<metrics::Counter>,
+    pub rx_errors: Registered<metrics::Counter>,
+    pub tx_errors: Registered<metrics::Counter>,
+    pub rx_nombuf: Registered<metrics::Counter>,
+    pub xstats: HashMap<String, Registered<metrics::Counter>>,
+    #[serde(skip)]
+    labels: Vec<(String, String)>,
+}
+
+impl RegisteredNicMetrics {
+    fn new(labels: &[(String, String)]) -> RegisteredNicMetrics {
+        let spec = |id: &str, unit: Unit| MetricSpec::new(id, unit, labels.to_vec());
+        RegisteredNicMetrics {
+            rx_packets: spec("nic_rx_packet_count", Unit::Count).register(),
+            tx_packets: spec("nic_tx_packet_count", Unit::Count).register(),
+            rx_bytes: spec("nic_rx_byte_count", Unit::Bytes).register(),
+            tx_bytes: spec("nic_tx_byte_count", Unit::Bytes).register(),
+            rx_missed: spec("nic_rx_missed_count", Unit::Count).register(),
+            rx_errors: spec("nic_rx_error_count", Unit::Count).register(),
+            tx_errors: spec("nic_tx_error_count", Unit::Count).register(),
+            rx_nombuf: spec("nic_rx_nombuf_count", Unit::Count).register(),
+            xstats: HashMap::new(),
+            labels: labels.to_vec(),
+        }
+    }
+
+    fn set(&mut self, stats: &NicPortStats) {
+        self.rx_packets.metric.absolute(stats.rx_packets);
+        self.tx_packets.metric.absolute(stats.tx_packets);
+        self.rx_bytes.metric.absolute(stats.rx_bytes);
+        self.tx_bytes.metric.absolute(stats.tx_bytes);
+        self.rx_missed.metric.absolute(stats.rx_missed);
+        self.rx_errors.metric.absolute(stats.rx_errors);
+        self.tx_errors.metric.absolute(stats.tx_errors);
+        self.rx_nombuf.metric.absolute(stats.rx_nombuf);
+        // the extended statistics share a metric, told apart by their name
+        for (name, value) in &stats.xstats {
+            self.xstats
+                .entry(name.clone())
+                .or_insert_with(|| {
+                    let mut labels = self.labels.clone();
+                    labels.push(("xstat".to_string(), name.clone()));
+                    MetricSpec::new("nic_xstat_count", Unit::Count, labels).register()
+                })
+                .metric
+                .absolute(*value);
+        }
+    }
+}
+
+/// Metrics for the NIC ports.
+/// Metrics are registered lazily, as ports and extended statistics show up in the counters
+/// provided by the source.
+pub struct NicMetrics {
+    source: NicStatsSource,
+    ports: HashMap<String, RegisteredNicMetrics>,
+}
+
+impl Debug for NicMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NicMetrics")
+            .field("ports", &self.ports)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NicMetrics {
+    #[must_use]
+    pub fn new(source: NicStatsSource) -> NicMetrics {
+        NicMetrics {
+            source,
+            ports: HashMap::new(),
+        }
+    }
+
+    /// Copy the current values of the counters to the metrics.
+    pub fn update(&mut self) {
+        for (port, stats) in (self.source)() {
+            self.ports
+                .entry(port)
+                .or_insert_with_key(|port| {
+                    RegisteredNicMetrics::new(&[("port".to_string(), port.clone())])
+                })
+                .set(&stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nic_metrics_are_registered_per_port() {
+        let mut metrics = NicMetrics::new(Box::new(|| {
+            let stats = NicPortStats {
+                rx_packets: 100,
+                rx_missed: 3,
+                xstats: vec![
+                    ("rx_good_packets".to_string(), 100),
+                    ("rx_crc_errors".to_string(), 2),
+                ],
+                ..NicPortStats::default()
+            };
+            vec![
+                ("0".to_string(), stats),
+                ("1".to_string(), NicPortStats::default()),
+            ]
+        }));
+        assert!(metrics.ports.is_empty());
+        metrics.update();
+        assert_eq!(metrics.ports.len(), 2);
+        assert_eq!(metrics.ports["0"].xstats.len(), 2);
+        assert!(metrics.ports["1"].xstats.is_empty());
+        metrics.update();
+        assert_eq!(metrics.ports.len(), 2);
+        assert_eq!(metrics.ports["0"].xstats.len(), 2);
+    }
+}
@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Exposes the per-stage packet counters and latencies of the pipelines as metrics.
+
+use crate::register::Registered;
+use crate::{MetricSpec, Register};
+use metrics::Unit;
+use pipeline::{LatencySnapshot, PipelineCounters, PipelineLatencies, StageStats};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Debug, Serialize)]
+pub struct RegisteredStageMetrics {
+    pub packets_in: Registered<metrics::Counter>,
+    pub packets_out: Registered<metrics::Counter>,
+    pub drops: Registered<metrics::Counter>,
+}
+
+impl RegisteredStageMetrics {
+    fn new(labels: &[(String, String)]) -> RegisteredStageMetrics {
+        let spec = |id: &str| MetricSpec::new(id, Unit::Count, labels.to_vec());
+        RegisteredStageMetrics {
+            packets_in: spec("pipeline_stage_rx_packet_count").register(),
+            packets_out: spec("pipeline_stage_tx_packet_count").register(),
+            drops: spec("pipeline_stage_drop_packet_count").register(),
+        }
+    }
+
+    fn set(&self, stats: &StageStats) {
+        self.packets_in.metric.absolute(stats.packets_in);
+        self.packets_out.metric.absolute(stats.packets_out);
+        self.drops.metric.absolute(stats.drops);
+    }
+}
+
+/// Metrics for the stages of a pipeline, per stage name.
+/// Metrics are registered lazily, as stages show up in the counters.
+#[derive(Debug)]
+pub struct PipelineMetrics {
+    counters: Arc<PipelineCounters>,
+    stages: HashMap<String, RegisteredStageMetrics>,
+}
+
+impl PipelineMetrics {
+    #[must_use]
+    pub fn new(counters: Arc<PipelineCounters>) -> PipelineMetrics {
+        PipelineMetrics {
+            counters,
+            stages: HashMap::new(),
+        }
+    }
+
+    /// Copy the current values of the counters to the metrics.
+    pub fn update(&mut self) {
+        for (name, stats) in self.counters.snapshot() {
+            self.stages
+                .entry(name)
+                .or_insert_with_key(|name| {
+                    RegisteredStageMetrics::new(&[("stage".to_string(), name.clone())])
+                })
+                .set(&stats);
+        }
+    }
+}
+
+/// Name of the latency histogram metric, to configure its buckets in the exporter
+pub const PIPELINE_STAGE_LATENCY: &str = "pipeline_stage_latency_seconds";
+
+#[derive(Debug)]
+struct RegisteredStageLatency {
+    latency: Registered<metrics::Histogram>,
+    /// The latencies already recorded in the metric
+    recorded: LatencySnapshot,
+}
+
+impl RegisteredStageLatency {
+    fn new(labels: &[(String, String)]) -> RegisteredStageLatency {
+        let spec = MetricSpec::new(PIPELINE_STAGE_LATENCY, Unit::Seconds, labels.to_vec());
+        RegisteredStageLatency {
+            latency: spec.register(),
+            recorded: LatencySnapshot::default(),
+        }
+    }
+
+    /// Record the latencies of `latencies` not yet recorded in the metric
+    #[allow(clippy::cast_precision_loss)]
+    fn set(&mut self, latencies: LatencySnapshot) {
+        for (nanos, count) in latencies.since(&self.recorded).buckets() {
+            let count = usize::try_from(count).unwrap_or(usize::MAX);
+            self.latency
+                .metric
+                .record_many(nanos as f64 / 1_000_000_000.0, count);
+        }
+        self.recorded = latencies;
+    }
+}
+
+/// Latency histograms for the stages of a pipeline, per stage name.
+/// Metrics are registered lazily, as stages show up in the histograms.
+#[derive(Debug)]
+pub struct PipelineLatencyMetrics {
+    latencies: Arc<PipelineLatencies>,
+    stages: HashMap<String, RegisteredStageLatency>,
+}
+
+impl PipelineLatencyMetrics {
+    #[must_use]
+    pub fn new(latencies: Arc<PipelineLatencies>) -> PipelineLatencyMetrics {
+        PipelineLatencyMetrics {
+            latencies,
+            stages: HashMap::new(),
+        }
+    }
+
+    /// Record the latencies measured since the last update in the metrics.
+    pub fn update(&mut self) {
+        for (name, latencies) in self.latencies.snapshot() {
+            self.stages
+                .entry(name)
+                .or_insert_with_key(|name| {
+                    RegisteredStageLatency::new(&[("stage".to_string(), name.clone())])
+                })
+                .set(latencies);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use net::buffer::TestBuffer;
+    use net::packet::test_utils::build_test_ipv4_packet;
+    use pipeline::sample_nfs::{DecrementTtl, Passthrough};
+    use pipeline::{DynPipeline, NetworkFunction};
+
+    #[test]
+    fn pipeline_metrics_are_registered_per_stage() {
+        let counters = Arc::new(PipelineCounters::new());
+        let mut metrics = PipelineMetrics::new(counters.clone());
+        metrics.update();
+        assert!(metrics.stages.is_empty());
+
+        let _pipeline = DynPipeline::<TestBuffer>::new()
+            .with_counters(counters.clone())
+            .add_stage(DecrementTtl)
+            .add_stage(Passthrough);
+        metrics.update();
+        assert_eq!(metrics.stages.len(), 2);
+        metrics.update();
+        assert_eq!(metrics.stages.len(), 2);
+    }
+
+    #[test]
+    fn latency_metrics_record_new_latencies() {
+        let latencies = Arc::new(PipelineLatencies::new());
+        let mut metrics = PipelineLatencyMetrics::new(latencies.clone());
+        let mut pipeline = DynPipeline::<TestBuffer>::new()
+            .with_latencies(latencies.clone())
+            .add_stage(DecrementTtl);
+        let packets = vec![build_test_ipv4_packet(10).unwrap()];
+        assert_eq!(pipeline.process(packets.into_iter()).count(), 1);
+        metrics.update();
+        assert_eq!(metrics.stages["DecrementTtl"].recorded.count(), 1);
+
+        let packets = vec![build_test_ipv4_packet(10).unwrap()];
+        assert_eq!(pipeline.process(packets.into_iter()).count(), 1);
+        metrics.update();
+        assert_eq!(metrics.stages["DecrementTtl"].recorded.count(), 2);
+    }
+}
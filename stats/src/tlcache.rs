@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Exposes the counters of thread-local read-handle caches as metrics.
+
+use crate::register::Registered;
+use crate::{MetricSpec, Register};
+use left_right_tlcache::ReadHandleCacheStats;
+use metrics::Unit;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A function returning the counters of a cache for every thread that used it
+pub type CacheStatsSource = fn() -> Vec<(String, ReadHandleCacheStats)>;
+
+#[derive(Debug, Serialize)]
+pub struct RegisteredCacheMetrics {
+    pub hits: Registered<metrics::Counter>,
+    pub misses: Registered<metrics::Counter>,
+    pub invalidations: Registered<metrics::Counter>,
+    pub refreshes: Registered<metrics::Counter>,
+    pub evictions: Registered<metrics::Counter>,
+    pub entries: Registered<metrics::Gauge>,
+}
+
+impl RegisteredCacheMetrics {
+    fn new(labels: &[(String, String)]) -> RegisteredCacheMetrics {
+        let spec = |id: &str, unit: Unit| MetricSpec::new(id, unit, labels.to_vec());
+        RegisteredCacheMetrics {
+            hits: spec("readhandle_cache_hit_count", Unit::Count).register(),
+            misses: spec("readhandle_cache_miss_count", Unit::Count).register(),
+            invalidations: spec("readhandle_cache_invalidation_count", Unit::Count).register(),
+            refreshes: spec("readhandle_cache_refresh_count", Unit::Count).register(),
+            evictions: spec("readhandle_cache_eviction_count", Unit::Count).register(),
+            entries: spec("readhandle_cache_entries", Unit::Count).register(),
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn set(&self, stats: &ReadHandleCacheStats) {
+        self.hits.metric.absolute(stats.hits);
+        self.misses.metric.absolute(stats.misses);
+        self.invalidations.metric.absolute(stats.invalidations);
+        self.refreshes.metric.absolute(stats.refreshes);
+        self.evictions.metric.absolute(stats.evictions);
+        self.entries.metric.set(stats.entries as f64);
+    }
+}
+
+/// Metrics for a thread-local read-handle cache, per thread and aggregated over all threads.
+#[derive(Debug)]
+pub struct ReadHandleCacheMetrics {
+    name: String,
+    source: CacheStatsSource,
+    total: RegisteredCacheMetrics,
+    threads: HashMap<String, RegisteredCacheMetrics>,
+}
+
+impl ReadHandleCacheMetrics {
+    #[must_use]
+    pub fn new(name: &str, source: CacheStatsSource) -> ReadHandleCacheMetrics {
+        let labels = [
+            ("cache".to_string(), name.to_string()),
+            ("thread".to_string(), "total".to_string()),
+        ];
+        ReadHandleCacheMetrics {
+            name: name.to_string(),
+            source,
+            total: RegisteredCacheMetrics::new(&labels),
+            threads: HashMap::new(),
+        }
+    }
+
+    /// The name of the cache
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Copy the current values of the counters to the metrics and return the aggregated counters.
+    pub fn update(&mut self) -> ReadHandleCacheStats {
+        let per_thread = (self.source)();
+        for (thread, stats) in &per_thread {
+            self.threads
+                .entry(thread.clone())
+                .or_insert_with(|| {
+                    RegisteredCacheMetrics::new(&[
+                        ("cache".to_string(), self.name.clone()),
+                        ("thread".to_string(), thread.clone()),
+                    ])
+                })
+                .set(stats);
+        }
+        let total = per_thread.into_iter().map(|(_, stats)| stats).sum();
+        self.total.set(&total);
+        total
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn source() -> Vec<(String, ReadHandleCacheStats)> {
+        let stats = ReadHandleCacheStats {
+            hits: 10,
+            misses: 2,
+            invalidations: 1,
+            refreshes: 1,
+            evictions: 0,
+            entries: 4,
+        };
+        vec![
+            ("worker-1".to_string(), stats),
+            ("worker-2".to_string(), stats),
+        ]
+    }
+
+    #[test]
+    fn cache_metrics_are_aggregated() {
+        let mut metrics = ReadHandleCacheMetrics::new("fibtable", source);
+        let total = metrics.update();
+        assert_eq!(metrics.name(), "fibtable");
+        assert_eq!(metrics.threads.len(), 2);
+        assert_eq!(total.hits, 20);
+        assert_eq!(total.misses, 4);
+        assert_eq!(total.entries, 8);
+    }
+}
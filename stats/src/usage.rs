@@ -0,0 +1,100 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Exposes the lookup counters of the VPC discriminants as metrics.
+
+use crate::register::Registered;
+use crate::{MetricSpec, Register};
+use metrics::Unit;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use vpcmap::VpcDiscriminant;
+use vpcmap::usage::{LookupStats, VpcUsage};
+
+#[derive(Debug, Serialize)]
+pub struct RegisteredLookup {
+    pub hit: Registered<metrics::Counter>,
+    pub miss: Registered<metrics::Counter>,
+}
+
+impl RegisteredLookup {
+    fn new(labels: &[(String, String)]) -> RegisteredLookup {
+        let spec = |id: &str| MetricSpec::new(id, Unit::Count, labels.to_vec());
+        RegisteredLookup {
+            hit: spec("vpcd_lookup_hit_count").register(),
+            miss: spec("vpcd_lookup_miss_count").register(),
+        }
+    }
+
+    fn set(&self, stats: LookupStats) {
+        self.hit.metric.absolute(stats.hits);
+        self.miss.metric.absolute(stats.misses);
+    }
+}
+
+/// Metrics for the lookups of VPC discriminants, per discriminant.
+/// Metrics are registered lazily, as discriminants show up in the [`VpcUsage`].
+#[derive(Debug)]
+pub struct VpcUsageMetrics {
+    usage: Arc<VpcUsage>,
+    discs: HashMap<VpcDiscriminant, RegisteredLookup>,
+    untracked: RegisteredLookup,
+}
+
+impl VpcUsageMetrics {
+    #[must_use]
+    pub fn new(usage: Arc<VpcUsage>) -> VpcUsageMetrics {
+        VpcUsageMetrics {
+            usage,
+            discs: HashMap::new(),
+            untracked: RegisteredLookup::new(&[("vpcd".to_string(), "untracked".to_string())]),
+        }
+    }
+
+    /// Copy the current values of the lookup counters to the metrics.
+    pub fn update(&mut self) {
+        for (disc, stats) in self.usage.snapshot() {
+            self.discs
+                .entry(disc)
+                .or_insert_with(|| RegisteredLookup::new(&[("vpcd".to_string(), disc.to_string())]))
+                .set(stats);
+        }
+        self.untracked.set(self.usage.untracked());
+    }
+
+    /// Discriminants that were looked up but never found, along with their number of misses.
+    /// These usually denote traffic for a VPC that is not (or no longer) configured.
+    #[must_use]
+    pub fn unmapped(&self) -> Vec<(VpcDiscriminant, u64)> {
+        self.usage
+            .snapshot()
+            .into_iter()
+            .filter(|(_, stats)| stats.hits == 0 && stats.misses > 0)
+            .map(|(disc, stats)| (disc, stats.misses))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use net::vxlan::Vni;
+
+    #[test]
+    fn unmapped_discriminants_are_reported() {
+        let usage = Arc::new(VpcUsage::new());
+        let mut metrics = VpcUsageMetrics::new(usage.clone());
+        let mapped = VpcDiscriminant::from_vni(Vni::new_checked(100).unwrap());
+        let unmapped = VpcDiscriminant::from_vni(Vni::new_checked(200).unwrap());
+
+        usage.record(mapped, true);
+        usage.record(mapped, false);
+        usage.record(unmapped, false);
+        usage.record(unmapped, false);
+        metrics.update();
+
+        assert_eq!(metrics.discs.len(), 2);
+        assert_eq!(metrics.unmapped(), vec![(unmapped, 2)]);
+    }
+}
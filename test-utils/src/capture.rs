@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Packet capture and assertion helpers for end-to-end tests.
+//!
+//! A [`PacketCapture`] opens an `AF_PACKET` socket bound to a (test) interface and collects the
+//! frames seen on it, parsed into [`Headers`].
+//! Tests can then wait for a number of packets matching some predicate within a timeout.
+//!
+//! Opening a capture requires the `CAP_NET_RAW` capability (see [`crate::with_caps`]).
+
+use net::headers::Headers;
+use net::parse::Parse;
+use nix::errno::Errno;
+use nix::sys::socket::{
+    AddressFamily, LinkAddr, SockFlag, SockProtocol, SockType, SockaddrLike, bind, recvfrom,
+    setsockopt, socket, sockopt,
+};
+use nix::sys::time::TimeVal;
+use std::fmt::{Debug, Display, Formatter};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Largest frame we expect to capture
+const SNAPLEN: usize = 65536;
+/// Longest time a single receive call may block
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Direction of the frames to capture, relative to the interface
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CaptureDirection {
+    /// Frames received by the interface
+    Incoming,
+    /// Frames sent by the interface
+    Outgoing,
+    /// Frames in both directions
+    Both,
+}
+
+/// A frame captured on an interface
+#[derive(Debug, Clone)]
+pub struct CapturedPacket {
+    /// Parsed headers of the frame
+    pub headers: Headers,
+    /// Raw content of the frame
+    pub data: Vec<u8>,
+    /// Whether the frame was sent by the interface (as opposed to received)
+    pub outgoing: bool,
+    /// When the frame was captured
+    pub timestamp: Instant,
+}
+
+/// Errors which may occur when capturing packets
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The interface does not exist
+    NoSuchInterface(String),
+    /// A socket operation failed
+    Socket(Errno),
+    /// Fewer packets than expected were captured before the deadline
+    Timeout {
+        /// Number of packets expected
+        expected: usize,
+        /// Packets matching the predicate captured before the deadline
+        matched: Vec<CapturedPacket>,
+    },
+    /// Packets matching the predicate were captured when none were expected
+    Unexpected(Vec<CapturedPacket>),
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::NoSuchInterface(name) => write!(f, "no such interface: {name}"),
+            CaptureError::Socket(errno) => write!(f, "socket error: {errno}"),
+            CaptureError::Timeout { expected, matched } => write!(
+                f,
+                "expected {expected} matching packets, captured {}: {matched:#?}",
+                matched.len()
+            ),
+            CaptureError::Unexpected(packets) => {
+                write!(
+                    f,
+                    "captured {} unexpected packets: {packets:#?}",
+                    packets.len()
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+/// Open an `AF_PACKET` socket bound to interface `interface`, for all protocols.
+/// Returns the socket and the index of the interface.
+///
+/// Fails with `ENODEV` if the interface does not exist.
+pub(crate) fn open_packet_socket(interface: &str) -> Result<(OwnedFd, u32), Errno> {
+    let ifindex = nix::net::if_::if_nametoindex(interface).map_err(|_| Errno::ENODEV)?;
+    let socket = socket(
+        AddressFamily::Packet,
+        SockType::Raw,
+        SockFlag::SOCK_CLOEXEC,
+        SockProtocol::EthAll,
+    )?;
+
+    #[allow(clippy::cast_possible_truncation)] // protocol is a u16 by definition
+    let protocol = (nix::libc::ETH_P_ALL as u16).to_be();
+    #[allow(clippy::cast_possible_wrap)] // kernel interface indices fit in an i32
+    let ll = nix::libc::sockaddr_ll {
+        sll_family: AddressFamily::Packet as u16,
+        sll_protocol: protocol,
+        sll_ifindex: ifindex as i32,
+        sll_hatype: 0,
+        sll_pkttype: 0,
+        sll_halen: 0,
+        sll_addr: [0; 8],
+    };
+    #[allow(unsafe_code)] // the pointer refers to a properly initialized sockaddr_ll
+    #[allow(clippy::cast_possible_truncation)]
+    let addr = unsafe {
+        LinkAddr::from_raw(
+            std::ptr::from_ref(&ll).cast(),
+            Some(size_of::<nix::libc::sockaddr_ll>() as u32),
+        )
+    }
+    .ok_or(Errno::EINVAL)?;
+    bind(socket.as_raw_fd(), &addr)?;
+    Ok((socket, ifindex))
+}
+
+/// A packet capture on a single interface.
+pub struct PacketCapture {
+    interface: String,
+    direction: CaptureDirection,
+    socket: OwnedFd,
+}
+
+impl Debug for PacketCapture {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PacketCapture")
+            .field("interface", &self.interface)
+            .field("direction", &self.direction)
+            .field("socket", &self.socket.as_raw_fd())
+            .finish()
+    }
+}
+
+impl PacketCapture {
+    /// Start capturing the frames seen in the given direction on interface `interface`.
+    ///
+    /// Only frames seen after this call are captured.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the interface does not exist or if the socket cannot be set up (e.g. for lack of
+    /// the `CAP_NET_RAW` capability).
+    pub fn open(
+        interface: impl AsRef<str>,
+        direction: CaptureDirection,
+    ) -> Result<Self, CaptureError> {
+        let interface = interface.as_ref().to_string();
+        let (socket, ifindex) = open_packet_socket(&interface).map_err(|errno| match errno {
+            Errno::ENODEV => CaptureError::NoSuchInterface(interface.clone()),
+            errno => CaptureError::Socket(errno),
+        })?;
+
+        #[allow(clippy::cast_possible_truncation)] // interval is well under a second
+        let timeout = TimeVal::new(0, POLL_INTERVAL.as_micros() as _);
+        setsockopt(&socket, sockopt::ReceiveTimeout, &timeout).map_err(CaptureError::Socket)?;
+
+        debug!("Capturing {direction:?} packets on {interface} (ifindex {ifindex})");
+        Ok(Self {
+            interface,
+            direction,
+            socket,
+        })
+    }
+
+    /// The name of the interface this capture is bound to
+    #[must_use]
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    /// Wait up to `timeout` for the next captured frame in the direction of the capture.
+    /// Frames which cannot be parsed are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Fails if receiving from the socket fails.
+    pub fn next(&self, timeout: Duration) -> Result<Option<CapturedPacket>, CaptureError> {
+        let deadline = Instant::now() + timeout;
+        let mut buffer = vec![0u8; SNAPLEN];
+        loop {
+            match recvfrom::<LinkAddr>(self.socket.as_raw_fd(), &mut buffer) {
+                Ok((len, addr)) => {
+                    let outgoing =
+                        addr.is_some_and(|addr| addr.pkttype() == nix::libc::PACKET_OUTGOING);
+                    if self.wants(outgoing) {
+                        let data = buffer[..len].to_vec();
+                        match Headers::parse(&data) {
+                            Ok((headers, _)) => {
+                                return Ok(Some(CapturedPacket {
+                                    headers,
+                                    data,
+                                    outgoing,
+                                    timestamp: Instant::now(),
+                                }));
+                            }
+                            Err(e) => {
+                                debug!("Skipping unparsable frame on {}: {e:?}", self.interface)
+                            }
+                        }
+                    }
+                }
+                Err(Errno::EAGAIN | Errno::EINTR) => {}
+                Err(errno) => return Err(CaptureError::Socket(errno)),
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn wants(&self, outgoing: bool) -> bool {
+        match self.direction {
+            CaptureDirection::Incoming => !outgoing,
+            CaptureDirection::Outgoing => outgoing,
+            CaptureDirection::Both => true,
+        }
+    }
+
+    /// Collect frames for `timeout`, keeping the ones matching `predicate`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if receiving from the socket fails.
+    pub fn collect(
+        &self,
+        timeout: Duration,
+        predicate: impl Fn(&CapturedPacket) -> bool,
+    ) -> Result<Vec<CapturedPacket>, CaptureError> {
+        self.collect_until(timeout, usize::MAX, predicate)
+    }
+
+    fn collect_until(
+        &self,
+        timeout: Duration,
+        count: usize,
+        predicate: impl Fn(&CapturedPacket) -> bool,
+    ) -> Result<Vec<CapturedPacket>, CaptureError> {
+        let deadline = Instant::now() + timeout;
+        let mut matched = Vec::new();
+        while matched.len() < count {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.next(remaining)? {
+                Some(packet) if predicate(&packet) => matched.push(packet),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        Ok(matched)
+    }
+
+    /// Wait up to `timeout` for `count` frames matching `predicate`. Frames not matching the
+    /// predicate are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CaptureError::Timeout`] if fewer than `count` matching frames are captured in time.
+    pub fn expect(
+        &self,
+        count: usize,
+        timeout: Duration,
+        predicate: impl Fn(&CapturedPacket) -> bool,
+    ) -> Result<Vec<CapturedPacket>, CaptureError> {
+        let matched = self.collect_until(timeout, count, predicate)?;
+        if matched.len() < count {
+            return Err(CaptureError::Timeout {
+                expected: count,
+                matched,
+            });
+        }
+        Ok(matched)
+    }
+
+    /// Check that no frame matching `predicate` is captured for `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CaptureError::Unexpected`] with the matching frames, if any.
+    pub fn expect_none(
+        &self,
+        timeout: Duration,
+        predicate: impl Fn(&CapturedPacket) -> bool,
+    ) -> Result<(), CaptureError> {
+        let matched = self.collect(timeout, predicate)?;
+        if matched.is_empty() {
+            Ok(())
+        } else {
+            Err(CaptureError::Unexpected(matched))
+        }
+    }
+
+    /// Assert that `count` frames matching `predicate` are captured within `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer matching frames are captured in time, or if capturing fails.
+    #[track_caller]
+    pub fn assert_captured(
+        &self,
+        count: usize,
+        timeout: Duration,
+        predicate: impl Fn(&CapturedPacket) -> bool,
+    ) -> Vec<CapturedPacket> {
+        self.expect(count, timeout, predicate)
+            .unwrap_or_else(|e| panic!("capture on {}: {e}", self.interface))
+    }
+
+    /// Assert that no frame matching `predicate` is captured within `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a matching frame is captured, or if capturing fails.
+    #[track_caller]
+    pub fn assert_not_captured(
+        &self,
+        timeout: Duration,
+        predicate: impl Fn(&CapturedPacket) -> bool,
+    ) {
+        self.expect_none(timeout, predicate)
+            .unwrap_or_else(|e| panic!("capture on {}: {e}", self.interface));
+    }
+}
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Controllable clock for time-dependent tests.
+//!
+//! [`MockClock`] implements [`flow_info::Clock`]: hand it to the component under test (e.g.,
+//! with `FlowTable::with_clock` or `StatefulNat::with_clock`) and advance time explicitly instead
+//! of sleeping.
+//!
+//! ```ignore
+//! let clock = MockClock::new();
+//! let flow_table = FlowTable::default().with_clock(clock.shared());
+//! // ... insert a flow expiring in 10 seconds
+//! clock.advance(Duration::from_secs(11));
+//! assert_eq!(flow_table.reap_all_expired(), 1);
+//! ```
+
+use flow_info::Clock;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A clock which only moves when told to. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    /// Create a clock, initially set to the current system time
+    #[must_use]
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    /// Create a clock, initially set to `start`
+    #[must_use]
+    pub fn starting_at(start: Instant) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    /// The current (virtual) time
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock is poisoned.
+    #[must_use]
+    pub fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    /// Move time forward by `duration`, and return the new time
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock is poisoned.
+    pub fn advance(&self, duration: Duration) -> Instant {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+        *now
+    }
+
+    /// Set time to `instant`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `instant` is earlier than the current time, since the clock is monotonic, or if
+    /// the lock is poisoned.
+    pub fn set(&self, instant: Instant) {
+        let mut now = self.now.lock().unwrap();
+        assert!(instant >= *now, "MockClock can't go backwards");
+        *now = instant;
+    }
+
+    /// A handle to this clock, suitable for the components taking a shared [`Clock`]
+    #[must_use]
+    pub fn shared(&self) -> Arc<dyn Clock> {
+        Arc::new(self.clone())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        MockClock::now(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn advance() {
+        let clock = MockClock::new();
+        let shared = clock.shared();
+        let start = clock.now();
+        assert_eq!(shared.now(), start);
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(shared.now(), start + Duration::from_secs(5));
+        clock.set(start + Duration::from_secs(7));
+        assert_eq!(clock.now(), start + Duration::from_secs(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "backwards")]
+    fn no_going_back() {
+        let clock = MockClock::new();
+        clock.advance(Duration::from_secs(1));
+        clock.set(clock.now() - Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,487 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! FRR fixture for routing tests.
+//!
+//! [`FrrFixture`] launches FRR daemons (zebra and, optionally, bgpd and staticd) with a generated
+//! or supplied configuration, optionally inside a network namespace, with zebra's dataplane plugin
+//! pointed at the dataplane's CPI socket. This allows exercising the route-learning paths of the
+//! dataplane in CI, with a real control plane.
+//!
+//! The daemons run in their own FRR "pathspace" and run directory, so several fixtures may run
+//! concurrently and do not interfere with a system-wide FRR instance.
+//!
+//! The FRR binaries are looked up in the directory given by environment variable
+//! [`FRR_SBIN_DIR_ENV`], or in [`DEFAULT_FRR_SBIN_DIR`]. If they are missing, [`FrrFixture::start`]
+//! fails with [`FrrFixtureError::NotInstalled`], which tests may use to skip.
+
+use std::fmt::Write as _;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Environment variable to override the location of the FRR daemons
+pub const FRR_SBIN_DIR_ENV: &str = "FRR_SBIN_DIR";
+/// Default location of the FRR daemons
+pub const DEFAULT_FRR_SBIN_DIR: &str = "/usr/lib/frr";
+/// Default name of the zebra module implementing the dataplane plugin
+pub const DEFAULT_DPLANE_MODULE: &str = "dplane_hh";
+
+/// How long to wait for the daemons to come up
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors which may occur when running an FRR fixture
+#[derive(Debug)]
+pub enum FrrFixtureError {
+    /// An FRR binary was not found
+    NotInstalled(PathBuf),
+    /// A daemon could not be spawned, or the run directory could not be set up
+    Io(std::io::Error),
+    /// A daemon did not come up in time
+    Startup(String),
+    /// A vtysh command failed
+    Vtysh(String),
+}
+
+impl std::fmt::Display for FrrFixtureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrrFixtureError::NotInstalled(path) => {
+                write!(f, "FRR binary not found: {}", path.display())
+            }
+            FrrFixtureError::Io(e) => write!(f, "I/O error: {e}"),
+            FrrFixtureError::Startup(msg) => write!(f, "FRR failed to start: {msg}"),
+            FrrFixtureError::Vtysh(msg) => write!(f, "vtysh failed: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for FrrFixtureError {}
+
+impl From<std::io::Error> for FrrFixtureError {
+    fn from(e: std::io::Error) -> Self {
+        FrrFixtureError::Io(e)
+    }
+}
+
+/// FRR daemons which the fixture can launch
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrrDaemon {
+    /// zebra, always launched
+    Zebra,
+    /// BGP daemon
+    Bgpd,
+    /// static routes daemon
+    Staticd,
+}
+
+impl FrrDaemon {
+    fn name(self) -> &'static str {
+        match self {
+            FrrDaemon::Zebra => "zebra",
+            FrrDaemon::Bgpd => "bgpd",
+            FrrDaemon::Staticd => "staticd",
+        }
+    }
+}
+
+/// A BGP neighbor in a generated configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BgpNeighbor {
+    /// Address of the neighbor
+    pub address: IpAddr,
+    /// AS of the neighbor
+    pub remote_as: u32,
+}
+
+/// A minimal FRR configuration, for the common test cases.
+/// Use [`FrrFixtureBuilder::raw_config`] for anything more elaborate.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrrTestConfig {
+    /// Hostname of the instance
+    pub hostname: String,
+    /// BGP AS number. No BGP configuration is generated if `None`.
+    pub asn: Option<u32>,
+    /// BGP router id
+    pub router_id: Option<IpAddr>,
+    /// BGP neighbors
+    pub neighbors: Vec<BgpNeighbor>,
+    /// Prefixes advertised with BGP
+    pub networks: Vec<String>,
+    /// Static routes, as `(prefix, next-hop)` pairs
+    pub static_routes: Vec<(String, String)>,
+}
+
+impl FrrTestConfig {
+    /// Render the configuration in FRR syntax
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "frr defaults datacenter");
+        if !self.hostname.is_empty() {
+            let _ = writeln!(out, "hostname {}", self.hostname);
+        }
+        let _ = writeln!(out, "log stdout debugging");
+        for (prefix, nexthop) in &self.static_routes {
+            let _ = writeln!(out, "ip route {prefix} {nexthop}");
+        }
+        if let Some(asn) = self.asn {
+            let _ = writeln!(out, "!\nrouter bgp {asn}");
+            if let Some(router_id) = self.router_id {
+                let _ = writeln!(out, " bgp router-id {router_id}");
+            }
+            let _ = writeln!(out, " no bgp ebgp-requires-policy");
+            for neighbor in &self.neighbors {
+                let _ = writeln!(
+                    out,
+                    " neighbor {} remote-as {}",
+                    neighbor.address, neighbor.remote_as
+                );
+            }
+            if !self.networks.is_empty() {
+                let _ = writeln!(out, " address-family ipv4 unicast");
+                for network in &self.networks {
+                    let _ = writeln!(out, "  network {network}");
+                }
+                let _ = writeln!(out, " exit-address-family");
+            }
+        }
+        let _ = writeln!(out, "!");
+        out
+    }
+}
+
+/// Builder for an [`FrrFixture`]
+#[derive(Debug, Clone)]
+pub struct FrrFixtureBuilder {
+    netns: Option<String>,
+    sbin_dir: PathBuf,
+    daemons: Vec<FrrDaemon>,
+    config: String,
+    cpi_sock: Option<PathBuf>,
+    dplane_module: String,
+}
+
+impl Default for FrrFixtureBuilder {
+    fn default() -> Self {
+        Self {
+            netns: None,
+            sbin_dir: std::env::var_os(FRR_SBIN_DIR_ENV)
+                .map_or_else(|| PathBuf::from(DEFAULT_FRR_SBIN_DIR), PathBuf::from),
+            daemons: vec![FrrDaemon::Zebra],
+            config: FrrTestConfig::default().render(),
+            cpi_sock: None,
+            dplane_module: DEFAULT_DPLANE_MODULE.to_string(),
+        }
+    }
+}
+
+impl FrrFixtureBuilder {
+    /// Run the daemons in the (existing) network namespace `netns`
+    #[must_use]
+    pub fn netns(mut self, netns: impl Into<String>) -> Self {
+        self.netns = Some(netns.into());
+        self
+    }
+
+    /// Look up the FRR daemons in `dir`
+    #[must_use]
+    pub fn sbin_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.sbin_dir = dir.into();
+        self
+    }
+
+    /// Also launch `daemon` (zebra is always launched)
+    #[must_use]
+    pub fn daemon(mut self, daemon: FrrDaemon) -> Self {
+        if !self.daemons.contains(&daemon) {
+            self.daemons.push(daemon);
+        }
+        self
+    }
+
+    /// Use a generated configuration. This also launches the daemons it needs.
+    #[must_use]
+    pub fn config(mut self, config: &FrrTestConfig) -> Self {
+        if config.asn.is_some() {
+            self = self.daemon(FrrDaemon::Bgpd);
+        }
+        if !config.static_routes.is_empty() {
+            self = self.daemon(FrrDaemon::Staticd);
+        }
+        self.config = config.render();
+        self
+    }
+
+    /// Use a configuration in FRR syntax
+    #[must_use]
+    pub fn raw_config(mut self, config: impl Into<String>) -> Self {
+        self.config = config.into();
+        self
+    }
+
+    /// Connect zebra's dataplane plugin to the CPI socket at `path`
+    #[must_use]
+    pub fn cpi_sock(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cpi_sock = Some(path.into());
+        self
+    }
+
+    /// Name of the zebra module implementing the dataplane plugin
+    #[must_use]
+    pub fn dplane_module(mut self, module: impl Into<String>) -> Self {
+        self.dplane_module = module.into();
+        self
+    }
+
+    /// Launch the daemons
+    ///
+    /// # Errors
+    ///
+    /// Fails if the FRR binaries are not installed, if the daemons cannot be spawned, or if they
+    /// do not come up in time.
+    pub fn start(self) -> Result<FrrFixture, FrrFixtureError> {
+        FrrFixture::start(self)
+    }
+}
+
+/// Distinguishes the pathspaces of fixtures within a process
+static FIXTURE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Running FRR daemons. The daemons are killed when the fixture is dropped.
+#[derive(Debug)]
+pub struct FrrFixture {
+    netns: Option<String>,
+    sbin_dir: PathBuf,
+    pathspace: String,
+    run_dir: PathBuf,
+    children: Vec<(FrrDaemon, Child)>,
+}
+
+impl FrrFixture {
+    /// Create a builder for a fixture
+    #[must_use]
+    pub fn builder() -> FrrFixtureBuilder {
+        FrrFixtureBuilder::default()
+    }
+
+    /// Launch the daemons described by `builder`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the FRR binaries are not installed, if the daemons cannot be spawned, or if they
+    /// do not come up in time.
+    pub fn start(builder: FrrFixtureBuilder) -> Result<Self, FrrFixtureError> {
+        for daemon in &builder.daemons {
+            let binary = builder.sbin_dir.join(daemon.name());
+            if !binary.exists() {
+                return Err(FrrFixtureError::NotInstalled(binary));
+            }
+        }
+        let count = FIXTURE_COUNT.fetch_add(1, Ordering::Relaxed);
+        let pathspace = format!("test-{}-{count}", std::process::id());
+        let run_dir = std::env::temp_dir().join(format!("frr-{pathspace}"));
+        std::fs::create_dir_all(&run_dir)?;
+        let config_path = run_dir.join("frr.conf");
+        std::fs::write(&config_path, &builder.config)?;
+
+        let mut fixture = FrrFixture {
+            netns: builder.netns.clone(),
+            sbin_dir: builder.sbin_dir.clone(),
+            pathspace,
+            run_dir,
+            children: Vec::new(),
+        };
+        // zebra first: the other daemons connect to it
+        for daemon in &builder.daemons {
+            let mut command = fixture.command(&fixture.sbin_dir.join(daemon.name()));
+            command
+                .arg("-N")
+                .arg(&fixture.pathspace)
+                .arg("-f")
+                .arg(&config_path)
+                .arg("-i")
+                .arg(fixture.run_dir.join(format!("{}.pid", daemon.name())))
+                .arg("--vty_socket")
+                .arg(&fixture.run_dir)
+                .arg("-z")
+                .arg(fixture.zserv_path());
+            if *daemon == FrrDaemon::Zebra
+                && let Some(cpi_sock) = &builder.cpi_sock
+            {
+                command
+                    .arg("-M")
+                    .arg(format!("{}:{}", builder.dplane_module, cpi_sock.display()));
+            }
+            let log =
+                std::fs::File::create(fixture.run_dir.join(format!("{}.log", daemon.name())))?;
+            command
+                .stdout(log.try_clone()?)
+                .stderr(log)
+                .stdin(Stdio::null());
+            debug!("Launching {command:?}");
+            let child = command.spawn()?;
+            fixture.children.push((*daemon, child));
+            fixture.wait_for(*daemon)?;
+        }
+        Ok(fixture)
+    }
+
+    /// Build a command running `program`, in the network namespace of the fixture if any
+    fn command(&self, program: &Path) -> Command {
+        match &self.netns {
+            Some(netns) => {
+                let mut command = Command::new("ip");
+                command.args(["netns", "exec", netns]).arg(program);
+                command
+            }
+            None => Command::new(program),
+        }
+    }
+
+    fn zserv_path(&self) -> PathBuf {
+        self.run_dir.join("zserv.api")
+    }
+
+    /// Wait for the vty socket of a daemon to show up
+    fn wait_for(&mut self, daemon: FrrDaemon) -> Result<(), FrrFixtureError> {
+        let vty = self.run_dir.join(format!("{}.vty", daemon.name()));
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        while !vty.exists() {
+            if let Some((_, child)) = self.children.iter_mut().find(|(d, _)| *d == daemon)
+                && let Ok(Some(status)) = child.try_wait()
+            {
+                return Err(FrrFixtureError::Startup(format!(
+                    "{} exited with {status}, see logs in {}",
+                    daemon.name(),
+                    self.run_dir.display()
+                )));
+            }
+            if Instant::now() >= deadline {
+                return Err(FrrFixtureError::Startup(format!(
+                    "{} did not come up within {STARTUP_TIMEOUT:?}",
+                    daemon.name()
+                )));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    }
+
+    /// Directory holding the configuration, logs and sockets of the daemons
+    #[must_use]
+    pub fn run_dir(&self) -> &Path {
+        &self.run_dir
+    }
+
+    /// Run a vtysh command against the daemons of the fixture and return its output
+    ///
+    /// # Errors
+    ///
+    /// Fails if vtysh cannot be run or if the command fails.
+    pub fn vtysh(&self, cmd: &str) -> Result<String, FrrFixtureError> {
+        let vtysh = self
+            .sbin_dir
+            .parent()
+            .map_or_else(|| PathBuf::from("vtysh"), |dir| dir.join("bin/vtysh"));
+        let vtysh = if vtysh.exists() {
+            vtysh
+        } else {
+            PathBuf::from("vtysh")
+        };
+        let output = self
+            .command(&vtysh)
+            .arg("-N")
+            .arg(&self.pathspace)
+            .arg("--vty_socket")
+            .arg(&self.run_dir)
+            .arg("-c")
+            .arg(cmd)
+            .output()?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(FrrFixtureError::Vtysh(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ))
+        }
+    }
+
+    /// Wait up to `timeout` for the output of vtysh command `cmd` to satisfy `predicate`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the command fails, or with the last output if it never satisfies the predicate.
+    pub fn wait_vtysh(
+        &self,
+        cmd: &str,
+        timeout: Duration,
+        predicate: impl Fn(&str) -> bool,
+    ) -> Result<String, FrrFixtureError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let output = self.vtysh(cmd)?;
+            if predicate(&output) {
+                return Ok(output);
+            }
+            if Instant::now() >= deadline {
+                return Err(FrrFixtureError::Vtysh(format!(
+                    "timed out waiting on '{cmd}', last output:\n{output}"
+                )));
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+impl Drop for FrrFixture {
+    fn drop(&mut self) {
+        // stop in reverse order, zebra last
+        for (daemon, child) in self.children.iter_mut().rev() {
+            if let Err(e) = child.kill() {
+                warn!("Failed to kill {}: {e}", daemon.name());
+            }
+            let _ = child.wait();
+        }
+        let _ = std::fs::remove_dir_all(&self.run_dir);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render_config() {
+        let config = FrrTestConfig {
+            hostname: "peer".to_string(),
+            asn: Some(65001),
+            router_id: Some("10.0.0.1".parse().unwrap()),
+            neighbors: vec![BgpNeighbor {
+                address: "10.0.0.2".parse().unwrap(),
+                remote_as: 65002,
+            }],
+            networks: vec!["192.168.1.0/24".to_string()],
+            static_routes: vec![],
+        };
+        let rendered = config.render();
+        assert!(rendered.contains("router bgp 65001\n bgp router-id 10.0.0.1\n"));
+        assert!(rendered.contains(" neighbor 10.0.0.2 remote-as 65002\n"));
+        assert!(rendered.contains("  network 192.168.1.0/24\n"));
+
+        let builder = FrrFixture::builder().config(&config);
+        assert_eq!(builder.daemons, vec![FrrDaemon::Zebra, FrrDaemon::Bgpd]);
+    }
+
+    #[test]
+    fn missing_binaries() {
+        let err = FrrFixture::builder()
+            .sbin_dir("/nonexistent")
+            .start()
+            .unwrap_err();
+        assert!(matches!(err, FrrFixtureError::NotInstalled(_)));
+    }
+}
@@ -3,6 +3,12 @@
 
 //! Testing utilities for the dataplane
 
+pub mod capture;
+pub mod clock;
+pub mod frr;
+pub mod topology;
+pub mod traffic;
+
 use caps::{CapSet, Capability};
 use rtnetlink::NetworkNamespace;
 use std::panic::{RefUnwindSafe, UnwindSafe, catch_unwind};
@@ -0,0 +1,412 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Declarative multi-node topologies for tests.
+//!
+//! A [`Topology`] is a set of nodes, each one a network namespace, connected by veth links.
+//! Interfaces may be given addresses and enslaved to bridges within their node. The whole fabric
+//! is built by [`TopologyBuilder::build`] and torn down when the [`Topology`] is dropped.
+//!
+//! ```ignore
+//! let topology = Topology::builder()
+//!     .node("dut")
+//!     .node("spine1")
+//!     .node("spine2")
+//!     .link(("dut", "eth0", "10.0.1.1/31"), ("spine1", "eth0", "10.0.1.0/31"))
+//!     .link(("dut", "eth1", "10.0.2.1/31"), ("spine2", "eth0", "10.0.2.0/31"))
+//!     .build()?;
+//! in_netns(&topology.netns_path("dut")?, || async { /* ... */ });
+//! ```
+//!
+//! Namespace names are prefixed with a per-topology tag, so that concurrent tests may reuse the
+//! same node names. Use [`Topology::netns`] to get the actual name of a node's namespace.
+//! Building a topology requires `CAP_SYS_ADMIN` and `CAP_NET_ADMIN`, and the `ip` tool.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{debug, warn};
+
+/// Errors which may occur when building or using a [`Topology`]
+#[derive(Debug)]
+pub enum TopologyError {
+    /// A node was declared twice
+    DuplicateNode(String),
+    /// A link or bridge refers to an undeclared node
+    UnknownNode(String),
+    /// An interface refers to an undeclared bridge
+    UnknownBridge(String),
+    /// An interface name is used twice in a node
+    DuplicateInterface(String, String),
+    /// A command failed
+    Command(String, String),
+    /// A command could not be run
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TopologyError::DuplicateNode(node) => write!(f, "duplicate node {node}"),
+            TopologyError::UnknownNode(node) => write!(f, "unknown node {node}"),
+            TopologyError::UnknownBridge(bridge) => write!(f, "unknown bridge {bridge}"),
+            TopologyError::DuplicateInterface(node, iface) => {
+                write!(f, "duplicate interface {iface} in node {node}")
+            }
+            TopologyError::Command(cmd, stderr) => write!(f, "'{cmd}' failed: {stderr}"),
+            TopologyError::Io(e) => write!(f, "I/O error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+impl From<std::io::Error> for TopologyError {
+    fn from(e: std::io::Error) -> Self {
+        TopologyError::Io(e)
+    }
+}
+
+/// One end of a link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// Node the interface lives in
+    pub node: String,
+    /// Name of the interface
+    pub iface: String,
+    /// Addresses (in CIDR notation) of the interface
+    pub addresses: Vec<String>,
+    /// Bridge the interface is enslaved to, if any
+    pub bridge: Option<String>,
+}
+
+impl Endpoint {
+    /// An endpoint without addresses
+    #[must_use]
+    pub fn new(node: impl Into<String>, iface: impl Into<String>) -> Self {
+        Self {
+            node: node.into(),
+            iface: iface.into(),
+            addresses: Vec::new(),
+            bridge: None,
+        }
+    }
+
+    /// Add an address (in CIDR notation) to the interface
+    #[must_use]
+    pub fn address(mut self, address: impl Into<String>) -> Self {
+        self.addresses.push(address.into());
+        self
+    }
+
+    /// Enslave the interface to `bridge`, declared in the same node
+    #[must_use]
+    pub fn bridge(mut self, bridge: impl Into<String>) -> Self {
+        self.bridge = Some(bridge.into());
+        self
+    }
+}
+
+impl From<(&str, &str)> for Endpoint {
+    fn from((node, iface): (&str, &str)) -> Self {
+        Endpoint::new(node, iface)
+    }
+}
+
+impl From<(&str, &str, &str)> for Endpoint {
+    fn from((node, iface, address): (&str, &str, &str)) -> Self {
+        Endpoint::new(node, iface).address(address)
+    }
+}
+
+/// A bridge within a node
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bridge {
+    node: String,
+    name: String,
+    addresses: Vec<String>,
+}
+
+/// Builder for a [`Topology`]
+#[derive(Debug, Clone, Default)]
+pub struct TopologyBuilder {
+    nodes: Vec<String>,
+    bridges: Vec<Bridge>,
+    links: Vec<(Endpoint, Endpoint)>,
+}
+
+impl TopologyBuilder {
+    /// Declare a node
+    #[must_use]
+    pub fn node(mut self, name: impl Into<String>) -> Self {
+        self.nodes.push(name.into());
+        self
+    }
+
+    /// Declare a bridge in `node`, with the given addresses (in CIDR notation)
+    #[must_use]
+    pub fn bridge<A: Into<String>>(
+        mut self,
+        node: impl Into<String>,
+        name: impl Into<String>,
+        addresses: impl IntoIterator<Item = A>,
+    ) -> Self {
+        self.bridges.push(Bridge {
+            node: node.into(),
+            name: name.into(),
+            addresses: addresses.into_iter().map(Into::into).collect(),
+        });
+        self
+    }
+
+    /// Connect two nodes with a veth pair
+    #[must_use]
+    pub fn link(mut self, a: impl Into<Endpoint>, b: impl Into<Endpoint>) -> Self {
+        self.links.push((a.into(), b.into()));
+        self
+    }
+
+    /// Check that the topology is consistent
+    fn validate(&self) -> Result<(), TopologyError> {
+        let mut nodes: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for node in &self.nodes {
+            if nodes.insert(node, Vec::new()).is_some() {
+                return Err(TopologyError::DuplicateNode(node.clone()));
+            }
+        }
+        let bridges = self.bridges.iter().map(|b| (&b.node, &b.name));
+        let endpoints = self
+            .links
+            .iter()
+            .flat_map(|(a, b)| [a, b])
+            .map(|e| (&e.node, &e.iface));
+        for (node, iface) in bridges.chain(endpoints) {
+            let ifaces = nodes
+                .get_mut(node.as_str())
+                .ok_or_else(|| TopologyError::UnknownNode(node.clone()))?;
+            if ifaces.contains(&iface.as_str()) {
+                return Err(TopologyError::DuplicateInterface(
+                    node.clone(),
+                    iface.clone(),
+                ));
+            }
+            ifaces.push(iface);
+        }
+        for endpoint in self.links.iter().flat_map(|(a, b)| [a, b]) {
+            if let Some(bridge) = &endpoint.bridge
+                && !self
+                    .bridges
+                    .iter()
+                    .any(|b| b.node == endpoint.node && &b.name == bridge)
+            {
+                return Err(TopologyError::UnknownBridge(bridge.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the namespaces, bridges and links of the topology.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the topology is inconsistent or if any of the objects cannot be created. Whatever
+    /// was created is removed in that case.
+    pub fn build(self) -> Result<Topology, TopologyError> {
+        self.validate()?;
+        let count = TOPOLOGY_COUNT.fetch_add(1, Ordering::Relaxed);
+        let tag = format!("t{}-{count}", std::process::id());
+        let mut topology = Topology {
+            tag,
+            nodes: Vec::new(),
+        };
+        for node in &self.nodes {
+            let netns = format!("{}-{node}", topology.tag);
+            ip(None, &["netns", "add", &netns])?;
+            topology.nodes.push((node.clone(), netns));
+            topology.exec(node, "ip", &["link", "set", "lo", "up"])?;
+        }
+        for bridge in &self.bridges {
+            let netns = topology.netns(&bridge.node)?.to_string();
+            let ns = Some(netns.as_str());
+            ip(ns, &["link", "add", &bridge.name, "type", "bridge"])?;
+            for address in &bridge.addresses {
+                ip(ns, &["addr", "add", address, "dev", &bridge.name])?;
+            }
+            ip(ns, &["link", "set", &bridge.name, "up"])?;
+        }
+        for (index, (a, b)) in self.links.iter().enumerate() {
+            // create both ends in the first node under temporary names, then move and rename
+            // them, so that interface names may repeat across nodes
+            let tmp_a = format!("vt{count}x{index}a");
+            let tmp_b = format!("vt{count}x{index}b");
+            let netns_a = topology.netns(&a.node)?.to_string();
+            let netns_b = topology.netns(&b.node)?.to_string();
+            ip(
+                Some(&netns_a),
+                &[
+                    "link", "add", &tmp_a, "type", "veth", "peer", "name", &tmp_b,
+                ],
+            )?;
+            ip(Some(&netns_a), &["link", "set", &tmp_b, "netns", &netns_b])?;
+            for (endpoint, tmp, netns) in [(a, &tmp_a, &netns_a), (b, &tmp_b, &netns_b)] {
+                let ns = Some(netns.as_str());
+                ip(ns, &["link", "set", tmp, "name", &endpoint.iface])?;
+                for address in &endpoint.addresses {
+                    ip(ns, &["addr", "add", address, "dev", &endpoint.iface])?;
+                }
+                if let Some(bridge) = &endpoint.bridge {
+                    ip(ns, &["link", "set", &endpoint.iface, "master", bridge])?;
+                }
+                ip(ns, &["link", "set", &endpoint.iface, "up"])?;
+            }
+        }
+        Ok(topology)
+    }
+}
+
+/// Distinguishes the topologies within a process
+static TOPOLOGY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Run `ip` with `args`, in network namespace `netns` if given
+fn ip(netns: Option<&str>, args: &[&str]) -> Result<String, TopologyError> {
+    let mut command = Command::new("ip");
+    if let Some(netns) = netns {
+        command.args(["-n", netns]);
+    }
+    command.args(args);
+    run(command)
+}
+
+fn run(mut command: Command) -> Result<String, TopologyError> {
+    debug!("Running {command:?}");
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(TopologyError::Command(
+            format!("{command:?}"),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// A test fabric built by a [`TopologyBuilder`]. Its namespaces (and therefore all its links and
+/// bridges) are removed when it is dropped.
+#[derive(Debug)]
+pub struct Topology {
+    tag: String,
+    nodes: Vec<(String, String)>,
+}
+
+impl Topology {
+    /// Create a builder for a topology
+    #[must_use]
+    pub fn builder() -> TopologyBuilder {
+        TopologyBuilder::default()
+    }
+
+    /// Names of the nodes of the topology
+    pub fn nodes(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().map(|(node, _)| node.as_str())
+    }
+
+    /// Name of the network namespace of `node`
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such node.
+    pub fn netns(&self, node: &str) -> Result<&str, TopologyError> {
+        self.nodes
+            .iter()
+            .find(|(name, _)| name == node)
+            .map(|(_, netns)| netns.as_str())
+            .ok_or_else(|| TopologyError::UnknownNode(node.to_string()))
+    }
+
+    /// Path of the network namespace of `node`, as expected by [`crate::in_netns`]
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such node.
+    pub fn netns_path(&self, node: &str) -> Result<PathBuf, TopologyError> {
+        Ok(PathBuf::from("/run/netns").join(self.netns(node)?))
+    }
+
+    /// Run `program` with `args` in the network namespace of `node` and return its output
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such node, or if the command fails.
+    pub fn exec(&self, node: &str, program: &str, args: &[&str]) -> Result<String, TopologyError> {
+        let mut command = Command::new("ip");
+        command
+            .args(["netns", "exec", self.netns(node)?, program])
+            .args(args);
+        run(command)
+    }
+
+    /// Check that `address` can be pinged from `node`
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such node, or if the ping fails.
+    pub fn ping(&self, node: &str, address: &str) -> Result<(), TopologyError> {
+        self.exec(node, "ping", &["-c", "1", "-W", "1", address])
+            .map(|_| ())
+    }
+}
+
+impl Drop for Topology {
+    fn drop(&mut self) {
+        for (node, netns) in &self.nodes {
+            if let Err(e) = ip(None, &["netns", "del", netns]) {
+                warn!(
+                    "Failed to remove namespace of node {node} ({}): {e}",
+                    self.tag
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validation() {
+        let builder = Topology::builder()
+            .node("a")
+            .node("b")
+            .bridge("b", "br0", ["10.0.0.254/24"])
+            .link(
+                ("a", "eth0", "10.0.0.1/24"),
+                Endpoint::new("b", "eth0").bridge("br0"),
+            );
+        assert!(builder.validate().is_ok());
+
+        let err = builder.clone().node("a").validate().unwrap_err();
+        assert!(matches!(err, TopologyError::DuplicateNode(node) if node == "a"));
+
+        let err = builder
+            .clone()
+            .link(("a", "eth1"), ("c", "eth0"))
+            .validate()
+            .unwrap_err();
+        assert!(matches!(err, TopologyError::UnknownNode(node) if node == "c"));
+
+        let err = builder
+            .clone()
+            .link(("a", "eth0"), ("b", "eth1"))
+            .validate()
+            .unwrap_err();
+        assert!(matches!(err, TopologyError::DuplicateInterface(_, iface) if iface == "eth0"));
+
+        let err = builder
+            .link(("a", "eth1"), Endpoint::new("b", "eth1").bridge("br1"))
+            .validate()
+            .unwrap_err();
+        assert!(matches!(err, TopologyError::UnknownBridge(bridge) if bridge == "br1"));
+    }
+}
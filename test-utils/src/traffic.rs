@@ -0,0 +1,354 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! A simple traffic generator for integration and soak tests.
+//!
+//! A [`FlowSpec`] describes a set of IPv4 UDP or TCP flows: the 5-tuples of the generated packets
+//! cycle through the configured address and port ranges, so that a single spec can exercise
+//! NAT port allocation or ECMP hashing over many flows.
+//! Packets can optionally be VXLAN-encapsulated.
+//!
+//! A [`TrafficGenerator`] emits the packets of a [`FlowSpec`] on an interface (e.g. a TAP device
+//! or a veth in a test namespace, see [`crate::in_netns`]) at an optional rate.
+//!
+//! Sending packets requires the `CAP_NET_RAW` capability (see [`crate::with_caps`]).
+
+use crate::capture::open_packet_socket;
+use etherparse::PacketBuilder;
+use nix::errno::Errno;
+use nix::sys::socket::{MsgFlags, send};
+use std::fmt::{Display, Formatter};
+use std::net::Ipv4Addr;
+use std::ops::RangeInclusive;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Length of the Ethernet, IPv4 and UDP headers prepended by VXLAN encapsulation, plus the VXLAN
+/// header itself
+const VXLAN_OVERHEAD: usize = 14 + 20 + 8 + 8;
+/// Well-known VXLAN UDP port
+pub const VXLAN_PORT: u16 = 4789;
+
+/// Transport protocol of generated packets
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Transport {
+    /// UDP datagrams
+    Udp,
+    /// TCP segments (with the SYN flag set)
+    Tcp,
+}
+
+/// VXLAN encapsulation of generated packets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VxlanEncap {
+    /// VXLAN network identifier
+    pub vni: u32,
+    /// Outer source MAC address
+    pub src_mac: [u8; 6],
+    /// Outer destination MAC address
+    pub dst_mac: [u8; 6],
+    /// Outer source IP address (the VTEP sending the packets)
+    pub src_ip: Ipv4Addr,
+    /// Outer destination IP address (the VTEP receiving the packets)
+    pub dst_ip: Ipv4Addr,
+    /// Outer UDP source port
+    pub src_port: u16,
+}
+
+/// Description of a set of flows to generate.
+///
+/// Packet number `n` of the spec uses the `n`-th value (modulo the range length) of each of the
+/// address and port ranges. The number of distinct flows is thus the least common multiple of the
+/// range lengths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowSpec {
+    /// Source MAC address
+    pub src_mac: [u8; 6],
+    /// Destination MAC address
+    pub dst_mac: [u8; 6],
+    /// Range of source IP addresses
+    pub src_ips: RangeInclusive<Ipv4Addr>,
+    /// Range of destination IP addresses
+    pub dst_ips: RangeInclusive<Ipv4Addr>,
+    /// Transport protocol
+    pub transport: Transport,
+    /// Range of source ports
+    pub src_ports: RangeInclusive<u16>,
+    /// Range of destination ports
+    pub dst_ports: RangeInclusive<u16>,
+    /// Size of the (inner) Ethernet frames, excluding the FCS. Frames are never smaller than
+    /// their headers.
+    pub frame_size: usize,
+    /// IP time-to-live
+    pub ttl: u8,
+    /// Optional VXLAN encapsulation
+    pub vxlan: Option<VxlanEncap>,
+}
+
+impl Default for FlowSpec {
+    fn default() -> Self {
+        Self {
+            src_mac: [0x02, 0, 0, 0, 0, 1],
+            dst_mac: [0x02, 0, 0, 0, 0, 2],
+            src_ips: Ipv4Addr::new(10, 0, 0, 1)..=Ipv4Addr::new(10, 0, 0, 1),
+            dst_ips: Ipv4Addr::new(10, 0, 1, 1)..=Ipv4Addr::new(10, 0, 1, 1),
+            transport: Transport::Udp,
+            src_ports: 1024..=1024,
+            dst_ports: 80..=80,
+            frame_size: 64,
+            ttl: 64,
+            vxlan: None,
+        }
+    }
+}
+
+fn nth_ip(range: &RangeInclusive<Ipv4Addr>, n: u64) -> Ipv4Addr {
+    let start = u32::from(*range.start());
+    let len = u64::from(u32::from(*range.end()).saturating_sub(start)) + 1;
+    #[allow(clippy::cast_possible_truncation)] // offset is smaller than len, which fits in u32 + 1
+    Ipv4Addr::from(start.wrapping_add((n % len) as u32))
+}
+
+fn nth_port(range: &RangeInclusive<u16>, n: u64) -> u16 {
+    let start = *range.start();
+    let len = u64::from(range.end().saturating_sub(start)) + 1;
+    #[allow(clippy::cast_possible_truncation)] // offset is smaller than len, which fits in u16 + 1
+    start.wrapping_add((n % len) as u16)
+}
+
+impl FlowSpec {
+    /// Build the `n`-th packet of this spec.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the frame cannot be serialized, which would be a bug.
+    #[must_use]
+    pub fn packet(&self, n: u64) -> Vec<u8> {
+        let builder = PacketBuilder::ethernet2(self.src_mac, self.dst_mac).ipv4(
+            nth_ip(&self.src_ips, n).octets(),
+            nth_ip(&self.dst_ips, n).octets(),
+            self.ttl,
+        );
+        let (src_port, dst_port) = (nth_port(&self.src_ports, n), nth_port(&self.dst_ports, n));
+        let mut frame = Vec::with_capacity(self.frame_size + VXLAN_OVERHEAD);
+        #[allow(clippy::cast_possible_truncation)] // sequence numbers wrap around
+        let seq = n as u32;
+        match self.transport {
+            Transport::Udp => {
+                let builder = builder.udp(src_port, dst_port);
+                let payload = vec![0u8; self.frame_size.saturating_sub(builder.size(0))];
+                builder.write(&mut frame, &payload)
+            }
+            Transport::Tcp => {
+                let builder = builder.tcp(src_port, dst_port, seq, 65535).syn();
+                let payload = vec![0u8; self.frame_size.saturating_sub(builder.size(0))];
+                builder.write(&mut frame, &payload)
+            }
+        }
+        .unwrap_or_else(|e| panic!("failed to build packet: {e}"));
+
+        match &self.vxlan {
+            None => frame,
+            Some(vxlan) => {
+                let mut payload = Vec::with_capacity(8 + frame.len());
+                // flags (VNI present), reserved, VNI, reserved
+                payload.extend_from_slice(&[0x08, 0, 0, 0]);
+                payload.extend_from_slice(&(vxlan.vni << 8).to_be_bytes());
+                payload.extend_from_slice(&frame);
+                let mut outer = Vec::with_capacity(VXLAN_OVERHEAD + frame.len());
+                PacketBuilder::ethernet2(vxlan.src_mac, vxlan.dst_mac)
+                    .ipv4(vxlan.src_ip.octets(), vxlan.dst_ip.octets(), self.ttl)
+                    .udp(vxlan.src_port, VXLAN_PORT)
+                    .write(&mut outer, &payload)
+                    .unwrap_or_else(|e| panic!("failed to build packet: {e}"));
+                outer
+            }
+        }
+    }
+
+    /// Iterate over the first `count` packets of this spec.
+    pub fn packets(&self, count: u64) -> impl Iterator<Item = Vec<u8>> + '_ {
+        (0..count).map(|n| self.packet(n))
+    }
+}
+
+/// Errors which may occur when generating traffic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrafficError {
+    /// The interface does not exist
+    NoSuchInterface(String),
+    /// A socket operation failed
+    Socket(Errno),
+}
+
+impl Display for TrafficError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrafficError::NoSuchInterface(name) => write!(f, "no such interface: {name}"),
+            TrafficError::Socket(errno) => write!(f, "socket error: {errno}"),
+        }
+    }
+}
+
+impl std::error::Error for TrafficError {}
+
+/// Summary of a generation run
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TrafficStats {
+    /// Number of packets sent
+    pub packets: u64,
+    /// Number of bytes sent
+    pub bytes: u64,
+    /// Time spent sending
+    pub elapsed: Duration,
+}
+
+/// Emits the packets of [`FlowSpec`]s on an interface.
+#[derive(Debug)]
+pub struct TrafficGenerator {
+    interface: String,
+    socket: OwnedFd,
+}
+
+impl TrafficGenerator {
+    /// Create a generator sending packets on interface `interface`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the interface does not exist or if the socket cannot be set up (e.g. for lack of
+    /// the `CAP_NET_RAW` capability).
+    pub fn open(interface: impl AsRef<str>) -> Result<Self, TrafficError> {
+        let interface = interface.as_ref().to_string();
+        let (socket, _) = open_packet_socket(&interface).map_err(|errno| match errno {
+            Errno::ENODEV => TrafficError::NoSuchInterface(interface.clone()),
+            errno => TrafficError::Socket(errno),
+        })?;
+        Ok(Self { interface, socket })
+    }
+
+    /// Send the first `count` packets of `spec`, at `rate` packets per second if specified, or
+    /// as fast as possible otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Fails if sending a packet fails.
+    pub fn send(
+        &self,
+        spec: &FlowSpec,
+        count: u64,
+        rate: Option<u64>,
+    ) -> Result<TrafficStats, TrafficError> {
+        let interval = rate
+            .filter(|rate| *rate > 0)
+            .map(|rate| Duration::from_secs(1) / u32::try_from(rate).unwrap_or(u32::MAX));
+        let start = Instant::now();
+        let mut stats = TrafficStats {
+            packets: 0,
+            bytes: 0,
+            elapsed: Duration::ZERO,
+        };
+        for (n, packet) in spec.packets(count).enumerate() {
+            if let Some(interval) = interval {
+                // pace packets against the start time so that delays do not accumulate
+                let due = start + interval * u32::try_from(n).unwrap_or(u32::MAX);
+                let now = Instant::now();
+                if due > now {
+                    std::thread::sleep(due - now);
+                }
+            }
+            loop {
+                match send(self.socket.as_raw_fd(), &packet, MsgFlags::empty()) {
+                    Ok(sent) => {
+                        stats.packets += 1;
+                        stats.bytes += sent as u64;
+                        break;
+                    }
+                    Err(Errno::EINTR | Errno::ENOBUFS) => {}
+                    Err(errno) => return Err(TrafficError::Socket(errno)),
+                }
+            }
+        }
+        stats.elapsed = start.elapsed();
+        debug!(
+            "Sent {} packets ({} bytes) on {} in {:?}",
+            stats.packets, stats.bytes, self.interface, stats.elapsed
+        );
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use etherparse::{NetSlice, SlicedPacket, TransportSlice};
+
+    #[test]
+    fn flows_cycle_through_ranges() {
+        let spec = FlowSpec {
+            src_ips: Ipv4Addr::new(10, 0, 0, 1)..=Ipv4Addr::new(10, 0, 0, 2),
+            src_ports: 1000..=1002,
+            frame_size: 128,
+            ..FlowSpec::default()
+        };
+        let tuples: Vec<_> = spec
+            .packets(6)
+            .map(|frame| {
+                assert_eq!(frame.len(), 128);
+                let sliced = SlicedPacket::from_ethernet(&frame).unwrap();
+                let Some(NetSlice::Ipv4(ip)) = sliced.net else {
+                    panic!("not ipv4");
+                };
+                let Some(TransportSlice::Udp(udp)) = sliced.transport else {
+                    panic!("not udp");
+                };
+                (ip.header().source_addr(), udp.source_port())
+            })
+            .collect();
+        assert_eq!(tuples[0], (Ipv4Addr::new(10, 0, 0, 1), 1000));
+        assert_eq!(tuples[1], (Ipv4Addr::new(10, 0, 0, 2), 1001));
+        assert_eq!(tuples[2], (Ipv4Addr::new(10, 0, 0, 1), 1002));
+        assert_eq!(tuples[3], (Ipv4Addr::new(10, 0, 0, 2), 1000));
+        // 6 packets, 6 distinct flows
+        let mut distinct = tuples.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 6);
+    }
+
+    #[test]
+    fn vxlan_encapsulation() {
+        let spec = FlowSpec {
+            vxlan: Some(VxlanEncap {
+                vni: 1234,
+                src_mac: [0x02, 0, 0, 0, 1, 1],
+                dst_mac: [0x02, 0, 0, 0, 1, 2],
+                src_ip: Ipv4Addr::new(192, 168, 0, 1),
+                dst_ip: Ipv4Addr::new(192, 168, 0, 2),
+                src_port: 50000,
+            }),
+            ..FlowSpec::default()
+        };
+        let frame = spec.packet(0);
+        assert_eq!(frame.len(), 64 + VXLAN_OVERHEAD);
+        let sliced = SlicedPacket::from_ethernet(&frame).unwrap();
+        let Some(TransportSlice::Udp(udp)) = sliced.transport else {
+            panic!("not udp");
+        };
+        assert_eq!(udp.destination_port(), VXLAN_PORT);
+        let vxlan = udp.payload();
+        assert_eq!(
+            u32::from_be_bytes(vxlan[4..8].try_into().unwrap()) >> 8,
+            1234
+        );
+        assert_eq!(
+            &vxlan[8..],
+            FlowSpec {
+                vxlan: None,
+                ..spec.clone()
+            }
+            .packet(0)
+            .as_slice()
+        );
+    }
+}
@@ -5,8 +5,9 @@
 //! This crate contains two types of tables allowing to store arbitrary data
 //! for a VPC discriminant or a pair of them. A Vpc discriminant is a value
 //! that allows associating a packet to a VPC. In the simplest case, it is a
-//! VxLAN Vni, but it could be an MPLS label, the id of a sub-interface or
-//! some packet meta-data.
+//! VxLAN Vni, but it can also be an MPLS label or an (outer, inner) pair of
+//! VLAN ids (`QinQ`). Discriminants of distinct kinds never compare equal, so
+//! VPCs reached over different fabrics can coexist in the same tables.
 
 #![deny(clippy::all, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
 
@@ -32,6 +33,13 @@ type VpcMapResult<T> = Result<T, VpcMapError>;
 pub mod map;
 #[cfg(test)]
 mod map_test;
+pub mod notify;
 pub mod pairmap;
 #[cfg(test)]
 pub mod pairmap_test;
+pub mod tables;
+#[cfg(test)]
+mod tables_test;
+pub mod usage;
+#[cfg(test)]
+mod usage_test;
@@ -3,20 +3,22 @@
 
 //! A table to store arbitrary data for `VpcDiscriminants`.
 //! This module implements a table that allows building tables to associate arbitrary data to
-//! a VPC identified by a Vpc discriminant. A discriminant may be VxLAN vni or any other value
-//! that allows associating a packet to a Vpc. The advantage of the map in this module is that
+//! a VPC identified by a Vpc discriminant. A discriminant may be a VxLAN vni, an MPLS label or
+//! a pair of (outer, inner) VLAN ids. The advantage of the map in this module is that
 //! it does not make assumptions about the nature of the data stored and allows concurrent access
 //! among threads using left-right. The only requirement for the data type is to implement trait
 //! `Clone`.
 
 #![allow(unused)]
 
+use crate::notify::{VpcMapEvent, VpcMapNotifier, diff_keys};
 use crate::{VpcDiscriminant, VpcMapError, VpcMapResult};
 use ahash::RandomState;
 use left_right::new_from_empty;
 use left_right::{Absorb, ReadGuard, ReadHandle, WriteHandle};
 use std::clone::Clone;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
 
 #[derive(Clone, Default)]
 pub struct VpcMap<T: Clone>(pub HashMap<VpcDiscriminant, T, RandomState>);
@@ -37,7 +39,7 @@ impl<T: Clone> VpcMap<T> {
         }
     }
     /// Add the entry unconditionally.
-    fn add_checked(&mut self, disc: VpcDiscriminant, entry: T) {
+    pub(crate) fn add_checked(&mut self, disc: VpcDiscriminant, entry: T) {
         self.0.insert(disc, entry);
     }
     /// Remove element with the given `VpcDiscriminant`. Won't fail if not there.
@@ -48,6 +50,36 @@ impl<T: Clone> VpcMap<T> {
     pub fn get(&self, disc: VpcDiscriminant) -> Option<&T> {
         self.0.get(&disc)
     }
+    /// Iterate over the entries of the map, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&VpcDiscriminant, &T)> {
+        self.0.iter()
+    }
+    /// Get the entries of the map, sorted by `VpcDiscriminant`
+    #[must_use]
+    pub fn sorted(&self) -> Vec<(&VpcDiscriminant, &T)> {
+        let mut entries: Vec<_> = self.0.iter().collect();
+        entries.sort_by_key(|(disc, _)| **disc);
+        entries
+    }
+    /// Number of entries in the map
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    /// Tell if the map has no entries
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T: Clone + Display> Display for VpcMap<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (disc, entry) in self.sorted() {
+            writeln!(f, " {disc}: {entry}")?;
+        }
+        Ok(())
+    }
 }
 
 enum VpcMapChange<T: Clone> {
@@ -75,7 +107,10 @@ impl<T: Clone> Absorb<VpcMapChange<T>> for VpcMap<T> {
     }
 }
 
-pub struct VpcMapWriter<T: Clone>(WriteHandle<VpcMap<T>, VpcMapChange<T>>);
+pub struct VpcMapWriter<T: Clone> {
+    handle: WriteHandle<VpcMap<T>, VpcMapChange<T>>,
+    notifier: VpcMapNotifier,
+}
 #[derive(Clone, Debug)]
 pub struct VpcMapReader<T: Clone>(ReadHandle<VpcMap<T>>);
 
@@ -84,42 +119,72 @@ impl<T: Clone> VpcMapWriter<T> {
     #[allow(clippy::new_without_default)]
     pub fn new() -> VpcMapWriter<T> {
         let (w, _) = new_from_empty::<VpcMap<T>, VpcMapChange<T>>(VpcMap::new());
-        VpcMapWriter(w)
+        VpcMapWriter {
+            handle: w,
+            notifier: VpcMapNotifier::new(),
+        }
     }
     #[must_use]
     pub fn get_reader(&self) -> VpcMapReader<T> {
-        VpcMapReader(self.0.clone())
+        VpcMapReader(self.handle.clone())
     }
     /// Completely replaces the inner `VpcMap` with the provided one. This is useful when the
     /// map is built for configuration purposes (E.g. some NAT tables).
     pub fn set_map(&mut self, map: VpcMap<T>) {
-        self.0.append(VpcMapChange::SetMap(map));
-        self.0.publish();
+        self.handle.append(VpcMapChange::SetMap(map));
+        self.do_publish();
     }
     /// Add an entry to the `VpcMap`
     pub fn add(&mut self, disc: VpcDiscriminant, entry: T, publish: bool) -> VpcMapResult<()> {
-        let inner = self.0.raw_write_handle();
+        let inner = self.handle.raw_write_handle();
         unsafe {
             let inner = inner.as_ref();
             if inner.0.contains_key(&disc) {
                 return Err(VpcMapError::EntryExists(disc));
             }
         }
-        self.0.append(VpcMapChange::Add(disc, entry));
+        self.handle.append(VpcMapChange::Add(disc, entry));
         if publish {
-            self.0.publish();
+            self.do_publish();
         }
         Ok(())
     }
     /// Remove the entry with the given `VpcDiscriminant`
     pub fn del(&mut self, disc: VpcDiscriminant, publish: bool) {
-        self.0.append(VpcMapChange::Del(disc));
+        self.handle.append(VpcMapChange::Del(disc));
         if publish {
-            self.0.publish();
+            self.do_publish();
         }
     }
     pub fn publish(&mut self) {
-        self.0.publish();
+        self.do_publish();
+    }
+    /// Access the notifier of this writer, to register observers of the changes
+    pub fn notifier(&mut self) -> &mut VpcMapNotifier {
+        &mut self.notifier
+    }
+    fn published_keys(&self) -> HashSet<VpcDiscriminant> {
+        self.handle
+            .enter()
+            .map(|map| map.0.keys().copied().collect())
+            .unwrap_or_default()
+    }
+    /// Publish the pending changes, notifying observers of the entries added and removed.
+    fn do_publish(&mut self) {
+        if self.notifier.is_empty() {
+            self.handle.publish();
+            return;
+        }
+        let before = self.published_keys();
+        self.handle.publish();
+        let after = self.published_keys();
+        let (removed, added) = diff_keys(&before, &after);
+        let events: Vec<_> = removed
+            .into_iter()
+            .map(VpcMapEvent::Removed)
+            .chain(added.into_iter().map(VpcMapEvent::Added))
+            .collect();
+        self.notifier.notify(&events);
     }
 }
 
@@ -127,5 +192,17 @@ impl<T: Clone> VpcMapReader<T> {
     pub fn enter(&self) -> Option<ReadGuard<'_, VpcMap<T>>> {
         self.0.enter()
     }
-    // TODO provide an easy api to read
+    /// Get a copy of all the entries currently published, sorted by `VpcDiscriminant`.
+    /// Returns None if the map can't be read.
+    #[must_use]
+    pub fn snapshot(&self) -> Option<Vec<(VpcDiscriminant, T)>> {
+        let guard = self.0.enter()?;
+        Some(
+            guard
+                .sorted()
+                .into_iter()
+                .map(|(disc, entry)| (*disc, entry.clone()))
+                .collect(),
+        )
+    }
 }
@@ -3,7 +3,10 @@
 
 //! Tests and sample usage for VpcMap
 use crate::map::*;
+use crate::notify::VpcMapEvent;
 use crate::*;
+use net::mpls::MplsLabel;
+use net::vlan::Vid;
 use net::vxlan::Vni;
 
 /// Sample mapping that maps a discriminant to a string (e.g. Vpc name)
@@ -49,3 +52,152 @@ fn test_vpcmap_vpcname() {
     map.del(disc);
     assert!(map.get(disc).is_none());
 }
+
+#[test]
+fn test_vpcmap_mixed_discriminants() {
+    let mut map: VpcMap<VpcName> = VpcMap::new();
+    let vni = VpcDiscriminant::from_vni(Vni::new_checked(100).unwrap());
+    let mpls = VpcDiscriminant::from_mpls_label(MplsLabel::new(100).unwrap());
+    let qinq = VpcDiscriminant::from_vlans(Vid::new(100).unwrap(), Vid::new(200).unwrap());
+    let qinq_swapped = VpcDiscriminant::from_vlans(Vid::new(200).unwrap(), Vid::new(100).unwrap());
+
+    // same numerical value, distinct kinds: no collisions
+    assert_eq!(map.add(vni, VpcName::new(vni, "VPC-VNI")), Ok(()));
+    assert_eq!(map.add(mpls, VpcName::new(mpls, "VPC-MPLS")), Ok(()));
+    assert_eq!(map.add(qinq, VpcName::new(qinq, "VPC-QINQ")), Ok(()));
+
+    assert_eq!(map.get(vni).unwrap().name, "VPC-VNI");
+    assert_eq!(map.get(mpls).unwrap().name, "VPC-MPLS");
+    assert_eq!(map.get(qinq).unwrap().name, "VPC-QINQ");
+
+    // order of the vlans matters
+    assert!(map.get(qinq_swapped).is_none());
+
+    // duplicates are still detected
+    assert!(
+        map.add(mpls, VpcName::new(mpls, "VPC-MPLS-2"))
+            .is_err_and(|e| e == VpcMapError::EntryExists(mpls))
+    );
+}
+
+#[test]
+fn test_vpcmap_writer_mpls_qinq() {
+    let mut writer: VpcMapWriter<VpcName> = VpcMapWriter::new();
+    let reader = writer.get_reader();
+    let mpls = VpcDiscriminant::from_mpls_label(MplsLabel::new(3000).unwrap());
+    let qinq = VpcDiscriminant::from_vlans(Vid::new(10).unwrap(), Vid::new(20).unwrap());
+
+    writer
+        .add(mpls, VpcName::new(mpls, "VPC-1"), false)
+        .unwrap();
+    writer.add(qinq, VpcName::new(qinq, "VPC-2"), true).unwrap();
+    {
+        let guard = reader.enter().unwrap();
+        assert_eq!(guard.get(mpls).unwrap().name, "VPC-1");
+        assert_eq!(guard.get(qinq).unwrap().name, "VPC-2");
+    }
+
+    writer.del(qinq, true);
+    let guard = reader.enter().unwrap();
+    assert!(guard.get(qinq).is_none());
+    assert!(guard.get(mpls).is_some());
+}
+
+impl std::fmt::Display for VpcName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[test]
+fn test_vpcmap_iter_and_dump() {
+    let mut writer: VpcMapWriter<VpcName> = VpcMapWriter::new();
+    let reader = writer.get_reader();
+    assert_eq!(reader.snapshot().unwrap().len(), 0);
+
+    let disc1 = VpcDiscriminant::from_vni(Vni::new_checked(3000).unwrap());
+    let disc2 = VpcDiscriminant::from_vni(Vni::new_checked(1000).unwrap());
+    writer
+        .add(disc1, VpcName::new(disc1, "VPC-1"), false)
+        .unwrap();
+    writer
+        .add(disc2, VpcName::new(disc2, "VPC-2"), true)
+        .unwrap();
+
+    let guard = reader.enter().unwrap();
+    assert_eq!(guard.len(), 2);
+    assert!(!guard.is_empty());
+    let mut seen: Vec<_> = guard.iter().map(|(d, e)| (*d, e.name.clone())).collect();
+    seen.sort();
+    assert_eq!(
+        seen,
+        vec![(disc2, "VPC-2".to_string()), (disc1, "VPC-1".to_string())]
+    );
+    assert_eq!(guard.to_string(), " VNI(1000): VPC-2\n VNI(3000): VPC-1\n");
+    drop(guard);
+
+    let snapshot = reader.snapshot().unwrap();
+    assert_eq!(snapshot.len(), 2);
+    assert_eq!(snapshot[0].0, disc2);
+    assert_eq!(snapshot[1].1.name, "VPC-1");
+}
+
+#[test]
+fn test_vpcmap_notifications() {
+    use std::sync::{Arc, Mutex};
+
+    let mut writer: VpcMapWriter<VpcName> = VpcMapWriter::new();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen_clone = seen.clone();
+    writer
+        .notifier()
+        .subscribe(move |event| seen_clone.lock().unwrap().push(*event));
+    let rx = writer.notifier().subscribe_channel();
+    assert_eq!(writer.notifier().len(), 2);
+
+    let disc1 = VpcDiscriminant::from_vni(Vni::new_checked(1000).unwrap());
+    let disc2 = VpcDiscriminant::from_vni(Vni::new_checked(2000).unwrap());
+    let disc3 = VpcDiscriminant::from_vni(Vni::new_checked(3000).unwrap());
+
+    // nothing is notified until published
+    writer
+        .add(disc1, VpcName::new(disc1, "VPC-1"), false)
+        .unwrap();
+    assert!(seen.lock().unwrap().is_empty());
+    writer
+        .add(disc2, VpcName::new(disc2, "VPC-2"), true)
+        .unwrap();
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![VpcMapEvent::Added(disc1), VpcMapEvent::Added(disc2)]
+    );
+    assert_eq!(rx.try_recv(), Ok(VpcMapEvent::Added(disc1)));
+    assert_eq!(rx.try_recv(), Ok(VpcMapEvent::Added(disc2)));
+    assert!(rx.try_recv().is_err());
+
+    // replacing the map notifies of the differences only
+    seen.lock().unwrap().clear();
+    let mut map = VpcMap::new();
+    map.add(disc2, VpcName::new(disc2, "VPC-2-renamed"))
+        .unwrap();
+    map.add(disc3, VpcName::new(disc3, "VPC-3")).unwrap();
+    writer.set_map(map);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![VpcMapEvent::Removed(disc1), VpcMapEvent::Added(disc3)]
+    );
+
+    // dropped receivers are unregistered
+    drop(rx);
+    writer.del(disc3, true);
+    assert_eq!(writer.notifier().len(), 1);
+    assert_eq!(
+        seen.lock().unwrap().last(),
+        Some(&VpcMapEvent::Removed(disc3))
+    );
+
+    // removing a non-existent entry is not notified
+    seen.lock().unwrap().clear();
+    writer.del(disc3, true);
+    assert!(seen.lock().unwrap().is_empty());
+}
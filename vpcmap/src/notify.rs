@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Notifications of changes in the vpc tables.
+//! Writers of the tables in this crate own a [`VpcMapNotifier`] where observers can be
+//! registered, either as callbacks or as channels. Observers are told about the entries that
+//! were added or removed whenever a writer publishes, that is, once readers can see the change.
+//! Entries whose data is replaced without the discriminant(s) changing produce no events.
+
+use crate::VpcDiscriminant;
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// A change in a vpc table
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VpcMapEvent {
+    /// An entry for a discriminant was added to a `VpcMap`
+    Added(VpcDiscriminant),
+    /// The entry for a discriminant was removed from a `VpcMap`
+    Removed(VpcDiscriminant),
+    /// An entry for an (east, west) pair was added to a `VpcPairMap`
+    PairAdded(VpcDiscriminant, VpcDiscriminant),
+    /// The entry for an (east, west) pair was removed from a `VpcPairMap`
+    PairRemoved(VpcDiscriminant, VpcDiscriminant),
+}
+
+type Callback = Box<dyn Fn(&VpcMapEvent) + Send>;
+
+enum Observer {
+    Callback(Callback),
+    Channel(Sender<VpcMapEvent>),
+}
+
+/// A set of observers interested in changes of a vpc table
+#[derive(Default)]
+pub struct VpcMapNotifier {
+    observers: Vec<Observer>,
+}
+
+impl VpcMapNotifier {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Register a callback to be called for every event.
+    /// Callbacks are called from the writer's thread and should not block.
+    pub fn subscribe(&mut self, callback: impl Fn(&VpcMapEvent) + Send + 'static) {
+        self.observers.push(Observer::Callback(Box::new(callback)));
+    }
+    /// Register a channel where every event will be sent. The channel is automatically
+    /// unregistered when the returned [`Receiver`] is dropped.
+    #[must_use]
+    pub fn subscribe_channel(&mut self) -> Receiver<VpcMapEvent> {
+        let (tx, rx) = channel();
+        self.observers.push(Observer::Channel(tx));
+        rx
+    }
+    /// Number of registered observers
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.observers.len()
+    }
+    /// Tell if there are no observers
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+    /// Deliver the events to all observers, dropping the channels that got disconnected.
+    pub(crate) fn notify(&mut self, events: &[VpcMapEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        self.observers.retain(|observer| match observer {
+            Observer::Callback(callback) => {
+                events.iter().for_each(callback);
+                true
+            }
+            Observer::Channel(tx) => events.iter().all(|event| tx.send(*event).is_ok()),
+        });
+    }
+}
+
+impl std::fmt::Debug for VpcMapNotifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VpcMapNotifier")
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+/// Compute the (sorted) keys that were removed and added between two sets of keys.
+pub(crate) fn diff_keys<K: Copy + Ord + Hash>(
+    before: &HashSet<K>,
+    after: &HashSet<K>,
+) -> (Vec<K>, Vec<K>) {
+    let mut removed: Vec<K> = before.difference(after).copied().collect();
+    let mut added: Vec<K> = after.difference(before).copied().collect();
+    removed.sort_unstable();
+    added.sort_unstable();
+    (removed, added)
+}
@@ -47,10 +47,12 @@
 #![allow(unused)]
 
 use super::{VpcDiscriminant, VpcMapError, VpcMapResult};
+use crate::notify::{VpcMapEvent, VpcMapNotifier, diff_keys};
 use ahash::RandomState;
 use left_right::new_from_empty;
 use left_right::{Absorb, ReadGuard, ReadHandle, WriteHandle};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
 use std::rc::Rc;
 
 pub trait VpcPair {
@@ -84,11 +86,40 @@ impl<P: VpcPair + Clone> VpcPairMap<P> {
         self.0.remove(&(east, west));
         self.0.remove(&(west, east));
     }
+    /// Tell if some data is associated to (east, west) or (west, east).
+    pub fn contains(&self, east: VpcDiscriminant, west: VpcDiscriminant) -> bool {
+        self.0.contains_key(&(east, west)) || self.0.contains_key(&(west, east))
+    }
     /// Get the data associated to a certain (east, west) pair.
     /// Returns None if no data is associated to (east, west) or (west, east).
     pub fn get(&self, east: VpcDiscriminant, west: VpcDiscriminant) -> Option<&P> {
         self.0.get(&(east, west)).map(|v| &**v)
     }
+    /// Iterate over the entries of the map, in no particular order. Every entry is visited
+    /// once, along with its (east, west) discriminants as reported by the entry itself.
+    pub fn iter(&self) -> impl Iterator<Item = (VpcDiscriminant, VpcDiscriminant, &P)> {
+        self.0.iter().filter_map(|((east, west), entry)| {
+            (entry.get_east_disc() == *east && entry.get_west_disc() == *west)
+                .then_some((*east, *west, &**entry))
+        })
+    }
+    /// Get the entries of the map, sorted by (east, west) discriminants
+    #[must_use]
+    pub fn sorted(&self) -> Vec<(VpcDiscriminant, VpcDiscriminant, &P)> {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by_key(|(east, west, _)| (*east, *west));
+        entries
+    }
+    /// Number of entries (pairs) in the map
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+    /// Tell if the map has no entries
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
     fn get_data(entry: &P, disc: VpcDiscriminant) -> &P::SidedData {
         if entry.get_east_disc() == disc {
             entry.get_east_data()
@@ -113,6 +144,15 @@ impl<P: VpcPair + Clone> VpcPairMap<P> {
     }
 }
 
+impl<P: VpcPair + Clone + Display> Display for VpcPairMap<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (east, west, entry) in self.sorted() {
+            writeln!(f, " {east} <-> {west}: {entry}")?;
+        }
+        Ok(())
+    }
+}
+
 enum VpcPairMapChange<P: Clone + VpcPair> {
     Add(P),
     Del(VpcDiscriminant, VpcDiscriminant),
@@ -132,7 +172,10 @@ impl<T: VpcPair + Clone> Absorb<VpcPairMapChange<T>> for VpcPairMap<T> {
     }
 }
 
-pub struct VpcPairMapWriter<P: VpcPair + Clone>(WriteHandle<VpcPairMap<P>, VpcPairMapChange<P>>);
+pub struct VpcPairMapWriter<P: VpcPair + Clone> {
+    handle: WriteHandle<VpcPairMap<P>, VpcPairMapChange<P>>,
+    notifier: VpcMapNotifier,
+}
 pub struct VpcPairMapReader<P: VpcPair + Clone>(ReadHandle<VpcPairMap<P>>);
 
 impl<P: VpcPair + Clone> VpcPairMapWriter<P> {
@@ -140,11 +183,14 @@ impl<P: VpcPair + Clone> VpcPairMapWriter<P> {
     #[allow(clippy::new_without_default)]
     pub fn new() -> VpcPairMapWriter<P> {
         let (w, _) = new_from_empty::<VpcPairMap<P>, VpcPairMapChange<P>>(VpcPairMap::new());
-        VpcPairMapWriter(w)
+        VpcPairMapWriter {
+            handle: w,
+            notifier: VpcMapNotifier::new(),
+        }
     }
     #[must_use]
     pub fn get_reader(&self) -> VpcPairMapReader<P> {
-        VpcPairMapReader(self.0.clone())
+        VpcPairMapReader(self.handle.clone())
     }
 
     /// Add an entry to the `VpcMap`
@@ -155,28 +201,59 @@ impl<P: VpcPair + Clone> VpcPairMapWriter<P> {
             return Err(VpcMapError::InvalidInput);
         }
         let key1 = (east, west);
-        let key2 = (east, west);
-        let inner = self.0.raw_write_handle();
+        let key2 = (west, east);
+        let inner = self.handle.raw_write_handle();
         unsafe {
             let inner = inner.as_ref();
             if inner.0.contains_key(&key1) || inner.0.contains_key(&key2) {
                 return Err(VpcMapError::PairedEntryExists(east, west));
             }
         }
-        self.0.append(VpcPairMapChange::Add(pair));
+        self.handle.append(VpcPairMapChange::Add(pair));
         if publish {
-            self.0.publish();
+            self.do_publish();
         }
         Ok(())
     }
     pub fn del(&mut self, east: VpcDiscriminant, west: VpcDiscriminant, publish: bool) {
-        self.0.append(VpcPairMapChange::Del(east, west));
+        self.handle.append(VpcPairMapChange::Del(east, west));
         if publish {
-            self.0.publish();
+            self.do_publish();
         }
     }
     pub fn publish(&mut self) {
-        self.0.publish();
+        self.do_publish();
+    }
+    /// Access the notifier of this writer, to register observers of the changes
+    pub fn notifier(&mut self) -> &mut VpcMapNotifier {
+        &mut self.notifier
+    }
+    fn published_keys(&self) -> HashSet<(VpcDiscriminant, VpcDiscriminant)> {
+        self.handle
+            .enter()
+            .map(|map| map.iter().map(|(east, west, _)| (east, west)).collect())
+            .unwrap_or_default()
+    }
+    /// Publish the pending changes, notifying observers of the pairs added and removed.
+    fn do_publish(&mut self) {
+        if self.notifier.is_empty() {
+            self.handle.publish();
+            return;
+        }
+        let before = self.published_keys();
+        self.handle.publish();
+        let after = self.published_keys();
+        let (removed, added) = diff_keys(&before, &after);
+        let events: Vec<_> = removed
+            .into_iter()
+            .map(|(east, west)| VpcMapEvent::PairRemoved(east, west))
+            .chain(
+                added
+                    .into_iter()
+                    .map(|(east, west)| VpcMapEvent::PairAdded(east, west)),
+            )
+            .collect();
+        self.notifier.notify(&events);
     }
 }
 
@@ -184,5 +261,17 @@ impl<P: VpcPair + Clone> VpcPairMapReader<P> {
     pub fn enter(&self) -> Option<ReadGuard<'_, VpcPairMap<P>>> {
         self.0.enter()
     }
-    // TODO provide an easy api to read
+    /// Get a copy of all the entries currently published, sorted by (east, west)
+    /// discriminants. Returns None if the map can't be read.
+    #[must_use]
+    pub fn snapshot(&self) -> Option<Vec<P>> {
+        let guard = self.0.enter()?;
+        Some(
+            guard
+                .sorted()
+                .into_iter()
+                .map(|(_, _, entry)| entry.clone())
+                .collect(),
+        )
+    }
 }
@@ -3,8 +3,11 @@
 
 //! Tests and sample usage for VpcPairMap
 //!
+use crate::notify::VpcMapEvent;
 use crate::pairmap::*;
 use crate::*;
+use net::mpls::MplsLabel;
+use net::vlan::Vid;
 use net::vxlan::Vni;
 use std::{net::IpAddr, str::FromStr};
 
@@ -140,3 +143,126 @@ fn test_vpc_pair_map_non_sided() {
     assert_eq!(first.data, some_data);
     assert_eq!(second.data, some_data);
 }
+
+#[test]
+fn test_vpc_pair_map_mixed_discriminants() {
+    let mut writer: VpcPairMapWriter<VpcPairSample> = VpcPairMapWriter::new();
+    let reader = writer.get_reader();
+
+    // a VPC reached over MPLS paired with another one reached over QinQ
+    let disc1 = VpcDiscriminant::from_mpls_label(MplsLabel::new(3000).unwrap());
+    let disc2 = VpcDiscriminant::from_vlans(Vid::new(100).unwrap(), Vid::new(200).unwrap());
+
+    let vpc1 = VpcData::new(disc1, "VPC-1", "192.168.10.1");
+    let vpc2 = VpcData::new(disc2, "VPC-2", "192.168.20.2");
+    writer
+        .add(VpcPairSample::new(vpc1.clone(), vpc2.clone()), true)
+        .unwrap();
+
+    // adding the same pair in the reverse order is rejected
+    assert_eq!(
+        writer.add(VpcPairSample::new(vpc2, vpc1), true),
+        Err(VpcMapError::PairedEntryExists(disc2, disc1))
+    );
+
+    let guard = reader.enter().unwrap();
+    let (first, second) = guard.ordered_get(disc2, disc1).expect("Should be found");
+    assert_eq!(first.name, "VPC-2");
+    assert_eq!(second.name, "VPC-1");
+
+    // a VNI with the same numerical value as the label is a different VPC
+    let vni = VpcDiscriminant::from_vni(Vni::new_checked(3000).unwrap());
+    assert!(guard.get(vni, disc2).is_none());
+}
+
+impl std::fmt::Display for VpcPairNonSided {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.data)
+    }
+}
+
+#[test]
+fn test_vpc_pair_map_iter_and_dump() {
+    let mut writer: VpcPairMapWriter<VpcPairNonSided> = VpcPairMapWriter::new();
+    let reader = writer.get_reader();
+
+    let disc1 = VpcDiscriminant::from_vni(Vni::new_checked(1000).unwrap());
+    let disc2 = VpcDiscriminant::from_vni(Vni::new_checked(2000).unwrap());
+    let disc3 = VpcDiscriminant::from_vni(Vni::new_checked(3000).unwrap());
+    writer
+        .add(VpcPairNonSided::new(disc3, disc1, "3-1"), false)
+        .unwrap();
+    writer
+        .add(VpcPairNonSided::new(disc1, disc2, "1-2"), true)
+        .unwrap();
+
+    let guard = reader.enter().unwrap();
+    // each pair is visited once, with the orientation it was added with
+    assert_eq!(guard.len(), 2);
+    let sorted: Vec<_> = guard
+        .sorted()
+        .into_iter()
+        .map(|(east, west, entry)| (east, west, entry.data.clone()))
+        .collect();
+    assert_eq!(
+        sorted,
+        vec![
+            (disc1, disc2, "1-2".to_string()),
+            (disc3, disc1, "3-1".to_string())
+        ]
+    );
+    assert_eq!(
+        guard.to_string(),
+        " VNI(1000) <-> VNI(2000): 1-2\n VNI(3000) <-> VNI(1000): 3-1\n"
+    );
+    drop(guard);
+
+    let snapshot = reader.snapshot().unwrap();
+    assert_eq!(snapshot.len(), 2);
+    assert_eq!(snapshot[0].data, "1-2");
+}
+
+#[test]
+fn test_vpc_pair_map_notifications() {
+    let mut writer: VpcPairMapWriter<VpcPairNonSided> = VpcPairMapWriter::new();
+    let rx = writer.notifier().subscribe_channel();
+
+    let disc1 = VpcDiscriminant::from_vni(Vni::new_checked(1000).unwrap());
+    let disc2 = VpcDiscriminant::from_vni(Vni::new_checked(2000).unwrap());
+
+    writer
+        .add(VpcPairNonSided::new(disc1, disc2, "1-2"), true)
+        .unwrap();
+    assert_eq!(rx.try_recv(), Ok(VpcMapEvent::PairAdded(disc1, disc2)));
+    assert!(rx.try_recv().is_err());
+
+    // removal using the reverse order is reported with the orientation of the entry
+    writer.del(disc2, disc1, true);
+    assert_eq!(rx.try_recv(), Ok(VpcMapEvent::PairRemoved(disc1, disc2)));
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_vpc_pair_map_writer_rejects_reversed_pair() {
+    let mut writer: VpcPairMapWriter<VpcPairSample> = VpcPairMapWriter::new();
+
+    let disc1 = VpcDiscriminant::from_vni(Vni::new_checked(3000).unwrap());
+    let disc2 = VpcDiscriminant::from_vni(Vni::new_checked(4000).unwrap());
+    let vpc1 = VpcData::new(disc1, "VPC-1", "192.168.10.1");
+    let vpc2 = VpcData::new(disc2, "VPC-2", "192.168.20.2");
+
+    writer
+        .add(VpcPairSample::new(vpc1.clone(), vpc2.clone()), true)
+        .unwrap();
+
+    // the pair is known in both orders
+    assert_eq!(
+        writer.add(VpcPairSample::new(vpc1, vpc2.clone()), true),
+        Err(VpcMapError::PairedEntryExists(disc1, disc2))
+    );
+    let vpc1 = VpcData::new(disc1, "VPC-1", "192.168.10.1");
+    assert_eq!(
+        writer.add(VpcPairSample::new(vpc2, vpc1), true),
+        Err(VpcMapError::PairedEntryExists(disc2, disc1))
+    );
+}
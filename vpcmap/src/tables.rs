@@ -0,0 +1,299 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! A [`VpcMap`] and a [`VpcPairMap`] updated together.
+//! Keeping both tables behind a single left-right instance allows applying a set of changes
+//! to both of them with a single publish. Readers therefore either see the tables before a
+//! [`VpcTransaction`] or after it, but never a mix of the two. This is what a configuration
+//! change needs: the per-VPC data and the data for pairs of VPCs must remain consistent.
+
+use crate::map::VpcMap;
+use crate::notify::{VpcMapEvent, VpcMapNotifier, diff_keys};
+use crate::pairmap::{VpcPair, VpcPairMap};
+use crate::{VpcDiscriminant, VpcMapError, VpcMapResult};
+use ahash::RandomState;
+use left_right::new_from_empty;
+use left_right::{Absorb, ReadGuard, ReadHandle, WriteHandle};
+use std::collections::{HashMap, HashSet};
+
+/// A [`VpcMap`] and a [`VpcPairMap`]
+#[derive(Clone)]
+pub struct VpcTables<T: Clone, P: VpcPair + Clone> {
+    map: VpcMap<T>,
+    pairs: VpcPairMap<P>,
+}
+
+impl<T: Clone, P: VpcPair + Clone> VpcTables<T, P> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            map: VpcMap::new(),
+            pairs: VpcPairMap::new(),
+        }
+    }
+    /// Build a [`VpcTables`] from an existing [`VpcMap`] and [`VpcPairMap`]
+    #[must_use]
+    pub fn from_maps(map: VpcMap<T>, pairs: VpcPairMap<P>) -> Self {
+        Self { map, pairs }
+    }
+    /// The table with per-vpc data
+    #[must_use]
+    pub fn map(&self) -> &VpcMap<T> {
+        &self.map
+    }
+    /// The table with data for pairs of vpcs
+    #[must_use]
+    pub fn pairs(&self) -> &VpcPairMap<P> {
+        &self.pairs
+    }
+    fn apply(&mut self, op: &VpcTxnOp<T, P>) {
+        match op {
+            VpcTxnOp::Add(disc, entry) => self.map.add_checked(*disc, entry.clone()),
+            VpcTxnOp::Del(disc) => self.map.del(*disc),
+            VpcTxnOp::AddPair(pair) => self.pairs.add(pair.clone()),
+            VpcTxnOp::DelPair(east, west) => self.pairs.del(*east, *west),
+        }
+    }
+}
+
+impl<T: Clone, P: VpcPair + Clone> Default for VpcTables<T, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An individual operation within a [`VpcTransaction`]
+#[derive(Clone)]
+enum VpcTxnOp<T: Clone, P: VpcPair + Clone> {
+    Add(VpcDiscriminant, T),
+    Del(VpcDiscriminant),
+    AddPair(P),
+    DelPair(VpcDiscriminant, VpcDiscriminant),
+}
+
+/// A set of additions and removals to be applied to a [`VpcTables`] all at once.
+/// Operations are applied in the order they were added to the transaction.
+/// A transaction is built independently of any writer and only validated when committed
+/// with [`VpcTablesWriter::commit`].
+#[derive(Clone)]
+pub struct VpcTransaction<T: Clone, P: VpcPair + Clone> {
+    ops: Vec<VpcTxnOp<T, P>>,
+}
+
+impl<T: Clone, P: VpcPair + Clone> VpcTransaction<T, P> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self { ops: Vec::new() }
+    }
+    /// Add an entry for the given discriminant
+    pub fn add(&mut self, disc: VpcDiscriminant, entry: T) -> &mut Self {
+        self.ops.push(VpcTxnOp::Add(disc, entry));
+        self
+    }
+    /// Remove the entry for the given discriminant, if it exists
+    pub fn del(&mut self, disc: VpcDiscriminant) -> &mut Self {
+        self.ops.push(VpcTxnOp::Del(disc));
+        self
+    }
+    /// Add an entry for a pair of discriminants
+    pub fn add_pair(&mut self, pair: P) -> &mut Self {
+        self.ops.push(VpcTxnOp::AddPair(pair));
+        self
+    }
+    /// Remove the entry for a pair of discriminants, if it exists
+    pub fn del_pair(&mut self, east: VpcDiscriminant, west: VpcDiscriminant) -> &mut Self {
+        self.ops.push(VpcTxnOp::DelPair(east, west));
+        self
+    }
+    /// Number of operations in the transaction
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+    /// Tell if the transaction has no operations
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Check that the transaction can be applied on top of the provided tables: additions
+    /// must not collide with existing entries, unless these get removed earlier in the same
+    /// transaction, and pairs must have distinct discriminants.
+    fn validate(&self, tables: &VpcTables<T, P>) -> VpcMapResult<()> {
+        // presence of keys, as modified by the operations seen so far
+        let mut discs: HashMap<VpcDiscriminant, bool, RandomState> = HashMap::default();
+        let mut pairs: HashMap<(VpcDiscriminant, VpcDiscriminant), bool, RandomState> =
+            HashMap::default();
+        let ordered = |a: VpcDiscriminant, b: VpcDiscriminant| if a <= b { (a, b) } else { (b, a) };
+
+        for op in &self.ops {
+            match op {
+                VpcTxnOp::Add(disc, _) => {
+                    let present = discs
+                        .get(disc)
+                        .copied()
+                        .unwrap_or_else(|| tables.map.get(*disc).is_some());
+                    if present {
+                        return Err(VpcMapError::EntryExists(*disc));
+                    }
+                    discs.insert(*disc, true);
+                }
+                VpcTxnOp::Del(disc) => {
+                    discs.insert(*disc, false);
+                }
+                VpcTxnOp::AddPair(pair) => {
+                    let east = pair.get_east_disc();
+                    let west = pair.get_west_disc();
+                    if east == west {
+                        return Err(VpcMapError::InvalidInput);
+                    }
+                    let key = ordered(east, west);
+                    let present = pairs
+                        .get(&key)
+                        .copied()
+                        .unwrap_or_else(|| tables.pairs.contains(east, west));
+                    if present {
+                        return Err(VpcMapError::PairedEntryExists(east, west));
+                    }
+                    pairs.insert(key, true);
+                }
+                VpcTxnOp::DelPair(east, west) => {
+                    pairs.insert(ordered(*east, *west), false);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Clone, P: VpcPair + Clone> Default for VpcTransaction<T, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+enum VpcTablesChange<T: Clone, P: VpcPair + Clone> {
+    Commit(Vec<VpcTxnOp<T, P>>),
+    SetTables(VpcTables<T, P>),
+}
+
+impl<T: Clone, P: VpcPair + Clone> Absorb<VpcTablesChange<T, P>> for VpcTables<T, P> {
+    fn absorb_first(&mut self, change: &mut VpcTablesChange<T, P>, _: &Self) {
+        match change {
+            VpcTablesChange::Commit(ops) => ops.iter().for_each(|op| self.apply(op)),
+            VpcTablesChange::SetTables(tables) => *self = tables.clone(),
+        }
+    }
+    fn sync_with(&mut self, first: &Self) {
+        *self = first.clone();
+    }
+}
+
+pub struct VpcTablesWriter<T: Clone, P: VpcPair + Clone> {
+    handle: WriteHandle<VpcTables<T, P>, VpcTablesChange<T, P>>,
+    notifier: VpcMapNotifier,
+}
+pub struct VpcTablesReader<T: Clone, P: VpcPair + Clone>(ReadHandle<VpcTables<T, P>>);
+
+impl<T: Clone, P: VpcPair + Clone> VpcTablesWriter<T, P> {
+    #[must_use]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> VpcTablesWriter<T, P> {
+        let (w, _) = new_from_empty::<VpcTables<T, P>, VpcTablesChange<T, P>>(VpcTables::new());
+        VpcTablesWriter {
+            handle: w,
+            notifier: VpcMapNotifier::new(),
+        }
+    }
+    #[must_use]
+    pub fn get_reader(&self) -> VpcTablesReader<T, P> {
+        VpcTablesReader(self.handle.clone())
+    }
+    /// Completely replace both tables, publishing the change.
+    pub fn set_tables(&mut self, tables: VpcTables<T, P>) {
+        self.handle.append(VpcTablesChange::SetTables(tables));
+        self.do_publish();
+    }
+    /// Apply all the operations in a [`VpcTransaction`] and publish them at once.
+    /// The transaction is validated against the current contents of the tables first. If
+    /// validation fails, no operation is applied and readers are left untouched.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VpcMapError::EntryExists`] or [`VpcMapError::PairedEntryExists`] if the
+    /// transaction adds an entry that already exists, [`VpcMapError::InvalidInput`] if it
+    /// adds a pair with identical discriminants, or [`VpcMapError::Unavailable`] if the
+    /// tables can't be read.
+    pub fn commit(&mut self, txn: VpcTransaction<T, P>) -> VpcMapResult<()> {
+        if txn.is_empty() {
+            return Ok(());
+        }
+        {
+            // every change is published on commit, so the read side is up to date
+            let current = self.handle.enter().ok_or(VpcMapError::Unavailable)?;
+            txn.validate(&current)?;
+        }
+        self.handle.append(VpcTablesChange::Commit(txn.ops));
+        self.do_publish();
+        Ok(())
+    }
+    /// Access the notifier of this writer, to register observers of the changes
+    pub fn notifier(&mut self) -> &mut VpcMapNotifier {
+        &mut self.notifier
+    }
+    #[allow(clippy::type_complexity)]
+    fn published_keys(
+        &self,
+    ) -> (
+        HashSet<VpcDiscriminant>,
+        HashSet<(VpcDiscriminant, VpcDiscriminant)>,
+    ) {
+        self.handle
+            .enter()
+            .map(|tables| {
+                (
+                    tables.map.iter().map(|(disc, _)| *disc).collect(),
+                    tables
+                        .pairs
+                        .iter()
+                        .map(|(east, west, _)| (east, west))
+                        .collect(),
+                )
+            })
+            .unwrap_or_default()
+    }
+    /// Publish the pending changes, notifying observers of the entries added and removed.
+    fn do_publish(&mut self) {
+        if self.notifier.is_empty() {
+            self.handle.publish();
+            return;
+        }
+        let (discs_before, pairs_before) = self.published_keys();
+        self.handle.publish();
+        let (discs_after, pairs_after) = self.published_keys();
+        let (removed, added) = diff_keys(&discs_before, &discs_after);
+        let (pairs_removed, pairs_added) = diff_keys(&pairs_before, &pairs_after);
+        let events: Vec<_> = removed
+            .into_iter()
+            .map(VpcMapEvent::Removed)
+            .chain(
+                pairs_removed
+                    .into_iter()
+                    .map(|(east, west)| VpcMapEvent::PairRemoved(east, west)),
+            )
+            .chain(added.into_iter().map(VpcMapEvent::Added))
+            .chain(
+                pairs_added
+                    .into_iter()
+                    .map(|(east, west)| VpcMapEvent::PairAdded(east, west)),
+            )
+            .collect();
+        self.notifier.notify(&events);
+    }
+}
+
+impl<T: Clone, P: VpcPair + Clone> VpcTablesReader<T, P> {
+    pub fn enter(&self) -> Option<ReadGuard<'_, VpcTables<T, P>>> {
+        self.0.enter()
+    }
+}
@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Tests and sample usage for VpcTables and transactions
+
+use crate::notify::VpcMapEvent;
+use crate::pairmap::*;
+use crate::tables::*;
+use crate::*;
+use net::vxlan::Vni;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Peering {
+    east: VpcDiscriminant,
+    west: VpcDiscriminant,
+    name: String,
+}
+impl Peering {
+    fn new(east: VpcDiscriminant, west: VpcDiscriminant, name: &str) -> Self {
+        Self {
+            east,
+            west,
+            name: name.to_owned(),
+        }
+    }
+}
+impl VpcPair for Peering {
+    type SidedData = Self;
+    fn get_east_data(&self) -> &Self::SidedData {
+        self
+    }
+    fn get_west_data(&self) -> &Self::SidedData {
+        self
+    }
+    fn get_east_disc(&self) -> VpcDiscriminant {
+        self.east
+    }
+    fn get_west_disc(&self) -> VpcDiscriminant {
+        self.west
+    }
+}
+
+fn disc(vni: u32) -> VpcDiscriminant {
+    VpcDiscriminant::from_vni(Vni::new_checked(vni).unwrap())
+}
+
+#[test]
+fn test_vpc_tables_commit() {
+    let mut writer: VpcTablesWriter<String, Peering> = VpcTablesWriter::new();
+    let reader = writer.get_reader();
+
+    let mut txn = VpcTransaction::new();
+    txn.add(disc(1000), "VPC-1".to_owned())
+        .add(disc(2000), "VPC-2".to_owned())
+        .add_pair(Peering::new(disc(1000), disc(2000), "VPC-1--VPC-2"));
+    assert_eq!(txn.len(), 3);
+    writer.commit(txn).unwrap();
+
+    let tables = reader.enter().unwrap();
+    assert_eq!(tables.map().get(disc(1000)).unwrap(), "VPC-1");
+    assert_eq!(tables.map().get(disc(2000)).unwrap(), "VPC-2");
+    let peering = tables.pairs().get(disc(2000), disc(1000)).unwrap();
+    assert_eq!(peering.name, "VPC-1--VPC-2");
+}
+
+#[test]
+fn test_vpc_tables_readers_see_all_or_nothing() {
+    let mut writer: VpcTablesWriter<String, Peering> = VpcTablesWriter::new();
+    let reader = writer.get_reader();
+
+    let mut txn = VpcTransaction::new();
+    txn.add(disc(1000), "VPC-1".to_owned())
+        .add(disc(2000), "VPC-2".to_owned())
+        .add_pair(Peering::new(disc(1000), disc(2000), "old"));
+    writer.commit(txn).unwrap();
+
+    // a guard taken before the commit keeps seeing the old generation in full
+    let old = reader.enter().unwrap();
+
+    let mut txn = VpcTransaction::new();
+    txn.del_pair(disc(1000), disc(2000))
+        .del(disc(2000))
+        .add(disc(3000), "VPC-3".to_owned())
+        .add_pair(Peering::new(disc(1000), disc(3000), "new"));
+
+    writer.commit(txn).unwrap();
+
+    assert!(old.map().get(disc(2000)).is_some());
+    assert!(old.map().get(disc(3000)).is_none());
+    assert!(old.pairs().get(disc(1000), disc(2000)).is_some());
+    assert!(old.pairs().get(disc(1000), disc(3000)).is_none());
+    drop(old);
+
+    // new guards see the new generation in full
+    let new = reader.enter().unwrap();
+    assert!(new.map().get(disc(2000)).is_none());
+    assert!(new.map().get(disc(3000)).is_some());
+    assert!(new.pairs().get(disc(1000), disc(2000)).is_none());
+    assert_eq!(new.pairs().get(disc(3000), disc(1000)).unwrap().name, "new");
+}
+
+#[test]
+fn test_vpc_tables_failed_commit_has_no_effect() {
+    let mut writer: VpcTablesWriter<String, Peering> = VpcTablesWriter::new();
+    let reader = writer.get_reader();
+
+    let mut txn = VpcTransaction::new();
+    txn.add(disc(1000), "VPC-1".to_owned());
+    writer.commit(txn).unwrap();
+
+    // second addition of the same discriminant makes the whole transaction fail
+    let mut txn = VpcTransaction::new();
+    txn.add(disc(2000), "VPC-2".to_owned())
+        .add(disc(1000), "VPC-1-again".to_owned());
+    assert_eq!(
+        writer.commit(txn),
+        Err(VpcMapError::EntryExists(disc(1000)))
+    );
+
+    // pairs with identical discriminants are rejected
+    let mut txn = VpcTransaction::new();
+    txn.add(disc(2000), "VPC-2".to_owned())
+        .add_pair(Peering::new(disc(2000), disc(2000), "bad"));
+    assert_eq!(writer.commit(txn), Err(VpcMapError::InvalidInput));
+
+    let tables = reader.enter().unwrap();
+    assert_eq!(tables.map().get(disc(1000)).unwrap(), "VPC-1");
+    assert!(tables.map().get(disc(2000)).is_none());
+}
+
+#[test]
+fn test_vpc_tables_replace_within_transaction() {
+    let mut writer: VpcTablesWriter<String, Peering> = VpcTablesWriter::new();
+    let reader = writer.get_reader();
+
+    let mut txn = VpcTransaction::new();
+    txn.add(disc(1000), "VPC-1".to_owned())
+        .add(disc(2000), "VPC-2".to_owned())
+        .add_pair(Peering::new(disc(1000), disc(2000), "v1"));
+    writer.commit(txn).unwrap();
+
+    // deleting and re-adding in the same transaction is fine, in either pair order
+    let mut txn = VpcTransaction::new();
+    txn.del(disc(1000))
+        .add(disc(1000), "VPC-1-renamed".to_owned())
+        .del_pair(disc(2000), disc(1000))
+        .add_pair(Peering::new(disc(1000), disc(2000), "v2"));
+    writer.commit(txn).unwrap();
+
+    // but adding a pair twice is not
+    let mut txn = VpcTransaction::new();
+    txn.add_pair(Peering::new(disc(2000), disc(1000), "v3"));
+    assert_eq!(
+        writer.commit(txn),
+        Err(VpcMapError::PairedEntryExists(disc(2000), disc(1000)))
+    );
+
+    let tables = reader.enter().unwrap();
+    assert_eq!(tables.map().get(disc(1000)).unwrap(), "VPC-1-renamed");
+    assert_eq!(
+        tables.pairs().get(disc(1000), disc(2000)).unwrap().name,
+        "v2"
+    );
+}
+
+#[test]
+fn test_vpc_tables_notifications() {
+    let mut writer: VpcTablesWriter<String, Peering> = VpcTablesWriter::new();
+    let rx = writer.notifier().subscribe_channel();
+
+    let mut txn = VpcTransaction::new();
+    txn.add(disc(1000), "VPC-1".to_owned())
+        .add(disc(2000), "VPC-2".to_owned())
+        .add_pair(Peering::new(disc(1000), disc(2000), "1-2"));
+    writer.commit(txn).unwrap();
+    let events: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        events,
+        vec![
+            VpcMapEvent::Added(disc(1000)),
+            VpcMapEvent::Added(disc(2000)),
+            VpcMapEvent::PairAdded(disc(1000), disc(2000)),
+        ]
+    );
+
+    // a failed transaction notifies nothing
+    let mut txn = VpcTransaction::new();
+    txn.add(disc(1000), "VPC-1".to_owned());
+    assert!(writer.commit(txn).is_err());
+    assert!(rx.try_recv().is_err());
+
+    let mut txn = VpcTransaction::new();
+    txn.del_pair(disc(1000), disc(2000)).del(disc(2000));
+    writer.commit(txn).unwrap();
+    let events: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        events,
+        vec![
+            VpcMapEvent::Removed(disc(2000)),
+            VpcMapEvent::PairRemoved(disc(1000), disc(2000)),
+        ]
+    );
+}
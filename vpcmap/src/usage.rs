@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Lookup usage counters per VPC discriminant.
+//! A [`VpcUsage`] keeps track of the number of times that the VPC of a discriminant was resolved
+//! (hits) or could not be resolved (misses), e.g. when mapping the VNI of a received VxLAN packet
+//! to its VRF. Discriminants with misses only usually denote traffic for a VPC that is not (or no
+//! longer) configured. Since discriminants are taken from packets, the number of discriminants
+//! tracked is bounded, and lookups for discriminants beyond that bound are accounted as untracked.
+
+use crate::VpcDiscriminant;
+use ahash::RandomState;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{PoisonError, RwLock};
+
+/// The number of hits and misses for a discriminant
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct LookupStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Counters shared by the workers doing the lookups, hence updated atomically
+#[derive(Debug, Default)]
+struct LookupCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl LookupCounters {
+    fn record(&self, hit: bool) {
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    fn stats(&self) -> LookupStats {
+        LookupStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Lookup counters for the VPC discriminants
+#[derive(Debug)]
+pub struct VpcUsage {
+    counters: RwLock<HashMap<VpcDiscriminant, LookupCounters, RandomState>>,
+    untracked: LookupCounters,
+    max_tracked: usize,
+}
+
+impl VpcUsage {
+    /// Default maximum number of discriminants tracked
+    pub const DEFAULT_MAX_TRACKED: usize = 4096;
+
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_tracked(Self::DEFAULT_MAX_TRACKED)
+    }
+    /// Create a [`VpcUsage`] tracking at most `max_tracked` discriminants
+    #[must_use]
+    pub fn with_max_tracked(max_tracked: usize) -> Self {
+        Self {
+            counters: RwLock::new(HashMap::with_hasher(RandomState::with_seed(0))),
+            untracked: LookupCounters::default(),
+            max_tracked,
+        }
+    }
+    /// Account a lookup for a discriminant, which found a VPC if `hit`
+    pub fn record(&self, disc: VpcDiscriminant, hit: bool) {
+        // fast path: the discriminant is already tracked
+        {
+            let counters = self.counters.read().unwrap_or_else(PoisonError::into_inner);
+            if let Some(entry) = counters.get(&disc) {
+                entry.record(hit);
+                return;
+            }
+        }
+        let mut counters = self
+            .counters
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = counters.get(&disc) {
+            entry.record(hit);
+        } else if counters.len() < self.max_tracked {
+            counters.entry(disc).or_default().record(hit);
+        } else {
+            self.untracked.record(hit);
+        }
+    }
+    /// Get the counters for a discriminant, if tracked
+    #[must_use]
+    pub fn get(&self, disc: VpcDiscriminant) -> Option<LookupStats> {
+        let counters = self.counters.read().unwrap_or_else(PoisonError::into_inner);
+        counters.get(&disc).map(LookupCounters::stats)
+    }
+    /// Get the counters of all the tracked discriminants, sorted by discriminant
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<(VpcDiscriminant, LookupStats)> {
+        let counters = self.counters.read().unwrap_or_else(PoisonError::into_inner);
+        let mut snapshot: Vec<_> = counters
+            .iter()
+            .map(|(disc, counters)| (*disc, counters.stats()))
+            .collect();
+        snapshot.sort_by_key(|(disc, _)| *disc);
+        snapshot
+    }
+    /// Counters for the lookups of discriminants that could not be tracked
+    #[must_use]
+    pub fn untracked(&self) -> LookupStats {
+        self.untracked.stats()
+    }
+}
+
+impl Default for VpcUsage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
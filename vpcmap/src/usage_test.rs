@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Open Network Fabric Authors
+
+//! Tests for the lookup usage counters
+
+use crate::usage::*;
+use crate::*;
+use net::vxlan::Vni;
+
+fn disc(vni: u32) -> VpcDiscriminant {
+    VpcDiscriminant::from_vni(Vni::new_checked(vni).unwrap())
+}
+
+#[test]
+fn test_vpc_usage() {
+    let usage = VpcUsage::new();
+    usage.record(disc(100), true);
+    usage.record(disc(100), true);
+    usage.record(disc(100), false);
+    usage.record(disc(200), false);
+
+    assert_eq!(
+        usage.get(disc(100)),
+        Some(LookupStats { hits: 2, misses: 1 })
+    );
+    assert_eq!(
+        usage.get(disc(200)),
+        Some(LookupStats { hits: 0, misses: 1 })
+    );
+    assert_eq!(usage.get(disc(300)), None);
+    let discs: Vec<_> = usage.snapshot().into_iter().map(|(disc, _)| disc).collect();
+    assert_eq!(discs, vec![disc(100), disc(200)]);
+    assert_eq!(usage.untracked(), LookupStats::default());
+}
+
+#[test]
+fn test_vpc_usage_bounded() {
+    let usage = VpcUsage::with_max_tracked(2);
+    for vni in [100, 200, 300, 400, 100] {
+        usage.record(disc(vni), false);
+    }
+    // tracked discriminants are still accounted once the bound is reached
+    assert_eq!(
+        usage.get(disc(100)),
+        Some(LookupStats { hits: 0, misses: 2 })
+    );
+    assert_eq!(usage.get(disc(300)), None);
+    assert_eq!(usage.snapshot().len(), 2);
+    assert_eq!(usage.untracked(), LookupStats { hits: 0, misses: 2 });
+}